@@ -23,6 +23,8 @@ pub use locked::Variable;
 
 /// MetadataKey for extracting the application name.
 pub const APP_NAME_KEY: MetadataKey = MetadataKey::new("name");
+/// MetadataKey for extracting the URL the application was loaded from.
+pub const APP_ORIGIN_KEY: MetadataKey = MetadataKey::new("origin");
 /// MetadataKey for extracting the application version.
 pub const APP_VERSION_KEY: MetadataKey = MetadataKey::new("version");
 /// MetadataKey for extracting the application description.
@@ -188,15 +190,22 @@ impl App {
         for validator in validators {
             validator(&self, retained_components).map_err(Error::ValidationError)?;
         }
-        let (component_ids, trigger_ids): (HashSet<String>, HashSet<String>) = self
-            .triggers()
-            .filter_map(|t| match t.component() {
+        let mut component_ids = HashSet::new();
+        let mut trigger_ids = HashSet::new();
+        for t in self.triggers() {
+            match t.component() {
                 Ok(comp) if retained_components.contains(&comp.id()) => {
-                    Some((comp.id().to_owned(), t.id().to_owned()))
+                    component_ids.insert(comp.id().to_owned());
+                    trigger_ids.insert(t.id().to_owned());
+                    // The trigger may also invoke the components in its
+                    // 'components' table.
+                    for (_, comp) in t.all_components()? {
+                        component_ids.insert(comp.id().to_owned());
+                    }
                 }
-                _ => None,
-            })
-            .collect();
+                _ => {}
+            }
+        }
         let mut locked = Arc::unwrap_or_clone(self.locked);
         locked.components.retain(|c| component_ids.contains(&c.id));
         locked.triggers.retain(|t| trigger_ids.contains(&t.id));
@@ -312,9 +321,42 @@ impl<'a> AppTrigger<'a> {
         let component_id = common_config.component.ok_or_else(|| {
             Error::MetadataError(format!("trigger {id:?} missing 'component' config field"))
         })?;
-        self.app.get_component(&component_id).ok_or_else(|| {
+        self.get_component(&component_id)
+    }
+
+    /// Returns the [`AppComponent`]s configured under the given key of this
+    /// trigger's 'components' table.
+    pub fn components(&self, key: &str) -> Result<Vec<AppComponent<'a>>> {
+        self.components_config()?
+            .components
+            .remove(key)
+            .unwrap_or_default()
+            .iter()
+            .map(|component_id| self.get_component(component_id))
+            .collect()
+    }
+
+    /// Returns every [`AppComponent`] in this trigger's 'components' table,
+    /// by key.
+    pub fn all_components(&self) -> Result<Vec<(String, AppComponent<'a>)>> {
+        let mut components = Vec::new();
+        for (key, ids) in self.components_config()?.components {
+            for component_id in ids {
+                components.push((key.clone(), self.get_component(&component_id)?));
+            }
+        }
+        Ok(components)
+    }
+
+    fn components_config(&self) -> Result<ComponentsTriggerConfig> {
+        self.typed_config()
+    }
+
+    fn get_component(&self, component_id: &str) -> Result<AppComponent<'a>> {
+        self.app.get_component(component_id).ok_or_else(|| {
             Error::MetadataError(format!(
-                "missing component {component_id:?} configured for trigger {id:?}"
+                "missing component {component_id:?} configured for trigger {:?}",
+                self.locked.id
             ))
         })
     }
@@ -325,6 +367,12 @@ struct CommonTriggerConfig {
     component: Option<String>,
 }
 
+#[derive(Deserialize)]
+struct ComponentsTriggerConfig {
+    #[serde(default)]
+    components: std::collections::BTreeMap<String, Vec<String>>,
+}
+
 /// Scrubs the locked app to only contain the given list of components
 /// Introspects the LockedApp to find and selectively retain the triggers that correspond to those components
 pub fn retain_components(
@@ -369,4 +417,40 @@ mod test {
         assert!(components.contains("empty"));
         assert!(components.len() == 1);
     }
+
+    #[tokio::test]
+    async fn test_retain_components_keeps_trigger_components_table() {
+        let manifest = toml::toml! {
+            spin_manifest_version = 2
+
+            [application]
+            name = "test-app"
+
+            [[trigger.test-trigger]]
+            component = "stable"
+            components = { canary = "canary" }
+
+            [component.stable]
+            source = "does-not-exist.wasm"
+
+            [component.canary]
+            source = "does-not-exist.wasm"
+
+            [component.unused]
+            source = "does-not-exist.wasm"
+        };
+        let locked_app = build_locked_app(&manifest).await.unwrap();
+        let locked_app =
+            retain_components(locked_app, &["stable"], &[&does_nothing_validator]).unwrap();
+        let app = App::new("test-app", locked_app);
+        assert_eq!(2, app.components().count());
+
+        let trigger = app.triggers().next().unwrap();
+        let canaries = trigger.components("canary").unwrap();
+        assert_eq!(
+            vec!["canary"],
+            canaries.iter().map(|c| c.id()).collect::<Vec<_>>()
+        );
+        assert!(trigger.components("other").unwrap().is_empty());
+    }
 }
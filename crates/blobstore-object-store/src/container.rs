@@ -0,0 +1,122 @@
+use std::sync::Arc;
+
+use anyhow::{Context as _, Result};
+use async_trait::async_trait;
+use futures::{StreamExt, TryStreamExt};
+use object_store::{GetOptions, GetRange, ObjectStore, WriteMultipart, path::Path};
+use spin_factor_blobstore::{Container, IncomingData, ObjectInfo, ObjectNames};
+use tokio::io::{AsyncRead, AsyncReadExt};
+use tokio_util::io::StreamReader;
+
+/// The size of the chunks read from a guest's outgoing value and buffered
+/// into multipart upload parts.
+const WRITE_CHUNK_SIZE: usize = 64 * 1024;
+
+/// The maximum number of multipart upload parts in flight per write.
+const MAX_CONCURRENT_PARTS: usize = 8;
+
+/// A blob container backed by an [`ObjectStore`].
+pub struct ObjectStoreContainer {
+    store: Arc<dyn ObjectStore>,
+    summary: String,
+}
+
+impl ObjectStoreContainer {
+    pub fn new(store: impl ObjectStore, summary: String) -> Self {
+        Self {
+            store: Arc::new(store),
+            summary,
+        }
+    }
+}
+
+fn object_path(name: &str) -> Result<Path> {
+    Path::parse(name).with_context(|| format!("invalid object name {name:?}"))
+}
+
+#[async_trait]
+impl Container for ObjectStoreContainer {
+    fn summary(&self) -> Option<String> {
+        Some(self.summary.clone())
+    }
+
+    async fn exists(&self) -> Result<bool> {
+        match self.store.list(None).next().await {
+            Some(Err(object_store::Error::NotFound { .. })) => Ok(false),
+            Some(Err(err)) => Err(err.into()),
+            _ => Ok(true),
+        }
+    }
+
+    async fn has_object(&self, name: &str) -> Result<bool> {
+        match self.store.head(&object_path(name)?).await {
+            Ok(_) => Ok(true),
+            Err(object_store::Error::NotFound { .. }) => Ok(false),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    async fn object_info(&self, name: &str) -> Result<ObjectInfo> {
+        let meta = self.store.head(&object_path(name)?).await?;
+        Ok(ObjectInfo {
+            created_at: meta.last_modified.timestamp().try_into().unwrap_or(0),
+            size: meta.size,
+        })
+    }
+
+    async fn get_data(&self, name: &str, start: u64, end: u64) -> Result<IncomingData> {
+        let range = match end.checked_add(1) {
+            Some(end) => GetRange::Bounded(start..end),
+            None => GetRange::Offset(start),
+        };
+        let options = GetOptions {
+            range: Some(range),
+            ..Default::default()
+        };
+        let result = self.store.get_opts(&object_path(name)?, options).await?;
+        let size = result.range.end - result.range.start;
+        let body = StreamReader::new(result.into_stream().map_err(std::io::Error::other));
+        Ok(IncomingData::new(size, body))
+    }
+
+    async fn write_data(
+        &self,
+        name: &str,
+        mut data: Box<dyn AsyncRead + Send + Unpin>,
+    ) -> Result<()> {
+        let upload = self.store.put_multipart(&object_path(name)?).await?;
+        let mut writer = WriteMultipart::new(upload);
+        let mut buf = vec![0; WRITE_CHUNK_SIZE];
+        loop {
+            let n = match data.read(&mut buf).await {
+                Ok(0) => break,
+                Ok(n) => n,
+                Err(err) => {
+                    // Don't commit a partial object.
+                    _ = writer.abort().await;
+                    return Err(err.into());
+                }
+            };
+            writer.wait_for_capacity(MAX_CONCURRENT_PARTS).await?;
+            writer.write(&buf[..n]);
+        }
+        writer.finish().await?;
+        Ok(())
+    }
+
+    async fn delete_object(&self, name: &str) -> Result<()> {
+        match self.store.delete(&object_path(name)?).await {
+            Ok(()) | Err(object_store::Error::NotFound { .. }) => Ok(()),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    async fn list_objects(&self) -> Result<ObjectNames> {
+        let names = self
+            .store
+            .list(None)
+            .map(|meta| -> Result<String> { Ok(meta?.location.to_string()) })
+            .boxed();
+        Ok(ObjectNames::new(names))
+    }
+}
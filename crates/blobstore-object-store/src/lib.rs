@@ -0,0 +1,312 @@
+//! Blob containers for the [`spin_factor_blobstore`] factor, backed by the
+//! local filesystem or cloud object storage.
+
+mod container;
+
+use std::path::{Path, PathBuf};
+
+use anyhow::Context as _;
+use object_store::{
+    aws::AmazonS3Builder, azure::MicrosoftAzureBuilder, gcp::GoogleCloudStorageBuilder,
+    local::LocalFileSystem,
+};
+use serde::{Deserialize, Serialize};
+use spin_factor_blobstore::runtime_config::spin::MakeBlobContainer;
+
+pub use container::ObjectStoreContainer;
+
+/// A blob container stored in a directory on the local filesystem.
+pub struct FileBlobContainer {
+    /// The directory that relative container paths are resolved against.
+    base_path: Option<PathBuf>,
+}
+
+impl FileBlobContainer {
+    /// Create a new FileBlobContainer with the given base path.
+    ///
+    /// If it's `Some`, relative paths specified in the runtime configuration
+    /// are resolved against `base_path`.
+    pub fn new(base_path: Option<PathBuf>) -> Self {
+        Self { base_path }
+    }
+}
+
+impl MakeBlobContainer for FileBlobContainer {
+    const RUNTIME_CONFIG_TYPE: &'static str = "file";
+
+    type RuntimeConfig = FileBlobContainerRuntimeConfig;
+
+    type Container = ObjectStoreContainer;
+
+    fn make_container(
+        &self,
+        runtime_config: Self::RuntimeConfig,
+    ) -> anyhow::Result<Self::Container> {
+        let path = match &self.base_path {
+            Some(base_path) => resolve_relative_path(&runtime_config.path, base_path),
+            None => runtime_config.path,
+        };
+        std::fs::create_dir_all(&path).with_context(|| {
+            format!(
+                "failed to create blob container directory '{}'",
+                path.display()
+            )
+        })?;
+        let store = LocalFileSystem::new_with_prefix(&path)?;
+        Ok(ObjectStoreContainer::new(
+            store,
+            format!("directory {}", path.display()),
+        ))
+    }
+}
+
+/// The serialized runtime configuration for a filesystem blob container.
+#[derive(Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct FileBlobContainerRuntimeConfig {
+    /// The directory holding the container's objects.
+    path: PathBuf,
+}
+
+impl FileBlobContainerRuntimeConfig {
+    /// Create a new FileBlobContainerRuntimeConfig storing objects in `path`.
+    pub fn new(path: PathBuf) -> Self {
+        Self { path }
+    }
+}
+
+/// A blob container stored in an S3 (or S3-compatible) bucket.
+#[derive(Default)]
+pub struct S3BlobContainer {
+    _priv: (),
+}
+
+impl S3BlobContainer {
+    /// Create a new S3BlobContainer.
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl MakeBlobContainer for S3BlobContainer {
+    const RUNTIME_CONFIG_TYPE: &'static str = "s3";
+
+    type RuntimeConfig = S3BlobContainerRuntimeConfig;
+
+    type Container = ObjectStoreContainer;
+
+    fn make_container(
+        &self,
+        runtime_config: Self::RuntimeConfig,
+    ) -> anyhow::Result<Self::Container> {
+        let S3BlobContainerRuntimeConfig {
+            bucket,
+            region,
+            endpoint,
+            access_key,
+            secret_key,
+            token,
+            allow_http,
+        } = runtime_config;
+        // Settings missing from the runtime config fall back to the usual
+        // `AWS_*` environment variables.
+        let mut builder = AmazonS3Builder::from_env()
+            .with_bucket_name(&bucket)
+            .with_allow_http(allow_http);
+        if let Some(region) = region {
+            builder = builder.with_region(region);
+        }
+        if let Some(endpoint) = endpoint {
+            builder = builder.with_endpoint(endpoint);
+        }
+        if let Some(access_key) = access_key {
+            builder = builder.with_access_key_id(access_key);
+        }
+        if let Some(secret_key) = secret_key {
+            builder = builder.with_secret_access_key(secret_key);
+        }
+        if let Some(token) = token {
+            builder = builder.with_token(token);
+        }
+        Ok(ObjectStoreContainer::new(
+            builder.build()?,
+            format!("S3 bucket {bucket}"),
+        ))
+    }
+}
+
+/// The serialized runtime configuration for an S3 blob container.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct S3BlobContainerRuntimeConfig {
+    /// The name of the bucket.
+    bucket: String,
+    /// The AWS region of the bucket.
+    region: Option<String>,
+    /// The endpoint of an S3-compatible service, if not AWS.
+    endpoint: Option<String>,
+    /// The access key ID.
+    access_key: Option<String>,
+    /// The secret access key.
+    secret_key: Option<String>,
+    /// The session token.
+    token: Option<String>,
+    /// Whether to allow plain HTTP endpoints, e.g. for a local S3-compatible service.
+    #[serde(default)]
+    allow_http: bool,
+}
+
+/// A blob container stored in an Azure Blob Storage container.
+#[derive(Default)]
+pub struct AzureBlobContainer {
+    _priv: (),
+}
+
+impl AzureBlobContainer {
+    /// Create a new AzureBlobContainer.
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl MakeBlobContainer for AzureBlobContainer {
+    const RUNTIME_CONFIG_TYPE: &'static str = "azure_blob";
+
+    type RuntimeConfig = AzureBlobContainerRuntimeConfig;
+
+    type Container = ObjectStoreContainer;
+
+    fn make_container(
+        &self,
+        runtime_config: Self::RuntimeConfig,
+    ) -> anyhow::Result<Self::Container> {
+        let AzureBlobContainerRuntimeConfig {
+            account,
+            key,
+            container,
+        } = runtime_config;
+        // Without a key, credentials come from the usual `AZURE_*` environment
+        // variables or the managed identity.
+        let mut builder = MicrosoftAzureBuilder::from_env()
+            .with_account(&account)
+            .with_container_name(&container);
+        if let Some(key) = key {
+            builder = builder.with_access_key(key);
+        }
+        Ok(ObjectStoreContainer::new(
+            builder.build()?,
+            format!("Azure Blob container {account}/{container}"),
+        ))
+    }
+}
+
+/// The serialized runtime configuration for an Azure Blob Storage container.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AzureBlobContainerRuntimeConfig {
+    /// The storage account name.
+    account: String,
+    /// The storage account access key.
+    key: Option<String>,
+    /// The name of the container within the account.
+    container: String,
+}
+
+/// A blob container stored in a Google Cloud Storage bucket.
+#[derive(Default)]
+pub struct GcsBlobContainer {
+    _priv: (),
+}
+
+impl GcsBlobContainer {
+    /// Create a new GcsBlobContainer.
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl MakeBlobContainer for GcsBlobContainer {
+    const RUNTIME_CONFIG_TYPE: &'static str = "gcs";
+
+    type RuntimeConfig = GcsBlobContainerRuntimeConfig;
+
+    type Container = ObjectStoreContainer;
+
+    fn make_container(
+        &self,
+        runtime_config: Self::RuntimeConfig,
+    ) -> anyhow::Result<Self::Container> {
+        let GcsBlobContainerRuntimeConfig {
+            bucket,
+            service_account_path,
+        } = runtime_config;
+        // Without a service account file, credentials come from the usual
+        // `GOOGLE_*` environment variables or application default credentials.
+        let mut builder = GoogleCloudStorageBuilder::from_env().with_bucket_name(&bucket);
+        if let Some(path) = service_account_path {
+            builder = builder.with_service_account_path(path.to_string_lossy());
+        }
+        Ok(ObjectStoreContainer::new(
+            builder.build()?,
+            format!("GCS bucket {bucket}"),
+        ))
+    }
+}
+
+/// The serialized runtime configuration for a Google Cloud Storage container.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct GcsBlobContainerRuntimeConfig {
+    /// The name of the bucket.
+    bucket: String,
+    /// The path to a service account key file.
+    service_account_path: Option<PathBuf>,
+}
+
+/// Resolves a relative path against a base dir.
+///
+/// If the path is absolute, it is returned as is. Otherwise, it is resolved against the base dir.
+fn resolve_relative_path(path: &Path, base_dir: &Path) -> PathBuf {
+    if path.is_absolute() {
+        return path.to_owned();
+    }
+    base_dir.join(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use spin_factor_blobstore::Container;
+    use tokio::io::AsyncReadExt;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn file_container_round_trips_objects() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let container = FileBlobContainer::new(Some(dir.path().to_owned()))
+            .make_container(FileBlobContainerRuntimeConfig::new("blobs".into()))?;
+
+        container
+            .write_data("greeting.txt", Box::new(&b"hello, world"[..]))
+            .await?;
+        assert!(container.has_object("greeting.txt").await?);
+        assert_eq!(12, container.object_info("greeting.txt").await?.size);
+
+        let mut contents = String::new();
+        container
+            .get_data("greeting.txt", 7, 11)
+            .await?
+            .into_body()
+            .read_to_string(&mut contents)
+            .await?;
+        assert_eq!("world", contents);
+
+        let (names, end) = container.list_objects().await?.read(10).await?;
+        assert_eq!(vec!["greeting.txt".to_owned()], names);
+        assert!(end);
+
+        container.delete_object("greeting.txt").await?;
+        assert!(!container.has_object("greeting.txt").await?);
+        Ok(())
+    }
+}
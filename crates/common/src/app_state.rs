@@ -0,0 +1,241 @@
+//! Resolves per-app state directories for local apps
+
+use std::path::{Path, PathBuf};
+
+/// The name of the state directory created alongside a local app's manifest.
+pub const STATE_DIR_NAME: &str = ".spin";
+
+/// The file in a state directory recording which app owns it.
+const APP_ID_MARKER_FILE: &str = "app-id";
+
+/// The file in a state directory recording the name of the app owning it, for
+/// display only.
+const APP_NAME_FILE: &str = "app-name";
+
+/// The subdirectory of the state directory holding state for apps which do
+/// not own the state directory itself.
+const NAMESPACED_STATE_DIR_NAME: &str = "apps";
+
+/// The subdirectory of the state directory recording the identities of the
+/// apps whose manifests are in the app directory, by manifest file name.
+pub const APP_IDS_DIR_NAME: &str = "app-ids";
+
+/// Return a stable, filesystem-safe identity for the app whose manifest is at
+/// `manifest_path`.
+///
+/// The first time an app is seen, its identity is derived from the
+/// manifest's absolute path rather than the app's name, so apps with similar
+/// names never share it. The identity is then recorded in the state directory
+/// beside the manifest and reused, so renaming the app, or moving or renaming
+/// its directory, keeps its state.
+pub fn app_id(manifest_path: &Path) -> std::io::Result<String> {
+    let path =
+        std::fs::canonicalize(manifest_path).or_else(|_| std::path::absolute(manifest_path))?;
+    let recorded = match (path.parent(), path.file_name()) {
+        (Some(app_dir), Some(file_name)) => Some(
+            app_dir
+                .join(STATE_DIR_NAME)
+                .join(APP_IDS_DIR_NAME)
+                .join(file_name),
+        ),
+        _ => None,
+    };
+    if let Some(recorded) = &recorded
+        && let Some(app_id) = read_trimmed(recorded)?
+    {
+        return Ok(app_id);
+    }
+
+    let digest = crate::sha256::hex_digest_from_bytes(path.as_os_str().as_encoded_bytes());
+    let app_id = digest[..16].to_owned();
+    if let Some(recorded) = &recorded {
+        // An app whose directory can't be written to still has an identity,
+        // if not one that survives moving it.
+        _ = std::fs::create_dir_all(recorded.parent().unwrap())
+            .and_then(|()| std::fs::write(recorded, &app_id));
+    }
+    Ok(app_id)
+}
+
+/// Return the identity of a locked app from its `origin` metadata, if it was
+/// loaded from a local manifest.
+pub fn app_id_from_origin(origin: &str) -> Option<String> {
+    let manifest_path = url::Url::parse(origin).ok()?.to_file_path().ok()?;
+    app_id(&manifest_path).ok()
+}
+
+/// Return the default state directory for the app with the given identity,
+/// whose manifest is in `local_app_dir`.
+///
+/// The first app to use `<local_app_dir>/.spin` claims it. Any other app
+/// sharing the same directory (for example, via a second manifest file) gets
+/// its own namespaced directory under `.spin/apps/` instead, so that apps
+/// never share key-value stores, databases, or logs by accident. State
+/// directories created before app identities were recorded are claimed by
+/// the next app to use them.
+///
+/// This only resolves the path; an app claims its directory with
+/// [`claim_state_dir`].
+pub fn default_state_dir(local_app_dir: &Path, app_id: Option<&str>) -> std::io::Result<PathBuf> {
+    let root = local_app_dir.join(STATE_DIR_NAME);
+    let Some(app_id) = app_id else {
+        return Ok(root);
+    };
+    Ok(match read_owner(&root)? {
+        Some(owner) if owner != app_id => root.join(NAMESPACED_STATE_DIR_NAME).join(app_id),
+        _ => root,
+    })
+}
+
+/// Record that the app with the given identity owns `state_dir`, unless
+/// another app already does, and record the app's current name for display.
+pub fn claim_state_dir(
+    state_dir: &Path,
+    app_id: &str,
+    app_name: Option<&str>,
+) -> std::io::Result<()> {
+    let owner = read_owner(state_dir)?;
+    if owner.as_deref().is_some_and(|owner| owner != app_id) {
+        return Ok(());
+    }
+    std::fs::create_dir_all(state_dir)?;
+    if owner.is_none() {
+        std::fs::write(state_dir.join(APP_ID_MARKER_FILE), app_id)?;
+    }
+    if let Some(app_name) = app_name {
+        std::fs::write(state_dir.join(APP_NAME_FILE), app_name)?;
+    }
+    Ok(())
+}
+
+/// A state directory belonging to one of the apps in a directory.
+#[derive(Debug, PartialEq)]
+pub struct AppStateDir {
+    /// The identity of the app owning the directory, if recorded.
+    pub app_id: Option<String>,
+    /// The name of the app owning the directory, if recorded.
+    pub app_name: Option<String>,
+    /// The path to the state directory.
+    pub path: PathBuf,
+}
+
+/// List the state directories for apps whose manifests are in `local_app_dir`.
+pub fn list_state_dirs(local_app_dir: &Path) -> std::io::Result<Vec<AppStateDir>> {
+    let root = local_app_dir.join(STATE_DIR_NAME);
+    if !root.is_dir() {
+        return Ok(vec![]);
+    }
+    let mut dirs = vec![AppStateDir {
+        app_id: read_owner(&root)?,
+        app_name: read_trimmed(&root.join(APP_NAME_FILE))?,
+        path: root.clone(),
+    }];
+    let namespaced = root.join(NAMESPACED_STATE_DIR_NAME);
+    if namespaced.is_dir() {
+        let mut entries = std::fs::read_dir(&namespaced)?
+            .map(|entry| entry.map(|e| e.path()))
+            .collect::<Result<Vec<_>, _>>()?;
+        entries.sort();
+        for path in entries.into_iter().filter(|p| p.is_dir()) {
+            dirs.push(AppStateDir {
+                app_id: read_owner(&path)?,
+                app_name: read_trimmed(&path.join(APP_NAME_FILE))?,
+                path,
+            });
+        }
+    }
+    Ok(dirs)
+}
+
+fn read_owner(state_dir: &Path) -> std::io::Result<Option<String>> {
+    read_trimmed(&state_dir.join(APP_ID_MARKER_FILE))
+}
+
+fn read_trimmed(path: &Path) -> std::io::Result<Option<String>> {
+    match std::fs::read_to_string(path) {
+        Ok(contents) => Ok(Some(contents.trim().to_owned())),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn app_id_is_keyed_on_manifest_path() {
+        let dir = tempfile::tempdir().unwrap();
+        let manifest = dir.path().join("spin.toml");
+        std::fs::write(&manifest, "").unwrap();
+
+        let id = app_id(&manifest).unwrap();
+        assert!(id.chars().all(|c| c.is_ascii_hexdigit()));
+        assert_eq!(id, app_id(&dir.path().join("./spin.toml")).unwrap());
+        assert_ne!(id, app_id(&dir.path().join("other.toml")).unwrap());
+
+        let origin = url::Url::from_file_path(&manifest).unwrap();
+        assert_eq!(Some(id), app_id_from_origin(origin.as_str()));
+        assert_eq!(None, app_id_from_origin("vnd.fermyon.origin-oci:app:1.0"));
+    }
+
+    #[test]
+    fn app_id_survives_moving_the_app() {
+        let dir = tempfile::tempdir().unwrap();
+        let before = dir.path().join("before");
+        std::fs::create_dir(&before).unwrap();
+        std::fs::write(before.join("spin.toml"), "").unwrap();
+        let id = app_id(&before.join("spin.toml")).unwrap();
+
+        let after = dir.path().join("after");
+        std::fs::rename(&before, &after).unwrap();
+        assert_eq!(id, app_id(&after.join("spin.toml")).unwrap());
+        assert_ne!(id, app_id(&after.join("other.toml")).unwrap());
+    }
+
+    #[test]
+    fn apps_sharing_a_directory_are_namespaced() {
+        let dir = tempfile::tempdir().unwrap();
+
+        let first = default_state_dir(dir.path(), Some("first")).unwrap();
+        assert_eq!(dir.path().join(".spin"), first);
+        // Resolving doesn't claim the directory
+        assert!(!first.exists());
+        claim_state_dir(&first, "first", Some("First")).unwrap();
+        // Resolving again is stable
+        assert_eq!(first, default_state_dir(dir.path(), Some("first")).unwrap());
+
+        let second = default_state_dir(dir.path(), Some("second")).unwrap();
+        assert_eq!(dir.path().join(".spin/apps/second"), second);
+        claim_state_dir(&second, "second", None).unwrap();
+        // Another app can't claim an owned directory
+        claim_state_dir(&first, "second", Some("Second")).unwrap();
+
+        let listed = list_state_dirs(dir.path()).unwrap();
+        assert_eq!(
+            vec![Some("first"), Some("second")],
+            listed
+                .iter()
+                .map(|d| d.app_id.as_deref())
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(
+            vec![Some("First"), None],
+            listed
+                .iter()
+                .map(|d| d.app_name.as_deref())
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn legacy_state_dir_is_claimed() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir(dir.path().join(".spin")).unwrap();
+
+        let state_dir = default_state_dir(dir.path(), Some("legacy")).unwrap();
+        assert_eq!(dir.path().join(".spin"), state_dir);
+        claim_state_dir(&state_dir, "legacy", None).unwrap();
+        assert_eq!(Some("legacy".to_owned()), read_owner(&state_dir).unwrap());
+    }
+}
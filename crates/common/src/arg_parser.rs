@@ -15,3 +15,57 @@ fn parse_eq_pair(s: &str, err_msg: &str) -> anyhow::Result<(String, String)> {
         bail!("{err_msg}");
     }
 }
+
+/// Parse a duration such as `250ms`, `2s`, `5m`, `1h` or `7d`. A number with
+/// no suffix is interpreted as seconds.
+pub fn parse_duration(s: &str) -> anyhow::Result<std::time::Duration> {
+    let error = || anyhow::anyhow!("expected a duration such as `250ms` or `2s`; got {s:?}");
+    if let Some(ms) = s.strip_suffix("ms") {
+        return Ok(std::time::Duration::from_millis(
+            ms.parse().map_err(|_| error())?,
+        ));
+    }
+    let (num, unit_secs) = if let Some(num) = s.strip_suffix('s') {
+        (num, 1)
+    } else if let Some(num) = s.strip_suffix('m') {
+        (num, 60)
+    } else if let Some(num) = s.strip_suffix('h') {
+        (num, 60 * 60)
+    } else if let Some(num) = s.strip_suffix('d') {
+        (num, 24 * 60 * 60)
+    } else {
+        (s, 1)
+    };
+    let secs = num
+        .parse::<u64>()
+        .ok()
+        .and_then(|num| num.checked_mul(unit_secs))
+        .ok_or_else(error)?;
+    Ok(std::time::Duration::from_secs(secs))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[test]
+    fn parses_durations_in_each_unit() {
+        assert_eq!(Duration::from_millis(250), parse_duration("250ms").unwrap());
+        assert_eq!(Duration::from_secs(2), parse_duration("2s").unwrap());
+        assert_eq!(Duration::from_secs(2), parse_duration("2").unwrap());
+        assert_eq!(Duration::from_secs(300), parse_duration("5m").unwrap());
+        assert_eq!(Duration::from_secs(3600), parse_duration("1h").unwrap());
+        assert_eq!(
+            Duration::from_secs(7 * 24 * 60 * 60),
+            parse_duration("7d").unwrap()
+        );
+    }
+
+    #[test]
+    fn rejects_durations_that_overflow() {
+        assert!(parse_duration(&format!("{}d", u64::MAX / 60)).is_err());
+        assert!(parse_duration("1y").is_err());
+    }
+}
@@ -8,11 +8,13 @@
 // - No dependencies on other Spin crates
 // - Code should have at least 2 dependents
 
+pub mod app_state;
 pub mod arg_parser;
 pub mod assert;
 pub mod cli;
 pub mod data_dir;
 pub mod paths;
+pub mod secrets;
 pub mod sha256;
 pub mod sloth;
 pub mod ui;
@@ -0,0 +1,93 @@
+//! Tracking of secret values, so that they can be scrubbed from output
+//!
+//! Values of variables marked `secret` are registered here as they are
+//! resolved. Anything that writes host output - logs, panic messages, error
+//! chains - should pass it through [`redact`] first.
+
+use std::borrow::Cow;
+use std::sync::RwLock;
+
+/// The text secret values are replaced with.
+pub const REDACTED: &str = "[REDACTED]";
+
+/// Values shorter than this are not redacted, as they would match too much
+/// unrelated output to be useful.
+const MIN_SECRET_LEN: usize = 4;
+
+static SECRETS: RwLock<Secrets> = RwLock::new(Secrets { values: Vec::new() });
+
+/// Register a value to be redacted from output from now on.
+pub fn register(value: &str) {
+    if value.len() < MIN_SECRET_LEN {
+        return;
+    }
+    SECRETS.write().unwrap().insert(value);
+}
+
+/// Returns whether any secret values have been registered.
+pub fn any() -> bool {
+    !SECRETS.read().unwrap().values.is_empty()
+}
+
+/// Replace any registered secret values in the given text with [`REDACTED`].
+pub fn redact(text: &str) -> Cow<'_, str> {
+    SECRETS.read().unwrap().redact(text)
+}
+
+struct Secrets {
+    /// Registered values, longest first so that a value containing another is
+    /// redacted whole.
+    values: Vec<String>,
+}
+
+impl Secrets {
+    fn insert(&mut self, value: &str) {
+        if let Err(index) = self.values.binary_search_by(|existing| {
+            value
+                .len()
+                .cmp(&existing.len())
+                .then(existing.as_str().cmp(value))
+        }) {
+            self.values.insert(index, value.to_owned());
+        }
+    }
+
+    fn redact<'a>(&self, text: &'a str) -> Cow<'a, str> {
+        let mut text = Cow::Borrowed(text);
+        for value in &self.values {
+            if text.contains(value.as_str()) {
+                text = Cow::Owned(text.replace(value.as_str(), REDACTED));
+            }
+        }
+        text
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn redacts_registered_values() {
+        let mut secrets = Secrets { values: Vec::new() };
+        secrets.insert("hunter2");
+        secrets.insert("hunter2-extended");
+        secrets.insert("hunter2");
+        assert_eq!(2, secrets.values.len());
+        assert_eq!(
+            "postgres://admin:[REDACTED]@db and [REDACTED]",
+            secrets.redact("postgres://admin:hunter2-extended@db and hunter2")
+        );
+        assert!(matches!(
+            secrets.redact("nothing to see"),
+            Cow::Borrowed("nothing to see")
+        ));
+    }
+
+    #[test]
+    fn ignores_short_values() {
+        register("abc");
+        register("s3cr3t-from-test");
+        assert_eq!("abc [REDACTED]", redact("abc s3cr3t-from-test"));
+    }
+}
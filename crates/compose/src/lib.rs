@@ -91,7 +91,11 @@ pub trait ComponentSourceLoader {
     type Component: ComponentLike<Dependency = Self::Dependency>;
     type Dependency: DependencyLike;
     async fn load_component_source(&self, source: &Self::Component) -> anyhow::Result<Vec<u8>>;
-    async fn load_dependency_source(&self, source: &Self::Dependency) -> anyhow::Result<Vec<u8>>;
+    async fn load_dependency_source(
+        &self,
+        name: &DependencyName,
+        source: &Self::Dependency,
+    ) -> anyhow::Result<Vec<u8>>;
 }
 
 /// A ComponentSourceLoader that loads component sources from the filesystem.
@@ -106,7 +110,11 @@ impl ComponentSourceLoader for ComponentSourceLoaderFs {
         Self::load_from_locked_source(&source.source).await
     }
 
-    async fn load_dependency_source(&self, source: &Self::Dependency) -> anyhow::Result<Vec<u8>> {
+    async fn load_dependency_source(
+        &self,
+        _name: &DependencyName,
+        source: &Self::Dependency,
+    ) -> anyhow::Result<Vec<u8>> {
         Self::load_from_locked_source(&source.source).await
     }
 }
@@ -398,7 +406,10 @@ impl<'a, L: ComponentSourceLoader> Composer<'a, L> {
         dependency_name: DependencyName,
         dependency: &L::Dependency,
     ) -> anyhow::Result<DependencyInfo> {
-        let mut dependency_source = self.loader.load_dependency_source(dependency).await?;
+        let mut dependency_source = self
+            .loader
+            .load_dependency_source(&dependency_name, dependency)
+            .await?;
 
         let package_name = match &dependency_name {
             DependencyName::Package(name) => name.package.to_string(),
@@ -0,0 +1,12 @@
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    const PROTO: &str = "proto/spin/control/v1/control.proto";
+    println!("cargo:rerun-if-changed={PROTO}");
+    // Compiled with protox rather than protoc, so that building needs no
+    // tools beyond Cargo.
+    let file_descriptors = protox::compile([PROTO], ["proto"])?;
+    tonic_build::configure()
+        .build_client(true)
+        .build_server(true)
+        .compile_fds(file_descriptors)?;
+    Ok(())
+}
@@ -0,0 +1,434 @@
+//! A gRPC API for orchestrators to control the apps a Spin daemon serves.
+//!
+//! The API is defined in `proto/spin/control/v1/control.proto`. A
+//! [`ControlPlaneService`] implements it by deploying apps from a registry
+//! into a [`Gateway`], which serves their HTTP triggers from one listener.
+
+mod logs;
+
+use std::{
+    collections::HashMap,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::Context as _;
+use spin_embed::{AppBuilder, TriggerAppArgs, TriggerFactors, UserProvidedPath};
+use spin_trigger_http::{Gateway, GatewayRoute, HttpServer, HttpTrigger, HttpTriggerConfig};
+use spin_variables_static::VariableSource;
+use subtle::ConstantTimeEq as _;
+use tempfile::TempDir;
+use tokio_stream::wrappers::ReceiverStream;
+use tonic::{Request, Response, Status};
+
+use crate::logs::LogTail;
+use crate::v1::{
+    App, AppStatus, DeployRequest, DeployResponse, DrainRequest, DrainResponse, HealthRequest,
+    HealthResponse, ListAppsRequest, ListAppsResponse, LogLine, Route, StreamLogsRequest,
+    control_plane_server::ControlPlane, health_response,
+};
+
+/// The generated API types, client and server.
+#[allow(clippy::all)]
+pub mod v1 {
+    tonic::include_proto!("spin.control.v1");
+}
+
+pub use v1::control_plane_server::ControlPlaneServer;
+
+/// How long a drain waits for requests to finish if the caller doesn't say.
+const DEFAULT_DRAIN_TIMEOUT: Duration = Duration::from_secs(30);
+/// How often a drain checks for requests still in flight.
+const DRAIN_POLL_INTERVAL: Duration = Duration::from_millis(50);
+/// How often followed logs are checked for new output.
+const LOG_POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Implements the control-plane API over a gateway.
+pub struct ControlPlaneService {
+    gateway: Arc<Gateway<TriggerFactors>>,
+    apps: Arc<Mutex<HashMap<String, DeployedApp>>>,
+    /// Serializes deployments, so that concurrent deployments of the same
+    /// app can't interleave.
+    deploy_lock: tokio::sync::Mutex<()>,
+    data_dir: PathBuf,
+    cache_dir: Option<PathBuf>,
+}
+
+struct DeployedApp {
+    app: App,
+    server: Arc<HttpServer<TriggerFactors>>,
+    log_dir: PathBuf,
+    _working_dir: TempDir,
+}
+
+impl ControlPlaneService {
+    /// Creates a service which deploys apps into the given gateway, keeping
+    /// each app's state and logs under `data_dir/apps/<name>`.
+    pub fn new(
+        gateway: Arc<Gateway<TriggerFactors>>,
+        data_dir: impl Into<PathBuf>,
+        cache_dir: Option<PathBuf>,
+    ) -> Self {
+        Self {
+            gateway,
+            apps: Default::default(),
+            deploy_lock: Default::default(),
+            data_dir: data_dir.into(),
+            cache_dir,
+        }
+    }
+
+    async fn deploy_app(&self, req: DeployRequest) -> Result<App, Status> {
+        validate_app_name(&req.name).map_err(|err| Status::invalid_argument(err.to_string()))?;
+        if req.reference.is_empty() {
+            return Err(Status::invalid_argument("a registry reference is required"));
+        }
+        let route = req.route.unwrap_or_default();
+        let path_prefix = match route.path_prefix.as_str() {
+            "" => "/",
+            prefix => prefix,
+        };
+        let gateway_route = GatewayRoute::new(route.hosts.clone(), path_prefix)
+            .map_err(|err| Status::invalid_argument(format!("{err:#}")))?;
+
+        let _guard = self.deploy_lock.lock().await;
+        let app_dir = self.data_dir.join("apps").join(&req.name);
+        let log_dir = app_dir.join("logs");
+        let working_dir = tempfile::tempdir()
+            .context("failed to create working directory")
+            .map_err(internal)?;
+
+        let mut args = TriggerAppArgs::default();
+        args.variable = req
+            .variables
+            .into_iter()
+            .map(|(name, value)| VariableSource::Literal(name, value))
+            .collect();
+        let mut builder = AppBuilder::factors(args)
+            .registry(&req.reference, req.insecure)
+            .working_dir(working_dir.path())
+            .state_dir(UserProvidedPath::Provided(app_dir.join("state")))
+            .log_dir(UserProvidedPath::Provided(log_dir.clone()));
+        if let Some(cache_dir) = &self.cache_dir {
+            builder = builder.cache_dir(cache_dir);
+        }
+        if !req.runtime_config_file.is_empty() {
+            builder = builder.runtime_config_file(&req.runtime_config_file);
+        }
+
+        let embedded = builder
+            .load()
+            .await
+            .with_context(|| format!("failed to load {}", req.reference))
+            .map_err(failed_precondition)?;
+        if !embedded.trigger_types().contains(&"http") {
+            return Err(Status::failed_precondition("app has no HTTP triggers"));
+        }
+        let components = embedded
+            .locked_app()
+            .components
+            .iter()
+            .map(|component| component.id.clone())
+            .collect();
+        let listen_addr = self.gateway.listen_addr();
+        let prepared = embedded
+            .prepare_with(|app| HttpTrigger::new(app, HttpTriggerConfig::new(listen_addr)))
+            .await
+            .map_err(failed_precondition)?;
+        let (trigger, trigger_app) = prepared.into_parts();
+        let server = trigger.into_server(trigger_app).map_err(internal)?;
+
+        self.gateway
+            .insert_app(&req.name, gateway_route, server.clone())
+            .map_err(|err| Status::already_exists(format!("{err:#}")))?;
+
+        let app = App {
+            name: req.name.clone(),
+            reference: req.reference,
+            route: Some(Route {
+                hosts: route.hosts,
+                path_prefix: path_prefix.to_owned(),
+            }),
+            status: AppStatus::Serving.into(),
+            deployed_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs() as i64,
+            components,
+        };
+        tracing::info!("Deployed app {:?} from {}", app.name, app.reference);
+        self.apps.lock().unwrap().insert(
+            req.name,
+            DeployedApp {
+                app: app.clone(),
+                server,
+                log_dir,
+                _working_dir: working_dir,
+            },
+        );
+        Ok(app)
+    }
+}
+
+#[tonic::async_trait]
+impl ControlPlane for ControlPlaneService {
+    async fn deploy(
+        &self,
+        request: Request<DeployRequest>,
+    ) -> Result<Response<DeployResponse>, Status> {
+        let app = self.deploy_app(request.into_inner()).await?;
+        Ok(Response::new(DeployResponse { app: Some(app) }))
+    }
+
+    async fn list_apps(
+        &self,
+        _request: Request<ListAppsRequest>,
+    ) -> Result<Response<ListAppsResponse>, Status> {
+        let apps = self.apps.lock().unwrap();
+        let mut apps = apps
+            .values()
+            .map(|deployed| deployed.app.clone())
+            .collect::<Vec<_>>();
+        apps.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(Response::new(ListAppsResponse { apps }))
+    }
+
+    async fn drain(
+        &self,
+        request: Request<DrainRequest>,
+    ) -> Result<Response<DrainResponse>, Status> {
+        let request = request.into_inner();
+        let server = {
+            let mut apps = self.apps.lock().unwrap();
+            let deployed = apps
+                .get_mut(&request.name)
+                .ok_or_else(|| Status::not_found(format!("no app named {:?}", request.name)))?;
+            deployed.app.status = AppStatus::Draining.into();
+            deployed.server.clone()
+        };
+        self.gateway.remove_app(&request.name);
+        tracing::info!("Draining app {:?}", request.name);
+
+        let timeout = match request.timeout_seconds {
+            0 => DEFAULT_DRAIN_TIMEOUT,
+            seconds => Duration::from_secs(seconds.into()),
+        };
+        let deadline = Instant::now() + timeout;
+        // The gateway no longer routes requests to the app, so once the
+        // requests the server is handling finish, it has drained.
+        let in_flight = || server.in_flight_requests() > 0;
+        while in_flight() && Instant::now() < deadline {
+            tokio::time::sleep(DRAIN_POLL_INTERVAL).await;
+        }
+        let drained = !in_flight();
+
+        let mut apps = self.apps.lock().unwrap();
+        // The app may have been redeployed while it drained.
+        if apps
+            .get(&request.name)
+            .is_some_and(|deployed| Arc::ptr_eq(&deployed.server, &server))
+        {
+            apps.remove(&request.name);
+        }
+        if drained {
+            tracing::info!("Drained app {:?}", request.name);
+        } else {
+            tracing::warn!(
+                "Removed app {:?} with requests still in flight after {timeout:?}",
+                request.name
+            );
+        }
+        Ok(Response::new(DrainResponse { drained }))
+    }
+
+    async fn health(
+        &self,
+        _request: Request<HealthRequest>,
+    ) -> Result<Response<HealthResponse>, Status> {
+        let app_count = self.gateway.app_names().len() as u32;
+        Ok(Response::new(HealthResponse {
+            status: health_response::Status::Serving.into(),
+            version: env!("CARGO_PKG_VERSION").to_owned(),
+            app_count,
+        }))
+    }
+
+    type StreamLogsStream = ReceiverStream<Result<LogLine, Status>>;
+
+    async fn stream_logs(
+        &self,
+        request: Request<StreamLogsRequest>,
+    ) -> Result<Response<Self::StreamLogsStream>, Status> {
+        let request = request.into_inner();
+        let log_dir = self
+            .apps
+            .lock()
+            .unwrap()
+            .get(&request.name)
+            .map(|deployed| deployed.log_dir.clone())
+            .ok_or_else(|| Status::not_found(format!("no app named {:?}", request.name)))?;
+        let mut tail = LogTail::new(log_dir, request.components);
+        let apps = self.apps.clone();
+        let (tx, rx) = tokio::sync::mpsc::channel(128);
+        tokio::spawn(async move {
+            loop {
+                match tail.read_new_lines().await {
+                    Ok(lines) => {
+                        for line in lines {
+                            if tx.send(Ok(line)).await.is_err() {
+                                return;
+                            }
+                        }
+                    }
+                    Err(err) => {
+                        let status = Status::internal(format!("failed to read logs: {err}"));
+                        let _ = tx.send(Err(status)).await;
+                        return;
+                    }
+                }
+                if !request.follow || !apps.lock().unwrap().contains_key(&request.name) {
+                    return;
+                }
+                tokio::select! {
+                    _ = tokio::time::sleep(LOG_POLL_INTERVAL) => {}
+                    _ = tx.closed() => return,
+                }
+            }
+        });
+        Ok(Response::new(ReceiverStream::new(rx)))
+    }
+}
+
+/// Returns an interceptor which requires calls to carry the given token as
+/// `authorization: Bearer <token>` metadata.
+pub fn require_bearer_token(
+    token: String,
+) -> impl FnMut(Request<()>) -> Result<Request<()>, Status> + Clone {
+    move |request: Request<()>| {
+        let authorized = request
+            .metadata()
+            .get("authorization")
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "))
+            // Compared in constant time, so the time taken does not reveal
+            // how much of a guessed token is correct
+            .is_some_and(|presented| presented.as_bytes().ct_eq(token.as_bytes()).into());
+        if authorized {
+            Ok(request)
+        } else {
+            Err(Status::unauthenticated("missing or invalid control token"))
+        }
+    }
+}
+
+/// App names are used as directory names, so are restricted to letters,
+/// digits, `-` and `_`.
+fn validate_app_name(name: &str) -> anyhow::Result<()> {
+    anyhow::ensure!(!name.is_empty(), "an app name is required");
+    anyhow::ensure!(
+        name.chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_'),
+        "app name {name:?} may only contain letters, digits, `-` and `_`"
+    );
+    Ok(())
+}
+
+fn failed_precondition(err: anyhow::Error) -> Status {
+    Status::failed_precondition(format!("{err:#}"))
+}
+
+fn internal(err: anyhow::Error) -> Status {
+    Status::internal(format!("{err:#}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn service(data_dir: &std::path::Path) -> ControlPlaneService {
+        let gateway = Arc::new(Gateway::new("127.0.0.1:0".parse().unwrap()));
+        ControlPlaneService::new(gateway, data_dir, None)
+    }
+
+    #[test]
+    fn app_names_are_validated() {
+        assert!(validate_app_name("blog-v2_canary").is_ok());
+        assert!(validate_app_name("").is_err());
+        assert!(validate_app_name("../etc").is_err());
+        assert!(validate_app_name("a b").is_err());
+    }
+
+    #[test]
+    fn calls_must_carry_the_token() {
+        let mut interceptor = require_bearer_token("s3cret".into());
+        let mut request = Request::new(());
+        request
+            .metadata_mut()
+            .insert("authorization", "Bearer s3cret".parse().unwrap());
+        assert!(interceptor(request).is_ok());
+
+        let mut request = Request::new(());
+        request
+            .metadata_mut()
+            .insert("authorization", "Bearer s3cres".parse().unwrap());
+        assert!(interceptor(request).is_err());
+        assert!(interceptor(Request::new(())).is_err());
+    }
+
+    #[tokio::test]
+    async fn an_empty_daemon_is_healthy() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let service = service(dir.path());
+
+        let health = service
+            .health(Request::new(HealthRequest {}))
+            .await?
+            .into_inner();
+        assert_eq!(health_response::Status::Serving as i32, health.status);
+        assert_eq!(0, health.app_count);
+
+        let apps = service
+            .list_apps(Request::new(ListAppsRequest {}))
+            .await?
+            .into_inner();
+        assert!(apps.apps.is_empty());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn unknown_apps_are_not_found() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let service = service(dir.path());
+
+        let status = service
+            .drain(Request::new(DrainRequest {
+                name: "blog".into(),
+                timeout_seconds: 1,
+            }))
+            .await
+            .unwrap_err();
+        assert_eq!(tonic::Code::NotFound, status.code());
+
+        let status = service
+            .stream_logs(Request::new(StreamLogsRequest {
+                name: "blog".into(),
+                components: vec![],
+                follow: false,
+            }))
+            .await
+            .unwrap_err();
+        assert_eq!(tonic::Code::NotFound, status.code());
+
+        let status = service
+            .deploy(Request::new(DeployRequest {
+                name: "../blog".into(),
+                reference: "ghcr.io/example/blog:v1".into(),
+                ..Default::default()
+            }))
+            .await
+            .unwrap_err();
+        assert_eq!(tonic::Code::InvalidArgument, status.code());
+        Ok(())
+    }
+}
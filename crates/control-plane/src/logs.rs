@@ -0,0 +1,137 @@
+//! Reading the output components write to their log files.
+
+use std::{
+    collections::HashMap,
+    io::SeekFrom,
+    path::{Path, PathBuf},
+};
+
+use spin_trigger::cli::{STDERR_LOG_FILE_SUFFIX, STDOUT_LOG_FILE_SUFFIX};
+use tokio::io::{AsyncReadExt as _, AsyncSeekExt as _};
+
+use crate::v1::{LogLine, LogStream};
+
+/// Reads the lines components have written to the log files in a directory
+/// since the last read.
+pub(crate) struct LogTail {
+    dir: PathBuf,
+    /// The components to read the output of; all if empty.
+    components: Vec<String>,
+    /// How far each file has been read, and any incomplete last line.
+    files: HashMap<PathBuf, (u64, Vec<u8>)>,
+}
+
+impl LogTail {
+    pub fn new(dir: impl Into<PathBuf>, components: Vec<String>) -> Self {
+        Self {
+            dir: dir.into(),
+            components,
+            files: HashMap::new(),
+        }
+    }
+
+    /// Reads the complete lines written since the last read.
+    pub async fn read_new_lines(&mut self) -> std::io::Result<Vec<LogLine>> {
+        let mut paths = vec![];
+        let mut entries = match tokio::fs::read_dir(&self.dir).await {
+            Ok(entries) => entries,
+            // Nothing has been logged yet.
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(vec![]),
+            Err(err) => return Err(err),
+        };
+        while let Some(entry) = entries.next_entry().await? {
+            paths.push(entry.path());
+        }
+        paths.sort();
+
+        let mut lines = vec![];
+        for path in paths {
+            let Some((component, stream)) = parse_log_file_name(&path) else {
+                continue;
+            };
+            if !self.components.is_empty() && !self.components.iter().any(|c| c == component) {
+                continue;
+            }
+            let (offset, partial) = self.files.entry(path.clone()).or_default();
+            let mut file = tokio::fs::File::open(&path).await?;
+            if file.metadata().await?.len() < *offset {
+                // The file was truncated or rotated; start again.
+                *offset = 0;
+                partial.clear();
+            }
+            file.seek(SeekFrom::Start(*offset)).await?;
+            let read = file.read_to_end(partial).await?;
+            *offset += read as u64;
+            while let Some(end) = partial.iter().position(|&b| b == b'\n') {
+                let line = partial.drain(..=end).collect::<Vec<_>>();
+                let line = line.strip_suffix(b"\n").unwrap_or(&line);
+                let line = line.strip_suffix(b"\r").unwrap_or(line);
+                lines.push(LogLine {
+                    component: component.to_owned(),
+                    stream: stream.into(),
+                    line: String::from_utf8_lossy(line).into_owned(),
+                });
+            }
+        }
+        Ok(lines)
+    }
+}
+
+/// Parses the name of a component log file, `<component>_<stream>.txt`.
+fn parse_log_file_name(path: &Path) -> Option<(&str, LogStream)> {
+    let name = path.file_name()?.to_str()?.strip_suffix(".txt")?;
+    if let Some(component) = name.strip_suffix(STDOUT_LOG_FILE_SUFFIX) {
+        Some((component.strip_suffix('_')?, LogStream::Stdout))
+    } else if let Some(component) = name.strip_suffix(STDERR_LOG_FILE_SUFFIX) {
+        Some((component.strip_suffix('_')?, LogStream::Stderr))
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn log_file_names_are_parsed() {
+        assert_eq!(
+            Some(("hello-world", LogStream::Stdout)),
+            parse_log_file_name(Path::new("/logs/hello-world_stdout.txt"))
+        );
+        assert_eq!(
+            Some(("api", LogStream::Stderr)),
+            parse_log_file_name(Path::new("api_stderr.txt"))
+        );
+        assert_eq!(None, parse_log_file_name(Path::new("api_stdout.log")));
+        assert_eq!(None, parse_log_file_name(Path::new("stdout.txt")));
+    }
+
+    #[tokio::test]
+    async fn only_new_complete_lines_are_read() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let mut tail = LogTail::new(dir.path(), vec!["api".into()]);
+        assert!(tail.read_new_lines().await?.is_empty());
+
+        let log = dir.path().join("api_stdout.txt");
+        std::fs::write(&log, "first\r\nsecond\nthi")?;
+        std::fs::write(dir.path().join("web_stdout.txt"), "not followed\n")?;
+        let lines = tail.read_new_lines().await?;
+        assert_eq!(
+            vec!["first", "second"],
+            lines.iter().map(|l| l.line.as_str()).collect::<Vec<_>>()
+        );
+        assert_eq!("api", lines[0].component);
+        assert_eq!(LogStream::Stdout as i32, lines[0].stream);
+
+        std::fs::write(&log, "first\r\nsecond\nthird\n")?;
+        let lines = tail.read_new_lines().await?;
+        assert_eq!(1, lines.len());
+        assert_eq!("third", lines[0].line);
+
+        std::fs::write(&log, "rotated\n")?;
+        let lines = tail.read_new_lines().await?;
+        assert_eq!("rotated", lines[0].line);
+        Ok(())
+    }
+}
@@ -24,6 +24,7 @@ pub use wasmtime::{
     component::{Component, Instance, InstancePre, Linker},
 };
 
+pub use limits::{MemoryBudget, MemoryBudgetExhausted};
 pub use store::{AsState, Store, StoreBuilder};
 
 /// The default [`EngineBuilder::epoch_tick_interval`].
@@ -261,6 +262,8 @@ fn use_pooling_allocator_by_default() -> bool {
 #[derive(Default)]
 pub struct State {
     store_limits: limits::StoreLimitsAsync,
+    epoch_interruptions: u64,
+    execution_budget: Option<store::ExecutionBudget>,
 }
 
 impl State {
@@ -268,6 +271,29 @@ impl State {
     pub fn memory_consumed(&self) -> u64 {
         self.store_limits.memory_consumed()
     }
+
+    /// Get the number of times guest execution in the store was interrupted
+    /// for exceeding its deadline or execution time limit.
+    pub fn epoch_interruptions(&self) -> u64 {
+        self.epoch_interruptions
+    }
+
+    /// Get the limit on the time the store may spend executing guest code, if
+    /// any; see [`StoreBuilder::max_execution_time`].
+    pub fn max_execution_time(&self) -> Option<Duration> {
+        self.execution_budget
+            .as_ref()
+            .map(|budget| budget.max_execution_time)
+    }
+
+    /// Restores the store's full execution time budget, so that an instance
+    /// reused for another request gets the whole limit rather than what
+    /// earlier requests left of it.
+    pub fn reset_execution_time(&mut self) {
+        if let Some(budget) = &mut self.execution_budget {
+            budget.reset();
+        }
+    }
 }
 
 /// A builder interface for configuring a new [`Engine`].
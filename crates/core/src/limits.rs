@@ -1,6 +1,73 @@
+use std::sync::{
+    Arc,
+    atomic::{AtomicU64, Ordering},
+};
+
 use async_trait::async_trait;
 use wasmtime::ResourceLimiterAsync;
 
+/// A memory allowance shared between many instances, e.g. all instances of
+/// an app.
+#[derive(Debug)]
+pub struct MemoryBudget {
+    limit: u64,
+    consumed: AtomicU64,
+}
+
+impl MemoryBudget {
+    /// Creates a new budget allowing up to `limit` bytes in total.
+    pub fn new(limit: u64) -> Self {
+        Self {
+            limit,
+            consumed: AtomicU64::new(0),
+        }
+    }
+
+    /// How much of the budget is currently in use, in bytes.
+    pub fn consumed(&self) -> u64 {
+        self.consumed.load(Ordering::Acquire)
+    }
+
+    /// Checks whether there is room left in the budget for another instance,
+    /// so that work can be turned away before instantiation rather than
+    /// trapping once the instance tries to grow its memory.
+    pub fn check_admission(&self) -> Result<(), MemoryBudgetExhausted> {
+        // An instance needs at least one Wasm page for its memory
+        const WASM_PAGE_SIZE: u64 = 64 * 1024;
+        if self.limit.saturating_sub(self.consumed()) < WASM_PAGE_SIZE {
+            return Err(MemoryBudgetExhausted);
+        }
+        Ok(())
+    }
+
+    fn try_reserve(&self, bytes: u64) -> bool {
+        self.consumed
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |consumed| {
+                consumed
+                    .checked_add(bytes)
+                    .filter(|&total| total <= self.limit)
+            })
+            .is_ok()
+    }
+
+    fn release(&self, bytes: u64) {
+        self.consumed.fetch_sub(bytes, Ordering::AcqRel);
+    }
+}
+
+/// The error returned when a [`MemoryBudget`] has no room for another
+/// instance.
+#[derive(Debug)]
+pub struct MemoryBudgetExhausted;
+
+impl std::fmt::Display for MemoryBudgetExhausted {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("app memory limit reached; no memory is available for another instance")
+    }
+}
+
+impl std::error::Error for MemoryBudgetExhausted {}
+
 /// Async implementation of wasmtime's `StoreLimits`: https://github.com/bytecodealliance/wasmtime/blob/main/crates/wasmtime/src/limits.rs
 /// Used to limit the memory use and table size of each Instance
 #[derive(Default)]
@@ -8,6 +75,7 @@ pub struct StoreLimitsAsync {
     max_memory_size: Option<usize>,
     max_table_elements: Option<usize>,
     memory_consumed: u64,
+    memory_budget: Option<Arc<MemoryBudget>>,
 }
 
 #[async_trait]
@@ -23,6 +91,21 @@ impl ResourceLimiterAsync for StoreLimitsAsync {
         } else {
             true
         };
+        let growth = desired.saturating_sub(current) as u64;
+        let within_budget = !can_grow
+            || self
+                .memory_budget
+                .as_ref()
+                .is_none_or(|budget| budget.try_reserve(growth));
+        if !within_budget {
+            tracing::warn!(
+                "error.type" = "app_memory_limit_exceeded",
+                current,
+                desired,
+                "app memory limit exceeded",
+            );
+            return Ok(false);
+        }
         if can_grow {
             self.memory_consumed =
                 (self.memory_consumed as i64 + (desired as i64 - current as i64)) as u64;
@@ -55,12 +138,15 @@ impl ResourceLimiterAsync for StoreLimitsAsync {
 }
 
 impl StoreLimitsAsync {
-    pub fn new(max_memory_size: Option<usize>, max_table_elements: Option<usize>) -> Self {
-        Self {
-            max_memory_size,
-            max_table_elements,
-            memory_consumed: 0,
-        }
+    /// Sets a maximum memory allocation limit.
+    pub(crate) fn set_max_memory_size(&mut self, max_memory_size: usize) {
+        self.max_memory_size = Some(max_memory_size);
+    }
+
+    /// Sets a memory budget shared with other stores. Memory consumed by this
+    /// store counts against the budget until the store is dropped.
+    pub(crate) fn set_memory_budget(&mut self, memory_budget: Arc<MemoryBudget>) {
+        self.memory_budget = Some(memory_budget);
     }
 
     /// How much memory has been consumed in bytes
@@ -69,28 +155,66 @@ impl StoreLimitsAsync {
     }
 }
 
+impl Drop for StoreLimitsAsync {
+    fn drop(&mut self) {
+        if let Some(budget) = &self.memory_budget {
+            budget.release(self.memory_consumed);
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
     #[tokio::test]
     async fn test_store_limits_memory() {
-        let mut limits = StoreLimitsAsync {
-            max_memory_size: Some(65536),
-            ..Default::default()
-        };
+        let mut limits = StoreLimitsAsync::default();
+        limits.set_max_memory_size(65536);
         assert!(limits.memory_growing(0, 65536, None).await.unwrap());
         assert_eq!(limits.memory_consumed, 65536);
         assert!(!limits.memory_growing(65536, 131072, None).await.unwrap());
         assert_eq!(limits.memory_consumed, 65536);
     }
 
+    #[tokio::test]
+    async fn test_store_limits_memory_budget() {
+        let budget = Arc::new(MemoryBudget::new(65536));
+        let mut first = StoreLimitsAsync::default();
+        first.set_memory_budget(budget.clone());
+        let mut second = StoreLimitsAsync::default();
+        second.set_memory_budget(budget.clone());
+
+        assert!(first.memory_growing(0, 65536, None).await.unwrap());
+        assert!(!second.memory_growing(0, 65536, None).await.unwrap());
+        assert_eq!(budget.consumed(), 65536);
+
+        drop(first);
+        assert_eq!(budget.consumed(), 0);
+        assert!(second.memory_growing(0, 65536, None).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_memory_budget_admission() {
+        let budget = Arc::new(MemoryBudget::new(2 * 65536));
+        let mut limits = StoreLimitsAsync::default();
+        limits.set_memory_budget(budget.clone());
+        assert!(budget.check_admission().is_ok());
+
+        assert!(limits.memory_growing(0, 65536, None).await.unwrap());
+        assert!(budget.check_admission().is_ok());
+
+        assert!(limits.memory_growing(65536, 2 * 65536, None).await.unwrap());
+        assert!(budget.check_admission().is_err());
+
+        drop(limits);
+        assert!(budget.check_admission().is_ok());
+    }
+
     #[tokio::test]
     async fn test_store_limits_table() {
-        let mut limits = StoreLimitsAsync {
-            max_table_elements: Some(10),
-            ..Default::default()
-        };
+        let mut limits = StoreLimitsAsync::default();
+        limits.max_table_elements = Some(10);
         assert!(limits.table_growing(9, 10, None).await.unwrap());
         assert!(!limits.table_growing(10, 11, None).await.unwrap());
     }
@@ -1,7 +1,13 @@
 use anyhow::Result;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
 use std::time::{Duration, Instant};
+use wasmtime::{AsContext, GuestProfiler, Trap, UpdateDeadline};
 
-use crate::{State, WasmtimeEngine, limits::StoreLimitsAsync};
+use crate::{
+    State, WasmtimeEngine,
+    limits::{MemoryBudget, StoreLimitsAsync},
+};
 
 #[cfg(doc)]
 use crate::EngineBuilder;
@@ -15,6 +21,12 @@ use crate::EngineBuilder;
 pub struct Store<T: 'static> {
     inner: wasmtime::Store<T>,
     epoch_tick_interval: Duration,
+    // Checked by the epoch deadline callback.
+    wall_deadline: Arc<Mutex<Option<Instant>>>,
+    // Whether the store has an execution time limit or guest profiler, in
+    // which case the epoch deadline callback runs on every tick rather than
+    // only once the wall clock deadline is due.
+    ticking: bool,
 }
 
 impl<T: 'static> Store<T> {
@@ -26,17 +38,18 @@ impl<T: 'static> Store<T> {
     ///
     /// See [`wasmtime::Store::set_epoch_deadline`](https://docs.rs/wasmtime/latest/wasmtime/struct.Store.html#method.set_epoch_deadline).
     pub fn set_deadline(&mut self, deadline: Instant) {
+        *self.wall_deadline.lock().unwrap() = Some(deadline);
+        if self.ticking {
+            return;
+        }
         let now = Instant::now();
-        let duration = deadline - now;
-        let ticks = if duration.is_zero() {
+        if deadline <= now {
             tracing::warn!("Execution deadline set in past: {deadline:?} < {now:?}");
-            0
+            self.inner.set_epoch_deadline(0);
         } else {
-            let ticks = duration.as_micros() / self.epoch_tick_interval.as_micros();
-            let ticks = ticks.min(u64::MAX as u128) as u64;
-            ticks + 1 // Add one to allow for current partially-completed tick
-        };
-        self.inner.set_epoch_deadline(ticks);
+            self.inner
+                .set_epoch_deadline(ticks_until(deadline, self.epoch_tick_interval));
+        }
     }
 
     /// Provides access to the inner [`wasmtime::Store`]'s data.
@@ -88,6 +101,8 @@ pub struct StoreBuilder {
     engine: WasmtimeEngine,
     epoch_tick_interval: Duration,
     store_limits: StoreLimitsAsync,
+    max_execution_time: Option<Duration>,
+    guest_profile: Option<GuestProfile>,
 }
 
 impl StoreBuilder {
@@ -97,15 +112,57 @@ impl StoreBuilder {
             engine,
             epoch_tick_interval,
             store_limits: StoreLimitsAsync::default(),
+            max_execution_time: None,
+            guest_profile: None,
         }
     }
 
+    /// The interval between the engine's epoch ticks (see
+    /// [`EngineBuilder::epoch_tick_interval`]).
+    pub fn epoch_tick_interval(&self) -> Duration {
+        self.epoch_tick_interval
+    }
+
     /// Sets a maximum memory allocation limit.
     ///
     /// See [`wasmtime::ResourceLimiter::memory_growing`] (`maximum`) for
     /// details on how this limit is enforced.
     pub fn max_memory_size(&mut self, max_memory_size: usize) {
-        self.store_limits = StoreLimitsAsync::new(Some(max_memory_size), None);
+        self.store_limits.set_max_memory_size(max_memory_size);
+    }
+
+    /// Counts memory allocated by the store against the given budget, which
+    /// may be shared with other stores. Memory growth beyond the budget fails.
+    pub fn memory_budget(&mut self, memory_budget: Arc<MemoryBudget>) {
+        self.store_limits.set_memory_budget(memory_budget);
+    }
+
+    /// Sets a limit on the time the store may spend executing guest code.
+    ///
+    /// Unlike [`Store::set_deadline`], time spent waiting on the host (e.g.
+    /// for outbound I/O) does not count against this limit, so long-polling
+    /// guests are not interrupted but CPU-bound ones are. Execution time is
+    /// measured in epoch ticks (see [`EngineBuilder::epoch_tick_interval`]):
+    /// every tick during which the guest runs counts as a whole tick.
+    ///
+    /// The limit applies to the store as a whole; an instance reused for
+    /// several requests should restore it before each with
+    /// [`State::reset_execution_time`].
+    pub fn max_execution_time(&mut self, max_execution_time: Duration) {
+        self.max_execution_time = Some(max_execution_time);
+    }
+
+    /// Samples the guest's call stack with the given profiler on every epoch
+    /// tick in which it is executing.
+    ///
+    /// When the store is dropped, the profile is written to `output` in the
+    /// [Firefox profiler](https://profiler.firefox.com/) format.
+    pub fn guest_profiler(&mut self, profiler: GuestProfiler, output: PathBuf) {
+        self.guest_profile = Some(GuestProfile {
+            profiler: Some(profiler),
+            output,
+            last_sample: Instant::now(),
+        });
     }
 
     /// Builds a [`Store`] from this builder with given host state data.
@@ -114,6 +171,9 @@ impl StoreBuilder {
     /// AsMut<State>`.
     pub fn build<T: AsState>(self, mut data: T) -> Result<Store<T>> {
         data.as_state().store_limits = self.store_limits;
+        data.as_state().execution_budget = self.max_execution_time.map(|max_execution_time| {
+            ExecutionBudget::new(max_execution_time, self.epoch_tick_interval)
+        });
 
         let mut inner = wasmtime::Store::new(&self.engine, data);
         inner.limiter_async(|data| &mut data.as_state().store_limits);
@@ -124,13 +184,124 @@ impl StoreBuilder {
         // forever" for any plausible tick interval.
         inner.set_epoch_deadline(u64::MAX / 2);
 
+        let guest_profile = self.guest_profile.map(Mutex::new);
+        let ticking = self.max_execution_time.is_some() || guest_profile.is_some();
+        let wall_deadline = Arc::new(Mutex::new(None::<Instant>));
+        let callback_wall_deadline = wall_deadline.clone();
+        let epoch_tick_interval = self.epoch_tick_interval;
+        // When ticking, the callback runs at most once per tick in which the
+        // guest is executing, as ticks that pass while it waits on the host
+        // are only noticed once it resumes. Otherwise it runs once the
+        // deadline set by `Store::set_deadline` is due.
+        inner.epoch_deadline_callback(move |mut store| {
+            if let Some(guest_profile) = &guest_profile {
+                guest_profile.lock().unwrap().sample(&store);
+            }
+            let wall_deadline = *callback_wall_deadline.lock().unwrap();
+            if wall_deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+                store.data_mut().as_state().epoch_interruptions += 1;
+                return Err(Trap::Interrupt.into());
+            }
+            let state = store.data_mut().as_state();
+            if let Some(budget) = &mut state.execution_budget {
+                if budget.remaining_ticks == 0 {
+                    state.epoch_interruptions += 1;
+                    return Err(wasmtime::format_err!(
+                        "guest execution time limit of {:?} exceeded",
+                        budget.max_execution_time
+                    ));
+                }
+                budget.remaining_ticks -= 1;
+            }
+            if ticking {
+                return Ok(UpdateDeadline::Continue(1));
+            }
+            // Ticks can come early; wait out the rest of the deadline
+            Ok(UpdateDeadline::Continue(match wall_deadline {
+                Some(deadline) => ticks_until(deadline, epoch_tick_interval),
+                None => u64::MAX / 2,
+            }))
+        });
+        if ticking {
+            inner.set_epoch_deadline(1);
+        }
+
         Ok(Store {
             inner,
             epoch_tick_interval: self.epoch_tick_interval,
+            wall_deadline,
+            ticking,
         })
     }
 }
 
+/// The number of epoch ticks until the given (future) deadline.
+fn ticks_until(deadline: Instant, epoch_tick_interval: Duration) -> u64 {
+    let duration = deadline.saturating_duration_since(Instant::now());
+    let ticks = duration.as_micros() / epoch_tick_interval.as_micros();
+    let ticks = ticks.min(u64::MAX as u128) as u64;
+    ticks + 1 // Add one to allow for current partially-completed tick
+}
+
+/// A limit on the time a store may spend executing guest code, counted in
+/// epoch ticks.
+pub(crate) struct ExecutionBudget {
+    pub(crate) max_execution_time: Duration,
+    max_ticks: u64,
+    remaining_ticks: u64,
+}
+
+impl ExecutionBudget {
+    fn new(max_execution_time: Duration, epoch_tick_interval: Duration) -> Self {
+        let max_ticks = (max_execution_time.as_micros() / epoch_tick_interval.as_micros())
+            .min(u64::MAX as u128) as u64;
+        Self {
+            max_execution_time,
+            max_ticks,
+            remaining_ticks: max_ticks,
+        }
+    }
+
+    pub(crate) fn reset(&mut self) {
+        self.remaining_ticks = self.max_ticks;
+    }
+}
+
+/// A guest profiler and where to write its profile.
+struct GuestProfile {
+    profiler: Option<GuestProfiler>,
+    output: PathBuf,
+    last_sample: Instant,
+}
+
+impl GuestProfile {
+    fn sample(&mut self, store: impl AsContext) {
+        let now = Instant::now();
+        if let Some(profiler) = &mut self.profiler {
+            profiler.sample(store, now - self.last_sample);
+        }
+        self.last_sample = now;
+    }
+}
+
+impl Drop for GuestProfile {
+    fn drop(&mut self) {
+        let Some(profiler) = self.profiler.take() else {
+            return;
+        };
+        let result = std::fs::File::create(&self.output)
+            .map_err(wasmtime::Error::from)
+            .and_then(|file| profiler.finish(std::io::BufWriter::new(file)));
+        match result {
+            Ok(()) => tracing::info!("Wrote guest profile to {}", self.output.display()),
+            Err(err) => tracing::warn!(
+                "Failed to write guest profile to {}: {err:?}",
+                self.output.display()
+            ),
+        }
+    }
+}
+
 /// For consumers that need to use a type other than [`State`] as the [`Store`]
 /// `data`, this trait must be implemented for that type.
 pub trait AsState {
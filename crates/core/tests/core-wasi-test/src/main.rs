@@ -45,6 +45,15 @@ fn main() -> Result {
             eprintln!("sleep {duration:?}");
             std::thread::sleep(duration);
         }
+        "busy" => {
+            let duration =
+                Duration::from_millis(args.next().expect("duration_ms").parse().expect("u64"));
+            eprintln!("busy {duration:?}");
+            let start = std::time::Instant::now();
+            while start.elapsed() < duration {
+                std::hint::spin_loop();
+            }
+        }
         "panic" => {
             eprintln!("panic");
             panic!("intentional panic");
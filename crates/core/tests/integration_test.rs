@@ -76,6 +76,136 @@ async fn test_set_deadline_violated() {
     assert_eq!(trap, Trap::Interrupt);
 }
 
+#[tokio::test(flavor = "multi_thread")]
+async fn test_set_deadline_counts_interruptions() {
+    for max_execution_time in [None, Some(Duration::from_secs(10))] {
+        let mut interruptions = None;
+        let err = run_test_calls_with(
+            ["busy", "200"],
+            |store_builder, _| {
+                if let Some(max_execution_time) = max_execution_time {
+                    store_builder.max_execution_time(max_execution_time);
+                }
+            },
+            |store| {
+                store.set_deadline(Instant::now() + Duration::from_millis(20));
+            },
+            1,
+            |_| {},
+            |store| interruptions = Some(store.data_mut().core.epoch_interruptions()),
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(err.downcast::<Trap>().expect("trap"), Trap::Interrupt);
+        // Recorded as the spin.component_epoch_interruptions metric
+        assert_eq!(interruptions, Some(1));
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_max_execution_time_ignores_waiting() {
+    run_test(
+        ["sleep", "200"],
+        |store_builder| {
+            store_builder.max_execution_time(Duration::from_millis(50));
+        },
+        |_| {},
+    )
+    .await
+    .unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_max_execution_time_violated() {
+    let err = run_test(
+        ["busy", "200"],
+        |store_builder| {
+            store_builder.max_execution_time(Duration::from_millis(50));
+        },
+        |_| {},
+    )
+    .await
+    .unwrap_err();
+    assert!(
+        format!("{err:?}").contains("execution time limit"),
+        "unexpected error: {err:?}"
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_max_execution_time_reset_for_reuse() {
+    // Each call fits within the limit, but together they exceed it.
+    let limit = |store_builder: &mut StoreBuilder| {
+        store_builder.max_execution_time(Duration::from_millis(100));
+    };
+    run_test_calls(
+        ["busy", "40"],
+        limit,
+        |_| {},
+        4,
+        |store| {
+            store.data_mut().core.reset_execution_time();
+        },
+    )
+    .await
+    .unwrap();
+
+    let err = run_test_calls(["busy", "40"], limit, |_| {}, 4, |_| {})
+        .await
+        .unwrap_err();
+    assert!(
+        format!("{err:?}").contains("execution time limit"),
+        "unexpected error: {err:?}"
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_set_deadline_violated_with_max_execution_time() {
+    let err = run_test(
+        ["sleep", "100"],
+        |store_builder| {
+            store_builder.max_execution_time(Duration::from_secs(10));
+        },
+        |store| {
+            store.set_deadline(Instant::now() + Duration::from_millis(10));
+        },
+    )
+    .await
+    .unwrap_err();
+    let trap = err.downcast::<Trap>().expect("trap");
+    assert_eq!(trap, Trap::Interrupt);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_guest_profile_written() {
+    let dir = tempfile::tempdir().unwrap();
+    let output = dir.path().join("test-component.profile.json");
+    run_test_calls_with(
+        ["busy", "100"],
+        |store_builder, component| {
+            let profiler = wasmtime::GuestProfiler::new_component(
+                component.engine(),
+                "test-component",
+                store_builder.epoch_tick_interval(),
+                component.clone(),
+                [],
+            )
+            .unwrap();
+            store_builder.guest_profiler(profiler, output.clone());
+        },
+        |_| {},
+        1,
+        |_| {},
+        |_| {},
+    )
+    .await
+    .unwrap();
+    // The profile is written when the store is dropped
+    let profile: serde_json::Value =
+        serde_json::from_slice(&std::fs::read(&output).unwrap()).unwrap();
+    assert!(profile["threads"][0]["samples"]["length"].as_u64().unwrap() > 0);
+}
+
 #[tokio::test(flavor = "multi_thread")]
 async fn test_panic() {
     let err = run_test(["panic"], |_| {}, |_| {}).await.unwrap_err();
@@ -109,6 +239,40 @@ async fn run_test(
     args: impl IntoIterator<Item = &'_ str>,
     update_store_builder: impl FnOnce(&mut StoreBuilder),
     update_store: impl FnOnce(&mut Store<TestState>),
+) -> wasmtime::Result<()> {
+    run_test_calls(args, update_store_builder, update_store, 1, |_| {}).await
+}
+
+/// Like [`run_test`], but runs the command `calls` times in the same instance,
+/// calling `before_call` before each.
+async fn run_test_calls(
+    args: impl IntoIterator<Item = &'_ str>,
+    update_store_builder: impl FnOnce(&mut StoreBuilder),
+    update_store: impl FnOnce(&mut Store<TestState>),
+    calls: usize,
+    before_call: impl FnMut(&mut Store<TestState>),
+) -> wasmtime::Result<()> {
+    run_test_calls_with(
+        args,
+        |store_builder, _| update_store_builder(store_builder),
+        update_store,
+        calls,
+        before_call,
+        |_| {},
+    )
+    .await
+}
+
+/// Like [`run_test_calls`], but also passes the test component to
+/// `update_store_builder`, and calls `after_calls` once the calls are done,
+/// whether or not they succeeded.
+async fn run_test_calls_with(
+    args: impl IntoIterator<Item = &'_ str>,
+    update_store_builder: impl FnOnce(&mut StoreBuilder, &Component),
+    update_store: impl FnOnce(&mut Store<TestState>),
+    calls: usize,
+    mut before_call: impl FnMut(&mut Store<TestState>),
+    after_calls: impl FnOnce(&mut Store<TestState>),
     // FIXME: this should be `anyhow::Error` and below there should be no usages
     // of `map_err(wasmtime::Error::from_anyhow)` ideally. That requires
     // bytecodealliance/wasmtime#12689 to be released first. Once that's
@@ -128,8 +292,15 @@ async fn run_test(
     factors.init(builder.linker())?;
     let engine = builder.build();
 
+    let module_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("../../target/test-programs/core-wasi-test.wasm");
+    let module_bytes = fs::read(module_path).await?;
+    let component = spin_componentize::componentize_if_necessary(&module_bytes)
+        .map_err(wasmtime::Error::from_anyhow)?;
+    let component = Component::new(engine.as_ref(), &component)?;
+
     let mut store_builder = engine.store_builder();
-    update_store_builder(&mut store_builder);
+    update_store_builder(&mut store_builder, &component);
 
     let locked: LockedApp = serde_json::from_value(json!({
         "spin_lock_version": 1,
@@ -157,12 +328,6 @@ async fn run_test(
         .map_err(wasmtime::Error::from_anyhow)?;
     update_store(&mut store);
 
-    let module_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
-        .join("../../target/test-programs/core-wasi-test.wasm");
-    let module_bytes = fs::read(module_path).await?;
-    let component = spin_componentize::componentize_if_necessary(&module_bytes)
-        .map_err(wasmtime::Error::from_anyhow)?;
-    let component = Component::new(engine.as_ref(), &component)?;
     let instance_pre = engine
         .instantiate_pre(&component)
         .map_err(wasmtime::Error::from_anyhow)?;
@@ -176,8 +341,20 @@ async fn run_test(
         instance.get_typed_func::<(), (Result<(), ()>,)>(&mut store, &func)?
     };
 
-    func.call_async(&mut store, ())
-        .await?
-        .0
-        .map_err(|()| wasmtime::format_err!("command failed"))
+    let mut result = Ok(());
+    for _ in 0..calls {
+        before_call(&mut store);
+        result = async {
+            func.call_async(&mut store, ())
+                .await?
+                .0
+                .map_err(|()| wasmtime::format_err!("command failed"))
+        }
+        .await;
+        if result.is_err() {
+            break;
+        }
+    }
+    after_calls(&mut store);
+    result
 }
@@ -0,0 +1,168 @@
+use std::collections::BTreeMap;
+use std::io::BufRead;
+
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use spin_manifest::schema::v2::AppManifest;
+
+/// The audit log operation recorded for each outbound connection.
+const OUTBOUND_CONNECT: &str = "outbound.connect";
+
+/// A summary of the outbound connections an app made, read from an audit log
+/// written by `spin up --audit-log`.
+#[derive(Debug, Default)]
+pub struct EgressReport {
+    /// The connections made by each component, by component ID.
+    pub components: BTreeMap<String, ComponentEgress>,
+}
+
+/// The outbound connections made by a single component.
+#[derive(Debug, Default)]
+pub struct ComponentEgress {
+    /// The destinations contacted, as `scheme://host`.
+    pub destinations: BTreeMap<String, Destination>,
+    /// The component's `allowed_outbound_hosts`, if known.
+    pub grants: Vec<String>,
+}
+
+/// A destination contacted by a component.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Destination {
+    /// How many connections were made.
+    pub count: usize,
+    /// Whether the connections were allowed by `allowed_outbound_hosts`.
+    pub allowed: bool,
+}
+
+#[derive(Deserialize)]
+struct AuditRecord {
+    component_id: Option<String>,
+    operation: String,
+    #[serde(default)]
+    scheme: String,
+    #[serde(default)]
+    host: String,
+    #[serde(default)]
+    allowed: bool,
+}
+
+impl EgressReport {
+    /// Summarizes the outbound connections in the given JSON lines audit log.
+    pub fn from_audit_log(log: impl BufRead) -> Result<Self> {
+        let mut report = Self::default();
+        for (index, line) in log.lines().enumerate() {
+            let line = line.context("failed to read audit log")?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let record: AuditRecord = serde_json::from_str(&line)
+                .with_context(|| format!("invalid audit log record on line {}", index + 1))?;
+            if record.operation != OUTBOUND_CONNECT {
+                continue;
+            }
+            let component_id = record.component_id.unwrap_or_default();
+            let destination = report
+                .components
+                .entry(component_id)
+                .or_default()
+                .destinations
+                .entry(format!("{}://{}", record.scheme, record.host))
+                .or_default();
+            destination.count += 1;
+            destination.allowed = record.allowed;
+        }
+        Ok(report)
+    }
+
+    /// Records each component's `allowed_outbound_hosts` from the manifest.
+    pub fn add_grants(&mut self, manifest: &AppManifest) {
+        for (id, component) in &manifest.components {
+            self.components
+                .entry(id.to_string())
+                .or_default()
+                .grants
+                .clone_from(&component.allowed_outbound_hosts);
+        }
+    }
+}
+
+impl ComponentEgress {
+    /// Returns the grants that allow any host, e.g. `*://*:*` or `https://*`.
+    pub fn wildcard_grants(&self) -> Vec<&str> {
+        self.grants
+            .iter()
+            .map(String::as_str)
+            .filter(|grant| is_wildcard_host(grant))
+            .collect()
+    }
+
+    /// Returns the grants that would allow exactly the destinations contacted.
+    pub fn suggested_grants(&self) -> Vec<&str> {
+        self.destinations.keys().map(String::as_str).collect()
+    }
+}
+
+fn is_wildcard_host(grant: &str) -> bool {
+    let Some((_, rest)) = grant.trim().split_once("://") else {
+        return false;
+    };
+    let host = rest.rsplit_once(':').map_or(rest, |(host, _)| host);
+    host.trim_end_matches('/') == "*"
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const AUDIT_LOG: &str = r#"
+{"timestamp":"t","component_id":"api","operation":"outbound.connect","scheme":"https","host":"api.example.com","allowed":true}
+{"timestamp":"t","component_id":"api","operation":"kv.get","store":"default","key":"k"}
+{"timestamp":"t","component_id":"api","operation":"outbound.connect","scheme":"https","host":"api.example.com","allowed":true}
+{"timestamp":"t","component_id":"api","operation":"outbound.connect","scheme":"redis","host":"cache:6379","allowed":false}
+{"timestamp":"t","component_id":"web","operation":"outbound.connect","scheme":"http","host":"self","allowed":true}
+"#;
+
+    #[test]
+    fn summarizes_outbound_connections() {
+        let report = EgressReport::from_audit_log(AUDIT_LOG.as_bytes()).unwrap();
+        assert_eq!(2, report.components.len());
+
+        let api = &report.components["api"];
+        assert_eq!(
+            Destination {
+                count: 2,
+                allowed: true
+            },
+            api.destinations["https://api.example.com"]
+        );
+        assert!(!api.destinations["redis://cache:6379"].allowed);
+        assert_eq!(
+            vec!["https://api.example.com", "redis://cache:6379"],
+            api.suggested_grants()
+        );
+        assert_eq!(
+            vec!["http://self"],
+            report.components["web"].suggested_grants()
+        );
+    }
+
+    #[test]
+    fn invalid_records_are_reported_by_line() {
+        let err = EgressReport::from_audit_log("{}\nnot json".as_bytes()).unwrap_err();
+        assert_eq!("invalid audit log record on line 1", err.to_string());
+    }
+
+    #[test]
+    fn detects_wildcard_grants() {
+        let component = ComponentEgress {
+            grants: vec![
+                "*://*:*".into(),
+                "https://*".into(),
+                "https://*.example.com".into(),
+                "redis://cache:*".into(),
+            ],
+            ..Default::default()
+        };
+        assert_eq!(vec!["*://*:*", "https://*"], component.wildcard_grants());
+    }
+}
@@ -8,10 +8,14 @@ use async_trait::async_trait;
 use spin_common::ui::quoted_path;
 use toml_edit::DocumentMut;
 
+/// Summaries of the outbound connections apps make.
+pub mod egress;
 /// Diagnoses for app manifest format problems.
 pub mod manifest;
 /// Diagnose for Rust-specific problems.
 pub mod rustlang;
+/// Checks of the telemetry exporter configuration.
+pub mod telemetry;
 /// Test helpers.
 pub(crate) mod test;
 /// Diagnoses for Wasm source problems.
@@ -0,0 +1,664 @@
+use std::time::Duration;
+
+use reqwest::{StatusCode, Url, header::HeaderMap};
+
+/// How long to wait for the collector to answer a test export.
+const EXPORT_TIMEOUT: Duration = Duration::from_secs(5);
+/// The port collectors conventionally receive OTLP over gRPC on.
+const GRPC_PORT: u16 = 4317;
+/// The port collectors conventionally receive OTLP over HTTP on.
+const HTTP_PORT: u16 = 4318;
+
+const OTEL_SDK_DISABLED: &str = "OTEL_SDK_DISABLED";
+const OTEL_EXPORTER_OTLP_ENDPOINT: &str = "OTEL_EXPORTER_OTLP_ENDPOINT";
+const OTEL_EXPORTER_OTLP_PROTOCOL: &str = "OTEL_EXPORTER_OTLP_PROTOCOL";
+const OTEL_EXPORTER_OTLP_HEADERS: &str = "OTEL_EXPORTER_OTLP_HEADERS";
+
+/// A kind of telemetry Spin exports.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Signal {
+    /// Spans.
+    Traces,
+    /// Host and guest metrics.
+    Metrics,
+    /// Host and guest log records.
+    Logs,
+}
+
+impl Signal {
+    /// Every signal.
+    pub const ALL: [Signal; 3] = [Signal::Traces, Signal::Metrics, Signal::Logs];
+
+    fn name(self) -> &'static str {
+        match self {
+            Signal::Traces => "traces",
+            Signal::Metrics => "metrics",
+            Signal::Logs => "logs",
+        }
+    }
+
+    /// The name of the signal-specific variant of an `OTEL_EXPORTER_OTLP_*`
+    /// variable, e.g. `OTEL_EXPORTER_OTLP_TRACES_ENDPOINT`.
+    fn var(self, general: &str) -> String {
+        let suffix = general
+            .strip_prefix("OTEL_EXPORTER_OTLP_")
+            .unwrap_or(general);
+        format!(
+            "OTEL_EXPORTER_OTLP_{}_{suffix}",
+            self.name().to_ascii_uppercase()
+        )
+    }
+
+    /// The path of the signal's OTLP/HTTP endpoint.
+    fn http_path(self) -> String {
+        format!("/v1/{}", self.name())
+    }
+
+    /// The full name of the signal's OTLP/gRPC export method.
+    fn grpc_path(self) -> &'static str {
+        match self {
+            Signal::Traces => "/opentelemetry.proto.collector.trace.v1.TraceService/Export",
+            Signal::Metrics => "/opentelemetry.proto.collector.metrics.v1.MetricsService/Export",
+            Signal::Logs => "/opentelemetry.proto.collector.logs.v1.LogsService/Export",
+        }
+    }
+}
+
+impl std::fmt::Display for Signal {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// An OTLP transport.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Protocol {
+    /// `grpc`
+    Grpc,
+    /// `http/protobuf`
+    HttpProtobuf,
+}
+
+impl Protocol {
+    fn name(self) -> &'static str {
+        match self {
+            Protocol::Grpc => "grpc",
+            Protocol::HttpProtobuf => "http/protobuf",
+        }
+    }
+
+    fn other(self) -> Self {
+        match self {
+            Protocol::Grpc => Protocol::HttpProtobuf,
+            Protocol::HttpProtobuf => Protocol::Grpc,
+        }
+    }
+}
+
+/// How a signal is exported, as configured by the environment.
+#[derive(Clone, Debug)]
+pub struct SignalExport {
+    /// The signal exported.
+    pub signal: Signal,
+    /// The endpoint, as configured.
+    pub endpoint: String,
+    /// The variable the endpoint is configured by.
+    pub endpoint_var: String,
+    /// The protocol, as configured.
+    pub protocol: String,
+    /// The variable the protocol is configured by, or the general one if
+    /// unset.
+    pub protocol_var: String,
+    /// The headers sent with each export, as configured.
+    pub headers: Option<String>,
+}
+
+impl SignalExport {
+    fn is_signal_specific_endpoint(&self) -> bool {
+        self.endpoint_var != OTEL_EXPORTER_OTLP_ENDPOINT
+    }
+
+    /// The URL exports of the signal are sent to over the given protocol.
+    fn export_url(&self, protocol: Protocol) -> anyhow::Result<Url> {
+        let mut url = Url::parse(&self.endpoint)?;
+        match protocol {
+            // Per-signal endpoints are used as-is; the general one is a base.
+            Protocol::HttpProtobuf if self.is_signal_specific_endpoint() => {}
+            Protocol::HttpProtobuf => {
+                let path = format!(
+                    "{}{}",
+                    url.path().trim_end_matches('/'),
+                    self.signal.http_path()
+                );
+                url.set_path(&path);
+            }
+            // gRPC clients ignore the path.
+            Protocol::Grpc => url.set_path(self.signal.grpc_path()),
+        }
+        Ok(url)
+    }
+}
+
+/// How telemetry export is configured by the environment.
+#[derive(Clone, Debug, Default)]
+pub struct TelemetryConfig {
+    /// Whether `OTEL_SDK_DISABLED` is set.
+    pub sdk_disabled: bool,
+    /// The signals with an endpoint configured.
+    pub exports: Vec<SignalExport>,
+}
+
+impl TelemetryConfig {
+    /// Reads the configuration from the environment of this process.
+    pub fn from_env() -> Self {
+        Self::from_vars(|name| std::env::var(name).ok())
+    }
+
+    /// Reads the configuration from the given variables. Empty variables
+    /// are treated as unset, as Spin does.
+    pub fn from_vars(var: impl Fn(&str) -> Option<String>) -> Self {
+        let var = |name: &str| var(name).filter(|value| !value.is_empty());
+        let first_set = |names: [String; 2]| {
+            names
+                .into_iter()
+                .find_map(|name| var(&name).map(|value| (name, value)))
+        };
+        let exports = Signal::ALL
+            .into_iter()
+            .filter_map(|signal| {
+                let general = |name: &str| [signal.var(name), name.to_owned()];
+                let (endpoint_var, endpoint) = first_set(general(OTEL_EXPORTER_OTLP_ENDPOINT))?;
+                let (protocol_var, protocol) = first_set(general(OTEL_EXPORTER_OTLP_PROTOCOL))
+                    .unwrap_or_else(|| {
+                        (
+                            OTEL_EXPORTER_OTLP_PROTOCOL.to_owned(),
+                            Protocol::HttpProtobuf.name().to_owned(),
+                        )
+                    });
+                let headers = first_set(general(OTEL_EXPORTER_OTLP_HEADERS)).map(|(_, h)| h);
+                Some(SignalExport {
+                    signal,
+                    endpoint,
+                    endpoint_var,
+                    protocol,
+                    protocol_var,
+                    headers,
+                })
+            })
+            .collect();
+        Self {
+            sdk_disabled: var(OTEL_SDK_DISABLED).is_some(),
+            exports,
+        }
+    }
+}
+
+/// Something a telemetry check found.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Finding {
+    /// The signal the finding is about, if it is about one.
+    pub signal: Option<Signal>,
+    /// How much the finding matters.
+    pub severity: Severity,
+    /// A description of what was found.
+    pub message: String,
+}
+
+/// How much a [`Finding`] matters.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    /// Everything works.
+    Ok,
+    /// Telemetry may not be exported as intended.
+    Warning,
+    /// Telemetry is not being exported.
+    Problem,
+}
+
+impl Finding {
+    fn new(signal: impl Into<Option<Signal>>, severity: Severity, message: String) -> Self {
+        Self {
+            signal: signal.into(),
+            severity,
+            message,
+        }
+    }
+}
+
+/// Checks the telemetry configuration without contacting the collector.
+pub fn check_config(config: &TelemetryConfig) -> Vec<Finding> {
+    let mut findings = vec![];
+    if config.exports.is_empty() {
+        findings.push(Finding::new(
+            None,
+            Severity::Warning,
+            format!("No telemetry is exported: set {OTEL_EXPORTER_OTLP_ENDPOINT} to the OTLP endpoint of a collector"),
+        ));
+        return findings;
+    }
+    if config.sdk_disabled {
+        findings.push(Finding::new(
+            None,
+            Severity::Problem,
+            format!("{OTEL_SDK_DISABLED} is set, so no telemetry is exported despite the endpoints configured"),
+        ));
+    }
+    for export in &config.exports {
+        findings.extend(check_export_config(export));
+    }
+    findings
+}
+
+fn check_export_config(export: &SignalExport) -> Vec<Finding> {
+    let signal = export.signal;
+    let finding = |severity, message| Finding::new(signal, severity, message);
+    let mut findings = vec![];
+
+    let protocol = match export.protocol.as_str() {
+        "grpc" => Some(Protocol::Grpc),
+        "http/protobuf" => Some(Protocol::HttpProtobuf),
+        "http/json" => {
+            findings.push(finding(
+                Severity::Problem,
+                format!(
+                    "{}=http/json is not supported by Spin; use grpc or http/protobuf",
+                    export.protocol_var
+                ),
+            ));
+            None
+        }
+        other => {
+            findings.push(finding(
+                Severity::Problem,
+                format!(
+                    "{}={other:?} is not an OTLP protocol; Spin falls back to http/protobuf",
+                    export.protocol_var
+                ),
+            ));
+            Some(Protocol::HttpProtobuf)
+        }
+    };
+
+    let url = match Url::parse(&export.endpoint) {
+        Ok(url) if matches!(url.scheme(), "http" | "https") && url.has_host() => url,
+        _ => {
+            findings.push(finding(
+                Severity::Problem,
+                format!(
+                    "{}={:?} is not an http:// or https:// URL",
+                    export.endpoint_var, export.endpoint
+                ),
+            ));
+            return findings;
+        }
+    };
+
+    match (protocol, url.port_or_known_default()) {
+        (Some(Protocol::HttpProtobuf), Some(GRPC_PORT)) => findings.push(finding(
+            Severity::Warning,
+            format!(
+                "{} uses port {GRPC_PORT}, which collectors conventionally serve OTLP/gRPC on, but the protocol is http/protobuf; set {}=grpc or use port {HTTP_PORT}",
+                export.endpoint_var, export.protocol_var
+            ),
+        )),
+        (Some(Protocol::Grpc), Some(HTTP_PORT)) => findings.push(finding(
+            Severity::Warning,
+            format!(
+                "{} uses port {HTTP_PORT}, which collectors conventionally serve OTLP/HTTP on, but the protocol is grpc; set {}=http/protobuf or use port {GRPC_PORT}",
+                export.endpoint_var, export.protocol_var
+            ),
+        )),
+        _ => {}
+    }
+
+    if protocol == Some(Protocol::HttpProtobuf)
+        && export.is_signal_specific_endpoint()
+        && !url.path().ends_with(&signal.http_path())
+    {
+        findings.push(finding(
+            Severity::Warning,
+            format!(
+                "{} is used as-is, and usually ends in {}; did you mean to set {OTEL_EXPORTER_OTLP_ENDPOINT}?",
+                export.endpoint_var,
+                signal.http_path()
+            ),
+        ));
+    }
+
+    if let Some(headers) = &export.headers
+        && let Err(err) = parse_headers(headers)
+    {
+        findings.push(finding(
+            Severity::Problem,
+            format!("invalid OTLP headers: {err}"),
+        ));
+    }
+    findings
+}
+
+/// Sends an empty export of each configured signal to its collector, and
+/// reports whether the collector accepted it.
+pub async fn check_exports(config: &TelemetryConfig) -> Vec<Finding> {
+    let mut findings = vec![];
+    for export in &config.exports {
+        let protocol = match export.protocol.as_str() {
+            "grpc" => Protocol::Grpc,
+            "http/json" => continue,
+            _ => Protocol::HttpProtobuf,
+        };
+        let headers = export
+            .headers
+            .as_deref()
+            .map(parse_headers)
+            .transpose()
+            .unwrap_or_default()
+            .unwrap_or_default();
+        findings.extend(check_export(export, protocol, &headers).await);
+    }
+    findings
+}
+
+async fn check_export(
+    export: &SignalExport,
+    protocol: Protocol,
+    headers: &HeaderMap,
+) -> Vec<Finding> {
+    let signal = export.signal;
+    let url = match export.export_url(protocol) {
+        Ok(url) => url,
+        // Reported by `check_config`.
+        Err(_) => return vec![],
+    };
+    let outcome = test_export(&url, protocol, headers).await;
+    let mut findings = vec![match &outcome {
+        ExportOutcome::Accepted => Finding::new(
+            signal,
+            Severity::Ok,
+            format!("The collector at {url} accepted a test export"),
+        ),
+        ExportOutcome::Rejected(reason) => Finding::new(
+            signal,
+            Severity::Problem,
+            format!("The collector at {url} rejected a test export: {reason}"),
+        ),
+        ExportOutcome::Unreachable(reason) => Finding::new(
+            signal,
+            Severity::Problem,
+            format!("Could not send a test export to {url}: {reason}"),
+        ),
+    }];
+    // A collector which rejects, or cannot be reached with, one protocol may
+    // be serving the other at the same address.
+    if outcome != ExportOutcome::Accepted {
+        let other = protocol.other();
+        if let Ok(other_url) = export.export_url(other)
+            && test_export(&other_url, other, headers).await == ExportOutcome::Accepted
+        {
+            findings.push(Finding::new(
+                signal,
+                Severity::Problem,
+                format!(
+                    "The endpoint accepts {} exports: set {}={}",
+                    other.name(),
+                    export.protocol_var,
+                    other.name()
+                ),
+            ));
+        }
+    }
+    findings
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum ExportOutcome {
+    Accepted,
+    Rejected(String),
+    Unreachable(String),
+}
+
+/// Sends an empty export request, which is a valid request for every
+/// signal, so collectors accept it without recording anything.
+async fn test_export(url: &Url, protocol: Protocol, headers: &HeaderMap) -> ExportOutcome {
+    let client = reqwest::Client::builder().timeout(EXPORT_TIMEOUT);
+    let (client, content_type, body) = match protocol {
+        Protocol::HttpProtobuf => (client, "application/x-protobuf", vec![]),
+        Protocol::Grpc => {
+            // gRPC runs over HTTP/2, which plaintext connections must assume.
+            let client = if url.scheme() == "http" {
+                client.http2_prior_knowledge()
+            } else {
+                client
+            };
+            // An uncompressed, empty message.
+            (client, "application/grpc", vec![0; 5])
+        }
+    };
+    let client = match client.build() {
+        Ok(client) => client,
+        Err(err) => return ExportOutcome::Unreachable(format!("{err:#}")),
+    };
+    let mut request = client
+        .post(url.clone())
+        .headers(headers.clone())
+        .header(reqwest::header::CONTENT_TYPE, content_type)
+        .body(body);
+    if protocol == Protocol::Grpc {
+        request = request.header("te", "trailers");
+    }
+    let response = match request.send().await {
+        Ok(response) => response,
+        Err(err) => return ExportOutcome::Unreachable(describe_error(&err)),
+    };
+    let status = response.status();
+    match protocol {
+        Protocol::HttpProtobuf if status.is_success() => ExportOutcome::Accepted,
+        Protocol::HttpProtobuf => {
+            let body = response.text().await.unwrap_or_default();
+            ExportOutcome::Rejected(describe_status(status, &body))
+        }
+        Protocol::Grpc if status != StatusCode::OK => {
+            ExportOutcome::Rejected(describe_status(status, ""))
+        }
+        Protocol::Grpc => {
+            // Errors are reported in headers when a call fails before any
+            // response; success is reported in trailers, which are not read.
+            let header = |name| {
+                response
+                    .headers()
+                    .get(name)
+                    .and_then(|value| value.to_str().ok())
+                    .map(str::to_owned)
+            };
+            match header("grpc-status").as_deref() {
+                None | Some("0") => ExportOutcome::Accepted,
+                Some(code) => ExportOutcome::Rejected(format!(
+                    "gRPC status {code}{}",
+                    header("grpc-message")
+                        .map(|message| format!(": {message}"))
+                        .unwrap_or_default()
+                )),
+            }
+        }
+    }
+}
+
+fn describe_status(status: StatusCode, body: &str) -> String {
+    let hint = match status {
+        StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => {
+            format!(" (check the credentials in {OTEL_EXPORTER_OTLP_HEADERS})")
+        }
+        StatusCode::NOT_FOUND => " (check the endpoint's path)".into(),
+        StatusCode::UNSUPPORTED_MEDIA_TYPE => " (check the protocol)".into(),
+        _ => String::new(),
+    };
+    let body = body.trim();
+    let detail = if body.is_empty() {
+        String::new()
+    } else {
+        let excerpt = body.chars().take(200).collect::<String>();
+        format!(": {excerpt}")
+    };
+    format!("HTTP {status}{hint}{detail}")
+}
+
+fn describe_error(err: &reqwest::Error) -> String {
+    if err.is_timeout() {
+        format!("no response within {EXPORT_TIMEOUT:?}")
+    } else if err.is_connect() {
+        "could not connect".into()
+    } else {
+        // The innermost cause is usually the informative one.
+        let mut source: &dyn std::error::Error = err;
+        while let Some(inner) = source.source() {
+            source = inner;
+        }
+        source.to_string()
+    }
+}
+
+/// Parses the `key=value,...` list of `OTEL_EXPORTER_OTLP_HEADERS`.
+fn parse_headers(headers: &str) -> anyhow::Result<HeaderMap> {
+    let mut map = HeaderMap::new();
+    for pair in headers.split(',').filter(|pair| !pair.trim().is_empty()) {
+        let (name, value) = pair
+            .split_once('=')
+            .ok_or_else(|| anyhow::anyhow!("expected `key=value`; got {:?}", pair.trim()))?;
+        let name = reqwest::header::HeaderName::from_bytes(name.trim().as_bytes())?;
+        let value = percent_decode(value.trim());
+        map.append(name, value.parse()?);
+    }
+    Ok(map)
+}
+
+/// Decodes the `%XX` escapes the OTel specification allows in header values.
+fn percent_decode(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = (bytes[i] == b'%')
+            .then(|| bytes.get(i + 1..i + 3))
+            .flatten()
+            .and_then(|hex| u8::from_str_radix(std::str::from_utf8(hex).ok()?, 16).ok());
+        match escaped {
+            Some(byte) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            None => {
+                decoded.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    fn config(vars: &[(&str, &str)]) -> TelemetryConfig {
+        let vars: HashMap<String, String> = vars
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        TelemetryConfig::from_vars(|name| vars.get(name).cloned())
+    }
+
+    fn messages(findings: &[Finding], severity: Severity) -> Vec<&str> {
+        findings
+            .iter()
+            .filter(|f| f.severity == severity)
+            .map(|f| f.message.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn signal_specific_variables_override_general_ones() {
+        let config = config(&[
+            ("OTEL_EXPORTER_OTLP_ENDPOINT", "http://collector:4318"),
+            ("OTEL_EXPORTER_OTLP_TRACES_ENDPOINT", "http://traces:4317"),
+            ("OTEL_EXPORTER_OTLP_TRACES_PROTOCOL", "grpc"),
+            ("OTEL_EXPORTER_OTLP_LOGS_ENDPOINT", ""),
+        ]);
+        assert_eq!(3, config.exports.len());
+        let traces = &config.exports[0];
+        assert_eq!("http://traces:4317", traces.endpoint);
+        assert_eq!("OTEL_EXPORTER_OTLP_TRACES_PROTOCOL", traces.protocol_var);
+        assert_eq!(
+            "http://traces:4317/opentelemetry.proto.collector.trace.v1.TraceService/Export",
+            traces.export_url(Protocol::Grpc).unwrap().as_str()
+        );
+        let logs = &config.exports[2];
+        assert_eq!("http://collector:4318", logs.endpoint);
+        assert_eq!("http/protobuf", logs.protocol);
+        assert_eq!(
+            "http://collector:4318/v1/logs",
+            logs.export_url(Protocol::HttpProtobuf).unwrap().as_str()
+        );
+    }
+
+    #[test]
+    fn no_endpoint_is_reported() {
+        let findings = check_config(&config(&[]));
+        assert_eq!(1, messages(&findings, Severity::Warning).len());
+    }
+
+    #[test]
+    fn port_protocol_mismatches_are_reported() {
+        let findings = check_config(&config(&[(
+            "OTEL_EXPORTER_OTLP_ENDPOINT",
+            "http://localhost:4317",
+        )]));
+        let warnings = messages(&findings, Severity::Warning);
+        assert_eq!(3, warnings.len());
+        assert!(warnings[0].contains("port 4317"), "{warnings:?}");
+
+        let findings = check_config(&config(&[
+            ("OTEL_EXPORTER_OTLP_ENDPOINT", "http://localhost:4318"),
+            ("OTEL_EXPORTER_OTLP_PROTOCOL", "grpc"),
+        ]));
+        assert_eq!(3, messages(&findings, Severity::Warning).len());
+
+        let findings = check_config(&config(&[(
+            "OTEL_EXPORTER_OTLP_ENDPOINT",
+            "http://localhost:4318",
+        )]));
+        assert!(findings.is_empty(), "{findings:?}");
+    }
+
+    #[test]
+    fn invalid_settings_are_problems() {
+        let findings = check_config(&config(&[
+            ("OTEL_SDK_DISABLED", "true"),
+            ("OTEL_EXPORTER_OTLP_TRACES_ENDPOINT", "collector:4318"),
+            (
+                "OTEL_EXPORTER_OTLP_METRICS_ENDPOINT",
+                "http://collector/v1/metrics",
+            ),
+            ("OTEL_EXPORTER_OTLP_METRICS_PROTOCOL", "http/json"),
+            ("OTEL_EXPORTER_OTLP_LOGS_ENDPOINT", "http://collector:4318/"),
+            ("OTEL_EXPORTER_OTLP_LOGS_HEADERS", "api-key"),
+        ]));
+        let problems = messages(&findings, Severity::Problem);
+        assert_eq!(4, problems.len(), "{problems:?}");
+        assert!(problems[0].contains("OTEL_SDK_DISABLED"));
+        assert!(problems[1].contains("not an http:// or https:// URL"));
+        assert!(problems[2].contains("http/json"));
+        assert!(problems[3].contains("invalid OTLP headers"));
+        // The logs endpoint is specific to logs, so is used as-is.
+        let warnings = messages(&findings, Severity::Warning);
+        assert_eq!(1, warnings.len(), "{warnings:?}");
+        assert!(warnings[0].contains("/v1/logs"));
+    }
+
+    #[test]
+    fn headers_are_percent_decoded() {
+        let headers = parse_headers("api-key=abc%3D%3D, x-tenant = acme ,").unwrap();
+        assert_eq!("abc==", headers["api-key"]);
+        assert_eq!("acme", headers["x-tenant"]);
+    }
+}
@@ -0,0 +1,118 @@
+/*
+ * A C ABI for embedding Spin's HTTP trigger.
+ *
+ * Functions which can fail return 0 on success and -1 on failure, after which
+ * spin_last_error() describes the failure.
+ */
+
+#ifndef SPIN_EMBED_H
+#define SPIN_EMBED_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* A loaded app. */
+typedef struct SpinApp SpinApp;
+
+/* An app's response to a request. */
+typedef struct SpinResponse SpinResponse;
+
+/* Options for loading an app. */
+typedef struct SpinAppOptions {
+    /* A manifest file, a directory containing one, or a registry reference. */
+    const char *source;
+    /* The runtime config file, or NULL for none. */
+    const char *runtime_config_file;
+    /* The state directory, or NULL for the default. */
+    const char *state_dir;
+    /* The working directory, or NULL for a temporary directory. */
+    const char *working_dir;
+    /* The number of threads to run the app on, or 0 for one per CPU. */
+    size_t worker_threads;
+} SpinAppOptions;
+
+/* A header, whose name and value need not be nul-terminated. */
+typedef struct SpinHeader {
+    const uint8_t *name;
+    size_t name_len;
+    const uint8_t *value;
+    size_t value_len;
+} SpinHeader;
+
+/* A request to dispatch to an app. */
+typedef struct SpinRequest {
+    /* The request method, such as "GET". */
+    const char *method;
+    /* The request URI, either a path and query or an absolute URI. */
+    const char *uri;
+    const SpinHeader *headers;
+    size_t header_count;
+    const uint8_t *body;
+    size_t body_len;
+    /* The address of the client, such as "192.0.2.1:54321", or NULL. */
+    const char *client_addr;
+} SpinRequest;
+
+/*
+ * Receives a log message, at a level from 0 (errors) to 4 (tracing). The
+ * message is only valid during the call.
+ */
+typedef void (*SpinLogCallback)(void *user_data, int level, const char *message);
+
+/*
+ * Returns a description of the last failure on the calling thread, or NULL if
+ * there has been none. The string is valid until the next failure on the
+ * thread.
+ */
+const char *spin_last_error(void);
+
+/*
+ * Sets the function runtime logs are passed to, and the most verbose level
+ * passed. The callback may be called from any thread. A NULL callback stops
+ * passing logs.
+ */
+void spin_set_log_callback(SpinLogCallback callback, void *user_data, int max_level);
+
+/* Loads an app, ready to dispatch requests to its HTTP triggers. */
+int spin_app_load(const SpinAppOptions *options, SpinApp **out);
+
+/*
+ * Dispatches a request to an app, blocking until the response is complete.
+ * Requests may be dispatched from several threads at once. The response must
+ * be freed with spin_response_free().
+ */
+int spin_app_handle_request(const SpinApp *app, const SpinRequest *request, SpinResponse **out);
+
+/* Returns a response's status code. */
+uint16_t spin_response_status(const SpinResponse *response);
+
+/* Returns the number of headers in a response. */
+size_t spin_response_header_count(const SpinResponse *response);
+
+/*
+ * Returns a response header, which is valid until the response is freed.
+ * Fails if index is out of range.
+ */
+int spin_response_header(const SpinResponse *response, size_t index, SpinHeader *out);
+
+/* Returns a response's body, which is valid until the response is freed. */
+void spin_response_body(const SpinResponse *response, const uint8_t **body, size_t *body_len);
+
+/* Frees a response. NULL is ignored. */
+void spin_response_free(SpinResponse *response);
+
+/*
+ * Shuts an app down. No requests may be dispatched to it once this is called.
+ * NULL is ignored.
+ */
+void spin_app_shutdown(SpinApp *app);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* SPIN_EMBED_H */
@@ -0,0 +1,554 @@
+//! A C ABI for embedding Spin's HTTP trigger in non-Rust hosts.
+//!
+//! A host loads an app with `spin_app_load`, dispatches requests to it with
+//! `spin_app_handle_request`, reads the response through the `spin_response_*`
+//! accessors, and shuts the app down with `spin_app_shutdown`. Functions which
+//! can fail return `0` on success and `-1` on failure, after which
+//! `spin_last_error` describes the failure. Runtime logs can be passed to the
+//! host with `spin_set_log_callback`.
+//!
+//! The declarations are in `include/spin_embed.h`.
+
+use std::{
+    cell::RefCell,
+    ffi::{CStr, CString, c_char, c_int, c_void},
+    net::SocketAddr,
+    path::Path,
+    sync::{Arc, Once},
+};
+
+use anyhow::{Context as _, bail};
+use bytes::Bytes;
+use http::{Request, Response, header};
+use http_body_util::BodyExt as _;
+use spin_embed::{AppBuilder, TriggerFactors, UserProvidedPath};
+use spin_trigger_http::{HttpServer, HttpTrigger, HttpTriggerConfig};
+
+mod log;
+
+pub use log::SpinLogCallback;
+
+/// The address requests are reported to come from when the host does not
+/// give one.
+const DEFAULT_CLIENT_ADDR: &str = "127.0.0.1:0";
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+fn set_last_error(err: anyhow::Error) {
+    let message = format!("{err:#}").replace('\0', "\\0");
+    LAST_ERROR.with(|last| *last.borrow_mut() = Some(CString::new(message).unwrap()));
+}
+
+/// Runs `f`, returning `0` if it succeeds or `-1` if it fails or panics.
+fn catch(f: impl FnOnce() -> anyhow::Result<()>) -> c_int {
+    match std::panic::catch_unwind(std::panic::AssertUnwindSafe(f)) {
+        Ok(Ok(())) => 0,
+        Ok(Err(err)) => {
+            set_last_error(err);
+            -1
+        }
+        Err(_) => {
+            set_last_error(anyhow::anyhow!("Spin panicked"));
+            -1
+        }
+    }
+}
+
+/// Reads a string argument, or `None` if it is null.
+///
+/// # Safety
+///
+/// `ptr` must be null or point to a nul-terminated string.
+unsafe fn str_arg<'a>(ptr: *const c_char, name: &str) -> anyhow::Result<Option<&'a str>> {
+    if ptr.is_null() {
+        return Ok(None);
+    }
+    // SAFETY: the caller guarantees `ptr` is a nul-terminated string.
+    let s = unsafe { CStr::from_ptr(ptr) };
+    Ok(Some(
+        s.to_str()
+            .with_context(|| format!("`{name}` is not valid UTF-8"))?,
+    ))
+}
+
+/// Reads a byte slice argument.
+///
+/// # Safety
+///
+/// `ptr` must be null, in which case `len` must be 0, or point to `len` bytes.
+unsafe fn bytes_arg<'a>(ptr: *const u8, len: usize) -> &'a [u8] {
+    if ptr.is_null() || len == 0 {
+        &[]
+    } else {
+        // SAFETY: the caller guarantees `ptr` points to `len` bytes.
+        unsafe { std::slice::from_raw_parts(ptr, len) }
+    }
+}
+
+/// Options for loading an app.
+#[repr(C)]
+pub struct SpinAppOptions {
+    /// A manifest file, a directory containing one, or a registry reference.
+    pub source: *const c_char,
+    /// The runtime config file, or null for none.
+    pub runtime_config_file: *const c_char,
+    /// The state directory, or null for the default.
+    pub state_dir: *const c_char,
+    /// The working directory, or null for a temporary directory.
+    pub working_dir: *const c_char,
+    /// The number of threads to run the app on, or 0 for one per CPU.
+    pub worker_threads: usize,
+}
+
+/// A loaded app.
+pub struct SpinApp {
+    server: Arc<HttpServer<TriggerFactors>>,
+    runtime: tokio::runtime::Runtime,
+    /// The app's working directory, if the host did not give one.
+    _temp_dir: Option<tempfile::TempDir>,
+}
+
+/// A request header.
+#[repr(C)]
+pub struct SpinHeader {
+    pub name: *const u8,
+    pub name_len: usize,
+    pub value: *const u8,
+    pub value_len: usize,
+}
+
+/// A request to dispatch to an app.
+#[repr(C)]
+pub struct SpinRequest {
+    /// The request method, such as `GET`.
+    pub method: *const c_char,
+    /// The request URI, either a path and query or an absolute URI.
+    pub uri: *const c_char,
+    pub headers: *const SpinHeader,
+    pub header_count: usize,
+    pub body: *const u8,
+    pub body_len: usize,
+    /// The address of the client, such as `192.0.2.1:54321`, or null.
+    pub client_addr: *const c_char,
+}
+
+/// An app's response to a request.
+pub struct SpinResponse {
+    status: u16,
+    headers: Vec<(Vec<u8>, Vec<u8>)>,
+    body: Bytes,
+}
+
+/// Returns a description of the last failure on the calling thread, or null
+/// if there has been none. The string is valid until the next failure on the
+/// thread.
+#[unsafe(no_mangle)]
+pub extern "C" fn spin_last_error() -> *const c_char {
+    LAST_ERROR.with(|last| {
+        last.borrow()
+            .as_ref()
+            .map_or(std::ptr::null(), |err| err.as_ptr())
+    })
+}
+
+/// Loads an app, ready to dispatch requests to its HTTP triggers.
+///
+/// # Safety
+///
+/// `options` must point to a valid `SpinAppOptions`, whose strings are null
+/// or nul-terminated, and `out` must be a valid pointer to write the app to.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn spin_app_load(
+    options: *const SpinAppOptions,
+    out: *mut *mut SpinApp,
+) -> c_int {
+    catch(|| {
+        if options.is_null() || out.is_null() {
+            bail!("`options` and `out` must not be null");
+        }
+        // SAFETY: the caller guarantees `options` is valid.
+        let options = unsafe { &*options };
+        // SAFETY: the caller guarantees the option strings are valid.
+        let (source, runtime_config_file, state_dir, working_dir) = unsafe {
+            (
+                str_arg(options.source, "source")?.context("`source` must not be null")?,
+                str_arg(options.runtime_config_file, "runtime_config_file")?,
+                str_arg(options.state_dir, "state_dir")?,
+                str_arg(options.working_dir, "working_dir")?,
+            )
+        };
+
+        let mut builder = AppBuilder::new();
+        builder = if Path::new(source).exists() {
+            builder.manifest(source)
+        } else {
+            builder.registry(source, false)
+        };
+        if let Some(file) = runtime_config_file {
+            builder = builder.runtime_config_file(file);
+        }
+        if let Some(dir) = state_dir {
+            builder = builder.state_dir(UserProvidedPath::Provided(dir.into()));
+        }
+        // The working directory is owned here rather than by the embedded
+        // app, which is only needed while loading.
+        let temp_dir = match working_dir {
+            Some(dir) => {
+                builder = builder.working_dir(dir);
+                None
+            }
+            None => {
+                let dir = tempfile::tempdir().context("failed to create working directory")?;
+                builder = builder.working_dir(dir.path());
+                Some(dir)
+            }
+        };
+
+        let mut runtime = tokio::runtime::Builder::new_multi_thread();
+        if options.worker_threads > 0 {
+            runtime.worker_threads(options.worker_threads);
+        }
+        let runtime = runtime.enable_all().build()?;
+        let server = runtime.block_on(async {
+            let app = builder.load().await?;
+            let prepared = app
+                .prepare_with(|app| {
+                    // The trigger's listen address is unused, as the host
+                    // dispatches requests itself.
+                    HttpTrigger::new(
+                        app,
+                        HttpTriggerConfig::new(SocketAddr::from(([127, 0, 0, 1], 0))),
+                    )
+                })
+                .await?;
+            let (trigger, trigger_app) = prepared.into_parts();
+            trigger.into_server(trigger_app)
+        })?;
+
+        let app = Box::new(SpinApp {
+            server,
+            runtime,
+            _temp_dir: temp_dir,
+        });
+        // SAFETY: the caller guarantees `out` is valid.
+        unsafe { *out = Box::into_raw(app) };
+        Ok(())
+    })
+}
+
+/// Dispatches a request to an app, blocking until the response is complete.
+///
+/// Requests may be dispatched from several threads at once, but not from a
+/// thread which is running an async runtime.
+///
+/// # Safety
+///
+/// `app` must have been returned by `spin_app_load` and not shut down,
+/// `request` must point to a valid `SpinRequest`, and `out` must be a valid
+/// pointer to write the response to.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn spin_app_handle_request(
+    app: *const SpinApp,
+    request: *const SpinRequest,
+    out: *mut *mut SpinResponse,
+) -> c_int {
+    catch(|| {
+        if app.is_null() || request.is_null() || out.is_null() {
+            bail!("`app`, `request` and `out` must not be null");
+        }
+        // SAFETY: the caller guarantees `app` and `request` are valid.
+        let (app, request) = unsafe { (&*app, &*request) };
+        // SAFETY: the caller guarantees the request's fields are valid.
+        let (req, client_addr) = unsafe { to_http_request(request)? };
+        let response = app.runtime.block_on(async {
+            let response = app
+                .server
+                .handle(req, http::uri::Scheme::HTTP, client_addr)
+                .await?;
+            let (parts, body) = response.into_parts();
+            let body = body
+                .collect()
+                .await
+                .context("failed to read response body")?
+                .to_bytes();
+            anyhow::Ok(Response::from_parts(parts, body))
+        })?;
+        let response = Box::new(SpinResponse {
+            status: response.status().as_u16(),
+            headers: response
+                .headers()
+                .iter()
+                .map(|(name, value)| (name.as_str().into(), value.as_bytes().into()))
+                .collect(),
+            body: response.into_body(),
+        });
+        // SAFETY: the caller guarantees `out` is valid.
+        unsafe { *out = Box::into_raw(response) };
+        Ok(())
+    })
+}
+
+/// Converts a request from the host.
+///
+/// # Safety
+///
+/// The request's fields must be valid.
+unsafe fn to_http_request(
+    request: &SpinRequest,
+) -> anyhow::Result<(Request<spin_http::Body>, SocketAddr)> {
+    // SAFETY: the caller guarantees the request's strings are valid.
+    let (method, uri, client_addr) = unsafe {
+        (
+            str_arg(request.method, "method")?.context("`method` must not be null")?,
+            str_arg(request.uri, "uri")?.context("`uri` must not be null")?,
+            str_arg(request.client_addr, "client_addr")?,
+        )
+    };
+    let mut builder = Request::builder().method(method).uri(uri);
+    if request.header_count > 0 && request.headers.is_null() {
+        bail!("`headers` must not be null");
+    }
+    for i in 0..request.header_count {
+        // SAFETY: the caller guarantees `headers` points to `header_count`
+        // valid headers.
+        let (name, value) = unsafe {
+            let header = &*request.headers.add(i);
+            (
+                bytes_arg(header.name, header.name_len),
+                bytes_arg(header.value, header.value_len),
+            )
+        };
+        builder = builder.header(name, value);
+    }
+    // SAFETY: the caller guarantees `body` points to `body_len` bytes.
+    let body = Bytes::copy_from_slice(unsafe { bytes_arg(request.body, request.body_len) });
+    let mut req = builder
+        .body(spin_http::body::full(body))
+        .context("invalid request")?;
+    if req.uri().authority().is_none() && !req.headers().contains_key(header::HOST) {
+        req.headers_mut()
+            .insert(header::HOST, header::HeaderValue::from_static("localhost"));
+    }
+    let client_addr = client_addr
+        .unwrap_or(DEFAULT_CLIENT_ADDR)
+        .parse()
+        .context("invalid `client_addr`")?;
+    Ok((req, client_addr))
+}
+
+/// Returns a response's status code.
+///
+/// # Safety
+///
+/// `response` must have been returned by `spin_app_handle_request` and not
+/// freed.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn spin_response_status(response: *const SpinResponse) -> u16 {
+    // SAFETY: the caller guarantees `response` is valid.
+    unsafe { (*response).status }
+}
+
+/// Returns the number of headers in a response.
+///
+/// # Safety
+///
+/// `response` must have been returned by `spin_app_handle_request` and not
+/// freed.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn spin_response_header_count(response: *const SpinResponse) -> usize {
+    // SAFETY: the caller guarantees `response` is valid.
+    unsafe { (*response).headers.len() }
+}
+
+/// Returns a response header, which is valid until the response is freed.
+/// Returns `-1` if `index` is out of range.
+///
+/// # Safety
+///
+/// `response` must have been returned by `spin_app_handle_request` and not
+/// freed, and `out` must be a valid pointer to write the header to.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn spin_response_header(
+    response: *const SpinResponse,
+    index: usize,
+    out: *mut SpinHeader,
+) -> c_int {
+    // SAFETY: the caller guarantees `response` is valid.
+    let response = unsafe { &*response };
+    let Some((name, value)) = response.headers.get(index) else {
+        set_last_error(anyhow::anyhow!("header index {index} out of range"));
+        return -1;
+    };
+    let header = SpinHeader {
+        name: name.as_ptr(),
+        name_len: name.len(),
+        value: value.as_ptr(),
+        value_len: value.len(),
+    };
+    // SAFETY: the caller guarantees `out` is valid.
+    unsafe { *out = header };
+    0
+}
+
+/// Returns a response's body, which is valid until the response is freed.
+///
+/// # Safety
+///
+/// `response` must have been returned by `spin_app_handle_request` and not
+/// freed, and `body` and `body_len` must be valid pointers to write to.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn spin_response_body(
+    response: *const SpinResponse,
+    body: *mut *const u8,
+    body_len: *mut usize,
+) {
+    // SAFETY: the caller guarantees the pointers are valid.
+    unsafe {
+        let response = &*response;
+        *body = response.body.as_ptr();
+        *body_len = response.body.len();
+    }
+}
+
+/// Frees a response. Null is ignored.
+///
+/// # Safety
+///
+/// `response` must be null or have been returned by `spin_app_handle_request`
+/// and not freed.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn spin_response_free(response: *mut SpinResponse) {
+    if !response.is_null() {
+        // SAFETY: the caller guarantees `response` came from `Box::into_raw`.
+        drop(unsafe { Box::from_raw(response) });
+    }
+}
+
+/// Shuts an app down. Null is ignored.
+///
+/// # Safety
+///
+/// `app` must be null or have been returned by `spin_app_load` and not shut
+/// down, and no requests may be dispatched to it once this is called.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn spin_app_shutdown(app: *mut SpinApp) {
+    if app.is_null() {
+        return;
+    }
+    // SAFETY: the caller guarantees `app` came from `Box::into_raw`.
+    let app = unsafe { Box::from_raw(app) };
+    let SpinApp {
+        server,
+        runtime,
+        _temp_dir,
+    } = *app;
+    // Instances may be returned to pools from the runtime's threads, so the
+    // server is dropped before they stop.
+    drop(server);
+    runtime.shutdown_background();
+}
+
+/// Sets the function runtime logs are passed to, and the most verbose level
+/// passed: 0 for errors, 1 for warnings, 2 for information, 3 for debugging
+/// and 4 for tracing. A null callback stops passing logs.
+///
+/// # Safety
+///
+/// `callback` must be safe to call from any thread with `user_data` until it
+/// is replaced.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn spin_set_log_callback(
+    callback: Option<SpinLogCallback>,
+    user_data: *mut c_void,
+    max_level: c_int,
+) {
+    static INIT: Once = Once::new();
+    INIT.call_once(log::init);
+    log::set_callback(callback, user_data, max_level);
+}
+
+// Apps are shared between the host's threads.
+const _: fn() = || {
+    fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<SpinApp>();
+};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn last_error() -> String {
+        let err = spin_last_error();
+        assert!(!err.is_null());
+        unsafe { CStr::from_ptr(err) }
+            .to_string_lossy()
+            .into_owned()
+    }
+
+    #[test]
+    fn load_requires_a_source() {
+        let options = SpinAppOptions {
+            source: std::ptr::null(),
+            runtime_config_file: std::ptr::null(),
+            state_dir: std::ptr::null(),
+            working_dir: std::ptr::null(),
+            worker_threads: 1,
+        };
+        let mut app = std::ptr::null_mut();
+        assert_eq!(-1, unsafe { spin_app_load(&options, &mut app) });
+        assert!(app.is_null());
+        assert!(last_error().contains("source"), "{}", last_error());
+    }
+
+    #[test]
+    fn requests_are_converted() -> anyhow::Result<()> {
+        let method = CString::new("POST")?;
+        let uri = CString::new("/echo?x=1")?;
+        let headers = [SpinHeader {
+            name: b"content-type".as_ptr(),
+            name_len: 12,
+            value: b"text/plain".as_ptr(),
+            value_len: 10,
+        }];
+        let body = b"hello";
+        let request = SpinRequest {
+            method: method.as_ptr(),
+            uri: uri.as_ptr(),
+            headers: headers.as_ptr(),
+            header_count: headers.len(),
+            body: body.as_ptr(),
+            body_len: body.len(),
+            client_addr: std::ptr::null(),
+        };
+        let (req, client_addr) = unsafe { to_http_request(&request)? };
+        assert_eq!("POST", req.method());
+        assert_eq!("/echo?x=1", req.uri());
+        assert_eq!("text/plain", req.headers()[header::CONTENT_TYPE]);
+        assert_eq!("localhost", req.headers()[header::HOST]);
+        assert_eq!(DEFAULT_CLIENT_ADDR.parse::<SocketAddr>()?, client_addr);
+        Ok(())
+    }
+
+    #[test]
+    fn response_accessors_reject_bad_indices() {
+        let response = Box::into_raw(Box::new(SpinResponse {
+            status: 201,
+            headers: vec![(b"x-a".to_vec(), b"1".to_vec())],
+            body: Bytes::from_static(b"done"),
+        }));
+        unsafe {
+            assert_eq!(201, spin_response_status(response));
+            assert_eq!(1, spin_response_header_count(response));
+            let mut header = std::mem::zeroed::<SpinHeader>();
+            assert_eq!(0, spin_response_header(response, 0, &mut header));
+            assert_eq!(b"x-a", bytes_arg(header.name, header.name_len));
+            assert_eq!(-1, spin_response_header(response, 1, &mut header));
+            let (mut body, mut body_len) = (std::ptr::null(), 0);
+            spin_response_body(response, &mut body, &mut body_len);
+            assert_eq!(b"done", bytes_arg(body, body_len));
+            spin_response_free(response);
+        }
+    }
+}
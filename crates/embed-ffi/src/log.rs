@@ -0,0 +1,139 @@
+//! Passing the runtime's logs to the host.
+
+use std::{
+    ffi::{CString, c_char, c_int, c_void},
+    fmt::Write as _,
+    sync::RwLock,
+};
+
+use tracing::{Event, Level, Subscriber, field::Field};
+use tracing_subscriber::{Layer, layer::Context, prelude::*};
+
+/// Receives a log message, at a level from 0 (errors) to 4 (tracing). The
+/// message is only valid during the call.
+pub type SpinLogCallback =
+    unsafe extern "C" fn(user_data: *mut c_void, level: c_int, message: *const c_char);
+
+#[derive(Clone, Copy)]
+struct Callback {
+    callback: SpinLogCallback,
+    user_data: *mut c_void,
+    max_level: c_int,
+}
+
+// SAFETY: the host guarantees the callback may be called from any thread with
+// its user data.
+unsafe impl Send for Callback {}
+unsafe impl Sync for Callback {}
+
+static CALLBACK: RwLock<Option<Callback>> = RwLock::new(None);
+
+/// Installs the layer which passes logs to the callback, if no other
+/// subscriber has been installed.
+pub(crate) fn init() {
+    if tracing_subscriber::registry()
+        .with(CallbackLayer)
+        .try_init()
+        .is_err()
+    {
+        tracing::warn!("A tracing subscriber is already installed; logs will not be passed on");
+    }
+}
+
+pub(crate) fn set_callback(
+    callback: Option<SpinLogCallback>,
+    user_data: *mut c_void,
+    max_level: c_int,
+) {
+    *CALLBACK.write().unwrap() = callback.map(|callback| Callback {
+        callback,
+        user_data,
+        max_level,
+    });
+}
+
+fn level_number(level: &Level) -> c_int {
+    match *level {
+        Level::ERROR => 0,
+        Level::WARN => 1,
+        Level::INFO => 2,
+        Level::DEBUG => 3,
+        Level::TRACE => 4,
+    }
+}
+
+struct CallbackLayer;
+
+impl<S: Subscriber> Layer<S> for CallbackLayer {
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        // Copied out so that the lock is not held during the call, in case
+        // the callback logs or replaces itself.
+        let Some(callback) = *CALLBACK.read().unwrap() else {
+            return;
+        };
+        let level = level_number(event.metadata().level());
+        if level > callback.max_level {
+            return;
+        }
+        let mut message = Message::default();
+        event.record(&mut message);
+        let text = format!("{}: {}", event.metadata().target(), message.0);
+        let Ok(text) = CString::new(text.replace('\0', "\\0")) else {
+            return;
+        };
+        // SAFETY: the host guarantees the callback may be called with its
+        // user data.
+        unsafe { (callback.callback)(callback.user_data, level, text.as_ptr()) };
+    }
+}
+
+/// Formats an event's message and fields.
+#[derive(Default)]
+struct Message(String);
+
+impl tracing::field::Visit for Message {
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        if field.name() == "message" {
+            let _ = write!(self.0, "{value:?}");
+        } else {
+            let _ = write!(self.0, " {}={value:?}", field.name());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use super::*;
+
+    static RECEIVED: Mutex<Vec<(c_int, String)>> = Mutex::new(vec![]);
+
+    unsafe extern "C" fn receive(_user_data: *mut c_void, level: c_int, message: *const c_char) {
+        let message = unsafe { std::ffi::CStr::from_ptr(message) };
+        RECEIVED
+            .lock()
+            .unwrap()
+            .push((level, message.to_string_lossy().into_owned()));
+    }
+
+    #[test]
+    fn logs_are_passed_up_to_the_max_level() {
+        let _guard =
+            tracing::subscriber::set_default(tracing_subscriber::registry().with(CallbackLayer));
+        set_callback(Some(receive), std::ptr::null_mut(), 1);
+        tracing::warn!(component = "api", "slow request");
+        tracing::info!("not passed on");
+        set_callback(None, std::ptr::null_mut(), 4);
+        tracing::error!("after the callback is cleared");
+
+        let received = RECEIVED.lock().unwrap();
+        assert_eq!(1, received.len());
+        assert_eq!(1, received[0].0);
+        assert!(
+            received[0].1.ends_with("slow request component=\"api\""),
+            "{}",
+            received[0].1
+        );
+    }
+}
@@ -0,0 +1,489 @@
+//! Embedding the Spin runtime in other Rust programs.
+//!
+//! This crate is the supported way to run Spin applications from Rust without
+//! going through the `spin` CLI. An [`AppBuilder`] loads an application from
+//! a manifest, a registry, or a lock file, and the resulting [`EmbeddedApp`]
+//! runs its triggers:
+//!
+//! ```no_run
+//! # async fn run() -> anyhow::Result<()> {
+//! use spin_embed::AppBuilder;
+//! use spin_trigger_http::{HttpTrigger, HttpTriggerConfig};
+//!
+//! let app = AppBuilder::new()
+//!     .manifest("spin.toml")
+//!     .runtime_config_file("runtime-config.toml")
+//!     .load()
+//!     .await?;
+//! let trigger = app
+//!     .prepare_with(|app| {
+//!         HttpTrigger::new(app, HttpTriggerConfig::new("127.0.0.1:3000".parse()?))
+//!     })
+//!     .await?;
+//! trigger.run().await
+//! # }
+//! ```
+//!
+//! Applications run with Spin's standard factors, [`TriggerFactors`], unless
+//! another [`RuntimeFactorsBuilder`] is chosen with [`AppBuilder::factors`];
+//! host components are added by including their factors there. Host functions
+//! which need no state of their own can instead be added with
+//! [`AppBuilder::add_to_linker`].
+
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use anyhow::{Context as _, bail};
+use spin_app::{App, locked::LockedApp};
+use spin_common::ui::quoted_path;
+use spin_core::Linker;
+use spin_factors::RuntimeFactors;
+use spin_factors_executor::InstanceState;
+use spin_loader::FilesMountStrategy;
+use spin_runtime_factors::FactorsBuilder;
+use spin_trigger::{
+    Trigger, TriggerApp,
+    cli::{FactorsConfig, FollowComponents, RuntimeFactorsBuilder, TriggerAppBuilder},
+    loader::ComponentLoader,
+};
+use tempfile::TempDir;
+
+pub use spin_runtime_factors::{TriggerAppArgs, TriggerFactors};
+pub use spin_trigger::cli::UserProvidedPath;
+
+/// The instance state of the components of an app run with the factors `F`.
+pub type EmbeddedInstanceState<F> = InstanceState<<F as RuntimeFactors>::InstanceState, ()>;
+
+/// Adds host functions to the linker of an app run with the factors `F`.
+type LinkerHook<F> =
+    Arc<dyn Fn(&mut Linker<EmbeddedInstanceState<F>>) -> anyhow::Result<()> + Send + Sync>;
+
+/// Where an app is loaded from.
+enum AppSource {
+    Manifest(PathBuf),
+    Registry { reference: String, insecure: bool },
+    Locked(Box<LockedApp>),
+}
+
+/// A builder for an [`EmbeddedApp`].
+pub struct AppBuilder<B: RuntimeFactorsBuilder = FactorsBuilder> {
+    source: Option<AppSource>,
+    working_dir: Option<PathBuf>,
+    cache_dir: Option<PathBuf>,
+    runtime_config_file: Option<PathBuf>,
+    state_dir: UserProvidedPath,
+    log_dir: UserProvidedPath,
+    follow_components: FollowComponents,
+    factors_args: B::CliArgs,
+    engine_hooks: Vec<Arc<dyn Fn(&mut spin_core::Config) + Send + Sync>>,
+    linker_hooks: Vec<LinkerHook<B::Factors>>,
+}
+
+impl AppBuilder {
+    /// Creates a builder for an app run with Spin's standard factors.
+    pub fn new() -> Self {
+        Self::factors(TriggerAppArgs::default())
+    }
+}
+
+impl Default for AppBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<B: RuntimeFactorsBuilder> AppBuilder<B> {
+    /// Creates a builder for an app run with the factors built by `B`, from
+    /// the given options.
+    pub fn factors(factors_args: B::CliArgs) -> Self {
+        Self {
+            source: None,
+            working_dir: None,
+            cache_dir: None,
+            runtime_config_file: None,
+            state_dir: UserProvidedPath::Default,
+            log_dir: UserProvidedPath::Unset,
+            follow_components: FollowComponents::None,
+            factors_args,
+            engine_hooks: vec![],
+            linker_hooks: vec![],
+        }
+    }
+
+    /// Loads the app from a manifest (`spin.toml`) file, or a directory
+    /// containing one.
+    pub fn manifest(mut self, path: impl Into<PathBuf>) -> Self {
+        self.source = Some(AppSource::Manifest(path.into()));
+        self
+    }
+
+    /// Loads the app from a registry reference, such as
+    /// `ghcr.io/example/app:v1`.
+    pub fn registry(mut self, reference: impl Into<String>, insecure: bool) -> Self {
+        self.source = Some(AppSource::Registry {
+            reference: reference.into(),
+            insecure,
+        });
+        self
+    }
+
+    /// Loads the app from a lock file's contents.
+    pub fn locked_app(mut self, locked_app: LockedApp) -> Self {
+        self.source = Some(AppSource::Locked(Box::new(locked_app)));
+        self
+    }
+
+    /// Sets the directory the app's files are copied to and its transient
+    /// state written to. Defaults to a temporary directory which is removed
+    /// when the [`EmbeddedApp`] is dropped.
+    pub fn working_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.working_dir = Some(dir.into());
+        self
+    }
+
+    /// Sets the directory registry artifacts and dependencies are cached in.
+    pub fn cache_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.cache_dir = Some(dir.into());
+        self
+    }
+
+    /// Sets the runtime config file to run the app with.
+    pub fn runtime_config_file(mut self, path: impl Into<PathBuf>) -> Self {
+        self.runtime_config_file = Some(path.into());
+        self
+    }
+
+    /// Sets where the app keeps its state, such as its default key-value
+    /// store. Defaults to the `.spin` directory beside its manifest.
+    pub fn state_dir(mut self, dir: UserProvidedPath) -> Self {
+        self.state_dir = dir;
+        self
+    }
+
+    /// Sets where component output is logged to. Defaults to not logging it
+    /// to files.
+    pub fn log_dir(mut self, dir: UserProvidedPath) -> Self {
+        self.log_dir = dir;
+        self
+    }
+
+    /// Writes the output of all components to the process's stdout and
+    /// stderr.
+    pub fn follow_components(mut self) -> Self {
+        self.follow_components = FollowComponents::All;
+        self
+    }
+
+    /// Updates the engine config before the engine of each trigger is built.
+    pub fn configure_engine(
+        mut self,
+        f: impl Fn(&mut spin_core::Config) + Send + Sync + 'static,
+    ) -> Self {
+        self.engine_hooks.push(Arc::new(f));
+        self
+    }
+
+    /// Adds host functions to the linker of each trigger.
+    pub fn add_to_linker(
+        mut self,
+        f: impl Fn(&mut Linker<EmbeddedInstanceState<B::Factors>>) -> anyhow::Result<()>
+        + Send
+        + Sync
+        + 'static,
+    ) -> Self {
+        self.linker_hooks.push(Arc::new(f));
+        self
+    }
+
+    /// Loads the app.
+    pub async fn load(self) -> anyhow::Result<EmbeddedApp<B>> {
+        let temp_dir;
+        let working_dir = match &self.working_dir {
+            Some(dir) => {
+                std::fs::create_dir_all(dir)
+                    .with_context(|| format!("failed to create {}", quoted_path(dir)))?;
+                temp_dir = None;
+                dir.clone()
+            }
+            None => {
+                let dir = tempfile::tempdir().context("failed to create working directory")?;
+                let path = dir.path().to_owned();
+                temp_dir = Some(dir);
+                path
+            }
+        };
+
+        let (locked_app, local_app_dir) = match self.source {
+            None => bail!("no app source; set one with `manifest`, `registry` or `locked_app`"),
+            Some(AppSource::Manifest(path)) => {
+                let (manifest_path, _) = spin_common::paths::find_manifest_file_path(Some(path))?;
+                let locked_app = spin_loader::from_file(
+                    &manifest_path,
+                    FilesMountStrategy::Copy(working_dir.join("assets")),
+                    None,
+                    self.cache_dir.clone(),
+                )
+                .await
+                .with_context(|| {
+                    format!(
+                        "failed to load manifest from {}",
+                        quoted_path(&manifest_path)
+                    )
+                })?;
+                let app_dir = spin_common::paths::parent_dir(&manifest_path)?;
+                (locked_app, Some(app_dir))
+            }
+            Some(AppSource::Registry {
+                reference,
+                insecure,
+            }) => {
+                let mut client = spin_oci::Client::new(insecure, self.cache_dir.clone())
+                    .await
+                    .context("cannot create registry client")?;
+                let artifact = spin_oci::OciLoader::new(&working_dir)
+                    .load_app(&mut client, &reference)
+                    .await?;
+                let locked_app = match artifact {
+                    spin_oci::ExecutableArtifact::Application(locked_app) => locked_app,
+                    spin_oci::ExecutableArtifact::Package(wasm_path) => {
+                        spin_loader::from_wasm_file(&wasm_path).await?
+                    }
+                };
+                (locked_app, None)
+            }
+            Some(AppSource::Locked(locked_app)) => (*locked_app, None),
+        };
+
+        Ok(EmbeddedApp {
+            locked_app,
+            local_app_dir,
+            working_dir,
+            runtime_config_file: self.runtime_config_file,
+            state_dir: self.state_dir,
+            log_dir: self.log_dir,
+            follow_components: self.follow_components,
+            factors_args: self.factors_args,
+            engine_hooks: self.engine_hooks,
+            linker_hooks: self.linker_hooks,
+            _temp_dir: temp_dir,
+        })
+    }
+}
+
+/// A loaded app, whose triggers can be run.
+pub struct EmbeddedApp<B: RuntimeFactorsBuilder = FactorsBuilder> {
+    locked_app: LockedApp,
+    local_app_dir: Option<PathBuf>,
+    working_dir: PathBuf,
+    runtime_config_file: Option<PathBuf>,
+    state_dir: UserProvidedPath,
+    log_dir: UserProvidedPath,
+    follow_components: FollowComponents,
+    factors_args: B::CliArgs,
+    engine_hooks: Vec<Arc<dyn Fn(&mut spin_core::Config) + Send + Sync>>,
+    linker_hooks: Vec<LinkerHook<B::Factors>>,
+    _temp_dir: Option<TempDir>,
+}
+
+impl<B: RuntimeFactorsBuilder> EmbeddedApp<B>
+where
+    B::CliArgs: Clone,
+{
+    /// The app's lock file contents.
+    pub fn locked_app(&self) -> &LockedApp {
+        &self.locked_app
+    }
+
+    /// The types of the app's triggers, in the order they first appear.
+    pub fn trigger_types(&self) -> Vec<&str> {
+        let mut types: Vec<&str> = vec![];
+        for trigger in &self.locked_app.triggers {
+            if !types.contains(&trigger.trigger_type.as_str()) {
+                types.push(&trigger.trigger_type);
+            }
+        }
+        types
+    }
+
+    /// The directory the app is run in.
+    pub fn working_dir(&self) -> &Path {
+        &self.working_dir
+    }
+
+    /// Prepares the app's triggers of type `T` to run, constructing the
+    /// trigger from its CLI arguments.
+    pub async fn prepare<T>(&self, cli_args: T::CliArgs) -> anyhow::Result<PreparedTrigger<T, B>>
+    where
+        T: Trigger<B::Factors, InstanceState = ()>,
+    {
+        self.prepare_with(|app| T::new(cli_args, app)).await
+    }
+
+    /// Prepares the app's triggers of type `T` to run, constructing the
+    /// trigger with the given function.
+    pub async fn prepare_with<T>(
+        &self,
+        new_trigger: impl FnOnce(&App) -> anyhow::Result<T>,
+    ) -> anyhow::Result<PreparedTrigger<T, B>>
+    where
+        T: Trigger<B::Factors, InstanceState = ()>,
+    {
+        let app = App::new(T::TYPE, self.locked_app.clone());
+        if let Err(unmet) = app.ensure_needs_only(T::TYPE, &T::supported_host_requirements()) {
+            bail!(
+                "This application requires the following features that are not available in the '{}' trigger: {unmet}",
+                T::TYPE
+            );
+        }
+        let app_id = app
+            .get_metadata(spin_app::APP_ORIGIN_KEY)?
+            .and_then(|origin: String| spin_common::app_state::app_id_from_origin(&origin));
+        let factors_config = FactorsConfig {
+            working_dir: self.working_dir.clone(),
+            runtime_config_file: self.runtime_config_file.clone(),
+            state_dir: self.state_dir.clone(),
+            local_app_dir: self
+                .local_app_dir
+                .as_ref()
+                .map(|dir| dir.to_string_lossy().into_owned()),
+            app_id,
+            follow_components: self.follow_components.clone(),
+            log_dir: self.log_dir.clone(),
+            ..Default::default()
+        };
+
+        let trigger = HostFunctions {
+            trigger: new_trigger(&app)?,
+            linker_hooks: self.linker_hooks.clone(),
+        };
+        let mut builder = TriggerAppBuilder::<_, B>::new(trigger);
+        for hook in &self.engine_hooks {
+            hook(builder.engine_config());
+        }
+        let trigger_app = builder
+            .build(
+                app,
+                factors_config,
+                self.factors_args.clone(),
+                &ComponentLoader::new(),
+            )
+            .await?;
+        Ok(PreparedTrigger {
+            trigger: builder.trigger.trigger,
+            trigger_app,
+        })
+    }
+}
+
+/// A trigger and the app it runs, ready to run.
+pub struct PreparedTrigger<T: Trigger<B::Factors>, B: RuntimeFactorsBuilder = FactorsBuilder> {
+    trigger: T,
+    trigger_app: TriggerApp<T, B::Factors>,
+}
+
+impl<T: Trigger<B::Factors>, B: RuntimeFactorsBuilder> PreparedTrigger<T, B> {
+    /// Runs the trigger until it stops or fails.
+    pub async fn run(self) -> anyhow::Result<()> {
+        self.trigger.run(self.trigger_app).await
+    }
+
+    /// Returns the trigger and the app it runs, for triggers which can be run
+    /// in other ways, such as by serving requests from the embedder's own
+    /// listener.
+    pub fn into_parts(self) -> (T, TriggerApp<T, B::Factors>) {
+        (self.trigger, self.trigger_app)
+    }
+}
+
+/// Wraps a trigger to add the embedder's host functions to its linker.
+struct HostFunctions<T, F: RuntimeFactors> {
+    trigger: T,
+    linker_hooks: Vec<LinkerHook<F>>,
+}
+
+impl<T, F> Trigger<F> for HostFunctions<T, F>
+where
+    T: Trigger<F, InstanceState = ()>,
+    F: RuntimeFactors,
+{
+    const TYPE: &'static str = T::TYPE;
+    type CliArgs = T::CliArgs;
+    type InstanceState = ();
+
+    fn new(cli_args: Self::CliArgs, app: &App) -> anyhow::Result<Self> {
+        Ok(Self {
+            trigger: T::new(cli_args, app)?,
+            linker_hooks: vec![],
+        })
+    }
+
+    fn update_core_config(&mut self, config: &mut spin_core::Config) -> anyhow::Result<()> {
+        self.trigger.update_core_config(config)
+    }
+
+    fn add_to_linker(
+        &mut self,
+        linker: &mut Linker<EmbeddedInstanceState<F>>,
+    ) -> anyhow::Result<()> {
+        self.trigger.add_to_linker(linker)?;
+        for hook in &self.linker_hooks {
+            hook(linker)?;
+        }
+        Ok(())
+    }
+
+    async fn run(self, trigger_app: TriggerApp<Self, F>) -> anyhow::Result<()> {
+        self.trigger.run(trigger_app).await
+    }
+
+    fn supported_host_requirements() -> Vec<&'static str> {
+        T::supported_host_requirements()
+    }
+
+    fn display_name() -> String {
+        T::display_name()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn locked_app() -> LockedApp {
+        serde_json::from_value(serde_json::json!({
+            "spin_lock_version": 1,
+            "metadata": { "name": "embedded" },
+            "triggers": [
+                { "id": "a", "trigger_type": "http", "trigger_config": {} },
+                { "id": "b", "trigger_type": "redis", "trigger_config": {} },
+                { "id": "c", "trigger_type": "http", "trigger_config": {} },
+            ],
+            "components": [],
+        }))
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn loads_locked_apps() -> anyhow::Result<()> {
+        let app = AppBuilder::new().locked_app(locked_app()).load().await?;
+        assert_eq!(vec!["http", "redis"], app.trigger_types());
+        assert!(app.working_dir().is_dir());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn temporary_working_dir_is_removed_on_drop() -> anyhow::Result<()> {
+        let app = AppBuilder::new().locked_app(locked_app()).load().await?;
+        let working_dir = app.working_dir().to_owned();
+        drop(app);
+        assert!(!working_dir.exists());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn requires_an_app_source() {
+        assert!(AppBuilder::new().load().await.is_err());
+    }
+}
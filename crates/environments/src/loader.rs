@@ -198,7 +198,11 @@ impl<'a> spin_compose::ComponentSourceLoader for ComponentSourceLoader<'a> {
         Ok(component.into())
     }
 
-    async fn load_dependency_source(&self, source: &Self::Dependency) -> anyhow::Result<Vec<u8>> {
+    async fn load_dependency_source(
+        &self,
+        _name: &spin_serde::DependencyName,
+        source: &Self::Dependency,
+    ) -> anyhow::Result<Vec<u8>> {
         let (path, _) = self
             .wasm_loader
             .load_dependency_content(&source.name, &source.dependency)
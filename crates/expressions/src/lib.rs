@@ -116,6 +116,7 @@ impl ProviderResolver {
     async fn resolve_variable(&self, key: &str) -> Result<String> {
         for provider in &self.providers {
             if let Some(value) = provider.get(&Key(key)).await.map_err(Error::Provider)? {
+                self.internal.register_if_secret(key, &value);
                 return Ok(value);
             }
         }
@@ -203,11 +204,20 @@ impl Resolver {
             // This should have been caught by validate_template
             .ok_or_else(|| Error::InvalidName(key.to_string()))?;
 
-        var.default.clone().ok_or_else(|| {
+        let value = var.default.clone().ok_or_else(|| {
             Error::Provider(anyhow::anyhow!(
                 "no provider resolved required variable {key:?}"
             ))
-        })
+        })?;
+        self.register_if_secret(key, &value);
+        Ok(value)
+    }
+
+    /// Registers the value of a secret variable to be redacted from output.
+    fn register_if_secret(&self, key: &str, value: &str) {
+        if self.variables.get(key).is_some_and(|var| var.secret) {
+            spin_common::secrets::register(value);
+        }
     }
 
     fn validate_template(&self, template: String) -> Result<Template> {
@@ -406,6 +416,33 @@ mod tests {
         );
     }
 
+    #[tokio::test]
+    async fn secret_values_are_registered_for_redaction() {
+        let mut resolver = ProviderResolver::new([(
+            "password".into(),
+            Variable {
+                description: None,
+                default: Some("correct-horse-battery".into()),
+                secret: true,
+            },
+        )])
+        .unwrap();
+        resolver
+            .add_component_variables(
+                "test-component",
+                [("db_url".into(), "postgres://admin:{{ password }}@db".into())],
+            )
+            .unwrap();
+        let url = resolver
+            .resolve("test-component", Key("db_url"))
+            .await
+            .unwrap();
+        assert_eq!(
+            "postgres://admin:[REDACTED]@db",
+            spin_common::secrets::redact(&url)
+        );
+    }
+
     #[test]
     fn keys_good() {
         for key in ["a", "abc", "a1b2c3", "a_1", "a_1_b_3"] {
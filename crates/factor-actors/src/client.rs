@@ -0,0 +1,64 @@
+use spin_world::spin::actors::client::Error;
+
+use crate::protocol::{ACTOR_FAILED, NO_SUCH_ACTOR_TYPE, actor_path};
+
+/// Sends calls to the actors served by the actor trigger.
+pub struct ActorClient {
+    http: reqwest::Client,
+    address: String,
+}
+
+impl ActorClient {
+    /// Creates a client of the actor trigger serving on the given address.
+    pub fn new(address: impl Into<String>) -> Self {
+        Self {
+            http: reqwest::Client::new(),
+            address: address.into(),
+        }
+    }
+
+    /// The address of the actor trigger.
+    pub fn address(&self) -> &str {
+        &self.address
+    }
+
+    /// Sends a message to an actor, returning its reply.
+    pub async fn call(
+        &self,
+        actor_type: &str,
+        id: &str,
+        message: Vec<u8>,
+    ) -> Result<Vec<u8>, Error> {
+        let url = format!("http://{}{}", self.address, actor_path(actor_type, id));
+        let mut headers = reqwest::header::HeaderMap::new();
+        spin_telemetry::inject_trace_context(&mut headers);
+        let response = self
+            .http
+            .post(url)
+            .headers(headers)
+            .body(message)
+            .send()
+            .await
+            .map_err(|err| {
+                if err.is_connect() {
+                    Error::Unavailable
+                } else {
+                    Error::Other(err.to_string())
+                }
+            })?;
+        let status = response.status();
+        let body = response
+            .bytes()
+            .await
+            .map_err(|err| Error::Other(err.to_string()))?;
+        match status {
+            status if status.is_success() => Ok(body.into()),
+            NO_SUCH_ACTOR_TYPE => Err(Error::NoSuchActorType),
+            ACTOR_FAILED => Err(Error::Failed(String::from_utf8_lossy(&body).into_owned())),
+            status => Err(Error::Other(format!(
+                "actor trigger responded with {status}: {}",
+                String::from_utf8_lossy(&body)
+            ))),
+        }
+    }
+}
@@ -0,0 +1,103 @@
+use std::{collections::HashSet, sync::Arc};
+
+use spin_factor_capability_policy::{CapabilityChecker, operation};
+use spin_factors::anyhow;
+use spin_world::spin::actors::client::{self, Error};
+use spin_world::spin::actors::state;
+use tracing::instrument;
+
+use crate::{ActorClient, ActorStates};
+
+/// The actors state of a component instance, and the host implementation of
+/// `spin:actors/client` and `spin:actors/state`.
+pub struct InstanceState {
+    client: Arc<ActorClient>,
+    states: Arc<ActorStates>,
+    allowed_types: Arc<HashSet<String>>,
+    capabilities: CapabilityChecker,
+    actor: Option<(String, String)>,
+}
+
+impl InstanceState {
+    pub fn new(
+        client: Arc<ActorClient>,
+        states: Arc<ActorStates>,
+        allowed_types: Arc<HashSet<String>>,
+        capabilities: CapabilityChecker,
+        actor: Option<(String, String)>,
+    ) -> Self {
+        Self {
+            client,
+            states,
+            allowed_types,
+            capabilities,
+            actor,
+        }
+    }
+
+    /// Returns the actor types the component may call.
+    pub fn allowed_types(&self) -> &HashSet<String> {
+        &self.allowed_types
+    }
+
+    /// The type and ID of the actor the instance serves, if it is an actor.
+    pub fn actor(&self) -> Option<(&str, &str)> {
+        self.actor
+            .as_ref()
+            .map(|(actor_type, id)| (actor_type.as_str(), id.as_str()))
+    }
+
+    fn actor_or_err(&self) -> Result<(&str, &str), Error> {
+        self.actor()
+            .ok_or_else(|| Error::Other("spin:actors/state may only be used by an actor".into()))
+    }
+}
+
+fn other_error(err: anyhow::Error) -> Error {
+    Error::Other(format!("{err:#}"))
+}
+
+impl client::Host for InstanceState {
+    #[instrument(name = "spin_actors.call", skip(self, message), err, fields(otel.kind = "client"))]
+    async fn call(
+        &mut self,
+        actor_type: String,
+        id: String,
+        message: Vec<u8>,
+    ) -> Result<Vec<u8>, Error> {
+        if !self.allowed_types.contains(&actor_type) {
+            return Err(Error::AccessDenied);
+        }
+        self.capabilities
+            .check(operation::ACTORS, &actor_type, Some("call"))
+            .map_err(|_| Error::AccessDenied)?;
+        self.client.call(&actor_type, &id, message).await
+    }
+
+    fn convert_error(&mut self, err: Error) -> anyhow::Result<Error> {
+        Ok(err)
+    }
+}
+
+impl state::Host for InstanceState {
+    #[instrument(name = "spin_actors.get_state", skip(self), err, fields(otel.kind = "client"))]
+    async fn get(&mut self) -> Result<Option<Vec<u8>>, Error> {
+        let (actor_type, id) = self.actor_or_err()?;
+        self.states.get(actor_type, id).await.map_err(other_error)
+    }
+
+    #[instrument(name = "spin_actors.set_state", skip(self, state), err, fields(otel.kind = "client"))]
+    async fn set(&mut self, state: Vec<u8>) -> Result<(), Error> {
+        let (actor_type, id) = self.actor_or_err()?;
+        self.states
+            .set(actor_type, id, &state)
+            .await
+            .map_err(other_error)
+    }
+
+    #[instrument(name = "spin_actors.clear_state", skip(self), err, fields(otel.kind = "client"))]
+    async fn clear(&mut self) -> Result<(), Error> {
+        let (actor_type, id) = self.actor_or_err()?;
+        self.states.clear(actor_type, id).await.map_err(other_error)
+    }
+}
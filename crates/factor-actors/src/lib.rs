@@ -0,0 +1,183 @@
+//! A factor providing the `spin:actors` client and state interfaces.
+//!
+//! Components call actors, addressed by type and ID, and the actor trigger
+//! serves each actor with a warm instance of the component for its type,
+//! handling its calls one at a time. Calls are sent to the trigger's address,
+//! `127.0.0.1:3400` unless the runtime config names another. An actor's
+//! persistent state is held in a key-value store of its own, "spin-actors"
+//! unless the runtime config names another. No component may open that
+//! store, so components can only reach actor state through the interfaces.
+
+mod client;
+mod host;
+pub mod protocol;
+pub mod runtime_config;
+mod state;
+
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+};
+
+use anyhow::ensure;
+use spin_factor_capability_policy::CapabilityChecker;
+use spin_factor_key_value::{KEY_VALUE_STORES_KEY, KeyValueFactor};
+use spin_factors::{
+    ConfigureAppContext, Factor, FactorData, FactorInstanceBuilder, InitContext, PrepareContext,
+    RuntimeFactors,
+};
+use spin_locked_app::MetadataKey;
+
+pub use client::ActorClient;
+pub use host::InstanceState;
+pub use runtime_config::RuntimeConfig;
+pub use spin_world::spin::actors::client as v3;
+pub use state::ActorStates;
+
+/// Metadata key for the actor types a component may call.
+pub const ACTORS_KEY: MetadataKey<Vec<String>> = MetadataKey::new("actors");
+
+/// The address the actor trigger serves actors on if not configured otherwise.
+pub const DEFAULT_ADDRESS: &str = "127.0.0.1:3400";
+
+/// The label of the store holding actor state if the runtime config names
+/// none.
+pub const DEFAULT_KEY_VALUE_STORE: &str = "spin-actors";
+
+/// A factor that provides actors.
+#[derive(Default)]
+pub struct ActorsFactor {
+    _priv: (),
+}
+
+impl ActorsFactor {
+    /// Create a new ActorsFactor.
+    pub fn new() -> Self {
+        Self { _priv: () }
+    }
+}
+
+impl Factor for ActorsFactor {
+    type RuntimeConfig = RuntimeConfig;
+    type AppState = AppState;
+    type InstanceBuilder = InstanceBuilder;
+
+    fn init(&mut self, ctx: &mut impl InitContext<Self>) -> anyhow::Result<()> {
+        ctx.link_bindings(spin_world::spin::actors::client::add_to_linker::<_, FactorData<Self>>)?;
+        ctx.link_bindings(spin_world::spin::actors::state::add_to_linker::<_, FactorData<Self>>)?;
+        Ok(())
+    }
+
+    fn configure_app<T: RuntimeFactors>(
+        &self,
+        mut ctx: ConfigureAppContext<T, Self>,
+    ) -> anyhow::Result<Self::AppState> {
+        let config = ctx.take_runtime_config().unwrap_or_default();
+        let store_manager = ctx.app_state::<KeyValueFactor>()?.store_manager();
+        let states = Arc::new(ActorStates::new(store_manager, config.key_value_store));
+        let client = Arc::new(ActorClient::new(config.address));
+
+        // Build component -> allowed actor types map
+        let mut component_actors = HashMap::new();
+        for component in ctx.app().components() {
+            let key_value_stores = component
+                .get_metadata(KEY_VALUE_STORES_KEY)?
+                .unwrap_or_default();
+            ensure!(
+                !key_value_stores.iter().any(|label| label == states.label()),
+                "component {:?} uses the key-value store {:?}, which holds actor state and can't be opened by components",
+                component.id(),
+                states.label()
+            );
+            let actor_types = component
+                .get_metadata(ACTORS_KEY)?
+                .unwrap_or_default()
+                .into_iter()
+                .collect::<HashSet<_>>();
+            component_actors.insert(component.id().to_string(), Arc::new(actor_types));
+        }
+
+        Ok(AppState {
+            client,
+            states,
+            component_actors,
+        })
+    }
+
+    fn prepare<T: RuntimeFactors>(
+        &self,
+        mut ctx: PrepareContext<T, Self>,
+    ) -> anyhow::Result<InstanceBuilder> {
+        let app_state = ctx.app_state();
+        let allowed_types = app_state
+            .component_actors
+            .get(ctx.app_component().id())
+            .expect("component should be in component_actors")
+            .clone();
+        let client = app_state.client.clone();
+        let states = app_state.states.clone();
+        let capabilities = CapabilityChecker::from_prepare_context(&mut ctx)?;
+        Ok(InstanceBuilder {
+            client,
+            states,
+            allowed_types,
+            capabilities,
+            actor: None,
+        })
+    }
+}
+
+pub struct AppState {
+    /// Sends calls to the actor trigger.
+    client: Arc<ActorClient>,
+    /// The app's actor state.
+    states: Arc<ActorStates>,
+    /// The actor types each component is allowed to call.
+    ///
+    /// This is a map from component ID to the set of actor types.
+    component_actors: HashMap<String, Arc<HashSet<String>>>,
+}
+
+impl AppState {
+    /// The app's actor state.
+    pub fn states(&self) -> Arc<ActorStates> {
+        self.states.clone()
+    }
+
+    /// Returns true if any component may call actors.
+    pub fn is_used(&self) -> bool {
+        self.component_actors
+            .values()
+            .any(|types| !types.is_empty())
+    }
+}
+
+pub struct InstanceBuilder {
+    client: Arc<ActorClient>,
+    states: Arc<ActorStates>,
+    allowed_types: Arc<HashSet<String>>,
+    capabilities: CapabilityChecker,
+    actor: Option<(String, String)>,
+}
+
+impl InstanceBuilder {
+    /// Sets up the instance to serve the actor with the given type and ID,
+    /// giving it access to the actor's state.
+    pub fn serve_actor(&mut self, actor_type: impl Into<String>, id: impl Into<String>) {
+        self.actor = Some((actor_type.into(), id.into()));
+    }
+}
+
+impl FactorInstanceBuilder for InstanceBuilder {
+    type InstanceState = InstanceState;
+
+    fn build(self) -> anyhow::Result<Self::InstanceState> {
+        Ok(InstanceState::new(
+            self.client,
+            self.states,
+            self.allowed_types,
+            self.capabilities,
+            self.actor,
+        ))
+    }
+}
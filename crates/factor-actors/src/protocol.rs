@@ -0,0 +1,54 @@
+//! How calls are sent to the actor trigger.
+//!
+//! A call is a `POST` to `/{actor-type}/{id}`, with the type and ID
+//! percent-encoded, and the message as the body. The trigger responds with
+//! `200 OK` and the reply as the body, [`NO_SUCH_ACTOR_TYPE`] if no component
+//! serves the type, or [`ACTOR_FAILED`] and the error as the body if the actor
+//! returned or trapped with an error.
+
+use percent_encoding::{NON_ALPHANUMERIC, percent_decode_str, utf8_percent_encode};
+use reqwest::StatusCode;
+
+/// The status of a call to an actor type which no component serves.
+pub const NO_SUCH_ACTOR_TYPE: StatusCode = StatusCode::NOT_FOUND;
+/// The status of a call which the actor returned or trapped with an error.
+pub const ACTOR_FAILED: StatusCode = StatusCode::UNPROCESSABLE_ENTITY;
+
+/// Returns the path a call to an actor is sent to.
+pub fn actor_path(actor_type: &str, id: &str) -> String {
+    format!("/{}/{}", encode(actor_type), encode(id))
+}
+
+/// Returns the actor type and ID a call was sent to, if the path is an actor
+/// path.
+pub fn parse_actor_path(path: &str) -> Option<(String, String)> {
+    let (actor_type, id) = path.strip_prefix('/')?.split_once('/')?;
+    let decode = |s: &str| {
+        percent_decode_str(s)
+            .decode_utf8()
+            .ok()
+            .map(|s| s.into_owned())
+    };
+    Some((decode(actor_type)?, decode(id)?))
+}
+
+pub(crate) fn encode(s: &str) -> String {
+    utf8_percent_encode(s, NON_ALPHANUMERIC).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn actor_paths_round_trip() {
+        let path = actor_path("cart", "user/1 ü");
+        assert_eq!("/cart/user%2F1%20%C3%BC", path);
+        assert_eq!(
+            Some(("cart".to_owned(), "user/1 ü".to_owned())),
+            parse_actor_path(&path)
+        );
+        assert_eq!(None, parse_actor_path("/cart"));
+        assert_eq!(None, parse_actor_path("cart/1"));
+    }
+}
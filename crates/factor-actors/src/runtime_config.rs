@@ -0,0 +1,39 @@
+use serde::Deserialize;
+use spin_factors::runtime_config::toml::GetTomlValue;
+
+use crate::{DEFAULT_ADDRESS, DEFAULT_KEY_VALUE_STORE};
+
+/// Runtime configuration for actors.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RuntimeConfig {
+    /// The label of the key-value store holding actor state. No component may
+    /// list it in `key_value_stores`.
+    pub key_value_store: String,
+    /// The address the actor trigger serves actors on.
+    pub address: String,
+}
+
+impl Default for RuntimeConfig {
+    fn default() -> Self {
+        Self {
+            key_value_store: DEFAULT_KEY_VALUE_STORE.into(),
+            address: DEFAULT_ADDRESS.into(),
+        }
+    }
+}
+
+/// Get the runtime configuration for actors from a TOML table.
+///
+/// Expects table to be in the format:
+/// ```toml
+/// [actors]
+/// key_value_store = "actors"
+/// address = "127.0.0.1:3400"
+/// ```
+pub fn config_from_table(table: &impl GetTomlValue) -> anyhow::Result<Option<RuntimeConfig>> {
+    let Some(config) = table.get("actors") else {
+        return Ok(None);
+    };
+    Ok(Some(config.clone().try_into()?))
+}
@@ -0,0 +1,77 @@
+use std::sync::Arc;
+
+use anyhow::Context as _;
+use spin_factor_key_value::{Store, StoreManager};
+use spin_world::MAX_HOST_BUFFERED_BYTES;
+
+/// The prefix of the keys of actor state.
+const STATE_PREFIX: &str = "spin-actors:state:";
+
+/// The persistent state of an app's actors, held in a key-value store.
+///
+/// The store is dedicated to actor state: [`ActorsFactor`](crate::ActorsFactor)
+/// refuses apps whose components may open it.
+pub struct ActorStates {
+    store_manager: Arc<dyn StoreManager>,
+    label: String,
+}
+
+impl ActorStates {
+    /// Creates actor state held in the store with the given label.
+    pub fn new(store_manager: Arc<dyn StoreManager>, label: impl Into<String>) -> Self {
+        Self {
+            store_manager,
+            label: label.into(),
+        }
+    }
+
+    /// The label of the store holding actor state.
+    pub fn label(&self) -> &str {
+        &self.label
+    }
+
+    /// Returns true if the store holding actor state is defined.
+    pub fn is_defined(&self) -> bool {
+        self.store_manager.is_defined(&self.label)
+    }
+
+    async fn store(&self) -> anyhow::Result<Arc<dyn Store>> {
+        self.store_manager
+            .get(&self.label)
+            .await
+            .with_context(|| format!("failed to open key-value store {:?} for actors", self.label))
+    }
+
+    /// Returns the state of an actor, if it has been set.
+    pub async fn get(&self, actor_type: &str, id: &str) -> anyhow::Result<Option<Vec<u8>>> {
+        Ok(self
+            .store()
+            .await?
+            .get(&key(actor_type, id), MAX_HOST_BUFFERED_BYTES)
+            .await?)
+    }
+
+    /// Sets the state of an actor.
+    pub async fn set(&self, actor_type: &str, id: &str, state: &[u8]) -> anyhow::Result<()> {
+        self.store()
+            .await?
+            .set(&key(actor_type, id), state)
+            .await
+            .with_context(|| format!("failed to store state of actor {actor_type}/{id}"))
+    }
+
+    /// Clears the state of an actor.
+    pub async fn clear(&self, actor_type: &str, id: &str) -> anyhow::Result<()> {
+        self.store()
+            .await?
+            .delete(&key(actor_type, id))
+            .await
+            .with_context(|| format!("failed to clear state of actor {actor_type}/{id}"))
+    }
+}
+
+/// The key of an actor's state. The type is encoded so that it cannot contain
+/// the separator.
+fn key(actor_type: &str, id: &str) -> String {
+    format!("{STATE_PREFIX}{}:{id}", crate::protocol::encode(actor_type))
+}
@@ -0,0 +1,159 @@
+use std::sync::Arc;
+
+use anyhow::bail;
+use spin_factor_actors::{
+    ActorClient, ActorStates, ActorsFactor, DEFAULT_KEY_VALUE_STORE, InstanceState, v3,
+};
+use spin_factor_capability_policy::{CapabilityPolicyFactor, runtime_config::config_from_table};
+use spin_factor_key_value::runtime_config::spin::MakeKeyValueStore;
+use spin_factor_key_value::{DelegatingStoreManager, KeyValueFactor, RuntimeConfig};
+use spin_factors::RuntimeFactors;
+use spin_factors_test::{TestEnvironment, toml};
+use spin_key_value_spin::{SpinKeyValueRuntimeConfig, SpinKeyValueStore};
+use spin_world::spin::actors::state::Host as _;
+use v3::Host as _;
+
+#[derive(RuntimeFactors)]
+struct TestFactors {
+    key_value: KeyValueFactor,
+    actors: ActorsFactor,
+}
+
+fn factors() -> TestFactors {
+    TestFactors {
+        key_value: KeyValueFactor::new(),
+        actors: ActorsFactor::new(),
+    }
+}
+
+fn in_memory_store() -> anyhow::Result<RuntimeConfig> {
+    let store_manager =
+        Arc::new(SpinKeyValueStore::new(None).make_store(SpinKeyValueRuntimeConfig::new(None))?);
+    let mut runtime_config = RuntimeConfig::default();
+    runtime_config.add_store_manager(DEFAULT_KEY_VALUE_STORE.into(), store_manager);
+    Ok(runtime_config)
+}
+
+#[tokio::test]
+async fn calls_only_allowed_actor_types() -> anyhow::Result<()> {
+    let env = TestEnvironment::new(factors()).extend_manifest(toml! {
+        [component.test-component]
+        source = "does-not-exist.wasm"
+        actors = ["cart"]
+    });
+    let mut state = env
+        .runtime_config(TestFactorsRuntimeConfig {
+            key_value: Some(in_memory_store()?),
+            actors: Some(spin_factor_actors::RuntimeConfig {
+                // Nothing serves actors on this port.
+                address: "127.0.0.1:1".into(),
+                ..Default::default()
+            }),
+        })?
+        .build_instance_state()
+        .await?;
+
+    assert!(matches!(
+        state.actors.call("device".into(), "1".into(), vec![]).await,
+        Err(v3::Error::AccessDenied)
+    ));
+    assert!(matches!(
+        state.actors.call("cart".into(), "1".into(), vec![]).await,
+        Err(v3::Error::Unavailable)
+    ));
+    assert!(matches!(state.actors.get().await, Err(v3::Error::Other(_))));
+    Ok(())
+}
+
+#[tokio::test]
+async fn actors_have_their_own_state() -> anyhow::Result<()> {
+    let store_manager = Arc::new(DelegatingStoreManager::new(in_memory_store()?));
+    let states = Arc::new(ActorStates::new(store_manager, DEFAULT_KEY_VALUE_STORE));
+    let actor = |id: &str| {
+        InstanceState::new(
+            Arc::new(ActorClient::new(spin_factor_actors::DEFAULT_ADDRESS)),
+            states.clone(),
+            Default::default(),
+            Default::default(),
+            Some(("cart".into(), id.into())),
+        )
+    };
+    let mut alice = actor("alice");
+    let mut bob = actor("bob");
+
+    assert_eq!(None, alice.get().await.unwrap());
+    alice.set(b"apples".to_vec()).await.unwrap();
+    bob.set(b"bananas".to_vec()).await.unwrap();
+    assert_eq!(Some(b"apples".to_vec()), alice.get().await.unwrap());
+    assert_eq!(
+        Some(b"apples".to_vec()),
+        actor("alice").get().await.unwrap()
+    );
+
+    alice.clear().await.unwrap();
+    assert_eq!(None, alice.get().await.unwrap());
+    assert_eq!(Some(b"bananas".to_vec()), bob.get().await.unwrap());
+    Ok(())
+}
+
+#[tokio::test]
+async fn errors_when_component_uses_actors_store() -> anyhow::Result<()> {
+    let env = TestEnvironment::new(factors()).extend_manifest(toml! {
+        [component.test-component]
+        source = "does-not-exist.wasm"
+        key_value_stores = ["spin-actors"]
+    });
+    let Err(err) = env
+        .runtime_config(TestFactorsRuntimeConfig {
+            key_value: Some(in_memory_store()?),
+            actors: None,
+        })?
+        .build_instance_state()
+        .await
+    else {
+        bail!("expected instance build to fail but it didn't");
+    };
+
+    assert!(
+        err.to_string()
+            .contains(r#"uses the key-value store "spin-actors", which holds actor state"#)
+    );
+    Ok(())
+}
+
+#[derive(RuntimeFactors)]
+struct PolicyTestFactors {
+    capability_policy: CapabilityPolicyFactor,
+    key_value: KeyValueFactor,
+    actors: ActorsFactor,
+}
+
+#[tokio::test]
+async fn capability_policy_denies_actor_calls() -> anyhow::Result<()> {
+    let env = TestEnvironment::new(PolicyTestFactors {
+        capability_policy: CapabilityPolicyFactor::new(),
+        key_value: KeyValueFactor::new(),
+        actors: ActorsFactor::new(),
+    })
+    .extend_manifest(toml! {
+        [component.test-component]
+        source = "does-not-exist.wasm"
+        actors = ["cart"]
+    })
+    .runtime_config(PolicyTestFactorsRuntimeConfig {
+        capability_policy: config_from_table(&toml! {
+            [[capability_policy]]
+            effect = "deny"
+            operations = ["*"]
+        })?,
+        key_value: Some(in_memory_store()?),
+        actors: None,
+    })?;
+    let mut state = env.build_instance_state().await?;
+
+    assert!(matches!(
+        state.actors.call("cart".into(), "1".into(), vec![]).await,
+        Err(v3::Error::AccessDenied)
+    ));
+    Ok(())
+}
@@ -0,0 +1,688 @@
+use std::{
+    collections::{HashMap, HashSet},
+    io,
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll, ready},
+};
+
+use anyhow::{Context as _, Result};
+use futures::{
+    StreamExt,
+    stream::{BoxStream, Peekable},
+};
+use spin_core::async_trait;
+use spin_factor_capability_policy::{CapabilityChecker, operation};
+use spin_factors::{
+    InitContext,
+    wasmtime::component::{HasData, Resource, ResourceTable},
+};
+use spin_world::{
+    MAX_HOST_BUFFERED_BYTES,
+    wasi::blobstore::{blobstore, container, types},
+};
+use tokio::io::{AsyncRead, AsyncReadExt, ReadBuf, ReadHalf, SimplexStream, WriteHalf};
+use tokio::sync::oneshot;
+use wasmtime_wasi::p2::{
+    DynInputStream, DynOutputStream,
+    pipe::{AsyncReadStream, AsyncWriteStream},
+};
+
+use crate::BlobStoreFactor;
+
+/// The size of the pipe buffer between a guest's output stream and the
+/// backend upload of an outgoing value.
+const OUTGOING_PIPE_CAPACITY: usize = 64 * 1024;
+
+/// A blob container backing a `blob_containers` label.
+#[async_trait]
+pub trait Container: Send + Sync {
+    /// A human-readable summary of the container's configuration
+    ///
+    /// Example: "S3 bucket my-bucket"
+    fn summary(&self) -> Option<String> {
+        None
+    }
+
+    /// Returns true if the backing container exists.
+    async fn exists(&self) -> Result<bool>;
+
+    /// The time the container was created, in seconds since the Unix epoch,
+    /// or 0 if the backend does not record it.
+    async fn created_at(&self) -> Result<u64> {
+        Ok(0)
+    }
+
+    /// Returns true if the object exists.
+    async fn has_object(&self, name: &str) -> Result<bool>;
+
+    /// Returns metadata for the object.
+    async fn object_info(&self, name: &str) -> Result<ObjectInfo>;
+
+    /// Reads the bytes of the object from `start` to `end`, both inclusive.
+    ///
+    /// `end` may be beyond the end of the object, in which case the object is
+    /// read to its end.
+    async fn get_data(&self, name: &str, start: u64, end: u64) -> Result<IncomingData>;
+
+    /// Creates or replaces the object with the contents of `data`, which is
+    /// read to its end.
+    ///
+    /// If `data` returns an error, the write must be abandoned and the
+    /// previous object (if any) left in place.
+    async fn write_data(&self, name: &str, data: Box<dyn AsyncRead + Send + Unpin>) -> Result<()>;
+
+    /// Deletes the object. Succeeds if the object does not exist.
+    async fn delete_object(&self, name: &str) -> Result<()>;
+
+    /// Deletes each of the objects.
+    async fn delete_objects(&self, names: &[String]) -> Result<()> {
+        for name in names {
+            self.delete_object(name).await?;
+        }
+        Ok(())
+    }
+
+    /// Lists the names of the objects in the container.
+    async fn list_objects(&self) -> Result<ObjectNames>;
+
+    /// Deletes all objects in the container.
+    async fn clear(&self) -> Result<()> {
+        let mut names = self.list_objects().await?;
+        loop {
+            let (batch, end) = names.read(100).await?;
+            self.delete_objects(&batch).await?;
+            if end {
+                return Ok(());
+            }
+        }
+    }
+}
+
+/// Metadata about an object, as reported by a [`Container`].
+#[derive(Clone, Debug, Default)]
+pub struct ObjectInfo {
+    /// The time the object was created, in seconds since the Unix epoch.
+    pub created_at: u64,
+    /// The size of the object in bytes.
+    pub size: u64,
+}
+
+/// The contents of (part of) an object being read from a [`Container`].
+pub struct IncomingData {
+    size: u64,
+    body: Box<dyn AsyncRead + Send + Unpin>,
+}
+
+impl IncomingData {
+    /// Creates incoming data of the given size in bytes, read from `body`.
+    pub fn new(size: u64, body: impl AsyncRead + Send + Unpin + 'static) -> Self {
+        Self {
+            size,
+            body: Box::new(body),
+        }
+    }
+
+    /// The size of the data in bytes.
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    /// Returns the reader of the data.
+    pub fn into_body(self) -> Box<dyn AsyncRead + Send + Unpin> {
+        self.body
+    }
+}
+
+/// A stream of object names being listed from a [`Container`].
+pub struct ObjectNames {
+    names: Peekable<BoxStream<'static, Result<String>>>,
+}
+
+impl ObjectNames {
+    /// Creates a listing from a stream of object names.
+    pub fn new(names: BoxStream<'static, Result<String>>) -> Self {
+        Self {
+            names: names.peekable(),
+        }
+    }
+
+    /// Reads up to `len` names, returning them and whether the listing ended.
+    pub async fn read(&mut self, len: u64) -> Result<(Vec<String>, bool)> {
+        let mut names = vec![];
+        while (names.len() as u64) < len {
+            match self.names.next().await {
+                Some(name) => names.push(name?),
+                None => return Ok((names, true)),
+            }
+        }
+        Ok((names, self.at_end().await))
+    }
+
+    /// Skips up to `num` names, returning how many were skipped and whether
+    /// the listing ended.
+    pub async fn skip(&mut self, num: u64) -> Result<(u64, bool)> {
+        let mut skipped = 0;
+        while skipped < num {
+            match self.names.next().await {
+                Some(name) => {
+                    name?;
+                    skipped += 1;
+                }
+                None => return Ok((skipped, true)),
+            }
+        }
+        Ok((skipped, self.at_end().await))
+    }
+
+    async fn at_end(&mut self) -> bool {
+        Pin::new(&mut self.names).peek().await.is_none()
+    }
+}
+
+/// The blob store state of a component instance.
+pub struct InstanceState {
+    allowed_containers: HashSet<String>,
+    containers: Arc<HashMap<String, Arc<dyn Container>>>,
+    capabilities: CapabilityChecker,
+}
+
+impl InstanceState {
+    pub fn new(
+        allowed_containers: HashSet<String>,
+        containers: Arc<HashMap<String, Arc<dyn Container>>>,
+        capabilities: CapabilityChecker,
+    ) -> Self {
+        Self {
+            allowed_containers,
+            containers,
+            capabilities,
+        }
+    }
+
+    pub fn allowed_containers(&self) -> &HashSet<String> {
+        &self.allowed_containers
+    }
+
+    fn get_container(&self, label: &str) -> Result<Arc<dyn Container>, types::Error> {
+        if !self.allowed_containers.contains(label) {
+            return Err(format!("access to container {label:?} is not allowed"));
+        }
+        self.capabilities
+            .check(operation::BLOBSTORE, label, None)
+            .map_err(|err| err.to_string())?;
+        self.containers
+            .get(label)
+            .cloned()
+            .ok_or_else(|| format!("no such container {label:?}"))
+    }
+}
+
+/// The host implementation of `wasi:blobstore`.
+///
+/// Values and streams are kept in the instance's resource table so that an
+/// outgoing value's output stream can be tracked as its child.
+pub struct BlobStoreDispatch<'a> {
+    state: &'a mut InstanceState,
+    table: &'a mut ResourceTable,
+}
+
+impl<'a> BlobStoreDispatch<'a> {
+    pub fn new(state: &'a mut InstanceState, table: &'a mut ResourceTable) -> Self {
+        Self { state, table }
+    }
+
+    fn container(&self, container: &Resource<container::Container>) -> Result<&ContainerEntry> {
+        self.table
+            .get(&Resource::new_borrow(container.rep()))
+            .context("invalid container")
+    }
+}
+
+struct HasBlobStore;
+
+impl HasData for HasBlobStore {
+    type Data<'a> = BlobStoreDispatch<'a>;
+}
+
+pub(crate) fn add_to_linker<C>(ctx: &mut C) -> Result<()>
+where
+    C: InitContext<BlobStoreFactor>,
+{
+    fn get_dispatch<C>(store: &mut C::StoreData) -> BlobStoreDispatch<'_>
+    where
+        C: InitContext<BlobStoreFactor>,
+    {
+        let (state, table) = C::get_data_with_table(store);
+        BlobStoreDispatch::new(state, table)
+    }
+
+    let get_dispatch = get_dispatch::<C> as fn(&mut C::StoreData) -> BlobStoreDispatch<'_>;
+    let linker = ctx.linker();
+    blobstore::add_to_linker::<_, HasBlobStore>(linker, get_dispatch)?;
+    container::add_to_linker::<_, HasBlobStore>(linker, get_dispatch)?;
+    types::add_to_linker::<_, HasBlobStore>(linker, get_dispatch)?;
+    Ok(())
+}
+
+/// An opened container in the resource table.
+struct ContainerEntry {
+    label: String,
+    container: Arc<dyn Container>,
+}
+
+/// An outgoing value in the resource table.
+///
+/// The guest writes to one end of a pipe; `write-data` spawns an upload
+/// reading from the other. The upload only sees the end of the data once
+/// the value is finished, so an abandoned value is never committed.
+struct OutgoingValue {
+    writer: Option<WriteHalf<SimplexStream>>,
+    body: Option<OutgoingBody>,
+    finish_tx: Option<oneshot::Sender<()>>,
+    written_rx: Option<oneshot::Receiver<Result<()>>>,
+}
+
+struct OutgoingBody {
+    reader: ReadHalf<SimplexStream>,
+    finish_rx: Option<oneshot::Receiver<()>>,
+}
+
+impl AsyncRead for OutgoingBody {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let filled = buf.filled().len();
+        ready!(Pin::new(&mut self.reader).poll_read(cx, buf))?;
+        if buf.filled().len() > filled || buf.remaining() == 0 {
+            return Poll::Ready(Ok(()));
+        }
+        // The pipe has been closed; only report the end of the data if the
+        // value was finished rather than dropped.
+        let Some(finish_rx) = &mut self.finish_rx else {
+            return Poll::Ready(Ok(()));
+        };
+        match ready!(Pin::new(finish_rx).poll(cx)) {
+            Ok(()) => {
+                self.finish_rx = None;
+                Poll::Ready(Ok(()))
+            }
+            Err(_) => Poll::Ready(Err(io::Error::other(
+                "outgoing value was dropped without being finished",
+            ))),
+        }
+    }
+}
+
+fn to_wasi_err(err: anyhow::Error) -> types::Error {
+    format!("{err:#}")
+}
+
+impl blobstore::Host for BlobStoreDispatch<'_> {
+    async fn create_container(
+        &mut self,
+        name: String,
+    ) -> Result<Result<Resource<container::Container>, types::Error>> {
+        Ok(Err(format!(
+            "cannot create container {name:?}: containers must be defined in the runtime config"
+        )))
+    }
+
+    async fn get_container(
+        &mut self,
+        name: String,
+    ) -> Result<Result<Resource<container::Container>, types::Error>> {
+        let container = match self.state.get_container(&name) {
+            Ok(container) => container,
+            Err(err) => return Ok(Err(err)),
+        };
+        let entry = self.table.push(ContainerEntry {
+            label: name,
+            container,
+        })?;
+        Ok(Ok(Resource::new_own(entry.rep())))
+    }
+
+    async fn delete_container(&mut self, name: String) -> Result<Result<(), types::Error>> {
+        Ok(Err(format!(
+            "cannot delete container {name:?}: containers must be defined in the runtime config"
+        )))
+    }
+
+    async fn container_exists(&mut self, name: String) -> Result<Result<bool, types::Error>> {
+        let container = match self.state.get_container(&name) {
+            Ok(container) => container,
+            Err(err) => return Ok(Err(err)),
+        };
+        Ok(container.exists().await.map_err(to_wasi_err))
+    }
+
+    async fn copy_object(
+        &mut self,
+        src: types::ObjectId,
+        dest: types::ObjectId,
+    ) -> Result<Result<(), types::Error>> {
+        let result = async {
+            let src_container = self.state.get_container(&src.container)?;
+            let dest_container = self.state.get_container(&dest.container)?;
+            copy(&*src_container, &src.object, &*dest_container, &dest.object).await
+        };
+        Ok(result.await)
+    }
+
+    async fn move_object(
+        &mut self,
+        src: types::ObjectId,
+        dest: types::ObjectId,
+    ) -> Result<Result<(), types::Error>> {
+        let result = async {
+            let src_container = self.state.get_container(&src.container)?;
+            let dest_container = self.state.get_container(&dest.container)?;
+            copy(&*src_container, &src.object, &*dest_container, &dest.object).await?;
+            src_container
+                .delete_object(&src.object)
+                .await
+                .map_err(to_wasi_err)
+        };
+        Ok(result.await)
+    }
+}
+
+async fn copy(
+    src: &dyn Container,
+    src_name: &str,
+    dest: &dyn Container,
+    dest_name: &str,
+) -> Result<(), types::Error> {
+    let data = src
+        .get_data(src_name, 0, u64::MAX)
+        .await
+        .map_err(to_wasi_err)?;
+    dest.write_data(dest_name, data.body)
+        .await
+        .map_err(to_wasi_err)
+}
+
+impl container::Host for BlobStoreDispatch<'_> {}
+
+impl container::HostContainer for BlobStoreDispatch<'_> {
+    async fn name(
+        &mut self,
+        self_: Resource<container::Container>,
+    ) -> Result<Result<String, types::Error>> {
+        Ok(Ok(self.container(&self_)?.label.clone()))
+    }
+
+    async fn info(
+        &mut self,
+        self_: Resource<container::Container>,
+    ) -> Result<Result<types::ContainerMetadata, types::Error>> {
+        let entry = self.container(&self_)?;
+        let (name, container) = (entry.label.clone(), entry.container.clone());
+        Ok(container
+            .created_at()
+            .await
+            .map(|created_at| types::ContainerMetadata { name, created_at })
+            .map_err(to_wasi_err))
+    }
+
+    async fn get_data(
+        &mut self,
+        self_: Resource<container::Container>,
+        name: types::ObjectName,
+        start: u64,
+        end: u64,
+    ) -> Result<Result<Resource<types::IncomingValue>, types::Error>> {
+        if end < start {
+            return Ok(Err(format!(
+                "invalid range: end {end} is before start {start}"
+            )));
+        }
+        let container = self.container(&self_)?.container.clone();
+        let data = match container.get_data(&name, start, end).await {
+            Ok(data) => data,
+            Err(err) => return Ok(Err(to_wasi_err(err))),
+        };
+        let value = self.table.push(data)?;
+        Ok(Ok(Resource::new_own(value.rep())))
+    }
+
+    async fn write_data(
+        &mut self,
+        self_: Resource<container::Container>,
+        name: types::ObjectName,
+        data: Resource<types::OutgoingValue>,
+    ) -> Result<Result<(), types::Error>> {
+        let container = self.container(&self_)?.container.clone();
+        let value: &mut OutgoingValue = self
+            .table
+            .get_mut(&Resource::new_borrow(data.rep()))
+            .context("invalid outgoing value")?;
+        let Some(body) = value.body.take() else {
+            return Ok(Err("outgoing value has already been written".into()));
+        };
+        let (written_tx, written_rx) = oneshot::channel();
+        value.written_rx = Some(written_rx);
+        // The upload completes (or fails) once the value is finished; the
+        // result is reported by `outgoing-value.finish`.
+        tokio::spawn(async move {
+            let result = container.write_data(&name, Box::new(body)).await;
+            _ = written_tx.send(result);
+        });
+        Ok(Ok(()))
+    }
+
+    async fn list_objects(
+        &mut self,
+        self_: Resource<container::Container>,
+    ) -> Result<Result<Resource<container::StreamObjectNames>, types::Error>> {
+        let container = self.container(&self_)?.container.clone();
+        let names = match container.list_objects().await {
+            Ok(names) => names,
+            Err(err) => return Ok(Err(to_wasi_err(err))),
+        };
+        let names = self.table.push(names)?;
+        Ok(Ok(Resource::new_own(names.rep())))
+    }
+
+    async fn delete_object(
+        &mut self,
+        self_: Resource<container::Container>,
+        name: types::ObjectName,
+    ) -> Result<Result<(), types::Error>> {
+        let container = self.container(&self_)?.container.clone();
+        Ok(container.delete_object(&name).await.map_err(to_wasi_err))
+    }
+
+    async fn delete_objects(
+        &mut self,
+        self_: Resource<container::Container>,
+        names: Vec<types::ObjectName>,
+    ) -> Result<Result<(), types::Error>> {
+        let container = self.container(&self_)?.container.clone();
+        Ok(container.delete_objects(&names).await.map_err(to_wasi_err))
+    }
+
+    async fn has_object(
+        &mut self,
+        self_: Resource<container::Container>,
+        name: types::ObjectName,
+    ) -> Result<Result<bool, types::Error>> {
+        let container = self.container(&self_)?.container.clone();
+        Ok(container.has_object(&name).await.map_err(to_wasi_err))
+    }
+
+    async fn object_info(
+        &mut self,
+        self_: Resource<container::Container>,
+        name: types::ObjectName,
+    ) -> Result<Result<types::ObjectMetadata, types::Error>> {
+        let entry = self.container(&self_)?;
+        let (label, container) = (entry.label.clone(), entry.container.clone());
+        Ok(container
+            .object_info(&name)
+            .await
+            .map(|info| types::ObjectMetadata {
+                name,
+                container: label,
+                created_at: info.created_at,
+                size: info.size,
+            })
+            .map_err(to_wasi_err))
+    }
+
+    async fn clear(
+        &mut self,
+        self_: Resource<container::Container>,
+    ) -> Result<Result<(), types::Error>> {
+        let container = self.container(&self_)?.container.clone();
+        Ok(container.clear().await.map_err(to_wasi_err))
+    }
+
+    async fn drop(&mut self, rep: Resource<container::Container>) -> Result<()> {
+        self.table
+            .delete::<ContainerEntry>(Resource::new_own(rep.rep()))?;
+        Ok(())
+    }
+}
+
+impl container::HostStreamObjectNames for BlobStoreDispatch<'_> {
+    async fn read_stream_object_names(
+        &mut self,
+        self_: Resource<container::StreamObjectNames>,
+        len: u64,
+    ) -> Result<Result<(Vec<types::ObjectName>, bool), types::Error>> {
+        let names: &mut ObjectNames = self
+            .table
+            .get_mut(&Resource::new_borrow(self_.rep()))
+            .context("invalid object name stream")?;
+        Ok(names.read(len).await.map_err(to_wasi_err))
+    }
+
+    async fn skip_stream_object_names(
+        &mut self,
+        self_: Resource<container::StreamObjectNames>,
+        num: u64,
+    ) -> Result<Result<(u64, bool), types::Error>> {
+        let names: &mut ObjectNames = self
+            .table
+            .get_mut(&Resource::new_borrow(self_.rep()))
+            .context("invalid object name stream")?;
+        Ok(names.skip(num).await.map_err(to_wasi_err))
+    }
+
+    async fn drop(&mut self, rep: Resource<container::StreamObjectNames>) -> Result<()> {
+        self.table
+            .delete::<ObjectNames>(Resource::new_own(rep.rep()))?;
+        Ok(())
+    }
+}
+
+impl types::Host for BlobStoreDispatch<'_> {}
+
+impl types::HostOutgoingValue for BlobStoreDispatch<'_> {
+    async fn new_outgoing_value(&mut self) -> Result<Resource<types::OutgoingValue>> {
+        let (reader, writer) = tokio::io::simplex(OUTGOING_PIPE_CAPACITY);
+        let (finish_tx, finish_rx) = oneshot::channel();
+        let value = self.table.push(OutgoingValue {
+            writer: Some(writer),
+            body: Some(OutgoingBody {
+                reader,
+                finish_rx: Some(finish_rx),
+            }),
+            finish_tx: Some(finish_tx),
+            written_rx: None,
+        })?;
+        Ok(Resource::new_own(value.rep()))
+    }
+
+    async fn outgoing_value_write_body(
+        &mut self,
+        self_: Resource<types::OutgoingValue>,
+    ) -> Result<Result<Resource<DynOutputStream>, ()>> {
+        let parent = Resource::<OutgoingValue>::new_borrow(self_.rep());
+        let value = self
+            .table
+            .get_mut(&parent)
+            .context("invalid outgoing value")?;
+        let Some(writer) = value.writer.take() else {
+            return Ok(Err(()));
+        };
+        let stream: DynOutputStream =
+            Box::new(AsyncWriteStream::new(OUTGOING_PIPE_CAPACITY, writer));
+        // As a child of the value, the stream must be dropped before the value
+        // can be finished or dropped.
+        Ok(Ok(self.table.push_child(stream, &parent)?))
+    }
+
+    async fn finish(
+        &mut self,
+        this: Resource<types::OutgoingValue>,
+    ) -> Result<Result<(), types::Error>> {
+        let mut value: OutgoingValue = self.table.delete(Resource::new_own(this.rep()))?;
+        // Close the pipe if the guest never took the body stream, then let the
+        // upload see the end of the data.
+        value.writer.take();
+        if let Some(finish_tx) = value.finish_tx.take() {
+            _ = finish_tx.send(());
+        }
+        let Some(written_rx) = value.written_rx.take() else {
+            return Ok(Ok(()));
+        };
+        match written_rx.await {
+            Ok(result) => Ok(result.map_err(to_wasi_err)),
+            Err(_) => Ok(Err("write task failed".into())),
+        }
+    }
+
+    async fn drop(&mut self, rep: Resource<types::OutgoingValue>) -> Result<()> {
+        self.table
+            .delete::<OutgoingValue>(Resource::new_own(rep.rep()))?;
+        Ok(())
+    }
+}
+
+impl types::HostIncomingValue for BlobStoreDispatch<'_> {
+    async fn incoming_value_consume_sync(
+        &mut self,
+        this: Resource<types::IncomingValue>,
+    ) -> Result<Result<types::IncomingValueSyncBody, types::Error>> {
+        let data: IncomingData = self.table.delete(Resource::new_own(this.rep()))?;
+        let mut buf = Vec::new();
+        let limit = MAX_HOST_BUFFERED_BYTES as u64;
+        if let Err(err) = data.body.take(limit + 1).read_to_end(&mut buf).await {
+            return Ok(Err(err.to_string()));
+        }
+        if buf.len() as u64 > limit {
+            return Ok(Err(format!(
+                "value exceeds the {limit} byte limit for synchronous reads; use incoming-value-consume-async"
+            )));
+        }
+        Ok(Ok(buf))
+    }
+
+    async fn incoming_value_consume_async(
+        &mut self,
+        this: Resource<types::IncomingValue>,
+    ) -> Result<Result<Resource<DynInputStream>, types::Error>> {
+        let data: IncomingData = self.table.delete(Resource::new_own(this.rep()))?;
+        let stream: DynInputStream = Box::new(AsyncReadStream::new(data.body));
+        Ok(Ok(self.table.push(stream)?))
+    }
+
+    async fn size(&mut self, self_: Resource<types::IncomingValue>) -> Result<u64> {
+        let data: &IncomingData = self
+            .table
+            .get(&Resource::new_borrow(self_.rep()))
+            .context("invalid incoming value")?;
+        Ok(data.size)
+    }
+
+    async fn drop(&mut self, rep: Resource<types::IncomingValue>) -> Result<()> {
+        self.table
+            .delete::<IncomingData>(Resource::new_own(rep.rep()))?;
+        Ok(())
+    }
+}
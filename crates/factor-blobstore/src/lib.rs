@@ -0,0 +1,147 @@
+//! A factor providing the `wasi:blobstore` interface.
+//!
+//! Blob containers are identified by label, like key-value stores: a component
+//! may only open the containers listed in its manifest `blob_containers`, and
+//! each label is mapped to a backing [`Container`] by the runtime config.
+//! Unlike key-value, object contents are streamed to and from the backend
+//! rather than buffered in full.
+
+mod host;
+pub mod runtime_config;
+
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+};
+
+use anyhow::ensure;
+use spin_factor_capability_policy::CapabilityChecker;
+use spin_factors::{
+    ConfigureAppContext, Factor, FactorInstanceBuilder, InitContext, PrepareContext, RuntimeFactors,
+};
+use spin_locked_app::MetadataKey;
+
+pub use host::{
+    BlobStoreDispatch, Container, IncomingData, InstanceState, ObjectInfo, ObjectNames,
+};
+pub use runtime_config::RuntimeConfig;
+
+/// Metadata key for blob containers.
+pub const BLOB_CONTAINERS_KEY: MetadataKey<Vec<String>> = MetadataKey::new("blob_containers");
+
+/// A factor that provides blob storage.
+#[derive(Default)]
+pub struct BlobStoreFactor {
+    _priv: (),
+}
+
+impl BlobStoreFactor {
+    /// Create a new BlobStoreFactor.
+    pub fn new() -> Self {
+        Self { _priv: () }
+    }
+}
+
+impl Factor for BlobStoreFactor {
+    type RuntimeConfig = RuntimeConfig;
+    type AppState = AppState;
+    type InstanceBuilder = InstanceBuilder;
+
+    fn init(&mut self, ctx: &mut impl InitContext<Self>) -> anyhow::Result<()> {
+        host::add_to_linker(ctx)
+    }
+
+    fn configure_app<T: RuntimeFactors>(
+        &self,
+        mut ctx: ConfigureAppContext<T, Self>,
+    ) -> anyhow::Result<Self::AppState> {
+        let runtime_config = ctx.take_runtime_config().unwrap_or_default();
+        let containers = Arc::new(runtime_config.into_iter().collect::<HashMap<_, _>>());
+
+        // Build component -> allowed containers map
+        let mut component_allowed_containers = HashMap::new();
+        for component in ctx.app().components() {
+            let component_id = component.id().to_string();
+            let blob_containers = component
+                .get_metadata(BLOB_CONTAINERS_KEY)?
+                .unwrap_or_default()
+                .into_iter()
+                .collect::<HashSet<_>>();
+            for label in &blob_containers {
+                ensure!(
+                    containers.contains_key(label),
+                    "unknown blob_containers label {label:?} for component {component_id:?}"
+                );
+            }
+            component_allowed_containers.insert(component_id, blob_containers);
+        }
+
+        Ok(AppState {
+            containers,
+            component_allowed_containers,
+        })
+    }
+
+    fn prepare<T: RuntimeFactors>(
+        &self,
+        mut ctx: PrepareContext<T, Self>,
+    ) -> anyhow::Result<InstanceBuilder> {
+        let app_state = ctx.app_state();
+        let allowed_containers = app_state
+            .component_allowed_containers
+            .get(ctx.app_component().id())
+            .expect("component should be in component_allowed_containers")
+            .clone();
+        let containers = app_state.containers.clone();
+        let capabilities = CapabilityChecker::from_prepare_context(&mut ctx)?;
+        Ok(InstanceBuilder {
+            containers,
+            allowed_containers,
+            capabilities,
+        })
+    }
+}
+
+pub struct AppState {
+    /// The containers defined for the app, by label.
+    containers: Arc<HashMap<String, Arc<dyn Container>>>,
+    /// The allowed containers for each component.
+    ///
+    /// This is a map from component ID to the set of container labels that
+    /// the component is allowed to use.
+    component_allowed_containers: HashMap<String, HashSet<String>>,
+}
+
+impl AppState {
+    /// Returns the [`Container::summary`] for the given container label.
+    pub fn container_summary(&self, label: &str) -> Option<String> {
+        self.containers.get(label)?.summary()
+    }
+
+    /// Returns true if the given container label is used by any component.
+    pub fn container_is_used(&self, label: &str) -> bool {
+        self.component_allowed_containers
+            .values()
+            .any(|containers| containers.contains(label))
+    }
+}
+
+pub struct InstanceBuilder {
+    /// The containers defined for the app, by label.
+    containers: Arc<HashMap<String, Arc<dyn Container>>>,
+    /// The allowed containers for this component instance.
+    allowed_containers: HashSet<String>,
+    capabilities: CapabilityChecker,
+}
+
+impl FactorInstanceBuilder for InstanceBuilder {
+    type InstanceState = InstanceState;
+
+    fn build(self) -> anyhow::Result<Self::InstanceState> {
+        Ok(InstanceState::new(
+            self.allowed_containers,
+            self.containers,
+            self.capabilities,
+        ))
+    }
+}
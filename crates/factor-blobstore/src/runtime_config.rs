@@ -0,0 +1,40 @@
+pub mod spin;
+
+use std::{collections::HashMap, sync::Arc};
+
+use crate::Container;
+
+/// Runtime configuration for all blob containers.
+#[derive(Default, Clone)]
+pub struct RuntimeConfig {
+    /// Map of container labels to containers.
+    containers: HashMap<String, Arc<dyn Container>>,
+}
+
+impl RuntimeConfig {
+    /// Adds a container with the given label to the runtime configuration.
+    ///
+    /// If a container already exists for the given label, it will be replaced.
+    pub fn add_container(&mut self, label: String, container: Arc<dyn Container>) {
+        self.containers.insert(label, container);
+    }
+
+    /// Returns whether a container exists with the given label.
+    pub fn has_container(&self, label: &str) -> bool {
+        self.containers.contains_key(label)
+    }
+
+    /// Returns the container with the given label.
+    pub fn get_container(&self, label: &str) -> Option<Arc<dyn Container>> {
+        self.containers.get(label).cloned()
+    }
+}
+
+impl IntoIterator for RuntimeConfig {
+    type Item = (String, Arc<dyn Container>);
+    type IntoIter = std::collections::hash_map::IntoIter<String, Arc<dyn Container>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.containers.into_iter()
+    }
+}
@@ -0,0 +1,164 @@
+//! Runtime configuration implementation used by Spin CLI.
+
+use crate::{Container, RuntimeConfig};
+use anyhow::Context as _;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use spin_factors::runtime_config::toml::GetTomlValue;
+use std::{collections::HashMap, sync::Arc};
+
+/// Defines the construction of a blob container from a serialized runtime config.
+pub trait MakeBlobContainer: 'static + Send + Sync {
+    /// Unique type identifier for the container.
+    const RUNTIME_CONFIG_TYPE: &'static str;
+    /// Runtime configuration for the container.
+    type RuntimeConfig: DeserializeOwned;
+    /// The container.
+    type Container: Container;
+
+    /// Creates a new container from the runtime configuration.
+    fn make_container(
+        &self,
+        runtime_config: Self::RuntimeConfig,
+    ) -> anyhow::Result<Self::Container>;
+}
+
+/// A function that creates a container from a TOML table.
+type ContainerFromToml =
+    Arc<dyn Fn(toml::Table) -> anyhow::Result<Arc<dyn Container>> + Send + Sync>;
+
+/// Creates a `ContainerFromToml` function from a `MakeBlobContainer` implementation.
+fn container_from_toml_fn<T: MakeBlobContainer>(container_type: T) -> ContainerFromToml {
+    Arc::new(move |table| {
+        let runtime_config: T::RuntimeConfig = table
+            .try_into()
+            .context("could not parse blob container runtime config")?;
+        let container = container_type
+            .make_container(runtime_config)
+            .context("could not make blob container from runtime config")?;
+        Ok(Arc::new(container))
+    })
+}
+
+/// Converts from toml based runtime configuration into a [`RuntimeConfig`].
+///
+/// The various container types (i.e., the "type" field in the toml field) are
+/// registered with the resolver using `register_container_type`. The default
+/// container for a label is registered using `add_default_container`.
+#[derive(Default, Clone)]
+pub struct RuntimeConfigResolver {
+    /// A map of container types to a function that returns the appropriate
+    /// container from runtime config TOML.
+    container_types: HashMap<&'static str, ContainerFromToml>,
+    /// A map of default container configurations for a label.
+    defaults: HashMap<&'static str, ContainerConfig>,
+}
+
+impl RuntimeConfigResolver {
+    /// Create a new RuntimeConfigResolver.
+    pub fn new() -> Self {
+        <Self as Default>::default()
+    }
+
+    /// Adds a default container configuration for a label.
+    ///
+    /// Users must ensure that the container type for `config` has been
+    /// registered with the resolver using [`Self::register_container_type`].
+    pub fn add_default_container<T>(
+        &mut self,
+        label: &'static str,
+        config: T::RuntimeConfig,
+    ) -> anyhow::Result<()>
+    where
+        T: MakeBlobContainer,
+        T::RuntimeConfig: Serialize,
+    {
+        self.defaults.insert(
+            label,
+            ContainerConfig::new(T::RUNTIME_CONFIG_TYPE.to_owned(), config)?,
+        );
+        Ok(())
+    }
+
+    /// Registers a container type to the resolver.
+    pub fn register_container_type<T: MakeBlobContainer>(
+        &mut self,
+        container_type: T,
+    ) -> anyhow::Result<()> {
+        if self
+            .container_types
+            .insert(
+                T::RUNTIME_CONFIG_TYPE,
+                container_from_toml_fn(container_type),
+            )
+            .is_some()
+        {
+            anyhow::bail!("duplicate blob container type {:?}", T::RUNTIME_CONFIG_TYPE);
+        }
+        Ok(())
+    }
+
+    /// Resolves a toml table into a runtime config.
+    ///
+    /// The default containers are also added to the runtime config.
+    pub fn resolve(&self, table: Option<&impl GetTomlValue>) -> anyhow::Result<RuntimeConfig> {
+        let mut runtime_config = RuntimeConfig::default();
+
+        if let Some(table) = table.and_then(|t| t.get("blob_container")) {
+            let table: HashMap<String, ContainerConfig> = table.clone().try_into()?;
+            for (label, config) in table {
+                let container = self.container_from_config(config).with_context(|| {
+                    format!("could not configure blob container with label '{label}'")
+                })?;
+                runtime_config.add_container(label, container);
+            }
+        }
+
+        for (&label, config) in &self.defaults {
+            if !runtime_config.has_container(label) {
+                let container = self
+                    .container_from_config(config.clone())
+                    .with_context(|| {
+                        format!("could not configure blob container with label '{label}'")
+                    })?;
+                runtime_config.add_container(label.to_owned(), container);
+            }
+        }
+        Ok(runtime_config)
+    }
+
+    /// Given a [`ContainerConfig`], returns a container.
+    ///
+    /// Errors if there is no [`MakeBlobContainer`] registered for the
+    /// container config's type or if the container cannot be created from
+    /// the config.
+    fn container_from_config(&self, config: ContainerConfig) -> anyhow::Result<Arc<dyn Container>> {
+        let config_type = config.type_.as_str();
+        let maker = self.container_types.get(config_type).with_context(|| {
+            format!(
+                "the blob container type '{config_type}' was not registered with the config resolver"
+            )
+        })?;
+        maker(config.config)
+    }
+}
+
+#[derive(Deserialize, Clone)]
+pub struct ContainerConfig {
+    #[serde(rename = "type")]
+    pub type_: String,
+    #[serde(flatten)]
+    pub config: toml::Table,
+}
+
+impl ContainerConfig {
+    pub fn new<T>(type_: String, config: T) -> anyhow::Result<Self>
+    where
+        T: Serialize,
+    {
+        Ok(Self {
+            type_,
+            config: toml::value::Table::try_from(config)?,
+        })
+    }
+}
@@ -0,0 +1,244 @@
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{Arc, Mutex},
+};
+
+use anyhow::bail;
+use spin_core::{async_trait, wasmtime::component::Resource};
+use spin_factor_blobstore::{
+    BlobStoreDispatch, BlobStoreFactor, Container, IncomingData, ObjectInfo, ObjectNames,
+    RuntimeConfig,
+};
+use spin_factor_capability_policy::{CapabilityPolicyFactor, runtime_config::config_from_table};
+use spin_factors::{RuntimeFactors, RuntimeFactorsInstanceState};
+use spin_factors_test::{TestEnvironment, toml};
+use spin_world::wasi::blobstore::{
+    blobstore::Host as _,
+    container::HostContainer as _,
+    types::{HostIncomingValue as _, HostOutgoingValue as _},
+};
+use tokio::io::{AsyncRead, AsyncReadExt};
+
+#[derive(RuntimeFactors)]
+struct TestFactors {
+    blobstore: BlobStoreFactor,
+}
+
+impl From<RuntimeConfig> for TestFactorsRuntimeConfig {
+    fn from(value: RuntimeConfig) -> Self {
+        Self {
+            blobstore: Some(value),
+        }
+    }
+}
+
+#[tokio::test]
+async fn works_when_allowed_container_is_defined() -> anyhow::Result<()> {
+    let mut runtime_config = RuntimeConfig::default();
+    runtime_config.add_container("default".into(), Arc::new(MemoryContainer::default()));
+    let env = TestEnvironment::new(TestFactors {
+        blobstore: BlobStoreFactor::new(),
+    })
+    .extend_manifest(toml! {
+        [component.test-component]
+        source = "does-not-exist.wasm"
+        blob_containers = ["default"]
+    });
+    let state = env
+        .runtime_config(runtime_config)?
+        .build_instance_state()
+        .await?;
+
+    assert_eq!(
+        state.blobstore.allowed_containers(),
+        &["default".into()].into_iter().collect::<HashSet<_>>()
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn errors_when_container_is_not_defined() -> anyhow::Result<()> {
+    let env = TestEnvironment::new(TestFactors {
+        blobstore: BlobStoreFactor::new(),
+    })
+    .extend_manifest(toml! {
+        [component.test-component]
+        source = "does-not-exist.wasm"
+        blob_containers = ["default"]
+    });
+    let Err(err) = env
+        .runtime_config(RuntimeConfig::default())?
+        .build_instance_state()
+        .await
+    else {
+        bail!("expected instance build to fail but it didn't");
+    };
+
+    assert!(
+        err.to_string()
+            .contains(r#"unknown blob_containers label "default""#)
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn finished_values_are_written() -> anyhow::Result<()> {
+    let container = Arc::new(MemoryContainer::default());
+    container
+        .objects
+        .lock()
+        .unwrap()
+        .insert("existing".into(), b"some data".to_vec());
+    let mut runtime_config = RuntimeConfig::default();
+    runtime_config.add_container("default".into(), container.clone());
+    runtime_config.add_container("other".into(), Arc::new(MemoryContainer::default()));
+    let env = TestEnvironment::new(TestFactors {
+        blobstore: BlobStoreFactor::new(),
+    })
+    .extend_manifest(toml! {
+        [component.test-component]
+        source = "does-not-exist.wasm"
+        blob_containers = ["default"]
+    });
+    let mut state = env
+        .runtime_config(runtime_config)?
+        .build_instance_state()
+        .await?;
+    let (state, table) = state.get_with_table::<BlobStoreFactor>().unwrap();
+    let mut blobstore = BlobStoreDispatch::new(state, table);
+
+    assert!(blobstore.get_container("other".into()).await?.is_err());
+    let default = blobstore.get_container("default".into()).await?.unwrap();
+
+    let value = blobstore
+        .get_data(
+            Resource::new_borrow(default.rep()),
+            "existing".into(),
+            5,
+            100,
+        )
+        .await?
+        .unwrap();
+    assert_eq!(4, blobstore.size(Resource::new_borrow(value.rep())).await?);
+    assert_eq!(
+        b"data".to_vec(),
+        blobstore.incoming_value_consume_sync(value).await?.unwrap()
+    );
+
+    let value = blobstore.new_outgoing_value().await?;
+    blobstore
+        .write_data(
+            Resource::new_borrow(default.rep()),
+            "empty".into(),
+            Resource::new_borrow(value.rep()),
+        )
+        .await?
+        .unwrap();
+    blobstore.finish(value).await?.unwrap();
+    assert!(container.has_object("empty").await?);
+    Ok(())
+}
+
+#[derive(RuntimeFactors)]
+struct PolicyTestFactors {
+    capability_policy: CapabilityPolicyFactor,
+    blobstore: BlobStoreFactor,
+}
+
+#[tokio::test]
+async fn capability_policy_denies_containers() -> anyhow::Result<()> {
+    let mut runtime_config = RuntimeConfig::default();
+    runtime_config.add_container("default".into(), Arc::new(MemoryContainer::default()));
+    let env = TestEnvironment::new(PolicyTestFactors {
+        capability_policy: CapabilityPolicyFactor::new(),
+        blobstore: BlobStoreFactor::new(),
+    })
+    .extend_manifest(toml! {
+        [component.test-component]
+        source = "does-not-exist.wasm"
+        blob_containers = ["default"]
+    })
+    .runtime_config(PolicyTestFactorsRuntimeConfig {
+        capability_policy: config_from_table(&toml! {
+            [[capability_policy]]
+            effect = "deny"
+            operations = ["*"]
+        })?,
+        blobstore: Some(runtime_config),
+    })?;
+    let mut state = env.build_instance_state().await?;
+    let (state, table) = state.get_with_table::<BlobStoreFactor>().unwrap();
+    let mut blobstore = BlobStoreDispatch::new(state, table);
+
+    let err = blobstore
+        .get_container("default".into())
+        .await?
+        .unwrap_err();
+    assert!(err.contains("denied by capability policy"), "{err}");
+    assert!(blobstore.container_exists("default".into()).await?.is_err());
+    Ok(())
+}
+
+/// A container holding objects in memory.
+#[derive(Default)]
+struct MemoryContainer {
+    objects: Mutex<HashMap<String, Vec<u8>>>,
+}
+
+#[async_trait]
+impl Container for MemoryContainer {
+    async fn exists(&self) -> anyhow::Result<bool> {
+        Ok(true)
+    }
+
+    async fn has_object(&self, name: &str) -> anyhow::Result<bool> {
+        Ok(self.objects.lock().unwrap().contains_key(name))
+    }
+
+    async fn object_info(&self, name: &str) -> anyhow::Result<ObjectInfo> {
+        let objects = self.objects.lock().unwrap();
+        let Some(object) = objects.get(name) else {
+            bail!("no such object");
+        };
+        Ok(ObjectInfo {
+            created_at: 0,
+            size: object.len() as u64,
+        })
+    }
+
+    async fn get_data(&self, name: &str, start: u64, end: u64) -> anyhow::Result<IncomingData> {
+        let objects = self.objects.lock().unwrap();
+        let Some(object) = objects.get(name) else {
+            bail!("no such object");
+        };
+        let end = (end as usize).saturating_add(1).min(object.len());
+        let data = object[(start as usize).min(end)..end].to_vec();
+        Ok(IncomingData::new(
+            data.len() as u64,
+            std::io::Cursor::new(data),
+        ))
+    }
+
+    async fn write_data(
+        &self,
+        name: &str,
+        mut data: Box<dyn AsyncRead + Send + Unpin>,
+    ) -> anyhow::Result<()> {
+        let mut buf = vec![];
+        data.read_to_end(&mut buf).await?;
+        self.objects.lock().unwrap().insert(name.into(), buf);
+        Ok(())
+    }
+
+    async fn delete_object(&self, name: &str) -> anyhow::Result<()> {
+        self.objects.lock().unwrap().remove(name);
+        Ok(())
+    }
+
+    async fn list_objects(&self) -> anyhow::Result<ObjectNames> {
+        let names: Vec<_> = self.objects.lock().unwrap().keys().cloned().collect();
+        Ok(ObjectNames::new(Box::pin(futures::stream::iter(
+            names.into_iter().map(Ok),
+        ))))
+    }
+}
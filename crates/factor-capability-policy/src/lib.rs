@@ -0,0 +1,180 @@
+//! A factor applying operator-defined policy to host calls.
+//!
+//! The manifest grants a component its capabilities statically, e.g. the hosts
+//! it may connect to. The capability policy, set in runtime config, lets
+//! operators narrow those grants further, e.g. to particular HTTP methods or
+//! times of day. Factors providing host calls consult the policy through a
+//! [`CapabilityChecker`] before performing each call.
+
+mod policy;
+pub mod runtime_config;
+
+use std::sync::Arc;
+
+use spin_factors::{
+    ConfigureAppContext, Factor, InitContext, PrepareContext, RuntimeFactors, SelfInstanceBuilder,
+};
+
+pub use policy::{Effect, HostCall, Hours, Policy, Rule};
+
+/// The operations host calls are checked as.
+pub mod operation {
+    pub const OUTBOUND_HTTP: &str = "outbound_http";
+    pub const OUTBOUND_REDIS: &str = "outbound_redis";
+    pub const OUTBOUND_PG: &str = "outbound_pg";
+    pub const OUTBOUND_MYSQL: &str = "outbound_mysql";
+    pub const OUTBOUND_MQTT: &str = "outbound_mqtt";
+    pub const SOCKETS: &str = "sockets";
+    pub const KEY_VALUE: &str = "key_value";
+    pub const SQLITE: &str = "sqlite";
+    pub const VARIABLES: &str = "variables";
+    pub const LLM: &str = "llm";
+    pub const WASI_NN: &str = "wasi_nn";
+    pub const BLOBSTORE: &str = "blobstore";
+    pub const MESSAGING: &str = "messaging";
+    pub const CRYPTO: &str = "crypto";
+    pub const JOBS: &str = "jobs";
+    pub const WORKFLOWS: &str = "workflows";
+    pub const ACTORS: &str = "actors";
+    pub const EMAIL: &str = "email";
+}
+
+/// A factor that checks host calls against the capability policy.
+#[derive(Default)]
+pub struct CapabilityPolicyFactor {
+    _priv: (),
+}
+
+impl CapabilityPolicyFactor {
+    /// Create a new CapabilityPolicyFactor.
+    pub fn new() -> Self {
+        Self { _priv: () }
+    }
+}
+
+impl Factor for CapabilityPolicyFactor {
+    type RuntimeConfig = runtime_config::RuntimeConfig;
+    type AppState = Arc<Policy>;
+    type InstanceBuilder = InstanceState;
+
+    fn init(&mut self, _ctx: &mut impl InitContext<Self>) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    fn configure_app<T: RuntimeFactors>(
+        &self,
+        mut ctx: ConfigureAppContext<T, Self>,
+    ) -> anyhow::Result<Self::AppState> {
+        let policy = ctx.take_runtime_config().unwrap_or_default().policy;
+        Ok(Arc::new(policy))
+    }
+
+    fn prepare<T: RuntimeFactors>(
+        &self,
+        ctx: PrepareContext<T, Self>,
+    ) -> anyhow::Result<Self::InstanceBuilder> {
+        Ok(InstanceState {
+            checker: CapabilityChecker::new(ctx.app_component().id(), ctx.app_state().clone()),
+        })
+    }
+}
+
+pub struct InstanceState {
+    checker: CapabilityChecker,
+}
+
+impl InstanceState {
+    /// Returns the checker for the instance's component.
+    pub fn checker(&self) -> &CapabilityChecker {
+        &self.checker
+    }
+}
+
+impl SelfInstanceBuilder for InstanceState {}
+
+/// Checks a component's host calls against the capability policy.
+#[derive(Clone, Debug, Default)]
+pub struct CapabilityChecker {
+    component_id: Arc<str>,
+    policy: Arc<Policy>,
+}
+
+impl CapabilityChecker {
+    /// Creates a checker applying `policy` to the calls of the given component.
+    pub fn new(component_id: impl Into<Arc<str>>, policy: Arc<Policy>) -> Self {
+        Self {
+            component_id: component_id.into(),
+            policy,
+        }
+    }
+
+    /// Returns the checker for the component being prepared, or one that
+    /// allows every call if the [`CapabilityPolicyFactor`] is not in use.
+    pub fn from_prepare_context<T: RuntimeFactors, F: Factor>(
+        ctx: &mut PrepareContext<T, F>,
+    ) -> anyhow::Result<Self> {
+        match ctx.instance_builder::<CapabilityPolicyFactor>() {
+            Ok(instance_state) => Ok(instance_state.checker.clone()),
+            Err(spin_factors::Error::NoSuchFactor(_)) => Ok(Self::default()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Whether the policy allows every call, so there is nothing to check.
+    pub fn is_unrestricted(&self) -> bool {
+        self.policy.is_empty()
+    }
+
+    /// Checks whether the component may perform `operation` on `resource`.
+    pub fn check(
+        &self,
+        operation: &str,
+        resource: &str,
+        method: Option<&str>,
+    ) -> Result<(), Denied> {
+        if self.policy.is_empty() {
+            return Ok(());
+        }
+        let call = HostCall {
+            component_id: &self.component_id,
+            operation,
+            resource,
+            method,
+        };
+        match self.policy.evaluate(&call) {
+            Effect::Allow => Ok(()),
+            Effect::Deny => {
+                tracing::warn!(
+                    component_id = call.component_id,
+                    operation,
+                    resource,
+                    method,
+                    "Host call denied by capability policy"
+                );
+                Err(Denied {
+                    operation: operation.into(),
+                    resource: resource.into(),
+                })
+            }
+        }
+    }
+}
+
+/// A host call was denied by the capability policy.
+#[derive(Debug)]
+pub struct Denied {
+    operation: String,
+    resource: String,
+}
+
+impl std::fmt::Display for Denied {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} access to {:?} denied by capability policy",
+            self.operation, self.resource
+        )
+    }
+}
+
+impl std::error::Error for Denied {}
@@ -0,0 +1,268 @@
+use anyhow::{Context as _, bail, ensure};
+use serde::Deserialize;
+
+/// An ordered list of rules deciding which host calls components may make.
+///
+/// The first rule matching a call decides whether it is allowed; calls that
+/// match no rule are allowed, so that the policy only narrows the capabilities
+/// granted by the manifest.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(transparent)]
+pub struct Policy {
+    rules: Vec<Rule>,
+}
+
+impl Policy {
+    /// Create a policy from rules, in the order they are evaluated.
+    pub fn new(rules: impl IntoIterator<Item = Rule>) -> Self {
+        Self {
+            rules: rules.into_iter().collect(),
+        }
+    }
+
+    /// Returns whether the policy has no rules, allowing every call.
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    /// Decide whether the given call is allowed at the current local time.
+    pub fn evaluate(&self, call: &HostCall) -> Effect {
+        if self.rules.iter().all(|rule| rule.hours.is_none()) {
+            return self.evaluate_at(call, 0);
+        }
+        let now = jiff::Zoned::now();
+        self.evaluate_at(call, now.hour() as u16 * 60 + now.minute() as u16)
+    }
+
+    /// Decide whether the given call is allowed at the given local time, in
+    /// minutes since midnight.
+    pub fn evaluate_at(&self, call: &HostCall, minute_of_day: u16) -> Effect {
+        self.rules
+            .iter()
+            .find(|rule| rule.matches(call, minute_of_day))
+            .map(|rule| rule.effect)
+            .unwrap_or(Effect::Allow)
+    }
+}
+
+/// A host call as seen by the policy.
+#[derive(Clone, Copy, Debug)]
+pub struct HostCall<'a> {
+    /// The ID of the component making the call.
+    pub component_id: &'a str,
+    /// The kind of host call, e.g. `outbound_http` or `key_value`.
+    pub operation: &'a str,
+    /// What the call acts on, e.g. the host of an HTTP request or the label of
+    /// a key-value store.
+    pub resource: &'a str,
+    /// The method of the call, for operations that have one, e.g. `GET`.
+    pub method: Option<&'a str>,
+}
+
+/// Whether a rule allows or denies the calls it matches.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Effect {
+    Allow,
+    Deny,
+}
+
+/// A rule in a [`Policy`].
+///
+/// Each condition that is set must match for the rule to apply. Components,
+/// operations, and resources may be patterns with a leading or trailing `*`
+/// wildcard, e.g. `*.example.com`; methods are matched ignoring case.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Rule {
+    pub effect: Effect,
+    #[serde(default)]
+    pub components: Vec<String>,
+    #[serde(default)]
+    pub operations: Vec<String>,
+    #[serde(default)]
+    pub resources: Vec<String>,
+    #[serde(default)]
+    pub methods: Vec<String>,
+    /// The local time of day during which the rule applies, e.g. `"18:00-08:00"`.
+    #[serde(default)]
+    pub hours: Option<Hours>,
+}
+
+impl Rule {
+    fn matches(&self, call: &HostCall, minute_of_day: u16) -> bool {
+        matches_any(&self.components, call.component_id)
+            && matches_any(&self.operations, call.operation)
+            && matches_any(&self.resources, call.resource)
+            && (self.methods.is_empty()
+                || call.method.is_some_and(|method| {
+                    self.methods
+                        .iter()
+                        .any(|allowed| allowed.eq_ignore_ascii_case(method))
+                }))
+            && self
+                .hours
+                .as_ref()
+                .is_none_or(|hours| hours.contains(minute_of_day))
+    }
+}
+
+/// Returns whether `value` matches any of `patterns`, or true if there are no
+/// patterns.
+fn matches_any(patterns: &[String], value: &str) -> bool {
+    patterns.is_empty() || patterns.iter().any(|pattern| matches(pattern, value))
+}
+
+fn matches(pattern: &str, value: &str) -> bool {
+    if pattern == "*" {
+        true
+    } else if let Some(suffix) = pattern.strip_prefix('*') {
+        value.ends_with(suffix)
+    } else if let Some(prefix) = pattern.strip_suffix('*') {
+        value.starts_with(prefix)
+    } else {
+        pattern == value
+    }
+}
+
+/// A range of local time of day, which may wrap past midnight.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq)]
+#[serde(try_from = "String")]
+pub struct Hours {
+    start: u16,
+    end: u16,
+}
+
+impl Hours {
+    fn contains(&self, minute_of_day: u16) -> bool {
+        if self.start <= self.end {
+            (self.start..self.end).contains(&minute_of_day)
+        } else {
+            minute_of_day >= self.start || minute_of_day < self.end
+        }
+    }
+}
+
+impl TryFrom<String> for Hours {
+    type Error = anyhow::Error;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        let (start, end) = value
+            .split_once('-')
+            .with_context(|| format!("invalid hours {value:?}: expected e.g. \"18:00-24:00\""))?;
+        Ok(Self {
+            start: parse_time(start.trim())?,
+            end: parse_time(end.trim())?,
+        })
+    }
+}
+
+/// Parses `HH:MM` into minutes since midnight; `24:00` is the end of the day.
+fn parse_time(time: &str) -> anyhow::Result<u16> {
+    let Some((hours, minutes)) = time.split_once(':') else {
+        bail!("invalid time {time:?}: expected HH:MM");
+    };
+    let hours: u16 = hours
+        .parse()
+        .with_context(|| format!("invalid time {time:?}"))?;
+    let minutes: u16 = minutes
+        .parse()
+        .with_context(|| format!("invalid time {time:?}"))?;
+    ensure!(
+        minutes < 60 && (hours < 24 || (hours == 24 && minutes == 0)),
+        "invalid time {time:?}"
+    );
+    Ok(hours * 60 + minutes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn call<'a>(component_id: &'a str, resource: &'a str, method: &'a str) -> HostCall<'a> {
+        HostCall {
+            component_id,
+            operation: "outbound_http",
+            resource,
+            method: Some(method),
+        }
+    }
+
+    fn policy(toml: &str) -> Policy {
+        #[derive(Deserialize)]
+        struct Config {
+            capability_policy: Policy,
+        }
+        toml::from_str::<Config>(toml).unwrap().capability_policy
+    }
+
+    #[test]
+    fn first_matching_rule_wins() {
+        let policy = policy(
+            r#"
+            [[capability_policy]]
+            effect = "allow"
+            components = ["x"]
+            operations = ["outbound_http"]
+            resources = ["api.example.com"]
+            methods = ["GET"]
+
+            [[capability_policy]]
+            effect = "deny"
+            components = ["x"]
+            operations = ["outbound_http"]
+            "#,
+        );
+        let at = |call| policy.evaluate_at(&call, 0);
+        assert_eq!(Effect::Allow, at(call("x", "api.example.com", "get")));
+        assert_eq!(Effect::Deny, at(call("x", "api.example.com", "POST")));
+        assert_eq!(Effect::Deny, at(call("x", "other.example.com", "GET")));
+        assert_eq!(Effect::Allow, at(call("y", "other.example.com", "POST")));
+    }
+
+    #[test]
+    fn rules_apply_during_hours() {
+        let policy = policy(
+            r#"
+            [[capability_policy]]
+            effect = "deny"
+            operations = ["variables"]
+            resources = ["secret_*"]
+            hours = "18:00-08:00"
+            "#,
+        );
+        let call = HostCall {
+            component_id: "x",
+            operation: "variables",
+            resource: "secret_token",
+            method: None,
+        };
+        assert_eq!(Effect::Allow, policy.evaluate_at(&call, 12 * 60));
+        assert_eq!(Effect::Deny, policy.evaluate_at(&call, 18 * 60));
+        assert_eq!(Effect::Deny, policy.evaluate_at(&call, 7 * 60 + 59));
+        assert_eq!(Effect::Allow, policy.evaluate_at(&call, 8 * 60));
+    }
+
+    #[test]
+    fn patterns_match_prefixes_and_suffixes() {
+        assert!(matches("*", "anything"));
+        assert!(matches("*.example.com", "api.example.com"));
+        assert!(!matches("*.example.com", "example.org"));
+        assert!(matches("secret_*", "secret_token"));
+        assert!(!matches("secret", "secret_token"));
+    }
+
+    #[test]
+    fn invalid_hours_are_rejected() {
+        assert_eq!(
+            Hours {
+                start: 18 * 60,
+                end: 24 * 60
+            },
+            Hours::try_from("18:00-24:00".to_owned()).unwrap()
+        );
+        assert!(Hours::try_from("18:00".to_owned()).is_err());
+        assert!(Hours::try_from("18:00-24:30".to_owned()).is_err());
+        assert!(Hours::try_from("6pm-8am".to_owned()).is_err());
+    }
+}
@@ -0,0 +1,41 @@
+use spin_factors::runtime_config::toml::GetTomlValue;
+
+use crate::Policy;
+
+/// Runtime configuration for the capability policy.
+#[derive(Default)]
+pub struct RuntimeConfig {
+    pub policy: Policy,
+}
+
+/// Get the runtime configuration for the capability policy from a TOML table.
+///
+/// Expects table to be in the format:
+/// ```toml
+/// # Component "x" may only GET from api.example.com
+/// [[capability_policy]]
+/// effect = "allow"
+/// components = ["x"]
+/// operations = ["outbound_http"]
+/// resources = ["api.example.com"]
+/// methods = ["GET"]
+///
+/// [[capability_policy]]
+/// effect = "deny"
+/// components = ["x"]
+/// operations = ["outbound_http"]
+///
+/// # No component may read the secret_token variable after 18:00
+/// [[capability_policy]]
+/// effect = "deny"
+/// operations = ["variables"]
+/// resources = ["secret_token"]
+/// hours = "18:00-24:00"
+/// ```
+pub fn config_from_table(table: &impl GetTomlValue) -> anyhow::Result<Option<RuntimeConfig>> {
+    let Some(rules) = table.get("capability_policy") else {
+        return Ok(None);
+    };
+    let policy = rules.clone().try_into::<Policy>()?;
+    Ok(Some(RuntimeConfig { policy }))
+}
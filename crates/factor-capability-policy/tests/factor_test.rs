@@ -0,0 +1,64 @@
+use spin_factor_capability_policy::{
+    CapabilityPolicyFactor, operation, runtime_config::config_from_table,
+};
+use spin_factors::{RuntimeFactors, anyhow};
+use spin_factors_test::{TestEnvironment, toml};
+
+#[derive(RuntimeFactors)]
+struct TestFactors {
+    capability_policy: CapabilityPolicyFactor,
+}
+
+#[tokio::test]
+async fn policy_applies_to_configured_components() -> anyhow::Result<()> {
+    let runtime_config = TestFactorsRuntimeConfig {
+        capability_policy: config_from_table(&toml! {
+            [[capability_policy]]
+            effect = "allow"
+            components = ["test-component"]
+            operations = ["outbound_http"]
+            resources = ["api.example.com"]
+            methods = ["GET"]
+
+            [[capability_policy]]
+            effect = "deny"
+            components = ["test-component"]
+            operations = ["outbound_http"]
+        })?,
+    };
+    let env = TestEnvironment::new(TestFactors {
+        capability_policy: CapabilityPolicyFactor::new(),
+    })
+    .runtime_config(runtime_config)?;
+
+    let state = env.build_instance_state().await?;
+    let checker = state.capability_policy.checker();
+    assert!(!checker.is_unrestricted());
+    checker.check(operation::OUTBOUND_HTTP, "api.example.com", Some("GET"))?;
+    checker.check(operation::KEY_VALUE, "default", None)?;
+    let err = checker
+        .check(operation::OUTBOUND_HTTP, "api.example.com", Some("POST"))
+        .unwrap_err();
+    assert_eq!(
+        r#"outbound_http access to "api.example.com" denied by capability policy"#,
+        err.to_string()
+    );
+    assert!(
+        checker
+            .check(operation::OUTBOUND_HTTP, "example.org", Some("GET"))
+            .is_err()
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn no_policy_allows_everything() -> anyhow::Result<()> {
+    let env = TestEnvironment::new(TestFactors {
+        capability_policy: CapabilityPolicyFactor::new(),
+    });
+    let state = env.build_instance_state().await?;
+    let checker = state.capability_policy.checker();
+    assert!(checker.is_unrestricted());
+    checker.check(operation::OUTBOUND_HTTP, "example.org", Some("DELETE"))?;
+    Ok(())
+}
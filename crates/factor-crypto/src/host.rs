@@ -0,0 +1,140 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use spin_core::wasmtime::component::Resource;
+use spin_expressions::{Key as VariableKey, ProviderResolver};
+use spin_factor_capability_policy::{CapabilityChecker, operation};
+use spin_factors::anyhow;
+use spin_resource_table::Table;
+use spin_world::spin::crypto::signatures::{self, Error, Key};
+use tracing::instrument;
+
+use crate::{Algorithm, KeyDefinition, SigningKey};
+
+const DEFAULT_TABLE_CAPACITY: u32 = 256;
+
+/// The crypto state of a component instance, and the host implementation
+/// of `spin:crypto/signatures`.
+pub struct InstanceState {
+    component_id: String,
+    keys: Arc<HashMap<String, KeyDefinition>>,
+    resolver: Arc<ProviderResolver>,
+    capabilities: CapabilityChecker,
+    /// Keys resolved so far, by name, so each is only resolved once.
+    resolved: HashMap<String, Arc<(Algorithm, SigningKey)>>,
+    open_keys: Table<Arc<(Algorithm, SigningKey)>>,
+}
+
+impl InstanceState {
+    pub fn new(
+        component_id: String,
+        keys: Arc<HashMap<String, KeyDefinition>>,
+        resolver: Arc<ProviderResolver>,
+        capabilities: CapabilityChecker,
+    ) -> Self {
+        Self {
+            component_id,
+            keys,
+            resolver,
+            capabilities,
+            resolved: HashMap::new(),
+            open_keys: Table::new(DEFAULT_TABLE_CAPACITY),
+        }
+    }
+
+    /// Returns the names of the keys the component may open.
+    pub fn key_names(&self) -> impl Iterator<Item = &str> {
+        self.keys.keys().map(String::as_str)
+    }
+
+    async fn resolve(&mut self, name: &str) -> Result<Arc<(Algorithm, SigningKey)>, Error> {
+        if let Some(key) = self.resolved.get(name) {
+            return Ok(key.clone());
+        }
+        let definition = self.keys.get(name).ok_or(Error::NoSuchKey)?;
+        let variable = VariableKey::new(&definition.variable)
+            .map_err(|err| Error::InvalidKey(err.to_string()))?;
+        let material = self
+            .resolver
+            .resolve(&self.component_id, variable)
+            .await
+            .map_err(|err| Error::InvalidKey(err.to_string()))?;
+        let key = SigningKey::new(definition.algorithm, &material)
+            .map_err(|err| Error::InvalidKey(format!("{err:#}")))?;
+        let key = Arc::new((definition.algorithm, key));
+        self.resolved.insert(name.to_owned(), key.clone());
+        Ok(key)
+    }
+
+    fn get_key(&self, key: &Resource<Key>) -> anyhow::Result<&Arc<(Algorithm, SigningKey)>> {
+        self.open_keys
+            .get(key.rep())
+            .ok_or_else(|| anyhow::anyhow!("invalid key resource"))
+    }
+}
+
+impl signatures::Host for InstanceState {
+    fn convert_error(&mut self, err: Error) -> anyhow::Result<Error> {
+        Ok(err)
+    }
+}
+
+impl signatures::HostKey for InstanceState {
+    #[instrument(name = "spin_crypto.open", skip(self), fields(otel.kind = "client"))]
+    async fn open(&mut self, name: String) -> Result<Resource<Key>, Error> {
+        self.capabilities
+            .check(operation::CRYPTO, &name, None)
+            .map_err(|err| Error::Other(err.to_string()))?;
+        let key = self.resolve(&name).await?;
+        self.open_keys
+            .push(key)
+            .map(Resource::new_own)
+            .map_err(|()| Error::KeyTableFull)
+    }
+
+    async fn algorithm(&mut self, key: Resource<Key>) -> anyhow::Result<signatures::Algorithm> {
+        let (algorithm, _) = &**self.get_key(&key)?;
+        Ok(match algorithm {
+            Algorithm::HmacSha256 => signatures::Algorithm::HmacSha256,
+            Algorithm::HmacSha384 => signatures::Algorithm::HmacSha384,
+            Algorithm::HmacSha512 => signatures::Algorithm::HmacSha512,
+            Algorithm::Ed25519 => signatures::Algorithm::Ed25519,
+            Algorithm::EcdsaP256Sha256 => signatures::Algorithm::EcdsaP256Sha256,
+            Algorithm::RsaPkcs1Sha256 => signatures::Algorithm::RsaPkcs1Sha256,
+        })
+    }
+
+    #[instrument(name = "spin_crypto.sign", skip_all, fields(otel.kind = "client"))]
+    async fn sign(&mut self, key: Resource<Key>, message: Vec<u8>) -> Result<Vec<u8>, Error> {
+        let (_, key) = &**self
+            .get_key(&key)
+            .map_err(|err| Error::Other(err.to_string()))?;
+        key.sign(&message)
+            .map_err(|err| Error::Other(err.to_string()))
+    }
+
+    #[instrument(name = "spin_crypto.verify", skip_all, fields(otel.kind = "client"))]
+    async fn verify(
+        &mut self,
+        key: Resource<Key>,
+        message: Vec<u8>,
+        signature: Vec<u8>,
+    ) -> Result<bool, Error> {
+        let (_, key) = &**self
+            .get_key(&key)
+            .map_err(|err| Error::Other(err.to_string()))?;
+        Ok(key.verify(&message, &signature))
+    }
+
+    async fn public_key(&mut self, key: Resource<Key>) -> Result<Vec<u8>, Error> {
+        let (_, key) = &**self
+            .get_key(&key)
+            .map_err(|err| Error::Other(err.to_string()))?;
+        key.public_key().ok_or(Error::Unsupported)
+    }
+
+    async fn drop(&mut self, key: Resource<Key>) -> anyhow::Result<()> {
+        self.open_keys.remove(key.rep());
+        Ok(())
+    }
+}
@@ -0,0 +1,140 @@
+//! A factor providing the `spin:crypto` signatures interface.
+//!
+//! Components sign and verify messages with keys held by the host. Each key
+//! is declared in the component's manifest `crypto_keys` with an algorithm and
+//! the component variable holding its material, so the material can come from
+//! any variables provider (e.g. a secrets vault) and is never exposed to the
+//! guest.
+
+mod host;
+mod key;
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use anyhow::{Context as _, ensure};
+use serde::Deserialize;
+use spin_expressions::ProviderResolver;
+use spin_factor_capability_policy::CapabilityChecker;
+use spin_factor_variables::VariablesFactor;
+use spin_factors::{
+    ConfigureAppContext, Factor, FactorData, FactorInstanceBuilder, InitContext, PrepareContext,
+    RuntimeFactors,
+};
+use spin_locked_app::MetadataKey;
+
+pub use host::InstanceState;
+pub use key::{Algorithm, SigningKey};
+
+/// Metadata key for the keys available to a component.
+pub const CRYPTO_KEYS_KEY: MetadataKey<HashMap<String, KeyDefinition>> =
+    MetadataKey::new("crypto_keys");
+
+/// A factor that provides signing and verification with host-held keys.
+#[derive(Default)]
+pub struct CryptoFactor {
+    _priv: (),
+}
+
+impl CryptoFactor {
+    /// Create a new CryptoFactor.
+    pub fn new() -> Self {
+        Self { _priv: () }
+    }
+}
+
+impl Factor for CryptoFactor {
+    type RuntimeConfig = ();
+    type AppState = AppState;
+    type InstanceBuilder = InstanceBuilder;
+
+    fn init(&mut self, ctx: &mut impl InitContext<Self>) -> anyhow::Result<()> {
+        ctx.link_bindings(
+            spin_world::spin::crypto::signatures::add_to_linker::<_, FactorData<Self>>,
+        )
+    }
+
+    fn configure_app<T: RuntimeFactors>(
+        &self,
+        ctx: ConfigureAppContext<T, Self>,
+    ) -> anyhow::Result<Self::AppState> {
+        // Build component -> key definitions map
+        let mut component_keys = HashMap::new();
+        for component in ctx.app().components() {
+            let component_id = component.id().to_string();
+            let keys = component
+                .get_metadata(CRYPTO_KEYS_KEY)
+                .with_context(|| format!("invalid crypto_keys for component {component_id:?}"))?
+                .unwrap_or_default();
+            for (name, key) in &keys {
+                ensure!(
+                    component
+                        .config()
+                        .any(|(variable, _)| variable == &key.variable),
+                    "crypto key {name:?} for component {component_id:?} refers to undefined variable {:?}",
+                    key.variable
+                );
+            }
+            component_keys.insert(component_id, Arc::new(keys));
+        }
+        Ok(AppState { component_keys })
+    }
+
+    fn prepare<T: RuntimeFactors>(
+        &self,
+        mut ctx: PrepareContext<T, Self>,
+    ) -> anyhow::Result<InstanceBuilder> {
+        let component_id = ctx.app_component().id().to_string();
+        let keys = ctx
+            .app_state()
+            .component_keys
+            .get(&component_id)
+            .expect("component should be in component_keys")
+            .clone();
+        let resolver = ctx
+            .instance_builder::<VariablesFactor>()?
+            .expression_resolver()
+            .clone();
+        let capabilities = CapabilityChecker::from_prepare_context(&mut ctx)?;
+        Ok(InstanceBuilder {
+            component_id,
+            keys,
+            resolver,
+            capabilities,
+        })
+    }
+}
+
+/// The definition of a key in a component's `crypto_keys`.
+#[derive(Clone, Debug, Deserialize)]
+pub struct KeyDefinition {
+    /// The algorithm the key is used with.
+    pub algorithm: Algorithm,
+    /// The component variable holding the key material.
+    pub variable: String,
+}
+
+pub struct AppState {
+    /// The keys defined for each component, by name.
+    component_keys: HashMap<String, Arc<HashMap<String, KeyDefinition>>>,
+}
+
+pub struct InstanceBuilder {
+    component_id: String,
+    keys: Arc<HashMap<String, KeyDefinition>>,
+    resolver: Arc<ProviderResolver>,
+    capabilities: CapabilityChecker,
+}
+
+impl FactorInstanceBuilder for InstanceBuilder {
+    type InstanceState = InstanceState;
+
+    fn build(self) -> anyhow::Result<Self::InstanceState> {
+        Ok(InstanceState::new(
+            self.component_id,
+            self.keys,
+            self.resolver,
+            self.capabilities,
+        ))
+    }
+}
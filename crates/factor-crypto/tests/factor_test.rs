@@ -0,0 +1,127 @@
+use anyhow::bail;
+use spin_core::wasmtime::component::Resource;
+use spin_factor_capability_policy::{CapabilityPolicyFactor, runtime_config::config_from_table};
+use spin_factor_crypto::CryptoFactor;
+use spin_factor_variables::VariablesFactor;
+use spin_factors::RuntimeFactors;
+use spin_factors_test::{TestEnvironment, toml};
+use spin_world::spin::crypto::signatures::{Algorithm, Error, HostKey};
+
+#[derive(RuntimeFactors)]
+struct TestFactors {
+    variables: VariablesFactor,
+    crypto: CryptoFactor,
+}
+
+fn factors() -> TestFactors {
+    TestFactors {
+        variables: VariablesFactor::default(),
+        crypto: CryptoFactor::new(),
+    }
+}
+
+#[tokio::test]
+async fn signs_with_declared_keys() -> anyhow::Result<()> {
+    let env = TestEnvironment::new(factors()).extend_manifest(toml! {
+        [variables]
+        webhook_secret = { default = "Jefe" }
+
+        [component.test-component]
+        source = "does-not-exist.wasm"
+        variables = { webhook_key = "{{ webhook_secret }}" }
+        crypto_keys = { webhook = { algorithm = "hmac-sha256", variable = "webhook_key" } }
+    });
+    let mut state = env.build_instance_state().await?;
+    let crypto = &mut state.crypto;
+
+    let key = crypto.open("webhook".into()).await.unwrap();
+    let key_rep = key.rep();
+    assert_eq!(
+        Algorithm::HmacSha256,
+        crypto.algorithm(Resource::new_borrow(key_rep)).await?
+    );
+    let message = b"what do ya want for nothing?".to_vec();
+    let signature = crypto
+        .sign(Resource::new_borrow(key_rep), message.clone())
+        .await
+        .unwrap();
+    assert!(
+        crypto
+            .verify(Resource::new_borrow(key_rep), message, signature)
+            .await
+            .unwrap()
+    );
+    assert!(matches!(
+        crypto.public_key(Resource::new_borrow(key_rep)).await,
+        Err(Error::Unsupported)
+    ));
+    Ok(())
+}
+
+#[tokio::test]
+async fn undeclared_keys_cannot_be_opened() -> anyhow::Result<()> {
+    let env = TestEnvironment::new(factors());
+    let mut state = env.build_instance_state().await?;
+
+    assert!(matches!(
+        state.crypto.open("webhook".into()).await,
+        Err(Error::NoSuchKey)
+    ));
+    Ok(())
+}
+
+#[tokio::test]
+async fn errors_when_key_variable_is_not_defined() -> anyhow::Result<()> {
+    let env = TestEnvironment::new(factors()).extend_manifest(toml! {
+        [component.test-component]
+        source = "does-not-exist.wasm"
+        crypto_keys = { webhook = { algorithm = "hmac-sha256", variable = "webhook_key" } }
+    });
+    let Err(err) = env.build_instance_state().await else {
+        bail!("expected instance build to fail but it didn't");
+    };
+
+    assert!(
+        err.to_string()
+            .contains(r#"refers to undefined variable "webhook_key""#)
+    );
+    Ok(())
+}
+
+#[derive(RuntimeFactors)]
+struct PolicyTestFactors {
+    capability_policy: CapabilityPolicyFactor,
+    variables: VariablesFactor,
+    crypto: CryptoFactor,
+}
+
+#[tokio::test]
+async fn capability_policy_denies_keys() -> anyhow::Result<()> {
+    let env = TestEnvironment::new(PolicyTestFactors {
+        capability_policy: CapabilityPolicyFactor::new(),
+        variables: VariablesFactor::default(),
+        crypto: CryptoFactor::new(),
+    })
+    .extend_manifest(toml! {
+        [component.test-component]
+        source = "does-not-exist.wasm"
+        variables = { webhook_key = "Jefe" }
+        crypto_keys = { webhook = { algorithm = "hmac-sha256", variable = "webhook_key" } }
+    })
+    .runtime_config(PolicyTestFactorsRuntimeConfig {
+        capability_policy: config_from_table(&toml! {
+            [[capability_policy]]
+            effect = "deny"
+            operations = ["*"]
+        })?,
+        variables: None,
+        crypto: None,
+    })?;
+    let mut state = env.build_instance_state().await?;
+
+    assert!(matches!(
+        state.crypto.open("webhook".into()).await,
+        Err(Error::Other(_))
+    ));
+    Ok(())
+}
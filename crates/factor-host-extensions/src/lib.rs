@@ -0,0 +1,287 @@
+//! A factor for adding custom host functionality to Spin without forking it.
+//!
+//! A [`HostExtension`] provides host functions under one or more WIT
+//! interface names. Embedders (such as custom trigger executables) register
+//! their extensions with [`register`] before building the runtime, and the
+//! [`HostExtensionsFactor`] links them for every component. A component may
+//! only call an extension listed in its manifest `host_extensions`; each
+//! extension is configured from its `[host_extension.<name>]` runtime config
+//! table.
+
+pub mod runtime_config;
+
+use std::any::Any;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+
+use runtime_config::RuntimeConfig;
+use spin_factors::{
+    ConfigureAppContext, Factor, InitContext, PrepareContext, RuntimeFactors, SelfInstanceBuilder,
+    anyhow::{self, Context as _, bail},
+    wasmtime::{self, component::Val},
+};
+use spin_locked_app::MetadataKey;
+
+/// Metadata key for the host extensions a component is allowed to use.
+pub const HOST_EXTENSIONS_KEY: MetadataKey<Vec<String>> = MetadataKey::new("host_extensions");
+
+static REGISTERED_EXTENSIONS: Mutex<Vec<Arc<dyn HostExtension>>> = Mutex::new(Vec::new());
+
+/// Registers a host extension to be included by [`HostExtensionsFactor::from_registered`].
+///
+/// This must be called before the runtime is built, typically at the start of `main`.
+pub fn register(extension: impl HostExtension) {
+    REGISTERED_EXTENSIONS
+        .lock()
+        .unwrap()
+        .push(Arc::new(extension));
+}
+
+/// Returns the host extensions registered with [`register`].
+pub fn registered() -> Vec<Arc<dyn HostExtension>> {
+    REGISTERED_EXTENSIONS.lock().unwrap().clone()
+}
+
+/// A piece of host functionality provided from outside Spin.
+pub trait HostExtension: Send + Sync + 'static {
+    /// The name of the extension, as used in manifest `host_extensions` and
+    /// `[host_extension.<name>]` runtime config tables.
+    fn name(&self) -> &str;
+
+    /// Defines the extension's host functions.
+    fn link(&self, linker: &mut ExtensionLinker) -> anyhow::Result<()>;
+
+    /// Configures the extension for an app, given its runtime config table (if any).
+    fn configure_app(
+        &self,
+        runtime_config: Option<&toml::Table>,
+    ) -> anyhow::Result<Box<dyn HostExtensionApp>>;
+}
+
+/// A [`HostExtension`] configured for a particular app.
+pub trait HostExtensionApp: Send + Sync {
+    /// Creates the extension's state for a new instance of the given component.
+    ///
+    /// This state is passed to the extension's host functions, and must be of
+    /// the type they were defined with in [`ExtensionLinker::func_new`].
+    fn instantiate(&self, component_id: &str) -> anyhow::Result<Box<dyn Any + Send>>;
+}
+
+type HostFunc =
+    dyn Fn(&mut (dyn Any + Send), &[Val], &mut [Val]) -> anyhow::Result<()> + Send + Sync;
+
+/// Collects the host functions defined by a [`HostExtension`].
+#[derive(Default)]
+pub struct ExtensionLinker {
+    functions: Vec<(String, String, Arc<HostFunc>)>,
+}
+
+impl ExtensionLinker {
+    /// Defines a host function `name` in the given interface (e.g.
+    /// `acme:queue/client@1.0.0`).
+    ///
+    /// The function is called with the instance state created by
+    /// [`HostExtensionApp::instantiate`], which must be of type `S`.
+    pub fn func_new<S: Any + Send>(
+        &mut self,
+        interface: &str,
+        name: &str,
+        func: impl Fn(&mut S, &[Val], &mut [Val]) -> anyhow::Result<()> + Send + Sync + 'static,
+    ) {
+        let func = move |state: &mut (dyn Any + Send), params: &[Val], results: &mut [Val]| {
+            let state = state
+                .downcast_mut::<S>()
+                .context("host extension instance state has an unexpected type")?;
+            func(state, params, results)
+        };
+        self.functions
+            .push((interface.to_owned(), name.to_owned(), Arc::new(func)));
+    }
+}
+
+/// The factor for host extensions.
+pub struct HostExtensionsFactor {
+    extensions: Vec<Arc<dyn HostExtension>>,
+}
+
+impl HostExtensionsFactor {
+    /// Creates a new host extensions factor with the given extensions.
+    pub fn new(
+        extensions: impl IntoIterator<Item = Arc<dyn HostExtension>>,
+    ) -> anyhow::Result<Self> {
+        let extensions: Vec<_> = extensions.into_iter().collect();
+        let mut names = HashSet::new();
+        for extension in &extensions {
+            if !names.insert(extension.name()) {
+                bail!(
+                    "host extension '{}' is registered more than once",
+                    extension.name()
+                );
+            }
+        }
+        Ok(Self { extensions })
+    }
+
+    /// Creates a new host extensions factor with the extensions registered
+    /// with [`register`].
+    pub fn from_registered() -> anyhow::Result<Self> {
+        Self::new(registered())
+    }
+
+    fn extension(&self, name: &str) -> Option<&Arc<dyn HostExtension>> {
+        self.extensions.iter().find(|ext| ext.name() == name)
+    }
+}
+
+impl Factor for HostExtensionsFactor {
+    type RuntimeConfig = RuntimeConfig;
+    type AppState = AppState;
+    type InstanceBuilder = InstanceState;
+
+    fn init(&mut self, ctx: &mut impl InitContext<Self>) -> anyhow::Result<()> {
+        // Group functions by interface, as extensions may share an interface
+        // but each linker instance may only be defined once.
+        let mut interfaces = BTreeMap::<_, Vec<_>>::new();
+        for extension in &self.extensions {
+            let mut linker = ExtensionLinker::default();
+            extension
+                .link(&mut linker)
+                .with_context(|| format!("failed to link host extension '{}'", extension.name()))?;
+            for (interface, name, func) in linker.functions {
+                interfaces.entry(interface).or_default().push((
+                    extension.name().to_owned(),
+                    name,
+                    func,
+                ));
+            }
+        }
+        ctx.link_bindings(|linker, get_data| {
+            for (interface, functions) in &interfaces {
+                let mut instance = linker.instance(interface)?;
+                for (extension, name, func) in functions {
+                    let extension = extension.clone();
+                    let func = func.clone();
+                    instance.func_new(name, move |mut store, _ty, params, results| {
+                        get_data(store.data_mut())
+                            .call(&extension, &*func, params, results)
+                            .map_err(wasmtime::Error::from_anyhow)
+                    })?;
+                }
+            }
+            Ok(())
+        })?;
+        Ok(())
+    }
+
+    fn configure_app<T: RuntimeFactors>(
+        &self,
+        mut ctx: ConfigureAppContext<T, Self>,
+    ) -> anyhow::Result<Self::AppState> {
+        let mut runtime_config = ctx.take_runtime_config().unwrap_or_default().extensions;
+        if let Some(name) = runtime_config
+            .keys()
+            .find(|name| self.extension(name).is_none())
+        {
+            bail!("runtime config configures host extension '{name}', which is not available");
+        }
+
+        let mut component_extensions = HashMap::new();
+        for component in ctx.app().components() {
+            let names = component
+                .get_metadata(HOST_EXTENSIONS_KEY)?
+                .unwrap_or_default();
+            if let Some(name) = names.iter().find(|name| self.extension(name).is_none()) {
+                bail!(
+                    "component '{}' uses host extension '{name}', which is not available",
+                    component.id()
+                );
+            }
+            component_extensions.insert(component.id().to_owned(), names);
+        }
+
+        let extensions = self
+            .extensions
+            .iter()
+            .map(|extension| {
+                let name = extension.name();
+                let app = extension
+                    .configure_app(runtime_config.remove(name).as_ref())
+                    .with_context(|| format!("failed to configure host extension '{name}'"))?;
+                Ok((name.to_owned(), app))
+            })
+            .collect::<anyhow::Result<_>>()?;
+
+        Ok(AppState {
+            extensions,
+            component_extensions,
+        })
+    }
+
+    fn prepare<T: RuntimeFactors>(
+        &self,
+        ctx: PrepareContext<T, Self>,
+    ) -> anyhow::Result<Self::InstanceBuilder> {
+        let component_id = ctx.app_component().id();
+        let app_state = ctx.app_state();
+        let states = app_state
+            .component_extensions
+            .get(component_id)
+            .into_iter()
+            .flatten()
+            .map(|name| {
+                let state = app_state.extensions[name]
+                    .instantiate(component_id)
+                    .with_context(|| format!("failed to instantiate host extension '{name}'"))?;
+                Ok((name.clone(), state))
+            })
+            .collect::<anyhow::Result<_>>()?;
+        Ok(InstanceState {
+            component_id: component_id.to_owned(),
+            states,
+        })
+    }
+}
+
+/// The application state for the host extensions factor.
+pub struct AppState {
+    extensions: HashMap<String, Box<dyn HostExtensionApp>>,
+    component_extensions: HashMap<String, Vec<String>>,
+}
+
+/// The instance state for the host extensions factor.
+pub struct InstanceState {
+    component_id: String,
+    states: HashMap<String, Box<dyn Any + Send>>,
+}
+
+impl InstanceState {
+    /// Returns true if the instance's component may use the given extension.
+    pub fn is_allowed(&self, extension: &str) -> bool {
+        self.states.contains_key(extension)
+    }
+
+    /// Returns the state of the given extension, if the component may use it
+    /// and the state is of type `S`.
+    pub fn state_mut<S: Any>(&mut self, extension: &str) -> Option<&mut S> {
+        self.states.get_mut(extension)?.downcast_mut()
+    }
+
+    fn call(
+        &mut self,
+        extension: &str,
+        func: &HostFunc,
+        params: &[Val],
+        results: &mut [Val],
+    ) -> anyhow::Result<()> {
+        let Some(state) = self.states.get_mut(extension) else {
+            bail!(
+                "component '{}' is not allowed to use host extension '{extension}'; \
+                add it to the component's `host_extensions` in the manifest",
+                self.component_id
+            );
+        };
+        func(state.as_mut(), params, results)
+    }
+}
+
+impl SelfInstanceBuilder for InstanceState {}
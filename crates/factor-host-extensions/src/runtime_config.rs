@@ -0,0 +1,28 @@
+use std::collections::HashMap;
+
+use spin_factors::runtime_config::toml::GetTomlValue;
+
+/// Runtime configuration for host extensions.
+#[derive(Default)]
+pub struct RuntimeConfig {
+    /// The configuration table for each extension, by extension name.
+    pub extensions: HashMap<String, toml::Table>,
+}
+
+/// Get the runtime configuration for host extensions from a TOML table.
+///
+/// Expects table to be in the format:
+/// ```toml
+/// [host_extension.acme-queue]
+/// # any settings understood by the extension
+/// endpoint = "https://queue.example.com"
+/// ```
+pub fn config_from_table(table: &impl GetTomlValue) -> anyhow::Result<Option<RuntimeConfig>> {
+    let Some(host_extension) = table.get("host_extension") else {
+        return Ok(None);
+    };
+    let extensions = host_extension
+        .clone()
+        .try_into::<HashMap<String, toml::Table>>()?;
+    Ok(Some(RuntimeConfig { extensions }))
+}
@@ -0,0 +1,112 @@
+use std::any::Any;
+use std::sync::Arc;
+
+use spin_factor_host_extensions::{
+    ExtensionLinker, HostExtension, HostExtensionApp, HostExtensionsFactor,
+    runtime_config::RuntimeConfig,
+};
+use spin_factors::{RuntimeFactors, anyhow};
+use spin_factors_test::{TestEnvironment, toml};
+
+#[derive(RuntimeFactors)]
+struct TestFactors {
+    host_extensions: HostExtensionsFactor,
+}
+
+/// An extension whose instance state is the `greeting` from its runtime config.
+struct Greeter;
+
+impl HostExtension for Greeter {
+    fn name(&self) -> &str {
+        "greeter"
+    }
+
+    fn link(&self, linker: &mut ExtensionLinker) -> anyhow::Result<()> {
+        linker.func_new::<String>("test:greeter/greet", "greet", |_, _, _| Ok(()));
+        Ok(())
+    }
+
+    fn configure_app(
+        &self,
+        runtime_config: Option<&toml::Table>,
+    ) -> anyhow::Result<Box<dyn HostExtensionApp>> {
+        let greeting = runtime_config
+            .and_then(|t| t.get("greeting"))
+            .and_then(|v| v.as_str())
+            .unwrap_or("hello")
+            .to_owned();
+        Ok(Box::new(GreeterApp(greeting)))
+    }
+}
+
+struct GreeterApp(String);
+
+impl HostExtensionApp for GreeterApp {
+    fn instantiate(&self, component_id: &str) -> anyhow::Result<Box<dyn Any + Send>> {
+        Ok(Box::new(format!("{} {component_id}", self.0)))
+    }
+}
+
+fn test_factors() -> TestFactors {
+    TestFactors {
+        host_extensions: HostExtensionsFactor::new([Arc::new(Greeter) as _]).unwrap(),
+    }
+}
+
+#[tokio::test]
+async fn allowed_extension_is_instantiated() -> anyhow::Result<()> {
+    let runtime_config = TestFactorsRuntimeConfig {
+        host_extensions: Some(RuntimeConfig {
+            extensions: [("greeter".into(), toml! { greeting = "hi" })].into(),
+        }),
+    };
+    let env = TestEnvironment::new(test_factors())
+        .extend_manifest(toml! {
+            [component.test-component]
+            source = "does-not-exist.wasm"
+            host_extensions = ["greeter"]
+        })
+        .runtime_config(runtime_config)?;
+    let mut state = env.build_instance_state().await?;
+
+    assert!(state.host_extensions.is_allowed("greeter"));
+    assert_eq!(
+        state
+            .host_extensions
+            .state_mut::<String>("greeter")
+            .unwrap(),
+        "hi test-component"
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn extension_is_gated_by_manifest() -> anyhow::Result<()> {
+    let env = TestEnvironment::new(test_factors()).extend_manifest(toml! {
+        [component.test-component]
+        source = "does-not-exist.wasm"
+    });
+    let state = env.build_instance_state().await?;
+
+    assert!(!state.host_extensions.is_allowed("greeter"));
+    Ok(())
+}
+
+#[tokio::test]
+async fn unknown_extension_is_an_error() -> anyhow::Result<()> {
+    let env = TestEnvironment::new(test_factors()).extend_manifest(toml! {
+        [component.test-component]
+        source = "does-not-exist.wasm"
+        host_extensions = ["acme-queue"]
+    });
+    let Err(err) = env.build_instance_state().await else {
+        panic!("expected unknown host extension to fail");
+    };
+    assert!(format!("{err:#}").contains("acme-queue"), "{err:#}");
+    Ok(())
+}
+
+#[test]
+fn duplicate_extensions_are_rejected() {
+    assert!(HostExtensionsFactor::new([Arc::new(Greeter) as _, Arc::new(Greeter) as _]).is_err());
+}
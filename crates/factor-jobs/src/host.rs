@@ -0,0 +1,148 @@
+use std::{
+    collections::HashSet,
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use spin_factor_capability_policy::{CapabilityChecker, operation};
+use spin_factors::anyhow;
+use spin_world::spin::jobs::queue::{self, EnqueueOptions, Error};
+use spin_world::spin::jobs::scheduler::{self, Invocation, ScheduleTime};
+use tracing::instrument;
+
+use crate::JobQueue;
+
+/// The jobs state of a component instance, and the host implementation of
+/// `spin:jobs/queue` and `spin:jobs/scheduler`.
+pub struct InstanceState {
+    component_id: String,
+    queue: Arc<JobQueue>,
+    allowed_jobs: Arc<HashSet<String>>,
+    allowed_components: Arc<HashSet<String>>,
+    capabilities: CapabilityChecker,
+}
+
+impl InstanceState {
+    pub fn new(
+        component_id: String,
+        queue: Arc<JobQueue>,
+        allowed_jobs: Arc<HashSet<String>>,
+        allowed_components: Arc<HashSet<String>>,
+        capabilities: CapabilityChecker,
+    ) -> Self {
+        Self {
+            component_id,
+            queue,
+            allowed_jobs,
+            allowed_components,
+            capabilities,
+        }
+    }
+
+    /// The app's job queue.
+    pub fn queue(&self) -> &JobQueue {
+        &self.queue
+    }
+
+    /// Returns the names of the jobs the component may enqueue.
+    pub fn allowed_jobs(&self) -> &HashSet<String> {
+        &self.allowed_jobs
+    }
+
+    /// Returns the IDs of the components the component may schedule
+    /// invocations of.
+    pub fn allowed_components(&self) -> &HashSet<String> {
+        &self.allowed_components
+    }
+
+    /// Checks the capability policy for a call acting on the named job or
+    /// scheduled component.
+    fn check(&self, resource: &str, method: &str) -> Result<(), Error> {
+        self.capabilities
+            .check(operation::JOBS, resource, Some(method))
+            .map_err(|_| Error::AccessDenied)
+    }
+}
+
+fn other_error(err: anyhow::Error) -> Error {
+    Error::Other(format!("{err:#}"))
+}
+
+impl queue::Host for InstanceState {
+    #[instrument(name = "spin_jobs.enqueue", skip(self, payload, options), err, fields(otel.kind = "producer"))]
+    async fn enqueue(
+        &mut self,
+        job: String,
+        payload: Vec<u8>,
+        options: EnqueueOptions,
+    ) -> Result<String, Error> {
+        if !self.allowed_jobs.contains(&job) {
+            return Err(Error::AccessDenied);
+        }
+        self.check(&job, "enqueue")?;
+        self.queue
+            .enqueue(&job, payload, options)
+            .await
+            .map_err(other_error)
+    }
+
+    fn convert_error(&mut self, err: Error) -> anyhow::Result<Error> {
+        Ok(err)
+    }
+}
+
+impl scheduler::Host for InstanceState {
+    #[instrument(name = "spin_jobs.schedule", skip(self, payload), err, fields(otel.kind = "producer"))]
+    async fn schedule(
+        &mut self,
+        component: String,
+        payload: Vec<u8>,
+        when: ScheduleTime,
+    ) -> Result<String, Error> {
+        if !self.allowed_components.contains(&component) {
+            return Err(Error::AccessDenied);
+        }
+        self.check(&component, "schedule")?;
+        let run_at = match when {
+            ScheduleTime::DelayMs(ms) => SystemTime::now().checked_add(Duration::from_millis(ms)),
+            ScheduleTime::AtMs(ms) => UNIX_EPOCH.checked_add(Duration::from_millis(ms)),
+        }
+        .ok_or_else(|| Error::Other("the schedule is too far in the future".into()))?;
+        self.queue
+            .schedule(&self.component_id, &component, payload, run_at)
+            .await
+            .map_err(other_error)
+    }
+
+    #[instrument(name = "spin_jobs.list", skip(self), err, fields(otel.kind = "client"))]
+    async fn list(&mut self) -> Result<Vec<Invocation>, Error> {
+        let jobs = self.queue.pending().await.map_err(other_error)?;
+        Ok(jobs
+            .into_iter()
+            .filter(|job| job.scheduled_by.as_ref() == Some(&self.component_id))
+            .filter_map(|job| {
+                let component = job.component?;
+                self.check(&component, "list").ok()?;
+                Some(Invocation {
+                    component,
+                    id: job.id,
+                    payload: job.payload,
+                    run_at_ms: job.run_at_ms,
+                    failed_attempts: job.attempts,
+                })
+            })
+            .collect())
+    }
+
+    #[instrument(name = "spin_jobs.cancel", skip(self), err, fields(otel.kind = "client"))]
+    async fn cancel(&mut self, id: String) -> Result<bool, Error> {
+        let Some(job) = self.queue.get(&id).await.map_err(other_error)? else {
+            return Ok(false);
+        };
+        if job.scheduled_by.as_ref() != Some(&self.component_id) {
+            return Err(Error::AccessDenied);
+        }
+        self.check(job.component.as_deref().unwrap_or_default(), "cancel")?;
+        self.queue.cancel(&id).await.map_err(other_error)
+    }
+}
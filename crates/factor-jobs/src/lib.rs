@@ -0,0 +1,200 @@
+//! A factor providing the `spin:jobs` queue and scheduler interfaces.
+//!
+//! Components enqueue background jobs by name, or schedule one-shot
+//! invocations of other components, and the job trigger runs them by
+//! dispatching them to the components which handle them. The queue is
+//! held in a key-value store of its own, "spin-jobs" unless the runtime
+//! config names another, so it is backed by whatever the store is (e.g. a
+//! local SQLite file or Redis) and survives restarts. No component may open
+//! that store, so components can only reach jobs through the interfaces.
+
+mod host;
+mod queue;
+pub mod runtime_config;
+
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+};
+
+use anyhow::ensure;
+use spin_factor_capability_policy::CapabilityChecker;
+use spin_factor_key_value::{KEY_VALUE_STORES_KEY, KeyValueFactor};
+use spin_factors::{
+    ConfigureAppContext, Factor, FactorData, FactorInstanceBuilder, InitContext, PrepareContext,
+    RuntimeFactors,
+};
+use spin_locked_app::MetadataKey;
+
+pub use host::InstanceState;
+pub use queue::{Failure, Job, JobQueue, RetryPolicy, millis_since_epoch};
+pub use runtime_config::RuntimeConfig;
+pub use spin_world::spin::jobs::queue as v3;
+
+/// Metadata key for the jobs a component may enqueue.
+pub const JOBS_KEY: MetadataKey<Vec<String>> = MetadataKey::new("jobs");
+/// Metadata key for the components a component may schedule invocations of.
+pub const SCHEDULED_COMPONENTS_KEY: MetadataKey<Vec<String>> =
+    MetadataKey::new("scheduled_components");
+
+/// The label of the store holding the queue if the runtime config names none.
+pub const DEFAULT_KEY_VALUE_STORE: &str = "spin-jobs";
+
+/// A factor that provides a background job queue.
+#[derive(Default)]
+pub struct JobsFactor {
+    _priv: (),
+}
+
+impl JobsFactor {
+    /// Create a new JobsFactor.
+    pub fn new() -> Self {
+        Self { _priv: () }
+    }
+}
+
+impl Factor for JobsFactor {
+    type RuntimeConfig = RuntimeConfig;
+    type AppState = AppState;
+    type InstanceBuilder = InstanceBuilder;
+
+    fn init(&mut self, ctx: &mut impl InitContext<Self>) -> anyhow::Result<()> {
+        ctx.link_bindings(spin_world::spin::jobs::queue::add_to_linker::<_, FactorData<Self>>)?;
+        ctx.link_bindings(spin_world::spin::jobs::scheduler::add_to_linker::<_, FactorData<Self>>)?;
+        Ok(())
+    }
+
+    fn configure_app<T: RuntimeFactors>(
+        &self,
+        mut ctx: ConfigureAppContext<T, Self>,
+    ) -> anyhow::Result<Self::AppState> {
+        let label = ctx
+            .take_runtime_config()
+            .map(|config| config.key_value_store)
+            .unwrap_or_else(|| DEFAULT_KEY_VALUE_STORE.to_owned());
+        let store_manager = ctx.app_state::<KeyValueFactor>()?.store_manager();
+        let queue = Arc::new(JobQueue::new(store_manager, label));
+
+        // Build component -> allowed jobs and scheduled components maps
+        let mut component_jobs = HashMap::new();
+        let mut component_scheduled = HashMap::new();
+        for component in ctx.app().components() {
+            let component_id = component.id().to_string();
+            let jobs = component
+                .get_metadata(JOBS_KEY)?
+                .unwrap_or_default()
+                .into_iter()
+                .collect::<HashSet<_>>();
+            let scheduled = component
+                .get_metadata(SCHEDULED_COMPONENTS_KEY)?
+                .unwrap_or_default()
+                .into_iter()
+                .collect::<HashSet<_>>();
+            let key_value_stores = component
+                .get_metadata(KEY_VALUE_STORES_KEY)?
+                .unwrap_or_default();
+            ensure!(
+                !key_value_stores.iter().any(|label| label == queue.label()),
+                "component {component_id:?} uses the key-value store {:?}, which holds the job queue and can't be opened by components",
+                queue.label()
+            );
+            for scheduled_id in &scheduled {
+                ensure!(
+                    ctx.app().get_component(scheduled_id).is_some(),
+                    "component {component_id:?} schedules invocations of unknown component {scheduled_id:?}"
+                );
+            }
+            ensure!(
+                (jobs.is_empty() && scheduled.is_empty()) || queue.is_defined(),
+                "component {component_id:?} uses the job queue, but the key-value store {:?} for jobs is not defined",
+                queue.label()
+            );
+            component_jobs.insert(component_id.clone(), Arc::new(jobs));
+            component_scheduled.insert(component_id, Arc::new(scheduled));
+        }
+
+        Ok(AppState {
+            queue,
+            component_jobs,
+            component_scheduled,
+        })
+    }
+
+    fn prepare<T: RuntimeFactors>(
+        &self,
+        mut ctx: PrepareContext<T, Self>,
+    ) -> anyhow::Result<InstanceBuilder> {
+        let app_state = ctx.app_state();
+        let component_id = ctx.app_component().id().to_string();
+        let allowed_jobs = app_state
+            .component_jobs
+            .get(&component_id)
+            .expect("component should be in component_jobs")
+            .clone();
+        let allowed_components = app_state
+            .component_scheduled
+            .get(&component_id)
+            .expect("component should be in component_scheduled")
+            .clone();
+        let queue = app_state.queue.clone();
+        let capabilities = CapabilityChecker::from_prepare_context(&mut ctx)?;
+        Ok(InstanceBuilder {
+            component_id,
+            queue,
+            allowed_jobs,
+            allowed_components,
+            capabilities,
+        })
+    }
+}
+
+pub struct AppState {
+    /// The app's job queue.
+    queue: Arc<JobQueue>,
+    /// The jobs each component is allowed to enqueue.
+    ///
+    /// This is a map from component ID to the set of job names.
+    component_jobs: HashMap<String, Arc<HashSet<String>>>,
+    /// The components each component is allowed to schedule invocations of.
+    ///
+    /// This is a map from component ID to the set of component IDs.
+    component_scheduled: HashMap<String, Arc<HashSet<String>>>,
+}
+
+impl AppState {
+    /// The app's job queue.
+    pub fn queue(&self) -> Arc<JobQueue> {
+        self.queue.clone()
+    }
+
+    /// Returns true if any component may enqueue jobs or schedule invocations.
+    pub fn is_used(&self) -> bool {
+        self.component_jobs.values().any(|jobs| !jobs.is_empty())
+            || self
+                .component_scheduled
+                .values()
+                .any(|scheduled| !scheduled.is_empty())
+    }
+}
+
+pub struct InstanceBuilder {
+    component_id: String,
+    queue: Arc<JobQueue>,
+    allowed_jobs: Arc<HashSet<String>>,
+    allowed_components: Arc<HashSet<String>>,
+    capabilities: CapabilityChecker,
+}
+
+impl FactorInstanceBuilder for InstanceBuilder {
+    type InstanceState = InstanceState;
+
+    fn build(self) -> anyhow::Result<Self::InstanceState> {
+        Ok(InstanceState::new(
+            self.component_id,
+            self.queue,
+            self.allowed_jobs,
+            self.allowed_components,
+            self.capabilities,
+        ))
+    }
+}
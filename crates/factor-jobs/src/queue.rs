@@ -0,0 +1,334 @@
+use std::{
+    collections::HashMap,
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::Context as _;
+use serde::{Deserialize, Serialize};
+use spin_factor_key_value::{Store, StoreManager};
+use spin_world::MAX_HOST_BUFFERED_BYTES;
+
+use crate::v3::EnqueueOptions;
+
+/// The prefix of the keys of jobs waiting to be run.
+const PENDING_PREFIX: &str = "spin-jobs:pending:";
+/// The prefix of the keys of jobs which ran out of attempts.
+const DEAD_PREFIX: &str = "spin-jobs:dead:";
+
+/// A queue of jobs, held in a key-value store.
+///
+/// The store is dedicated to the queue: [`JobsFactor`](crate::JobsFactor)
+/// refuses apps whose components may open it. The queue does not lock jobs,
+/// so it should be served by a single job trigger at a time.
+pub struct JobQueue {
+    store_manager: Arc<dyn StoreManager>,
+    label: String,
+}
+
+impl JobQueue {
+    /// Creates a queue held in the store with the given label.
+    pub fn new(store_manager: Arc<dyn StoreManager>, label: impl Into<String>) -> Self {
+        Self {
+            store_manager,
+            label: label.into(),
+        }
+    }
+
+    /// The label of the store holding the queue.
+    pub fn label(&self) -> &str {
+        &self.label
+    }
+
+    /// Returns true if the store holding the queue is defined.
+    pub fn is_defined(&self) -> bool {
+        self.store_manager.is_defined(&self.label)
+    }
+
+    async fn store(&self) -> anyhow::Result<Arc<dyn Store>> {
+        self.store_manager
+            .get(&self.label)
+            .await
+            .with_context(|| format!("failed to open key-value store {:?} for jobs", self.label))
+    }
+
+    /// Enqueues a job, returning its ID.
+    pub async fn enqueue(
+        &self,
+        name: &str,
+        payload: Vec<u8>,
+        options: EnqueueOptions,
+    ) -> anyhow::Result<String> {
+        let delay = Duration::from_millis(options.delay_ms.unwrap_or_default());
+        let job = Job {
+            max_attempts: options.max_attempts,
+            backoff_ms: options.backoff_ms,
+            ..Job::new(name.to_owned(), payload, millis_from_now(delay))
+        };
+        self.put(PENDING_PREFIX, &job).await?;
+        Ok(job.id)
+    }
+
+    /// Schedules a one-shot invocation of `component`, returning its ID.
+    ///
+    /// The invocation is run as a job with an empty name at `run_at`.
+    pub async fn schedule(
+        &self,
+        scheduled_by: &str,
+        component: &str,
+        payload: Vec<u8>,
+        run_at: SystemTime,
+    ) -> anyhow::Result<String> {
+        let job = Job {
+            component: Some(component.to_owned()),
+            scheduled_by: Some(scheduled_by.to_owned()),
+            ..Job::new(String::new(), payload, millis_since_epoch(run_at))
+        };
+        self.put(PENDING_PREFIX, &job).await?;
+        Ok(job.id)
+    }
+
+    /// Returns the jobs waiting to be run, soonest first.
+    pub async fn pending(&self) -> anyhow::Result<Vec<Job>> {
+        let mut jobs = self.list(PENDING_PREFIX).await?;
+        jobs.sort_by_key(|job| job.run_at_ms);
+        Ok(jobs)
+    }
+
+    /// Returns the pending job with the given ID, if there is one.
+    pub async fn get(&self, id: &str) -> anyhow::Result<Option<Job>> {
+        let Some(value) = self
+            .store()
+            .await?
+            .get(&key(PENDING_PREFIX, id), MAX_HOST_BUFFERED_BYTES)
+            .await?
+        else {
+            return Ok(None);
+        };
+        Ok(Some(
+            serde_json::from_slice(&value).with_context(|| format!("job {id} is malformed"))?,
+        ))
+    }
+
+    /// Removes the pending job with the given ID, returning whether there was
+    /// one.
+    pub async fn cancel(&self, id: &str) -> anyhow::Result<bool> {
+        let store = self.store().await?;
+        let key = key(PENDING_PREFIX, id);
+        if !store.exists(&key).await? {
+            return Ok(false);
+        }
+        store
+            .delete(&key)
+            .await
+            .with_context(|| format!("failed to remove job {id}"))?;
+        Ok(true)
+    }
+
+    /// Returns the pending jobs which are due to run at `now`.
+    pub async fn due(&self, now: SystemTime) -> anyhow::Result<Vec<Job>> {
+        let now = millis_since_epoch(now);
+        let mut jobs = self.pending().await?;
+        jobs.retain(|job| job.run_at_ms <= now);
+        Ok(jobs)
+    }
+
+    /// Removes a job which ran successfully.
+    pub async fn complete(&self, job: &Job) -> anyhow::Result<()> {
+        self.store()
+            .await?
+            .delete(&key(PENDING_PREFIX, &job.id))
+            .await
+            .with_context(|| format!("failed to remove job {}", job.id))
+    }
+
+    /// Records a failed attempt at running a job, scheduling it to be retried
+    /// or, once it has run out of attempts, moving it to dead-letter storage.
+    /// A job which was cancelled while it ran is left removed.
+    pub async fn fail(
+        &self,
+        mut job: Job,
+        error: String,
+        policy: &RetryPolicy,
+    ) -> anyhow::Result<Failure> {
+        if !self
+            .store()
+            .await?
+            .exists(&key(PENDING_PREFIX, &job.id))
+            .await?
+        {
+            return Ok(Failure::Cancelled);
+        }
+        job.attempts += 1;
+        job.last_error = Some(error);
+        let max_attempts = job.max_attempts.unwrap_or(policy.max_attempts);
+        if job.attempts >= max_attempts {
+            self.put(DEAD_PREFIX, &job).await?;
+            self.complete(&job).await?;
+            return Ok(Failure::DeadLettered);
+        }
+        let backoff = job
+            .backoff_ms
+            .map(Duration::from_millis)
+            .unwrap_or(policy.backoff);
+        let after = backoff_after(backoff, job.attempts);
+        job.run_at_ms = millis_from_now(after);
+        self.put(PENDING_PREFIX, &job).await?;
+        Ok(Failure::Retry { after })
+    }
+
+    /// Returns the jobs which ran out of attempts.
+    pub async fn dead_letters(&self) -> anyhow::Result<Vec<Job>> {
+        self.list(DEAD_PREFIX).await
+    }
+
+    async fn put(&self, prefix: &str, job: &Job) -> anyhow::Result<()> {
+        let value = serde_json::to_vec(job)?;
+        self.store()
+            .await?
+            .set(&key(prefix, &job.id), &value)
+            .await
+            .with_context(|| format!("failed to store job {}", job.id))
+    }
+
+    async fn list(&self, prefix: &str) -> anyhow::Result<Vec<Job>> {
+        let store = self.store().await?;
+        let keys = store
+            .get_keys(MAX_HOST_BUFFERED_BYTES)
+            .await
+            .context("failed to list jobs")?;
+        let mut jobs = vec![];
+        for key in keys.iter().filter(|key| key.starts_with(prefix)) {
+            // The job may have been removed since the keys were listed.
+            let Some(value) = store.get(key, MAX_HOST_BUFFERED_BYTES).await? else {
+                continue;
+            };
+            match serde_json::from_slice(&value) {
+                Ok(job) => jobs.push(job),
+                Err(err) => tracing::warn!("Ignoring malformed job {key:?}: {err}"),
+            }
+        }
+        Ok(jobs)
+    }
+}
+
+fn key(prefix: &str, id: &str) -> String {
+    format!("{prefix}{id}")
+}
+
+/// Returns a time as milliseconds since the Unix epoch.
+pub fn millis_since_epoch(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis()
+        .try_into()
+        .unwrap_or(u64::MAX)
+}
+
+fn millis_from_now(wait: Duration) -> u64 {
+    let wait = wait.as_millis().try_into().unwrap_or(u64::MAX);
+    millis_since_epoch(SystemTime::now()).saturating_add(wait)
+}
+
+/// The wait before retrying a job which has failed `attempts` times: the
+/// backoff, doubled for each failure after the first.
+fn backoff_after(backoff: Duration, attempts: u32) -> Duration {
+    backoff.saturating_mul(1 << attempts.saturating_sub(1).min(20))
+}
+
+/// A job in the queue.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Job {
+    /// The ID returned when the job was enqueued.
+    pub id: String,
+    /// The name of the job, which determines the components that handle it.
+    /// Scheduled invocations have an empty name.
+    pub name: String,
+    /// For a scheduled invocation, the component to invoke.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub component: Option<String>,
+    /// For a scheduled invocation, the component which scheduled it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scheduled_by: Option<String>,
+    /// The payload the job was enqueued with.
+    pub payload: Vec<u8>,
+    /// The number of failed attempts at running the job.
+    pub attempts: u32,
+    /// The number of attempts to make, if given when the job was enqueued.
+    pub max_attempts: Option<u32>,
+    /// The backoff after the first failure, if given when the job was enqueued.
+    pub backoff_ms: Option<u64>,
+    /// When the job is next due to run, in milliseconds since the Unix epoch.
+    pub run_at_ms: u64,
+    /// The error from the last failed attempt.
+    pub last_error: Option<String>,
+    /// The trace context of the span which enqueued the job, so that running
+    /// it continues the trace.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub trace_context: HashMap<String, String>,
+}
+
+impl Job {
+    fn new(name: String, payload: Vec<u8>, run_at_ms: u64) -> Self {
+        let mut trace_context = HashMap::new();
+        spin_telemetry::inject_message_trace_context(&mut trace_context);
+        Self {
+            id: format!("{:032x}", rand::random::<u128>()),
+            name,
+            component: None,
+            scheduled_by: None,
+            payload,
+            attempts: 0,
+            max_attempts: None,
+            backoff_ms: None,
+            run_at_ms,
+            last_error: None,
+            trace_context,
+        }
+    }
+}
+
+/// How failed jobs are retried, unless they were enqueued with their own
+/// options.
+#[derive(Clone, Debug)]
+pub struct RetryPolicy {
+    /// The number of attempts to make before dead-lettering a job.
+    pub max_attempts: u32,
+    /// The wait before retrying a job after its first failure, which doubles
+    /// after each further failure.
+    pub backoff: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            backoff: Duration::from_secs(1),
+        }
+    }
+}
+
+/// What became of a job after a failed attempt.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Failure {
+    /// The job will be retried after the given wait.
+    Retry { after: Duration },
+    /// The job ran out of attempts, and was moved to dead-letter storage.
+    DeadLettered,
+    /// The job was cancelled while it ran, so it is not retried.
+    Cancelled,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn backoff_doubles_after_each_failure() {
+        let backoff = Duration::from_millis(100);
+        assert_eq!(Duration::from_millis(100), backoff_after(backoff, 1));
+        assert_eq!(Duration::from_millis(200), backoff_after(backoff, 2));
+        assert_eq!(Duration::from_millis(800), backoff_after(backoff, 4));
+        assert_eq!(Duration::MAX, backoff_after(Duration::MAX, 100));
+    }
+}
@@ -0,0 +1,25 @@
+use serde::Deserialize;
+use spin_factors::runtime_config::toml::GetTomlValue;
+
+/// Runtime configuration for the job queue.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RuntimeConfig {
+    /// The label of the key-value store holding the queue. No component may
+    /// list it in `key_value_stores`.
+    pub key_value_store: String,
+}
+
+/// Get the runtime configuration for the job queue from a TOML table.
+///
+/// Expects table to be in the format:
+/// ```toml
+/// [jobs]
+/// key_value_store = "jobs"
+/// ```
+pub fn config_from_table(table: &impl GetTomlValue) -> anyhow::Result<Option<RuntimeConfig>> {
+    let Some(config) = table.get("jobs") else {
+        return Ok(None);
+    };
+    Ok(Some(config.clone().try_into()?))
+}
@@ -0,0 +1,356 @@
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+use anyhow::bail;
+use spin_factor_capability_policy::{CapabilityPolicyFactor, runtime_config::config_from_table};
+use spin_factor_jobs::{DEFAULT_KEY_VALUE_STORE, Failure, JobsFactor, RetryPolicy, v3};
+use spin_factor_key_value::runtime_config::spin::MakeKeyValueStore;
+use spin_factor_key_value::{KeyValueFactor, RuntimeConfig};
+use spin_factors::RuntimeFactors;
+use spin_factors_test::{TestEnvironment, toml};
+use spin_key_value_spin::{SpinKeyValueRuntimeConfig, SpinKeyValueStore};
+use spin_world::spin::jobs::scheduler::{Host as _, ScheduleTime};
+use v3::Host as _;
+
+#[derive(RuntimeFactors)]
+struct TestFactors {
+    key_value: KeyValueFactor,
+    jobs: JobsFactor,
+}
+
+impl From<RuntimeConfig> for TestFactorsRuntimeConfig {
+    fn from(value: RuntimeConfig) -> Self {
+        Self {
+            key_value: Some(value),
+            jobs: None,
+        }
+    }
+}
+
+fn factors() -> TestFactors {
+    TestFactors {
+        key_value: KeyValueFactor::new(),
+        jobs: JobsFactor::new(),
+    }
+}
+
+fn in_memory_store() -> anyhow::Result<RuntimeConfig> {
+    let store_manager =
+        Arc::new(SpinKeyValueStore::new(None).make_store(SpinKeyValueRuntimeConfig::new(None))?);
+    let mut runtime_config = RuntimeConfig::default();
+    runtime_config.add_store_manager(DEFAULT_KEY_VALUE_STORE.into(), store_manager);
+    Ok(runtime_config)
+}
+
+fn options() -> v3::EnqueueOptions {
+    v3::EnqueueOptions {
+        delay_ms: None,
+        max_attempts: None,
+        backoff_ms: None,
+    }
+}
+
+#[tokio::test]
+async fn enqueues_allowed_jobs() -> anyhow::Result<()> {
+    let env = TestEnvironment::new(factors()).extend_manifest(toml! {
+        [component.test-component]
+        source = "does-not-exist.wasm"
+        jobs = ["send-email"]
+    });
+    let mut state = env
+        .runtime_config(in_memory_store()?)?
+        .build_instance_state()
+        .await?;
+
+    let id = state
+        .jobs
+        .enqueue("send-email".into(), b"hello".to_vec(), options())
+        .await
+        .unwrap();
+    assert!(matches!(
+        state
+            .jobs
+            .enqueue("delete-account".into(), vec![], options())
+            .await,
+        Err(v3::Error::AccessDenied)
+    ));
+
+    let queue = state.jobs.queue();
+    let due = queue.due(SystemTime::now()).await?;
+    assert_eq!(1, due.len());
+    assert_eq!(id, due[0].id);
+    assert_eq!("send-email", due[0].name);
+    assert_eq!(b"hello", &due[0].payload[..]);
+
+    queue.complete(&due[0]).await?;
+    assert!(queue.due(SystemTime::now()).await?.is_empty());
+    Ok(())
+}
+
+#[tokio::test]
+async fn failed_jobs_are_retried_then_dead_lettered() -> anyhow::Result<()> {
+    let env = TestEnvironment::new(factors()).extend_manifest(toml! {
+        [component.test-component]
+        source = "does-not-exist.wasm"
+        jobs = ["send-email"]
+    });
+    let mut state = env
+        .runtime_config(in_memory_store()?)?
+        .build_instance_state()
+        .await?;
+    let delayed = v3::EnqueueOptions {
+        delay_ms: Some(60_000),
+        ..options()
+    };
+    state
+        .jobs
+        .enqueue("send-email".into(), vec![], delayed)
+        .await
+        .unwrap();
+    let retried = v3::EnqueueOptions {
+        max_attempts: Some(2),
+        backoff_ms: Some(500),
+        ..options()
+    };
+    state
+        .jobs
+        .enqueue("send-email".into(), vec![], retried)
+        .await
+        .unwrap();
+
+    let queue = state.jobs.queue();
+    let policy = RetryPolicy::default();
+    let mut due = queue.due(SystemTime::now()).await?;
+    assert_eq!(1, due.len(), "delayed job should not be due");
+    let job = due.remove(0);
+    assert_eq!(
+        Failure::Retry {
+            after: Duration::from_millis(500)
+        },
+        queue.fail(job, "smtp timeout".into(), &policy).await?
+    );
+
+    let later = SystemTime::now() + Duration::from_secs(1);
+    let mut due = queue.due(later).await?;
+    assert_eq!(1, due.len());
+    let job = due.remove(0);
+    assert_eq!(1, job.attempts);
+    assert_eq!(
+        Failure::DeadLettered,
+        queue.fail(job, "smtp timeout".into(), &policy).await?
+    );
+
+    assert!(queue.due(later).await?.is_empty());
+    let dead = queue.dead_letters().await?;
+    assert_eq!(1, dead.len());
+    assert_eq!(2, dead[0].attempts);
+    assert_eq!(Some("smtp timeout"), dead[0].last_error.as_deref());
+    Ok(())
+}
+
+#[tokio::test]
+async fn jobs_cancelled_while_running_are_not_retried() -> anyhow::Result<()> {
+    let env = TestEnvironment::new(factors()).extend_manifest(toml! {
+        [component.test-component]
+        source = "does-not-exist.wasm"
+        jobs = ["send-email"]
+    });
+    let mut state = env
+        .runtime_config(in_memory_store()?)?
+        .build_instance_state()
+        .await?;
+    let id = state
+        .jobs
+        .enqueue("send-email".into(), vec![], options())
+        .await
+        .unwrap();
+
+    let queue = state.jobs.queue();
+    let mut due = queue.due(SystemTime::now()).await?;
+    let job = due.remove(0);
+    assert!(queue.cancel(&id).await?);
+    assert_eq!(
+        Failure::Cancelled,
+        queue
+            .fail(job, "smtp timeout".into(), &RetryPolicy::default())
+            .await?
+    );
+
+    let later = SystemTime::now() + Duration::from_secs(60);
+    assert!(queue.due(later).await?.is_empty());
+    assert!(queue.dead_letters().await?.is_empty());
+    Ok(())
+}
+
+#[tokio::test]
+async fn schedules_and_cancels_invocations() -> anyhow::Result<()> {
+    let env = TestEnvironment::new(factors()).extend_manifest(toml! {
+        [component.test-component]
+        source = "does-not-exist.wasm"
+        scheduled_components = ["reminder-sender"]
+
+        [component.reminder-sender]
+        source = "does-not-exist.wasm"
+    });
+    let mut state = env
+        .runtime_config(in_memory_store()?)?
+        .build_instance_state()
+        .await?;
+    let jobs = &mut state.jobs;
+
+    let soon = jobs
+        .schedule(
+            "reminder-sender".into(),
+            b"remind".to_vec(),
+            ScheduleTime::DelayMs(0),
+        )
+        .await
+        .unwrap();
+    let later = jobs
+        .schedule(
+            "reminder-sender".into(),
+            vec![],
+            ScheduleTime::DelayMs(2 * 60 * 60 * 1000),
+        )
+        .await
+        .unwrap();
+    assert!(matches!(
+        jobs.schedule("test-component".into(), vec![], ScheduleTime::DelayMs(0))
+            .await,
+        Err(v3::Error::AccessDenied)
+    ));
+
+    let invocations = jobs.list().await.unwrap();
+    let ids = invocations.iter().map(|i| &i.id).collect::<Vec<_>>();
+    assert_eq!(vec![&soon, &later], ids);
+    assert_eq!("reminder-sender", invocations[0].component);
+
+    let due = jobs.queue().due(SystemTime::now()).await?;
+    assert_eq!(1, due.len());
+    assert_eq!("", due[0].name);
+    assert_eq!(Some("reminder-sender"), due[0].component.as_deref());
+    assert_eq!(Some("test-component"), due[0].scheduled_by.as_deref());
+
+    assert!(jobs.cancel(later.clone()).await.unwrap());
+    assert!(!jobs.cancel(later).await.unwrap());
+    assert_eq!(1, jobs.list().await.unwrap().len());
+    Ok(())
+}
+
+#[tokio::test]
+async fn errors_when_scheduled_component_is_unknown() -> anyhow::Result<()> {
+    let env = TestEnvironment::new(factors()).extend_manifest(toml! {
+        [component.test-component]
+        source = "does-not-exist.wasm"
+        scheduled_components = ["reminder-sender"]
+    });
+    let Err(err) = env
+        .runtime_config(in_memory_store()?)?
+        .build_instance_state()
+        .await
+    else {
+        bail!("expected instance build to fail but it didn't");
+    };
+
+    assert!(
+        err.to_string()
+            .contains(r#"schedules invocations of unknown component "reminder-sender""#)
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn errors_when_jobs_store_is_not_defined() -> anyhow::Result<()> {
+    let env = TestEnvironment::new(factors()).extend_manifest(toml! {
+        [component.test-component]
+        source = "does-not-exist.wasm"
+        jobs = ["send-email"]
+    });
+    let Err(err) = env
+        .runtime_config(RuntimeConfig::default())?
+        .build_instance_state()
+        .await
+    else {
+        bail!("expected instance build to fail but it didn't");
+    };
+
+    assert!(
+        err.to_string()
+            .contains(r#"the key-value store "spin-jobs" for jobs is not defined"#)
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn errors_when_component_uses_jobs_store() -> anyhow::Result<()> {
+    let env = TestEnvironment::new(factors()).extend_manifest(toml! {
+        [component.test-component]
+        source = "does-not-exist.wasm"
+        key_value_stores = ["spin-jobs"]
+    });
+    let Err(err) = env
+        .runtime_config(in_memory_store()?)?
+        .build_instance_state()
+        .await
+    else {
+        bail!("expected instance build to fail but it didn't");
+    };
+
+    assert!(
+        err.to_string()
+            .contains(r#"uses the key-value store "spin-jobs", which holds the job queue"#)
+    );
+    Ok(())
+}
+
+#[derive(RuntimeFactors)]
+struct PolicyTestFactors {
+    capability_policy: CapabilityPolicyFactor,
+    key_value: KeyValueFactor,
+    jobs: JobsFactor,
+}
+
+#[tokio::test]
+async fn capability_policy_denies_jobs() -> anyhow::Result<()> {
+    let env = TestEnvironment::new(PolicyTestFactors {
+        capability_policy: CapabilityPolicyFactor::new(),
+        key_value: KeyValueFactor::new(),
+        jobs: JobsFactor::new(),
+    })
+    .extend_manifest(toml! {
+        [component.test-component]
+        source = "does-not-exist.wasm"
+        jobs = ["send-email"]
+        scheduled_components = ["reminder-sender"]
+
+        [component.reminder-sender]
+        source = "does-not-exist.wasm"
+    })
+    .runtime_config(PolicyTestFactorsRuntimeConfig {
+        capability_policy: config_from_table(&toml! {
+            [[capability_policy]]
+            effect = "deny"
+            operations = ["*"]
+        })?,
+        key_value: Some(in_memory_store()?),
+        jobs: None,
+    })?;
+    let mut state = env.build_instance_state().await?;
+
+    assert!(matches!(
+        state
+            .jobs
+            .enqueue("send-email".into(), vec![], options())
+            .await,
+        Err(v3::Error::AccessDenied)
+    ));
+    assert!(matches!(
+        state
+            .jobs
+            .schedule("reminder-sender".into(), vec![], ScheduleTime::DelayMs(0))
+            .await,
+        Err(v3::Error::AccessDenied)
+    ));
+    assert!(state.jobs.queue().due(SystemTime::now()).await?.is_empty());
+    Ok(())
+}
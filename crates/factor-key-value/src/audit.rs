@@ -0,0 +1,163 @@
+use crate::{Cas, Error, Store, StoreManager};
+use spin_core::async_trait;
+use spin_telemetry::audit;
+use spin_world::spin::key_value::key_value as v3;
+use std::{any::Any, sync::Arc};
+
+/// A [`StoreManager`] which records each access to its stores in the audit log.
+pub struct AuditingStoreManager {
+    inner: Arc<dyn StoreManager>,
+}
+
+impl AuditingStoreManager {
+    pub fn new(inner: Arc<dyn StoreManager>) -> Self {
+        Self { inner }
+    }
+}
+
+#[async_trait]
+impl StoreManager for AuditingStoreManager {
+    async fn get(&self, name: &str) -> Result<Arc<dyn Store>, Error> {
+        audit::event!("key_value.open", store = name);
+        let inner = self.inner.get(name).await?;
+        Ok(Arc::new(AuditingStore {
+            name: name.to_owned(),
+            inner,
+        }))
+    }
+
+    fn is_defined(&self, store_name: &str) -> bool {
+        self.inner.is_defined(store_name)
+    }
+
+    fn summary(&self, store_name: &str) -> Option<String> {
+        self.inner.summary(store_name)
+    }
+
+    fn metadata(&self) -> Arc<dyn Any> {
+        self.inner.metadata()
+    }
+}
+
+struct AuditingStore {
+    name: String,
+    inner: Arc<dyn Store>,
+}
+
+#[async_trait]
+impl Store for AuditingStore {
+    async fn after_open(&self) -> Result<(), Error> {
+        self.inner.after_open().await
+    }
+
+    async fn get(&self, key: &str, max_result_bytes: usize) -> Result<Option<Vec<u8>>, Error> {
+        audit::event!("key_value.get", store = self.name.as_str(), key = key);
+        self.inner.get(key, max_result_bytes).await
+    }
+
+    async fn set(&self, key: &str, value: &[u8]) -> Result<(), Error> {
+        audit::event!("key_value.set", store = self.name.as_str(), key = key);
+        self.inner.set(key, value).await
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), Error> {
+        audit::event!("key_value.delete", store = self.name.as_str(), key = key);
+        self.inner.delete(key).await
+    }
+
+    async fn exists(&self, key: &str) -> Result<bool, Error> {
+        audit::event!("key_value.exists", store = self.name.as_str(), key = key);
+        self.inner.exists(key).await
+    }
+
+    async fn get_keys(&self, max_result_bytes: usize) -> Result<Vec<String>, Error> {
+        audit::event!("key_value.get_keys", store = self.name.as_str());
+        self.inner.get_keys(max_result_bytes).await
+    }
+
+    async fn get_keys_after(
+        &self,
+        after: &str,
+        limit: usize,
+        max_result_bytes: usize,
+    ) -> Result<Vec<String>, Error> {
+        audit::event!("key_value.get_keys", store = self.name.as_str());
+        self.inner
+            .get_keys_after(after, limit, max_result_bytes)
+            .await
+    }
+
+    fn lists_keys_in_order(&self) -> bool {
+        self.inner.lists_keys_in_order()
+    }
+
+    async fn get_keys_async(
+        &self,
+        max_result_bytes: usize,
+    ) -> (
+        tokio::sync::mpsc::Receiver<String>,
+        tokio::sync::oneshot::Receiver<Result<(), v3::Error>>,
+    ) {
+        audit::event!("key_value.get_keys", store = self.name.as_str());
+        self.inner.get_keys_async(max_result_bytes).await
+    }
+
+    async fn get_many(
+        &self,
+        keys: Vec<String>,
+        max_result_bytes: usize,
+    ) -> Result<Vec<(String, Option<Vec<u8>>)>, Error> {
+        for key in &keys {
+            audit::event!(
+                "key_value.get",
+                store = self.name.as_str(),
+                key = key.as_str()
+            );
+        }
+        self.inner.get_many(keys, max_result_bytes).await
+    }
+
+    async fn set_many(&self, key_values: Vec<(String, Vec<u8>)>) -> Result<(), Error> {
+        for (key, _) in &key_values {
+            audit::event!(
+                "key_value.set",
+                store = self.name.as_str(),
+                key = key.as_str()
+            );
+        }
+        self.inner.set_many(key_values).await
+    }
+
+    async fn delete_many(&self, keys: Vec<String>) -> Result<(), Error> {
+        for key in &keys {
+            audit::event!(
+                "key_value.delete",
+                store = self.name.as_str(),
+                key = key.as_str()
+            );
+        }
+        self.inner.delete_many(keys).await
+    }
+
+    async fn increment(&self, key: String, delta: i64) -> Result<i64, Error> {
+        audit::event!(
+            "key_value.increment",
+            store = self.name.as_str(),
+            key = key.as_str()
+        );
+        self.inner.increment(key, delta).await
+    }
+
+    async fn new_compare_and_swap(
+        &self,
+        bucket_rep: u32,
+        key: &str,
+    ) -> Result<Arc<dyn Cas>, Error> {
+        audit::event!(
+            "key_value.compare_and_swap",
+            store = self.name.as_str(),
+            key = key
+        );
+        self.inner.new_compare_and_swap(bucket_rep, key).await
+    }
+}
@@ -0,0 +1,157 @@
+use crate::{Cas, Error, Store, StoreManager};
+use spin_core::async_trait;
+use spin_factor_capability_policy::{CapabilityChecker, operation};
+use spin_world::spin::key_value::key_value as v3;
+use std::{any::Any, sync::Arc};
+
+/// A [`StoreManager`] which checks each call to its stores against the
+/// capability policy, as well as the opening of the store, since the policy
+/// may only allow a store during certain hours.
+pub struct CapabilityCheckingStoreManager {
+    inner: Arc<dyn StoreManager>,
+    capabilities: CapabilityChecker,
+}
+
+impl CapabilityCheckingStoreManager {
+    pub fn new(inner: Arc<dyn StoreManager>, capabilities: CapabilityChecker) -> Self {
+        Self {
+            inner,
+            capabilities,
+        }
+    }
+}
+
+#[async_trait]
+impl StoreManager for CapabilityCheckingStoreManager {
+    async fn get(&self, name: &str) -> Result<Arc<dyn Store>, Error> {
+        let inner = self.inner.get(name).await?;
+        Ok(Arc::new(CapabilityCheckingStore {
+            name: name.to_owned(),
+            inner,
+            capabilities: self.capabilities.clone(),
+        }))
+    }
+
+    fn is_defined(&self, store_name: &str) -> bool {
+        self.inner.is_defined(store_name)
+    }
+
+    fn summary(&self, store_name: &str) -> Option<String> {
+        self.inner.summary(store_name)
+    }
+
+    fn metadata(&self) -> Arc<dyn Any> {
+        self.inner.metadata()
+    }
+}
+
+struct CapabilityCheckingStore {
+    name: String,
+    inner: Arc<dyn Store>,
+    capabilities: CapabilityChecker,
+}
+
+impl CapabilityCheckingStore {
+    fn check(&self) -> Result<(), Error> {
+        self.capabilities
+            .check(operation::KEY_VALUE, &self.name, None)
+            .map_err(|_| Error::AccessDenied)
+    }
+}
+
+#[async_trait]
+impl Store for CapabilityCheckingStore {
+    async fn after_open(&self) -> Result<(), Error> {
+        self.inner.after_open().await
+    }
+
+    async fn get(&self, key: &str, max_result_bytes: usize) -> Result<Option<Vec<u8>>, Error> {
+        self.check()?;
+        self.inner.get(key, max_result_bytes).await
+    }
+
+    async fn set(&self, key: &str, value: &[u8]) -> Result<(), Error> {
+        self.check()?;
+        self.inner.set(key, value).await
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), Error> {
+        self.check()?;
+        self.inner.delete(key).await
+    }
+
+    async fn exists(&self, key: &str) -> Result<bool, Error> {
+        self.check()?;
+        self.inner.exists(key).await
+    }
+
+    async fn get_keys(&self, max_result_bytes: usize) -> Result<Vec<String>, Error> {
+        self.check()?;
+        self.inner.get_keys(max_result_bytes).await
+    }
+
+    async fn get_keys_after(
+        &self,
+        after: &str,
+        limit: usize,
+        max_result_bytes: usize,
+    ) -> Result<Vec<String>, Error> {
+        self.check()?;
+        self.inner
+            .get_keys_after(after, limit, max_result_bytes)
+            .await
+    }
+
+    fn lists_keys_in_order(&self) -> bool {
+        self.inner.lists_keys_in_order()
+    }
+
+    async fn get_keys_async(
+        &self,
+        max_result_bytes: usize,
+    ) -> (
+        tokio::sync::mpsc::Receiver<String>,
+        tokio::sync::oneshot::Receiver<Result<(), v3::Error>>,
+    ) {
+        if let Err(err) = self.check() {
+            let (_, keys_rx) = tokio::sync::mpsc::channel(1);
+            let (err_tx, err_rx) = tokio::sync::oneshot::channel();
+            _ = err_tx.send(Err(crate::to_v3_err(err)));
+            return (keys_rx, err_rx);
+        }
+        self.inner.get_keys_async(max_result_bytes).await
+    }
+
+    async fn get_many(
+        &self,
+        keys: Vec<String>,
+        max_result_bytes: usize,
+    ) -> Result<Vec<(String, Option<Vec<u8>>)>, Error> {
+        self.check()?;
+        self.inner.get_many(keys, max_result_bytes).await
+    }
+
+    async fn set_many(&self, key_values: Vec<(String, Vec<u8>)>) -> Result<(), Error> {
+        self.check()?;
+        self.inner.set_many(key_values).await
+    }
+
+    async fn delete_many(&self, keys: Vec<String>) -> Result<(), Error> {
+        self.check()?;
+        self.inner.delete_many(keys).await
+    }
+
+    async fn increment(&self, key: String, delta: i64) -> Result<i64, Error> {
+        self.check()?;
+        self.inner.increment(key, delta).await
+    }
+
+    async fn new_compare_and_swap(
+        &self,
+        bucket_rep: u32,
+        key: &str,
+    ) -> Result<Arc<dyn Cas>, Error> {
+        self.check()?;
+        self.inner.new_compare_and_swap(bucket_rep, key).await
+    }
+}
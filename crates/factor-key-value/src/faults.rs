@@ -0,0 +1,159 @@
+use crate::{Cas, Error, Store, StoreManager};
+use spin_core::async_trait;
+use spin_fault_injection::FaultInjector;
+use spin_world::spin::key_value::key_value as v3;
+use std::{any::Any, sync::Arc};
+
+/// A [`StoreManager`] which delays and fails calls to its stores as a
+/// [`FaultInjector`] directs.
+pub struct FaultInjectingStoreManager {
+    inner: Arc<dyn StoreManager>,
+    faults: Arc<FaultInjector>,
+}
+
+impl FaultInjectingStoreManager {
+    pub fn new(inner: Arc<dyn StoreManager>, faults: Arc<FaultInjector>) -> Self {
+        Self { inner, faults }
+    }
+}
+
+async fn inject(faults: &FaultInjector, operation: &str, store: &str) -> Result<(), Error> {
+    faults
+        .inject(&format!("key_value.{operation}"), store)
+        .await
+        .map_err(Error::Other)
+}
+
+#[async_trait]
+impl StoreManager for FaultInjectingStoreManager {
+    async fn get(&self, name: &str) -> Result<Arc<dyn Store>, Error> {
+        inject(&self.faults, "open", name).await?;
+        let inner = self.inner.get(name).await?;
+        Ok(Arc::new(FaultInjectingStore {
+            name: name.to_owned(),
+            inner,
+            faults: self.faults.clone(),
+        }))
+    }
+
+    fn is_defined(&self, store_name: &str) -> bool {
+        self.inner.is_defined(store_name)
+    }
+
+    fn summary(&self, store_name: &str) -> Option<String> {
+        self.inner.summary(store_name)
+    }
+
+    fn metadata(&self) -> Arc<dyn Any> {
+        self.inner.metadata()
+    }
+}
+
+struct FaultInjectingStore {
+    name: String,
+    inner: Arc<dyn Store>,
+    faults: Arc<FaultInjector>,
+}
+
+impl FaultInjectingStore {
+    async fn inject(&self, operation: &str) -> Result<(), Error> {
+        inject(&self.faults, operation, &self.name).await
+    }
+}
+
+#[async_trait]
+impl Store for FaultInjectingStore {
+    async fn after_open(&self) -> Result<(), Error> {
+        self.inner.after_open().await
+    }
+
+    async fn get(&self, key: &str, max_result_bytes: usize) -> Result<Option<Vec<u8>>, Error> {
+        self.inject("get").await?;
+        self.inner.get(key, max_result_bytes).await
+    }
+
+    async fn set(&self, key: &str, value: &[u8]) -> Result<(), Error> {
+        self.inject("set").await?;
+        self.inner.set(key, value).await
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), Error> {
+        self.inject("delete").await?;
+        self.inner.delete(key).await
+    }
+
+    async fn exists(&self, key: &str) -> Result<bool, Error> {
+        self.inject("exists").await?;
+        self.inner.exists(key).await
+    }
+
+    async fn get_keys(&self, max_result_bytes: usize) -> Result<Vec<String>, Error> {
+        self.inject("get_keys").await?;
+        self.inner.get_keys(max_result_bytes).await
+    }
+
+    async fn get_keys_after(
+        &self,
+        after: &str,
+        limit: usize,
+        max_result_bytes: usize,
+    ) -> Result<Vec<String>, Error> {
+        self.inject("get_keys").await?;
+        self.inner
+            .get_keys_after(after, limit, max_result_bytes)
+            .await
+    }
+
+    fn lists_keys_in_order(&self) -> bool {
+        self.inner.lists_keys_in_order()
+    }
+
+    async fn get_keys_async(
+        &self,
+        max_result_bytes: usize,
+    ) -> (
+        tokio::sync::mpsc::Receiver<String>,
+        tokio::sync::oneshot::Receiver<Result<(), v3::Error>>,
+    ) {
+        if let Err(err) = self.inject("get_keys").await {
+            let (_, keys_rx) = tokio::sync::mpsc::channel(1);
+            let (err_tx, err_rx) = tokio::sync::oneshot::channel();
+            _ = err_tx.send(Err(crate::to_v3_err(err)));
+            return (keys_rx, err_rx);
+        }
+        self.inner.get_keys_async(max_result_bytes).await
+    }
+
+    async fn get_many(
+        &self,
+        keys: Vec<String>,
+        max_result_bytes: usize,
+    ) -> Result<Vec<(String, Option<Vec<u8>>)>, Error> {
+        self.inject("get_many").await?;
+        self.inner.get_many(keys, max_result_bytes).await
+    }
+
+    async fn set_many(&self, key_values: Vec<(String, Vec<u8>)>) -> Result<(), Error> {
+        self.inject("set_many").await?;
+        self.inner.set_many(key_values).await
+    }
+
+    async fn delete_many(&self, keys: Vec<String>) -> Result<(), Error> {
+        self.inject("delete_many").await?;
+        self.inner.delete_many(keys).await
+    }
+
+    async fn increment(&self, key: String, delta: i64) -> Result<i64, Error> {
+        self.inject("increment").await?;
+        self.inner.increment(key, delta).await
+    }
+
+    async fn new_compare_and_swap(
+        &self,
+        bucket_rep: u32,
+        key: &str,
+    ) -> Result<Arc<dyn Cas>, Error> {
+        self.inject("compare_and_swap").await?;
+        self.inner.new_compare_and_swap(bucket_rep, key).await
+    }
+}
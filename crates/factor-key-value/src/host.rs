@@ -4,6 +4,7 @@ use spin_core::{
     async_trait,
     wasmtime::component::{Accessor, FutureReader, Resource, StreamReader},
 };
+use spin_factor_capability_policy::{CapabilityChecker, operation};
 use spin_factor_otel::OtelFactorState;
 use spin_resource_table::Table;
 use spin_telemetry::traces::{self, Blame};
@@ -16,6 +17,10 @@ use tracing::instrument;
 
 const DEFAULT_STORE_TABLE_CAPACITY: u32 = 256;
 
+/// The most keys returned by a `wasi:keyvalue` `list-keys` call resuming from
+/// a cursor.
+const LIST_KEYS_PAGE_SIZE: usize = 1000;
+
 pub use key_value::Error;
 
 #[async_trait]
@@ -47,6 +52,29 @@ pub trait Store: Sync + Send {
     async fn delete(&self, key: &str) -> Result<(), Error>;
     async fn exists(&self, key: &str) -> Result<bool, Error>;
     async fn get_keys(&self, max_result_bytes: usize) -> Result<Vec<String>, Error>;
+    /// Returns up to `limit` of the keys which sort after `after`, in key
+    /// order.
+    ///
+    /// Stores which can list keys in order should override this, and
+    /// [`Store::lists_keys_in_order`]; the default lists every key and sorts
+    /// them.
+    async fn get_keys_after(
+        &self,
+        after: &str,
+        limit: usize,
+        max_result_bytes: usize,
+    ) -> Result<Vec<String>, Error> {
+        let mut keys = self.get_keys(max_result_bytes).await?;
+        keys.retain(|key| key.as_str() > after);
+        keys.sort_unstable();
+        keys.truncate(limit);
+        Ok(keys)
+    }
+    /// Whether the store lists keys in order natively, so that paging with
+    /// [`Store::get_keys_after`] does not list every key for each page.
+    fn lists_keys_in_order(&self) -> bool {
+        false
+    }
     async fn get_keys_async(
         &self,
         max_result_bytes: usize,
@@ -72,6 +100,7 @@ pub struct KeyValueDispatch {
     stores: Table<Arc<dyn Store>>,
     compare_and_swaps: Table<Arc<dyn Cas>>,
     otel: OtelFactorState,
+    capabilities: CapabilityChecker,
 }
 
 impl KeyValueDispatch {
@@ -96,9 +125,27 @@ impl KeyValueDispatch {
             stores: Table::new(capacity),
             compare_and_swaps: Table::new(capacity),
             otel,
+            capabilities: Default::default(),
         }
     }
 
+    /// Checks stores against the given capability policy as well as the
+    /// allowed stores when they are opened. (Calls on open stores are checked
+    /// by the store manager.)
+    pub fn with_capabilities(mut self, capabilities: CapabilityChecker) -> Self {
+        self.capabilities = capabilities;
+        self
+    }
+
+    /// Returns whether the component may open the given store.
+    fn may_open(&self, label: &str) -> bool {
+        self.allowed_stores.contains(label)
+            && self
+                .capabilities
+                .check(operation::KEY_VALUE, label, None)
+                .is_ok()
+    }
+
     pub fn get_store<T: 'static>(&self, store: Resource<T>) -> anyhow::Result<&Arc<dyn Store>> {
         let res = self.stores.get(store.rep()).context("invalid store");
         if let Err(err) = &res {
@@ -149,7 +196,7 @@ impl key_value::HostStore for KeyValueDispatch {
     async fn open(&mut self, name: String) -> Result<Result<Resource<key_value::Store>, Error>> {
         self.otel.reparent_tracing_span();
         Ok(async {
-            if self.allowed_stores.contains(&name) {
+            if self.may_open(&name) {
                 let store = self.manager.get(&name).await?;
                 store.after_open().await?;
                 let store_idx = self
@@ -256,7 +303,7 @@ impl v3::HostStoreWithStore for crate::KeyValueFactorData {
         let (allowed, manager) = accessor.with(|mut access| {
             let host = access.get();
             host.otel.reparent_tracing_span();
-            (host.allowed_stores.contains(&label), host.manager.clone())
+            (host.may_open(&label), host.manager.clone())
         });
 
         if !allowed {
@@ -421,7 +468,8 @@ impl wasi_keyvalue::store::Host for KeyValueDispatch {
         &mut self,
         identifier: String,
     ) -> Result<Resource<wasi_keyvalue::store::Bucket>, wasi_keyvalue::store::Error> {
-        if self.allowed_stores.contains(&identifier) {
+        self.otel.reparent_tracing_span();
+        if self.may_open(&identifier) {
             let store = self.manager.get(&identifier).await.map_err(to_wasi_err)?;
             store.after_open().await.map_err(to_wasi_err)?;
             let store_idx = self
@@ -450,6 +498,7 @@ impl wasi_keyvalue::store::HostBucket for KeyValueDispatch {
         self_: Resource<Bucket>,
         key: String,
     ) -> Result<Option<Vec<u8>>, wasi_keyvalue::store::Error> {
+        self.otel.reparent_tracing_span();
         let store = self.get_store_wasi(self_)?;
         store
             .get(&key, MAX_HOST_BUFFERED_BYTES)
@@ -464,6 +513,7 @@ impl wasi_keyvalue::store::HostBucket for KeyValueDispatch {
         key: String,
         value: Vec<u8>,
     ) -> Result<(), wasi_keyvalue::store::Error> {
+        self.otel.reparent_tracing_span();
         let store = self.get_store_wasi(self_)?;
         store.set(&key, &value).await.map_err(to_wasi_err)
     }
@@ -474,6 +524,7 @@ impl wasi_keyvalue::store::HostBucket for KeyValueDispatch {
         self_: Resource<Bucket>,
         key: String,
     ) -> Result<(), wasi_keyvalue::store::Error> {
+        self.otel.reparent_tracing_span();
         let store = self.get_store_wasi(self_)?;
         store.delete(&key).await.map_err(to_wasi_err)
     }
@@ -484,6 +535,7 @@ impl wasi_keyvalue::store::HostBucket for KeyValueDispatch {
         self_: Resource<Bucket>,
         key: String,
     ) -> Result<bool, wasi_keyvalue::store::Error> {
+        self.otel.reparent_tracing_span();
         let store = self.get_store_wasi(self_)?;
         store.exists(&key).await.map_err(to_wasi_err)
     }
@@ -494,19 +546,19 @@ impl wasi_keyvalue::store::HostBucket for KeyValueDispatch {
         self_: Resource<Bucket>,
         cursor: Option<String>,
     ) -> Result<wasi_keyvalue::store::KeyResponse, wasi_keyvalue::store::Error> {
-        match cursor {
-            Some(_) => Err(wasi_keyvalue::store::Error::Other(
-                "list_keys: cursor not supported".to_owned(),
-            )),
-            None => {
-                let store = self.get_store_wasi(self_)?;
-                let keys = store
-                    .get_keys(MAX_HOST_BUFFERED_BYTES)
-                    .await
-                    .map_err(to_wasi_err)?;
-                Ok(wasi_keyvalue::store::KeyResponse { keys, cursor: None })
-            }
-        }
+        self.otel.reparent_tracing_span();
+        let store = self.get_store_wasi(self_)?;
+        // Ask for one more key than a page holds to learn whether another
+        // page follows.
+        let keys = store
+            .get_keys_after(
+                cursor.as_deref().unwrap_or_default(),
+                LIST_KEYS_PAGE_SIZE + 1,
+                MAX_HOST_BUFFERED_BYTES,
+            )
+            .await
+            .map_err(to_wasi_err)?;
+        Ok(key_page(keys, LIST_KEYS_PAGE_SIZE))
     }
 
     async fn drop(&mut self, rep: Resource<Bucket>) -> anyhow::Result<()> {
@@ -515,6 +567,21 @@ impl wasi_keyvalue::store::HostBucket for KeyValueDispatch {
     }
 }
 
+/// Returns a page of `keys`, which are in key order, with the cursor for the
+/// next page if there are more than `page_size`.
+///
+/// The cursor is the last key of the page, so paging stays correct if keys
+/// are added or removed between calls.
+fn key_page(mut keys: Vec<String>, page_size: usize) -> wasi_keyvalue::store::KeyResponse {
+    let cursor = if keys.len() > page_size {
+        keys.truncate(page_size);
+        keys.last().cloned()
+    } else {
+        None
+    };
+    wasi_keyvalue::store::KeyResponse { keys, cursor }
+}
+
 impl wasi_keyvalue::batch::Host for KeyValueDispatch {
     #[instrument(name = "spin_key_value.get_many", skip_all, fields(otel.kind = "client"))]
     #[allow(clippy::type_complexity)]
@@ -523,6 +590,7 @@ impl wasi_keyvalue::batch::Host for KeyValueDispatch {
         bucket: Resource<wasi_keyvalue::batch::Bucket>,
         keys: Vec<String>,
     ) -> std::result::Result<Vec<(String, Option<Vec<u8>>)>, wasi_keyvalue::store::Error> {
+        self.otel.reparent_tracing_span();
         let store = self.get_store_wasi(bucket)?;
         if keys.is_empty() {
             return Ok(vec![]);
@@ -539,6 +607,7 @@ impl wasi_keyvalue::batch::Host for KeyValueDispatch {
         bucket: Resource<wasi_keyvalue::batch::Bucket>,
         key_values: Vec<(String, Vec<u8>)>,
     ) -> std::result::Result<(), wasi_keyvalue::store::Error> {
+        self.otel.reparent_tracing_span();
         let store = self.get_store_wasi(bucket)?;
         if key_values.is_empty() {
             return Ok(());
@@ -552,6 +621,7 @@ impl wasi_keyvalue::batch::Host for KeyValueDispatch {
         bucket: Resource<wasi_keyvalue::batch::Bucket>,
         keys: Vec<String>,
     ) -> std::result::Result<(), wasi_keyvalue::store::Error> {
+        self.otel.reparent_tracing_span();
         let store = self.get_store_wasi(bucket)?;
         if keys.is_empty() {
             return Ok(());
@@ -567,6 +637,7 @@ impl wasi_keyvalue::atomics::HostCas for KeyValueDispatch {
         bucket: Resource<wasi_keyvalue::atomics::Bucket>,
         key: String,
     ) -> Result<Resource<wasi_keyvalue::atomics::Cas>, wasi_keyvalue::store::Error> {
+        self.otel.reparent_tracing_span();
         let bucket_rep = bucket.rep();
         let bucket: Resource<Bucket> = Resource::new_own(bucket_rep);
         let store = self.get_store_wasi(bucket)?;
@@ -589,6 +660,7 @@ impl wasi_keyvalue::atomics::HostCas for KeyValueDispatch {
         &mut self,
         cas: Resource<wasi_keyvalue::atomics::Cas>,
     ) -> Result<Option<Vec<u8>>, wasi_keyvalue::store::Error> {
+        self.otel.reparent_tracing_span();
         let cas = self
             .get_cas(cas)
             .map_err(|e| wasi_keyvalue::store::Error::Other(e.to_string()))?;
@@ -618,6 +690,7 @@ impl wasi_keyvalue::atomics::Host for KeyValueDispatch {
         key: String,
         delta: i64,
     ) -> Result<i64, wasi_keyvalue::store::Error> {
+        self.otel.reparent_tracing_span();
         let store = self.get_store_wasi(bucket)?;
         store.increment(key, delta).await.map_err(to_wasi_err)
     }
@@ -628,6 +701,7 @@ impl wasi_keyvalue::atomics::Host for KeyValueDispatch {
         cas_res: Resource<atomics::Cas>,
         value: Vec<u8>,
     ) -> Result<(), CasError> {
+        self.otel.reparent_tracing_span();
         let cas_rep = cas_res.rep();
         let cas = self
             .get_cas(Resource::<Bucket>::new_own(cas_rep))
@@ -731,3 +805,19 @@ impl spin_world::v1::key_value::Host for KeyValueDispatch {
         <Self as key_value::HostStore>::drop(self, this).await
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn key_pages_end_with_their_cursor() {
+        let page = key_page(["a", "b", "c"].map(String::from).to_vec(), 2);
+        assert_eq!(page.keys, ["a", "b"]);
+        assert_eq!(page.cursor.as_deref(), Some("b"));
+
+        let page = key_page(["c", "d"].map(String::from).to_vec(), 2);
+        assert_eq!(page.keys, ["c", "d"]);
+        assert_eq!(page.cursor, None);
+    }
+}
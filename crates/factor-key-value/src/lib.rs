@@ -1,5 +1,9 @@
+mod audit;
+mod capabilities;
+mod faults;
 mod host;
 pub mod runtime_config;
+mod scope;
 mod util;
 
 use std::{
@@ -8,11 +12,13 @@ use std::{
 };
 
 use anyhow::ensure;
+use spin_factor_capability_policy::CapabilityChecker;
 use spin_factor_otel::OtelFactorState;
 use spin_factors::{
     ConfigureAppContext, Factor, FactorData, FactorInstanceBuilder, InitContext, PrepareContext,
     RuntimeFactors,
 };
+use spin_fault_injection::FaultInjector;
 use spin_locked_app::MetadataKey;
 
 /// Metadata key for key-value stores.
@@ -21,7 +27,7 @@ pub use host::to_v3_err;
 pub use host::{
     Error, KeyValueDispatch, Store, StoreManager, log_cas_error, log_error, log_error_v3,
 };
-pub use runtime_config::RuntimeConfig;
+pub use runtime_config::{RuntimeConfig, StoreGrant};
 use spin_core::async_trait;
 pub use spin_world::spin::key_value::key_value as v3;
 pub use util::DelegatingStoreManager;
@@ -29,13 +35,18 @@ pub use util::DelegatingStoreManager;
 /// A factor that provides key-value storage.
 #[derive(Default)]
 pub struct KeyValueFactor {
-    _priv: (),
+    fault_injector: Option<Arc<FaultInjector>>,
 }
 
 impl KeyValueFactor {
     /// Create a new KeyValueFactor.
     pub fn new() -> Self {
-        Self { _priv: () }
+        Self::default()
+    }
+
+    /// Delays and fails store operations as the given injector directs.
+    pub fn set_fault_injector(&mut self, fault_injector: Arc<FaultInjector>) {
+        self.fault_injector = Some(fault_injector);
     }
 }
 
@@ -62,7 +73,8 @@ impl Factor for KeyValueFactor {
         &self,
         mut ctx: ConfigureAppContext<T, Self>,
     ) -> anyhow::Result<Self::AppState> {
-        let store_managers = ctx.take_runtime_config().unwrap_or_default();
+        let mut store_managers = ctx.take_runtime_config().unwrap_or_default();
+        let component_store_grants = std::mem::take(&mut store_managers.store_grants);
 
         let delegating_manager = DelegatingStoreManager::new(store_managers);
         let store_manager = Arc::new(delegating_manager);
@@ -87,9 +99,24 @@ impl Factor for KeyValueFactor {
             // TODO: warn (?) on unused store?
         }
 
+        for (component_id, grants) in &component_store_grants {
+            for label in grants.keys() {
+                ensure!(
+                    store_manager.is_defined(label),
+                    "key-value store grant to component {component_id:?} is for unknown store {label:?}"
+                );
+            }
+            if !component_allowed_stores.contains_key(component_id) {
+                tracing::warn!(
+                    "Key-value store grants are configured for component {component_id:?}, which is not in the app"
+                );
+            }
+        }
+
         Ok(AppState {
             store_manager,
             component_allowed_stores,
+            component_store_grants,
         })
     }
 
@@ -103,11 +130,20 @@ impl Factor for KeyValueFactor {
             .get(ctx.app_component().id())
             .expect("component should be in component_stores")
             .clone();
+        let store_grants = app_state
+            .component_store_grants
+            .get(ctx.app_component().id())
+            .cloned()
+            .unwrap_or_default();
         let otel = OtelFactorState::from_prepare_context(&mut ctx)?;
+        let capabilities = CapabilityChecker::from_prepare_context(&mut ctx)?;
         Ok(InstanceBuilder {
             store_manager: app_state.store_manager.clone(),
             allowed_stores,
+            store_grants,
             otel,
+            capabilities,
+            fault_injector: self.fault_injector.clone(),
         })
     }
 }
@@ -126,6 +162,10 @@ pub struct AppState {
     /// This is a map from component ID to the set of store labels that the
     /// component is allowed to use.
     component_allowed_stores: HashMap<String, HashSet<String>>,
+    /// The grants restricting each component's use of stores.
+    ///
+    /// This is a map from component ID to a map from store label to grant.
+    component_store_grants: HashMap<String, HashMap<String, StoreGrant>>,
 }
 
 impl AppState {
@@ -141,10 +181,21 @@ impl AppState {
             .any(|stores| stores.contains(label))
     }
 
+    /// Returns the labels of all the app's stores, in order.
+    pub fn store_labels(&self) -> Vec<String> {
+        self.store_manager.labels()
+    }
+
     /// Get a store by label.
     pub async fn get_store(&self, label: &str) -> Option<Arc<dyn Store>> {
         self.store_manager.get(label).await.ok()
     }
+
+    /// Returns the app's store manager, for host features which keep their
+    /// own state in the app's stores.
+    pub fn store_manager(&self) -> Arc<dyn StoreManager> {
+        self.store_manager.clone()
+    }
 }
 
 /// `SwapError` are errors that occur during compare and swap operations
@@ -187,7 +238,11 @@ pub struct InstanceBuilder {
     store_manager: Arc<AppStoreManager>,
     /// The allowed stores for this component instance.
     allowed_stores: HashSet<String>,
+    /// The grants restricting this component instance's use of stores.
+    store_grants: HashMap<String, StoreGrant>,
     otel: OtelFactorState,
+    capabilities: CapabilityChecker,
+    fault_injector: Option<Arc<FaultInjector>>,
 }
 
 impl FactorInstanceBuilder for InstanceBuilder {
@@ -197,14 +252,42 @@ impl FactorInstanceBuilder for InstanceBuilder {
         let Self {
             store_manager,
             allowed_stores,
+            store_grants,
             otel,
+            capabilities,
+            fault_injector,
         } = self;
-        Ok(KeyValueDispatch::new_with_capacity(
-            allowed_stores,
-            store_manager,
-            u32::MAX,
-            otel,
-        ))
+        let store_manager: Arc<dyn StoreManager> = if spin_telemetry::audit::enabled() {
+            Arc::new(audit::AuditingStoreManager::new(store_manager))
+        } else {
+            store_manager
+        };
+        let store_manager: Arc<dyn StoreManager> = match fault_injector {
+            Some(faults) => Arc::new(faults::FaultInjectingStoreManager::new(
+                store_manager,
+                faults,
+            )),
+            None => store_manager,
+        };
+        let store_manager: Arc<dyn StoreManager> = if capabilities.is_unrestricted() {
+            store_manager
+        } else {
+            Arc::new(capabilities::CapabilityCheckingStoreManager::new(
+                store_manager,
+                capabilities.clone(),
+            ))
+        };
+        // Scoping is applied last so that audit events record the keys as
+        // stored, with any prefix.
+        let store_manager: Arc<dyn StoreManager> = if store_grants.is_empty() {
+            store_manager
+        } else {
+            Arc::new(scope::ScopedStoreManager::new(store_manager, store_grants))
+        };
+        Ok(
+            KeyValueDispatch::new_with_capacity(allowed_stores, store_manager, u32::MAX, otel)
+                .with_capabilities(capabilities),
+        )
     }
 }
 
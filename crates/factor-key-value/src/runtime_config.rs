@@ -9,6 +9,9 @@ use crate::StoreManager;
 pub struct RuntimeConfig {
     /// Map of store names to store managers.
     store_managers: HashMap<String, Arc<dyn StoreManager>>,
+    /// Map of component IDs to the grants restricting their use of stores,
+    /// by store label.
+    pub(crate) store_grants: HashMap<String, HashMap<String, StoreGrant>>,
 }
 
 impl RuntimeConfig {
@@ -28,6 +31,37 @@ impl RuntimeConfig {
     pub fn get_store_manager(&self, label: &str) -> Option<Arc<dyn StoreManager>> {
         self.store_managers.get(label).cloned()
     }
+
+    /// Restricts the given component's use of the store with the given label.
+    ///
+    /// Components with no grant for a store have full access to it, provided
+    /// the store is listed in their `key_value_stores`. Errors if the component
+    /// already has a grant for the store.
+    pub fn add_store_grant(
+        &mut self,
+        component_id: String,
+        label: String,
+        grant: StoreGrant,
+    ) -> anyhow::Result<()> {
+        let grants = self.store_grants.entry(component_id.clone()).or_default();
+        if grants.contains_key(&label) {
+            anyhow::bail!(
+                "duplicate grant for key-value store {label:?} to component {component_id:?}"
+            );
+        }
+        grants.insert(label, grant);
+        Ok(())
+    }
+}
+
+/// Restricts a component's use of a key-value store.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct StoreGrant {
+    /// Prepended to every key the component uses, so that it sees only the
+    /// keys starting with the prefix, with the prefix removed.
+    pub key_prefix: Option<String>,
+    /// Whether writes by the component are denied.
+    pub read_only: bool,
 }
 
 impl IntoIterator for RuntimeConfig {
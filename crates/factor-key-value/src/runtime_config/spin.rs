@@ -1,6 +1,6 @@
 //! Runtime configuration implementation used by Spin CLI.
 
-use crate::{RuntimeConfig, StoreManager};
+use crate::{RuntimeConfig, StoreManager, runtime_config::StoreGrant};
 use anyhow::Context as _;
 use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
@@ -125,7 +125,17 @@ impl RuntimeConfigResolver {
         let table: HashMap<String, StoreConfig> = table.clone().try_into()?;
 
         let mut runtime_config = RuntimeConfig::default();
-        for (label, config) in table {
+        for (label, mut config) in table {
+            for grant in std::mem::take(&mut config.grants) {
+                runtime_config.add_store_grant(
+                    grant.component,
+                    label.clone(),
+                    StoreGrant {
+                        key_prefix: grant.key_prefix.filter(|prefix| !prefix.is_empty()),
+                        read_only: grant.read_only,
+                    },
+                )?;
+            }
             let store_manager = self.store_manager_from_config(config).with_context(|| {
                 format!("could not configure key-value store with label '{label}'")
             })?;
@@ -155,6 +165,16 @@ impl RuntimeConfigResolver {
 pub struct StoreConfig {
     #[serde(rename = "type")]
     pub type_: String,
+    /// Restrictions on particular components' use of the store.
+    ///
+    /// ```toml
+    /// [[key_value_store.shared.grants]]
+    /// component = "plugin"
+    /// key_prefix = "plugin/"
+    /// read_only = true
+    /// ```
+    #[serde(default)]
+    pub grants: Vec<GrantConfig>,
     #[serde(flatten)]
     pub config: toml::Table,
 }
@@ -166,7 +186,19 @@ impl StoreConfig {
     {
         Ok(Self {
             type_,
+            grants: vec![],
             config: toml::value::Table::try_from(config)?,
         })
     }
 }
+
+/// A [`StoreGrant`] to a component, as written in runtime config.
+#[derive(Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct GrantConfig {
+    pub component: String,
+    #[serde(default)]
+    pub key_prefix: Option<String>,
+    #[serde(default)]
+    pub read_only: bool,
+}
@@ -0,0 +1,273 @@
+use crate::{Cas, Error, Store, StoreManager, SwapError, runtime_config::StoreGrant};
+use spin_core::async_trait;
+use spin_world::spin::key_value::key_value as v3;
+use std::{any::Any, collections::HashMap, sync::Arc};
+
+/// The number of keys [`ScopedStore::get_keys`] fetches at a time.
+const KEYS_PAGE_SIZE: usize = 256;
+
+/// A [`StoreManager`] which restricts a component's use of its stores to the
+/// component's [`StoreGrant`]s.
+pub struct ScopedStoreManager {
+    inner: Arc<dyn StoreManager>,
+    /// Map of store labels to grants.
+    grants: HashMap<String, StoreGrant>,
+}
+
+impl ScopedStoreManager {
+    pub fn new(inner: Arc<dyn StoreManager>, grants: HashMap<String, StoreGrant>) -> Self {
+        Self { inner, grants }
+    }
+}
+
+#[async_trait]
+impl StoreManager for ScopedStoreManager {
+    async fn get(&self, name: &str) -> Result<Arc<dyn Store>, Error> {
+        let inner = self.inner.get(name).await?;
+        let Some(grant) = self.grants.get(name) else {
+            return Ok(inner);
+        };
+        Ok(Arc::new(ScopedStore {
+            inner,
+            prefix: grant.key_prefix.clone().unwrap_or_default(),
+            read_only: grant.read_only,
+        }))
+    }
+
+    fn is_defined(&self, store_name: &str) -> bool {
+        self.inner.is_defined(store_name)
+    }
+
+    fn summary(&self, store_name: &str) -> Option<String> {
+        self.inner.summary(store_name)
+    }
+
+    fn metadata(&self) -> Arc<dyn Any> {
+        self.inner.metadata()
+    }
+}
+
+struct ScopedStore {
+    inner: Arc<dyn Store>,
+    prefix: String,
+    read_only: bool,
+}
+
+impl ScopedStore {
+    /// The key in the underlying store for the component's `key`.
+    fn scoped(&self, key: &str) -> String {
+        format!("{}{key}", self.prefix)
+    }
+
+    /// The component's key for `key` in the underlying store, if it is in the
+    /// component's scope.
+    fn unscoped(&self, key: &str) -> Option<String> {
+        key.strip_prefix(&self.prefix).map(str::to_owned)
+    }
+
+    fn ensure_writable(&self) -> Result<(), Error> {
+        if self.read_only {
+            return Err(Error::AccessDenied);
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Store for ScopedStore {
+    async fn after_open(&self) -> Result<(), Error> {
+        self.inner.after_open().await
+    }
+
+    async fn get(&self, key: &str, max_result_bytes: usize) -> Result<Option<Vec<u8>>, Error> {
+        self.inner.get(&self.scoped(key), max_result_bytes).await
+    }
+
+    async fn set(&self, key: &str, value: &[u8]) -> Result<(), Error> {
+        self.ensure_writable()?;
+        self.inner.set(&self.scoped(key), value).await
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), Error> {
+        self.ensure_writable()?;
+        self.inner.delete(&self.scoped(key)).await
+    }
+
+    async fn exists(&self, key: &str) -> Result<bool, Error> {
+        self.inner.exists(&self.scoped(key)).await
+    }
+
+    async fn get_keys(&self, max_result_bytes: usize) -> Result<Vec<String>, Error> {
+        if self.prefix.is_empty() {
+            return self.inner.get_keys(max_result_bytes).await;
+        }
+        if !self.inner.lists_keys_in_order() {
+            // Paging would list every key of the underlying store for each
+            // page, so list them once instead. Keys outside the scope count
+            // against the limit here.
+            let keys = self.inner.get_keys(max_result_bytes).await?;
+            return Ok(keys.iter().filter_map(|key| self.unscoped(key)).collect());
+        }
+        // Page through only the keys in scope, so that keys outside the scope
+        // count neither against the component's limit nor towards the result.
+        let mut keys = Vec::new();
+        let mut byte_count = std::mem::size_of::<Vec<String>>();
+        let mut push = |key: String| {
+            byte_count += std::mem::size_of::<String>() + key.len();
+            if byte_count > max_result_bytes {
+                return Err(Error::Other(format!(
+                    "query result exceeds limit of {max_result_bytes} bytes"
+                )));
+            }
+            keys.push(key);
+            Ok(())
+        };
+        // `get_keys_after` excludes `after` itself, which is the scoped key
+        // for the empty key.
+        if self.inner.exists(&self.prefix).await? {
+            push(String::new())?;
+        }
+        let mut after = self.prefix.clone();
+        loop {
+            let page = self
+                .inner
+                .get_keys_after(&after, KEYS_PAGE_SIZE, usize::MAX)
+                .await?;
+            let page_len = page.len();
+            let Some(last) = page.last().cloned() else {
+                break;
+            };
+            let mut in_scope = 0;
+            for key in page.iter().map_while(|key| self.unscoped(key)) {
+                push(key)?;
+                in_scope += 1;
+            }
+            if in_scope < page_len || page_len < KEYS_PAGE_SIZE {
+                break;
+            }
+            after = last;
+        }
+        Ok(keys)
+    }
+
+    async fn get_keys_after(
+        &self,
+        after: &str,
+        limit: usize,
+        max_result_bytes: usize,
+    ) -> Result<Vec<String>, Error> {
+        // The keys in scope are contiguous in key order, so the page ends at
+        // the first key out of scope.
+        let keys = self
+            .inner
+            .get_keys_after(&self.scoped(after), limit, max_result_bytes)
+            .await?;
+        Ok(keys.iter().map_while(|key| self.unscoped(key)).collect())
+    }
+
+    fn lists_keys_in_order(&self) -> bool {
+        self.inner.lists_keys_in_order()
+    }
+
+    async fn get_keys_async(
+        &self,
+        max_result_bytes: usize,
+    ) -> (
+        tokio::sync::mpsc::Receiver<String>,
+        tokio::sync::oneshot::Receiver<Result<(), v3::Error>>,
+    ) {
+        let (mut inner_keys_rx, err_rx) = self.inner.get_keys_async(max_result_bytes).await;
+        if self.prefix.is_empty() {
+            return (inner_keys_rx, err_rx);
+        }
+        let (keys_tx, keys_rx) = tokio::sync::mpsc::channel(4);
+        let prefix = self.prefix.clone();
+        tokio::spawn(async move {
+            while let Some(key) = inner_keys_rx.recv().await {
+                if let Some(key) = key.strip_prefix(&prefix)
+                    && keys_tx.send(key.to_owned()).await.is_err()
+                {
+                    break;
+                }
+            }
+        });
+        (keys_rx, err_rx)
+    }
+
+    async fn get_many(
+        &self,
+        keys: Vec<String>,
+        max_result_bytes: usize,
+    ) -> Result<Vec<(String, Option<Vec<u8>>)>, Error> {
+        let keys = keys.iter().map(|key| self.scoped(key)).collect();
+        let key_values = self.inner.get_many(keys, max_result_bytes).await?;
+        Ok(key_values
+            .into_iter()
+            .filter_map(|(key, value)| Some((self.unscoped(&key)?, value)))
+            .collect())
+    }
+
+    async fn set_many(&self, key_values: Vec<(String, Vec<u8>)>) -> Result<(), Error> {
+        self.ensure_writable()?;
+        let key_values = key_values
+            .into_iter()
+            .map(|(key, value)| (self.scoped(&key), value))
+            .collect();
+        self.inner.set_many(key_values).await
+    }
+
+    async fn delete_many(&self, keys: Vec<String>) -> Result<(), Error> {
+        self.ensure_writable()?;
+        let keys = keys.iter().map(|key| self.scoped(key)).collect();
+        self.inner.delete_many(keys).await
+    }
+
+    async fn increment(&self, key: String, delta: i64) -> Result<i64, Error> {
+        self.ensure_writable()?;
+        self.inner.increment(self.scoped(&key), delta).await
+    }
+
+    async fn new_compare_and_swap(
+        &self,
+        bucket_rep: u32,
+        key: &str,
+    ) -> Result<Arc<dyn Cas>, Error> {
+        self.ensure_writable()?;
+        let inner = self
+            .inner
+            .new_compare_and_swap(bucket_rep, &self.scoped(key))
+            .await?;
+        Ok(Arc::new(ScopedCas {
+            inner,
+            prefix_len: self.prefix.len(),
+        }))
+    }
+}
+
+/// A [`Cas`] which reports the component's key, so that a retried swap opens
+/// the same key through the [`ScopedStore`].
+struct ScopedCas {
+    inner: Arc<dyn Cas>,
+    prefix_len: usize,
+}
+
+#[async_trait]
+impl Cas for ScopedCas {
+    async fn current(&self, max_result_bytes: usize) -> Result<Option<Vec<u8>>, Error> {
+        self.inner.current(max_result_bytes).await
+    }
+
+    async fn swap(&self, value: Vec<u8>) -> Result<(), SwapError> {
+        self.inner.swap(value).await
+    }
+
+    async fn bucket_rep(&self) -> u32 {
+        self.inner.bucket_rep().await
+    }
+
+    async fn key(&self) -> String {
+        let mut key = self.inner.key().await;
+        key.drain(..self.prefix_len);
+        key
+    }
+}
@@ -12,6 +12,13 @@ impl DelegatingStoreManager {
         let delegates = delegates.into_iter().collect();
         Self { delegates }
     }
+
+    /// The labels of the stores delegated to, in order.
+    pub fn labels(&self) -> Vec<String> {
+        let mut labels = self.delegates.keys().cloned().collect::<Vec<_>>();
+        labels.sort();
+        labels
+    }
 }
 
 #[async_trait]
@@ -1,8 +1,13 @@
 use anyhow::bail;
 use spin_core::async_trait;
-use spin_factor_key_value::{Cas, KeyValueFactor, RuntimeConfig, Store, StoreManager, v3};
+use spin_core::wasmtime::component::Resource;
+use spin_factor_key_value::runtime_config::spin::MakeKeyValueStore;
+use spin_factor_key_value::{
+    Cas, KeyValueFactor, RuntimeConfig, Store, StoreGrant, StoreManager, v3,
+};
 use spin_factors::RuntimeFactors;
 use spin_factors_test::{TestEnvironment, toml};
+use spin_key_value_spin::{SpinKeyValueRuntimeConfig, SpinKeyValueStore};
 use spin_world::v2::key_value::{Error, HostStore};
 use std::{collections::HashSet, sync::Arc};
 
@@ -99,6 +104,183 @@ async fn errors_when_store_is_not_allowed() -> anyhow::Result<()> {
     Ok(())
 }
 
+#[tokio::test]
+async fn grants_scope_and_restrict_store_access() -> anyhow::Result<()> {
+    let store_manager: Arc<dyn StoreManager> =
+        Arc::new(SpinKeyValueStore::new(None).make_store(SpinKeyValueRuntimeConfig::new(None))?);
+    let mut runtime_config = RuntimeConfig::default();
+    runtime_config.add_store_manager("shared".into(), store_manager.clone());
+    runtime_config.add_store_grant(
+        "test-component".into(),
+        "shared".into(),
+        StoreGrant {
+            key_prefix: Some("plugin/".into()),
+            read_only: true,
+        },
+    )?;
+    let shared = store_manager.get("shared").await?;
+    shared.set("plugin/greeting", b"hello").await?;
+    shared.set("secret", b"hunter2").await?;
+    shared.set("plugin/", b"root").await?;
+    shared.set("plugin0", b"neighbour").await?;
+
+    let factors = TestFactors {
+        key_value: KeyValueFactor::new(),
+    };
+    let env = TestEnvironment::new(factors).extend_manifest(toml! {
+        [component.test-component]
+        source = "does-not-exist.wasm"
+        key_value_stores = ["shared"]
+    });
+    let mut state = env
+        .runtime_config(runtime_config)?
+        .build_instance_state()
+        .await?;
+
+    let store = state.key_value.open("shared".to_owned()).await??;
+    let rep = store.rep();
+    assert_eq!(
+        state
+            .key_value
+            .get(Resource::new_borrow(rep), "greeting".into())
+            .await??,
+        Some(b"hello".to_vec())
+    );
+    assert_eq!(
+        state
+            .key_value
+            .get(Resource::new_borrow(rep), "secret".into())
+            .await??,
+        None
+    );
+    assert_eq!(
+        state
+            .key_value
+            .get_keys(Resource::new_borrow(rep))
+            .await??,
+        ["", "greeting"]
+    );
+    assert!(matches!(
+        state
+            .key_value
+            .set(
+                Resource::new_borrow(rep),
+                "greeting".into(),
+                b"bye".to_vec()
+            )
+            .await?,
+        Err(Error::AccessDenied)
+    ));
+    assert_eq!(
+        shared.get("plugin/greeting", usize::MAX).await?,
+        Some(b"hello".to_vec())
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn list_keys_pages_through_every_key() -> anyhow::Result<()> {
+    use spin_world::wasi::keyvalue::store as wasi_store;
+
+    let store_manager: Arc<dyn StoreManager> =
+        Arc::new(SpinKeyValueStore::new(None).make_store(SpinKeyValueRuntimeConfig::new(None))?);
+    let mut runtime_config = RuntimeConfig::default();
+    runtime_config.add_store_manager("default".into(), store_manager.clone());
+    let expected = (0..2500).map(|i| format!("key{i:04}")).collect::<Vec<_>>();
+    store_manager
+        .get("default")
+        .await?
+        .set_many(expected.iter().map(|key| (key.clone(), vec![])).collect())
+        .await?;
+
+    let factors = TestFactors {
+        key_value: KeyValueFactor::new(),
+    };
+    let env = TestEnvironment::new(factors).extend_manifest(toml! {
+        [component.test-component]
+        source = "does-not-exist.wasm"
+        key_value_stores = ["default"]
+    });
+    let mut state = env
+        .runtime_config(runtime_config)?
+        .build_instance_state()
+        .await?;
+
+    let bucket = wasi_store::Host::open(&mut state.key_value, "default".into())
+        .await
+        .unwrap();
+    let mut keys = vec![];
+    let mut pages = 0;
+    let mut cursor = None;
+    loop {
+        let page = wasi_store::HostBucket::list_keys(
+            &mut state.key_value,
+            Resource::new_borrow(bucket.rep()),
+            cursor,
+        )
+        .await
+        .unwrap();
+        keys.extend(page.keys);
+        pages += 1;
+        cursor = page.cursor;
+        if cursor.is_none() {
+            break;
+        }
+    }
+    assert_eq!(pages, 3);
+    assert_eq!(keys, expected);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn fault_injector_fails_store_calls() -> anyhow::Result<()> {
+    let store_manager: Arc<dyn StoreManager> =
+        Arc::new(SpinKeyValueStore::new(None).make_store(SpinKeyValueRuntimeConfig::new(None))?);
+    let mut runtime_config = RuntimeConfig::default();
+    runtime_config.add_store_manager("default".into(), store_manager);
+    let scenario: spin_fault_injection::Scenario = toml::from_str(
+        r#"
+        [[fail]]
+        call = "key_value.get"
+        target = "default"
+        message = "unavailable"
+        times = 1
+        "#,
+    )?;
+    let mut key_value = KeyValueFactor::new();
+    key_value.set_fault_injector(Arc::new(scenario.fault_injector()?.unwrap()));
+    let env = TestEnvironment::new(TestFactors { key_value }).extend_manifest(toml! {
+        [component.test-component]
+        source = "does-not-exist.wasm"
+        key_value_stores = ["default"]
+    });
+    let mut state = env
+        .runtime_config(runtime_config)?
+        .build_instance_state()
+        .await?;
+
+    let store = state.key_value.open("default".to_owned()).await??;
+    let rep = store.rep();
+    assert!(matches!(
+        state
+            .key_value
+            .get(Resource::new_borrow(rep), "key".into())
+            .await?,
+        Err(Error::Other(message)) if message == "unavailable"
+    ));
+    assert!(
+        state
+            .key_value
+            .get(Resource::new_borrow(rep), "key".into())
+            .await?
+            .is_ok()
+    );
+
+    Ok(())
+}
+
 fn mock_store_manager() -> Arc<dyn StoreManager> {
     Arc::new(MockStoreManager)
 }
@@ -1,3 +1,4 @@
+use spin_factor_capability_policy::operation;
 use spin_world::MAX_HOST_BUFFERED_BYTES;
 use spin_world::v1::llm::{self as v1};
 use spin_world::v2::llm::{self as v2};
@@ -19,6 +20,9 @@ impl v2::Host for InstanceState {
         if !self.allowed_models.contains(&model) {
             return Err(access_denied_error(&model));
         }
+        self.capabilities
+            .check(operation::LLM, &model, Some("infer"))
+            .map_err(|err| v2::Error::InvalidInput(err.to_string()))?;
         let mut engine = self.engine.lock().await;
         tracing::Span::current().record("llm.backend", engine.summary());
         engine
@@ -49,6 +53,9 @@ impl v2::Host for InstanceState {
         if !self.allowed_models.contains(&model) {
             return Err(access_denied_error(&model));
         }
+        self.capabilities
+            .check(operation::LLM, &model, Some("generate_embeddings"))
+            .map_err(|err| v2::Error::InvalidInput(err.to_string()))?;
         let mut engine = self.engine.lock().await;
         tracing::Span::current().record("llm.backend", engine.summary());
         engine
@@ -1,6 +1,7 @@
 use std::collections::HashSet;
 use std::sync::Arc;
 
+use spin_factor_capability_policy::{CapabilityPolicyFactor, runtime_config::config_from_table};
 use spin_factor_llm::{LlmEngine, LlmFactor};
 use spin_factors::{RuntimeFactors, anyhow};
 use spin_factors_test::{TestEnvironment, toml};
@@ -73,6 +74,51 @@ async fn llm_works() -> anyhow::Result<()> {
     Ok(())
 }
 
+#[derive(RuntimeFactors)]
+struct PolicyTestFactors {
+    capability_policy: CapabilityPolicyFactor,
+    llm: LlmFactor,
+}
+
+#[tokio::test]
+async fn capability_policy_denies_models() -> anyhow::Result<()> {
+    let handle = Box::new(|_: Operation| -> Result<OperationResult, v2::Error> {
+        panic!("the engine should not be called")
+    });
+    let factors = PolicyTestFactors {
+        capability_policy: CapabilityPolicyFactor::new(),
+        llm: LlmFactor::new(move || {
+            Arc::new(Mutex::new(FakeLLm {
+                handle: handle.clone(),
+            })) as _
+        }),
+    };
+    let env = TestEnvironment::new(factors)
+        .extend_manifest(toml! {
+            [component.test-component]
+            source = "does-not-exist.wasm"
+            ai_models = ["llama2-chat"]
+        })
+        .runtime_config(PolicyTestFactorsRuntimeConfig {
+            capability_policy: config_from_table(&toml! {
+                [[capability_policy]]
+                effect = "deny"
+                operations = ["*"]
+            })?,
+            llm: None,
+        })?;
+    let mut state = env.build_instance_state().await?;
+
+    assert!(matches!(
+        state
+            .llm
+            .infer("llama2-chat".into(), "some prompt".into(), None)
+            .await,
+        Err(v2::Error::InvalidInput(msg)) if msg.contains("denied by capability policy")
+    ));
+    Ok(())
+}
+
 struct FakeLLm {
     handle: Box<dyn Fn(Operation) -> Result<OperationResult, v2::Error> + Sync + Send>,
 }
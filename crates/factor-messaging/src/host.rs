@@ -0,0 +1,251 @@
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+    time::Duration,
+};
+
+use anyhow::{Context as _, Result};
+use futures::{FutureExt as _, StreamExt as _, stream::BoxStream};
+use spin_core::{async_trait, wasmtime::component::Resource};
+use spin_factor_capability_policy::{CapabilityChecker, operation};
+use spin_factors::{FactorData, InitContext};
+use spin_resource_table::Table;
+use spin_world::wasi::messaging::{
+    consumer, messaging_types,
+    messaging_types::{Client, Error, GuestConfiguration, Message},
+    producer,
+};
+
+use crate::MessagingFactor;
+
+const DEFAULT_TABLE_CAPACITY: u32 = 256;
+
+/// The most messages returned by a single receive.
+const MAX_RECEIVE_BATCH: usize = 256;
+
+/// A stream of messages published to a channel.
+pub type Subscription = BoxStream<'static, Result<Message>>;
+
+/// A message broker backing a `messaging_brokers` label.
+#[async_trait]
+pub trait Broker: Send + Sync {
+    /// A human-readable summary of the broker's configuration
+    ///
+    /// Example: "NATS at localhost:4222"
+    fn summary(&self) -> Option<String> {
+        None
+    }
+
+    /// Publishes a message to a channel.
+    async fn publish(&self, channel: &str, message: Message) -> Result<()>;
+
+    /// Subscribes to a channel, returning the messages published to it from
+    /// now on.
+    async fn subscribe(&self, channel: &str) -> Result<Subscription>;
+}
+
+/// A client connected to a broker, with its subscriptions.
+struct ClientEntry {
+    broker: Arc<dyn Broker>,
+    subscriptions: HashMap<String, Subscription>,
+}
+
+/// The messaging state of a component instance, and the host implementation
+/// of `wasi:messaging`.
+pub struct InstanceState {
+    allowed_brokers: HashSet<String>,
+    brokers: Arc<HashMap<String, Arc<dyn Broker>>>,
+    capabilities: CapabilityChecker,
+    clients: Table<ClientEntry>,
+    errors: Table<String>,
+}
+
+impl InstanceState {
+    pub fn new(
+        allowed_brokers: HashSet<String>,
+        brokers: Arc<HashMap<String, Arc<dyn Broker>>>,
+        capabilities: CapabilityChecker,
+    ) -> Self {
+        Self {
+            allowed_brokers,
+            brokers,
+            capabilities,
+            clients: Table::new(DEFAULT_TABLE_CAPACITY),
+            errors: Table::new(DEFAULT_TABLE_CAPACITY),
+        }
+    }
+
+    pub fn allowed_brokers(&self) -> &HashSet<String> {
+        &self.allowed_brokers
+    }
+
+    fn client(&mut self, client: &Resource<Client>) -> Result<&mut ClientEntry> {
+        self.clients.get_mut(client.rep()).context("invalid client")
+    }
+
+    /// Converts an error to a guest `error` resource.
+    fn error(&mut self, err: impl std::fmt::Display) -> Result<Resource<Error>> {
+        let rep = self
+            .errors
+            .push(err.to_string())
+            .map_err(|()| anyhow::anyhow!("messaging error table full"))?;
+        Ok(Resource::new_own(rep))
+    }
+
+    async fn receive(
+        &mut self,
+        client: &Resource<Client>,
+        channel: String,
+        timeout: Option<Duration>,
+    ) -> Result<Result<Option<Vec<Message>>, Resource<Error>>> {
+        let entry = self.client(client)?;
+        let result = async {
+            if !entry.subscriptions.contains_key(&channel) {
+                let subscription = entry.broker.subscribe(&channel).await?;
+                entry.subscriptions.insert(channel.clone(), subscription);
+            }
+            let subscription = entry.subscriptions.get_mut(&channel).unwrap();
+            receive(subscription, timeout).await
+        }
+        .await;
+        match result {
+            Ok(messages) => Ok(Ok(messages)),
+            Err(err) => Ok(Err(self.error(format!("{err:#}"))?)),
+        }
+    }
+}
+
+/// Waits up to `timeout` (or indefinitely) for a message on the
+/// subscription, then returns it with any others already received.
+async fn receive(
+    subscription: &mut Subscription,
+    timeout: Option<Duration>,
+) -> Result<Option<Vec<Message>>> {
+    let first = match timeout {
+        Some(timeout) => match tokio::time::timeout(timeout, subscription.next()).await {
+            Ok(first) => first,
+            Err(_) => return Ok(None),
+        },
+        None => subscription.next().await,
+    };
+    let first = first.context("subscription closed by broker")??;
+    let mut messages = vec![first];
+    while messages.len() < MAX_RECEIVE_BATCH {
+        match subscription.next().now_or_never() {
+            Some(Some(message)) => messages.push(message?),
+            _ => break,
+        }
+    }
+    Ok(Some(messages))
+}
+
+pub(crate) fn add_to_linker(ctx: &mut impl InitContext<MessagingFactor>) -> Result<()> {
+    ctx.link_bindings(messaging_types::add_to_linker::<_, FactorData<MessagingFactor>>)?;
+    ctx.link_bindings(producer::add_to_linker::<_, FactorData<MessagingFactor>>)?;
+    ctx.link_bindings(consumer::add_to_linker::<_, FactorData<MessagingFactor>>)?;
+    Ok(())
+}
+
+impl messaging_types::Host for InstanceState {}
+
+impl messaging_types::HostClient for InstanceState {
+    async fn connect(&mut self, name: String) -> Result<Result<Resource<Client>, Resource<Error>>> {
+        if !self.allowed_brokers.contains(&name) {
+            let err = format!("access to messaging broker {name:?} is not allowed");
+            return Ok(Err(self.error(err)?));
+        }
+        if let Err(err) = self.capabilities.check(operation::MESSAGING, &name, None) {
+            return Ok(Err(self.error(err)?));
+        }
+        let Some(broker) = self.brokers.get(&name).cloned() else {
+            return Ok(Err(
+                self.error(format!("no such messaging broker {name:?}"))?
+            ));
+        };
+        let rep = self.clients.push(ClientEntry {
+            broker,
+            subscriptions: HashMap::new(),
+        });
+        match rep {
+            Ok(rep) => Ok(Ok(Resource::new_own(rep))),
+            Err(()) => Ok(Err(self.error("too many messaging clients")?)),
+        }
+    }
+
+    async fn drop(&mut self, client: Resource<Client>) -> Result<()> {
+        self.clients.remove(client.rep());
+        Ok(())
+    }
+}
+
+impl messaging_types::HostError for InstanceState {
+    async fn trace(&mut self, error: Resource<Error>) -> Result<String> {
+        self.errors
+            .get(error.rep())
+            .cloned()
+            .context("invalid messaging error")
+    }
+
+    async fn drop(&mut self, error: Resource<Error>) -> Result<()> {
+        self.errors.remove(error.rep());
+        Ok(())
+    }
+}
+
+impl producer::Host for InstanceState {
+    async fn send(
+        &mut self,
+        c: Resource<Client>,
+        ch: String,
+        m: Vec<Message>,
+    ) -> Result<Result<(), Resource<Error>>> {
+        let broker = self.client(&c)?.broker.clone();
+        for message in m {
+            if let Err(err) = broker.publish(&ch, message).await {
+                return Ok(Err(self.error(format!("{err:#}"))?));
+            }
+        }
+        Ok(Ok(()))
+    }
+}
+
+impl consumer::Host for InstanceState {
+    async fn subscribe_try_receive(
+        &mut self,
+        c: Resource<Client>,
+        ch: String,
+        t_milliseconds: u32,
+    ) -> Result<Result<Option<Vec<Message>>, Resource<Error>>> {
+        let timeout = Duration::from_millis(t_milliseconds.into());
+        self.receive(&c, ch, Some(timeout)).await
+    }
+
+    async fn subscribe_receive(
+        &mut self,
+        c: Resource<Client>,
+        ch: String,
+    ) -> Result<Result<Vec<Message>, Resource<Error>>> {
+        Ok(self
+            .receive(&c, ch, None)
+            .await?
+            .map(|messages| messages.unwrap_or_default()))
+    }
+
+    async fn update_guest_configuration(
+        &mut self,
+        _gc: GuestConfiguration,
+    ) -> Result<Result<(), Resource<Error>>> {
+        let err = "guest configuration is not supported; subscribe with subscribe-receive instead";
+        Ok(Err(self.error(err)?))
+    }
+
+    // Messages are acknowledged by the broker clients as they are received,
+    // so there is nothing further to do.
+    async fn complete_message(&mut self, _m: Message) -> Result<Result<(), Resource<Error>>> {
+        Ok(Ok(()))
+    }
+
+    async fn abandon_message(&mut self, _m: Message) -> Result<Result<(), Resource<Error>>> {
+        Ok(Ok(()))
+    }
+}
@@ -0,0 +1,145 @@
+//! A factor providing the `wasi:messaging` interface.
+//!
+//! A component connects to a broker by label, and may only connect to the
+//! brokers listed in its manifest `messaging_brokers`. Each label is mapped
+//! to a backing [`Broker`] (e.g. Redis, NATS or Kafka) by the runtime config,
+//! so the same component can be run against different brokers.
+
+mod host;
+pub mod runtime_config;
+
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+};
+
+use anyhow::ensure;
+use spin_factor_capability_policy::CapabilityChecker;
+use spin_factors::{
+    ConfigureAppContext, Factor, FactorInstanceBuilder, InitContext, PrepareContext, RuntimeFactors,
+};
+use spin_locked_app::MetadataKey;
+
+pub use host::{Broker, InstanceState, Subscription};
+pub use runtime_config::RuntimeConfig;
+pub use spin_world::wasi::messaging::messaging_types::{FormatSpec, Message};
+
+/// Metadata key for messaging brokers.
+pub const MESSAGING_BROKERS_KEY: MetadataKey<Vec<String>> = MetadataKey::new("messaging_brokers");
+
+/// A factor that provides messaging.
+#[derive(Default)]
+pub struct MessagingFactor {
+    _priv: (),
+}
+
+impl MessagingFactor {
+    /// Create a new MessagingFactor.
+    pub fn new() -> Self {
+        Self { _priv: () }
+    }
+}
+
+impl Factor for MessagingFactor {
+    type RuntimeConfig = RuntimeConfig;
+    type AppState = AppState;
+    type InstanceBuilder = InstanceBuilder;
+
+    fn init(&mut self, ctx: &mut impl InitContext<Self>) -> anyhow::Result<()> {
+        host::add_to_linker(ctx)
+    }
+
+    fn configure_app<T: RuntimeFactors>(
+        &self,
+        mut ctx: ConfigureAppContext<T, Self>,
+    ) -> anyhow::Result<Self::AppState> {
+        let runtime_config = ctx.take_runtime_config().unwrap_or_default();
+        let brokers = Arc::new(runtime_config.into_iter().collect::<HashMap<_, _>>());
+
+        // Build component -> allowed brokers map
+        let mut component_allowed_brokers = HashMap::new();
+        for component in ctx.app().components() {
+            let component_id = component.id().to_string();
+            let messaging_brokers = component
+                .get_metadata(MESSAGING_BROKERS_KEY)?
+                .unwrap_or_default()
+                .into_iter()
+                .collect::<HashSet<_>>();
+            for label in &messaging_brokers {
+                ensure!(
+                    brokers.contains_key(label),
+                    "unknown messaging_brokers label {label:?} for component {component_id:?}"
+                );
+            }
+            component_allowed_brokers.insert(component_id, messaging_brokers);
+        }
+
+        Ok(AppState {
+            brokers,
+            component_allowed_brokers,
+        })
+    }
+
+    fn prepare<T: RuntimeFactors>(
+        &self,
+        mut ctx: PrepareContext<T, Self>,
+    ) -> anyhow::Result<InstanceBuilder> {
+        let app_state = ctx.app_state();
+        let allowed_brokers = app_state
+            .component_allowed_brokers
+            .get(ctx.app_component().id())
+            .expect("component should be in component_allowed_brokers")
+            .clone();
+        let brokers = app_state.brokers.clone();
+        let capabilities = CapabilityChecker::from_prepare_context(&mut ctx)?;
+        Ok(InstanceBuilder {
+            brokers,
+            allowed_brokers,
+            capabilities,
+        })
+    }
+}
+
+pub struct AppState {
+    /// The brokers defined for the app, by label.
+    brokers: Arc<HashMap<String, Arc<dyn Broker>>>,
+    /// The allowed brokers for each component.
+    ///
+    /// This is a map from component ID to the set of broker labels that the
+    /// component is allowed to use.
+    component_allowed_brokers: HashMap<String, HashSet<String>>,
+}
+
+impl AppState {
+    /// Returns the [`Broker::summary`] for the given broker label.
+    pub fn broker_summary(&self, label: &str) -> Option<String> {
+        self.brokers.get(label)?.summary()
+    }
+
+    /// Returns true if the given broker label is used by any component.
+    pub fn broker_is_used(&self, label: &str) -> bool {
+        self.component_allowed_brokers
+            .values()
+            .any(|brokers| brokers.contains(label))
+    }
+}
+
+pub struct InstanceBuilder {
+    /// The brokers defined for the app, by label.
+    brokers: Arc<HashMap<String, Arc<dyn Broker>>>,
+    /// The allowed brokers for this component instance.
+    allowed_brokers: HashSet<String>,
+    capabilities: CapabilityChecker,
+}
+
+impl FactorInstanceBuilder for InstanceBuilder {
+    type InstanceState = InstanceState;
+
+    fn build(self) -> anyhow::Result<Self::InstanceState> {
+        Ok(InstanceState::new(
+            self.allowed_brokers,
+            self.brokers,
+            self.capabilities,
+        ))
+    }
+}
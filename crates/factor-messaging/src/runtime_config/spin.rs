@@ -0,0 +1,158 @@
+//! Runtime configuration implementation used by Spin CLI.
+
+use crate::{Broker, RuntimeConfig};
+use anyhow::Context as _;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use spin_factors::runtime_config::toml::GetTomlValue;
+use std::{collections::HashMap, sync::Arc};
+
+/// Defines the construction of a messaging broker from a serialized runtime config.
+pub trait MakeMessagingBroker: 'static + Send + Sync {
+    /// Unique type identifier for the broker.
+    const RUNTIME_CONFIG_TYPE: &'static str;
+    /// Runtime configuration for the broker.
+    type RuntimeConfig: DeserializeOwned;
+    /// The broker.
+    type Broker: Broker;
+
+    /// Creates a new broker from the runtime configuration.
+    fn make_broker(&self, runtime_config: Self::RuntimeConfig) -> anyhow::Result<Self::Broker>;
+}
+
+/// A function that creates a broker from a TOML table.
+type BrokerFromToml = Arc<dyn Fn(toml::Table) -> anyhow::Result<Arc<dyn Broker>> + Send + Sync>;
+
+/// Creates a `BrokerFromToml` function from a `MakeMessagingBroker` implementation.
+fn broker_from_toml_fn<T: MakeMessagingBroker>(broker_type: T) -> BrokerFromToml {
+    Arc::new(move |table| {
+        let runtime_config: T::RuntimeConfig = table
+            .try_into()
+            .context("could not parse messaging broker runtime config")?;
+        let broker = broker_type
+            .make_broker(runtime_config)
+            .context("could not make messaging broker from runtime config")?;
+        Ok(Arc::new(broker))
+    })
+}
+
+/// Converts from toml based runtime configuration into a [`RuntimeConfig`].
+///
+/// The various broker types (i.e., the "type" field in the toml field) are
+/// registered with the resolver using `register_broker_type`. The default
+/// broker for a label is registered using `add_default_broker`.
+#[derive(Default, Clone)]
+pub struct RuntimeConfigResolver {
+    /// A map of broker types to a function that returns the appropriate
+    /// broker from runtime config TOML.
+    broker_types: HashMap<&'static str, BrokerFromToml>,
+    /// A map of default broker configurations for a label.
+    defaults: HashMap<&'static str, BrokerConfig>,
+}
+
+impl RuntimeConfigResolver {
+    /// Create a new RuntimeConfigResolver.
+    pub fn new() -> Self {
+        <Self as Default>::default()
+    }
+
+    /// Adds a default broker configuration for a label.
+    ///
+    /// Users must ensure that the broker type for `config` has been
+    /// registered with the resolver using [`Self::register_broker_type`].
+    pub fn add_default_broker<T>(
+        &mut self,
+        label: &'static str,
+        config: T::RuntimeConfig,
+    ) -> anyhow::Result<()>
+    where
+        T: MakeMessagingBroker,
+        T::RuntimeConfig: Serialize,
+    {
+        self.defaults.insert(
+            label,
+            BrokerConfig::new(T::RUNTIME_CONFIG_TYPE.to_owned(), config)?,
+        );
+        Ok(())
+    }
+
+    /// Registers a broker type to the resolver.
+    pub fn register_broker_type<T: MakeMessagingBroker>(
+        &mut self,
+        broker_type: T,
+    ) -> anyhow::Result<()> {
+        if self
+            .broker_types
+            .insert(T::RUNTIME_CONFIG_TYPE, broker_from_toml_fn(broker_type))
+            .is_some()
+        {
+            anyhow::bail!(
+                "duplicate messaging broker type {:?}",
+                T::RUNTIME_CONFIG_TYPE
+            );
+        }
+        Ok(())
+    }
+
+    /// Resolves a toml table into a runtime config.
+    ///
+    /// The default brokers are also added to the runtime config.
+    pub fn resolve(&self, table: Option<&impl GetTomlValue>) -> anyhow::Result<RuntimeConfig> {
+        let mut runtime_config = RuntimeConfig::default();
+
+        if let Some(table) = table.and_then(|t| t.get("messaging_broker")) {
+            let table: HashMap<String, BrokerConfig> = table.clone().try_into()?;
+            for (label, config) in table {
+                let broker = self.broker_from_config(config).with_context(|| {
+                    format!("could not configure messaging broker with label '{label}'")
+                })?;
+                runtime_config.add_broker(label, broker);
+            }
+        }
+
+        for (&label, config) in &self.defaults {
+            if !runtime_config.has_broker(label) {
+                let broker = self.broker_from_config(config.clone()).with_context(|| {
+                    format!("could not configure messaging broker with label '{label}'")
+                })?;
+                runtime_config.add_broker(label.to_owned(), broker);
+            }
+        }
+        Ok(runtime_config)
+    }
+
+    /// Given a [`BrokerConfig`], returns a broker.
+    ///
+    /// Errors if there is no [`MakeMessagingBroker`] registered for the
+    /// broker config's type or if the broker cannot be created from
+    /// the config.
+    fn broker_from_config(&self, config: BrokerConfig) -> anyhow::Result<Arc<dyn Broker>> {
+        let config_type = config.type_.as_str();
+        let maker = self.broker_types.get(config_type).with_context(|| {
+            format!(
+                "the messaging broker type '{config_type}' was not registered with the config resolver"
+            )
+        })?;
+        maker(config.config)
+    }
+}
+
+#[derive(Deserialize, Clone)]
+pub struct BrokerConfig {
+    #[serde(rename = "type")]
+    pub type_: String,
+    #[serde(flatten)]
+    pub config: toml::Table,
+}
+
+impl BrokerConfig {
+    pub fn new<T>(type_: String, config: T) -> anyhow::Result<Self>
+    where
+        T: Serialize,
+    {
+        Ok(Self {
+            type_,
+            config: toml::value::Table::try_from(config)?,
+        })
+    }
+}
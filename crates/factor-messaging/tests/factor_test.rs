@@ -0,0 +1,161 @@
+use std::sync::{Arc, Mutex};
+
+use anyhow::bail;
+use futures::StreamExt as _;
+use spin_core::async_trait;
+use spin_factor_capability_policy::{CapabilityPolicyFactor, runtime_config::config_from_table};
+use spin_factor_messaging::{
+    Broker, FormatSpec, Message, MessagingFactor, RuntimeConfig, Subscription,
+};
+use spin_factors::RuntimeFactors;
+use spin_factors_test::{TestEnvironment, toml};
+use spin_world::wasi::messaging::{
+    consumer::Host as _, messaging_types::HostClient as _, producer::Host as _,
+};
+use tokio::sync::mpsc;
+
+#[derive(RuntimeFactors)]
+struct TestFactors {
+    messaging: MessagingFactor,
+}
+
+impl From<RuntimeConfig> for TestFactorsRuntimeConfig {
+    fn from(value: RuntimeConfig) -> Self {
+        Self {
+            messaging: Some(value),
+        }
+    }
+}
+
+fn test_env() -> TestEnvironment<TestFactors> {
+    TestEnvironment::new(TestFactors {
+        messaging: MessagingFactor::new(),
+    })
+    .extend_manifest(toml! {
+        [component.test-component]
+        source = "does-not-exist.wasm"
+        messaging_brokers = ["default"]
+    })
+}
+
+#[tokio::test]
+async fn errors_when_broker_is_not_defined() -> anyhow::Result<()> {
+    let Err(err) = test_env()
+        .runtime_config(RuntimeConfig::default())?
+        .build_instance_state()
+        .await
+    else {
+        bail!("expected instance build to fail but it didn't");
+    };
+
+    assert!(
+        err.to_string()
+            .contains(r#"unknown messaging_brokers label "default""#)
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn subscribers_receive_published_messages() -> anyhow::Result<()> {
+    let mut runtime_config = RuntimeConfig::default();
+    runtime_config.add_broker("default".into(), Arc::new(MemoryBroker::default()));
+    runtime_config.add_broker("other".into(), Arc::new(MemoryBroker::default()));
+    let mut state = test_env()
+        .runtime_config(runtime_config)?
+        .build_instance_state()
+        .await?;
+    let messaging = &mut state.messaging;
+
+    assert!(messaging.connect("other".into()).await?.is_err());
+    let client = messaging.connect("default".into()).await?.unwrap();
+    let borrow = || spin_core::wasmtime::component::Resource::new_borrow(client.rep());
+
+    // Nothing has been published yet; this also subscribes to the channel.
+    let received = messaging
+        .subscribe_try_receive(borrow(), "events".into(), 0)
+        .await?
+        .unwrap();
+    assert!(received.is_none());
+
+    let message = Message {
+        data: b"hello".to_vec(),
+        format: FormatSpec::Raw,
+        metadata: None,
+    };
+    messaging
+        .send(borrow(), "events".into(), vec![message])
+        .await?
+        .unwrap();
+
+    let received = messaging
+        .subscribe_receive(borrow(), "events".into())
+        .await?
+        .unwrap();
+    assert_eq!(1, received.len());
+    assert_eq!(b"hello".to_vec(), received[0].data);
+    Ok(())
+}
+
+#[derive(RuntimeFactors)]
+struct PolicyTestFactors {
+    capability_policy: CapabilityPolicyFactor,
+    messaging: MessagingFactor,
+}
+
+#[tokio::test]
+async fn capability_policy_denies_brokers() -> anyhow::Result<()> {
+    let mut runtime_config = RuntimeConfig::default();
+    runtime_config.add_broker("default".into(), Arc::new(MemoryBroker::default()));
+    let mut state = TestEnvironment::new(PolicyTestFactors {
+        capability_policy: CapabilityPolicyFactor::new(),
+        messaging: MessagingFactor::new(),
+    })
+    .extend_manifest(toml! {
+        [component.test-component]
+        source = "does-not-exist.wasm"
+        messaging_brokers = ["default"]
+    })
+    .runtime_config(PolicyTestFactorsRuntimeConfig {
+        capability_policy: config_from_table(&toml! {
+            [[capability_policy]]
+            effect = "deny"
+            operations = ["*"]
+        })?,
+        messaging: Some(runtime_config),
+    })?
+    .build_instance_state()
+    .await?;
+
+    assert!(state.messaging.connect("default".into()).await?.is_err());
+    Ok(())
+}
+
+/// A broker delivering messages to in-process subscribers.
+#[derive(Default)]
+struct MemoryBroker {
+    subscribers: Mutex<Vec<(String, mpsc::UnboundedSender<Message>)>>,
+}
+
+#[async_trait]
+impl Broker for MemoryBroker {
+    async fn publish(&self, channel: &str, message: Message) -> anyhow::Result<()> {
+        for (subscribed, tx) in self.subscribers.lock().unwrap().iter() {
+            if subscribed == channel {
+                _ = tx.send(Message {
+                    data: message.data.clone(),
+                    format: message.format,
+                    metadata: message.metadata.clone(),
+                });
+            }
+        }
+        Ok(())
+    }
+
+    async fn subscribe(&self, channel: &str) -> anyhow::Result<Subscription> {
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        self.subscribers.lock().unwrap().push((channel.into(), tx));
+        Ok(futures::stream::poll_fn(move |cx| rx.poll_recv(cx))
+            .map(Ok)
+            .boxed())
+    }
+}
@@ -0,0 +1,327 @@
+//! A bounded on-disk queue of guest telemetry that failed to export, e.g. because the OTLP
+//! endpoint was unreachable, which is retried with exponential backoff until delivered.
+
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::{
+        Arc, Mutex, Weak,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::Duration,
+};
+
+use anyhow::Context as _;
+use futures::future::BoxFuture;
+use opentelemetry_sdk::{
+    Resource,
+    error::{OTelSdkError, OTelSdkResult},
+    trace::{SpanData, SpanExporter},
+};
+use spin_telemetry::otlp::{RequestSender, Signal, encode_spans};
+use tokio::sync::Notify;
+
+use crate::runtime_config::BufferConfig;
+
+/// How long to wait before first retrying a failed export.
+const INITIAL_BACKOFF: Duration = Duration::from_secs(1);
+
+/// A bounded on-disk queue of encoded OTLP export requests, which are sent in the order they were
+/// pushed by a background task.
+///
+/// Requests are stored one per file, named by sequence number and signal, so that requests
+/// buffered by an earlier run of Spin are delivered by this one.
+#[derive(Clone, Debug)]
+pub(crate) struct ExportBuffer {
+    inner: Arc<Inner>,
+}
+
+#[derive(Debug)]
+struct Inner {
+    directory: PathBuf,
+    max_size: u64,
+    next_seq: AtomicU64,
+    /// Serializes changes to the directory's contents by pushes.
+    lock: Mutex<()>,
+    /// Wakes the replay task when a request is pushed, or when the buffer is dropped.
+    wake: Arc<Notify>,
+}
+
+impl Drop for Inner {
+    fn drop(&mut self) {
+        self.wake.notify_one();
+    }
+}
+
+impl ExportBuffer {
+    /// Opens the buffer in the configured directory and starts delivering the requests in it
+    /// with the given senders.
+    ///
+    /// This must be called from within a Tokio runtime.
+    pub(crate) fn open(config: &BufferConfig, senders: Vec<RequestSender>) -> anyhow::Result<Self> {
+        std::fs::create_dir_all(&config.directory).with_context(|| {
+            format!(
+                "failed to create telemetry buffer directory {}",
+                config.directory.display()
+            )
+        })?;
+        let next_seq = buffered_requests(&config.directory)?
+            .last()
+            .map_or(0, |request| request.seq + 1);
+        let buffer = Self {
+            inner: Arc::new(Inner {
+                directory: config.directory.clone(),
+                max_size: config.max_size_mb.saturating_mul(1024 * 1024),
+                next_seq: AtomicU64::new(next_seq),
+                lock: Mutex::new(()),
+                wake: Arc::new(Notify::new()),
+            }),
+        };
+        let senders = senders
+            .into_iter()
+            .map(|sender| (sender.signal(), sender))
+            .collect();
+        tokio::spawn(replay(
+            Arc::downgrade(&buffer.inner),
+            buffer.inner.wake.clone(),
+            senders,
+            Duration::from_secs(config.max_backoff_secs),
+        ));
+        Ok(buffer)
+    }
+
+    /// Buffers an encoded export request for delivery, dropping the oldest buffered requests if
+    /// the buffer would exceed its maximum size.
+    pub(crate) fn push(&self, signal: Signal, request: Vec<u8>) {
+        if let Err(e) = self.try_push(signal, &request) {
+            tracing::warn!(
+                "Dropping guest {} that failed to export: {e:#}",
+                signal.name()
+            );
+        }
+        self.inner.wake.notify_one();
+    }
+
+    fn try_push(&self, signal: Signal, request: &[u8]) -> anyhow::Result<()> {
+        let inner = &self.inner;
+        let _lock = inner.lock.lock().unwrap();
+        let seq = inner.next_seq.fetch_add(1, Ordering::Relaxed);
+        let path = inner.directory.join(format!("{seq:020}.{}", signal.name()));
+        // Write to a temporary file first so that the replay task never reads a partial request.
+        let tmp_path = path.with_extension("tmp");
+        std::fs::write(&tmp_path, request)
+            .with_context(|| format!("failed to write {}", tmp_path.display()))?;
+        std::fs::rename(&tmp_path, &path)
+            .with_context(|| format!("failed to write {}", path.display()))?;
+
+        let requests = buffered_requests(&inner.directory)?;
+        let mut size: u64 = requests.iter().map(|request| request.size).sum();
+        let mut dropped = 0;
+        for request in &requests {
+            if size <= inner.max_size || request.path == path {
+                break;
+            }
+            // The replay task may have just delivered it.
+            let _ = std::fs::remove_file(&request.path);
+            size -= request.size;
+            dropped += 1;
+        }
+        if dropped > 0 {
+            tracing::warn!(
+                "Telemetry buffer {} is full; dropped the {dropped} oldest buffered exports",
+                inner.directory.display()
+            );
+        }
+        Ok(())
+    }
+}
+
+/// An export request stored in the buffer.
+#[derive(Debug)]
+struct BufferedRequest {
+    seq: u64,
+    signal: Signal,
+    path: PathBuf,
+    size: u64,
+}
+
+/// Lists the requests stored in the buffer directory, oldest first.
+fn buffered_requests(directory: &Path) -> anyhow::Result<Vec<BufferedRequest>> {
+    let entries = std::fs::read_dir(directory)
+        .with_context(|| format!("failed to read {}", directory.display()))?;
+    let mut requests = vec![];
+    for entry in entries {
+        let entry = entry?;
+        let path = entry.path();
+        let Some(seq) = path
+            .file_stem()
+            .and_then(|stem| stem.to_str()?.parse().ok())
+        else {
+            continue;
+        };
+        let signal = match path.extension().and_then(|ext| ext.to_str()) {
+            Some("traces") => Signal::Traces,
+            Some("metrics") => Signal::Metrics,
+            _ => continue,
+        };
+        // The request may have been delivered or dropped since the directory was read.
+        let Ok(metadata) = entry.metadata() else {
+            continue;
+        };
+        requests.push(BufferedRequest {
+            seq,
+            signal,
+            path,
+            size: metadata.len(),
+        });
+    }
+    requests.sort_by_key(|request| request.seq);
+    Ok(requests)
+}
+
+/// Delivers buffered requests oldest first, backing off exponentially while delivery fails, until
+/// the buffer is dropped.
+async fn replay(
+    inner: Weak<Inner>,
+    wake: Arc<Notify>,
+    senders: HashMap<Signal, RequestSender>,
+    max_backoff: Duration,
+) {
+    let mut backoff = INITIAL_BACKOFF;
+    loop {
+        let oldest = match inner.upgrade() {
+            Some(inner) => buffered_requests(&inner.directory).map(|requests| {
+                requests
+                    .into_iter()
+                    .find(|request| senders.contains_key(&request.signal))
+            }),
+            None => return,
+        };
+        let request = match oldest {
+            Ok(Some(request)) => request,
+            Ok(None) => {
+                wake.notified().await;
+                continue;
+            }
+            Err(e) => {
+                tracing::warn!("Failed to read telemetry buffer: {e:#}");
+                wake.notified().await;
+                continue;
+            }
+        };
+        let Ok(body) = std::fs::read(&request.path) else {
+            // Dropped to bound the buffer since it was listed.
+            continue;
+        };
+        match senders[&request.signal].send(body).await {
+            Ok(()) => {
+                let _ = std::fs::remove_file(&request.path);
+                backoff = INITIAL_BACKOFF;
+            }
+            Err(e) => {
+                tracing::debug!(
+                    "Failed to deliver buffered guest {}, retrying in {backoff:?}: {e:#}",
+                    request.signal.name()
+                );
+                tokio::time::sleep(backoff).await;
+                backoff = (backoff * 2).min(max_backoff);
+            }
+        }
+    }
+}
+
+/// A span exporter which buffers the batches it fails to export for later delivery.
+#[derive(Debug)]
+pub(crate) struct BufferingSpanExporter<E> {
+    exporter: E,
+    buffer: ExportBuffer,
+    resource: Resource,
+}
+
+impl<E> BufferingSpanExporter<E> {
+    pub(crate) fn new(exporter: E, buffer: ExportBuffer) -> Self {
+        Self {
+            exporter,
+            buffer,
+            resource: Resource::builder_empty().build(),
+        }
+    }
+}
+
+impl<E: SpanExporter> SpanExporter for BufferingSpanExporter<E> {
+    fn export(&mut self, batch: Vec<SpanData>) -> BoxFuture<'static, OTelSdkResult> {
+        let export = self.exporter.export(batch.clone());
+        let buffer = self.buffer.clone();
+        let resource = self.resource.clone();
+        Box::pin(async move {
+            match export.await {
+                Err(OTelSdkError::AlreadyShutdown) => Err(OTelSdkError::AlreadyShutdown),
+                Err(e) => {
+                    tracing::debug!("Buffering guest spans that failed to export: {e}");
+                    buffer.push(Signal::Traces, encode_spans(batch, &resource));
+                    Ok(())
+                }
+                Ok(()) => Ok(()),
+            }
+        })
+    }
+
+    fn shutdown(&mut self) -> OTelSdkResult {
+        self.exporter.shutdown()
+    }
+
+    fn set_resource(&mut self, resource: &Resource) {
+        self.resource = resource.clone();
+        self.exporter.set_resource(resource);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn buffer(directory: &Path, max_size: u64) -> ExportBuffer {
+        ExportBuffer {
+            inner: Arc::new(Inner {
+                directory: directory.to_owned(),
+                max_size,
+                next_seq: AtomicU64::new(0),
+                lock: Mutex::new(()),
+                wake: Arc::new(Notify::new()),
+            }),
+        }
+    }
+
+    #[test]
+    fn queues_requests_in_order() {
+        let dir = tempfile::tempdir().unwrap();
+        let buffer = buffer(dir.path(), 1024);
+        buffer.push(Signal::Metrics, b"first".to_vec());
+        buffer.push(Signal::Traces, b"second".to_vec());
+        let requests = buffered_requests(dir.path()).unwrap();
+        assert_eq!(
+            vec![Signal::Metrics, Signal::Traces],
+            requests.iter().map(|r| r.signal).collect::<Vec<_>>()
+        );
+        assert_eq!(b"first".to_vec(), std::fs::read(&requests[0].path).unwrap());
+    }
+
+    #[test]
+    fn drops_oldest_requests_when_full() {
+        let dir = tempfile::tempdir().unwrap();
+        let buffer = buffer(dir.path(), 10);
+        buffer.push(Signal::Traces, b"1111".to_vec());
+        buffer.push(Signal::Traces, b"2222".to_vec());
+        buffer.push(Signal::Traces, b"3333".to_vec());
+        let requests = buffered_requests(dir.path()).unwrap();
+        assert_eq!(
+            vec![1, 2],
+            requests.iter().map(|r| r.seq).collect::<Vec<_>>()
+        );
+
+        // A request larger than the buffer is kept until the next push.
+        buffer.push(Signal::Traces, b"large request".to_vec());
+        let requests = buffered_requests(dir.path()).unwrap();
+        assert_eq!(vec![3], requests.iter().map(|r| r.seq).collect::<Vec<_>>());
+    }
+}
@@ -0,0 +1,272 @@
+//! Cardinality limiting of guest metrics, which protects the collector from guests generating
+//! unbounded attribute values.
+//!
+//! As the OTel spec recommends, each instrument exports at most `limit - 1` attribute sets as
+//! they are first seen; data points of any further attribute sets are merged into one data point
+//! with the single attribute `otel.metric.overflow = true`.
+
+use std::collections::{HashMap, HashSet};
+use std::sync::Mutex;
+
+use spin_world::wasi::otel::{
+    metrics::{self as wasi_metrics, ExponentialHistogramDataPoint, MetricData},
+    types::KeyValue,
+};
+
+use crate::views::{DataPoint, merge_gauge, merge_histogram, merge_sum};
+
+/// The attribute of the data point into which overflowing data points are merged.
+const OVERFLOW_ATTRIBUTE: &str = "otel.metric.overflow";
+
+/// Tracks the attribute sets exported by each guest instrument, limiting how many there are.
+#[derive(Debug)]
+pub(crate) struct CardinalityLimiter {
+    limit: usize,
+    /// The attribute sets exported by each instrument, by component, scope and metric name.
+    instruments: Mutex<HashMap<(String, String, String), Instrument>>,
+}
+
+#[derive(Debug, Default)]
+struct Instrument {
+    attribute_sets: HashSet<Vec<(String, String)>>,
+    /// Whether the instrument has exceeded the limit, which is logged only once.
+    overflowed: bool,
+}
+
+impl CardinalityLimiter {
+    pub(crate) fn new(limit: usize) -> Self {
+        Self {
+            limit,
+            instruments: Default::default(),
+        }
+    }
+
+    /// Merges the data points of a component's guest metrics whose attribute sets exceed the
+    /// limit into overflow data points. Returns how many data points overflowed, by metric name.
+    pub(crate) fn apply(
+        &self,
+        component_id: &str,
+        metrics: &mut wasi_metrics::ResourceMetrics,
+    ) -> Vec<(String, usize)> {
+        let mut instruments = self.instruments.lock().unwrap();
+        let mut overflows = vec![];
+        for scope_metrics in &mut metrics.scope_metrics {
+            for metric in &mut scope_metrics.metrics {
+                let key = (
+                    component_id.to_owned(),
+                    scope_metrics.scope.name.clone(),
+                    metric.name.clone(),
+                );
+                let instrument = instruments.entry(key).or_default();
+                let overflowed = self.limit_metric(instrument, &mut metric.data);
+                if overflowed == 0 {
+                    continue;
+                }
+                if !instrument.overflowed {
+                    instrument.overflowed = true;
+                    tracing::warn!(
+                        "Guest metric {} of component {component_id:?} has exceeded the cardinality limit of {} attribute sets; further attribute sets are exported as {OVERFLOW_ATTRIBUTE}",
+                        metric.name,
+                        self.limit
+                    );
+                }
+                overflows.push((metric.name.clone(), overflowed));
+            }
+        }
+        overflows
+    }
+
+    fn limit_metric(&self, instrument: &mut Instrument, data: &mut MetricData) -> usize {
+        match data {
+            MetricData::F64Gauge(gauge)
+            | MetricData::S64Gauge(gauge)
+            | MetricData::U64Gauge(gauge) => {
+                self.limit_data_points(instrument, &mut gauge.data_points, merge_gauge)
+            }
+            MetricData::F64Sum(sum) | MetricData::S64Sum(sum) | MetricData::U64Sum(sum) => {
+                self.limit_data_points(instrument, &mut sum.data_points, merge_sum)
+            }
+            MetricData::F64Histogram(histogram)
+            | MetricData::S64Histogram(histogram)
+            | MetricData::U64Histogram(histogram) => {
+                self.limit_data_points(instrument, &mut histogram.data_points, merge_histogram)
+            }
+            MetricData::F64ExponentialHistogram(histogram)
+            | MetricData::S64ExponentialHistogram(histogram)
+            | MetricData::U64ExponentialHistogram(histogram) => {
+                // Exponential histograms aren't merged, so only the first overflowing data point
+                // is exported.
+                self.limit_data_points(instrument, &mut histogram.data_points, |_, dp| Some(dp))
+            }
+        }
+    }
+
+    /// Merges the data points beyond the limit into an overflow data point, returning how many
+    /// there were.
+    ///
+    /// `merge` merges a data point into another, returning it if they cannot be merged, in which
+    /// case it is dropped.
+    fn limit_data_points<T: DataPoint>(
+        &self,
+        instrument: &mut Instrument,
+        data_points: &mut Vec<T>,
+        merge: fn(&mut T, T) -> Option<T>,
+    ) -> usize {
+        let mut overflow: Option<T> = None;
+        let mut overflowed = 0;
+        let mut kept = Vec::with_capacity(data_points.len());
+        for mut dp in std::mem::take(data_points) {
+            let attribute_set = attribute_set(dp.attributes());
+            if instrument.attribute_sets.contains(&attribute_set)
+                || (instrument.attribute_sets.len() + 1 < self.limit
+                    && instrument.attribute_sets.insert(attribute_set))
+            {
+                kept.push(dp);
+                continue;
+            }
+            overflowed += 1;
+            *dp.attributes_mut() = vec![KeyValue {
+                key: OVERFLOW_ATTRIBUTE.to_owned(),
+                value: "true".to_owned(),
+            }];
+            match &mut overflow {
+                Some(existing) => {
+                    if merge(existing, dp).is_some() {
+                        tracing::debug!(
+                            "Dropping a guest data point exceeding the cardinality limit"
+                        );
+                    }
+                }
+                None => overflow = Some(dp),
+            }
+        }
+        kept.extend(overflow);
+        *data_points = kept;
+        overflowed
+    }
+}
+
+/// Identifies a set of attributes regardless of their order.
+fn attribute_set(attributes: &[KeyValue]) -> Vec<(String, String)> {
+    let mut set: Vec<_> = attributes
+        .iter()
+        .map(|kv| (kv.key.clone(), kv.value.clone()))
+        .collect();
+    set.sort();
+    set
+}
+
+impl DataPoint for ExponentialHistogramDataPoint {
+    fn attributes(&self) -> &[KeyValue] {
+        &self.attributes
+    }
+
+    fn attributes_mut(&mut self) -> &mut Vec<KeyValue> {
+        &mut self.attributes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use spin_world::wasi::{
+        clocks0_2_0::wall_clock::Datetime,
+        otel::metrics::{MetricNumber, SumDataPoint},
+    };
+
+    use super::*;
+
+    const TIME: Datetime = Datetime {
+        seconds: 1,
+        nanoseconds: 0,
+    };
+
+    fn sum_point(user: &str, value: u64) -> SumDataPoint {
+        SumDataPoint {
+            attributes: vec![KeyValue {
+                key: "user".into(),
+                value: format!("{user:?}"),
+            }],
+            exemplars: vec![],
+            value: MetricNumber::U64(value),
+        }
+    }
+
+    fn resource_metrics(data_points: Vec<SumDataPoint>) -> wasi_metrics::ResourceMetrics {
+        wasi_metrics::ResourceMetrics {
+            resource: wasi_metrics::Resource {
+                attributes: vec![],
+                schema_url: None,
+            },
+            scope_metrics: vec![wasi_metrics::ScopeMetrics {
+                scope: wasi_metrics::InstrumentationScope {
+                    name: "test".into(),
+                    version: None,
+                    schema_url: None,
+                    attributes: vec![],
+                },
+                metrics: vec![wasi_metrics::Metric {
+                    name: "logins".into(),
+                    description: String::new(),
+                    unit: String::new(),
+                    data: MetricData::U64Sum(wasi_metrics::Sum {
+                        data_points,
+                        start_time: TIME,
+                        time: TIME,
+                        temporality: wasi_metrics::Temporality::Cumulative,
+                        is_monotonic: true,
+                    }),
+                }],
+            }],
+        }
+    }
+
+    fn values(metrics: &wasi_metrics::ResourceMetrics) -> Vec<(String, u64)> {
+        let MetricData::U64Sum(sum) = &metrics.scope_metrics[0].metrics[0].data else {
+            panic!("unexpected metric data");
+        };
+        sum.data_points
+            .iter()
+            .map(|dp| {
+                let MetricNumber::U64(value) = dp.value else {
+                    panic!("unexpected value");
+                };
+                (dp.attributes[0].value.clone(), value)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn merges_overflowing_attribute_sets() {
+        let limiter = CardinalityLimiter::new(3);
+        let mut metrics = resource_metrics(vec![
+            sum_point("alice", 1),
+            sum_point("bob", 2),
+            sum_point("carol", 3),
+            sum_point("dave", 4),
+        ]);
+        assert_eq!(
+            vec![("logins".to_owned(), 2)],
+            limiter.apply("app", &mut metrics)
+        );
+        assert_eq!(
+            vec![
+                ("\"alice\"".to_owned(), 1),
+                ("\"bob\"".to_owned(), 2),
+                ("true".to_owned(), 7)
+            ],
+            values(&metrics)
+        );
+
+        // Attribute sets seen before are still exported on their own.
+        let mut metrics = resource_metrics(vec![sum_point("bob", 5), sum_point("erin", 6)]);
+        limiter.apply("app", &mut metrics);
+        assert_eq!(
+            vec![("\"bob\"".to_owned(), 5), ("true".to_owned(), 6)],
+            values(&metrics)
+        );
+
+        // Each component's instruments have their own limit.
+        let mut metrics = resource_metrics(vec![sum_point("erin", 6)]);
+        assert!(limiter.apply("other", &mut metrics).is_empty());
+    }
+}
@@ -0,0 +1,227 @@
+//! Printing of guest telemetry in the terminal for local development, alongside the host
+//! telemetry printed by [`spin_telemetry::console`].
+
+use std::fmt::Write as _;
+
+use opentelemetry_sdk::trace::SpanData;
+use spin_world::wasi::otel::{
+    metrics::{self as wasi_metrics, MetricData, MetricNumber},
+    types::KeyValue,
+};
+
+/// Buffers the ended guest spans of an instance until they can be printed as complete trees.
+pub(crate) struct ConsoleSpans {
+    component_id: String,
+    spans: Vec<SpanData>,
+}
+
+impl ConsoleSpans {
+    pub(crate) fn new(component_id: String) -> Self {
+        Self {
+            component_id,
+            spans: Vec::new(),
+        }
+    }
+
+    /// Buffers an ended span.
+    pub(crate) fn push(&mut self, span: SpanData) {
+        self.spans.push(span);
+    }
+
+    /// Prints the buffered spans to stderr.
+    pub(crate) fn flush(&mut self) {
+        if self.spans.is_empty() {
+            return;
+        }
+        eprint!(
+            "Guest spans of component {}:\n{}",
+            self.component_id,
+            spin_telemetry::console::render_spans(&self.spans)
+        );
+        self.spans.clear();
+    }
+}
+
+/// Prints guest metrics to stderr.
+pub(crate) fn print_metrics(component_id: &str, metrics: &wasi_metrics::ResourceMetrics) {
+    eprint!("{}", render_metrics(component_id, metrics));
+}
+
+/// Renders guest metrics, one line per data point.
+fn render_metrics(component_id: &str, metrics: &wasi_metrics::ResourceMetrics) -> String {
+    let mut out = format!("Guest metrics of component {component_id}:\n");
+    let metrics = metrics
+        .scope_metrics
+        .iter()
+        .flat_map(|scope_metrics| &scope_metrics.metrics);
+    for metric in metrics {
+        let name = &metric.name;
+        let _ = match &metric.data {
+            MetricData::F64Gauge(gauge)
+            | MetricData::S64Gauge(gauge)
+            | MetricData::U64Gauge(gauge) => gauge.data_points.iter().try_for_each(|dp| {
+                writeln!(
+                    out,
+                    "  {name}{}: {}",
+                    attributes(&dp.attributes),
+                    number(dp.value)
+                )
+            }),
+            MetricData::F64Sum(sum) | MetricData::S64Sum(sum) | MetricData::U64Sum(sum) => {
+                sum.data_points.iter().try_for_each(|dp| {
+                    writeln!(
+                        out,
+                        "  {name}{}: {}",
+                        attributes(&dp.attributes),
+                        number(dp.value)
+                    )
+                })
+            }
+            MetricData::F64Histogram(histogram)
+            | MetricData::S64Histogram(histogram)
+            | MetricData::U64Histogram(histogram) => {
+                histogram.data_points.iter().try_for_each(|dp| {
+                    writeln!(
+                        out,
+                        "  {name}{}: {}",
+                        attributes(&dp.attributes),
+                        distribution(dp.count, dp.sum, dp.min, dp.max)
+                    )
+                })
+            }
+            MetricData::F64ExponentialHistogram(histogram)
+            | MetricData::S64ExponentialHistogram(histogram)
+            | MetricData::U64ExponentialHistogram(histogram) => {
+                histogram.data_points.iter().try_for_each(|dp| {
+                    writeln!(
+                        out,
+                        "  {name}{}: {}",
+                        attributes(&dp.attributes),
+                        distribution(dp.count, dp.sum, dp.min, dp.max)
+                    )
+                })
+            }
+        };
+    }
+    out
+}
+
+/// Summarizes a histogram data point in the format of host metric summaries.
+fn distribution(
+    count: u64,
+    sum: MetricNumber,
+    min: Option<MetricNumber>,
+    max: Option<MetricNumber>,
+) -> String {
+    let mean = if count == 0 {
+        0.0
+    } else {
+        number(sum) / count as f64
+    };
+    let mut summary = format!("count={count} mean={mean:.4}");
+    if let Some(min) = min {
+        let _ = write!(summary, " min={:.4}", number(min));
+    }
+    if let Some(max) = max {
+        let _ = write!(summary, " max={:.4}", number(max));
+    }
+    summary
+}
+
+/// Renders attributes in braces, as in the Prometheus text format.
+fn attributes(attributes: &[KeyValue]) -> String {
+    if attributes.is_empty() {
+        return String::new();
+    }
+    let attributes: Vec<_> = attributes
+        .iter()
+        .map(|attribute| {
+            let attribute: opentelemetry::KeyValue = attribute.into();
+            format!("{}={}", attribute.key, attribute.value)
+        })
+        .collect();
+    format!("{{{}}}", attributes.join(","))
+}
+
+fn number(number: MetricNumber) -> f64 {
+    match number {
+        MetricNumber::F64(n) => n,
+        MetricNumber::S64(n) => n as f64,
+        MetricNumber::U64(n) => n as f64,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use spin_world::wasi::clocks0_2_0::wall_clock::Datetime;
+
+    use super::*;
+
+    const TIME: Datetime = Datetime {
+        seconds: 1,
+        nanoseconds: 0,
+    };
+
+    #[test]
+    fn renders_metrics() {
+        let metrics = wasi_metrics::ResourceMetrics {
+            resource: wasi_metrics::Resource {
+                attributes: vec![],
+                schema_url: None,
+            },
+            scope_metrics: vec![wasi_metrics::ScopeMetrics {
+                scope: wasi_metrics::InstrumentationScope {
+                    name: "test".into(),
+                    version: None,
+                    schema_url: None,
+                    attributes: vec![],
+                },
+                metrics: vec![
+                    wasi_metrics::Metric {
+                        name: "orders.placed".into(),
+                        description: String::new(),
+                        unit: String::new(),
+                        data: MetricData::U64Sum(wasi_metrics::Sum {
+                            data_points: vec![wasi_metrics::SumDataPoint {
+                                attributes: vec![KeyValue {
+                                    key: "http.route".into(),
+                                    value: "\"/orders\"".into(),
+                                }],
+                                exemplars: vec![],
+                                value: MetricNumber::U64(3),
+                            }],
+                            start_time: TIME,
+                            time: TIME,
+                            temporality: wasi_metrics::Temporality::Cumulative,
+                            is_monotonic: true,
+                        }),
+                    },
+                    wasi_metrics::Metric {
+                        name: "latency".into(),
+                        description: String::new(),
+                        unit: String::new(),
+                        data: MetricData::F64Histogram(wasi_metrics::Histogram {
+                            data_points: vec![wasi_metrics::HistogramDataPoint {
+                                attributes: vec![],
+                                bounds: vec![],
+                                bucket_counts: vec![2],
+                                exemplars: vec![],
+                                count: 2,
+                                max: Some(MetricNumber::F64(1.5)),
+                                min: Some(MetricNumber::F64(0.5)),
+                                sum: MetricNumber::F64(2.0),
+                            }],
+                            start_time: TIME,
+                            time: TIME,
+                            temporality: wasi_metrics::Temporality::Delta,
+                        }),
+                    },
+                ],
+            }],
+        };
+        assert_eq!(
+            "Guest metrics of component api:\n  orders.placed{http.route=/orders}: 3\n  latency: count=2 mean=1.0000 min=0.5000 max=1.5000\n",
+            render_metrics("api", &metrics)
+        );
+    }
+}
@@ -1,12 +1,16 @@
-use crate::InstanceState;
+use crate::{InstanceState, copy_baggage};
 use anyhow::Result;
 use anyhow::anyhow;
+use opentelemetry::baggage::{Baggage, BaggageExt};
 use opentelemetry::trace::TraceContextExt;
+use opentelemetry::{InstrumentationScope, KeyValue};
+use opentelemetry_sdk::Resource;
 use opentelemetry_sdk::error::OTelSdkError;
 use opentelemetry_sdk::logs::LogProcessor;
+use opentelemetry_sdk::metrics::data::ResourceMetrics;
 use opentelemetry_sdk::metrics::exporter::PushMetricExporter;
-use opentelemetry_sdk::trace::SpanProcessor;
-use spin_world::wasi;
+use spin_telemetry::otlp::{Signal, encode_metrics};
+use spin_world::{spin, wasi};
 use tracing_opentelemetry::OpenTelemetrySpanExt;
 
 impl wasi::otel::tracing::Host for InstanceState {
@@ -55,7 +59,25 @@ impl wasi::otel::tracing::Host for InstanceState {
             Err(anyhow!("Trying to end a span that was not started"))?;
         }
 
-        tracing_state.span_processor.on_end(span_data.into());
+        // Head sample the guest's spans before doing the work of converting them
+        if !tracing_state.is_sampled(span_context.trace_id()) {
+            return Ok(());
+        }
+
+        let (mut span_data, limited) =
+            crate::limits::convert_span(&tracing_state.span_limits, span_data);
+        for (limit, count) in limited.by_limit() {
+            if count > 0 {
+                spin_telemetry::metrics::monotonic_counter!(
+                    spin.guest_span_limit_exceeded = count as u64,
+                    component_id = tracing_state.component_id.as_str(),
+                    limit = limit
+                );
+            }
+        }
+        span_data.instrumentation_scope = self.component_scope(span_data.instrumentation_scope);
+        spin_telemetry::policy::export_policy().redact_span(&mut span_data);
+        tracing_state.export(span_data);
 
         Ok(())
     }
@@ -70,17 +92,99 @@ impl wasi::otel::tracing::Host for InstanceState {
     }
 }
 
+impl spin::otel::tracing::Host for InstanceState {
+    async fn inbound_span_context(&mut self) -> Result<Option<wasi::otel::tracing::SpanContext>> {
+        Ok(spin_telemetry::inbound_span_context().map(Into::into))
+    }
+}
+
 impl wasi::otel::metrics::Host for InstanceState {
     async fn export(
         &mut self,
-        metrics: wasi::otel::metrics::ResourceMetrics,
+        mut metrics: wasi::otel::metrics::ResourceMetrics,
     ) -> anyhow::Result<Result<(), wasi::otel::metrics::Error>> {
-        // If the host does not have metrics enabled we just no-op
+        // Redact before applying views, so that views merge data points by exported attributes
+        crate::redaction::redact_metrics(&spin_telemetry::policy::export_policy(), &mut metrics);
+        crate::views::apply_views(&self.metric_views, &mut metrics);
+        // Limit cardinality after applying views, which may drop high-cardinality attributes
+        if let (Some(limiter), Some(component_id)) = (&self.cardinality_limiter, &self.component_id)
+        {
+            for (metric, overflowed) in limiter.apply(component_id, &mut metrics) {
+                spin_telemetry::metrics::monotonic_counter!(
+                    spin.guest_metric_cardinality_overflows = overflowed as u64,
+                    component_id = component_id.as_str(),
+                    metric = metric.as_str()
+                );
+            }
+        }
+        self.add_component_attributes(&mut metrics);
+
+        // Serve the guest's metrics alongside host metrics if the Prometheus endpoint is enabled
+        if let Some(component_id) = &self.component_id
+            && spin_telemetry::metrics::prometheus_enabled()
+        {
+            spin_telemetry::metrics::record_guest_metrics(
+                component_id,
+                crate::prometheus_conversions::metric_families(&metrics),
+            );
+        }
+
+        if let Some(component_id) = &self.component_id
+            && self.console
+        {
+            crate::console::print_metrics(component_id, &metrics);
+        }
+
+        // If the host does not have OTLP metrics enabled we just no-op
         let Some(metric_exporter) = self.metric_exporter.as_ref() else {
             return Ok(Ok(()));
         };
 
-        match metric_exporter.export(&mut metrics.into()).await {
+        let malformed_exemplars = spin_world::wasi_otel::malformed_exemplar_count(&metrics);
+        if malformed_exemplars > 0 {
+            tracing::debug!(
+                "Unlinking {malformed_exemplars} guest exemplars with malformed span or trace IDs from their traces"
+            );
+            spin_telemetry::metrics::monotonic_counter!(
+                spin.malformed_guest_exemplars = malformed_exemplars as u64,
+                component_id = self.component_id.as_deref().unwrap_or_default()
+            );
+        }
+
+        let mut metrics: ResourceMetrics = match metrics.try_into() {
+            Ok(metrics) => metrics,
+            Err(e) => {
+                let msg = format!("Invalid metric data: {e}");
+                tracing::error!(msg);
+                return Ok(Err(msg));
+            }
+        };
+
+        // Describe guest metrics by the app's resource, as guest spans and logs are, keeping any
+        // attributes of the guest's resource that it doesn't set
+        if let Some(resource) = &self.resource {
+            let attributes = |resource: &Resource| {
+                resource
+                    .iter()
+                    .map(|(key, value)| KeyValue::new(key.clone(), value.clone()))
+                    .collect::<Vec<_>>()
+            };
+            metrics.resource = Resource::builder_empty()
+                .with_attributes(attributes(&metrics.resource))
+                .with_attributes(attributes(resource))
+                .build();
+        }
+
+        let result = metric_exporter.export(&mut metrics).await;
+        // Keep metrics that failed to export for later delivery, if they are buffered
+        if let Err(e @ (OTelSdkError::InternalFailure(_) | OTelSdkError::Timeout(_))) = &result
+            && let Some(export_buffer) = &self.export_buffer
+        {
+            tracing::debug!("Buffering guest metrics that failed to export: {e}");
+            export_buffer.push(Signal::Metrics, encode_metrics(&metrics));
+            return Ok(Ok(()));
+        }
+        match result {
             Ok(_) => Ok(Ok(())),
             Err(e) => match e {
                 OTelSdkError::AlreadyShutdown => {
@@ -104,14 +208,139 @@ impl wasi::otel::metrics::Host for InstanceState {
 }
 
 impl wasi::otel::logs::Host for InstanceState {
-    async fn on_emit(&mut self, data: wasi::otel::logs::LogRecord) -> anyhow::Result<()> {
+    async fn on_emit(&mut self, mut data: wasi::otel::logs::LogRecord) -> anyhow::Result<()> {
         // If the host does not have logs enabled we just no-op
         let Some(log_processor) = self.log_processor.as_ref() else {
             return Ok(());
         };
 
+        crate::redaction::redact_log_record(&spin_telemetry::policy::export_policy(), &mut data);
+
         let (mut record, scope) = spin_world::wasi_otel::parse_wasi_log_record(data);
-        log_processor.emit(&mut record, &scope);
+        log_processor.emit(&mut record, &self.component_scope(scope));
         Ok(())
     }
 }
+
+impl InstanceState {
+    /// Adds the component's telemetry attributes to an instrumentation scope of its guest
+    /// telemetry, replacing those the guest set with the same keys.
+    fn component_scope(&self, scope: InstrumentationScope) -> InstrumentationScope {
+        if self.component_attributes.is_empty() {
+            return scope;
+        }
+        let attributes = scope
+            .attributes()
+            .filter(|attribute| !self.is_component_attribute(attribute.key.as_str()))
+            .chain(&self.component_attributes)
+            .cloned();
+        let mut builder =
+            InstrumentationScope::builder(scope.name().to_owned()).with_attributes(attributes);
+        if let Some(version) = scope.version() {
+            builder = builder.with_version(version.to_owned());
+        }
+        if let Some(schema_url) = scope.schema_url() {
+            builder = builder.with_schema_url(schema_url.to_owned());
+        }
+        builder.build()
+    }
+
+    /// Adds the component's telemetry attributes to the instrumentation scopes of guest metrics.
+    fn add_component_attributes(&self, metrics: &mut wasi::otel::metrics::ResourceMetrics) {
+        if self.component_attributes.is_empty() {
+            return;
+        }
+        for scope_metrics in &mut metrics.scope_metrics {
+            let attributes = &mut scope_metrics.scope.attributes;
+            attributes.retain(|attribute| !self.is_component_attribute(&attribute.key));
+            attributes.extend(self.component_attributes.iter().map(|attribute| {
+                wasi::otel::types::KeyValue {
+                    key: attribute.key.to_string(),
+                    // Guest attribute values are JSON-encoded
+                    value: serde_json::to_string(&attribute.value.as_str()).unwrap(),
+                }
+            }));
+        }
+    }
+
+    fn is_component_attribute(&self, key: &str) -> bool {
+        self.component_attributes
+            .iter()
+            .any(|attribute| attribute.key.as_str() == key)
+    }
+
+    /// Returns the current baggage: that modified by the guest if any, otherwise that of the
+    /// current context.
+    fn current_baggage(&self) -> Baggage {
+        match self.guest_baggage.read().unwrap().as_ref() {
+            Some(baggage) => copy_baggage(baggage),
+            None => copy_baggage(tracing::Span::current().context().baggage()),
+        }
+    }
+
+    /// Applies a guest modification to the current baggage.
+    fn modify_baggage(&mut self, f: impl FnOnce(&mut Baggage)) {
+        let mut baggage = self.current_baggage();
+        f(&mut baggage);
+        *self.guest_baggage.write().unwrap() = Some(baggage);
+    }
+}
+
+impl spin::otel::baggage::Host for InstanceState {
+    async fn get_all(&mut self) -> Result<Vec<spin::otel::baggage::Entry>> {
+        Ok(spin_world::wasi_otel::baggage_entries(
+            &self.current_baggage(),
+        ))
+    }
+
+    async fn get(&mut self, key: String) -> Result<Option<String>> {
+        Ok(self
+            .current_baggage()
+            .get(key)
+            .map(|value| value.to_string()))
+    }
+
+    async fn set(&mut self, key: String, value: String, metadata: Option<String>) -> Result<()> {
+        self.modify_baggage(|baggage| {
+            baggage.insert_with_metadata(key, value, metadata.unwrap_or_default());
+        });
+        Ok(())
+    }
+
+    async fn remove(&mut self, key: String) -> Result<()> {
+        self.modify_baggage(|baggage| {
+            baggage.remove(key);
+        });
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn adds_component_attributes_to_scopes() {
+        let state = InstanceState {
+            component_attributes: vec![KeyValue::new("team", "payments")],
+            ..Default::default()
+        };
+        let scope = InstrumentationScope::builder("orders")
+            .with_version("1.0")
+            .with_attributes([
+                KeyValue::new("team", "guest-set"),
+                KeyValue::new("library", "http"),
+            ])
+            .build();
+        let scope = state.component_scope(scope);
+        assert_eq!("orders", scope.name());
+        assert_eq!(Some("1.0"), scope.version());
+        assert_eq!(
+            vec![
+                KeyValue::new("library", "http"),
+                KeyValue::new("team", "payments")
+            ],
+            scope.attributes().cloned().collect::<Vec<_>>()
+        );
+    }
+}
@@ -1,37 +1,72 @@
+mod buffer;
+mod cardinality;
+mod console;
 mod host;
+mod limits;
+mod prometheus_conversions;
+mod redaction;
+pub mod runtime_config;
+mod sampling;
+mod views;
 
-use anyhow::bail;
 use indexmap::IndexMap;
 use opentelemetry::{
-    Context,
-    trace::{SpanContext, SpanId, TraceContextExt},
+    Context, KeyValue,
+    baggage::Baggage,
+    trace::{SpanContext, SpanId, TraceContextExt, TraceId},
 };
 use opentelemetry_otlp::MetricExporter;
 use opentelemetry_sdk::{
     Resource,
     logs::{LogProcessor, log_processor_with_async_runtime::BatchLogProcessor},
-    resource::{EnvResourceDetector, ResourceDetector, TelemetryResourceDetector},
     runtime::Tokio,
-    trace::{SpanProcessor, span_processor_with_async_runtime::BatchSpanProcessor},
+    trace::{SpanData, SpanProcessor, span_processor_with_async_runtime::BatchSpanProcessor},
 };
 use spin_factors::{Factor, FactorData, PrepareContext, RuntimeFactors, SelfInstanceBuilder};
+use spin_locked_app::MetadataKey;
 use spin_telemetry::{
-    detector::SpinResourceDetector,
-    env::{OtlpProtocol, otel_logs_enabled, otel_metrics_enabled, otel_tracing_enabled},
+    detector::detected_resource,
+    env::otel_console_enabled,
+    otlp::{OtlpConnection, Signal},
+    policy::ExportPolicy,
+};
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::{Arc, RwLock},
+    time::Duration,
 };
-use std::sync::{Arc, RwLock};
 use tracing_opentelemetry::OpenTelemetrySpanExt;
 
+use crate::buffer::{BufferingSpanExporter, ExportBuffer};
+use crate::cardinality::CardinalityLimiter;
+use crate::console::ConsoleSpans;
+use crate::runtime_config::{BufferConfig, SpanBatchConfig, SpanLimits};
+use crate::sampling::TailSampler;
+pub use crate::sampling::TailSamplingPolicy;
+pub use crate::views::MetricView;
+
+/// The maximum number of attribute sets exported by each guest instrument, as the OTel SDK
+/// defaults to.
+const DEFAULT_METRIC_CARDINALITY_LIMIT: usize = 2000;
+
+/// Attributes describing the app in the telemetry it exports, from its manifest.
+pub const TELEMETRY_RESOURCE_ATTRIBUTES_KEY: MetadataKey<HashMap<String, String>> =
+    MetadataKey::new("telemetry_resource_attributes");
+
+/// Attributes describing a component in the telemetry it exports, from its manifest.
+pub const TELEMETRY_ATTRIBUTES_KEY: MetadataKey<BTreeMap<String, String>> =
+    MetadataKey::new("telemetry_attributes");
+
 pub struct OtelFactor {
-    span_processor: Option<Arc<BatchSpanProcessor<Tokio>>>,
-    metric_exporter: Option<Arc<MetricExporter>>,
-    log_processor: Option<Arc<BatchLogProcessor<Tokio>>>,
+    spin_version: String,
     enable_interface: bool,
+    /// Whether guest telemetry is printed in the terminal.
+    console: bool,
 }
 
 impl Factor for OtelFactor {
-    type RuntimeConfig = ();
-    type AppState = ();
+    type RuntimeConfig = runtime_config::RuntimeConfig;
+    type AppState = AppState;
     type InstanceBuilder = InstanceState;
 
     fn init(&mut self, ctx: &mut impl spin_factors::InitContext<Self>) -> anyhow::Result<()> {
@@ -47,149 +82,315 @@ impl Factor for OtelFactor {
                 spin_world::wasi::otel::metrics::add_to_linker::<_, FactorData<Self>>,
             )?;
             ctx.link_bindings(spin_world::wasi::otel::logs::add_to_linker::<_, FactorData<Self>>)?;
+            ctx.link_bindings(
+                spin_world::spin::otel::baggage::add_to_linker::<_, FactorData<Self>>,
+            )?;
+            ctx.link_bindings(
+                spin_world::spin::otel::tracing::add_to_linker::<_, FactorData<Self>>,
+            )?;
         }
         Ok(())
     }
 
     fn configure_app<T: spin_factors::RuntimeFactors>(
         &self,
-        _ctx: spin_factors::ConfigureAppContext<T, Self>,
+        mut ctx: spin_factors::ConfigureAppContext<T, Self>,
     ) -> anyhow::Result<Self::AppState> {
-        Ok(())
+        let runtime_config = ctx.take_runtime_config().unwrap_or_default();
+        let export_policy = runtime_config.export_policy;
+        for component_id in &export_policy.disabled_components {
+            if ctx.app().get_component(component_id).is_none() {
+                tracing::warn!(
+                    "Telemetry is disabled for component {component_id:?}, which is not in the app"
+                );
+            }
+        }
+        for component_id in runtime_config.component_sample_ratios.keys() {
+            if ctx.app().get_component(component_id).is_none() {
+                tracing::warn!(
+                    "A sample ratio is set for component {component_id:?}, which is not in the app"
+                );
+            }
+        }
+        // Host telemetry is recorded outside of any instance, so it follows the policy globally.
+        spin_telemetry::policy::set_export_policy(export_policy.clone());
+        // Likewise, trace context is propagated by the host rather than by instances.
+        if let Some(propagators) = &runtime_config.propagators {
+            spin_telemetry::propagators::set_propagators(propagators);
+        }
+        let resource = self.resource(
+            ctx.app()
+                .get_metadata(TELEMETRY_RESOURCE_ATTRIBUTES_KEY)?
+                .unwrap_or_default(),
+        );
+        // Exporters are built here rather than in `new` so that runtime config can tune them.
+        let (span_processor, metric_exporter, log_processor, export_buffer) =
+            if self.enable_interface {
+                let connection = OtlpConnection::from_env().with_overrides(runtime_config.otlp);
+                let export_buffer = match &runtime_config.buffer {
+                    Some(config) => Self::export_buffer(&connection, config)?,
+                    None => None,
+                };
+                (
+                    Self::span_processor(
+                        &connection,
+                        &runtime_config.span_batch,
+                        &resource,
+                        export_buffer.as_ref(),
+                    )?,
+                    Self::metric_exporter(&connection, runtime_config.metrics_export_timeout)?,
+                    Self::log_processor(&connection, &resource)?,
+                    export_buffer,
+                )
+            } else {
+                (None, None, None, None)
+            };
+        Ok(AppState {
+            resource,
+            span_processor,
+            metric_exporter,
+            log_processor,
+            export_buffer,
+            export_policy,
+            tail_sampling: runtime_config.tail_sampling.map(Arc::new),
+            component_sample_ratios: runtime_config.component_sample_ratios,
+            span_limits: runtime_config.span_limits,
+            metric_views: Arc::new(runtime_config.metric_views),
+            cardinality_limiter: Arc::new(CardinalityLimiter::new(
+                runtime_config
+                    .metric_cardinality_limit
+                    .unwrap_or(DEFAULT_METRIC_CARDINALITY_LIMIT),
+            )),
+        })
     }
 
     fn prepare<T: spin_factors::RuntimeFactors>(
         &self,
-        _: spin_factors::PrepareContext<T, Self>,
+        ctx: spin_factors::PrepareContext<T, Self>,
     ) -> anyhow::Result<Self::InstanceBuilder> {
-        if !self.enable_interface {
+        if !self.enable_interface
+            || !ctx
+                .app_state()
+                .export_policy
+                .exports_component(ctx.app_component().id())
+        {
             return Ok(InstanceState::default());
         }
 
         // Warn the user if they enabled experimental support but didn't supply any environment variables
-        if self.span_processor.is_none()
-            && self.metric_exporter.is_none()
-            && self.log_processor.is_none()
+        let app_state = ctx.app_state();
+        if app_state.span_processor.is_none()
+            && app_state.metric_exporter.is_none()
+            && app_state.log_processor.is_none()
+            && !self.console
+            && !spin_telemetry::metrics::prometheus_enabled()
+            && !spin_telemetry::recent_traces::enabled()
         {
             tracing::warn!(
-                "WASI OTel experimental support is enabled but no OTEL_EXPORTER_* environment variables were found. No telemetry will be exported."
+                "WASI OTel experimental support is enabled but no OTLP endpoint was found in the OTEL_EXPORTER_* environment variables or runtime config. No telemetry will be exported."
             );
         }
 
+        let component_id = ctx.app_component().id();
+        let component_attributes = ctx
+            .app_component()
+            .get_metadata(TELEMETRY_ATTRIBUTES_KEY)?
+            .unwrap_or_default()
+            .into_iter()
+            .map(|(key, value)| KeyValue::new(key, value))
+            .collect();
+        let tracing_enabled = app_state.span_processor.is_some()
+            || self.console
+            || spin_telemetry::recent_traces::enabled();
         Ok(InstanceState {
-            tracing_state: self.span_processor.as_ref().map(|span_processor| {
+            tracing_state: tracing_enabled.then(|| {
                 Arc::new(RwLock::new(TracingState {
+                    component_id: component_id.to_owned(),
                     guest_span_contexts: Default::default(),
                     original_host_span_id: None,
-                    span_processor: span_processor.clone(),
+                    span_limits: app_state.span_limits,
+                    span_processor: app_state.span_processor.clone(),
+                    sample_ratio: app_state.component_sample_ratios.get(component_id).copied(),
+                    tail_sampler: app_state.tail_sampling.clone().map(TailSampler::new),
+                    console_spans: self
+                        .console
+                        .then(|| ConsoleSpans::new(component_id.to_owned())),
                 }))
             }),
-            metric_exporter: self.metric_exporter.clone(),
-            log_processor: self.log_processor.clone(),
+            resource: Some(app_state.resource.clone()),
+            metric_exporter: app_state.metric_exporter.clone(),
+            export_buffer: app_state.export_buffer.clone(),
+            metric_views: app_state.metric_views.clone(),
+            cardinality_limiter: Some(app_state.cardinality_limiter.clone()),
+            log_processor: app_state.log_processor.clone(),
+            guest_baggage: Default::default(),
+            component_id: Some(component_id.to_owned()),
+            component_attributes,
+            console: self.console,
         })
     }
 }
 
 impl OtelFactor {
     pub fn new(spin_version: &str, enable_interface: bool) -> anyhow::Result<Self> {
-        if !enable_interface {
-            return Ok(Self {
-                span_processor: None,
-                metric_exporter: None,
-                log_processor: None,
-                enable_interface,
-            });
-        }
+        Ok(Self {
+            spin_version: spin_version.to_string(),
+            enable_interface,
+            console: enable_interface && otel_console_enabled(),
+        })
+    }
 
-        let resource = Resource::builder()
-            .with_detectors(&[
-                // Set service.name from env OTEL_SERVICE_NAME > env OTEL_RESOURCE_ATTRIBUTES > spin
-                // Set service.version from Spin metadata
-                Box::new(SpinResourceDetector::new(spin_version.to_string()))
-                    as Box<dyn ResourceDetector>,
-                // Sets fields from env OTEL_RESOURCE_ATTRIBUTES
-                Box::new(EnvResourceDetector::new()),
-                // Sets telemetry.sdk{name, language, version}
-                Box::new(TelemetryResourceDetector),
-            ])
-            .build();
-
-        let span_processor = if otel_tracing_enabled() {
-            // This will configure the exporter based on the OTEL_EXPORTER_* environment variables.
-            let span_exporter = match OtlpProtocol::traces_protocol_from_env() {
-                OtlpProtocol::Grpc => opentelemetry_otlp::SpanExporter::builder()
-                    .with_tonic()
-                    .build()?,
-                OtlpProtocol::HttpProtobuf => opentelemetry_otlp::SpanExporter::builder()
-                    .with_http()
-                    .build()?,
-                OtlpProtocol::HttpJson => bail!("http/json OTLP protocol is not supported"),
-            };
+    /// Builds the resource describing an app's guest telemetry.
+    fn resource(&self, app_attributes: HashMap<String, String>) -> Resource {
+        detected_resource(self.spin_version.clone(), app_attributes)
+    }
 
-            let mut span_processor = BatchSpanProcessor::builder(span_exporter, Tokio).build();
-            span_processor.set_resource(&resource);
-            Some(Arc::new(span_processor))
-        } else {
-            None
-        };
+    /// Opens the buffer of guest spans and metrics that failed to export, if either is exported
+    /// over OTLP.
+    fn export_buffer(
+        connection: &OtlpConnection,
+        config: &BufferConfig,
+    ) -> anyhow::Result<Option<ExportBuffer>> {
+        let mut senders = vec![];
+        if connection.traces_enabled() {
+            senders.push(connection.request_sender(Signal::Traces)?);
+        }
+        if connection.metrics_enabled() {
+            senders.push(connection.request_sender(Signal::Metrics)?);
+        }
+        if senders.is_empty() {
+            return Ok(None);
+        }
+        Ok(Some(ExportBuffer::open(config, senders)?))
+    }
 
-        let metric_exporter = if otel_metrics_enabled() {
-            let metric_exporter = match OtlpProtocol::metrics_protocol_from_env() {
-                OtlpProtocol::Grpc => opentelemetry_otlp::MetricExporter::builder()
-                    .with_tonic()
-                    .build()?,
-                OtlpProtocol::HttpProtobuf => opentelemetry_otlp::MetricExporter::builder()
-                    .with_http()
-                    .build()?,
-                OtlpProtocol::HttpJson => bail!("http/json OTLP protocol is not supported"),
-            };
-            Some(Arc::new(metric_exporter))
-        } else {
-            None
+    /// Builds the processor exporting guest spans, if traces are exported over OTLP.
+    fn span_processor(
+        connection: &OtlpConnection,
+        batch: &SpanBatchConfig,
+        resource: &Resource,
+        export_buffer: Option<&ExportBuffer>,
+    ) -> anyhow::Result<Option<Arc<BatchSpanProcessor<Tokio>>>> {
+        if !connection.traces_enabled() {
+            return Ok(None);
+        }
+        // This will configure the exporter based on the OTEL_EXPORTER_* environment variables and
+        // the runtime config.
+        let span_exporter = connection.span_exporter()?;
+        let mut span_processor = match export_buffer {
+            Some(export_buffer) => BatchSpanProcessor::builder(
+                BufferingSpanExporter::new(span_exporter, export_buffer.clone()),
+                Tokio,
+            )
+            .with_batch_config(batch.batch_config())
+            .build(),
+            None => BatchSpanProcessor::builder(span_exporter, Tokio)
+                .with_batch_config(batch.batch_config())
+                .build(),
         };
+        span_processor.set_resource(resource);
+        Ok(Some(Arc::new(span_processor)))
+    }
 
-        let log_processor = if otel_logs_enabled() {
-            let log_exporter = match OtlpProtocol::logs_protocol_from_env() {
-                OtlpProtocol::Grpc => opentelemetry_otlp::LogExporter::builder()
-                    .with_tonic()
-                    .build()?,
-                OtlpProtocol::HttpProtobuf => opentelemetry_otlp::LogExporter::builder()
-                    .with_http()
-                    .build()?,
-                OtlpProtocol::HttpJson => bail!("http/json OTLP protocol is not supported"),
-            };
-
-            let log_processor = BatchLogProcessor::builder(log_exporter, Tokio).build();
-            log_processor.set_resource(&resource);
-            Some(Arc::new(log_processor))
-        } else {
-            None
-        };
+    /// Builds the exporter of guest metrics, if metrics are exported over OTLP.
+    fn metric_exporter(
+        connection: &OtlpConnection,
+        timeout: Option<Duration>,
+    ) -> anyhow::Result<Option<Arc<MetricExporter>>> {
+        if !connection.metrics_enabled() {
+            return Ok(None);
+        }
+        Ok(Some(Arc::new(connection.metric_exporter(timeout)?)))
+    }
 
-        Ok(Self {
-            span_processor,
-            metric_exporter,
-            log_processor,
-            enable_interface,
-        })
+    /// Builds the processor exporting guest logs, if logs are exported over OTLP.
+    fn log_processor(
+        connection: &OtlpConnection,
+        resource: &Resource,
+    ) -> anyhow::Result<Option<Arc<BatchLogProcessor<Tokio>>>> {
+        if !connection.logs_enabled() {
+            return Ok(None);
+        }
+        let log_processor = BatchLogProcessor::builder(connection.log_exporter()?, Tokio).build();
+        log_processor.set_resource(resource);
+        Ok(Some(Arc::new(log_processor)))
     }
 }
 
+/// The app state of the [`OtelFactor`].
+pub struct AppState {
+    /// The resource describing the app's guest telemetry.
+    resource: Resource,
+    /// The processor exporting guest spans over OTLP, if enabled.
+    span_processor: Option<Arc<BatchSpanProcessor<Tokio>>>,
+    /// The exporter of guest metrics over OTLP, if enabled.
+    metric_exporter: Option<Arc<MetricExporter>>,
+    /// The processor exporting guest logs over OTLP, if enabled.
+    log_processor: Option<Arc<BatchLogProcessor<Tokio>>>,
+    /// The buffer of guest spans and metrics that failed to export, if enabled.
+    export_buffer: Option<ExportBuffer>,
+    /// Which telemetry is exported, and how attributes are redacted before export.
+    pub export_policy: ExportPolicy,
+    /// The policy for sampling guest traces, if they are tail sampled.
+    pub tail_sampling: Option<Arc<TailSamplingPolicy>>,
+    /// The fraction of traces for which guest spans are exported, by component ID, for
+    /// components whose guest spans are head sampled.
+    pub component_sample_ratios: HashMap<String, f64>,
+    /// Limits on the size of guest spans.
+    pub span_limits: SpanLimits,
+    /// Views customizing guest metrics before they are exported.
+    pub metric_views: Arc<Vec<MetricView>>,
+    /// Limits the attribute sets of guest metrics.
+    cardinality_limiter: Arc<CardinalityLimiter>,
+}
+
 #[derive(Default)]
 pub struct InstanceState {
     tracing_state: Option<Arc<RwLock<TracingState>>>,
+    /// The resource describing the app's guest telemetry, if the component's telemetry is
+    /// exported.
+    resource: Option<Resource>,
     metric_exporter: Option<Arc<MetricExporter>>,
+    /// The buffer of guest metrics that failed to export, if enabled.
+    export_buffer: Option<ExportBuffer>,
+    metric_views: Arc<Vec<MetricView>>,
+    /// Limits the attribute sets of guest metrics, if the component's telemetry is exported.
+    cardinality_limiter: Option<Arc<CardinalityLimiter>>,
     log_processor: Option<Arc<BatchLogProcessor<Tokio>>>,
+    guest_baggage: GuestBaggage,
+    /// The ID of the component, if its telemetry is exported.
+    component_id: Option<String>,
+    /// Attributes added to the instrumentation scopes of the component's guest telemetry.
+    component_attributes: Vec<KeyValue>,
+    /// Whether guest telemetry is printed in the terminal.
+    console: bool,
 }
 
 impl SelfInstanceBuilder for InstanceState {}
 
+/// Baggage as modified by the guest through the `spin:otel/baggage` interface.
+///
+/// This is `None` until the guest first modifies the baggage; until then, the baggage of the
+/// current context applies.
+pub(crate) type GuestBaggage = Arc<RwLock<Option<Baggage>>>;
+
+/// Copies baggage entry by entry, as [`Baggage`] doesn't implement `Clone`.
+pub(crate) fn copy_baggage(baggage: &Baggage) -> Baggage {
+    baggage
+        .iter()
+        .map(|(key, (value, metadata))| (key.clone(), (value.clone(), metadata.clone())))
+        .collect()
+}
+
 /// Internal tracing state of the OtelFactor InstanceState.
 ///
 /// This data lives here rather than directly on InstanceState so that we can have multiple things
 /// take Arc references to it and so that if tracing is disabled we don't keep doing needless
 /// bookkeeping of host spans.
 pub(crate) struct TracingState {
+    /// The ID of the component whose guest spans are tracked.
+    component_id: String,
+
     /// An order-preserved mapping between immutable [SpanId]s of guest created spans and their
     /// corresponding [SpanContext].
     ///
@@ -204,8 +405,83 @@ pub(crate) struct TracingState {
     /// span.
     pub(crate) original_host_span_id: Option<SpanId>,
 
-    /// The span processor used to export spans.
-    span_processor: Arc<BatchSpanProcessor<Tokio>>,
+    /// Limits on the size of the component's guest spans.
+    pub(crate) span_limits: SpanLimits,
+
+    /// The span processor used to export spans, if they are exported over OTLP.
+    span_processor: Option<Arc<BatchSpanProcessor<Tokio>>>,
+
+    /// The fraction of traces for which guest spans are exported, if the component's guest spans
+    /// are head sampled.
+    sample_ratio: Option<f64>,
+
+    /// Buffers ended guest spans for sampling decisions, if guest traces are tail sampled.
+    tail_sampler: Option<TailSampler>,
+
+    /// Buffers ended guest spans for printing in the terminal, if enabled.
+    console_spans: Option<ConsoleSpans>,
+}
+
+impl TracingState {
+    /// Whether the component's guest spans in the given trace are exported, subject to tail
+    /// sampling.
+    pub(crate) fn is_sampled(&self, trace_id: TraceId) -> bool {
+        self.sample_ratio
+            .is_none_or(|ratio| sampling::sampled_by_ratio(trace_id, ratio))
+    }
+
+    /// Exports an ended guest span, subject to tail sampling.
+    pub(crate) fn export(&mut self, span_data: SpanData) {
+        let sampled = match self.tail_sampler.as_mut() {
+            Some(tail_sampler) => {
+                tail_sampler.push(span_data);
+                // Once the guest has no open spans, its traces are as complete as they will get.
+                if self.guest_span_contexts.is_empty() || tail_sampler.is_full() {
+                    tail_sampler.flush()
+                } else {
+                    vec![]
+                }
+            }
+            None => vec![span_data],
+        };
+        for span_data in sampled {
+            self.emit(span_data);
+        }
+        if self.guest_span_contexts.is_empty()
+            && let Some(console_spans) = self.console_spans.as_mut()
+        {
+            console_spans.flush();
+        }
+    }
+
+    /// Hands a sampled guest span to the configured exporters.
+    fn emit(&mut self, span_data: SpanData) {
+        spin_telemetry::recent_traces::record_guest_span(&self.component_id, &span_data);
+        if let Some(console_spans) = self.console_spans.as_mut() {
+            console_spans.push(span_data.clone());
+        }
+        if let Some(span_processor) = &self.span_processor {
+            span_processor.on_end(span_data);
+        }
+    }
+}
+
+impl Drop for TracingState {
+    fn drop(&mut self) {
+        // Make sampling decisions for any traces still buffered, e.g. because the guest never
+        // ended some of their spans.
+        let buffered = self
+            .tail_sampler
+            .as_mut()
+            .map(TailSampler::flush)
+            .unwrap_or_default();
+        for span_data in buffered {
+            self.emit(span_data);
+        }
+        if let Some(console_spans) = self.console_spans.as_mut() {
+            console_spans.flush();
+        }
+    }
 }
 
 /// Manages access to the OtelFactor tracing state for the purpose of maintaining proper span
@@ -213,6 +489,7 @@ pub(crate) struct TracingState {
 #[derive(Default)]
 pub struct OtelFactorState {
     pub(crate) tracing_state: Option<Arc<RwLock<TracingState>>>,
+    pub(crate) guest_baggage: GuestBaggage,
 }
 
 impl OtelFactorState {
@@ -223,12 +500,31 @@ impl OtelFactorState {
     pub fn from_prepare_context<T: RuntimeFactors, F: Factor>(
         prepare_context: &mut PrepareContext<T, F>,
     ) -> anyhow::Result<Self> {
-        let tracing_state = match prepare_context.instance_builder::<OtelFactor>() {
-            Ok(instance_state) => instance_state.tracing_state.clone(),
-            Err(spin_factors::Error::NoSuchFactor(_)) => None,
+        let (tracing_state, guest_baggage) = match prepare_context.instance_builder::<OtelFactor>()
+        {
+            Ok(instance_state) => (
+                instance_state.tracing_state.clone(),
+                instance_state.guest_baggage.clone(),
+            ),
+            Err(spin_factors::Error::NoSuchFactor(_)) => Default::default(),
             Err(e) => return Err(e.into()),
         };
-        Ok(Self { tracing_state })
+        Ok(Self {
+            tracing_state,
+            guest_baggage,
+        })
+    }
+
+    /// Returns the baggage to propagate on outbound requests if the guest has modified it, or
+    /// `None` if the baggage of the current context applies.
+    ///
+    /// This is intended to be passed to [`spin_telemetry::inject_trace_context_with_baggage`].
+    pub fn guest_baggage(&self) -> Option<Baggage> {
+        self.guest_baggage
+            .read()
+            .unwrap()
+            .as_ref()
+            .map(copy_baggage)
     }
 
     /// Reparents the current [tracing] span to be a child of the last active guest span.
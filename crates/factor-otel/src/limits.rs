@@ -0,0 +1,238 @@
+//! Enforcement of span limits on guest spans as they are converted, so that a misbehaving guest
+//! can't exhaust the memory of the exporters.
+
+use opentelemetry::{Array, KeyValue, StringValue, Value};
+use opentelemetry_sdk::trace::SpanData;
+use spin_world::wasi::otel::tracing as wasi_tracing;
+
+use crate::runtime_config::SpanLimits;
+
+/// How much of a guest span was dropped or truncated to fit within the span limits, by limit.
+#[derive(Debug, Default, PartialEq)]
+pub(crate) struct LimitedCounts {
+    /// Attributes dropped from the span, its events and its links.
+    pub(crate) attributes: u32,
+    pub(crate) events: u32,
+    pub(crate) links: u32,
+    /// Attribute values truncated.
+    pub(crate) truncated_values: u32,
+}
+
+impl LimitedCounts {
+    /// The counts paired with the names of the limits that caused them.
+    pub(crate) fn by_limit(&self) -> [(&'static str, u32); 4] {
+        [
+            ("max_attributes", self.attributes),
+            ("max_events", self.events),
+            ("max_links", self.links),
+            ("max_attribute_value_length", self.truncated_values),
+        ]
+    }
+}
+
+/// Converts a guest span, dropping the attributes, events and links beyond the limits and
+/// truncating long attribute values. Dropped items are added to the span's dropped counts.
+pub(crate) fn convert_span(
+    limits: &SpanLimits,
+    mut span: wasi_tracing::SpanData,
+) -> (SpanData, LimitedCounts) {
+    let mut counts = LimitedCounts::default();
+
+    // Drop before converting, so that dropped items are never converted.
+    let dropped = truncate(&mut span.attributes, limits.max_attributes);
+    span.dropped_attributes = span.dropped_attributes.saturating_add(dropped);
+    counts.attributes += dropped;
+    let dropped = truncate(&mut span.events, limits.max_events);
+    span.dropped_events = span.dropped_events.saturating_add(dropped);
+    counts.events += dropped;
+    let dropped = truncate(&mut span.links, limits.max_links);
+    span.dropped_links = span.dropped_links.saturating_add(dropped);
+    counts.links += dropped;
+
+    let mut event_dropped = Vec::with_capacity(span.events.len());
+    for event in &mut span.events {
+        let dropped = truncate(&mut event.attributes, limits.max_attributes);
+        event_dropped.push(dropped);
+        counts.attributes += dropped;
+    }
+    let mut link_dropped = Vec::with_capacity(span.links.len());
+    for link in &mut span.links {
+        let dropped = truncate(&mut link.attributes, limits.max_attributes);
+        link_dropped.push(dropped);
+        counts.attributes += dropped;
+    }
+
+    let mut span: SpanData = span.into();
+    for (event, dropped) in span.events.events.iter_mut().zip(event_dropped) {
+        event.dropped_attributes_count = event.dropped_attributes_count.saturating_add(dropped);
+    }
+    for (link, dropped) in span.links.links.iter_mut().zip(link_dropped) {
+        link.dropped_attributes_count = link.dropped_attributes_count.saturating_add(dropped);
+    }
+
+    if let Some(max_length) = limits.max_attribute_value_length {
+        let attributes = span
+            .attributes
+            .iter_mut()
+            .chain(
+                span.events
+                    .events
+                    .iter_mut()
+                    .flat_map(|e| &mut e.attributes),
+            )
+            .chain(span.links.links.iter_mut().flat_map(|l| &mut l.attributes));
+        for kv in attributes {
+            if truncate_value(kv, max_length) {
+                counts.truncated_values += 1;
+            }
+        }
+    }
+
+    (span, counts)
+}
+
+/// Truncates a list to the given length, returning how many items were dropped.
+fn truncate<T>(items: &mut Vec<T>, max: usize) -> u32 {
+    let dropped = items.len().saturating_sub(max);
+    items.truncate(max);
+    dropped.try_into().unwrap_or(u32::MAX)
+}
+
+/// Truncates a string attribute value, or the strings of a string array value, to the given
+/// number of characters. Returns whether anything was truncated.
+fn truncate_value(kv: &mut KeyValue, max_length: usize) -> bool {
+    let truncate_str = |s: &mut StringValue| {
+        let Some((end, _)) = s.as_str().char_indices().nth(max_length) else {
+            return false;
+        };
+        *s = s.as_str()[..end].to_owned().into();
+        true
+    };
+    match &mut kv.value {
+        Value::String(s) => truncate_str(s),
+        Value::Array(Array::String(strings)) => {
+            // Every string is truncated, so this must not short-circuit
+            let mut truncated = false;
+            for s in strings {
+                truncated |= truncate_str(s);
+            }
+            truncated
+        }
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use spin_world::wasi::{
+        clocks0_2_0::wall_clock::Datetime,
+        otel::{
+            tracing::{Event, Link, SpanContext, SpanKind, Status, TraceFlags},
+            types::{InstrumentationScope, KeyValue as WasiKeyValue},
+        },
+    };
+
+    use super::*;
+
+    fn kv(key: &str, value: &str) -> WasiKeyValue {
+        WasiKeyValue {
+            key: key.to_owned(),
+            value: serde_json::to_string(value).unwrap(),
+        }
+    }
+
+    fn span_context() -> SpanContext {
+        SpanContext {
+            trace_id: "4fb34cb4484029f7881399b149e41e98".to_owned(),
+            span_id: "9ffd58d3cd4dd90b".to_owned(),
+            trace_flags: TraceFlags::SAMPLED,
+            is_remote: false,
+            trace_state: vec![],
+        }
+    }
+
+    fn span(attributes: usize, events: usize, links: usize) -> wasi_tracing::SpanData {
+        let time = Datetime {
+            seconds: 0,
+            nanoseconds: 0,
+        };
+        wasi_tracing::SpanData {
+            span_context: span_context(),
+            parent_span_id: String::new(),
+            span_kind: SpanKind::Internal,
+            name: "span".to_owned(),
+            start_time: time,
+            end_time: time,
+            attributes: (0..attributes)
+                .map(|i| kv(&format!("key{i}"), "value"))
+                .collect(),
+            events: (0..events)
+                .map(|i| Event {
+                    name: format!("event{i}"),
+                    time,
+                    attributes: vec![kv("a", "1"), kv("b", "2"), kv("c", "3")],
+                })
+                .collect(),
+            links: (0..links)
+                .map(|_| Link {
+                    span_context: span_context(),
+                    attributes: vec![kv("a", "1"), kv("b", "2"), kv("c", "3")],
+                })
+                .collect(),
+            status: Status::Unset,
+            instrumentation_scope: InstrumentationScope {
+                name: "test".to_owned(),
+                version: None,
+                schema_url: None,
+                attributes: vec![],
+            },
+            dropped_attributes: 1,
+            dropped_events: 0,
+            dropped_links: 0,
+        }
+    }
+
+    #[test]
+    fn drops_items_beyond_limits() {
+        let limits = SpanLimits {
+            max_attributes: 2,
+            max_events: 1,
+            max_links: 1,
+            max_attribute_value_length: None,
+        };
+        let (span, counts) = convert_span(&limits, span(5, 3, 2));
+        assert_eq!(2, span.attributes.len());
+        // Attributes the guest dropped itself are still counted.
+        assert_eq!(4, span.dropped_attributes_count);
+        assert_eq!(1, span.events.events.len());
+        assert_eq!(2, span.events.dropped_count);
+        assert_eq!(1, span.events.events[0].dropped_attributes_count);
+        assert_eq!(1, span.links.links.len());
+        assert_eq!(1, span.links.dropped_count);
+        assert_eq!(
+            LimitedCounts {
+                attributes: 5,
+                events: 2,
+                links: 1,
+                truncated_values: 0,
+            },
+            counts
+        );
+    }
+
+    #[test]
+    fn truncates_long_values() {
+        let limits = SpanLimits {
+            max_attribute_value_length: Some(3),
+            ..Default::default()
+        };
+        let mut guest_span = span(0, 0, 0);
+        guest_span.attributes = vec![kv("short", "abc"), kv("long", "éàüö")];
+        let (span, counts) = convert_span(&limits, guest_span);
+        assert_eq!(
+            vec![KeyValue::new("short", "abc"), KeyValue::new("long", "éàü")],
+            span.attributes
+        );
+        assert_eq!(1, counts.truncated_values);
+    }
+}
@@ -0,0 +1,303 @@
+//! Conversion of guest metrics to the Prometheus data model, for serving by the host's Prometheus
+//! endpoint.
+
+use prometheus::proto::{
+    Bucket, Counter, Gauge, Histogram, LabelPair, Metric, MetricFamily, MetricType,
+};
+use spin_world::wasi::otel::metrics::{
+    self as wasi_metrics, MetricData, MetricNumber, Temporality,
+};
+
+/// Converts guest metrics to Prometheus metric families.
+///
+/// Sums and histograms are only converted if they have cumulative temporality, as Prometheus
+/// expects. Exponential histograms are not supported.
+pub(crate) fn metric_families(metrics: &wasi_metrics::ResourceMetrics) -> Vec<MetricFamily> {
+    metrics
+        .scope_metrics
+        .iter()
+        .flat_map(|scope_metrics| &scope_metrics.metrics)
+        .filter_map(metric_family)
+        .collect()
+}
+
+fn metric_family(metric: &wasi_metrics::Metric) -> Option<MetricFamily> {
+    let name = sanitize_name(&metric.name);
+    let (name, field_type, metrics) = match &metric.data {
+        MetricData::F64Gauge(gauge) | MetricData::S64Gauge(gauge) | MetricData::U64Gauge(gauge) => {
+            let metrics: Vec<_> = gauge
+                .data_points
+                .iter()
+                .map(|dp| gauge_metric(&dp.attributes, dp.value))
+                .collect();
+            (name, MetricType::GAUGE, metrics)
+        }
+        MetricData::F64Sum(sum) | MetricData::S64Sum(sum) | MetricData::U64Sum(sum) => {
+            if !is_cumulative(sum.temporality, &metric.name) {
+                return None;
+            }
+            if sum.is_monotonic {
+                let metrics: Vec<_> = sum
+                    .data_points
+                    .iter()
+                    .map(|dp| {
+                        let mut counter = Counter::default();
+                        counter.set_value(number(dp.value));
+                        let mut metric = labeled_metric(&dp.attributes);
+                        metric.set_counter(counter);
+                        metric
+                    })
+                    .collect();
+                (counter_name(name), MetricType::COUNTER, metrics)
+            } else {
+                let metrics: Vec<_> = sum
+                    .data_points
+                    .iter()
+                    .map(|dp| gauge_metric(&dp.attributes, dp.value))
+                    .collect();
+                (name, MetricType::GAUGE, metrics)
+            }
+        }
+        MetricData::F64Histogram(histogram)
+        | MetricData::S64Histogram(histogram)
+        | MetricData::U64Histogram(histogram) => {
+            if !is_cumulative(histogram.temporality, &metric.name) {
+                return None;
+            }
+            let metrics: Vec<_> = histogram
+                .data_points
+                .iter()
+                .map(|dp| {
+                    let mut prom_histogram = Histogram::default();
+                    prom_histogram.set_sample_count(dp.count);
+                    prom_histogram.set_sample_sum(number(dp.sum));
+                    // Prometheus buckets are cumulative, and the +Inf bucket is implied by the
+                    // sample count.
+                    let mut cumulative_count = 0;
+                    for (bound, count) in dp.bounds.iter().zip(&dp.bucket_counts) {
+                        cumulative_count += count;
+                        let mut bucket = Bucket::default();
+                        bucket.set_upper_bound(*bound);
+                        bucket.set_cumulative_count(cumulative_count);
+                        prom_histogram.mut_bucket().push(bucket);
+                    }
+                    let mut metric = labeled_metric(&dp.attributes);
+                    metric.set_histogram(prom_histogram);
+                    metric
+                })
+                .collect();
+            (name, MetricType::HISTOGRAM, metrics)
+        }
+        MetricData::F64ExponentialHistogram(_)
+        | MetricData::S64ExponentialHistogram(_)
+        | MetricData::U64ExponentialHistogram(_) => {
+            tracing::debug!(
+                "Not serving guest metric {} to Prometheus: exponential histograms are not supported",
+                metric.name
+            );
+            return None;
+        }
+    };
+
+    let mut family = MetricFamily::default();
+    family.set_name(name);
+    family.set_help(metric.description.clone());
+    family.set_field_type(field_type);
+    for metric in metrics {
+        family.mut_metric().push(metric);
+    }
+    Some(family)
+}
+
+fn is_cumulative(temporality: Temporality, name: &str) -> bool {
+    let cumulative = matches!(temporality, Temporality::Cumulative);
+    if !cumulative {
+        tracing::debug!(
+            "Not serving guest metric {name} to Prometheus: only cumulative temporality is supported"
+        );
+    }
+    cumulative
+}
+
+fn gauge_metric(
+    attributes: &[spin_world::wasi::otel::types::KeyValue],
+    value: MetricNumber,
+) -> Metric {
+    let mut gauge = Gauge::default();
+    gauge.set_value(number(value));
+    let mut metric = labeled_metric(attributes);
+    metric.set_gauge(gauge);
+    metric
+}
+
+fn labeled_metric(attributes: &[spin_world::wasi::otel::types::KeyValue]) -> Metric {
+    let mut metric = Metric::default();
+    for attribute in attributes {
+        let attribute: opentelemetry::KeyValue = attribute.into();
+        let mut label = LabelPair::default();
+        label.set_name(sanitize_name(attribute.key.as_str()).replace(':', "_"));
+        label.set_value(attribute.value.as_str().into_owned());
+        metric.mut_label().push(label);
+    }
+    metric
+}
+
+fn number(number: MetricNumber) -> f64 {
+    match number {
+        MetricNumber::F64(n) => n,
+        MetricNumber::S64(n) => n as f64,
+        MetricNumber::U64(n) => n as f64,
+    }
+}
+
+/// Names counters with the conventional `_total` suffix.
+fn counter_name(name: String) -> String {
+    if name.ends_with("_total") {
+        name
+    } else {
+        format!("{name}_total")
+    }
+}
+
+/// Replaces characters that are not valid in Prometheus metric names, such as the dots of OTel
+/// metric names, with underscores.
+fn sanitize_name(name: &str) -> String {
+    let mut sanitized: String = name
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '_' || c == ':' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    if !sanitized.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_' || c == ':') {
+        sanitized.insert(0, '_');
+    }
+    sanitized
+}
+
+#[cfg(test)]
+mod tests {
+    use spin_world::wasi::{clocks0_2_0::wall_clock::Datetime, otel::types::KeyValue};
+
+    use super::*;
+
+    const TIME: Datetime = Datetime {
+        seconds: 1,
+        nanoseconds: 0,
+    };
+
+    fn resource_metrics(metrics: Vec<wasi_metrics::Metric>) -> wasi_metrics::ResourceMetrics {
+        wasi_metrics::ResourceMetrics {
+            resource: wasi_metrics::Resource {
+                attributes: vec![],
+                schema_url: None,
+            },
+            scope_metrics: vec![wasi_metrics::ScopeMetrics {
+                scope: wasi_metrics::InstrumentationScope {
+                    name: "test".into(),
+                    version: None,
+                    schema_url: None,
+                    attributes: vec![],
+                },
+                metrics,
+            }],
+        }
+    }
+
+    fn metric(name: &str, data: MetricData) -> wasi_metrics::Metric {
+        wasi_metrics::Metric {
+            name: name.into(),
+            description: "A test metric".into(),
+            unit: String::new(),
+            data,
+        }
+    }
+
+    fn sum(temporality: Temporality) -> wasi_metrics::Sum {
+        wasi_metrics::Sum {
+            data_points: vec![wasi_metrics::SumDataPoint {
+                attributes: vec![KeyValue {
+                    key: "http.route".into(),
+                    value: "\"/orders\"".into(),
+                }],
+                exemplars: vec![],
+                value: MetricNumber::U64(3),
+            }],
+            start_time: TIME,
+            time: TIME,
+            temporality,
+            is_monotonic: true,
+        }
+    }
+
+    #[test]
+    fn converts_monotonic_sums_to_counters() {
+        let families = metric_families(&resource_metrics(vec![metric(
+            "orders.placed",
+            MetricData::U64Sum(sum(Temporality::Cumulative)),
+        )]));
+
+        assert_eq!(1, families.len());
+        let family = &families[0];
+        assert_eq!("orders_placed_total", family.get_name());
+        assert_eq!(MetricType::COUNTER, family.get_field_type());
+        let metric = &family.get_metric()[0];
+        assert_eq!(3.0, metric.get_counter().get_value());
+        assert_eq!("http_route", metric.get_label()[0].get_name());
+        assert_eq!("/orders", metric.get_label()[0].get_value());
+    }
+
+    #[test]
+    fn converts_histograms_to_cumulative_buckets() {
+        let histogram = wasi_metrics::Histogram {
+            data_points: vec![wasi_metrics::HistogramDataPoint {
+                attributes: vec![],
+                bounds: vec![10.0, 100.0],
+                bucket_counts: vec![1, 2, 3],
+                exemplars: vec![],
+                count: 6,
+                max: None,
+                min: None,
+                sum: MetricNumber::F64(1234.0),
+            }],
+            start_time: TIME,
+            time: TIME,
+            temporality: Temporality::Cumulative,
+        };
+        let families = metric_families(&resource_metrics(vec![metric(
+            "latency",
+            MetricData::F64Histogram(histogram),
+        )]));
+
+        let histogram = families[0].get_metric()[0].get_histogram();
+        assert_eq!(6, histogram.get_sample_count());
+        assert_eq!(1234.0, histogram.get_sample_sum());
+        let buckets: Vec<_> = histogram
+            .get_bucket()
+            .iter()
+            .map(|b| (b.get_upper_bound(), b.get_cumulative_count()))
+            .collect();
+        assert_eq!(vec![(10.0, 1), (100.0, 3)], buckets);
+    }
+
+    #[test]
+    fn skips_delta_temporality() {
+        let families = metric_families(&resource_metrics(vec![metric(
+            "orders.placed",
+            MetricData::U64Sum(sum(Temporality::Delta)),
+        )]));
+        assert!(families.is_empty());
+    }
+
+    #[test]
+    fn sanitizes_names() {
+        assert_eq!(
+            "http_server_duration",
+            sanitize_name("http.server.duration")
+        );
+        assert_eq!("_2xx", sanitize_name("2xx"));
+    }
+}
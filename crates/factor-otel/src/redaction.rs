@@ -0,0 +1,167 @@
+//! Redaction of the attributes of guest metrics and logs by the export policy, which is applied to
+//! guest spans once they are converted.
+
+use spin_telemetry::policy::ExportPolicy;
+use spin_world::wasi::otel::{
+    logs::LogRecord,
+    metrics::{self as wasi_metrics, Exemplar, MetricData},
+    types::KeyValue,
+};
+
+/// Redacts or hashes the attributes of guest metrics' data points and exemplars.
+pub(crate) fn redact_metrics(policy: &ExportPolicy, metrics: &mut wasi_metrics::ResourceMetrics) {
+    if !policy.redacts_anything() {
+        return;
+    }
+    let metrics = metrics
+        .scope_metrics
+        .iter_mut()
+        .flat_map(|scope_metrics| &mut scope_metrics.metrics);
+    for metric in metrics {
+        match &mut metric.data {
+            MetricData::F64Gauge(gauge)
+            | MetricData::S64Gauge(gauge)
+            | MetricData::U64Gauge(gauge) => {
+                for dp in &mut gauge.data_points {
+                    redact(policy, &mut dp.attributes);
+                    redact_exemplars(policy, &mut dp.exemplars);
+                }
+            }
+            MetricData::F64Sum(sum) | MetricData::S64Sum(sum) | MetricData::U64Sum(sum) => {
+                for dp in &mut sum.data_points {
+                    redact(policy, &mut dp.attributes);
+                    redact_exemplars(policy, &mut dp.exemplars);
+                }
+            }
+            MetricData::F64Histogram(histogram)
+            | MetricData::S64Histogram(histogram)
+            | MetricData::U64Histogram(histogram) => {
+                for dp in &mut histogram.data_points {
+                    redact(policy, &mut dp.attributes);
+                    redact_exemplars(policy, &mut dp.exemplars);
+                }
+            }
+            MetricData::F64ExponentialHistogram(histogram)
+            | MetricData::S64ExponentialHistogram(histogram)
+            | MetricData::U64ExponentialHistogram(histogram) => {
+                for dp in &mut histogram.data_points {
+                    redact(policy, &mut dp.attributes);
+                    redact_exemplars(policy, &mut dp.exemplars);
+                }
+            }
+        }
+    }
+}
+
+/// Redacts or hashes the attributes of a guest log record.
+pub(crate) fn redact_log_record(policy: &ExportPolicy, record: &mut LogRecord) {
+    if let Some(attributes) = &mut record.attributes
+        && policy.redacts_anything()
+    {
+        redact(policy, attributes);
+    }
+}
+
+fn redact_exemplars(policy: &ExportPolicy, exemplars: &mut [Exemplar]) {
+    for exemplar in exemplars {
+        redact(policy, &mut exemplar.filtered_attributes);
+    }
+}
+
+/// Redacts guest attributes, whose values are JSON-encoded.
+fn redact(policy: &ExportPolicy, attributes: &mut [KeyValue]) {
+    for attribute in attributes {
+        let otel_attribute: opentelemetry::KeyValue = (&*attribute).into();
+        if let Some(value) = policy.redacted_value(&attribute.key, &otel_attribute.value) {
+            attribute.value = serde_json::to_string(&value.as_str()).unwrap();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use spin_world::wasi::clocks0_2_0::wall_clock::Datetime;
+
+    use super::*;
+
+    const TIME: Datetime = Datetime {
+        seconds: 1,
+        nanoseconds: 0,
+    };
+
+    fn attribute(key: &str, value: &str) -> KeyValue {
+        KeyValue {
+            key: key.into(),
+            value: serde_json::to_string(value).unwrap(),
+        }
+    }
+
+    #[test]
+    fn redacts_data_point_and_exemplar_attributes() {
+        let policy = ExportPolicy {
+            redact_attributes: HashSet::from(["user.email".to_owned()]),
+            redact_attribute_patterns: vec![
+                "http\\.request\\.header\\..*"
+                    .to_owned()
+                    .try_into()
+                    .unwrap(),
+            ],
+            ..Default::default()
+        };
+        let mut metrics = wasi_metrics::ResourceMetrics {
+            resource: wasi_metrics::Resource {
+                attributes: vec![],
+                schema_url: None,
+            },
+            scope_metrics: vec![wasi_metrics::ScopeMetrics {
+                scope: wasi_metrics::InstrumentationScope {
+                    name: "test".into(),
+                    version: None,
+                    schema_url: None,
+                    attributes: vec![],
+                },
+                metrics: vec![wasi_metrics::Metric {
+                    name: "logins".into(),
+                    description: String::new(),
+                    unit: String::new(),
+                    data: MetricData::U64Sum(wasi_metrics::Sum {
+                        data_points: vec![wasi_metrics::SumDataPoint {
+                            attributes: vec![
+                                attribute("user.email", "someone@example.com"),
+                                attribute("http.route", "/login"),
+                            ],
+                            exemplars: vec![Exemplar {
+                                filtered_attributes: vec![attribute(
+                                    "http.request.header.authorization",
+                                    "Bearer abc",
+                                )],
+                                time: TIME,
+                                value: wasi_metrics::MetricNumber::U64(1),
+                                span_id: String::new(),
+                                trace_id: String::new(),
+                            }],
+                            value: wasi_metrics::MetricNumber::U64(1),
+                        }],
+                        start_time: TIME,
+                        time: TIME,
+                        temporality: wasi_metrics::Temporality::Cumulative,
+                        is_monotonic: true,
+                    }),
+                }],
+            }],
+        };
+        redact_metrics(&policy, &mut metrics);
+        let MetricData::U64Sum(sum) = &metrics.scope_metrics[0].metrics[0].data else {
+            unreachable!()
+        };
+        let dp = &sum.data_points[0];
+        assert_eq!("\"[REDACTED]\"", dp.attributes[0].value);
+        assert_eq!("\"/login\"", dp.attributes[1].value);
+        assert_eq!(
+            "\"[REDACTED]\"",
+            dp.exemplars[0].filtered_attributes[0].value
+        );
+    }
+}
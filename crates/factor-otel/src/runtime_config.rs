@@ -0,0 +1,655 @@
+use std::{collections::HashMap, path::PathBuf, time::Duration};
+
+use anyhow::{Context, ensure};
+use opentelemetry_sdk::trace::{BatchConfig, BatchConfigBuilder};
+use serde::Deserialize;
+use spin_factors::runtime_config::toml::GetTomlValue;
+use spin_telemetry::{otlp::OtlpConnection, policy::ExportPolicy, propagators::Propagator};
+
+use crate::{MetricView, TailSamplingPolicy};
+
+/// Runtime configuration for telemetry.
+#[derive(Default)]
+pub struct RuntimeConfig {
+    /// Which telemetry is exported, and how attributes are redacted before export.
+    pub export_policy: ExportPolicy,
+    /// The formats in which trace context is propagated, if not the defaults.
+    pub propagators: Option<Vec<Propagator>>,
+    /// The policy for sampling guest traces, if they are tail sampled.
+    pub tail_sampling: Option<TailSamplingPolicy>,
+    /// The fraction of traces for which guest spans are exported, by component ID, for
+    /// components whose guest spans are head sampled.
+    pub component_sample_ratios: HashMap<String, f64>,
+    /// Settings of the batch processor exporting guest spans.
+    pub span_batch: SpanBatchConfig,
+    /// Limits on the size of guest spans.
+    pub span_limits: SpanLimits,
+    /// The timeout for exporting guest metrics, if not the default.
+    pub metrics_export_timeout: Option<Duration>,
+    /// Views customizing guest metrics before they are exported.
+    pub metric_views: Vec<MetricView>,
+    /// The maximum number of attribute sets exported by each guest instrument, if not the
+    /// default.
+    pub metric_cardinality_limit: Option<usize>,
+    /// How to connect to the OTLP endpoint, in addition to the `OTEL_EXPORTER_OTLP_*`
+    /// environment variables.
+    pub otlp: OtlpConnection,
+    /// Settings of the on-disk buffer of guest spans and metrics that failed to export, if
+    /// they are buffered for later delivery.
+    pub buffer: Option<BufferConfig>,
+}
+
+/// Settings of the batch processor exporting guest spans over OTLP.
+///
+/// Unset settings take their defaults, which may be overridden by the standard `OTEL_BSP_*`
+/// environment variables.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct SpanBatchConfig {
+    /// The maximum number of spans buffered for export. Spans ended while the queue is full are
+    /// dropped.
+    pub max_queue_size: Option<usize>,
+    /// The maximum number of spans exported in a batch.
+    pub max_export_batch_size: Option<usize>,
+    /// How long to wait between exports, in milliseconds.
+    pub scheduled_delay_ms: Option<u64>,
+    /// How long an export may take before it is cancelled, in milliseconds.
+    pub export_timeout_ms: Option<u64>,
+}
+
+impl SpanBatchConfig {
+    fn validate(&self) -> anyhow::Result<()> {
+        ensure!(
+            self.max_queue_size != Some(0),
+            "max_queue_size must be greater than 0"
+        );
+        ensure!(
+            self.max_export_batch_size != Some(0),
+            "max_export_batch_size must be greater than 0"
+        );
+        if let (Some(queue), Some(batch)) = (self.max_queue_size, self.max_export_batch_size) {
+            ensure!(
+                batch <= queue,
+                "max_export_batch_size ({batch}) must not exceed max_queue_size ({queue})"
+            );
+        }
+        Ok(())
+    }
+
+    /// Builds the configuration of the SDK's batch span processor.
+    pub(crate) fn batch_config(&self) -> BatchConfig {
+        let mut builder = BatchConfigBuilder::default();
+        if let Some(max_queue_size) = self.max_queue_size {
+            builder = builder.with_max_queue_size(max_queue_size);
+        }
+        if let Some(max_export_batch_size) = self.max_export_batch_size {
+            builder = builder.with_max_export_batch_size(max_export_batch_size);
+        }
+        if let Some(scheduled_delay_ms) = self.scheduled_delay_ms {
+            builder = builder.with_scheduled_delay(Duration::from_millis(scheduled_delay_ms));
+        }
+        if let Some(export_timeout_ms) = self.export_timeout_ms {
+            builder = builder.with_max_export_timeout(Duration::from_millis(export_timeout_ms));
+        }
+        builder.build()
+    }
+}
+
+/// Limits on the size of guest spans, enforced as they are converted so that a misbehaving guest
+/// can't exhaust the memory of the exporters. Dropped attributes, events and links are reported in
+/// the spans' dropped counts.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct SpanLimits {
+    /// The maximum number of attributes of a span, and of each of its events and links.
+    pub max_attributes: usize,
+    /// The maximum number of events of a span.
+    pub max_events: usize,
+    /// The maximum number of links of a span.
+    pub max_links: usize,
+    /// The maximum length, in characters, of string attribute values, and of the strings of
+    /// string array values. Longer strings are truncated.
+    pub max_attribute_value_length: Option<usize>,
+}
+
+impl Default for SpanLimits {
+    /// The OTel SDK's default span limits.
+    fn default() -> Self {
+        Self {
+            max_attributes: 128,
+            max_events: 128,
+            max_links: 128,
+            max_attribute_value_length: None,
+        }
+    }
+}
+
+/// Settings of the on-disk buffer of guest spans and metrics that failed to export over OTLP,
+/// e.g. during a collector outage, which are retried with exponential backoff.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct BufferConfig {
+    /// The directory in which failed exports are stored until they are delivered.
+    pub directory: PathBuf,
+    /// The maximum size of the buffer, in megabytes. The oldest exports are dropped to stay
+    /// within it.
+    #[serde(default = "BufferConfig::default_max_size_mb")]
+    pub max_size_mb: u64,
+    /// The maximum delay between retries, in seconds.
+    #[serde(default = "BufferConfig::default_max_backoff_secs")]
+    pub max_backoff_secs: u64,
+}
+
+impl BufferConfig {
+    fn default_max_size_mb() -> u64 {
+        100
+    }
+
+    fn default_max_backoff_secs() -> u64 {
+        300
+    }
+
+    fn validate(&self) -> anyhow::Result<()> {
+        ensure!(self.max_size_mb > 0, "max_size_mb must be greater than 0");
+        ensure!(
+            self.max_backoff_secs > 0,
+            "max_backoff_secs must be greater than 0"
+        );
+        Ok(())
+    }
+}
+
+/// A `[component.<id>]` table. Other keys may be used by other factors.
+#[derive(Deserialize)]
+struct ComponentConfig {
+    tracing: Option<ComponentTracingConfig>,
+}
+
+/// A `[component.<id>.tracing]` table.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ComponentTracingConfig {
+    sample_ratio: Option<f64>,
+}
+
+/// The `[observability.tracing]` table.
+#[derive(Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TracingConfig {
+    propagators: Option<Vec<Propagator>>,
+    tail_sampling: Option<TailSamplingPolicy>,
+    #[serde(default)]
+    batch: SpanBatchConfig,
+    #[serde(default)]
+    limits: SpanLimits,
+}
+
+/// The `[observability.metrics]` table.
+#[derive(Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct MetricsConfig {
+    export_timeout_ms: Option<u64>,
+    cardinality_limit: Option<usize>,
+    #[serde(default)]
+    views: Vec<MetricView>,
+}
+
+/// Get the runtime configuration for telemetry from a TOML table.
+///
+/// Expects table to be in the format:
+/// ```toml
+/// [telemetry]
+/// # Export nothing for these components
+/// disabled_components = ["healthcheck"]
+/// # Export these attributes as "[REDACTED]"
+/// redact_attributes = ["url.full"]
+/// # Export these attributes as SHA-256 digests
+/// hash_attributes = ["client.address"]
+/// # Likewise for attributes whose whole keys match these regular expressions
+/// redact_attribute_patterns = ["http\\.request\\.header\\..*"]
+/// hash_attribute_patterns = ["user\\..*"]
+///
+/// [observability.tracing]
+/// # Extract and inject trace context in these formats: any of "tracecontext",
+/// # "baggage", "b3", "b3multi" and "jaeger"
+/// propagators = ["b3", "tracecontext"]
+///
+/// # Buffer guest spans and export only the traces matching any of these policies
+/// [observability.tracing.tail_sampling]
+/// # Traces containing an error span
+/// errors = true
+/// # Traces taking at least this long
+/// latency_threshold_ms = 500
+/// # This fraction of traces
+/// rate = 0.1
+///
+/// # Tune the batching of guest spans exported over OTLP
+/// [observability.tracing.batch]
+/// max_queue_size = 2048
+/// max_export_batch_size = 512
+/// scheduled_delay_ms = 5000
+/// export_timeout_ms = 30000
+///
+/// # Drop or truncate what exceeds these limits of guest spans
+/// [observability.tracing.limits]
+/// max_attributes = 128
+/// max_events = 128
+/// max_links = 128
+/// max_attribute_value_length = 4096
+///
+/// [observability.metrics]
+/// # Cancel exports of guest metrics taking longer than this
+/// export_timeout_ms = 10000
+/// # Export at most this many attribute sets per instrument, merging the data points of any
+/// # further attribute sets into one with the attribute otel.metric.overflow = true
+/// cardinality_limit = 2000
+///
+/// # Customize guest metrics before export; the first view matching an instrument applies
+/// [[observability.metrics.views]]
+/// instrument = "http.server.request.duration"
+/// # Export under this name
+/// name = "request_duration"
+/// # Remove these attributes, merging data points left with the same attributes
+/// drop_attributes = ["user.id"]
+/// # Merge histogram buckets into buckets with these boundaries
+/// bucket_boundaries = [0.1, 1.0, 10.0]
+///
+/// [observability.otlp]
+/// # Export each signal to its own backend, rather than to OTEL_EXPORTER_OTLP_ENDPOINT
+/// traces_endpoint = "https://traces.example.com/v1/traces"
+/// metrics_endpoint = "https://metrics.example.com/v1/metrics"
+/// logs_endpoint = "https://logs.example.com/v1/logs"
+/// # Authenticate to the OTLP endpoints
+/// headers = { authorization = "Bearer <token>" }
+/// ca_certificate = "/etc/ssl/collector-ca.pem"
+/// client_certificate = "/etc/ssl/spin.pem"
+/// client_key = "/etc/ssl/spin-key.pem"
+///
+/// # Store guest spans and metrics that fail to export, e.g. while the collector is
+/// # unreachable, and retry them with exponential backoff
+/// [observability.buffer]
+/// directory = "/var/lib/spin/telemetry"
+/// # Drop the oldest stored exports beyond this size
+/// max_size_mb = 100
+/// # Wait at most this long between retries
+/// max_backoff_secs = 300
+///
+/// # Export guest spans for this fraction of the component's traces
+/// [component.api.tracing]
+/// sample_ratio = 0.1
+/// ```
+pub fn config_from_table(table: &impl GetTomlValue) -> anyhow::Result<Option<RuntimeConfig>> {
+    let telemetry = table.get("telemetry");
+    let observability = table.get("observability");
+    let tracing = observability.and_then(|observability| observability.get("tracing"));
+    let metrics = observability.and_then(|observability| observability.get("metrics"));
+    let otlp = observability.and_then(|observability| observability.get("otlp"));
+    let buffer = observability.and_then(|observability| observability.get("buffer"));
+    let components = table.get("component");
+    if telemetry.is_none()
+        && tracing.is_none()
+        && metrics.is_none()
+        && otlp.is_none()
+        && buffer.is_none()
+        && components.is_none()
+    {
+        return Ok(None);
+    }
+    let export_policy = match telemetry {
+        Some(telemetry) => telemetry.clone().try_into::<ExportPolicy>()?,
+        None => ExportPolicy::default(),
+    };
+    let tracing = match tracing {
+        Some(tracing) => tracing.clone().try_into::<TracingConfig>()?,
+        None => TracingConfig::default(),
+    };
+    if let Some(tail_sampling) = &tracing.tail_sampling {
+        tail_sampling.validate()?;
+    }
+    tracing.batch.validate()?;
+    let metrics = match metrics {
+        Some(metrics) => metrics.clone().try_into::<MetricsConfig>()?,
+        None => MetricsConfig::default(),
+    };
+    ensure!(
+        metrics.cardinality_limit != Some(0),
+        "cardinality_limit must be greater than 0"
+    );
+    for view in &metrics.views {
+        view.validate()?;
+    }
+    let otlp = match otlp {
+        Some(otlp) => otlp.clone().try_into::<OtlpConnection>()?,
+        None => OtlpConnection::default(),
+    };
+    otlp.validate()?;
+    let buffer = buffer
+        .map(|buffer| buffer.clone().try_into::<BufferConfig>())
+        .transpose()?;
+    if let Some(buffer) = &buffer {
+        buffer.validate()?;
+    }
+    let component_sample_ratios = match components {
+        Some(components) => component_sample_ratios(components)?,
+        None => HashMap::new(),
+    };
+    Ok(Some(RuntimeConfig {
+        export_policy,
+        propagators: tracing.propagators,
+        tail_sampling: tracing.tail_sampling,
+        component_sample_ratios,
+        span_batch: tracing.batch,
+        span_limits: tracing.limits,
+        metrics_export_timeout: metrics.export_timeout_ms.map(Duration::from_millis),
+        metric_views: metrics.views,
+        metric_cardinality_limit: metrics.cardinality_limit,
+        otlp,
+        buffer,
+    }))
+}
+
+fn component_sample_ratios(components: &toml::Value) -> anyhow::Result<HashMap<String, f64>> {
+    let components = components
+        .clone()
+        .try_into::<HashMap<String, ComponentConfig>>()
+        .context("invalid [component] runtime config")?;
+    let mut ratios = HashMap::new();
+    for (id, component) in components {
+        if let Some(ratio) = component.tracing.and_then(|tracing| tracing.sample_ratio) {
+            ensure!(
+                (0.0..=1.0).contains(&ratio),
+                "sample_ratio for component {id:?} must be between 0 and 1, got {ratio}"
+            );
+            ratios.insert(id, ratio);
+        }
+    }
+    Ok(ratios)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::*;
+
+    #[test]
+    fn parses_export_policy() {
+        let table: toml::Table = toml::toml! {
+            [telemetry]
+            disabled_components = ["healthcheck"]
+            redact_attributes = ["url.full"]
+            redact_attribute_patterns = ["http\\.request\\.header\\..*"]
+        };
+        let config = config_from_table(&table).unwrap().unwrap();
+        assert_eq!(
+            ExportPolicy {
+                disabled_components: HashSet::from(["healthcheck".to_owned()]),
+                redact_attributes: HashSet::from(["url.full".to_owned()]),
+                redact_attribute_patterns: vec![
+                    "http\\.request\\.header\\..*"
+                        .to_owned()
+                        .try_into()
+                        .unwrap()
+                ],
+                ..Default::default()
+            },
+            config.export_policy
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_attribute_patterns() {
+        let table: toml::Table = toml::toml! {
+            [telemetry]
+            redact_attribute_patterns = ["http.("]
+        };
+        assert!(config_from_table(&table).is_err());
+    }
+
+    #[test]
+    fn parses_propagators() {
+        let table: toml::Table = toml::toml! {
+            [observability.tracing]
+            propagators = ["b3", "tracecontext"]
+        };
+        let config = config_from_table(&table).unwrap().unwrap();
+        assert_eq!(
+            Some(vec![Propagator::B3, Propagator::TraceContext]),
+            config.propagators
+        );
+        assert_eq!(ExportPolicy::default(), config.export_policy);
+    }
+
+    #[test]
+    fn parses_tail_sampling_policy() {
+        let table: toml::Table = toml::toml! {
+            [observability.tracing.tail_sampling]
+            errors = true
+            latency_threshold_ms = 500
+        };
+        let config = config_from_table(&table).unwrap().unwrap();
+        assert_eq!(
+            Some(TailSamplingPolicy {
+                errors: true,
+                latency_threshold_ms: Some(500),
+                rate: None,
+            }),
+            config.tail_sampling
+        );
+        assert_eq!(None, config.propagators);
+
+        let table: toml::Table = toml::toml! {
+            [observability.tracing.tail_sampling]
+            rate = 2.0
+        };
+        assert!(config_from_table(&table).is_err());
+    }
+
+    #[test]
+    fn parses_component_sample_ratios() {
+        let table: toml::Table = toml::toml! {
+            [component.api.tracing]
+            sample_ratio = 0.1
+
+            [component.other]
+            unrelated = true
+        };
+        let config = config_from_table(&table).unwrap().unwrap();
+        assert_eq!(
+            HashMap::from([("api".to_owned(), 0.1)]),
+            config.component_sample_ratios
+        );
+
+        let table: toml::Table = toml::toml! {
+            [component.api.tracing]
+            sample_ratio = -1.0
+        };
+        assert!(config_from_table(&table).is_err());
+
+        let table: toml::Table = toml::toml! {
+            [component.api.tracing]
+            sample_rate = 0.1
+        };
+        assert!(config_from_table(&table).is_err());
+    }
+
+    #[test]
+    fn parses_export_settings() {
+        let table: toml::Table = toml::toml! {
+            [observability.tracing.batch]
+            max_queue_size = 4096
+            scheduled_delay_ms = 1000
+
+            [observability.metrics]
+            export_timeout_ms = 10000
+            cardinality_limit = 100
+        };
+        let config = config_from_table(&table).unwrap().unwrap();
+        assert_eq!(
+            SpanBatchConfig {
+                max_queue_size: Some(4096),
+                scheduled_delay_ms: Some(1000),
+                ..Default::default()
+            },
+            config.span_batch
+        );
+        assert_eq!(Some(Duration::from_secs(10)), config.metrics_export_timeout);
+        assert_eq!(Some(100), config.metric_cardinality_limit);
+
+        let table: toml::Table = toml::toml! {
+            [observability.tracing.batch]
+            max_queue_size = 100
+            max_export_batch_size = 512
+        };
+        assert!(config_from_table(&table).is_err());
+
+        let table: toml::Table = toml::toml! {
+            [observability.metrics]
+            cardinality_limit = 0
+        };
+        assert!(config_from_table(&table).is_err());
+    }
+
+    #[test]
+    fn parses_span_limits() {
+        let table: toml::Table = toml::toml! {
+            [observability.tracing.limits]
+            max_events = 16
+            max_attribute_value_length = 1024
+        };
+        let config = config_from_table(&table).unwrap().unwrap();
+        assert_eq!(
+            SpanLimits {
+                max_attributes: 128,
+                max_events: 16,
+                max_links: 128,
+                max_attribute_value_length: Some(1024),
+            },
+            config.span_limits
+        );
+
+        let table: toml::Table = toml::toml! {
+            [observability.tracing.limits]
+            max_spans = 16
+        };
+        assert!(config_from_table(&table).is_err());
+    }
+
+    #[test]
+    fn parses_metric_views() {
+        let table: toml::Table = toml::toml! {
+            [[observability.metrics.views]]
+            instrument = "http.*"
+            drop_attributes = ["user.id"]
+
+            [[observability.metrics.views]]
+            instrument = "latency"
+            name = "request_latency"
+            bucket_boundaries = [0.1, 1.0]
+        };
+        let config = config_from_table(&table).unwrap().unwrap();
+        assert_eq!(
+            vec![
+                MetricView {
+                    instrument: "http.*".into(),
+                    name: None,
+                    drop_attributes: vec!["user.id".into()],
+                    bucket_boundaries: None,
+                },
+                MetricView {
+                    instrument: "latency".into(),
+                    name: Some("request_latency".into()),
+                    drop_attributes: vec![],
+                    bucket_boundaries: Some(vec![0.1, 1.0]),
+                },
+            ],
+            config.metric_views
+        );
+
+        let table: toml::Table = toml::toml! {
+            [[observability.metrics.views]]
+            instrument = "http.*"
+            name = "renamed"
+        };
+        assert!(config_from_table(&table).is_err());
+    }
+
+    #[test]
+    fn parses_otlp_connection() {
+        let table: toml::Table = toml::toml! {
+            [observability.otlp]
+            metrics_endpoint = "http://metrics:4318/v1/metrics"
+            headers = { authorization = "Bearer token" }
+            client_certificate = "spin.pem"
+            client_key = "spin-key.pem"
+        };
+        let config = config_from_table(&table).unwrap().unwrap();
+        assert_eq!(
+            OtlpConnection {
+                traces_endpoint: None,
+                metrics_endpoint: Some("http://metrics:4318/v1/metrics".to_owned()),
+                logs_endpoint: None,
+                headers: HashMap::from([("authorization".to_owned(), "Bearer token".to_owned())]),
+                ca_certificate: None,
+                client_certificate: Some("spin.pem".into()),
+                client_key: Some("spin-key.pem".into()),
+            },
+            config.otlp
+        );
+
+        let table: toml::Table = toml::toml! {
+            [observability.otlp]
+            client_certificate = "spin.pem"
+        };
+        assert!(config_from_table(&table).is_err());
+    }
+
+    #[test]
+    fn parses_buffer_config() {
+        let table: toml::Table = toml::toml! {
+            [observability.buffer]
+            directory = "/var/lib/spin/telemetry"
+            max_backoff_secs = 60
+        };
+        let config = config_from_table(&table).unwrap().unwrap();
+        assert_eq!(
+            Some(BufferConfig {
+                directory: "/var/lib/spin/telemetry".into(),
+                max_size_mb: 100,
+                max_backoff_secs: 60,
+            }),
+            config.buffer
+        );
+
+        let table: toml::Table = toml::toml! {
+            [observability.buffer]
+            directory = "/var/lib/spin/telemetry"
+            max_size_mb = 0
+        };
+        assert!(config_from_table(&table).is_err());
+
+        let table: toml::Table = toml::toml! {
+            [observability.buffer]
+            max_size_mb = 10
+        };
+        assert!(config_from_table(&table).is_err());
+    }
+
+    #[test]
+    fn rejects_unknown_propagators() {
+        let table: toml::Table = toml::toml! {
+            [observability.tracing]
+            propagators = ["ot"]
+        };
+        assert!(config_from_table(&table).is_err());
+    }
+
+    #[test]
+    fn rejects_unknown_keys() {
+        let table: toml::Table = toml::toml! {
+            [telemetry]
+            disable_components = ["healthcheck"]
+        };
+        assert!(config_from_table(&table).is_err());
+    }
+}
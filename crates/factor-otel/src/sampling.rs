@@ -0,0 +1,257 @@
+//! Sampling of guest spans.
+//!
+//! Guest spans may be head sampled per component, by trace ID ratio, and tail sampled. For tail
+//! sampling, rather than deciding whether to export each span as it ends, spans are buffered per
+//! trace until the guest has no open spans, and whole traces are then kept or dropped according
+//! to a [`TailSamplingPolicy`].
+
+use std::{collections::HashMap, sync::Arc, time::Duration};
+
+use anyhow::ensure;
+use opentelemetry::trace::{Status, TraceId};
+use opentelemetry_sdk::trace::SpanData;
+use serde::Deserialize;
+
+/// The maximum number of spans buffered by an instance. If a guest exceeds this, sampling
+/// decisions are made for the traces buffered so far.
+const MAX_BUFFERED_SPANS: usize = 4096;
+
+/// Decides which guest traces are exported.
+///
+/// A trace is exported if it matches any of the configured policies, and dropped otherwise.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct TailSamplingPolicy {
+    /// Export traces containing a span with an error status.
+    #[serde(default)]
+    pub errors: bool,
+    /// Export traces taking at least this many milliseconds, from the start of their first span
+    /// to the end of their last.
+    pub latency_threshold_ms: Option<u64>,
+    /// Export this fraction of traces, chosen by trace ID so that the decision is consistent
+    /// across instances and services.
+    pub rate: Option<f64>,
+}
+
+impl TailSamplingPolicy {
+    pub(crate) fn validate(&self) -> anyhow::Result<()> {
+        if let Some(rate) = self.rate {
+            ensure!(
+                (0.0..=1.0).contains(&rate),
+                "tail sampling rate must be between 0 and 1, got {rate}"
+            );
+        }
+        Ok(())
+    }
+
+    /// Whether the trace with the given ID, made up of the given spans, should be exported.
+    fn keep(&self, trace_id: TraceId, spans: &[SpanData]) -> bool {
+        if self.errors
+            && spans
+                .iter()
+                .any(|span| matches!(span.status, Status::Error { .. }))
+        {
+            return true;
+        }
+        if let Some(threshold) = self.latency_threshold_ms
+            && trace_duration(spans) >= Duration::from_millis(threshold)
+        {
+            return true;
+        }
+        if let Some(rate) = self.rate
+            && sampled_by_ratio(trace_id, rate)
+        {
+            return true;
+        }
+        false
+    }
+}
+
+/// Buffers the guest spans of an instance until sampling decisions can be made.
+pub(crate) struct TailSampler {
+    policy: Arc<TailSamplingPolicy>,
+    traces: HashMap<TraceId, Vec<SpanData>>,
+    buffered: usize,
+}
+
+impl TailSampler {
+    pub(crate) fn new(policy: Arc<TailSamplingPolicy>) -> Self {
+        Self {
+            policy,
+            traces: Default::default(),
+            buffered: 0,
+        }
+    }
+
+    /// Buffers an ended span.
+    pub(crate) fn push(&mut self, span: SpanData) {
+        self.traces
+            .entry(span.span_context.trace_id())
+            .or_default()
+            .push(span);
+        self.buffered += 1;
+    }
+
+    /// Whether the buffer is full, so that sampling decisions should be made even though the
+    /// guest has open spans.
+    pub(crate) fn is_full(&self) -> bool {
+        self.buffered >= MAX_BUFFERED_SPANS
+    }
+
+    /// Makes sampling decisions for all buffered traces, returning the spans to export.
+    pub(crate) fn flush(&mut self) -> Vec<SpanData> {
+        self.buffered = 0;
+        self.traces
+            .drain()
+            .filter(|(trace_id, spans)| self.policy.keep(*trace_id, spans))
+            .flat_map(|(_, spans)| spans)
+            .collect()
+    }
+}
+
+/// The time from the start of the earliest span to the end of the latest.
+fn trace_duration(spans: &[SpanData]) -> Duration {
+    let start = spans.iter().map(|span| span.start_time).min();
+    let end = spans.iter().map(|span| span.end_time).max();
+    match (start, end) {
+        (Some(start), Some(end)) => end.duration_since(start).unwrap_or_default(),
+        _ => Duration::ZERO,
+    }
+}
+
+/// Whether the trace with the given ID is among the given fraction of traces.
+///
+/// The decision depends only on the trace ID, so it is consistent wherever it is made.
+pub(crate) fn sampled_by_ratio(trace_id: TraceId, ratio: f64) -> bool {
+    trace_id_ratio(trace_id) < ratio
+}
+
+/// Maps a trace ID to a number in [0, 1), using its random low 64 bits.
+fn trace_id_ratio(trace_id: TraceId) -> f64 {
+    let bytes = trace_id.to_bytes();
+    let low = u64::from_be_bytes(bytes[8..].try_into().unwrap());
+    low as f64 / (u64::MAX as f64 + 1.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{borrow::Cow, collections::HashSet, time::SystemTime};
+
+    use opentelemetry::{
+        InstrumentationScope,
+        trace::{SpanContext, SpanId, SpanKind, TraceFlags, TraceState},
+    };
+    use opentelemetry_sdk::trace::{SpanEvents, SpanLinks};
+
+    use super::*;
+
+    fn span(trace_id: u128, duration_ms: u64, status: Status) -> SpanData {
+        let start_time = SystemTime::UNIX_EPOCH;
+        SpanData {
+            span_context: SpanContext::new(
+                TraceId::from(trace_id),
+                SpanId::from(1),
+                TraceFlags::SAMPLED,
+                false,
+                TraceState::default(),
+            ),
+            parent_span_id: SpanId::INVALID,
+            span_kind: SpanKind::Internal,
+            name: Cow::Borrowed("guest"),
+            start_time,
+            end_time: start_time + Duration::from_millis(duration_ms),
+            attributes: vec![],
+            dropped_attributes_count: 0,
+            events: SpanEvents::default(),
+            links: SpanLinks::default(),
+            status,
+            instrumentation_scope: InstrumentationScope::builder("test").build(),
+        }
+    }
+
+    fn sampled_traces(policy: TailSamplingPolicy, spans: Vec<SpanData>) -> HashSet<TraceId> {
+        let mut sampler = TailSampler::new(Arc::new(policy));
+        for span in spans {
+            sampler.push(span);
+        }
+        sampler
+            .flush()
+            .iter()
+            .map(|span| span.span_context.trace_id())
+            .collect()
+    }
+
+    #[test]
+    fn keeps_traces_with_errors() {
+        let policy = TailSamplingPolicy {
+            errors: true,
+            ..Default::default()
+        };
+        let spans = vec![
+            span(1, 1, Status::Ok),
+            span(1, 1, Status::error("failed")),
+            span(2, 1, Status::Ok),
+        ];
+        assert_eq!(
+            HashSet::from([TraceId::from(1)]),
+            sampled_traces(policy, spans)
+        );
+    }
+
+    #[test]
+    fn keeps_slow_traces() {
+        let policy = TailSamplingPolicy {
+            latency_threshold_ms: Some(100),
+            ..Default::default()
+        };
+        let spans = vec![span(1, 150, Status::Unset), span(2, 50, Status::Unset)];
+        assert_eq!(
+            HashSet::from([TraceId::from(1)]),
+            sampled_traces(policy, spans)
+        );
+    }
+
+    #[test]
+    fn samples_traces_by_rate() {
+        let all = TailSamplingPolicy {
+            rate: Some(1.0),
+            ..Default::default()
+        };
+        let none = TailSamplingPolicy {
+            rate: Some(0.0),
+            ..Default::default()
+        };
+        let spans = || vec![span(1, 1, Status::Unset), span(u128::MAX, 1, Status::Unset)];
+        assert_eq!(2, sampled_traces(all, spans()).len());
+        assert!(sampled_traces(none, spans()).is_empty());
+    }
+
+    #[test]
+    fn flushing_empties_buffer() {
+        let mut sampler = TailSampler::new(Arc::new(TailSamplingPolicy {
+            errors: true,
+            ..Default::default()
+        }));
+        sampler.push(span(1, 1, Status::error("failed")));
+        assert_eq!(1, sampler.flush().len());
+        assert!(sampler.flush().is_empty());
+        assert!(!sampler.is_full());
+    }
+
+    #[test]
+    fn samples_by_trace_id_ratio() {
+        assert!(sampled_by_ratio(TraceId::from(1), 0.1));
+        assert!(!sampled_by_ratio(TraceId::from(u128::MAX), 0.1));
+        assert!(!sampled_by_ratio(TraceId::from(1), 0.0));
+        assert!(sampled_by_ratio(TraceId::from(u128::MAX), 1.0));
+    }
+
+    #[test]
+    fn rejects_invalid_rates() {
+        let policy = TailSamplingPolicy {
+            rate: Some(1.5),
+            ..Default::default()
+        };
+        assert!(policy.validate().is_err());
+    }
+}
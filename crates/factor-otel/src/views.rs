@@ -0,0 +1,431 @@
+//! Metric views, which customize guest metrics before they are exported.
+//!
+//! Guests export metrics that they have already aggregated, so views are applied to aggregated
+//! data: dropping attributes merges the data points left with the same attributes, and histogram
+//! buckets can only be merged, not split.
+
+use anyhow::ensure;
+use serde::Deserialize;
+use spin_world::wasi::otel::{
+    metrics::{
+        self as wasi_metrics, GaugeDataPoint, HistogramDataPoint, MetricData, MetricNumber,
+        SumDataPoint,
+    },
+    types::KeyValue,
+};
+
+/// Customizes the guest metrics exported from matching instruments.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct MetricView {
+    /// The name of the instruments the view applies to, in which `*` matches any sequence of
+    /// characters.
+    pub instrument: String,
+    /// The name to export the metric under, rather than the instrument's.
+    pub name: Option<String>,
+    /// Attributes removed from data points. Data points left with the same attributes are merged.
+    #[serde(default)]
+    pub drop_attributes: Vec<String>,
+    /// The boundaries of the buckets of histograms. Each must be a boundary of the guest's
+    /// histogram, as buckets can only be merged.
+    pub bucket_boundaries: Option<Vec<f64>>,
+}
+
+impl MetricView {
+    pub(crate) fn validate(&self) -> anyhow::Result<()> {
+        ensure!(
+            self.name.is_none() || !self.instrument.contains('*'),
+            "metric view for {:?} cannot rename instruments matched by a wildcard",
+            self.instrument
+        );
+        if let Some(boundaries) = &self.bucket_boundaries {
+            ensure!(
+                boundaries.iter().all(|b| b.is_finite())
+                    && boundaries.windows(2).all(|pair| pair[0] < pair[1]),
+                "bucket_boundaries of the metric view for {:?} must be finite and increasing",
+                self.instrument
+            );
+        }
+        Ok(())
+    }
+
+    fn matches(&self, instrument: &str) -> bool {
+        wildcard_match(&self.instrument, instrument)
+    }
+
+    fn apply(&self, metric: &mut wasi_metrics::Metric) {
+        if let Some(name) = &self.name {
+            metric.name = name.clone();
+        }
+        match &mut metric.data {
+            MetricData::F64Gauge(gauge)
+            | MetricData::S64Gauge(gauge)
+            | MetricData::U64Gauge(gauge) => {
+                gauge.data_points = self.merge(std::mem::take(&mut gauge.data_points), merge_gauge);
+            }
+            MetricData::F64Sum(sum) | MetricData::S64Sum(sum) | MetricData::U64Sum(sum) => {
+                sum.data_points = self.merge(std::mem::take(&mut sum.data_points), merge_sum);
+            }
+            MetricData::F64Histogram(histogram)
+            | MetricData::S64Histogram(histogram)
+            | MetricData::U64Histogram(histogram) => {
+                if let Some(boundaries) = &self.bucket_boundaries {
+                    for dp in &mut histogram.data_points {
+                        rebucket(dp, boundaries, &metric.name);
+                    }
+                }
+                histogram.data_points =
+                    self.merge(std::mem::take(&mut histogram.data_points), merge_histogram);
+            }
+            MetricData::F64ExponentialHistogram(_)
+            | MetricData::S64ExponentialHistogram(_)
+            | MetricData::U64ExponentialHistogram(_) => {
+                if !self.drop_attributes.is_empty() || self.bucket_boundaries.is_some() {
+                    tracing::debug!(
+                        "Metric view for {:?} only renames exponential histogram {}",
+                        self.instrument,
+                        metric.name
+                    );
+                }
+            }
+        }
+    }
+
+    /// Drops attributes from data points, merging those left with the same attributes.
+    ///
+    /// `merge` merges a data point into another, returning it if they cannot be merged.
+    fn merge<T: DataPoint>(
+        &self,
+        data_points: Vec<T>,
+        merge: fn(&mut T, T) -> Option<T>,
+    ) -> Vec<T> {
+        if self.drop_attributes.is_empty() {
+            return data_points;
+        }
+        let mut merged: Vec<T> = Vec::with_capacity(data_points.len());
+        for mut dp in data_points {
+            dp.attributes_mut()
+                .retain(|attribute| !self.drop_attributes.contains(&attribute.key));
+            let unmerged = match merged
+                .iter_mut()
+                .find(|existing| same_attributes(existing.attributes(), dp.attributes()))
+            {
+                Some(existing) => merge(existing, dp),
+                None => Some(dp),
+            };
+            merged.extend(unmerged);
+        }
+        merged
+    }
+}
+
+/// Applies the first matching view to each guest metric.
+pub(crate) fn apply_views(views: &[MetricView], metrics: &mut wasi_metrics::ResourceMetrics) {
+    if views.is_empty() {
+        return;
+    }
+    let metrics = metrics
+        .scope_metrics
+        .iter_mut()
+        .flat_map(|scope_metrics| &mut scope_metrics.metrics);
+    for metric in metrics {
+        if let Some(view) = views.iter().find(|view| view.matches(&metric.name)) {
+            view.apply(metric);
+        }
+    }
+}
+
+/// A data point of a guest metric, identified by its attributes.
+pub(crate) trait DataPoint {
+    fn attributes(&self) -> &[KeyValue];
+    fn attributes_mut(&mut self) -> &mut Vec<KeyValue>;
+}
+
+impl DataPoint for GaugeDataPoint {
+    fn attributes(&self) -> &[KeyValue] {
+        &self.attributes
+    }
+
+    fn attributes_mut(&mut self) -> &mut Vec<KeyValue> {
+        &mut self.attributes
+    }
+}
+
+impl DataPoint for SumDataPoint {
+    fn attributes(&self) -> &[KeyValue] {
+        &self.attributes
+    }
+
+    fn attributes_mut(&mut self) -> &mut Vec<KeyValue> {
+        &mut self.attributes
+    }
+}
+
+impl DataPoint for HistogramDataPoint {
+    fn attributes(&self) -> &[KeyValue] {
+        &self.attributes
+    }
+
+    fn attributes_mut(&mut self) -> &mut Vec<KeyValue> {
+        &mut self.attributes
+    }
+}
+
+fn same_attributes(a: &[KeyValue], b: &[KeyValue]) -> bool {
+    a.len() == b.len()
+        && a.iter()
+            .all(|x| b.iter().any(|y| x.key == y.key && x.value == y.value))
+}
+
+/// Gauges report the last value; with no ordering between data points, the one seen last wins.
+pub(crate) fn merge_gauge(
+    existing: &mut GaugeDataPoint,
+    dp: GaugeDataPoint,
+) -> Option<GaugeDataPoint> {
+    existing.value = dp.value;
+    existing.exemplars.extend(dp.exemplars);
+    None
+}
+
+pub(crate) fn merge_sum(existing: &mut SumDataPoint, dp: SumDataPoint) -> Option<SumDataPoint> {
+    existing.value = add(existing.value, dp.value);
+    existing.exemplars.extend(dp.exemplars);
+    None
+}
+
+pub(crate) fn merge_histogram(
+    existing: &mut HistogramDataPoint,
+    dp: HistogramDataPoint,
+) -> Option<HistogramDataPoint> {
+    if existing.bounds != dp.bounds || existing.bucket_counts.len() != dp.bucket_counts.len() {
+        tracing::debug!("Not merging histogram data points with different buckets");
+        return Some(dp);
+    }
+    existing.count += dp.count;
+    existing.sum = add(existing.sum, dp.sum);
+    for (count, other) in existing.bucket_counts.iter_mut().zip(dp.bucket_counts) {
+        *count += other;
+    }
+    existing.min = match (existing.min, dp.min) {
+        (Some(a), Some(b)) => Some(if as_f64(b) < as_f64(a) { b } else { a }),
+        (a, b) => a.or(b),
+    };
+    existing.max = match (existing.max, dp.max) {
+        (Some(a), Some(b)) => Some(if as_f64(b) > as_f64(a) { b } else { a }),
+        (a, b) => a.or(b),
+    };
+    existing.exemplars.extend(dp.exemplars);
+    None
+}
+
+/// Merges the buckets of a histogram data point into buckets with the given boundaries, leaving
+/// it as it is if a boundary is not one of its own.
+fn rebucket(dp: &mut HistogramDataPoint, boundaries: &[f64], metric_name: &str) {
+    match merged_bucket_counts(&dp.bounds, &dp.bucket_counts, boundaries) {
+        Some(bucket_counts) => {
+            dp.bounds = boundaries.to_vec();
+            dp.bucket_counts = bucket_counts;
+        }
+        None => tracing::debug!(
+            "Not changing the buckets of histogram {metric_name}: the new boundaries are not a subset of its boundaries"
+        ),
+    }
+}
+
+fn merged_bucket_counts(bounds: &[f64], counts: &[u64], boundaries: &[f64]) -> Option<Vec<u64>> {
+    if counts.len() != bounds.len() + 1 {
+        return None;
+    }
+    let mut merged = Vec::with_capacity(boundaries.len() + 1);
+    let mut old = 0;
+    for &boundary in boundaries {
+        let mut count = 0;
+        loop {
+            let bound = *bounds.get(old)?;
+            if bound > boundary {
+                return None;
+            }
+            count += counts[old];
+            old += 1;
+            if bound == boundary {
+                break;
+            }
+        }
+        merged.push(count);
+    }
+    // The last bucket holds everything above the last boundary.
+    merged.push(counts[old..].iter().sum());
+    Some(merged)
+}
+
+fn add(a: MetricNumber, b: MetricNumber) -> MetricNumber {
+    match (a, b) {
+        (MetricNumber::F64(a), MetricNumber::F64(b)) => MetricNumber::F64(a + b),
+        (MetricNumber::S64(a), MetricNumber::S64(b)) => MetricNumber::S64(a.saturating_add(b)),
+        (MetricNumber::U64(a), MetricNumber::U64(b)) => MetricNumber::U64(a.saturating_add(b)),
+        (a, b) => MetricNumber::F64(as_f64(a) + as_f64(b)),
+    }
+}
+
+fn as_f64(number: MetricNumber) -> f64 {
+    match number {
+        MetricNumber::F64(n) => n,
+        MetricNumber::S64(n) => n as f64,
+        MetricNumber::U64(n) => n as f64,
+    }
+}
+
+/// Matches `name` against `pattern`, in which `*` matches any sequence of characters.
+fn wildcard_match(pattern: &str, name: &str) -> bool {
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or_default();
+    let Some(mut rest) = name.strip_prefix(first) else {
+        return false;
+    };
+    let parts: Vec<_> = parts.collect();
+    let Some((last, middle)) = parts.split_last() else {
+        // No wildcard: the whole name must match.
+        return rest.is_empty();
+    };
+    for part in middle {
+        match rest.find(part) {
+            Some(i) => rest = &rest[i + part.len()..],
+            None => return false,
+        }
+    }
+    rest.ends_with(last)
+}
+
+#[cfg(test)]
+mod tests {
+    use spin_world::wasi::clocks0_2_0::wall_clock::Datetime;
+
+    use super::*;
+
+    const TIME: Datetime = Datetime {
+        seconds: 1,
+        nanoseconds: 0,
+    };
+
+    fn attribute(key: &str, value: &str) -> KeyValue {
+        KeyValue {
+            key: key.into(),
+            value: format!("{value:?}"),
+        }
+    }
+
+    fn resource_metrics(metric: wasi_metrics::Metric) -> wasi_metrics::ResourceMetrics {
+        wasi_metrics::ResourceMetrics {
+            resource: wasi_metrics::Resource {
+                attributes: vec![],
+                schema_url: None,
+            },
+            scope_metrics: vec![wasi_metrics::ScopeMetrics {
+                scope: wasi_metrics::InstrumentationScope {
+                    name: "test".into(),
+                    version: None,
+                    schema_url: None,
+                    attributes: vec![],
+                },
+                metrics: vec![metric],
+            }],
+        }
+    }
+
+    fn view(instrument: &str) -> MetricView {
+        MetricView {
+            instrument: instrument.into(),
+            name: None,
+            drop_attributes: vec![],
+            bucket_boundaries: None,
+        }
+    }
+
+    #[test]
+    fn renames_and_drops_attributes() {
+        let sum_point = |user: &str, value| SumDataPoint {
+            attributes: vec![attribute("route", "/orders"), attribute("user", user)],
+            exemplars: vec![],
+            value: MetricNumber::U64(value),
+        };
+        let mut metrics = resource_metrics(wasi_metrics::Metric {
+            name: "orders.placed".into(),
+            description: String::new(),
+            unit: String::new(),
+            data: MetricData::U64Sum(wasi_metrics::Sum {
+                data_points: vec![sum_point("alice", 2), sum_point("bob", 3)],
+                start_time: TIME,
+                time: TIME,
+                temporality: wasi_metrics::Temporality::Cumulative,
+                is_monotonic: true,
+            }),
+        });
+        let views = [MetricView {
+            name: Some("orders".into()),
+            drop_attributes: vec!["user".into()],
+            ..view("orders.placed")
+        }];
+
+        apply_views(&views, &mut metrics);
+
+        let metric = &metrics.scope_metrics[0].metrics[0];
+        assert_eq!("orders", metric.name);
+        let MetricData::U64Sum(sum) = &metric.data else {
+            panic!("unexpected metric data");
+        };
+        assert_eq!(1, sum.data_points.len());
+        let attributes = &sum.data_points[0].attributes;
+        assert_eq!(1, attributes.len());
+        assert_eq!("route", attributes[0].key);
+        assert!(matches!(sum.data_points[0].value, MetricNumber::U64(5)));
+    }
+
+    #[test]
+    fn merges_histogram_buckets() {
+        assert_eq!(
+            Some(vec![3, 4, 5]),
+            merged_bucket_counts(&[1.0, 2.0, 5.0, 10.0], &[1, 2, 3, 1, 5], &[2.0, 10.0])
+        );
+        assert_eq!(
+            Some(vec![12]),
+            merged_bucket_counts(&[1.0, 2.0, 5.0, 10.0], &[1, 2, 3, 1, 5], &[])
+        );
+        assert_eq!(
+            None,
+            merged_bucket_counts(&[1.0, 2.0, 5.0, 10.0], &[1, 2, 3, 1, 5], &[3.0])
+        );
+        assert_eq!(None, merged_bucket_counts(&[1.0, 2.0], &[1, 2, 3], &[20.0]));
+    }
+
+    #[test]
+    fn matches_wildcards() {
+        assert!(wildcard_match(
+            "http.server.duration",
+            "http.server.duration"
+        ));
+        assert!(!wildcard_match(
+            "http.server.duration",
+            "http.server.duration2"
+        ));
+        assert!(wildcard_match("http.*", "http.server.duration"));
+        assert!(wildcard_match("*.duration", "http.server.duration"));
+        assert!(wildcard_match("http.*.duration", "http.server.duration"));
+        assert!(!wildcard_match("db.*", "http.server.duration"));
+        assert!(wildcard_match("*", "anything"));
+    }
+
+    #[test]
+    fn rejects_invalid_views() {
+        let rename_wildcard = MetricView {
+            name: Some("renamed".into()),
+            ..view("http.*")
+        };
+        assert!(rename_wildcard.validate().is_err());
+
+        let unordered = MetricView {
+            bucket_boundaries: Some(vec![10.0, 5.0]),
+            ..view("latency")
+        };
+        assert!(unordered.validate().is_err());
+    }
+}
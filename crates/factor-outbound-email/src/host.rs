@@ -0,0 +1,139 @@
+use std::sync::Arc;
+
+use spin_core::wasmtime::component::Resource;
+use spin_expressions::{Key as VariableKey, ProviderResolver};
+use spin_factor_capability_policy::{CapabilityChecker, operation};
+use spin_factor_otel::OtelFactorState;
+use spin_factor_outbound_networking::config::allowed_hosts::{OutboundAllowedHosts, OutboundUrl};
+use spin_factors::anyhow;
+use spin_resource_table::Table;
+use spin_world::spin::email::smtp::{self, Connection, Error, Message};
+use tracing::{Level, instrument};
+
+use crate::{Credentials, EmailTransport, TransportCreator};
+
+const DEFAULT_TABLE_CAPACITY: u32 = 256;
+
+/// The email state of a component instance, and the host implementation of
+/// `spin:email/smtp`.
+pub struct InstanceState {
+    component_id: String,
+    allowed_hosts: OutboundAllowedHosts,
+    capabilities: CapabilityChecker,
+    resolver: Arc<ProviderResolver>,
+    create_transport: Arc<dyn TransportCreator>,
+    connections: Table<Arc<dyn EmailTransport>>,
+    otel: OtelFactorState,
+}
+
+impl InstanceState {
+    pub fn new(
+        component_id: String,
+        allowed_hosts: OutboundAllowedHosts,
+        capabilities: CapabilityChecker,
+        resolver: Arc<ProviderResolver>,
+        create_transport: Arc<dyn TransportCreator>,
+        otel: OtelFactorState,
+    ) -> Self {
+        Self {
+            component_id,
+            allowed_hosts,
+            capabilities,
+            resolver,
+            create_transport,
+            connections: Table::new(DEFAULT_TABLE_CAPACITY),
+            otel,
+        }
+    }
+
+    async fn resolve_variable(&self, name: &str) -> Result<String, Error> {
+        let key =
+            VariableKey::new(name).map_err(|err| Error::InvalidCredentials(err.to_string()))?;
+        self.resolver
+            .resolve(&self.component_id, key)
+            .await
+            .map_err(|err| Error::InvalidCredentials(err.to_string()))
+    }
+
+    async fn resolve_credentials(
+        &self,
+        credentials: smtp::Credentials,
+    ) -> Result<Credentials, Error> {
+        Ok(Credentials {
+            username: self
+                .resolve_variable(&credentials.username_variable)
+                .await?,
+            password: self
+                .resolve_variable(&credentials.password_variable)
+                .await?,
+        })
+    }
+
+    fn get_conn(
+        &self,
+        connection: &Resource<Connection>,
+    ) -> Result<Arc<dyn EmailTransport>, Error> {
+        self.connections
+            .get(connection.rep())
+            .cloned()
+            .ok_or_else(|| Error::Other("could not find connection for resource".into()))
+    }
+}
+
+impl smtp::Host for InstanceState {
+    fn convert_error(&mut self, err: Error) -> anyhow::Result<Error> {
+        Ok(err)
+    }
+}
+
+impl smtp::HostConnection for InstanceState {
+    #[instrument(name = "spin_outbound_email.open_connection", skip(self, credentials), err(level = Level::INFO), fields(otel.kind = "client"))]
+    async fn open(
+        &mut self,
+        address: String,
+        credentials: Option<smtp::Credentials>,
+    ) -> Result<Resource<Connection>, Error> {
+        self.otel.reparent_tracing_span();
+
+        if !self
+            .allowed_hosts
+            .check_url(&address, "smtps")
+            .await
+            .map_err(|err| Error::Other(err.to_string()))?
+        {
+            return Err(Error::AddressNotPermitted);
+        }
+        let url = OutboundUrl::parse(&address, "smtps").map_err(|_| Error::AddressNotPermitted)?;
+        self.capabilities
+            .check(operation::EMAIL, url.host(), None)
+            .map_err(|_| Error::AddressNotPermitted)?;
+
+        let credentials = match credentials {
+            Some(credentials) => Some(self.resolve_credentials(credentials).await?),
+            None => None,
+        };
+        let transport = self.create_transport.create(&address, credentials)?;
+        self.connections
+            .push(transport)
+            .map(Resource::new_own)
+            .map_err(|_| Error::Other("too many connections".into()))
+    }
+
+    #[instrument(name = "spin_outbound_email.send", skip_all, err(level = Level::INFO),
+        fields(otel.kind = "client", email.recipients = message.to.len() + message.cc.len() + message.bcc.len()))]
+    async fn send(
+        &mut self,
+        connection: Resource<Connection>,
+        message: Message,
+    ) -> Result<(), Error> {
+        self.otel.reparent_tracing_span();
+
+        let conn = self.get_conn(&connection)?;
+        conn.send(message).await
+    }
+
+    async fn drop(&mut self, connection: Resource<Connection>) -> anyhow::Result<()> {
+        self.connections.remove(connection.rep());
+        Ok(())
+    }
+}
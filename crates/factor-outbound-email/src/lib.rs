@@ -0,0 +1,126 @@
+//! A factor providing the `spin:email/smtp` interface.
+//!
+//! Components send email through SMTP servers permitted by their
+//! `allowed_outbound_hosts`. The host speaks SMTP and TLS, and resolves the
+//! credentials to log in with from component variables, so they can come from
+//! any variables provider and are never exposed to the guest.
+
+mod host;
+mod smtp;
+
+use std::sync::Arc;
+
+use spin_core::async_trait;
+use spin_factor_capability_policy::CapabilityChecker;
+use spin_factor_otel::OtelFactorState;
+use spin_factor_outbound_networking::OutboundNetworkingFactor;
+use spin_factor_variables::VariablesFactor;
+use spin_factors::{
+    ConfigureAppContext, Factor, FactorData, InitContext, PrepareContext, RuntimeFactors,
+    SelfInstanceBuilder,
+};
+use spin_world::spin::email::smtp::{Error, Message};
+
+pub use host::InstanceState;
+pub use smtp::SmtpTransport;
+
+/// A factor that sends email over SMTP.
+pub struct OutboundEmailFactor {
+    create_transport: Arc<dyn TransportCreator>,
+}
+
+impl OutboundEmailFactor {
+    /// Create a new OutboundEmailFactor, which connects to servers with the
+    /// given [`TransportCreator`].
+    pub fn new(create_transport: Arc<dyn TransportCreator>) -> Self {
+        Self { create_transport }
+    }
+}
+
+impl Factor for OutboundEmailFactor {
+    type RuntimeConfig = ();
+    type AppState = ();
+    type InstanceBuilder = InstanceState;
+
+    fn init(&mut self, ctx: &mut impl InitContext<Self>) -> anyhow::Result<()> {
+        ctx.link_bindings(spin_world::spin::email::smtp::add_to_linker::<_, FactorData<Self>>)
+    }
+
+    fn configure_app<T: RuntimeFactors>(
+        &self,
+        _ctx: ConfigureAppContext<T, Self>,
+    ) -> anyhow::Result<Self::AppState> {
+        Ok(())
+    }
+
+    fn prepare<T: RuntimeFactors>(
+        &self,
+        mut ctx: PrepareContext<T, Self>,
+    ) -> anyhow::Result<Self::InstanceBuilder> {
+        let component_id = ctx.app_component().id().to_string();
+        let allowed_hosts = ctx
+            .instance_builder::<OutboundNetworkingFactor>()?
+            .allowed_hosts();
+        let resolver = ctx
+            .instance_builder::<VariablesFactor>()?
+            .expression_resolver()
+            .clone();
+        let capabilities = CapabilityChecker::from_prepare_context(&mut ctx)?;
+        let otel = OtelFactorState::from_prepare_context(&mut ctx)?;
+
+        Ok(InstanceState::new(
+            component_id,
+            allowed_hosts,
+            capabilities,
+            resolver,
+            self.create_transport.clone(),
+            otel,
+        ))
+    }
+}
+
+impl SelfInstanceBuilder for InstanceState {}
+
+/// Credentials to log in to an SMTP server with, as resolved by the host.
+#[derive(Clone)]
+pub struct Credentials {
+    pub username: String,
+    pub password: String,
+}
+
+impl std::fmt::Debug for Credentials {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Credentials")
+            .field("username", &self.username)
+            .finish_non_exhaustive()
+    }
+}
+
+/// A connection to an SMTP server.
+#[async_trait]
+pub trait EmailTransport: Send + Sync {
+    /// Send a message, returning once the server has accepted it.
+    async fn send(&self, message: Message) -> Result<(), Error>;
+}
+
+/// A trait for creating connections to SMTP servers.
+pub trait TransportCreator: Send + Sync {
+    fn create(
+        &self,
+        address: &str,
+        credentials: Option<Credentials>,
+    ) -> Result<Arc<dyn EmailTransport>, Error>;
+}
+
+impl<F> TransportCreator for F
+where
+    F: Fn(&str, Option<Credentials>) -> Result<Arc<dyn EmailTransport>, Error> + Send + Sync,
+{
+    fn create(
+        &self,
+        address: &str,
+        credentials: Option<Credentials>,
+    ) -> Result<Arc<dyn EmailTransport>, Error> {
+        self(address, credentials)
+    }
+}
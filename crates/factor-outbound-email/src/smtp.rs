@@ -0,0 +1,251 @@
+use std::net::IpAddr;
+use std::sync::Arc;
+
+use lettre::message::{Attachment, Mailbox, MultiPart, SinglePart, header::ContentType};
+use lettre::transport::smtp::client::{Tls, TlsParameters};
+use lettre::{AsyncTransport, Tokio1Executor};
+use spin_core::async_trait;
+use spin_world::spin::email::smtp::{Error, Message};
+use url::Url;
+
+use crate::{Credentials, EmailTransport, TransportCreator};
+
+/// SMTP reply codes for authentication failures.
+const AUTHENTICATION_FAILURE_CODES: &[&str] = &["530", "534", "535"];
+
+/// An [`EmailTransport`] which sends messages to an SMTP server over TLS.
+pub struct SmtpTransport {
+    inner: lettre::AsyncSmtpTransport<Tokio1Executor>,
+}
+
+impl SmtpTransport {
+    /// Create a [`TransportCreator`] that creates an [`SmtpTransport`].
+    pub fn creator() -> Arc<dyn TransportCreator> {
+        Arc::new(|address: &str, credentials: Option<Credentials>| {
+            Ok(Arc::new(SmtpTransport::create(address, credentials)?) as Arc<dyn EmailTransport>)
+        })
+    }
+
+    /// Create a new [`SmtpTransport`] for the server at the given
+    /// `smtp://` or `smtps://` address.
+    pub fn create(address: &str, credentials: Option<Credentials>) -> Result<Self, Error> {
+        let url = Url::parse(address).map_err(|_| Error::InvalidAddress)?;
+        let host = url
+            .host_str()
+            .filter(|host| !host.is_empty())
+            .ok_or(Error::InvalidAddress)?;
+        let mut builder = match url.scheme() {
+            "smtps" => lettre::AsyncSmtpTransport::<Tokio1Executor>::relay(host)
+                .map_err(connection_failed)?,
+            // Local development servers seldom offer TLS, and traffic to them
+            // never leaves the machine.
+            "smtp" if is_loopback(host) => {
+                let tls = TlsParameters::new(host.into()).map_err(connection_failed)?;
+                lettre::AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(host)
+                    .port(lettre::transport::smtp::SUBMISSION_PORT)
+                    .tls(Tls::Opportunistic(tls))
+            }
+            "smtp" => lettre::AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(host)
+                .map_err(connection_failed)?,
+            _ => return Err(Error::InvalidAddress),
+        };
+        if let Some(port) = url.port() {
+            builder = builder.port(port);
+        }
+        if let Some(Credentials { username, password }) = credentials {
+            builder = builder.credentials(
+                lettre::transport::smtp::authentication::Credentials::new(username, password),
+            );
+        }
+        Ok(Self {
+            inner: builder.build(),
+        })
+    }
+}
+
+#[async_trait]
+impl EmailTransport for SmtpTransport {
+    async fn send(&self, message: Message) -> Result<(), Error> {
+        let message = build_message(message)?;
+        self.inner.send(message).await.map_err(send_error)?;
+        Ok(())
+    }
+}
+
+/// Builds a MIME message from a guest's message.
+fn build_message(message: Message) -> Result<lettre::Message, Error> {
+    if message.to.is_empty() && message.cc.is_empty() && message.bcc.is_empty() {
+        return Err(Error::InvalidMessage(
+            "the message has no recipients".into(),
+        ));
+    }
+    let mut builder = lettre::Message::builder()
+        .from(mailbox(&message.from)?)
+        .subject(message.subject);
+    for to in &message.to {
+        builder = builder.to(mailbox(to)?);
+    }
+    for cc in &message.cc {
+        builder = builder.cc(mailbox(cc)?);
+    }
+    for bcc in &message.bcc {
+        builder = builder.bcc(mailbox(bcc)?);
+    }
+    if let Some(reply_to) = &message.reply_to {
+        builder = builder.reply_to(mailbox(reply_to)?);
+    }
+
+    let built = if message.attachments.is_empty() {
+        match message.html_body {
+            Some(html) => builder.multipart(MultiPart::alternative_plain_html(message.body, html)),
+            None => builder.singlepart(SinglePart::plain(message.body)),
+        }
+    } else {
+        let mut content = match message.html_body {
+            Some(html) => {
+                MultiPart::mixed().multipart(MultiPart::alternative_plain_html(message.body, html))
+            }
+            None => MultiPart::mixed().singlepart(SinglePart::plain(message.body)),
+        };
+        for attachment in message.attachments {
+            let content_type = ContentType::parse(&attachment.content_type).map_err(|err| {
+                Error::InvalidMessage(format!(
+                    "invalid content type {:?} for attachment {:?}: {err}",
+                    attachment.content_type, attachment.filename
+                ))
+            })?;
+            content = content.singlepart(
+                Attachment::new(attachment.filename).body(attachment.content, content_type),
+            );
+        }
+        builder.multipart(content)
+    };
+    built.map_err(|err| Error::InvalidMessage(err.to_string()))
+}
+
+fn mailbox(mailbox: &str) -> Result<Mailbox, Error> {
+    mailbox
+        .parse()
+        .map_err(|err| Error::InvalidMessage(format!("invalid mailbox {mailbox:?}: {err}")))
+}
+
+fn is_loopback(host: &str) -> bool {
+    host == "localhost"
+        || host
+            .trim_start_matches('[')
+            .trim_end_matches(']')
+            .parse::<IpAddr>()
+            .is_ok_and(|ip| ip.is_loopback())
+}
+
+fn connection_failed(err: impl std::fmt::Display) -> Error {
+    Error::ConnectionFailed(err.to_string())
+}
+
+fn send_error(err: lettre::transport::smtp::Error) -> Error {
+    let code = err.status().map(|code| code.to_string());
+    if code
+        .as_deref()
+        .is_some_and(|code| AUTHENTICATION_FAILURE_CODES.contains(&code))
+    {
+        Error::AuthenticationFailed(err.to_string())
+    } else if err.is_permanent() || err.is_transient() {
+        Error::Rejected(err.to_string())
+    } else if err.is_tls() || err.is_timeout() || err.is_client() {
+        Error::ConnectionFailed(err.to_string())
+    } else {
+        Error::Other(err.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use spin_world::spin::email::smtp::Attachment as GuestAttachment;
+
+    use super::*;
+
+    fn message() -> Message {
+        Message {
+            from: "Alerts <alerts@example.com>".into(),
+            to: vec!["ops@example.com".into()],
+            cc: vec![],
+            bcc: vec!["audit@example.com".into()],
+            reply_to: None,
+            subject: "Disk space low".into(),
+            body: "The disk is 95% full.".into(),
+            html_body: None,
+            attachments: vec![],
+        }
+    }
+
+    fn formatted(message: Message) -> String {
+        String::from_utf8(build_message(message).unwrap().formatted()).unwrap()
+    }
+
+    #[test]
+    fn builds_plain_messages_without_bcc_header() {
+        let formatted = formatted(message());
+        assert!(formatted.contains("From: Alerts <alerts@example.com>"));
+        assert!(formatted.contains("To: ops@example.com"));
+        assert!(formatted.contains("Subject: Disk space low"));
+        assert!(!formatted.contains("audit@example.com"));
+        assert!(formatted.contains("The disk is 95% full."));
+    }
+
+    #[test]
+    fn builds_multipart_messages() {
+        let formatted = formatted(Message {
+            html_body: Some("<p>The disk is 95% full.</p>".into()),
+            attachments: vec![GuestAttachment {
+                filename: "df.txt".into(),
+                content_type: "text/plain".into(),
+                content: b"/dev/sda1 95%".to_vec(),
+            }],
+            ..message()
+        });
+        assert!(formatted.contains("multipart/mixed"));
+        assert!(formatted.contains("multipart/alternative"));
+        assert!(formatted.contains("<p>The disk is 95% full.</p>"));
+        assert!(formatted.contains("filename=\"df.txt\""));
+    }
+
+    #[test]
+    fn rejects_invalid_messages() {
+        let invalid = [
+            Message {
+                to: vec![],
+                bcc: vec![],
+                ..message()
+            },
+            Message {
+                from: "not a mailbox".into(),
+                ..message()
+            },
+            Message {
+                attachments: vec![GuestAttachment {
+                    filename: "x".into(),
+                    content_type: "not a type".into(),
+                    content: vec![],
+                }],
+                ..message()
+            },
+        ];
+        for message in invalid {
+            assert!(matches!(
+                build_message(message),
+                Err(Error::InvalidMessage(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_addresses() {
+        for address in ["smtp.example.com", "https://smtp.example.com", "smtps://"] {
+            assert!(matches!(
+                SmtpTransport::create(address, None),
+                Err(Error::InvalidAddress)
+            ));
+        }
+        assert!(SmtpTransport::create("smtp://localhost:1025", None).is_ok());
+    }
+}
@@ -0,0 +1,194 @@
+use std::sync::{Arc, Mutex};
+
+use anyhow::bail;
+use spin_core::async_trait;
+use spin_core::wasmtime::component::Resource;
+use spin_factor_capability_policy::{CapabilityPolicyFactor, runtime_config::config_from_table};
+use spin_factor_outbound_email::{Credentials, EmailTransport, OutboundEmailFactor};
+use spin_factor_outbound_networking::OutboundNetworkingFactor;
+use spin_factor_variables::VariablesFactor;
+use spin_factors::{RuntimeFactors, anyhow};
+use spin_factors_test::{TestEnvironment, toml};
+use spin_world::spin::email::smtp::{self, Error, HostConnection, Message};
+
+/// Records the connections opened, and the messages sent over them.
+#[derive(Clone, Default)]
+struct MockTransports {
+    opened: Arc<Mutex<Vec<(String, Option<Credentials>)>>>,
+    sent: Arc<Mutex<Vec<Message>>>,
+}
+
+#[async_trait]
+impl EmailTransport for MockTransports {
+    async fn send(&self, message: Message) -> Result<(), Error> {
+        self.sent.lock().unwrap().push(message);
+        Ok(())
+    }
+}
+
+#[derive(RuntimeFactors)]
+struct TestFactors {
+    variables: VariablesFactor,
+    networking: OutboundNetworkingFactor,
+    email: OutboundEmailFactor,
+}
+
+fn email_factor(mock: &MockTransports) -> OutboundEmailFactor {
+    let creator = mock.clone();
+    OutboundEmailFactor::new(Arc::new(
+        move |address: &str, credentials: Option<Credentials>| {
+            creator
+                .opened
+                .lock()
+                .unwrap()
+                .push((address.to_owned(), credentials));
+            Ok(Arc::new(creator.clone()) as Arc<dyn EmailTransport>)
+        },
+    ))
+}
+
+fn test_env(mock: &MockTransports) -> TestEnvironment<TestFactors> {
+    let factors = TestFactors {
+        variables: VariablesFactor::default(),
+        networking: OutboundNetworkingFactor::new(),
+        email: email_factor(mock),
+    };
+    TestEnvironment::new(factors).extend_manifest(toml! {
+        [variables]
+        smtp_password = { default = "hunter2" }
+
+        [component.test-component]
+        source = "does-not-exist.wasm"
+        allowed_outbound_hosts = ["smtps://smtp.example.com"]
+        variables = { smtp_user = "alerts", smtp_pass = "{{ smtp_password }}" }
+    })
+}
+
+fn message() -> Message {
+    Message {
+        from: "alerts@example.com".into(),
+        to: vec!["ops@example.com".into()],
+        cc: vec![],
+        bcc: vec![],
+        reply_to: None,
+        subject: "Disk space low".into(),
+        body: "The disk is 95% full.".into(),
+        html_body: None,
+        attachments: vec![],
+    }
+}
+
+#[tokio::test]
+async fn sends_with_credentials_from_variables() -> anyhow::Result<()> {
+    let mock = MockTransports::default();
+    let mut state = test_env(&mock).build_instance_state().await?;
+
+    let connection = state
+        .email
+        .open(
+            "smtps://smtp.example.com".into(),
+            Some(smtp::Credentials {
+                username_variable: "smtp_user".into(),
+                password_variable: "smtp_pass".into(),
+            }),
+        )
+        .await
+        .unwrap();
+    state
+        .email
+        .send(Resource::new_borrow(connection.rep()), message())
+        .await
+        .unwrap();
+
+    let opened = mock.opened.lock().unwrap();
+    let (address, credentials) = &opened[0];
+    assert_eq!("smtps://smtp.example.com", address);
+    let credentials = credentials.as_ref().unwrap();
+    assert_eq!("alerts", credentials.username);
+    assert_eq!("hunter2", credentials.password);
+    assert_eq!("Disk space low", mock.sent.lock().unwrap()[0].subject);
+    Ok(())
+}
+
+#[tokio::test]
+async fn disallowed_host_fails() -> anyhow::Result<()> {
+    let mock = MockTransports::default();
+    let mut state = test_env(&mock).build_instance_state().await?;
+
+    for address in ["smtps://smtp.other.test", "smtp://smtp.example.com"] {
+        let Err(err) = state.email.open(address.into(), None).await else {
+            bail!("expected {address} to be disallowed");
+        };
+        assert!(matches!(err, Error::AddressNotPermitted), "{err:?}");
+    }
+    assert!(mock.opened.lock().unwrap().is_empty());
+    Ok(())
+}
+
+#[tokio::test]
+async fn undefined_credential_variables_fail() -> anyhow::Result<()> {
+    let mock = MockTransports::default();
+    let mut state = test_env(&mock).build_instance_state().await?;
+
+    let Err(err) = state
+        .email
+        .open(
+            "smtps://smtp.example.com".into(),
+            Some(smtp::Credentials {
+                username_variable: "smtp_user".into(),
+                password_variable: "undefined".into(),
+            }),
+        )
+        .await
+    else {
+        bail!("expected undefined variable to fail");
+    };
+    assert!(matches!(err, Error::InvalidCredentials(_)), "{err:?}");
+    Ok(())
+}
+
+#[derive(RuntimeFactors)]
+struct PolicyTestFactors {
+    capability_policy: CapabilityPolicyFactor,
+    variables: VariablesFactor,
+    networking: OutboundNetworkingFactor,
+    email: OutboundEmailFactor,
+}
+
+#[tokio::test]
+async fn capability_policy_denies_servers() -> anyhow::Result<()> {
+    let mock = MockTransports::default();
+    let env = TestEnvironment::new(PolicyTestFactors {
+        capability_policy: CapabilityPolicyFactor::new(),
+        variables: VariablesFactor::default(),
+        networking: OutboundNetworkingFactor::new(),
+        email: email_factor(&mock),
+    })
+    .extend_manifest(toml! {
+        [component.test-component]
+        source = "does-not-exist.wasm"
+        allowed_outbound_hosts = ["smtps://smtp.example.com"]
+    })
+    .runtime_config(PolicyTestFactorsRuntimeConfig {
+        capability_policy: config_from_table(&toml! {
+            [[capability_policy]]
+            effect = "deny"
+            operations = ["*"]
+        })?,
+        variables: None,
+        networking: None,
+        email: None,
+    })?;
+    let mut state = env.build_instance_state().await?;
+
+    let Err(err) = state
+        .email
+        .open("smtps://smtp.example.com".into(), None)
+        .await
+    else {
+        bail!("expected the policy to deny the connection");
+    };
+    assert!(matches!(err, Error::AddressNotPermitted), "{err:?}");
+    assert!(mock.opened.lock().unwrap().is_empty());
+    Ok(())
+}
@@ -0,0 +1,158 @@
+use std::{
+    sync::Mutex,
+    time::{Duration, SystemTime},
+};
+
+use anyhow::Context as _;
+use aws_config::BehaviorVersion;
+use aws_credential_types::{
+    Credentials,
+    provider::{ProvideCredentials as _, SharedCredentialsProvider},
+};
+use aws_sigv4::{
+    http_request::{
+        PayloadChecksumKind, PercentEncodingMode, SignableBody, SignableRequest, SigningSettings,
+        UriPathNormalizationMode,
+    },
+    sign::v4,
+};
+use bytes::Bytes;
+use spin_world::async_trait;
+use tokio::sync::OnceCell;
+
+use crate::signing::RequestSigner;
+
+/// How long before they expire credentials are refreshed.
+const CREDENTIALS_REFRESH_MARGIN: Duration = Duration::from_secs(5 * 60);
+
+/// Signs requests with AWS Signature Version 4, using credentials from the
+/// standard AWS credentials chain: environment variables, the shared config
+/// and credentials files, SSO, and container or instance metadata.
+pub struct AwsSigV4Signer {
+    service: String,
+    region: String,
+    provider: OnceCell<Option<SharedCredentialsProvider>>,
+    credentials: Mutex<Option<Credentials>>,
+}
+
+impl AwsSigV4Signer {
+    /// Creates a signer for the given AWS service (e.g. `s3`) and region.
+    pub fn new(service: impl Into<String>, region: impl Into<String>) -> Self {
+        Self {
+            service: service.into(),
+            region: region.into(),
+            provider: OnceCell::new(),
+            credentials: Mutex::new(None),
+        }
+    }
+
+    async fn credentials(&self) -> anyhow::Result<Credentials> {
+        if let Some(credentials) = self.credentials.lock().unwrap().as_ref()
+            && credentials
+                .expiry()
+                .is_none_or(|expiry| expiry > SystemTime::now() + CREDENTIALS_REFRESH_MARGIN)
+        {
+            return Ok(credentials.clone());
+        }
+        let provider = self
+            .provider
+            .get_or_init(|| async {
+                aws_config::load_defaults(BehaviorVersion::latest())
+                    .await
+                    .credentials_provider()
+            })
+            .await
+            .as_ref()
+            .context("no AWS credentials provider is available")?;
+        let credentials = provider
+            .provide_credentials()
+            .await
+            .context("failed to load AWS credentials")?;
+        *self.credentials.lock().unwrap() = Some(credentials.clone());
+        Ok(credentials)
+    }
+
+    fn settings(&self) -> SigningSettings {
+        let mut settings = SigningSettings::default();
+        // S3 signs the payload hash as a header and doesn't normalize paths.
+        if self.service == "s3" {
+            settings.payload_checksum_kind = PayloadChecksumKind::XAmzSha256;
+            settings.percent_encoding_mode = PercentEncodingMode::Single;
+            settings.uri_path_normalization_mode = UriPathNormalizationMode::Disabled;
+        }
+        settings
+    }
+}
+
+#[async_trait]
+impl RequestSigner for AwsSigV4Signer {
+    async fn sign(&self, request: &mut http::Request<Bytes>) -> anyhow::Result<()> {
+        let identity = self.credentials().await?.into();
+        let params = v4::SigningParams::builder()
+            .identity(&identity)
+            .region(&self.region)
+            .name(&self.service)
+            .time(SystemTime::now())
+            .settings(self.settings())
+            .build()?
+            .into();
+        let headers = request
+            .headers()
+            .iter()
+            .map(|(name, value)| Ok((name.as_str(), value.to_str()?)))
+            .collect::<anyhow::Result<Vec<_>>>()
+            .context("cannot sign non-UTF-8 header values")?;
+        let signable = SignableRequest::new(
+            request.method().as_str(),
+            request.uri().to_string(),
+            headers.into_iter(),
+            SignableBody::Bytes(request.body()),
+        )?;
+        let (instructions, _signature) =
+            aws_sigv4::http_request::sign(signable, &params)?.into_parts();
+        instructions.apply_to_request_http1x(request);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_credentials() -> Credentials {
+        Credentials::new("ANOTREAL", "notrealsecret", None, None, "test")
+    }
+
+    #[tokio::test]
+    async fn signs_requests() {
+        let signer = AwsSigV4Signer::new("sqs", "us-east-1");
+        *signer.credentials.lock().unwrap() = Some(test_credentials());
+
+        let mut request = http::Request::post("https://sqs.us-east-1.amazonaws.com/")
+            .header("content-type", "application/x-amz-json-1.0")
+            .body(Bytes::from_static(b"{}"))
+            .unwrap();
+        signer.sign(&mut request).await.unwrap();
+
+        let authorization = request.headers()["authorization"].to_str().unwrap();
+        assert!(
+            authorization.starts_with("AWS4-HMAC-SHA256 Credential=ANOTREAL/"),
+            "{authorization}"
+        );
+        assert!(authorization.contains("/us-east-1/sqs/aws4_request"));
+        assert!(request.headers().contains_key("x-amz-date"));
+        assert!(!request.headers().contains_key("x-amz-content-sha256"));
+    }
+
+    #[tokio::test]
+    async fn signs_s3_payloads() {
+        let signer = AwsSigV4Signer::new("s3", "us-east-1");
+        *signer.credentials.lock().unwrap() = Some(test_credentials());
+
+        let mut request = http::Request::put("https://bucket.s3.us-east-1.amazonaws.com/key")
+            .body(Bytes::from_static(b"hello"))
+            .unwrap();
+        signer.sign(&mut request).await.unwrap();
+        assert!(request.headers().contains_key("x-amz-content-sha256"));
+    }
+}
@@ -0,0 +1,33 @@
+//! Delaying and failing outbound HTTP requests as a scenario directs.
+
+use std::sync::Arc;
+
+use spin_fault_injection::FaultInjector;
+use spin_world::async_trait;
+use wasmtime_wasi_http::p2::{HttpResult, bindings::http::types::ErrorCode};
+
+use crate::intercept::{InterceptOutcome, InterceptRequest, OutboundHttpInterceptor};
+
+/// An [`OutboundHttpInterceptor`] which applies a [`FaultInjector`]'s faults
+/// to `outbound_http.request` calls, targeted by request URL, and passes on
+/// the requests that aren't failed.
+pub struct FaultInjectingInterceptor {
+    faults: Arc<FaultInjector>,
+}
+
+impl FaultInjectingInterceptor {
+    pub fn new(faults: Arc<FaultInjector>) -> Self {
+        Self { faults }
+    }
+}
+
+#[async_trait]
+impl OutboundHttpInterceptor for FaultInjectingInterceptor {
+    async fn intercept(&self, request: InterceptRequest) -> HttpResult<InterceptOutcome> {
+        let url = request.uri().to_string();
+        match self.faults.inject("outbound_http.request", &url).await {
+            Ok(()) => Ok(InterceptOutcome::Continue(request)),
+            Err(message) => Err(ErrorCode::InternalError(Some(message)).into()),
+        }
+    }
+}
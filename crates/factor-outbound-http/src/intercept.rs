@@ -1,4 +1,4 @@
-use std::net::SocketAddr;
+use std::{net::SocketAddr, sync::Arc};
 
 use http::{Request, Response};
 use http_body_util::{BodyExt, Full};
@@ -23,6 +23,33 @@ pub trait OutboundHttpInterceptor: Send + Sync {
     async fn intercept(&self, request: InterceptRequest) -> HttpResult<InterceptOutcome>;
 }
 
+/// Inspects or replaces the response to a request which an interceptor passed
+/// on to the default outgoing request handler; see
+/// [`InterceptRequest::on_response`].
+#[async_trait]
+pub trait ResponseInterceptor: Send + Sync {
+    async fn intercept_response(
+        self: Box<Self>,
+        response: Response<HyperBody>,
+    ) -> HttpResult<Response<HyperBody>>;
+}
+
+/// Passes requests that the `first` interceptor continues on to `then`.
+pub(crate) struct ChainedInterceptor {
+    pub first: Arc<dyn OutboundHttpInterceptor>,
+    pub then: Arc<dyn OutboundHttpInterceptor>,
+}
+
+#[async_trait]
+impl OutboundHttpInterceptor for ChainedInterceptor {
+    async fn intercept(&self, request: InterceptRequest) -> HttpResult<InterceptOutcome> {
+        match self.first.intercept(request).await? {
+            InterceptOutcome::Continue(request) => self.then.intercept(request).await,
+            complete => Ok(complete),
+        }
+    }
+}
+
 /// The type returned by an [`OutboundHttpInterceptor`].
 pub enum InterceptOutcome {
     /// The intercepted request will be passed on to the default outgoing
@@ -42,6 +69,7 @@ pub struct InterceptRequest {
     inner: Request<()>,
     body: InterceptBody,
     pub(crate) override_connect_addr: Option<SocketAddr>,
+    pub(crate) response_interceptors: Vec<Box<dyn ResponseInterceptor>>,
 }
 
 enum InterceptBody {
@@ -63,6 +91,15 @@ impl InterceptRequest {
         self.override_connect_addr = Some(endpoint);
     }
 
+    /// Passes the response to this request to the given interceptor, if the
+    /// request is sent by the default outgoing request handler.
+    ///
+    /// Response interceptors run in the reverse of the order they were added
+    /// in, so that the first request interceptor sees the response last.
+    pub fn on_response(&mut self, interceptor: impl ResponseInterceptor + 'static) {
+        self.response_interceptors.push(Box::new(interceptor));
+    }
+
     pub fn into_hyper_request(self) -> Request<HyperBody> {
         let (parts, ()) = self.inner.into_parts();
         Request::from_parts(parts, self.body.into())
@@ -98,6 +135,7 @@ impl From<Request<HyperBody>> for InterceptRequest {
             inner: Request::from_parts(parts, ()),
             body: InterceptBody::Hyper(body),
             override_connect_addr: None,
+            response_interceptors: Vec::new(),
         }
     }
 }
@@ -109,6 +147,7 @@ impl From<Request<Vec<u8>>> for InterceptRequest {
             inner: Request::from_parts(parts, ()),
             body: InterceptBody::Vec(body),
             override_connect_addr: None,
+            response_interceptors: Vec::new(),
         }
     }
 }
@@ -123,3 +162,15 @@ impl From<InterceptBody> for HyperBody {
         }
     }
 }
+
+/// Runs the given response interceptors on a response, in the order described
+/// in [`InterceptRequest::on_response`].
+pub(crate) async fn intercept_response(
+    interceptors: Vec<Box<dyn ResponseInterceptor>>,
+    mut response: Response<HyperBody>,
+) -> HttpResult<Response<HyperBody>> {
+    for interceptor in interceptors.into_iter().rev() {
+        response = interceptor.intercept_response(response).await?;
+    }
+    Ok(response)
+}
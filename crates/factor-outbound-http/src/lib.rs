@@ -1,5 +1,10 @@
+#[cfg(feature = "aws-sigv4")]
+pub mod aws_sigv4;
+pub mod cassette;
+pub mod faults;
 pub mod intercept;
 pub mod runtime_config;
+pub mod signing;
 mod spin;
 mod wasi;
 pub mod wasi_2023_10_18;
@@ -14,6 +19,8 @@ use http::{
 };
 use intercept::OutboundHttpInterceptor;
 use runtime_config::RuntimeConfig;
+use signing::HostSigner;
+use spin_factor_capability_policy::CapabilityChecker;
 use spin_factor_otel::OtelFactorState;
 use spin_factor_outbound_networking::{
     ComponentTlsClientConfigs, OutboundNetworkingFactor,
@@ -37,7 +44,31 @@ pub use wasi::{MutexBody, NotifyOnDropBody, p2_to_p3_error_code, p3_to_p2_error_
 
 #[derive(Default)]
 pub struct OutboundHttpFactor {
-    _priv: (),
+    request_interceptor: Option<Arc<dyn OutboundHttpInterceptor>>,
+}
+
+impl OutboundHttpFactor {
+    /// Sets an [`OutboundHttpInterceptor`] for every instance of every app.
+    ///
+    /// If an instance also has its own interceptor (see
+    /// [`InstanceState::set_request_interceptor`]), this interceptor sees only
+    /// the requests that the instance's interceptor passes on.
+    pub fn set_request_interceptor(&mut self, interceptor: impl OutboundHttpInterceptor + 'static) {
+        self.request_interceptor = Some(Arc::new(interceptor));
+    }
+
+    /// Adds an [`OutboundHttpInterceptor`] for every instance of every app,
+    /// which sees only the requests that any interceptor already set passes on.
+    pub fn add_request_interceptor(&mut self, interceptor: impl OutboundHttpInterceptor + 'static) {
+        let interceptor: Arc<dyn OutboundHttpInterceptor> = Arc::new(interceptor);
+        self.request_interceptor = Some(match self.request_interceptor.take() {
+            Some(first) => Arc::new(intercept::ChainedInterceptor {
+                first,
+                then: interceptor,
+            }),
+            None => interceptor,
+        });
+    }
 }
 
 impl Factor for OutboundHttpFactor {
@@ -64,6 +95,7 @@ impl Factor for OutboundHttpFactor {
                 // Permit count is the max concurrent connections + 1.
                 // i.e., 0 concurrent connections means 1 total connection.
                 .map(|n| Arc::new(Semaphore::new(n + 1))),
+            request_signers: config.request_signers.into(),
         })
     }
 
@@ -76,6 +108,7 @@ impl Factor for OutboundHttpFactor {
         let blocked_networks = outbound_networking.blocked_networks();
         let component_tls_configs = outbound_networking.component_tls_configs();
         let otel = OtelFactorState::from_prepare_context(&mut ctx)?;
+        let capabilities = CapabilityChecker::from_prepare_context(&mut ctx)?;
         Ok(InstanceState {
             wasi_http_ctx: WasiHttpCtx::new(),
             hooks: InstanceHttpHooks {
@@ -83,7 +116,8 @@ impl Factor for OutboundHttpFactor {
                 blocked_networks,
                 component_tls_configs,
                 self_request_origin: None,
-                request_interceptor: None,
+                request_interceptor: self.request_interceptor.clone(),
+                instance_interceptor_set: false,
                 spin_http_client: None,
                 wasi_http_clients: ctx.app_state().wasi_http_clients.clone(),
                 connection_pooling_enabled: ctx.app_state().connection_pooling_enabled,
@@ -91,7 +125,9 @@ impl Factor for OutboundHttpFactor {
                     .app_state()
                     .concurrent_outbound_connections_semaphore
                     .clone(),
+                request_signers: ctx.app_state().request_signers.clone(),
                 otel,
+                capabilities,
             },
         })
     }
@@ -108,6 +144,9 @@ struct InstanceHttpHooks {
     component_tls_configs: ComponentTlsClientConfigs,
     self_request_origin: Option<SelfRequestOrigin>,
     request_interceptor: Option<Arc<dyn OutboundHttpInterceptor>>,
+    /// Whether `request_interceptor` includes one set for this instance, in
+    /// addition to any set for the factor.
+    instance_interceptor_set: bool,
     // Connection-pooling client for 'fermyon:spin/http' interface
     //
     // TODO: We could move this to `AppState` like the
@@ -123,8 +162,12 @@ struct InstanceHttpHooks {
     connection_pooling_enabled: bool,
     /// A semaphore to limit the number of concurrent outbound connections.
     concurrent_outbound_connections_semaphore: Option<Arc<Semaphore>>,
+    /// Signers for requests to particular hosts.
+    request_signers: Arc<[HostSigner]>,
     /// Manages access to the OtelFactor state.
     otel: OtelFactorState,
+    /// Checks requests against the capability policy.
+    capabilities: CapabilityChecker,
 }
 
 impl InstanceState {
@@ -143,10 +186,18 @@ impl InstanceState {
         &mut self,
         interceptor: impl OutboundHttpInterceptor + 'static,
     ) -> anyhow::Result<()> {
-        if self.hooks.request_interceptor.is_some() {
+        if self.hooks.instance_interceptor_set {
             anyhow::bail!("set_request_interceptor can only be called once");
         }
-        self.hooks.request_interceptor = Some(Arc::new(interceptor));
+        let interceptor: Arc<dyn OutboundHttpInterceptor> = Arc::new(interceptor);
+        self.hooks.request_interceptor = Some(match self.hooks.request_interceptor.take() {
+            Some(factor_interceptor) => Arc::new(intercept::ChainedInterceptor {
+                first: interceptor,
+                then: factor_interceptor,
+            }),
+            None => interceptor,
+        });
+        self.hooks.instance_interceptor_set = true;
         Ok(())
     }
 }
@@ -270,6 +321,8 @@ pub struct AppState {
     connection_pooling_enabled: bool,
     /// A semaphore to limit the number of concurrent outbound connections.
     concurrent_outbound_connections_semaphore: Option<Arc<Semaphore>>,
+    /// Signers for requests to particular hosts.
+    request_signers: Arc<[HostSigner]>,
 }
 
 /// Removes IPs in the given [`BlockedNetworks`].
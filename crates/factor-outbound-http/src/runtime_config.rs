@@ -1,6 +1,8 @@
 #[cfg(feature = "spin-cli")]
 pub mod spin;
 
+use crate::signing::HostSigner;
+
 /// Runtime configuration for outbound HTTP.
 #[derive(Debug)]
 pub struct RuntimeConfig {
@@ -8,6 +10,8 @@ pub struct RuntimeConfig {
     pub connection_pooling_enabled: bool,
     /// If set, limits the number of concurrent outbound connections.
     pub max_concurrent_connections: Option<usize>,
+    /// Signers for requests to particular hosts; the first match is used.
+    pub request_signers: Vec<HostSigner>,
 }
 
 impl Default for RuntimeConfig {
@@ -15,6 +19,7 @@ impl Default for RuntimeConfig {
         Self {
             connection_pooling_enabled: true,
             max_concurrent_connections: None,
+            request_signers: Vec::new(),
         }
     }
 }
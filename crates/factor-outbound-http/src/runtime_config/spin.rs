@@ -1,6 +1,8 @@
 use serde::Deserialize;
 use spin_factors::runtime_config::toml::GetTomlValue;
 
+use crate::signing::HostSigner;
+
 /// Get the runtime configuration for outbound HTTP from a TOML table.
 ///
 /// Expects table to be in the format:
@@ -8,6 +10,12 @@ use spin_factors::runtime_config::toml::GetTomlValue;
 /// [outbound_http]
 /// connection_pooling = true # optional, defaults to true
 /// max_concurrent_requests = 10 # optional, defaults to unlimited
+///
+/// # optional; signs requests to the host with AWS Signature Version 4
+/// [[outbound_http.aws_sigv4]]
+/// host = "sqs.us-east-1.amazonaws.com" # or e.g. "*.s3.us-east-1.amazonaws.com"
+/// service = "sqs"
+/// region = "us-east-1"
 /// ```
 pub fn config_from_table(
     table: &impl GetTomlValue,
@@ -17,6 +25,11 @@ pub fn config_from_table(
         Ok(Some(super::RuntimeConfig {
             connection_pooling_enabled: outbound_http_toml.connection_pooling,
             max_concurrent_connections: outbound_http_toml.max_concurrent_requests,
+            request_signers: outbound_http_toml
+                .aws_sigv4
+                .into_iter()
+                .map(AwsSigV4Toml::into_host_signer)
+                .collect::<anyhow::Result<_>>()?,
         }))
     } else {
         Ok(None)
@@ -30,4 +43,36 @@ struct OutboundHttpToml {
     connection_pooling: bool,
     #[serde(default)]
     max_concurrent_requests: Option<usize>,
+    #[serde(default)]
+    aws_sigv4: Vec<AwsSigV4Toml>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct AwsSigV4Toml {
+    host: String,
+    service: String,
+    region: String,
+}
+
+impl AwsSigV4Toml {
+    #[cfg(feature = "aws-sigv4")]
+    fn into_host_signer(self) -> anyhow::Result<HostSigner> {
+        anyhow::ensure!(
+            !self.host.is_empty(),
+            "outbound_http.aws_sigv4 host must not be empty"
+        );
+        Ok(HostSigner {
+            host: self.host,
+            signer: std::sync::Arc::new(crate::aws_sigv4::AwsSigV4Signer::new(
+                self.service,
+                self.region,
+            )),
+        })
+    }
+
+    #[cfg(not(feature = "aws-sigv4"))]
+    fn into_host_signer(self) -> anyhow::Result<HostSigner> {
+        anyhow::bail!("outbound_http.aws_sigv4 is not supported by this build of Spin")
+    }
 }
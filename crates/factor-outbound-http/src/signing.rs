@@ -0,0 +1,89 @@
+use std::sync::Arc;
+
+use bytes::Bytes;
+use spin_world::async_trait;
+
+/// Signs outbound HTTP requests on behalf of components, e.g. with cloud
+/// provider credentials which components should not need to hold.
+#[async_trait]
+pub trait RequestSigner: Send + Sync {
+    /// Signs a request which is about to be sent, typically by adding headers.
+    async fn sign(&self, request: &mut http::Request<Bytes>) -> anyhow::Result<()>;
+}
+
+/// A [`RequestSigner`] for requests to a particular host.
+#[derive(Clone)]
+pub struct HostSigner {
+    /// The host whose requests are signed, e.g. `sqs.us-east-1.amazonaws.com`,
+    /// or `*.` followed by a domain to sign requests to all of its subdomains.
+    pub host: String,
+    /// The signer to use.
+    pub signer: Arc<dyn RequestSigner>,
+}
+
+impl HostSigner {
+    fn matches(&self, host: &str) -> bool {
+        match self.host.strip_prefix("*.") {
+            Some(domain) => host
+                .strip_suffix(domain)
+                .is_some_and(|subdomain| subdomain.ends_with('.')),
+            None => self.host.eq_ignore_ascii_case(host),
+        }
+    }
+}
+
+impl std::fmt::Debug for HostSigner {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("HostSigner")
+            .field("host", &self.host)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Returns the signer for requests to the given host, if any.
+pub(crate) fn find_signer<'a>(
+    signers: &'a [HostSigner],
+    host: &str,
+) -> Option<&'a dyn RequestSigner> {
+    signers
+        .iter()
+        .find(|signer| signer.matches(host))
+        .map(|signer| signer.signer.as_ref())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct NoopSigner;
+
+    #[async_trait]
+    impl RequestSigner for NoopSigner {
+        async fn sign(&self, _request: &mut http::Request<Bytes>) -> anyhow::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn signer(host: &str) -> HostSigner {
+        HostSigner {
+            host: host.into(),
+            signer: Arc::new(NoopSigner),
+        }
+    }
+
+    #[test]
+    fn signers_match_hosts() {
+        let exact = signer("s3.us-east-1.amazonaws.com");
+        assert!(exact.matches("s3.us-east-1.amazonaws.com"));
+        assert!(!exact.matches("bucket.s3.us-east-1.amazonaws.com"));
+
+        let wildcard = signer("*.s3.us-east-1.amazonaws.com");
+        assert!(wildcard.matches("bucket.s3.us-east-1.amazonaws.com"));
+        assert!(!wildcard.matches("s3.us-east-1.amazonaws.com"));
+        assert!(!wildcard.matches("evils3.us-east-1.amazonaws.com"));
+
+        let signers = [exact, wildcard];
+        assert!(find_signer(&signers, "example.com").is_none());
+        assert!(find_signer(&signers, "bucket.s3.us-east-1.amazonaws.com").is_some());
+    }
+}
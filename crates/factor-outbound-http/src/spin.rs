@@ -1,7 +1,10 @@
 use std::sync::Arc;
 
+use bytes::Bytes;
+
 use futures::stream::TryStreamExt as _;
 use http_body_util::BodyExt;
+use spin_factor_capability_policy::operation;
 use spin_factor_outbound_networking::config::blocked_networks::BlockedNetworks;
 use spin_world::MAX_HOST_BUFFERED_BYTES;
 use spin_world::v1::{
@@ -9,8 +12,9 @@ use spin_world::v1::{
     http_types::{self, HttpError, Method, Request, Response},
 };
 use tracing::{Span, field::Empty, instrument};
+use wasmtime_wasi_http::p2::bindings::http::types::ErrorCode;
 
-use crate::intercept::InterceptOutcome;
+use crate::intercept::{InterceptOutcome, intercept_response};
 
 impl spin_http::Host for crate::InstanceState {
     #[instrument(name = "spin_outbound_http.send_request", skip_all,
@@ -62,6 +66,19 @@ impl spin_http::Host for crate::InstanceState {
             origin.clone().into_uri(Some(path_and_query))
         };
 
+        let host = match req_url.host() {
+            Some(host) if !uri.starts_with('/') => host,
+            _ => "self",
+        };
+        self.hooks
+            .capabilities
+            .check(
+                operation::OUTBOUND_HTTP,
+                host,
+                Some(hyper_method(req.method).as_str()),
+            )
+            .map_err(|_| HttpError::DestinationNotAllowed)?;
+
         // Build an http::Request for OutboundHttpInterceptor
         let mut req = {
             let mut builder = http::Request::builder()
@@ -77,12 +94,18 @@ impl spin_http::Host for crate::InstanceState {
             HttpError::RuntimeError
         })?;
 
-        spin_telemetry::inject_trace_context(req.headers_mut());
+        spin_telemetry::inject_trace_context_with_baggage(
+            req.headers_mut(),
+            self.hooks.otel.guest_baggage(),
+        );
 
+        let mut response_interceptors = Vec::new();
         if let Some(interceptor) = &self.hooks.request_interceptor {
             let intercepted_request = std::mem::take(&mut req).into();
             match interceptor.intercept(intercepted_request).await {
-                Ok(InterceptOutcome::Continue(intercepted_request)) => {
+                Ok(InterceptOutcome::Continue(mut intercepted_request)) => {
+                    response_interceptors =
+                        std::mem::take(&mut intercepted_request.response_interceptors);
                     req = intercepted_request.into_vec_request().unwrap();
                 }
                 Ok(InterceptOutcome::Complete(resp)) => return response_from_hyper(resp).await,
@@ -93,6 +116,18 @@ impl spin_http::Host for crate::InstanceState {
             }
         }
 
+        let mut req = req.map(Bytes::from);
+        if let Some(signer) = req
+            .uri()
+            .host()
+            .and_then(|host| crate::signing::find_signer(&self.hooks.request_signers, host))
+        {
+            signer.sign(&mut req).await.map_err(|err| {
+                tracing::error!("Failed to sign outbound request: {err:#}");
+                HttpError::RuntimeError
+            })?;
+        }
+
         // Convert http::Request to reqwest::Request
         let req = reqwest::Request::try_from(req).map_err(|_| HttpError::InvalidUrl)?;
 
@@ -121,7 +156,16 @@ impl spin_http::Host for crate::InstanceState {
 
         tracing::trace!("Returning response from outbound request to {req_url}");
         span.record("http.response.status_code", resp.status().as_u16());
-        response_from_reqwest(resp).await
+        if response_interceptors.is_empty() {
+            return response_from_reqwest(resp).await;
+        }
+        let resp = intercept_response(response_interceptors, hyper_response_from_reqwest(resp))
+            .await
+            .map_err(|err| {
+                tracing::error!("Error in outbound HTTP response interceptor: {err:?}");
+                HttpError::RuntimeError
+            })?;
+        response_from_hyper(resp).await
     }
 }
 
@@ -273,6 +317,15 @@ async fn response_from_reqwest(res: reqwest::Response) -> Result<Response, HttpE
     })
 }
 
+/// Converts a response so that it can be passed to response interceptors,
+/// streaming its body.
+fn hyper_response_from_reqwest(res: reqwest::Response) -> crate::Response {
+    http::Response::from(res).map(|body| {
+        body.map_err(|err| ErrorCode::InternalError(Some(err.to_string())))
+            .boxed_unsync()
+    })
+}
+
 fn check_byte_count(count: usize) -> Result<(), HttpError> {
     if count > MAX_HOST_BUFFERED_BYTES {
         tracing::warn!("query result exceeds limit of {MAX_HOST_BUFFERED_BYTES} bytes");
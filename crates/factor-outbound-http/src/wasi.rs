@@ -26,11 +26,14 @@ use hyper_util::{
     },
     rt::{TokioExecutor, TokioIo},
 };
+use opentelemetry::baggage::Baggage;
+use spin_factor_capability_policy::{CapabilityChecker, operation};
 use spin_factor_outbound_networking::{
     ComponentTlsClientConfigs, TlsClientConfig,
     config::{allowed_hosts::OutboundAllowedHosts, blocked_networks::BlockedNetworks},
 };
 use spin_factors::RuntimeFactorsInstanceState;
+use spin_world::MAX_HOST_BUFFERED_BYTES;
 use tokio::{
     io::{AsyncRead, AsyncWrite, ReadBuf},
     net::TcpStream,
@@ -54,7 +57,8 @@ use wasmtime_wasi_http::{
 
 use crate::{
     InstanceHttpHooks, OutboundHttpFactor, SelfRequestOrigin,
-    intercept::{InterceptOutcome, OutboundHttpInterceptor},
+    intercept::{InterceptOutcome, OutboundHttpInterceptor, intercept_response},
+    signing::{HostSigner, RequestSigner, find_signer},
     wasi_2023_10_18, wasi_2023_11_10,
 };
 
@@ -184,6 +188,9 @@ impl p3::WasiHttpHooks for InstanceHttpHooks {
             concurrent_outbound_connections_semaphore: self
                 .concurrent_outbound_connections_semaphore
                 .clone(),
+            capabilities: self.capabilities.clone(),
+            request_signers: self.request_signers.clone(),
+            baggage: self.otel.guest_baggage(),
         };
         let config = OutgoingRequestConfig {
             use_tls: request.uri().scheme() == Some(&Scheme::HTTPS),
@@ -388,6 +395,9 @@ impl p2::WasiHttpHooks for InstanceHttpHooks {
             concurrent_outbound_connections_semaphore: self
                 .concurrent_outbound_connections_semaphore
                 .clone(),
+            capabilities: self.capabilities.clone(),
+            request_signers: self.request_signers.clone(),
+            baggage: self.otel.guest_baggage(),
         };
         Ok(HostFutureIncomingResponse::Pending(
             wasmtime_wasi::runtime::spawn(
@@ -414,26 +424,32 @@ struct RequestSender {
     request_interceptor: Option<Arc<dyn OutboundHttpInterceptor>>,
     http_clients: HttpClients,
     concurrent_outbound_connections_semaphore: Option<Arc<Semaphore>>,
+    capabilities: CapabilityChecker,
+    request_signers: Arc<[HostSigner]>,
+    /// Baggage set by the guest, to propagate in place of that of the current context.
+    baggage: Option<Baggage>,
 }
 
 impl RequestSender {
     async fn send(
-        self,
+        mut self,
         mut request: OutgoingRequest,
         mut config: OutgoingRequestConfig,
     ) -> Result<IncomingResponse, HttpError> {
         self.prepare_request(&mut request, &mut config).await?;
 
         // If the current span has opentelemetry trace context, inject it into the request
-        spin_telemetry::inject_trace_context(&mut request);
+        spin_telemetry::inject_trace_context_with_baggage(&mut request, self.baggage.take());
 
         // Run any configured request interceptor
         let mut override_connect_addr = None;
+        let mut response_interceptors = Vec::new();
         if let Some(interceptor) = &self.request_interceptor {
             let intercept_request = std::mem::take(&mut request).into();
             match interceptor.intercept(intercept_request).await? {
                 InterceptOutcome::Continue(mut req) => {
                     override_connect_addr = req.override_connect_addr.take();
+                    response_interceptors = std::mem::take(&mut req.response_interceptors);
                     request = req.into_hyper_request();
                 }
                 InterceptOutcome::Complete(resp) => {
@@ -459,15 +475,28 @@ impl RequestSender {
             }
         }
 
+        // Sign last, so that the signature covers any changes made above
+        if let Some(signer) = request
+            .uri()
+            .host()
+            .and_then(|host| find_signer(&self.request_signers, host))
+        {
+            request = sign_request(signer, request).await?;
+        }
+
         record_content_length_header(
             &span,
             request.headers(),
             "http.request.header.content-length",
         );
 
-        Ok(self
+        let mut response = self
             .send_request(request, config, override_connect_addr)
-            .await?)
+            .await?;
+        if !response_interceptors.is_empty() {
+            response.resp = intercept_response(response_interceptors, response.resp).await?;
+        }
+        Ok(response)
     }
 
     async fn prepare_request(
@@ -513,6 +542,17 @@ impl RequestSender {
         if !is_allowed {
             return Err(ErrorCode::HttpRequestDenied);
         }
+        let host = match request.uri().host() {
+            Some(host) if !is_self_request => host,
+            _ => "self",
+        };
+        self.capabilities
+            .check(
+                operation::OUTBOUND_HTTP,
+                host,
+                Some(request.method().as_str()),
+            )
+            .map_err(|_| ErrorCode::HttpRequestDenied)?;
 
         if is_self_request {
             // Replace the authority with the "self request origin"
@@ -608,6 +648,32 @@ impl RequestSender {
     }
 }
 
+/// Buffers the request body so that it can be signed, then signs the request.
+async fn sign_request(
+    signer: &dyn RequestSigner,
+    request: OutgoingRequest,
+) -> Result<OutgoingRequest, ErrorCode> {
+    let (parts, body) = request.into_parts();
+    let body = http_body_util::Limited::new(body, MAX_HOST_BUFFERED_BYTES)
+        .collect()
+        .await
+        .map_err(|err| match err.downcast::<ErrorCode>() {
+            Ok(error_code) => *error_code,
+            Err(_) => ErrorCode::HttpRequestBodySize(None),
+        })?
+        .to_bytes();
+    let mut request = http::Request::from_parts(parts, body);
+    signer.sign(&mut request).await.map_err(|err| {
+        tracing::error!("Failed to sign outbound request: {err:#}");
+        ErrorCode::InternalError(Some(format!("failed to sign request: {err}")))
+    })?;
+    Ok(request.map(|body| {
+        http_body_util::Full::new(body)
+            .map_err(|never| match never {})
+            .boxed_unsync()
+    }))
+}
+
 type HttpClient = Client<HttpConnector, HyperOutgoingBody>;
 type HttpsClient = Client<HttpsConnector, HyperOutgoingBody>;
 
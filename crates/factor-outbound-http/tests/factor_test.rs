@@ -1,7 +1,11 @@
 use std::time::Duration;
 
-use anyhow::bail;
-use http::{Request, Uri};
+use anyhow::{Context as _, bail};
+use bytes::Bytes;
+use http::{HeaderMap, HeaderValue, Request, Response, Uri};
+use http_body_util::{BodyExt as _, Full, StreamBody};
+use hyper::body::{Frame, Incoming};
+use hyper_util::rt::TokioIo;
 use spin_common::{assert_matches, assert_not_matches};
 use spin_factor_outbound_http::{
     ErrorCode, HostFutureIncomingResponse, OutboundHttpFactor, SelfRequestOrigin,
@@ -12,6 +16,8 @@ use spin_factor_variables::VariablesFactor;
 use spin_factors::{RuntimeFactors, anyhow};
 use spin_factors_test::{TestEnvironment, toml};
 use spin_world::async_trait;
+use tokio::io::{AsyncReadExt as _, AsyncWriteExt as _};
+use tokio::net::TcpListener;
 use wasmtime_wasi::p2::Pollable;
 use wasmtime_wasi_http::p2::types::OutgoingRequestConfig;
 
@@ -129,6 +135,159 @@ async fn override_connect_addr_disallowed_private_ip_fails() -> anyhow::Result<(
     Ok(())
 }
 
+#[tokio::test(flavor = "multi_thread")]
+async fn trailers_are_propagated() -> anyhow::Result<()> {
+    // Echoes the request's `checksum` trailer back as a response trailer,
+    // alongside a `grpc-status` trailer.
+    async fn echo_trailers(
+        req: Request<Incoming>,
+    ) -> anyhow::Result<Response<StreamBody<FrameStream>>> {
+        let request_trailers = req
+            .into_body()
+            .collect()
+            .await?
+            .trailers()
+            .cloned()
+            .context("request had no trailers")?;
+        let mut trailers = HeaderMap::new();
+        trailers.insert("grpc-status", HeaderValue::from_static("0"));
+        trailers.insert("checksum", request_trailers["checksum"].clone());
+        Ok(Response::builder()
+            .header("trailer", "grpc-status, checksum")
+            .body(frames(b"response", trailers))?)
+    }
+
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let addr = listener.local_addr()?;
+    tokio::spawn(async move {
+        let (stream, _) = listener.accept().await?;
+        hyper::server::conn::http1::Builder::new()
+            .serve_connection(
+                TokioIo::new(stream),
+                hyper::service::service_fn(echo_trailers),
+            )
+            .await?;
+        anyhow::Ok(())
+    });
+
+    let mut state = test_instance_state("http://127.0.0.1:*", true).await?;
+    let wasi_http = OutboundHttpFactor::get_wasi_http_impl(&mut state).unwrap();
+    let mut trailers = HeaderMap::new();
+    trailers.insert("checksum", HeaderValue::from_static("abc123"));
+    let req = Request::post(format!("http://{addr}/"))
+        // HTTP/1.1 peers only send trailers that are declared up front, and
+        // servers only to clients that accept them
+        .header("trailer", "checksum")
+        .header("te", "trailers")
+        .body(
+            frames(b"request", trailers)
+                .map_err(|never| match never {})
+                .boxed_unsync(),
+        )?;
+    let mut future_resp = wasi_http
+        .hooks
+        .send_request(req, test_request_config_with_timeouts())?;
+    future_resp.ready().await;
+    let resp = future_resp
+        .unwrap_ready()
+        .unwrap()
+        .map_err(|err| anyhow::anyhow!("request failed: {err:?}"))?;
+
+    let body = resp
+        .resp
+        .into_body()
+        .collect()
+        .await
+        .map_err(|err| anyhow::anyhow!("reading body failed: {err:?}"))?;
+    let trailers = body
+        .trailers()
+        .cloned()
+        .context("response had no trailers")?;
+    assert_eq!(trailers["grpc-status"], "0");
+    assert_eq!(trailers["checksum"], "abc123");
+    assert_eq!(body.to_bytes(), "response");
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn informational_responses_precede_final_response() -> anyhow::Result<()> {
+    // Answers `Expect: 100-continue` with `100 Continue`, then sends `103
+    // Early Hints` before echoing the request body.
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let addr = listener.local_addr()?;
+    tokio::spawn(async move {
+        let (mut stream, _) = listener.accept().await?;
+        let mut request = vec![];
+        let mut buf = [0; 1024];
+        let head_len = loop {
+            let n = stream.read(&mut buf).await?;
+            anyhow::ensure!(n > 0, "connection closed before request head");
+            request.extend_from_slice(&buf[..n]);
+            if let Some(pos) = request.windows(4).position(|w| w == b"\r\n\r\n") {
+                break pos + 4;
+            }
+        };
+        let head = String::from_utf8_lossy(&request[..head_len]).to_lowercase();
+        anyhow::ensure!(
+            head.contains("expect: 100-continue"),
+            "missing expect header"
+        );
+        stream.write_all(b"HTTP/1.1 100 Continue\r\n\r\n").await?;
+        while request.len() < head_len + b"hello".len() {
+            let n = stream.read(&mut buf).await?;
+            anyhow::ensure!(n > 0, "connection closed before request body");
+            request.extend_from_slice(&buf[..n]);
+        }
+        stream
+            .write_all(
+                b"HTTP/1.1 103 Early Hints\r\nlink: </style.css>; rel=preload\r\n\r\n\
+                  HTTP/1.1 200 OK\r\ncontent-length: 5\r\n\r\n",
+            )
+            .await?;
+        stream.write_all(&request[head_len..]).await?;
+        anyhow::Ok(())
+    });
+
+    let mut state = test_instance_state("http://127.0.0.1:*", true).await?;
+    let wasi_http = OutboundHttpFactor::get_wasi_http_impl(&mut state).unwrap();
+    let req = Request::post(format!("http://{addr}/"))
+        .header("expect", "100-continue")
+        .body(
+            Full::new(Bytes::from_static(b"hello"))
+                .map_err(|never| match never {})
+                .boxed_unsync(),
+        )?;
+    let mut future_resp = wasi_http
+        .hooks
+        .send_request(req, test_request_config_with_timeouts())?;
+    future_resp.ready().await;
+    let resp = future_resp
+        .unwrap_ready()
+        .unwrap()
+        .map_err(|err| anyhow::anyhow!("request failed: {err:?}"))?;
+
+    assert_eq!(resp.resp.status(), 200);
+    let body = resp
+        .resp
+        .into_body()
+        .collect()
+        .await
+        .map_err(|err| anyhow::anyhow!("reading body failed: {err:?}"))?;
+    assert_eq!(body.to_bytes(), "hello");
+    Ok(())
+}
+
+type FrameStream =
+    futures::stream::Iter<std::vec::IntoIter<Result<Frame<Bytes>, std::convert::Infallible>>>;
+
+/// A body of `data` followed by `trailers`.
+fn frames(data: &'static [u8], trailers: HeaderMap) -> StreamBody<FrameStream> {
+    StreamBody::new(futures::stream::iter(vec![
+        Ok(Frame::data(Bytes::from_static(data))),
+        Ok(Frame::trailers(trailers)),
+    ]))
+}
+
 async fn test_instance_state(
     allowed_outbound_hosts: &str,
     allow_private_ips: bool,
@@ -165,6 +324,16 @@ fn test_request_config() -> OutgoingRequestConfig {
     }
 }
 
+/// A request config with timeouts long enough for a local server to respond.
+fn test_request_config_with_timeouts() -> OutgoingRequestConfig {
+    OutgoingRequestConfig {
+        use_tls: false,
+        connect_timeout: Duration::from_secs(5),
+        first_byte_timeout: Duration::from_secs(5),
+        between_bytes_timeout: Duration::from_secs(5),
+    }
+}
+
 fn assert_discard_prefix_error(future_resp: HostFutureIncomingResponse) {
     // Different systems handle the discard prefix differently; some will
     // immediately reject it while others will silently let it time out
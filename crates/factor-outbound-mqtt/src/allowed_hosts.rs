@@ -1,21 +1,31 @@
 use std::sync::Arc;
 
 use anyhow::Result;
-use spin_factor_outbound_networking::config::allowed_hosts::OutboundAllowedHosts;
+use spin_factor_capability_policy::{CapabilityChecker, operation};
+use spin_factor_outbound_networking::config::allowed_hosts::{OutboundAllowedHosts, OutboundUrl};
 
 #[derive(Clone)]
 pub struct AllowedHostChecker {
     allowed_hosts: Arc<OutboundAllowedHosts>,
+    capabilities: CapabilityChecker,
 }
 
 impl AllowedHostChecker {
-    pub fn new(allowed_hosts: OutboundAllowedHosts) -> Self {
+    pub fn new(allowed_hosts: OutboundAllowedHosts, capabilities: CapabilityChecker) -> Self {
         Self {
             allowed_hosts: Arc::new(allowed_hosts),
+            capabilities,
         }
     }
 
     pub async fn is_address_allowed(&self, address: &str) -> Result<bool> {
-        self.allowed_hosts.check_url(address, "mqtt").await
+        if !self.allowed_hosts.check_url(address, "mqtt").await? {
+            return Ok(false);
+        }
+        let url = OutboundUrl::parse(address, "mqtt")?;
+        Ok(self
+            .capabilities
+            .check(operation::OUTBOUND_MQTT, url.host(), None)
+            .is_ok())
     }
 }
@@ -5,6 +5,7 @@ use spin_core::{
     async_trait,
     wasmtime::component::{Accessor, Resource},
 };
+use spin_factor_capability_policy::CapabilityChecker;
 use spin_factor_otel::OtelFactorState;
 use spin_factor_outbound_networking::config::allowed_hosts::OutboundAllowedHosts;
 use spin_world::spin::mqtt::mqtt as v3;
@@ -23,11 +24,12 @@ pub struct InstanceState {
 impl InstanceState {
     pub fn new(
         allowed_hosts: OutboundAllowedHosts,
+        capabilities: CapabilityChecker,
         create_client: Arc<dyn ClientCreator>,
         otel: OtelFactorState,
     ) -> Self {
         Self {
-            allowed_hosts: AllowedHostChecker::new(allowed_hosts),
+            allowed_hosts: AllowedHostChecker::new(allowed_hosts, capabilities),
             create_client,
             connections: spin_resource_table::Table::new(1024),
             otel,
@@ -7,6 +7,7 @@ use std::time::Duration;
 use host::InstanceState;
 use rumqttc::{AsyncClient, Event, Incoming, Outgoing, QoS};
 use spin_core::async_trait;
+use spin_factor_capability_policy::CapabilityChecker;
 use spin_factor_otel::OtelFactorState;
 use spin_factor_outbound_networking::OutboundNetworkingFactor;
 use spin_factors::{
@@ -55,10 +56,12 @@ impl Factor for OutboundMqttFactor {
         let allowed_hosts = ctx
             .instance_builder::<OutboundNetworkingFactor>()?
             .allowed_hosts();
+        let capabilities = CapabilityChecker::from_prepare_context(&mut ctx)?;
         let otel = OtelFactorState::from_prepare_context(&mut ctx)?;
 
         Ok(InstanceState::new(
             allowed_hosts,
+            capabilities,
             self.create_client.clone(),
             otel,
         ))
@@ -3,6 +3,7 @@ use std::time::Duration;
 
 use anyhow::{Result, bail};
 use spin_core::async_trait;
+use spin_factor_capability_policy::{CapabilityPolicyFactor, runtime_config::config_from_table};
 use spin_factor_outbound_mqtt::{ClientCreator, MqttClient, OutboundMqttFactor};
 use spin_factor_outbound_networking::OutboundNetworkingFactor;
 use spin_factor_variables::VariablesFactor;
@@ -87,6 +88,59 @@ async fn disallowed_host_fails() -> anyhow::Result<()> {
     Ok(())
 }
 
+#[derive(RuntimeFactors)]
+struct PolicyTestFactors {
+    capability_policy: CapabilityPolicyFactor,
+    variables: VariablesFactor,
+    networking: OutboundNetworkingFactor,
+    mqtt: OutboundMqttFactor,
+}
+
+#[tokio::test]
+async fn capability_policy_denies_hosts() -> anyhow::Result<()> {
+    use spin_world::v2::mqtt as v2;
+    use v2::HostConnection;
+
+    let env = TestEnvironment::new(PolicyTestFactors {
+        capability_policy: CapabilityPolicyFactor::new(),
+        variables: VariablesFactor::default(),
+        networking: OutboundNetworkingFactor::new(),
+        mqtt: OutboundMqttFactor::new(Arc::new(MockMqttClient {})),
+    })
+    .extend_manifest(toml! {
+        [component.test-component]
+        source = "does-not-exist.wasm"
+        allowed_outbound_hosts = ["mqtt://*:*"]
+    })
+    .runtime_config(PolicyTestFactorsRuntimeConfig {
+        capability_policy: config_from_table(&toml! {
+            [[capability_policy]]
+            effect = "deny"
+            operations = ["*"]
+        })?,
+        variables: None,
+        networking: None,
+        mqtt: None,
+    })?;
+    let mut state = env.build_instance_state().await?;
+
+    let res = state
+        .mqtt
+        .open(
+            "mqtt://mqtt.test:1883".to_string(),
+            "username".to_string(),
+            "password".to_string(),
+            1,
+        )
+        .await;
+    let Err(err) = res else {
+        bail!("expected Err, got Ok");
+    };
+    assert!(matches!(err, v2::Error::ConnectionFailed(_)));
+
+    Ok(())
+}
+
 #[tokio::test]
 async fn allowed_host_succeeds() -> anyhow::Result<()> {
     use spin_world::v2::mqtt as v2;
@@ -1,5 +1,7 @@
 use anyhow::Result;
 use spin_core::wasmtime::component::Resource;
+use spin_factor_capability_policy::operation;
+use spin_factor_outbound_networking::config::allowed_hosts::OutboundUrl;
 use spin_telemetry::traces::{self, Blame};
 use spin_world::MAX_HOST_BUFFERED_BYTES;
 use spin_world::v1::mysql as v1;
@@ -44,7 +46,14 @@ impl<C: Client> InstanceState<C> {
     }
 
     async fn is_address_allowed(&self, address: &str) -> Result<bool> {
-        self.allowed_hosts.check_url(address, "mysql").await
+        if !self.allowed_hosts.check_url(address, "mysql").await? {
+            return Ok(false);
+        }
+        let url = OutboundUrl::parse(address, "mysql")?;
+        Ok(self
+            .capabilities
+            .check(operation::OUTBOUND_MYSQL, url.host(), None)
+            .is_ok())
     }
 }
 
@@ -3,6 +3,7 @@ mod host;
 
 use client::Client;
 use mysql_async::Conn as MysqlClient;
+use spin_factor_capability_policy::CapabilityChecker;
 use spin_factor_otel::OtelFactorState;
 use spin_factor_outbound_networking::{
     OutboundNetworkingFactor, config::allowed_hosts::OutboundAllowedHosts,
@@ -40,10 +41,12 @@ impl<C: Send + Sync + Client + 'static> Factor for OutboundMysqlFactor<C> {
         let allowed_hosts = ctx
             .instance_builder::<OutboundNetworkingFactor>()?
             .allowed_hosts();
+        let capabilities = CapabilityChecker::from_prepare_context(&mut ctx)?;
         let otel = OtelFactorState::from_prepare_context(&mut ctx)?;
 
         Ok(InstanceState {
             allowed_hosts,
+            capabilities,
             connections: Default::default(),
             otel,
         })
@@ -66,6 +69,7 @@ impl<C> OutboundMysqlFactor<C> {
 
 pub struct InstanceState<C> {
     allowed_hosts: OutboundAllowedHosts,
+    capabilities: CapabilityChecker,
     connections: spin_resource_table::Table<C>,
     otel: OtelFactorState,
 }
@@ -5,6 +5,7 @@ mod tls;
 use std::{collections::HashMap, sync::Arc};
 
 use futures_util::FutureExt as _;
+use spin_factor_capability_policy::{CapabilityChecker, operation};
 use spin_factor_variables::VariablesFactor;
 use spin_factor_wasi::{SocketAddrUse, WasiFactor};
 use spin_factors::{
@@ -27,6 +28,7 @@ pub use spin_outbound_networking_config as config;
 #[derive(Default)]
 pub struct OutboundNetworkingFactor {
     disallowed_host_handler: Option<Arc<dyn DisallowedHostHandler>>,
+    egress_dry_run: bool,
 }
 
 impl OutboundNetworkingFactor {
@@ -39,6 +41,12 @@ impl OutboundNetworkingFactor {
     pub fn set_disallowed_host_handler(&mut self, handler: impl DisallowedHostHandler + 'static) {
         self.disallowed_host_handler = Some(Arc::new(handler));
     }
+
+    /// Sets whether requests disallowed by `allowed_outbound_hosts` are only
+    /// reported to the disallowed host handler rather than blocked.
+    pub fn set_egress_dry_run(&mut self, dry_run: bool) {
+        self.egress_dry_run = dry_run;
+    }
 }
 
 impl Factor for OutboundNetworkingFactor {
@@ -120,8 +128,10 @@ impl Factor for OutboundNetworkingFactor {
         let allowed_hosts = OutboundAllowedHosts::new(
             allowed_hosts_future.clone(),
             self.disallowed_host_handler.clone(),
-        );
+        )
+        .with_dry_run(self.egress_dry_run);
         let blocked_networks = ctx.app_state().blocked_networks.clone();
+        let capabilities = CapabilityChecker::from_prepare_context(&mut ctx)?;
 
         match ctx.instance_builder::<WasiFactor>() {
             Ok(wasi_builder) => {
@@ -130,6 +140,7 @@ impl Factor for OutboundNetworkingFactor {
                 wasi_builder.outbound_socket_addr_check(move |addr, addr_use| {
                     let allowed_hosts = allowed_hosts.clone();
                     let blocked_networks = blocked_networks.clone();
+                    let capabilities = capabilities.clone();
                     async move {
                         let scheme = match addr_use {
                             SocketAddrUse::TcpBind => return false,
@@ -156,7 +167,9 @@ impl Factor for OutboundNetworkingFactor {
                             );
                             return false;
                         }
-                        true
+                        capabilities
+                            .check(operation::SOCKETS, &addr.ip().to_string(), Some(scheme))
+                            .is_ok()
                     }
                 });
             }
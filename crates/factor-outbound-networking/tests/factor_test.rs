@@ -1,3 +1,4 @@
+use spin_factor_capability_policy::{CapabilityPolicyFactor, runtime_config::config_from_table};
 use spin_factor_outbound_networking::OutboundNetworkingFactor;
 use spin_factor_outbound_networking::runtime_config::spin::SpinRuntimeConfig;
 use spin_factor_variables::VariablesFactor;
@@ -66,6 +67,54 @@ async fn configures_wasi_socket_addr_check() -> anyhow::Result<()> {
     Ok(())
 }
 
+#[tokio::test]
+async fn capability_policy_denies_sockets() -> anyhow::Result<()> {
+    #[derive(RuntimeFactors)]
+    struct PolicyTestFactors {
+        capability_policy: CapabilityPolicyFactor,
+        wasi: WasiFactor,
+        variables: VariablesFactor,
+        networking: OutboundNetworkingFactor,
+    }
+    let env = TestEnvironment::new(PolicyTestFactors {
+        capability_policy: CapabilityPolicyFactor::new(),
+        wasi: WasiFactor::new(DummyFilesMounter),
+        variables: VariablesFactor::default(),
+        networking: OutboundNetworkingFactor::new(),
+    })
+    .extend_manifest(toml! {
+        [component.test-component]
+        source = "does-not-exist.wasm"
+        allowed_outbound_hosts = ["*://123.0.2.1:12345"]
+    })
+    .runtime_config(PolicyTestFactorsRuntimeConfig {
+        capability_policy: config_from_table(&toml! {
+            [[capability_policy]]
+            effect = "deny"
+            operations = ["*"]
+        })?,
+        ..Default::default()
+    })?;
+    let mut state = env.build_instance_state().await?;
+    let mut sockets = WasiFactor::get_sockets_impl(&mut state).unwrap();
+
+    let network_resource = sockets.instance_network()?;
+    let network = sockets.table.get(&network_resource)?;
+
+    assert_eq!(
+        network
+            .check_socket_addr(
+                "123.0.2.1:12345".parse().unwrap(),
+                SocketAddrUse::TcpConnect,
+            )
+            .await
+            .unwrap_err()
+            .kind(),
+        std::io::ErrorKind::PermissionDenied
+    );
+    Ok(())
+}
+
 #[tokio::test]
 async fn wasi_factor_is_optional() -> anyhow::Result<()> {
     #[derive(RuntimeFactors)]
@@ -1,22 +1,25 @@
 use std::sync::Arc;
 
+use spin_factor_capability_policy::{CapabilityChecker, operation};
 use spin_factor_outbound_networking::config::allowed_hosts::OutboundAllowedHosts;
 use spin_world::spin::postgres4_2_0::postgres::{self as v4};
 
 /// Encapsulates checking of a PostgreSQL address/connection string against
-/// an allow-list.
+/// an allow-list and the capability policy.
 ///
 /// This is broken out as a distinct object to allow it to be synchronously retrieved
 /// within a P3 Accessor block and then asynchronously queried outside the block.
 #[derive(Clone)]
 pub(crate) struct AllowedHostChecker {
     allowed_hosts: Arc<OutboundAllowedHosts>,
+    capabilities: CapabilityChecker,
 }
 
 impl AllowedHostChecker {
-    pub fn new(allowed_hosts: OutboundAllowedHosts) -> Self {
+    pub fn new(allowed_hosts: OutboundAllowedHosts, capabilities: CapabilityChecker) -> Self {
         Self {
             allowed_hosts: Arc::new(allowed_hosts),
+            capabilities,
         }
     }
 
@@ -59,6 +62,9 @@ impl AllowedHostChecker {
                             "address postgres://{url} is not permitted"
                         )));
                     }
+                    self.capabilities
+                        .check(operation::OUTBOUND_PG, address, None)
+                        .map_err(|err| conn_failed(err.to_string()))?;
                 }
                 #[cfg(unix)]
                 tokio_postgres::config::Host::Unix(_) => {
@@ -7,6 +7,7 @@ use std::{collections::HashMap, sync::Arc};
 
 use allowed_hosts::AllowedHostChecker;
 use client::ClientFactory;
+use spin_factor_capability_policy::CapabilityChecker;
 use spin_factor_otel::OtelFactorState;
 use spin_factor_outbound_networking::OutboundNetworkingFactor;
 use spin_factors::{
@@ -52,11 +53,12 @@ impl<CF: ClientFactory> Factor for OutboundPgFactor<CF> {
         let allowed_hosts = ctx
             .instance_builder::<OutboundNetworkingFactor>()?
             .allowed_hosts();
+        let capabilities = CapabilityChecker::from_prepare_context(&mut ctx)?;
         let otel = OtelFactorState::from_prepare_context(&mut ctx)?;
         let cf = ctx.app_state().get(ctx.app_component().id()).unwrap();
 
         Ok(InstanceState {
-            allowed_host_checker: AllowedHostChecker::new(allowed_hosts),
+            allowed_host_checker: AllowedHostChecker::new(allowed_hosts, capabilities),
             client_factory: cf.clone(),
             connections: Default::default(),
             otel,
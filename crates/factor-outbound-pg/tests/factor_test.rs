@@ -1,4 +1,5 @@
 use anyhow::{Result, bail};
+use spin_factor_capability_policy::{CapabilityPolicyFactor, runtime_config::config_from_table};
 use spin_factor_outbound_networking::OutboundNetworkingFactor;
 use spin_factor_outbound_pg::OutboundPgFactor;
 use spin_factor_outbound_pg::client::Client;
@@ -57,6 +58,51 @@ async fn disallowed_host_fails() -> anyhow::Result<()> {
     Ok(())
 }
 
+#[derive(RuntimeFactors)]
+struct PolicyTestFactors {
+    capability_policy: CapabilityPolicyFactor,
+    variables: VariablesFactor,
+    networking: OutboundNetworkingFactor,
+    pg: OutboundPgFactor<MockClientFactory>,
+}
+
+#[tokio::test]
+async fn capability_policy_denies_hosts() -> anyhow::Result<()> {
+    let env = TestEnvironment::new(PolicyTestFactors {
+        capability_policy: CapabilityPolicyFactor::new(),
+        variables: VariablesFactor::default(),
+        networking: OutboundNetworkingFactor::new(),
+        pg: OutboundPgFactor::<MockClientFactory>::new(),
+    })
+    .extend_manifest(toml! {
+        [component.test-component]
+        source = "does-not-exist.wasm"
+        allowed_outbound_hosts = ["postgres://*:*"]
+    })
+    .runtime_config(PolicyTestFactorsRuntimeConfig {
+        capability_policy: config_from_table(&toml! {
+            [[capability_policy]]
+            effect = "deny"
+            operations = ["*"]
+        })?,
+        variables: None,
+        networking: None,
+        pg: None,
+    })?;
+    let mut state = env.build_instance_state().await?;
+
+    let res = state
+        .pg
+        .open("postgres://localhost:5432/test".to_string())
+        .await;
+    let Err(err) = res else {
+        bail!("expected Err, got Ok");
+    };
+    assert!(matches!(err, PgError::ConnectionFailed(_)));
+
+    Ok(())
+}
+
 #[tokio::test]
 async fn allowed_host_succeeds() -> anyhow::Result<()> {
     let mut state = test_env().build_instance_state().await?;
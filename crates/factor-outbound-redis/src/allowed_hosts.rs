@@ -1,27 +1,37 @@
 use std::sync::Arc;
 
-use spin_factor_outbound_networking::config::allowed_hosts::OutboundAllowedHosts;
+use spin_factor_capability_policy::{CapabilityChecker, operation};
+use spin_factor_outbound_networking::config::allowed_hosts::{OutboundAllowedHosts, OutboundUrl};
 
-/// Encapsulates checking of a PostgreSQL address/connection string against
-/// an allow-list.
+/// Encapsulates checking of a Redis address against an allow-list and the
+/// capability policy.
 ///
 /// This is broken out as a distinct object to allow it to be synchronously retrieved
 /// within a P3 Accessor block and then asynchronously queried outside the block.
 #[derive(Clone)]
 pub(crate) struct AllowedHostChecker {
     allowed_hosts: Arc<OutboundAllowedHosts>,
+    capabilities: CapabilityChecker,
 }
 
 impl AllowedHostChecker {
-    pub fn new(allowed_hosts: OutboundAllowedHosts) -> Self {
+    pub fn new(allowed_hosts: OutboundAllowedHosts, capabilities: CapabilityChecker) -> Self {
         Self {
             allowed_hosts: Arc::new(allowed_hosts),
+            capabilities,
         }
     }
 }
 
 impl AllowedHostChecker {
     pub async fn is_address_allowed(&self, address: &str) -> anyhow::Result<bool> {
-        self.allowed_hosts.check_url(address, "redis").await
+        if !self.allowed_hosts.check_url(address, "redis").await? {
+            return Ok(false);
+        }
+        let url = OutboundUrl::parse(address, "redis")?;
+        Ok(self
+            .capabilities
+            .check(operation::OUTBOUND_REDIS, url.host(), None)
+            .is_ok())
     }
 }
@@ -2,6 +2,7 @@ mod allowed_hosts;
 mod host;
 
 use host::InstanceState;
+use spin_factor_capability_policy::CapabilityChecker;
 use spin_factor_otel::OtelFactorState;
 use spin_factor_outbound_networking::OutboundNetworkingFactor;
 use spin_factors::{
@@ -48,10 +49,14 @@ impl Factor for OutboundRedisFactor {
         mut ctx: PrepareContext<T, Self>,
     ) -> anyhow::Result<Self::InstanceBuilder> {
         let otel = OtelFactorState::from_prepare_context(&mut ctx)?;
+        let capabilities = CapabilityChecker::from_prepare_context(&mut ctx)?;
         let outbound_networking = ctx.instance_builder::<OutboundNetworkingFactor>()?;
 
         Ok(InstanceState {
-            allowed_host_checker: AllowedHostChecker::new(outbound_networking.allowed_hosts()),
+            allowed_host_checker: AllowedHostChecker::new(
+                outbound_networking.allowed_hosts(),
+                capabilities,
+            ),
             blocked_networks: outbound_networking.blocked_networks(),
             connections: spin_resource_table::Table::new(1024),
             otel,
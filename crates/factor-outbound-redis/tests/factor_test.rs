@@ -1,4 +1,5 @@
 use anyhow::bail;
+use spin_factor_capability_policy::{CapabilityPolicyFactor, runtime_config::config_from_table};
 use spin_factor_outbound_networking::OutboundNetworkingFactor;
 use spin_factor_outbound_redis::OutboundRedisFactor;
 use spin_factor_variables::VariablesFactor;
@@ -41,3 +42,47 @@ async fn no_outbound_hosts_fails() -> anyhow::Result<()> {
     assert!(matches!(err, Error::InvalidAddress));
     Ok(())
 }
+
+#[derive(RuntimeFactors)]
+struct PolicyTestFactors {
+    capability_policy: CapabilityPolicyFactor,
+    variables: VariablesFactor,
+    networking: OutboundNetworkingFactor,
+    redis: OutboundRedisFactor,
+}
+
+#[tokio::test]
+async fn capability_policy_denies_addresses() -> anyhow::Result<()> {
+    let env = TestEnvironment::new(PolicyTestFactors {
+        capability_policy: CapabilityPolicyFactor::new(),
+        variables: VariablesFactor::default(),
+        networking: OutboundNetworkingFactor::new(),
+        redis: OutboundRedisFactor::new(),
+    })
+    .extend_manifest(toml! {
+        [component.test-component]
+        source = "does-not-exist.wasm"
+        allowed_outbound_hosts = ["redis://redis.test:8080"]
+    })
+    .runtime_config(PolicyTestFactorsRuntimeConfig {
+        capability_policy: config_from_table(&toml! {
+            [[capability_policy]]
+            effect = "deny"
+            operations = ["*"]
+        })?,
+        variables: None,
+        networking: None,
+        redis: None,
+    })?;
+    let mut state = env.build_instance_state().await?;
+
+    let Err(err) = state
+        .redis
+        .open("redis://redis.test:8080".to_string())
+        .await
+    else {
+        bail!("expected the policy to deny the connection");
+    };
+    assert!(matches!(err, Error::InvalidAddress), "{err:?}");
+    Ok(())
+}
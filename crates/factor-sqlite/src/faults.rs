@@ -0,0 +1,95 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use spin_factors::anyhow;
+use spin_fault_injection::FaultInjector;
+use spin_world::spin::sqlite3_1_0::sqlite as v3;
+
+use crate::{Connection, ConnectionCreator, QueryAsyncResult};
+
+/// A [`ConnectionCreator`] whose connections are delayed and failed as a
+/// [`FaultInjector`] directs.
+pub struct FaultInjectingConnectionCreator {
+    inner: Arc<dyn ConnectionCreator>,
+    faults: Arc<FaultInjector>,
+}
+
+impl FaultInjectingConnectionCreator {
+    pub fn new(inner: Arc<dyn ConnectionCreator>, faults: Arc<FaultInjector>) -> Self {
+        Self { inner, faults }
+    }
+}
+
+async fn inject(faults: &FaultInjector, operation: &str, database: &str) -> Result<(), v3::Error> {
+    faults
+        .inject(&format!("sqlite.{operation}"), database)
+        .await
+        .map_err(v3::Error::Io)
+}
+
+#[async_trait]
+impl ConnectionCreator for FaultInjectingConnectionCreator {
+    async fn create_connection(
+        &self,
+        label: &str,
+    ) -> Result<Arc<dyn Connection + 'static>, v3::Error> {
+        inject(&self.faults, "open", label).await?;
+        let inner = self.inner.create_connection(label).await?;
+        Ok(Arc::new(FaultInjectingConnection {
+            label: label.to_owned(),
+            inner,
+            faults: self.faults.clone(),
+        }))
+    }
+}
+
+struct FaultInjectingConnection {
+    label: String,
+    inner: Arc<dyn Connection>,
+    faults: Arc<FaultInjector>,
+}
+
+#[async_trait]
+impl Connection for FaultInjectingConnection {
+    async fn query(
+        &self,
+        query: &str,
+        parameters: Vec<v3::Value>,
+        max_result_bytes: usize,
+    ) -> Result<v3::QueryResult, v3::Error> {
+        inject(&self.faults, "query", &self.label).await?;
+        self.inner.query(query, parameters, max_result_bytes).await
+    }
+
+    async fn query_async(
+        &self,
+        query: &str,
+        parameters: Vec<v3::Value>,
+        max_result_bytes: usize,
+    ) -> Result<QueryAsyncResult, v3::Error> {
+        inject(&self.faults, "query", &self.label).await?;
+        self.inner
+            .query_async(query, parameters, max_result_bytes)
+            .await
+    }
+
+    async fn execute_batch(&self, statements: &str) -> anyhow::Result<()> {
+        self.faults
+            .inject("sqlite.execute", &self.label)
+            .await
+            .map_err(anyhow::Error::msg)?;
+        self.inner.execute_batch(statements).await
+    }
+
+    async fn changes(&self) -> Result<u64, v3::Error> {
+        self.inner.changes().await
+    }
+
+    async fn last_insert_rowid(&self) -> Result<i64, v3::Error> {
+        self.inner.last_insert_rowid().await
+    }
+
+    fn summary(&self) -> Option<String> {
+        self.inner.summary()
+    }
+}
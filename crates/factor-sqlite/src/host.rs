@@ -2,6 +2,7 @@ use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
 
 use spin_core::wasmtime::component::{Accessor, FutureReader, StreamReader};
+use spin_factor_capability_policy::{CapabilityChecker, operation};
 use spin_factor_otel::OtelFactorState;
 use spin_factors::wasmtime::component::Resource;
 use spin_factors::{SelfInstanceBuilder, anyhow};
@@ -16,11 +17,12 @@ use crate::{Connection, ConnectionCreator, QueryAsyncResult};
 
 pub struct InstanceState {
     allowed_databases: Arc<HashSet<String>>,
-    /// A resource table of connections.
-    connections: spin_resource_table::Table<Arc<dyn Connection>>,
+    /// A resource table of connections, with the labels of their databases.
+    connections: spin_resource_table::Table<(String, Arc<dyn Connection>)>,
     /// A map from database label to connection creators.
     connection_creators: HashMap<String, Arc<dyn ConnectionCreator>>,
     otel: OtelFactorState,
+    capabilities: CapabilityChecker,
 }
 
 impl InstanceState {
@@ -37,9 +39,27 @@ impl InstanceState {
             connections: spin_resource_table::Table::new(256),
             connection_creators,
             otel,
+            capabilities: Default::default(),
         }
     }
 
+    /// Checks databases against the given capability policy as well as the
+    /// allowed databases when they are opened, and again for each statement
+    /// executed.
+    pub fn with_capabilities(mut self, capabilities: CapabilityChecker) -> Self {
+        self.capabilities = capabilities;
+        self
+    }
+
+    /// Returns whether the component may open the given database.
+    fn may_open(&self, database: &str) -> bool {
+        self.allowed_databases.contains(database)
+            && self
+                .capabilities
+                .check(operation::SQLITE, database, None)
+                .is_ok()
+    }
+
     /// Get a connection for a given database label.
     fn get_connection<T: 'static>(
         &self,
@@ -47,12 +67,29 @@ impl InstanceState {
     ) -> Result<Arc<dyn Connection>, v3::Error> {
         self.connections
             .get(connection.rep())
-            .cloned()
+            .map(|(_, conn)| conn.clone())
             .ok_or(v3::Error::InvalidConnection)
     }
 
+    /// Get a connection to execute a statement on, if the capability policy
+    /// still allows its database; it may only allow it during certain hours.
+    fn get_connection_to_execute<T: 'static>(
+        &self,
+        connection: Resource<T>,
+    ) -> Result<Arc<dyn Connection>, v3::Error> {
+        let (database, conn) = self
+            .connections
+            .get(connection.rep())
+            .ok_or(v3::Error::InvalidConnection)?;
+        self.capabilities
+            .check(operation::SQLITE, database, None)
+            .map_err(|_| v3::Error::AccessDenied)?;
+        Ok(conn.clone())
+    }
+
     async fn open_impl<T: 'static>(&mut self, database: String) -> Result<Resource<T>, v3::Error> {
-        if !self.allowed_databases.contains(&database) {
+        spin_telemetry::audit::event!("sqlite.open", database = database.as_str());
+        if !self.may_open(&database) {
             return Err(v3::Error::AccessDenied);
         }
         let conn = self
@@ -66,7 +103,7 @@ impl InstanceState {
             conn.summary().as_deref().unwrap_or("unknown"),
         );
         self.connections
-            .push(conn)
+            .push((database, conn))
             .map_err(|()| v3::Error::Io("too many connections opened".to_string()))
             .map(Resource::new_own)
     }
@@ -77,7 +114,8 @@ impl InstanceState {
         query: String,
         parameters: Vec<v3::Value>,
     ) -> Result<v3::QueryResult, v3::Error> {
-        let conn = self.get_connection(connection)?;
+        let conn = self.get_connection_to_execute(connection)?;
+        audit_execute(&query);
         tracing::Span::current().record(
             "sqlite.backend",
             conn.summary().as_deref().unwrap_or("unknown"),
@@ -94,6 +132,16 @@ impl InstanceState {
 
 impl SelfInstanceBuilder for InstanceState {}
 
+/// Records a statement in the audit log by its digest, so that its parameters aren't revealed.
+fn audit_execute(query: &str) {
+    if spin_telemetry::audit::enabled() {
+        spin_telemetry::audit::event!(
+            "sqlite.execute",
+            statement_sha256 = spin_telemetry::audit::digest(query)
+        );
+    }
+}
+
 impl v3::Host for InstanceState {
     fn convert_error(&mut self, error: v3::Error) -> anyhow::Result<v3::Error> {
         Ok(error)
@@ -103,6 +151,7 @@ impl v3::Host for InstanceState {
 impl v3::HostConnection for InstanceState {
     #[instrument(name = "spin_sqlite.open", skip(self), err(level = Level::INFO), fields(otel.kind = "client", db.system = "sqlite", sqlite.backend = Empty))]
     async fn open(&mut self, database: String) -> Result<Resource<v3::Connection>, v3::Error> {
+        self.otel.reparent_tracing_span();
         self.open_impl(database).await
     }
 
@@ -113,6 +162,7 @@ impl v3::HostConnection for InstanceState {
         query: String,
         parameters: Vec<v3::Value>,
     ) -> Result<v3::QueryResult, v3::Error> {
+        self.otel.reparent_tracing_span();
         self.execute_impl(connection, query, parameters).await
     }
 
@@ -156,9 +206,10 @@ impl v3::HostConnectionWithStore for crate::SqliteFactorData {
     ) -> Result<Resource<v3::Connection>, v3::Error> {
         // TODO: this duplicates `open_impl` logic but split up to move
         // in and out of the Accessor. How to dedupe?
+        spin_telemetry::audit::event!("sqlite.open", database = database.as_str());
         let conn_creator = accessor.with(|mut access| {
             let host = access.get();
-            if !host.allowed_databases.contains(&database) {
+            if !host.may_open(&database) {
                 return Err(v3::Error::AccessDenied);
             }
             host.connection_creators
@@ -177,7 +228,7 @@ impl v3::HostConnectionWithStore for crate::SqliteFactorData {
         accessor.with(|mut access| {
             let host = access.get();
             host.connections
-                .push(conn)
+                .push((database, conn))
                 .map_err(|()| v3::Error::Io("too many connections opened".to_string()))
                 .map(Resource::new_own)
         })
@@ -198,8 +249,9 @@ impl v3::HostConnectionWithStore for crate::SqliteFactorData {
     > {
         let conn = accessor.with(|mut access| {
             let host = access.get();
-            host.get_connection(connection)
+            host.get_connection_to_execute(connection)
         })?;
+        audit_execute(&query);
 
         tracing::Span::current().record(
             "sqlite.backend",
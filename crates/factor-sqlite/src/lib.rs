@@ -1,3 +1,4 @@
+mod faults;
 mod host;
 pub mod runtime_config;
 
@@ -7,8 +8,10 @@ use std::sync::Arc;
 use host::InstanceState;
 
 use async_trait::async_trait;
+use spin_factor_capability_policy::CapabilityChecker;
 use spin_factor_otel::OtelFactorState;
 use spin_factors::{Factor, anyhow};
+use spin_fault_injection::FaultInjector;
 use spin_locked_app::MetadataKey;
 use spin_world::spin::sqlite3_1_0::sqlite as v3;
 use spin_world::v1::sqlite as v1;
@@ -18,13 +21,21 @@ pub use runtime_config::RuntimeConfig;
 
 #[derive(Default)]
 pub struct SqliteFactor {
-    _priv: (),
+    fault_injector: Option<Arc<FaultInjector>>,
 }
 
 impl SqliteFactor {
     /// Create a new `SqliteFactor`
     pub fn new() -> Self {
-        Self { _priv: () }
+        Self::default()
+    }
+
+    /// Delays and fails database operations as the given injector directs.
+    ///
+    /// Only components' operations are affected, not those made by the host
+    /// (e.g. to seed databases).
+    pub fn set_fault_injector(&mut self, fault_injector: Arc<FaultInjector>) {
+        self.fault_injector = Some(fault_injector);
     }
 }
 
@@ -84,11 +95,20 @@ impl Factor for SqliteFactor {
             .cloned()
             .unwrap_or_default();
         let otel = OtelFactorState::from_prepare_context(&mut ctx)?;
-        Ok(InstanceState::new(
-            allowed_databases,
-            ctx.app_state().connection_creators.clone(),
-            otel,
-        ))
+        let capabilities = CapabilityChecker::from_prepare_context(&mut ctx)?;
+        let mut connection_creators = ctx.app_state().connection_creators.clone();
+        if let Some(faults) = &self.fault_injector {
+            for creator in connection_creators.values_mut() {
+                *creator = Arc::new(faults::FaultInjectingConnectionCreator::new(
+                    creator.clone(),
+                    faults.clone(),
+                ));
+            }
+        }
+        Ok(
+            InstanceState::new(allowed_databases, connection_creators, otel)
+                .with_capabilities(capabilities),
+        )
     }
 }
 
@@ -1,4 +1,5 @@
 use spin_core::wasmtime::component::Accessor;
+use spin_factor_capability_policy::operation;
 use spin_factors::anyhow;
 use spin_telemetry::traces::{self, Blame};
 use spin_world::{
@@ -11,13 +12,21 @@ use crate::{InstanceState, VariablesFactorData};
 impl v3::HostWithStore for VariablesFactorData {
     #[instrument(name = "spin_variables.get", skip(accessor), fields(otel.kind = "client"))]
     async fn get<T: Send>(accessor: &Accessor<T, Self>, key: String) -> Result<String, v3::Error> {
-        let (resolver, component_id) = accessor.with(|mut access| {
+        let (resolver, component_id, capabilities) = accessor.with(|mut access| {
             let host = access.get();
             host.otel.reparent_tracing_span();
-            (host.expression_resolver.clone(), host.component_id.clone())
+            (
+                host.expression_resolver.clone(),
+                host.component_id.clone(),
+                host.capabilities.clone(),
+            )
         });
 
         let key = spin_expressions::Key::new(&key).map_err(expressions_to_variables_err_v3)?;
+        spin_telemetry::audit::event!("variables.get", variable = key.as_str());
+        capabilities
+            .check(operation::VARIABLES, key.as_str(), None)
+            .map_err(|err| v3::Error::Other(err.to_string()))?;
 
         resolver
             .resolve(&component_id, key)
@@ -37,6 +46,10 @@ impl v2::Host for InstanceState {
     async fn get(&mut self, key: String) -> Result<String, v2::Error> {
         self.otel.reparent_tracing_span();
         let key = spin_expressions::Key::new(&key).map_err(expressions_to_variables_err)?;
+        spin_telemetry::audit::event!("variables.get", variable = key.as_str());
+        self.capabilities
+            .check(operation::VARIABLES, key.as_str(), None)
+            .map_err(|err| v2::Error::Other(err.to_string()))?;
         self.expression_resolver
             .resolve(&self.component_id, key)
             .await
@@ -68,6 +81,14 @@ impl v1::config::Host for InstanceState {
 impl wasi_config::store::Host for InstanceState {
     #[instrument(name = "wasi_config.get", skip(self), fields(otel.kind = "client"))]
     async fn get(&mut self, key: String) -> Result<Option<String>, wasi_config::store::Error> {
+        // Off-the-shelf components often use keys that aren't valid Spin
+        // variable names, e.g. `DATABASE_URL` or `database.url`; look those
+        // up as the equivalent variable name, e.g. `database_url`.
+        let key = if spin_expressions::Key::new(&key).is_ok() {
+            key
+        } else {
+            wasi_config_key_to_variable_name(&key)
+        };
         match <Self as v2::Host>::get(self, key).await {
             Ok(value) => Ok(Some(value)),
             Err(v2::Error::Undefined(_)) => Ok(None),
@@ -79,10 +100,22 @@ impl wasi_config::store::Host for InstanceState {
 
     #[instrument(name = "wasi_config.get_all", skip(self), fields(otel.kind = "client"))]
     async fn get_all(&mut self) -> Result<Vec<(String, String)>, wasi_config::store::Error> {
+        spin_telemetry::audit::event!("variables.get_all");
         let all = self
             .expression_resolver
             .resolve_all(&self.component_id)
             .await;
+        // Leave out variables the component may not read, rather than failing
+        // the whole call.
+        let all = all.map(|all| {
+            all.into_iter()
+                .filter(|(key, _)| {
+                    self.capabilities
+                        .check(operation::VARIABLES, key, None)
+                        .is_ok()
+                })
+                .collect()
+        });
         all.map_err(|e| {
             match expressions_to_variables_err(e) {
                 v2::Error::Undefined(msg) => wasi_config::store::Error::Io(msg), // this shouldn't happen but just in case
@@ -101,6 +134,17 @@ impl wasi_config::store::Host for InstanceState {
     }
 }
 
+/// Maps a `wasi:config` key to a Spin variable name by lowercasing it and
+/// replacing separators with underscores.
+fn wasi_config_key_to_variable_name(key: &str) -> String {
+    key.chars()
+        .map(|c| match c {
+            '.' | '-' | '/' | ':' => '_',
+            c => c.to_ascii_lowercase(),
+        })
+        .collect()
+}
+
 /// Convert a `spin_expressions::Error` to a `v2::Error`, setting the current span's status and fault attribute.
 fn expressions_to_variables_err(err: spin_expressions::Error) -> v2::Error {
     use spin_expressions::Error;
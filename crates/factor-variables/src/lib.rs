@@ -5,6 +5,7 @@ use std::sync::Arc;
 
 use runtime_config::RuntimeConfig;
 use spin_expressions::{ProviderResolver as ExpressionResolver, Template};
+use spin_factor_capability_policy::CapabilityChecker;
 use spin_factor_otel::OtelFactorState;
 use spin_factors::{
     ConfigureAppContext, Factor, FactorData, InitContext, PrepareContext, RuntimeFactors,
@@ -70,10 +71,12 @@ impl Factor for VariablesFactor {
         let component_id = ctx.app_component().id().to_string();
         let expression_resolver = ctx.app_state().expression_resolver.clone();
         let otel = OtelFactorState::from_prepare_context(&mut ctx)?;
+        let capabilities = CapabilityChecker::from_prepare_context(&mut ctx)?;
         Ok(InstanceState {
             component_id,
             expression_resolver,
             otel,
+            capabilities,
         })
     }
 }
@@ -100,6 +103,7 @@ pub struct InstanceState {
     component_id: String,
     expression_resolver: Arc<ExpressionResolver>,
     otel: OtelFactorState,
+    capabilities: CapabilityChecker,
 }
 
 impl InstanceState {
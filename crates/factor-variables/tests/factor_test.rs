@@ -1,8 +1,10 @@
 use spin_expressions::{Key, Provider};
+use spin_factor_capability_policy::{CapabilityPolicyFactor, runtime_config::config_from_table};
 use spin_factor_variables::{VariablesFactor, runtime_config::RuntimeConfig};
 use spin_factors::{RuntimeFactors, anyhow};
 use spin_factors_test::{TestEnvironment, toml};
 use spin_world::v2::variables::Host;
+use spin_world::wasi::config::store as wasi_config;
 
 #[derive(RuntimeFactors)]
 struct TestFactors {
@@ -35,6 +37,92 @@ async fn provider_works() -> anyhow::Result<()> {
     Ok(())
 }
 
+#[tokio::test(flavor = "multi_thread")]
+async fn wasi_config_resolves_component_variables() -> anyhow::Result<()> {
+    let factors = TestFactors {
+        variables: VariablesFactor::default(),
+    };
+    let env = TestEnvironment::new(factors).extend_manifest(toml! {
+        [variables]
+        foo = { default = "bar" }
+
+        [component.test-component]
+        source = "does-not-exist.wasm"
+        variables = { database_url = "db://{{ foo }}" }
+    });
+
+    let mut state = env.build_instance_state().await?;
+    let state = &mut state.variables;
+    assert_eq!(
+        wasi_config::Host::get(state, "database_url".into())
+            .await?
+            .as_deref(),
+        Some("db://bar")
+    );
+    assert_eq!(
+        wasi_config::Host::get(state, "DATABASE_URL".into())
+            .await?
+            .as_deref(),
+        Some("db://bar")
+    );
+    assert_eq!(
+        wasi_config::Host::get(state, "database.url".into())
+            .await?
+            .as_deref(),
+        Some("db://bar")
+    );
+    // Application variables are only visible through the component's own.
+    assert_eq!(wasi_config::Host::get(state, "foo".into()).await?, None);
+    assert_eq!(
+        wasi_config::Host::get_all(state).await?,
+        vec![("database_url".to_owned(), "db://bar".to_owned())]
+    );
+    Ok(())
+}
+
+#[derive(RuntimeFactors)]
+struct PolicyTestFactors {
+    capability_policy: CapabilityPolicyFactor,
+    variables: VariablesFactor,
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn capability_policy_denies_variables() -> anyhow::Result<()> {
+    let factors = PolicyTestFactors {
+        capability_policy: CapabilityPolicyFactor::new(),
+        variables: VariablesFactor::default(),
+    };
+    let runtime_config = PolicyTestFactorsRuntimeConfig {
+        capability_policy: config_from_table(&toml! {
+            [[capability_policy]]
+            effect = "deny"
+            operations = ["variables"]
+            resources = ["secret_*"]
+        })?,
+        variables: None,
+    };
+    let env = TestEnvironment::new(factors)
+        .extend_manifest(toml! {
+            [component.test-component]
+            source = "does-not-exist.wasm"
+            variables = { secret_token = "s3cr3t", greeting = "hello" }
+        })
+        .runtime_config(runtime_config)?;
+
+    let mut state = env.build_instance_state().await?;
+    let state = &mut state.variables;
+    assert_eq!(state.get("greeting".into()).await?, "hello");
+    assert!(matches!(
+        state.get("secret_token".into()).await,
+        Err(spin_world::v2::variables::Error::Other(_))
+    ));
+    assert_eq!(
+        wasi_config::Host::get_all(state).await?,
+        vec![("greeting".to_owned(), "hello".to_owned())]
+    );
+    Ok(())
+}
+
 #[derive(Debug)]
 struct MockProvider;
 
@@ -0,0 +1,304 @@
+//! A factor providing the `wasi:nn` interface for local ML inference.
+//!
+//! Models are identified by label: a component may only load (by name) the
+//! models listed in its manifest `nn_models`, and each label is mapped to a
+//! model directory and backend by the runtime config. Models are loaded once
+//! per app and shared by all instances.
+
+pub mod spin;
+
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use anyhow::{Context as _, bail, ensure};
+use spin_factor_capability_policy::{CapabilityChecker, operation};
+use spin_factors::{
+    ConfigureAppContext, Factor, FactorInstanceBuilder, InitContext, PrepareContext, RuntimeFactors,
+};
+use spin_locked_app::MetadataKey;
+use wasmtime_wasi_nn::wit::{ExecutionTarget, WasiNnCtx, WasiNnView};
+use wasmtime_wasi_nn::{Graph, GraphRegistry, Registry, backend};
+
+/// Metadata key for the models a component may load.
+pub const NN_MODELS_KEY: MetadataKey<Vec<String>> = MetadataKey::new("nn_models");
+
+/// A factor that provides `wasi:nn`.
+#[derive(Default)]
+pub struct WasiNnFactor {
+    _priv: (),
+}
+
+impl WasiNnFactor {
+    /// Create a new WasiNnFactor.
+    pub fn new() -> Self {
+        Self { _priv: () }
+    }
+}
+
+impl Factor for WasiNnFactor {
+    type RuntimeConfig = RuntimeConfig;
+    type AppState = AppState;
+    type InstanceBuilder = InstanceBuilder;
+
+    fn init(&mut self, ctx: &mut impl InitContext<Self>) -> anyhow::Result<()> {
+        add_to_linker(ctx)
+    }
+
+    fn configure_app<T: RuntimeFactors>(
+        &self,
+        mut ctx: ConfigureAppContext<T, Self>,
+    ) -> anyhow::Result<Self::AppState> {
+        let runtime_config = ctx.take_runtime_config().unwrap_or_default();
+
+        // Build component -> allowed models map
+        let mut component_allowed_models = HashMap::new();
+        for component in ctx.app().components() {
+            let component_id = component.id().to_string();
+            let nn_models = component
+                .get_metadata(NN_MODELS_KEY)?
+                .unwrap_or_default()
+                .into_iter()
+                .collect::<HashSet<_>>();
+            for label in &nn_models {
+                ensure!(
+                    runtime_config.models.contains_key(label),
+                    "unknown nn_models label {label:?} for component {component_id:?}"
+                );
+            }
+            component_allowed_models.insert(component_id, nn_models);
+        }
+
+        // Only load the models some component can use; loading can be slow.
+        let mut graphs = HashMap::new();
+        for (label, model) in runtime_config.models {
+            if component_allowed_models
+                .values()
+                .any(|models| models.contains(&label))
+            {
+                let graph = model
+                    .load()
+                    .with_context(|| format!("failed to load nn model {label:?}"))?;
+                graphs.insert(label, graph);
+            }
+        }
+
+        Ok(AppState {
+            graphs: Arc::new(graphs),
+            component_allowed_models,
+        })
+    }
+
+    fn prepare<T: RuntimeFactors>(
+        &self,
+        mut ctx: PrepareContext<T, Self>,
+    ) -> anyhow::Result<InstanceBuilder> {
+        let app_state = ctx.app_state();
+        let allowed_models = app_state
+            .component_allowed_models
+            .get(ctx.app_component().id())
+            .expect("component should be in component_allowed_models")
+            .clone();
+        let graphs = app_state.graphs.clone();
+        let capabilities = CapabilityChecker::from_prepare_context(&mut ctx)?;
+        Ok(InstanceBuilder {
+            graphs,
+            allowed_models,
+            capabilities,
+        })
+    }
+}
+
+fn add_to_linker<C: InitContext<WasiNnFactor>>(ctx: &mut C) -> anyhow::Result<()> {
+    fn get_view<C: InitContext<WasiNnFactor>>(data: &mut C::StoreData) -> WasiNnView<'_> {
+        let (state, table) = C::get_data_with_table(data);
+        WasiNnView::new(table, &mut state.ctx)
+    }
+    Ok(wasmtime_wasi_nn::wit::add_to_linker(
+        ctx.linker(),
+        get_view::<C>,
+    )?)
+}
+
+/// The runtime configuration for the wasi-nn factor.
+#[derive(Default)]
+pub struct RuntimeConfig {
+    /// The models defined for the app, by label.
+    models: HashMap<String, ModelConfig>,
+}
+
+impl RuntimeConfig {
+    /// Adds a model definition, replacing any existing one with the same label.
+    pub fn add_model(&mut self, label: String, model: ModelConfig) {
+        self.models.insert(label, model);
+    }
+
+    /// Returns whether a model with the given label is defined.
+    pub fn has_model(&self, label: &str) -> bool {
+        self.models.contains_key(label)
+    }
+}
+
+/// The definition of a model: the backend to run it with, and the directory
+/// holding its files (e.g. `model.onnx` for the ONNX backend).
+pub struct ModelConfig {
+    backend: String,
+    path: PathBuf,
+}
+
+impl ModelConfig {
+    pub fn new(backend: String, path: PathBuf) -> Self {
+        Self { backend, path }
+    }
+
+    fn load(&self) -> anyhow::Result<Graph> {
+        let encoding = self
+            .backend
+            .parse()
+            .with_context(|| format!("unknown nn backend {:?}", self.backend))?;
+        let mut backends = backend::list();
+        let Some(backend) = backends.iter_mut().find(|b| b.encoding() == encoding) else {
+            bail!(
+                "nn backend {:?} is not supported in this version of Spin",
+                self.backend
+            );
+        };
+        let Some(backend) = backend.as_dir_loadable() else {
+            bail!(
+                "nn backend {:?} cannot load models from a directory",
+                self.backend
+            );
+        };
+        Ok(backend.load_from_dir(&self.path, ExecutionTarget::Cpu)?)
+    }
+}
+
+pub struct AppState {
+    /// The loaded models, by label.
+    graphs: Arc<HashMap<String, Graph>>,
+    /// The allowed models for each component.
+    ///
+    /// This is a map from component ID to the set of model labels that
+    /// the component is allowed to load.
+    component_allowed_models: HashMap<String, HashSet<String>>,
+}
+
+pub struct InstanceBuilder {
+    /// The loaded models, by label.
+    graphs: Arc<HashMap<String, Graph>>,
+    /// The allowed models for this component instance.
+    allowed_models: HashSet<String>,
+    capabilities: CapabilityChecker,
+}
+
+impl FactorInstanceBuilder for InstanceBuilder {
+    type InstanceState = InstanceState;
+
+    fn build(self) -> anyhow::Result<Self::InstanceState> {
+        let graphs = self
+            .graphs
+            .iter()
+            .filter(|(label, _)| self.allowed_models.contains(*label))
+            .map(|(label, graph)| (label.clone(), graph.clone()))
+            .collect();
+        let registry = AllowedModels {
+            graphs,
+            capabilities: self.capabilities,
+        };
+        Ok(InstanceState {
+            allowed_models: self.allowed_models,
+            ctx: WasiNnCtx::new(backend::list(), Registry::from(registry)),
+        })
+    }
+}
+
+pub struct InstanceState {
+    allowed_models: HashSet<String>,
+    ctx: WasiNnCtx,
+}
+
+impl InstanceState {
+    pub fn allowed_models(&self) -> &HashSet<String> {
+        &self.allowed_models
+    }
+}
+
+/// The models an instance may load by name.
+///
+/// Models denied by the capability policy are reported as not found.
+struct AllowedModels {
+    graphs: HashMap<String, Graph>,
+    capabilities: CapabilityChecker,
+}
+
+impl AllowedModels {
+    fn is_allowed(&self, name: &str) -> bool {
+        self.capabilities
+            .check(operation::WASI_NN, name, None)
+            .is_ok()
+    }
+}
+
+impl GraphRegistry for AllowedModels {
+    fn get(&self, name: &str) -> Option<&Graph> {
+        if !self.is_allowed(name) {
+            return None;
+        }
+        self.graphs.get(name)
+    }
+
+    fn get_mut(&mut self, name: &str) -> Option<&mut Graph> {
+        if !self.is_allowed(name) {
+            return None;
+        }
+        self.graphs.get_mut(name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use spin_factor_capability_policy::{Effect, Policy, Rule};
+    use wasmtime_wasi_nn::{
+        ExecutionContext,
+        backend::{BackendError, BackendGraph},
+    };
+
+    use super::*;
+
+    struct TestGraph;
+
+    impl BackendGraph for TestGraph {
+        fn init_execution_context(&self) -> Result<ExecutionContext, BackendError> {
+            unreachable!("test graphs are never executed")
+        }
+    }
+
+    #[test]
+    fn capability_policy_denies_models() {
+        let graphs = HashMap::from([(
+            "mobilenet".to_owned(),
+            Graph::from(Box::new(TestGraph) as Box<dyn BackendGraph>),
+        )]);
+        let registry = AllowedModels {
+            graphs: graphs.clone(),
+            capabilities: CapabilityChecker::default(),
+        };
+        assert!(registry.get("mobilenet").is_some());
+
+        let registry = AllowedModels {
+            graphs,
+            capabilities: CapabilityChecker::new(
+                "test-component",
+                Arc::new(Policy::new([Rule {
+                    effect: Effect::Deny,
+                    components: vec![],
+                    operations: vec!["*".into()],
+                    resources: vec![],
+                    methods: vec![],
+                    hours: None,
+                }])),
+            ),
+        };
+        assert!(registry.get("mobilenet").is_none());
+    }
+}
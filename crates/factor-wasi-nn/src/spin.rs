@@ -0,0 +1,91 @@
+//! Runtime configuration for the wasi-nn factor in the Spin CLI.
+//!
+//! ```toml
+//! [nn_model.mobilenet]
+//! backend = "onnx"
+//! path = "models/mobilenet"
+//! ```
+
+use std::path::{Path, PathBuf};
+
+use anyhow::Context as _;
+use serde::Deserialize;
+use spin_factors::runtime_config::toml::GetTomlValue;
+
+use crate::{ModelConfig, RuntimeConfig};
+
+/// Resolves the `[nn_model.<label>]` tables of a runtime config.
+///
+/// Relative model paths are resolved against `base_dir` if given.
+pub fn runtime_config_from_toml(
+    table: &impl GetTomlValue,
+    base_dir: Option<&Path>,
+) -> anyhow::Result<Option<RuntimeConfig>> {
+    let Some(value) = table.get("nn_model") else {
+        return Ok(None);
+    };
+    let models = value
+        .as_table()
+        .context("expected a [nn_model.<label>] table")?;
+
+    let mut runtime_config = RuntimeConfig::default();
+    for (label, model) in models {
+        let NnModel { backend, path } = model
+            .clone()
+            .try_into()
+            .with_context(|| format!("invalid runtime config for nn model {label:?}"))?;
+        let path = match base_dir {
+            Some(base_dir) if path.is_relative() => base_dir.join(path),
+            _ => path,
+        };
+        runtime_config.add_model(label.clone(), ModelConfig::new(backend, path));
+    }
+    Ok(Some(runtime_config))
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct NnModel {
+    /// The backend to run the model with, e.g. "onnx".
+    backend: String,
+    /// The directory holding the model files.
+    path: PathBuf,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resolves_relative_model_paths() -> anyhow::Result<()> {
+        let toml: toml::Table = toml::toml! {
+            [nn_model.relative]
+            backend = "onnx"
+            path = "models/relative"
+
+            [nn_model.absolute]
+            backend = "onnx"
+            path = "/models/absolute"
+        };
+        let config = runtime_config_from_toml(&toml, Some(Path::new("/app")))?.unwrap();
+        assert_eq!(
+            Path::new("/app/models/relative"),
+            config.models["relative"].path
+        );
+        assert_eq!(
+            Path::new("/models/absolute"),
+            config.models["absolute"].path
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn rejects_unknown_fields() {
+        let toml: toml::Table = toml::toml! {
+            [nn_model.typo]
+            backend = "onnx"
+            pth = "models/typo"
+        };
+        assert!(runtime_config_from_toml(&toml, None).is_err());
+    }
+}
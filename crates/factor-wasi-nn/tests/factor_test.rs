@@ -0,0 +1,73 @@
+use std::collections::HashSet;
+
+use anyhow::bail;
+use spin_factor_wasi_nn::{ModelConfig, RuntimeConfig, WasiNnFactor};
+use spin_factors::RuntimeFactors;
+use spin_factors_test::{TestEnvironment, toml};
+
+#[derive(RuntimeFactors)]
+struct TestFactors {
+    nn: WasiNnFactor,
+}
+
+impl From<RuntimeConfig> for TestFactorsRuntimeConfig {
+    fn from(value: RuntimeConfig) -> Self {
+        Self { nn: Some(value) }
+    }
+}
+
+#[tokio::test]
+async fn works_without_models() -> anyhow::Result<()> {
+    let env = TestEnvironment::new(TestFactors {
+        nn: WasiNnFactor::new(),
+    });
+    let state = env.build_instance_state().await?;
+
+    assert!(state.nn.allowed_models().is_empty());
+    Ok(())
+}
+
+#[tokio::test]
+async fn errors_when_model_is_not_defined() -> anyhow::Result<()> {
+    let env = TestEnvironment::new(TestFactors {
+        nn: WasiNnFactor::new(),
+    })
+    .extend_manifest(toml! {
+        [component.test-component]
+        source = "does-not-exist.wasm"
+        nn_models = ["mobilenet"]
+    });
+    let Err(err) = env
+        .runtime_config(RuntimeConfig::default())?
+        .build_instance_state()
+        .await
+    else {
+        bail!("expected instance build to fail but it didn't");
+    };
+
+    assert!(
+        err.to_string()
+            .contains(r#"unknown nn_models label "mobilenet""#)
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn unused_models_are_not_loaded() -> anyhow::Result<()> {
+    // The model directory doesn't exist, so loading it would fail.
+    let mut runtime_config = RuntimeConfig::default();
+    runtime_config.add_model(
+        "unused".into(),
+        ModelConfig::new("onnx".into(), "does-not-exist".into()),
+    );
+    let env = TestEnvironment::new(TestFactors {
+        nn: WasiNnFactor::new(),
+    });
+    let state = env
+        .runtime_config(runtime_config)?
+        .build_instance_state()
+        .await?;
+
+    assert_eq!(state.nn.allowed_models(), &HashSet::new());
+    Ok(())
+}
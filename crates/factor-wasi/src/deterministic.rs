@@ -0,0 +1,345 @@
+//! A mode in which components see seeded randomness and virtual clocks, so
+//! that runs can be reproduced.
+
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use async_trait::async_trait;
+use cap_rand::SeedableRng as _;
+use cap_rand::rngs::StdRng;
+use tokio::sync::watch;
+use wasmtime::component::{HasData, Resource};
+use wasmtime_wasi::clocks::WasiClocksCtxView;
+use wasmtime_wasi::p2::{DynPollable, Pollable, subscribe};
+use wasmtime_wasi::{HostMonotonicClock, HostWallClock, WasiCtxBuilder};
+
+mod bindings {
+    wasmtime::component::bindgen!({
+        inline: r#"
+        package spin:virtual-clock;
+        world virtual-clock {
+            import wasi:clocks/monotonic-clock@0.2.6;
+        }
+        "#,
+        path: "../../wit",
+        imports: { default: trappable },
+        with: {
+            "wasi:io/poll": wasmtime_wasi::p2::bindings::io::poll,
+        },
+    });
+}
+
+pub(crate) use bindings::wasi::clocks::monotonic_clock;
+
+mod latest {
+    pub use wasmtime_wasi::p2::bindings::clocks::monotonic_clock::Host;
+}
+
+/// Settings for running components reproducibly.
+///
+/// Every instance gets random number generators seeded from the same seed,
+/// and observes the same [`VirtualClock`].
+#[derive(Clone, Debug)]
+pub struct DeterministicMode {
+    seed: u64,
+    clock: VirtualClock,
+}
+
+impl DeterministicMode {
+    /// Creates a deterministic mode with the given random seed, whose clock
+    /// starts at `clock_start`.
+    pub fn new(seed: u64, clock_start: SystemTime) -> Self {
+        Self {
+            seed,
+            clock: VirtualClock::new(clock_start),
+        }
+    }
+
+    /// The clock observed by all instances.
+    pub fn clock(&self) -> &VirtualClock {
+        &self.clock
+    }
+
+    pub(crate) fn apply(&self, ctx: &mut WasiCtxBuilder) {
+        ctx.secure_random(StdRng::seed_from_u64(self.seed));
+        ctx.insecure_random(StdRng::seed_from_u64(self.seed.wrapping_add(1)));
+        ctx.insecure_random_seed(self.seed.into());
+        ctx.wall_clock(self.clock.clone());
+        ctx.monotonic_clock(self.clock.clone());
+    }
+}
+
+/// A clock that tests control: it may be advanced at will, and frozen so that
+/// it stands still except when advanced.
+///
+/// It serves as both the wall clock and the monotonic clock; clones share
+/// the same time.
+#[derive(Clone, Debug)]
+pub struct VirtualClock {
+    start: Duration,
+    state: Arc<Mutex<ClockState>>,
+    /// Wakes timers waiting on the clock whenever it is advanced, frozen or
+    /// resumed.
+    changed: Arc<watch::Sender<()>>,
+}
+
+#[derive(Debug)]
+struct ClockState {
+    /// The time elapsed up to `running_since`, or in total if frozen.
+    elapsed: Duration,
+    /// When the clock last started keeping real time, if it is running.
+    running_since: Option<Instant>,
+}
+
+impl ClockState {
+    fn elapsed(&self) -> Duration {
+        self.elapsed + self.running_since.map_or(Duration::ZERO, |t| t.elapsed())
+    }
+}
+
+impl VirtualClock {
+    /// Creates a frozen clock reading `start`.
+    pub fn new(start: SystemTime) -> Self {
+        Self {
+            start: start.duration_since(UNIX_EPOCH).unwrap_or_default(),
+            state: Arc::new(Mutex::new(ClockState {
+                elapsed: Duration::ZERO,
+                running_since: None,
+            })),
+            changed: Arc::new(watch::Sender::new(())),
+        }
+    }
+
+    /// Creates a clock reading the current time, which keeps real time until
+    /// it is frozen.
+    pub fn running() -> Self {
+        let clock = Self::new(SystemTime::now());
+        clock.resume();
+        clock
+    }
+
+    /// Moves the clock forward by `by`.
+    pub fn advance(&self, by: Duration) {
+        {
+            let mut state = self.state.lock().unwrap();
+            state.elapsed = state.elapsed.saturating_add(by);
+        }
+        self.changed.send_replace(());
+    }
+
+    /// Stops the clock, so that it only moves when advanced.
+    pub fn freeze(&self) {
+        {
+            let mut state = self.state.lock().unwrap();
+            state.elapsed = state.elapsed();
+            state.running_since = None;
+        }
+        self.changed.send_replace(());
+    }
+
+    /// Starts the clock keeping real time again.
+    pub fn resume(&self) {
+        {
+            let mut state = self.state.lock().unwrap();
+            if state.running_since.is_none() {
+                state.running_since = Some(Instant::now());
+            }
+        }
+        self.changed.send_replace(());
+    }
+
+    /// Whether the clock only moves when advanced.
+    pub fn is_frozen(&self) -> bool {
+        self.state.lock().unwrap().running_since.is_none()
+    }
+
+    /// The time the clock reads now.
+    pub fn now(&self) -> SystemTime {
+        UNIX_EPOCH + self.start + self.elapsed()
+    }
+
+    /// The time elapsed since the clock started.
+    pub fn elapsed(&self) -> Duration {
+        self.state.lock().unwrap().elapsed()
+    }
+
+    /// Waits until the clock has moved `deadline` past its start. A frozen
+    /// clock only gets there by being advanced.
+    pub async fn sleep_until(&self, deadline: Duration) {
+        let mut changed = self.changed.subscribe();
+        loop {
+            let (elapsed, running) = {
+                let state = self.state.lock().unwrap();
+                (state.elapsed(), state.running_since.is_some())
+            };
+            let remaining = deadline.saturating_sub(elapsed);
+            if remaining.is_zero() {
+                return;
+            }
+            if running {
+                tokio::select! {
+                    _ = changed.changed() => {}
+                    _ = tokio::time::sleep(remaining) => {}
+                }
+            } else {
+                // The sender lives as long as `self`, so this never fails.
+                let _ = changed.changed().await;
+            }
+        }
+    }
+}
+
+/// A guest timer that fires when a [`VirtualClock`] reaches its deadline.
+struct VirtualTimer {
+    clock: VirtualClock,
+    deadline: Duration,
+}
+
+#[async_trait]
+impl Pollable for VirtualTimer {
+    async fn ready(&mut self) {
+        self.clock.sleep_until(self.deadline).await
+    }
+}
+
+/// The monotonic clock seen by an instance. Guest timers follow the virtual
+/// clock if there is one, so that advancing it wakes sleeping guests.
+pub(crate) struct VirtualClockView<'a> {
+    pub(crate) clocks: WasiClocksCtxView<'a>,
+    pub(crate) virtual_clock: Option<&'a VirtualClock>,
+}
+
+impl VirtualClockView<'_> {
+    fn subscribe(
+        &mut self,
+        clock: VirtualClock,
+        deadline: Duration,
+    ) -> wasmtime::Result<Resource<DynPollable>> {
+        let timer = self.clocks.table.push(VirtualTimer { clock, deadline })?;
+        subscribe(self.clocks.table, timer)
+    }
+}
+
+pub(crate) struct HasVirtualClock;
+
+impl HasData for HasVirtualClock {
+    type Data<'a> = VirtualClockView<'a>;
+}
+
+impl monotonic_clock::Host for VirtualClockView<'_> {
+    fn now(&mut self) -> wasmtime::Result<monotonic_clock::Instant> {
+        latest::Host::now(&mut self.clocks)
+    }
+
+    fn resolution(&mut self) -> wasmtime::Result<monotonic_clock::Duration> {
+        latest::Host::resolution(&mut self.clocks)
+    }
+
+    fn subscribe_instant(
+        &mut self,
+        when: monotonic_clock::Instant,
+    ) -> wasmtime::Result<Resource<DynPollable>> {
+        match self.virtual_clock {
+            Some(clock) => self.subscribe(clock.clone(), Duration::from_nanos(when)),
+            None => latest::Host::subscribe_instant(&mut self.clocks, when),
+        }
+    }
+
+    fn subscribe_duration(
+        &mut self,
+        when: monotonic_clock::Duration,
+    ) -> wasmtime::Result<Resource<DynPollable>> {
+        match self.virtual_clock {
+            Some(clock) => {
+                let deadline = clock.elapsed().saturating_add(Duration::from_nanos(when));
+                self.subscribe(clock.clone(), deadline)
+            }
+            None => latest::Host::subscribe_duration(&mut self.clocks, when),
+        }
+    }
+}
+
+impl HostWallClock for VirtualClock {
+    fn resolution(&self) -> Duration {
+        Duration::from_nanos(1)
+    }
+
+    fn now(&self) -> Duration {
+        self.start + self.elapsed()
+    }
+}
+
+impl HostMonotonicClock for VirtualClock {
+    fn resolution(&self) -> u64 {
+        1
+    }
+
+    fn now(&self) -> u64 {
+        self.elapsed().as_nanos().try_into().unwrap_or(u64::MAX)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn virtual_clock_only_moves_when_advanced() {
+        let start = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        let clock = VirtualClock::new(start);
+        let shared = clock.clone();
+        assert_eq!(start, clock.now());
+        assert_eq!(0, HostMonotonicClock::now(&clock));
+
+        shared.advance(Duration::from_millis(1500));
+        assert_eq!(start + Duration::from_millis(1500), clock.now());
+        assert_eq!(1_500_000_000, HostMonotonicClock::now(&clock));
+        assert_eq!(
+            Duration::from_secs(1_700_000_000) + Duration::from_millis(1500),
+            HostWallClock::now(&clock)
+        );
+    }
+
+    #[test]
+    fn virtual_clock_can_be_frozen_and_resumed() {
+        let clock = VirtualClock::running();
+        assert!(!clock.is_frozen());
+
+        clock.freeze();
+        assert!(clock.is_frozen());
+        let frozen_at = clock.now();
+        std::thread::sleep(Duration::from_millis(10));
+        assert_eq!(frozen_at, clock.now());
+
+        clock.advance(Duration::from_secs(3600));
+        assert_eq!(frozen_at + Duration::from_secs(3600), clock.now());
+
+        clock.resume();
+        std::thread::sleep(Duration::from_millis(10));
+        assert!(clock.now() > frozen_at + Duration::from_secs(3600));
+    }
+
+    #[tokio::test]
+    async fn timers_fire_when_the_clock_advances() {
+        let clock = VirtualClock::new(UNIX_EPOCH);
+        let started = Instant::now();
+        let mut timer = VirtualTimer {
+            clock: clock.clone(),
+            deadline: Duration::from_secs(3600),
+        };
+        let sleep = tokio::spawn(async move { timer.ready().await });
+        tokio::task::yield_now().await;
+        assert!(!sleep.is_finished());
+
+        clock.advance(Duration::from_secs(1800));
+        tokio::task::yield_now().await;
+        assert!(!sleep.is_finished());
+
+        clock.advance(Duration::from_secs(1800));
+        tokio::time::timeout(Duration::from_secs(5), sleep)
+            .await
+            .expect("timer should fire once the clock reaches its deadline")
+            .unwrap();
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+}
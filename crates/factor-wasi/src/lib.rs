@@ -1,20 +1,28 @@
+mod deterministic;
 mod io;
+mod listeners;
 pub mod spin;
+pub mod timezone;
 mod wasi_2023_10_18;
 mod wasi_2023_11_10;
 
 use std::{
+    collections::HashMap,
     future::Future,
     io::{Read, Write},
     net::SocketAddr,
     path::Path,
+    sync::Arc,
 };
 
+use deterministic::{HasVirtualClock, VirtualClockView};
 use io::{PipeReadStream, PipedWriteStream};
+use spin_factors::anyhow::Context as _;
 use spin_factors::{
-    AppComponent, Factor, FactorInstanceBuilder, InitContext, PrepareContext, RuntimeFactors,
-    RuntimeFactorsInstanceState, anyhow,
+    AppComponent, Factor, FactorData, FactorInstanceBuilder, InitContext, PrepareContext,
+    RuntimeFactors, RuntimeFactorsInstanceState, anyhow,
 };
+use spin_locked_app::MetadataKey;
 use wasmtime::component::HasData;
 use wasmtime_wasi::cli::{StdinStream, StdoutStream, WasiCli, WasiCliCtxView};
 use wasmtime_wasi::clocks::{WasiClocks, WasiClocksCtxView};
@@ -23,19 +31,42 @@ use wasmtime_wasi::random::{WasiRandom, WasiRandomCtx};
 use wasmtime_wasi::sockets::{WasiSockets, WasiSocketsCtxView};
 use wasmtime_wasi::{DirPerms, FilePerms, ResourceTable, WasiCtx, WasiCtxBuilder, WasiCtxView};
 
+pub use deterministic::{DeterministicMode, VirtualClock};
+pub use listeners::TcpListenerAddr;
+pub use timezone::Timezone;
 pub use wasmtime_wasi::sockets::SocketAddrUse;
 
+/// Metadata key for the addresses a component may listen on.
+pub const TCP_LISTENERS_KEY: MetadataKey<Vec<String>> = MetadataKey::new("tcp_listeners");
+
 pub struct WasiFactor {
     files_mounter: Box<dyn FilesMounter>,
+    deterministic_mode: Option<DeterministicMode>,
+    virtual_clock: Option<VirtualClock>,
 }
 
 impl WasiFactor {
     pub fn new(files_mounter: impl FilesMounter + 'static) -> Self {
         Self {
             files_mounter: Box::new(files_mounter),
+            deterministic_mode: None,
+            virtual_clock: None,
         }
     }
 
+    /// Runs all instances with seeded randomness and a virtual clock, for
+    /// reproducible tests and debugging.
+    pub fn set_deterministic_mode(&mut self, mode: DeterministicMode) {
+        self.deterministic_mode = Some(mode);
+    }
+
+    /// Runs all instances against the given clock, so that tests can freeze
+    /// and advance the time components see. Deterministic mode, if set, uses
+    /// its own clock instead.
+    pub fn set_virtual_clock(&mut self, clock: VirtualClock) {
+        self.virtual_clock = Some(clock);
+    }
+
     pub fn get_wasi_impl(
         runtime_instance_state: &mut impl RuntimeFactorsInstanceState,
     ) -> Option<WasiCtxView<'_>> {
@@ -90,6 +121,17 @@ trait InitContextExt: InitContext<WasiFactor> {
         state.ctx.random()
     }
 
+    fn get_virtual_clock(data: &mut Self::StoreData) -> VirtualClockView<'_> {
+        let (state, table) = Self::get_data_with_table(data);
+        VirtualClockView {
+            clocks: WasiClocksCtxView {
+                ctx: state.ctx.clocks(),
+                table,
+            },
+            virtual_clock: state.virtual_clock.as_ref(),
+        }
+    }
+
     fn link_clocks_bindings(
         &mut self,
         add_to_linker: fn(
@@ -100,6 +142,16 @@ trait InitContextExt: InitContext<WasiFactor> {
         add_to_linker(self.linker(), Self::get_clocks)
     }
 
+    fn link_virtual_clock_bindings(
+        &mut self,
+        add_to_linker: fn(
+            &mut wasmtime::component::Linker<Self::StoreData>,
+            fn(&mut Self::StoreData) -> VirtualClockView<'_>,
+        ) -> wasmtime::Result<()>,
+    ) -> wasmtime::Result<()> {
+        add_to_linker(self.linker(), Self::get_virtual_clock)
+    }
+
     fn get_cli(data: &mut Self::StoreData) -> WasiCliCtxView<'_> {
         let (state, table) = Self::get_data_with_table(data);
         WasiCliCtxView {
@@ -232,8 +284,8 @@ impl HasData for HasIo {
 }
 
 impl Factor for WasiFactor {
-    type RuntimeConfig = ();
-    type AppState = ();
+    type RuntimeConfig = RuntimeConfig;
+    type AppState = AppState;
     type InstanceBuilder = InstanceBuilder;
 
     fn init(&mut self, ctx: &mut impl InitContext<Self>) -> anyhow::Result<()> {
@@ -243,12 +295,22 @@ impl Factor for WasiFactor {
         ctx.link_clocks_bindings(
             p3::bindings::clocks::system_clock::add_to_linker::<_, WasiClocks>,
         )?;
-        ctx.link_clocks_bindings(
-            p2::bindings::clocks::monotonic_clock::add_to_linker::<_, WasiClocks>,
+        ctx.link_virtual_clock_bindings(
+            deterministic::monotonic_clock::add_to_linker::<_, HasVirtualClock>,
         )?;
         ctx.link_clocks_bindings(
             p3::bindings::clocks::monotonic_clock::add_to_linker::<_, WasiClocks>,
         )?;
+        ctx.link_bindings(|linker, get| {
+            let mut options = timezone::p2::LinkOptions::default();
+            options.clocks_timezone(true);
+            timezone::p2::add_to_linker::<_, FactorData<Self>>(linker, &options, get)
+        })?;
+        ctx.link_bindings(|linker, get| {
+            let mut options = timezone::p3::LinkOptions::default();
+            options.clocks_timezone(true);
+            timezone::p3::add_to_linker::<_, FactorData<Self>>(linker, &options, get)
+        })?;
         ctx.link_filesystem_bindings(
             p2::bindings::filesystem::types::add_to_linker::<_, WasiFilesystem>,
         )?;
@@ -323,9 +385,28 @@ impl Factor for WasiFactor {
 
     fn configure_app<T: RuntimeFactors>(
         &self,
-        _ctx: spin_factors::ConfigureAppContext<T, Self>,
+        mut ctx: spin_factors::ConfigureAppContext<T, Self>,
     ) -> anyhow::Result<Self::AppState> {
-        Ok(())
+        let timezone = ctx.take_runtime_config().unwrap_or_default().timezone;
+        let mut component_tcp_listeners = HashMap::new();
+        for component in ctx.app().components() {
+            let listeners = component
+                .get_metadata(TCP_LISTENERS_KEY)?
+                .unwrap_or_default()
+                .iter()
+                .map(|addr| TcpListenerAddr::parse(addr))
+                .collect::<anyhow::Result<Arc<[_]>>>()
+                .with_context(|| {
+                    format!("invalid tcp_listeners for component {:?}", component.id())
+                })?;
+            component_tcp_listeners.insert(component.id().to_string(), listeners);
+        }
+        Ok(AppState {
+            component_tcp_listeners,
+            timezone,
+            deterministic_mode: self.deterministic_mode.clone(),
+            virtual_clock: self.virtual_clock.clone(),
+        })
     }
 
     fn prepare<T: RuntimeFactors>(
@@ -333,13 +414,41 @@ impl Factor for WasiFactor {
         ctx: PrepareContext<T, Self>,
     ) -> anyhow::Result<InstanceBuilder> {
         let mut wasi_ctx = WasiCtxBuilder::new();
+        if let Some(mode) = &ctx.app_state().deterministic_mode {
+            mode.apply(&mut wasi_ctx);
+        } else if let Some(clock) = &ctx.app_state().virtual_clock {
+            wasi_ctx.wall_clock(clock.clone());
+            wasi_ctx.monotonic_clock(clock.clone());
+        }
 
         // Mount files
         let mount_ctx = MountFilesContext { ctx: &mut wasi_ctx };
         self.files_mounter
             .mount_files(ctx.app_component(), mount_ctx)?;
 
-        let mut builder = InstanceBuilder { ctx: wasi_ctx };
+        let tcp_listeners = ctx
+            .app_state()
+            .component_tcp_listeners
+            .get(ctx.app_component().id())
+            .cloned()
+            .unwrap_or_else(|| Arc::new([]));
+        if !tcp_listeners.is_empty() {
+            // Without outbound networking, the only socket use allowed is
+            // listening on the declared addresses.
+            let tcp_listeners = tcp_listeners.clone();
+            wasi_ctx.socket_addr_check(move |addr, addr_use| {
+                let allowed = matches!(addr_use, SocketAddrUse::TcpBind)
+                    && tcp_listeners.iter().any(|listener| listener.allows(&addr));
+                Box::pin(async move { allowed })
+            });
+        }
+
+        let mut builder = InstanceBuilder {
+            ctx: wasi_ctx,
+            tcp_listeners,
+            timezone: ctx.app_state().timezone.clone(),
+            virtual_clock: ctx.app_state().virtual_clock().cloned(),
+        };
 
         // Apply environment variables
         builder.env(ctx.app_component().environment());
@@ -394,8 +503,38 @@ impl MountFilesContext<'_> {
     }
 }
 
+/// The runtime configuration for the WASI factor.
+#[derive(Default)]
+pub struct RuntimeConfig {
+    /// The timezone in which components observe local time.
+    pub timezone: Timezone,
+}
+
+pub struct AppState {
+    /// The addresses each component may listen on, by component ID.
+    component_tcp_listeners: HashMap<String, Arc<[TcpListenerAddr]>>,
+    /// The timezone in which components observe local time.
+    timezone: Timezone,
+    deterministic_mode: Option<DeterministicMode>,
+    virtual_clock: Option<VirtualClock>,
+}
+
+impl AppState {
+    /// Returns the virtual clock if the app runs in deterministic mode or
+    /// with a virtual clock.
+    pub fn virtual_clock(&self) -> Option<&VirtualClock> {
+        self.deterministic_mode
+            .as_ref()
+            .map(DeterministicMode::clock)
+            .or(self.virtual_clock.as_ref())
+    }
+}
+
 pub struct InstanceBuilder {
     ctx: WasiCtxBuilder,
+    tcp_listeners: Arc<[TcpListenerAddr]>,
+    timezone: Timezone,
+    virtual_clock: Option<VirtualClock>,
 }
 
 impl InstanceBuilder {
@@ -466,24 +605,37 @@ impl FactorInstanceBuilder for InstanceBuilder {
     type InstanceState = InstanceState;
 
     fn build(self) -> anyhow::Result<Self::InstanceState> {
-        let InstanceBuilder { ctx: mut wasi_ctx } = self;
+        let InstanceBuilder {
+            ctx: mut wasi_ctx,
+            timezone,
+            virtual_clock,
+            ..
+        } = self;
         Ok(InstanceState {
             ctx: wasi_ctx.build(),
+            timezone,
+            virtual_clock,
         })
     }
 }
 
 impl InstanceBuilder {
+    /// Sets the check for outbound socket uses. Binding a TCP socket is
+    /// allowed only on the component's declared `tcp_listeners`.
     pub fn outbound_socket_addr_check<F, Fut>(&mut self, check: F)
     where
         F: Fn(SocketAddr, SocketAddrUse) -> Fut + Send + Sync + Clone + 'static,
         Fut: Future<Output = bool> + Send + Sync,
     {
+        let tcp_listeners = self.tcp_listeners.clone();
         self.ctx.socket_addr_check(move |addr, addr_use| {
             let check = check.clone();
+            let tcp_listeners = tcp_listeners.clone();
             Box::pin(async move {
                 match addr_use {
-                    SocketAddrUse::TcpBind => false,
+                    SocketAddrUse::TcpBind => {
+                        tcp_listeners.iter().any(|listener| listener.allows(&addr))
+                    }
                     SocketAddrUse::TcpConnect
                     | SocketAddrUse::UdpBind
                     | SocketAddrUse::UdpConnect
@@ -496,4 +648,6 @@ impl InstanceBuilder {
 
 pub struct InstanceState {
     ctx: WasiCtx,
+    timezone: Timezone,
+    virtual_clock: Option<VirtualClock>,
 }
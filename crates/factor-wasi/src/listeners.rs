@@ -0,0 +1,71 @@
+use std::net::{IpAddr, SocketAddr};
+
+use spin_factors::anyhow::{self, Context as _};
+
+/// A local address on which a component may listen for TCP connections, as
+/// declared in its `tcp_listeners`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TcpListenerAddr {
+    /// The IP address to listen on, or `None` for any local address.
+    ip: Option<IpAddr>,
+    port: u16,
+}
+
+impl TcpListenerAddr {
+    /// Parses an "(ip):(port)" listener address, where the IP may be "*".
+    pub fn parse(addr: &str) -> anyhow::Result<Self> {
+        let (ip, port) = addr
+            .rsplit_once(':')
+            .with_context(|| format!("TCP listener {addr:?} must be in the form (ip):(port)"))?;
+        let port = port
+            .parse()
+            .with_context(|| format!("TCP listener {addr:?} has an invalid port"))?;
+        let ip = match ip {
+            "*" => None,
+            ip => {
+                // IPv6 addresses are bracketed, as in URLs.
+                let ip = ip
+                    .strip_prefix('[')
+                    .and_then(|ip| ip.strip_suffix(']'))
+                    .unwrap_or(ip);
+                Some(
+                    ip.parse()
+                        .with_context(|| format!("TCP listener {addr:?} has an invalid IP"))?,
+                )
+            }
+        };
+        Ok(Self { ip, port })
+    }
+
+    /// Returns whether binding to `addr` is allowed by this listener.
+    pub fn allows(&self, addr: &SocketAddr) -> bool {
+        addr.port() == self.port && self.ip.is_none_or(|ip| ip == addr.ip())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_listener_addrs() {
+        let any = TcpListenerAddr::parse("*:6379").unwrap();
+        assert!(any.allows(&"0.0.0.0:6379".parse().unwrap()));
+        assert!(any.allows(&"[::1]:6379".parse().unwrap()));
+        assert!(!any.allows(&"0.0.0.0:6380".parse().unwrap()));
+
+        let loopback = TcpListenerAddr::parse("127.0.0.1:9000").unwrap();
+        assert!(loopback.allows(&"127.0.0.1:9000".parse().unwrap()));
+        assert!(!loopback.allows(&"0.0.0.0:9000".parse().unwrap()));
+
+        let v6 = TcpListenerAddr::parse("[::1]:9000").unwrap();
+        assert!(v6.allows(&"[::1]:9000".parse().unwrap()));
+    }
+
+    #[test]
+    fn rejects_invalid_listener_addrs() {
+        for addr in ["6379", "*:", "*:http", "*:70000", "localhost:80"] {
+            assert!(TcpListenerAddr::parse(addr).is_err(), "{addr:?}");
+        }
+    }
+}
@@ -1,9 +1,10 @@
 use std::path::PathBuf;
 
 use spin_common::{ui::quoted_path, url::parse_file_url};
-use spin_factors::anyhow::{Context, ensure};
+use spin_factors::anyhow::{self, Context, ensure};
+use spin_factors::runtime_config::toml::GetTomlValue;
 
-use crate::FilesMounter;
+use crate::{FilesMounter, RuntimeConfig, Timezone};
 
 pub struct SpinFilesMounter {
     working_dir: PathBuf,
@@ -46,3 +47,19 @@ impl FilesMounter for SpinFilesMounter {
         Ok(())
     }
 }
+
+/// Resolves the WASI runtime config from the top-level `timezone` key, e.g.
+/// `timezone = "Europe/Berlin"`.
+pub fn runtime_config_from_toml(
+    table: &impl GetTomlValue,
+) -> anyhow::Result<Option<RuntimeConfig>> {
+    let Some(timezone) = table.get("timezone") else {
+        return Ok(None);
+    };
+    let timezone = timezone
+        .as_str()
+        .context("timezone must be a string such as \"Europe/Berlin\"")?;
+    Ok(Some(RuntimeConfig {
+        timezone: Timezone::new(timezone)?,
+    }))
+}
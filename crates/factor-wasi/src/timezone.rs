@@ -0,0 +1,160 @@
+//! Host implementations of the unstable `wasi:clocks/timezone` interfaces.
+
+use jiff::Timestamp;
+use jiff::tz::TimeZone;
+use spin_factors::anyhow::{self, Context as _};
+
+use crate::InstanceState;
+
+mod bindings {
+    wasmtime::component::bindgen!({
+        inline: r#"
+        package spin:timezone;
+        world timezone {
+            import wasi:clocks/timezone@0.2.6;
+            import wasi:clocks/timezone@0.3.0-rc-2026-03-15;
+        }
+        "#,
+        path: "../../wit",
+    });
+}
+
+pub use bindings::wasi::clocks0_2_6::timezone as p2;
+pub use bindings::wasi::clocks0_3_0_rc_2026_03_15::timezone as p3;
+
+/// The timezone in which guests observe local time.
+///
+/// The default is UTC, reported to guests as "no timezone" where the
+/// interface allows it.
+#[derive(Clone, Debug, Default)]
+pub struct Timezone(Option<TimeZone>);
+
+impl Timezone {
+    /// Looks up a timezone by IANA identifier, e.g. "Europe/Berlin".
+    pub fn new(name: &str) -> anyhow::Result<Self> {
+        let tz = TimeZone::get(name).with_context(|| format!("unknown timezone {name:?}"))?;
+        Ok(Self(Some(tz)))
+    }
+
+    /// Returns the IANA identifier of the timezone, or `None` for the default.
+    pub fn iana_name(&self) -> Option<&str> {
+        self.0.as_ref()?.iana_name()
+    }
+
+    /// Returns the offset from UTC in seconds, the abbreviated name, and
+    /// whether daylight saving time is in effect at the given time.
+    fn display(&self, when: Timestamp) -> (i32, String, bool) {
+        match &self.0 {
+            Some(tz) => {
+                let info = tz.to_offset_info(when);
+                (
+                    info.offset().seconds(),
+                    info.abbreviation().to_owned(),
+                    info.dst().is_dst(),
+                )
+            }
+            None => (0, "UTC".to_owned(), false),
+        }
+    }
+}
+
+impl From<p2::Datetime> for Timestamp {
+    fn from(datetime: p2::Datetime) -> Self {
+        to_timestamp(
+            datetime.seconds.try_into().unwrap_or(i64::MAX),
+            datetime.nanoseconds,
+        )
+    }
+}
+
+impl From<p3::Instant> for Timestamp {
+    fn from(instant: p3::Instant) -> Self {
+        to_timestamp(instant.seconds, instant.nanoseconds)
+    }
+}
+
+/// Converts a guest wall-clock time to a timestamp, saturating at the limits
+/// of the timezone database.
+fn to_timestamp(seconds: i64, nanoseconds: u32) -> Timestamp {
+    let seconds = seconds.clamp(Timestamp::MIN.as_second(), Timestamp::MAX.as_second());
+    let nanoseconds = nanoseconds.min(999_999_999) as i32;
+    Timestamp::new(seconds, nanoseconds).unwrap_or(if seconds < 0 {
+        Timestamp::MIN
+    } else {
+        Timestamp::MAX
+    })
+}
+
+impl p2::Host for InstanceState {
+    fn display(&mut self, when: p2::Datetime) -> p2::TimezoneDisplay {
+        let (utc_offset, name, in_daylight_saving_time) = self.timezone.display(when.into());
+        p2::TimezoneDisplay {
+            utc_offset,
+            name,
+            in_daylight_saving_time,
+        }
+    }
+
+    fn utc_offset(&mut self, when: p2::Datetime) -> i32 {
+        self.timezone.display(when.into()).0
+    }
+}
+
+impl p3::Host for InstanceState {
+    fn iana_id(&mut self) -> Option<String> {
+        self.timezone.iana_name().map(ToOwned::to_owned)
+    }
+
+    fn utc_offset(&mut self, when: p3::Instant) -> Option<i64> {
+        self.timezone.0.as_ref()?;
+        let (offset_seconds, _, _) = self.timezone.display(when.into());
+        Some(i64::from(offset_seconds) * 1_000_000_000)
+    }
+
+    fn to_debug_string(&mut self) -> String {
+        self.timezone.iana_name().unwrap_or("UTC").to_owned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reports_offset_and_dst() {
+        let tz = Timezone::new("Europe/Berlin").unwrap();
+        // 2024-01-15T12:00:00Z, winter
+        let (offset, name, dst) = tz.display(to_timestamp(1_705_320_000, 0));
+        assert_eq!((3600, "CET", false), (offset, name.as_str(), dst));
+        // 2024-07-15T12:00:00Z, summer
+        let (offset, name, dst) = tz.display(to_timestamp(1_721_044_800, 0));
+        assert_eq!((7200, "CEST", true), (offset, name.as_str(), dst));
+    }
+
+    #[test]
+    fn defaults_to_utc() {
+        let tz = Timezone::default();
+        assert_eq!(None, tz.iana_name());
+        assert_eq!(
+            (0, "UTC".to_owned(), false),
+            tz.display(to_timestamp(1_705_320_000, 0))
+        );
+    }
+
+    #[test]
+    fn rejects_unknown_timezones() {
+        assert!(Timezone::new("Mars/Olympus_Mons").is_err());
+    }
+
+    #[test]
+    fn saturates_out_of_range_times() {
+        assert_eq!(
+            Timestamp::MAX.as_second(),
+            to_timestamp(i64::MAX, 0).as_second()
+        );
+        assert_eq!(
+            Timestamp::MIN.as_second(),
+            to_timestamp(i64::MIN, 0).as_second()
+        );
+    }
+}
@@ -1,7 +1,10 @@
-use spin_factor_wasi::{DummyFilesMounter, WasiFactor};
+use spin_factor_wasi::timezone::{p2 as timezone_p2, p3 as timezone_p3};
+use spin_factor_wasi::{DummyFilesMounter, RuntimeConfig, Timezone, WasiFactor};
 use spin_factors::{RuntimeFactors, anyhow};
 use spin_factors_test::{TestEnvironment, toml};
 use wasmtime_wasi::p2::bindings::cli::environment::Host;
+use wasmtime_wasi::p2::bindings::sockets::instance_network::Host as _;
+use wasmtime_wasi::sockets::SocketAddrUse;
 
 #[derive(RuntimeFactors)]
 struct TestFactors {
@@ -28,3 +31,105 @@ async fn environment_works() -> anyhow::Result<()> {
     assert_eq!(val.as_deref(), Some("bar"));
     Ok(())
 }
+
+#[tokio::test]
+async fn tcp_listeners_allow_bind() -> anyhow::Result<()> {
+    let factors = TestFactors {
+        wasi: WasiFactor::new(DummyFilesMounter),
+    };
+    let env = TestEnvironment::new(factors).extend_manifest(toml! {
+        [component.test-component]
+        source = "does-not-exist.wasm"
+        tcp_listeners = ["*:6379", "127.0.0.1:9000"]
+    });
+    let mut state = env.build_instance_state().await?;
+    let mut sockets = WasiFactor::get_sockets_impl(&mut state).unwrap();
+
+    let network_resource = sockets.instance_network()?;
+    let network = sockets.table.get(&network_resource)?;
+
+    for allowed in ["0.0.0.0:6379", "127.0.0.1:9000"] {
+        network
+            .check_socket_addr(allowed.parse().unwrap(), SocketAddrUse::TcpBind)
+            .await?;
+    }
+    for (not_allowed, addr_use) in [
+        ("0.0.0.0:9000", SocketAddrUse::TcpBind),
+        ("0.0.0.0:6380", SocketAddrUse::TcpBind),
+        ("127.0.0.1:6379", SocketAddrUse::TcpConnect),
+    ] {
+        assert_eq!(
+            network
+                .check_socket_addr(not_allowed.parse().unwrap(), addr_use)
+                .await
+                .unwrap_err()
+                .kind(),
+            std::io::ErrorKind::PermissionDenied
+        );
+    }
+    Ok(())
+}
+
+#[tokio::test]
+async fn invalid_tcp_listeners_are_rejected() -> anyhow::Result<()> {
+    let factors = TestFactors {
+        wasi: WasiFactor::new(DummyFilesMounter),
+    };
+    let env = TestEnvironment::new(factors).extend_manifest(toml! {
+        [component.test-component]
+        source = "does-not-exist.wasm"
+        tcp_listeners = ["6379"]
+    });
+    let Err(err) = env.build_instance_state().await else {
+        anyhow::bail!("expected instance build to fail but it didn't");
+    };
+    assert!(format!("{err:#}").contains("invalid tcp_listeners"));
+    Ok(())
+}
+
+#[tokio::test]
+async fn timezone_is_configurable() -> anyhow::Result<()> {
+    let factors = TestFactors {
+        wasi: WasiFactor::new(DummyFilesMounter),
+    };
+    let env = TestEnvironment::new(factors).runtime_config(TestFactorsRuntimeConfig {
+        wasi: Some(RuntimeConfig {
+            timezone: Timezone::new("America/New_York")?,
+        }),
+    })?;
+    let mut state = env.build_instance_state().await?;
+
+    // 2024-01-15T12:00:00Z
+    let display = timezone_p2::Host::display(
+        &mut state.wasi,
+        timezone_p2::Datetime {
+            seconds: 1_705_320_000,
+            nanoseconds: 0,
+        },
+    );
+    assert_eq!(-5 * 3600, display.utc_offset);
+    assert_eq!("EST", display.name);
+    assert!(!display.in_daylight_saving_time);
+    assert_eq!(
+        Some("America/New_York".to_owned()),
+        timezone_p3::Host::iana_id(&mut state.wasi)
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn timezone_defaults_to_utc() -> anyhow::Result<()> {
+    let factors = TestFactors {
+        wasi: WasiFactor::new(DummyFilesMounter),
+    };
+    let env = TestEnvironment::new(factors);
+    let mut state = env.build_instance_state().await?;
+
+    let when = timezone_p3::Instant {
+        seconds: 1_705_320_000,
+        nanoseconds: 0,
+    };
+    assert_eq!(None, timezone_p3::Host::utc_offset(&mut state.wasi, when));
+    assert_eq!(None, timezone_p3::Host::iana_id(&mut state.wasi));
+    Ok(())
+}
@@ -0,0 +1,165 @@
+use std::{
+    collections::HashSet,
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
+
+use spin_factor_capability_policy::{CapabilityChecker, operation};
+use spin_factors::anyhow::{self, Context as _};
+use spin_world::spin::workflows::client::{self, Error, InstanceStatus};
+use spin_world::spin::workflows::context;
+use tracing::instrument;
+
+use crate::journal::{Completed, Replay, Waiting};
+use crate::store::{Status, WorkflowStore, millis_since_epoch};
+
+/// The workflows state of a component instance, and the host implementation
+/// of `spin:workflows/client` and `spin:workflows/context`.
+pub struct InstanceState {
+    store: Arc<WorkflowStore>,
+    allowed_workflows: Arc<HashSet<String>>,
+    capabilities: CapabilityChecker,
+    replay: Option<Replay>,
+}
+
+impl InstanceState {
+    pub fn new(
+        store: Arc<WorkflowStore>,
+        allowed_workflows: Arc<HashSet<String>>,
+        capabilities: CapabilityChecker,
+        replay: Option<Replay>,
+    ) -> Self {
+        Self {
+            store,
+            allowed_workflows,
+            capabilities,
+            replay,
+        }
+    }
+
+    /// The app's workflow store.
+    pub fn store(&self) -> &WorkflowStore {
+        &self.store
+    }
+
+    /// Returns the names of the workflows the component may use.
+    pub fn allowed_workflows(&self) -> &HashSet<String> {
+        &self.allowed_workflows
+    }
+
+    /// The replay of the workflow instance the component is orchestrating,
+    /// if it is run as an orchestrator.
+    pub fn replay(&self) -> Option<&Replay> {
+        self.replay.as_ref()
+    }
+
+    fn replay_mut(&mut self) -> anyhow::Result<&mut Replay> {
+        self.replay
+            .as_mut()
+            .context("spin:workflows/context may only be used by a workflow orchestrator")
+    }
+
+    /// Returns an error unless the component may call `method` on the
+    /// workflow.
+    fn check_workflow(&self, workflow: &str, method: &str) -> Result<(), Error> {
+        if !self.allowed_workflows.contains(workflow) {
+            return Err(Error::AccessDenied);
+        }
+        self.capabilities
+            .check(operation::WORKFLOWS, workflow, Some(method))
+            .map_err(|_| Error::AccessDenied)
+    }
+
+    /// Returns an error unless the component may call `method` on the
+    /// instance's workflow.
+    async fn check_instance(&self, instance: &str, method: &str) -> Result<Status, Error> {
+        let instance = self
+            .store
+            .get(instance)
+            .await
+            .map_err(other_error)?
+            .ok_or(Error::NoSuchInstance)?;
+        self.check_workflow(&instance.workflow, method)?;
+        Ok(instance.status)
+    }
+}
+
+fn other_error(err: anyhow::Error) -> Error {
+    Error::Other(format!("{err:#}"))
+}
+
+impl client::Host for InstanceState {
+    #[instrument(name = "spin_workflows.start", skip(self, input), err, fields(otel.kind = "producer"))]
+    async fn start(&mut self, workflow: String, input: Vec<u8>) -> Result<String, Error> {
+        self.check_workflow(&workflow, "start")?;
+        self.store
+            .start(&workflow, input)
+            .await
+            .map_err(other_error)
+    }
+
+    #[instrument(name = "spin_workflows.raise_event", skip(self, payload), err, fields(otel.kind = "producer"))]
+    async fn raise_event(
+        &mut self,
+        instance: String,
+        event: String,
+        payload: Vec<u8>,
+    ) -> Result<(), Error> {
+        self.check_instance(&instance, "raise_event").await?;
+        self.store
+            .raise_event(&instance, &event, payload)
+            .await
+            .map_err(other_error)
+    }
+
+    #[instrument(name = "spin_workflows.status", skip(self), err, fields(otel.kind = "client"))]
+    async fn status(&mut self, instance: String) -> Result<InstanceStatus, Error> {
+        Ok(match self.check_instance(&instance, "status").await? {
+            Status::Running => InstanceStatus::Running,
+            Status::Completed { output } => InstanceStatus::Completed(output),
+            Status::Failed { error } => InstanceStatus::Failed(error),
+        })
+    }
+
+    fn convert_error(&mut self, err: Error) -> anyhow::Result<Error> {
+        Ok(err)
+    }
+}
+
+impl context::Host for InstanceState {
+    async fn instance_id(&mut self) -> anyhow::Result<String> {
+        Ok(self.replay_mut()?.instance_id().to_owned())
+    }
+
+    async fn call_activity(
+        &mut self,
+        activity: String,
+        input: Vec<u8>,
+    ) -> anyhow::Result<Result<Vec<u8>, String>> {
+        match self
+            .replay_mut()?
+            .next(Waiting::Activity { activity, input })?
+        {
+            Completed::Activity { result, .. } => Ok(result),
+            completed => unreachable!("activity completed by {completed}"),
+        }
+    }
+
+    async fn sleep(&mut self, ms: u64) -> anyhow::Result<()> {
+        let fire_at = SystemTime::now()
+            .checked_add(Duration::from_millis(ms))
+            .context("the sleep is too long")?;
+        self.replay_mut()?.next(Waiting::Timer {
+            duration_ms: ms,
+            fire_at_ms: millis_since_epoch(fire_at),
+        })?;
+        Ok(())
+    }
+
+    async fn wait_for_event(&mut self, event: String) -> anyhow::Result<Vec<u8>> {
+        match self.replay_mut()?.next(Waiting::Event { event })? {
+            Completed::Event { payload, .. } => Ok(payload),
+            completed => unreachable!("event completed by {completed}"),
+        }
+    }
+}
@@ -0,0 +1,192 @@
+use std::fmt;
+
+use anyhow::bail;
+use serde::{Deserialize, Serialize};
+
+use crate::store::WorkflowInstance;
+
+/// A durable operation an orchestrator has completed, with its result.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+pub enum Completed {
+    /// An activity was run, returning an output or error.
+    Activity {
+        activity: String,
+        result: Result<Vec<u8>, String>,
+    },
+    /// A timer fired.
+    Timer { duration_ms: u64 },
+    /// An event was delivered.
+    Event { event: String, payload: Vec<u8> },
+}
+
+/// A durable operation an orchestrator is waiting on.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+pub enum Waiting {
+    /// An activity is to be run with an input.
+    Activity { activity: String, input: Vec<u8> },
+    /// A timer is to fire at a time, in milliseconds since the Unix epoch.
+    Timer { duration_ms: u64, fire_at_ms: u64 },
+    /// An event is to be raised.
+    Event { event: String },
+}
+
+impl Waiting {
+    /// Returns true if `completed` is the result of this operation.
+    fn is_completed_by(&self, completed: &Completed) -> bool {
+        match (self, completed) {
+            (Waiting::Activity { activity, .. }, Completed::Activity { activity: a, .. }) => {
+                activity == a
+            }
+            (Waiting::Timer { duration_ms, .. }, Completed::Timer { duration_ms: d }) => {
+                duration_ms == d
+            }
+            (Waiting::Event { event }, Completed::Event { event: e, .. }) => event == e,
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Display for Waiting {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Waiting::Activity { activity, .. } => write!(f, "activity {activity:?}"),
+            Waiting::Timer { duration_ms, .. } => write!(f, "a {duration_ms}ms timer"),
+            Waiting::Event { event } => write!(f, "event {event:?}"),
+        }
+    }
+}
+
+impl fmt::Display for Completed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Completed::Activity { activity, .. } => write!(f, "activity {activity:?}"),
+            Completed::Timer { duration_ms } => write!(f, "a {duration_ms}ms timer"),
+            Completed::Event { event, .. } => write!(f, "event {event:?}"),
+        }
+    }
+}
+
+/// The trap with which an orchestrator is stopped when it waits on an
+/// operation which has not completed.
+#[derive(Debug)]
+pub struct Suspended;
+
+impl fmt::Display for Suspended {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("workflow suspended until its operation completes")
+    }
+}
+
+impl std::error::Error for Suspended {}
+
+/// Replays a workflow instance's journal to a run of its orchestrator.
+#[derive(Debug)]
+pub struct Replay {
+    instance_id: String,
+    history: Vec<Completed>,
+    next: usize,
+    waiting: Option<Waiting>,
+}
+
+impl Replay {
+    pub fn new(instance: &WorkflowInstance) -> Self {
+        Self {
+            instance_id: instance.id.clone(),
+            history: instance.history.clone(),
+            next: 0,
+            waiting: None,
+        }
+    }
+
+    /// The ID of the workflow instance being replayed.
+    pub fn instance_id(&self) -> &str {
+        &self.instance_id
+    }
+
+    /// The operation the orchestrator stopped to wait on, if it did.
+    pub fn waiting(&self) -> Option<&Waiting> {
+        self.waiting.as_ref()
+    }
+
+    /// Returns the journaled result of the orchestrator's next operation.
+    ///
+    /// If the operation has not completed, records that the orchestrator is
+    /// waiting on it and returns [`Suspended`], which should be returned as a
+    /// trap to stop the orchestrator. Errors if the operation is not the one
+    /// the journal recorded at this point.
+    pub fn next(&mut self, operation: Waiting) -> anyhow::Result<Completed> {
+        if let Some(waiting) = &self.waiting {
+            bail!("workflow continued after waiting on {waiting}");
+        }
+        let Some(completed) = self.history.get(self.next) else {
+            self.waiting = Some(operation);
+            return Err(Suspended.into());
+        };
+        if !operation.is_completed_by(completed) {
+            bail!(
+                "workflow is not deterministic: operation {} was {completed} when first run, but is now {operation}",
+                self.next + 1
+            );
+        }
+        self.next += 1;
+        Ok(completed.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::Status;
+
+    fn instance(history: Vec<Completed>) -> WorkflowInstance {
+        WorkflowInstance {
+            id: "1".into(),
+            workflow: "process-order".into(),
+            input: vec![],
+            status: Status::Running,
+            history,
+            waiting: None,
+            started_at_ms: 0,
+            trace_context: Default::default(),
+        }
+    }
+
+    fn activity(name: &str) -> Waiting {
+        Waiting::Activity {
+            activity: name.into(),
+            input: vec![],
+        }
+    }
+
+    #[test]
+    fn replays_journal_then_suspends() {
+        let charged = Completed::Activity {
+            activity: "charge".into(),
+            result: Ok(b"receipt".to_vec()),
+        };
+        let mut replay = Replay::new(&instance(vec![charged.clone()]));
+
+        assert_eq!(charged, replay.next(activity("charge")).unwrap());
+        assert!(replay.waiting().is_none());
+
+        let err = replay.next(activity("ship")).unwrap_err();
+        assert!(err.is::<Suspended>());
+        assert_eq!(Some(&activity("ship")), replay.waiting());
+        assert!(replay.next(activity("notify")).is_err());
+    }
+
+    #[test]
+    fn rejects_nondeterministic_operations() {
+        let mut replay = Replay::new(&instance(vec![Completed::Timer { duration_ms: 1000 }]));
+        let err = replay
+            .next(Waiting::Event {
+                event: "approved".into(),
+            })
+            .unwrap_err();
+        assert!(!err.is::<Suspended>());
+        assert!(err.to_string().contains("not deterministic"));
+        assert!(replay.waiting().is_none());
+    }
+}
@@ -0,0 +1,187 @@
+//! A factor providing the `spin:workflows` client and context interfaces.
+//!
+//! Components start durable workflow instances by name, and the workflow
+//! trigger runs them with the orchestrator components which handle them. An
+//! orchestrator awaits activities, timers and events through
+//! `spin:workflows/context`, and the results of these are journaled with the
+//! instance; each time the instance makes progress its orchestrator is run
+//! again from the start, replaying the journal, so a workflow resumes where
+//! it left off after a restart.
+//!
+//! Instances are held in a key-value store of their own, "spin-workflows"
+//! unless the runtime config names another, so they are backed by whatever
+//! the store is (e.g. a local SQLite file or Redis). No component may open
+//! that store, so components can only reach instances through the interfaces.
+
+mod host;
+mod journal;
+pub mod runtime_config;
+mod store;
+
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+};
+
+use anyhow::ensure;
+use spin_factor_capability_policy::CapabilityChecker;
+use spin_factor_key_value::{KEY_VALUE_STORES_KEY, KeyValueFactor};
+use spin_factors::{
+    ConfigureAppContext, Factor, FactorData, FactorInstanceBuilder, InitContext, PrepareContext,
+    RuntimeFactors,
+};
+use spin_locked_app::MetadataKey;
+
+pub use host::InstanceState;
+pub use journal::{Completed, Replay, Suspended, Waiting};
+pub use runtime_config::RuntimeConfig;
+pub use spin_world::spin::workflows::client as v3;
+pub use store::{RaisedEvent, Status, WorkflowInstance, WorkflowStore, millis_since_epoch};
+
+/// Metadata key for the workflows a component may use.
+pub const WORKFLOWS_KEY: MetadataKey<Vec<String>> = MetadataKey::new("workflows");
+
+/// The label of the store holding workflows if the runtime config names none.
+pub const DEFAULT_KEY_VALUE_STORE: &str = "spin-workflows";
+
+/// A factor that provides durable workflows.
+#[derive(Default)]
+pub struct WorkflowsFactor {
+    _priv: (),
+}
+
+impl WorkflowsFactor {
+    /// Create a new WorkflowsFactor.
+    pub fn new() -> Self {
+        Self { _priv: () }
+    }
+}
+
+impl Factor for WorkflowsFactor {
+    type RuntimeConfig = RuntimeConfig;
+    type AppState = AppState;
+    type InstanceBuilder = InstanceBuilder;
+
+    fn init(&mut self, ctx: &mut impl InitContext<Self>) -> anyhow::Result<()> {
+        ctx.link_bindings(
+            spin_world::spin::workflows::client::add_to_linker::<_, FactorData<Self>>,
+        )?;
+        ctx.link_bindings(
+            spin_world::spin::workflows::context::add_to_linker::<_, FactorData<Self>>,
+        )?;
+        Ok(())
+    }
+
+    fn configure_app<T: RuntimeFactors>(
+        &self,
+        mut ctx: ConfigureAppContext<T, Self>,
+    ) -> anyhow::Result<Self::AppState> {
+        let label = ctx
+            .take_runtime_config()
+            .map(|config| config.key_value_store)
+            .unwrap_or_else(|| DEFAULT_KEY_VALUE_STORE.to_owned());
+        let store_manager = ctx.app_state::<KeyValueFactor>()?.store_manager();
+        let store = Arc::new(WorkflowStore::new(store_manager, label));
+
+        // Build component -> allowed workflows map
+        let mut component_workflows = HashMap::new();
+        for component in ctx.app().components() {
+            let component_id = component.id().to_string();
+            let workflows = component
+                .get_metadata(WORKFLOWS_KEY)?
+                .unwrap_or_default()
+                .into_iter()
+                .collect::<HashSet<_>>();
+            let key_value_stores = component
+                .get_metadata(KEY_VALUE_STORES_KEY)?
+                .unwrap_or_default();
+            ensure!(
+                !key_value_stores.iter().any(|label| label == store.label()),
+                "component {component_id:?} uses the key-value store {:?}, which holds workflows and can't be opened by components",
+                store.label()
+            );
+            ensure!(
+                workflows.is_empty() || store.is_defined(),
+                "component {component_id:?} uses workflows, but the key-value store {:?} for workflows is not defined",
+                store.label()
+            );
+            component_workflows.insert(component_id, Arc::new(workflows));
+        }
+
+        Ok(AppState {
+            store,
+            component_workflows,
+        })
+    }
+
+    fn prepare<T: RuntimeFactors>(
+        &self,
+        mut ctx: PrepareContext<T, Self>,
+    ) -> anyhow::Result<InstanceBuilder> {
+        let app_state = ctx.app_state();
+        let allowed_workflows = app_state
+            .component_workflows
+            .get(ctx.app_component().id())
+            .expect("component should be in component_workflows")
+            .clone();
+        let store = app_state.store.clone();
+        let capabilities = CapabilityChecker::from_prepare_context(&mut ctx)?;
+        Ok(InstanceBuilder {
+            store,
+            allowed_workflows,
+            capabilities,
+            replay: None,
+        })
+    }
+}
+
+pub struct AppState {
+    /// The app's workflow store.
+    store: Arc<WorkflowStore>,
+    /// The workflows each component is allowed to use.
+    ///
+    /// This is a map from component ID to the set of workflow names.
+    component_workflows: HashMap<String, Arc<HashSet<String>>>,
+}
+
+impl AppState {
+    /// The app's workflow store.
+    pub fn store(&self) -> Arc<WorkflowStore> {
+        self.store.clone()
+    }
+
+    /// Returns true if any component may use workflows.
+    pub fn is_used(&self) -> bool {
+        self.component_workflows
+            .values()
+            .any(|workflows| !workflows.is_empty())
+    }
+}
+
+pub struct InstanceBuilder {
+    store: Arc<WorkflowStore>,
+    allowed_workflows: Arc<HashSet<String>>,
+    capabilities: CapabilityChecker,
+    replay: Option<Replay>,
+}
+
+impl InstanceBuilder {
+    /// Sets up the instance to orchestrate a workflow instance, replaying its
+    /// journal.
+    pub fn orchestrate(&mut self, instance: &WorkflowInstance) {
+        self.replay = Some(Replay::new(instance));
+    }
+}
+
+impl FactorInstanceBuilder for InstanceBuilder {
+    type InstanceState = InstanceState;
+
+    fn build(self) -> anyhow::Result<Self::InstanceState> {
+        Ok(InstanceState::new(
+            self.store,
+            self.allowed_workflows,
+            self.capabilities,
+            self.replay,
+        ))
+    }
+}
@@ -0,0 +1,25 @@
+use serde::Deserialize;
+use spin_factors::runtime_config::toml::GetTomlValue;
+
+/// Runtime configuration for workflows.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RuntimeConfig {
+    /// The label of the key-value store holding workflow instances and their
+    /// journals. No component may list it in `key_value_stores`.
+    pub key_value_store: String,
+}
+
+/// Get the runtime configuration for workflows from a TOML table.
+///
+/// Expects table to be in the format:
+/// ```toml
+/// [workflows]
+/// key_value_store = "workflows"
+/// ```
+pub fn config_from_table(table: &impl GetTomlValue) -> anyhow::Result<Option<RuntimeConfig>> {
+    let Some(config) = table.get("workflows") else {
+        return Ok(None);
+    };
+    Ok(Some(config.clone().try_into()?))
+}
@@ -0,0 +1,270 @@
+use std::{
+    collections::HashMap,
+    sync::Arc,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::Context as _;
+use serde::{Deserialize, Serialize};
+use spin_factor_key_value::{Store, StoreManager};
+use spin_world::MAX_HOST_BUFFERED_BYTES;
+
+use crate::journal::{Completed, Waiting};
+
+/// The prefix of the keys of workflow instances.
+const INSTANCE_PREFIX: &str = "spin-workflows:instance:";
+/// The prefix of the keys of events raised for workflow instances.
+const EVENT_PREFIX: &str = "spin-workflows:event:";
+
+/// The workflow instances of an app, held in a key-value store.
+///
+/// The key-value store is dedicated to workflows:
+/// [`WorkflowsFactor`](crate::WorkflowsFactor) refuses apps whose components
+/// may open it. Events are kept apart from the instances they are raised for,
+/// so components can raise them while the workflow trigger updates the
+/// instances. The store does not lock instances, so it should be served by a
+/// single workflow trigger at a time.
+pub struct WorkflowStore {
+    store_manager: Arc<dyn StoreManager>,
+    label: String,
+}
+
+impl WorkflowStore {
+    /// Creates a workflow store held in the key-value store with the given
+    /// label.
+    pub fn new(store_manager: Arc<dyn StoreManager>, label: impl Into<String>) -> Self {
+        Self {
+            store_manager,
+            label: label.into(),
+        }
+    }
+
+    /// The label of the key-value store holding the workflows.
+    pub fn label(&self) -> &str {
+        &self.label
+    }
+
+    /// Returns true if the key-value store holding the workflows is defined.
+    pub fn is_defined(&self) -> bool {
+        self.store_manager.is_defined(&self.label)
+    }
+
+    async fn store(&self) -> anyhow::Result<Arc<dyn Store>> {
+        self.store_manager.get(&self.label).await.with_context(|| {
+            format!(
+                "failed to open key-value store {:?} for workflows",
+                self.label
+            )
+        })
+    }
+
+    /// Starts an instance of a workflow, returning its ID.
+    pub async fn start(&self, workflow: &str, input: Vec<u8>) -> anyhow::Result<String> {
+        let mut instance = WorkflowInstance {
+            id: new_id(),
+            workflow: workflow.to_owned(),
+            input,
+            status: Status::Running,
+            history: vec![],
+            waiting: None,
+            started_at_ms: millis_since_epoch(SystemTime::now()),
+            trace_context: Default::default(),
+        };
+        spin_telemetry::inject_message_trace_context(&mut instance.trace_context);
+        self.save(&instance).await?;
+        Ok(instance.id)
+    }
+
+    /// Returns the workflow instance with the given ID, if there is one.
+    pub async fn get(&self, id: &str) -> anyhow::Result<Option<WorkflowInstance>> {
+        let Some(value) = self
+            .store()
+            .await?
+            .get(&key(INSTANCE_PREFIX, id), MAX_HOST_BUFFERED_BYTES)
+            .await?
+        else {
+            return Ok(None);
+        };
+        Ok(Some(serde_json::from_slice(&value).with_context(|| {
+            format!("workflow instance {id} is malformed")
+        })?))
+    }
+
+    /// Stores a workflow instance, replacing its previous state.
+    pub async fn save(&self, instance: &WorkflowInstance) -> anyhow::Result<()> {
+        let value = serde_json::to_vec(instance)?;
+        self.store()
+            .await?
+            .set(&key(INSTANCE_PREFIX, &instance.id), &value)
+            .await
+            .with_context(|| format!("failed to store workflow instance {}", instance.id))
+    }
+
+    /// Returns the workflow instances which are still running, oldest first.
+    pub async fn running(&self) -> anyhow::Result<Vec<WorkflowInstance>> {
+        let store = self.store().await?;
+        let keys = store
+            .get_keys(MAX_HOST_BUFFERED_BYTES)
+            .await
+            .context("failed to list workflow instances")?;
+        let mut instances: Vec<WorkflowInstance> = vec![];
+        for key in keys.iter().filter(|key| key.starts_with(INSTANCE_PREFIX)) {
+            let Some(value) = store.get(key, MAX_HOST_BUFFERED_BYTES).await? else {
+                continue;
+            };
+            match serde_json::from_slice(&value) {
+                Ok(instance) => instances.push(instance),
+                Err(err) => tracing::warn!("Ignoring malformed workflow instance {key:?}: {err}"),
+            }
+        }
+        instances.retain(|instance| instance.status == Status::Running);
+        instances.sort_by_key(|instance| instance.started_at_ms);
+        Ok(instances)
+    }
+
+    /// Raises an event for a workflow instance.
+    pub async fn raise_event(
+        &self,
+        instance: &str,
+        event: &str,
+        payload: Vec<u8>,
+    ) -> anyhow::Result<()> {
+        // Keys sort in the order the events were raised.
+        let id = format!("{:020}-{}", millis_since_epoch(SystemTime::now()), new_id());
+        let raised = RaisedEvent {
+            key: event_key(instance, &id),
+            event: event.to_owned(),
+            payload,
+        };
+        let value = serde_json::to_vec(&raised)?;
+        self.store()
+            .await?
+            .set(&raised.key, &value)
+            .await
+            .with_context(|| format!("failed to store event for workflow instance {instance}"))
+    }
+
+    /// Returns the earliest event with the given name raised for a workflow
+    /// instance, if there is one.
+    ///
+    /// The event stays in the store until it is removed with
+    /// [`WorkflowStore::remove_event`].
+    pub async fn next_event(
+        &self,
+        instance: &str,
+        event: &str,
+    ) -> anyhow::Result<Option<RaisedEvent>> {
+        Ok(self
+            .events(instance)
+            .await?
+            .into_iter()
+            .find(|raised| raised.event == event))
+    }
+
+    /// Removes an event which has been delivered.
+    pub async fn remove_event(&self, raised: &RaisedEvent) -> anyhow::Result<()> {
+        self.store()
+            .await?
+            .delete(&raised.key)
+            .await
+            .context("failed to remove workflow event")
+    }
+
+    /// Removes the events raised for a workflow instance which were never
+    /// delivered.
+    pub async fn remove_events(&self, instance: &str) -> anyhow::Result<()> {
+        for raised in self.events(instance).await? {
+            self.remove_event(&raised).await?;
+        }
+        Ok(())
+    }
+
+    /// Returns the events raised for a workflow instance, in the order they
+    /// were raised.
+    async fn events(&self, instance: &str) -> anyhow::Result<Vec<RaisedEvent>> {
+        let store = self.store().await?;
+        let prefix = event_key(instance, "");
+        let mut keys = store
+            .get_keys(MAX_HOST_BUFFERED_BYTES)
+            .await
+            .context("failed to list workflow events")?;
+        keys.retain(|key| key.starts_with(&prefix));
+        keys.sort();
+        let mut events = vec![];
+        for key in keys {
+            let Some(value) = store.get(&key, MAX_HOST_BUFFERED_BYTES).await? else {
+                continue;
+            };
+            match serde_json::from_slice(&value) {
+                Ok(raised) => events.push(raised),
+                Err(err) => tracing::warn!("Ignoring malformed workflow event {key:?}: {err}"),
+            }
+        }
+        Ok(events)
+    }
+}
+
+fn key(prefix: &str, id: &str) -> String {
+    format!("{prefix}{id}")
+}
+
+fn event_key(instance: &str, id: &str) -> String {
+    format!("{EVENT_PREFIX}{instance}:{id}")
+}
+
+fn new_id() -> String {
+    format!("{:032x}", rand::random::<u128>())
+}
+
+/// Returns a time as milliseconds since the Unix epoch.
+pub fn millis_since_epoch(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis()
+        .try_into()
+        .unwrap_or(u64::MAX)
+}
+
+/// A workflow instance and the journal of its progress.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct WorkflowInstance {
+    /// The ID returned when the instance was started.
+    pub id: String,
+    /// The name of the workflow, which determines the orchestrator that runs
+    /// it.
+    pub workflow: String,
+    /// The input the instance was started with.
+    pub input: Vec<u8>,
+    /// Whether the instance is running or has finished.
+    pub status: Status,
+    /// The results of the operations the orchestrator has completed, in the
+    /// order it performed them.
+    pub history: Vec<Completed>,
+    /// The operation the orchestrator is waiting on, if any.
+    pub waiting: Option<Waiting>,
+    /// When the instance was started, in milliseconds since the Unix epoch.
+    pub started_at_ms: u64,
+    /// The trace context of the span which started the instance, so that
+    /// advancing it continues the trace.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub trace_context: HashMap<String, String>,
+}
+
+/// Whether a workflow instance is running or has finished.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "status", rename_all = "kebab-case")]
+pub enum Status {
+    Running,
+    Completed { output: Vec<u8> },
+    Failed { error: String },
+}
+
+/// An event raised for a workflow instance.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RaisedEvent {
+    key: String,
+    /// The name of the event.
+    pub event: String,
+    /// The payload the event was raised with.
+    pub payload: Vec<u8>,
+}
@@ -0,0 +1,235 @@
+use std::sync::Arc;
+
+use anyhow::bail;
+use spin_factor_capability_policy::{CapabilityPolicyFactor, runtime_config::config_from_table};
+use spin_factor_key_value::runtime_config::spin::MakeKeyValueStore;
+use spin_factor_key_value::{DelegatingStoreManager, KeyValueFactor, RuntimeConfig};
+use spin_factor_workflows::{
+    Completed, DEFAULT_KEY_VALUE_STORE, InstanceState, Replay, Suspended, Waiting, WorkflowStore,
+    WorkflowsFactor, v3,
+};
+use spin_factors::RuntimeFactors;
+use spin_factors_test::{TestEnvironment, toml};
+use spin_key_value_spin::{SpinKeyValueRuntimeConfig, SpinKeyValueStore};
+use spin_world::spin::workflows::context::Host as _;
+use v3::Host as _;
+
+#[derive(RuntimeFactors)]
+struct TestFactors {
+    key_value: KeyValueFactor,
+    workflows: WorkflowsFactor,
+}
+
+impl From<RuntimeConfig> for TestFactorsRuntimeConfig {
+    fn from(value: RuntimeConfig) -> Self {
+        Self {
+            key_value: Some(value),
+            workflows: None,
+        }
+    }
+}
+
+fn factors() -> TestFactors {
+    TestFactors {
+        key_value: KeyValueFactor::new(),
+        workflows: WorkflowsFactor::new(),
+    }
+}
+
+fn in_memory_store() -> anyhow::Result<RuntimeConfig> {
+    let store_manager =
+        Arc::new(SpinKeyValueStore::new(None).make_store(SpinKeyValueRuntimeConfig::new(None))?);
+    let mut runtime_config = RuntimeConfig::default();
+    runtime_config.add_store_manager(DEFAULT_KEY_VALUE_STORE.into(), store_manager);
+    Ok(runtime_config)
+}
+
+#[tokio::test]
+async fn starts_and_signals_allowed_workflows() -> anyhow::Result<()> {
+    let env = TestEnvironment::new(factors()).extend_manifest(toml! {
+        [component.test-component]
+        source = "does-not-exist.wasm"
+        workflows = ["process-order"]
+    });
+    let mut state = env
+        .runtime_config(in_memory_store()?)?
+        .build_instance_state()
+        .await?;
+    let workflows = &mut state.workflows;
+
+    let id = workflows
+        .start("process-order".into(), b"order-1".to_vec())
+        .await
+        .unwrap();
+    assert!(matches!(
+        workflows.start("refund".into(), vec![]).await,
+        Err(v3::Error::AccessDenied)
+    ));
+    assert!(matches!(
+        workflows.status(id.clone()).await,
+        Ok(v3::InstanceStatus::Running)
+    ));
+    assert!(matches!(
+        workflows.status("no-such-id".into()).await,
+        Err(v3::Error::NoSuchInstance)
+    ));
+
+    workflows
+        .raise_event(id.clone(), "approved".into(), b"yes".to_vec())
+        .await
+        .unwrap();
+    workflows
+        .raise_event(id.clone(), "approved".into(), b"again".to_vec())
+        .await
+        .unwrap();
+
+    let store = workflows.store();
+    let instance = store.get(&id).await?.unwrap();
+    assert_eq!("process-order", instance.workflow);
+    assert_eq!(b"order-1", &instance.input[..]);
+    assert_eq!(1, store.running().await?.len());
+
+    let first = store.next_event(&id, "approved").await?.unwrap();
+    assert_eq!(b"yes", &first.payload[..]);
+    store.remove_event(&first).await?;
+    let second = store.next_event(&id, "approved").await?.unwrap();
+    assert_eq!(b"again", &second.payload[..]);
+    assert!(store.next_event(&id, "rejected").await?.is_none());
+    Ok(())
+}
+
+#[tokio::test]
+async fn context_replays_journal() -> anyhow::Result<()> {
+    let env = TestEnvironment::new(factors()).extend_manifest(toml! {
+        [component.test-component]
+        source = "does-not-exist.wasm"
+        workflows = ["process-order"]
+    });
+    let mut state = env
+        .runtime_config(in_memory_store()?)?
+        .build_instance_state()
+        .await?;
+    assert!(state.workflows.instance_id().await.is_err());
+
+    let id = state
+        .workflows
+        .start("process-order".into(), vec![])
+        .await
+        .unwrap();
+    let store = state.workflows.store();
+    let mut instance = store.get(&id).await?.unwrap();
+    instance.history = vec![
+        Completed::Activity {
+            activity: "charge".into(),
+            result: Err("card declined".into()),
+        },
+        Completed::Timer { duration_ms: 1000 },
+    ];
+
+    let store_manager = Arc::new(DelegatingStoreManager::new(RuntimeConfig::default()));
+    let mut orchestrator = InstanceState::new(
+        Arc::new(WorkflowStore::new(store_manager, DEFAULT_KEY_VALUE_STORE)),
+        Default::default(),
+        Default::default(),
+        Some(Replay::new(&instance)),
+    );
+    assert_eq!(id, orchestrator.instance_id().await?);
+    assert_eq!(
+        Err("card declined".to_owned()),
+        orchestrator.call_activity("charge".into(), vec![]).await?
+    );
+    orchestrator.sleep(1000).await?;
+    let err = orchestrator
+        .wait_for_event("approved".into())
+        .await
+        .unwrap_err();
+    assert!(err.is::<Suspended>());
+    assert_eq!(
+        Some(&Waiting::Event {
+            event: "approved".into()
+        }),
+        orchestrator.replay().unwrap().waiting()
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn errors_when_workflows_store_is_not_defined() -> anyhow::Result<()> {
+    let env = TestEnvironment::new(factors()).extend_manifest(toml! {
+        [component.test-component]
+        source = "does-not-exist.wasm"
+        workflows = ["process-order"]
+    });
+    let Err(err) = env
+        .runtime_config(RuntimeConfig::default())?
+        .build_instance_state()
+        .await
+    else {
+        bail!("expected instance build to fail but it didn't");
+    };
+
+    assert!(
+        err.to_string()
+            .contains(r#"the key-value store "spin-workflows" for workflows is not defined"#)
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn errors_when_component_uses_workflows_store() -> anyhow::Result<()> {
+    let env = TestEnvironment::new(factors()).extend_manifest(toml! {
+        [component.test-component]
+        source = "does-not-exist.wasm"
+        key_value_stores = ["spin-workflows"]
+    });
+    let Err(err) = env
+        .runtime_config(in_memory_store()?)?
+        .build_instance_state()
+        .await
+    else {
+        bail!("expected instance build to fail but it didn't");
+    };
+
+    assert!(
+        err.to_string()
+            .contains(r#"uses the key-value store "spin-workflows", which holds workflows"#)
+    );
+    Ok(())
+}
+
+#[derive(RuntimeFactors)]
+struct PolicyTestFactors {
+    capability_policy: CapabilityPolicyFactor,
+    key_value: KeyValueFactor,
+    workflows: WorkflowsFactor,
+}
+
+#[tokio::test]
+async fn capability_policy_denies_workflows() -> anyhow::Result<()> {
+    let env = TestEnvironment::new(PolicyTestFactors {
+        capability_policy: CapabilityPolicyFactor::new(),
+        key_value: KeyValueFactor::new(),
+        workflows: WorkflowsFactor::new(),
+    })
+    .extend_manifest(toml! {
+        [component.test-component]
+        source = "does-not-exist.wasm"
+        workflows = ["process-order"]
+    })
+    .runtime_config(PolicyTestFactorsRuntimeConfig {
+        capability_policy: config_from_table(&toml! {
+            [[capability_policy]]
+            effect = "deny"
+            operations = ["*"]
+        })?,
+        key_value: Some(in_memory_store()?),
+        workflows: None,
+    })?;
+    let mut state = env.build_instance_state().await?;
+
+    assert!(matches!(
+        state.workflows.start("process-order".into(), vec![]).await,
+        Err(v3::Error::AccessDenied)
+    ));
+    Ok(())
+}
@@ -1,13 +1,18 @@
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::time::{Duration, Instant};
-use std::{collections::HashMap, sync::Arc};
+use std::{
+    collections::{BTreeSet, HashMap, HashSet},
+    sync::{Arc, RwLock},
+};
 
-use anyhow::Context;
+use anyhow::{Context, bail};
 use spin_app::{App, AppComponent};
 use spin_core::{Component, async_trait, wasmtime::CallHook};
 use spin_factors::{
     AsInstanceState, ConfiguredApp, Factor, HasInstanceBuilder, RuntimeFactors,
     RuntimeFactorsInstanceState,
 };
+use tracing::Instrument as _;
 
 /// A FactorsExecutor manages execution of a Spin app.
 ///
@@ -56,6 +61,7 @@ impl<T: RuntimeFactors, U: Send + 'static> FactorsExecutor<T, U> {
         component_loader: &impl ComponentLoader<T, U>,
         trigger_type: Option<&str>,
     ) -> anyhow::Result<FactorsExecutorApp<T, U>> {
+        let configure_start = Instant::now();
         let configured_app = self
             .factors
             .configure_app(app, runtime_config)
@@ -64,30 +70,103 @@ impl<T: RuntimeFactors, U: Send + 'static> FactorsExecutor<T, U> {
         for hooks in &self.hooks {
             hooks.configure_app(&configured_app).await?;
         }
+        let mut load_timings = LoadTimings {
+            configure_app: configure_start.elapsed(),
+            components: Vec::new(),
+        };
 
         let components = match trigger_type {
-            Some(trigger_type) => configured_app
-                .app()
-                .triggers_with_type(trigger_type)
-                .filter_map(|t| t.component().ok())
-                .collect::<Vec<_>>(),
+            Some(trigger_type) => {
+                // Triggers may invoke the components in their 'components'
+                // table as well as their main component.
+                let mut components = Vec::new();
+                for trigger in configured_app.app().triggers_with_type(trigger_type) {
+                    components.extend(trigger.component().ok());
+                    components.extend(trigger.all_components()?.into_iter().map(|(_, c)| c));
+                }
+                let mut seen = HashSet::new();
+                components.retain(|c| seen.insert(c.id().to_owned()));
+                components
+            }
             None => configured_app.app().components().collect(),
         };
         let mut component_instance_pres = HashMap::with_capacity(components.len());
 
+        let sources = SourceChecker {
+            configured_app: &configured_app,
+            hooks: &self.hooks,
+        };
         for component in components {
+            let load_start = Instant::now();
             let instance_pre = component_loader
-                .load_instance_pre(&self.core_engine, &component)
+                .load_instance_pre(&self.core_engine, &component, &sources)
+                .instrument(tracing::info_span!(
+                    "spin_factors_executor.load_component",
+                    component_id = component.id()
+                ))
                 .await?;
-            component_instance_pres.insert(component.id().to_string(), instance_pre);
+            let load_duration = load_start.elapsed();
+            spin_telemetry::metrics::histogram!(
+                spin.component_load_duration = load_duration.as_secs_f64(),
+                component_id = component.id(),
+                unit = "s"
+            );
+            load_timings
+                .components
+                .push((component.id().to_string(), load_duration));
+            component_instance_pres.insert(
+                component.id().to_string(),
+                LoadedComponent {
+                    current: RwLock::new(ComponentVersion {
+                        instance_pre,
+                        generation: 0,
+                    }),
+                    instantiated: AtomicBool::new(false),
+                },
+            );
         }
 
         Ok(FactorsExecutorApp {
             executor: self.clone(),
-            configured_app,
-            component_instance_pres,
+            configured_app: Arc::new(configured_app),
+            component_instance_pres: Arc::new(component_instance_pres),
+            load_timings,
         })
     }
+
+    /// Returns an instance builder for the given version of a component.
+    fn prepare_instance<'a>(
+        &'a self,
+        configured_app: &'a ConfiguredApp<T>,
+        component_id: &str,
+        version: ComponentVersion<T, U>,
+        first_instantiation: bool,
+    ) -> anyhow::Result<FactorsInstanceBuilder<'a, T, U>> {
+        let app_component = configured_app
+            .app()
+            .get_component(component_id)
+            .with_context(|| format!("no such component {component_id:?}"))?;
+
+        let factor_builders = self.factors.prepare(configured_app, component_id)?;
+
+        let store_builder = self.core_engine.store_builder();
+
+        let mut builder = FactorsInstanceBuilder {
+            store_builder,
+            factor_builders,
+            instance_pre: version.instance_pre,
+            generation: version.generation,
+            app_component,
+            factors: &self.factors,
+            first_instantiation,
+        };
+
+        for hooks in &self.hooks {
+            hooks.prepare_instance(&mut builder)?;
+        }
+
+        Ok(builder)
+    }
 }
 
 #[async_trait]
@@ -106,16 +185,48 @@ where
         let _ = builder;
         Ok(())
     }
+
+    /// Load source hooks run on the Wasm source of each component, and on
+    /// that of each of its dependencies, as the [`ComponentLoader`] loads it
+    /// and before it is compiled; an error fails the load. `dependency` is the
+    /// name of the dependency whose source this is, if any.
+    fn load_source(
+        &self,
+        configured_app: &ConfiguredApp<T>,
+        component: &AppComponent,
+        dependency: Option<&str>,
+        wasm: &[u8],
+    ) -> anyhow::Result<()> {
+        let _ = (configured_app, component, dependency, wasm);
+        Ok(())
+    }
+
+    /// Swap component hooks run before [`ComponentSwapper::swap_component`]
+    /// loads a new version of a component; an error keeps the current version.
+    fn swap_component(
+        &self,
+        configured_app: &ConfiguredApp<T>,
+        component_id: &str,
+        wasm: &[u8],
+    ) -> anyhow::Result<()> {
+        let _ = (configured_app, component_id, wasm);
+        Ok(())
+    }
 }
 
 /// A ComponentLoader is responsible for loading Wasmtime [`Component`]s.
 #[async_trait]
 pub trait ComponentLoader<T: RuntimeFactors, U>: Sync {
     /// Loads a [`Component`] for the given [`AppComponent`].
+    ///
+    /// The loader must pass the Wasm source of the component, and that of each
+    /// of its dependencies, to [`SourceChecker::check`] as it reads it, and
+    /// compile exactly what was checked.
     async fn load_component(
         &self,
         engine: &spin_core::wasmtime::Engine,
         component: &AppComponent,
+        sources: &SourceChecker<'_, T, U>,
     ) -> anyhow::Result<Component>;
 
     /// Loads [`InstancePre`] for the given [`AppComponent`].
@@ -123,24 +234,103 @@ pub trait ComponentLoader<T: RuntimeFactors, U>: Sync {
         &self,
         engine: &spin_core::Engine<InstanceState<T::InstanceState, U>>,
         component: &AppComponent,
+        sources: &SourceChecker<'_, T, U>,
     ) -> anyhow::Result<spin_core::InstancePre<InstanceState<T::InstanceState, U>>> {
-        let component = self.load_component(engine.as_ref(), component).await?;
+        let component = self
+            .load_component(engine.as_ref(), component, sources)
+            .await?;
         engine.instantiate_pre(&component)
     }
 }
 
+/// Runs the [`ExecutorHooks::load_source`] hooks on the Wasm sources a
+/// [`ComponentLoader`] loads.
+pub struct SourceChecker<'a, T: RuntimeFactors, U: 'static> {
+    configured_app: &'a ConfiguredApp<T>,
+    hooks: &'a [Box<dyn ExecutorHooks<T, U>>],
+}
+
+impl<T: RuntimeFactors, U> SourceChecker<'_, T, U> {
+    /// Checks the Wasm source of the given component, or of its dependency
+    /// with the given name.
+    pub fn check(
+        &self,
+        component: &AppComponent,
+        dependency: Option<&str>,
+        wasm: &[u8],
+    ) -> anyhow::Result<()> {
+        for hooks in self.hooks {
+            hooks.load_source(self.configured_app, component, dependency, wasm)?;
+        }
+        Ok(())
+    }
+}
+
 type InstancePre<T, U> =
     spin_core::InstancePre<InstanceState<<T as RuntimeFactors>::InstanceState, U>>;
 
+/// A component which has been loaded and pre-instantiated.
+struct LoadedComponent<T: RuntimeFactors, U: 'static> {
+    /// The version of the component new instances are created from.
+    current: RwLock<ComponentVersion<T, U>>,
+    /// Whether the current version has been instantiated yet.
+    instantiated: AtomicBool,
+}
+
+impl<T: RuntimeFactors, U: 'static> LoadedComponent<T, U> {
+    fn current(&self) -> ComponentVersion<T, U> {
+        self.current.read().unwrap().clone()
+    }
+}
+
+/// A version of a loaded component.
+struct ComponentVersion<T: RuntimeFactors, U: 'static> {
+    instance_pre: InstancePre<T, U>,
+    /// The number of times the component had been swapped when this version
+    /// was loaded.
+    generation: u64,
+}
+
+impl<T: RuntimeFactors, U: 'static> Clone for ComponentVersion<T, U> {
+    fn clone(&self) -> Self {
+        Self {
+            instance_pre: self.instance_pre.clone(),
+            generation: self.generation,
+        }
+    }
+}
+
+type LoadedComponents<T, U> = HashMap<String, LoadedComponent<T, U>>;
+
+/// Looks up a loaded component by ID.
+fn loaded_component<'a, T: RuntimeFactors, U: 'static>(
+    components: &'a LoadedComponents<T, U>,
+    component_id: &str,
+) -> anyhow::Result<&'a LoadedComponent<T, U>> {
+    components
+        .get(component_id)
+        .with_context(|| format!("no such component {component_id:?}"))
+}
+
+/// Time spent in each phase of [`FactorsExecutor::load_app`].
+#[derive(Clone, Debug, Default)]
+pub struct LoadTimings {
+    /// Time spent configuring factors and running configure app hooks.
+    pub configure_app: Duration,
+    /// Time spent loading (and compiling) each component, in load order.
+    pub components: Vec<(String, Duration)>,
+}
+
 /// A FactorsExecutorApp represents a loaded Spin app, ready for instantiation.
 ///
 /// It is generic over the executor's [`RuntimeFactors`] and any ad-hoc additional
 /// per-instance state needed by the caller.
 pub struct FactorsExecutorApp<T: RuntimeFactors, U: 'static> {
     executor: Arc<FactorsExecutor<T, U>>,
-    configured_app: ConfiguredApp<T>,
-    // Maps component IDs -> InstancePres
-    component_instance_pres: HashMap<String, InstancePre<T, U>>,
+    configured_app: Arc<ConfiguredApp<T>>,
+    // Maps component IDs -> loaded components
+    component_instance_pres: Arc<LoadedComponents<T, U>>,
+    load_timings: LoadTimings,
 }
 
 impl<T: RuntimeFactors, U: Send + 'static> FactorsExecutorApp<T, U> {
@@ -156,46 +346,152 @@ impl<T: RuntimeFactors, U: Send + 'static> FactorsExecutorApp<T, U> {
         self.configured_app.app()
     }
 
-    pub fn get_component(&self, component_id: &str) -> anyhow::Result<&Component> {
-        Ok(self.get_instance_pre(component_id)?.component())
+    /// Returns the current version of the given component.
+    pub fn get_component(&self, component_id: &str) -> anyhow::Result<Component> {
+        Ok(self.get_instance_pre(component_id)?.component().clone())
     }
 
-    pub fn get_instance_pre(&self, component_id: &str) -> anyhow::Result<&InstancePre<T, U>> {
-        self.component_instance_pres
-            .get(component_id)
-            .with_context(|| format!("no such component {component_id:?}"))
+    /// Returns the current version of the given component, pre-instantiated.
+    pub fn get_instance_pre(&self, component_id: &str) -> anyhow::Result<InstancePre<T, U>> {
+        Ok(
+            loaded_component(&self.component_instance_pres, component_id)?
+                .current()
+                .instance_pre,
+        )
     }
 
-    /// Returns an instance builder for the given component ID.
-    pub fn prepare(&self, component_id: &str) -> anyhow::Result<FactorsInstanceBuilder<'_, T, U>> {
-        let app_component = self
-            .configured_app
-            .app()
-            .get_component(component_id)
-            .with_context(|| format!("no such component {component_id:?}"))?;
+    /// Returns the number of times the given component has been swapped.
+    ///
+    /// Callers which cache anything derived from a component, such as export
+    /// indices, should rebuild it when this changes.
+    pub fn component_generation(&self, component_id: &str) -> anyhow::Result<u64> {
+        Ok(
+            loaded_component(&self.component_instance_pres, component_id)?
+                .current()
+                .generation,
+        )
+    }
+
+    /// Returns a handle for swapping this app's components while it runs.
+    pub fn component_swapper(&self) -> ComponentSwapper<T, U> {
+        ComponentSwapper {
+            executor: self.executor.clone(),
+            configured_app: self.configured_app.clone(),
+            components: self.component_instance_pres.clone(),
+        }
+    }
 
-        let instance_pre = self.component_instance_pres.get(component_id).unwrap();
+    /// Returns the time spent in each phase of loading this app.
+    pub fn load_timings(&self) -> &LoadTimings {
+        &self.load_timings
+    }
 
-        let factor_builders = self
-            .executor
-            .factors
-            .prepare(&self.configured_app, component_id)?;
+    /// Returns an instance builder for the given component ID.
+    pub fn prepare(&self, component_id: &str) -> anyhow::Result<FactorsInstanceBuilder<'_, T, U>> {
+        let loaded = loaded_component(&self.component_instance_pres, component_id)?;
+        let version = loaded.current();
+        let first_instantiation = !loaded.instantiated.swap(true, Ordering::Relaxed);
+        self.executor.prepare_instance(
+            &self.configured_app,
+            component_id,
+            version,
+            first_instantiation,
+        )
+    }
+}
 
-        let store_builder = self.executor.core_engine.store_builder();
+/// A ComponentSwapper replaces the components of a running
+/// [`FactorsExecutorApp`] with new versions.
+pub struct ComponentSwapper<T: RuntimeFactors, U: 'static> {
+    executor: Arc<FactorsExecutor<T, U>>,
+    configured_app: Arc<ConfiguredApp<T>>,
+    components: Arc<LoadedComponents<T, U>>,
+}
 
-        let mut builder = FactorsInstanceBuilder {
-            store_builder,
-            factor_builders,
-            instance_pre,
-            app_component,
-            factors: &self.executor.factors,
-        };
+impl<T: RuntimeFactors, U: 'static> Clone for ComponentSwapper<T, U> {
+    fn clone(&self) -> Self {
+        Self {
+            executor: self.executor.clone(),
+            configured_app: self.configured_app.clone(),
+            components: self.components.clone(),
+        }
+    }
+}
 
+impl<T: RuntimeFactors, U: Send + 'static> ComponentSwapper<T, U> {
+    /// Replaces the given component with a new version, compiled from the
+    /// given Wasm.
+    ///
+    /// The new version is only swapped in if the engine provides all of its
+    /// imports, it exports the same interfaces as the current version, and a
+    /// trial instance of it can be created with `executor_instance_state`.
+    /// Otherwise the current version is kept and an error returned.
+    ///
+    /// Instances of the current version which already exist are unaffected.
+    pub async fn swap_component(
+        &self,
+        component_id: &str,
+        wasm: &[u8],
+        executor_instance_state: U,
+    ) -> anyhow::Result<()> {
+        let loaded = loaded_component(&self.components, component_id)?;
         for hooks in &self.executor.hooks {
-            hooks.prepare_instance(&mut builder)?;
+            hooks.swap_component(&self.configured_app, component_id, wasm)?;
         }
+        let component = Component::new(self.executor.core_engine.as_ref(), wasm)
+            .map_err(anyhow::Error::from)
+            .with_context(|| {
+                format!("failed to compile new version of component {component_id:?}")
+            })?;
+        let current = loaded.current();
+
+        let exports = |component: &Component| {
+            component
+                .component_type()
+                .exports(self.executor.core_engine.as_ref())
+                .map(|(name, _)| name.to_owned())
+                .collect::<BTreeSet<_>>()
+        };
+        let current_exports = exports(current.instance_pre.component());
+        let new_exports = exports(&component);
+        if current_exports != new_exports {
+            let missing = current_exports.difference(&new_exports).collect::<Vec<_>>();
+            let added = new_exports.difference(&current_exports).collect::<Vec<_>>();
+            bail!(
+                "the new version of component {component_id:?} must export the same interfaces as the current version; missing {missing:?}, added {added:?}"
+            );
+        }
+        let instance_pre = self
+            .executor
+            .core_engine
+            .instantiate_pre(&component)
+            .with_context(|| {
+                format!("the new version of component {component_id:?} has unsatisfied imports")
+            })?;
+        let candidate = ComponentVersion {
+            instance_pre,
+            generation: current.generation + 1,
+        };
 
-        Ok(builder)
+        self.executor
+            .prepare_instance(&self.configured_app, component_id, candidate.clone(), false)?
+            .instantiate(executor_instance_state)
+            .await
+            .with_context(|| {
+                format!("the new version of component {component_id:?} failed to instantiate")
+            })?;
+
+        let mut current = loaded.current.write().unwrap();
+        *current = ComponentVersion {
+            generation: current.generation + 1,
+            ..candidate
+        };
+        loaded.instantiated.store(false, Ordering::Relaxed);
+        tracing::info!(
+            "Swapped component {component_id:?} to version {}",
+            current.generation
+        );
+        Ok(())
     }
 }
 
@@ -207,8 +503,12 @@ pub struct FactorsInstanceBuilder<'a, F: RuntimeFactors, U: 'static> {
     app_component: AppComponent<'a>,
     store_builder: spin_core::StoreBuilder,
     factor_builders: F::InstanceBuilders,
-    instance_pre: &'a InstancePre<F, U>,
+    instance_pre: InstancePre<F, U>,
+    /// The generation of the component version being instantiated.
+    generation: u64,
     factors: &'a F,
+    /// Whether this is the first instance of this component.
+    first_instantiation: bool,
 }
 
 impl<T: RuntimeFactors, U: 'static> FactorsInstanceBuilder<'_, T, U> {
@@ -241,6 +541,19 @@ impl<T: RuntimeFactors, U: 'static> FactorsInstanceBuilder<'_, T, U> {
     pub fn component(&self) -> &Component {
         self.instance_pre.component()
     }
+
+    /// Returns the pre-instantiated component for the instance.
+    pub fn instance_pre(&self) -> &InstancePre<T, U> {
+        &self.instance_pre
+    }
+
+    /// Returns the number of times the component had been swapped when the
+    /// version being instantiated was loaded.
+    ///
+    /// See [`FactorsExecutorApp::component_generation`].
+    pub fn component_generation(&self) -> u64 {
+        self.generation
+    }
 }
 
 impl<T: RuntimeFactors, U: Send> FactorsInstanceBuilder<'_, T, U> {
@@ -261,6 +574,11 @@ impl<T: RuntimeFactors, U: Send> FactorsInstanceBuilder<'_, T, U> {
             memory_used_on_init: 0,
             component_id: self.app_component.id().into(),
         };
+        // Decremented when the instance state is dropped.
+        spin_telemetry::metrics::counter!(
+            spin.component_active_instances = 1,
+            component_id = instance_state.component_id
+        );
         let mut store = self.store_builder.build(instance_state)?;
 
         #[cfg(feature = "cpu-time-metrics")]
@@ -268,7 +586,25 @@ impl<T: RuntimeFactors, U: Send> FactorsInstanceBuilder<'_, T, U> {
             CpuTimeCallHook.handle_call_event::<T, U>(store.data_mut(), hook)
         });
 
+        let instantiate_start = Instant::now();
         let instance = self.instance_pre.instantiate_async(&mut store).await?;
+        let duration = instantiate_start.elapsed();
+        spin_telemetry::metrics::histogram!(
+            spin.component_instantiation_duration = duration.as_secs_f64(),
+            component_id = self.app_component.id(),
+            unit = "s"
+        );
+        if self.first_instantiation {
+            tracing::debug!(
+                "First instantiation of component {:?} took {duration:?}",
+                self.app_component.id()
+            );
+            spin_telemetry::metrics::histogram!(
+                spin.component_first_instantiation_duration = duration.as_secs_f64(),
+                component_id = self.app_component.id(),
+                unit = "s"
+            );
+        }
 
         // Track memory usage after instantiation in the instance state.
         // Note: This only applies if the component has initial memory reservations.
@@ -341,6 +677,11 @@ pub struct InstanceState<T, U> {
 
 impl<T, U> Drop for InstanceState<T, U> {
     fn drop(&mut self) {
+        spin_telemetry::metrics::counter!(
+            spin.component_active_instances = -1,
+            component_id = self.component_id
+        );
+
         // Record the component execution time.
         #[cfg(feature = "cpu-time-metrics")]
         spin_telemetry::metrics::histogram!(
@@ -364,6 +705,15 @@ impl<T, U> Drop for InstanceState<T, U> {
             component_id = self.component_id,
             unit = "By"
         );
+
+        // Record guest execution interrupted for exceeding its deadline or execution time limit.
+        let epoch_interruptions = self.core.epoch_interruptions();
+        if epoch_interruptions > 0 {
+            spin_telemetry::metrics::monotonic_counter!(
+                spin.component_epoch_interruptions = epoch_interruptions,
+                component_id = self.component_id
+            );
+        }
     }
 }
 
@@ -455,6 +805,97 @@ mod tests {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn swap_component_validates_new_version() -> anyhow::Result<()> {
+        let factors = TestFactors {
+            wasi: WasiFactor::new(DummyFilesMounter),
+        };
+        let env = TestEnvironment::new(factors);
+        let locked = env.build_locked_app().await?;
+        let app = App::new("test-app", locked);
+
+        let engine_builder = spin_core::Engine::builder(&Default::default())?;
+        let executor = Arc::new(FactorsExecutor::new(engine_builder, env.factors)?);
+
+        let factors_app = executor
+            .load_app(app, Default::default(), &DummyComponentLoader, None)
+            .await?;
+        let swapper = factors_app.component_swapper();
+
+        // Invalid Wasm
+        let invalid = b"not wasm";
+        assert!(swapper.swap_component("empty", invalid, ()).await.is_err());
+        // Different exports
+        let exports_func = br#"(component (import "f" (func $f)) (export "g" (func $f)))"#;
+        assert!(
+            swapper
+                .swap_component("empty", exports_func, ())
+                .await
+                .is_err()
+        );
+        // Unsatisfied imports
+        let imports_func = br#"(component (import "f" (func)))"#;
+        assert!(
+            swapper
+                .swap_component("empty", imports_func, ())
+                .await
+                .is_err()
+        );
+        assert_eq!(factors_app.component_generation("empty")?, 0);
+
+        swapper.swap_component("empty", b"(component)", ()).await?;
+        assert_eq!(factors_app.component_generation("empty")?, 1);
+        let instance_builder = factors_app.prepare("empty")?;
+        assert_eq!(instance_builder.component_generation(), 1);
+        instance_builder.instantiate(()).await?;
+
+        assert!(
+            swapper
+                .swap_component("missing", b"(component)", ())
+                .await
+                .is_err()
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn load_source_hooks_check_loaded_sources() -> anyhow::Result<()> {
+        struct RejectSources;
+
+        impl ExecutorHooks<TestFactors, ()> for RejectSources {
+            fn load_source(
+                &self,
+                _configured_app: &ConfiguredApp<TestFactors>,
+                component: &AppComponent,
+                dependency: Option<&str>,
+                wasm: &[u8],
+            ) -> anyhow::Result<()> {
+                assert_eq!(None, dependency);
+                assert_eq!(b"(component)", wasm);
+                bail!("rejected {}", component.id())
+            }
+        }
+
+        let factors = TestFactors {
+            wasi: WasiFactor::new(DummyFilesMounter),
+        };
+        let env = TestEnvironment::new(factors);
+        let locked = env.build_locked_app().await?;
+        let app = App::new("test-app", locked);
+
+        let engine_builder = spin_core::Engine::builder(&Default::default())?;
+        let mut executor = FactorsExecutor::new(engine_builder, env.factors)?;
+        executor.add_hooks(RejectSources);
+
+        let err = Arc::new(executor)
+            .load_app(app, Default::default(), &DummyComponentLoader, None)
+            .await
+            .err()
+            .expect("load should fail");
+        assert_eq!("rejected empty", err.to_string());
+        Ok(())
+    }
+
     struct DummyComponentLoader;
 
     #[async_trait]
@@ -462,9 +903,12 @@ mod tests {
         async fn load_component(
             &self,
             engine: &spin_core::wasmtime::Engine,
-            _component: &AppComponent,
+            component: &AppComponent,
+            sources: &SourceChecker<'_, TestFactors, ()>,
         ) -> anyhow::Result<Component> {
-            Ok(Component::new(engine, "(component)")?)
+            let wasm = b"(component)";
+            sources.check(component, None, wasm)?;
+            Ok(Component::new(engine, wasm)?)
         }
     }
 }
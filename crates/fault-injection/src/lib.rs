@@ -0,0 +1,277 @@
+//! Failure and latency injection into the host calls components make, for
+//! testing how components handle errors and slow services.
+//!
+//! A [`Scenario`] is read from a TOML file:
+//!
+//! ```toml
+//! # Override the app's variables
+//! [variables]
+//! api_key = "test-key"
+//!
+//! # Fail the first two writes to the default key-value store
+//! [[fail]]
+//! call = "key_value.set"
+//! target = "default"
+//! message = "disk full"
+//! times = 2
+//!
+//! # Delay every outbound HTTP request to an API
+//! [[latency]]
+//! call = "outbound_http.*"
+//! target = "https://api.example.com/*"
+//! delay = "2s"
+//! ```
+//!
+//! Calls are named `<factor>.<operation>`, e.g. `key_value.get`,
+//! `sqlite.query` or `outbound_http.request`, and targets are the store or
+//! database label, or the request URL. In both, `*` matches any text.
+
+use std::{
+    collections::HashMap,
+    path::Path,
+    sync::atomic::{AtomicU32, Ordering},
+    time::Duration,
+};
+
+use anyhow::Context as _;
+use serde::Deserialize;
+
+/// Variable overrides and host call faults to run an app with.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Scenario {
+    /// Values for the app's variables, overriding those from other sources.
+    #[serde(default)]
+    pub variables: HashMap<String, String>,
+    /// Host calls to fail.
+    #[serde(default)]
+    pub fail: Vec<FailRule>,
+    /// Host calls to delay.
+    #[serde(default)]
+    pub latency: Vec<LatencyRule>,
+}
+
+/// Fails the host calls matching `call` and `target`.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FailRule {
+    pub call: String,
+    pub target: Option<String>,
+    /// The error message the calls fail with.
+    #[serde(default = "default_message")]
+    pub message: String,
+    /// Fail only this many matching calls, then let the rest through.
+    pub times: Option<u32>,
+}
+
+/// Delays the host calls matching `call` and `target`.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct LatencyRule {
+    pub call: String,
+    pub target: Option<String>,
+    /// How long to delay the calls, e.g. `250ms` or `2s`.
+    pub delay: String,
+}
+
+fn default_message() -> String {
+    "injected fault".into()
+}
+
+impl Scenario {
+    /// Reads a scenario file.
+    pub fn from_file(path: &Path) -> anyhow::Result<Self> {
+        let contents = std::fs::read_to_string(path)
+            .with_context(|| format!("failed to read scenario file {}", path.display()))?;
+        toml::from_str(&contents)
+            .with_context(|| format!("failed to parse scenario file {}", path.display()))
+    }
+
+    /// Returns an injector for the scenario's faults, or `None` if it has
+    /// none.
+    pub fn fault_injector(&self) -> anyhow::Result<Option<FaultInjector>> {
+        if self.fail.is_empty() && self.latency.is_empty() {
+            return Ok(None);
+        }
+        let latencies = self
+            .latency
+            .iter()
+            .map(|rule| {
+                let delay = spin_common::arg_parser::parse_duration(&rule.delay)
+                    .with_context(|| format!("invalid delay for {:?}", rule.call))?;
+                Ok(Latency {
+                    call: rule.call.clone(),
+                    target: rule.target.clone(),
+                    delay,
+                })
+            })
+            .collect::<anyhow::Result<_>>()?;
+        let failures = self
+            .fail
+            .iter()
+            .map(|rule| Failure {
+                call: rule.call.clone(),
+                target: rule.target.clone(),
+                message: rule.message.clone(),
+                remaining: rule.times.map(AtomicU32::new),
+            })
+            .collect();
+        Ok(Some(FaultInjector {
+            latencies,
+            failures,
+        }))
+    }
+}
+
+/// Applies a scenario's faults to host calls.
+#[derive(Debug)]
+pub struct FaultInjector {
+    latencies: Vec<Latency>,
+    failures: Vec<Failure>,
+}
+
+#[derive(Debug)]
+struct Latency {
+    call: String,
+    target: Option<String>,
+    delay: Duration,
+}
+
+#[derive(Debug)]
+struct Failure {
+    call: String,
+    target: Option<String>,
+    message: String,
+    /// How many more calls to fail, if limited.
+    remaining: Option<AtomicU32>,
+}
+
+fn rule_matches(rule_call: &str, rule_target: Option<&str>, call: &str, target: &str) -> bool {
+    glob_match(rule_call, call) && rule_target.is_none_or(|pattern| glob_match(pattern, target))
+}
+
+impl FaultInjector {
+    /// Applies any faults for a call, to be made before the call: waits out
+    /// any latency, then returns the message of any failure.
+    pub async fn inject(&self, call: &str, target: &str) -> Result<(), String> {
+        let delay = self
+            .latencies
+            .iter()
+            .filter(|latency| rule_matches(&latency.call, latency.target.as_deref(), call, target))
+            .map(|latency| latency.delay)
+            .sum::<Duration>();
+        if !delay.is_zero() {
+            tracing::debug!("Delaying {call} on {target:?} by {delay:?}");
+            tokio::time::sleep(delay).await;
+        }
+
+        for failure in &self.failures {
+            if !rule_matches(&failure.call, failure.target.as_deref(), call, target) {
+                continue;
+            }
+            if let Some(remaining) = &failure.remaining {
+                let take = remaining
+                    .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| n.checked_sub(1));
+                if take.is_err() {
+                    continue;
+                }
+            }
+            tracing::debug!("Failing {call} on {target:?}: {}", failure.message);
+            return Err(failure.message.clone());
+        }
+        Ok(())
+    }
+}
+
+/// Matches `text` against `pattern`, in which `*` matches any text.
+fn glob_match(pattern: &str, text: &str) -> bool {
+    let mut parts = pattern.split('*');
+    // There is always a first part, which must be a prefix.
+    let first = parts.next().unwrap_or_default();
+    let Some(mut rest) = text.strip_prefix(first) else {
+        return false;
+    };
+    let parts = parts.collect::<Vec<_>>();
+    let Some((last, middle)) = parts.split_last() else {
+        // No `*`: the whole text must match.
+        return rest.is_empty();
+    };
+    for part in middle {
+        match rest.find(part) {
+            Some(index) => rest = &rest[index + part.len()..],
+            None => return false,
+        }
+    }
+    rest.ends_with(last)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn globs_match() {
+        assert!(glob_match("key_value.get", "key_value.get"));
+        assert!(!glob_match("key_value.get", "key_value.get_keys"));
+        assert!(glob_match("key_value.*", "key_value.get_keys"));
+        assert!(glob_match("*", ""));
+        assert!(glob_match(
+            "https://*.example.com/*",
+            "https://api.example.com/users"
+        ));
+        assert!(!glob_match(
+            "https://*.example.com/*",
+            "https://example.org/"
+        ));
+        assert!(glob_match("a*b*c", "abc"));
+        assert!(!glob_match("a*b*c", "acb"));
+    }
+
+    #[tokio::test]
+    async fn failures_are_limited_by_times() {
+        let scenario: Scenario = toml::from_str(
+            r#"
+            [[fail]]
+            call = "key_value.set"
+            target = "default"
+            message = "disk full"
+            times = 2
+            "#,
+        )
+        .unwrap();
+        let injector = scenario.fault_injector().unwrap().unwrap();
+
+        assert_eq!(Ok(()), injector.inject("key_value.get", "default").await);
+        assert_eq!(Ok(()), injector.inject("key_value.set", "other").await);
+        for _ in 0..2 {
+            assert_eq!(
+                Err("disk full".to_owned()),
+                injector.inject("key_value.set", "default").await
+            );
+        }
+        assert_eq!(Ok(()), injector.inject("key_value.set", "default").await);
+    }
+
+    #[test]
+    fn scenarios_without_faults_need_no_injector() {
+        let scenario: Scenario = toml::from_str(
+            r#"
+            [variables]
+            api_key = "test-key"
+            "#,
+        )
+        .unwrap();
+        assert_eq!("test-key", scenario.variables["api_key"]);
+        assert!(scenario.fault_injector().unwrap().is_none());
+
+        let scenario: Scenario = toml::from_str(
+            r#"
+            [[latency]]
+            call = "sqlite.*"
+            delay = "soon"
+            "#,
+        )
+        .unwrap();
+        assert!(scenario.fault_injector().is_err());
+    }
+}
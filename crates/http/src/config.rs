@@ -14,6 +14,28 @@ pub struct HttpTriggerConfig {
     /// The HTTP executor the component requires
     #[serde(default)]
     pub executor: Option<HttpExecutorType>,
+    /// The authentication required to access the route
+    #[serde(default)]
+    pub auth: Option<HttpAuth>,
+    /// Routes requests from the same session to the same component instance
+    #[serde(default)]
+    pub affinity: Option<SessionAffinity>,
+    /// Further components the route may invoke, by role
+    #[serde(default)]
+    pub components: indexmap::IndexMap<String, Vec<String>>,
+    /// Sends a share of the route's requests to its canary component
+    #[serde(default)]
+    pub canary: Option<CanaryConfig>,
+    /// Assigns the route's requests to the variants of an A/B experiment
+    #[serde(default)]
+    pub experiment: Option<ExperimentConfig>,
+    /// The client addresses allowed to use the route
+    #[serde(default)]
+    pub ip_filter: Option<IpFilterConfig>,
+    /// Limits on the number of requests the route's component may be
+    /// handling at once, overriding the `spin up` defaults
+    #[serde(default)]
+    pub concurrency: Option<ConcurrencyLimit>,
 }
 
 impl HttpTriggerConfig {
@@ -33,6 +55,99 @@ impl HttpTriggerConfig {
     }
 }
 
+/// Authentication required by an HTTP route.
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum HttpAuth {
+    /// Users must sign in with the app's OpenID Connect provider, configured
+    /// in `[application.trigger.http.oidc]`.
+    Oidc,
+}
+
+/// Session affinity for an HTTP route.
+///
+/// Requests carrying the same session key, taken from either a cookie or a
+/// header, are routed to the same warm component instance.
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct SessionAffinity {
+    /// The cookie holding the session key.
+    pub cookie: Option<String>,
+    /// The header holding the session key.
+    pub header: Option<String>,
+    /// How long to keep a session's instance warm after its last request,
+    /// e.g. "300s".
+    pub idle_timeout: Option<String>,
+}
+
+/// Canary routing for an HTTP route.
+///
+/// The canary version of the route's component is configured as
+/// `components = { canary = "..." }`.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct CanaryConfig {
+    /// The percentage of requests sent to the canary.
+    pub weight: u8,
+    /// A header which, if set to the ID of the route's component or of its
+    /// canary, sends the request to that component regardless of weight.
+    #[serde(default)]
+    pub header: Option<String>,
+}
+
+/// An A/B experiment on an HTTP route.
+///
+/// Each request is assigned to a variant by hashing the key in its cookie or
+/// header, so a client sending the same key always gets the same variant.
+/// The variant is passed to the component in the `spin-experiment-variant`
+/// header.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct ExperimentConfig {
+    /// The name of the experiment, which keeps its assignments independent
+    /// of other experiments'.
+    pub name: String,
+    /// The cookie holding the key requests are assigned by.
+    #[serde(default)]
+    pub cookie: Option<String>,
+    /// The header holding the key requests are assigned by.
+    #[serde(default)]
+    pub header: Option<String>,
+    /// The variants of the experiment, and the relative share of requests
+    /// assigned to each.
+    pub variants: indexmap::IndexMap<String, u32>,
+}
+
+/// Client IP filtering for an HTTP route.
+///
+/// Entries are CIDR networks, e.g. "10.0.0.0/8", or single addresses. An
+/// address in `deny` is refused even if `allow` covers it; if `allow` is not
+/// empty, addresses it does not cover are refused.
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct IpFilterConfig {
+    /// The networks allowed to use the route; all if empty.
+    #[serde(default)]
+    pub allow: Vec<String>,
+    /// The networks refused.
+    #[serde(default)]
+    pub deny: Vec<String>,
+}
+
+/// Concurrency limits for an HTTP route's component.
+///
+/// Requests beyond `max_in_flight` wait for a free slot, up to
+/// `max_queued_requests` of them; further requests are shed.
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct ConcurrencyLimit {
+    /// The maximum number of requests the component may be handling at once.
+    pub max_in_flight: usize,
+    /// The maximum number of requests which may wait for a free slot.
+    #[serde(default)]
+    pub max_queued_requests: usize,
+}
+
 /// The executor for the HTTP component.
 /// The component can either implement the Spin HTTP interface,
 /// the `wasi-http` interface, or the Wagi CGI interface.
@@ -123,4 +238,53 @@ mod tests {
         assert_eq!(config.entrypoint, "_start");
         assert_eq!(config.argv, "${SCRIPT_NAME} ${ARGS}");
     }
+
+    #[test]
+    fn affinity_config_smoke_test() {
+        let config: HttpTriggerConfig = toml::toml! {
+            component = "cart"
+            route = "/cart/..."
+            affinity = { cookie = "session", idle_timeout = "300s" }
+        }
+        .try_into()
+        .unwrap();
+        let affinity = config.affinity.unwrap();
+        assert_eq!(affinity.cookie.as_deref(), Some("session"));
+        assert_eq!(affinity.header, None);
+        assert_eq!(affinity.idle_timeout.as_deref(), Some("300s"));
+    }
+
+    #[test]
+    fn canary_config_smoke_test() {
+        let config: HttpTriggerConfig = toml::toml! {
+            component = "api-v1"
+            route = "/api/..."
+            components = { canary = ["api-v2"] }
+            canary = { weight = 10, header = "x-version" }
+        }
+        .try_into()
+        .unwrap();
+        assert_eq!(config.components["canary"], ["api-v2"]);
+        let canary = config.canary.unwrap();
+        assert_eq!(canary.weight, 10);
+        assert_eq!(canary.header.as_deref(), Some("x-version"));
+    }
+
+    #[test]
+    fn experiment_config_smoke_test() {
+        let config: HttpTriggerConfig = toml::toml! {
+            component = "checkout"
+            route = "/checkout"
+            experiment = { name = "one-click", cookie = "uid", variants = { control = 90, one-click = 10 } }
+        }
+        .try_into()
+        .unwrap();
+        let experiment = config.experiment.unwrap();
+        assert_eq!(experiment.name, "one-click");
+        assert_eq!(experiment.cookie.as_deref(), Some("uid"));
+        assert_eq!(
+            experiment.variants.into_iter().collect::<Vec<_>>(),
+            [("control".to_owned(), 90), ("one-click".to_owned(), 10)]
+        );
+    }
 }
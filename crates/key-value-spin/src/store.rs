@@ -189,6 +189,47 @@ impl Store for SqliteStore {
         })
     }
 
+    async fn get_keys_after(
+        &self,
+        after: &str,
+        limit: usize,
+        max_result_bytes: usize,
+    ) -> Result<Vec<String>, Error> {
+        task::block_in_place(|| {
+            let mut byte_count = std::mem::size_of::<Vec<String>>();
+            self.connection
+                .lock()
+                .unwrap()
+                .prepare_cached(
+                    "SELECT key FROM spin_key_value WHERE store=$1 AND key>$2 ORDER BY key LIMIT $3",
+                )
+                .map_err(log_error)?
+                .query_map(
+                    rusqlite::params![&self.name, after, i64::try_from(limit).unwrap_or(i64::MAX)],
+                    |row| row.get::<_, String>(0),
+                )
+                .map_err(log_error)?
+                .map(|r| match r {
+                    Ok(r) => {
+                        byte_count += std::mem::size_of::<String>() + r.len();
+                        if byte_count > max_result_bytes {
+                            Err(Error::Other(format!(
+                                "query result exceeds limit of {max_result_bytes} bytes"
+                            )))
+                        } else {
+                            Ok(r)
+                        }
+                    }
+                    Err(e) => Err(log_error(e)),
+                })
+                .collect()
+        })
+    }
+
+    fn lists_keys_in_order(&self) -> bool {
+        true
+    }
+
     async fn get_keys_async(
         &self,
         max_result_bytes: usize,
@@ -658,6 +699,28 @@ mod test {
         }
     }
 
+    #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+    async fn keys_after_are_paged_in_order() -> Result<()> {
+        let store = KeyValueSqlite::new(DatabaseLocation::InMemory)
+            .get("default")
+            .await?;
+        for key in ["d", "a", "e", "c", "b"] {
+            store.set(key, b"").await?;
+        }
+
+        assert_eq!(
+            ["c", "d"],
+            store.get_keys_after("b", 2, usize::MAX).await?[..]
+        );
+        assert_eq!(["e"], store.get_keys_after("d", 2, usize::MAX).await?[..]);
+        // A cursor key removed since the previous page still resumes in order.
+        assert_eq!(
+            ["c", "d", "e"],
+            store.get_keys_after("bb", 10, usize::MAX).await?[..]
+        );
+        Ok(())
+    }
+
     async fn kv_incr(kv: &mut KeyValueDispatch, rep: u32, delta: i64) -> i64 {
         let res = kv
             .increment(Resource::new_own(rep), "counter".to_owned(), delta)
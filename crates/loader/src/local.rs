@@ -172,10 +172,34 @@ impl LocalLoader {
         let metadata = ValuesMapBuilder::new()
             .string("description", component.description)
             .string_array("allowed_outbound_hosts", allowed_outbound_hosts)
+            .string_array("tcp_listeners", component.tcp_listeners)
             .string_array("key_value_stores", component.key_value_stores)
             .string_array("databases", component.sqlite_databases)
+            .string_array("blob_containers", component.blob_containers)
+            .string_array("messaging_brokers", component.messaging_brokers)
+            .string_array("jobs", component.jobs)
+            .string_array("scheduled_components", component.scheduled_components)
+            .string_array("workflows", component.workflows)
+            .string_array("actors", component.actors)
             .string_array("ai_models", component.ai_models)
+            .string_array("nn_models", component.nn_models)
+            .string_array("host_extensions", component.host_extensions)
+            .string_option("signature", component.signature)
+            .serializable(
+                "dependency_signatures",
+                (!component.dependency_signatures.is_empty())
+                    .then_some(component.dependency_signatures),
+            )?
+            .serializable(
+                "crypto_keys",
+                (!component.crypto_keys.is_empty()).then_some(component.crypto_keys),
+            )?
             .serializable("build", component.build)?
+            .serializable(
+                "telemetry_attributes",
+                (!component.telemetry_attributes.is_empty())
+                    .then_some(component.telemetry_attributes),
+            )?
             .take();
 
         let source = self
@@ -594,6 +618,12 @@ fn locked_metadata(
         .string("description", details.description)
         .string_array("authors", details.authors)
         .serializable("triggers", &details.trigger_global_configs)?;
+    if !details.telemetry_resource_attributes.is_empty() {
+        builder.serializable(
+            "telemetry_resource_attributes",
+            &details.telemetry_resource_attributes,
+        )?;
+    }
 
     // Duplicate single-trigger global options into "trigger" with "type"
     // key to maintain backward compatibility for a while.
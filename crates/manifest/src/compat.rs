@@ -21,6 +21,7 @@ pub fn v1_to_v2_app(manifest: v1::AppManifestV1) -> Result<v2::AppManifest, Erro
         description: manifest.description,
         authors: manifest.authors,
         targets: Default::default(),
+        telemetry_resource_attributes: Default::default(),
         trigger_global_configs,
         tool: Default::default(),
     };
@@ -66,11 +67,24 @@ pub fn v1_to_v2_app(manifest: v1::AppManifestV1) -> Result<v2::AppManifest, Erro
                 exclude_files: component.exclude_files,
                 key_value_stores: component.key_value_stores,
                 sqlite_databases: component.sqlite_databases,
+                blob_containers: Vec::new(),
+                messaging_brokers: Vec::new(),
+                jobs: Vec::new(),
+                scheduled_components: Vec::new(),
+                workflows: Vec::new(),
+                actors: Vec::new(),
                 ai_models: component.ai_models,
+                nn_models: Vec::new(),
+                crypto_keys: Default::default(),
+                host_extensions: Vec::new(),
+                telemetry_attributes: Default::default(),
+                signature: None,
+                dependency_signatures: Default::default(),
                 targets: Default::default(),
                 build: component.build,
                 tool: Default::default(),
                 allowed_outbound_hosts,
+                tcp_listeners: Vec::new(),
                 allowed_http_hosts: Vec::new(),
                 dependencies_inherit_configuration: None,
                 dependencies: Default::default(),
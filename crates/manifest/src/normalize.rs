@@ -266,9 +266,22 @@ fn ensure_is_acceptable_dependency(
         exclude_files: _,
         allowed_http_hosts,
         allowed_outbound_hosts,
+        tcp_listeners,
         key_value_stores,
         sqlite_databases,
+        blob_containers,
+        messaging_brokers,
+        jobs,
+        scheduled_components,
+        workflows,
+        actors,
         ai_models,
+        nn_models,
+        crypto_keys,
+        host_extensions,
+        telemetry_attributes,
+        signature: _,
+        dependency_signatures: _,
         targets: _,
         build: _,
         tool: _,
@@ -277,15 +290,30 @@ fn ensure_is_acceptable_dependency(
         profile: _,
     } = component;
 
+    if !actors.is_empty() {
+        surprises.push("actors");
+    }
     if !ai_models.is_empty() {
         surprises.push("ai_models");
     }
+    if !host_extensions.is_empty() {
+        surprises.push("host_extensions");
+    }
+    if !telemetry_attributes.is_empty() {
+        surprises.push("telemetry_attributes");
+    }
     if !allowed_http_hosts.is_empty() {
         surprises.push("allowed_http_hosts");
     }
     if !allowed_outbound_hosts.is_empty() {
         surprises.push("allowed_outbound_hosts");
     }
+    if !blob_containers.is_empty() {
+        surprises.push("blob_containers");
+    }
+    if !crypto_keys.is_empty() {
+        surprises.push("crypto_keys");
+    }
     if !dependencies.inner.is_empty() {
         surprises.push("dependencies");
     }
@@ -295,15 +323,33 @@ fn ensure_is_acceptable_dependency(
     if !files.is_empty() {
         surprises.push("files");
     }
+    if !jobs.is_empty() {
+        surprises.push("jobs");
+    }
     if !key_value_stores.is_empty() {
         surprises.push("key_value_stores");
     }
+    if !messaging_brokers.is_empty() {
+        surprises.push("messaging_brokers");
+    }
+    if !nn_models.is_empty() {
+        surprises.push("nn_models");
+    }
+    if !scheduled_components.is_empty() {
+        surprises.push("scheduled_components");
+    }
     if !sqlite_databases.is_empty() {
         surprises.push("sqlite_databases");
     }
+    if !tcp_listeners.is_empty() {
+        surprises.push("tcp_listeners");
+    }
     if !variables.is_empty() {
         surprises.push("variables");
     }
+    if !workflows.is_empty() {
+        surprises.push("workflows");
+    }
 
     if surprises.is_empty() {
         Ok(())
@@ -103,6 +103,86 @@ pub enum SqliteDatabase {
     Label(String),
 }
 
+/// The blob containers which the component is allowed to access. Containers are identified
+/// by label e.g. "default" or "uploads". Containers other than "default" must be mapped
+/// to a backing store in the runtime config.
+///
+/// Example: `blob_containers = ["default", "my-container"]`
+#[allow(dead_code)]
+#[derive(JsonSchema)]
+#[serde(untagged)]
+pub enum BlobContainer {
+    Label(String),
+}
+
+/// The wasi-nn models which the component is allowed to load by name. Models are identified
+/// by label e.g. "mobilenet", and must be mapped to a model directory in the runtime config.
+///
+/// Example: `nn_models = ["mobilenet"]`
+#[allow(dead_code)]
+#[derive(JsonSchema)]
+#[serde(untagged)]
+pub enum NnModel {
+    Label(String),
+}
+
+/// The message brokers which the component is allowed to access. Brokers are identified
+/// by label e.g. "events", and must be mapped to a backing broker in the runtime config.
+///
+/// Example: `messaging_brokers = ["events"]`
+#[allow(dead_code)]
+#[derive(JsonSchema)]
+#[serde(untagged)]
+pub enum MessagingBroker {
+    Label(String),
+}
+
+/// The background jobs which the component is allowed to enqueue. Jobs are identified
+/// by name e.g. "send-email", and are run by components handling them with the job
+/// trigger.
+///
+/// Example: `jobs = ["send-email"]`
+#[allow(dead_code)]
+#[derive(JsonSchema)]
+#[serde(untagged)]
+pub enum Job {
+    Name(String),
+}
+
+/// The components which the component is allowed to schedule invocations of. The
+/// invoked components must have a job trigger.
+///
+/// Example: `scheduled_components = ["reminder-sender"]`
+#[allow(dead_code)]
+#[derive(JsonSchema)]
+#[serde(untagged)]
+pub enum ScheduledComponent {
+    Id(String),
+}
+
+/// The durable workflows which the component is allowed to start, raise events for
+/// and query. Workflows are identified by name e.g. "process-order", and are run by
+/// orchestrator components with the workflow trigger.
+///
+/// Example: `workflows = ["process-order"]`
+#[allow(dead_code)]
+#[derive(JsonSchema)]
+#[serde(untagged)]
+pub enum Workflow {
+    Name(String),
+}
+
+/// The types of actor which the component is allowed to call. Actor types are identified
+/// by name e.g. "cart", and are served by actor components with the actor trigger.
+///
+/// Example: `actors = ["cart"]`
+#[allow(dead_code)]
+#[derive(JsonSchema)]
+#[serde(untagged)]
+pub enum ActorType {
+    Name(String),
+}
+
 /// The key-value stores which the component is allowed to access. Stores are identified
 /// by label e.g. "default" or "customer". Stores other than "default" must be mapped
 /// to a backing store in the runtime config.
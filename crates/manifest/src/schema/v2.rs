@@ -103,6 +103,13 @@ pub struct AppDetails {
     /// Example: `targets = ["spin-up:3.3", "spinkube:0.4"]`
     #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub targets: Vec<TargetEnvironmentRef>,
+    /// Attributes describing the application in the telemetry it exports, in
+    /// addition to those detected from its environment. Attributes set by the
+    /// `OTEL_RESOURCE_ATTRIBUTES` environment variable take precedence.
+    ///
+    /// Example: `telemetry_resource_attributes = { "deployment.environment.name" = "production" }`
+    #[serde(default, skip_serializing_if = "Map::is_empty")]
+    pub telemetry_resource_attributes: Map<String, String>,
     /// Application-level settings for the trigger types used in the application.
     /// The possible values are trigger type-specific.
     ///
@@ -483,6 +490,13 @@ pub struct Component {
     #[serde(default, skip_serializing_if = "Vec::is_empty")]
     #[schemars(with = "Vec<json_schema::AllowedOutboundHost>")]
     pub allowed_outbound_hosts: Vec<String>,
+    /// The local addresses on which the component may listen for inbound TCP
+    /// connections using `wasi:sockets`. Each entry is in the form "(ip):(port)";
+    /// the IP may be "*" to allow listening on any local address.
+    ///
+    /// Example: `tcp_listeners = ["*:6379", "127.0.0.1:9000"]`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tcp_listeners: Vec<String>,
     /// The key-value stores which the component is allowed to access. Stores are identified
     /// by label e.g. "default" or "customer". Stores other than "default" must be mapped
     /// to a backing store in the runtime config.
@@ -511,6 +525,59 @@ pub struct Component {
     )]
     #[schemars(with = "Vec<json_schema::SqliteDatabase>")]
     pub sqlite_databases: Vec<String>,
+    /// The blob containers which the component is allowed to access. Containers are identified
+    /// by label e.g. "default" or "uploads". Containers other than "default" must be mapped
+    /// to a backing store in the runtime config.
+    ///
+    /// Example: `blob_containers = ["default", "my-container"]`
+    #[serde(
+        default,
+        with = "kebab_or_snake_case",
+        skip_serializing_if = "Vec::is_empty"
+    )]
+    #[schemars(with = "Vec<json_schema::BlobContainer>")]
+    pub blob_containers: Vec<String>,
+    /// The message brokers which the component is allowed to access. Brokers are identified
+    /// by label e.g. "events", and must be mapped to a backing broker in the runtime config.
+    ///
+    /// Example: `messaging_brokers = ["events"]`
+    #[serde(
+        default,
+        with = "kebab_or_snake_case",
+        skip_serializing_if = "Vec::is_empty"
+    )]
+    #[schemars(with = "Vec<json_schema::MessagingBroker>")]
+    pub messaging_brokers: Vec<String>,
+    /// The background jobs which the component is allowed to enqueue. Jobs are identified
+    /// by name e.g. "send-email", and are run by components handling them with the job
+    /// trigger.
+    ///
+    /// Example: `jobs = ["send-email"]`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    #[schemars(with = "Vec<json_schema::Job>")]
+    pub jobs: Vec<String>,
+    /// The components which the component is allowed to schedule invocations of. The
+    /// invoked components must have a job trigger.
+    ///
+    /// Example: `scheduled_components = ["reminder-sender"]`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    #[schemars(with = "Vec<json_schema::ScheduledComponent>")]
+    pub scheduled_components: Vec<String>,
+    /// The durable workflows which the component is allowed to start, raise events for
+    /// and query. Workflows are identified by name e.g. "process-order", and are run by
+    /// orchestrator components with the workflow trigger.
+    ///
+    /// Example: `workflows = ["process-order"]`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    #[schemars(with = "Vec<json_schema::Workflow>")]
+    pub workflows: Vec<String>,
+    /// The types of actor which the component is allowed to call. Actor types are identified
+    /// by name e.g. "cart", and are served by actor components with the actor trigger.
+    ///
+    /// Example: `actors = ["cart"]`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    #[schemars(with = "Vec<json_schema::ActorType>")]
+    pub actors: Vec<String>,
     /// The AI models which the component is allowed to access. For local execution, you must
     /// download all models; for hosted execution, you should check which models are available
     /// in your target environment.
@@ -521,6 +588,53 @@ pub struct Component {
     #[serde(default, skip_serializing_if = "Vec::is_empty")]
     #[schemars(with = "Vec<json_schema::AIModel>")]
     pub ai_models: Vec<String>,
+    /// The wasi-nn models which the component is allowed to load by name. Models are identified
+    /// by label e.g. "mobilenet", and must be mapped to a model directory in the runtime config.
+    ///
+    /// Example: `nn_models = ["mobilenet"]`
+    #[serde(
+        default,
+        with = "kebab_or_snake_case",
+        skip_serializing_if = "Vec::is_empty"
+    )]
+    #[schemars(with = "Vec<json_schema::NnModel>")]
+    pub nn_models: Vec<String>,
+    /// The keys which the component may use to sign and verify messages. Each key
+    /// names a component variable holding its material, which is never exposed to
+    /// the component, and the algorithm it is used with: one of "hmac-sha256",
+    /// "hmac-sha384", "hmac-sha512", "ed25519", "ecdsa-p256-sha256" or "rsa-pkcs1-sha256".
+    ///
+    /// Example: `crypto_keys = { webhook = { algorithm = "hmac-sha256", variable = "webhook_secret" } }`
+    #[serde(default, skip_serializing_if = "Map::is_empty")]
+    pub crypto_keys: Map<String, CryptoKey>,
+    /// The host extensions which the component is allowed to use. Host extensions
+    /// are provided by the runtime rather than by Spin itself, and may be configured
+    /// in the runtime config.
+    ///
+    /// Example: `host_extensions = ["acme-queue"]`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub host_extensions: Vec<String>,
+    /// Attributes describing the component in the telemetry it exports, such as
+    /// its version or the team that owns it. They are added to the
+    /// instrumentation scope of the component's spans, metrics and logs.
+    ///
+    /// Example: `telemetry_attributes = { "component.version" = "1.2.0", team = "payments" }`
+    #[serde(default, skip_serializing_if = "Map::is_empty")]
+    pub telemetry_attributes: Map<String, String>,
+    /// A base64-encoded Ed25519 signature of the component's Wasm source. If the
+    /// runtime config lists trusted component signing keys, the component is only
+    /// run if this is a valid signature by one of them.
+    ///
+    /// Example: `signature = "mT0xk4J8...=="`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signature: Option<String>,
+    /// Base64-encoded Ed25519 signatures of the Wasm sources of the component's
+    /// dependencies, by dependency name. If the runtime config lists trusted component
+    /// signing keys, each dependency must be signed like the component itself.
+    ///
+    /// Example: `dependency_signatures = { "my:dep/import" = "mT0xk4J8...==" }`
+    #[serde(default, skip_serializing_if = "Map::is_empty")]
+    pub dependency_signatures: Map<DependencyName, String>,
     /// The Spin environments with which the component must be compatible.
     /// If present, this overrides the default application targets (they are not combined).
     ///
@@ -555,6 +669,22 @@ pub struct Component {
     pub(crate) profile: Map<String, ComponentProfileOverride>,
 }
 
+/// A key which a component may use to sign and verify messages.
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct CryptoKey {
+    /// The algorithm with which the key is used.
+    ///
+    /// Example: `algorithm = "ed25519"`
+    pub algorithm: String,
+    /// The component variable holding the key material. For HMAC algorithms this
+    /// is the secret itself; for other algorithms it is a PEM-encoded PKCS#8
+    /// private key.
+    ///
+    /// Example: `variable = "signing_key"`
+    pub variable: LowerSnakeId,
+}
+
 /// Customisations for a Spin component in a non-default profile.
 #[derive(Clone, Debug, Serialize, Deserialize, JsonSchema)]
 #[serde(deny_unknown_fields)]
@@ -964,9 +1094,22 @@ mod tests {
             exclude_files: vec![],
             allowed_http_hosts: vec![],
             allowed_outbound_hosts: vec![],
+            tcp_listeners: vec![],
             key_value_stores: labels.clone(),
             sqlite_databases: labels,
+            blob_containers: vec![],
+            messaging_brokers: vec![],
+            jobs: vec![],
+            scheduled_components: vec![],
+            workflows: vec![],
+            actors: vec![],
             ai_models: vec![],
+            nn_models: vec![],
+            crypto_keys: Map::new(),
+            host_extensions: vec![],
+            telemetry_attributes: Map::new(),
+            signature: None,
+            dependency_signatures: Map::new(),
             targets: None,
             build: None,
             tool: Map::new(),
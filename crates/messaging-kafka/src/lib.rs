@@ -0,0 +1,138 @@
+use std::sync::Arc;
+
+use anyhow::Result;
+use futures::StreamExt as _;
+use rskafka::{
+    client::{
+        Client, ClientBuilder,
+        consumer::{StartOffset, StreamConsumerBuilder},
+        partition::{Compression, UnknownTopicHandling},
+    },
+    record::Record,
+};
+use serde::Deserialize;
+use spin_core::async_trait;
+use spin_factor_messaging::{
+    Broker, FormatSpec, Message, Subscription, runtime_config::spin::MakeMessagingBroker,
+};
+use tokio::sync::OnceCell;
+
+/// A messaging broker that uses Kafka.
+#[derive(Default)]
+pub struct KafkaMessagingBroker {
+    _priv: (),
+}
+
+impl KafkaMessagingBroker {
+    /// Creates a new `KafkaMessagingBroker`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+/// Runtime configuration for the Kafka messaging broker.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct KafkaMessagingRuntimeConfig {
+    /// The bootstrap brokers, e.g. `["localhost:9092"]`.
+    brokers: Vec<String>,
+    /// The partition of each topic to produce to and consume from.
+    #[serde(default)]
+    partition: i32,
+}
+
+impl MakeMessagingBroker for KafkaMessagingBroker {
+    const RUNTIME_CONFIG_TYPE: &'static str = "kafka";
+
+    type RuntimeConfig = KafkaMessagingRuntimeConfig;
+
+    type Broker = KafkaBroker;
+
+    fn make_broker(&self, runtime_config: Self::RuntimeConfig) -> Result<Self::Broker> {
+        anyhow::ensure!(
+            !runtime_config.brokers.is_empty(),
+            "at least one Kafka broker must be specified"
+        );
+        Ok(KafkaBroker {
+            brokers: runtime_config.brokers,
+            partition: runtime_config.partition,
+            client: OnceCell::new(),
+        })
+    }
+}
+
+/// A Kafka cluster used as a messaging broker.
+///
+/// Channels are topics, of which a single configured partition is used.
+/// Message metadata is carried in record headers. Subscriptions start from
+/// the latest offset, so only records produced after subscribing are seen.
+pub struct KafkaBroker {
+    brokers: Vec<String>,
+    partition: i32,
+    client: OnceCell<Client>,
+}
+
+impl KafkaBroker {
+    async fn client(&self) -> Result<&Client> {
+        self.client
+            .get_or_try_init(|| async {
+                Ok(ClientBuilder::new(self.brokers.clone()).build().await?)
+            })
+            .await
+    }
+}
+
+#[async_trait]
+impl Broker for KafkaBroker {
+    fn summary(&self) -> Option<String> {
+        Some(format!("Kafka at {}", self.brokers.join(",")))
+    }
+
+    async fn publish(&self, channel: &str, message: Message) -> Result<()> {
+        let partition_client = self
+            .client()
+            .await?
+            .partition_client(channel, self.partition, UnknownTopicHandling::Retry)
+            .await?;
+        let record = Record {
+            key: None,
+            value: Some(message.data),
+            headers: message
+                .metadata
+                .unwrap_or_default()
+                .into_iter()
+                .map(|(name, value)| (name, value.into_bytes()))
+                .collect(),
+            timestamp: chrono::Utc::now(),
+        };
+        partition_client
+            .produce(vec![record], Compression::NoCompression)
+            .await?;
+        Ok(())
+    }
+
+    async fn subscribe(&self, channel: &str) -> Result<Subscription> {
+        let partition_client = self
+            .client()
+            .await?
+            .partition_client(channel, self.partition, UnknownTopicHandling::Retry)
+            .await?;
+        let consumer =
+            StreamConsumerBuilder::new(Arc::new(partition_client), StartOffset::Latest).build();
+        let messages = consumer.map(|record| {
+            let (record, _high_watermark) = record?;
+            let record = record.record;
+            let metadata = record
+                .headers
+                .into_iter()
+                .map(|(name, value)| (name, String::from_utf8_lossy(&value).into_owned()))
+                .collect();
+            Ok(Message {
+                data: record.value.unwrap_or_default(),
+                format: FormatSpec::Kafka,
+                metadata: Some(metadata),
+            })
+        });
+        Ok(messages.boxed())
+    }
+}
@@ -0,0 +1,113 @@
+use anyhow::Result;
+use async_nats::{Client, ConnectOptions, HeaderMap};
+use futures::StreamExt as _;
+use serde::Deserialize;
+use spin_core::async_trait;
+use spin_factor_messaging::{
+    Broker, FormatSpec, Message, Subscription, runtime_config::spin::MakeMessagingBroker,
+};
+use tokio::sync::OnceCell;
+
+/// A messaging broker that uses NATS.
+#[derive(Default)]
+pub struct NatsMessagingBroker {
+    _priv: (),
+}
+
+impl NatsMessagingBroker {
+    /// Creates a new `NatsMessagingBroker`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+/// Runtime configuration for the NATS messaging broker.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct NatsMessagingRuntimeConfig {
+    /// The URL of the NATS server, e.g. "nats://localhost:4222".
+    url: String,
+    /// A token to authenticate with.
+    token: Option<String>,
+}
+
+impl MakeMessagingBroker for NatsMessagingBroker {
+    const RUNTIME_CONFIG_TYPE: &'static str = "nats";
+
+    type RuntimeConfig = NatsMessagingRuntimeConfig;
+
+    type Broker = NatsBroker;
+
+    fn make_broker(&self, runtime_config: Self::RuntimeConfig) -> Result<Self::Broker> {
+        Ok(NatsBroker {
+            url: runtime_config.url,
+            token: runtime_config.token,
+            client: OnceCell::new(),
+        })
+    }
+}
+
+/// A NATS server used as a messaging broker.
+///
+/// Message metadata is carried in NATS headers. The message format is not
+/// preserved.
+pub struct NatsBroker {
+    url: String,
+    token: Option<String>,
+    client: OnceCell<Client>,
+}
+
+impl NatsBroker {
+    async fn client(&self) -> Result<&Client> {
+        self.client
+            .get_or_try_init(|| async {
+                let mut options = ConnectOptions::new();
+                if let Some(token) = &self.token {
+                    options = options.token(token.clone());
+                }
+                Ok(options.connect(&self.url).await?)
+            })
+            .await
+    }
+}
+
+#[async_trait]
+impl Broker for NatsBroker {
+    fn summary(&self) -> Option<String> {
+        Some(format!("NATS at {}", self.url))
+    }
+
+    async fn publish(&self, channel: &str, message: Message) -> Result<()> {
+        let mut headers = HeaderMap::new();
+        for (name, value) in message.metadata.unwrap_or_default() {
+            headers.insert(name.as_str(), value.as_str());
+        }
+        self.client()
+            .await?
+            .publish_with_headers(channel.to_owned(), headers, message.data.into())
+            .await?;
+        Ok(())
+    }
+
+    async fn subscribe(&self, channel: &str) -> Result<Subscription> {
+        let subscriber = self.client().await?.subscribe(channel.to_owned()).await?;
+        let messages = subscriber.map(|msg| {
+            let metadata = msg.headers.map(|headers| {
+                headers
+                    .iter()
+                    .flat_map(|(name, values)| {
+                        values
+                            .iter()
+                            .map(move |value| (name.to_string(), value.as_str().to_owned()))
+                    })
+                    .collect()
+            });
+            Ok(Message {
+                data: msg.payload.to_vec(),
+                format: FormatSpec::Raw,
+                metadata,
+            })
+        });
+        Ok(messages.boxed())
+    }
+}
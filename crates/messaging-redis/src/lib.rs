@@ -0,0 +1,88 @@
+use anyhow::{Context as _, Result};
+use futures::StreamExt as _;
+use redis::{AsyncCommands as _, Client, aio::ConnectionManager};
+use serde::Deserialize;
+use spin_core::async_trait;
+use spin_factor_messaging::{
+    Broker, FormatSpec, Message, Subscription, runtime_config::spin::MakeMessagingBroker,
+};
+use tokio::sync::OnceCell;
+
+/// A messaging broker that uses Redis pub/sub.
+#[derive(Default)]
+pub struct RedisMessagingBroker {
+    _priv: (),
+}
+
+impl RedisMessagingBroker {
+    /// Creates a new `RedisMessagingBroker`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+/// Runtime configuration for the Redis messaging broker.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RedisMessagingRuntimeConfig {
+    /// The URL of the Redis server.
+    url: String,
+}
+
+impl MakeMessagingBroker for RedisMessagingBroker {
+    const RUNTIME_CONFIG_TYPE: &'static str = "redis";
+
+    type RuntimeConfig = RedisMessagingRuntimeConfig;
+
+    type Broker = RedisBroker;
+
+    fn make_broker(&self, runtime_config: Self::RuntimeConfig) -> Result<Self::Broker> {
+        let client = Client::open(runtime_config.url).context("Invalid Redis URL")?;
+        Ok(RedisBroker {
+            client,
+            connection: OnceCell::new(),
+        })
+    }
+}
+
+/// A Redis server used as a messaging broker.
+///
+/// Redis pub/sub carries only the message data, so message format and
+/// metadata are not preserved.
+pub struct RedisBroker {
+    client: Client,
+    connection: OnceCell<ConnectionManager>,
+}
+
+#[async_trait]
+impl Broker for RedisBroker {
+    fn summary(&self) -> Option<String> {
+        Some(format!(
+            "Redis at {}",
+            self.client.get_connection_info().addr
+        ))
+    }
+
+    async fn publish(&self, channel: &str, message: Message) -> Result<()> {
+        let mut connection = self
+            .connection
+            .get_or_try_init(|| self.client.get_connection_manager())
+            .await?
+            .clone();
+        let _: () = connection.publish(channel, message.data).await?;
+        Ok(())
+    }
+
+    async fn subscribe(&self, channel: &str) -> Result<Subscription> {
+        let mut pubsub = self.client.get_async_pubsub().await?;
+        pubsub.subscribe(channel).await?;
+        let messages = pubsub.into_on_message().map(|msg| {
+            Ok(Message {
+                data: msg.get_payload_bytes().to_vec(),
+                format: FormatSpec::Raw,
+                metadata: None,
+            })
+        });
+        Ok(messages.boxed())
+    }
+}
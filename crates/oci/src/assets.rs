@@ -0,0 +1,240 @@
+//! Offloading of large static assets to object storage when pushing apps.
+//!
+//! Rather than packing every file mount into OCI layers, large files can be
+//! uploaded to an object storage bucket. The locked app then records the URL
+//! each file can be fetched from, along with its digest, and the loader
+//! downloads such files into the cache when the app starts.
+
+use std::sync::Arc;
+
+use anyhow::{Context, Result, bail};
+use futures_util::TryStreamExt;
+use object_store::{ObjectStore, PutPayload, path::Path as ObjectPath};
+use reqwest::Url;
+use spin_common::sha256;
+use spin_loader::cache::Cache;
+use tokio::io::AsyncWriteExt;
+
+/// Default minimum size of a file, in bytes, for it to be offloaded to an
+/// asset store.
+pub const DEFAULT_ASSET_STORE_THRESHOLD: u64 = 1024 * 1024;
+
+/// An object storage bucket to which large file mounts are uploaded when
+/// pushing an app.
+pub struct AssetStore {
+    store: Arc<dyn ObjectStore>,
+    prefix: ObjectPath,
+    public_url: Url,
+    threshold: u64,
+}
+
+impl AssetStore {
+    /// Create an asset store for the given bucket URL (`s3://bucket/prefix`,
+    /// `gs://bucket/prefix` or `az://container/prefix`). Credentials are read
+    /// from the environment in the same way as the provider's own tools.
+    ///
+    /// Uploaded files are expected to be publicly readable under `public_url`,
+    /// which should correspond to the bucket URL's prefix. Files of at least
+    /// `threshold` bytes are offloaded.
+    pub fn new(bucket_url: &str, public_url: &str, threshold: u64) -> Result<Self> {
+        let bucket_url =
+            Url::parse(bucket_url).with_context(|| format!("invalid bucket URL {bucket_url:?}"))?;
+        let store: Arc<dyn ObjectStore> = match bucket_url.scheme() {
+            "s3" => Arc::new(
+                object_store::aws::AmazonS3Builder::from_env()
+                    .with_url(bucket_url.as_str())
+                    .build()?,
+            ),
+            "gs" => Arc::new(
+                object_store::gcp::GoogleCloudStorageBuilder::from_env()
+                    .with_url(bucket_url.as_str())
+                    .build()?,
+            ),
+            "az" => Arc::new(
+                object_store::azure::MicrosoftAzureBuilder::from_env()
+                    .with_url(bucket_url.as_str())
+                    .build()?,
+            ),
+            scheme => {
+                bail!("unsupported bucket URL scheme {scheme:?}: expected 's3', 'gs' or 'az'")
+            }
+        };
+        let prefix = ObjectPath::parse(bucket_url.path())
+            .with_context(|| format!("invalid prefix in bucket URL {bucket_url}"))?;
+        Self::with_store(store, prefix, public_url, threshold)
+    }
+
+    fn with_store(
+        store: Arc<dyn ObjectStore>,
+        prefix: ObjectPath,
+        public_url: &str,
+        threshold: u64,
+    ) -> Result<Self> {
+        let mut public_url =
+            Url::parse(public_url).with_context(|| format!("invalid public URL {public_url:?}"))?;
+        if !matches!(public_url.scheme(), "http" | "https") {
+            bail!("public URL {public_url} must be an http or https URL");
+        }
+        // Ensure that joining object names appends to, rather than replaces,
+        // the last path segment.
+        if !public_url.path().ends_with('/') {
+            public_url.set_path(&format!("{}/", public_url.path()));
+        }
+        Ok(Self {
+            store,
+            prefix,
+            public_url,
+            threshold,
+        })
+    }
+
+    /// Whether content of the given size should be offloaded.
+    pub(crate) fn should_offload(&self, size: usize) -> bool {
+        size as u64 >= self.threshold
+    }
+
+    /// Upload content with the given `sha256:<hex>` digest, unless an object
+    /// for that digest already exists, and return the URL it can be fetched
+    /// from.
+    pub(crate) async fn upload(&self, bytes: &[u8], digest: &str) -> Result<String> {
+        let name = object_name(digest)?;
+        let location: ObjectPath = self
+            .prefix
+            .parts()
+            .chain(ObjectPath::from(name.as_str()).parts())
+            .collect();
+        match self.store.head(&location).await {
+            Ok(_) => tracing::trace!("Asset {digest} already exists in asset store"),
+            Err(object_store::Error::NotFound { .. }) => {
+                tracing::trace!("Uploading asset {digest} to {location}");
+                self.store
+                    .put(&location, PutPayload::from(bytes.to_vec()))
+                    .await
+                    .with_context(|| format!("failed to upload asset {digest} to {location}"))?;
+            }
+            Err(e) => {
+                return Err(e).with_context(|| format!("failed to check for asset {location}"));
+            }
+        }
+        Ok(self.public_url.join(&name)?.to_string())
+    }
+}
+
+/// The name of the object holding content with the given digest, relative to
+/// the asset store prefix.
+fn object_name(digest: &str) -> Result<String> {
+    match digest.split_once(':') {
+        Some(("sha256", hex)) if !hex.is_empty() && hex.chars().all(|c| c.is_ascii_hexdigit()) => {
+            Ok(format!("sha256/{hex}"))
+        }
+        _ => bail!("unsupported asset digest {digest:?}"),
+    }
+}
+
+/// Whether the content source is a URL that [`fetch_asset`] can download.
+pub(crate) fn is_remote_source(source: &str) -> bool {
+    source.starts_with("https://") || source.starts_with("http://")
+}
+
+/// Download an offloaded asset into the cache, verifying its digest.
+pub(crate) async fn fetch_asset(cache: &Cache, url: &str, digest: &str) -> Result<()> {
+    let Some(expected) = digest.strip_prefix("sha256:") else {
+        bail!("unsupported asset digest {digest:?}");
+    };
+    tracing::debug!("Fetching asset {digest} from {url}");
+    let response = reqwest::get(url)
+        .await
+        .and_then(|r| r.error_for_status())
+        .with_context(|| format!("failed to fetch asset from {url}"))?;
+
+    // Stream to a temporary file in the cache, so that a failed or corrupt
+    // download never appears under the digest's name.
+    cache.ensure_dirs().await?;
+    let path = cache.data_path(digest);
+    let staging = tempfile::NamedTempFile::new_in(path.parent().context("invalid cache path")?)?;
+    let mut file = tokio::fs::File::from_std(staging.reopen()?);
+    let mut stream = response.bytes_stream();
+    while let Some(chunk) = stream
+        .try_next()
+        .await
+        .with_context(|| format!("failed to fetch asset from {url}"))?
+    {
+        file.write_all(&chunk).await?;
+    }
+    file.flush().await?;
+    drop(file);
+
+    let staging_path = staging.path().to_owned();
+    let actual =
+        tokio::task::spawn_blocking(move || sha256::hex_digest_from_file(staging_path)).await??;
+    if actual != expected {
+        bail!("asset fetched from {url} has digest sha256:{actual}, expected {digest}");
+    }
+    staging
+        .persist(&path)
+        .with_context(|| format!("failed to write asset to {}", path.display()))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use object_store::memory::InMemory;
+
+    use super::*;
+
+    fn asset_store(store: Arc<InMemory>, public_url: &str) -> AssetStore {
+        AssetStore::with_store(store, ObjectPath::from("site/assets"), public_url, 4).unwrap()
+    }
+
+    #[tokio::test]
+    async fn uploads_assets_by_digest() {
+        let store = Arc::new(InMemory::new());
+        let assets = asset_store(store.clone(), "https://cdn.example.com/assets");
+        let bytes = b"large enough";
+        let digest = format!("sha256:{}", sha256::hex_digest_from_bytes(bytes));
+
+        let url = assets.upload(bytes, &digest).await.unwrap();
+
+        let hex = digest.strip_prefix("sha256:").unwrap();
+        assert_eq!(url, format!("https://cdn.example.com/assets/sha256/{hex}"));
+        let location = ObjectPath::from(format!("site/assets/sha256/{hex}"));
+        let stored = store.get(&location).await.unwrap().bytes().await.unwrap();
+        assert_eq!(stored.as_ref(), bytes);
+
+        // Uploading the same content again is a no-op
+        assert_eq!(url, assets.upload(bytes, &digest).await.unwrap());
+    }
+
+    #[test]
+    fn offloads_only_large_content() {
+        let assets = asset_store(Arc::new(InMemory::new()), "https://cdn.example.com/");
+        assert!(!assets.should_offload(3));
+        assert!(assets.should_offload(4));
+    }
+
+    #[test]
+    fn rejects_invalid_urls_and_digests() {
+        assert!(AssetStore::new("ftp://bucket/prefix", "https://cdn.example.com", 1).is_err());
+        assert!(
+            AssetStore::with_store(
+                Arc::new(InMemory::new()),
+                ObjectPath::default(),
+                "file:///tmp",
+                1
+            )
+            .is_err()
+        );
+        assert!(object_name("md5:abc").is_err());
+        assert!(object_name("sha256:../x").is_err());
+    }
+
+    #[tokio::test]
+    async fn fetch_rejects_unsupported_digests() {
+        let cache_dir = tempfile::tempdir().unwrap();
+        let cache = Cache::new(Some(cache_dir.path().to_owned())).await.unwrap();
+        let err = fetch_asset(&cache, "https://cdn.example.com/x", "md5:abc")
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("unsupported asset digest"));
+    }
+}
@@ -1,7 +1,8 @@
 //! Spin's client for distributing applications via OCI registries
 
-use std::collections::{BTreeMap, HashMap};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::path::{Path, PathBuf};
+use std::sync::Arc;
 
 use anyhow::{Context, Result, bail};
 use docker_credential::DockerCredential;
@@ -23,6 +24,7 @@ use spin_locked_app::locked::{ContentPath, ContentRef, LockedApp, LockedComponen
 use tokio::fs;
 use walkdir::WalkDir;
 
+use crate::assets::AssetStore;
 use crate::auth::AuthConfig;
 use crate::validate;
 
@@ -96,6 +98,9 @@ pub struct Client {
 pub struct ClientOpts {
     /// Inline content into ContentRef iff < this size.
     pub content_ref_inline_max_size: usize,
+    /// Object storage to upload large file mounts to, rather than pushing
+    /// them as layers.
+    pub asset_store: Option<Arc<AssetStore>>,
 }
 
 /// Controls whether predefined annotations are generated when pushing an application.
@@ -116,6 +121,7 @@ impl Client {
         let cache = Cache::new(cache_root).await?;
         let opts = ClientOpts {
             content_ref_inline_max_size: DEFAULT_CONTENT_REF_INLINE_MAX_SIZE,
+            asset_store: None,
         };
 
         Ok(Self {
@@ -431,6 +437,7 @@ impl Client {
         layers: &mut Vec<ImageLayer>,
     ) -> Result<()> {
         // Add all archived file entries to the locked app manifest
+        let mut offloaded = HashSet::new();
         for entry in WalkDir::new(source) {
             let entry = entry?;
             if !entry.file_type().is_file() {
@@ -441,7 +448,10 @@ impl Client {
             tracing::trace!("Adding asset {rel_path:?} to component files list");
             // Add content/path to the locked component files list
             let layer = Self::data_layer(entry.path(), DATA_MEDIATYPE.to_string()).await?;
-            let content = self.content_ref_for_layer(&layer);
+            let mut content = self.content_ref_for_layer(&layer);
+            if self.offload(&layer, &mut content).await? {
+                offloaded.insert(rel_path.to_owned());
+            }
             files.push(ContentPath {
                 content,
                 path: rel_path.into(),
@@ -451,7 +461,7 @@ impl Client {
         // Only add the archive layer to the OCI manifest
         tracing::trace!("Adding archive layer for all files in source {:?}", &source);
         let working_dir = tempfile::tempdir()?;
-        let archive_path = crate::utils::archive(source, &working_dir.keep())
+        let archive_path = crate::utils::archive_excluding(source, &working_dir.keep(), offloaded)
             .await
             .context(format!(
                 "Unable to create compressed archive for source {source:?}"
@@ -485,8 +495,9 @@ impl Client {
             tracing::trace!("Adding new layer for asset {rel_path:?}");
             // Construct and push layer, adding its digest to the locked component files Vec
             let layer = Self::data_layer(entry.path(), DATA_MEDIATYPE.to_string()).await?;
-            let content = self.content_ref_for_layer(&layer);
+            let mut content = self.content_ref_for_layer(&layer);
             let content_inline = content.inline.is_some();
+            let content_offloaded = self.offload(&layer, &mut content).await?;
             files.push(ContentPath {
                 content,
                 path: rel_path,
@@ -494,7 +505,8 @@ impl Client {
             // As a workaround for OCI implementations that don't support very small blobs,
             // don't push very small content that has been inlined into the manifest:
             // https://github.com/distribution/distribution/discussions/4029
-            let skip_layer = content_inline;
+            // Content uploaded to the asset store is fetched from there instead.
+            let skip_layer = content_inline || content_offloaded;
             if !skip_layer {
                 layers.push(layer);
             }
@@ -573,6 +585,83 @@ impl Client {
         Ok(manifest)
     }
 
+    /// Push the contents of a directory to an OCI registry as a single
+    /// compressed archive layer, with an empty config of the given media type,
+    /// and return the digest (or None if the digest cannot be determined).
+    pub async fn push_directory(
+        &mut self,
+        dir: &Path,
+        reference: impl AsRef<str>,
+        config_media_type: &str,
+    ) -> Result<Option<String>> {
+        let reference: Reference = reference
+            .as_ref()
+            .parse()
+            .with_context(|| format!("cannot parse reference {}", reference.as_ref()))?;
+        let auth = Self::auth(&reference).await?;
+
+        let working_dir = tempfile::tempdir()?;
+        let archive_path = crate::utils::archive(dir, working_dir.path())
+            .await
+            .with_context(|| format!("cannot create archive of {}", quoted_path(dir)))?;
+        let layers = vec![Self::data_layer(&archive_path, ARCHIVE_MEDIATYPE.to_string()).await?];
+
+        let config = oci_distribution::client::Config::new(
+            b"{}".to_vec(),
+            config_media_type.to_string(),
+            None,
+        );
+        let manifest = OciImageManifest::build(&layers, &config, None);
+
+        let response = self
+            .oci
+            .push(&reference, &layers, config, &auth, Some(manifest))
+            .await
+            .map(|push_response| push_response.manifest_url)
+            .with_context(|| format!("cannot push {}", quoted_path(dir)))?;
+        tracing::info!("Pushed {:?}", response);
+
+        Ok(digest_from_url(&response))
+    }
+
+    /// Pull an artifact pushed by [`Client::push_directory`] from an OCI
+    /// registry and unpack its contents into `dest`.
+    pub async fn pull_directory(
+        &mut self,
+        reference: &str,
+        config_media_type: &str,
+        dest: &Path,
+    ) -> Result<()> {
+        let reference: Reference = reference.parse().context("cannot parse reference")?;
+        let auth = Self::auth(&reference).await?;
+
+        let (manifest, _) = self.oci.pull_image_manifest(&reference, &auth).await?;
+        if manifest.config.media_type != config_media_type {
+            bail!(
+                "{reference} has config media type {}, expected {config_media_type}",
+                manifest.config.media_type
+            );
+        }
+        let [layer] = manifest.layers.as_slice() else {
+            bail!(
+                "expected a single layer in {reference}, found {} layers",
+                manifest.layers.len()
+            );
+        };
+        if layer.media_type != ARCHIVE_MEDIATYPE {
+            bail!(
+                "{reference} has layer media type {}, expected {ARCHIVE_MEDIATYPE}",
+                layer.media_type
+            );
+        }
+
+        let mut bytes = Vec::with_capacity(layer.size.try_into()?);
+        self.oci.pull_blob(&reference, layer, &mut bytes).await?;
+        let archive = tempfile::NamedTempFile::new()?;
+        fs::write(archive.path(), &bytes).await?;
+        crate::utils::unarchive(archive.path(), dest).await
+    }
+
     /// Get the file path to an OCI manifest given a reference.
     /// If the directory for the manifest does not exist, this will create it.
     async fn manifest_path(&self, reference: impl AsRef<str>) -> Result<PathBuf> {
@@ -666,6 +755,23 @@ impl Client {
         }
     }
 
+    /// If an asset store is configured and the layer is large enough, upload
+    /// the layer's content to the store and record its URL as the content
+    /// source. Returns whether the content was offloaded.
+    async fn offload(&self, layer: &ImageLayer, content: &mut ContentRef) -> Result<bool> {
+        let Some(asset_store) = &self.opts.asset_store else {
+            return Ok(false);
+        };
+        if content.inline.is_some() || !asset_store.should_offload(layer.data.len()) {
+            return Ok(false);
+        }
+        let url = asset_store
+            .upload(&layer.data, &layer.sha256_digest())
+            .await?;
+        content.source = Some(url);
+        Ok(true)
+    }
+
     /// Save a credential set containing the registry username and password.
     pub async fn login(
         server: impl AsRef<str>,
@@ -1113,7 +1219,7 @@ mod test {
             },
             TestCase {
                 name: "One component layer and two file layers",
-                opts: Some(ClientOpts{content_ref_inline_max_size: 0}),
+                opts: Some(ClientOpts{content_ref_inline_max_size: 0, asset_store: None}),
                 locked_components: from_json!([{
                 "id": "component1",
                 "source": {
@@ -1159,7 +1265,7 @@ mod test {
             },
             TestCase {
                 name: "One component layer and one dependency component layer skipping composition",
-                opts: Some(ClientOpts{content_ref_inline_max_size: 0}),
+                opts: Some(ClientOpts{content_ref_inline_max_size: 0, asset_store: None}),
                 locked_components: from_json!([{
                 "id": "component1",
                 "source": {
@@ -1220,7 +1326,7 @@ mod test {
             },
             TestCase {
                 name: "Duplicate file paths",
-                opts: Some(ClientOpts{content_ref_inline_max_size: 0}),
+                opts: Some(ClientOpts{content_ref_inline_max_size: 0, asset_store: None}),
                 locked_components: from_json!([{
                 "id": "component1",
                 "source": {
@@ -1258,7 +1364,7 @@ mod test {
             },
             TestCase {
                 name: "One component layer and one dependency component layer with composition",
-                opts: Some(ClientOpts{content_ref_inline_max_size: 0}),
+                opts: Some(ClientOpts{content_ref_inline_max_size: 0, asset_store: None}),
                 locked_components: from_json!([{
                 "id": "component-with-deps",
                 "source": {
@@ -1,6 +1,7 @@
 //! OCI registries integration.
 #![deny(missing_docs)]
 
+pub mod assets;
 mod auth;
 pub mod client;
 mod loader;
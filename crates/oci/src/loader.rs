@@ -7,6 +7,7 @@ use spin_common::ui::quoted_path;
 use spin_loader::cache::Cache;
 use spin_locked_app::locked::{ContentPath, ContentRef, LockedApp, LockedComponent};
 
+use crate::assets::{fetch_asset, is_remote_source};
 use crate::{Client, ORIGIN_URL_SCHEME};
 
 /// OciLoader loads an OCI app in preparation for running with Spin.
@@ -107,7 +108,9 @@ impl OciLoader {
     /// This function assumes that:
     ///
     /// 1. All resolvable items have digest or inline sources.
-    /// 2. All digest-addressed content is already in the cache.
+    /// 2. All digest-addressed content is already in the cache, except for
+    ///    asset files offloaded to an asset store, which are fetched from
+    ///    their HTTP(S) source into the cache.
     ///
     /// If either of these is not true, the function errors.
     pub async fn resolve_component_content_refs(
@@ -152,6 +155,12 @@ impl OciLoader {
                 } else {
                     // Copy content
                     let digest = content_digest(&file.content)?;
+                    if let Some(source) = file.content.source.as_deref()
+                        && is_remote_source(source)
+                        && cache.data_file(digest).is_err()
+                    {
+                        fetch_asset(cache, source, digest).await?;
+                    }
                     let content_path = cache.data_file(digest)?;
                     // TODO: parallelize
                     tokio::fs::copy(&content_path, &mount_path)
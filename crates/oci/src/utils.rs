@@ -4,11 +4,23 @@ use anyhow::{Context, Result};
 use flate2::read::GzDecoder;
 use flate2::write::GzEncoder;
 use spin_common::ui::quoted_path;
+use std::collections::HashSet;
 use std::path::{Path, PathBuf};
 use tar::Archive;
+use walkdir::WalkDir;
 
 /// Create a compressed archive of source, returning its path in working_dir
 pub async fn archive(source: &Path, working_dir: &Path) -> Result<PathBuf> {
+    archive_excluding(source, working_dir, HashSet::new()).await
+}
+
+/// Create a compressed archive of source, omitting the files at the given
+/// paths relative to source, and return its path in working_dir
+pub async fn archive_excluding(
+    source: &Path,
+    working_dir: &Path,
+    excluded: HashSet<PathBuf>,
+) -> Result<PathBuf> {
     let source = source.to_owned();
     let working_dir = working_dir.to_owned();
 
@@ -28,7 +40,12 @@ pub async fn archive(source: &Path, working_dir: &Path) -> Result<PathBuf> {
 
         // Build tar archive
         let mut tar_builder = tar::Builder::new(tar_gz_enc);
-        tar_builder.append_dir_all(".", &source).context(format!(
+        if excluded.is_empty() {
+            tar_builder.append_dir_all(".", &source)
+        } else {
+            append_files_excluding(&mut tar_builder, &source, &excluded)
+        }
+        .context(format!(
             "Unable to create tar archive for source {}",
             quoted_path(&source)
         ))?;
@@ -42,6 +59,22 @@ pub async fn archive(source: &Path, working_dir: &Path) -> Result<PathBuf> {
     .await?
 }
 
+fn append_files_excluding<W: std::io::Write>(
+    tar_builder: &mut tar::Builder<W>,
+    source: &Path,
+    excluded: &HashSet<PathBuf>,
+) -> std::io::Result<()> {
+    for entry in WalkDir::new(source).min_depth(1) {
+        let entry = entry?;
+        // Can unwrap because we got to 'entry' from walking 'source'
+        let rel_path = entry.path().strip_prefix(source).unwrap();
+        if entry.file_type().is_file() && !excluded.contains(rel_path) {
+            tar_builder.append_path_with_name(entry.path(), Path::new(".").join(rel_path))?;
+        }
+    }
+    Ok(())
+}
+
 /// Unpack a compressed archive existing at source into dest
 pub async fn unarchive(source: &Path, dest: &Path) -> Result<()> {
     let source = source.to_owned();
@@ -19,6 +19,7 @@ pub type SharedFutureResult<T> = Shared<BoxFuture<'static, Result<Arc<T>, Arc<an
 pub struct OutboundAllowedHosts {
     allowed_hosts_future: SharedFutureResult<AllowedHostsConfig>,
     disallowed_host_handler: Option<Arc<dyn DisallowedHostHandler>>,
+    dry_run: bool,
 }
 
 impl OutboundAllowedHosts {
@@ -30,9 +31,19 @@ impl OutboundAllowedHosts {
         Self {
             allowed_hosts_future,
             disallowed_host_handler,
+            dry_run: false,
         }
     }
 
+    /// Sets whether disallowed hosts are only reported rather than blocked.
+    ///
+    /// In dry-run mode the checks still audit each destination and call the
+    /// [`DisallowedHostHandler`] for disallowed ones, but always allow them.
+    pub fn with_dry_run(mut self, dry_run: bool) -> Self {
+        self.dry_run = dry_run;
+        self
+    }
+
     /// Checks address against allowed hosts
     ///
     /// Calls the [`DisallowedHostHandler`] if set and URL is disallowed.
@@ -51,11 +62,17 @@ impl OutboundAllowedHosts {
 
         let allowed_hosts = self.resolve().await?;
         let is_allowed = allowed_hosts.allows(&url);
+        spin_telemetry::audit::event!(
+            "outbound.connect",
+            scheme = url.scheme(),
+            host = url.authority().as_str(),
+            allowed = is_allowed,
+        );
         if !is_allowed {
             tracing::debug!("Disallowed outbound networking request to '{url}'");
             self.report_disallowed_host(url.scheme(), &url.authority());
         }
-        Ok(is_allowed)
+        Ok(is_allowed || self.dry_run)
     }
 
     /// Checks if allowed hosts permit relative requests
@@ -66,6 +83,12 @@ impl OutboundAllowedHosts {
         tracing::debug!("Checking relative outbound networking request with schemes {schemes:?}");
         let allowed_hosts = self.resolve().await?;
         let is_allowed = allowed_hosts.allows_relative_url(schemes);
+        spin_telemetry::audit::event!(
+            "outbound.connect",
+            scheme = schemes.first().copied().unwrap_or_default(),
+            host = "self",
+            allowed = is_allowed,
+        );
         if !is_allowed {
             tracing::debug!(
                 "Disallowed relative outbound networking request with schemes {schemes:?}"
@@ -73,7 +96,7 @@ impl OutboundAllowedHosts {
             let scheme = schemes.first().unwrap_or(&"");
             self.report_disallowed_host(scheme, "self");
         }
-        Ok(is_allowed)
+        Ok(is_allowed || self.dry_run)
     }
 
     async fn resolve(&self) -> anyhow::Result<Arc<AllowedHostsConfig>> {
@@ -414,6 +437,8 @@ fn well_known_port(scheme: &str) -> Option<u16> {
         "mysql" => Some(3306),
         "redis" => Some(6379),
         "mqtt" => Some(1883),
+        "smtp" => Some(587),
+        "smtps" => Some(465),
         "http" => Some(80),
         "https" => Some(443),
         _ => None,
@@ -624,6 +649,10 @@ impl OutboundUrl {
         &self.scheme
     }
 
+    pub fn host(&self) -> &str {
+        &self.host
+    }
+
     pub fn authority(&self) -> String {
         if let Some(port) = self.port {
             format!("{}:{port}", self.host)
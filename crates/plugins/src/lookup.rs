@@ -132,6 +132,14 @@ pub async fn fetch_plugins_repo(
     update: bool,
 ) -> anyhow::Result<()> {
     let git_root = plugin_manifests_repo_path(plugins_dir);
+    if let Some(mirror) = crate::mirror::recorded_mirror(&git_root) {
+        // The catalogue was copied from a mirror rather than cloned. Refresh it
+        // if the mirror is still there, but never reach out to the repository.
+        if update && mirror.is_dir() {
+            crate::mirror::use_mirror(plugins_dir, &mirror)?;
+        }
+        return Ok(());
+    }
     let git_source = GitSource::new(repo_url, None, &git_root);
     if accept_as_repo(&git_root) {
         if update {
@@ -143,7 +151,7 @@ pub async fn fetch_plugins_repo(
     Ok(())
 }
 
-fn plugin_manifests_repo_path(plugins_dir: &Path) -> PathBuf {
+pub(crate) fn plugin_manifests_repo_path(plugins_dir: &Path) -> PathBuf {
     plugins_dir.join(PLUGINS_REPO_LOCAL_DIRECTORY)
 }
 
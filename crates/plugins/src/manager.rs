@@ -386,7 +386,7 @@ async fn download_plugin(
     Ok(plugin_file)
 }
 
-fn verify_checksum(plugin_file: &Path, expected_sha256: &str) -> Result<()> {
+pub(crate) fn verify_checksum(plugin_file: &Path, expected_sha256: &str) -> Result<()> {
     let actual_sha256 = sha256::hex_digest_from_file(plugin_file)
         .with_context(|| format!("Cannot get digest for {}", plugin_file.display()))?;
     if actual_sha256 == expected_sha256 {
@@ -10,7 +10,7 @@ use crate::PluginStore;
 /// Expected schema of a plugin manifest. Should match the latest Spin plugin
 /// manifest JSON schema:
 /// <https://github.com/spinframework/spin-plugins/tree/main/json-schema>
-#[derive(Serialize, Debug, Deserialize, PartialEq)]
+#[derive(Clone, Serialize, Debug, Deserialize, PartialEq)]
 #[serde(rename_all = "camelCase")]
 pub struct PluginManifest {
     /// Name of the plugin.
@@ -83,7 +83,7 @@ impl PluginManifest {
 }
 
 /// Describes compatibility and location of a plugin source.
-#[derive(Serialize, Debug, Deserialize, PartialEq)]
+#[derive(Clone, Serialize, Debug, Deserialize, PartialEq)]
 pub struct PluginPackage {
     /// Compatible OS.
     pub(crate) os: Os,
@@ -106,7 +106,7 @@ impl PluginPackage {
 }
 
 /// Describes the compatible OS of a plugin
-#[derive(Serialize, Debug, Deserialize, PartialEq)]
+#[derive(Clone, Serialize, Debug, Deserialize, PartialEq)]
 #[serde(rename_all = "camelCase")]
 pub(crate) enum Os {
     Linux,
@@ -127,7 +127,7 @@ impl Os {
 }
 
 /// Describes the compatible architecture of a plugin
-#[derive(Serialize, Debug, Deserialize, PartialEq)]
+#[derive(Clone, Serialize, Debug, Deserialize, PartialEq)]
 #[serde(rename_all = "camelCase")]
 pub(crate) enum Architecture {
     Amd64,
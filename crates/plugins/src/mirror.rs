@@ -0,0 +1,324 @@
+//! Offline mirrors of the plugins catalogue.
+//!
+//! A mirror has the same `manifests/<name>/<name>[@<version>].json` layout as
+//! the spin-plugins repository, plus a `packages` directory holding the plugin
+//! tarballs that the manifests refer to. Package URLs in a mirror are relative
+//! to the mirror root, so that the mirror can be moved between machines.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result, anyhow, bail};
+use url::Url;
+
+use crate::{
+    PluginStore,
+    lookup::{PLUGINS_REPO_MANIFESTS_DIRECTORY, plugin_manifests_repo_path},
+    manager::verify_checksum,
+    manifest::PluginManifest,
+    store::manifest_file_name,
+};
+
+/// Directory in a mirror containing the plugin packages.
+const MIRROR_PACKAGES_DIRECTORY: &str = "packages";
+/// File recording the mirror from which a local catalogue was copied.
+const MIRROR_RECORD_FILE_NAME: &str = ".mirror";
+
+/// Exports the installed plugins to a mirror at `dest`. The package for each
+/// plugin is copied or downloaded from its original location and verified
+/// against its checksum. If `all_platforms` is false, only packages for the
+/// current OS and architecture are exported.
+///
+/// Returns the manifests of the exported plugins.
+pub async fn export(
+    store: &PluginStore,
+    dest: &Path,
+    all_platforms: bool,
+) -> Result<Vec<PluginManifest>> {
+    let packages_dir = dest.join(MIRROR_PACKAGES_DIRECTORY);
+    fs::create_dir_all(&packages_dir)
+        .with_context(|| format!("Failed to create directory {}", packages_dir.display()))?;
+
+    let mut exported = vec![];
+    for manifest in store.installed_manifests()? {
+        let name = manifest.name();
+        let mut mirrored = manifest.clone();
+        mirrored
+            .packages
+            .retain(|p| all_platforms || p.matches_current_os_arch());
+        for package in &mut mirrored.packages {
+            let file_name = format!(
+                "{name}-{}-{}-{}.tar.gz",
+                manifest.version(),
+                package.os.rust_name(),
+                package.arch.rust_name()
+            );
+            let package_path = packages_dir.join(&file_name);
+            fetch_package(&package.url, &package_path)
+                .await
+                .with_context(|| format!("Failed to export package for plugin '{name}'"))?;
+            verify_checksum(&package_path, &package.sha256)?;
+            package.url = format!("{MIRROR_PACKAGES_DIRECTORY}/{file_name}");
+        }
+
+        let manifest_dir = dest.join(PLUGINS_REPO_MANIFESTS_DIRECTORY).join(&name);
+        fs::create_dir_all(&manifest_dir)
+            .with_context(|| format!("Failed to create directory {}", manifest_dir.display()))?;
+        let manifest_text = serde_json::to_string_pretty(&mirrored)?;
+        // As in the plugins repository, the unversioned file is the latest version.
+        for file_name in [
+            manifest_file_name(&name),
+            format!("{name}@{}.json", manifest.version()),
+        ] {
+            fs::write(manifest_dir.join(file_name), &manifest_text)?;
+        }
+        exported.push(mirrored);
+    }
+    Ok(exported)
+}
+
+async fn fetch_package(url: &str, dest: &Path) -> Result<()> {
+    let url = Url::parse(url).with_context(|| format!("Invalid package URL {url}"))?;
+    if url.scheme() == "file" {
+        let path = url
+            .to_file_path()
+            .map_err(|_| anyhow!("Invalid file URL: {url}"))?;
+        fs::copy(&path, dest).with_context(|| format!("Failed to copy {}", path.display()))?;
+    } else {
+        tracing::trace!("Downloading plugin package from {url}");
+        let bytes = reqwest::get(url.clone())
+            .await
+            .and_then(|r| r.error_for_status())
+            .with_context(|| format!("Failed to download {url}"))?
+            .bytes()
+            .await
+            .with_context(|| format!("Failed to download {url}"))?;
+        fs::write(dest, bytes)?;
+    }
+    Ok(())
+}
+
+/// Replaces the local plugins catalogue under `plugins_dir` with a copy of the
+/// mirror at `mirror`, so that plugins can be looked up and installed without
+/// access to the spin-plugins repository. The catalogue is refreshed from the
+/// same mirror by later updates for as long as the mirror exists.
+pub fn use_mirror(plugins_dir: &Path, mirror: &Path) -> Result<()> {
+    let mirror = mirror
+        .canonicalize()
+        .with_context(|| format!("Mirror {} does not exist", mirror.display()))?;
+    let mirror_manifests_dir = mirror.join(PLUGINS_REPO_MANIFESTS_DIRECTORY);
+    if !mirror_manifests_dir.is_dir() {
+        bail!(
+            "{} is not a plugins mirror: it has no '{PLUGINS_REPO_MANIFESTS_DIRECTORY}' directory",
+            mirror.display()
+        );
+    }
+
+    let catalogue_dir = plugin_manifests_repo_path(plugins_dir);
+    let stage_dir = catalogue_dir.with_extension("stage");
+    if stage_dir.exists() {
+        fs::remove_dir_all(&stage_dir)?;
+    }
+
+    // Copy the packages first, so that the manifests can refer to them.
+    let stage_packages_dir = stage_dir.join(MIRROR_PACKAGES_DIRECTORY);
+    fs::create_dir_all(&stage_packages_dir)?;
+    for package in files_in(&mirror.join(MIRROR_PACKAGES_DIRECTORY))? {
+        // Can unwrap because files_in only returns files
+        fs::copy(
+            &package,
+            stage_packages_dir.join(package.file_name().unwrap()),
+        )
+        .with_context(|| format!("Failed to copy {}", package.display()))?;
+    }
+
+    for plugin_dir in subdirectories(&mirror_manifests_dir)? {
+        // Can unwrap because subdirectories only returns named directories
+        let plugin_name = plugin_dir.file_name().unwrap();
+        let stage_plugin_dir = stage_dir
+            .join(PLUGINS_REPO_MANIFESTS_DIRECTORY)
+            .join(plugin_name);
+        fs::create_dir_all(&stage_plugin_dir)?;
+        for manifest_path in files_in(&plugin_dir)? {
+            let text = fs::read(&manifest_path)?;
+            let mut manifest: PluginManifest = serde_json::from_slice(&text)
+                .with_context(|| format!("Invalid plugin manifest {}", manifest_path.display()))?;
+            for package in &mut manifest.packages {
+                package.url = resolve_package_url(&package.url, &catalogue_dir)?;
+            }
+            fs::write(
+                stage_plugin_dir.join(manifest_path.file_name().unwrap()),
+                serde_json::to_string_pretty(&manifest)?,
+            )?;
+        }
+    }
+
+    fs::write(
+        stage_dir.join(MIRROR_RECORD_FILE_NAME),
+        mirror.to_string_lossy().as_bytes(),
+    )?;
+
+    if catalogue_dir.exists() {
+        fs::remove_dir_all(&catalogue_dir).with_context(|| {
+            format!(
+                "Failed to remove plugins catalogue {}",
+                catalogue_dir.display()
+            )
+        })?;
+    }
+    fs::rename(&stage_dir, &catalogue_dir)?;
+    Ok(())
+}
+
+/// Removes the local plugins catalogue if it was copied from a mirror, so that
+/// the next lookup fetches the spin-plugins repository. Returns whether a
+/// mirrored catalogue was removed.
+pub fn stop_using_mirror(plugins_dir: &Path) -> Result<bool> {
+    let catalogue_dir = plugin_manifests_repo_path(plugins_dir);
+    if recorded_mirror(&catalogue_dir).is_none() {
+        return Ok(false);
+    }
+    fs::remove_dir_all(&catalogue_dir)?;
+    Ok(true)
+}
+
+/// Gets the mirror from which the catalogue in `catalogue_dir` was copied, or
+/// None if the catalogue was cloned from a repository.
+pub(crate) fn recorded_mirror(catalogue_dir: &Path) -> Option<PathBuf> {
+    fs::read_to_string(catalogue_dir.join(MIRROR_RECORD_FILE_NAME))
+        .ok()
+        .map(PathBuf::from)
+}
+
+/// Mirrored manifests refer to packages relative to the mirror root: make
+/// those refer to the copies in the catalogue. Absolute URLs are kept as is.
+fn resolve_package_url(url: &str, catalogue_dir: &Path) -> Result<String> {
+    match Url::parse(url) {
+        Ok(_) => Ok(url.to_owned()),
+        Err(url::ParseError::RelativeUrlWithoutBase) => {
+            let relative = Path::new(url);
+            if !relative
+                .components()
+                .all(|c| matches!(c, std::path::Component::Normal(_)))
+            {
+                bail!("Invalid package path {url} in mirror");
+            }
+            let url = Url::from_file_path(catalogue_dir.join(relative))
+                .map_err(|_| anyhow!("Cannot build file URL for package {url}"))?;
+            Ok(url.to_string())
+        }
+        Err(e) => Err(e).with_context(|| format!("Invalid package URL {url}")),
+    }
+}
+
+fn subdirectories(dir: &Path) -> Result<Vec<PathBuf>> {
+    entries_in(dir, |p| p.is_dir())
+}
+
+fn files_in(dir: &Path) -> Result<Vec<PathBuf>> {
+    if !dir.exists() {
+        return Ok(vec![]);
+    }
+    entries_in(dir, |p| p.is_file())
+}
+
+fn entries_in(dir: &Path, filter: impl Fn(&Path) -> bool) -> Result<Vec<PathBuf>> {
+    let entries = dir
+        .read_dir()
+        .with_context(|| format!("Failed to read {}", dir.display()))?;
+    Ok(entries
+        .filter_map(|de| de.ok())
+        .map(|de| de.path())
+        .filter(|p| filter(p))
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use spin_common::sha256;
+    use tempfile::tempdir;
+
+    use super::*;
+    use crate::lookup::PluginLookup;
+
+    const PLUGIN_NAME: &str = "mirrored";
+
+    // Installs a manifest for a plugin whose package is a local file. Only
+    // the manifest is needed for export.
+    fn install_plugin(store: &PluginStore, package_dir: &Path) {
+        let package_path = package_dir.join("mirrored.tar.gz");
+        fs::write(&package_path, b"not really a tarball").unwrap();
+        let manifest: PluginManifest = serde_json::from_value(serde_json::json!({
+            "name": PLUGIN_NAME,
+            "version": "1.2.3",
+            "spinCompatibility": ">=2.0",
+            "license": "Apache-2.0",
+            "packages": [{
+                "os": current_os(),
+                "arch": current_arch(),
+                "url": Url::from_file_path(&package_path).unwrap().to_string(),
+                "sha256": sha256::hex_digest_from_file(&package_path).unwrap(),
+            }]
+        }))
+        .unwrap();
+        store.add_manifest(&manifest).unwrap();
+    }
+
+    fn current_os() -> &'static str {
+        match std::env::consts::OS {
+            "macos" => "macos",
+            "windows" => "windows",
+            _ => "linux",
+        }
+    }
+
+    fn current_arch() -> &'static str {
+        match std::env::consts::ARCH {
+            "aarch64" => "aarch64",
+            "arm" => "arm",
+            _ => "amd64",
+        }
+    }
+
+    #[tokio::test]
+    async fn can_install_from_exported_mirror() {
+        let installed_dir = tempdir().unwrap();
+        let package_dir = tempdir().unwrap();
+        let store = PluginStore::new(installed_dir.path());
+        install_plugin(&store, package_dir.path());
+
+        let mirror_dir = tempdir().unwrap();
+        let exported = export(&store, mirror_dir.path(), false).await.unwrap();
+        assert_eq!(1, exported.len());
+        let package_url = &exported[0].packages[0].url;
+        assert!(package_url.starts_with("packages/"), "{package_url}");
+        assert!(mirror_dir.path().join(package_url).is_file());
+
+        let offline_dir = tempdir().unwrap();
+        use_mirror(offline_dir.path(), mirror_dir.path()).unwrap();
+        let manifest = PluginLookup::new(PLUGIN_NAME, None)
+            .resolve_manifest_exact(offline_dir.path())
+            .await
+            .unwrap();
+        assert_eq!("1.2.3", manifest.version());
+        let package_url = Url::parse(&manifest.packages[0].url).unwrap();
+        let package_path = package_url.to_file_path().unwrap();
+        assert!(package_path.starts_with(offline_dir.path()));
+        verify_checksum(&package_path, &manifest.packages[0].sha256).unwrap();
+
+        assert!(stop_using_mirror(offline_dir.path()).unwrap());
+        assert!(!stop_using_mirror(offline_dir.path()).unwrap());
+    }
+
+    #[test]
+    fn rejects_escaping_package_paths() {
+        let catalogue_dir = tempdir().unwrap();
+        assert!(resolve_package_url("../outside.tar.gz", catalogue_dir.path()).is_err());
+        assert_eq!(
+            "https://example.com/p.tar.gz",
+            resolve_package_url("https://example.com/p.tar.gz", catalogue_dir.path()).unwrap()
+        );
+    }
+}
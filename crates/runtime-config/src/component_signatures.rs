@@ -0,0 +1,35 @@
+use serde::Deserialize;
+use spin_trigger::cli::SignaturesRequired;
+
+/// Component signing settings from the `[component_signatures]` runtime config
+/// table.
+///
+/// Expects table to be in the format:
+/// ```toml
+/// [component_signatures]
+/// # Base64-encoded Ed25519 public keys
+/// trusted_keys = ["9Nf1mUSYi6m0yzrP6wOVV2VDP5ms3hDxhxaOmm0dBEk="]
+/// # Optional: only require signatures of apps pulled from a registry
+/// require = "registry"
+/// ```
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ComponentSignaturesConfig {
+    /// The keys components may be signed by.
+    pub trusted_keys: Vec<String>,
+    /// Which apps must have signed components.
+    #[serde(default)]
+    pub require: SignaturesRequired,
+}
+
+impl ComponentSignaturesConfig {
+    pub(crate) fn from_toml(toml: Option<&toml::Value>) -> anyhow::Result<Option<Self>> {
+        let Some(toml) = toml else {
+            return Ok(None);
+        };
+        toml.clone()
+            .try_into()
+            .map(Some)
+            .map_err(|e| anyhow::anyhow!("invalid `[component_signatures]` runtime config: {e}"))
+    }
+}
@@ -1,11 +1,22 @@
 use std::path::{Path, PathBuf};
 
 use anyhow::Context as _;
+use spin_blobstore_object_store::{FileBlobContainer, FileBlobContainerRuntimeConfig};
 use spin_common::ui::quoted_path;
+use spin_factor_actors::ActorsFactor;
+use spin_factor_blobstore::BlobStoreFactor;
+use spin_factor_blobstore::runtime_config::spin::{self as blobstore};
+use spin_factor_capability_policy::CapabilityPolicyFactor;
+use spin_factor_crypto::CryptoFactor;
+use spin_factor_host_extensions::HostExtensionsFactor;
+use spin_factor_jobs::JobsFactor;
 use spin_factor_key_value::KeyValueFactor;
 use spin_factor_key_value::runtime_config::spin::{self as key_value};
 use spin_factor_llm::{LlmFactor, spin as llm};
+use spin_factor_messaging::MessagingFactor;
+use spin_factor_messaging::runtime_config::spin::{self as messaging};
 use spin_factor_otel::OtelFactor;
+use spin_factor_outbound_email::OutboundEmailFactor;
 use spin_factor_outbound_http::OutboundHttpFactor;
 use spin_factor_outbound_mqtt::OutboundMqttFactor;
 use spin_factor_outbound_mysql::OutboundMysqlFactor;
@@ -16,19 +27,23 @@ use spin_factor_outbound_redis::OutboundRedisFactor;
 use spin_factor_sqlite::SqliteFactor;
 use spin_factor_variables::VariablesFactor;
 use spin_factor_wasi::WasiFactor;
+use spin_factor_wasi_nn::WasiNnFactor;
+use spin_factor_workflows::WorkflowsFactor;
 use spin_factors::runtime_config::toml::GetTomlValue as _;
 use spin_factors::{
     FactorRuntimeConfigSource, RuntimeConfigSourceFinalizer, runtime_config::toml::TomlKeyTracker,
 };
 use spin_key_value_spin::{SpinKeyValueRuntimeConfig, SpinKeyValueStore};
 use spin_sqlite as sqlite;
-use spin_trigger::cli::UserProvidedPath;
+use spin_trigger::cli::{SeedConfig, UserProvidedPath};
 use toml::Value;
 
+pub mod component_signatures;
 pub mod variables;
+pub mod wasmtime;
 
 /// The default state directory for the trigger.
-pub const DEFAULT_STATE_DIR: &str = ".spin";
+pub const DEFAULT_STATE_DIR: &str = spin_common::app_state::STATE_DIR_NAME;
 
 /// A runtime configuration which has been resolved from a runtime config source.
 ///
@@ -50,6 +65,10 @@ pub struct ResolvedRuntimeConfig<T> {
     pub log_dir: Option<PathBuf>,
     /// The maximum memory allocation limit.
     pub max_instance_memory: Option<usize>,
+    /// The keys components must be signed by, if any.
+    pub component_signatures: Option<component_signatures::ComponentSignaturesConfig>,
+    /// Data to seed the app's stores and databases with, from the `[seed]` table.
+    pub seed: Option<SeedConfig>,
     /// The input TOML, for informational summaries.
     pub toml: toml::Table,
 }
@@ -73,6 +92,22 @@ impl<T> ResolvedRuntimeConfig<T> {
         summaries.extend(summarize_labeled_typed_tables("key_value_store"));
         // [sqlite_database.<label>: <type>]
         summaries.extend(summarize_labeled_typed_tables("sqlite_database"));
+        // [blob_container.<label>: <type>]
+        summaries.extend(summarize_labeled_typed_tables("blob_container"));
+        // [messaging_broker.<label>: <type>]
+        summaries.extend(summarize_labeled_typed_tables("messaging_broker"));
+        // [nn_model.<label>: <backend>]
+        if let Some(tables) = self.toml.get("nn_model").and_then(Value::as_table) {
+            for (label, config) in tables {
+                if let Some(backend) = config.get("backend").and_then(Value::as_str) {
+                    summaries.push(format!("[nn_model.{label}: {backend}]"));
+                }
+            }
+        }
+        // [timezone: <iana id>]
+        if let Some(timezone) = self.toml.get("timezone").and_then(Value::as_str) {
+            summaries.push(format!("[timezone: {timezone}]"));
+        }
         // [llm_compute: <type>]
         if let Some(table) = self.toml.get("llm_compute").and_then(Value::as_table) {
             if let Some(ty) = table.get("type").and_then(Value::as_str) {
@@ -87,6 +122,43 @@ impl<T> ResolvedRuntimeConfig<T> {
             eprintln!("Using runtime config {summaries} {from_path}");
         }
     }
+
+    /// The labels of the key-value stores held on this host's filesystem,
+    /// including the default stores unless the runtime config replaces them.
+    pub fn local_key_value_stores(&self) -> Vec<String> {
+        self.local_labels("key_value_store", "spin", DEFAULT_KEY_VALUE_STORE_LABELS)
+    }
+
+    /// The labels of the SQLite databases held on this host's filesystem or in
+    /// memory, including the default database unless the runtime config
+    /// replaces it.
+    pub fn local_sqlite_databases(&self) -> Vec<String> {
+        self.local_labels("sqlite_database", "spin", &[DEFAULT_SQLITE_DATABASE_LABEL])
+    }
+
+    /// The labels of the blob containers held on this host's filesystem,
+    /// including the default container unless the runtime config replaces it.
+    pub fn local_blob_containers(&self) -> Vec<String> {
+        self.local_labels("blob_container", "file", &[DEFAULT_BLOB_CONTAINER_LABEL])
+    }
+
+    /// The labels in the `[<key>.<label>]` tables of type `local_type`, plus
+    /// the default labels which aren't configured.
+    fn local_labels(&self, key: &str, local_type: &str, default_labels: &[&str]) -> Vec<String> {
+        let tables = self.toml.get(key).and_then(Value::as_table);
+        let mut labels = vec![];
+        for default_label in default_labels {
+            if tables.is_none_or(|tables| !tables.contains_key(*default_label)) {
+                labels.push((*default_label).to_owned());
+            }
+        }
+        for (label, config) in tables.into_iter().flatten() {
+            if config.get("type").and_then(Value::as_str) == Some(local_type) {
+                labels.push(label.clone());
+            }
+        }
+        labels
+    }
 }
 
 impl<T> ResolvedRuntimeConfig<T>
@@ -97,31 +169,20 @@ where
     /// Creates a new resolved runtime configuration from a runtime config source TOML file.
     ///
     /// `provided_state_dir` is the explicitly provided state directory, if any.
+    ///
+    /// `app_id` is the identity of the app, used to namespace its default
+    /// state directory (see [`spin_common::app_state::default_state_dir`]).
     pub fn from_file(
         runtime_config_path: Option<&Path>,
         local_app_dir: Option<PathBuf>,
+        app_id: Option<String>,
         provided_state_dir: UserProvidedPath,
         provided_log_dir: UserProvidedPath,
     ) -> anyhow::Result<Self> {
-        let toml = match runtime_config_path {
-            Some(runtime_config_path) => {
-                let file = std::fs::read_to_string(runtime_config_path).with_context(|| {
-                    format!(
-                        "failed to read runtime config file '{}'",
-                        runtime_config_path.display()
-                    )
-                })?;
-                toml::from_str(&file).with_context(|| {
-                    format!(
-                        "failed to parse runtime config file '{}' as toml",
-                        runtime_config_path.display()
-                    )
-                })?
-            }
-            None => Default::default(),
-        };
+        let toml = read_toml(runtime_config_path)?;
         let toml_resolver =
-            TomlResolver::new(&toml, local_app_dir, provided_state_dir, provided_log_dir);
+            TomlResolver::new(&toml, local_app_dir, provided_state_dir, provided_log_dir)
+                .with_app_id(app_id);
 
         Self::new(toml_resolver, runtime_config_path)
     }
@@ -138,19 +199,31 @@ where
         let outbound_networking = runtime_config_dir
             .clone()
             .map(OutboundNetworkingSpinRuntimeConfig::new);
-        let key_value_resolver = key_value_config_resolver(runtime_config_dir, state_dir.clone());
+        let key_value_resolver =
+            key_value_config_resolver(runtime_config_dir.clone(), state_dir.clone());
         let sqlite_resolver = sqlite_config_resolver(state_dir.clone())
             .context("failed to resolve sqlite runtime config")?;
+        let blobstore_resolver =
+            blobstore_config_resolver(runtime_config_dir.clone(), state_dir.clone());
+        let messaging_resolver = messaging_config_resolver();
 
         let toml = toml_resolver.toml();
         let log_dir = toml_resolver.log_dir()?;
         let max_instance_memory = toml_resolver.max_instance_memory()?;
+        let component_signatures = toml_resolver.component_signatures()?;
+        let seed = toml_resolver.seed(runtime_config_dir.as_deref())?;
+        // The Wasmtime config is applied when the engine is built, before the
+        // runtime config is resolved, but is validated here too.
+        toml_resolver.wasmtime_config()?;
 
         let source = TomlRuntimeConfigSource::new(
             toml_resolver,
+            runtime_config_dir.as_deref(),
             &key_value_resolver,
             outbound_networking.as_ref(),
             &sqlite_resolver,
+            &blobstore_resolver,
+            &messaging_resolver,
         );
 
         // Note: all valid fields in the runtime config must have been referenced at
@@ -165,6 +238,8 @@ where
             state_dir,
             log_dir,
             max_instance_memory,
+            component_signatures,
+            seed,
             toml,
         })
     }
@@ -183,6 +258,35 @@ where
     pub fn max_instance_memory(&self) -> Option<usize> {
         self.max_instance_memory
     }
+
+    /// The keys components must be signed by, if any.
+    pub fn component_signatures(&self) -> Option<&component_signatures::ComponentSignaturesConfig> {
+        self.component_signatures.as_ref()
+    }
+
+    /// The data to seed the app's stores and databases with, if any.
+    pub fn seed(&self) -> Option<&SeedConfig> {
+        self.seed.as_ref()
+    }
+}
+
+/// Reads and parses a runtime config file, or returns an empty table if there is none.
+fn read_toml(runtime_config_path: Option<&Path>) -> anyhow::Result<toml::Table> {
+    let Some(runtime_config_path) = runtime_config_path else {
+        return Ok(Default::default());
+    };
+    let file = std::fs::read_to_string(runtime_config_path).with_context(|| {
+        format!(
+            "failed to read runtime config file '{}'",
+            runtime_config_path.display()
+        )
+    })?;
+    toml::from_str(&file).with_context(|| {
+        format!(
+            "failed to parse runtime config file '{}' as toml",
+            runtime_config_path.display()
+        )
+    })
 }
 
 #[derive(Clone, Debug)]
@@ -191,6 +295,8 @@ pub struct TomlResolver<'a> {
     table: TomlKeyTracker<'a>,
     /// The local app directory.
     local_app_dir: Option<PathBuf>,
+    /// The identity of the app, used to namespace the default state directory.
+    app_id: Option<String>,
     /// Explicitly provided state directory.
     state_dir: UserProvidedPath,
     /// Explicitly provided log directory.
@@ -208,11 +314,18 @@ impl<'a> TomlResolver<'a> {
         Self {
             table: TomlKeyTracker::new(table),
             local_app_dir,
+            app_id: None,
             state_dir,
             log_dir,
         }
     }
 
+    /// Set the identity of the app, used to namespace the default state directory.
+    pub fn with_app_id(mut self, app_id: Option<String>) -> Self {
+        self.app_id = app_id;
+        self
+    }
+
     /// Get the configured state_directory.
     ///
     /// Errors if the path cannot be converted to an absolute path.
@@ -239,9 +352,9 @@ impl<'a> TomlResolver<'a> {
 
         match (state_dir, &self.local_app_dir) {
             (UserProvidedPath::Provided(p), _) => Ok(Some(std::path::absolute(p)?)),
-            (UserProvidedPath::Default, Some(local_app_dir)) => {
-                Ok(Some(local_app_dir.join(".spin")))
-            }
+            (UserProvidedPath::Default, Some(local_app_dir)) => Ok(Some(
+                spin_common::app_state::default_state_dir(local_app_dir, self.app_id.as_deref())?,
+            )),
             (UserProvidedPath::Default | UserProvidedPath::Unset, _) => Ok(None),
         }
     }
@@ -287,6 +400,31 @@ impl<'a> TomlResolver<'a> {
             .map_err(Into::into)
     }
 
+    /// Get the configured component signing settings.
+    pub fn component_signatures(
+        &self,
+    ) -> anyhow::Result<Option<component_signatures::ComponentSignaturesConfig>> {
+        component_signatures::ComponentSignaturesConfig::from_toml(
+            self.table.get("component_signatures"),
+        )
+    }
+
+    /// Get the `[seed]` table, resolving seed script paths relative to the
+    /// runtime config file's directory.
+    pub fn seed(&self, runtime_config_dir: Option<&Path>) -> anyhow::Result<Option<SeedConfig>> {
+        let Some(toml) = self.table.get("seed") else {
+            return Ok(None);
+        };
+        SeedConfig::from_toml(toml, runtime_config_dir.unwrap_or(Path::new(".")))
+            .map(Some)
+            .map_err(|e| anyhow::anyhow!("invalid `[seed]` runtime config: {e}"))
+    }
+
+    /// Get the configured Wasmtime settings.
+    pub fn wasmtime_config(&self) -> anyhow::Result<wasmtime::WasmtimeConfig> {
+        wasmtime::WasmtimeConfig::from_toml(self.table.get("wasmtime"))
+    }
+
     /// Validate that all keys in the TOML file have been used.
     pub fn validate_all_keys_used(&self) -> spin_factors::Result<()> {
         self.table.validate_all_keys_used()
@@ -300,23 +438,32 @@ impl<'a> TomlResolver<'a> {
 /// The TOML based runtime configuration source Spin CLI.
 pub struct TomlRuntimeConfigSource<'a, 'b> {
     toml: TomlResolver<'b>,
+    runtime_config_dir: Option<&'a Path>,
     key_value: &'a key_value::RuntimeConfigResolver,
     outbound_networking: Option<&'a OutboundNetworkingSpinRuntimeConfig>,
     sqlite: &'a sqlite::RuntimeConfigResolver,
+    blobstore: &'a blobstore::RuntimeConfigResolver,
+    messaging: &'a messaging::RuntimeConfigResolver,
 }
 
 impl<'a, 'b> TomlRuntimeConfigSource<'a, 'b> {
     pub fn new(
         toml_resolver: TomlResolver<'b>,
+        runtime_config_dir: Option<&'a Path>,
         key_value: &'a key_value::RuntimeConfigResolver,
         outbound_networking: Option<&'a OutboundNetworkingSpinRuntimeConfig>,
         sqlite: &'a sqlite::RuntimeConfigResolver,
+        blobstore: &'a blobstore::RuntimeConfigResolver,
+        messaging: &'a messaging::RuntimeConfigResolver,
     ) -> Self {
         Self {
             toml: toml_resolver,
+            runtime_config_dir,
             key_value,
             outbound_networking,
             sqlite,
+            blobstore,
+            messaging,
         }
     }
 }
@@ -329,6 +476,22 @@ impl FactorRuntimeConfigSource<KeyValueFactor> for TomlRuntimeConfigSource<'_, '
     }
 }
 
+impl FactorRuntimeConfigSource<BlobStoreFactor> for TomlRuntimeConfigSource<'_, '_> {
+    fn get_runtime_config(
+        &mut self,
+    ) -> anyhow::Result<Option<spin_factor_blobstore::RuntimeConfig>> {
+        Ok(Some(self.blobstore.resolve(Some(&self.toml.table))?))
+    }
+}
+
+impl FactorRuntimeConfigSource<MessagingFactor> for TomlRuntimeConfigSource<'_, '_> {
+    fn get_runtime_config(
+        &mut self,
+    ) -> anyhow::Result<Option<spin_factor_messaging::RuntimeConfig>> {
+        Ok(Some(self.messaging.resolve(Some(&self.toml.table))?))
+    }
+}
+
 impl FactorRuntimeConfigSource<OutboundNetworkingFactor> for TomlRuntimeConfigSource<'_, '_> {
     fn get_runtime_config(
         &mut self,
@@ -367,6 +530,15 @@ impl FactorRuntimeConfigSource<LlmFactor> for TomlRuntimeConfigSource<'_, '_> {
     }
 }
 
+impl FactorRuntimeConfigSource<WasiNnFactor> for TomlRuntimeConfigSource<'_, '_> {
+    fn get_runtime_config(&mut self) -> anyhow::Result<Option<spin_factor_wasi_nn::RuntimeConfig>> {
+        spin_factor_wasi_nn::spin::runtime_config_from_toml(
+            &self.toml.table,
+            self.runtime_config_dir.as_deref(),
+        )
+    }
+}
+
 impl FactorRuntimeConfigSource<OutboundRedisFactor> for TomlRuntimeConfigSource<'_, '_> {
     fn get_runtime_config(&mut self) -> anyhow::Result<Option<()>> {
         Ok(None)
@@ -374,11 +546,37 @@ impl FactorRuntimeConfigSource<OutboundRedisFactor> for TomlRuntimeConfigSource<
 }
 
 impl FactorRuntimeConfigSource<WasiFactor> for TomlRuntimeConfigSource<'_, '_> {
+    fn get_runtime_config(&mut self) -> anyhow::Result<Option<spin_factor_wasi::RuntimeConfig>> {
+        spin_factor_wasi::spin::runtime_config_from_toml(&self.toml.table)
+    }
+}
+
+impl FactorRuntimeConfigSource<CryptoFactor> for TomlRuntimeConfigSource<'_, '_> {
     fn get_runtime_config(&mut self) -> anyhow::Result<Option<()>> {
         Ok(None)
     }
 }
 
+impl FactorRuntimeConfigSource<ActorsFactor> for TomlRuntimeConfigSource<'_, '_> {
+    fn get_runtime_config(&mut self) -> anyhow::Result<Option<spin_factor_actors::RuntimeConfig>> {
+        spin_factor_actors::runtime_config::config_from_table(&self.toml.table)
+    }
+}
+
+impl FactorRuntimeConfigSource<JobsFactor> for TomlRuntimeConfigSource<'_, '_> {
+    fn get_runtime_config(&mut self) -> anyhow::Result<Option<spin_factor_jobs::RuntimeConfig>> {
+        spin_factor_jobs::runtime_config::config_from_table(&self.toml.table)
+    }
+}
+
+impl FactorRuntimeConfigSource<WorkflowsFactor> for TomlRuntimeConfigSource<'_, '_> {
+    fn get_runtime_config(
+        &mut self,
+    ) -> anyhow::Result<Option<spin_factor_workflows::RuntimeConfig>> {
+        spin_factor_workflows::runtime_config::config_from_table(&self.toml.table)
+    }
+}
+
 impl FactorRuntimeConfigSource<OutboundHttpFactor> for TomlRuntimeConfigSource<'_, '_> {
     fn get_runtime_config(
         &mut self,
@@ -393,6 +591,12 @@ impl FactorRuntimeConfigSource<OutboundMqttFactor> for TomlRuntimeConfigSource<'
     }
 }
 
+impl FactorRuntimeConfigSource<OutboundEmailFactor> for TomlRuntimeConfigSource<'_, '_> {
+    fn get_runtime_config(&mut self) -> anyhow::Result<Option<()>> {
+        Ok(None)
+    }
+}
+
 impl FactorRuntimeConfigSource<SqliteFactor> for TomlRuntimeConfigSource<'_, '_> {
     fn get_runtime_config(&mut self) -> anyhow::Result<Option<spin_factor_sqlite::RuntimeConfig>> {
         Ok(Some(self.sqlite.resolve(&self.toml.table)?))
@@ -400,8 +604,26 @@ impl FactorRuntimeConfigSource<SqliteFactor> for TomlRuntimeConfigSource<'_, '_>
 }
 
 impl FactorRuntimeConfigSource<OtelFactor> for TomlRuntimeConfigSource<'_, '_> {
-    fn get_runtime_config(&mut self) -> anyhow::Result<Option<()>> {
-        Ok(None)
+    fn get_runtime_config(
+        &mut self,
+    ) -> anyhow::Result<Option<spin_factor_otel::runtime_config::RuntimeConfig>> {
+        spin_factor_otel::runtime_config::config_from_table(&self.toml.table)
+    }
+}
+
+impl FactorRuntimeConfigSource<HostExtensionsFactor> for TomlRuntimeConfigSource<'_, '_> {
+    fn get_runtime_config(
+        &mut self,
+    ) -> anyhow::Result<Option<spin_factor_host_extensions::runtime_config::RuntimeConfig>> {
+        spin_factor_host_extensions::runtime_config::config_from_table(&self.toml.table)
+    }
+}
+
+impl FactorRuntimeConfigSource<CapabilityPolicyFactor> for TomlRuntimeConfigSource<'_, '_> {
+    fn get_runtime_config(
+        &mut self,
+    ) -> anyhow::Result<Option<spin_factor_capability_policy::runtime_config::RuntimeConfig>> {
+        spin_factor_capability_policy::runtime_config::config_from_table(&self.toml.table)
     }
 }
 
@@ -413,6 +635,15 @@ impl RuntimeConfigSourceFinalizer for TomlRuntimeConfigSource<'_, '_> {
 
 const DEFAULT_KEY_VALUE_STORE_LABEL: &str = "default";
 
+/// The labels of the stores defined unless the runtime config replaces them:
+/// the default store, and the stores dedicated to Spin's own state.
+const DEFAULT_KEY_VALUE_STORE_LABELS: &[&str] = &[
+    DEFAULT_KEY_VALUE_STORE_LABEL,
+    spin_factor_jobs::DEFAULT_KEY_VALUE_STORE,
+    spin_factor_workflows::DEFAULT_KEY_VALUE_STORE,
+    spin_factor_actors::DEFAULT_KEY_VALUE_STORE,
+];
+
 /// The key-value runtime configuration resolver.
 ///
 /// Takes a base path that all local key-value stores which are configured with
@@ -441,19 +672,93 @@ pub fn key_value_config_resolver(
         .register_store_type(spin_key_value_aws::AwsDynamoKeyValueStore::new())
         .unwrap();
 
-    // Add handling of "default" store.
+    // Add handling of "default" store, and of the stores dedicated to Spin's
+    // own state, which share its file.
     let default_store_path = default_store_base_path.map(|p| p.join(DEFAULT_SPIN_STORE_FILENAME));
-    // Unwraps are safe because the store is known to be serializable as toml.
+    for label in DEFAULT_KEY_VALUE_STORE_LABELS {
+        // Unwraps are safe because the store is known to be serializable as toml.
+        key_value
+            .add_default_store::<SpinKeyValueStore>(
+                label,
+                SpinKeyValueRuntimeConfig::new(default_store_path.clone()),
+            )
+            .unwrap();
+    }
+
     key_value
-        .add_default_store::<SpinKeyValueStore>(
-            DEFAULT_KEY_VALUE_STORE_LABEL,
-            SpinKeyValueRuntimeConfig::new(default_store_path),
-        )
+}
+
+const DEFAULT_BLOB_CONTAINER_LABEL: &str = "default";
+
+/// The blob container runtime configuration resolver.
+///
+/// Takes a base path that filesystem containers configured with relative
+/// paths will be relative to, and the state directory under which the default
+/// container is stored. If there is no state directory, there is no default
+/// container.
+pub fn blobstore_config_resolver(
+    local_container_base_path: Option<PathBuf>,
+    default_container_base_path: Option<PathBuf>,
+) -> blobstore::RuntimeConfigResolver {
+    let mut blobstore = blobstore::RuntimeConfigResolver::new();
+
+    // Register the supported container types.
+    // Unwraps are safe because the container types are known to not overlap.
+    blobstore
+        .register_container_type(FileBlobContainer::new(local_container_base_path))
+        .unwrap();
+    blobstore
+        .register_container_type(spin_blobstore_object_store::S3BlobContainer::new())
+        .unwrap();
+    blobstore
+        .register_container_type(spin_blobstore_object_store::AzureBlobContainer::new())
+        .unwrap();
+    blobstore
+        .register_container_type(spin_blobstore_object_store::GcsBlobContainer::new())
         .unwrap();
 
-    key_value
+    // Add handling of "default" container.
+    if let Some(base_path) = default_container_base_path {
+        let path = base_path.join(DEFAULT_BLOB_CONTAINER_DIR);
+        // Unwrap is safe because the container config is known to be serializable as toml.
+        blobstore
+            .add_default_container::<FileBlobContainer>(
+                DEFAULT_BLOB_CONTAINER_LABEL,
+                FileBlobContainerRuntimeConfig::new(path),
+            )
+            .unwrap();
+    }
+
+    blobstore
 }
 
+/// The messaging broker runtime configuration resolver.
+///
+/// There is no default broker: every broker a component uses must be defined
+/// in the runtime config.
+pub fn messaging_config_resolver() -> messaging::RuntimeConfigResolver {
+    let mut messaging = messaging::RuntimeConfigResolver::new();
+
+    // Register the supported broker types.
+    // Unwraps are safe because the broker types are known to not overlap.
+    messaging
+        .register_broker_type(spin_messaging_redis::RedisMessagingBroker::new())
+        .unwrap();
+    messaging
+        .register_broker_type(spin_messaging_nats::NatsMessagingBroker::new())
+        .unwrap();
+    messaging
+        .register_broker_type(spin_messaging_kafka::KafkaMessagingBroker::new())
+        .unwrap();
+
+    messaging
+}
+
+const DEFAULT_SQLITE_DATABASE_LABEL: &str = "default";
+
+/// The directory for the default blob container, under the state directory.
+const DEFAULT_BLOB_CONTAINER_DIR: &str = "blob_containers/default";
+
 /// The default filename for the SQLite database.
 const DEFAULT_SPIN_STORE_FILENAME: &str = "sqlite_key_value.db";
 
@@ -478,6 +783,7 @@ mod tests {
 
     use spin_factors::RuntimeFactors;
     use spin_factors_test::TestEnvironment;
+    use spin_trigger::cli::SignaturesRequired;
 
     use super::*;
 
@@ -645,6 +951,126 @@ mod tests {
         resolve_toml(toml, "config.toml").unwrap();
     }
 
+    #[test]
+    fn wasmtime_config_is_resolved() {
+        define_test_factor!(sqlite: SqliteFactor);
+
+        let toml = toml::toml! {
+            [wasmtime.wasm_features]
+            relaxed_simd = true
+            threads = false
+
+            [wasmtime.cranelift_flags]
+            regalloc_algorithm = "single_pass"
+        };
+        let config = toml_resolver(&toml).wasmtime_config().unwrap();
+        assert_eq!(Some(true), config.wasm_features.relaxed_simd);
+        assert_eq!(Some(false), config.wasm_features.threads);
+        assert_eq!(None, config.wasm_features.memory64);
+        assert_eq!("single_pass", config.cranelift_flags["regalloc_algorithm"]);
+        resolve_toml(toml, "config.toml").unwrap();
+    }
+
+    #[test]
+    fn fails_to_resolve_with_unsupported_cranelift_flag() {
+        let toml = toml::toml! {
+            [wasmtime.cranelift_flags]
+            has_avx2 = "true"
+        };
+        assert!(toml_resolver(&toml).wasmtime_config().is_err());
+    }
+
+    #[test]
+    fn fails_to_resolve_with_unknown_wasm_feature() {
+        let toml = toml::toml! {
+            [wasmtime.wasm_features]
+            tail_calls = true
+        };
+        assert!(toml_resolver(&toml).wasmtime_config().is_err());
+    }
+
+    #[test]
+    fn component_signatures_are_resolved() {
+        define_test_factor!(sqlite: SqliteFactor);
+
+        let toml = toml::toml! {
+            [component_signatures]
+            trusted_keys = ["9Nf1mUSYi6m0yzrP6wOVV2VDP5ms3hDxhxaOmm0dBEk="]
+            require = "registry"
+        };
+        let config = toml_resolver(&toml)
+            .component_signatures()
+            .unwrap()
+            .unwrap();
+        assert_eq!(1, config.trusted_keys.len());
+        assert_eq!(SignaturesRequired::Registry, config.require);
+        resolve_toml(toml, "config.toml").unwrap();
+
+        let toml = toml::toml! {
+            [component_signatures]
+            trusted_key = "9Nf1mUSYi6m0yzrP6wOVV2VDP5ms3hDxhxaOmm0dBEk="
+        };
+        assert!(toml_resolver(&toml).component_signatures().is_err());
+    }
+
+    #[test]
+    fn seed_is_resolved() {
+        define_test_factor!(sqlite: SqliteFactor);
+
+        let toml = toml::toml! {
+            [seed.sqlite.default]
+            scripts = ["schema.sql"]
+        };
+        let seed = toml_resolver(&toml)
+            .seed(Some(Path::new("/config")))
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            vec![PathBuf::from("/config/schema.sql")],
+            seed.sqlite["default"].scripts
+        );
+        resolve_toml(toml, "config.toml").unwrap();
+    }
+
+    #[test]
+    fn local_stores_are_listed() {
+        define_test_factor!(key_value: KeyValueFactor);
+
+        let toml = toml::toml! {
+            [key_value_store.cache]
+            type = "spin"
+            path = "cache.db"
+
+            [key_value_store.shared]
+            type = "redis"
+            url = "redis://localhost:6379"
+        };
+        let runtime_config = resolve_toml(toml, "config.toml").unwrap();
+        assert_eq!(
+            vec![
+                "default",
+                "spin-jobs",
+                "spin-workflows",
+                "spin-actors",
+                "cache"
+            ],
+            runtime_config.local_key_value_stores()
+        );
+        assert_eq!(vec!["default"], runtime_config.local_sqlite_databases());
+        assert_eq!(vec!["default"], runtime_config.local_blob_containers());
+
+        let toml = toml::toml! {
+            [key_value_store.default]
+            type = "redis"
+            url = "redis://localhost:6379"
+        };
+        let runtime_config = resolve_toml(toml, "config.toml").unwrap();
+        assert_eq!(
+            vec!["spin-jobs", "spin-workflows", "spin-actors"],
+            runtime_config.local_key_value_stores()
+        );
+    }
+
     #[test]
     fn fails_to_resolve_with_unused_key() {
         define_test_factor!(sqlite: SqliteFactor);
@@ -0,0 +1,106 @@
+use std::collections::BTreeMap;
+
+use serde::Deserialize;
+use spin_factors::wasmtime::Config;
+
+/// Wasmtime settings from the `[wasmtime]` runtime config table.
+///
+/// Expects table to be in the format:
+/// ```toml
+/// [wasmtime.wasm_features]
+/// relaxed_simd = true
+/// memory64 = true
+///
+/// [wasmtime.cranelift_flags]
+/// regalloc_algorithm = "single_pass"
+/// ```
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct WasmtimeConfig {
+    /// Wasm proposals to enable or disable.
+    #[serde(default)]
+    pub wasm_features: WasmFeatures,
+    /// Cranelift code generation settings, by name. Only the settings in
+    /// [`SUPPORTED_CRANELIFT_FLAGS`] may be set.
+    #[serde(default)]
+    pub cranelift_flags: BTreeMap<String, String>,
+}
+
+/// The Cranelift settings which may be set in runtime config.
+///
+/// These only change how Cranelift checks and optimizes code, never what the
+/// generated code assumes about the host, so they cannot produce code which
+/// is unsound to run. ISA flags such as `has_avx2` are deliberately excluded.
+pub const SUPPORTED_CRANELIFT_FLAGS: &[&str] = &[
+    "enable_alias_analysis",
+    "enable_verifier",
+    "regalloc_algorithm",
+    "regalloc_checker",
+];
+
+/// Wasm proposals which may be enabled or disabled. Proposals which are not
+/// set keep Spin's defaults.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct WasmFeatures {
+    pub simd: Option<bool>,
+    pub relaxed_simd: Option<bool>,
+    pub memory64: Option<bool>,
+    /// Note that shared memories are not supported by components, so this
+    /// only has an effect on core modules.
+    pub threads: Option<bool>,
+}
+
+impl WasmtimeConfig {
+    /// Reads the `[wasmtime]` table from the given runtime config file, if any.
+    pub fn from_file(runtime_config_path: Option<&std::path::Path>) -> anyhow::Result<Self> {
+        let toml = super::read_toml(runtime_config_path)?;
+        Self::from_toml(toml.get("wasmtime"))
+    }
+
+    pub(crate) fn from_toml(toml: Option<&toml::Value>) -> anyhow::Result<Self> {
+        let Some(toml) = toml else {
+            return Ok(Self::default());
+        };
+        let config: Self = toml
+            .clone()
+            .try_into()
+            .map_err(|e| anyhow::anyhow!("invalid `[wasmtime]` runtime config: {e}"))?;
+        if let Some(name) = config
+            .cranelift_flags
+            .keys()
+            .find(|name| !SUPPORTED_CRANELIFT_FLAGS.contains(&name.as_str()))
+        {
+            anyhow::bail!(
+                "invalid `[wasmtime]` runtime config: unsupported Cranelift flag `{name}`; expected one of {}",
+                SUPPORTED_CRANELIFT_FLAGS.join(", ")
+            );
+        }
+        Ok(config)
+    }
+
+    /// Applies the settings to the given Wasmtime config.
+    pub fn apply(&self, config: &mut Config) {
+        let features = &self.wasm_features;
+        if let Some(enable) = features.simd {
+            config.wasm_simd(enable);
+        }
+        if let Some(enable) = features.relaxed_simd {
+            config.wasm_relaxed_simd(enable);
+        }
+        if let Some(enable) = features.memory64 {
+            config.wasm_memory64(enable);
+        }
+        if let Some(enable) = features.threads {
+            config.wasm_threads(enable);
+        }
+        for (name, value) in &self.cranelift_flags {
+            // SAFETY: `from_toml` only accepts the flags in
+            // `SUPPORTED_CRANELIFT_FLAGS`, none of which change the host
+            // features the generated code relies on.
+            unsafe {
+                config.cranelift_flag_set(name, value);
+            }
+        }
+    }
+}
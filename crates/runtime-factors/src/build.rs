@@ -1,14 +1,23 @@
 use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, UNIX_EPOCH};
 
 use super::{TriggerAppArgs, TriggerFactors, TriggerFactorsRuntimeConfig};
+use crate::cluster::ClusterStateHook;
 
 use anyhow::Context as _;
+use futures::future::BoxFuture;
+use spin_factor_outbound_http::cassette::{RecordingInterceptor, ReplayingInterceptor};
+use spin_factor_outbound_http::faults::FaultInjectingInterceptor;
+use spin_factor_wasi::{DeterministicMode, VirtualClock};
 use spin_factors_executor::FactorsExecutor;
-use spin_runtime_config::ResolvedRuntimeConfig;
+use spin_fault_injection::Scenario;
+use spin_runtime_config::{ResolvedRuntimeConfig, wasmtime::WasmtimeConfig};
 use spin_trigger::cli::{
-    FactorsConfig, InitialKvSetterHook, KeyValueDefaultStoreSummaryHook, MaxInstanceMemoryHook,
-    RuntimeFactorsBuilder, SqlStatementExecutorHook, SqliteDefaultStoreSummaryHook,
-    StdioLoggingExecutorHooks, VariablesValidatorHook,
+    ComponentSignatureHook, FactorsConfig, GuestProfilerHook, InitialKvSetterHook,
+    KeyValueDefaultStoreSummaryHook, MaxAppMemoryHook, MaxExecutionTimeHook, MaxInstanceMemoryHook,
+    RegistryCache, RuntimeFactorsBuilder, SeedConfig, SeedHook, SqlStatementExecutorHook,
+    SqliteDefaultStoreSummaryHook, StdioLoggingExecutorHooks, VariablesValidatorHook,
 };
 use spin_variables_static::StaticVariablesProvider;
 
@@ -20,6 +29,16 @@ impl RuntimeFactorsBuilder for FactorsBuilder {
     type Factors = TriggerFactors;
     type RuntimeConfig = ResolvedRuntimeConfig<TriggerFactorsRuntimeConfig>;
 
+    fn configure_engine(
+        engine_config: &mut spin_core::Config,
+        config: &FactorsConfig,
+        _args: &Self::CliArgs,
+    ) -> anyhow::Result<()> {
+        WasmtimeConfig::from_file(config.runtime_config_file.as_deref())?
+            .apply(engine_config.wasmtime_config());
+        Ok(())
+    }
+
     fn build(
         config: &FactorsConfig,
         args: &Self::CliArgs,
@@ -27,29 +46,45 @@ impl RuntimeFactorsBuilder for FactorsBuilder {
         let mut runtime_config = ResolvedRuntimeConfig::<TriggerFactorsRuntimeConfig>::from_file(
             config.runtime_config_file.clone().as_deref(),
             config.local_app_dir.clone().map(PathBuf::from),
+            config.app_id.clone(),
             config.state_dir.clone(),
             config.log_dir.clone(),
         )?;
 
+        let scenario = args
+            .scenario
+            .as_deref()
+            .map(Scenario::from_file)
+            .transpose()?
+            .unwrap_or_default();
+        let variable_providers = &mut runtime_config
+            .runtime_config
+            .variables
+            .as_mut()
+            .unwrap()
+            .providers;
+
+        // The scenario's variables override those from the runtime config.
+        if !scenario.variables.is_empty() {
+            variable_providers.insert(
+                0,
+                Box::new(StaticVariablesProvider::new(scenario.variables.clone())),
+            );
+        }
+
         let cli_static_variables = args.get_variables()?.clone();
         let cli_static_variables_provider = StaticVariablesProvider::new(cli_static_variables);
 
         // Insert the parsed static variables provided via cli arguments
         // into the set of variable providers with highest precedence.
-        runtime_config
-            .runtime_config
-            .variables
-            .as_mut()
-            .unwrap()
-            .providers
-            .insert(0, Box::new(cli_static_variables_provider));
+        variable_providers.insert(0, Box::new(cli_static_variables_provider));
 
         runtime_config.summarize(config.runtime_config_file.as_deref());
 
         // This is a hack b/c we know the version of this crate will be the same as the version of Spin
         let spin_version = env!("CARGO_PKG_VERSION");
 
-        let factors = TriggerFactors::new(
+        let mut factors = TriggerFactors::new(
             runtime_config.state_dir(),
             config.working_dir.clone(),
             args.allow_transient_write,
@@ -57,6 +92,40 @@ impl RuntimeFactorsBuilder for FactorsBuilder {
             spin_version,
         )
         .context("failed to create factors")?;
+        if let Some(seed) = args.deterministic_seed {
+            let clock_start = UNIX_EPOCH + Duration::from_secs(args.deterministic_clock_start);
+            factors
+                .wasi
+                .set_deterministic_mode(DeterministicMode::new(seed, clock_start));
+        }
+        if args.virtual_clock {
+            factors.wasi.set_virtual_clock(VirtualClock::running());
+        }
+        if args.egress_dry_run {
+            factors.outbound_networking.set_egress_dry_run(true);
+            factors
+                .outbound_networking
+                .set_disallowed_host_handler(super::egress_dry_run_host_handler);
+        }
+        if let Some(faults) = scenario.fault_injector()? {
+            let faults = Arc::new(faults);
+            factors.key_value.set_fault_injector(faults.clone());
+            factors.sqlite.set_fault_injector(faults.clone());
+            factors
+                .outbound_http
+                .set_request_interceptor(FaultInjectingInterceptor::new(faults));
+        }
+        // Recording and replaying come after fault injection, so that failed
+        // requests are neither recorded nor replayed.
+        if let Some(dir) = &args.record {
+            factors
+                .outbound_http
+                .add_request_interceptor(RecordingInterceptor::new(dir)?);
+        } else if let Some(dir) = &args.replay {
+            factors
+                .outbound_http
+                .add_request_interceptor(ReplayingInterceptor::new(dir)?);
+        }
         Ok((factors, runtime_config))
     }
 
@@ -70,7 +139,18 @@ impl RuntimeFactorsBuilder for FactorsBuilder {
             config.follow_components.clone(),
             runtime_config.log_dir(),
             config.truncate_logs,
+            config.log_rotation.clone(),
         ));
+        let mut seeds = vec![];
+        if let Some(seed) = runtime_config.seed() {
+            seeds.push(seed.clone());
+        }
+        for path in &args.seed {
+            seeds.push(SeedConfig::from_file(path)?);
+        }
+        if !seeds.is_empty() {
+            executor.add_hooks(SeedHook::new(seeds));
+        }
         executor.add_hooks(SqlStatementExecutorHook::new(
             args.sqlite_statements.clone(),
         ));
@@ -79,6 +159,22 @@ impl RuntimeFactorsBuilder for FactorsBuilder {
         executor.add_hooks(KeyValueDefaultStoreSummaryHook);
         executor.add_hooks(VariablesValidatorHook);
 
+        if let Some(node_id) = &args.cluster_node_id {
+            executor.add_hooks(ClusterStateHook::new(
+                node_id,
+                runtime_config.local_key_value_stores(),
+                runtime_config.local_sqlite_databases(),
+                runtime_config.local_blob_containers(),
+            ));
+        }
+
+        if let Some(signatures) = runtime_config.component_signatures() {
+            executor.add_hooks(
+                ComponentSignatureHook::new(&signatures.trusted_keys, signatures.require)
+                    .context("invalid `[component_signatures]` runtime config")?,
+            );
+        }
+
         let max_instance_memory = args
             .max_instance_memory
             .or(runtime_config.max_instance_memory());
@@ -88,6 +184,37 @@ impl RuntimeFactorsBuilder for FactorsBuilder {
             executor.add_hooks(MaxInstanceMemoryHook::new(max_instance_memory));
         }
 
+        if let Some(max_app_memory) = args.max_app_memory {
+            executor.add_hooks(MaxAppMemoryHook::new(max_app_memory));
+        }
+
+        if let Some(max_execution_time) = args.max_execution_time {
+            executor.add_hooks(MaxExecutionTimeHook::new(max_execution_time));
+        }
+
+        if !args.profile_guest.is_empty() {
+            executor.add_hooks(GuestProfilerHook::new(
+                args.profile_guest.clone(),
+                args.profile_guest_dir.clone(),
+            ));
+        }
+
         Ok(())
     }
+
+    fn registry_cache() -> Option<Box<dyn RegistryCache>> {
+        Some(Box::new(LoaderRegistryCache))
+    }
+}
+
+/// A [`RegistryCache`] reading the cache that `spin registry pull` fills.
+struct LoaderRegistryCache;
+
+impl RegistryCache for LoaderRegistryCache {
+    fn wasm_file<'a>(&'a self, digest: &'a str) -> BoxFuture<'a, anyhow::Result<PathBuf>> {
+        Box::pin(async move {
+            let cache = spin_loader::cache::Cache::new(None).await?;
+            cache.wasm_file(digest)
+        })
+    }
 }
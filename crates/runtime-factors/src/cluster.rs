@@ -0,0 +1,126 @@
+use spin_core::async_trait;
+use spin_factor_actors::ActorsFactor;
+use spin_factor_blobstore::BlobStoreFactor;
+use spin_factor_jobs::JobsFactor;
+use spin_factor_key_value::KeyValueFactor;
+use spin_factor_sqlite::SqliteFactor;
+use spin_factor_workflows::WorkflowsFactor;
+use spin_factors::RuntimeFactors;
+use spin_factors_executor::ExecutorHooks;
+
+/// An [`ExecutorHooks`] that refuses to run an app as a cluster node if any
+/// state the app uses is held locally, where other nodes serving the same app
+/// can't see it.
+pub struct ClusterStateHook {
+    node_id: String,
+    local_key_value_stores: Vec<String>,
+    local_sqlite_databases: Vec<String>,
+    local_blob_containers: Vec<String>,
+}
+
+impl ClusterStateHook {
+    /// Creates a hook for the given node, which holds the given key-value
+    /// stores, SQLite databases and blob containers locally.
+    pub fn new(
+        node_id: impl Into<String>,
+        local_key_value_stores: Vec<String>,
+        local_sqlite_databases: Vec<String>,
+        local_blob_containers: Vec<String>,
+    ) -> Self {
+        Self {
+            node_id: node_id.into(),
+            local_key_value_stores,
+            local_sqlite_databases,
+            local_blob_containers,
+        }
+    }
+
+    fn local_state_problems<F: RuntimeFactors>(
+        &self,
+        configured_app: &spin_factors::ConfiguredApp<F>,
+    ) -> Vec<String> {
+        let mut problems = vec![];
+        if let Ok(kv_app_state) = configured_app.app_state::<KeyValueFactor>() {
+            for label in &self.local_key_value_stores {
+                if kv_app_state.store_is_used(label) {
+                    problems.push(format!("key-value store {label:?} is local"));
+                }
+            }
+        }
+        if let Ok(sqlite_app_state) = configured_app.app_state::<SqliteFactor>() {
+            for label in &self.local_sqlite_databases {
+                if sqlite_app_state.database_is_used(label) {
+                    problems.push(format!("SQLite database {label:?} is local"));
+                }
+            }
+        }
+        if let Ok(blob_app_state) = configured_app.app_state::<BlobStoreFactor>() {
+            for label in &self.local_blob_containers {
+                if blob_app_state.container_is_used(label) {
+                    problems.push(format!("blob container {label:?} is local"));
+                }
+            }
+        }
+        let has_trigger = |trigger_type| {
+            configured_app
+                .app()
+                .triggers_with_type(trigger_type)
+                .next()
+                .is_some()
+        };
+        if let Ok(jobs_app_state) = configured_app.app_state::<JobsFactor>() {
+            self.check_feature_store(
+                &mut problems,
+                jobs_app_state.is_used() || has_trigger("job"),
+                "the job queue is held",
+                jobs_app_state.queue().label(),
+            );
+        }
+        if let Ok(workflows_app_state) = configured_app.app_state::<WorkflowsFactor>() {
+            self.check_feature_store(
+                &mut problems,
+                workflows_app_state.is_used() || has_trigger("workflow"),
+                "workflows are held",
+                workflows_app_state.store().label(),
+            );
+        }
+        if let Ok(actors_app_state) = configured_app.app_state::<ActorsFactor>() {
+            self.check_feature_store(
+                &mut problems,
+                actors_app_state.is_used() || has_trigger("actor"),
+                "actor state is held",
+                actors_app_state.states().label(),
+            );
+        }
+        problems
+    }
+
+    /// Records a problem if the app uses a feature whose state Spin keeps in
+    /// the key-value store `label`, and that store is local.
+    fn check_feature_store(&self, problems: &mut Vec<String>, used: bool, held: &str, label: &str) {
+        if used && self.local_key_value_stores.iter().any(|l| l == label) {
+            problems.push(format!("{held} in local key-value store {label:?}"));
+        }
+    }
+}
+
+#[async_trait]
+impl<F: RuntimeFactors, U> ExecutorHooks<F, U> for ClusterStateHook {
+    async fn configure_app(
+        &self,
+        configured_app: &spin_factors::ConfiguredApp<F>,
+    ) -> anyhow::Result<()> {
+        let problems = self.local_state_problems(configured_app);
+        if !problems.is_empty() {
+            anyhow::bail!(
+                "cannot run as cluster node {:?}: {}. In cluster mode, all state must be held in \
+                 remote backends such as Redis for key-value stores, libSQL for SQLite \
+                 databases and S3 for blob containers; configure them in the runtime config file",
+                self.node_id,
+                problems.join("; ")
+            );
+        }
+        tracing::info!(node_id = %self.node_id, "Running as cluster node");
+        Ok(())
+    }
+}
@@ -1,4 +1,5 @@
 mod build;
+mod cluster;
 
 pub use build::FactorsBuilder;
 
@@ -7,10 +8,18 @@ use std::collections::HashMap;
 use std::path::PathBuf;
 
 use anyhow::Context as _;
-use spin_common::arg_parser::parse_kv;
+use spin_common::arg_parser::{parse_duration, parse_kv};
+use spin_factor_actors::ActorsFactor;
+use spin_factor_blobstore::BlobStoreFactor;
+use spin_factor_capability_policy::CapabilityPolicyFactor;
+use spin_factor_crypto::CryptoFactor;
+use spin_factor_host_extensions::HostExtensionsFactor;
+use spin_factor_jobs::JobsFactor;
 use spin_factor_key_value::KeyValueFactor;
 use spin_factor_llm::LlmFactor;
+use spin_factor_messaging::MessagingFactor;
 use spin_factor_otel::OtelFactor;
+use spin_factor_outbound_email::{OutboundEmailFactor, SmtpTransport};
 use spin_factor_outbound_http::OutboundHttpFactor;
 use spin_factor_outbound_mqtt::{NetworkedMqttClient, OutboundMqttFactor};
 use spin_factor_outbound_mysql::OutboundMysqlFactor;
@@ -20,6 +29,8 @@ use spin_factor_outbound_redis::OutboundRedisFactor;
 use spin_factor_sqlite::SqliteFactor;
 use spin_factor_variables::VariablesFactor;
 use spin_factor_wasi::{WasiFactor, spin::SpinFilesMounter};
+use spin_factor_wasi_nn::WasiNnFactor;
+use spin_factor_workflows::WorkflowsFactor;
 use spin_factors::RuntimeFactors;
 use spin_runtime_config::{ResolvedRuntimeConfig, TomlRuntimeConfigSource};
 use spin_variables_static::VariableSource;
@@ -27,17 +38,27 @@ use spin_variables_static::VariableSource;
 #[derive(RuntimeFactors)]
 pub struct TriggerFactors {
     pub otel: OtelFactor,
+    pub capability_policy: CapabilityPolicyFactor,
     pub wasi: WasiFactor,
     pub variables: VariablesFactor,
     pub key_value: KeyValueFactor,
+    pub blobstore: BlobStoreFactor,
     pub outbound_networking: OutboundNetworkingFactor,
     pub outbound_http: OutboundHttpFactor,
     pub sqlite: SqliteFactor,
     pub redis: OutboundRedisFactor,
     pub mqtt: OutboundMqttFactor,
+    pub email: OutboundEmailFactor,
     pub pg: OutboundPgFactor,
     pub mysql: OutboundMysqlFactor,
     pub llm: LlmFactor,
+    pub messaging: MessagingFactor,
+    pub nn: WasiNnFactor,
+    pub crypto: CryptoFactor,
+    pub jobs: JobsFactor,
+    pub workflows: WorkflowsFactor,
+    pub actors: ActorsFactor,
+    pub host_extensions: HostExtensionsFactor,
 }
 
 impl TriggerFactors {
@@ -50,20 +71,31 @@ impl TriggerFactors {
     ) -> anyhow::Result<Self> {
         Ok(Self {
             otel: OtelFactor::new(spin_version, experimental_wasi_otel)?,
+            capability_policy: CapabilityPolicyFactor::new(),
             wasi: wasi_factor(working_dir, allow_transient_writes),
             variables: VariablesFactor::default(),
             key_value: KeyValueFactor::new(),
+            blobstore: BlobStoreFactor::new(),
             outbound_networking: outbound_networking_factor(),
             outbound_http: OutboundHttpFactor::default(),
             sqlite: SqliteFactor::new(),
             redis: OutboundRedisFactor::new(),
             mqtt: OutboundMqttFactor::new(NetworkedMqttClient::creator()),
+            email: OutboundEmailFactor::new(SmtpTransport::creator()),
             pg: OutboundPgFactor::new(),
             mysql: OutboundMysqlFactor::new(),
             llm: LlmFactor::new(
                 spin_factor_llm::spin::default_engine_creator(state_dir)
                     .context("failed to configure LLM factor")?,
             ),
+            messaging: MessagingFactor::new(),
+            nn: WasiNnFactor::new(),
+            crypto: CryptoFactor::new(),
+            jobs: JobsFactor::new(),
+            workflows: WorkflowsFactor::new(),
+            actors: ActorsFactor::new(),
+            host_extensions: HostExtensionsFactor::from_registered()
+                .context("failed to configure host extensions")?,
         })
     }
 }
@@ -95,8 +127,21 @@ fn outbound_networking_factor() -> OutboundNetworkingFactor {
     factor
 }
 
+/// Reports destinations that `allowed_outbound_hosts` would block when
+/// running with `--egress-dry-run`.
+fn egress_dry_run_host_handler(scheme: &str, authority: &str) {
+    let host_pattern = format!("{scheme}://{authority}");
+    tracing::warn!("Outbound network destination would not be allowed: {host_pattern}");
+    terminal::warn!(
+        "A component made an outbound network connection to '{host_pattern}', which would not be allowed outside of egress dry-run mode."
+    );
+    eprintln!(
+        "To allow this request, add 'allowed_outbound_hosts = [\"{host_pattern}\"]' to the manifest component section."
+    );
+}
+
 /// Options for building a [`TriggerFactors`].
-#[derive(Default, clap::Args)]
+#[derive(Clone, Default, clap::Args)]
 pub struct TriggerAppArgs {
     /// Set the static assets of the components in the temporary directory as writable.
     #[clap(long = "allow-transient-write")]
@@ -112,6 +157,11 @@ pub struct TriggerAppArgs {
     #[clap(long = "key-value", value_parser = parse_kv)]
     pub key_values: Vec<(String, String)>,
 
+    /// Populate key-value stores and SQLite databases from a seed file before
+    /// the app's triggers start. Can be used multiple times.
+    #[clap(long = "seed", value_name = "FILE")]
+    pub seed: Vec<PathBuf>,
+
     /// Run a SQLite statement such as a migration against the default database.
     /// To run from a file, prefix the filename with @ e.g. spin up --sqlite @migration.sql
     #[clap(long = "sqlite")]
@@ -121,6 +171,85 @@ pub struct TriggerAppArgs {
     #[clap(long, env = "SPIN_MAX_INSTANCE_MEMORY")]
     pub max_instance_memory: Option<usize>,
 
+    /// Sets the maximum memory allocation limit in bytes across all
+    /// instances of the app at once. When reached, further memory growth in
+    /// any instance fails.
+    #[clap(long, env = "SPIN_MAX_APP_MEMORY")]
+    pub max_app_memory: Option<usize>,
+
+    /// Sets the maximum time an instance may spend executing guest code,
+    /// e.g. `500ms` or `2s`. Time spent waiting on I/O (such as outbound
+    /// requests) does not count, so long-polling components are not
+    /// interrupted but CPU-bound ones are.
+    #[clap(long, env = "SPIN_MAX_EXECUTION_TIME", value_parser = parse_duration)]
+    pub max_execution_time: Option<std::time::Duration>,
+
+    /// Profile the guest code of a component, writing a profile for each
+    /// instance in the Firefox profiler format (https://profiler.firefox.com/).
+    /// Can be used multiple times.
+    #[clap(long = "profile-guest", value_name = "COMPONENT")]
+    pub profile_guest: Vec<String>,
+
+    /// The directory to write guest profiles to.
+    #[clap(
+        long = "profile-guest-dir",
+        default_value = ".",
+        requires = "profile_guest"
+    )]
+    pub profile_guest_dir: PathBuf,
+
+    /// [Testing] Run components reproducibly: seed wasi:random with this value
+    /// and virtualize the wasi clocks. Virtual clocks stand still unless
+    /// advanced through the admin API.
+    #[clap(long = "deterministic-seed", value_name = "SEED")]
+    pub deterministic_seed: Option<u64>,
+
+    /// The Unix time, in seconds, at which virtual clocks start when running
+    /// with `--deterministic-seed`.
+    #[clap(
+        long = "deterministic-clock-start",
+        value_name = "UNIX_SECONDS",
+        default_value_t = 0,
+        requires = "deterministic_seed"
+    )]
+    pub deterministic_clock_start: u64,
+
+    /// [Testing] Run components against a virtual clock, which keeps real time
+    /// until it is frozen or advanced through the admin API.
+    #[clap(long = "virtual-clock", conflicts_with = "deterministic_seed")]
+    pub virtual_clock: bool,
+
+    /// Allow outbound network connections that the components'
+    /// `allowed_outbound_hosts` do not permit, reporting each one instead of
+    /// blocking it. Combine with `--audit-log` and `spin doctor egress` to see
+    /// which destinations an app actually needs.
+    #[clap(long = "egress-dry-run", env = "SPIN_EGRESS_DRY_RUN")]
+    pub egress_dry_run: bool,
+
+    /// [Testing] Make outbound HTTP requests as normal, and record each
+    /// request and its response in this directory.
+    #[clap(long = "record", value_name = "DIR", conflicts_with = "replay")]
+    pub record: Option<PathBuf>,
+
+    /// [Testing] Answer outbound HTTP requests with the responses recorded
+    /// in this directory by `--record`, instead of making them. Requests
+    /// which were not recorded fail.
+    #[clap(long = "replay", value_name = "DIR")]
+    pub replay: Option<PathBuf>,
+
+    /// [Testing] Run the app under a scenario file, which can override
+    /// variables, fail chosen key-value, SQLite and outbound HTTP calls, and
+    /// delay them. Variables passed with `--variable` take precedence over
+    /// the scenario's.
+    #[clap(long = "scenario", value_name = "FILE")]
+    pub scenario: Option<PathBuf>,
+
+    /// Run as a node of a cluster serving the same app, identified by this ID
+    /// in telemetry. Refuses to start if any state the app uses is held
+    /// locally rather than in a remote backend.
+    #[clap(long = "cluster-node-id", env = "SPIN_CLUSTER_NODE_ID", hide = true)]
+    pub cluster_node_id: Option<String>,
+
     /// Variable(s) to be passed to the app
     ///
     /// A single key-value pair can be passed as `key=value`, or `key=@file` to
@@ -0,0 +1,192 @@
+//! An opt-in audit log of the privileged host operations components perform.
+//!
+//! Factors record operations with [`event!`]. When the audit log is enabled with the
+//! `SPIN_AUDIT_LOG` environment variable, each operation is written with a timestamp and the
+//! identity of the component that performed it, either as a JSON line to a file or as an OTel log
+//! record.
+
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::{Mutex, OnceLock};
+
+use anyhow::{Context as _, bail};
+use serde_json::{Map, Value};
+use sha2::{Digest as _, Sha256};
+use tracing::{Event, Level, Subscriber};
+use tracing_subscriber::{
+    Layer,
+    filter::filter_fn,
+    fmt::{
+        format::Writer,
+        time::{FormatTime, SystemTime},
+    },
+    layer::Context,
+    registry::LookupSpan,
+};
+
+use crate::{
+    component,
+    env::{self, AuditLogDestination},
+    json_format::JsonVisitor,
+    logs,
+};
+
+/// The tracing target of audit events.
+pub const AUDIT_TARGET: &str = "spin_audit";
+
+#[macro_export]
+/// Records a privileged host operation in the audit log, with any details about it.
+///
+/// The operation is attributed to the component whose work is being done, as identified by the
+/// closest enclosing span with a `spin.component.id` field.
+///
+/// ```no_run
+/// # use spin_telemetry::audit;
+/// audit::event!("key_value.get", store = "default", key = "user:1");
+/// ```
+macro_rules! audit_event {
+    ($operation:expr $(, $field:ident = $value:expr)* $(,)?) => {
+        tracing::info!(target: "spin_audit", operation = $operation $(, $field = $value)*)
+    };
+}
+
+pub use audit_event as event;
+
+/// Returns a boolean indicating if the audit log is enabled.
+///
+/// Factors can check this to avoid extra work when nothing is audited.
+pub fn enabled() -> bool {
+    static ENABLED: OnceLock<bool> = OnceLock::new();
+    *ENABLED.get_or_init(|| env::audit_log_destination().is_some())
+}
+
+/// Returns a digest of sensitive text, such as a SQL statement, that identifies it in the audit
+/// log without revealing it.
+pub fn digest(text: &str) -> String {
+    format!("{:x}", Sha256::digest(text.as_bytes()))
+}
+
+/// Constructs a layer for the tracing subscriber that writes audit events to the destination
+/// given by `SPIN_AUDIT_LOG`.
+pub(crate) fn audit_layer<S: Subscriber + for<'span> LookupSpan<'span>>()
+-> anyhow::Result<Option<impl Layer<S>>> {
+    let sink = match env::audit_log_destination() {
+        None => return Ok(None),
+        Some(AuditLogDestination::Otel) => {
+            if !env::otel_logs_enabled() {
+                bail!(
+                    "{}=otel requires OTLP log export to be enabled, e.g. with OTEL_EXPORTER_OTLP_ENDPOINT",
+                    env::SPIN_AUDIT_LOG
+                );
+            }
+            AuditSink::Otel
+        }
+        Some(AuditLogDestination::File(path)) => AuditSink::json_lines(path)?,
+    };
+    Ok(Some(AuditLayer { sink }.with_filter(filter_fn(
+        |metadata| {
+            if metadata.is_span() {
+                *metadata.level() <= Level::INFO
+            } else {
+                metadata.target() == AUDIT_TARGET
+            }
+        },
+    ))))
+}
+
+enum AuditSink {
+    JsonLines(Mutex<Box<dyn Write + Send>>),
+    Otel,
+}
+
+impl AuditSink {
+    fn json_lines(path: PathBuf) -> anyhow::Result<Self> {
+        let file = std::fs::File::options()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .with_context(|| format!("failed to open audit log {}", path.display()))?;
+        Ok(Self::JsonLines(Mutex::new(Box::new(file))))
+    }
+}
+
+struct AuditLayer {
+    sink: AuditSink,
+}
+
+impl<S: Subscriber + for<'span> LookupSpan<'span>> Layer<S> for AuditLayer {
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        let mut fields = Map::new();
+        event.record(&mut JsonVisitor(&mut fields));
+        let component_id = ctx
+            .event_span(event)
+            .and_then(|span| component::component_id(&span));
+
+        match &self.sink {
+            AuditSink::JsonLines(writer) => {
+                let mut timestamp = String::new();
+                let _ = SystemTime.format_time(&mut Writer::new(&mut timestamp));
+                let mut record = Map::new();
+                record.insert("timestamp".into(), timestamp.into());
+                record.insert("component_id".into(), component_id.into());
+                record.append(&mut fields);
+                let mut writer = writer.lock().unwrap();
+                if let Err(err) = writeln!(writer, "{}", Value::Object(record)) {
+                    // Avoid `tracing` here, as this runs inside the subscriber.
+                    eprintln!("Failed to write to audit log: {err}");
+                }
+            }
+            AuditSink::Otel => logs::emit_audit_record(component_id.as_deref(), fields),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use tracing_subscriber::prelude::*;
+
+    use super::*;
+
+    #[derive(Clone, Default)]
+    struct Buffer(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Buffer {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn audit_events_are_attributed_to_components() {
+        let buffer = Buffer::default();
+        let layer = AuditLayer {
+            sink: AuditSink::JsonLines(Mutex::new(Box::new(buffer.clone()))),
+        };
+        let subscriber = tracing_subscriber::registry()
+            .with(component::ComponentIdLayer)
+            .with(layer);
+        tracing::subscriber::with_default(subscriber, || {
+            let _request = tracing::info_span!("request", spin.component.id = "hello").entered();
+            let _call = tracing::info_span!("spin_sqlite.execute").entered();
+            event!(
+                "sqlite.execute",
+                database = "default",
+                statement_sha256 = digest("SELECT 1")
+            );
+        });
+
+        let output = String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+        let record: Value = serde_json::from_str(output.trim()).unwrap();
+        assert_eq!(record["component_id"], "hello");
+        assert_eq!(record["operation"], "sqlite.execute");
+        assert_eq!(record["database"], "default");
+        assert_eq!(record["statement_sha256"], digest("SELECT 1"));
+        assert!(record["timestamp"].is_string());
+    }
+}
@@ -0,0 +1,205 @@
+//! Captures spans to a local file, so that the performance of a run can be analyzed without an
+//! OTel collector, e.g. with `spin telemetry report`.
+
+use std::fmt::Debug;
+use std::fs::File;
+use std::io::{BufWriter, Write as _};
+use std::path::Path;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+use anyhow::Context as _;
+use serde::{Deserialize, Serialize};
+use tracing::{
+    Level, Subscriber,
+    field::{Field, Visit},
+    span::{Attributes, Id, Record},
+};
+use tracing_subscriber::{Layer, filter::filter_fn, layer::Context, registry::LookupSpan};
+
+/// A span as written to a capture file, one JSON object per line.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct CapturedSpan {
+    /// Identifies the span within the capture file.
+    pub id: u64,
+    /// The `id` of the span's parent, if it has one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parent_id: Option<u64>,
+    /// The name of the span, taken from its `otel.name` field if it has one.
+    pub name: String,
+    /// When the span started, in nanoseconds since the Unix epoch.
+    pub start_unix_nanos: u64,
+    /// How long the span lasted, in nanoseconds.
+    pub duration_nanos: u64,
+    /// The span's `otel.kind` field, e.g. `server` or `client`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub kind: Option<String>,
+    /// The span's `http.route` field.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub route: Option<String>,
+    /// The span's `spin.component.id` field.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub component_id: Option<String>,
+}
+
+/// Constructs a layer for the tracing subscriber that appends spans at INFO level or above to
+/// the given file as they close.
+pub(crate) fn capture_layer<S: Subscriber + for<'span> LookupSpan<'span>>(
+    path: &Path,
+) -> anyhow::Result<impl Layer<S>> {
+    let file = File::options()
+        .create(true)
+        .append(true)
+        .open(path)
+        .with_context(|| format!("failed to open span capture file {}", path.display()))?;
+    let layer = CaptureLayer {
+        next_id: AtomicU64::new(1),
+        writer: Mutex::new(BufWriter::new(file)),
+    };
+    Ok(layer.with_filter(filter_fn(|metadata| {
+        metadata.is_span() && *metadata.level() <= Level::INFO
+    })))
+}
+
+struct CaptureLayer {
+    next_id: AtomicU64,
+    writer: Mutex<BufWriter<File>>,
+}
+
+/// A span that has not yet closed.
+struct OpenSpan {
+    span: CapturedSpan,
+    start: Instant,
+}
+
+impl<S: Subscriber + for<'span> LookupSpan<'span>> Layer<S> for CaptureLayer {
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(id) else {
+            return;
+        };
+        let parent_id = span
+            .parent()
+            .and_then(|parent| Some(parent.extensions().get::<OpenSpan>()?.span.id));
+        let start_unix_nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_nanos() as u64)
+            .unwrap_or_default();
+        let mut captured = CapturedSpan {
+            id: self.next_id.fetch_add(1, Ordering::Relaxed),
+            parent_id,
+            name: span.name().to_owned(),
+            start_unix_nanos,
+            ..Default::default()
+        };
+        attrs.record(&mut CapturedSpanVisitor(&mut captured));
+        span.extensions_mut().insert(OpenSpan {
+            span: captured,
+            start: Instant::now(),
+        });
+    }
+
+    fn on_record(&self, id: &Id, values: &Record<'_>, ctx: Context<'_, S>) {
+        if let Some(span) = ctx.span(id)
+            && let Some(open) = span.extensions_mut().get_mut::<OpenSpan>()
+        {
+            values.record(&mut CapturedSpanVisitor(&mut open.span));
+        }
+    }
+
+    fn on_close(&self, id: Id, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(&id) else {
+            return;
+        };
+        let Some(OpenSpan {
+            span: mut captured,
+            start,
+        }) = span.extensions_mut().remove::<OpenSpan>()
+        else {
+            return;
+        };
+        captured.duration_nanos = start.elapsed().as_nanos() as u64;
+
+        let mut writer = self.writer.lock().unwrap();
+        let result = serde_json::to_writer(&mut *writer, &captured)
+            .map_err(std::io::Error::from)
+            .and_then(|()| writer.write_all(b"\n"));
+        // Flush whole traces, so that the file is usable while Spin is running.
+        let result = result.and_then(|()| {
+            if captured.parent_id.is_none() {
+                writer.flush()
+            } else {
+                Ok(())
+            }
+        });
+        if let Err(err) = result {
+            // Avoid `tracing` here, as this runs inside the subscriber.
+            eprintln!("Failed to write to span capture file: {err}");
+        }
+    }
+}
+
+/// Records the span fields that are captured.
+struct CapturedSpanVisitor<'a>(&'a mut CapturedSpan);
+
+impl Visit for CapturedSpanVisitor<'_> {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        let value = value.to_owned();
+        match field.name() {
+            "otel.name" => self.0.name = value,
+            "otel.kind" => self.0.kind = Some(value),
+            "http.route" => self.0.route = Some(value),
+            "spin.component.id" => self.0.component_id = Some(value),
+            _ => {}
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+        if matches!(
+            field.name(),
+            "otel.name" | "otel.kind" | "http.route" | "spin.component.id"
+        ) {
+            self.record_str(field, &format!("{value:?}"));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tracing_subscriber::prelude::*;
+
+    use super::*;
+
+    #[test]
+    fn captures_spans_with_parents() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("spans.jsonl");
+        let subscriber = tracing_subscriber::registry().with(capture_layer(&path).unwrap());
+        tracing::subscriber::with_default(subscriber, || {
+            let request = tracing::info_span!(
+                "request",
+                otel.name = "GET /hello",
+                http.route = "/hello",
+                otel.kind = "server"
+            );
+            let _request = request.enter();
+            let _call = tracing::info_span!("spin_key_value.get", otel.kind = "client").entered();
+            let _ignored = tracing::debug_span!("detail").entered();
+        });
+
+        let spans: Vec<CapturedSpan> = std::fs::read_to_string(&path)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        let [call, request] = &spans[..] else {
+            panic!("expected two spans, got {spans:?}");
+        };
+        assert_eq!("GET /hello", request.name);
+        assert_eq!(Some("/hello"), request.route.as_deref());
+        assert_eq!(None, request.parent_id);
+        assert_eq!("spin_key_value.get", call.name);
+        assert_eq!(Some("client"), call.kind.as_deref());
+        assert_eq!(Some(request.id), call.parent_id);
+    }
+}
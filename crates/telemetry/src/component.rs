@@ -0,0 +1,71 @@
+//! Attribution of telemetry to the component on whose behalf work is done.
+
+use std::fmt::Debug;
+
+use tracing::{
+    Subscriber,
+    field::{Field, Visit},
+    span::{Attributes, Id, Record},
+};
+use tracing_subscriber::{
+    Layer,
+    field::RecordFields,
+    layer::Context,
+    registry::{LookupSpan, SpanRef},
+};
+
+/// The span field identifying the component on whose behalf work is done. Telemetry is
+/// attributed to the component of its closest ancestor span that has this field.
+const COMPONENT_ID_FIELD: &str = "spin.component.id";
+
+/// The component a span does work for.
+struct ComponentId(String);
+
+/// Records the component each span does work for, for other layers and filters to attribute
+/// telemetry with [`component_id`].
+pub(crate) struct ComponentIdLayer;
+
+impl<S: Subscriber + for<'span> LookupSpan<'span>> Layer<S> for ComponentIdLayer {
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        if let Some(span) = ctx.span(id) {
+            record_component_id(&span, attrs);
+        }
+    }
+
+    fn on_record(&self, id: &Id, values: &Record<'_>, ctx: Context<'_, S>) {
+        if let Some(span) = ctx.span(id) {
+            record_component_id(&span, values);
+        }
+    }
+}
+
+/// Records the component a span does work for, if given by its fields.
+fn record_component_id<'a, S: LookupSpan<'a>>(span: &SpanRef<'a, S>, fields: &impl RecordFields) {
+    let mut visitor = ComponentIdVisitor(None);
+    fields.record(&mut visitor);
+    if let Some(component_id) = visitor.0 {
+        span.extensions_mut().replace(ComponentId(component_id));
+    }
+}
+
+/// Returns the component a span, or its closest ancestor that has one, does work for.
+pub(crate) fn component_id<'a, S: LookupSpan<'a>>(span: &SpanRef<'a, S>) -> Option<String> {
+    span.scope()
+        .find_map(|span| Some(span.extensions().get::<ComponentId>()?.0.clone()))
+}
+
+struct ComponentIdVisitor(Option<String>);
+
+impl Visit for ComponentIdVisitor {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == COMPONENT_ID_FIELD {
+            self.0 = Some(value.to_owned());
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+        if field.name() == COMPONENT_ID_FIELD {
+            self.0 = Some(format!("{value:?}"));
+        }
+    }
+}
@@ -0,0 +1,450 @@
+//! Renders telemetry in the terminal for local development, without a collector.
+//!
+//! Spans are printed as an indented latency tree once their root span closes, and metrics
+//! recorded through the [`crate::metrics`] macros are printed as periodic summaries. Spans
+//! exported by guests can be rendered the same way with [`render_spans`].
+
+use std::collections::BTreeMap;
+use std::fmt::{Debug, Write as _};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime};
+
+use opentelemetry_sdk::trace::SpanData;
+
+use tracing::{
+    Event, Level, Metadata, Subscriber,
+    field::{Field, Visit},
+    span::{Attributes, Id, Record},
+};
+use tracing_subscriber::{
+    Layer,
+    filter::{FilterFn, filter_fn},
+    layer::Context,
+    registry::LookupSpan,
+};
+
+/// How often metric summaries are printed.
+const METRICS_SUMMARY_INTERVAL: Duration = Duration::from_secs(10);
+
+/// The prefixes the metrics macros give metric fields.
+const METRIC_PREFIXES: [&str; 4] = ["monotonic_counter.", "counter.", "histogram.", "gauge."];
+
+/// Constructs a layer for the tracing subscriber that prints spans and metrics to stderr.
+pub(crate) fn console_layer<S: Subscriber + for<'span> LookupSpan<'span>>() -> impl Layer<S> {
+    let metrics = Arc::new(Mutex::new(MetricSummaries::default()));
+    let summaries = metrics.clone();
+    std::thread::spawn(move || {
+        loop {
+            std::thread::sleep(METRICS_SUMMARY_INTERVAL);
+            let mut summaries = summaries.lock().unwrap();
+            if summaries.updated {
+                eprint!("{}", summaries.render());
+                summaries.updated = false;
+            }
+        }
+    });
+    ConsoleLayer { metrics }.with_filter(console_filter())
+}
+
+/// Lets through spans at INFO level or above, and metric events.
+fn console_filter() -> FilterFn<impl Fn(&Metadata<'_>) -> bool> {
+    filter_fn(|metadata| {
+        if metadata.is_span() {
+            *metadata.level() <= Level::INFO
+        } else {
+            metadata
+                .fields()
+                .iter()
+                .any(|field| metric_name(field.name()).is_some())
+        }
+    })
+}
+
+struct ConsoleLayer {
+    metrics: Arc<Mutex<MetricSummaries>>,
+}
+
+/// A span that has not yet closed.
+struct OpenSpan {
+    name: String,
+    start: Instant,
+    children: Vec<ClosedSpan>,
+}
+
+/// A span that has closed, with its closed children.
+struct ClosedSpan {
+    name: String,
+    start: SystemTime,
+    duration: Duration,
+    children: Vec<ClosedSpan>,
+}
+
+impl<S: Subscriber + for<'span> LookupSpan<'span>> Layer<S> for ConsoleLayer {
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(id) else {
+            return;
+        };
+        let mut visitor = OtelNameVisitor(None);
+        attrs.record(&mut visitor);
+        span.extensions_mut().insert(OpenSpan {
+            name: visitor.0.unwrap_or_else(|| span.name().to_owned()),
+            start: Instant::now(),
+            children: Vec::new(),
+        });
+    }
+
+    fn on_record(&self, id: &Id, values: &Record<'_>, ctx: Context<'_, S>) {
+        let mut visitor = OtelNameVisitor(None);
+        values.record(&mut visitor);
+        let (Some(name), Some(span)) = (visitor.0, ctx.span(id)) else {
+            return;
+        };
+        if let Some(open) = span.extensions_mut().get_mut::<OpenSpan>() {
+            open.name = name;
+        }
+    }
+
+    fn on_close(&self, id: Id, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(&id) else {
+            return;
+        };
+        let Some(open) = span.extensions_mut().remove::<OpenSpan>() else {
+            return;
+        };
+        let duration = open.start.elapsed();
+        let mut closed = ClosedSpan {
+            name: open.name,
+            start: SystemTime::now() - duration,
+            duration,
+            children: open.children,
+        };
+        closed.children.sort_by_key(|child| child.start);
+
+        // Hand the span to its closest ancestor that is still open; print it if there is none.
+        let parent = span
+            .scope()
+            .skip(1)
+            .find(|ancestor| ancestor.extensions().get::<OpenSpan>().is_some());
+        match parent {
+            Some(parent) => {
+                if let Some(parent) = parent.extensions_mut().get_mut::<OpenSpan>() {
+                    parent.children.push(closed);
+                }
+            }
+            None => eprint!("{}", render_tree(&closed)),
+        }
+    }
+
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        let mut visitor = MetricVisitor(Vec::new());
+        event.record(&mut visitor);
+        if visitor.0.is_empty() {
+            return;
+        }
+        let mut metrics = self.metrics.lock().unwrap();
+        for (field, value) in visitor.0 {
+            metrics.record(field, value);
+        }
+    }
+}
+
+/// Renders spans as latency trees, one per span whose parent is not among them.
+pub fn render_spans(spans: &[SpanData]) -> String {
+    fn closed(span: &SpanData, spans: &[SpanData]) -> ClosedSpan {
+        let span_id = span.span_context.span_id();
+        let mut children: Vec<_> = spans
+            .iter()
+            .filter(|child| child.parent_span_id == span_id)
+            .map(|child| closed(child, spans))
+            .collect();
+        children.sort_by_key(|child| child.start);
+        ClosedSpan {
+            name: span.name.to_string(),
+            start: span.start_time,
+            duration: span
+                .end_time
+                .duration_since(span.start_time)
+                .unwrap_or_default(),
+            children,
+        }
+    }
+
+    let mut roots: Vec<_> = spans
+        .iter()
+        .filter(|span| {
+            !spans
+                .iter()
+                .any(|parent| parent.span_context.span_id() == span.parent_span_id)
+        })
+        .map(|root| closed(root, spans))
+        .collect();
+    roots.sort_by_key(|root| root.start);
+    roots.iter().map(render_tree).collect()
+}
+
+/// Renders a span and its descendants, one per line, indented by depth.
+fn render_tree(root: &ClosedSpan) -> String {
+    fn render(span: &ClosedSpan, prefix: &str, is_last: bool, is_root: bool, out: &mut String) {
+        let (branch, child_prefix) = if is_root {
+            ("", String::new())
+        } else if is_last {
+            ("└─ ", format!("{prefix}   "))
+        } else {
+            ("├─ ", format!("{prefix}│  "))
+        };
+        let _ = writeln!(
+            out,
+            "{prefix}{branch}{} {}",
+            span.name,
+            format_duration(span.duration)
+        );
+        for (i, child) in span.children.iter().enumerate() {
+            render(
+                child,
+                &child_prefix,
+                i + 1 == span.children.len(),
+                false,
+                out,
+            );
+        }
+    }
+
+    let mut out = String::new();
+    render(root, "", true, true, &mut out);
+    out
+}
+
+fn format_duration(duration: Duration) -> String {
+    let micros = duration.as_micros();
+    if micros < 1_000 {
+        format!("{micros}µs")
+    } else if micros < 1_000_000 {
+        format!("{:.2}ms", duration.as_secs_f64() * 1_000.0)
+    } else {
+        format!("{:.2}s", duration.as_secs_f64())
+    }
+}
+
+/// Captures the `otel.name` field, which names spans in exported traces.
+struct OtelNameVisitor(Option<String>);
+
+impl Visit for OtelNameVisitor {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "otel.name" {
+            self.0 = Some(value.to_owned());
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+        if field.name() == "otel.name" {
+            self.0 = Some(format!("{value:?}"));
+        }
+    }
+}
+
+/// Returns the kind and name of a metric from the name of the field recording it.
+fn metric_name(field: &str) -> Option<(&str, &str)> {
+    METRIC_PREFIXES.iter().find_map(|prefix| {
+        field
+            .strip_prefix(prefix)
+            .map(|name| (prefix.trim_end_matches('.'), name))
+    })
+}
+
+/// Collects the metric fields of an event.
+struct MetricVisitor(Vec<(&'static str, f64)>);
+
+impl MetricVisitor {
+    fn record_value(&mut self, field: &Field, value: f64) {
+        if metric_name(field.name()).is_some() {
+            self.0.push((field.name(), value));
+        }
+    }
+}
+
+impl Visit for MetricVisitor {
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        self.record_value(field, value);
+    }
+
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.record_value(field, value as f64);
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.record_value(field, value as f64);
+    }
+
+    fn record_debug(&mut self, _field: &Field, _value: &dyn Debug) {}
+}
+
+/// Running summaries of the metrics recorded so far, by metric name.
+#[derive(Default)]
+struct MetricSummaries {
+    metrics: BTreeMap<&'static str, MetricSummary>,
+    /// Whether any metric was recorded since the summaries were last printed.
+    updated: bool,
+}
+
+enum MetricSummary {
+    Sum(f64),
+    Last(f64),
+    Distribution {
+        count: u64,
+        sum: f64,
+        min: f64,
+        max: f64,
+    },
+}
+
+impl MetricSummaries {
+    fn record(&mut self, field: &'static str, value: f64) {
+        let Some((kind, name)) = metric_name(field) else {
+            return;
+        };
+        self.updated = true;
+        let summary = self.metrics.entry(name).or_insert_with(|| match kind {
+            "histogram" => MetricSummary::Distribution {
+                count: 0,
+                sum: 0.0,
+                min: f64::INFINITY,
+                max: f64::NEG_INFINITY,
+            },
+            "gauge" => MetricSummary::Last(0.0),
+            _ => MetricSummary::Sum(0.0),
+        });
+        match summary {
+            MetricSummary::Sum(sum) => *sum += value,
+            MetricSummary::Last(last) => *last = value,
+            MetricSummary::Distribution {
+                count,
+                sum,
+                min,
+                max,
+            } => {
+                *count += 1;
+                *sum += value;
+                *min = min.min(value);
+                *max = max.max(value);
+            }
+        }
+    }
+
+    fn render(&self) -> String {
+        let mut out = String::from("Metrics:\n");
+        for (name, summary) in &self.metrics {
+            let _ = match summary {
+                MetricSummary::Sum(sum) | MetricSummary::Last(sum) => {
+                    writeln!(out, "  {name}: {sum}")
+                }
+                MetricSummary::Distribution {
+                    count,
+                    sum,
+                    min,
+                    max,
+                } => writeln!(
+                    out,
+                    "  {name}: count={count} mean={:.4} min={min:.4} max={max:.4}",
+                    sum / *count as f64
+                ),
+            };
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use opentelemetry::{
+        InstrumentationScope,
+        trace::{SpanContext, SpanId, SpanKind, Status, TraceFlags, TraceId, TraceState},
+    };
+    use opentelemetry_sdk::trace::{SpanEvents, SpanLinks};
+
+    use super::*;
+
+    fn closed(name: &str, millis: u64, children: Vec<ClosedSpan>) -> ClosedSpan {
+        ClosedSpan {
+            name: name.to_owned(),
+            start: SystemTime::now(),
+            duration: Duration::from_millis(millis),
+            children,
+        }
+    }
+
+    #[test]
+    fn renders_span_tree() {
+        let tree = closed(
+            "GET /hello",
+            12,
+            vec![
+                closed(
+                    "execute_wasm_component hello",
+                    10,
+                    vec![closed("spin_key_value.get", 2, vec![])],
+                ),
+                closed("spin_outbound_http.send_request", 1, vec![]),
+            ],
+        );
+        assert_eq!(
+            "GET /hello 12.00ms\n\
+             ├─ execute_wasm_component hello 10.00ms\n\
+             │  └─ spin_key_value.get 2.00ms\n\
+             └─ spin_outbound_http.send_request 1.00ms\n",
+            render_tree(&tree)
+        );
+    }
+
+    fn span_data(name: &str, span_id: u64, parent_span_id: u64, millis: u64) -> SpanData {
+        let start_time = SystemTime::UNIX_EPOCH + Duration::from_millis(span_id);
+        SpanData {
+            span_context: SpanContext::new(
+                TraceId::from(1),
+                SpanId::from(span_id),
+                TraceFlags::SAMPLED,
+                false,
+                TraceState::default(),
+            ),
+            parent_span_id: SpanId::from(parent_span_id),
+            span_kind: SpanKind::Internal,
+            name: name.to_owned().into(),
+            start_time,
+            end_time: start_time + Duration::from_millis(millis),
+            attributes: vec![],
+            dropped_attributes_count: 0,
+            events: SpanEvents::default(),
+            links: SpanLinks::default(),
+            status: Status::Unset,
+            instrumentation_scope: InstrumentationScope::builder("test").build(),
+        }
+    }
+
+    #[test]
+    fn renders_spans_by_parent() {
+        let spans = vec![
+            span_data("db.query", 3, 2, 2),
+            span_data("handle", 2, 100, 5),
+            span_data("render", 4, 2, 1),
+        ];
+        assert_eq!(
+            "handle 5.00ms\n\
+             ├─ db.query 2.00ms\n\
+             └─ render 1.00ms\n",
+            render_spans(&spans)
+        );
+    }
+
+    #[test]
+    fn summarizes_metrics_by_kind() {
+        let mut summaries = MetricSummaries::default();
+        summaries.record("monotonic_counter.spin.request_count", 1.0);
+        summaries.record("monotonic_counter.spin.request_count", 1.0);
+        summaries.record("histogram.spin.request_duration", 0.5);
+        summaries.record("histogram.spin.request_duration", 1.5);
+        summaries.record("not_a_metric", 1.0);
+        assert_eq!(
+            "Metrics:\n  spin.request_count: 2\n  spin.request_duration: count=2 mean=1.0000 min=0.5000 max=1.5000\n",
+            summaries.render()
+        );
+    }
+}
@@ -1,20 +1,49 @@
-use std::env;
+use std::{env, fs};
 
 use opentelemetry::{Key, KeyValue, Value};
 use opentelemetry_sdk::{
     Resource,
-    resource::{EnvResourceDetector, ResourceDetector},
+    resource::{EnvResourceDetector, ResourceDetector, TelemetryResourceDetector},
 };
 
+use crate::env::SPIN_CLUSTER_NODE_ID;
+
 const OTEL_SERVICE_NAME: &str = "OTEL_SERVICE_NAME";
 
-/// Custom resource detector for Spin relevant attributes service.name and service.version.
+/// Builds the resource describing Spin's telemetry, or an app's if given the attributes from its
+/// manifest.
+pub fn detected_resource(
+    spin_version: String,
+    app_attributes: impl IntoIterator<Item = (String, String)>,
+) -> Resource {
+    Resource::builder()
+        .with_detectors(&[
+            // Set service.name from env OTEL_SERVICE_NAME > env OTEL_RESOURCE_ATTRIBUTES > spin
+            // Set service.version from Spin metadata
+            Box::new(SpinResourceDetector::new(spin_version)) as Box<dyn ResourceDetector>,
+            // Sets host.*, container.id, k8s.* and cloud.* fields detected from the environment
+            Box::new(EnvironmentResourceDetector),
+            // Sets fields from the app manifest
+            Box::new(AppResourceDetector::new(app_attributes)),
+            // Sets fields from env OTEL_RESOURCE_ATTRIBUTES
+            Box::new(EnvResourceDetector::new()),
+            // Sets telemetry.sdk{name, language, version}
+            Box::new(TelemetryResourceDetector),
+        ])
+        .build()
+}
+
+/// Custom resource detector for Spin relevant attributes service.name, service.version and
+/// service.instance.id.
 ///
 /// To set service.name this detector will first try `OTEL_SERVICE_NAME` env. If it's not available,
 /// then it will check the `OTEL_RESOURCE_ATTRIBUTES` env and see if it contains `service.name`
 /// resource. If it's also not available, it will use `spin`.
 ///
 /// To set service.version, it will use the spin_version passed in new.
+///
+/// When running as a cluster node, service.instance.id is set to the node ID from the
+/// `SPIN_CLUSTER_NODE_ID` env, so that telemetry from the nodes serving an app can be told apart.
 #[derive(Debug)]
 pub struct SpinResourceDetector {
     spin_version: String,
@@ -39,11 +68,289 @@ impl ResourceDetector for SpinResourceDetector {
                     .get(&Key::new("service.name"))
             })
             .unwrap_or_else(|| "spin".into());
-        Resource::builder()
-            .with_attributes(vec![
-                KeyValue::new("service.name", service_name),
-                KeyValue::new("service.version", self.spin_version.clone()),
-            ])
+        let mut attributes = vec![
+            KeyValue::new("service.name", service_name),
+            KeyValue::new("service.version", self.spin_version.clone()),
+        ];
+        if let Some(node_id) = env::var(SPIN_CLUSTER_NODE_ID)
+            .ok()
+            .filter(|s| !s.is_empty())
+        {
+            attributes.push(KeyValue::new("service.instance.id", node_id));
+        }
+        Resource::builder().with_attributes(attributes).build()
+    }
+}
+
+/// Resource detector for the environment Spin runs in: the host, and the container, Kubernetes pod
+/// and cloud platform if any.
+///
+/// Detection only reads local files and env vars, so that it cannot hold up startup.
+#[derive(Debug, Default)]
+pub struct EnvironmentResourceDetector;
+
+impl ResourceDetector for EnvironmentResourceDetector {
+    fn detect(&self) -> Resource {
+        let var = |name: &str| env::var(name).ok().filter(|s| !s.is_empty());
+        let mut attributes = host_attributes(&var);
+        if let Some(container_id) = container_id() {
+            attributes.push(KeyValue::new("container.id", container_id));
+        }
+        attributes.extend(kubernetes_attributes(&var, &read_trimmed));
+        attributes.extend(cloud_attributes(&var, &|name| {
+            read_trimmed(&format!("/sys/class/dmi/id/{name}"))
+        }));
+        Resource::builder_empty()
+            .with_attributes(attributes)
             .build()
     }
 }
+
+/// Resource detector for attributes given in an app's manifest.
+///
+/// It is meant to run before [`EnvResourceDetector`], so that the `OTEL_RESOURCE_ATTRIBUTES` env
+/// overrides attributes from the manifest. Likewise, `service.name` is ignored if the
+/// `OTEL_SERVICE_NAME` env is set.
+#[derive(Debug)]
+pub struct AppResourceDetector {
+    attributes: Vec<KeyValue>,
+}
+
+impl AppResourceDetector {
+    /// Create a new instance of AppResourceDetector.
+    pub fn new(attributes: impl IntoIterator<Item = (String, String)>) -> Self {
+        let service_name_set = env::var(OTEL_SERVICE_NAME).is_ok_and(|s| !s.is_empty());
+        let attributes = attributes
+            .into_iter()
+            .filter(|(key, _)| !(service_name_set && key == "service.name"))
+            .map(|(key, value)| KeyValue::new(key, value))
+            .collect();
+        Self { attributes }
+    }
+}
+
+impl ResourceDetector for AppResourceDetector {
+    fn detect(&self) -> Resource {
+        Resource::builder_empty()
+            .with_attributes(self.attributes.clone())
+            .build()
+    }
+}
+
+type Lookup<'a> = &'a dyn Fn(&str) -> Option<String>;
+
+fn read_trimmed(path: &str) -> Option<String> {
+    let contents = fs::read_to_string(path).ok()?;
+    Some(contents.trim().to_owned()).filter(|s| !s.is_empty())
+}
+
+fn host_attributes(var: Lookup) -> Vec<KeyValue> {
+    let arch = match env::consts::ARCH {
+        "x86_64" => "amd64",
+        "aarch64" => "arm64",
+        "arm" => "arm32",
+        "powerpc" => "ppc32",
+        "powerpc64" => "ppc64",
+        arch => arch,
+    };
+    let os_type = match env::consts::OS {
+        "macos" => "darwin",
+        os => os,
+    };
+    let mut attributes = vec![
+        KeyValue::new("host.arch", arch),
+        KeyValue::new("os.type", os_type),
+    ];
+    let host_name = read_trimmed("/proc/sys/kernel/hostname")
+        .or_else(|| var("HOSTNAME"))
+        .or_else(|| var("COMPUTERNAME"));
+    if let Some(host_name) = host_name {
+        attributes.push(KeyValue::new("host.name", host_name));
+    }
+    attributes
+}
+
+/// Returns the ID of the container Spin runs in, if any, from the cgroup (v1) or the mounts
+/// (cgroup v2) of the process.
+fn container_id() -> Option<String> {
+    read_trimmed("/proc/self/cgroup")
+        .and_then(|cgroup| container_id_from_cgroup(&cgroup))
+        .or_else(|| {
+            read_trimmed("/proc/self/mountinfo")
+                .and_then(|mountinfo| container_id_from_mountinfo(&mountinfo))
+        })
+}
+
+/// Finds a container ID as the last segment of a cgroup path, e.g.
+/// `0::/system.slice/docker-<id>.scope`.
+fn container_id_from_cgroup(cgroup: &str) -> Option<String> {
+    cgroup.lines().find_map(|line| {
+        let segment = line.rsplit('/').next()?;
+        let segment = segment.strip_suffix(".scope").unwrap_or(segment);
+        let id = segment.rsplit('-').next()?;
+        is_container_id(id).then(|| id.to_owned())
+    })
+}
+
+/// Finds a container ID in the path of a container runtime's mount, e.g.
+/// `/var/lib/docker/containers/<id>/hostname`.
+fn container_id_from_mountinfo(mountinfo: &str) -> Option<String> {
+    mountinfo.lines().find_map(|line| {
+        let mut segments = line.split(['/', ' ']);
+        segments.find(|segment| *segment == "containers")?;
+        let id = segments.next()?;
+        is_container_id(id).then(|| id.to_owned())
+    })
+}
+
+fn is_container_id(id: &str) -> bool {
+    id.len() == 64 && id.bytes().all(|b| b.is_ascii_hexdigit())
+}
+
+/// Detects the Kubernetes pod Spin runs in. Pod details are taken from the env vars that the
+/// downward API conventionally sets, falling back to what every pod can see.
+fn kubernetes_attributes(var: Lookup, read: Lookup) -> Vec<KeyValue> {
+    if var("KUBERNETES_SERVICE_HOST").is_none() {
+        return vec![];
+    }
+    let namespace = var("K8S_NAMESPACE_NAME")
+        .or_else(|| read("/var/run/secrets/kubernetes.io/serviceaccount/namespace"));
+    // A pod's host name is its name unless the pod spec sets one
+    let pod_name = var("K8S_POD_NAME").or_else(|| var("HOSTNAME"));
+    [
+        ("k8s.namespace.name", namespace),
+        ("k8s.pod.name", pod_name),
+        ("k8s.pod.uid", var("K8S_POD_UID")),
+        ("k8s.node.name", var("K8S_NODE_NAME")),
+    ]
+    .into_iter()
+    .filter_map(|(key, value)| Some(KeyValue::new(key, value?)))
+    .collect()
+}
+
+/// Detects the cloud platform Spin runs on from the env vars set by serverless platforms and the
+/// DMI (firmware) details of virtual machines.
+fn cloud_attributes(var: Lookup, dmi: Lookup) -> Vec<KeyValue> {
+    let kubernetes = var("KUBERNETES_SERVICE_HOST").is_some();
+    let sys_vendor = dmi("sys_vendor").unwrap_or_default();
+    let product_name = dmi("product_name").unwrap_or_default();
+    let (provider, platform, region) = if var("AWS_LAMBDA_FUNCTION_NAME").is_some() {
+        ("aws", "aws_lambda", var("AWS_REGION"))
+    } else if var("ECS_CONTAINER_METADATA_URI_V4").is_some() {
+        ("aws", "aws_ecs", var("AWS_REGION"))
+    } else if sys_vendor == "Amazon EC2" {
+        let platform = if kubernetes { "aws_eks" } else { "aws_ec2" };
+        ("aws", platform, var("AWS_REGION"))
+    } else if var("K_SERVICE").is_some() && var("K_CONFIGURATION").is_some() {
+        ("gcp", "gcp_cloud_run", None)
+    } else if product_name == "Google Compute Engine" {
+        let platform = if kubernetes {
+            "gcp_kubernetes_engine"
+        } else {
+            "gcp_compute_engine"
+        };
+        ("gcp", platform, None)
+    } else if var("WEBSITE_SITE_NAME").is_some() {
+        ("azure", "azure_app_service", var("REGION_NAME"))
+    } else if dmi("chassis_asset_tag").as_deref() == Some(AZURE_CHASSIS_ASSET_TAG) {
+        let platform = if kubernetes { "azure_aks" } else { "azure_vm" };
+        ("azure", platform, None)
+    } else {
+        return vec![];
+    };
+    let mut attributes = vec![
+        KeyValue::new("cloud.provider", provider),
+        KeyValue::new("cloud.platform", platform),
+    ];
+    if let Some(region) = region {
+        attributes.push(KeyValue::new("cloud.region", region));
+    }
+    attributes
+}
+
+/// The chassis asset tag of Azure virtual machines.
+const AZURE_CHASSIS_ASSET_TAG: &str = "7783-7084-3265-9085-8269-3286-77";
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    fn lookup<'a>(values: &'a HashMap<&str, &str>) -> impl Fn(&str) -> Option<String> + 'a {
+        move |name: &str| values.get(name).map(|value| value.to_string())
+    }
+
+    const ID: &str = "3c1bd1c8d4f2ad42c3e4a8a1e1b1f1f1c1d1e1f1a1b1c1d1e1f1a1b1c1d1e1f1";
+
+    #[test]
+    fn finds_container_ids() {
+        let cgroup = format!("12:cpu:/\n0::/system.slice/docker-{ID}.scope");
+        assert_eq!(Some(ID.to_owned()), container_id_from_cgroup(&cgroup));
+        let cgroup = format!("1:name=systemd:/kubepods/besteffort/pod1234/{ID}");
+        assert_eq!(Some(ID.to_owned()), container_id_from_cgroup(&cgroup));
+        assert_eq!(None, container_id_from_cgroup("0::/user.slice"));
+
+        let mountinfo = format!(
+            "1 2 8:1 /var/lib/docker/containers/{ID}/hostname /etc/hostname rw - ext4 /dev/sda1 rw"
+        );
+        assert_eq!(Some(ID.to_owned()), container_id_from_mountinfo(&mountinfo));
+        assert_eq!(
+            None,
+            container_id_from_mountinfo("1 2 8:1 / / rw - ext4 /dev/sda1 rw")
+        );
+    }
+
+    #[test]
+    fn detects_kubernetes_pods() {
+        let vars = HashMap::from([
+            ("KUBERNETES_SERVICE_HOST", "10.0.0.1"),
+            ("HOSTNAME", "api-7d9f-x2x"),
+            ("K8S_NODE_NAME", "node-1"),
+        ]);
+        let files = HashMap::from([(
+            "/var/run/secrets/kubernetes.io/serviceaccount/namespace",
+            "shop",
+        )]);
+        assert_eq!(
+            vec![
+                KeyValue::new("k8s.namespace.name", "shop"),
+                KeyValue::new("k8s.pod.name", "api-7d9f-x2x"),
+                KeyValue::new("k8s.node.name", "node-1"),
+            ],
+            kubernetes_attributes(&lookup(&vars), &lookup(&files))
+        );
+
+        let vars = HashMap::from([("HOSTNAME", "laptop")]);
+        assert!(kubernetes_attributes(&lookup(&vars), &lookup(&files)).is_empty());
+    }
+
+    #[test]
+    fn detects_cloud_platforms() {
+        let no_dmi = HashMap::new();
+        let vars = HashMap::from([
+            ("AWS_LAMBDA_FUNCTION_NAME", "orders"),
+            ("AWS_REGION", "eu-west-1"),
+        ]);
+        assert_eq!(
+            vec![
+                KeyValue::new("cloud.provider", "aws"),
+                KeyValue::new("cloud.platform", "aws_lambda"),
+                KeyValue::new("cloud.region", "eu-west-1"),
+            ],
+            cloud_attributes(&lookup(&vars), &lookup(&no_dmi))
+        );
+
+        let vars = HashMap::from([("KUBERNETES_SERVICE_HOST", "10.0.0.1")]);
+        let dmi = HashMap::from([("product_name", "Google Compute Engine")]);
+        assert_eq!(
+            vec![
+                KeyValue::new("cloud.provider", "gcp"),
+                KeyValue::new("cloud.platform", "gcp_kubernetes_engine"),
+            ],
+            cloud_attributes(&lookup(&vars), &lookup(&dmi))
+        );
+
+        assert!(cloud_attributes(&lookup(&HashMap::new()), &lookup(&no_dmi)).is_empty());
+    }
+}
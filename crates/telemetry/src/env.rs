@@ -1,5 +1,7 @@
 use std::env::VarError;
 
+use anyhow::Context as _;
+
 use opentelemetry_otlp::{
     OTEL_EXPORTER_OTLP_ENDPOINT, OTEL_EXPORTER_OTLP_LOGS_ENDPOINT,
     OTEL_EXPORTER_OTLP_METRICS_ENDPOINT, OTEL_EXPORTER_OTLP_PROTOCOL,
@@ -11,6 +13,28 @@ const OTEL_EXPORTER_OTLP_TRACES_PROTOCOL: &str = "OTEL_EXPORTER_OTLP_TRACES_PROT
 const OTEL_EXPORTER_OTLP_METRICS_PROTOCOL: &str = "OTEL_EXPORTER_OTLP_METRICS_PROTOCOL";
 const OTEL_EXPORTER_OTLP_LOGS_PROTOCOL: &str = "OTEL_EXPORTER_OTLP_LOGS_PROTOCOL";
 const SPIN_DISABLE_LOG_TO_TRACING: &str = "SPIN_DISABLE_LOG_TO_TRACING";
+/// The environment variable giving the address on which to serve host metrics
+/// in the Prometheus format, e.g. `127.0.0.1:9090`.
+pub const SPIN_METRICS_LISTEN: &str = "SPIN_METRICS_LISTEN";
+/// The environment variable enabling the console telemetry exporter for local development.
+pub const SPIN_OTEL_CONSOLE: &str = "SPIN_OTEL_CONSOLE";
+/// The environment variable enabling the audit log of privileged host operations. It is set to a
+/// file path to write JSON lines to, or to `otel` to export OTel log records.
+pub const SPIN_AUDIT_LOG: &str = "SPIN_AUDIT_LOG";
+/// The environment variable giving a file to capture spans to, for analysis with
+/// `spin telemetry report`.
+pub const SPIN_OTEL_CAPTURE: &str = "SPIN_OTEL_CAPTURE";
+/// The environment variable giving the number of recently completed traces kept in memory, for
+/// viewing with `spin trace tail`.
+pub const SPIN_OTEL_RECENT_TRACES: &str = "SPIN_OTEL_RECENT_TRACES";
+/// The environment variable giving the duration, e.g. `500ms`, above which requests are logged
+/// as slow.
+pub const SPIN_SLOW_REQUEST_THRESHOLD: &str = "SPIN_SLOW_REQUEST_THRESHOLD";
+/// The environment variable selecting the format of host logs (`text` or `json`).
+pub const SPIN_LOG_FORMAT: &str = "SPIN_LOG_FORMAT";
+/// The environment variable giving the identity of this node when several
+/// nodes serve the same app in cluster mode.
+pub const SPIN_CLUSTER_NODE_ID: &str = "SPIN_CLUSTER_NODE_ID";
 
 /// Returns a boolean indicating if the OTEL tracing layer should be enabled.
 ///
@@ -40,6 +64,84 @@ pub fn otel_metrics_enabled() -> bool {
     ]) && !otel_sdk_disabled()
 }
 
+/// Returns the address on which to serve host metrics in the Prometheus format, if any.
+///
+/// It is set by the `SPIN_METRICS_LISTEN` environment variable. Unlike the OTEL signals,
+/// it is not affected by `OTEL_SDK_DISABLED`.
+pub fn prometheus_metrics_addr() -> Option<String> {
+    std::env::var(SPIN_METRICS_LISTEN)
+        .ok()
+        .filter(|addr| !addr.is_empty())
+}
+
+/// Returns a boolean indicating if spans and metrics should be rendered in the terminal.
+///
+/// It is considered enabled if the environment variable `SPIN_OTEL_CONSOLE` is set to anything
+/// other than an empty string, `0`, or `false`.
+pub fn otel_console_enabled() -> bool {
+    std::env::var(SPIN_OTEL_CONSOLE)
+        .is_ok_and(|val| !(val.is_empty() || val == "0" || val.eq_ignore_ascii_case("false")))
+}
+
+/// Returns the file to capture spans to, if any.
+///
+/// It is set by the `SPIN_OTEL_CAPTURE` environment variable.
+pub fn otel_capture_path() -> Option<std::path::PathBuf> {
+    std::env::var_os(SPIN_OTEL_CAPTURE)
+        .filter(|path| !path.is_empty())
+        .map(Into::into)
+}
+
+/// Returns the duration above which requests are logged as slow, if any.
+///
+/// It is set by the `SPIN_SLOW_REQUEST_THRESHOLD` environment variable, e.g. to `500ms` or `2s`.
+pub fn slow_request_threshold() -> anyhow::Result<Option<std::time::Duration>> {
+    match std::env::var(SPIN_SLOW_REQUEST_THRESHOLD) {
+        Ok(threshold) if !threshold.is_empty() => {
+            spin_common::arg_parser::parse_duration(&threshold)
+                .map(Some)
+                .with_context(|| format!("invalid {SPIN_SLOW_REQUEST_THRESHOLD}"))
+        }
+        _ => Ok(None),
+    }
+}
+
+/// Returns the number of recently completed traces to keep in memory, if any.
+///
+/// It is set by the `SPIN_OTEL_RECENT_TRACES` environment variable; `0` keeps none.
+pub fn otel_recent_traces() -> anyhow::Result<Option<usize>> {
+    match std::env::var(SPIN_OTEL_RECENT_TRACES) {
+        Ok(count) if !count.is_empty() => {
+            let count = count
+                .parse::<usize>()
+                .with_context(|| format!("invalid {SPIN_OTEL_RECENT_TRACES}"))?;
+            Ok((count > 0).then_some(count))
+        }
+        _ => Ok(None),
+    }
+}
+
+/// Where the audit log of privileged host operations is written.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum AuditLogDestination {
+    /// A file that audit records are appended to as JSON lines.
+    File(std::path::PathBuf),
+    /// The OTel log exporter.
+    Otel,
+}
+
+/// Returns where the audit log should be written, if it is enabled.
+///
+/// It is set by the `SPIN_AUDIT_LOG` environment variable, to either `otel` or a file path.
+pub fn audit_log_destination() -> Option<AuditLogDestination> {
+    match std::env::var_os(SPIN_AUDIT_LOG) {
+        Some(val) if val.is_empty() => None,
+        Some(val) if val == "otel" => Some(AuditLogDestination::Otel),
+        Some(val) => Some(AuditLogDestination::File(val.into())),
+        None => None,
+    }
+}
+
 /// Returns a boolean indicating if the OTEL log layer should be enabled.
 ///
 /// It is considered enabled if any of the following environment variables are set and not empty:
@@ -63,6 +165,13 @@ pub fn spin_disable_log_to_tracing() -> bool {
     any_vars_set(&[SPIN_DISABLE_LOG_TO_TRACING])
 }
 
+/// Returns a boolean indicating if host logs should be emitted as JSON.
+///
+/// This is the case if the environment variable `SPIN_LOG_FORMAT` is set to `json`.
+pub fn json_log_format() -> bool {
+    std::env::var(SPIN_LOG_FORMAT).is_ok_and(|format| format.eq_ignore_ascii_case("json"))
+}
+
 fn any_vars_set(enabling_vars: &[&str]) -> bool {
     enabling_vars
         .iter()
@@ -0,0 +1,165 @@
+//! Metrics for calls from components to host services.
+
+use std::fmt::Debug;
+use std::time::Instant;
+
+use opentelemetry::{
+    KeyValue,
+    metrics::{Counter, Histogram, MeterProvider as _},
+};
+use opentelemetry_sdk::metrics::SdkMeterProvider;
+use tracing::{
+    Event, Subscriber,
+    field::{Field, Visit},
+    span::{Attributes, Id, Record},
+};
+use tracing_subscriber::{Layer, layer::Context, registry::LookupSpan};
+
+use crate::component;
+
+/// Records calls from components to host services as the `spin.host_call_duration` histogram
+/// and the `spin.host_call_errors` counter.
+///
+/// Host calls are identified by their spans being marked `otel.kind = "client"`, as the
+/// key-value, SQLite, LLM, and outbound networking factors do. Each call is tagged with the span
+/// name as `operation`, the calling component as `component_id`, and, where the span reports
+/// it, the backend serving the call as `backend`.
+pub(crate) struct HostCallLayer {
+    duration: Histogram<f64>,
+    errors: Counter<u64>,
+}
+
+impl HostCallLayer {
+    pub(crate) fn new(meter_provider: &SdkMeterProvider) -> Self {
+        let meter = meter_provider.meter("spin");
+        let duration = meter
+            .f64_histogram("spin.host_call_duration")
+            .with_unit("s")
+            .with_description("The duration of calls from components to host services")
+            .build();
+        let errors = meter
+            .u64_counter("spin.host_call_errors")
+            .with_description("The number of calls from components to host services that failed")
+            .build();
+        Self { duration, errors }
+    }
+}
+
+/// The state of a host call span.
+struct HostCall {
+    start: Instant,
+    backend: Option<String>,
+    failed: bool,
+}
+
+impl<S: Subscriber + for<'span> LookupSpan<'span>> Layer<S> for HostCallLayer {
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        let mut visitor = SpanVisitor::default();
+        attrs.record(&mut visitor);
+        let Some(span) = ctx.span(id) else {
+            return;
+        };
+        if visitor.is_client {
+            span.extensions_mut().insert(HostCall {
+                start: Instant::now(),
+                backend: visitor.backend.or(visitor.system),
+                failed: visitor.failed,
+            });
+        }
+    }
+
+    fn on_record(&self, id: &Id, values: &Record<'_>, ctx: Context<'_, S>) {
+        let mut visitor = SpanVisitor::default();
+        values.record(&mut visitor);
+        let Some(span) = ctx.span(id) else {
+            return;
+        };
+        if let Some(call) = span.extensions_mut().get_mut::<HostCall>() {
+            if let Some(backend) = visitor.backend {
+                call.backend = Some(backend);
+            }
+            call.failed |= visitor.failed;
+        }
+    }
+
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        // `#[instrument(err)]` reports errors as an `error` event in the span.
+        let mut visitor = ErrorVisitor(false);
+        event.record(&mut visitor);
+        if !visitor.0 {
+            return;
+        }
+        if let Some(span) = ctx.event_span(event)
+            && let Some(call) = span.extensions_mut().get_mut::<HostCall>()
+        {
+            call.failed = true;
+        }
+    }
+
+    fn on_close(&self, id: Id, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(&id) else {
+            return;
+        };
+        let Some(call) = span.extensions_mut().remove::<HostCall>() else {
+            return;
+        };
+        let component_id = component::component_id(&span);
+
+        let mut attributes = vec![KeyValue::new("operation", span.name())];
+        if let Some(component_id) = component_id {
+            attributes.push(KeyValue::new("component_id", component_id));
+        }
+        if let Some(backend) = call.backend {
+            attributes.push(KeyValue::new("backend", backend));
+        }
+        self.duration
+            .record(call.start.elapsed().as_secs_f64(), &attributes);
+        if call.failed {
+            self.errors.add(1, &attributes);
+        }
+    }
+}
+
+/// Collects the span fields relevant to host call metrics.
+#[derive(Default)]
+struct SpanVisitor {
+    is_client: bool,
+    /// A `*.backend` field, e.g. `kv.backend`.
+    backend: Option<String>,
+    /// A `db.system` or `messaging.system` field, used when there is no backend field.
+    system: Option<String>,
+    failed: bool,
+}
+
+impl Visit for SpanVisitor {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        match field.name() {
+            "otel.kind" => self.is_client = value == "client",
+            "db.system" | "messaging.system" => self.system = Some(value.to_owned()),
+            "error.type" => self.failed = true,
+            name if name.ends_with(".backend") => self.backend = Some(value.to_owned()),
+            _ => {}
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+        match field.name() {
+            "otel.kind" | "db.system" | "messaging.system" | "error.type" => {
+                self.record_str(field, &format!("{value:?}"))
+            }
+            name if name.ends_with(".backend") => self.record_str(field, &format!("{value:?}")),
+            _ => {}
+        }
+    }
+}
+
+/// Checks whether an event reports an error.
+struct ErrorVisitor(bool);
+
+impl Visit for ErrorVisitor {
+    fn record_debug(&mut self, field: &Field, _value: &dyn Debug) {
+        if field.name() == "error" {
+            self.0 = true;
+        }
+    }
+}
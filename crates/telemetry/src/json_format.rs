@@ -0,0 +1,203 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use opentelemetry::trace::TraceContextExt as _;
+use serde_json::{Map, Value};
+use tracing::{Event, Subscriber, field::Field};
+use tracing_opentelemetry::OpenTelemetrySpanExt as _;
+use tracing_subscriber::{
+    fmt::{
+        FmtContext, FormatEvent, FormatFields,
+        format::{Format, Writer},
+        time::{FormatTime, SystemTime},
+    },
+    registry::LookupSpan,
+};
+
+static JSON_LOG_FORMAT: AtomicBool = AtomicBool::new(false);
+
+/// Sets whether host logs are emitted as JSON rather than text.
+///
+/// This allows a command to apply a log format chosen on its command line,
+/// which is only known once telemetry has been initialized.
+pub fn set_json_log_format(json: bool) {
+    JSON_LOG_FORMAT.store(json, Ordering::Relaxed);
+}
+
+/// Formats host log events as JSON or text, as set by [`set_json_log_format`].
+#[derive(Default)]
+pub(crate) struct HostLogFormat {
+    text: Format,
+}
+
+impl<S, N> FormatEvent<S, N> for HostLogFormat
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+    N: for<'a> FormatFields<'a> + 'static,
+{
+    fn format_event(
+        &self,
+        ctx: &FmtContext<'_, S, N>,
+        writer: Writer<'_>,
+        event: &Event<'_>,
+    ) -> std::fmt::Result {
+        if JSON_LOG_FORMAT.load(Ordering::Relaxed) {
+            JsonFormat.format_event(ctx, writer, event)
+        } else {
+            self.text.format_event(ctx, writer, event)
+        }
+    }
+}
+
+/// Formats events as single-line JSON objects, including the trace and span
+/// IDs of the current span when OTel tracing is enabled.
+pub(crate) struct JsonFormat;
+
+impl<S, N> FormatEvent<S, N> for JsonFormat
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+    N: for<'a> FormatFields<'a> + 'static,
+{
+    fn format_event(
+        &self,
+        ctx: &FmtContext<'_, S, N>,
+        mut writer: Writer<'_>,
+        event: &Event<'_>,
+    ) -> std::fmt::Result {
+        let mut timestamp = String::new();
+        SystemTime.format_time(&mut Writer::new(&mut timestamp))?;
+
+        let mut fields = Map::new();
+        event.record(&mut JsonVisitor(&mut fields));
+
+        let mut object = Map::new();
+        object.insert("timestamp".into(), timestamp.into());
+        object.insert("level".into(), event.metadata().level().as_str().into());
+        object.insert("target".into(), event.metadata().target().into());
+        if let Some(message) = fields.remove("message") {
+            object.insert("message".into(), message);
+        }
+        if !fields.is_empty() {
+            object.insert("fields".into(), fields.into());
+        }
+
+        if let Some(scope) = ctx.event_scope() {
+            let spans: Vec<Value> = scope.from_root().map(|span| span.name().into()).collect();
+            object.insert("spans".into(), spans.into());
+        }
+
+        let context = tracing::Span::current().context();
+        let span_context = context.span().span_context().clone();
+        if span_context.is_valid() {
+            object.insert(
+                "trace_id".into(),
+                span_context.trace_id().to_string().into(),
+            );
+            object.insert("span_id".into(), span_context.span_id().to_string().into());
+        }
+
+        writeln!(writer, "{}", Value::Object(object))
+    }
+}
+
+/// Records event fields into a JSON object.
+pub(crate) struct JsonVisitor<'a>(pub(crate) &'a mut Map<String, Value>);
+
+impl tracing::field::Visit for JsonVisitor<'_> {
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        self.0.insert(field.name().into(), value.into());
+    }
+
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.0.insert(field.name().into(), value.into());
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.0.insert(field.name().into(), value.into());
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.0.insert(field.name().into(), value.into());
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.0.insert(field.name().into(), value.into());
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        self.0
+            .insert(field.name().into(), format!("{value:?}").into());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+    use tracing_subscriber::{fmt::MakeWriter, prelude::*};
+
+    #[derive(Clone, Default)]
+    struct Buffer(Arc<Mutex<Vec<u8>>>);
+
+    impl std::io::Write for Buffer {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl<'a> MakeWriter<'a> for Buffer {
+        type Writer = Self;
+
+        fn make_writer(&'a self) -> Self::Writer {
+            self.clone()
+        }
+    }
+
+    #[test]
+    fn events_are_formatted_as_json() {
+        let buffer = Buffer::default();
+        let subscriber = tracing_subscriber::registry().with(
+            tracing_subscriber::fmt::layer()
+                .with_writer(buffer.clone())
+                .event_format(JsonFormat),
+        );
+        tracing::subscriber::with_default(subscriber, || {
+            let _span = tracing::info_span!("handle_request").entered();
+            tracing::warn!(status = 500, "request failed");
+        });
+
+        let output = String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+        let line: Value = serde_json::from_str(output.trim()).unwrap();
+        assert_eq!(line["level"], "WARN");
+        assert_eq!(line["message"], "request failed");
+        assert_eq!(line["fields"]["status"], 500);
+        assert_eq!(line["spans"][0], "handle_request");
+        assert!(line.get("trace_id").is_none());
+    }
+
+    #[test]
+    fn host_log_format_can_be_switched_to_json() {
+        let buffer = Buffer::default();
+        let subscriber = tracing_subscriber::registry().with(
+            tracing_subscriber::fmt::layer()
+                .with_writer(buffer.clone())
+                .with_ansi(false)
+                .event_format(HostLogFormat::default()),
+        );
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::warn!("as text");
+            set_json_log_format(true);
+            tracing::warn!("as json");
+            set_json_log_format(false);
+        });
+
+        let output = String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+        let lines: Vec<_> = output.lines().collect();
+        assert!(lines[0].ends_with("as text"), "{output}");
+        let line: Value = serde_json::from_str(lines[1]).unwrap();
+        assert_eq!(line["message"], "as json");
+    }
+}
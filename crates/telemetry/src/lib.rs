@@ -1,31 +1,55 @@
 use std::io::IsTerminal;
 
 use anyhow::Context;
+use env::json_log_format;
+use env::otel_capture_path;
+use env::otel_console_enabled;
 use env::otel_logs_enabled;
 use env::otel_metrics_enabled;
+use env::otel_recent_traces;
 use env::otel_tracing_enabled;
-use opentelemetry_sdk::propagation::TraceContextPropagator;
-use tracing_subscriber::{EnvFilter, Layer, fmt, prelude::*, registry};
+use env::prometheus_metrics_addr;
+use env::slow_request_threshold;
+use tracing_subscriber::{Layer, fmt, prelude::*, registry, reload};
 
 mod alert_in_dev;
+pub mod audit;
+pub mod capture;
+mod component;
+pub mod console;
 pub mod detector;
 pub mod env;
+mod host_calls;
+mod json_format;
+pub mod log_filter;
 pub mod logs;
 pub mod metrics;
+pub mod otlp;
+pub mod policy;
 mod propagation;
+pub mod propagators;
+pub mod recent_traces;
+mod secrets;
+mod slow_requests;
 pub mod traces;
 
 #[cfg(feature = "testing")]
 pub mod testing;
 
+pub use json_format::set_json_log_format;
+pub use propagation::extract_message_trace_context;
 pub use propagation::extract_trace_context;
+pub use propagation::inbound_span_context;
+pub use propagation::inject_message_trace_context;
 pub use propagation::inject_trace_context;
+pub use propagation::inject_trace_context_with_baggage;
 
 /// Initializes telemetry for Spin using the [tracing] library.
 ///
 /// Under the hood this involves initializing a [tracing::Subscriber] with multiple [Layer]s. One
 /// [Layer] emits [tracing] events to stderr, another sends spans to an OTel collector, and another
-/// sends metrics to an OTel collector.
+/// sends metrics to an OTel collector and/or exposes them to Prometheus, and another writes the
+/// audit log.
 ///
 /// Configuration for the OTel layers is pulled from the environment.
 ///
@@ -53,23 +77,31 @@ pub use propagation::inject_trace_context;
 /// spin_telemetry::metrics::monotonic_counter!(spin.metric_name = 1, metric_attribute = "value");
 /// ```
 pub fn init(spin_version: String) -> anyhow::Result<()> {
-    // This layer will print all tracing library log messages to stderr.
+    // This layer will print all tracing library log messages to stderr, as
+    // JSON if `SPIN_LOG_FORMAT=json` (see `set_json_log_format`), with secret
+    // variable values redacted.
+    set_json_log_format(json_log_format());
     let fmt_layer = fmt::layer()
-        .with_writer(std::io::stderr)
+        .with_writer(secrets::RedactingMakeWriter(std::io::stderr))
         .with_ansi(std::io::stderr().is_terminal())
-        .with_filter(
-            // Filter directives explained here https://docs.rs/tracing-subscriber/latest/tracing_subscriber/filter/struct.EnvFilter.html#directives
-            EnvFilter::from_default_env()
-                // Wasmtime is too noisy
-                .add_directive("wasmtime_wasi_http=warn".parse()?)
-                // Watchexec is too noisy
-                .add_directive("watchexec=off".parse()?)
-                // We don't want to duplicate application logs
-                .add_directive("[{app_log}]=off".parse()?)
-                .add_directive("[{app_log_non_utf8}]=off".parse()?),
-        );
-
-    let otel_tracing_layer = if otel_tracing_enabled() {
+        .event_format(json_format::HostLogFormat::default());
+    let (fmt_filter, fmt_filter_handle) = reload::Layer::new(log_filter::filter(
+        &log_filter::initial_directives(),
+        false,
+    )?);
+    log_filter::set_reload(Box::new(move |filter| {
+        fmt_filter_handle
+            .reload(filter)
+            .context("failed to change log filter")
+    }));
+    let fmt_layer = fmt_layer.with_filter(fmt_filter);
+
+    if let Some(capacity) = otel_recent_traces()? {
+        recent_traces::init(capacity);
+    }
+
+    // Recent traces are recorded from the host tracer provider, even if spans aren't exported.
+    let otel_tracing_layer = if otel_tracing_enabled() || recent_traces::enabled() {
         Some(
             traces::otel_tracing_layer(spin_version.clone())
                 .context("failed to initialize otel tracing")?,
@@ -78,7 +110,7 @@ pub fn init(spin_version: String) -> anyhow::Result<()> {
         None
     };
 
-    let otel_metrics_layer = if otel_metrics_enabled() {
+    let otel_metrics_layer = if otel_metrics_enabled() || prometheus_metrics_addr().is_some() {
         Some(
             metrics::otel_metrics_layer(spin_version.clone())
                 .context("failed to initialize otel metrics")?,
@@ -89,17 +121,37 @@ pub fn init(spin_version: String) -> anyhow::Result<()> {
 
     let alert_in_dev_layer = alert_in_dev::alert_in_dev_layer();
 
+    let console_layer = otel_console_enabled().then(console::console_layer);
+
+    let capture_layer = match otel_capture_path() {
+        Some(path) => {
+            Some(capture::capture_layer(&path).context("failed to initialize span capture")?)
+        }
+        None => None,
+    };
+
+    let slow_request_layer = slow_request_threshold()?.map(slow_requests::slow_request_layer);
+
+    let audit_layer = audit::audit_layer().context("failed to initialize audit log")?;
+
     // Build a registry subscriber with the layers we want to use.
     registry()
+        .with(component::ComponentIdLayer)
         .with(otel_tracing_layer)
         .with(otel_metrics_layer)
         .with(fmt_layer)
         .with(alert_in_dev_layer)
+        .with(console_layer)
+        .with(capture_layer)
+        .with(slow_request_layer)
+        .with(audit_layer)
         .init();
 
-    // Used to propagate trace information in the standard W3C TraceContext format. Even if the otel
-    // layer is disabled we still want to propagate trace context.
-    opentelemetry::global::set_text_map_propagator(TraceContextPropagator::new());
+    secrets::install_panic_hook();
+
+    // Used to propagate trace information, by default in the standard W3C TraceContext and
+    // Baggage formats. Even if the otel layer is disabled we still want to propagate trace context.
+    propagators::set_propagators(propagators::DEFAULT_PROPAGATORS);
 
     if otel_logs_enabled() {
         logs::init_otel_logging_backend(spin_version)
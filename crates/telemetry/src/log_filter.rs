@@ -0,0 +1,79 @@
+//! The filter on host log output, which may be changed while Spin is running.
+
+use std::sync::{Mutex, OnceLock};
+
+use anyhow::Context as _;
+use tracing_subscriber::{EnvFilter, filter::LevelFilter};
+
+use crate::slow_requests;
+
+/// Applies a new filter to the host log output.
+type Reload = Box<dyn Fn(EnvFilter) -> anyhow::Result<()> + Send + Sync>;
+
+static RELOAD: OnceLock<Reload> = OnceLock::new();
+static DIRECTIVES: Mutex<String> = Mutex::new(String::new());
+
+/// The filter directives from `RUST_LOG`, which the host log output starts with.
+pub(crate) fn initial_directives() -> String {
+    let directives = std::env::var(EnvFilter::DEFAULT_ENV).unwrap_or_default();
+    DIRECTIVES.lock().unwrap().clone_from(&directives);
+    directives
+}
+
+/// Builds the filter for the given directives, with the directives Spin always
+/// applies added.
+///
+/// Invalid directives are an error if `strict`, and ignored otherwise.
+// Filter directives explained here https://docs.rs/tracing-subscriber/latest/tracing_subscriber/filter/struct.EnvFilter.html#directives
+pub(crate) fn filter(directives: &str, strict: bool) -> anyhow::Result<EnvFilter> {
+    let builder = EnvFilter::builder().with_default_directive(LevelFilter::ERROR.into());
+    let filter = if strict {
+        builder
+            .parse(directives)
+            .with_context(|| format!("invalid log filter {directives:?}"))?
+    } else {
+        builder.parse_lossy(directives)
+    };
+    Ok(filter
+        // Wasmtime is too noisy
+        .add_directive("wasmtime_wasi_http=warn".parse()?)
+        // Watchexec is too noisy
+        .add_directive("watchexec=off".parse()?)
+        // We don't want to duplicate application logs
+        .add_directive("[{app_log}]=off".parse()?)
+        .add_directive("[{app_log_non_utf8}]=off".parse()?)
+        // Audit events are written to the audit log
+        .add_directive("spin_audit=off".parse()?)
+        // Slow requests are logged whatever the log level
+        .add_directive(format!("{}=warn", slow_requests::SLOW_REQUEST_TARGET).parse()?))
+}
+
+pub(crate) fn set_reload(reload: Reload) {
+    let _ = RELOAD.set(reload);
+}
+
+/// Returns the current host log filter directives, in `RUST_LOG` syntax.
+pub fn current() -> String {
+    DIRECTIVES.lock().unwrap().clone()
+}
+
+/// Replaces the host log filter with the given directives, in `RUST_LOG`
+/// syntax, e.g. `spin_trigger_http=debug,info`.
+pub fn set(directives: &str) -> anyhow::Result<()> {
+    let reload = RELOAD.get().context("telemetry has not been initialized")?;
+    reload(filter(directives, true)?)?;
+    *DIRECTIVES.lock().unwrap() = directives.to_owned();
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn invalid_directives_are_rejected() {
+        assert!(filter("spin_trigger=debug,info", true).is_ok());
+        assert!(filter("spin_trigger=loud", true).is_err());
+        assert!(filter("spin_trigger=loud", false).is_ok());
+    }
+}
@@ -1,49 +1,157 @@
 use std::{ascii::escape_default, sync::OnceLock};
 
-use anyhow::bail;
-use opentelemetry::logs::{LogRecord, Logger, LoggerProvider};
+use opentelemetry::logs::{LogRecord, Logger, LoggerProvider, Severity};
+use opentelemetry::trace::{SpanContext, TraceContextExt as _};
 use opentelemetry_sdk::{
-    Resource,
     logs::{BatchConfigBuilder, SdkLogger, log_processor_with_async_runtime::BatchLogProcessor},
-    resource::{EnvResourceDetector, ResourceDetector, TelemetryResourceDetector},
     runtime::Tokio,
 };
+use tracing_opentelemetry::OpenTelemetrySpanExt as _;
 
 use crate::{
-    detector::SpinResourceDetector,
-    env::{self, OtlpProtocol, otel_logs_enabled},
+    detector::detected_resource,
+    env::{self, otel_logs_enabled},
+    otlp::OtlpConnection,
+    policy,
 };
 
 static LOGGER: OnceLock<SdkLogger> = OnceLock::new();
 
-/// Handle an application log. Has the potential to both forward the log to OTel and to emit it as a
-/// tracing event.
-pub fn handle_app_log(buf: &[u8], component_id: &str) {
-    app_log_to_otel(buf, component_id);
-    app_log_to_tracing_event(buf);
+/// Lines longer than this are split into several logs.
+const MAX_LINE_LEN: usize = 16 * 1024;
+
+/// The output stream an application log was written to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AppLogStream {
+    Stdout,
+    Stderr,
 }
 
-/// Forward the app log to OTel.
-fn app_log_to_otel(buf: &[u8], component_id: &str) {
-    if !otel_logs_enabled() {
-        return;
+impl AppLogStream {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Stdout => "stdout",
+            Self::Stderr => "stderr",
+        }
+    }
+}
+
+/// Handles a component's output as application logs, one per line. Each line has the potential to
+/// both be forwarded to OTel and be emitted as a tracing event.
+///
+/// A trailing partial line is handled when the forwarder is dropped.
+pub struct AppLogForwarder {
+    component_id: String,
+    app_name: Option<String>,
+    stream: AppLogStream,
+    /// The span the forwarder was created in, used to correlate logs written outside of any span
+    /// with the trace of the request.
+    span: tracing::Span,
+    partial_line: Vec<u8>,
+}
+
+impl AppLogForwarder {
+    /// Creates a forwarder for output written by the given component to the given stream.
+    pub fn new(component_id: &str, app_name: Option<&str>, stream: AppLogStream) -> Self {
+        Self {
+            component_id: component_id.to_owned(),
+            app_name: app_name.map(ToOwned::to_owned),
+            stream,
+            span: tracing::Span::current(),
+            partial_line: Vec::new(),
+        }
+    }
+
+    /// Handles output written by the component.
+    pub fn write(&mut self, buf: &[u8]) {
+        let mut partial_line = std::mem::take(&mut self.partial_line);
+        split_lines(&mut partial_line, buf, |line| self.handle_line(line));
+        self.partial_line = partial_line;
     }
 
-    if let Some(logger) = LOGGER.get() {
-        if let Ok(s) = std::str::from_utf8(buf) {
-            let mut record = logger.create_log_record();
+    fn handle_line(&self, line: &[u8]) {
+        let line = line.strip_suffix(b"\r").unwrap_or(line);
+        self.app_log_to_otel(line);
+        app_log_to_tracing_event(line);
+    }
+
+    /// Forward the app log to OTel.
+    fn app_log_to_otel(&self, line: &[u8]) {
+        if !otel_logs_enabled() || !policy::export_policy().exports_component(&self.component_id) {
+            return;
+        }
+
+        let Some(logger) = LOGGER.get() else {
+            tracing::trace!("OTel logger not initialized, failed to log");
+            return;
+        };
+
+        let mut record = logger.create_log_record();
+        if let Ok(s) = std::str::from_utf8(line) {
             record.set_body(s.to_string().into());
-            record.add_attribute("component_id", component_id.to_string());
-            logger.emit(record);
         } else {
-            let mut record = logger.create_log_record();
-            record.set_body(escape_non_utf8_buf(buf).into());
+            record.set_body(escape_non_utf8_buf(line).into());
             record.add_attribute("app_log_non_utf8", true);
-            record.add_attribute("component_id", component_id.to_string());
-            logger.emit(record);
         }
-    } else {
-        tracing::trace!("OTel logger not initialized, failed to log");
+        let (severity, severity_text) = match self.stream {
+            AppLogStream::Stdout => (Severity::Info, "INFO"),
+            AppLogStream::Stderr => (Severity::Error, "ERROR"),
+        };
+        record.set_severity_number(severity);
+        record.set_severity_text(severity_text);
+        record.add_attribute("component_id", self.component_id.clone());
+        if let Some(app_name) = &self.app_name {
+            record.add_attribute("app_name", app_name.clone());
+        }
+        record.add_attribute("log.iostream", self.stream.as_str());
+        if let Some(span_context) = self.span_context() {
+            record.set_trace_context(
+                span_context.trace_id(),
+                span_context.span_id(),
+                Some(span_context.trace_flags()),
+            );
+        }
+        logger.emit(record);
+    }
+
+    /// Returns the context of the span the output was written in, falling back to the span the
+    /// forwarder was created in.
+    fn span_context(&self) -> Option<SpanContext> {
+        [tracing::Span::current(), self.span.clone()]
+            .into_iter()
+            .map(|span| span.context().span().span_context().clone())
+            .find(SpanContext::is_valid)
+    }
+}
+
+impl Drop for AppLogForwarder {
+    fn drop(&mut self) {
+        if !self.partial_line.is_empty() {
+            let line = std::mem::take(&mut self.partial_line);
+            self.handle_line(&line);
+        }
+    }
+}
+
+/// Calls `handle_line` for each complete line of output, with line endings removed, keeping any
+/// trailing partial line in `partial_line` to be completed by later output.
+fn split_lines(partial_line: &mut Vec<u8>, buf: &[u8], mut handle_line: impl FnMut(&[u8])) {
+    let mut rest = buf;
+    while let Some(newline) = rest.iter().position(|&b| b == b'\n') {
+        let line = &rest[..newline];
+        if partial_line.is_empty() {
+            handle_line(line);
+        } else {
+            partial_line.extend_from_slice(line);
+            handle_line(&partial_line[..]);
+            partial_line.clear();
+        }
+        rest = &rest[newline + 1..];
+    }
+    partial_line.extend_from_slice(rest);
+    if partial_line.len() >= MAX_LINE_LEN {
+        handle_line(&partial_line[..]);
+        partial_line.clear();
     }
 }
 
@@ -69,33 +177,55 @@ fn escape_non_utf8_buf(buf: &[u8]) -> String {
         .collect::<String>()
 }
 
+/// Emits an audit log record for a privileged host operation.
+pub(crate) fn emit_audit_record(
+    component_id: Option<&str>,
+    mut fields: serde_json::Map<String, serde_json::Value>,
+) {
+    let Some(logger) = LOGGER.get() else {
+        return;
+    };
+
+    let mut record = logger.create_log_record();
+    record.set_event_name("spin.audit");
+    record.set_severity_number(Severity::Info);
+    record.set_severity_text("INFO");
+    if let Some(serde_json::Value::String(operation)) = fields.remove("operation") {
+        record.set_body(operation.into());
+    }
+    if let Some(component_id) = component_id {
+        record.add_attribute("component_id", component_id.to_owned());
+    }
+    for (name, value) in fields {
+        match value {
+            serde_json::Value::String(value) => record.add_attribute(name, value),
+            value => record.add_attribute(name, value.to_string()),
+        }
+    }
+    let span_context = tracing::Span::current()
+        .context()
+        .span()
+        .span_context()
+        .clone();
+    if span_context.is_valid() {
+        record.set_trace_context(
+            span_context.trace_id(),
+            span_context.span_id(),
+            Some(span_context.trace_flags()),
+        );
+    }
+    logger.emit(record);
+}
+
 /// Initialize the OTel logging backend.
 pub(crate) fn init_otel_logging_backend(spin_version: String) -> anyhow::Result<()> {
-    let resource = Resource::builder()
-        .with_detectors(&[
-            // Set service.name from env OTEL_SERVICE_NAME > env OTEL_RESOURCE_ATTRIBUTES > spin
-            // Set service.version from Spin metadata
-            Box::new(SpinResourceDetector::new(spin_version)) as Box<dyn ResourceDetector>,
-            // Sets fields from env OTEL_RESOURCE_ATTRIBUTES
-            Box::new(EnvResourceDetector::new()),
-            // Sets telemetry.sdk{name, language, version}
-            Box::new(TelemetryResourceDetector),
-        ])
-        .build();
+    let resource = detected_resource(spin_version, []);
 
     // This will configure the exporter based on the OTEL_EXPORTER_* environment variables. We
     // currently default to using the HTTP exporter but in the future we could select off of the
     // combination of OTEL_EXPORTER_OTLP_PROTOCOL and OTEL_EXPORTER_OTLP_LOGS_PROTOCOL to
     // determine whether we should use http/protobuf or grpc.
-    let exporter = match OtlpProtocol::logs_protocol_from_env() {
-        OtlpProtocol::Grpc => opentelemetry_otlp::LogExporter::builder()
-            .with_tonic()
-            .build()?,
-        OtlpProtocol::HttpProtobuf => opentelemetry_otlp::LogExporter::builder()
-            .with_http()
-            .build()?,
-        OtlpProtocol::HttpJson => bail!("http/json OTLP protocol is not supported"),
-    };
+    let exporter = OtlpConnection::from_env().log_exporter()?;
 
     let provider = opentelemetry_sdk::logs::SdkLoggerProvider::builder()
         .with_resource(resource)
@@ -109,3 +239,41 @@ pub(crate) fn init_otel_logging_backend(spin_version: String) -> anyhow::Result<
     let _ = LOGGER.set(provider.logger("spin"));
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines(writes: &[&[u8]]) -> (Vec<String>, Vec<u8>) {
+        let mut partial_line = Vec::new();
+        let mut lines = Vec::new();
+        for buf in writes {
+            split_lines(&mut partial_line, buf, |line| {
+                lines.push(String::from_utf8_lossy(line).into_owned())
+            });
+        }
+        (lines, partial_line)
+    }
+
+    #[test]
+    fn splits_output_into_lines() {
+        let (lines, partial) = lines(&[b"one\ntwo\n"]);
+        assert_eq!(vec!["one", "two"], lines);
+        assert!(partial.is_empty());
+    }
+
+    #[test]
+    fn joins_lines_across_writes() {
+        let (lines, partial) = lines(&[b"hel", b"lo\nwor", b"ld"]);
+        assert_eq!(vec!["hello"], lines);
+        assert_eq!(b"world".to_vec(), partial);
+    }
+
+    #[test]
+    fn splits_overlong_lines() {
+        let long = vec![b'x'; MAX_LINE_LEN + 1];
+        let (lines, partial) = lines(&[&long]);
+        assert_eq!(1, lines.len());
+        assert!(partial.is_empty());
+    }
+}
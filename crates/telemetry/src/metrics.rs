@@ -1,60 +1,207 @@
-use anyhow::{Result, bail};
+use std::collections::BTreeMap;
+use std::convert::Infallible;
+use std::sync::{Mutex, OnceLock};
+
+use anyhow::{Context as _, Result};
+use http_body_util::Full;
+use hyper::{Request, Response, StatusCode, body::Bytes, header, service::service_fn};
+use hyper_util::rt::TokioIo;
 use opentelemetry::global;
 use opentelemetry_sdk::{
-    Resource,
     metrics::{SdkMeterProvider, periodic_reader_with_async_runtime::PeriodicReader},
-    resource::{EnvResourceDetector, ResourceDetector, TelemetryResourceDetector},
     runtime::Tokio,
 };
+use prometheus::{
+    Encoder as _, Registry, TextEncoder,
+    proto::{LabelPair, MetricFamily},
+};
 use tracing::Subscriber;
 use tracing_opentelemetry::MetricsLayer;
 use tracing_subscriber::{Layer, registry::LookupSpan};
 
-use crate::{detector::SpinResourceDetector, env::OtlpProtocol};
+use crate::{
+    detector::detected_resource,
+    env::{otel_metrics_enabled, prometheus_metrics_addr},
+    host_calls::HostCallLayer,
+    otlp::OtlpConnection,
+    policy,
+};
+
+/// The registry read by the Prometheus endpoint, set if the endpoint is enabled.
+static PROMETHEUS_REGISTRY: OnceLock<Registry> = OnceLock::new();
+
+/// The latest metrics exported by guests, by component ID and metric name, served by the
+/// Prometheus endpoint alongside host metrics.
+static GUEST_METRIC_FAMILIES: Mutex<BTreeMap<(String, String), MetricFamily>> =
+    Mutex::new(BTreeMap::new());
+
+/// The label identifying the component that exported a guest metric.
+const COMPONENT_LABEL: &str = "spin_component";
 
-/// Constructs a layer for the tracing subscriber that sends metrics to an OTEL collector.
+/// Constructs a layer for the tracing subscriber that records metrics, sending them to an OTEL
+/// collector and/or exposing them to Prometheus.
 ///
 /// It pulls OTEL configuration from the environment based on the variables defined
 /// [here](https://opentelemetry.io/docs/specs/otel/protocol/exporter/) and
 /// [here](https://opentelemetry.io/docs/specs/otel/configuration/sdk-environment-variables/#general-sdk-configuration).
+/// Metrics are exposed to Prometheus if `SPIN_METRICS_LISTEN` is set; see
+/// [`serve_prometheus_endpoint`].
 pub(crate) fn otel_metrics_layer<S: Subscriber + for<'span> LookupSpan<'span>>(
     spin_version: String,
 ) -> Result<impl Layer<S>> {
-    let resource = Resource::builder()
-        .with_detectors(&[
-            // Set service.name from env OTEL_SERVICE_NAME > env OTEL_RESOURCE_ATTRIBUTES > spin
-            // Set service.version from Spin metadata
-            Box::new(SpinResourceDetector::new(spin_version)) as Box<dyn ResourceDetector>,
-            // Sets fields from env OTEL_RESOURCE_ATTRIBUTES
-            Box::new(EnvResourceDetector::new()),
-            // Sets telemetry.sdk{name, language, version}
-            Box::new(TelemetryResourceDetector),
-        ])
-        .build();
-
-    // This will configure the exporter based on the OTEL_EXPORTER_* environment variables. We
-    // currently default to using the HTTP exporter but in the future we could select off of the
-    // combination of OTEL_EXPORTER_OTLP_PROTOCOL and OTEL_EXPORTER_OTLP_TRACES_PROTOCOL to
-    // determine whether we should use http/protobuf or grpc.
-    let exporter = match OtlpProtocol::metrics_protocol_from_env() {
-        OtlpProtocol::Grpc => opentelemetry_otlp::MetricExporter::builder()
-            .with_tonic()
-            .build()?,
-        OtlpProtocol::HttpProtobuf => opentelemetry_otlp::MetricExporter::builder()
-            .with_http()
-            .build()?,
-        OtlpProtocol::HttpJson => bail!("http/json OTLP protocol is not supported"),
-    };
+    let resource = detected_resource(spin_version, []);
+
+    let mut meter_provider = SdkMeterProvider::builder().with_resource(resource);
+
+    if otel_metrics_enabled() {
+        // This will configure the exporter based on the OTEL_EXPORTER_* environment variables. We
+        // currently default to using the HTTP exporter but in the future we could select off of the
+        // combination of OTEL_EXPORTER_OTLP_PROTOCOL and OTEL_EXPORTER_OTLP_TRACES_PROTOCOL to
+        // determine whether we should use http/protobuf or grpc.
+        let exporter = OtlpConnection::from_env().metric_exporter(None)?;
+        meter_provider =
+            meter_provider.with_reader(PeriodicReader::builder(exporter, Tokio).build());
+    }
+
+    if prometheus_metrics_addr().is_some() {
+        let registry = PROMETHEUS_REGISTRY.get_or_init(Registry::new);
+        let exporter = opentelemetry_prometheus::exporter()
+            .with_registry(registry.clone())
+            .build()?;
+        meter_provider = meter_provider.with_reader(exporter);
+    }
 
-    let reader = PeriodicReader::builder(exporter, Tokio).build();
-    let meter_provider = SdkMeterProvider::builder()
-        .with_reader(reader)
-        .with_resource(resource)
-        .build();
+    let meter_provider = meter_provider.build();
 
     global::set_meter_provider(meter_provider.clone());
 
-    Ok(MetricsLayer::new(meter_provider))
+    Ok(HostCallLayer::new(&meter_provider)
+        .and_then(MetricsLayer::new(meter_provider))
+        .with_filter(policy::component_filter()))
+}
+
+/// Serves host metrics, and guest metrics recorded with [`record_guest_metrics`], in the Prometheus
+/// text format at `/metrics` on the address given by `SPIN_METRICS_LISTEN`.
+///
+/// Does nothing if the endpoint was not enabled when telemetry was initialized.
+pub async fn serve_prometheus_endpoint() -> Result<()> {
+    let (Some(registry), Some(addr)) = (PROMETHEUS_REGISTRY.get(), prometheus_metrics_addr())
+    else {
+        return Ok(());
+    };
+    let listener = tokio::net::TcpListener::bind(&addr)
+        .await
+        .with_context(|| format!("failed to listen for metrics requests on {addr}"))?;
+    tracing::info!("Serving Prometheus metrics on http://{addr}/metrics");
+
+    tokio::spawn(async move {
+        loop {
+            let stream = match listener.accept().await {
+                Ok((stream, _)) => stream,
+                Err(err) => {
+                    tracing::warn!("Failed to accept metrics connection: {err}");
+                    continue;
+                }
+            };
+            tokio::spawn(async move {
+                let service = service_fn(|req| async move {
+                    Ok::<_, Infallible>(metrics_response(registry, &req))
+                });
+                if let Err(err) = hyper::server::conn::http1::Builder::new()
+                    .serve_connection(TokioIo::new(stream), service)
+                    .await
+                {
+                    tracing::debug!("Error serving metrics connection: {err}");
+                }
+            });
+        }
+    });
+    Ok(())
+}
+
+/// Whether metrics are served by the Prometheus endpoint.
+pub fn prometheus_enabled() -> bool {
+    PROMETHEUS_REGISTRY.get().is_some()
+}
+
+/// Records the latest values of metrics exported by a guest, to be served by the Prometheus
+/// endpoint alongside host metrics.
+///
+/// Each family replaces any previously recorded by the component with the same name, so metrics
+/// should be exported with cumulative temporality. Each metric is labeled with the component ID.
+///
+/// Does nothing if the endpoint is not enabled.
+pub fn record_guest_metrics(component_id: &str, families: Vec<MetricFamily>) {
+    if !prometheus_enabled() {
+        return;
+    }
+    let mut guest_families = GUEST_METRIC_FAMILIES.lock().unwrap();
+    for mut family in families {
+        for metric in family.mut_metric().iter_mut() {
+            let mut label = LabelPair::default();
+            label.set_name(COMPONENT_LABEL.to_owned());
+            label.set_value(component_id.to_owned());
+            metric.mut_label().push(label);
+        }
+        let key = (component_id.to_owned(), family.get_name().to_owned());
+        guest_families.insert(key, family);
+    }
+}
+
+/// Gathers host metrics and guest metrics, merging families of the same name exported by
+/// different components.
+///
+/// Guest families whose name is used by a host metric, or by a guest metric of a different type,
+/// are omitted.
+fn gather(registry: &Registry) -> Vec<MetricFamily> {
+    let mut families = registry.gather();
+    let host_family_count = families.len();
+    for family in GUEST_METRIC_FAMILIES.lock().unwrap().values() {
+        match families
+            .iter_mut()
+            .position(|f| f.get_name() == family.get_name())
+        {
+            None => families.push(family.clone()),
+            Some(index) => {
+                let existing = &mut families[index];
+                if index < host_family_count || existing.get_field_type() != family.get_field_type()
+                {
+                    tracing::debug!(
+                        "Omitting guest metric {} from Prometheus endpoint as its name is in use",
+                        family.get_name()
+                    );
+                    continue;
+                }
+                for metric in family.get_metric() {
+                    existing.mut_metric().push(metric.clone());
+                }
+            }
+        }
+    }
+    families
+}
+
+fn metrics_response<B>(registry: &Registry, req: &Request<B>) -> Response<Full<Bytes>> {
+    let respond = |status: StatusCode, content_type: &str, body: Vec<u8>| {
+        Response::builder()
+            .status(status)
+            .header(header::CONTENT_TYPE, content_type)
+            .body(Full::new(Bytes::from(body)))
+            .expect("response should be valid")
+    };
+    if req.uri().path() != "/metrics" {
+        return respond(StatusCode::NOT_FOUND, "text/plain", b"not found".to_vec());
+    }
+    let encoder = TextEncoder::new();
+    let mut body = Vec::new();
+    match encoder.encode(&gather(registry), &mut body) {
+        Ok(()) => respond(StatusCode::OK, encoder.format_type(), body),
+        Err(err) => respond(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "text/plain",
+            err.to_string().into_bytes(),
+        ),
+    }
 }
 
 #[macro_export]
@@ -129,3 +276,69 @@ pub use counter;
 pub use gauge;
 pub use histogram;
 pub use monotonic_counter;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn serves_metrics_only_at_metrics_path() {
+        let registry = Registry::new();
+        let counter = prometheus::IntCounter::new("spin_test_total", "A test counter").unwrap();
+        registry.register(Box::new(counter.clone())).unwrap();
+        counter.inc();
+
+        let req = Request::get("/metrics").body(()).unwrap();
+        let res = metrics_response(&registry, &req);
+        assert_eq!(StatusCode::OK, res.status());
+
+        let req = Request::get("/").body(()).unwrap();
+        assert_eq!(
+            StatusCode::NOT_FOUND,
+            metrics_response(&registry, &req).status()
+        );
+    }
+
+    #[test]
+    fn merges_guest_metrics_by_name() {
+        use prometheus::proto::{Gauge, Metric, MetricType};
+
+        PROMETHEUS_REGISTRY.get_or_init(Registry::new);
+        let registry = Registry::new();
+        let counter = prometheus::IntCounter::new("spin_host_total", "A host counter").unwrap();
+        registry.register(Box::new(counter)).unwrap();
+
+        let family = |name: &str| {
+            let mut gauge = Gauge::default();
+            gauge.set_value(1.0);
+            let mut metric = Metric::default();
+            metric.set_gauge(gauge);
+            let mut family = MetricFamily::default();
+            family.set_name(name.to_owned());
+            family.set_field_type(MetricType::GAUGE);
+            family.mut_metric().push(metric);
+            family
+        };
+        record_guest_metrics("a", vec![family("queue_depth"), family("spin_host_total")]);
+        record_guest_metrics("b", vec![family("queue_depth")]);
+
+        let families = gather(&registry);
+        let queue_depth = families
+            .iter()
+            .find(|f| f.get_name() == "queue_depth")
+            .unwrap();
+        let components: Vec<_> = queue_depth
+            .get_metric()
+            .iter()
+            .map(|m| m.get_label()[0].get_value())
+            .collect();
+        assert_eq!(vec!["a", "b"], components);
+
+        let host: Vec<_> = families
+            .iter()
+            .filter(|f| f.get_name() == "spin_host_total")
+            .collect();
+        assert_eq!(1, host.len());
+        assert_eq!(MetricType::COUNTER, host[0].get_field_type());
+    }
+}
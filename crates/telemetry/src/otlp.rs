@@ -0,0 +1,629 @@
+//! Construction of OTLP exporters, including the headers and TLS settings needed to export to
+//! authenticated backends, and of senders of pre-encoded OTLP requests, e.g. those buffered while
+//! an endpoint was unreachable.
+
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use anyhow::{Context as _, bail, ensure};
+use opentelemetry_otlp::{
+    LogExporter, MetricExporter, OTEL_EXPORTER_OTLP_ENDPOINT, OTEL_EXPORTER_OTLP_METRICS_ENDPOINT,
+    OTEL_EXPORTER_OTLP_TRACES_ENDPOINT, SpanExporter, WithExportConfig, WithHttpConfig,
+    WithTonicConfig,
+};
+use opentelemetry_proto::{
+    tonic::collector::{
+        metrics::v1::{ExportMetricsServiceRequest, metrics_service_client::MetricsServiceClient},
+        trace::v1::{ExportTraceServiceRequest, trace_service_client::TraceServiceClient},
+    },
+    transform::{
+        common::tonic::ResourceAttributesWithSchema,
+        trace::tonic::group_spans_by_resource_and_scope,
+    },
+};
+use opentelemetry_sdk::{Resource, metrics::data::ResourceMetrics, trace::SpanData};
+use prost::Message as _;
+use serde::Deserialize;
+use tonic::{
+    metadata::MetadataMap,
+    transport::{Certificate, ClientTlsConfig, Identity},
+};
+
+use crate::env::{
+    OtlpProtocol, otel_logs_enabled, otel_metrics_enabled, otel_sdk_disabled, otel_tracing_enabled,
+};
+
+const OTEL_EXPORTER_OTLP_CERTIFICATE: &str = "OTEL_EXPORTER_OTLP_CERTIFICATE";
+const OTEL_EXPORTER_OTLP_CLIENT_CERTIFICATE: &str = "OTEL_EXPORTER_OTLP_CLIENT_CERTIFICATE";
+const OTEL_EXPORTER_OTLP_CLIENT_KEY: &str = "OTEL_EXPORTER_OTLP_CLIENT_KEY";
+const OTEL_EXPORTER_OTLP_HEADERS: &str = "OTEL_EXPORTER_OTLP_HEADERS";
+const OTEL_EXPORTER_OTLP_TRACES_HEADERS: &str = "OTEL_EXPORTER_OTLP_TRACES_HEADERS";
+const OTEL_EXPORTER_OTLP_METRICS_HEADERS: &str = "OTEL_EXPORTER_OTLP_METRICS_HEADERS";
+
+/// How to connect to OTLP endpoints, beyond the protocol.
+///
+/// Headers given here are sent in addition to those of the `OTEL_EXPORTER_OTLP_HEADERS`
+/// environment variable, which the exporters read themselves. Likewise, signals without an
+/// endpoint given here are exported to the endpoint given by the `OTEL_EXPORTER_OTLP_ENDPOINT`
+/// and `OTEL_EXPORTER_OTLP_<SIGNAL>_ENDPOINT` environment variables.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct OtlpConnection {
+    /// The URL to export traces to, used as-is.
+    pub traces_endpoint: Option<String>,
+    /// The URL to export metrics to, used as-is.
+    pub metrics_endpoint: Option<String>,
+    /// The URL to export logs to, used as-is.
+    pub logs_endpoint: Option<String>,
+    /// Headers sent with every export, e.g. for authentication.
+    #[serde(default)]
+    pub headers: HashMap<String, String>,
+    /// A PEM file of the certificate authorities trusted to sign the endpoint's certificate, in
+    /// addition to the platform's.
+    pub ca_certificate: Option<PathBuf>,
+    /// A PEM file of the client certificate presented for mutual TLS.
+    pub client_certificate: Option<PathBuf>,
+    /// A PEM file of the private key of the client certificate.
+    pub client_key: Option<PathBuf>,
+}
+
+impl OtlpConnection {
+    /// Reads the connection settings from the standard `OTEL_EXPORTER_OTLP_CERTIFICATE`,
+    /// `OTEL_EXPORTER_OTLP_CLIENT_CERTIFICATE` and `OTEL_EXPORTER_OTLP_CLIENT_KEY` environment
+    /// variables.
+    pub fn from_env() -> Self {
+        let path = |key| {
+            std::env::var_os(key)
+                .filter(|val| !val.is_empty())
+                .map(PathBuf::from)
+        };
+        Self {
+            traces_endpoint: None,
+            metrics_endpoint: None,
+            logs_endpoint: None,
+            headers: HashMap::new(),
+            ca_certificate: path(OTEL_EXPORTER_OTLP_CERTIFICATE),
+            client_certificate: path(OTEL_EXPORTER_OTLP_CLIENT_CERTIFICATE),
+            client_key: path(OTEL_EXPORTER_OTLP_CLIENT_KEY),
+        }
+    }
+
+    /// Applies the settings given in `overrides` over these, merging headers.
+    pub fn with_overrides(mut self, overrides: OtlpConnection) -> Self {
+        self.traces_endpoint = overrides.traces_endpoint.or(self.traces_endpoint);
+        self.metrics_endpoint = overrides.metrics_endpoint.or(self.metrics_endpoint);
+        self.logs_endpoint = overrides.logs_endpoint.or(self.logs_endpoint);
+        self.headers.extend(overrides.headers);
+        if overrides.ca_certificate.is_some() {
+            self.ca_certificate = overrides.ca_certificate;
+        }
+        // The client certificate and key only make sense as a pair.
+        if overrides.client_certificate.is_some() {
+            self.client_certificate = overrides.client_certificate;
+            self.client_key = overrides.client_key;
+        }
+        self
+    }
+
+    /// Checks that the settings are consistent, without reading any files.
+    pub fn validate(&self) -> anyhow::Result<()> {
+        ensure!(
+            self.client_certificate.is_some() == self.client_key.is_some(),
+            "client_certificate and client_key must be given together"
+        );
+        for (name, value) in &self.headers {
+            http1::HeaderName::try_from(name.as_str())
+                .with_context(|| format!("invalid OTLP header name {name:?}"))?;
+            http1::HeaderValue::try_from(value.as_str())
+                .with_context(|| format!("invalid value for OTLP header {name:?}"))?;
+        }
+        Ok(())
+    }
+
+    /// Whether traces are exported, to an endpoint given here or by the environment.
+    pub fn traces_enabled(&self) -> bool {
+        otel_tracing_enabled() || (self.traces_endpoint.is_some() && !otel_sdk_disabled())
+    }
+
+    /// Whether metrics are exported, to an endpoint given here or by the environment.
+    pub fn metrics_enabled(&self) -> bool {
+        otel_metrics_enabled() || (self.metrics_endpoint.is_some() && !otel_sdk_disabled())
+    }
+
+    /// Whether logs are exported, to an endpoint given here or by the environment.
+    pub fn logs_enabled(&self) -> bool {
+        otel_logs_enabled() || (self.logs_endpoint.is_some() && !otel_sdk_disabled())
+    }
+
+    /// Builds an exporter of spans, using the protocol given by the environment.
+    pub fn span_exporter(&self) -> anyhow::Result<SpanExporter> {
+        Ok(match OtlpProtocol::traces_protocol_from_env() {
+            OtlpProtocol::Grpc => self
+                .configure_tonic(
+                    SpanExporter::builder().with_tonic(),
+                    self.traces_endpoint.as_deref(),
+                )?
+                .build()?,
+            OtlpProtocol::HttpProtobuf => self
+                .configure_http(
+                    SpanExporter::builder().with_http(),
+                    self.traces_endpoint.as_deref(),
+                )?
+                .build()?,
+            OtlpProtocol::HttpJson => bail!("http/json OTLP protocol is not supported"),
+        })
+    }
+
+    /// Builds an exporter of metrics, using the protocol given by the environment.
+    pub fn metric_exporter(&self, timeout: Option<Duration>) -> anyhow::Result<MetricExporter> {
+        Ok(match OtlpProtocol::metrics_protocol_from_env() {
+            OtlpProtocol::Grpc => {
+                let mut builder = self.configure_tonic(
+                    MetricExporter::builder().with_tonic(),
+                    self.metrics_endpoint.as_deref(),
+                )?;
+                if let Some(timeout) = timeout {
+                    builder = builder.with_timeout(timeout);
+                }
+                builder.build()?
+            }
+            OtlpProtocol::HttpProtobuf => {
+                let mut builder = self.configure_http(
+                    MetricExporter::builder().with_http(),
+                    self.metrics_endpoint.as_deref(),
+                )?;
+                if let Some(timeout) = timeout {
+                    builder = builder.with_timeout(timeout);
+                }
+                builder.build()?
+            }
+            OtlpProtocol::HttpJson => bail!("http/json OTLP protocol is not supported"),
+        })
+    }
+
+    /// Builds an exporter of logs, using the protocol given by the environment.
+    pub fn log_exporter(&self) -> anyhow::Result<LogExporter> {
+        Ok(match OtlpProtocol::logs_protocol_from_env() {
+            OtlpProtocol::Grpc => self
+                .configure_tonic(
+                    LogExporter::builder().with_tonic(),
+                    self.logs_endpoint.as_deref(),
+                )?
+                .build()?,
+            OtlpProtocol::HttpProtobuf => self
+                .configure_http(
+                    LogExporter::builder().with_http(),
+                    self.logs_endpoint.as_deref(),
+                )?
+                .build()?,
+            OtlpProtocol::HttpJson => bail!("http/json OTLP protocol is not supported"),
+        })
+    }
+
+    /// Builds a sender of encoded OTLP export requests of the signal, to the endpoint its exporter
+    /// exports to and with the same headers and TLS settings.
+    pub fn request_sender(&self, signal: Signal) -> anyhow::Result<RequestSender> {
+        let env = |key: &str| std::env::var(key).ok().filter(|val| !val.is_empty());
+        let protocol = signal.protocol();
+        let endpoint = signal.endpoint(self, &protocol, env)?;
+        let mut headers = env(OTEL_EXPORTER_OTLP_HEADERS)
+            .map(|headers| parse_headers(&headers))
+            .unwrap_or_default();
+        headers.extend(
+            env(signal.headers_var())
+                .map(|headers| parse_headers(&headers))
+                .unwrap_or_default(),
+        );
+        headers.extend(self.header_map()?);
+        let transport = match protocol {
+            OtlpProtocol::Grpc => {
+                let mut grpc_endpoint = tonic::transport::Channel::from_shared(endpoint.clone())
+                    .with_context(|| format!("invalid OTLP endpoint {endpoint:?}"))?;
+                if endpoint.starts_with("https:") || self.uses_custom_tls() {
+                    grpc_endpoint = grpc_endpoint.tls_config(self.tonic_tls_config()?)?;
+                }
+                Transport::Grpc {
+                    channel: grpc_endpoint.connect_lazy(),
+                    metadata: MetadataMap::from_headers(headers),
+                }
+            }
+            OtlpProtocol::HttpProtobuf => Transport::Http {
+                client: self.http_client()?,
+                url: endpoint,
+                headers,
+            },
+            OtlpProtocol::HttpJson => bail!("http/json OTLP protocol is not supported"),
+        };
+        Ok(RequestSender { signal, transport })
+    }
+
+    fn configure_tonic<B: WithTonicConfig + WithExportConfig>(
+        &self,
+        mut builder: B,
+        endpoint: Option<&str>,
+    ) -> anyhow::Result<B> {
+        if let Some(endpoint) = endpoint {
+            builder = builder.with_endpoint(endpoint);
+        }
+        if !self.headers.is_empty() {
+            builder = builder.with_metadata(MetadataMap::from_headers(self.header_map()?));
+        }
+        if self.uses_custom_tls() {
+            builder = builder.with_tls_config(self.tonic_tls_config()?);
+        }
+        Ok(builder)
+    }
+
+    fn configure_http<B: WithHttpConfig + WithExportConfig>(
+        &self,
+        mut builder: B,
+        endpoint: Option<&str>,
+    ) -> anyhow::Result<B> {
+        if let Some(endpoint) = endpoint {
+            builder = builder.with_endpoint(endpoint);
+        }
+        if !self.headers.is_empty() {
+            builder = builder.with_headers(self.headers.clone());
+        }
+        if self.uses_custom_tls() {
+            builder = builder.with_http_client(self.http_client()?);
+        }
+        Ok(builder)
+    }
+
+    fn tonic_tls_config(&self) -> anyhow::Result<ClientTlsConfig> {
+        let mut tls_config = ClientTlsConfig::new().with_enabled_roots();
+        if let Some(ca_certificate) = self.read_ca_certificate()? {
+            tls_config = tls_config.ca_certificate(Certificate::from_pem(ca_certificate));
+        }
+        if let Some((certificate, key)) = self.read_client_identity()? {
+            tls_config = tls_config.identity(Identity::from_pem(certificate, key));
+        }
+        Ok(tls_config)
+    }
+
+    fn http_client(&self) -> anyhow::Result<reqwest::Client> {
+        let mut client = reqwest::Client::builder();
+        if let Some(ca_certificate) = self.read_ca_certificate()? {
+            client = client.add_root_certificate(
+                reqwest::Certificate::from_pem(&ca_certificate)
+                    .context("invalid OTLP CA certificate")?,
+            );
+        }
+        if let Some((certificate, key)) = self.read_client_identity()? {
+            client = client.identity(
+                reqwest::Identity::from_pkcs8_pem(&certificate, &key)
+                    .context("invalid OTLP client certificate or key")?,
+            );
+        }
+        Ok(client.build()?)
+    }
+
+    fn uses_custom_tls(&self) -> bool {
+        self.ca_certificate.is_some() || self.client_certificate.is_some()
+    }
+
+    fn header_map(&self) -> anyhow::Result<http1::HeaderMap> {
+        self.validate()?;
+        Ok(self
+            .headers
+            .iter()
+            .filter_map(|(name, value)| Some((name.parse().ok()?, value.parse().ok()?)))
+            .collect())
+    }
+
+    fn read_ca_certificate(&self) -> anyhow::Result<Option<Vec<u8>>> {
+        self.ca_certificate.as_deref().map(read_pem).transpose()
+    }
+
+    fn read_client_identity(&self) -> anyhow::Result<Option<(Vec<u8>, Vec<u8>)>> {
+        self.validate()?;
+        match (&self.client_certificate, &self.client_key) {
+            (Some(certificate), Some(key)) => Ok(Some((read_pem(certificate)?, read_pem(key)?))),
+            _ => Ok(None),
+        }
+    }
+}
+
+/// A telemetry signal whose export requests can be sent by a [`RequestSender`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Signal {
+    Traces,
+    Metrics,
+}
+
+impl Signal {
+    /// The name of the signal, as used in OTLP/HTTP paths.
+    pub fn name(self) -> &'static str {
+        match self {
+            Signal::Traces => "traces",
+            Signal::Metrics => "metrics",
+        }
+    }
+
+    fn protocol(self) -> OtlpProtocol {
+        match self {
+            Signal::Traces => OtlpProtocol::traces_protocol_from_env(),
+            Signal::Metrics => OtlpProtocol::metrics_protocol_from_env(),
+        }
+    }
+
+    fn headers_var(self) -> &'static str {
+        match self {
+            Signal::Traces => OTEL_EXPORTER_OTLP_TRACES_HEADERS,
+            Signal::Metrics => OTEL_EXPORTER_OTLP_METRICS_HEADERS,
+        }
+    }
+
+    /// Resolves the endpoint of the signal the way the OTLP exporters do: an endpoint given in the
+    /// connection or by the signal's environment variable is used as-is, while the general
+    /// endpoint has the signal's path appended for OTLP/HTTP.
+    fn endpoint(
+        self,
+        connection: &OtlpConnection,
+        protocol: &OtlpProtocol,
+        env: impl Fn(&str) -> Option<String>,
+    ) -> anyhow::Result<String> {
+        let (explicit, endpoint_var) = match self {
+            Signal::Traces => (
+                &connection.traces_endpoint,
+                OTEL_EXPORTER_OTLP_TRACES_ENDPOINT,
+            ),
+            Signal::Metrics => (
+                &connection.metrics_endpoint,
+                OTEL_EXPORTER_OTLP_METRICS_ENDPOINT,
+            ),
+        };
+        if let Some(endpoint) = explicit.clone().or_else(|| env(endpoint_var)) {
+            return Ok(endpoint);
+        }
+        Ok(match protocol {
+            OtlpProtocol::Grpc => env(OTEL_EXPORTER_OTLP_ENDPOINT)
+                .unwrap_or_else(|| "http://localhost:4317".to_owned()),
+            OtlpProtocol::HttpProtobuf => {
+                let base = env(OTEL_EXPORTER_OTLP_ENDPOINT)
+                    .unwrap_or_else(|| "http://localhost:4318".to_owned());
+                format!("{}/v1/{}", base.trim_end_matches('/'), self.name())
+            }
+            OtlpProtocol::HttpJson => bail!("http/json OTLP protocol is not supported"),
+        })
+    }
+}
+
+/// Sends encoded OTLP export requests of one signal.
+#[derive(Clone, Debug)]
+pub struct RequestSender {
+    signal: Signal,
+    transport: Transport,
+}
+
+#[derive(Clone, Debug)]
+enum Transport {
+    Grpc {
+        channel: tonic::transport::Channel,
+        metadata: MetadataMap,
+    },
+    Http {
+        client: reqwest::Client,
+        url: String,
+        headers: http1::HeaderMap,
+    },
+}
+
+impl RequestSender {
+    /// The signal whose export requests are sent.
+    pub fn signal(&self) -> Signal {
+        self.signal
+    }
+
+    /// Sends an export request encoded by [`encode_spans`] or [`encode_metrics`].
+    pub async fn send(&self, request: Vec<u8>) -> anyhow::Result<()> {
+        match &self.transport {
+            Transport::Grpc { channel, metadata } => match self.signal {
+                Signal::Traces => {
+                    let request = ExportTraceServiceRequest::decode(request.as_slice())?;
+                    TraceServiceClient::new(channel.clone())
+                        .export(grpc_request(request, metadata))
+                        .await?;
+                }
+                Signal::Metrics => {
+                    let request = ExportMetricsServiceRequest::decode(request.as_slice())?;
+                    MetricsServiceClient::new(channel.clone())
+                        .export(grpc_request(request, metadata))
+                        .await?;
+                }
+            },
+            Transport::Http {
+                client,
+                url,
+                headers,
+            } => {
+                let response = client
+                    .post(url)
+                    .headers(headers.clone())
+                    .header(http1::header::CONTENT_TYPE, "application/x-protobuf")
+                    .body(request)
+                    .send()
+                    .await?;
+                ensure!(
+                    response.status().is_success(),
+                    "OTLP endpoint {url} responded with {}",
+                    response.status()
+                );
+            }
+        }
+        Ok(())
+    }
+}
+
+fn grpc_request<T>(message: T, metadata: &MetadataMap) -> tonic::Request<T> {
+    let mut request = tonic::Request::new(message);
+    *request.metadata_mut() = metadata.clone();
+    request
+}
+
+/// Encodes spans described by the resource as an OTLP export request.
+pub fn encode_spans(spans: Vec<SpanData>, resource: &Resource) -> Vec<u8> {
+    let resource_spans =
+        group_spans_by_resource_and_scope(spans, &ResourceAttributesWithSchema::from(resource));
+    ExportTraceServiceRequest { resource_spans }.encode_to_vec()
+}
+
+/// Encodes metrics as an OTLP export request.
+pub fn encode_metrics(metrics: &ResourceMetrics) -> Vec<u8> {
+    ExportMetricsServiceRequest::from(metrics).encode_to_vec()
+}
+
+/// Parses headers in the `key1=value1,key2=value2` format of the `OTEL_EXPORTER_OTLP_HEADERS`
+/// environment variables, whose values are percent-encoded. Invalid headers are ignored.
+fn parse_headers(headers: &str) -> http1::HeaderMap {
+    headers
+        .split(',')
+        .filter_map(|header| {
+            let (name, value) = header.split_once('=')?;
+            let value = percent_encoding::percent_decode_str(value.trim())
+                .decode_utf8()
+                .ok()?;
+            Some((name.trim().parse().ok()?, value.parse().ok()?))
+        })
+        .collect()
+}
+
+fn read_pem(path: &Path) -> anyhow::Result<Vec<u8>> {
+    std::fs::read(path).with_context(|| format!("failed to read {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn overrides_merge_headers() {
+        let base = OtlpConnection {
+            traces_endpoint: None,
+            metrics_endpoint: None,
+            logs_endpoint: None,
+            headers: HashMap::from([("x-team".to_owned(), "web".to_owned())]),
+            ca_certificate: Some("env-ca.pem".into()),
+            client_certificate: Some("env-cert.pem".into()),
+            client_key: Some("env-key.pem".into()),
+        };
+        let merged = base.with_overrides(OtlpConnection {
+            traces_endpoint: Some("http://traces:4318/v1/traces".to_owned()),
+            headers: HashMap::from([("authorization".to_owned(), "Bearer token".to_owned())]),
+            client_certificate: Some("cert.pem".into()),
+            client_key: Some("key.pem".into()),
+            ..Default::default()
+        });
+        assert_eq!(2, merged.headers.len());
+        assert_eq!(
+            Some("http://traces:4318/v1/traces"),
+            merged.traces_endpoint.as_deref()
+        );
+        assert_eq!(None, merged.metrics_endpoint);
+        assert_eq!(Some(PathBuf::from("env-ca.pem")), merged.ca_certificate);
+        assert_eq!(Some(PathBuf::from("cert.pem")), merged.client_certificate);
+        assert_eq!(Some(PathBuf::from("key.pem")), merged.client_key);
+    }
+
+    #[test]
+    fn validates_settings() {
+        let unpaired = OtlpConnection {
+            client_certificate: Some("cert.pem".into()),
+            ..Default::default()
+        };
+        assert!(unpaired.validate().is_err());
+
+        let bad_header = OtlpConnection {
+            headers: HashMap::from([("bad header".to_owned(), "value".to_owned())]),
+            ..Default::default()
+        };
+        assert!(bad_header.validate().is_err());
+
+        assert!(OtlpConnection::default().validate().is_ok());
+    }
+
+    #[test]
+    fn resolves_endpoints() {
+        let env = HashMap::from([
+            (OTEL_EXPORTER_OTLP_ENDPOINT, "http://collector:4318/"),
+            (
+                OTEL_EXPORTER_OTLP_METRICS_ENDPOINT,
+                "http://metrics:4318/metrics",
+            ),
+        ]);
+        let env = |key: &str| env.get(key).map(|val| val.to_string());
+        let connection = OtlpConnection::default();
+        assert_eq!(
+            "http://collector:4318/v1/traces",
+            Signal::Traces
+                .endpoint(&connection, &OtlpProtocol::HttpProtobuf, env)
+                .unwrap()
+        );
+        assert_eq!(
+            "http://metrics:4318/metrics",
+            Signal::Metrics
+                .endpoint(&connection, &OtlpProtocol::HttpProtobuf, env)
+                .unwrap()
+        );
+        assert_eq!(
+            "http://collector:4318/",
+            Signal::Traces
+                .endpoint(&connection, &OtlpProtocol::Grpc, env)
+                .unwrap()
+        );
+
+        let connection = OtlpConnection {
+            traces_endpoint: Some("https://traces.example.com/v1/traces".to_owned()),
+            ..Default::default()
+        };
+        assert_eq!(
+            "https://traces.example.com/v1/traces",
+            Signal::Traces
+                .endpoint(&connection, &OtlpProtocol::HttpProtobuf, env)
+                .unwrap()
+        );
+        assert_eq!(
+            "http://localhost:4318/v1/traces",
+            Signal::Traces
+                .endpoint(
+                    &OtlpConnection::default(),
+                    &OtlpProtocol::HttpProtobuf,
+                    |_| { None }
+                )
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn parses_env_headers() {
+        let headers = parse_headers("authorization=Bearer%20token, x-team = web,invalid");
+        assert_eq!(2, headers.len());
+        assert_eq!("Bearer token", headers["authorization"]);
+        assert_eq!("web", headers["x-team"]);
+    }
+
+    #[test]
+    fn reads_certificates() {
+        let dir = tempfile::tempdir().unwrap();
+        let ca_certificate = dir.path().join("ca.pem");
+        std::fs::write(&ca_certificate, "-----BEGIN CERTIFICATE-----").unwrap();
+        let connection = OtlpConnection {
+            ca_certificate: Some(ca_certificate),
+            ..Default::default()
+        };
+        assert_eq!(
+            b"-----BEGIN CERTIFICATE-----".to_vec(),
+            connection.read_ca_certificate().unwrap().unwrap()
+        );
+
+        let missing = OtlpConnection {
+            ca_certificate: Some(dir.path().join("missing.pem")),
+            ..Default::default()
+        };
+        assert!(missing.read_ca_certificate().is_err());
+    }
+}
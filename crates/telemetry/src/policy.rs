@@ -0,0 +1,299 @@
+//! Control over which telemetry is exported, set from runtime configuration.
+//!
+//! Telemetry is initialized before runtime configuration is read, so the policy is held globally
+//! and consulted as telemetry is recorded and exported.
+
+use std::borrow::Cow;
+use std::collections::HashSet;
+use std::sync::{Arc, RwLock};
+
+use opentelemetry::{Context as OtelContext, KeyValue, Value, trace::Status};
+use opentelemetry_sdk::{
+    Resource,
+    error::OTelSdkResult,
+    trace::{Span, SpanData, SpanProcessor},
+};
+use regex::Regex;
+use serde::Deserialize;
+use tracing::Subscriber;
+use tracing_subscriber::{filter::DynFilterFn, registry::LookupSpan};
+
+use crate::component;
+
+static POLICY: RwLock<Option<Arc<ExportPolicy>>> = RwLock::new(None);
+
+/// The value that redacted attributes are exported with.
+const REDACTED: &str = "[REDACTED]";
+
+/// Which telemetry is exported, and how attributes are redacted before export.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct ExportPolicy {
+    /// Components whose spans, metrics, and logs are not exported.
+    #[serde(default)]
+    pub disabled_components: HashSet<String>,
+    /// Attribute keys whose values are replaced with `[REDACTED]` before export.
+    #[serde(default)]
+    pub redact_attributes: HashSet<String>,
+    /// Attribute keys whose values are replaced with their SHA-256 digest before export, so that
+    /// equal values can still be correlated.
+    #[serde(default)]
+    pub hash_attributes: HashSet<String>,
+    /// Patterns of attribute keys whose values are replaced with `[REDACTED]` before export.
+    #[serde(default)]
+    pub redact_attribute_patterns: Vec<AttributePattern>,
+    /// Patterns of attribute keys whose values are replaced with their SHA-256 digest before
+    /// export.
+    #[serde(default)]
+    pub hash_attribute_patterns: Vec<AttributePattern>,
+}
+
+/// A regular expression matched against whole attribute keys.
+#[derive(Clone, Debug)]
+pub struct AttributePattern {
+    pattern: String,
+    regex: Regex,
+}
+
+impl AttributePattern {
+    fn matches(&self, key: &str) -> bool {
+        self.regex.is_match(key)
+    }
+}
+
+impl TryFrom<String> for AttributePattern {
+    type Error = regex::Error;
+
+    fn try_from(pattern: String) -> Result<Self, Self::Error> {
+        let regex = Regex::new(&format!("^(?:{pattern})$"))?;
+        Ok(Self { pattern, regex })
+    }
+}
+
+impl<'de> Deserialize<'de> for AttributePattern {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .try_into()
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+impl PartialEq for AttributePattern {
+    fn eq(&self, other: &Self) -> bool {
+        self.pattern == other.pattern
+    }
+}
+
+impl Eq for AttributePattern {}
+
+impl ExportPolicy {
+    /// Returns whether telemetry from the given component is exported.
+    pub fn exports_component(&self, component_id: &str) -> bool {
+        !self.disabled_components.contains(component_id)
+    }
+
+    /// Redacts or hashes the configured attributes, and redacts secret variable values from
+    /// string attributes.
+    pub fn redact(&self, attributes: &mut [KeyValue]) {
+        for attribute in attributes {
+            if let Some(value) = self.redacted_value(attribute.key.as_str(), &attribute.value) {
+                attribute.value = value;
+            }
+        }
+    }
+
+    /// Returns the value an attribute is exported with, if it is to be redacted or hashed.
+    pub fn redacted_value(&self, key: &str, value: &Value) -> Option<Value> {
+        if self.redact_attributes.contains(key)
+            || self
+                .redact_attribute_patterns
+                .iter()
+                .any(|p| p.matches(key))
+        {
+            Some(Value::from(REDACTED))
+        } else if self.hash_attributes.contains(key)
+            || self.hash_attribute_patterns.iter().any(|p| p.matches(key))
+        {
+            Some(Value::from(crate::audit::digest(&value.as_str())))
+        } else if let Value::String(value) = value
+            && let Some(redacted) = redact_secrets(value.as_str())
+        {
+            Some(Value::from(redacted))
+        } else {
+            None
+        }
+    }
+
+    /// Redacts or hashes the configured attributes of a span and its events, and redacts secret
+    /// variable values from its status.
+    pub fn redact_span(&self, span: &mut SpanData) {
+        self.redact(&mut span.attributes);
+        for event in span.events.events.iter_mut() {
+            self.redact(&mut event.attributes);
+        }
+        if let Status::Error { description } = &mut span.status
+            && let Some(redacted) = redact_secrets(description)
+        {
+            *description = redacted.into();
+        }
+    }
+
+    /// Returns whether any attributes may be redacted, so that checking them can be skipped.
+    pub fn redacts_anything(&self) -> bool {
+        !(self.redact_attributes.is_empty()
+            && self.hash_attributes.is_empty()
+            && self.redact_attribute_patterns.is_empty()
+            && self.hash_attribute_patterns.is_empty())
+            || spin_common::secrets::any()
+    }
+}
+
+/// Returns the text with secret variable values redacted, if it contains any.
+fn redact_secrets(text: &str) -> Option<String> {
+    match spin_common::secrets::redact(text) {
+        Cow::Owned(redacted) => Some(redacted),
+        Cow::Borrowed(_) => None,
+    }
+}
+
+/// Sets the policy for telemetry recorded from now on.
+pub fn set_export_policy(policy: ExportPolicy) {
+    *POLICY.write().unwrap() = Some(Arc::new(policy));
+}
+
+/// Returns the current policy; by default all telemetry is exported as recorded.
+pub fn export_policy() -> Arc<ExportPolicy> {
+    POLICY.read().unwrap().clone().unwrap_or_default()
+}
+
+/// Filters out spans and events recorded on behalf of components whose telemetry isn't exported.
+pub(crate) fn component_filter<S: Subscriber + for<'span> LookupSpan<'span>>() -> DynFilterFn<S> {
+    DynFilterFn::new(|_, cx| {
+        let policy = export_policy();
+        if policy.disabled_components.is_empty() {
+            return true;
+        }
+        cx.lookup_current()
+            .and_then(|span| component::component_id(&span))
+            .is_none_or(|component_id| policy.exports_component(&component_id))
+    })
+}
+
+/// A span processor that applies the export policy's redactions before passing spans on.
+#[derive(Debug)]
+pub(crate) struct RedactingSpanProcessor<P>(pub(crate) P);
+
+impl<P: SpanProcessor> SpanProcessor for RedactingSpanProcessor<P> {
+    fn on_start(&self, span: &mut Span, cx: &OtelContext) {
+        self.0.on_start(span, cx);
+    }
+
+    fn on_end(&self, mut span: SpanData) {
+        let policy = export_policy();
+        if policy.redacts_anything() {
+            policy.redact_span(&mut span);
+        }
+        self.0.on_end(span);
+    }
+
+    fn force_flush(&self) -> OTelSdkResult {
+        self.0.force_flush()
+    }
+
+    fn shutdown(&self) -> OTelSdkResult {
+        self.0.shutdown()
+    }
+
+    fn set_resource(&mut self, resource: &Resource) {
+        self.0.set_resource(resource);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn redacts_and_hashes_configured_attributes() {
+        let policy = ExportPolicy {
+            redact_attributes: HashSet::from(["url.full".to_owned()]),
+            hash_attributes: HashSet::from(["client.address".to_owned()]),
+            ..Default::default()
+        };
+        let mut attributes = vec![
+            KeyValue::new("url.full", "https://example.com/?token=secret"),
+            KeyValue::new("client.address", "10.0.0.1"),
+            KeyValue::new("http.request.method", "GET"),
+        ];
+        policy.redact(&mut attributes);
+        assert_eq!(
+            vec![
+                KeyValue::new("url.full", REDACTED),
+                KeyValue::new("client.address", crate::audit::digest("10.0.0.1")),
+                KeyValue::new("http.request.method", "GET"),
+            ],
+            attributes
+        );
+    }
+
+    #[test]
+    fn redacts_and_hashes_attributes_matching_patterns() {
+        let policy = ExportPolicy {
+            redact_attribute_patterns: vec![
+                "http\\.request\\.header\\..*"
+                    .to_owned()
+                    .try_into()
+                    .unwrap(),
+            ],
+            hash_attribute_patterns: vec!["user\\.(id|email)".to_owned().try_into().unwrap()],
+            ..Default::default()
+        };
+        let mut attributes = vec![
+            KeyValue::new("http.request.header.authorization", "Bearer abc"),
+            KeyValue::new("user.email", "someone@example.com"),
+            // Patterns match whole keys
+            KeyValue::new("user.email.domain", "example.com"),
+        ];
+        policy.redact(&mut attributes);
+        assert_eq!(
+            vec![
+                KeyValue::new("http.request.header.authorization", REDACTED),
+                KeyValue::new("user.email", crate::audit::digest("someone@example.com")),
+                KeyValue::new("user.email.domain", "example.com"),
+            ],
+            attributes
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_patterns() {
+        assert!(AttributePattern::try_from("http.(".to_owned()).is_err());
+    }
+
+    #[test]
+    fn redacts_secret_values() {
+        spin_common::secrets::register("policy-test-secret");
+        let mut attributes = vec![
+            KeyValue::new("exception.message", "bad password policy-test-secret"),
+            KeyValue::new("http.request.method", "GET"),
+        ];
+        ExportPolicy::default().redact(&mut attributes);
+        assert_eq!(
+            vec![
+                KeyValue::new("exception.message", "bad password [REDACTED]"),
+                KeyValue::new("http.request.method", "GET"),
+            ],
+            attributes
+        );
+    }
+
+    #[test]
+    fn exports_components_unless_disabled() {
+        let policy = ExportPolicy {
+            disabled_components: HashSet::from(["healthcheck".to_owned()]),
+            ..Default::default()
+        };
+        assert!(!policy.exports_component("healthcheck"));
+        assert!(policy.exports_component("api"));
+    }
+}
@@ -1,25 +1,112 @@
+use std::collections::HashMap;
+
 use opentelemetry::{
+    Context,
+    baggage::Baggage,
     global,
     propagation::{Extractor, Injector},
+    trace::{SpanContext, TraceContextExt as _},
 };
 use tracing_opentelemetry::OpenTelemetrySpanExt;
+use tracing_subscriber::{Registry, registry::LookupSpan as _};
 
-/// Injects the current W3C TraceContext into the provided request.
+/// Injects the current W3C TraceContext and Baggage into the provided request.
 pub fn inject_trace_context<'a>(req: impl Into<HeaderInjector<'a>>) {
-    let mut injector = req.into();
+    inject_context(req.into(), tracing::Span::current().context());
+}
+
+/// Injects the current W3C TraceContext and Baggage into the provided request.
+///
+/// If `baggage` is given, it is injected in place of the current context's baggage.
+pub fn inject_trace_context_with_baggage<'a>(
+    req: impl Into<HeaderInjector<'a>>,
+    baggage: Option<Baggage>,
+) {
+    let context = tracing::Span::current().context();
+    let context = match baggage {
+        Some(baggage) => context.with_value(baggage),
+        None => context,
+    };
+    inject_context(req.into(), context);
+}
+
+fn inject_context(mut injector: HeaderInjector<'_>, context: Context) {
     global::get_text_map_propagator(|propagator| {
-        let context = tracing::Span::current().context();
         propagator.inject_context(&context, &mut injector);
     });
 }
 
-/// Extracts the W3C TraceContext from the provided request and sets it as the parent of the
-/// current span.
+/// Injects the current W3C TraceContext and Baggage into message metadata, so that the trigger
+/// which handles the message can continue the trace with [`extract_message_trace_context`].
+pub fn inject_message_trace_context(metadata: &mut HashMap<String, String>) {
+    let context = tracing::Span::current().context();
+    global::get_text_map_propagator(|propagator| {
+        propagator.inject_context(&context, metadata);
+    });
+}
+
+/// Extracts the W3C TraceContext and Baggage from the provided request and sets them as the
+/// parent of the current span.
 pub fn extract_trace_context<'a>(req: impl Into<HeaderExtractor<'a>>) {
-    let extractor = req.into();
+    set_parent_from(&req.into());
+}
+
+/// Extracts the W3C TraceContext from message metadata, such as Kafka or AMQP headers, and sets
+/// it as the parent of the current span.
+///
+/// Keys are matched case-insensitively, and entries whose values are not UTF-8 are ignored. If
+/// the metadata carries no trace context, the current span is left as it is.
+pub fn extract_message_trace_context<K, V>(metadata: impl IntoIterator<Item = (K, V)>)
+where
+    K: AsRef<str>,
+    V: AsRef<[u8]>,
+{
+    set_parent_from(&MetadataExtractor::new(metadata));
+}
+
+/// Returns the trace context extracted from the inbound request or message being handled, if it
+/// carried one.
+///
+/// This is the remote parent of the host's spans for the request, which guests can use to parent
+/// their own spans.
+pub fn inbound_span_context() -> Option<SpanContext> {
+    tracing::Span::current()
+        .with_subscriber(|(id, dispatch)| {
+            let registry = dispatch.downcast_ref::<Registry>()?;
+            registry.span(id)?.scope().find_map(|span| {
+                span.extensions()
+                    .get::<InboundSpanContext>()
+                    .map(|inbound| inbound.0.clone())
+            })
+        })
+        .flatten()
+}
+
+/// The trace context extracted from an inbound request or message, recorded on the span handling
+/// it.
+struct InboundSpanContext(SpanContext);
+
+fn set_parent_from(extractor: &dyn Extractor) {
     let parent_context =
-        global::get_text_map_propagator(|propagator| propagator.extract(&extractor));
-    tracing::Span::current().set_parent(parent_context);
+        global::get_text_map_propagator(|propagator| propagator.extract(extractor));
+    set_parent(parent_context);
+}
+
+fn set_parent(parent_context: Context) {
+    let span = tracing::Span::current();
+    let span_context = parent_context.span().span_context().clone();
+    if span_context.is_valid() {
+        span.with_subscriber(|(id, dispatch)| {
+            if let Some(span) = dispatch
+                .downcast_ref::<Registry>()
+                .and_then(|registry| registry.span(id))
+            {
+                span.extensions_mut()
+                    .insert(InboundSpanContext(span_context));
+            }
+        });
+    }
+    span.set_parent(parent_context);
 }
 
 pub enum HeaderInjector<'a> {
@@ -72,6 +159,33 @@ impl<'a> From<&'a mut http1::HeaderMap> for HeaderInjector<'a> {
     }
 }
 
+/// Extracts trace context from message metadata.
+struct MetadataExtractor(HashMap<String, String>);
+
+impl MetadataExtractor {
+    fn new<K: AsRef<str>, V: AsRef<[u8]>>(metadata: impl IntoIterator<Item = (K, V)>) -> Self {
+        Self(
+            metadata
+                .into_iter()
+                .filter_map(|(key, value)| {
+                    let value = std::str::from_utf8(value.as_ref()).ok()?;
+                    Some((key.as_ref().to_ascii_lowercase(), value.to_owned()))
+                })
+                .collect(),
+        )
+    }
+}
+
+impl Extractor for MetadataExtractor {
+    fn get(&self, key: &str) -> Option<&str> {
+        self.0.get(&key.to_ascii_lowercase()).map(String::as_str)
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        self.0.keys().map(String::as_str).collect()
+    }
+}
+
 pub enum HeaderExtractor<'a> {
     Http0(&'a http0::HeaderMap),
     Http1(&'a http1::HeaderMap),
@@ -108,3 +222,67 @@ impl<'a, T> From<&'a http1::Request<T>> for HeaderExtractor<'a> {
         Self::Http1(req.headers())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use opentelemetry::propagation::TextMapPropagator as _;
+    use opentelemetry::trace::TraceContextExt as _;
+    use opentelemetry_sdk::propagation::{BaggagePropagator, TraceContextPropagator};
+
+    use super::*;
+
+    #[test]
+    fn injects_given_baggage() {
+        global::set_text_map_propagator(BaggagePropagator::new());
+        let mut baggage = Baggage::new();
+        baggage.insert("tenant", "acme");
+
+        let mut headers = http1::HeaderMap::new();
+        inject_trace_context_with_baggage(&mut headers, Some(baggage));
+
+        assert_eq!("tenant=acme", headers["baggage"]);
+    }
+
+    #[test]
+    fn records_inbound_span_context() {
+        let span_context = SpanContext::new(
+            opentelemetry::trace::TraceId::from(1),
+            opentelemetry::trace::SpanId::from(2),
+            opentelemetry::trace::TraceFlags::SAMPLED,
+            true,
+            Default::default(),
+        );
+        tracing::subscriber::with_default(tracing_subscriber::registry(), || {
+            let request = tracing::info_span!("request");
+            let _request = request.enter();
+            assert_eq!(None, inbound_span_context());
+
+            set_parent(Context::new().with_remote_span_context(span_context.clone()));
+            let handler = tracing::info_span!("handler");
+            let _handler = handler.enter();
+            assert_eq!(Some(span_context), inbound_span_context());
+        });
+    }
+
+    #[test]
+    fn extracts_trace_context_from_message_metadata() {
+        let metadata: Vec<(&str, &[u8])> = vec![
+            (
+                "TraceParent",
+                b"00-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01",
+            ),
+            ("binary", &[0xff, 0xfe]),
+        ];
+        let extractor = MetadataExtractor::new(metadata);
+        assert_eq!(None, extractor.get("binary"));
+
+        let context = TraceContextPropagator::new().extract(&extractor);
+        let span_context = context.span().span_context().clone();
+        assert!(span_context.is_remote());
+        assert_eq!(
+            "0af7651916cd43dd8448eb211c80319c",
+            span_context.trace_id().to_string()
+        );
+        assert_eq!("b7ad6b7169203331", span_context.span_id().to_string());
+    }
+}
@@ -0,0 +1,400 @@
+//! Selection of the propagators used to carry trace context across process boundaries.
+//!
+//! In addition to the W3C formats implemented by the OTel SDK, this supports the B3 format used
+//! by Zipkin and many service meshes, and the Jaeger format.
+
+use opentelemetry::{
+    Context,
+    propagation::{
+        Extractor, Injector, TextMapCompositePropagator, TextMapPropagator,
+        text_map_propagator::FieldIter,
+    },
+    trace::{SpanContext, SpanId, TraceContextExt, TraceFlags, TraceId, TraceState},
+};
+use opentelemetry_sdk::propagation::{BaggagePropagator, TraceContextPropagator};
+use serde::Deserialize;
+
+/// A format for propagating trace context.
+///
+/// The names match those of the `OTEL_PROPAGATORS` environment variable.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Propagator {
+    /// W3C Trace Context (`traceparent` and `tracestate` headers).
+    TraceContext,
+    /// W3C Baggage (`baggage` header).
+    Baggage,
+    /// B3 single header (`b3` header).
+    B3,
+    /// B3 multiple headers (`x-b3-*` headers).
+    B3Multi,
+    /// Jaeger (`uber-trace-id` header).
+    Jaeger,
+}
+
+/// The propagators used unless configured otherwise.
+pub const DEFAULT_PROPAGATORS: &[Propagator] = &[Propagator::TraceContext, Propagator::Baggage];
+
+/// Sets the propagators used to extract trace context from inbound requests and inject it into
+/// outbound requests.
+///
+/// When extracting, later propagators take precedence over earlier ones if a request carries
+/// trace context in more than one format.
+pub fn set_propagators(propagators: &[Propagator]) {
+    let propagators = propagators
+        .iter()
+        .map(|propagator| -> Box<dyn TextMapPropagator + Send + Sync> {
+            match propagator {
+                Propagator::TraceContext => Box::new(TraceContextPropagator::new()),
+                Propagator::Baggage => Box::new(BaggagePropagator::new()),
+                Propagator::B3 => Box::new(B3Propagator::new(B3Encoding::SingleHeader)),
+                Propagator::B3Multi => Box::new(B3Propagator::new(B3Encoding::MultipleHeaders)),
+                Propagator::Jaeger => Box::new(JaegerPropagator::new()),
+            }
+        })
+        .collect();
+    opentelemetry::global::set_text_map_propagator(TextMapCompositePropagator::new(propagators));
+}
+
+const B3_SINGLE_HEADER: &str = "b3";
+const B3_TRACE_ID_HEADER: &str = "x-b3-traceid";
+const B3_SPAN_ID_HEADER: &str = "x-b3-spanid";
+const B3_SAMPLED_HEADER: &str = "x-b3-sampled";
+const B3_FLAGS_HEADER: &str = "x-b3-flags";
+const JAEGER_HEADER: &str = "uber-trace-id";
+
+/// Which B3 headers to inject.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum B3Encoding {
+    SingleHeader,
+    MultipleHeaders,
+}
+
+/// Propagates trace context in the [B3](https://github.com/openzipkin/b3-propagation) format.
+///
+/// Both encodings are accepted when extracting.
+#[derive(Debug)]
+struct B3Propagator {
+    encoding: B3Encoding,
+    fields: Vec<String>,
+}
+
+impl B3Propagator {
+    fn new(encoding: B3Encoding) -> Self {
+        let fields = match encoding {
+            B3Encoding::SingleHeader => vec![B3_SINGLE_HEADER],
+            B3Encoding::MultipleHeaders => vec![
+                B3_TRACE_ID_HEADER,
+                B3_SPAN_ID_HEADER,
+                B3_SAMPLED_HEADER,
+                B3_FLAGS_HEADER,
+            ],
+        };
+        Self {
+            encoding,
+            fields: fields.into_iter().map(String::from).collect(),
+        }
+    }
+
+    fn extract_single_header(extractor: &dyn Extractor) -> Option<SpanContext> {
+        let value = extractor.get(B3_SINGLE_HEADER)?.trim();
+        // The header is `{trace-id}-{span-id}[-{sampling}[-{parent-span-id}]]`. A header of just
+        // the sampling state carries no trace context.
+        let mut parts = value.split('-');
+        let trace_id = parse_trace_id(parts.next()?)?;
+        let span_id = parse_span_id(parts.next()?)?;
+        let flags = match parts.next() {
+            Some("1" | "d") => TraceFlags::SAMPLED,
+            Some("0") | None => TraceFlags::default(),
+            Some(_) => return None,
+        };
+        Some(SpanContext::new(
+            trace_id,
+            span_id,
+            flags,
+            true,
+            TraceState::default(),
+        ))
+    }
+
+    fn extract_multiple_headers(extractor: &dyn Extractor) -> Option<SpanContext> {
+        let trace_id = parse_trace_id(extractor.get(B3_TRACE_ID_HEADER)?.trim())?;
+        let span_id = parse_span_id(extractor.get(B3_SPAN_ID_HEADER)?.trim())?;
+        let debug = extractor.get(B3_FLAGS_HEADER).map(str::trim) == Some("1");
+        let sampled = matches!(
+            extractor.get(B3_SAMPLED_HEADER).map(str::trim),
+            Some("1" | "true")
+        );
+        let flags = if debug || sampled {
+            TraceFlags::SAMPLED
+        } else {
+            TraceFlags::default()
+        };
+        Some(SpanContext::new(
+            trace_id,
+            span_id,
+            flags,
+            true,
+            TraceState::default(),
+        ))
+    }
+}
+
+impl TextMapPropagator for B3Propagator {
+    fn inject_context(&self, cx: &Context, injector: &mut dyn Injector) {
+        let span = cx.span();
+        let span_context = span.span_context();
+        if !span_context.is_valid() {
+            return;
+        }
+        let sampled = if span_context.is_sampled() { "1" } else { "0" };
+        match self.encoding {
+            B3Encoding::SingleHeader => injector.set(
+                B3_SINGLE_HEADER,
+                format!(
+                    "{}-{}-{sampled}",
+                    span_context.trace_id(),
+                    span_context.span_id()
+                ),
+            ),
+            B3Encoding::MultipleHeaders => {
+                injector.set(B3_TRACE_ID_HEADER, span_context.trace_id().to_string());
+                injector.set(B3_SPAN_ID_HEADER, span_context.span_id().to_string());
+                injector.set(B3_SAMPLED_HEADER, sampled.to_owned());
+            }
+        }
+    }
+
+    fn extract_with_context(&self, cx: &Context, extractor: &dyn Extractor) -> Context {
+        match Self::extract_single_header(extractor)
+            .or_else(|| Self::extract_multiple_headers(extractor))
+        {
+            Some(span_context) => cx.with_remote_span_context(span_context),
+            None => cx.clone(),
+        }
+    }
+
+    fn fields(&self) -> FieldIter<'_> {
+        FieldIter::new(&self.fields)
+    }
+}
+
+/// Propagates trace context in the
+/// [Jaeger](https://www.jaegertracing.io/docs/latest/client-libraries/#propagation-format) format.
+#[derive(Debug)]
+struct JaegerPropagator {
+    fields: [String; 1],
+}
+
+impl JaegerPropagator {
+    fn new() -> Self {
+        Self {
+            fields: [JAEGER_HEADER.to_owned()],
+        }
+    }
+
+    fn extract_span_context(extractor: &dyn Extractor) -> Option<SpanContext> {
+        // The header is `{trace-id}:{span-id}:{parent-span-id}:{flags}`, and is sometimes sent
+        // URL-encoded.
+        let value = extractor
+            .get(JAEGER_HEADER)?
+            .trim()
+            .replace("%3A", ":")
+            .replace("%3a", ":");
+        let parts: Vec<&str> = value.split(':').collect();
+        let [trace_id, span_id, _parent_span_id, flags] = parts[..] else {
+            return None;
+        };
+        let trace_id = parse_trace_id(trace_id)?;
+        let span_id = parse_span_id(span_id)?;
+        let flags = u8::from_str_radix(flags, 16).ok()?;
+        // Bit 1 is sampled, and bit 2 is debug, which implies sampled.
+        let flags = if flags & 0b11 != 0 {
+            TraceFlags::SAMPLED
+        } else {
+            TraceFlags::default()
+        };
+        Some(SpanContext::new(
+            trace_id,
+            span_id,
+            flags,
+            true,
+            TraceState::default(),
+        ))
+    }
+}
+
+impl TextMapPropagator for JaegerPropagator {
+    fn inject_context(&self, cx: &Context, injector: &mut dyn Injector) {
+        let span = cx.span();
+        let span_context = span.span_context();
+        if !span_context.is_valid() {
+            return;
+        }
+        let flags = if span_context.is_sampled() { 1 } else { 0 };
+        injector.set(
+            JAEGER_HEADER,
+            format!(
+                "{}:{}:0:{flags}",
+                span_context.trace_id(),
+                span_context.span_id()
+            ),
+        );
+    }
+
+    fn extract_with_context(&self, cx: &Context, extractor: &dyn Extractor) -> Context {
+        match Self::extract_span_context(extractor) {
+            Some(span_context) => cx.with_remote_span_context(span_context),
+            None => cx.clone(),
+        }
+    }
+
+    fn fields(&self) -> FieldIter<'_> {
+        FieldIter::new(&self.fields)
+    }
+}
+
+/// Parses a hex trace ID, which may be 64 or 128 bits.
+fn parse_trace_id(hex: &str) -> Option<TraceId> {
+    if hex.is_empty() || hex.len() > 32 {
+        return None;
+    }
+    TraceId::from_hex(&format!("{hex:0>32}"))
+        .ok()
+        .filter(|id| *id != TraceId::INVALID)
+}
+
+/// Parses a hex span ID.
+fn parse_span_id(hex: &str) -> Option<SpanId> {
+    if hex.is_empty() || hex.len() > 16 {
+        return None;
+    }
+    SpanId::from_hex(&format!("{hex:0>16}"))
+        .ok()
+        .filter(|id| *id != SpanId::INVALID)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    const TRACE_ID: &str = "0af7651916cd43dd8448eb211c80319c";
+    const SPAN_ID: &str = "b7ad6b7169203331";
+
+    fn context() -> Context {
+        Context::new().with_remote_span_context(SpanContext::new(
+            TraceId::from_hex(TRACE_ID).unwrap(),
+            SpanId::from_hex(SPAN_ID).unwrap(),
+            TraceFlags::SAMPLED,
+            true,
+            TraceState::default(),
+        ))
+    }
+
+    fn inject(propagator: &dyn TextMapPropagator) -> HashMap<String, String> {
+        let mut headers = HashMap::new();
+        propagator.inject_context(&context(), &mut headers);
+        headers
+    }
+
+    fn extract(propagator: &dyn TextMapPropagator, headers: &[(&str, &str)]) -> SpanContext {
+        let headers: HashMap<String, String> = headers
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        propagator.extract(&headers).span().span_context().clone()
+    }
+
+    #[test]
+    fn deserializes_propagator_names() {
+        let propagators: Vec<Propagator> =
+            serde_json::from_str(r#"["tracecontext", "baggage", "b3", "b3multi", "jaeger"]"#)
+                .unwrap();
+        assert_eq!(
+            vec![
+                Propagator::TraceContext,
+                Propagator::Baggage,
+                Propagator::B3,
+                Propagator::B3Multi,
+                Propagator::Jaeger
+            ],
+            propagators
+        );
+    }
+
+    #[test]
+    fn b3_round_trips() {
+        for encoding in [B3Encoding::SingleHeader, B3Encoding::MultipleHeaders] {
+            let propagator = B3Propagator::new(encoding);
+            let headers = inject(&propagator);
+            let headers: Vec<(&str, &str)> = headers
+                .iter()
+                .map(|(k, v)| (k.as_str(), v.as_str()))
+                .collect();
+            let span_context = extract(&propagator, &headers);
+            assert_eq!(context().span().span_context(), &span_context);
+        }
+    }
+
+    #[test]
+    fn b3_injects_expected_headers() {
+        let headers = inject(&B3Propagator::new(B3Encoding::SingleHeader));
+        assert_eq!(
+            Some(&format!("{TRACE_ID}-{SPAN_ID}-1")),
+            headers.get(B3_SINGLE_HEADER)
+        );
+
+        let headers = inject(&B3Propagator::new(B3Encoding::MultipleHeaders));
+        assert_eq!(Some(&TRACE_ID.to_owned()), headers.get(B3_TRACE_ID_HEADER));
+        assert_eq!(Some(&SPAN_ID.to_owned()), headers.get(B3_SPAN_ID_HEADER));
+        assert_eq!(Some(&"1".to_owned()), headers.get(B3_SAMPLED_HEADER));
+    }
+
+    #[test]
+    fn b3_extracts_64_bit_trace_ids() {
+        let span_context = extract(
+            &B3Propagator::new(B3Encoding::SingleHeader),
+            &[("b3", "8448eb211c80319c-b7ad6b7169203331-d")],
+        );
+        assert_eq!(
+            "00000000000000008448eb211c80319c",
+            span_context.trace_id().to_string()
+        );
+        assert!(span_context.is_sampled());
+    }
+
+    #[test]
+    fn b3_ignores_invalid_headers() {
+        let propagator = B3Propagator::new(B3Encoding::SingleHeader);
+        assert!(!extract(&propagator, &[("b3", "0")]).is_valid());
+        assert!(!extract(&propagator, &[("b3", "not-hex")]).is_valid());
+        assert!(!extract(&propagator, &[("x-b3-traceid", TRACE_ID)]).is_valid());
+    }
+
+    #[test]
+    fn jaeger_round_trips() {
+        let propagator = JaegerPropagator::new();
+        let headers = inject(&propagator);
+        assert_eq!(
+            Some(&format!("{TRACE_ID}:{SPAN_ID}:0:1")),
+            headers.get(JAEGER_HEADER)
+        );
+        let span_context = extract(
+            &propagator,
+            &[(JAEGER_HEADER, headers[JAEGER_HEADER].as_str())],
+        );
+        assert_eq!(context().span().span_context(), &span_context);
+    }
+
+    #[test]
+    fn jaeger_extracts_url_encoded_headers() {
+        let span_context = extract(
+            &JaegerPropagator::new(),
+            &[(JAEGER_HEADER, "8448eb211c80319c%3Ab7ad6b7169203331%3A0%3A0")],
+        );
+        assert!(span_context.is_valid());
+        assert!(!span_context.is_sampled());
+    }
+}
@@ -0,0 +1,367 @@
+//! Keeps recently completed traces in memory, so that they can be viewed live without an OTel
+//! collector, e.g. with `spin trace tail`.
+//!
+//! Host spans are recorded by a span processor of the host tracer provider, which tracks the spans
+//! still open in each trace; a trace is complete once none are. Guest spans are recorded with
+//! [`record_guest_span`] as guests end them.
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant, UNIX_EPOCH};
+
+use opentelemetry::{
+    Context,
+    trace::{Span as _, SpanId, Status, TraceId},
+};
+use opentelemetry_sdk::{
+    error::OTelSdkResult,
+    trace::{Span, SpanData, SpanProcessor},
+};
+use serde::{Deserialize, Serialize};
+
+/// How long a trace may have open spans before it is abandoned, e.g. because a span leaked.
+const OPEN_TRACE_TIMEOUT: Duration = Duration::from_secs(600);
+
+/// The most traces that may have open spans at once; beyond this, the oldest is abandoned.
+const MAX_OPEN_TRACES: usize = 10_000;
+
+static RECENT_TRACES: OnceLock<RecentTraces> = OnceLock::new();
+
+/// A recently completed trace.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct RecentTrace {
+    /// Orders traces by when they completed, or last gained a late guest span; see
+    /// [`traces_after`].
+    pub seq: u64,
+    /// The trace's ID, in hex.
+    pub trace_id: String,
+    /// The trace's spans, in the order they ended.
+    pub spans: Vec<RecentSpan>,
+}
+
+impl RecentTrace {
+    /// Whether any span of the trace ended with an error status.
+    pub fn has_error(&self) -> bool {
+        self.spans.iter().any(|span| span.error.is_some())
+    }
+
+    /// Whether any span of the trace was emitted by or on behalf of the given component.
+    pub fn involves_component(&self, component_id: &str) -> bool {
+        self.spans
+            .iter()
+            .any(|span| span.component_id.as_deref() == Some(component_id))
+    }
+}
+
+/// A span of a recently completed trace.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct RecentSpan {
+    /// The span's ID, in hex.
+    pub span_id: String,
+    /// The ID of the span's parent, in hex, if it has one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parent_span_id: Option<String>,
+    /// The name of the span.
+    pub name: String,
+    /// When the span started, in nanoseconds since the Unix epoch.
+    pub start_unix_nanos: u64,
+    /// How long the span lasted, in nanoseconds.
+    pub duration_nanos: u64,
+    /// The component the span was emitted by or on behalf of, if any.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub component_id: Option<String>,
+    /// Whether the span was emitted by a guest rather than by Spin.
+    #[serde(default)]
+    pub guest: bool,
+    /// The description of the span's error, if it ended with an error status.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl RecentSpan {
+    fn new(span: &SpanData, component_id: Option<String>, guest: bool) -> Self {
+        let parent_span_id = span.parent_span_id;
+        Self {
+            span_id: span.span_context.span_id().to_string(),
+            parent_span_id: (parent_span_id != SpanId::INVALID).then(|| parent_span_id.to_string()),
+            name: span.name.to_string(),
+            start_unix_nanos: span
+                .start_time
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_nanos() as u64)
+                .unwrap_or_default(),
+            duration_nanos: span
+                .end_time
+                .duration_since(span.start_time)
+                .map(|d| d.as_nanos() as u64)
+                .unwrap_or_default(),
+            component_id,
+            guest,
+            error: match &span.status {
+                Status::Error { description } => Some(description.to_string()),
+                _ => None,
+            },
+        }
+    }
+}
+
+/// Starts keeping up to `capacity` recently completed traces.
+pub(crate) fn init(capacity: usize) {
+    let _ = RECENT_TRACES.set(RecentTraces::new(capacity));
+}
+
+/// Whether recently completed traces are kept.
+pub fn enabled() -> bool {
+    RECENT_TRACES.get().is_some()
+}
+
+/// Returns the kept traces that completed after the one with the given sequence number, or all
+/// of them if none is given, oldest first. Returns `None` if traces are not kept.
+pub fn traces_after(seq: Option<u64>) -> Option<Vec<RecentTrace>> {
+    Some(RECENT_TRACES.get()?.traces_after(seq))
+}
+
+/// Records a span ended by a guest of the given component.
+pub fn record_guest_span(component_id: &str, span: &SpanData) {
+    if let Some(recent_traces) = RECENT_TRACES.get() {
+        recent_traces.record_guest_span(
+            span.span_context.trace_id(),
+            RecentSpan::new(span, Some(component_id.to_owned()), true),
+        );
+    }
+}
+
+struct RecentTraces {
+    capacity: usize,
+    max_open_traces: usize,
+    state: Mutex<State>,
+}
+
+#[derive(Default)]
+struct State {
+    next_seq: u64,
+    /// Traces with host spans still open.
+    open: HashMap<TraceId, OpenTrace>,
+    completed: VecDeque<(TraceId, RecentTrace)>,
+}
+
+struct OpenTrace {
+    open_spans: usize,
+    spans: Vec<RecentSpan>,
+    started: Instant,
+}
+
+impl RecentTraces {
+    fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            max_open_traces: MAX_OPEN_TRACES,
+            state: Default::default(),
+        }
+    }
+
+    fn start_host_span(&self, trace_id: TraceId) {
+        let mut state = self.state.lock().unwrap();
+        if state.open.len() >= self.capacity {
+            state
+                .open
+                .retain(|_, trace| trace.started.elapsed() < OPEN_TRACE_TIMEOUT);
+        }
+        if state.open.len() >= self.max_open_traces
+            && !state.open.contains_key(&trace_id)
+            && let Some(oldest) = state
+                .open
+                .iter()
+                .min_by_key(|(_, trace)| trace.started)
+                .map(|(id, _)| *id)
+        {
+            state.open.remove(&oldest);
+        }
+        state
+            .open
+            .entry(trace_id)
+            .or_insert_with(|| OpenTrace {
+                open_spans: 0,
+                spans: vec![],
+                started: Instant::now(),
+            })
+            .open_spans += 1;
+    }
+
+    fn end_host_span(&self, trace_id: TraceId, span: RecentSpan) {
+        let mut state = self.state.lock().unwrap();
+        let Some(trace) = state.open.get_mut(&trace_id) else {
+            return;
+        };
+        trace.spans.push(span);
+        trace.open_spans -= 1;
+        if trace.open_spans == 0 {
+            let trace = state.open.remove(&trace_id).unwrap();
+            self.complete(&mut state, trace_id, trace.spans);
+        }
+    }
+
+    fn record_guest_span(&self, trace_id: TraceId, span: RecentSpan) {
+        let mut state = self.state.lock().unwrap();
+        if let Some(trace) = state.open.get_mut(&trace_id) {
+            trace.spans.push(span);
+            return;
+        }
+        // The host spans of the trace have ended, or were never recorded. Either way the trace is
+        // completed (again) with a new sequence number, so that `spin trace tail` sees the span.
+        let mut spans = match state.completed.iter().rposition(|(id, _)| *id == trace_id) {
+            Some(index) => state.completed.remove(index).unwrap().1.spans,
+            None => vec![],
+        };
+        spans.push(span);
+        self.complete(&mut state, trace_id, spans);
+    }
+
+    fn complete(&self, state: &mut State, trace_id: TraceId, spans: Vec<RecentSpan>) {
+        let seq = state.next_seq;
+        state.next_seq += 1;
+        state.completed.push_back((
+            trace_id,
+            RecentTrace {
+                seq,
+                trace_id: trace_id.to_string(),
+                spans,
+            },
+        ));
+        while state.completed.len() > self.capacity {
+            state.completed.pop_front();
+        }
+    }
+
+    fn traces_after(&self, seq: Option<u64>) -> Vec<RecentTrace> {
+        let state = self.state.lock().unwrap();
+        state
+            .completed
+            .iter()
+            .map(|(_, trace)| trace)
+            .filter(|trace| seq.is_none_or(|seq| trace.seq > seq))
+            .cloned()
+            .collect()
+    }
+}
+
+/// Records the host spans of recently completed traces.
+#[derive(Debug)]
+pub(crate) struct RecentTracesProcessor;
+
+impl SpanProcessor for RecentTracesProcessor {
+    fn on_start(&self, span: &mut Span, _cx: &Context) {
+        if let Some(recent_traces) = RECENT_TRACES.get() {
+            recent_traces.start_host_span(span.span_context().trace_id());
+        }
+    }
+
+    fn on_end(&self, span: SpanData) {
+        let Some(recent_traces) = RECENT_TRACES.get() else {
+            return;
+        };
+        let component_id = span
+            .attributes
+            .iter()
+            .find(|kv| kv.key.as_str() == "spin.component.id")
+            .map(|kv| kv.value.as_str().into_owned());
+        recent_traces.end_host_span(
+            span.span_context.trace_id(),
+            RecentSpan::new(&span, component_id, false),
+        );
+    }
+
+    fn force_flush(&self) -> OTelSdkResult {
+        Ok(())
+    }
+
+    fn shutdown(&self) -> OTelSdkResult {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn trace_id(id: u128) -> TraceId {
+        TraceId::from_bytes(id.to_be_bytes())
+    }
+
+    fn span(name: &str) -> RecentSpan {
+        RecentSpan {
+            name: name.to_owned(),
+            ..Default::default()
+        }
+    }
+
+    fn names(trace: &RecentTrace) -> Vec<&str> {
+        trace.spans.iter().map(|span| span.name.as_str()).collect()
+    }
+
+    #[test]
+    fn completes_traces_once_host_spans_end() {
+        let traces = RecentTraces::new(10);
+        traces.start_host_span(trace_id(1));
+        traces.start_host_span(trace_id(1));
+        traces.record_guest_span(trace_id(1), span("guest"));
+        traces.end_host_span(trace_id(1), span("child"));
+        assert!(traces.traces_after(None).is_empty());
+
+        traces.end_host_span(trace_id(1), span("root"));
+        let completed = traces.traces_after(None);
+        assert_eq!(1, completed.len());
+        assert_eq!(vec!["guest", "child", "root"], names(&completed[0]));
+
+        // Guest spans arriving late join their completed trace, which is completed again.
+        traces.record_guest_span(trace_id(1), span("late"));
+        let completed = traces.traces_after(Some(completed[0].seq));
+        assert_eq!(1, completed.len());
+        assert_eq!(vec!["guest", "child", "root", "late"], names(&completed[0]));
+        assert_eq!(1, traces.traces_after(None).len());
+    }
+
+    #[test]
+    fn abandons_the_oldest_open_trace() {
+        let mut traces = RecentTraces::new(10);
+        traces.max_open_traces = 2;
+        for id in 1..=3 {
+            traces.start_host_span(trace_id(id));
+            // Keep the start times distinct.
+            std::thread::sleep(Duration::from_millis(1));
+        }
+        for id in 1..=3 {
+            traces.end_host_span(trace_id(id), span(&format!("trace {id}")));
+        }
+        let completed = traces.traces_after(None);
+        assert_eq!(
+            vec!["trace 2", "trace 3"],
+            completed
+                .iter()
+                .map(|trace| trace.spans[0].name.as_str())
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn keeps_the_most_recent_traces() {
+        let traces = RecentTraces::new(2);
+        for id in 1..=3 {
+            traces.start_host_span(trace_id(id));
+            traces.end_host_span(trace_id(id), span(&format!("trace {id}")));
+        }
+        let completed = traces.traces_after(None);
+        assert_eq!(
+            vec![1, 2],
+            completed.iter().map(|trace| trace.seq).collect::<Vec<_>>()
+        );
+        assert_eq!(
+            vec![2],
+            traces
+                .traces_after(Some(1))
+                .iter()
+                .map(|trace| trace.seq)
+                .collect::<Vec<_>>()
+        );
+    }
+}
@@ -0,0 +1,76 @@
+//! Scrubbing of secret variable values from host output.
+//!
+//! See [`spin_common::secrets`] for how values come to be known as secret.
+
+use std::io::{self, Write};
+
+use spin_common::secrets;
+use tracing_subscriber::fmt::MakeWriter;
+
+/// A [`MakeWriter`] whose writers redact secret values from what is written.
+pub(crate) struct RedactingMakeWriter<M>(pub(crate) M);
+
+impl<'a, M: MakeWriter<'a>> MakeWriter<'a> for RedactingMakeWriter<M> {
+    type Writer = RedactingWriter<M::Writer>;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        RedactingWriter(self.0.make_writer())
+    }
+}
+
+/// A writer that redacts secret values from what is written.
+///
+/// Each event is written by the fmt layer in a single call, so a secret is
+/// never split across writes.
+pub(crate) struct RedactingWriter<W>(W);
+
+impl<W: Write> Write for RedactingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if !secrets::any() {
+            return self.0.write(buf);
+        }
+        let text = String::from_utf8_lossy(buf);
+        self.0.write_all(secrets::redact(&text).as_bytes())?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.0.flush()
+    }
+}
+
+/// Installs a panic hook that redacts secret values from panic messages.
+///
+/// Until a secret has been registered, panics are reported by the previous hook
+/// as usual.
+pub(crate) fn install_panic_hook() {
+    let previous = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        if !secrets::any() {
+            return previous(info);
+        }
+        let thread = std::thread::current();
+        let name = thread.name().unwrap_or("<unnamed>");
+        eprintln!("\nthread '{name}' {}", secrets::redact(&info.to_string()));
+    }));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn redacts_written_text() {
+        secrets::register("telemetry-test-secret");
+        let mut writer = RedactingWriter(Vec::new());
+        write!(
+            writer,
+            "failed to connect to redis://:telemetry-test-secret@cache"
+        )
+        .unwrap();
+        assert_eq!(
+            "failed to connect to redis://:[REDACTED]@cache",
+            String::from_utf8(writer.0).unwrap()
+        );
+    }
+}
@@ -0,0 +1,255 @@
+//! Logs requests that take longer than a threshold, so that performance regressions surface
+//! without tracing being exported.
+
+use std::fmt::Debug;
+use std::time::{Duration, Instant};
+
+use tracing::{
+    Level, Subscriber,
+    field::{Field, Visit},
+    span::{Attributes, Id, Record},
+};
+use tracing_subscriber::{
+    Layer,
+    filter::filter_fn,
+    layer::Context,
+    registry::{LookupSpan, SpanRef},
+};
+
+/// The tracing target of slow request warnings.
+pub const SLOW_REQUEST_TARGET: &str = "spin_slow_request";
+
+/// Constructs a layer for the tracing subscriber that warns about requests which take at least
+/// `threshold`.
+///
+/// Requests are spans marked `otel.kind = "server"` or `"consumer"`, as the HTTP and Redis
+/// triggers do. Each warning breaks the request's duration down into time spent in host calls
+/// (spans marked `otel.kind = "client"`) and the rest, which is mostly time spent in the guest.
+pub(crate) fn slow_request_layer<S: Subscriber + for<'span> LookupSpan<'span>>(
+    threshold: Duration,
+) -> impl Layer<S> {
+    SlowRequestLayer { threshold }.with_filter(filter_fn(|metadata| {
+        metadata.is_span() && *metadata.level() <= Level::INFO
+    }))
+}
+
+struct SlowRequestLayer {
+    threshold: Duration,
+}
+
+/// The state of a request span.
+struct Request {
+    start: Instant,
+    name: String,
+    route: Option<String>,
+    component_id: Option<String>,
+    host_call_time: Duration,
+}
+
+/// The state of a host call span.
+struct HostCall {
+    start: Instant,
+}
+
+impl<S: Subscriber + for<'span> LookupSpan<'span>> Layer<S> for SlowRequestLayer {
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(id) else {
+            return;
+        };
+        let mut fields = SpanFields::default();
+        attrs.record(&mut fields);
+        match fields.kind.as_deref() {
+            Some("server" | "consumer") => span.extensions_mut().insert(Request {
+                start: Instant::now(),
+                name: fields
+                    .name
+                    .clone()
+                    .unwrap_or_else(|| span.name().to_owned()),
+                route: None,
+                component_id: None,
+                host_call_time: Duration::ZERO,
+            }),
+            Some("client") => span.extensions_mut().insert(HostCall {
+                start: Instant::now(),
+            }),
+            _ => {}
+        }
+        update_request(&span, fields);
+    }
+
+    fn on_record(&self, id: &Id, values: &Record<'_>, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(id) else {
+            return;
+        };
+        let mut fields = SpanFields::default();
+        values.record(&mut fields);
+        update_request(&span, fields);
+    }
+
+    fn on_close(&self, id: Id, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(&id) else {
+            return;
+        };
+
+        if let Some(call) = span.extensions_mut().remove::<HostCall>() {
+            // Attribute the call to its request, unless it was made within another host call,
+            // whose time already includes it.
+            for ancestor in span.scope().skip(1) {
+                let mut extensions = ancestor.extensions_mut();
+                if extensions.get_mut::<HostCall>().is_some() {
+                    break;
+                }
+                if let Some(request) = extensions.get_mut::<Request>() {
+                    request.host_call_time += call.start.elapsed();
+                    break;
+                }
+            }
+        }
+
+        let Some(request) = span.extensions_mut().remove::<Request>() else {
+            return;
+        };
+        let duration = request.start.elapsed();
+        if duration < self.threshold {
+            return;
+        }
+        let guest_time = duration.saturating_sub(request.host_call_time);
+        tracing::warn!(
+            target: SLOW_REQUEST_TARGET,
+            route = request.route.as_deref().unwrap_or(&request.name),
+            component_id = request.component_id.as_deref().unwrap_or("unknown"),
+            duration_ms = millis(duration),
+            guest_ms = millis(guest_time),
+            host_calls_ms = millis(request.host_call_time),
+            "Slow request: {} took {:.1}ms ({:.1}ms in host calls)",
+            request.route.as_deref().unwrap_or(&request.name),
+            millis(duration),
+            millis(request.host_call_time),
+        );
+    }
+}
+
+/// Records fields that describe a request on the closest request span, starting with the span
+/// itself.
+fn update_request<'a, S: LookupSpan<'a>>(span: &SpanRef<'a, S>, fields: SpanFields) {
+    if fields.route.is_none() && fields.name.is_none() && fields.component_id.is_none() {
+        return;
+    }
+    for span in span.scope() {
+        let mut extensions = span.extensions_mut();
+        let Some(request) = extensions.get_mut::<Request>() else {
+            continue;
+        };
+        if let Some(route) = fields.route {
+            request.route = Some(route);
+        }
+        if let Some(name) = fields.name {
+            request.name = name;
+        }
+        // A request may be handled by several components, e.g. a Redis message; report the
+        // first.
+        if request.component_id.is_none() {
+            request.component_id = fields.component_id;
+        }
+        return;
+    }
+}
+
+fn millis(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
+
+/// Collects the span fields that describe requests.
+#[derive(Default)]
+struct SpanFields {
+    kind: Option<String>,
+    name: Option<String>,
+    route: Option<String>,
+    component_id: Option<String>,
+}
+
+impl Visit for SpanFields {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        let value = Some(value.to_owned());
+        match field.name() {
+            "otel.kind" => self.kind = value,
+            "otel.name" => self.name = value,
+            "http.route" => self.route = value,
+            "spin.component.id" => self.component_id = value,
+            _ => {}
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+        if matches!(
+            field.name(),
+            "otel.kind" | "otel.name" | "http.route" | "spin.component.id"
+        ) {
+            self.record_str(field, &format!("{value:?}"));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use tracing::Event;
+    use tracing_subscriber::prelude::*;
+
+    use super::*;
+    use crate::json_format::JsonVisitor;
+
+    /// Collects the fields of slow request warnings.
+    #[derive(Clone, Default)]
+    struct Warnings(Arc<Mutex<Vec<serde_json::Map<String, serde_json::Value>>>>);
+
+    impl<S: Subscriber> Layer<S> for Warnings {
+        fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+            if event.metadata().target() == SLOW_REQUEST_TARGET {
+                let mut fields = serde_json::Map::new();
+                event.record(&mut JsonVisitor(&mut fields));
+                self.0.lock().unwrap().push(fields);
+            }
+        }
+    }
+
+    #[test]
+    fn warns_about_slow_requests_only() {
+        let warnings = Warnings::default();
+        let subscriber = tracing_subscriber::registry()
+            .with(slow_request_layer(Duration::from_millis(20)))
+            .with(warnings.clone());
+        tracing::subscriber::with_default(subscriber, || {
+            let fast = tracing::info_span!("request", otel.kind = "server");
+            drop(fast);
+
+            let slow = tracing::info_span!(
+                "request",
+                otel.kind = "server",
+                http.route = tracing::field::Empty,
+                spin.component.id = tracing::field::Empty,
+            );
+            let _slow = slow.enter();
+            slow.record("http.route", "/hello");
+            slow.record("spin.component.id", "hello");
+            {
+                let _call =
+                    tracing::info_span!("spin_key_value.get", otel.kind = "client").entered();
+                std::thread::sleep(Duration::from_millis(25));
+            }
+        });
+
+        let warnings = warnings.0.lock().unwrap();
+        let [warning] = &warnings[..] else {
+            panic!("expected one warning, got {warnings:?}");
+        };
+        assert_eq!("/hello", warning["route"]);
+        assert_eq!("hello", warning["component_id"]);
+        let duration = warning["duration_ms"].as_f64().unwrap();
+        let host_calls = warning["host_calls_ms"].as_f64().unwrap();
+        let guest = warning["guest_ms"].as_f64().unwrap();
+        assert!(host_calls >= 25.0);
+        assert!((duration - host_calls - guest).abs() < 0.001);
+    }
+}
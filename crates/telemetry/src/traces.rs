@@ -1,18 +1,17 @@
-use anyhow::bail;
 use opentelemetry::{global, trace::TracerProvider};
-use opentelemetry_sdk::{
-    Resource,
-    resource::{EnvResourceDetector, ResourceDetector, TelemetryResourceDetector},
-    runtime::Tokio,
-};
+use opentelemetry_sdk::runtime::Tokio;
 use tracing::Subscriber;
 use tracing_opentelemetry::OpenTelemetrySpanExt as _;
-use tracing_subscriber::{EnvFilter, Layer, registry::LookupSpan};
+use tracing_subscriber::{EnvFilter, Layer, filter::FilterExt as _, registry::LookupSpan};
 
-use crate::detector::SpinResourceDetector;
-use crate::env::OtlpProtocol;
+use crate::detector::detected_resource;
+use crate::env::otel_tracing_enabled;
+use crate::otlp::OtlpConnection;
+use crate::policy::{self, RedactingSpanProcessor};
+use crate::recent_traces::{self, RecentTracesProcessor};
 
-/// Constructs a layer for the tracing subscriber that sends spans to an OTEL collector.
+/// Constructs a layer for the tracing subscriber that sends spans to an OTEL collector, if one is
+/// configured, and records recently completed traces, if enabled.
 ///
 /// It pulls OTEL configuration from the environment based on the variables defined
 /// [here](https://opentelemetry.io/docs/specs/otel/protocol/exporter/) and
@@ -20,39 +19,29 @@ use crate::env::OtlpProtocol;
 pub(crate) fn otel_tracing_layer<S: Subscriber + for<'span> LookupSpan<'span>>(
     spin_version: String,
 ) -> anyhow::Result<impl Layer<S>> {
-    let resource = Resource::builder()
-        .with_detectors(&[
-            // Set service.name from env OTEL_SERVICE_NAME > env OTEL_RESOURCE_ATTRIBUTES > spin
-            // Set service.version from Spin metadata
-            Box::new(SpinResourceDetector::new(spin_version)) as Box<dyn ResourceDetector>,
-            // Sets fields from env OTEL_RESOURCE_ATTRIBUTES
-            Box::new(EnvResourceDetector::new()),
-            // Sets telemetry.sdk{name, language, version}
-            Box::new(TelemetryResourceDetector),
-        ])
-        .build();
+    let resource = detected_resource(spin_version, []);
 
-    // This will configure the exporter based on the OTEL_EXPORTER_* environment variables.
-    let exporter = match OtlpProtocol::traces_protocol_from_env() {
-        OtlpProtocol::Grpc => opentelemetry_otlp::SpanExporter::builder()
-            .with_tonic()
-            .build()?,
-        OtlpProtocol::HttpProtobuf => opentelemetry_otlp::SpanExporter::builder()
-            .with_http()
-            .build()?,
-        OtlpProtocol::HttpJson => bail!("http/json OTLP protocol is not supported"),
-    };
+    let mut tracer_provider =
+        opentelemetry_sdk::trace::SdkTracerProvider::builder().with_resource(resource);
+
+    if otel_tracing_enabled() {
+        // This will configure the exporter based on the OTEL_EXPORTER_* environment variables.
+        let exporter = OtlpConnection::from_env().span_exporter()?;
 
-    let span_processor =
-        opentelemetry_sdk::trace::span_processor_with_async_runtime::BatchSpanProcessor::builder(
-            exporter, Tokio,
-        )
-        .build();
+        let span_processor =
+            opentelemetry_sdk::trace::span_processor_with_async_runtime::BatchSpanProcessor::builder(
+                exporter, Tokio,
+            )
+            .build();
+        tracer_provider =
+            tracer_provider.with_span_processor(RedactingSpanProcessor(span_processor));
+    }
+
+    if recent_traces::enabled() {
+        tracer_provider = tracer_provider.with_span_processor(RecentTracesProcessor);
+    }
 
-    let tracer_provider = opentelemetry_sdk::trace::SdkTracerProvider::builder()
-        .with_resource(resource)
-        .with_span_processor(span_processor)
-        .build();
+    let tracer_provider = tracer_provider.build();
 
     global::set_tracer_provider(tracer_provider.clone());
 
@@ -65,7 +54,7 @@ pub(crate) fn otel_tracing_layer<S: Subscriber + for<'span> LookupSpan<'span>>(
     Ok(tracing_opentelemetry::layer()
         .with_tracer(tracer_provider.tracer("spin"))
         .with_threads(false)
-        .with_filter(env_filter))
+        .with_filter(env_filter.and(policy::component_filter())))
 }
 
 /// Indicate whether the error is more likely caused by the guest or the host.
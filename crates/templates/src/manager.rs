@@ -3,11 +3,14 @@ use std::{io::IsTerminal, path::Path};
 use anyhow::Context;
 
 use crate::{
-    source::TemplateSource,
+    source::{TEMPLATE_SOURCE_DIR, TemplateSource},
     store::{TemplateLayout, TemplateStore},
     template::Template,
 };
 
+/// The name of the index file written by [`TemplateManager::export`].
+const EXPORT_INDEX_FILE_NAME: &str = "templates.json";
+
 /// Provides access to and operations on the set of installed
 /// templates.
 pub struct TemplateManager {
@@ -268,6 +271,73 @@ impl TemplateManager {
             .map(|l| Template::load_from(&l))
             .transpose()
     }
+
+    /// Copies all installed templates into a `templates` directory under
+    /// `dest`, so that `dest` can be used as a [`TemplateSource::File`]
+    /// where the original sources are not reachable, and writes an index
+    /// of the copied templates to `dest/templates.json`.
+    pub async fn export(&self, dest: &Path) -> anyhow::Result<ListResults> {
+        let list_results = self.list().await?;
+
+        let templates_dir = dest.join(TEMPLATE_SOURCE_DIR);
+        tokio::fs::create_dir_all(&templates_dir)
+            .await
+            .with_context(|| format!("Failed to create directory {}", templates_dir.display()))?;
+
+        let mut index = vec![];
+        for template in &list_results.templates {
+            let id = template.id();
+            let source_dir = self.store.get_directory(id);
+            let dir_name = source_dir
+                .file_name()
+                .with_context(|| format!("Invalid template directory for {id}"))?;
+            let dest_dir = templates_dir.join(dir_name);
+            if dest_dir.exists() {
+                tokio::fs::remove_dir_all(&dest_dir)
+                    .await
+                    .with_context(|| format!("Failed to replace {}", dest_dir.display()))?;
+            }
+            tokio::fs::create_dir_all(&dest_dir)
+                .await
+                .with_context(|| format!("Failed to create directory {}", dest_dir.display()))?;
+            fs_extra::dir::copy(&source_dir, &dest_dir, &copy_content()).with_context(|| {
+                format!("Failed to copy template {} to {}", id, dest_dir.display())
+            })?;
+            // The install record describes where the template was installed from,
+            // which is rewritten when the template is installed from the export.
+            let _ =
+                tokio::fs::remove_file(TemplateLayout::new(&dest_dir).installation_record_file())
+                    .await;
+
+            let mut tags: Vec<_> = template.tags().iter().cloned().collect();
+            tags.sort();
+            index.push(ExportedTemplate {
+                id: id.to_owned(),
+                description: template.description().clone(),
+                tags,
+                installed_from: template.installed_from_or_empty().to_owned(),
+                directory: format!("{TEMPLATE_SOURCE_DIR}/{}", dir_name.to_string_lossy()),
+            });
+        }
+
+        let index_path = dest.join(EXPORT_INDEX_FILE_NAME);
+        let index_text = serde_json::to_string_pretty(&index)?;
+        tokio::fs::write(&index_path, index_text)
+            .await
+            .with_context(|| format!("Failed to write {}", index_path.display()))?;
+
+        Ok(list_results)
+    }
+}
+
+/// An entry in the index written by [`TemplateManager::export`].
+#[derive(Debug, serde::Serialize)]
+struct ExportedTemplate {
+    id: String,
+    description: Option<String>,
+    tags: Vec<String>,
+    installed_from: String,
+    directory: String,
 }
 
 async fn copy_template_over_existing(
@@ -581,6 +651,32 @@ mod tests {
         assert_eq!(0, manager.list().await.unwrap().warnings.len());
     }
 
+    #[tokio::test]
+    async fn can_install_exported_templates() {
+        let manager = TempManager::new_with_this_repo_templates().await;
+        let export_dir = tempdir().unwrap();
+
+        let exported = manager.export(export_dir.path()).await.unwrap();
+        assert_eq!(TPLS_IN_THIS, exported.templates.len());
+
+        let index: Vec<serde_json::Value> = serde_json::from_slice(
+            &fs::read(export_dir.path().join(EXPORT_INDEX_FILE_NAME)).unwrap(),
+        )
+        .unwrap();
+        assert_eq!(TPLS_IN_THIS, index.len());
+
+        let offline_manager = TempManager::new();
+        let source = TemplateSource::File(export_dir.path().to_owned());
+        let install_result = offline_manager
+            .install(&source, &InstallOptions::default(), &DiscardingReporter)
+            .await
+            .unwrap();
+        assert_eq!(TPLS_IN_THIS, install_result.installed.len());
+        assert_eq!(0, install_result.skipped.len());
+        let installed_from = install_result.installed[0].installed_from_or_empty();
+        assert_eq!(format!("{}", export_dir.path().display()), installed_from);
+    }
+
     #[tokio::test]
     async fn skips_bad_templates() {
         let manager = TempManager::new();
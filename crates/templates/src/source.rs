@@ -7,7 +7,7 @@ use url::Url;
 
 use crate::{directory::subdirectories, git::UnderstandGitResult};
 
-const TEMPLATE_SOURCE_DIR: &str = "templates";
+pub(crate) const TEMPLATE_SOURCE_DIR: &str = "templates";
 const TEMPLATE_VERSION_TAG_PREFIX: &str = "spin/templates/v";
 
 /// A source from which to install templates.
@@ -0,0 +1,439 @@
+//! A trigger which serves actors called through `spin:actors/client`.
+//!
+//! Each actor type is served by one component, which exports
+//! `spin:actors/actor`:
+//!
+//! ```toml
+//! [[trigger.actor]]
+//! component = "cart"
+//! actor_type = "cart"
+//! idle_timeout = "600s"
+//! ```
+//!
+//! The trigger routes every call to an actor, addressed by its type and ID,
+//! to the same instance of the component, which it keeps warm between calls
+//! and sends calls one at a time. An instance is evicted once it has been
+//! idle for the type's idle timeout, or when it traps; the actor's next call
+//! gets a new instance, which can load the actor's state through
+//! `spin:actors/state`.
+
+use std::{
+    collections::HashMap,
+    convert::Infallible,
+    net::{SocketAddr, ToSocketAddrs},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use anyhow::{Context as _, anyhow, bail, ensure};
+use clap::Args;
+use http::{HeaderMap, Method, Request, Response, StatusCode};
+use http_body_util::{BodyExt, Full, Limited};
+use hyper::body::{Bytes, Incoming};
+use hyper_util::rt::TokioIo;
+use serde::Deserialize;
+use spin_common::arg_parser::parse_duration;
+use spin_factor_actors::ActorsFactor;
+use spin_factor_actors::protocol::{ACTOR_FAILED, NO_SUCH_ACTOR_TYPE, parse_actor_path};
+use spin_factors::RuntimeFactors;
+use spin_trigger::{App, Store, Trigger, TriggerApp};
+use spin_world::exports::spin::actors::actor;
+use tokio::net::TcpListener;
+use tracing::{Level, instrument};
+
+/// The largest message accepted.
+const MAX_MESSAGE_BYTES: usize = 16 << 20;
+/// How long an actor is kept warm after its last call, unless its trigger
+/// says otherwise.
+const DEFAULT_IDLE_TIMEOUT: Duration = Duration::from_secs(5 * 60);
+/// How often idle actors are evicted.
+const EVICTION_INTERVAL: Duration = Duration::from_secs(10);
+
+pub struct ActorTrigger {
+    listen_addr: SocketAddr,
+}
+
+#[derive(Args)]
+pub struct CliArgs {
+    /// IP address and port to serve actors on
+    #[clap(
+        long = "actor-listen",
+        env = "SPIN_ACTOR_LISTEN_ADDR",
+        default_value = spin_factor_actors::DEFAULT_ADDRESS,
+        value_parser = parse_listen_addr
+    )]
+    pub address: SocketAddr,
+}
+
+fn parse_listen_addr(addr: &str) -> anyhow::Result<SocketAddr> {
+    addr.to_socket_addrs()?
+        .next()
+        .with_context(|| format!("could not resolve {addr:?}"))
+}
+
+/// Actor trigger configuration.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TriggerConfig {
+    /// Component ID to invoke
+    component: String,
+    /// Actor type the component serves
+    actor_type: String,
+    /// How long to keep an actor warm after its last call
+    idle_timeout: Option<String>,
+}
+
+/// The component serving an actor type.
+#[derive(Debug)]
+struct ActorType {
+    component: String,
+    idle_timeout: Duration,
+}
+
+impl<F: RuntimeFactors> Trigger<F> for ActorTrigger {
+    const TYPE: &'static str = "actor";
+
+    type CliArgs = CliArgs;
+
+    type InstanceState = ();
+
+    fn new(cli_args: Self::CliArgs, _app: &App) -> anyhow::Result<Self> {
+        Ok(Self {
+            listen_addr: cli_args.address,
+        })
+    }
+
+    async fn run(self, trigger_app: TriggerApp<Self, F>) -> anyhow::Result<()> {
+        let states = trigger_app
+            .configured_app()
+            .app_state::<ActorsFactor>()
+            .context("ActorTrigger depends on ActorsFactor")?
+            .states();
+        ensure!(
+            states.is_defined(),
+            "the key-value store {:?} for actors is not defined",
+            states.label()
+        );
+        let types = actor_types(
+            trigger_app
+                .app()
+                .trigger_configs::<TriggerConfig>(<Self as Trigger<F>>::TYPE)?
+                .into_iter()
+                .map(|(_, config)| config),
+        )?;
+
+        let listener = TcpListener::bind(self.listen_addr)
+            .await
+            .with_context(|| format!("Unable to listen on {}", self.listen_addr))?;
+        println!("Serving actors at http://{}", listener.local_addr()?);
+        let mut names = types.keys().collect::<Vec<_>>();
+        names.sort();
+        for name in names {
+            println!("  {name}: {}", types[name].component);
+        }
+
+        let host = Arc::new(ActorHost {
+            trigger_app,
+            types,
+            actors: Default::default(),
+        });
+        let evictor = host.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(EVICTION_INTERVAL);
+            loop {
+                interval.tick().await;
+                evictor.evict_idle();
+            }
+        });
+        loop {
+            let (stream, client_addr) = listener.accept().await?;
+            let host = host.clone();
+            tokio::spawn(async move {
+                let service = hyper::service::service_fn(move |req| {
+                    let host = host.clone();
+                    async move { Ok::<_, Infallible>(host.handle(req).await) }
+                });
+                if let Err(err) = hyper::server::conn::http1::Builder::new()
+                    .serve_connection(TokioIo::new(stream), service)
+                    .await
+                {
+                    tracing::warn!("Error serving actor connection from {client_addr}: {err:?}");
+                }
+            });
+        }
+    }
+}
+
+/// Maps actor types to the components serving them.
+fn actor_types(
+    configs: impl IntoIterator<Item = TriggerConfig>,
+) -> anyhow::Result<HashMap<String, ActorType>> {
+    let mut types = HashMap::<String, ActorType>::new();
+    for config in configs {
+        if let Some(existing) = types.get(&config.actor_type) {
+            bail!(
+                "actor type {:?} is served by both {:?} and {:?}; an actor type may only have one component",
+                config.actor_type,
+                existing.component,
+                config.component
+            );
+        }
+        let idle_timeout = match &config.idle_timeout {
+            Some(timeout) => parse_duration(timeout).with_context(|| {
+                format!(
+                    "invalid idle timeout for actor trigger {:?}",
+                    config.component
+                )
+            })?,
+            None => DEFAULT_IDLE_TIMEOUT,
+        };
+        ensure!(
+            !idle_timeout.is_zero(),
+            "idle timeout for actor trigger {:?} must be greater than zero",
+            config.component
+        );
+        let actor_type = ActorType {
+            component: config.component,
+            idle_timeout,
+        };
+        types.insert(config.actor_type, actor_type);
+    }
+    Ok(types)
+}
+
+/// An actor, and its instance if it is warm.
+///
+/// Calls to the actor hold the lock while they run, so they run one at a
+/// time.
+type Actor<F> = tokio::sync::Mutex<Slot<F>>;
+
+struct Slot<F: RuntimeFactors> {
+    instance: Option<(Store<ActorTrigger, F>, actor::Guest)>,
+    last_used: Instant,
+    /// Set when the actor is evicted, so calls which were waiting for it look
+    /// it up again rather than running alongside its replacement.
+    evicted: bool,
+}
+
+/// Why a call to an actor failed.
+enum CallError {
+    NoSuchActorType,
+    /// The actor returned or trapped with an error.
+    Failed(String),
+    /// The actor could not be instantiated.
+    Internal(anyhow::Error),
+}
+
+/// Serves calls to the actors of an app.
+struct ActorHost<F: RuntimeFactors> {
+    trigger_app: TriggerApp<ActorTrigger, F>,
+    types: HashMap<String, ActorType>,
+    /// Maps actor types and IDs to the actors which have been called.
+    actors: Mutex<HashMap<(String, String), Arc<Actor<F>>>>,
+}
+
+impl<F: RuntimeFactors> ActorHost<F> {
+    async fn handle(&self, req: Request<Incoming>) -> Response<Full<Bytes>> {
+        if req.method() != Method::POST {
+            return text_response(StatusCode::METHOD_NOT_ALLOWED, "calls must be POST");
+        }
+        let Some((actor_type, id)) = parse_actor_path(req.uri().path()) else {
+            return text_response(StatusCode::BAD_REQUEST, "not an actor path");
+        };
+        let headers = req.headers().clone();
+        let message = match Limited::new(req.into_body(), MAX_MESSAGE_BYTES)
+            .collect()
+            .await
+        {
+            Ok(body) => body.to_bytes(),
+            Err(err) => return text_response(StatusCode::BAD_REQUEST, &err.to_string()),
+        };
+        match self.call(&actor_type, &id, &headers, &message).await {
+            Ok(reply) => {
+                let mut response = Response::new(Full::new(reply.into()));
+                *response.status_mut() = StatusCode::OK;
+                response
+            }
+            Err(CallError::NoSuchActorType) => {
+                text_response(NO_SUCH_ACTOR_TYPE, "no component serves the actor type")
+            }
+            Err(CallError::Failed(error)) => text_response(ACTOR_FAILED, &error),
+            Err(CallError::Internal(err)) => {
+                tracing::error!("Error calling actor {actor_type}/{id}: {err:#}");
+                text_response(StatusCode::INTERNAL_SERVER_ERROR, &format!("{err:#}"))
+            }
+        }
+    }
+
+    /// Sends a message to an actor, once it has finished any earlier calls.
+    async fn call(
+        &self,
+        actor_type: &str,
+        id: &str,
+        headers: &HeaderMap,
+        message: &[u8],
+    ) -> Result<Vec<u8>, CallError> {
+        let component = &self
+            .types
+            .get(actor_type)
+            .ok_or(CallError::NoSuchActorType)?
+            .component;
+        loop {
+            let actor = self
+                .actors
+                .lock()
+                .unwrap()
+                .entry((actor_type.to_owned(), id.to_owned()))
+                .or_insert_with(|| {
+                    Arc::new(tokio::sync::Mutex::new(Slot {
+                        instance: None,
+                        last_used: Instant::now(),
+                        evicted: false,
+                    }))
+                })
+                .clone();
+            let mut slot = actor.lock().await;
+            if slot.evicted {
+                continue;
+            }
+            return self
+                .dispatch(&mut slot, component, actor_type, id, headers, message)
+                .await;
+        }
+    }
+
+    #[instrument(name = "spin_trigger_actor.execute_wasm", skip_all, err(level = Level::INFO), fields(
+        otel.name = format!("execute_wasm_component {component_id}"),
+        spin.component.id = component_id,
+        spin.actor.type = actor_type,
+        spin.actor.id = id,
+    ))]
+    async fn dispatch(
+        &self,
+        slot: &mut Slot<F>,
+        component_id: &str,
+        actor_type: &str,
+        id: &str,
+        headers: &HeaderMap,
+        message: &[u8],
+    ) -> Result<Vec<u8>, CallError> {
+        // Continue the trace of the component which called the actor.
+        spin_telemetry::extract_message_trace_context(
+            headers
+                .iter()
+                .map(|(name, value)| (name.as_str(), value.as_bytes())),
+        );
+        spin_telemetry::metrics::monotonic_counter!(
+            spin.request_count = 1,
+            trigger_type = "actor",
+            app_id = self.trigger_app.app().id(),
+            component_id = component_id
+        );
+
+        let (store, guest) = match &mut slot.instance {
+            Some(instance) => instance,
+            None => {
+                let instance = self
+                    .instantiate(component_id, actor_type, id)
+                    .await
+                    .map_err(CallError::Internal)?;
+                slot.instance.insert(instance)
+            }
+        };
+        let result = guest.call_handle(&mut *store, id, message).await;
+        slot.last_used = Instant::now();
+        match result {
+            Ok(Ok(reply)) => Ok(reply),
+            Ok(Err(error)) => Err(CallError::Failed(error)),
+            Err(trap) => {
+                // The instance may have been left in a bad state.
+                slot.instance = None;
+                Err(CallError::Failed(format!("{trap:#}")))
+            }
+        }
+    }
+
+    /// Creates a warm instance of an actor.
+    async fn instantiate(
+        &self,
+        component_id: &str,
+        actor_type: &str,
+        id: &str,
+    ) -> anyhow::Result<(Store<ActorTrigger, F>, actor::Guest)> {
+        let mut builder = self.trigger_app.prepare(component_id)?;
+        builder
+            .factor_builder::<ActorsFactor>()
+            .context("ActorTrigger depends on ActorsFactor")?
+            .serve_actor(actor_type, id);
+        let (instance, mut store) = builder.instantiate(()).await?;
+        let pre = instance.instance_pre(&store);
+        let guest = actor::GuestIndices::new(&pre)
+            .context("component does not export spin:actors/actor")?
+            .load(&mut store, &instance)?;
+        Ok((store, guest))
+    }
+
+    /// Evicts the actors which have been idle for longer than their type's
+    /// idle timeout.
+    fn evict_idle(&self) {
+        self.actors
+            .lock()
+            .unwrap()
+            .retain(|(actor_type, _), actor| {
+                // An actor which is being called is not idle.
+                let Ok(mut slot) = actor.try_lock() else {
+                    return true;
+                };
+                let idle_timeout = self.types[actor_type].idle_timeout;
+                if slot.last_used.elapsed() < idle_timeout {
+                    return true;
+                }
+                slot.evicted = true;
+                slot.instance = None;
+                false
+            });
+    }
+}
+
+fn text_response(status: StatusCode, text: &str) -> Response<Full<Bytes>> {
+    let mut response = Response::new(Full::new(Bytes::from(text.to_owned())));
+    *response.status_mut() = status;
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(component: &str, actor_type: &str) -> TriggerConfig {
+        TriggerConfig {
+            component: component.into(),
+            actor_type: actor_type.into(),
+            idle_timeout: None,
+        }
+    }
+
+    #[test]
+    fn actor_types_have_one_component() {
+        let types = actor_types([
+            config("cart", "cart"),
+            TriggerConfig {
+                idle_timeout: Some("30s".into()),
+                ..config("thermostat", "device")
+            },
+        ])
+        .unwrap();
+        assert_eq!(DEFAULT_IDLE_TIMEOUT, types["cart"].idle_timeout);
+        assert_eq!("thermostat", types["device"].component);
+        assert_eq!(Duration::from_secs(30), types["device"].idle_timeout);
+
+        let err = actor_types([config("a", "cart"), config("b", "cart")]).unwrap_err();
+        assert!(err.to_string().contains("may only have one component"));
+        let err = actor_types([TriggerConfig {
+            idle_timeout: Some("0s".into()),
+            ..config("cart", "cart")
+        }])
+        .unwrap_err();
+        assert!(err.to_string().contains("must be greater than zero"));
+    }
+}
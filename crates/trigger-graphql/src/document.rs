@@ -0,0 +1,528 @@
+//! Analysis of parsed GraphQL documents: choosing the operation to run,
+//! measuring its depth and complexity, and splitting it into root fields.
+
+use std::collections::HashMap;
+
+use anyhow::{Context as _, bail};
+use graphql_parser::query::{
+    Definition, Directive, Document, Field, FragmentDefinition, OperationDefinition, Selection,
+    SelectionSet, Value,
+};
+use serde_json::{Map, Value as Json};
+
+/// Arguments which, given as an integer, multiply the complexity of a field's
+/// selections, since they typically bound the length of a list.
+const LIST_SIZE_ARGUMENTS: &[&str] = &["first", "last", "limit"];
+
+/// The most selections analysing an operation visits, counting those of a
+/// fragment each time it is spread, so a small document can't make it do
+/// unbounded work.
+const MAX_VISITED_SELECTIONS: usize = 10_000;
+
+/// The deepest nesting of selection sets, including fragments, analysing an
+/// operation follows.
+const MAX_NESTING: usize = 128;
+
+/// The kind of a GraphQL operation.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OperationKind {
+    Query,
+    Mutation,
+    Subscription,
+}
+
+impl OperationKind {
+    /// The name of the operation's root type.
+    pub fn type_name(self) -> &'static str {
+        match self {
+            Self::Query => "Query",
+            Self::Mutation => "Mutation",
+            Self::Subscription => "Subscription",
+        }
+    }
+}
+
+/// The operation of a document to run, with the fragments it may use.
+pub struct Operation<'d, 'a> {
+    pub kind: OperationKind,
+    selection_set: &'d SelectionSet<'a, &'a str>,
+    fragments: HashMap<&'a str, &'d FragmentDefinition<'a, &'a str>>,
+    variables: &'d Map<String, Json>,
+}
+
+impl<'d, 'a> Operation<'d, 'a> {
+    /// Chooses the operation named `name` from `document`, or its only
+    /// operation if no name is given.
+    pub fn select(
+        document: &'d Document<'a, &'a str>,
+        name: Option<&str>,
+        variables: &'d Map<String, Json>,
+    ) -> anyhow::Result<Self> {
+        let mut operations = vec![];
+        let mut fragments = HashMap::new();
+        for definition in &document.definitions {
+            match definition {
+                Definition::Operation(operation) => operations.push(operation),
+                Definition::Fragment(fragment) => {
+                    fragments.insert(fragment.name, fragment);
+                }
+            }
+        }
+
+        let operation = match name {
+            Some(name) => operations
+                .into_iter()
+                .find(|operation| operation_name(operation) == Some(name))
+                .with_context(|| format!("no operation named {name:?}"))?,
+            None => match operations[..] {
+                [operation] => operation,
+                [] => bail!("the document has no operations"),
+                _ => bail!("the document has several operations, so operationName is required"),
+            },
+        };
+        let (kind, selection_set) = match operation {
+            OperationDefinition::SelectionSet(set) => (OperationKind::Query, set),
+            OperationDefinition::Query(query) => (OperationKind::Query, &query.selection_set),
+            OperationDefinition::Mutation(mutation) => {
+                (OperationKind::Mutation, &mutation.selection_set)
+            }
+            OperationDefinition::Subscription(subscription) => {
+                (OperationKind::Subscription, &subscription.selection_set)
+            }
+        };
+        Ok(Self {
+            kind,
+            selection_set,
+            fragments,
+            variables,
+        })
+    }
+
+    /// Checks that the deepest nesting of fields in the operation is at most
+    /// `max_depth`.
+    pub fn check_depth(&self, max_depth: usize) -> anyhow::Result<()> {
+        self.set_depth(self.selection_set, 0, max_depth, &mut Walk::default())?;
+        Ok(())
+    }
+
+    /// The depth of `set`, which is nested in `level` fields. Gives up as soon
+    /// as a field is nested deeper than `max_depth`.
+    fn set_depth(
+        &self,
+        set: &SelectionSet<'a, &'a str>,
+        level: usize,
+        max_depth: usize,
+        walk: &mut Walk<'a, usize>,
+    ) -> anyhow::Result<usize> {
+        walk.enter()?;
+        let mut depth = 0;
+        for selection in &set.items {
+            walk.visit()?;
+            let selection_depth = match selection {
+                Selection::Field(field) => {
+                    check_depth(level + 1, max_depth)?;
+                    1 + self.set_depth(&field.selection_set, level + 1, max_depth, walk)?
+                }
+                Selection::InlineFragment(inline) => {
+                    self.set_depth(&inline.selection_set, level, max_depth, walk)?
+                }
+                Selection::FragmentSpread(spread) => {
+                    let depth = match walk.memo.get(spread.fragment_name) {
+                        Some(depth) => *depth,
+                        None => {
+                            let fragment = self.enter_fragment(spread.fragment_name, walk)?;
+                            let depth =
+                                self.set_depth(&fragment.selection_set, level, max_depth, walk)?;
+                            walk.leave_fragment(depth);
+                            depth
+                        }
+                    };
+                    check_depth(level + depth, max_depth)?;
+                    depth
+                }
+            };
+            depth = depth.max(selection_depth);
+        }
+        walk.leave();
+        Ok(depth)
+    }
+
+    /// Checks that the number of fields the operation selects, with the
+    /// selections of fields given a `first`, `last` or `limit` argument
+    /// counted that many times, is at most `max_complexity`.
+    pub fn check_complexity(&self, max_complexity: u64) -> anyhow::Result<()> {
+        let mut spent = 0;
+        self.set_complexity(
+            self.selection_set,
+            1,
+            &mut spent,
+            max_complexity,
+            &mut Walk::default(),
+        )?;
+        Ok(())
+    }
+
+    /// The complexity of `set`, whose fields each add `multiplier` to the
+    /// complexity spent on the operation so far. Gives up as soon as that
+    /// passes the maximum.
+    fn set_complexity(
+        &self,
+        set: &SelectionSet<'a, &'a str>,
+        multiplier: u64,
+        spent: &mut u64,
+        max_complexity: u64,
+        walk: &mut Walk<'a, u64>,
+    ) -> anyhow::Result<u64> {
+        walk.enter()?;
+        let mut complexity = 0u64;
+        for selection in &set.items {
+            walk.visit()?;
+            let selection_complexity = match selection {
+                Selection::Field(field) => {
+                    spend(spent, multiplier, max_complexity)?;
+                    let list_size = self.list_size(field);
+                    let children = self.set_complexity(
+                        &field.selection_set,
+                        multiplier.saturating_mul(list_size),
+                        spent,
+                        max_complexity,
+                        walk,
+                    )?;
+                    children.saturating_mul(list_size).saturating_add(1)
+                }
+                Selection::InlineFragment(inline) => self.set_complexity(
+                    &inline.selection_set,
+                    multiplier,
+                    spent,
+                    max_complexity,
+                    walk,
+                )?,
+                Selection::FragmentSpread(spread) => match walk.memo.get(spread.fragment_name) {
+                    Some(complexity) => {
+                        let complexity = *complexity;
+                        spend(spent, complexity.saturating_mul(multiplier), max_complexity)?;
+                        complexity
+                    }
+                    None => {
+                        let fragment = self.enter_fragment(spread.fragment_name, walk)?;
+                        let complexity = self.set_complexity(
+                            &fragment.selection_set,
+                            multiplier,
+                            spent,
+                            max_complexity,
+                            walk,
+                        )?;
+                        walk.leave_fragment(complexity);
+                        complexity
+                    }
+                },
+            };
+            complexity = complexity.saturating_add(selection_complexity);
+        }
+        walk.leave();
+        Ok(complexity)
+    }
+
+    fn list_size(&self, field: &Field<'a, &'a str>) -> u64 {
+        field
+            .arguments
+            .iter()
+            .filter(|(name, _)| LIST_SIZE_ARGUMENTS.contains(name))
+            .filter_map(|(_, value)| self.to_json(value).as_u64())
+            .max()
+            .unwrap_or(1)
+    }
+
+    fn enter_fragment<T>(
+        &self,
+        name: &'a str,
+        walk: &mut Walk<'a, T>,
+    ) -> anyhow::Result<&'d FragmentDefinition<'a, &'a str>> {
+        if walk.spreading.contains(&name) {
+            bail!("fragment {name:?} spreads itself");
+        }
+        let fragment = self
+            .fragments
+            .get(name)
+            .with_context(|| format!("unknown fragment {name:?}"))?;
+        walk.spreading.push(name);
+        Ok(fragment)
+    }
+
+    /// The fields the operation selects on its root type, with root fragments
+    /// spread and fields excluded by `@skip` or `@include` left out.
+    pub fn root_fields(&self) -> anyhow::Result<Vec<&'d Field<'a, &'a str>>> {
+        let mut fields = vec![];
+        self.collect_fields(self.selection_set, &mut fields, &mut Walk::default())?;
+        Ok(fields)
+    }
+
+    fn collect_fields(
+        &self,
+        set: &'d SelectionSet<'a, &'a str>,
+        fields: &mut Vec<&'d Field<'a, &'a str>>,
+        walk: &mut Walk<'a, ()>,
+    ) -> anyhow::Result<()> {
+        walk.enter()?;
+        for selection in &set.items {
+            walk.visit()?;
+            match selection {
+                Selection::Field(field) => {
+                    if self.included(&field.directives) {
+                        fields.push(field);
+                    }
+                }
+                Selection::InlineFragment(inline) => {
+                    if self.included(&inline.directives) {
+                        self.collect_fields(&inline.selection_set, fields, walk)?;
+                    }
+                }
+                Selection::FragmentSpread(spread) => {
+                    if self.included(&spread.directives) {
+                        let fragment = self.enter_fragment(spread.fragment_name, walk)?;
+                        self.collect_fields(&fragment.selection_set, fields, walk)?;
+                        walk.spreading.pop();
+                    }
+                }
+            }
+        }
+        walk.leave();
+        Ok(())
+    }
+
+    fn included(&self, directives: &[Directive<'a, &'a str>]) -> bool {
+        directives.iter().all(|directive| {
+            let condition = directive
+                .arguments
+                .iter()
+                .find(|(name, _)| *name == "if")
+                .map(|(_, value)| self.to_json(value));
+            match (directive.name, condition) {
+                ("skip", Some(Json::Bool(skip))) => !skip,
+                ("include", Some(Json::Bool(include))) => include,
+                _ => true,
+            }
+        })
+    }
+
+    /// The arguments of a field, with variables substituted.
+    pub fn arguments(&self, field: &Field<'a, &'a str>) -> Map<String, Json> {
+        field
+            .arguments
+            .iter()
+            .map(|(name, value)| (name.to_string(), self.to_json(value)))
+            .collect()
+    }
+
+    /// The fragment definitions the operation includes, as GraphQL text.
+    pub fn fragments_text(&self) -> String {
+        let mut fragments = self.fragments.values().collect::<Vec<_>>();
+        fragments.sort_by_key(|fragment| fragment.name);
+        fragments
+            .into_iter()
+            .map(|fragment| fragment.to_string())
+            .collect()
+    }
+
+    fn to_json(&self, value: &Value<'a, &'a str>) -> Json {
+        match value {
+            Value::Variable(name) => self.variables.get(*name).cloned().unwrap_or(Json::Null),
+            Value::Int(number) => number.as_i64().map_or(Json::Null, Json::from),
+            Value::Float(float) => Json::from(*float),
+            Value::String(string) => Json::from(string.as_str()),
+            Value::Boolean(boolean) => Json::from(*boolean),
+            Value::Null => Json::Null,
+            Value::Enum(name) => Json::from(*name),
+            Value::List(values) => values.iter().map(|value| self.to_json(value)).collect(),
+            Value::Object(fields) => Json::Object(
+                fields
+                    .iter()
+                    .map(|(name, value)| (name.to_string(), self.to_json(value)))
+                    .collect(),
+            ),
+        }
+    }
+}
+
+/// A walk over the selections of an operation, with what it has learned
+/// about the fragments it has spread.
+struct Walk<'a, T> {
+    /// The fragments being spread, innermost last.
+    spreading: Vec<&'a str>,
+    /// What has been measured of each fragment spread in full.
+    memo: HashMap<&'a str, T>,
+    /// The number of selections visited so far.
+    visited: usize,
+    /// The number of selection sets being walked.
+    nesting: usize,
+}
+
+impl<T> Default for Walk<'_, T> {
+    fn default() -> Self {
+        Self {
+            spreading: vec![],
+            memo: HashMap::new(),
+            visited: 0,
+            nesting: 0,
+        }
+    }
+}
+
+impl<T> Walk<'_, T> {
+    fn enter(&mut self) -> anyhow::Result<()> {
+        self.nesting += 1;
+        if self.nesting > MAX_NESTING {
+            bail!("the operation nests selections more than {MAX_NESTING} deep");
+        }
+        Ok(())
+    }
+
+    fn leave(&mut self) {
+        self.nesting -= 1;
+    }
+
+    fn visit(&mut self) -> anyhow::Result<()> {
+        self.visited += 1;
+        if self.visited > MAX_VISITED_SELECTIONS {
+            bail!("the operation has more than {MAX_VISITED_SELECTIONS} selections");
+        }
+        Ok(())
+    }
+
+    /// Finishes spreading the innermost fragment, remembering what was
+    /// measured of it.
+    fn leave_fragment(&mut self, measure: T) {
+        let name = self.spreading.pop().expect("a fragment should be spread");
+        self.memo.insert(name, measure);
+    }
+}
+
+fn check_depth(depth: usize, max_depth: usize) -> anyhow::Result<()> {
+    if depth > max_depth {
+        bail!("the operation's depth exceeds the limit of {max_depth}");
+    }
+    Ok(())
+}
+
+fn spend(spent: &mut u64, complexity: u64, max_complexity: u64) -> anyhow::Result<()> {
+    *spent = spent.saturating_add(complexity);
+    if *spent > max_complexity {
+        bail!("the operation's complexity exceeds the limit of {max_complexity}");
+    }
+    Ok(())
+}
+
+fn operation_name<'a>(operation: &OperationDefinition<'a, &'a str>) -> Option<&'a str> {
+    match operation {
+        OperationDefinition::SelectionSet(_) => None,
+        OperationDefinition::Query(query) => query.name,
+        OperationDefinition::Mutation(mutation) => mutation.name,
+        OperationDefinition::Subscription(subscription) => subscription.name,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(query: &str) -> Document<'_, &str> {
+        graphql_parser::parse_query(query).unwrap()
+    }
+
+    #[test]
+    fn operations_are_selected() {
+        let variables = Map::new();
+        let document = parse("query A { a } mutation B { b }");
+        let operation = Operation::select(&document, Some("B"), &variables).unwrap();
+        assert_eq!(OperationKind::Mutation, operation.kind);
+        assert!(Operation::select(&document, None, &variables).is_err());
+        assert!(Operation::select(&document, Some("C"), &variables).is_err());
+
+        let document = parse("{ a }");
+        let operation = Operation::select(&document, None, &variables).unwrap();
+        assert_eq!(OperationKind::Query, operation.kind);
+    }
+
+    #[test]
+    fn depth_and_complexity_follow_fragments() {
+        let variables = serde_json::json!({ "n": 10 });
+        let variables = variables.as_object().unwrap();
+        let document = parse(
+            "
+            query ($n: Int) {
+                users(first: $n) { ...user }
+                version
+            }
+            fragment user on User { name friends(limit: 2) { name } }
+            ",
+        );
+        let operation = Operation::select(&document, None, variables).unwrap();
+        operation.check_depth(3).unwrap();
+        assert!(operation.check_depth(2).is_err());
+        // users: 1 + 10 * (name + friends: 1 + 2 * name), version: 1
+        operation
+            .check_complexity(1 + 10 * (1 + 1 + 2) + 1)
+            .unwrap();
+        assert!(operation.check_complexity(10 * (1 + 1 + 2) + 1).is_err());
+
+        let document = parse("{ ...a } fragment a on Query { b { ...a } }");
+        let operation = Operation::select(&document, None, variables).unwrap();
+        assert!(operation.check_depth(10).is_err());
+    }
+
+    #[test]
+    fn repeated_fragments_are_measured_once() {
+        let variables = Map::new();
+        // Each fragment spreads the next twice, so spreading every one in
+        // full would visit 2^40 fields.
+        let mut query = "{ ...f0 }".to_owned();
+        for i in 0..40 {
+            query += &format!(
+                " fragment f{i} on T {{ a {{ ...f{} }} b {{ ...f{} }} }}",
+                i + 1,
+                i + 1
+            );
+        }
+        query += " fragment f40 on T { c }";
+        let document = parse(&query);
+        let operation = Operation::select(&document, None, &variables).unwrap();
+        operation.check_depth(41).unwrap();
+        assert!(operation.check_depth(40).is_err());
+        let err = operation.check_complexity(1_000_000).unwrap_err();
+        assert!(err.to_string().contains("exceeds the limit"));
+
+        let mut query = "{ ...g0 }".to_owned();
+        for i in 0..40 {
+            query += &format!(" fragment g{i} on Query {{ ...g{} ...g{} }}", i + 1, i + 1);
+        }
+        query += " fragment g40 on Query { c }";
+        let document = parse(&query);
+        let operation = Operation::select(&document, None, &variables).unwrap();
+        let err = operation.root_fields().unwrap_err();
+        assert!(err.to_string().contains("selections"));
+    }
+
+    #[test]
+    fn root_fields_are_collected() {
+        let variables = serde_json::json!({ "skip": true });
+        let variables = variables.as_object().unwrap();
+        let document = parse(
+            r#"
+            query ($skip: Boolean) {
+                a: user(id: "1", tags: [X]) { name }
+                ... on Query { b @skip(if: $skip) }
+                ...c
+            }
+            fragment c on Query { c @include(if: true) }
+            "#,
+        );
+        let operation = Operation::select(&document, None, variables).unwrap();
+        let fields = operation.root_fields().unwrap();
+        let names = fields.iter().map(|field| field.name).collect::<Vec<_>>();
+        assert_eq!(["user", "c"], names[..]);
+        assert_eq!(
+            serde_json::json!({ "id": "1", "tags": ["X"] }),
+            Json::Object(operation.arguments(fields[0]))
+        );
+    }
+}
@@ -0,0 +1,607 @@
+//! A trigger which serves GraphQL over HTTP.
+//!
+//! The trigger parses requests, enforces depth and complexity limits, and
+//! either routes each root field of an operation to the component which
+//! resolves it, or passes whole documents to a single component:
+//!
+//! ```toml
+//! [application.trigger.graphql]
+//! path = "/graphql"
+//! max_depth = 10
+//! max_complexity = 500
+//!
+//! [[trigger.graphql]]
+//! component = "users"
+//! fields = ["Query.user", "Query.users", "Mutation.createUser"]
+//! ```
+//!
+//! Components handle `wasi:http` requests. A component resolving fields is
+//! sent a `POST` for each one, with a JSON body describing the field, and
+//! responds with the field's value as JSON. A component handling whole
+//! documents is sent the GraphQL request, normalized, and its response is
+//! returned as it is.
+
+mod document;
+
+use std::{
+    collections::HashMap,
+    convert::Infallible,
+    net::{SocketAddr, ToSocketAddrs},
+    sync::Arc,
+};
+
+use anyhow::{Context as _, anyhow, bail};
+use clap::Args;
+use document::{Operation, OperationKind};
+use futures::future::join_all;
+use graphql_parser::query::Field;
+use http::{HeaderValue, Method, Request, Response, StatusCode, header::CONTENT_TYPE};
+use http_body_util::{BodyExt, Full, Limited};
+use hyper::body::{Bytes, Incoming};
+use hyper_util::rt::TokioIo;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value as Json, json};
+use spin_factor_outbound_http::OutboundHttpFactor;
+use spin_factors::RuntimeFactors;
+use spin_trigger::{App, Trigger, TriggerApp};
+use tokio::{net::TcpListener, sync::oneshot};
+use wasmtime_wasi_http::p2::bindings::{ProxyIndices, http::types::Scheme};
+
+/// The largest GraphQL request body accepted.
+const MAX_REQUEST_BYTES: usize = 1024 * 1024;
+/// The header telling a resolver component which field it is resolving.
+const FIELD_HEADER: &str = "spin-graphql-field";
+
+pub struct GraphqlTrigger {
+    listen_addr: SocketAddr,
+}
+
+#[derive(Args)]
+pub struct CliArgs {
+    /// IP address and port to serve GraphQL on
+    #[clap(
+        long = "graphql-listen",
+        env = "SPIN_GRAPHQL_LISTEN_ADDR",
+        default_value = "127.0.0.1:3100",
+        value_parser = parse_listen_addr
+    )]
+    pub address: SocketAddr,
+}
+
+fn parse_listen_addr(addr: &str) -> anyhow::Result<SocketAddr> {
+    addr.to_socket_addrs()?
+        .next()
+        .with_context(|| format!("could not resolve {addr:?}"))
+}
+
+/// App-level GraphQL trigger settings, from `[application.trigger.graphql]`.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TriggerMetadata {
+    /// The path GraphQL is served at.
+    #[serde(default = "default_path")]
+    path: String,
+    /// The deepest nesting of fields an operation may select.
+    #[serde(default = "default_max_depth")]
+    max_depth: usize,
+    /// The greatest complexity an operation may have; see
+    /// [`Operation::complexity`].
+    #[serde(default = "default_max_complexity")]
+    max_complexity: u64,
+}
+
+impl Default for TriggerMetadata {
+    fn default() -> Self {
+        Self {
+            path: default_path(),
+            max_depth: default_max_depth(),
+            max_complexity: default_max_complexity(),
+        }
+    }
+}
+
+fn default_path() -> String {
+    "/graphql".into()
+}
+
+fn default_max_depth() -> usize {
+    15
+}
+
+fn default_max_complexity() -> u64 {
+    1000
+}
+
+/// GraphQL trigger configuration.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TriggerConfig {
+    /// Component ID to invoke
+    component: String,
+    /// The root fields the component resolves, as `Type.field`. If omitted,
+    /// the component handles whole documents.
+    #[serde(default)]
+    fields: Vec<String>,
+}
+
+/// Where operations are executed.
+#[derive(Debug, PartialEq)]
+enum Routing {
+    /// Whole documents are passed to a single component.
+    Document(String),
+    /// Root fields, as `Type.field`, are resolved by components.
+    Fields(HashMap<String, String>),
+}
+
+impl Routing {
+    fn new(configs: impl IntoIterator<Item = TriggerConfig>) -> anyhow::Result<Self> {
+        let mut document_components = vec![];
+        let mut fields = HashMap::new();
+        for config in configs {
+            if config.fields.is_empty() {
+                document_components.push(config.component);
+                continue;
+            }
+            for field in config.fields {
+                match field.split_once('.') {
+                    Some(("Query" | "Mutation", name)) if !name.is_empty() => {}
+                    _ => bail!(
+                        "invalid GraphQL field {field:?} for component {:?}: expected `Query.<field>` or `Mutation.<field>`",
+                        config.component
+                    ),
+                }
+                if let Some(other) = fields.insert(field.clone(), config.component.clone()) {
+                    bail!(
+                        "GraphQL field {field:?} is resolved by both {other:?} and {:?}",
+                        config.component
+                    );
+                }
+            }
+        }
+        match (document_components.len(), fields.is_empty()) {
+            (0, _) => Ok(Self::Fields(fields)),
+            (1, true) => Ok(Self::Document(document_components.pop().unwrap())),
+            _ => bail!(
+                "a GraphQL trigger without `fields` handles whole documents, and must be the app's only GraphQL trigger"
+            ),
+        }
+    }
+}
+
+impl<F: RuntimeFactors> Trigger<F> for GraphqlTrigger {
+    const TYPE: &'static str = "graphql";
+
+    type CliArgs = CliArgs;
+
+    type InstanceState = ();
+
+    fn new(cli_args: Self::CliArgs, _app: &App) -> anyhow::Result<Self> {
+        Ok(Self {
+            listen_addr: cli_args.address,
+        })
+    }
+
+    async fn run(self, trigger_app: TriggerApp<Self, F>) -> anyhow::Result<()> {
+        let app = trigger_app.app();
+        let metadata = app
+            .get_trigger_metadata::<TriggerMetadata>(<Self as Trigger<F>>::TYPE)?
+            .unwrap_or_default();
+        let routing = Routing::new(
+            app.trigger_configs::<TriggerConfig>(<Self as Trigger<F>>::TYPE)?
+                .into_iter()
+                .map(|(_, config)| config),
+        )?;
+
+        let listener = TcpListener::bind(self.listen_addr)
+            .await
+            .with_context(|| format!("Unable to listen on {}", self.listen_addr))?;
+        println!(
+            "Serving GraphQL at http://{}{}",
+            listener.local_addr()?,
+            metadata.path
+        );
+        match &routing {
+            Routing::Document(component) => println!("  Documents: {component}"),
+            Routing::Fields(fields) => {
+                let mut fields = fields.iter().collect::<Vec<_>>();
+                fields.sort();
+                for (field, component) in fields {
+                    println!("  {field}: {component}");
+                }
+            }
+        }
+
+        let gateway = Arc::new(Gateway {
+            trigger_app,
+            metadata,
+            routing,
+        });
+        loop {
+            let (stream, client_addr) = listener.accept().await?;
+            let gateway = gateway.clone();
+            tokio::spawn(async move {
+                let service = hyper::service::service_fn(move |req| {
+                    let gateway = gateway.clone();
+                    async move { Ok::<_, Infallible>(gateway.handle(req).await) }
+                });
+                if let Err(err) = hyper::server::conn::http1::Builder::new()
+                    .serve_connection(TokioIo::new(stream), service)
+                    .await
+                {
+                    tracing::warn!("Error serving GraphQL connection from {client_addr}: {err:?}");
+                }
+            });
+        }
+    }
+
+    fn display_name() -> String {
+        "GraphQL".to_string()
+    }
+}
+
+/// A GraphQL request, as defined by GraphQL over HTTP.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct GraphqlRequest {
+    query: String,
+    operation_name: Option<String>,
+    #[serde(default)]
+    variables: Option<Map<String, Json>>,
+}
+
+impl GraphqlRequest {
+    /// Reads a request from the query string of a `GET`.
+    fn from_query_string(query: &str) -> anyhow::Result<Self> {
+        let mut request = Self {
+            query: String::new(),
+            operation_name: None,
+            variables: None,
+        };
+        let mut has_query = false;
+        for (name, value) in url::form_urlencoded::parse(query.as_bytes()) {
+            match &*name {
+                "query" => {
+                    request.query = value.into_owned();
+                    has_query = true;
+                }
+                "operationName" => request.operation_name = Some(value.into_owned()),
+                "variables" => {
+                    request.variables =
+                        serde_json::from_str(&value).context("variables must be a JSON object")?;
+                }
+                _ => {}
+            }
+        }
+        if !has_query {
+            bail!("the query parameter is required");
+        }
+        Ok(request)
+    }
+}
+
+/// An error in a GraphQL response.
+#[derive(Debug, Serialize)]
+struct GraphqlError {
+    message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    path: Option<Vec<String>>,
+}
+
+/// Serves GraphQL requests for an app.
+struct Gateway<F: RuntimeFactors> {
+    trigger_app: TriggerApp<GraphqlTrigger, F>,
+    metadata: TriggerMetadata,
+    routing: Routing,
+}
+
+impl<F: RuntimeFactors> Gateway<F> {
+    async fn handle(&self, req: Request<Incoming>) -> Response<Full<Bytes>> {
+        if req.uri().path() != self.metadata.path {
+            return text_response(StatusCode::NOT_FOUND, "Not Found");
+        }
+        let request = match self.read_request(req).await {
+            Ok(request) => request,
+            Err((status, err)) => return errors_response(status, &format!("{err:#}")),
+        };
+        match self.execute(request).await {
+            Ok(response) => response,
+            Err((status, err)) => errors_response(status, &format!("{err:#}")),
+        }
+    }
+
+    async fn read_request(
+        &self,
+        req: Request<Incoming>,
+    ) -> Result<(GraphqlRequest, bool), (StatusCode, anyhow::Error)> {
+        let bad_request = |err| (StatusCode::BAD_REQUEST, err);
+        match *req.method() {
+            Method::GET => {
+                let request = GraphqlRequest::from_query_string(req.uri().query().unwrap_or(""))
+                    .map_err(bad_request)?;
+                Ok((request, true))
+            }
+            Method::POST => {
+                let body = Limited::new(req.into_body(), MAX_REQUEST_BYTES)
+                    .collect()
+                    .await
+                    .map_err(|err| bad_request(anyhow!(err)))?
+                    .to_bytes();
+                let request = serde_json::from_slice(&body)
+                    .context("invalid GraphQL request")
+                    .map_err(bad_request)?;
+                Ok((request, false))
+            }
+            _ => Err((
+                StatusCode::METHOD_NOT_ALLOWED,
+                anyhow!("GraphQL requests must be GET or POST"),
+            )),
+        }
+    }
+
+    async fn execute(
+        &self,
+        (request, is_get): (GraphqlRequest, bool),
+    ) -> Result<Response<Full<Bytes>>, (StatusCode, anyhow::Error)> {
+        let bad_request = |err| (StatusCode::BAD_REQUEST, err);
+        let document = graphql_parser::parse_query::<&str>(&request.query)
+            .map_err(|err| bad_request(anyhow!("{err}")))?;
+        let variables = request.variables.clone().unwrap_or_default();
+        let operation = Operation::select(&document, request.operation_name.as_deref(), &variables)
+            .map_err(bad_request)?;
+        if is_get && operation.kind != OperationKind::Query {
+            return Err((
+                StatusCode::METHOD_NOT_ALLOWED,
+                anyhow!("only queries may be sent with GET"),
+            ));
+        }
+        if operation.kind == OperationKind::Subscription {
+            return Err(bad_request(anyhow!("subscriptions are not supported")));
+        }
+
+        operation
+            .check_depth(self.metadata.max_depth)
+            .map_err(bad_request)?;
+        operation
+            .check_complexity(self.metadata.max_complexity)
+            .map_err(bad_request)?;
+
+        let internal_error = |err| (StatusCode::INTERNAL_SERVER_ERROR, err);
+        match &self.routing {
+            Routing::Document(component) => {
+                let body = json!({
+                    "query": document.to_string(),
+                    "operationName": request.operation_name,
+                    "variables": variables,
+                });
+                let (status, content_type, body) = self
+                    .invoke(component, None, body)
+                    .await
+                    .map_err(internal_error)?;
+                let mut response = Response::new(Full::new(body));
+                *response.status_mut() = status;
+                if let Some(content_type) = content_type {
+                    response.headers_mut().insert(CONTENT_TYPE, content_type);
+                }
+                Ok(response)
+            }
+            Routing::Fields(fields) => {
+                let root_fields = operation.root_fields().map_err(bad_request)?;
+                let results = if operation.kind == OperationKind::Mutation {
+                    // Mutations run one after another.
+                    let mut results = vec![];
+                    for field in root_fields {
+                        results.push(self.resolve(&operation, fields, field).await);
+                    }
+                    results
+                } else {
+                    join_all(
+                        root_fields
+                            .into_iter()
+                            .map(|field| self.resolve(&operation, fields, field)),
+                    )
+                    .await
+                };
+
+                let mut data = Map::new();
+                let mut errors = vec![];
+                for (key, result) in results {
+                    match result {
+                        Ok(value) => {
+                            data.insert(key, value);
+                        }
+                        Err(err) => {
+                            errors.push(GraphqlError {
+                                message: format!("{err:#}"),
+                                path: Some(vec![key.clone()]),
+                            });
+                            data.insert(key, Json::Null);
+                        }
+                    }
+                }
+                let mut body = json!({ "data": data });
+                if !errors.is_empty() {
+                    body["errors"] = json!(errors);
+                }
+                Ok(json_response(StatusCode::OK, &body))
+            }
+        }
+    }
+
+    /// Resolves a root field, returning its response key and value.
+    async fn resolve<'a>(
+        &self,
+        operation: &Operation<'_, 'a>,
+        fields: &HashMap<String, String>,
+        field: &Field<'a, &'a str>,
+    ) -> (String, anyhow::Result<Json>) {
+        let key = field.alias.unwrap_or(field.name).to_owned();
+        let type_name = operation.kind.type_name();
+        if field.name == "__typename" {
+            return (key, Ok(Json::from(type_name)));
+        }
+        let field_name = format!("{type_name}.{}", field.name);
+        let result = async {
+            let component = fields
+                .get(&field_name)
+                .with_context(|| format!("no component resolves {field_name}"))?;
+            let body = json!({
+                "field": field_name,
+                "arguments": operation.arguments(field),
+                "selection": field.to_string(),
+                "fragments": operation.fragments_text(),
+            });
+            let (status, _, body) = self.invoke(component, Some(&field_name), body).await?;
+            if !status.is_success() {
+                bail!(
+                    "resolver for {field_name} failed with {status}: {}",
+                    String::from_utf8_lossy(&body)
+                );
+            }
+            serde_json::from_slice(&body)
+                .with_context(|| format!("resolver for {field_name} returned invalid JSON"))
+        }
+        .await;
+        (key, result)
+    }
+
+    /// Sends a JSON `POST` to a component's `wasi:http` handler, returning
+    /// the response's status, content type and body.
+    async fn invoke(
+        &self,
+        component_id: &str,
+        field: Option<&str>,
+        body: Json,
+    ) -> anyhow::Result<(StatusCode, Option<HeaderValue>, Bytes)> {
+        let (instance, mut store) = self
+            .trigger_app
+            .prepare(component_id)?
+            .instantiate(())
+            .await?;
+        let pre = instance.instance_pre(&store);
+        let proxy = ProxyIndices::new(&pre)
+            .map_err(anyhow::Error::from)
+            .with_context(|| {
+                format!("component {component_id:?} does not export wasi:http/incoming-handler")
+            })?
+            .load(&mut store, &instance)?;
+
+        let mut builder = Request::builder()
+            .method(Method::POST)
+            .uri(format!("http://localhost{}", self.metadata.path))
+            .header(CONTENT_TYPE, "application/json");
+        if let Some(field) = field {
+            builder = builder.header(FIELD_HEADER, field);
+        }
+        let req = builder.body(spin_http::body::full(serde_json::to_vec(&body)?.into()))?;
+
+        let mut wasi_http =
+            OutboundHttpFactor::get_wasi_http_impl(store.data_mut().factors_instance_state_mut())
+                .context("missing OutboundHttpFactor")?;
+        let request = wasi_http.new_incoming_request(Scheme::Http, req)?;
+        let (response_tx, response_rx) = oneshot::channel();
+        let response = wasi_http.new_response_outparam(response_tx)?;
+
+        let handle = tokio::spawn(async move {
+            proxy
+                .wasi_http_incoming_handler()
+                .call_handle(&mut store, request, response)
+                .await
+        });
+        let Ok(response) = response_rx.await else {
+            handle
+                .await
+                .context("guest invocation panicked")?
+                .map_err(anyhow::Error::from)
+                .context("guest invocation failed")?;
+            bail!("component {component_id:?} did not produce a response");
+        };
+        let response = response.map_err(|err| anyhow!("component returned an error: {err:?}"))?;
+        let status = response.status();
+        let content_type = response.headers().get(CONTENT_TYPE).cloned();
+        let body = response
+            .into_body()
+            .collect()
+            .await
+            .map_err(|err| anyhow!("failed to read component response: {err:?}"))?
+            .to_bytes();
+        handle
+            .await
+            .context("guest invocation panicked")?
+            .map_err(anyhow::Error::from)
+            .context("guest invocation failed")?;
+        Ok((status, content_type, body))
+    }
+}
+
+fn json_response(status: StatusCode, body: &Json) -> Response<Full<Bytes>> {
+    let mut response = Response::new(Full::new(body.to_string().into()));
+    *response.status_mut() = status;
+    response
+        .headers_mut()
+        .insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+    response
+}
+
+fn errors_response(status: StatusCode, message: &str) -> Response<Full<Bytes>> {
+    let error = GraphqlError {
+        message: message.to_owned(),
+        path: None,
+    };
+    json_response(status, &json!({ "errors": [error] }))
+}
+
+fn text_response(status: StatusCode, text: &'static str) -> Response<Full<Bytes>> {
+    let mut response = Response::new(Full::new(Bytes::from_static(text.as_bytes())));
+    *response.status_mut() = status;
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(component: &str, fields: &[&str]) -> TriggerConfig {
+        TriggerConfig {
+            component: component.into(),
+            fields: fields.iter().map(|field| field.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn routing_is_validated() {
+        assert_eq!(
+            Routing::Document("graphql".into()),
+            Routing::new([config("graphql", &[])]).unwrap()
+        );
+        let Routing::Fields(fields) = Routing::new([
+            config("users", &["Query.user", "Mutation.createUser"]),
+            config("posts", &["Query.posts"]),
+        ])
+        .unwrap() else {
+            panic!("expected field routing");
+        };
+        assert_eq!("users", fields["Mutation.createUser"]);
+        assert_eq!("posts", fields["Query.posts"]);
+
+        assert!(Routing::new([config("a", &[]), config("b", &["Query.b"])]).is_err());
+        assert!(Routing::new([config("a", &["Query.a"]), config("b", &["Query.a"])]).is_err());
+        assert!(Routing::new([config("a", &["Subscription.a"])]).is_err());
+        assert!(Routing::new([config("a", &["user"])]).is_err());
+    }
+
+    #[test]
+    fn get_requests_are_read_from_the_query_string() {
+        let request = GraphqlRequest::from_query_string(
+            "query=query+Q(%24id%3A+ID)%7Buser(id%3A%24id)%7Bname%7D%7D&operationName=Q&variables=%7B%22id%22%3A%221%22%7D",
+        )
+        .unwrap();
+        assert_eq!("query Q($id: ID){user(id:$id){name}}", request.query);
+        assert_eq!(Some("Q"), request.operation_name.as_deref());
+        assert_eq!(
+            json!({ "id": "1" }),
+            Json::Object(request.variables.unwrap())
+        );
+
+        assert!(GraphqlRequest::from_query_string("operationName=Q").is_err());
+    }
+}
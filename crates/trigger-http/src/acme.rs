@@ -0,0 +1,624 @@
+//! Automatic TLS certificates from an ACME certificate authority, such as
+//! Let's Encrypt.
+
+use std::{
+    collections::HashMap,
+    convert::Infallible,
+    fmt::Display,
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, RwLock},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{Context, bail, ensure};
+use http::{Request, Response, StatusCode};
+use http_body_util::Full;
+use hyper::{body::Bytes, service::service_fn};
+use hyper_util::rt::TokioIo;
+use instant_acme::{
+    Account, AccountCredentials, AuthorizationStatus, ChallengeType, Identifier, NewAccount,
+    NewOrder, Order, OrderStatus,
+};
+use rcgen::{CertificateParams, CustomExtension, KeyPair};
+use rustls_pki_types::{CertificateDer, PrivateKeyDer, pem::PemObject};
+use spin_app::App;
+use spin_common::ui::quoted_path;
+use tokio::net::TcpListener;
+use tokio_rustls::{
+    TlsAcceptor,
+    rustls::{
+        self,
+        server::{ClientHello, ResolvesServerCert},
+        sign::CertifiedKey,
+    },
+};
+
+/// The directory URL of Let's Encrypt's production certificate authority.
+pub const LETS_ENCRYPT_DIRECTORY: &str = "https://acme-v02.api.letsencrypt.org/directory";
+
+/// The ALPN protocol with which the certificate authority makes TLS-ALPN-01
+/// validation connections (RFC 8737).
+const ACME_TLS_ALPN_NAME: &[u8] = b"acme-tls/1";
+/// The path under which HTTP-01 challenges are requested (RFC 8555 §8.3).
+const HTTP01_CHALLENGE_PREFIX: &str = "/.well-known/acme-challenge/";
+
+/// How long before it expires a certificate is renewed.
+const RENEW_BEFORE_EXPIRY: Duration = Duration::from_secs(30 * 24 * 60 * 60);
+/// How often the certificate is checked for renewal.
+const RENEWAL_CHECK_INTERVAL: Duration = Duration::from_secs(12 * 60 * 60);
+/// How long to wait before trying again when a certificate could not be obtained.
+const RENEWAL_RETRY_INTERVAL: Duration = Duration::from_secs(60 * 60);
+/// How many times to poll the certificate authority while it validates an order.
+const MAX_ORDER_POLLS: u32 = 10;
+/// How many times to poll the certificate authority for the certificate of a finalized order.
+const MAX_CERTIFICATE_POLLS: u32 = 30;
+
+/// How to prove control of the domains to the certificate authority.
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum AcmeChallenge {
+    /// Answer a TLS handshake on the HTTPS listener, which must be reachable
+    /// on port 443.
+    #[default]
+    #[value(name = "tls-alpn-01")]
+    TlsAlpn01,
+    /// Answer an HTTP request on a separate HTTP listener, which must be
+    /// reachable on port 80.
+    #[value(name = "http-01")]
+    Http01,
+}
+
+impl AcmeChallenge {
+    fn challenge_type(self) -> ChallengeType {
+        match self {
+            Self::TlsAlpn01 => ChallengeType::TlsAlpn01,
+            Self::Http01 => ChallengeType::Http01,
+        }
+    }
+}
+
+impl Display for AcmeChallenge {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::TlsAlpn01 => "tls-alpn-01",
+            Self::Http01 => "http-01",
+        })
+    }
+}
+
+/// Configuration for obtaining certificates with ACME.
+#[derive(Clone, Debug)]
+pub struct AcmeConfig {
+    /// The domains the certificate must cover.
+    pub domains: Vec<String>,
+    /// The contact email address registered with the certificate authority.
+    pub email: Option<String>,
+    /// The directory URL of the certificate authority.
+    pub directory_url: String,
+    /// How to prove control of the domains.
+    pub challenge: AcmeChallenge,
+    /// The address on which to answer HTTP-01 challenges.
+    pub http_listen: SocketAddr,
+    /// Where the account credentials and certificates are stored.
+    pub cache_dir: PathBuf,
+}
+
+impl AcmeConfig {
+    /// Returns the default directory for ACME state: `acme/` in the app's
+    /// default state directory for local apps, or in the Spin data directory
+    /// otherwise.
+    pub fn default_cache_dir(app: &App) -> anyhow::Result<PathBuf> {
+        let dir = match std::env::var_os(spin_trigger::cli::SPIN_LOCAL_APP_DIR) {
+            Some(local_app_dir) => {
+                let app_id = app
+                    .get_metadata(spin_app::APP_ORIGIN_KEY)?
+                    .and_then(|origin: String| spin_common::app_state::app_id_from_origin(&origin));
+                spin_common::app_state::default_state_dir(
+                    Path::new(&local_app_dir),
+                    app_id.as_deref(),
+                )?
+            }
+            None => spin_common::data_dir::data_dir()?,
+        };
+        Ok(dir.join("acme"))
+    }
+
+    fn validate(&self) -> anyhow::Result<()> {
+        ensure!(
+            !self.domains.is_empty(),
+            "ACME requires at least one domain"
+        );
+        for domain in &self.domains {
+            ensure!(
+                !domain.contains('*'),
+                "cannot obtain a certificate for {domain:?}: wildcard domains require the DNS-01 challenge, which is not supported"
+            );
+            ensure!(
+                !domain.is_empty()
+                    && domain
+                        .chars()
+                        .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '.'),
+                "{domain:?} is not a valid domain name"
+            );
+        }
+        Ok(())
+    }
+
+    fn account_path(&self) -> anyhow::Result<PathBuf> {
+        // Accounts belong to a particular certificate authority.
+        let url = url::Url::parse(&self.directory_url)
+            .with_context(|| format!("invalid ACME directory URL {:?}", self.directory_url))?;
+        let host = url.host_str().unwrap_or("default");
+        Ok(self.cache_dir.join(format!("account-{host}.json")))
+    }
+
+    fn cert_path(&self) -> PathBuf {
+        self.cache_dir
+            .join(format!("{}.crt.pem", self.domains.join("+")))
+    }
+
+    fn key_path(&self) -> PathBuf {
+        self.cache_dir
+            .join(format!("{}.key.pem", self.domains.join("+")))
+    }
+}
+
+/// Obtains a certificate for the configured domains, stores it, and renews it
+/// before it expires.
+pub(crate) struct AcmeManager {
+    config: AcmeConfig,
+    resolver: Arc<CertResolver>,
+    /// When the current certificate expires.
+    not_after: Mutex<Option<SystemTime>>,
+    /// Key authorizations for pending HTTP-01 challenges, by token.
+    http01_tokens: Mutex<HashMap<String, String>>,
+}
+
+impl AcmeManager {
+    /// Creates a manager, loading the stored certificate if there is one.
+    pub fn new(config: AcmeConfig) -> anyhow::Result<Self> {
+        config.validate()?;
+        std::fs::create_dir_all(&config.cache_dir).with_context(|| {
+            format!(
+                "failed to create ACME directory {}",
+                quoted_path(&config.cache_dir)
+            )
+        })?;
+        let manager = Self {
+            config,
+            resolver: Default::default(),
+            not_after: Default::default(),
+            http01_tokens: Default::default(),
+        };
+        if let Err(err) = manager.load_stored_cert() {
+            tracing::warn!("Ignoring stored TLS certificate: {err:#}");
+        }
+        Ok(manager)
+    }
+
+    pub fn challenge(&self) -> AcmeChallenge {
+        self.config.challenge
+    }
+
+    /// Creates a TLS acceptor which serves the current certificate, and
+    /// answers TLS-ALPN-01 challenges.
+    pub fn acceptor(&self) -> TlsAcceptor {
+        let mut cfg = rustls::ServerConfig::builder()
+            .with_no_client_auth()
+            .with_cert_resolver(self.resolver.clone());
+        if self.config.challenge == AcmeChallenge::TlsAlpn01 {
+            cfg.alpn_protocols = vec![ACME_TLS_ALPN_NAME.to_vec(), b"http/1.1".to_vec()];
+        }
+        Arc::new(cfg).into()
+    }
+
+    /// Whether a connection was made by the certificate authority to validate
+    /// a TLS-ALPN-01 challenge, and so should be closed once established.
+    pub fn is_challenge_connection(conn: &rustls::ServerConnection) -> bool {
+        conn.alpn_protocol() == Some(ACME_TLS_ALPN_NAME)
+    }
+
+    /// Starts answering HTTP-01 challenges, if configured, and obtaining and
+    /// renewing the certificate in the background.
+    pub async fn start(self: &Arc<Self>) -> anyhow::Result<()> {
+        if self.config.challenge == AcmeChallenge::Http01 {
+            self.clone().serve_http01().await?;
+        }
+        tokio::spawn(self.clone().renew_periodically());
+        Ok(())
+    }
+
+    async fn renew_periodically(self: Arc<Self>) {
+        loop {
+            let wait = match self.renew_if_needed().await {
+                Ok(()) => RENEWAL_CHECK_INTERVAL,
+                Err(err) => {
+                    tracing::error!(
+                        "Failed to obtain a TLS certificate for {}: {err:#}",
+                        self.config.domains.join(", ")
+                    );
+                    RENEWAL_RETRY_INTERVAL
+                }
+            };
+            tokio::time::sleep(wait).await;
+        }
+    }
+
+    async fn renew_if_needed(&self) -> anyhow::Result<()> {
+        let not_after = *self.not_after.lock().unwrap();
+        if let Some(not_after) = not_after
+            && not_after > SystemTime::now() + RENEW_BEFORE_EXPIRY
+        {
+            return Ok(());
+        }
+        self.obtain_cert().await
+    }
+
+    async fn obtain_cert(&self) -> anyhow::Result<()> {
+        tracing::info!(
+            "Requesting a TLS certificate for {} from {}",
+            self.config.domains.join(", "),
+            self.config.directory_url
+        );
+        let account = self.account().await?;
+        let identifiers = self
+            .config
+            .domains
+            .iter()
+            .map(|domain| Identifier::Dns(domain.clone()))
+            .collect::<Vec<_>>();
+        let mut order = account
+            .new_order(&NewOrder {
+                identifiers: &identifiers,
+            })
+            .await
+            .context("failed to place certificate order")?;
+
+        let result = self.complete_order(&mut order).await;
+        self.http01_tokens.lock().unwrap().clear();
+        self.resolver.challenge_certs.lock().unwrap().clear();
+        let (cert_pem, key_pem) = result?;
+
+        std::fs::write(self.config.cert_path(), &cert_pem)?;
+        write_private(&self.config.key_path(), &key_pem)?;
+        self.install_cert(&cert_pem, &key_pem)?;
+        tracing::info!(
+            "Obtained a TLS certificate for {}",
+            self.config.domains.join(", ")
+        );
+        Ok(())
+    }
+
+    /// Answers the order's challenges and returns the issued certificate chain
+    /// and its private key, in PEM format.
+    async fn complete_order(&self, order: &mut Order) -> anyhow::Result<(String, String)> {
+        let challenge_type = self.config.challenge.challenge_type();
+        for authz in order.authorizations().await? {
+            match authz.status {
+                AuthorizationStatus::Pending => {}
+                AuthorizationStatus::Valid => continue,
+                status => bail!("authorization for {:?} is {status:?}", authz.identifier),
+            }
+            #[allow(irrefutable_let_patterns)]
+            let Identifier::Dns(domain) = &authz.identifier else {
+                bail!("unsupported ACME identifier {:?}", authz.identifier);
+            };
+            let challenge = authz
+                .challenges
+                .iter()
+                .find(|challenge| challenge.r#type == challenge_type)
+                .with_context(|| {
+                    format!(
+                        "the certificate authority does not offer the {} challenge for {domain}",
+                        self.config.challenge
+                    )
+                })?;
+            let key_authorization = order.key_authorization(challenge);
+            match self.config.challenge {
+                AcmeChallenge::Http01 => {
+                    self.http01_tokens.lock().unwrap().insert(
+                        challenge.token.clone(),
+                        key_authorization.as_str().to_owned(),
+                    );
+                }
+                AcmeChallenge::TlsAlpn01 => {
+                    let cert = challenge_cert(domain, key_authorization.digest().as_ref())?;
+                    self.resolver
+                        .challenge_certs
+                        .lock()
+                        .unwrap()
+                        .insert(domain.clone(), Arc::new(cert));
+                }
+            }
+            order.set_challenge_ready(&challenge.url).await?;
+        }
+
+        let mut delay = Duration::from_secs(1);
+        let mut polls = 0;
+        loop {
+            tokio::time::sleep(delay).await;
+            match order.refresh().await?.status {
+                OrderStatus::Ready => break,
+                OrderStatus::Invalid => bail!(
+                    "the certificate authority could not validate control of the domains using the {} challenge",
+                    self.config.challenge
+                ),
+                status if polls >= MAX_ORDER_POLLS => {
+                    bail!("timed out waiting for the certificate order, which is {status:?}")
+                }
+                _ => {
+                    polls += 1;
+                    delay *= 2;
+                }
+            }
+        }
+
+        let key = KeyPair::generate()?;
+        let csr = CertificateParams::new(self.config.domains.clone())?.serialize_request(&key)?;
+        order.finalize(csr.der()).await?;
+        let mut polls = 0;
+        let cert_pem = loop {
+            match order.certificate().await? {
+                Some(cert_pem) => break cert_pem,
+                None if polls >= MAX_CERTIFICATE_POLLS => {
+                    bail!(
+                        "timed out waiting for the certificate authority to issue the certificate"
+                    )
+                }
+                None => {
+                    polls += 1;
+                    tokio::time::sleep(Duration::from_secs(1)).await;
+                }
+            }
+        };
+        Ok((cert_pem, key.serialize_pem()))
+    }
+
+    /// Loads the stored account, or registers a new one.
+    async fn account(&self) -> anyhow::Result<Account> {
+        let path = self.config.account_path()?;
+        if path.exists() {
+            let credentials: AccountCredentials = serde_json::from_slice(&std::fs::read(&path)?)
+                .with_context(|| format!("invalid ACME account file {}", quoted_path(&path)))?;
+            return Account::from_credentials(credentials)
+                .await
+                .context("failed to load ACME account");
+        }
+        let contact = self
+            .config
+            .email
+            .iter()
+            .map(|email| format!("mailto:{email}"))
+            .collect::<Vec<_>>();
+        let contact = contact.iter().map(String::as_str).collect::<Vec<_>>();
+        let (account, credentials) = Account::create(
+            &NewAccount {
+                contact: &contact,
+                terms_of_service_agreed: true,
+                only_return_existing: false,
+            },
+            &self.config.directory_url,
+            None,
+        )
+        .await
+        .context("failed to register ACME account")?;
+        write_private(&path, &serde_json::to_vec(&credentials)?)
+            .with_context(|| format!("failed to save ACME account to {}", quoted_path(&path)))?;
+        Ok(account)
+    }
+
+    fn load_stored_cert(&self) -> anyhow::Result<()> {
+        let (cert_path, key_path) = (self.config.cert_path(), self.config.key_path());
+        if !cert_path.exists() || !key_path.exists() {
+            return Ok(());
+        }
+        self.install_cert(
+            &std::fs::read_to_string(&cert_path)?,
+            &std::fs::read_to_string(&key_path)?,
+        )
+    }
+
+    fn install_cert(&self, cert_pem: &str, key_pem: &str) -> anyhow::Result<()> {
+        let certs = CertificateDer::pem_slice_iter(cert_pem.as_bytes())
+            .collect::<Result<Vec<_>, _>>()
+            .context("invalid certificate chain")?;
+        let key =
+            PrivateKeyDer::from_pem_slice(key_pem.as_bytes()).context("invalid private key")?;
+        let not_after = not_after(certs.first().context("empty certificate chain")?)?;
+        *self.resolver.cert.write().unwrap() = Some(Arc::new(certified_key(certs, key)?));
+        *self.not_after.lock().unwrap() = Some(not_after);
+        Ok(())
+    }
+
+    async fn serve_http01(self: Arc<Self>) -> anyhow::Result<()> {
+        let addr = self.config.http_listen;
+        let listener = TcpListener::bind(addr)
+            .await
+            .with_context(|| format!("failed to listen for ACME HTTP-01 challenges on {addr}"))?;
+        tokio::spawn(async move {
+            loop {
+                let stream = match listener.accept().await {
+                    Ok((stream, _)) => stream,
+                    Err(err) => {
+                        tracing::warn!("Failed to accept ACME challenge connection: {err}");
+                        continue;
+                    }
+                };
+                let manager = self.clone();
+                tokio::spawn(async move {
+                    let service = service_fn(|req| {
+                        let response = manager.http01_response(&req);
+                        async move { Ok::<_, Infallible>(response) }
+                    });
+                    if let Err(err) = hyper::server::conn::http1::Builder::new()
+                        .serve_connection(TokioIo::new(stream), service)
+                        .await
+                    {
+                        tracing::debug!("Error serving ACME challenge connection: {err}");
+                    }
+                });
+            }
+        });
+        Ok(())
+    }
+
+    fn http01_response<B>(&self, req: &Request<B>) -> Response<Full<Bytes>> {
+        let key_authorization = req
+            .uri()
+            .path()
+            .strip_prefix(HTTP01_CHALLENGE_PREFIX)
+            .and_then(|token| self.http01_tokens.lock().unwrap().get(token).cloned());
+        let (status, body) = match key_authorization {
+            Some(key_authorization) => (StatusCode::OK, key_authorization),
+            None => (StatusCode::NOT_FOUND, "not found".to_owned()),
+        };
+        Response::builder()
+            .status(status)
+            .header(http::header::CONTENT_TYPE, "text/plain")
+            .body(Full::new(Bytes::from(body)))
+            .expect("response should be valid")
+    }
+}
+
+/// Serves the current certificate, or a challenge certificate to the
+/// certificate authority's TLS-ALPN-01 validation connections.
+#[derive(Debug, Default)]
+struct CertResolver {
+    cert: RwLock<Option<Arc<CertifiedKey>>>,
+    /// Challenge certificates, by domain.
+    challenge_certs: Mutex<HashMap<String, Arc<CertifiedKey>>>,
+}
+
+impl ResolvesServerCert for CertResolver {
+    fn resolve(&self, client_hello: ClientHello<'_>) -> Option<Arc<CertifiedKey>> {
+        let is_challenge = client_hello
+            .alpn()
+            .is_some_and(|mut protocols| protocols.any(|p| p == ACME_TLS_ALPN_NAME));
+        if is_challenge {
+            let domain = client_hello.server_name()?;
+            return self.challenge_certs.lock().unwrap().get(domain).cloned();
+        }
+        self.cert.read().unwrap().clone()
+    }
+}
+
+/// Creates the self-signed certificate which answers a TLS-ALPN-01 challenge
+/// (RFC 8737 §3).
+fn challenge_cert(domain: &str, key_authorization_digest: &[u8]) -> anyhow::Result<CertifiedKey> {
+    let mut params = CertificateParams::new(vec![domain.to_owned()])?;
+    params.custom_extensions = vec![CustomExtension::new_acme_identifier(
+        key_authorization_digest,
+    )];
+    let key = KeyPair::generate()?;
+    let cert = params.self_signed(&key)?;
+    certified_key(
+        vec![cert.der().clone()],
+        PrivateKeyDer::Pkcs8(key.serialize_der().into()),
+    )
+}
+
+fn certified_key(
+    certs: Vec<CertificateDer<'static>>,
+    key: PrivateKeyDer<'static>,
+) -> anyhow::Result<CertifiedKey> {
+    let key = rustls::crypto::ring::sign::any_supported_type(&key)
+        .map_err(|err| anyhow::anyhow!("unsupported private key: {err}"))?;
+    Ok(CertifiedKey::new(certs, key))
+}
+
+/// Writes a file only the current user can read.
+fn write_private(path: &Path, contents: impl AsRef<[u8]>) -> std::io::Result<()> {
+    use std::io::Write as _;
+
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    options.open(path)?.write_all(contents.as_ref())
+}
+
+fn not_after(cert: &CertificateDer) -> anyhow::Result<SystemTime> {
+    let (_, cert) = x509_parser::parse_x509_certificate(cert)
+        .map_err(|err| anyhow::anyhow!("invalid certificate: {err}"))?;
+    let timestamp = cert.validity().not_after.timestamp();
+    Ok(UNIX_EPOCH + Duration::from_secs(timestamp.try_into().unwrap_or_default()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(domains: &[&str]) -> AcmeConfig {
+        AcmeConfig {
+            domains: domains.iter().map(|d| d.to_string()).collect(),
+            email: None,
+            directory_url: LETS_ENCRYPT_DIRECTORY.into(),
+            challenge: AcmeChallenge::Http01,
+            http_listen: "127.0.0.1:0".parse().unwrap(),
+            cache_dir: tempfile::tempdir().unwrap().keep(),
+        }
+    }
+
+    #[test]
+    fn rejects_unsupported_domains() {
+        config(&["example.com", "www.example.com"])
+            .validate()
+            .unwrap();
+        assert!(config(&[]).validate().is_err());
+        let err = config(&["*.example.com"]).validate().unwrap_err();
+        assert!(err.to_string().contains("DNS-01"), "{err}");
+        assert!(config(&["example.com/path"]).validate().is_err());
+    }
+
+    #[test]
+    fn stores_state_per_authority_and_domains() {
+        let config = config(&["example.com", "www.example.com"]);
+        assert_eq!(
+            config
+                .cache_dir
+                .join("account-acme-v02.api.letsencrypt.org.json"),
+            config.account_path().unwrap()
+        );
+        assert_eq!(
+            config.cache_dir.join("example.com+www.example.com.crt.pem"),
+            config.cert_path()
+        );
+    }
+
+    #[test]
+    fn answers_pending_http01_challenges() {
+        let manager = AcmeManager::new(config(&["example.com"])).unwrap();
+        manager
+            .http01_tokens
+            .lock()
+            .unwrap()
+            .insert("token".into(), "token.thumbprint".into());
+
+        let request = |path: &str| Request::get(path).body(()).unwrap();
+        let response = manager.http01_response(&request("/.well-known/acme-challenge/token"));
+        assert_eq!(StatusCode::OK, response.status());
+        let response = manager.http01_response(&request("/.well-known/acme-challenge/other"));
+        assert_eq!(StatusCode::NOT_FOUND, response.status());
+    }
+
+    #[test]
+    fn stored_certificates_are_reloaded() {
+        let config = config(&["example.com"]);
+        let key = KeyPair::generate().unwrap();
+        let cert = CertificateParams::new(vec!["example.com".into()])
+            .unwrap()
+            .self_signed(&key)
+            .unwrap();
+        std::fs::write(config.cert_path(), cert.pem()).unwrap();
+        std::fs::write(config.key_path(), key.serialize_pem()).unwrap();
+
+        let manager = AcmeManager::new(config).unwrap();
+        assert!(manager.resolver.cert.read().unwrap().is_some());
+        assert!(manager.not_after.lock().unwrap().unwrap() > SystemTime::now());
+    }
+
+    #[test]
+    fn creates_challenge_certificates() {
+        challenge_cert("example.com", &[0; 32]).unwrap();
+    }
+}
@@ -0,0 +1,236 @@
+//! Session affinity for HTTP routes.
+//!
+//! A route with affinity routes requests carrying the same session key, from
+//! a cookie or header, to the same warm instance of its component, so the
+//! component can keep per-session state in memory between requests. Each
+//! session gets its own instance pool, whose instance is kept warm for the
+//! session's idle timeout rather than `--idle-instance-timeout`. Requests
+//! without a session key are handled by the component's shared pool.
+//!
+//! As with instance reuse in general, only components which export
+//! `wasi:http/handler@0.3` can be routed this way.
+//!
+//! When a component is swapped for a new version, its sessions are dropped,
+//! and continue on instances of the new version.
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use anyhow::{Context as _, bail};
+use http::{HeaderMap, HeaderName};
+use spin_common::arg_parser::parse_duration;
+use spin_factors::RuntimeFactors;
+use spin_factors_executor::InstanceState;
+use spin_http::config::SessionAffinity;
+use wasmtime_wasi_http::handler::{ProxyHandler, ProxyPre};
+use wasmtime_wasi_http::p3::bindings::ServicePre;
+
+use crate::{
+    InstanceReuseConfig, Range, TriggerApp, TriggerInstanceBuilder, oidc, server::HttpHandlerState,
+};
+
+/// How long a session's instance is kept warm after its last request, unless
+/// the route says otherwise.
+const DEFAULT_IDLE_TIMEOUT: Duration = Duration::from_secs(5 * 60);
+
+/// The most sessions tracked per component. Requests for new sessions beyond
+/// this are handled by the component's shared pool until others expire.
+const MAX_SESSIONS: usize = 1024;
+
+/// Where a request's session key is found.
+#[derive(Debug, PartialEq)]
+pub(crate) enum SessionKey {
+    Cookie(String),
+    Header(HeaderName),
+}
+
+impl SessionKey {
+    fn from_config(affinity: &SessionAffinity) -> anyhow::Result<Self> {
+        Self::new(affinity.cookie.as_deref(), affinity.header.as_deref())?
+            .context("affinity must set one of `cookie` or `header`")
+    }
+
+    /// Returns the key in the given cookie or header, or `None` if neither is
+    /// given.
+    pub fn new(cookie: Option<&str>, header: Option<&str>) -> anyhow::Result<Option<Self>> {
+        match (cookie, header) {
+            (Some(cookie), None) => Ok(Some(Self::Cookie(cookie.to_owned()))),
+            (None, Some(header)) => {
+                Ok(Some(Self::Header(header.parse().with_context(|| {
+                    format!("invalid header name {header:?}")
+                })?)))
+            }
+            (None, None) => Ok(None),
+            (Some(_), Some(_)) => bail!("only one of `cookie` or `header` may be set"),
+        }
+    }
+
+    /// Returns the request's key, if it has one.
+    pub fn of<'a>(&self, headers: &'a HeaderMap) -> Option<&'a str> {
+        let key = match self {
+            Self::Cookie(name) => oidc::cookie(headers, name)?,
+            Self::Header(name) => headers.get(name)?.to_str().ok()?,
+        };
+        (!key.is_empty()).then_some(key)
+    }
+}
+
+struct Session<F: RuntimeFactors> {
+    handler: Arc<ProxyHandler<HttpHandlerState<F>>>,
+    last_used: Instant,
+}
+
+/// The sessions of a version of a component.
+struct Sessions<F: RuntimeFactors> {
+    /// The component's generation when the version was loaded.
+    generation: u64,
+    pre: ServicePre<InstanceState<F::InstanceState, ()>>,
+    /// Maps session keys to the sessions which have made requests.
+    by_key: HashMap<String, Session<F>>,
+}
+
+/// Routes the requests of each session to its own warm instance of a
+/// component.
+pub(crate) struct SessionRouter<F: RuntimeFactors> {
+    key: SessionKey,
+    idle_timeout: Duration,
+    trigger_app: Arc<TriggerApp<F>>,
+    component_id: String,
+    reuse_config: InstanceReuseConfig,
+    sessions: Mutex<Sessions<F>>,
+}
+
+impl<F: RuntimeFactors> SessionRouter<F> {
+    pub fn new(
+        affinity: &SessionAffinity,
+        trigger_app: &Arc<TriggerApp<F>>,
+        component_id: &str,
+        reuse_config: InstanceReuseConfig,
+    ) -> anyhow::Result<Self> {
+        let key = SessionKey::from_config(affinity)?;
+        let idle_timeout = match &affinity.idle_timeout {
+            Some(timeout) => parse_duration(timeout).context("invalid affinity idle timeout")?,
+            None => DEFAULT_IDLE_TIMEOUT,
+        };
+        anyhow::ensure!(
+            !idle_timeout.is_zero(),
+            "affinity idle timeout must be greater than zero"
+        );
+        let generation = trigger_app.component_generation(component_id)?;
+        let pre = ServicePre::new(trigger_app.get_instance_pre(component_id)?)
+            .map_err(anyhow::Error::from)
+            .context("session affinity requires a component which exports wasi:http/handler@0.3")?;
+        Ok(Self {
+            key,
+            idle_timeout,
+            trigger_app: trigger_app.clone(),
+            component_id: component_id.into(),
+            reuse_config: InstanceReuseConfig {
+                idle_instance_timeout: Range::Value(idle_timeout),
+                ..reuse_config
+            },
+            sessions: Mutex::new(Sessions {
+                generation,
+                pre,
+                by_key: Default::default(),
+            }),
+        })
+    }
+
+    /// Returns the instance pool of the request's session, or `None` if the
+    /// request has no session key or there are too many sessions.
+    ///
+    /// If the instance builder prepared for the request is of a newer version
+    /// of the component, the sessions of the old version are dropped.
+    pub fn handler(
+        &self,
+        headers: &HeaderMap,
+        instance_builder: &TriggerInstanceBuilder<F>,
+    ) -> Option<Arc<ProxyHandler<HttpHandlerState<F>>>> {
+        let key = self.key.of(headers)?;
+        let now = Instant::now();
+        let mut sessions = self.sessions.lock().unwrap();
+        if instance_builder.component_generation() > sessions.generation {
+            // The swap validated that the new version has the same exports.
+            let pre = ServicePre::new(instance_builder.instance_pre().clone()).ok()?;
+            *sessions = Sessions {
+                generation: instance_builder.component_generation(),
+                pre,
+                by_key: Default::default(),
+            };
+        }
+        let Sessions { pre, by_key, .. } = &mut *sessions;
+        if !by_key.contains_key(key) {
+            by_key.retain(|_, session| now.duration_since(session.last_used) < self.idle_timeout);
+            if by_key.len() >= MAX_SESSIONS {
+                tracing::debug!(
+                    "Too many sessions for component {}; using the shared instance pool",
+                    self.component_id
+                );
+                return None;
+            }
+        }
+        let session = by_key.entry(key.to_owned()).or_insert_with(|| Session {
+            handler: Arc::new(ProxyHandler::new(
+                HttpHandlerState::new(
+                    self.trigger_app.clone(),
+                    self.component_id.clone(),
+                    self.reuse_config,
+                ),
+                ProxyPre::P3(pre.clone()),
+            )),
+            last_used: now,
+        });
+        session.last_used = now;
+        Some(session.handler.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use http::header;
+
+    use super::*;
+
+    #[test]
+    fn reads_session_keys() {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::COOKIE, "theme=dark; sid=abc".parse().unwrap());
+        headers.insert("x-session-id", "def".parse().unwrap());
+        headers.insert("x-empty", "".parse().unwrap());
+
+        let cookie = SessionKey::from_config(&SessionAffinity {
+            cookie: Some("sid".into()),
+            ..Default::default()
+        })
+        .unwrap();
+        assert_eq!(Some("abc"), cookie.of(&headers));
+
+        let header = SessionKey::from_config(&SessionAffinity {
+            header: Some("X-Session-ID".into()),
+            ..Default::default()
+        })
+        .unwrap();
+        assert_eq!(Some("def"), header.of(&headers));
+
+        let empty = SessionKey::Header(HeaderName::from_static("x-empty"));
+        assert_eq!(None, empty.of(&headers));
+        assert_eq!(None, cookie.of(&HeaderMap::new()));
+    }
+
+    #[test]
+    fn affinity_needs_one_source() {
+        assert!(SessionKey::from_config(&SessionAffinity::default()).is_err());
+        assert!(
+            SessionKey::from_config(&SessionAffinity {
+                cookie: Some("sid".into()),
+                header: Some("x-session-id".into()),
+                ..Default::default()
+            })
+            .is_err()
+        );
+    }
+}
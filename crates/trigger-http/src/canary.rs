@@ -0,0 +1,172 @@
+//! Canary routing between two versions of a route's component.
+//!
+//! A route can name a canary version of its component in its `components`
+//! table and send it a percentage of the route's requests:
+//!
+//! ```toml
+//! [[trigger.http]]
+//! route = "/api/..."
+//! component = "api-v1"
+//! components = { canary = "api-v2" }
+//! canary = { weight = 10, header = "x-api-version" }
+//! ```
+//!
+//! A request whose canary header names either version is sent to that version
+//! regardless of weight. Request metrics are recorded against the version
+//! which handled the request, so the versions' error rates can be compared.
+
+use anyhow::{Context as _, bail, ensure};
+use http::{HeaderMap, HeaderName};
+use rand::Rng;
+use spin_http::config::HttpTriggerConfig;
+
+/// The key of the canary component in a route's `components` table.
+const CANARY_KEY: &str = "canary";
+
+/// The canary version of a route's component.
+#[derive(Debug)]
+pub(crate) struct Canary {
+    component: String,
+    /// The percentage of requests sent to the canary.
+    weight: u8,
+    header: Option<HeaderName>,
+}
+
+impl Canary {
+    /// Returns the canary configured for a route, if any.
+    pub fn from_config(config: &HttpTriggerConfig) -> anyhow::Result<Option<Self>> {
+        if let Some(key) = config.components.keys().find(|key| *key != CANARY_KEY) {
+            bail!("HTTP triggers only support `{CANARY_KEY}` in `components`; found {key:?}");
+        }
+        let component = match config.components.get(CANARY_KEY).map(Vec::as_slice) {
+            None => {
+                ensure!(
+                    config.canary.is_none(),
+                    "`canary` requires a canary component, set with `components = {{ {CANARY_KEY} = \"...\" }}`"
+                );
+                return Ok(None);
+            }
+            Some([component]) => component.clone(),
+            Some(_) => bail!("a route may only have one canary component"),
+        };
+        let canary = config
+            .canary
+            .as_ref()
+            .context("a canary component requires `canary = { weight = ... }`")?;
+        let Some(stable) = &config.component else {
+            bail!("a route with a canary component must also have a `component`");
+        };
+        ensure!(
+            component != *stable,
+            "the canary component must differ from the route's component"
+        );
+        ensure!(
+            canary.weight <= 100,
+            "canary weight must be a percentage from 0 to 100; got {}",
+            canary.weight
+        );
+        let header = canary
+            .header
+            .as_ref()
+            .map(|header| {
+                header
+                    .parse()
+                    .with_context(|| format!("invalid canary header {header:?}"))
+            })
+            .transpose()?;
+        Ok(Some(Self {
+            component,
+            weight: canary.weight,
+            header,
+        }))
+    }
+
+    /// The ID of the canary component.
+    pub fn component(&self) -> &str {
+        &self.component
+    }
+
+    /// Chooses the version of the route's component to handle a request.
+    pub fn choose<'a>(&'a self, stable: &'a str, headers: &HeaderMap) -> &'a str {
+        let requested = self
+            .header
+            .as_ref()
+            .and_then(|header| headers.get(header)?.to_str().ok());
+        match requested {
+            Some(id) if id == self.component => &self.component,
+            Some(id) if id == stable => stable,
+            _ if rand::rng().random_range(0..100) < self.weight => &self.component,
+            _ => stable,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(toml: toml::Table) -> HttpTriggerConfig {
+        toml.try_into().unwrap()
+    }
+
+    #[test]
+    fn routes_by_weight_and_header() {
+        let canary = Canary::from_config(&config(toml::toml! {
+            component = "v1"
+            route = "/..."
+            components = { canary = ["v2"] }
+            canary = { weight = 100, header = "x-version" }
+        }))
+        .unwrap()
+        .unwrap();
+        let mut headers = HeaderMap::new();
+        assert_eq!("v2", canary.choose("v1", &headers));
+        headers.insert("x-version", "v1".parse().unwrap());
+        assert_eq!("v1", canary.choose("v1", &headers));
+        headers.insert("x-version", "v3".parse().unwrap());
+        assert_eq!("v2", canary.choose("v1", &headers));
+
+        let canary = Canary {
+            weight: 0,
+            ..canary
+        };
+        assert_eq!("v1", canary.choose("v1", &headers));
+        headers.insert("x-version", "v2".parse().unwrap());
+        assert_eq!("v2", canary.choose("v1", &headers));
+    }
+
+    #[test]
+    fn validates_config() {
+        let no_canary = config(toml::toml! {
+            component = "v1"
+            route = "/..."
+        });
+        assert!(Canary::from_config(&no_canary).unwrap().is_none());
+
+        for invalid in [
+            toml::toml! {
+                component = "v1"
+                route = "/..."
+                canary = { weight = 10 }
+            },
+            toml::toml! {
+                component = "v1"
+                route = "/..."
+                components = { canary = ["v2"] }
+            },
+            toml::toml! {
+                component = "v1"
+                route = "/..."
+                components = { canary = ["v2"] }
+                canary = { weight = 101 }
+            },
+            toml::toml! {
+                component = "v1"
+                route = "/..."
+                components = { mirror = ["v2"] }
+            },
+        ] {
+            assert!(Canary::from_config(&config(invalid)).is_err());
+        }
+    }
+}
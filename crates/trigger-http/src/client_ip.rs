@@ -0,0 +1,191 @@
+//! Client IP addresses behind proxies, and per-route IP filtering.
+//!
+//! With `--trusted-proxies N`, the client address is taken from the
+//! `X-Forwarded-For` header: each of the `N` proxies in front of Spin vouches
+//! for the hop to its left, so the address `N` hops from the right is the
+//! client's. Fewer hops, or one which is not an address, end the walk early.
+//! The resolved address is the one routes filter on and components see.
+//!
+//! A route can allow or deny client addresses by CIDR:
+//!
+//! ```toml
+//! [[trigger.http]]
+//! route = "/admin/..."
+//! component = "admin"
+//! ip_filter = { allow = ["10.0.0.0/8"], deny = ["10.0.13.0/24"] }
+//! ```
+//!
+//! A denied address is refused even if it is also allowed; if `allow` is set,
+//! addresses it does not cover are refused too.
+
+use std::net::{IpAddr, SocketAddr};
+
+use anyhow::Context as _;
+use http::HeaderMap;
+use ip_network::IpNetwork;
+use spin_http::config::IpFilterConfig;
+
+/// The header proxies record the addresses they forward for in.
+const X_FORWARDED_FOR: &str = "x-forwarded-for";
+
+/// Returns the address of the client a request came from, trusting the
+/// given number of proxies in front of Spin.
+pub(crate) fn client_addr(
+    headers: &HeaderMap,
+    peer: SocketAddr,
+    trusted_proxies: usize,
+) -> SocketAddr {
+    if trusted_proxies == 0 {
+        return peer;
+    }
+    let hops = headers
+        .get_all(X_FORWARDED_FOR)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .collect::<Vec<_>>();
+    let mut client = peer;
+    for hop in hops.iter().rev().take(trusted_proxies) {
+        match parse_hop(hop.trim()) {
+            Some(addr) => client = addr,
+            None => break,
+        }
+    }
+    client
+}
+
+/// Parses an `X-Forwarded-For` entry, which may or may not carry a port.
+fn parse_hop(hop: &str) -> Option<SocketAddr> {
+    if let Ok(addr) = hop.parse() {
+        return Some(addr);
+    }
+    let ip = hop
+        .strip_prefix('[')
+        .and_then(|h| h.strip_suffix(']'))
+        .unwrap_or(hop);
+    ip.parse().ok().map(|ip| SocketAddr::new(ip, 0))
+}
+
+/// The client addresses a route accepts.
+#[derive(Debug)]
+pub(crate) struct IpFilter {
+    allow: Vec<IpNetwork>,
+    deny: Vec<IpNetwork>,
+}
+
+impl IpFilter {
+    pub fn from_config(config: &IpFilterConfig) -> anyhow::Result<Self> {
+        let parse = |networks: &[String]| {
+            networks
+                .iter()
+                .map(|network| parse_network(network))
+                .collect::<anyhow::Result<Vec<_>>>()
+        };
+        Ok(Self {
+            allow: parse(&config.allow)?,
+            deny: parse(&config.deny)?,
+        })
+    }
+
+    /// Whether requests from the given address may use the route.
+    pub fn permits(&self, ip: IpAddr) -> bool {
+        // Compare IPv4 clients of dual-stack listeners as IPv4.
+        let ip = match ip {
+            IpAddr::V6(v6) => v6.to_ipv4_mapped().map_or(ip, IpAddr::V4),
+            ip => ip,
+        };
+        let covers = |networks: &[IpNetwork]| networks.iter().any(|n| n.contains(ip));
+        !covers(&self.deny) && (self.allow.is_empty() || covers(&self.allow))
+    }
+}
+
+/// Parses a CIDR network, or a single address.
+fn parse_network(network: &str) -> anyhow::Result<IpNetwork> {
+    if network.contains('/') {
+        IpNetwork::from_str_truncate(network)
+            .with_context(|| format!("invalid CIDR network {network:?}"))
+    } else {
+        let ip: IpAddr = network
+            .parse()
+            .with_context(|| format!("invalid IP address {network:?}"))?;
+        Ok(IpNetwork::from(ip))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn headers(forwarded_for: &[&str]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for value in forwarded_for {
+            headers.append(X_FORWARDED_FOR, value.parse().unwrap());
+        }
+        headers
+    }
+
+    #[test]
+    fn client_addr_walks_trusted_proxies() {
+        let peer: SocketAddr = "10.0.0.1:4000".parse().unwrap();
+        let headers = headers(&["203.0.113.7, 198.51.100.2:8080", "10.0.0.9"]);
+
+        assert_eq!(peer, client_addr(&headers, peer, 0));
+        assert_eq!(
+            "10.0.0.9:0".parse::<SocketAddr>().unwrap(),
+            client_addr(&headers, peer, 1)
+        );
+        assert_eq!(
+            "198.51.100.2:8080".parse::<SocketAddr>().unwrap(),
+            client_addr(&headers, peer, 2)
+        );
+        // Hops beyond those recorded are ignored.
+        assert_eq!(
+            "203.0.113.7:0".parse::<SocketAddr>().unwrap(),
+            client_addr(&headers, peer, 5)
+        );
+        assert_eq!(peer, client_addr(&HeaderMap::new(), peer, 1));
+    }
+
+    #[test]
+    fn client_addr_stops_at_unparseable_hops() {
+        let peer: SocketAddr = "10.0.0.1:4000".parse().unwrap();
+        let headers = headers(&["203.0.113.7, unknown, [2001:db8::1]"]);
+        assert_eq!(
+            "[2001:db8::1]:0".parse::<SocketAddr>().unwrap(),
+            client_addr(&headers, peer, 3)
+        );
+    }
+
+    #[test]
+    fn filter_denies_before_allowing() -> anyhow::Result<()> {
+        let filter = IpFilter::from_config(&IpFilterConfig {
+            allow: vec!["10.0.0.0/8".into(), "2001:db8::/32".into()],
+            deny: vec!["10.0.13.0/24".into(), "10.0.0.1".into()],
+        })?;
+        assert!(filter.permits("10.1.2.3".parse()?));
+        assert!(filter.permits("::ffff:10.1.2.3".parse()?));
+        assert!(filter.permits("2001:db8::5".parse()?));
+        assert!(!filter.permits("10.0.13.40".parse()?));
+        assert!(!filter.permits("10.0.0.1".parse()?));
+        assert!(!filter.permits("192.0.2.1".parse()?));
+        Ok(())
+    }
+
+    #[test]
+    fn filter_without_allow_list_allows_all_but_denied() -> anyhow::Result<()> {
+        let filter = IpFilter::from_config(&IpFilterConfig {
+            allow: vec![],
+            deny: vec!["192.0.2.0/24".into()],
+        })?;
+        assert!(filter.permits("198.51.100.1".parse()?));
+        assert!(!filter.permits("192.0.2.9".parse()?));
+        assert!(
+            IpFilter::from_config(&IpFilterConfig {
+                allow: vec!["10.0.0.0/33".into()],
+                deny: vec![],
+            })
+            .is_err()
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,543 @@
+use std::{
+    collections::HashMap,
+    pin::Pin,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicUsize, Ordering},
+    },
+    task::{Context, Poll},
+};
+
+use anyhow::bail;
+use http::StatusCode;
+use http_body::{Frame, SizeHint};
+use hyper::body::Bytes;
+use spin_http::config::ConcurrencyLimit;
+use tokio::sync::{Notify, OwnedSemaphorePermit, Semaphore};
+use wasmtime_wasi_http::p2::bindings::http::types::ErrorCode;
+
+use crate::Body;
+
+/// Limits on the number of requests components may be handling at once.
+#[derive(Clone, Copy, Debug)]
+pub struct ConcurrencyLimitConfig {
+    /// The maximum number of requests a component may be handling at once,
+    /// unless its route sets its own limit.
+    ///
+    /// `None` means there is no limit.
+    pub max_in_flight: Option<usize>,
+    /// The maximum number of requests which may wait for a free slot once
+    /// `max_in_flight` has been reached. Requests beyond this are shed.
+    pub max_queued: usize,
+    /// The maximum number of requests all components of the app may be
+    /// handling at once.
+    ///
+    /// `None` means there is no limit.
+    pub max_app_in_flight: Option<usize>,
+    /// The maximum number of requests which may wait for a free slot once
+    /// `max_app_in_flight` has been reached. Requests beyond this are shed.
+    pub max_app_queued: usize,
+    /// The status code returned for shed requests.
+    pub shed_status: StatusCode,
+}
+
+impl Default for ConcurrencyLimitConfig {
+    fn default() -> Self {
+        Self {
+            max_in_flight: None,
+            max_queued: 0,
+            max_app_in_flight: None,
+            max_app_queued: 0,
+            shed_status: StatusCode::SERVICE_UNAVAILABLE,
+        }
+    }
+}
+
+impl ConcurrencyLimitConfig {
+    /// Builds a limiter for each of the given components which has an
+    /// in-flight limit, either its own or the default from this config.
+    pub(crate) fn limiters<'a>(
+        &self,
+        components: impl IntoIterator<Item = (&'a str, Option<&'a ConcurrencyLimit>)>,
+    ) -> anyhow::Result<HashMap<String, ComponentLimiter>> {
+        let mut limiters = HashMap::new();
+        for (component_id, limit) in components {
+            let (max_in_flight, max_queued) = match limit {
+                Some(limit) => (limit.max_in_flight, limit.max_queued_requests),
+                None => match self.max_in_flight {
+                    Some(max_in_flight) => (max_in_flight, self.max_queued),
+                    None => continue,
+                },
+            };
+            if max_in_flight == 0 {
+                bail!("component {component_id:?} has a concurrency limit of 0 requests in flight");
+            }
+            limiters.insert(
+                component_id.to_owned(),
+                ComponentLimiter::new(component_id.to_owned(), max_in_flight, max_queued),
+            );
+        }
+        Ok(limiters)
+    }
+
+    /// Builds the app-wide limiter, if an app-wide in-flight limit is configured.
+    pub(crate) fn app_limiter(&self) -> anyhow::Result<Option<Arc<AppLimiter>>> {
+        match self.max_app_in_flight {
+            Some(0) => bail!("the app concurrency limit must allow at least 1 request in flight"),
+            Some(max_in_flight) => Ok(Some(Arc::new(AppLimiter::new(
+                max_in_flight,
+                self.max_app_queued,
+            )))),
+            None => Ok(None),
+        }
+    }
+}
+
+/// Tracks in-flight and queued requests for a single component.
+pub(crate) struct ComponentLimiter {
+    component_id: String,
+    in_flight: Arc<Semaphore>,
+    queued: AtomicUsize,
+    max_queued: usize,
+}
+
+impl ComponentLimiter {
+    fn new(component_id: String, max_in_flight: usize, max_queued: usize) -> Self {
+        Self {
+            component_id,
+            in_flight: Arc::new(Semaphore::new(max_in_flight)),
+            queued: AtomicUsize::new(0),
+            max_queued,
+        }
+    }
+
+    /// Waits for an in-flight slot, queueing if none is free.
+    ///
+    /// Returns `None` if the queue is full and the request should be shed.
+    pub(crate) async fn acquire(&self) -> Option<OwnedSemaphorePermit> {
+        if let Ok(permit) = self.in_flight.clone().try_acquire_owned() {
+            return Some(permit);
+        }
+
+        let _slot = QueueSlot::try_new(&self.queued, self.max_queued, &self.component_id)?;
+        self.in_flight.clone().acquire_owned().await.ok()
+    }
+}
+
+/// A reserved place in a component's request queue, released on drop so
+/// that cancelled requests don't leak queue capacity.
+struct QueueSlot<'a> {
+    queued: &'a AtomicUsize,
+    component_id: &'a str,
+}
+
+impl<'a> QueueSlot<'a> {
+    fn try_new(queued: &'a AtomicUsize, max_queued: usize, component_id: &'a str) -> Option<Self> {
+        queued
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |n| {
+                (n < max_queued).then_some(n + 1)
+            })
+            .ok()?;
+        record_queue_depth(1, component_id, "component");
+        Some(Self {
+            queued,
+            component_id,
+        })
+    }
+}
+
+impl Drop for QueueSlot<'_> {
+    fn drop(&mut self) {
+        self.queued.fetch_sub(1, Ordering::AcqRel);
+        record_queue_depth(-1, self.component_id, "component");
+    }
+}
+
+/// Records a change in the number of requests waiting for an in-flight slot
+/// of the given limit, either "component" or "app".
+fn record_queue_depth(change: i64, component_id: &str, limit: &'static str) {
+    spin_telemetry::metrics::counter!(
+        spin.request_queue_depth = change,
+        trigger_type = "http",
+        component_id = component_id,
+        limit = limit
+    );
+}
+
+/// Tracks in-flight and queued requests across all components of an app.
+///
+/// When the app is at capacity, each busy component (one with requests in
+/// flight or queued) is admitted only up to an equal share of the capacity,
+/// so that one busy component cannot starve the others.
+pub(crate) struct AppLimiter {
+    max_in_flight: usize,
+    max_queued: usize,
+    state: Mutex<AppLimiterState>,
+    released: Notify,
+}
+
+#[derive(Default)]
+struct AppLimiterState {
+    in_flight: usize,
+    queued: usize,
+    // Component ID -> load
+    components: HashMap<String, ComponentLoad>,
+}
+
+#[derive(Default)]
+struct ComponentLoad {
+    in_flight: usize,
+    queued: usize,
+}
+
+impl AppLimiterState {
+    fn can_admit(&self, component_id: &str, max_in_flight: usize) -> bool {
+        if self.in_flight >= max_in_flight {
+            return false;
+        }
+        // Capacity is only shared out while other components are waiting for it
+        let others_waiting = self
+            .components
+            .iter()
+            .any(|(id, load)| id != component_id && load.queued > 0);
+        if !others_waiting {
+            return true;
+        }
+        let busy = self
+            .components
+            .iter()
+            .filter(|(id, load)| load.in_flight + load.queued > 0 || *id == component_id)
+            .count()
+            .max(1);
+        let in_flight = self
+            .components
+            .get(component_id)
+            .map_or(0, |load| load.in_flight);
+        in_flight < max_in_flight.div_ceil(busy)
+    }
+}
+
+impl AppLimiter {
+    fn new(max_in_flight: usize, max_queued: usize) -> Self {
+        Self {
+            max_in_flight,
+            max_queued,
+            state: Default::default(),
+            released: Notify::new(),
+        }
+    }
+
+    /// Waits for an in-flight slot for the given component, queueing if none
+    /// is free.
+    ///
+    /// Returns `None` if the queue is full and the request should be shed.
+    pub(crate) async fn acquire(self: &Arc<Self>, component_id: &str) -> Option<AppPermit> {
+        let mut entry: Option<AppQueueEntry> = None;
+        loop {
+            let released = {
+                let mut state = self.state.lock().unwrap();
+                if state.can_admit(component_id, self.max_in_flight) {
+                    if let Some(mut entry) = entry.take() {
+                        entry.dequeue(&mut state);
+                    }
+                    state.in_flight += 1;
+                    state
+                        .components
+                        .entry(component_id.to_owned())
+                        .or_default()
+                        .in_flight += 1;
+                    return Some(AppPermit {
+                        limiter: self.clone(),
+                        component_id: component_id.to_owned(),
+                    });
+                }
+                if entry.is_none() {
+                    if state.queued >= self.max_queued {
+                        return None;
+                    }
+                    state.queued += 1;
+                    state
+                        .components
+                        .entry(component_id.to_owned())
+                        .or_default()
+                        .queued += 1;
+                    record_queue_depth(1, component_id, "app");
+                    entry = Some(AppQueueEntry {
+                        limiter: self,
+                        component_id,
+                        queued: true,
+                    });
+                }
+                // Created while holding the lock so that no release is missed
+                self.released.notified()
+            };
+            released.await;
+        }
+    }
+}
+
+/// A held in-flight slot in an [`AppLimiter`], released on drop.
+pub(crate) struct AppPermit {
+    limiter: Arc<AppLimiter>,
+    component_id: String,
+}
+
+impl Drop for AppPermit {
+    fn drop(&mut self) {
+        {
+            let mut state = self.limiter.state.lock().unwrap();
+            state.in_flight -= 1;
+            if let Some(load) = state.components.get_mut(&self.component_id) {
+                load.in_flight -= 1;
+            }
+        }
+        self.limiter.released.notify_waiters();
+    }
+}
+
+/// The concurrency limiter slots held by a request.
+#[derive(Default)]
+pub(crate) struct RequestPermits {
+    pub component: Option<OwnedSemaphorePermit>,
+    pub app: Option<AppPermit>,
+}
+
+impl RequestPermits {
+    /// Wraps a response body so that the slots are held until the body has
+    /// been sent in full (or abandoned), rather than only until the
+    /// component has produced the response headers.
+    pub(crate) fn hold_until_sent(self, body: Body) -> Body {
+        if self.component.is_none() && self.app.is_none() {
+            return body;
+        }
+        Body::new(PermitBody {
+            body,
+            _permits: self,
+        })
+    }
+}
+
+/// A response body holding its request's concurrency limiter slots, which
+/// are released when the body is dropped.
+struct PermitBody {
+    body: Body,
+    _permits: RequestPermits,
+}
+
+impl http_body::Body for PermitBody {
+    type Data = Bytes;
+    type Error = ErrorCode;
+
+    fn poll_frame(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+        Pin::new(&mut self.body).poll_frame(cx)
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.body.is_end_stream()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        self.body.size_hint()
+    }
+}
+
+/// A queued request in an [`AppLimiter`], removed from the queue on drop so
+/// that cancelled requests don't leak queue capacity.
+struct AppQueueEntry<'a> {
+    limiter: &'a AppLimiter,
+    component_id: &'a str,
+    queued: bool,
+}
+
+impl AppQueueEntry<'_> {
+    fn dequeue(&mut self, state: &mut AppLimiterState) {
+        if std::mem::take(&mut self.queued) {
+            state.queued -= 1;
+            if let Some(load) = state.components.get_mut(self.component_id) {
+                load.queued -= 1;
+            }
+            record_queue_depth(-1, self.component_id, "app");
+        }
+    }
+}
+
+impl Drop for AppQueueEntry<'_> {
+    fn drop(&mut self) {
+        if self.queued {
+            let limiter = self.limiter;
+            self.dequeue(&mut limiter.state.lock().unwrap());
+            // The set of busy components may have changed
+            limiter.released.notify_waiters();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn sheds_when_queue_is_full() {
+        let limiter = ComponentLimiter::new("a".into(), 1, 0);
+        let permit = limiter.acquire().await;
+        assert!(permit.is_some());
+        assert!(limiter.acquire().await.is_none());
+        drop(permit);
+        assert!(limiter.acquire().await.is_some());
+    }
+
+    #[tokio::test]
+    async fn queued_request_runs_when_slot_frees() {
+        let limiter = Arc::new(ComponentLimiter::new("a".into(), 1, 1));
+        let permit = limiter.acquire().await.unwrap();
+
+        let waiter = tokio::spawn({
+            let limiter = limiter.clone();
+            async move { limiter.acquire().await.is_some() }
+        });
+        while limiter.queued.load(Ordering::Acquire) == 0 {
+            tokio::task::yield_now().await;
+        }
+        // The queue is now full so a third request is shed.
+        assert!(limiter.acquire().await.is_none());
+
+        drop(permit);
+        assert!(waiter.await.unwrap());
+        assert_eq!(limiter.queued.load(Ordering::Acquire), 0);
+    }
+
+    #[tokio::test]
+    async fn app_limiter_sheds_when_queue_is_full() {
+        let limiter = Arc::new(AppLimiter::new(1, 0));
+        let permit = limiter.acquire("a").await;
+        assert!(permit.is_some());
+        assert!(limiter.acquire("b").await.is_none());
+        drop(permit);
+        assert!(limiter.acquire("b").await.is_some());
+    }
+
+    #[tokio::test]
+    async fn app_limiter_shares_capacity_between_busy_components() {
+        let limiter = Arc::new(AppLimiter::new(2, 2));
+        let a1 = limiter.acquire("a").await.unwrap();
+        let a2 = limiter.acquire("a").await.unwrap();
+
+        // Queue a request for each component while the app is at capacity.
+        let waiter = |id: &'static str| {
+            let limiter = limiter.clone();
+            tokio::spawn(async move { limiter.acquire(id).await })
+        };
+        let a3 = waiter("a");
+        let b1 = waiter("b");
+        while limiter.state.lock().unwrap().queued < 2 {
+            tokio::task::yield_now().await;
+        }
+
+        // With two busy components, "a" is already over its share, so the
+        // freed slot goes to "b".
+        drop(a1);
+        let b1 = b1.await.unwrap().unwrap();
+        assert!(!a3.is_finished());
+
+        drop(a2);
+        let a3 = a3.await.unwrap().unwrap();
+        drop((a3, b1));
+        assert_eq!(limiter.state.lock().unwrap().in_flight, 0);
+    }
+
+    #[tokio::test]
+    async fn app_limiter_admits_over_share_while_no_one_waits() {
+        let limiter = Arc::new(AppLimiter::new(2, 0));
+        let _b = limiter.acquire("b").await.unwrap();
+        // "b" is busy, but with nothing queued "a" may take the free slot.
+        let _a = limiter.acquire("a").await.unwrap();
+        assert_eq!(limiter.state.lock().unwrap().in_flight, 2);
+    }
+
+    #[tokio::test]
+    async fn streamed_response_holds_app_slot_until_sent() {
+        use http_body_util::{BodyExt, StreamBody};
+
+        let limiter = Arc::new(AppLimiter::new(2, 2));
+        let permits = |permit| RequestPermits {
+            component: None,
+            app: Some(permit),
+        };
+        let streamed_body = |permit| {
+            let (tx, mut rx) = tokio::sync::mpsc::channel::<Result<Frame<Bytes>, ErrorCode>>(1);
+            let body = Body::new(StreamBody::new(futures::stream::poll_fn(move |cx| {
+                rx.poll_recv(cx)
+            })));
+            (tx, permits(permit).hold_until_sent(body))
+        };
+        let (a1_tx, mut a1_body) = streamed_body(limiter.acquire("a").await.unwrap());
+        let (_a2_tx, _a2_body) = streamed_body(limiter.acquire("a").await.unwrap());
+
+        let waiter = |id: &'static str| {
+            let limiter = limiter.clone();
+            tokio::spawn(async move { limiter.acquire(id).await })
+        };
+        let a3 = waiter("a");
+        let b1 = waiter("b");
+        while limiter.state.lock().unwrap().queued < 2 {
+            tokio::task::yield_now().await;
+        }
+
+        // The headers have long been sent, but the bodies are still streaming.
+        a1_tx.send(Ok(Frame::data(Bytes::from("x")))).await.unwrap();
+        a1_body.frame().await.unwrap().unwrap();
+        tokio::task::yield_now().await;
+        assert!(!b1.is_finished());
+
+        // Once a body has been sent, its slot goes to the component below its
+        // share.
+        drop(a1_tx);
+        assert!(a1_body.frame().await.is_none());
+        drop(a1_body);
+        let _b1 = b1.await.unwrap().unwrap();
+        assert!(!a3.is_finished());
+        a3.abort();
+    }
+
+    #[test]
+    fn routes_override_default_component_limit() {
+        let config = ConcurrencyLimitConfig {
+            max_in_flight: Some(4),
+            ..Default::default()
+        };
+        let limit = ConcurrencyLimit {
+            max_in_flight: 1,
+            max_queued_requests: 2,
+        };
+        let limiters = config.limiters([("a", None), ("b", Some(&limit))]).unwrap();
+        assert_eq!(limiters["a"].in_flight.available_permits(), 4);
+        assert_eq!(limiters["b"].in_flight.available_permits(), 1);
+        assert_eq!(limiters["b"].max_queued, 2);
+
+        // Without a default, only routes with their own limit are limited
+        let limiters = ConcurrencyLimitConfig::default()
+            .limiters([("a", None), ("b", Some(&limit))])
+            .unwrap();
+        assert!(!limiters.contains_key("a"));
+        assert!(limiters.contains_key("b"));
+    }
+
+    #[test]
+    fn zero_limits_are_rejected() {
+        let limit = ConcurrencyLimit {
+            max_in_flight: 0,
+            max_queued_requests: 0,
+        };
+        let config = ConcurrencyLimitConfig::default();
+        assert!(config.limiters([("a", Some(&limit))]).is_err());
+
+        let config = ConcurrencyLimitConfig {
+            max_app_in_flight: Some(0),
+            ..Default::default()
+        };
+        assert!(config.app_limiter().is_err());
+    }
+}
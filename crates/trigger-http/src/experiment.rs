@@ -0,0 +1,181 @@
+//! A/B experiments on HTTP routes.
+//!
+//! A route can split its requests between the named variants of an
+//! experiment:
+//!
+//! ```toml
+//! [[trigger.http]]
+//! route = "/checkout"
+//! component = "checkout"
+//! experiment = { name = "one-click", cookie = "uid", variants = { control = 90, one-click = 10 } }
+//! ```
+//!
+//! A request is assigned to a bucket by hashing the experiment's name with the
+//! key in the request's cookie or header, and the buckets are divided between
+//! the variants by weight, so the same key is always assigned the same
+//! variant. Requests without a key are assigned at random. The component is
+//! told the assignment in the `spin-experiment-name` and
+//! `spin-experiment-variant` headers.
+
+use anyhow::{Context as _, ensure};
+use http::{HeaderMap, HeaderValue};
+use rand::Rng;
+use ring::digest;
+use spin_http::config::ExperimentConfig;
+
+use crate::affinity::SessionKey;
+
+/// The prefix of the headers the runtime passes experiment assignments in.
+pub(crate) const EXPERIMENT_HEADER_PREFIX: &str = "spin-experiment-";
+/// The header holding the name of the experiment.
+const NAME_HEADER: &str = "spin-experiment-name";
+/// The header holding the variant the request was assigned.
+const VARIANT_HEADER: &str = "spin-experiment-variant";
+
+/// An A/B experiment on a route.
+#[derive(Debug)]
+pub(crate) struct Experiment {
+    name: HeaderValue,
+    key: Option<SessionKey>,
+    /// The variants and their weights, in configuration order.
+    variants: Vec<(HeaderValue, u64)>,
+    total_weight: u64,
+}
+
+impl Experiment {
+    pub fn from_config(config: &ExperimentConfig) -> anyhow::Result<Self> {
+        let name = HeaderValue::from_str(&config.name)
+            .with_context(|| format!("invalid experiment name {:?}", config.name))?;
+        let key = SessionKey::new(config.cookie.as_deref(), config.header.as_deref())?;
+        let variants = config
+            .variants
+            .iter()
+            .map(|(variant, weight)| {
+                let variant = HeaderValue::from_str(variant)
+                    .with_context(|| format!("invalid experiment variant {variant:?}"))?;
+                Ok((variant, u64::from(*weight)))
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        ensure!(
+            !variants.is_empty(),
+            "experiment {:?} has no variants",
+            config.name
+        );
+        let total_weight = variants.iter().map(|(_, weight)| weight).sum();
+        ensure!(
+            total_weight > 0,
+            "experiment {:?} must give at least one variant a weight",
+            config.name
+        );
+        Ok(Self {
+            name,
+            key,
+            variants,
+            total_weight,
+        })
+    }
+
+    /// Assigns a request to a variant, and tells the component which.
+    pub fn assign(&self, headers: &mut HeaderMap) {
+        let bucket = match self.key.as_ref().and_then(|key| key.of(headers)) {
+            Some(key) => self.bucket(key),
+            None => rand::rng().random_range(0..self.total_weight),
+        };
+        let variant = self.variant(bucket).clone();
+        headers.insert(NAME_HEADER, self.name.clone());
+        headers.insert(VARIANT_HEADER, variant);
+    }
+
+    /// Returns the bucket a key is assigned to, from `0..total_weight`.
+    fn bucket(&self, key: &str) -> u64 {
+        let mut context = digest::Context::new(&digest::SHA256);
+        context.update(self.name.as_bytes());
+        context.update(b"\0");
+        context.update(key.as_bytes());
+        let hash = context.finish();
+        let prefix = hash.as_ref()[..8].try_into().unwrap();
+        u64::from_be_bytes(prefix) % self.total_weight
+    }
+
+    fn variant(&self, mut bucket: u64) -> &HeaderValue {
+        for (variant, weight) in &self.variants {
+            if bucket < *weight {
+                return variant;
+            }
+            bucket -= weight;
+        }
+        unreachable!("bucket should be less than the total weight")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use http::header;
+
+    use super::*;
+
+    fn experiment(variants: &[(&str, u32)]) -> Experiment {
+        Experiment::from_config(&ExperimentConfig {
+            name: "test".into(),
+            cookie: Some("uid".into()),
+            header: None,
+            variants: variants.iter().map(|(v, w)| (v.to_string(), *w)).collect(),
+        })
+        .unwrap()
+    }
+
+    fn assign(experiment: &Experiment, uid: &str) -> String {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::COOKIE, format!("uid={uid}").parse().unwrap());
+        experiment.assign(&mut headers);
+        assert_eq!("test", headers[NAME_HEADER]);
+        headers[VARIANT_HEADER].to_str().unwrap().to_owned()
+    }
+
+    #[test]
+    fn assigns_keys_consistently_by_weight() {
+        let weighted = experiment(&[("a", 1), ("b", 3)]);
+        let mut b_count = 0;
+        for uid in 0..1000 {
+            let variant = assign(&weighted, &uid.to_string());
+            assert_eq!(variant, assign(&weighted, &uid.to_string()));
+            if variant == "b" {
+                b_count += 1;
+            }
+        }
+        assert!((650..850).contains(&b_count), "b assigned {b_count} times");
+
+        let unweighted_a = experiment(&[("a", 0), ("b", 1)]);
+        assert_eq!("b", assign(&unweighted_a, "anyone"));
+    }
+
+    #[test]
+    fn assigns_requests_without_keys() {
+        let single = experiment(&[("only", 1)]);
+        let mut headers = HeaderMap::new();
+        single.assign(&mut headers);
+        assert_eq!("only", headers[VARIANT_HEADER]);
+    }
+
+    #[test]
+    fn validates_config() {
+        assert!(
+            Experiment::from_config(&ExperimentConfig {
+                name: "test".into(),
+                cookie: None,
+                header: None,
+                variants: Default::default(),
+            })
+            .is_err()
+        );
+        assert!(
+            Experiment::from_config(&ExperimentConfig {
+                name: "test".into(),
+                cookie: None,
+                header: None,
+                variants: [("a".to_owned(), 0)].into_iter().collect(),
+            })
+            .is_err()
+        );
+    }
+}
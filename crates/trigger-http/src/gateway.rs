@@ -0,0 +1,298 @@
+//! Serving several apps from one listener.
+//!
+//! A [`Gateway`] routes each request to one of several apps by the request's
+//! host and path. Each app has its own [`HttpServer`], and so its own engine
+//! and factors, and can be replaced or removed while the gateway runs without
+//! affecting requests to the others.
+
+use std::{
+    net::SocketAddr,
+    sync::{Arc, RwLock},
+};
+
+use anyhow::{Context as _, bail, ensure};
+use http::{Request, Response, StatusCode, Uri, header, uri::Authority, uri::Scheme};
+use hyper::{body::Incoming, service::service_fn};
+use hyper_util::{
+    rt::{TokioExecutor, TokioIo},
+    server::conn::auto::Builder,
+};
+use spin_factors::RuntimeFactors;
+use spin_http::body;
+use tokio::net::TcpListener;
+use wasmtime_wasi_http::p2::body::HyperOutgoingBody;
+
+use crate::HttpServer;
+
+/// Which requests a gateway sends to an app.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GatewayRoute {
+    /// The hosts the app serves, in lowercase, or empty to serve any host.
+    hosts: Vec<String>,
+    /// The path prefix the app is mounted at, without a trailing slash; empty
+    /// to mount the app at the root.
+    path_prefix: String,
+}
+
+impl GatewayRoute {
+    /// Creates a route for requests to any of the given hosts, or any host if
+    /// none are given, whose paths start with the given prefix.
+    ///
+    /// The prefix is removed from the path of requests before the app routes
+    /// them, so an app mounted at `/blog` sees a request for `/blog/posts` as
+    /// a request for `/posts`.
+    pub fn new(hosts: Vec<String>, path_prefix: &str) -> anyhow::Result<Self> {
+        ensure!(
+            path_prefix.starts_with('/'),
+            "path prefix {path_prefix:?} must start with `/`"
+        );
+        let hosts = hosts
+            .into_iter()
+            .map(|host| {
+                let authority = host
+                    .parse::<Authority>()
+                    .with_context(|| format!("invalid host {host:?}"))?;
+                ensure!(
+                    authority.port().is_none(),
+                    "host {host:?} must not have a port"
+                );
+                Ok(authority.host().to_ascii_lowercase())
+            })
+            .collect::<anyhow::Result<_>>()?;
+        Ok(Self {
+            hosts,
+            path_prefix: path_prefix.trim_end_matches('/').to_owned(),
+        })
+    }
+
+    /// Returns how specifically the route matches a request, if it does:
+    /// routes for named hosts are preferred to routes for any host, then
+    /// longer path prefixes to shorter ones.
+    fn specificity(&self, host: Option<&str>, path: &str) -> Option<(bool, usize)> {
+        let named_host = !self.hosts.is_empty();
+        if named_host && !host.is_some_and(|host| self.hosts.iter().any(|h| h == host)) {
+            return None;
+        }
+        let rest = path.strip_prefix(&self.path_prefix)?;
+        if !(rest.is_empty() || rest.starts_with('/')) {
+            return None;
+        }
+        Some((named_host, self.path_prefix.len()))
+    }
+
+    /// Returns the path of a request as the app sees it.
+    fn app_path<'a>(&self, path: &'a str) -> &'a str {
+        match &path[self.path_prefix.len()..] {
+            "" => "/",
+            rest => rest,
+        }
+    }
+
+    /// Whether some request could match both routes equally well.
+    fn overlaps(&self, other: &Self) -> bool {
+        let hosts_overlap = match (self.hosts.is_empty(), other.hosts.is_empty()) {
+            (true, true) => true,
+            (false, false) => self.hosts.iter().any(|host| other.hosts.contains(host)),
+            _ => false,
+        };
+        hosts_overlap && self.path_prefix == other.path_prefix
+    }
+}
+
+struct GatewayApp<F: RuntimeFactors> {
+    name: String,
+    route: GatewayRoute,
+    server: Arc<HttpServer<F>>,
+}
+
+/// Routes requests to several apps by host and path.
+pub struct Gateway<F: RuntimeFactors> {
+    listen_addr: SocketAddr,
+    apps: RwLock<Vec<Arc<GatewayApp<F>>>>,
+}
+
+impl<F: RuntimeFactors> Gateway<F> {
+    /// Creates a gateway, with no apps, which listens on the given address.
+    pub fn new(listen_addr: SocketAddr) -> Self {
+        Self {
+            listen_addr,
+            apps: Default::default(),
+        }
+    }
+
+    /// The address the gateway listens on.
+    pub fn listen_addr(&self) -> SocketAddr {
+        self.listen_addr
+    }
+
+    /// Adds an app, replacing any app of the same name.
+    ///
+    /// Requests already being handled by a replaced app are unaffected.
+    pub fn insert_app(
+        &self,
+        name: &str,
+        route: GatewayRoute,
+        server: Arc<HttpServer<F>>,
+    ) -> anyhow::Result<()> {
+        let mut apps = self.apps.write().unwrap();
+        if let Some(other) = apps
+            .iter()
+            .find(|app| app.name != name && app.route.overlaps(&route))
+        {
+            bail!(
+                "app {name:?} has the same hosts and path prefix as app {:?}",
+                other.name
+            );
+        }
+        let app = Arc::new(GatewayApp {
+            name: name.to_owned(),
+            route,
+            server,
+        });
+        match apps.iter_mut().find(|app| app.name == name) {
+            Some(existing) => *existing = app,
+            None => apps.push(app),
+        }
+        Ok(())
+    }
+
+    /// Removes an app, returning whether there was one of that name.
+    pub fn remove_app(&self, name: &str) -> bool {
+        let mut apps = self.apps.write().unwrap();
+        let count = apps.len();
+        apps.retain(|app| app.name != name);
+        apps.len() != count
+    }
+
+    /// The names of the apps the gateway routes to.
+    pub fn app_names(&self) -> Vec<String> {
+        let apps = self.apps.read().unwrap();
+        apps.iter().map(|app| app.name.clone()).collect()
+    }
+
+    /// Serves requests until an error occurs.
+    pub async fn serve(self: Arc<Self>) -> anyhow::Result<()> {
+        let listener = TcpListener::bind(self.listen_addr)
+            .await
+            .with_context(|| format!("Unable to listen on {}", self.listen_addr))?;
+        loop {
+            let (stream, client_addr) = listener.accept().await?;
+            let gateway = self.clone();
+            tokio::task::spawn(async move {
+                if let Err(err) = Builder::new(TokioExecutor::new())
+                    .serve_connection(
+                        TokioIo::new(stream),
+                        service_fn(move |req| gateway.clone().handle(req, client_addr)),
+                    )
+                    .await
+                {
+                    tracing::warn!("Error serving HTTP connection: {err:?}");
+                }
+            });
+        }
+    }
+
+    async fn handle(
+        self: Arc<Self>,
+        mut req: Request<Incoming>,
+        client_addr: SocketAddr,
+    ) -> anyhow::Result<Response<HyperOutgoingBody>> {
+        let host = request_host(&req);
+        let path = req.uri().path().to_owned();
+        let Some(app) = self.route(host.as_deref(), &path) else {
+            tracing::info!("Request to {host:?} {path:?} matched no app");
+            return Ok(Response::builder()
+                .status(StatusCode::NOT_FOUND)
+                .body(body::empty())?);
+        };
+        let app_path = app.route.app_path(&path);
+        if app_path != path {
+            let mut parts = req.uri().clone().into_parts();
+            let path_and_query = match req.uri().query() {
+                Some(query) => format!("{app_path}?{query}"),
+                None => app_path.to_owned(),
+            };
+            parts.path_and_query = Some(path_and_query.parse()?);
+            *req.uri_mut() = Uri::from_parts(parts)?;
+        }
+        app.server
+            .clone()
+            .instrumented_service_fn(Scheme::HTTP, client_addr, req)
+            .await
+    }
+
+    /// Returns the app which most specifically matches a request.
+    fn route(&self, host: Option<&str>, path: &str) -> Option<Arc<GatewayApp<F>>> {
+        let apps = self.apps.read().unwrap();
+        apps.iter()
+            .filter_map(|app| Some((app.route.specificity(host, path)?, app)))
+            .max_by_key(|(specificity, _)| *specificity)
+            .map(|(_, app)| app.clone())
+    }
+}
+
+/// Returns the host a request was made to, in lowercase and without a port.
+fn request_host<B>(req: &Request<B>) -> Option<String> {
+    let authority = match req.headers().get(header::HOST) {
+        Some(host) => host.to_str().ok()?.parse::<Authority>().ok()?,
+        None => req.uri().authority()?.clone(),
+    };
+    Some(authority.host().to_ascii_lowercase())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn route(hosts: &[&str], path_prefix: &str) -> GatewayRoute {
+        GatewayRoute::new(hosts.iter().map(|h| h.to_string()).collect(), path_prefix).unwrap()
+    }
+
+    #[test]
+    fn routes_match_hosts_and_path_prefixes() {
+        let blog = route(&["Blog.example.com"], "/");
+        assert_eq!(
+            Some((true, 0)),
+            blog.specificity(Some("blog.example.com"), "/posts")
+        );
+        assert_eq!(None, blog.specificity(Some("shop.example.com"), "/posts"));
+        assert_eq!(None, blog.specificity(None, "/posts"));
+
+        let api = route(&[], "/api/");
+        assert_eq!(Some((false, 4)), api.specificity(None, "/api"));
+        assert_eq!(Some((false, 4)), api.specificity(Some("any"), "/api/users"));
+        assert_eq!(None, api.specificity(None, "/apis"));
+        assert_eq!("/", api.app_path("/api"));
+        assert_eq!("/users", api.app_path("/api/users"));
+        assert_eq!("/posts", blog.app_path("/posts"));
+    }
+
+    #[test]
+    fn routes_must_be_valid() {
+        assert!(GatewayRoute::new(vec![], "api").is_err());
+        assert!(GatewayRoute::new(vec!["example.com:3000".into()], "/").is_err());
+        assert!(GatewayRoute::new(vec!["not a host".into()], "/").is_err());
+    }
+
+    #[test]
+    fn overlapping_routes_are_detected() {
+        assert!(route(&[], "/").overlaps(&route(&[], "/")));
+        assert!(route(&["a.com", "b.com"], "/x").overlaps(&route(&["b.com"], "/x/")));
+        assert!(!route(&["a.com"], "/").overlaps(&route(&[], "/")));
+        assert!(!route(&[], "/a").overlaps(&route(&[], "/b")));
+    }
+
+    #[test]
+    fn request_hosts_omit_ports() {
+        let req = Request::builder()
+            .header(header::HOST, "Example.com:3000")
+            .body(())
+            .unwrap();
+        assert_eq!(Some("example.com".into()), request_host(&req));
+        let req = Request::builder()
+            .uri("http://[::1]:3000/")
+            .body(())
+            .unwrap();
+        assert_eq!(Some("[::1]".into()), request_host(&req));
+    }
+}
@@ -88,6 +88,29 @@ pub fn strip_forbidden_headers(req: &mut Request<Body>) {
     {
         headers.remove("Host");
     }
+    strip_runtime_headers(req);
+}
+
+/// Removes the headers which only the runtime may set: the signed-in identity
+/// and experiment assignments.
+///
+/// This applies to requests chained from other components as well as to
+/// requests from clients.
+pub fn strip_runtime_headers(req: &mut Request<Body>) {
+    let headers = req.headers_mut();
+    let runtime_headers = headers
+        .keys()
+        .filter(|name| {
+            name.as_str().starts_with(crate::oidc::AUTH_HEADER_PREFIX)
+                || name
+                    .as_str()
+                    .starts_with(crate::experiment::EXPERIMENT_HEADER_PREFIX)
+        })
+        .cloned()
+        .collect::<Vec<_>>();
+    for name in runtime_headers {
+        headers.remove(name);
+    }
 }
 
 pub fn prepare_request_headers(
@@ -325,6 +348,32 @@ mod tests {
 
         assert_eq!(1, req.headers().len());
         assert!(req.headers().get("Host").is_none());
+
+        let mut req = Request::get("http://test.example.com")
+            .header("spin-experiment-variant", "treatment")
+            .header("accept", "text/plain")
+            .body(Default::default())
+            .unwrap();
+
+        strip_forbidden_headers(&mut req);
+
+        assert_eq!(1, req.headers().len());
+        assert!(req.headers().get("spin-experiment-variant").is_none());
+    }
+
+    #[test]
+    fn runtime_headers_are_removed_from_chained_requests() {
+        let mut req = Request::get("http://test.spin.internal")
+            .header("Host", "test.spin.internal")
+            .header("spin-auth-subject", "admin")
+            .header("spin-experiment-variant", "treatment")
+            .body(Default::default())
+            .unwrap();
+
+        strip_runtime_headers(&mut req);
+
+        assert_eq!(1, req.headers().len());
+        assert!(req.headers().get("Host").is_some());
     }
 
     #[test]
@@ -1,7 +1,15 @@
 //! Implementation for the Spin HTTP engine.
 
+mod acme;
+mod affinity;
+mod canary;
+mod client_ip;
+mod concurrency;
+mod experiment;
+mod gateway;
 mod headers;
 mod instrument;
+mod oidc;
 mod outbound_http;
 mod server;
 mod spin;
@@ -21,7 +29,7 @@ use std::{
 };
 
 use anyhow::{Context, bail};
-use clap::Args;
+use clap::{Args, builder::RangedU64ValueParser};
 use rand::{
     RngCore,
     distr::uniform::{SampleRange, SampleUniform},
@@ -32,6 +40,10 @@ use spin_factors::RuntimeFactors;
 use spin_trigger::Trigger;
 use wasmtime_wasi_http::p2::bindings::http::types::ErrorCode;
 
+pub use acme::{AcmeChallenge, AcmeConfig, LETS_ENCRYPT_DIRECTORY};
+pub use concurrency::ConcurrencyLimitConfig;
+pub use gateway::{Gateway, GatewayRoute};
+pub use oidc::OidcConfig;
 pub use server::HttpServer;
 
 pub use tls::TlsConfig;
@@ -74,6 +86,45 @@ pub struct CliArgs {
     #[clap(long, env = "SPIN_TLS_KEY", requires = "tls_cert")]
     pub tls_key: Option<PathBuf>,
 
+    /// Serve https with a certificate for this domain, obtained and renewed
+    /// automatically from an ACME certificate authority (Let's Encrypt by
+    /// default), whose terms of service you agree to by using this option.
+    /// May be repeated to cover several domains with one certificate.
+    #[clap(
+        long = "acme-domain",
+        env = "SPIN_ACME_DOMAIN",
+        value_delimiter = ',',
+        conflicts_with = "tls_cert"
+    )]
+    pub acme_domains: Vec<String>,
+
+    /// The contact email address to register with the ACME certificate authority.
+    #[clap(
+        long = "acme-email",
+        env = "SPIN_ACME_EMAIL",
+        requires = "acme_domains"
+    )]
+    pub acme_email: Option<String>,
+
+    /// How to prove control of the `--acme-domain`s. `tls-alpn-01` requires
+    /// the https listener to be reachable on port 443; `http-01` requires the
+    /// `--acme-http-listen` address to be reachable on port 80.
+    #[clap(long = "acme-challenge", value_enum, default_value_t = AcmeChallenge::default())]
+    pub acme_challenge: AcmeChallenge,
+
+    /// IP address and port on which to answer ACME `http-01` challenges.
+    #[clap(long = "acme-http-listen", default_value = "0.0.0.0:80", value_parser = parse_listen_addr)]
+    pub acme_http_listen: SocketAddr,
+
+    /// The directory URL of the ACME certificate authority.
+    #[clap(long = "acme-directory", env = "SPIN_ACME_DIRECTORY", default_value = LETS_ENCRYPT_DIRECTORY)]
+    pub acme_directory: String,
+
+    /// Where to store the ACME account and certificates. Defaults to `acme/`
+    /// in the app's state directory.
+    #[clap(long = "acme-dir", env = "SPIN_ACME_DIR")]
+    pub acme_dir: Option<PathBuf>,
+
     /// Sets the maximum buffer size (in bytes) for the HTTP connection. The minimum value allowed is 8192.
     #[clap(long, env = "SPIN_HTTP1_MAX_BUF_SIZE")]
     pub http1_max_buf_size: Option<usize>,
@@ -136,9 +187,80 @@ pub struct CliArgs {
     /// at random for each new instance.
     #[clap(long, default_value = "1s", value_parser = parse_duration_range)]
     pub idle_instance_timeout: Range<Duration>,
+
+    /// Maximum number of requests each component may be handling at once.
+    ///
+    /// Requests beyond this limit wait in a per-component queue (see
+    /// `--max-queued-requests`). A route may set its own limits for its
+    /// component with `concurrency = { max_in_flight = ..., max_queued_requests = ... }`.
+    /// If not set, there is no limit.
+    #[clap(long, env = "SPIN_HTTP_MAX_IN_FLIGHT", value_parser = RangedU64ValueParser::<usize>::new().range(1..))]
+    pub max_in_flight: Option<usize>,
+
+    /// Maximum number of requests which may wait for each component once
+    /// `--max-in-flight` is reached. Requests beyond this are rejected with
+    /// the `--shed-status` status code.
+    #[clap(long, default_value_t = 0, requires = "max_in_flight")]
+    pub max_queued_requests: usize,
+
+    /// Maximum number of requests all components of the app may be handling
+    /// at once.
+    ///
+    /// While the app is at this limit, capacity is shared equally between the
+    /// components with requests waiting, so one busy component cannot starve
+    /// the others. If not set, there is no limit.
+    #[clap(long, env = "SPIN_HTTP_MAX_APP_IN_FLIGHT", value_parser = RangedU64ValueParser::<usize>::new().range(1..))]
+    pub max_app_in_flight: Option<usize>,
+
+    /// Maximum number of requests which may wait across the whole app once
+    /// `--max-app-in-flight` is reached. Requests beyond this are rejected
+    /// with the `--shed-status` status code.
+    #[clap(long, default_value_t = 0, requires = "max_app_in_flight")]
+    pub max_app_queued_requests: usize,
+
+    /// The status code to return for requests rejected because a component's
+    /// or the app's queue is full. Must be 429 or 503.
+    #[clap(long, default_value_t = 503, value_parser = parse_shed_status)]
+    pub shed_status: u16,
+
+    /// The number of proxies, such as load balancers, in front of Spin whose
+    /// `X-Forwarded-For` entries are trusted. The client address routes
+    /// filter on and components see is taken from that many entries from
+    /// the right of the header.
+    #[clap(long, env = "SPIN_HTTP_TRUSTED_PROXIES", default_value_t = 0)]
+    pub trusted_proxies: usize,
 }
 
 impl CliArgs {
+    fn concurrency_limit_config(&self) -> ConcurrencyLimitConfig {
+        ConcurrencyLimitConfig {
+            max_in_flight: self.max_in_flight,
+            max_queued: self.max_queued_requests,
+            max_app_in_flight: self.max_app_in_flight,
+            max_app_queued: self.max_app_queued_requests,
+            // Validated by `parse_shed_status`
+            shed_status: http::StatusCode::from_u16(self.shed_status).unwrap(),
+        }
+    }
+
+    fn acme_config(&self, app: &App) -> anyhow::Result<Option<AcmeConfig>> {
+        if self.acme_domains.is_empty() {
+            return Ok(None);
+        }
+        let cache_dir = match &self.acme_dir {
+            Some(dir) => dir.clone(),
+            None => AcmeConfig::default_cache_dir(app)?,
+        };
+        Ok(Some(AcmeConfig {
+            domains: self.acme_domains.clone(),
+            email: self.acme_email.clone(),
+            directory_url: self.acme_directory.clone(),
+            challenge: self.acme_challenge,
+            http_listen: self.acme_http_listen,
+            cache_dir,
+        }))
+    }
+
     fn into_tls_config(self) -> Option<TlsConfig> {
         match (self.tls_cert, self.tls_key) {
             (Some(cert_path), Some(key_path)) => Some(TlsConfig {
@@ -236,6 +358,13 @@ fn parse_duration_range(s: &str) -> Result<Range<Duration>, String> {
     parse_range::<ParsedDuration>(s).map(|v| v.map(|v| v.0))
 }
 
+fn parse_shed_status(s: &str) -> Result<u16, String> {
+    match s.parse() {
+        Ok(status @ (429 | 503)) => Ok(status),
+        _ => Err(format!("expected 429 or 503; got {s:?}")),
+    }
+}
+
 #[derive(Clone, Copy)]
 pub struct InstanceReuseConfig {
     max_instance_reuse_count: Range<usize>,
@@ -257,18 +386,43 @@ impl Default for InstanceReuseConfig {
     }
 }
 
-/// The Spin HTTP trigger.
-pub struct HttpTrigger {
+/// How an [`HttpTrigger`] listens for requests and runs components.
+#[derive(Clone)]
+pub struct HttpTriggerConfig {
     /// The address the server should listen on.
     ///
     /// Note that this might not be the actual socket address that ends up being bound to.
     /// If the port is set to 0, the actual address will be determined by the OS.
-    listen_addr: SocketAddr,
-    tls_config: Option<TlsConfig>,
-    find_free_port: bool,
-    http1_max_buf_size: Option<usize>,
-    reuse_config: InstanceReuseConfig,
-    output_format: OutputFormat,
+    pub listen_addr: SocketAddr,
+    pub tls_config: Option<TlsConfig>,
+    pub find_free_port: bool,
+    pub http1_max_buf_size: Option<usize>,
+    pub reuse_config: InstanceReuseConfig,
+    pub concurrency_config: ConcurrencyLimitConfig,
+    pub output_format: OutputFormat,
+}
+
+impl HttpTriggerConfig {
+    /// Creates a config for listening on `listen_addr` without TLS, with
+    /// defaults for everything else.
+    pub fn new(listen_addr: SocketAddr) -> Self {
+        Self {
+            listen_addr,
+            tls_config: None,
+            find_free_port: false,
+            http1_max_buf_size: None,
+            reuse_config: InstanceReuseConfig::default(),
+            concurrency_config: ConcurrencyLimitConfig::default(),
+            output_format: OutputFormat::default(),
+        }
+    }
+}
+
+/// The Spin HTTP trigger.
+pub struct HttpTrigger {
+    config: HttpTriggerConfig,
+    acme_config: Option<AcmeConfig>,
+    trusted_proxies: usize,
 }
 
 impl<F: RuntimeFactors> Trigger<F> for HttpTrigger {
@@ -278,9 +432,6 @@ impl<F: RuntimeFactors> Trigger<F> for HttpTrigger {
     type InstanceState = ();
 
     fn new(cli_args: Self::CliArgs, app: &spin_app::App) -> anyhow::Result<Self> {
-        let find_free_port = cli_args.find_free_port;
-        let http1_max_buf_size = cli_args.http1_max_buf_size;
-        let output_format = cli_args.format;
         let reuse_config = InstanceReuseConfig {
             max_instance_reuse_count: cli_args
                 .max_instance_reuse_count
@@ -293,16 +444,24 @@ impl<F: RuntimeFactors> Trigger<F> for HttpTrigger {
             request_timeout: cli_args.request_timeout,
             idle_instance_timeout: cli_args.idle_instance_timeout,
         };
-
-        Self::new(
-            app,
-            cli_args.address,
-            cli_args.into_tls_config(),
-            find_free_port,
-            http1_max_buf_size,
+        let concurrency_config = cli_args.concurrency_limit_config();
+        let acme_config = cli_args.acme_config(app)?;
+        let trusted_proxies = cli_args.trusted_proxies;
+
+        let config = HttpTriggerConfig {
+            listen_addr: cli_args.address,
+            find_free_port: cli_args.find_free_port,
+            http1_max_buf_size: cli_args.http1_max_buf_size,
             reuse_config,
-            output_format,
-        )
+            concurrency_config,
+            output_format: cli_args.format,
+            tls_config: cli_args.into_tls_config(),
+        };
+
+        let mut trigger = Self::new(app, config)?;
+        trigger.acme_config = acme_config;
+        trigger.trusted_proxies = trusted_proxies;
+        Ok(trigger)
     }
 
     async fn run(self, trigger_app: TriggerApp<F>) -> anyhow::Result<()> {
@@ -324,24 +483,13 @@ impl<F: RuntimeFactors> Trigger<F> for HttpTrigger {
 
 impl HttpTrigger {
     /// Create a new `HttpTrigger`.
-    pub fn new(
-        app: &spin_app::App,
-        listen_addr: SocketAddr,
-        tls_config: Option<TlsConfig>,
-        find_free_port: bool,
-        http1_max_buf_size: Option<usize>,
-        reuse_config: InstanceReuseConfig,
-        output_format: OutputFormat,
-    ) -> anyhow::Result<Self> {
+    pub fn new(app: &spin_app::App, config: HttpTriggerConfig) -> anyhow::Result<Self> {
         Self::validate_app(app)?;
 
         Ok(Self {
-            listen_addr,
-            tls_config,
-            find_free_port,
-            http1_max_buf_size,
-            reuse_config,
-            output_format,
+            config,
+            acme_config: None,
+            trusted_proxies: 0,
         })
     }
 
@@ -351,32 +499,39 @@ impl HttpTrigger {
         trigger_app: TriggerApp<F>,
     ) -> anyhow::Result<Arc<HttpServer<F>>> {
         let Self {
-            listen_addr,
-            tls_config,
-            find_free_port,
-            http1_max_buf_size,
-            reuse_config,
-            output_format,
+            config:
+                HttpTriggerConfig {
+                    listen_addr,
+                    tls_config,
+                    find_free_port,
+                    http1_max_buf_size,
+                    reuse_config,
+                    concurrency_config,
+                    output_format,
+                },
+            acme_config,
+            trusted_proxies,
         } = self;
         let server = Arc::new(HttpServer::new(
             listen_addr,
             tls_config,
+            acme_config,
             find_free_port,
             trigger_app,
             http1_max_buf_size,
             reuse_config,
+            concurrency_config,
             output_format,
+            trusted_proxies,
         )?);
         Ok(server)
     }
 
     fn validate_app(app: &App) -> anyhow::Result<()> {
-        #[derive(Deserialize)]
-        #[serde(deny_unknown_fields)]
-        struct TriggerMetadata {
-            base: Option<String>,
-        }
-        if let Some(TriggerMetadata { base: Some(base) }) = app.get_trigger_metadata("http")? {
+        if let Some(TriggerMetadata {
+            base: Some(base), ..
+        }) = app.get_trigger_metadata("http")?
+        {
             if base == "/" {
                 tracing::warn!(
                     "This application has the deprecated trigger 'base' set to the default value '/'. This may be an error in the future!"
@@ -391,6 +546,15 @@ impl HttpTrigger {
     }
 }
 
+/// App-level HTTP trigger settings, from `[application.trigger.http]`.
+#[derive(Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct TriggerMetadata {
+    base: Option<String>,
+    /// The OpenID Connect provider for routes with `auth = "oidc"`.
+    pub oidc: Option<OidcConfig>,
+}
+
 fn parse_listen_addr(addr: &str) -> anyhow::Result<SocketAddr> {
     let addrs: Vec<SocketAddr> = addr.to_socket_addrs()?.collect();
     // Prefer 127.0.0.1 over e.g. [::1] because CHANGE IS HARD
@@ -0,0 +1,699 @@
+//! OpenID Connect authentication for HTTP routes.
+//!
+//! Routes with `auth = "oidc"` require users to sign in with the provider
+//! configured in `[application.trigger.http.oidc]`, using the authorization
+//! code flow. The signed-in identity is kept in a signed session cookie and
+//! passed to components in `spin-auth-*` headers.
+
+use std::collections::HashMap;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use anyhow::{Context, bail, ensure};
+use base64::{Engine as _, engine::general_purpose::URL_SAFE_NO_PAD};
+use http::{HeaderMap, HeaderValue, Request, Response, StatusCode, header};
+use hyper::body::Bytes;
+use ring::{hmac, rand::SecureRandom as _};
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+use serde_json::{Map, Value};
+use spin_factor_variables::AppState as VariablesAppState;
+use spin_http::body;
+use tokio::sync::OnceCell;
+
+use crate::Body;
+
+/// The path, under the well-known prefix, the provider redirects back to.
+pub(crate) const CALLBACK_PATH: &str = "oidc/callback";
+
+/// The prefix of the headers carrying the signed-in identity to components.
+///
+/// Incoming headers with this prefix are removed from every request, so
+/// components can trust them.
+pub(crate) const AUTH_HEADER_PREFIX: &str = "spin-auth-";
+/// The header carrying the user's subject identifier.
+const SUBJECT_HEADER: &str = "spin-auth-subject";
+/// The header carrying the user's email address, if the provider gave one.
+const EMAIL_HEADER: &str = "spin-auth-email";
+/// The header carrying all of the user's identity claims as JSON.
+const CLAIMS_HEADER: &str = "spin-auth-claims";
+
+const SESSION_COOKIE: &str = "spin_oidc_session";
+const LOGIN_COOKIE: &str = "spin_oidc_login";
+/// How long a user has to complete signing in with the provider.
+const LOGIN_TTL: Duration = Duration::from_secs(10 * 60);
+
+/// ID token claims which describe the token rather than the user, and so are
+/// not kept in the session.
+const TOKEN_CLAIMS: &[&str] = &[
+    "iss",
+    "aud",
+    "exp",
+    "iat",
+    "nbf",
+    "nonce",
+    "at_hash",
+    "c_hash",
+    "azp",
+    "auth_time",
+    "sid",
+];
+
+/// OpenID Connect provider settings, from `[application.trigger.http.oidc]`.
+///
+/// The client ID and secret may be templates referring to application
+/// variables, e.g. `"{{ oidc_client_secret }}"`.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct OidcConfig {
+    /// The provider's discovery document URL, usually ending in
+    /// `/.well-known/openid-configuration`.
+    pub discovery_url: String,
+    pub client_id: String,
+    pub client_secret: String,
+    /// The scopes to request; `openid` is always requested.
+    #[serde(default = "default_scopes")]
+    pub scopes: Vec<String>,
+    /// The URL the provider redirects back to after sign in. Defaults to
+    /// `/.well-known/spin/oidc/callback` on the host the request was made to;
+    /// set it when the app is served behind a proxy.
+    #[serde(default)]
+    pub redirect_url: Option<String>,
+}
+
+fn default_scopes() -> Vec<String> {
+    vec!["openid".into(), "email".into(), "profile".into()]
+}
+
+/// The outcome of authenticating a request to a protected route.
+pub(crate) enum Authentication {
+    /// The user is signed in; the request carries their identity.
+    Authenticated,
+    /// The user is not signed in; the response starts signing in or refuses
+    /// the request.
+    Challenge(Response<Body>),
+}
+
+/// Authenticates requests against the app's OpenID Connect provider.
+pub(crate) struct OidcAuthenticator {
+    config: OidcConfig,
+    provider: OnceCell<Provider>,
+    /// Signs session and login cookies. Generated on startup, so restarting
+    /// the app signs everyone out.
+    cookie_key: hmac::Key,
+    http_client: reqwest::Client,
+}
+
+impl OidcAuthenticator {
+    pub fn new(config: OidcConfig) -> anyhow::Result<Self> {
+        require_https("discovery_url", &config.discovery_url)?;
+        let cookie_key = hmac::Key::generate(hmac::HMAC_SHA256, &ring::rand::SystemRandom::new())
+            .map_err(|_| anyhow::anyhow!("failed to generate OIDC session key"))?;
+        Ok(Self {
+            config,
+            provider: OnceCell::new(),
+            cookie_key,
+            // The token endpoint is trusted in place of checking the ID token's
+            // signature, so it must only ever be reached over TLS, redirects included.
+            http_client: reqwest::Client::builder()
+                .https_only(true)
+                .build()
+                .context("failed to create OIDC HTTP client")?,
+        })
+    }
+
+    /// Checks the request for a session, adding the identity headers if
+    /// there is one.
+    ///
+    /// Otherwise, browser navigations (`GET` and `HEAD`) are redirected to the
+    /// provider to sign in, and other requests are refused.
+    pub async fn authenticate(
+        &self,
+        req: &mut Request<Body>,
+        variables: &VariablesAppState,
+    ) -> anyhow::Result<Authentication> {
+        if let Some(session) = cookie(req.headers(), SESSION_COOKIE)
+            .and_then(|value| self.open::<Session>(value))
+            .filter(|session| session.exp > unix_now())
+        {
+            session.add_headers(req.headers_mut())?;
+            return Ok(Authentication::Authenticated);
+        }
+
+        if !matches!(*req.method(), http::Method::GET | http::Method::HEAD) {
+            return Ok(Authentication::Challenge(
+                Response::builder()
+                    .status(StatusCode::UNAUTHORIZED)
+                    .body(body::empty())?,
+            ));
+        }
+
+        let provider = self.provider(variables).await?;
+        let login = Login {
+            state: random_token()?,
+            nonce: random_token()?,
+            return_to: local_path(req.uri().path_and_query().map_or("/", |p| p.as_str())),
+            exp: unix_now() + LOGIN_TTL.as_secs(),
+        };
+        let mut location = url::Url::parse(&provider.authorization_endpoint)
+            .context("invalid OIDC authorization endpoint")?;
+        location
+            .query_pairs_mut()
+            .append_pair("response_type", "code")
+            .append_pair("client_id", &provider.client_id)
+            .append_pair("redirect_uri", &self.redirect_url(req.uri())?)
+            .append_pair("scope", &self.scope())
+            .append_pair("state", &login.state)
+            .append_pair("nonce", &login.nonce);
+        Ok(Authentication::Challenge(
+            Response::builder()
+                .status(StatusCode::FOUND)
+                .header(header::LOCATION, location.as_str())
+                .header(
+                    header::SET_COOKIE,
+                    self.set_cookie(req.uri(), LOGIN_COOKIE, &self.seal(&login)?, LOGIN_TTL),
+                )
+                .body(body::empty())?,
+        ))
+    }
+
+    /// Completes signing in when the provider redirects back to the app.
+    pub async fn callback(
+        &self,
+        req: &http::request::Parts,
+        variables: &VariablesAppState,
+    ) -> anyhow::Result<Response<Body>> {
+        let query: HashMap<String, String> =
+            url::form_urlencoded::parse(req.uri.query().unwrap_or_default().as_bytes())
+                .into_owned()
+                .collect();
+        let param = |name: &str| query.get(name).map(String::as_str);
+
+        if let Some(error) = param("error") {
+            return Self::refuse(&format!("Sign in failed: {error}"));
+        }
+        let Some(login) = cookie(&req.headers, LOGIN_COOKIE)
+            .and_then(|value| self.open::<Login>(value))
+            .filter(|login| login.exp > unix_now())
+        else {
+            return Self::refuse("Sign in expired; please try again.");
+        };
+        if param("state") != Some(login.state.as_str()) {
+            return Self::refuse("Sign in state does not match; please try again.");
+        }
+        let Some(code) = param("code") else {
+            return Self::refuse("Sign in response is missing the authorization code.");
+        };
+
+        let provider = self.provider(variables).await?;
+        let id_token = provider
+            .exchange_code(&self.http_client, code, &self.redirect_url(&req.uri)?)
+            .await?;
+        let session = match provider.validate_id_token(&id_token, &login.nonce, unix_now()) {
+            Ok(session) => session,
+            Err(err) => {
+                tracing::warn!("Rejected OIDC ID token: {err:#}");
+                return Self::refuse("Sign in failed: the identity token is not valid.");
+            }
+        };
+        let ttl = Duration::from_secs(session.exp.saturating_sub(unix_now()));
+        Ok(Response::builder()
+            .status(StatusCode::FOUND)
+            .header(header::LOCATION, local_path(&login.return_to))
+            .header(
+                header::SET_COOKIE,
+                self.set_cookie(&req.uri, SESSION_COOKIE, &self.seal(&session)?, ttl),
+            )
+            .header(
+                header::SET_COOKIE,
+                self.set_cookie(&req.uri, LOGIN_COOKIE, "", Duration::ZERO),
+            )
+            .body(body::empty())?)
+    }
+
+    /// Resolves the provider settings and discovers its endpoints on first
+    /// use.
+    async fn provider(&self, variables: &VariablesAppState) -> anyhow::Result<&Provider> {
+        self.provider
+            .get_or_try_init(|| async {
+                let client_id = variables
+                    .resolve_expression(self.config.client_id.clone())
+                    .await
+                    .context("failed to resolve OIDC client_id")?;
+                let client_secret = variables
+                    .resolve_expression(self.config.client_secret.clone())
+                    .await
+                    .context("failed to resolve OIDC client_secret")?;
+                Provider::discover(
+                    &self.http_client,
+                    &self.config.discovery_url,
+                    client_id,
+                    client_secret,
+                )
+                .await
+            })
+            .await
+    }
+
+    fn scope(&self) -> String {
+        let mut scopes = vec!["openid"];
+        scopes.extend(
+            self.config
+                .scopes
+                .iter()
+                .map(String::as_str)
+                .filter(|scope| *scope != "openid"),
+        );
+        scopes.join(" ")
+    }
+
+    fn redirect_url(&self, uri: &http::Uri) -> anyhow::Result<String> {
+        if let Some(url) = &self.config.redirect_url {
+            return Ok(url.clone());
+        }
+        let (Some(scheme), Some(authority)) = (uri.scheme_str(), uri.authority()) else {
+            bail!("cannot determine the OIDC redirect URL for {uri}");
+        };
+        Ok(format!(
+            "{scheme}://{authority}{}{CALLBACK_PATH}",
+            spin_http::WELL_KNOWN_PREFIX
+        ))
+    }
+
+    fn set_cookie(&self, uri: &http::Uri, name: &str, value: &str, max_age: Duration) -> String {
+        let secure = if uri.scheme() == Some(&http::uri::Scheme::HTTPS) {
+            "; Secure"
+        } else {
+            ""
+        };
+        format!(
+            "{name}={value}; Path=/; Max-Age={}; HttpOnly; SameSite=Lax{secure}",
+            max_age.as_secs()
+        )
+    }
+
+    /// Signs and encodes a value for storing in a cookie.
+    fn seal(&self, value: &impl Serialize) -> anyhow::Result<String> {
+        let payload = URL_SAFE_NO_PAD.encode(serde_json::to_vec(value)?);
+        let tag = hmac::sign(&self.cookie_key, payload.as_bytes());
+        Ok(format!(
+            "{payload}.{}",
+            URL_SAFE_NO_PAD.encode(tag.as_ref())
+        ))
+    }
+
+    /// Decodes a value sealed by [`Self::seal`], if its signature is valid.
+    fn open<T: DeserializeOwned>(&self, sealed: &str) -> Option<T> {
+        let (payload, tag) = sealed.split_once('.')?;
+        let tag = URL_SAFE_NO_PAD.decode(tag).ok()?;
+        hmac::verify(&self.cookie_key, payload.as_bytes(), &tag).ok()?;
+        serde_json::from_slice(&URL_SAFE_NO_PAD.decode(payload).ok()?).ok()
+    }
+
+    fn refuse(message: &str) -> anyhow::Result<Response<Body>> {
+        Ok(Response::builder()
+            .status(StatusCode::UNAUTHORIZED)
+            .body(body::full(Bytes::copy_from_slice(message.as_bytes())))?)
+    }
+}
+
+/// A signed-in user, stored in the session cookie.
+#[derive(Debug, Deserialize, Serialize)]
+struct Session {
+    sub: String,
+    /// When the session expires, in seconds since the Unix epoch.
+    exp: u64,
+    /// The user's identity claims, other than those describing the token.
+    claims: Map<String, Value>,
+}
+
+impl Session {
+    fn add_headers(&self, headers: &mut HeaderMap) -> anyhow::Result<()> {
+        headers.insert(SUBJECT_HEADER, HeaderValue::from_str(&self.sub)?);
+        if let Some(email) = self.claims.get("email").and_then(Value::as_str) {
+            headers.insert(EMAIL_HEADER, HeaderValue::from_str(email)?);
+        }
+        headers.insert(
+            CLAIMS_HEADER,
+            HeaderValue::from_str(&ascii_json(&self.claims)?)?,
+        );
+        Ok(())
+    }
+}
+
+/// Serializes a value as JSON with non-ASCII characters escaped, so that it
+/// can be sent in a header.
+fn ascii_json(value: &impl Serialize) -> anyhow::Result<String> {
+    let json = serde_json::to_string(value)?;
+    let mut escaped = String::with_capacity(json.len());
+    for c in json.chars() {
+        if c.is_ascii() {
+            escaped.push(c);
+        } else {
+            for unit in c.encode_utf16(&mut [0; 2]) {
+                escaped.push_str(&format!("\\u{unit:04x}"));
+            }
+        }
+    }
+    Ok(escaped)
+}
+
+/// A sign in in progress, stored in the login cookie.
+#[derive(Debug, Deserialize, Serialize)]
+struct Login {
+    state: String,
+    nonce: String,
+    return_to: String,
+    exp: u64,
+}
+
+/// A discovered OpenID Connect provider.
+struct Provider {
+    issuer: String,
+    authorization_endpoint: String,
+    token_endpoint: String,
+    client_id: String,
+    client_secret: String,
+}
+
+impl Provider {
+    async fn discover(
+        http_client: &reqwest::Client,
+        discovery_url: &str,
+        client_id: String,
+        client_secret: String,
+    ) -> anyhow::Result<Self> {
+        #[derive(Deserialize)]
+        struct Discovery {
+            issuer: String,
+            authorization_endpoint: String,
+            token_endpoint: String,
+        }
+        let discovery: Discovery = get_json(http_client.get(discovery_url))
+            .await
+            .with_context(|| format!("failed to discover OIDC provider at {discovery_url}"))?;
+        require_https("authorization_endpoint", &discovery.authorization_endpoint)?;
+        require_https("token_endpoint", &discovery.token_endpoint)?;
+        Ok(Self {
+            issuer: discovery.issuer,
+            authorization_endpoint: discovery.authorization_endpoint,
+            token_endpoint: discovery.token_endpoint,
+            client_id,
+            client_secret,
+        })
+    }
+
+    /// Exchanges an authorization code for an ID token.
+    async fn exchange_code(
+        &self,
+        http_client: &reqwest::Client,
+        code: &str,
+        redirect_url: &str,
+    ) -> anyhow::Result<String> {
+        #[derive(Deserialize)]
+        struct TokenResponse {
+            id_token: String,
+        }
+        let request = http_client.post(&self.token_endpoint).form(&[
+            ("grant_type", "authorization_code"),
+            ("code", code),
+            ("redirect_uri", redirect_url),
+            ("client_id", &self.client_id),
+            ("client_secret", &self.client_secret),
+        ]);
+        let response: TokenResponse = get_json(request)
+            .await
+            .context("failed to exchange OIDC authorization code")?;
+        Ok(response.id_token)
+    }
+
+    /// Validates the claims of an ID token received from the token endpoint.
+    ///
+    /// The token's signature is not checked: it was received directly from
+    /// the provider over TLS in exchange for the client secret, which OpenID
+    /// Connect Core (section 3.1.3.7) allows in place of signature validation.
+    /// The provider's endpoints are therefore required to be `https`.
+    fn validate_id_token(&self, id_token: &str, nonce: &str, now: u64) -> anyhow::Result<Session> {
+        let payload = id_token
+            .split('.')
+            .nth(1)
+            .context("ID token is not a JWT")?;
+        let mut claims: Map<String, Value> =
+            serde_json::from_slice(&URL_SAFE_NO_PAD.decode(payload.trim_end_matches('='))?)
+                .context("ID token claims are not a JSON object")?;
+
+        let claim = |name| claims.get(name).and_then(Value::as_str);
+        ensure!(claim("iss") == Some(self.issuer.as_str()), "wrong issuer");
+        let audience_ok = match claims.get("aud") {
+            Some(Value::String(aud)) => *aud == self.client_id,
+            Some(Value::Array(auds)) => auds.iter().any(|aud| *aud == *self.client_id),
+            _ => false,
+        };
+        ensure!(audience_ok, "wrong audience");
+        // A token issued to several audiences must name this client as the
+        // party it was issued to (OpenID Connect Core section 3.1.3.7).
+        let multiple_audiences =
+            matches!(claims.get("aud"), Some(Value::Array(auds)) if auds.len() > 1);
+        match claim("azp") {
+            Some(azp) => ensure!(azp == self.client_id, "wrong authorized party"),
+            None => ensure!(!multiple_audiences, "missing authorized party"),
+        }
+        ensure!(claim("nonce") == Some(nonce), "wrong nonce");
+        let exp = claims
+            .get("exp")
+            .and_then(Value::as_u64)
+            .context("missing expiry")?;
+        ensure!(exp > now, "expired");
+        let sub = claim("sub").context("missing subject")?.to_owned();
+
+        claims.retain(|name, _| !TOKEN_CLAIMS.contains(&name.as_str()));
+        Ok(Session { sub, exp, claims })
+    }
+}
+
+/// Checks that a provider URL is `https`.
+fn require_https(name: &str, url: &str) -> anyhow::Result<()> {
+    let parsed = url::Url::parse(url).with_context(|| format!("invalid OIDC {name} {url:?}"))?;
+    ensure!(
+        parsed.scheme() == "https",
+        "OIDC {name} {url:?} must be an https URL"
+    );
+    Ok(())
+}
+
+/// Returns the target to send a user back to after signing in, if it is a path
+/// on this app, or `/` otherwise.
+///
+/// Targets starting `//` or `/\` are refused, as browsers treat them as
+/// references to another host.
+fn local_path(target: &str) -> String {
+    let local = target.starts_with('/') && !target.starts_with("//") && !target.starts_with("/\\");
+    if local { target } else { "/" }.to_owned()
+}
+
+async fn get_json<T: DeserializeOwned>(request: reqwest::RequestBuilder) -> anyhow::Result<T> {
+    let response = request.send().await?.error_for_status()?;
+    Ok(serde_json::from_slice(&response.bytes().await?)?)
+}
+
+/// Returns the value of the named cookie, if the request has it.
+pub(crate) fn cookie<'a>(headers: &'a HeaderMap, name: &str) -> Option<&'a str> {
+    headers
+        .get_all(header::COOKIE)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(';'))
+        .filter_map(|pair| pair.trim().split_once('='))
+        .find_map(|(key, value)| (key == name).then_some(value))
+}
+
+fn random_token() -> anyhow::Result<String> {
+    let mut bytes = [0; 16];
+    ring::rand::SystemRandom::new()
+        .fill(&mut bytes)
+        .map_err(|_| anyhow::anyhow!("failed to generate random token"))?;
+    Ok(URL_SAFE_NO_PAD.encode(bytes))
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn authenticator() -> OidcAuthenticator {
+        OidcAuthenticator::new(OidcConfig {
+            discovery_url: "https://id.example.com/.well-known/openid-configuration".into(),
+            client_id: "{{ client_id }}".into(),
+            client_secret: "{{ client_secret }}".into(),
+            scopes: vec!["email".into(), "openid".into()],
+            redirect_url: None,
+        })
+        .unwrap()
+    }
+
+    fn provider() -> Provider {
+        Provider {
+            issuer: "https://id.example.com".into(),
+            authorization_endpoint: "https://id.example.com/authorize".into(),
+            token_endpoint: "https://id.example.com/token".into(),
+            client_id: "spin-app".into(),
+            client_secret: "secret".into(),
+        }
+    }
+
+    fn id_token(claims: Value) -> String {
+        format!(
+            "e30.{}.c2ln",
+            URL_SAFE_NO_PAD.encode(serde_json::to_vec(&claims).unwrap())
+        )
+    }
+
+    #[test]
+    fn sealed_cookies_cannot_be_tampered_with() {
+        let auth = authenticator();
+        let login = Login {
+            state: "state".into(),
+            nonce: "nonce".into(),
+            return_to: "/admin".into(),
+            exp: 1,
+        };
+        let sealed = auth.seal(&login).unwrap();
+        let opened: Login = auth.open(&sealed).unwrap();
+        assert_eq!("/admin", opened.return_to);
+
+        let (_, tag) = sealed.split_once('.').unwrap();
+        let forged = URL_SAFE_NO_PAD.encode(
+            br#"{"state":"state","nonce":"nonce","return_to":"https://evil.example.com","exp":1}"#,
+        );
+        assert!(auth.open::<Login>(&format!("{forged}.{tag}")).is_none());
+        assert!(authenticator().open::<Login>(&sealed).is_none());
+    }
+
+    #[test]
+    fn validates_id_token_claims() {
+        let provider = provider();
+        let claims = json!({
+            "iss": "https://id.example.com",
+            "aud": ["spin-app"],
+            "sub": "user-1",
+            "email": "user@example.com",
+            "exp": 2000,
+            "nonce": "n",
+        });
+        let session = provider
+            .validate_id_token(&id_token(claims.clone()), "n", 1000)
+            .unwrap();
+        assert_eq!("user-1", session.sub);
+        assert_eq!(2000, session.exp);
+        assert_eq!(
+            json!({"sub": "user-1", "email": "user@example.com"}),
+            Value::Object(session.claims)
+        );
+
+        let token = id_token(claims.clone());
+        assert!(provider.validate_id_token(&token, "other", 1000).is_err());
+        assert!(provider.validate_id_token(&token, "n", 2000).is_err());
+        let mut wrong_audience = claims.clone();
+        wrong_audience["aud"] = json!("other-app");
+        assert!(
+            provider
+                .validate_id_token(&id_token(wrong_audience), "n", 1000)
+                .is_err()
+        );
+        let mut other_party = claims.clone();
+        other_party["azp"] = json!("other-app");
+        assert!(
+            provider
+                .validate_id_token(&id_token(other_party), "n", 1000)
+                .is_err()
+        );
+        let mut shared_audience = claims.clone();
+        shared_audience["aud"] = json!(["spin-app", "other-app"]);
+        assert!(
+            provider
+                .validate_id_token(&id_token(shared_audience.clone()), "n", 1000)
+                .is_err()
+        );
+        shared_audience["azp"] = json!("spin-app");
+        assert!(
+            provider
+                .validate_id_token(&id_token(shared_audience), "n", 1000)
+                .is_ok()
+        );
+        let mut wrong_issuer = claims;
+        wrong_issuer["iss"] = json!("https://evil.example.com");
+        assert!(
+            provider
+                .validate_id_token(&id_token(wrong_issuer), "n", 1000)
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn requires_https_provider() {
+        let result = OidcAuthenticator::new(OidcConfig {
+            discovery_url: "http://id.example.com/.well-known/openid-configuration".into(),
+            ..authenticator().config
+        });
+        assert!(result.is_err());
+        assert!(require_https("token_endpoint", "https://id.example.com/token").is_ok());
+        assert!(require_https("token_endpoint", "http://id.example.com/token").is_err());
+    }
+
+    #[test]
+    fn returns_only_to_local_paths() {
+        assert_eq!("/admin?x=1", local_path("/admin?x=1"));
+        assert_eq!("/", local_path("//evil.example.com/"));
+        assert_eq!("/", local_path("/\\evil.example.com/"));
+        assert_eq!("/", local_path("https://evil.example.com/"));
+        assert_eq!("/", local_path(""));
+    }
+
+    #[test]
+    fn session_headers_carry_identity() {
+        let session = Session {
+            sub: "user-1".into(),
+            exp: 0,
+            claims: json!({"email": "user@example.com", "groups": ["admins"], "name": "Zoë"})
+                .as_object()
+                .unwrap()
+                .clone(),
+        };
+        let mut headers = HeaderMap::new();
+        session.add_headers(&mut headers).unwrap();
+        assert_eq!("user-1", headers[SUBJECT_HEADER]);
+        assert_eq!("user@example.com", headers[EMAIL_HEADER]);
+        let claims: Value = serde_json::from_slice(headers[CLAIMS_HEADER].as_bytes()).unwrap();
+        assert_eq!(json!(["admins"]), claims["groups"]);
+        assert_eq!("Zoë", claims["name"]);
+    }
+
+    #[test]
+    fn reads_cookies_and_builds_urls() {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            header::COOKIE,
+            "a=1; spin_oidc_session=abc.def".parse().unwrap(),
+        );
+        assert_eq!(Some("abc.def"), cookie(&headers, SESSION_COOKIE));
+        assert_eq!(None, cookie(&headers, LOGIN_COOKIE));
+
+        let auth = authenticator();
+        assert_eq!("openid email", auth.scope());
+        let uri: http::Uri = "https://app.example.com/admin?x=1".parse().unwrap();
+        assert_eq!(
+            "https://app.example.com/.well-known/spin/oidc/callback",
+            auth.redirect_url(&uri).unwrap()
+        );
+        assert!(
+            auth.set_cookie(&uri, SESSION_COOKIE, "v", Duration::from_secs(60))
+                .ends_with("Max-Age=60; HttpOnly; SameSite=Lax; Secure")
+        );
+    }
+}
@@ -9,6 +9,7 @@ use spin_factor_outbound_http::intercept::{self, InterceptOutcome, InterceptRequ
 use spin_factor_outbound_networking::config::allowed_hosts::parse_service_chaining_target;
 use spin_factors::RuntimeFactors;
 use spin_http::routes::RouteMatch;
+use tracing::{Instrument, field::Empty};
 use wasmtime::ToWasmtimeResult;
 use wasmtime_wasi_http::p2::{HttpError, HttpResult};
 
@@ -17,11 +18,46 @@ use crate::HttpServer;
 /// An outbound HTTP interceptor that handles service chaining requests.
 pub struct OutboundHttpInterceptor<F: RuntimeFactors> {
     server: Arc<HttpServer<F>>,
+    /// The component making outbound requests.
+    component_id: String,
+    /// The components waiting on the component's own request, if it is a
+    /// chained request.
+    callers: ChainedCallers,
 }
 
 impl<F: RuntimeFactors> OutboundHttpInterceptor<F> {
-    pub fn new(server: Arc<HttpServer<F>>) -> Self {
-        Self { server }
+    pub(crate) fn new(
+        server: Arc<HttpServer<F>>,
+        component_id: impl Into<String>,
+        callers: ChainedCallers,
+    ) -> Self {
+        Self {
+            server,
+            component_id: component_id.into(),
+            callers,
+        }
+    }
+}
+
+/// The components, outermost first, whose requests are waiting on a service
+/// chaining request. Set as an extension on chained requests.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct ChainedCallers(Vec<String>);
+
+impl ChainedCallers {
+    /// The callers of a chained request made by the given component.
+    fn with_caller(&self, component_id: &str) -> Self {
+        let mut callers = self.0.clone();
+        callers.push(component_id.to_owned());
+        Self(callers)
+    }
+
+    pub(crate) fn contains(&self, component_id: &str) -> bool {
+        self.0.iter().any(|id| id == component_id)
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.0.is_empty()
     }
 }
 
@@ -32,15 +68,39 @@ impl<F: RuntimeFactors> intercept::OutboundHttpInterceptor for OutboundHttpInter
     async fn intercept(&self, request: InterceptRequest) -> HttpResult<InterceptOutcome> {
         // Handle service chaining requests
         if let Some(component_id) = parse_service_chaining_target(request.uri()) {
-            let req = request.into_hyper_request();
+            let mut req = request.into_hyper_request();
+            req.extensions_mut()
+                .insert(self.callers.with_caller(&self.component_id));
             let path = req.uri().path().to_owned();
-            let route_match = RouteMatch::synthetic(component_id, path);
+
+            // The current span is the calling component's outbound request
+            // span; the chained component's handling becomes its child.
+            let client_span = tracing::Span::current();
+            if let Some(host) = req.uri().host() {
+                client_span.record("server.address", host);
+            }
+            let server_span = tracing::info_span!(
+                "spin_trigger_http.handle_chained_request",
+                "otel.kind" = "server",
+                "otel.name" = format!("{} {component_id}", req.method()),
+                "http.request.method" = %req.method(),
+                "url.path" = path,
+                "spin.component.id" = component_id,
+                "spin.caller.component.id" = self.component_id,
+                "http.response.status_code" = Empty,
+            );
+
+            let route_match = RouteMatch::synthetic(component_id.clone(), path);
             let resp = self
                 .server
                 .handle_trigger_route(req, route_match, Scheme::HTTP, CHAINED_CLIENT_ADDR)
+                .instrument(server_span.clone())
                 .await
                 .to_wasmtime_result()
                 .map_err(HttpError::trap)?;
+            let status = resp.status().as_u16();
+            server_span.record("http.response.status_code", status);
+            client_span.record("http.response.status_code", status);
             Ok(InterceptOutcome::Complete(resp))
         } else {
             Ok(InterceptOutcome::Continue(request))
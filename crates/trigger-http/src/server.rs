@@ -3,8 +3,11 @@ use std::{
     future::Future,
     io::{ErrorKind, IsTerminal},
     net::SocketAddr,
-    sync::Arc,
-    time::Duration,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+    },
+    time::{Duration, Instant},
 };
 
 use anyhow::{Context, bail};
@@ -23,13 +26,15 @@ use hyper_util::{
 };
 use rand::Rng;
 use spin_app::{APP_DESCRIPTION_KEY, APP_NAME_KEY};
+use spin_core::MemoryBudgetExhausted;
 use spin_factor_outbound_http::{OutboundHttpFactor, SelfRequestOrigin};
+use spin_factor_variables::VariablesFactor;
 use spin_factors::RuntimeFactors;
 use spin_factors_executor::InstanceState;
 use spin_http::{
     app_info::AppInfo,
     body,
-    config::{HttpExecutorType, HttpTriggerConfig},
+    config::{HttpAuth, HttpExecutorType, HttpTriggerConfig},
     routes::{RouteInfo, RouteMatch, Router},
     trigger::HandlerType,
 };
@@ -38,6 +43,7 @@ use tokio::{
     net::TcpListener,
     task,
 };
+use tokio_rustls::TlsAcceptor;
 use tracing::Instrument;
 use wasmtime::ToWasmtimeResult;
 use wasmtime_wasi::p2::bindings::CommandIndices;
@@ -45,11 +51,19 @@ use wasmtime_wasi_http::handler::{HandlerState, StoreBundle};
 use wasmtime_wasi_http::p2::body::HyperOutgoingBody;
 
 use crate::{
-    Body, InstanceReuseConfig, NotFoundRouteKind, OutputFormat, TlsConfig, TriggerApp,
-    TriggerInstanceBuilder,
-    headers::strip_forbidden_headers,
+    AcmeChallenge, AcmeConfig, Body, ConcurrencyLimitConfig, InstanceReuseConfig,
+    NotFoundRouteKind, OutputFormat, TlsConfig, TriggerApp, TriggerInstanceBuilder,
+    TriggerMetadata,
+    acme::AcmeManager,
+    affinity::SessionRouter,
+    canary::Canary,
+    client_ip::{self, IpFilter},
+    concurrency::{AppLimiter, ComponentLimiter, RequestPermits},
+    experiment::Experiment,
+    headers::{strip_forbidden_headers, strip_runtime_headers},
     instrument::{MatchedRoute, finalize_http_span, http_span, instrument_error},
-    outbound_http::OutboundHttpInterceptor,
+    oidc::{self, Authentication, OidcAuthenticator},
+    outbound_http::{ChainedCallers, OutboundHttpInterceptor},
     spin::SpinHttpExecutor,
     wagi::WagiHttpExecutor,
     wasi::WasiHttpExecutor,
@@ -64,6 +78,8 @@ pub struct HttpServer<F: RuntimeFactors> {
     listen_addr: SocketAddr,
     /// The TLS configuration for the server.
     tls_config: Option<TlsConfig>,
+    /// Obtains the server's TLS certificate with ACME, if configured.
+    acme: Option<Arc<AcmeManager>>,
     /// The maximum buffer size for an HTTP1 connection.
     http1_max_buf_size: Option<usize>,
     /// Whether to find a free port if the specified port is already in use.
@@ -76,20 +92,48 @@ pub struct HttpServer<F: RuntimeFactors> {
     trigger_app: Arc<TriggerApp<F>>,
     // Component ID -> component trigger config
     component_trigger_configs: HashMap<spin_http::routes::TriggerLookupKey, HttpTriggerConfig>,
+    // Component ID -> canary version of the component
+    component_canaries: HashMap<String, Canary>,
+    // Component ID -> A/B experiment on the component's route
+    component_experiments: HashMap<String, Experiment>,
+    // Route -> the client addresses allowed to use it
+    route_ip_filters: HashMap<spin_http::routes::TriggerLookupKey, IpFilter>,
+    /// The number of proxies in front of the server whose `X-Forwarded-For`
+    /// entries are trusted.
+    trusted_proxies: usize,
     // Component ID -> handler type
-    component_handler_types: HashMap<String, HandlerType<HttpHandlerState<F>>>,
+    component_handler_types: HashMap<String, Mutex<ComponentHandlerType<F>>>,
+    /// How the instances of WASIp3 components are reused.
+    reuse_config: InstanceReuseConfig,
+    // Component ID -> session router, for components with session affinity
+    component_session_routers: HashMap<String, SessionRouter<F>>,
+    // Component ID -> concurrency limiter
+    component_limiters: HashMap<String, ComponentLimiter>,
+    /// The limiter shared by all components, if an app-wide limit is set.
+    app_limiter: Option<Arc<AppLimiter>>,
+    /// The status code for requests shed by a concurrency limiter.
+    shed_status: StatusCode,
+    /// Authenticates requests to routes with `auth = "oidc"`, if the app
+    /// configures an OpenID Connect provider.
+    oidc: Option<OidcAuthenticator>,
+    /// The number of requests being handled.
+    in_flight: AtomicUsize,
 }
 
 impl<F: RuntimeFactors> HttpServer<F> {
     /// Create a new [`HttpServer`].
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         listen_addr: SocketAddr,
         tls_config: Option<TlsConfig>,
+        acme_config: Option<AcmeConfig>,
         find_free_port: bool,
         trigger_app: TriggerApp<F>,
         http1_max_buf_size: Option<usize>,
         reuse_config: InstanceReuseConfig,
+        concurrency_config: ConcurrencyLimitConfig,
         output_format: OutputFormat,
+        trusted_proxies: usize,
     ) -> anyhow::Result<Self> {
         // This needs to be a vec before building the router to handle duplicate routes
         let component_trigger_configs = trigger_app
@@ -132,51 +176,159 @@ impl<F: RuntimeFactors> HttpServer<F> {
         // Now that router is built we can merge duplicate routes by component
         let component_trigger_configs = HashMap::from_iter(component_trigger_configs);
 
+        let oidc = trigger_app
+            .app()
+            .get_trigger_metadata::<TriggerMetadata>("http")?
+            .unwrap_or_default()
+            .oidc
+            .map(OidcAuthenticator::new)
+            .transpose()?;
+        if oidc.is_none()
+            && let Some(key) = component_trigger_configs
+                .iter()
+                .find_map(|(key, config)| (config.auth == Some(HttpAuth::Oidc)).then_some(key))
+        {
+            bail!(
+                "Trigger for {key} requires OIDC authentication, but the application has no [application.trigger.http.oidc] provider"
+            );
+        }
+
+        let acme = acme_config
+            .map(|config| AcmeManager::new(config).map(Arc::new))
+            .transpose()?;
+
         let trigger_app = Arc::new(trigger_app);
 
-        let component_handler_types = component_trigger_configs
+        let mut component_canaries = HashMap::new();
+        let mut component_experiments = HashMap::new();
+        let mut route_ip_filters = HashMap::new();
+        for (key, trigger_config) in &component_trigger_configs {
+            if let Some(ip_filter) = &trigger_config.ip_filter {
+                let ip_filter = IpFilter::from_config(ip_filter)
+                    .with_context(|| format!("invalid IP filter for {key}"))?;
+                route_ip_filters.insert(key.clone(), ip_filter);
+            }
+            let canary = Canary::from_config(trigger_config)
+                .with_context(|| format!("invalid canary for {key}"))?;
+            let experiment = trigger_config
+                .experiment
+                .as_ref()
+                .map(Experiment::from_config)
+                .transpose()
+                .with_context(|| format!("invalid experiment for {key}"))?;
+            match key {
+                spin_http::routes::TriggerLookupKey::Component(component) => {
+                    if let Some(canary) = canary {
+                        component_canaries.insert(component.clone(), canary);
+                    }
+                    if let Some(experiment) = experiment {
+                        component_experiments.insert(component.clone(), experiment);
+                    }
+                }
+                spin_http::routes::TriggerLookupKey::Trigger(trigger) => {
+                    if trigger_config.affinity.is_some() {
+                        bail!("Trigger {trigger} has session affinity but no component");
+                    }
+                    if experiment.is_some() {
+                        bail!("Trigger {trigger} has an experiment but no component");
+                    }
+                }
+            }
+        }
+
+        // The components each route may invoke: its own, and its canary's
+        let route_components = component_trigger_configs
             .iter()
             .filter_map(|(key, trigger_config)| match key {
-                spin_http::routes::TriggerLookupKey::Component(component) => Some(
-                    Self::handler_type_for_component(
-                        &trigger_app,
-                        component,
-                        &trigger_config.executor,
-                        reuse_config,
-                    )
-                    .map(|ht| (component.clone(), ht)),
-                ),
+                spin_http::routes::TriggerLookupKey::Component(component) => {
+                    Some((component, trigger_config))
+                }
                 spin_http::routes::TriggerLookupKey::Trigger(_) => None,
             })
-            .collect::<anyhow::Result<_>>()?;
+            .flat_map(|(component, trigger_config)| {
+                let canary = component_canaries.get(component).map(Canary::component);
+                std::iter::once(component.as_str())
+                    .chain(canary)
+                    .map(move |component| (component, trigger_config))
+            })
+            .collect::<Vec<_>>();
+
+        let component_handler_types = route_components
+            .iter()
+            .map(|(component, trigger_config)| {
+                let generation = trigger_app.component_generation(component)?;
+                let handler_type = Self::handler_type_for_component(
+                    &trigger_app,
+                    component,
+                    &trigger_app.get_instance_pre(component)?,
+                    &trigger_config.executor,
+                    reuse_config,
+                )?;
+                let handler_type = ComponentHandlerType {
+                    generation,
+                    handler_type: Arc::new(handler_type),
+                };
+                Ok((component.to_string(), Mutex::new(handler_type)))
+            })
+            .collect::<anyhow::Result<HashMap<_, _>>>()?;
+        let component_session_routers = route_components
+            .iter()
+            .filter_map(|(component, trigger_config)| {
+                let affinity = trigger_config.affinity.as_ref()?;
+                Some(
+                    SessionRouter::new(affinity, &trigger_app, component, reuse_config)
+                        .with_context(|| {
+                            format!("invalid session affinity for component {component:?}")
+                        })
+                        .map(|router| (component.to_string(), router)),
+                )
+            })
+            .collect::<anyhow::Result<HashMap<_, _>>>()?;
+        let component_limiters = concurrency_config.limiters(route_components.iter().map(
+            |(component, trigger_config)| (*component, trigger_config.concurrency.as_ref()),
+        ))?;
+        let app_limiter = concurrency_config.app_limiter()?;
         Ok(Self {
             listen_addr,
             tls_config,
+            acme,
             find_free_port,
             router,
             trigger_app,
             http1_max_buf_size,
             component_trigger_configs,
+            component_canaries,
+            component_experiments,
+            route_ip_filters,
+            trusted_proxies,
             component_handler_types,
+            reuse_config,
+            component_session_routers,
+            component_limiters,
+            app_limiter,
+            shed_status: concurrency_config.shed_status,
+            oidc,
             output_format,
+            in_flight: AtomicUsize::new(0),
         })
     }
 
+    /// The number of requests the server is handling.
+    pub fn in_flight_requests(&self) -> usize {
+        self.in_flight.load(Ordering::SeqCst)
+    }
+
     fn handler_type_for_component(
         trigger_app: &Arc<TriggerApp<F>>,
         component_id: &str,
+        pre: &wasmtime::component::InstancePre<InstanceState<F::InstanceState, ()>>,
         executor: &Option<HttpExecutorType>,
         reuse_config: InstanceReuseConfig,
     ) -> anyhow::Result<HandlerType<HttpHandlerState<F>>> {
-        let pre = trigger_app.get_instance_pre(component_id)?;
         let handler_type = match executor {
             None | Some(HttpExecutorType::Http) => HandlerType::from_instance_pre(
                 pre,
-                HttpHandlerState {
-                    trigger_app: trigger_app.clone(),
-                    component_id: component_id.into(),
-                    reuse_config,
-                },
+                HttpHandlerState::new(trigger_app.clone(), component_id.into(), reuse_config),
             )?,
             Some(HttpExecutorType::Wagi(wagi_config)) => {
                 anyhow::ensure!(
@@ -193,6 +345,41 @@ impl<F: RuntimeFactors> HttpServer<F> {
         Ok(handler_type)
     }
 
+    /// Returns the handler type of the component version an instance builder
+    /// instantiates, rebuilding it if the component has been swapped.
+    fn handler_type(
+        &self,
+        component_id: &str,
+        instance_builder: &TriggerInstanceBuilder<F>,
+        executor: &Option<HttpExecutorType>,
+    ) -> anyhow::Result<Arc<HandlerType<HttpHandlerState<F>>>> {
+        let mut current = self
+            .component_handler_types
+            .get(component_id)
+            .with_context(|| format!("unknown component ID {component_id:?}"))?
+            .lock()
+            .unwrap();
+        let generation = instance_builder.component_generation();
+        if generation == current.generation {
+            return Ok(current.handler_type.clone());
+        }
+        let handler_type = Arc::new(Self::handler_type_for_component(
+            &self.trigger_app,
+            component_id,
+            instance_builder.instance_pre(),
+            executor,
+            self.reuse_config,
+        )?);
+        // A request may have prepared an older version just before a swap.
+        if generation > current.generation {
+            *current = ComponentHandlerType {
+                generation,
+                handler_type: handler_type.clone(),
+            };
+        }
+        Ok(handler_type)
+    }
+
     /// Serve incoming requests over the provided [`TcpListener`].
     pub async fn serve(self: Arc<Self>) -> anyhow::Result<()> {
         let listener: TcpListener = if self.find_free_port {
@@ -207,8 +394,19 @@ impl<F: RuntimeFactors> HttpServer<F> {
             })?
         };
 
-        if let Some(tls_config) = self.tls_config.clone() {
-            self.serve_https(listener, tls_config).await?;
+        if let Some(acme) = self.acme.clone() {
+            if acme.challenge() == AcmeChallenge::TlsAlpn01 && listener.local_addr()?.port() != 443
+            {
+                tracing::warn!(
+                    "ACME tls-alpn-01 challenges are made on port 443; certificates can only be obtained if it is forwarded to {}",
+                    listener.local_addr()?
+                );
+            }
+            acme.start().await?;
+            self.serve_https(listener, acme.acceptor()).await?;
+        } else if let Some(tls_config) = self.tls_config.clone() {
+            let acceptor = tls_config.server_config()?;
+            self.serve_https(listener, acceptor).await?;
         } else {
             self.serve_http(listener).await?;
         }
@@ -258,13 +456,14 @@ impl<F: RuntimeFactors> HttpServer<F> {
     async fn serve_https(
         self: Arc<Self>,
         listener: TcpListener,
-        tls_config: TlsConfig,
+        acceptor: TlsAcceptor,
     ) -> anyhow::Result<()> {
         self.print_startup_msgs("https", &listener)?;
-        let acceptor = tls_config.server_config()?;
         loop {
             let (stream, client_addr) = listener.accept().await?;
             match acceptor.accept(stream).await {
+                // The certificate authority's TLS-ALPN-01 validation ends with the handshake.
+                Ok(stream) if AcmeManager::is_challenge_connection(stream.get_ref().1) => {}
                 Ok(stream) => self
                     .clone()
                     .serve_connection(stream, Scheme::HTTPS, client_addr),
@@ -298,13 +497,31 @@ impl<F: RuntimeFactors> HttpServer<F> {
                     Response::new(body::full(Bytes::from_static(b"OK"))),
                     path,
                 )),
+                "ready" => Ok(MatchedRoute::with_response_extension(
+                    Self::readiness(),
+                    path,
+                )),
                 "info" => self.app_info(path),
+                oidc::CALLBACK_PATH => self.oidc_callback(req, server_scheme, path).await,
                 _ => Self::not_found(NotFoundRouteKind::WellKnown),
             };
         }
 
         match self.router.route(&path) {
             Ok(route_match) => {
+                let client_addr =
+                    client_ip::client_addr(req.headers(), client_addr, self.trusted_proxies);
+                if let Some(ip_filter) = self.route_ip_filters.get(route_match.lookup_key())
+                    && !ip_filter.permits(client_addr.ip())
+                {
+                    tracing::info!("Refusing request from {} to '{path}'", client_addr.ip());
+                    return Ok(MatchedRoute::with_response_extension(
+                        Response::builder()
+                            .status(StatusCode::FORBIDDEN)
+                            .body(body::empty())?,
+                        route_match.raw_route(),
+                    ));
+                }
                 self.handle_trigger_route(req, route_match, server_scheme, client_addr)
                     .await
             }
@@ -320,6 +537,8 @@ impl<F: RuntimeFactors> HttpServer<F> {
         server_scheme: Scheme,
         client_addr: SocketAddr,
     ) -> anyhow::Result<Response<Body>> {
+        // Service chained requests reach here without going through `handle`
+        strip_runtime_headers(&mut req);
         set_req_uri(&mut req, server_scheme.clone())?;
         let app_id = self
             .trigger_app
@@ -329,19 +548,56 @@ impl<F: RuntimeFactors> HttpServer<F> {
 
         let lookup_key = route_match.lookup_key();
 
+        let trigger_config = self
+            .component_trigger_configs
+            .get(lookup_key)
+            .with_context(|| format!("unknown routing destination '{lookup_key}'"))?;
+
+        // Choose between the component and its canary, if it has one
+        let component = trigger_config.component.as_deref().map(|component| {
+            match self.component_canaries.get(component) {
+                Some(canary) => canary.choose(component, req.headers()),
+                None => component,
+            }
+        });
+        // Metrics are recorded against the version handling the request
+        let metrics_component_id = component.map_or_else(|| lookup_key.to_string(), Into::into);
+
         spin_telemetry::metrics::monotonic_counter!(
             spin.request_count = 1,
             trigger_type = "http",
             app_id = app_id,
-            component_id = lookup_key.to_string()
+            component_id = metrics_component_id.clone()
         );
 
-        let trigger_config = self
-            .component_trigger_configs
-            .get(lookup_key)
-            .with_context(|| format!("unknown routing destination '{lookup_key}'"))?;
+        if trigger_config.auth == Some(HttpAuth::Oidc) {
+            let oidc = self
+                .oidc
+                .as_ref()
+                .context("OIDC authentication is not configured")?;
+            if let Authentication::Challenge(res) =
+                oidc.authenticate(&mut req, self.variables()?).await?
+            {
+                return Ok(MatchedRoute::with_response_extension(
+                    res,
+                    route_match.raw_route(),
+                ));
+            }
+        }
 
-        match (&trigger_config.component, &trigger_config.static_response) {
+        if let Some(experiment) = trigger_config
+            .component
+            .as_ref()
+            .and_then(|component| self.component_experiments.get(component))
+        {
+            experiment.assign(req.headers_mut());
+        }
+
+        // The route match is handed on to the component, so keep the route
+        // for the metrics recorded afterwards.
+        let route = route_match.raw_route().to_owned();
+        let start = Instant::now();
+        let res = match (component, &trigger_config.static_response) {
             (Some(component), None) => {
                 self.respond_wasm_component(
                     req,
@@ -363,7 +619,21 @@ impl<F: RuntimeFactors> HttpServer<F> {
                 "Triggers must specify either component or static_response - both are specified for {}",
                 route_match.raw_route()
             )),
-        }
+        };
+
+        // Errors become 500 responses further up.
+        let status_code = res.as_ref().map_or(500, |res| res.status().as_u16());
+        spin_telemetry::metrics::histogram!(
+            spin.request_duration = start.elapsed().as_secs_f64(),
+            trigger_type = "http",
+            app_id = app_id,
+            component_id = metrics_component_id,
+            route = route,
+            status_code = status_code,
+            unit = "s"
+        );
+
+        res
     }
 
     async fn respond_wasm_component(
@@ -375,7 +645,41 @@ impl<F: RuntimeFactors> HttpServer<F> {
         component_id: &str,
         executor: &Option<HttpExecutorType>,
     ) -> anyhow::Result<Response<Body>> {
-        let mut instance_builder = self.trigger_app.prepare(component_id)?;
+        // A service chaining request runs while its callers hold their
+        // slots, so it must not wait for (or be shed for want of) the
+        // slots they hold.
+        let callers = req
+            .extensions()
+            .get::<ChainedCallers>()
+            .cloned()
+            .unwrap_or_default();
+        let mut permits = RequestPermits::default();
+        if let Some(limiter) = self.component_limiters.get(component_id)
+            && !callers.contains(component_id)
+        {
+            match limiter.acquire().await {
+                Some(permit) => permits.component = Some(permit),
+                None => return self.shed_request(component_id, route_match.raw_route()),
+            }
+        }
+        if let Some(limiter) = &self.app_limiter
+            && callers.is_empty()
+        {
+            match limiter.acquire(component_id).await {
+                Some(permit) => permits.app = Some(permit),
+                None => return self.shed_request(component_id, route_match.raw_route()),
+            }
+        }
+
+        tracing::Span::current().record("spin.component.id", component_id);
+
+        let mut instance_builder = match self.trigger_app.prepare(component_id) {
+            Ok(builder) => builder,
+            Err(err) if err.is::<MemoryBudgetExhausted>() => {
+                return self.memory_exhausted(component_id, route_match.raw_route());
+            }
+            Err(err) => return Err(err),
+        };
 
         // Set up outbound HTTP request origin and service chaining
         // The outbound HTTP factor is required since both inbound and outbound wasi HTTP
@@ -388,13 +692,15 @@ impl<F: RuntimeFactors> HttpServer<F> {
         )?;
         let origin = SelfRequestOrigin::create(server_scheme, &self.listen_addr.to_string())?;
         outbound_http.set_self_request_origin(origin);
-        outbound_http.set_request_interceptor(OutboundHttpInterceptor::new(self.clone()))?;
+        outbound_http.set_request_interceptor(OutboundHttpInterceptor::new(
+            self.clone(),
+            component_id,
+            callers,
+        ))?;
 
         // Prepare HTTP executor
-        let handler_type = self
-            .component_handler_types
-            .get(component_id)
-            .with_context(|| format!("unknown component ID {component_id:?}"))?;
+        let handler_type = self.handler_type(component_id, &instance_builder, executor)?;
+        let handler_type = handler_type.as_ref();
         let executor = executor.as_ref().unwrap_or(&HttpExecutorType::Http);
 
         let res = match executor {
@@ -405,7 +711,11 @@ impl<F: RuntimeFactors> HttpServer<F> {
                         .await
                 }
                 HandlerType::Wasi0_3(_, handler) => {
-                    Wasip3HttpExecutor(handler)
+                    let session_handler = self
+                        .component_session_routers
+                        .get(component_id)
+                        .and_then(|router| router.handler(req.headers(), &instance_builder));
+                    Wasip3HttpExecutor(session_handler.as_deref().unwrap_or(handler))
                         .execute(&route_match, req, client_addr)
                         .await
                 }
@@ -434,7 +744,7 @@ impl<F: RuntimeFactors> HttpServer<F> {
         };
         match res {
             Ok(res) => Ok(MatchedRoute::with_response_extension(
-                res,
+                res.map(|body| permits.hold_until_sent(body)),
                 route_match.raw_route(),
             )),
             Err(err) => {
@@ -463,6 +773,18 @@ impl<F: RuntimeFactors> HttpServer<F> {
         Ok(response.body(body)?)
     }
 
+    /// Reports whether the server should receive new requests, which stops
+    /// as soon as it is asked to shut down.
+    fn readiness() -> Response<Body> {
+        if spin_trigger::shutdown::state().is_ready() {
+            Response::new(body::full(Bytes::from_static(b"OK")))
+        } else {
+            let mut response = Response::new(body::full(Bytes::from_static(b"shutting down")));
+            *response.status_mut() = StatusCode::SERVICE_UNAVAILABLE;
+            response
+        }
+    }
+
     /// Returns spin status information.
     fn app_info(&self, route: String) -> anyhow::Result<Response<Body>> {
         let info = AppInfo::new(self.trigger_app.app());
@@ -475,6 +797,29 @@ impl<F: RuntimeFactors> HttpServer<F> {
         ))
     }
 
+    /// Completes an OpenID Connect sign in.
+    async fn oidc_callback(
+        &self,
+        mut req: Request<Body>,
+        server_scheme: Scheme,
+        route: String,
+    ) -> anyhow::Result<Response<Body>> {
+        let Some(oidc) = &self.oidc else {
+            return Self::not_found(NotFoundRouteKind::WellKnown);
+        };
+        set_req_uri(&mut req, server_scheme)?;
+        let (parts, _) = req.into_parts();
+        let res = oidc.callback(&parts, self.variables()?).await?;
+        Ok(MatchedRoute::with_response_extension(res, route))
+    }
+
+    fn variables(&self) -> anyhow::Result<&spin_factor_variables::AppState> {
+        self.trigger_app
+            .configured_app()
+            .app_state::<VariablesFactor>()
+            .context("OIDC authentication requires the variables factor")
+    }
+
     /// Creates an HTTP 500 response.
     fn internal_error(
         body: Option<&str>,
@@ -493,9 +838,51 @@ impl<F: RuntimeFactors> HttpServer<F> {
         ))
     }
 
+    /// Creates a response for a request rejected because the component is at capacity.
+    fn shed_request(
+        &self,
+        component_id: &str,
+        route: impl Into<String>,
+    ) -> anyhow::Result<Response<Body>> {
+        tracing::warn!("Component '{component_id}' is at capacity; shedding request");
+        spin_telemetry::metrics::monotonic_counter!(
+            spin.request_shed_count = 1,
+            trigger_type = "http",
+            component_id = component_id
+        );
+        Ok(MatchedRoute::with_response_extension(
+            Response::builder()
+                .status(self.shed_status)
+                .header(http::header::RETRY_AFTER, "1")
+                .body(body::empty())?,
+            route,
+        ))
+    }
+
+    /// Creates a response for a request rejected because the app's memory
+    /// limit has no room for another instance.
+    fn memory_exhausted(
+        &self,
+        component_id: &str,
+        route: impl Into<String>,
+    ) -> anyhow::Result<Response<Body>> {
+        tracing::warn!("App memory limit reached; refusing request for component '{component_id}'");
+        spin_telemetry::metrics::monotonic_counter!(
+            spin.request_shed_count = 1,
+            trigger_type = "http",
+            component_id = component_id
+        );
+        Ok(MatchedRoute::with_response_extension(
+            Response::builder()
+                .status(StatusCode::SERVICE_UNAVAILABLE)
+                .header(http::header::RETRY_AFTER, "1")
+                .body(body::empty())?,
+            route,
+        ))
+    }
+
     /// Creates an HTTP 404 response.
     fn not_found(kind: NotFoundRouteKind) -> anyhow::Result<Response<Body>> {
-        use std::sync::atomic::{AtomicBool, Ordering};
         static SHOWN_GENERIC_404_WARNING: AtomicBool = AtomicBool::new(false);
         if let NotFoundRouteKind::Normal(route) = kind
             && !SHOWN_GENERIC_404_WARNING.fetch_or(true, Ordering::Relaxed)
@@ -523,25 +910,34 @@ impl<F: RuntimeFactors> HttpServer<F> {
                 server_builder.http1().max_buf_size(http1_max_buf_size);
             }
 
-            if let Err(err) = server_builder
-                .serve_connection(
-                    TokioIo::new(stream),
-                    service_fn(move |request| {
-                        self.clone().instrumented_service_fn(
-                            server_scheme.clone(),
-                            client_addr,
-                            request,
-                        )
-                    }),
-                )
-                .await
-            {
+            let shutdown = spin_trigger::shutdown::state();
+            let _connection = shutdown.track_connection();
+            let connection = server_builder.serve_connection(
+                TokioIo::new(stream),
+                service_fn(move |request| {
+                    self.clone().instrumented_service_fn(
+                        server_scheme.clone(),
+                        client_addr,
+                        request,
+                    )
+                }),
+            );
+            tokio::pin!(connection);
+            let result = tokio::select! {
+                result = connection.as_mut() => result,
+                () = shutdown.draining() => {
+                    // Finish the requests in progress, then close.
+                    connection.as_mut().graceful_shutdown();
+                    connection.await
+                }
+            };
+            if let Err(err) = result {
                 tracing::warn!("Error serving HTTP connection: {err:?}");
             }
         });
     }
 
-    async fn instrumented_service_fn(
+    pub(crate) async fn instrumented_service_fn(
         self: Arc<Self>,
         server_scheme: Scheme,
         client_addr: SocketAddr,
@@ -550,6 +946,7 @@ impl<F: RuntimeFactors> HttpServer<F> {
         let span = http_span!(request, client_addr);
         let method = request.method().to_string();
         async {
+            let _in_flight = InFlightGuard::new(&self.in_flight);
             let result = self
                 .handle(
                     request.map(|body: Incoming| {
@@ -673,6 +1070,24 @@ fn set_req_uri(req: &mut Request<Body>, scheme: Scheme) -> anyhow::Result<()> {
     Ok(())
 }
 
+/// Counts a request as in flight until dropped.
+struct InFlightGuard<'a> {
+    in_flight: &'a AtomicUsize,
+}
+
+impl<'a> InFlightGuard<'a> {
+    fn new(in_flight: &'a AtomicUsize) -> Self {
+        in_flight.fetch_add(1, Ordering::SeqCst);
+        Self { in_flight }
+    }
+}
+
+impl Drop for InFlightGuard<'_> {
+    fn drop(&mut self) {
+        self.in_flight.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
 /// An HTTP executor.
 pub(crate) trait HttpExecutor {
     fn execute<F: RuntimeFactors>(
@@ -684,24 +1099,54 @@ pub(crate) trait HttpExecutor {
     ) -> impl Future<Output = anyhow::Result<Response<Body>>>;
 }
 
+/// The handler type of a version of a component.
+struct ComponentHandlerType<F: RuntimeFactors> {
+    /// The component's generation when the version was loaded.
+    generation: u64,
+    handler_type: Arc<HandlerType<HttpHandlerState<F>>>,
+}
+
 pub(crate) struct HttpHandlerState<F: RuntimeFactors> {
     trigger_app: Arc<TriggerApp<F>>,
     component_id: String,
     reuse_config: InstanceReuseConfig,
+    /// Whether the component's stores have an execution time limit, in which
+    /// case instances aren't reused concurrently, as concurrent requests
+    /// would share the budget of their store.
+    execution_time_limited: AtomicBool,
+}
+
+impl<F: RuntimeFactors> HttpHandlerState<F> {
+    pub fn new(
+        trigger_app: Arc<TriggerApp<F>>,
+        component_id: String,
+        reuse_config: InstanceReuseConfig,
+    ) -> Self {
+        Self {
+            trigger_app,
+            component_id,
+            reuse_config,
+            execution_time_limited: AtomicBool::new(false),
+        }
+    }
 }
 
 impl<F: RuntimeFactors> HandlerState for HttpHandlerState<F> {
     type StoreData = InstanceState<F::InstanceState, ()>;
 
     fn new_store(&self, _req_id: Option<u64>) -> wasmtime::Result<StoreBundle<Self::StoreData>> {
+        let store = self
+            .trigger_app
+            .prepare(&self.component_id)
+            .to_wasmtime_result()?
+            .instantiate_store(())
+            .to_wasmtime_result()?;
+        // The reuse counts are queried after each new store is created.
+        if store.data().core_state().max_execution_time().is_some() {
+            self.execution_time_limited.store(true, Ordering::Relaxed);
+        }
         Ok(StoreBundle {
-            store: self
-                .trigger_app
-                .prepare(&self.component_id)
-                .to_wasmtime_result()?
-                .instantiate_store(())
-                .to_wasmtime_result()?
-                .into_inner(),
+            store: store.into_inner(),
             write_profile: Box::new(|_| ()),
         })
     }
@@ -722,6 +1167,9 @@ impl<F: RuntimeFactors> HandlerState for HttpHandlerState<F> {
     }
 
     fn max_instance_concurrent_reuse_count(&self) -> usize {
+        if self.execution_time_limited.load(Ordering::Relaxed) {
+            return 1;
+        }
         rand::rng().random_range(self.reuse_config.max_instance_concurrent_reuse_count)
     }
 
@@ -49,6 +49,8 @@ impl<F: RuntimeFactors> Wasip3HttpExecutor<'_, F> {
                         };
 
                         let request = store.with(|mut store| {
+                            // The instance may have handled earlier requests.
+                            store.data_mut().core_state_mut().reset_execution_time();
                             anyhow::Ok(wasi_http::<F>(store.data_mut())?.table.push(request)?)
                         })?;
 
@@ -0,0 +1,397 @@
+//! A trigger which runs background jobs enqueued through `spin:jobs/queue`,
+//! and invocations scheduled through `spin:jobs/scheduler`.
+//!
+//! Each job name is handled by one component, which exports
+//! `spin:jobs/handler`. A trigger without a job only lets the component be
+//! invoked by scheduled invocations, which any component with a job trigger
+//! may be:
+//!
+//! ```toml
+//! [[trigger.job]]
+//! component = "mailer"
+//! job = "send-email"
+//! max_attempts = 5
+//! backoff = "2s"
+//!
+//! [[trigger.job]]
+//! component = "reminder-sender"
+//! ```
+//!
+//! The trigger polls the queue for due jobs and runs each of them. A job
+//! whose handler fails or traps is retried after a backoff, which doubles
+//! after each failure, until it runs out of attempts and is moved to
+//! dead-letter storage. Jobs with no handler in the app are left queued.
+
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime},
+};
+
+use anyhow::{Context as _, bail, ensure};
+use clap::Args;
+use serde::Deserialize;
+use spin_common::arg_parser::parse_duration;
+use spin_factor_jobs::{Failure, Job, JobQueue, JobsFactor, RetryPolicy, millis_since_epoch};
+use spin_factors::RuntimeFactors;
+use spin_trigger::{App, Trigger, TriggerApp};
+use spin_world::exports::spin::jobs::handler;
+use tracing::{Level, instrument};
+
+pub struct JobTrigger {
+    poll_interval: Duration,
+}
+
+#[derive(Args)]
+pub struct CliArgs {
+    /// How often to check the job queue for due jobs
+    #[clap(
+        long = "job-poll-interval",
+        env = "SPIN_JOB_POLL_INTERVAL",
+        default_value = "1s",
+        value_parser = parse_duration
+    )]
+    pub poll_interval: Duration,
+}
+
+/// Job trigger configuration.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TriggerConfig {
+    /// Component ID to invoke
+    component: String,
+    /// Name of the job the component handles, if any
+    job: Option<String>,
+    /// Number of attempts to make, for jobs enqueued without their own
+    max_attempts: Option<u32>,
+    /// Wait before the first retry, for jobs enqueued without their own
+    backoff: Option<String>,
+}
+
+/// The component handling a job, and how the job is retried.
+#[derive(Debug)]
+struct Handler {
+    component: String,
+    policy: RetryPolicy,
+}
+
+impl<F: RuntimeFactors> Trigger<F> for JobTrigger {
+    const TYPE: &'static str = "job";
+
+    type CliArgs = CliArgs;
+
+    type InstanceState = ();
+
+    fn new(cli_args: Self::CliArgs, _app: &App) -> anyhow::Result<Self> {
+        ensure!(
+            !cli_args.poll_interval.is_zero(),
+            "--job-poll-interval must be greater than zero"
+        );
+        Ok(Self {
+            poll_interval: cli_args.poll_interval,
+        })
+    }
+
+    async fn run(self, trigger_app: TriggerApp<Self, F>) -> anyhow::Result<()> {
+        let queue = trigger_app
+            .configured_app()
+            .app_state::<JobsFactor>()
+            .context("JobTrigger depends on JobsFactor")?
+            .queue();
+        ensure!(
+            queue.is_defined(),
+            "the key-value store {:?} for jobs is not defined",
+            queue.label()
+        );
+        let handlers = Handlers::new(
+            trigger_app
+                .app()
+                .trigger_configs::<TriggerConfig>(<Self as Trigger<F>>::TYPE)?
+                .into_iter()
+                .map(|(_, config)| config),
+        )?;
+
+        println!("Handling jobs from key-value store {:?}:", queue.label());
+        for (job, handler) in &handlers.jobs {
+            println!("\t{job}: {}", handler.component);
+        }
+        let mut components = handlers.components.keys().collect::<Vec<_>>();
+        components.sort();
+        for component in components {
+            println!("\t(scheduled): {component}");
+        }
+
+        let worker = Arc::new(Worker {
+            trigger_app,
+            queue,
+            handlers,
+            running: Default::default(),
+        });
+        let mut interval = tokio::time::interval(self.poll_interval);
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        loop {
+            interval.tick().await;
+            if let Err(err) = worker.clone().poll().await {
+                tracing::error!("Failed to poll job queue: {err:#}");
+            }
+        }
+    }
+}
+
+/// The components which run jobs and scheduled invocations.
+#[derive(Debug, Default)]
+struct Handlers {
+    /// Maps job names to their handlers.
+    jobs: HashMap<String, Handler>,
+    /// Maps the IDs of components with job triggers to how invocations
+    /// scheduled for them are retried.
+    components: HashMap<String, RetryPolicy>,
+}
+
+impl Handlers {
+    fn new(configs: impl IntoIterator<Item = TriggerConfig>) -> anyhow::Result<Self> {
+        let mut handlers = Self::default();
+        for config in configs {
+            let defaults = RetryPolicy::default();
+            let backoff = match &config.backoff {
+                Some(backoff) => parse_duration(backoff).with_context(|| {
+                    format!("invalid backoff for job trigger {:?}", config.component)
+                })?,
+                None => defaults.backoff,
+            };
+            let policy = RetryPolicy {
+                max_attempts: config.max_attempts.unwrap_or(defaults.max_attempts),
+                backoff,
+            };
+            let Some(job) = config.job else {
+                // The retry settings of a trigger without a job apply to
+                // scheduled invocations of the component.
+                handlers.components.insert(config.component, policy);
+                continue;
+            };
+            handlers
+                .components
+                .entry(config.component.clone())
+                .or_insert_with(RetryPolicy::default);
+            if let Some(existing) = handlers.jobs.get(&job) {
+                bail!(
+                    "job {job:?} is handled by both {:?} and {:?}; a job may only have one handler",
+                    existing.component,
+                    config.component
+                );
+            }
+            let handler = Handler {
+                component: config.component,
+                policy,
+            };
+            handlers.jobs.insert(job, handler);
+        }
+        Ok(handlers)
+    }
+
+    /// Returns the component to run a job with, and how to retry it.
+    fn get(&self, job: &Job) -> Option<(&str, &RetryPolicy)> {
+        match &job.component {
+            Some(component) => self
+                .components
+                .get_key_value(component)
+                .map(|(component, policy)| (component.as_str(), policy)),
+            None => self
+                .jobs
+                .get(&job.name)
+                .map(|handler| (handler.component.as_str(), &handler.policy)),
+        }
+    }
+}
+
+struct Worker<F: RuntimeFactors> {
+    trigger_app: TriggerApp<JobTrigger, F>,
+    queue: Arc<JobQueue>,
+    handlers: Handlers,
+    /// The IDs of the jobs being run, so they are not started again.
+    running: Mutex<HashSet<String>>,
+}
+
+impl<F: RuntimeFactors> Worker<F> {
+    /// Starts running the jobs which are due.
+    async fn poll(self: Arc<Self>) -> anyhow::Result<()> {
+        let now = SystemTime::now();
+        for job in self.queue.due(now).await? {
+            if self.handlers.get(&job).is_none() {
+                continue;
+            }
+            if !self.running.lock().unwrap().insert(job.id.clone()) {
+                continue;
+            }
+            let worker = self.clone();
+            tokio::spawn(async move {
+                let id = job.id;
+                if let Err(err) = worker.run_job(&id, now).await {
+                    tracing::error!("Failed to update job {id}: {err:#}");
+                }
+                worker.running.lock().unwrap().remove(&id);
+            });
+        }
+        Ok(())
+    }
+
+    /// Runs a job if it is still due at `now`, then removes it from the queue
+    /// or records its failure.
+    async fn run_job(&self, id: &str, now: SystemTime) -> anyhow::Result<()> {
+        // The job may have been run, rescheduled or cancelled since the queue
+        // was listed, so it is read again now that it is marked as running.
+        let Some(job) = self.queue.get(id).await? else {
+            return Ok(());
+        };
+        if job.run_at_ms > millis_since_epoch(now) {
+            return Ok(());
+        }
+        let (component, policy) = self.handlers.get(&job).context("job has no handler")?;
+        match self.dispatch(component, &job).await {
+            Ok(()) => self.queue.complete(&job).await,
+            Err(err) => {
+                let name = job_description(&job);
+                let error = format!("{err:#}");
+                match self.queue.fail(job, error, policy).await? {
+                    Failure::Retry { after } => tracing::info!(
+                        "Job {name} ({id}) failed and will be retried in {after:?}: {err:#}"
+                    ),
+                    Failure::DeadLettered => tracing::warn!(
+                        "Job {name} ({id}) failed and has run out of attempts: {err:#}"
+                    ),
+                    Failure::Cancelled => {
+                        tracing::info!("Job {name} ({id}) failed after it was cancelled: {err:#}")
+                    }
+                }
+                Ok(())
+            }
+        }
+    }
+
+    #[instrument(name = "spin_trigger_job.execute_wasm", skip_all, err(level = Level::INFO), fields(
+        otel.name = format!("execute_wasm_component {component_id}"),
+        spin.component.id = component_id,
+        spin.job.name = %job.name,
+        spin.job.id = %job.id,
+    ))]
+    async fn dispatch(&self, component_id: &str, job: &Job) -> anyhow::Result<()> {
+        spin_telemetry::extract_message_trace_context(&job.trace_context);
+        spin_telemetry::metrics::monotonic_counter!(
+            spin.request_count = 1,
+            trigger_type = "job",
+            app_id = self.trigger_app.app().id(),
+            component_id = component_id
+        );
+
+        let (instance, mut store) = self
+            .trigger_app
+            .prepare(component_id)?
+            .instantiate(())
+            .await?;
+        let pre = instance.instance_pre(&store);
+        let guest = handler::GuestIndices::new(&pre)
+            .context("component does not export spin:jobs/handler")?
+            .load(&mut store, &instance)?;
+
+        let job = handler::Job {
+            id: job.id.clone(),
+            name: job.name.clone(),
+            payload: job.payload.clone(),
+            attempt: job.attempts + 1,
+            scheduled_by: job.scheduled_by.clone(),
+        };
+        guest
+            .call_handle(&mut store, &job)
+            .await?
+            .map_err(|err| anyhow::anyhow!("job handler returned an error: {err}"))
+    }
+}
+
+/// Describes a job for logging.
+fn job_description(job: &Job) -> String {
+    match &job.component {
+        Some(component) => format!("scheduled invocation of {component}"),
+        None => job.name.clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(component: &str, job: Option<&str>) -> TriggerConfig {
+        TriggerConfig {
+            component: component.into(),
+            job: job.map(Into::into),
+            max_attempts: None,
+            backoff: None,
+        }
+    }
+
+    #[test]
+    fn handlers_take_retry_defaults() {
+        let handlers = Handlers::new([
+            config("mailer", Some("send-email")),
+            TriggerConfig {
+                max_attempts: Some(10),
+                backoff: Some("250ms".into()),
+                ..config("billing", Some("charge"))
+            },
+        ])
+        .unwrap();
+
+        let mailer = &handlers.jobs["send-email"];
+        assert_eq!("mailer", mailer.component);
+        assert_eq!(
+            RetryPolicy::default().max_attempts,
+            mailer.policy.max_attempts
+        );
+        let billing = &handlers.jobs["charge"];
+        assert_eq!(10, billing.policy.max_attempts);
+        assert_eq!(Duration::from_millis(250), billing.policy.backoff);
+    }
+
+    #[test]
+    fn jobs_have_one_handler() {
+        let err = Handlers::new([
+            config("a", Some("send-email")),
+            config("b", Some("send-email")),
+        ])
+        .unwrap_err();
+        assert!(err.to_string().contains("only have one handler"));
+    }
+
+    #[test]
+    fn scheduled_invocations_go_to_components_with_triggers() {
+        let handlers = Handlers::new([
+            config("mailer", Some("send-email")),
+            TriggerConfig {
+                max_attempts: Some(1),
+                ..config("reminder-sender", None)
+            },
+        ])
+        .unwrap();
+        let job = |name: &str, component: Option<&str>| Job {
+            id: "1".into(),
+            name: name.into(),
+            component: component.map(Into::into),
+            scheduled_by: None,
+            payload: vec![],
+            attempts: 0,
+            max_attempts: None,
+            backoff_ms: None,
+            run_at_ms: 0,
+            last_error: None,
+            trace_context: Default::default(),
+        };
+
+        let (component, _) = handlers.get(&job("send-email", None)).unwrap();
+        assert_eq!("mailer", component);
+        let (component, policy) = handlers.get(&job("", Some("reminder-sender"))).unwrap();
+        assert_eq!("reminder-sender", component);
+        assert_eq!(1, policy.max_attempts);
+        assert!(handlers.get(&job("", Some("mailer"))).is_some());
+        assert!(handlers.get(&job("", Some("billing"))).is_none());
+        assert!(handlers.get(&job("charge", None)).is_none());
+    }
+}
@@ -197,6 +197,10 @@ impl<F: RuntimeFactors> Subscriber<F> {
         Ok(())
     }
 
+    #[instrument(name = "spin_trigger_redis.execute_wasm", skip_all, err(level = Level::INFO), fields(
+        otel.name = format!("execute_wasm_component {component_id}"),
+        spin.component.id = component_id
+    ))]
     async fn dispatch_handler(&self, msg: &Msg, component_id: &str) -> anyhow::Result<()> {
         spin_telemetry::metrics::monotonic_counter!(
             spin.request_count = 1,
@@ -205,40 +209,48 @@ impl<F: RuntimeFactors> Subscriber<F> {
             component_id = component_id
         );
 
-        let (instance, mut store) = self
-            .trigger_app
-            .prepare(component_id)?
-            .instantiate(())
-            .await?;
-
-        let pre = instance.instance_pre(&store);
-
-        match HandlerType::from_instance_pre(&pre)? {
-            HandlerType::V1(guest_indices) => {
-                let guest = guest_indices.load(&mut store, &instance)?;
-
-                let payload = msg.get_payload_bytes().to_vec();
+        handle_message_payload(
+            &self.trigger_app,
+            component_id,
+            msg.get_payload_bytes().to_vec(),
+        )
+        .await
+    }
+}
 
-                guest
-                    .call_handle_message(&mut store, &payload)
-                    .await?
-                    .context("Redis handler returned an error")
-            }
-            HandlerType::V3(guest_indices) => {
-                let guest = guest_indices.load(&mut store, &instance)?;
-
-                let payload = msg.get_payload_bytes().to_vec();
-                let res = std::pin::pin!(store.as_mut().run_concurrent(async |accessor| {
-                    guest.call_handle_message(accessor, payload).await
-                }))
-                .await;
-
-                res.map_err(|e| anyhow::anyhow!("{e}"))
-                    .context("Redis handler returned an error (run_concurrent)")?
-                    .map_err(|e| anyhow::anyhow!("{e}"))
-                    .context("Redis handler returned an error")?
-                    .context("Redis handler returned an error")
-            }
+/// Invokes the Redis handler of the given component with a message payload,
+/// as if the message had been received on one of its channels.
+pub async fn handle_message_payload<F: RuntimeFactors>(
+    trigger_app: &TriggerApp<RedisTrigger, F>,
+    component_id: &str,
+    payload: Vec<u8>,
+) -> anyhow::Result<()> {
+    let (instance, mut store) = trigger_app.prepare(component_id)?.instantiate(()).await?;
+
+    let pre = instance.instance_pre(&store);
+
+    match HandlerType::from_instance_pre(&pre)? {
+        HandlerType::V1(guest_indices) => {
+            let guest = guest_indices.load(&mut store, &instance)?;
+
+            guest
+                .call_handle_message(&mut store, &payload)
+                .await?
+                .context("Redis handler returned an error")
+        }
+        HandlerType::V3(guest_indices) => {
+            let guest = guest_indices.load(&mut store, &instance)?;
+
+            let res = std::pin::pin!(store.as_mut().run_concurrent(async |accessor| {
+                guest.call_handle_message(accessor, payload).await
+            }))
+            .await;
+
+            res.map_err(|e| anyhow::anyhow!("{e}"))
+                .context("Redis handler returned an error (run_concurrent)")?
+                .map_err(|e| anyhow::anyhow!("{e}"))
+                .context("Redis handler returned an error")?
+                .context("Redis handler returned an error")
         }
     }
 }
@@ -0,0 +1,418 @@
+//! A trigger which runs durable workflows started through
+//! `spin:workflows/client`.
+//!
+//! Each workflow is orchestrated by one component, which exports
+//! `spin:workflows/orchestrator`, and may call the activity components listed
+//! for it, which export `spin:workflows/activity`. Activities need triggers of
+//! their own, without a workflow, so they are loaded:
+//!
+//! ```toml
+//! [[trigger.workflow]]
+//! component = "order-workflow"
+//! workflow = "process-order"
+//! activities = ["charge-card", "ship-order"]
+//!
+//! [[trigger.workflow]]
+//! component = "charge-card"
+//!
+//! [[trigger.workflow]]
+//! component = "ship-order"
+//! ```
+//!
+//! The trigger polls the store for running instances and advances each of
+//! them: it runs the activity, fires the timer or delivers the event the
+//! instance is waiting on, journals the result, then runs the orchestrator
+//! again to replay the journal up to its next wait. Instances of workflows
+//! with no orchestrator in the app are left running.
+
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime},
+};
+
+use anyhow::{Context as _, bail, ensure};
+use clap::Args;
+use serde::Deserialize;
+use spin_common::arg_parser::parse_duration;
+use spin_factor_workflows::{
+    Completed, Status, Waiting, WorkflowInstance, WorkflowStore, WorkflowsFactor,
+    millis_since_epoch,
+};
+use spin_factors::{RuntimeFactors, RuntimeFactorsInstanceState as _};
+use spin_trigger::{App, Trigger, TriggerApp};
+use spin_world::exports::spin::workflows::{activity, orchestrator};
+use tracing::{Level, instrument};
+
+pub struct WorkflowTrigger {
+    poll_interval: Duration,
+}
+
+#[derive(Args)]
+pub struct CliArgs {
+    /// How often to check the workflow store for instances to advance
+    #[clap(
+        long = "workflow-poll-interval",
+        env = "SPIN_WORKFLOW_POLL_INTERVAL",
+        default_value = "1s",
+        value_parser = parse_duration
+    )]
+    pub poll_interval: Duration,
+}
+
+/// Workflow trigger configuration.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TriggerConfig {
+    /// Component ID to invoke
+    component: String,
+    /// Name of the workflow the component orchestrates, if any
+    workflow: Option<String>,
+    /// Component IDs of the activities the orchestrator may call
+    #[serde(default)]
+    activities: Vec<String>,
+}
+
+/// The component orchestrating a workflow, and the activities it may call.
+#[derive(Debug)]
+struct Orchestrator {
+    component: String,
+    activities: HashSet<String>,
+}
+
+impl<F: RuntimeFactors> Trigger<F> for WorkflowTrigger {
+    const TYPE: &'static str = "workflow";
+
+    type CliArgs = CliArgs;
+
+    type InstanceState = ();
+
+    fn new(cli_args: Self::CliArgs, _app: &App) -> anyhow::Result<Self> {
+        ensure!(
+            !cli_args.poll_interval.is_zero(),
+            "--workflow-poll-interval must be greater than zero"
+        );
+        Ok(Self {
+            poll_interval: cli_args.poll_interval,
+        })
+    }
+
+    async fn run(self, trigger_app: TriggerApp<Self, F>) -> anyhow::Result<()> {
+        let store = trigger_app
+            .configured_app()
+            .app_state::<WorkflowsFactor>()
+            .context("WorkflowTrigger depends on WorkflowsFactor")?
+            .store();
+        ensure!(
+            store.is_defined(),
+            "the key-value store {:?} for workflows is not defined",
+            store.label()
+        );
+        let configs = trigger_app
+            .app()
+            .trigger_configs::<TriggerConfig>(<Self as Trigger<F>>::TYPE)?
+            .into_iter()
+            .map(|(_, config)| config);
+        let orchestrators = orchestrators(configs)?;
+
+        println!(
+            "Running workflows from key-value store {:?}:",
+            store.label()
+        );
+        for (workflow, orchestrator) in &orchestrators {
+            println!("\t{workflow}: {}", orchestrator.component);
+        }
+
+        let worker = Arc::new(Worker {
+            trigger_app,
+            store,
+            orchestrators,
+            running: Default::default(),
+        });
+        let mut interval = tokio::time::interval(self.poll_interval);
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        loop {
+            interval.tick().await;
+            if let Err(err) = worker.clone().poll().await {
+                tracing::error!("Failed to poll workflow store: {err:#}");
+            }
+        }
+    }
+}
+
+/// Maps workflow names to their orchestrators.
+fn orchestrators(
+    configs: impl IntoIterator<Item = TriggerConfig>,
+) -> anyhow::Result<HashMap<String, Orchestrator>> {
+    let mut orchestrators = HashMap::<String, Orchestrator>::new();
+    let mut components = HashSet::new();
+    for config in configs {
+        components.insert(config.component.clone());
+        let Some(workflow) = config.workflow else {
+            continue;
+        };
+        if let Some(existing) = orchestrators.get(&workflow) {
+            bail!(
+                "workflow {workflow:?} is orchestrated by both {:?} and {:?}; a workflow may only have one orchestrator",
+                existing.component,
+                config.component
+            );
+        }
+        let orchestrator = Orchestrator {
+            component: config.component,
+            activities: config.activities.into_iter().collect(),
+        };
+        orchestrators.insert(workflow, orchestrator);
+    }
+    for (workflow, orchestrator) in &orchestrators {
+        for activity in &orchestrator.activities {
+            ensure!(
+                components.contains(activity),
+                "activity {activity:?} of workflow {workflow:?} has no workflow trigger"
+            );
+        }
+    }
+    Ok(orchestrators)
+}
+
+struct Worker<F: RuntimeFactors> {
+    trigger_app: TriggerApp<WorkflowTrigger, F>,
+    store: Arc<WorkflowStore>,
+    orchestrators: HashMap<String, Orchestrator>,
+    /// The IDs of the instances being advanced, so they are not advanced
+    /// again at the same time.
+    running: Mutex<HashSet<String>>,
+}
+
+impl<F: RuntimeFactors> Worker<F> {
+    /// Starts advancing the running instances.
+    async fn poll(self: Arc<Self>) -> anyhow::Result<()> {
+        for instance in self.store.running().await? {
+            if !self.orchestrators.contains_key(&instance.workflow) {
+                continue;
+            }
+            if !self.running.lock().unwrap().insert(instance.id.clone()) {
+                continue;
+            }
+            let worker = self.clone();
+            tokio::spawn(async move {
+                let id = instance.id.clone();
+                if let Err(err) = worker.advance(instance).await {
+                    tracing::error!("Failed to advance workflow instance {id}: {err:#}");
+                }
+                worker.running.lock().unwrap().remove(&id);
+            });
+        }
+        Ok(())
+    }
+
+    /// Completes the operations an instance is waiting on and replays its
+    /// orchestrator, until it waits on an operation which cannot complete yet
+    /// or finishes.
+    async fn advance(&self, mut instance: WorkflowInstance) -> anyhow::Result<()> {
+        let orchestrator = self
+            .orchestrators
+            .get(&instance.workflow)
+            .context("workflow has no orchestrator")?;
+        loop {
+            let mut delivered = None;
+            match instance.waiting.take() {
+                None => {}
+                Some(Waiting::Activity { activity, input }) => {
+                    let result = if orchestrator.activities.contains(&activity) {
+                        // Only the activity's own result is journaled. If it
+                        // couldn't be run, the instance is left waiting on
+                        // it, so it is run again on the next poll.
+                        self.run_activity(&activity, &input, &instance.trace_context)
+                            .await
+                            .with_context(|| format!("failed to run activity {activity:?}"))?
+                    } else {
+                        Err(format!("workflow may not call activity {activity:?}"))
+                    };
+                    instance
+                        .history
+                        .push(Completed::Activity { activity, result });
+                }
+                Some(Waiting::Timer {
+                    duration_ms,
+                    fire_at_ms,
+                }) => {
+                    if millis_since_epoch(SystemTime::now()) < fire_at_ms {
+                        instance.waiting = Some(Waiting::Timer {
+                            duration_ms,
+                            fire_at_ms,
+                        });
+                        return Ok(());
+                    }
+                    instance.history.push(Completed::Timer { duration_ms });
+                }
+                Some(Waiting::Event { event }) => {
+                    let Some(raised) = self.store.next_event(&instance.id, &event).await? else {
+                        instance.waiting = Some(Waiting::Event { event });
+                        return Ok(());
+                    };
+                    let payload = raised.payload.clone();
+                    instance.history.push(Completed::Event { event, payload });
+                    delivered = Some(raised);
+                }
+            }
+            // Journal the result before the orchestrator acts on it.
+            self.store.save(&instance).await?;
+            if let Some(raised) = delivered {
+                self.store.remove_event(&raised).await?;
+            }
+
+            self.replay(&orchestrator.component, &mut instance).await;
+            self.store.save(&instance).await?;
+            match &instance.status {
+                Status::Running => {}
+                Status::Completed { .. } => {
+                    tracing::info!("Workflow instance {} completed", instance.id);
+                    return self.store.remove_events(&instance.id).await;
+                }
+                Status::Failed { error } => {
+                    tracing::warn!("Workflow instance {} failed: {error}", instance.id);
+                    return self.store.remove_events(&instance.id).await;
+                }
+            }
+        }
+    }
+
+    /// Runs an instance's orchestrator, recording the operation it stopped
+    /// to wait on or how it finished.
+    async fn replay(&self, component_id: &str, instance: &mut WorkflowInstance) {
+        match self.run_orchestrator(component_id, instance).await {
+            Ok((_, Some(waiting))) => instance.waiting = Some(waiting),
+            Ok((Ok(Ok(output)), None)) => instance.status = Status::Completed { output },
+            Ok((Ok(Err(error)), None)) => instance.status = Status::Failed { error },
+            Ok((Err(err), None)) | Err(err) => {
+                instance.status = Status::Failed {
+                    error: format!("{err:#}"),
+                }
+            }
+        }
+    }
+
+    /// Runs an orchestrator, returning its result and the operation it
+    /// stopped to wait on, if it did.
+    #[instrument(name = "spin_trigger_workflow.execute_wasm", skip_all, err(level = Level::INFO), fields(
+        otel.name = format!("execute_wasm_component {component_id}"),
+        spin.component.id = component_id,
+        spin.workflow.name = %instance.workflow,
+        spin.workflow.instance = %instance.id,
+    ))]
+    async fn run_orchestrator(
+        &self,
+        component_id: &str,
+        instance: &WorkflowInstance,
+    ) -> anyhow::Result<(anyhow::Result<Result<Vec<u8>, String>>, Option<Waiting>)> {
+        spin_telemetry::extract_message_trace_context(&instance.trace_context);
+        spin_telemetry::metrics::monotonic_counter!(
+            spin.request_count = 1,
+            trigger_type = "workflow",
+            app_id = self.trigger_app.app().id(),
+            component_id = component_id
+        );
+
+        let mut builder = self.trigger_app.prepare(component_id)?;
+        builder
+            .factor_builder::<WorkflowsFactor>()
+            .context("WorkflowTrigger depends on WorkflowsFactor")?
+            .orchestrate(instance);
+        let (wasm_instance, mut store) = builder.instantiate(()).await?;
+        let pre = wasm_instance.instance_pre(&store);
+        let guest = orchestrator::GuestIndices::new(&pre)
+            .context("component does not export spin:workflows/orchestrator")?
+            .load(&mut store, &wasm_instance)?;
+
+        let result = guest.call_run(&mut store, &instance.input).await;
+        let waiting = store
+            .data_mut()
+            .factors_instance_state_mut()
+            .get::<WorkflowsFactor>()
+            .and_then(|state| state.replay())
+            .and_then(|replay| replay.waiting())
+            .cloned();
+        Ok((result, waiting))
+    }
+
+    #[instrument(name = "spin_trigger_workflow.execute_wasm", skip_all, err(level = Level::INFO), fields(
+        otel.name = format!("execute_wasm_component {component_id}"),
+        spin.component.id = component_id,
+    ))]
+    async fn run_activity(
+        &self,
+        component_id: &str,
+        input: &[u8],
+        trace_context: &HashMap<String, String>,
+    ) -> anyhow::Result<Result<Vec<u8>, String>> {
+        spin_telemetry::extract_message_trace_context(trace_context);
+        spin_telemetry::metrics::monotonic_counter!(
+            spin.request_count = 1,
+            trigger_type = "workflow",
+            app_id = self.trigger_app.app().id(),
+            component_id = component_id
+        );
+
+        let (instance, mut store) = self
+            .trigger_app
+            .prepare(component_id)?
+            .instantiate(())
+            .await?;
+        let pre = instance.instance_pre(&store);
+        let guest = activity::GuestIndices::new(&pre)
+            .context("component does not export spin:workflows/activity")?
+            .load(&mut store, &instance)?;
+        guest.call_run(&mut store, input).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(component: &str, workflow: Option<&str>) -> TriggerConfig {
+        TriggerConfig {
+            component: component.into(),
+            workflow: workflow.map(Into::into),
+            activities: vec![],
+        }
+    }
+
+    #[test]
+    fn workflows_have_one_orchestrator() {
+        let orchestrators = orchestrators([
+            TriggerConfig {
+                activities: vec!["charge-card".into()],
+                ..config("orders", Some("process-order"))
+            },
+            config("refunds", Some("refund-order")),
+            config("charge-card", None),
+        ])
+        .unwrap();
+        assert_eq!(2, orchestrators.len());
+        assert_eq!("orders", orchestrators["process-order"].component);
+        assert!(
+            orchestrators["process-order"]
+                .activities
+                .contains("charge-card")
+        );
+
+        let err = orchestrators([
+            config("a", Some("process-order")),
+            config("b", Some("process-order")),
+        ])
+        .unwrap_err();
+        assert!(err.to_string().contains("only have one orchestrator"));
+    }
+
+    #[test]
+    fn activities_need_triggers() {
+        let err = orchestrators([TriggerConfig {
+            activities: vec!["charge-card".into()],
+            ..config("orders", Some("process-order"))
+        }])
+        .unwrap_err();
+        assert!(err.to_string().contains("has no workflow trigger"));
+    }
+}
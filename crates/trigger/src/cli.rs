@@ -1,12 +1,22 @@
+mod admin;
+mod component_signatures;
+mod guest_profiler;
 mod initial_kv_setter;
+mod kv_resp;
 mod launch_metadata;
+mod log_rotation;
+mod max_execution_time;
 mod max_instance_memory;
+mod sandbox;
+mod seed;
 mod sqlite_statements;
+mod startup;
 mod stdio;
 mod summary;
 mod variable;
 
-use std::path::PathBuf;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
 use std::{future::Future, sync::Arc};
 
 use anyhow::{Context, Result};
@@ -14,24 +24,39 @@ use anyhow::{Context, Result};
 use clap::ValueEnum;
 use clap::{Args, CommandFactory, Parser};
 use spin_app::App;
+use spin_common::arg_parser::parse_duration;
 use spin_common::sloth;
 use spin_common::ui::quoted_path;
 use spin_common::url::parse_file_url;
 use spin_factors::RuntimeFactors;
 use spin_factors_executor::{ComponentLoader, FactorsExecutor};
-
-use crate::{Trigger, TriggerApp, loader::ComponentLoader as ComponentLoaderImpl};
+use tracing::Instrument as _;
+
+use crate::{
+    Trigger, TriggerApp,
+    loader::ComponentLoader as ComponentLoaderImpl,
+    shutdown::{self, ShutdownConfig},
+};
+pub use admin::{AdminListen, AdminServer, RegistryCache, SwapComponent};
+pub use component_signatures::{ComponentSignatureHook, SignaturesRequired};
+pub use guest_profiler::GuestProfilerHook;
 pub use initial_kv_setter::InitialKvSetterHook;
+pub use kv_resp::{KeyValueRespServer, parse_resp_listen};
 pub use launch_metadata::LaunchMetadata;
-pub use max_instance_memory::MaxInstanceMemoryHook;
+pub use log_rotation::LogRotationConfig;
+pub use max_execution_time::MaxExecutionTimeHook;
+pub use max_instance_memory::{MaxAppMemoryHook, MaxInstanceMemoryHook};
+pub use seed::{SeedConfig, SeedHook, SqliteSeed};
 pub use sqlite_statements::SqlStatementExecutorHook;
-use stdio::FollowComponents;
+pub use startup::StartupTimings;
 pub use stdio::StdioLoggingExecutorHooks;
+pub use stdio::{FollowComponents, STDERR_LOG_FILE_SUFFIX, STDOUT_LOG_FILE_SUFFIX};
 pub use summary::{KeyValueDefaultStoreSummaryHook, SqliteDefaultStoreSummaryHook};
 pub use variable::VariablesValidatorHook;
 
 pub const APP_LOG_DIR: &str = "APP_LOG_DIR";
 pub const SPIN_TRUNCATE_LOGS: &str = "SPIN_TRUNCATE_LOGS";
+pub const DISABLE_LOG_FILES: &str = "DISABLE_LOG_FILES";
 pub const DISABLE_WASMTIME_CACHE: &str = "DISABLE_WASMTIME_CACHE";
 pub const FOLLOW_LOG_OPT: &str = "FOLLOW_ID";
 pub const WASMTIME_CACHE_FILE: &str = "WASMTIME_CACHE_FILE";
@@ -41,6 +66,7 @@ pub const RUNTIME_CONFIG_FILE: &str = "RUNTIME_CONFIG_FILE";
 pub const SPIN_LOCKED_URL: &str = "SPIN_LOCKED_URL";
 pub const SPIN_LOCAL_APP_DIR: &str = "SPIN_LOCAL_APP_DIR";
 pub const SPIN_WORKING_DIR: &str = "SPIN_WORKING_DIR";
+pub const SPIN_SANDBOX_HOST: &str = "SPIN_SANDBOX_HOST";
 
 /// A command that runs a TriggerExecutor.
 #[derive(Parser, Debug)]
@@ -67,6 +93,35 @@ pub struct FactorsTriggerCommand<T: Trigger<B::Factors>, B: RuntimeFactorsBuilde
     )]
     pub truncate_logs: bool,
 
+    /// If set, component stdout and stderr are not logged to disk.
+    #[clap(
+        name = DISABLE_LOG_FILES,
+        long = "disable-log-files",
+        env = "SPIN_DISABLE_LOG_FILES",
+        conflicts_with = APP_LOG_DIR,
+    )]
+    pub disable_log_files: bool,
+
+    /// Rotate a component log file once it reaches this size in bytes.
+    #[clap(long = "log-max-size", env = "SPIN_LOG_MAX_SIZE")]
+    pub log_max_size: Option<u64>,
+
+    /// Rotate a component log file once it reaches this age, e.g. `12h` or `7d`.
+    #[clap(
+        long = "log-max-age",
+        env = "SPIN_LOG_MAX_AGE",
+        value_parser = log_rotation::parse_age,
+    )]
+    pub log_max_age: Option<std::time::Duration>,
+
+    /// The number of rotated files to keep for each component log file.
+    #[clap(
+        long = "log-retention",
+        env = "SPIN_LOG_RETENTION",
+        default_value_t = 5
+    )]
+    pub log_retention: usize,
+
     /// Disable Wasmtime cache.
     #[clap(
         name = DISABLE_WASMTIME_CACHE,
@@ -85,6 +140,12 @@ pub struct FactorsTriggerCommand<T: Trigger<B::Factors>, B: RuntimeFactorsBuilde
     )]
     pub cache: Option<PathBuf>,
 
+    /// Compile all components into the Wasmtime compilation cache, then exit
+    /// without serving the app. Later runs with the same cache configuration
+    /// load the compiled components from the cache rather than compiling them.
+    #[clap(long = "precompile", conflicts_with = DISABLE_WASMTIME_CACHE)]
+    pub precompile: bool,
+
     /// Disable Wasmtime's pooling instance allocator.
     #[clap(long = "disable-pooling")]
     pub disable_pooling: bool,
@@ -126,6 +187,8 @@ pub struct FactorsTriggerCommand<T: Trigger<B::Factors>, B: RuntimeFactorsBuilde
     /// locations for logs, key value stores, etc.
     ///
     /// For local apps, this defaults to `.spin/` relative to the `spin.toml` file.
+    /// If that directory already belongs to a different app in the same directory,
+    /// it defaults to `.spin/apps/<app-id>/` instead.
     /// For remote apps, this has no default (unset).
     /// Passing an empty value forces the value to be unset.
     #[clap(long)]
@@ -137,6 +200,79 @@ pub struct FactorsTriggerCommand<T: Trigger<B::Factors>, B: RuntimeFactorsBuilde
     #[clap(flatten)]
     pub builder_args: B::CliArgs,
 
+    /// Print a summary of how long each phase of startup took before serving.
+    #[clap(long = "startup-summary")]
+    pub startup_summary: bool,
+
+    /// Serve an API for tooling to inspect and control the running app, on a
+    /// loopback address such as `127.0.0.1:3999` or on a Unix socket such as
+    /// `unix:/run/spin/admin.sock`. Requests must carry the `--admin-token`.
+    #[clap(
+        long = "admin-listen",
+        env = "SPIN_ADMIN_LISTEN",
+        requires = "admin_token"
+    )]
+    pub admin_listen: Option<AdminListen>,
+
+    /// The bearer token required by the admin API.
+    #[clap(long = "admin-token", env = "SPIN_ADMIN_TOKEN", hide_env_values = true)]
+    pub admin_token: Option<String>,
+
+    /// Serve the app's key-value stores over the Redis protocol on a loopback
+    /// address such as `127.0.0.1:6380`, so that tools such as `redis-cli`
+    /// can inspect them. Each store is a database, chosen with `SELECT`; `INFO`
+    /// lists which is which. Read-only unless `--key-value-resp-writable` is
+    /// passed. If `--key-value-resp-token` is set, clients must send it with
+    /// `AUTH`.
+    #[clap(
+        long = "key-value-resp-listen",
+        env = "SPIN_KEY_VALUE_RESP_LISTEN",
+        value_parser = parse_resp_listen
+    )]
+    pub key_value_resp_listen: Option<SocketAddr>,
+
+    /// The password clients of `--key-value-resp-listen` must send with
+    /// `AUTH`.
+    #[clap(
+        long = "key-value-resp-token",
+        env = "SPIN_KEY_VALUE_RESP_TOKEN",
+        hide_env_values = true,
+        requires = "key_value_resp_listen"
+    )]
+    pub key_value_resp_token: Option<String>,
+
+    /// Allow clients of `--key-value-resp-listen` to set and delete keys.
+    /// Requires `--key-value-resp-token`.
+    #[clap(long = "key-value-resp-writable", requires = "key_value_resp_token")]
+    pub key_value_resp_writable: bool,
+
+    /// After being asked to shut down, keep serving for this long before
+    /// draining, e.g. `5s`. Readiness (`/.well-known/spin/ready` for HTTP
+    /// apps) fails for the whole of shutdown, so load balancers have time to
+    /// stop sending requests.
+    #[clap(
+        long = "shutdown-delay",
+        env = "SPIN_SHUTDOWN_DELAY",
+        value_parser = parse_duration,
+        default_value = "0s"
+    )]
+    pub shutdown_delay: std::time::Duration,
+
+    /// How long to wait for open connections to finish their requests before
+    /// exiting, e.g. `30s`.
+    #[clap(
+        long = "shutdown-drain-timeout",
+        env = "SPIN_SHUTDOWN_DRAIN_TIMEOUT",
+        value_parser = parse_duration,
+        default_value = "0s"
+    )]
+    pub shutdown_drain_timeout: std::time::Duration,
+
+    /// Once initialized, block system calls the trigger never needs to make,
+    /// such as running other programs. Set by `spin up --sandbox-host`.
+    #[clap(long = "sandbox-host", env = SPIN_SANDBOX_HOST, hide = true)]
+    pub sandbox_host: bool,
+
     #[clap(long = "help-args-only", hide = true)]
     pub help_args_only: bool,
 
@@ -164,12 +300,16 @@ pub struct FactorsConfig {
     pub state_dir: UserProvidedPath,
     /// Path to the local app directory.
     pub local_app_dir: Option<String>,
+    /// The identity of the app, used to namespace its default state directory.
+    pub app_id: Option<String>,
     /// Which components should have their logs followed.
     pub follow_components: FollowComponents,
     /// Log directory for component stdout/stderr.
     pub log_dir: UserProvidedPath,
     /// If set, Spin truncates the log files before starting the application.
     pub truncate_logs: bool,
+    /// When to rotate log files.
+    pub log_rotation: LogRotationConfig,
 }
 
 /// An empty implementation of clap::Args to be used as TriggerExecutor::RunConfig
@@ -179,7 +319,10 @@ pub struct NoCliArgs;
 
 impl<T: Trigger<B::Factors>, B: RuntimeFactorsBuilder> FactorsTriggerCommand<T, B> {
     /// Create a new TriggerExecutorBuilder from this TriggerExecutorCommand.
-    pub async fn run(self) -> Result<()> {
+    pub async fn run(self) -> Result<()>
+    where
+        T::InstanceState: Default,
+    {
         // Handle --help-args-only
         if self.help_args_only {
             Self::command()
@@ -204,15 +347,20 @@ impl<T: Trigger<B::Factors>, B: RuntimeFactorsBuilder> FactorsTriggerCommand<T,
 
         let follow_components = self.follow_components();
 
+        let mut startup_timings = StartupTimings::default();
+
         // Load App
-        let app = {
-            let path = parse_file_url(&locked_url)?;
-            let contents = std::fs::read(&path)
-                .with_context(|| format!("failed to read manifest at {}", quoted_path(&path)))?;
-            let locked =
-                serde_json::from_slice(&contents).context("failed to parse app lock file JSON")?;
-            App::new(locked_url, locked)
-        };
+        let app = startup_timings.time("Load app", || {
+            tracing::info_span!("spin_trigger.load_app").in_scope(|| {
+                let path = parse_file_url(&locked_url)?;
+                let contents = std::fs::read(&path).with_context(|| {
+                    format!("failed to read manifest at {}", quoted_path(&path))
+                })?;
+                let locked = serde_json::from_slice(&contents)
+                    .context("failed to parse app lock file JSON")?;
+                anyhow::Ok(App::new(locked_url, locked))
+            })
+        })?;
 
         // Validate required host features
         if let Err(unmet) = app.ensure_needs_only(T::TYPE, &T::supported_host_requirements()) {
@@ -222,8 +370,19 @@ impl<T: Trigger<B::Factors>, B: RuntimeFactorsBuilder> FactorsTriggerCommand<T,
             );
         }
 
+        let (abort_handle, abort_registration) = futures::future::AbortHandle::new_pair();
+        // Interrupts and the admin API both request shutdown on this channel.
+        let (signal_tx, mut signal_rx) = tokio::sync::mpsc::unbounded_channel();
+        let admin_server = match (self.admin_listen, self.admin_token) {
+            (Some(listen), Some(token)) => {
+                Some((listen, AdminServer::new(token, &app, signal_tx.clone())?))
+            }
+            _ => None,
+        };
+
         let trigger = T::new(self.trigger_args, &app)?;
         let mut builder: TriggerAppBuilder<T, B> = TriggerAppBuilder::new(trigger);
+        builder.startup_timings = startup_timings;
         let config = builder.engine_config();
 
         // Apply --cache / --disable-cache
@@ -263,28 +422,113 @@ impl<T: Trigger<B::Factors>, B: RuntimeFactorsBuilder> FactorsTriggerCommand<T,
             None => UserProvidedPath::Default,
         };
         let log_dir = match &self.log {
+            _ if self.disable_log_files => UserProvidedPath::Unset,
             // Make sure `--log-dir=""` unsets the log dir
             Some(p) if p.as_os_str().is_empty() => UserProvidedPath::Unset,
             Some(p) => UserProvidedPath::Provided(p.clone()),
             None => UserProvidedPath::Default,
         };
+        let app_id = app
+            .get_metadata(spin_app::APP_ORIGIN_KEY)?
+            .and_then(|origin: String| spin_common::app_state::app_id_from_origin(&origin));
+        // Claim the app's default state directory, so that other apps in the
+        // same directory get their own.
+        if let (UserProvidedPath::Default, Some(local_app_dir), Some(app_id)) =
+            (&state_dir, &local_app_dir, &app_id)
+        {
+            let app_name: Option<String> = app.get_metadata(spin_app::APP_NAME_KEY)?;
+            let default_state_dir =
+                spin_common::app_state::default_state_dir(Path::new(local_app_dir), Some(app_id))?;
+            spin_common::app_state::claim_state_dir(
+                &default_state_dir,
+                app_id,
+                app_name.as_deref(),
+            )
+            .with_context(|| {
+                format!(
+                    "failed to claim state directory {}",
+                    quoted_path(&default_state_dir)
+                )
+            })?;
+        }
         let common_options = FactorsConfig {
             working_dir: PathBuf::from(working_dir),
             runtime_config_file: self.runtime_config_file.clone(),
             state_dir,
             local_app_dir: local_app_dir.clone(),
+            app_id,
             follow_components,
             log_dir,
             truncate_logs: self.truncate_logs,
+            log_rotation: LogRotationConfig {
+                max_size: self.log_max_size,
+                max_age: self.log_max_age,
+                retention: self.log_retention,
+            },
         };
 
         let loader = ComponentLoaderImpl::new();
-        let run_fut = builder
-            .run(app, common_options, self.builder_args, &loader)
+        let configured_app = builder
+            .build(app, common_options, self.builder_args, &loader)
             .await?;
+        if self.startup_summary {
+            builder.startup_timings().print_summary();
+        }
+        if self.precompile {
+            // Building the app compiled every component, through the cache.
+            eprintln!("Compiled all components into the Wasmtime cache");
+            return Ok(());
+        }
+        spin_telemetry::metrics::serve_prometheus_endpoint().await?;
+        if let Some((listen, mut admin_server)) = admin_server {
+            if let Some(clock) = configured_app
+                .configured_app()
+                .app_state::<spin_factor_wasi::WasiFactor>()
+                .ok()
+                .and_then(|state| state.virtual_clock())
+            {
+                admin_server.set_virtual_clock(clock.clone());
+            }
+            admin_server.set_component_swapper(configured_app.component_swapper());
+            if let Some(registry_cache) = B::registry_cache() {
+                admin_server.set_registry_cache(registry_cache);
+            }
+            Arc::new(admin_server).serve(&listen).await?;
+        }
+        if let Some(addr) = self.key_value_resp_listen {
+            let key_value = configured_app
+                .configured_app()
+                .app_state::<spin_factor_key_value::KeyValueFactor>()
+                .context("the app's key-value stores are not available to serve")?;
+            let server = KeyValueRespServer::new(
+                key_value,
+                self.key_value_resp_writable,
+                self.key_value_resp_token,
+            )
+            .await?;
+            Arc::new(server).serve(addr).await?;
+        }
+        if self.sandbox_host {
+            sandbox::restrict_syscalls()?;
+        }
+        let run_fut = builder.trigger.run(configured_app);
 
-        let (abortable, abort_handle) = futures::future::abortable(run_fut);
-        ctrlc::set_handler(move || abort_handle.abort())?;
+        let abortable = futures::future::Abortable::new(run_fut, abort_registration);
+        let shutdown_config = ShutdownConfig {
+            pre_stop_delay: self.shutdown_delay,
+            drain_timeout: self.shutdown_drain_timeout,
+        };
+        ctrlc::set_handler(move || _ = signal_tx.send(()))?;
+        tokio::spawn(async move {
+            if signal_rx.recv().await.is_some() {
+                // A second signal skips the rest of the shutdown sequence.
+                tokio::select! {
+                    () = shutdown_config.run(shutdown::state()) => {}
+                    _ = signal_rx.recv() => {}
+                }
+            }
+            abort_handle.abort();
+        });
         match abortable.await {
             Ok(Ok(())) => {
                 tracing::info!("Trigger executor shut down: exiting");
@@ -341,6 +585,7 @@ fn help_heading<T: Trigger<F>, F: RuntimeFactors>() -> Option<&'static str> {
 pub struct TriggerAppBuilder<T, B> {
     engine_config: spin_core::Config,
     pub trigger: T,
+    startup_timings: StartupTimings,
     _factors_builder: std::marker::PhantomData<B>,
 }
 
@@ -349,6 +594,7 @@ impl<T: Trigger<B::Factors>, B: RuntimeFactorsBuilder> TriggerAppBuilder<T, B> {
         Self {
             engine_config: spin_core::Config::default(),
             trigger,
+            startup_timings: Default::default(),
             _factors_builder: Default::default(),
         }
     }
@@ -357,6 +603,11 @@ impl<T: Trigger<B::Factors>, B: RuntimeFactorsBuilder> TriggerAppBuilder<T, B> {
         &mut self.engine_config
     }
 
+    /// Returns the durations of the startup phases run so far.
+    pub fn startup_timings(&self) -> &StartupTimings {
+        &self.startup_timings
+    }
+
     /// Build a [`TriggerApp`] from the given [`App`] and options.
     pub async fn build(
         &mut self,
@@ -365,25 +616,33 @@ impl<T: Trigger<B::Factors>, B: RuntimeFactorsBuilder> TriggerAppBuilder<T, B> {
         options: B::CliArgs,
         loader: &impl ComponentLoader<B::Factors, T::InstanceState>,
     ) -> anyhow::Result<TriggerApp<T, B::Factors>> {
-        let mut core_engine_builder = {
+        let mut core_engine_builder = self.startup_timings.time("Create engine", || {
             self.trigger.update_core_config(&mut self.engine_config)?;
+            B::configure_engine(&mut self.engine_config, &common_options, &options)?;
 
-            spin_core::Engine::builder(&self.engine_config)?
-        };
+            spin_core::Engine::builder(&self.engine_config)
+        })?;
         self.trigger.add_to_linker(core_engine_builder.linker())?;
 
-        let (factors, runtime_config) = B::build(&common_options, &options)?;
+        let (executor, runtime_config) = self.startup_timings.time("Initialize factors", || {
+            tracing::info_span!("spin_trigger.init_factors").in_scope(|| {
+                let (factors, runtime_config) = B::build(&common_options, &options)?;
 
-        let mut executor = FactorsExecutor::new(core_engine_builder, factors)?;
-        B::configure_app(&mut executor, &runtime_config, &common_options, &options)?;
-        let executor = Arc::new(executor);
+                let mut executor = FactorsExecutor::new(core_engine_builder, factors)?;
+                B::configure_app(&mut executor, &runtime_config, &common_options, &options)?;
+                anyhow::Ok((Arc::new(executor), runtime_config))
+            })
+        })?;
 
         let configured_app = {
             let _sloth_guard = warn_if_wasm_build_slothful();
             executor
                 .load_app(app, runtime_config.into(), loader, Some(T::TYPE))
+                .instrument(tracing::info_span!("spin_trigger.load_components"))
                 .await?
         };
+        self.startup_timings
+            .record_load_timings(configured_app.load_timings());
 
         Ok(configured_app)
     }
@@ -416,6 +675,22 @@ pub trait RuntimeFactorsBuilder {
         args: &Self::CliArgs,
     ) -> anyhow::Result<(Self::Factors, Self::RuntimeConfig)>;
 
+    /// Update the engine config before the engine is built.
+    fn configure_engine(
+        engine_config: &mut spin_core::Config,
+        config: &FactorsConfig,
+        args: &Self::CliArgs,
+    ) -> anyhow::Result<()> {
+        let _ = (engine_config, config, args);
+        Ok(())
+    }
+
+    /// The registry cache from which the admin API reads new versions of
+    /// components, if the host has one.
+    fn registry_cache() -> Option<Box<dyn RegistryCache>> {
+        None
+    }
+
     /// Configure the factors in the executor.
     fn configure_app<U: Send + 'static>(
         executor: &mut FactorsExecutor<Self::Factors, U>,
@@ -0,0 +1,555 @@
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{Context as _, bail, ensure};
+use futures::future::BoxFuture;
+use http_body_util::{BodyExt as _, Full};
+use hyper::{
+    Method, Request, Response, StatusCode, body::Bytes, body::Incoming, header, service::service_fn,
+};
+use hyper_util::rt::TokioIo;
+use serde::{Deserialize, Serialize};
+use spin_app::App;
+use spin_common::ui::quoted_path;
+use spin_factor_wasi::VirtualClock;
+use spin_factors::RuntimeFactors;
+use spin_factors_executor::ComponentSwapper;
+use subtle::ConstantTimeEq as _;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::sync::mpsc::UnboundedSender;
+
+/// The largest request body the admin API accepts.
+const MAX_BODY_SIZE: usize = 64 * 1024;
+/// The path prefix of the component routes, which is followed by a component ID.
+const COMPONENTS_PREFIX: &str = "/components/";
+
+/// Where the admin API listens.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum AdminListen {
+    /// A loopback TCP address.
+    Tcp(SocketAddr),
+    /// A Unix domain socket.
+    Unix(PathBuf),
+}
+
+impl std::str::FromStr for AdminListen {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some(path) = s.strip_prefix("unix:") {
+            return Ok(Self::Unix(path.into()));
+        }
+        let addr: SocketAddr = s
+            .parse()
+            .with_context(|| format!("expected `<ip>:<port>` or `unix:<path>`; got {s:?}"))?;
+        if !addr.ip().is_loopback() {
+            bail!("the admin API may only listen on a loopback address; got {addr}");
+        }
+        Ok(Self::Tcp(addr))
+    }
+}
+
+/// An API for tooling to inspect and control a running app.
+///
+/// Every request must carry `Authorization: Bearer <token>`. The API offers:
+///
+/// - `GET /health`: whether the app is running
+/// - `GET /config`: the app's components and triggers
+/// - `GET /log-level`, `PUT /log-level`: the host log filter, in `RUST_LOG` syntax
+/// - `POST /shutdown`: stops the app, as if interrupted
+/// - `GET /traces?after=<seq>`: the recently completed traces kept in memory
+///   (see `SPIN_OTEL_RECENT_TRACES`), optionally only those completed after
+///   the trace with the given sequence number
+/// - `PUT /components/<id>`: swaps a component for a new version, read from
+///   a file (`{"path": "..."}`) or, if the host provides a [`RegistryCache`],
+///   from the registry cache by layer digest (`{"digest": "sha256:..."}`);
+///   see [`ComponentSwapper::swap_component`] for how the new version is
+///   validated
+///
+/// If the app runs against a virtual clock, it also offers:
+///
+/// - `GET /clock`: the time components see, and whether it is frozen
+/// - `POST /clock/advance`: moves the clock forward by the duration in the body, e.g. `90s`
+/// - `POST /clock/freeze`, `POST /clock/resume`: stops and restarts the clock keeping real time
+pub struct AdminServer {
+    token: String,
+    config: AppConfig,
+    shutdown: UnboundedSender<()>,
+    virtual_clock: Option<VirtualClock>,
+    component_swapper: Option<Box<dyn SwapComponent>>,
+    registry_cache: Option<Box<dyn RegistryCache>>,
+}
+
+/// Finds Wasm layers in the registry cache, as left by `spin registry pull`.
+pub trait RegistryCache: Send + Sync {
+    /// Returns the path of the cached Wasm layer with the given digest.
+    fn wasm_file<'a>(&'a self, digest: &'a str) -> BoxFuture<'a, anyhow::Result<PathBuf>>;
+}
+
+/// Swaps the components of a running app for new versions.
+pub trait SwapComponent: Send + Sync {
+    /// Replaces the given component with one compiled from the given Wasm.
+    fn swap<'a>(
+        &'a self,
+        component_id: &'a str,
+        wasm: Vec<u8>,
+    ) -> BoxFuture<'a, anyhow::Result<()>>;
+}
+
+impl<T: RuntimeFactors, U: Default + Send + 'static> SwapComponent for ComponentSwapper<T, U> {
+    fn swap<'a>(
+        &'a self,
+        component_id: &'a str,
+        wasm: Vec<u8>,
+    ) -> BoxFuture<'a, anyhow::Result<()>> {
+        Box::pin(async move { self.swap_component(component_id, &wasm, U::default()).await })
+    }
+}
+
+/// Where the admin API reads the new version of a component from.
+#[derive(Debug, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+enum NewComponentSource {
+    /// A Wasm file.
+    Path(PathBuf),
+    /// A Wasm layer in the registry cache, as left by `spin registry pull`.
+    Digest(String),
+}
+
+impl NewComponentSource {
+    async fn read(&self, registry_cache: Option<&dyn RegistryCache>) -> anyhow::Result<Vec<u8>> {
+        match self {
+            Self::Path(path) => tokio::fs::read(path)
+                .await
+                .with_context(|| format!("failed to read {}", quoted_path(path))),
+            Self::Digest(digest) => {
+                ensure!(
+                    digest.starts_with("sha256:"),
+                    "expected a digest of the form `sha256:<hex>`; got {digest:?}"
+                );
+                let Some(registry_cache) = registry_cache else {
+                    bail!("this host cannot read components from the registry cache");
+                };
+                let path = registry_cache.wasm_file(digest).await?;
+                let wasm = tokio::fs::read(&path)
+                    .await
+                    .with_context(|| format!("failed to read {}", quoted_path(&path)))?;
+                verify_digest(&wasm, digest)?;
+                Ok(wasm)
+            }
+        }
+    }
+}
+
+/// Checks that Wasm read from the registry cache has the digest it is cached
+/// under.
+fn verify_digest(wasm: &[u8], digest: &str) -> anyhow::Result<()> {
+    let hash = ring::digest::digest(&ring::digest::SHA256, wasm);
+    let actual = hash
+        .as_ref()
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect::<String>();
+    ensure!(
+        digest.strip_prefix("sha256:") == Some(actual.as_str()),
+        "the cached Wasm for {digest} does not match its digest"
+    );
+    Ok(())
+}
+
+#[derive(Serialize)]
+struct AppConfig {
+    name: Option<String>,
+    components: Vec<ComponentConfig>,
+    triggers: Vec<TriggerConfig>,
+}
+
+#[derive(Serialize)]
+struct ComponentConfig {
+    id: String,
+    source: Option<String>,
+}
+
+#[derive(Serialize)]
+struct TriggerConfig {
+    id: String,
+    trigger_type: String,
+    component: Option<String>,
+}
+
+impl AdminServer {
+    /// Creates an admin API for the given app, which requests that it shut
+    /// down by sending on `shutdown`, as an interrupt does.
+    pub fn new(token: String, app: &App, shutdown: UnboundedSender<()>) -> anyhow::Result<Self> {
+        if token.is_empty() {
+            bail!("the admin API requires a token");
+        }
+        let config = AppConfig {
+            name: app.get_metadata(spin_app::APP_NAME_KEY)?,
+            components: app
+                .components()
+                .map(|component| ComponentConfig {
+                    id: component.id().to_owned(),
+                    source: component.source().content.source.clone(),
+                })
+                .collect(),
+            triggers: app
+                .triggers()
+                .map(|trigger| TriggerConfig {
+                    id: trigger.id().to_owned(),
+                    trigger_type: trigger.trigger_type().to_owned(),
+                    component: trigger.component().ok().map(|c| c.id().to_owned()),
+                })
+                .collect(),
+        };
+        Ok(Self {
+            token,
+            config,
+            shutdown,
+            virtual_clock: None,
+            component_swapper: None,
+            registry_cache: None,
+        })
+    }
+
+    /// Lets the API control the app's virtual clock.
+    pub fn set_virtual_clock(&mut self, clock: VirtualClock) {
+        self.virtual_clock = Some(clock);
+    }
+
+    /// Lets the API swap the app's components.
+    pub fn set_component_swapper(&mut self, swapper: impl SwapComponent + 'static) {
+        self.component_swapper = Some(Box::new(swapper));
+    }
+
+    /// Lets the API read new versions of components from the registry cache.
+    pub fn set_registry_cache(&mut self, registry_cache: Box<dyn RegistryCache>) {
+        self.registry_cache = Some(registry_cache);
+    }
+
+    /// Starts serving the API in the background.
+    pub async fn serve(self: Arc<Self>, listen: &AdminListen) -> anyhow::Result<()> {
+        match listen {
+            AdminListen::Tcp(addr) => {
+                let listener = tokio::net::TcpListener::bind(addr)
+                    .await
+                    .with_context(|| format!("failed to listen for admin requests on {addr}"))?;
+                tracing::info!("Serving admin API on http://{addr}");
+                tokio::spawn(async move {
+                    loop {
+                        match listener.accept().await {
+                            Ok((stream, _)) => self.clone().serve_connection(stream),
+                            Err(err) => tracing::warn!("Failed to accept admin connection: {err}"),
+                        }
+                    }
+                });
+            }
+            #[cfg(unix)]
+            AdminListen::Unix(path) => {
+                // A socket left behind by a previous run would prevent binding.
+                if path.exists() {
+                    std::fs::remove_file(path).with_context(|| {
+                        format!("failed to remove stale admin socket {}", path.display())
+                    })?;
+                }
+                let listener = tokio::net::UnixListener::bind(path).with_context(|| {
+                    format!("failed to listen for admin requests on {}", path.display())
+                })?;
+                tracing::info!("Serving admin API on unix:{}", path.display());
+                tokio::spawn(async move {
+                    loop {
+                        match listener.accept().await {
+                            Ok((stream, _)) => self.clone().serve_connection(stream),
+                            Err(err) => tracing::warn!("Failed to accept admin connection: {err}"),
+                        }
+                    }
+                });
+            }
+            #[cfg(not(unix))]
+            AdminListen::Unix(_) => bail!("Unix sockets are not supported on this platform"),
+        }
+        Ok(())
+    }
+
+    fn serve_connection(
+        self: Arc<Self>,
+        stream: impl AsyncRead + AsyncWrite + Unpin + Send + 'static,
+    ) {
+        tokio::spawn(async move {
+            let service = service_fn(|req| {
+                let server = self.clone();
+                async move { Ok::<_, Infallible>(server.handle(req).await) }
+            });
+            if let Err(err) = hyper::server::conn::http1::Builder::new()
+                .serve_connection(TokioIo::new(stream), service)
+                .await
+            {
+                tracing::debug!("Error serving admin connection: {err}");
+            }
+        });
+    }
+
+    async fn handle(&self, req: Request<Incoming>) -> Response<Full<Bytes>> {
+        if !self.is_authorized(&req) {
+            return text(StatusCode::UNAUTHORIZED, "missing or invalid admin token");
+        }
+        let method = req.method().clone();
+        let path = req.uri().path().to_owned();
+        match (&method, path.as_str()) {
+            (&Method::GET, "/health") => json(&serde_json::json!({ "status": "ok" })),
+            (&Method::GET, "/config") => json(&self.config),
+            (&Method::GET, "/log-level") => {
+                json(&serde_json::json!({ "filter": spin_telemetry::log_filter::current() }))
+            }
+            (&Method::PUT, "/log-level") => {
+                let body = match read_body(req).await {
+                    Ok(body) => body,
+                    Err(err) => return text(StatusCode::BAD_REQUEST, &err.to_string()),
+                };
+                match spin_telemetry::log_filter::set(body.trim()) {
+                    Ok(()) => {
+                        tracing::info!("Log filter changed to {:?} by admin API", body.trim());
+                        json(
+                            &serde_json::json!({ "filter": spin_telemetry::log_filter::current() }),
+                        )
+                    }
+                    Err(err) => text(StatusCode::BAD_REQUEST, &format!("{err:#}")),
+                }
+            }
+            (&Method::GET, "/traces") => recent_traces(req.uri().query()),
+            (&Method::POST, "/shutdown") => {
+                tracing::info!("Shutdown requested by admin API");
+                let shutdown = self.shutdown.clone();
+                tokio::spawn(async move {
+                    // Give the response a chance to be sent before the app exits.
+                    tokio::time::sleep(Duration::from_millis(100)).await;
+                    _ = shutdown.send(());
+                });
+                text(StatusCode::ACCEPTED, "shutting down")
+            }
+            (_, "/clock" | "/clock/advance" | "/clock/freeze" | "/clock/resume")
+                if self.virtual_clock.is_none() =>
+            {
+                text(
+                    StatusCode::NOT_FOUND,
+                    "the app does not run against a virtual clock",
+                )
+            }
+            (&Method::GET, "/clock") => self.clock_status(),
+            (&Method::POST, "/clock/advance") => {
+                let by = match read_body(req)
+                    .await
+                    .and_then(|body| spin_common::arg_parser::parse_duration(body.trim()))
+                {
+                    Ok(by) => by,
+                    Err(err) => return text(StatusCode::BAD_REQUEST, &format!("{err:#}")),
+                };
+                self.clock().advance(by);
+                tracing::info!("Virtual clock advanced by {by:?} by admin API");
+                self.clock_status()
+            }
+            (&Method::POST, "/clock/freeze") => {
+                self.clock().freeze();
+                tracing::info!("Virtual clock frozen by admin API");
+                self.clock_status()
+            }
+            (&Method::POST, "/clock/resume") => {
+                self.clock().resume();
+                tracing::info!("Virtual clock resumed by admin API");
+                self.clock_status()
+            }
+            (&Method::PUT, path) if path.starts_with(COMPONENTS_PREFIX) => {
+                self.swap_component(&path[COMPONENTS_PREFIX.len()..], req)
+                    .await
+            }
+            (
+                _,
+                "/health" | "/config" | "/log-level" | "/traces" | "/shutdown" | "/clock"
+                | "/clock/advance" | "/clock/freeze" | "/clock/resume",
+            ) => text(StatusCode::METHOD_NOT_ALLOWED, "method not allowed"),
+            (_, path) if path.starts_with(COMPONENTS_PREFIX) => {
+                text(StatusCode::METHOD_NOT_ALLOWED, "method not allowed")
+            }
+            _ => text(StatusCode::NOT_FOUND, "not found"),
+        }
+    }
+
+    async fn swap_component(
+        &self,
+        component_id: &str,
+        req: Request<Incoming>,
+    ) -> Response<Full<Bytes>> {
+        let Some(swapper) = &self.component_swapper else {
+            return text(
+                StatusCode::NOT_FOUND,
+                "the app does not support swapping components",
+            );
+        };
+        if !self.config.components.iter().any(|c| c.id == component_id) {
+            return text(
+                StatusCode::NOT_FOUND,
+                &format!("no such component {component_id:?}"),
+            );
+        }
+        let source = match read_body(req).await.and_then(|body| {
+            serde_json::from_str::<NewComponentSource>(&body)
+                .context("expected `{\"path\": ...}` or `{\"digest\": ...}`")
+        }) {
+            Ok(source) => source,
+            Err(err) => return text(StatusCode::BAD_REQUEST, &format!("{err:#}")),
+        };
+        let wasm = match source.read(self.registry_cache.as_deref()).await {
+            Ok(wasm) => wasm,
+            Err(err) => return text(StatusCode::BAD_REQUEST, &format!("{err:#}")),
+        };
+        match swapper.swap(component_id, wasm).await {
+            Ok(()) => {
+                tracing::info!("Component {component_id:?} swapped by admin API from {source:?}");
+                json(&serde_json::json!({ "id": component_id, "status": "swapped" }))
+            }
+            Err(err) => {
+                tracing::warn!("Failed to swap component {component_id:?}: {err:#}");
+                text(StatusCode::UNPROCESSABLE_ENTITY, &format!("{err:#}"))
+            }
+        }
+    }
+
+    fn clock(&self) -> &VirtualClock {
+        self.virtual_clock
+            .as_ref()
+            .expect("clock routes are only served with a virtual clock")
+    }
+
+    fn clock_status(&self) -> Response<Full<Bytes>> {
+        let clock = self.clock();
+        let now = clock
+            .now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default();
+        json(&serde_json::json!({
+            "now_unix_nanos": now.as_nanos() as u64,
+            "elapsed_nanos": clock.elapsed().as_nanos() as u64,
+            "frozen": clock.is_frozen(),
+        }))
+    }
+
+    fn is_authorized<B>(&self, req: &Request<B>) -> bool {
+        req.headers()
+            .get(header::AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "))
+            // Compared in constant time, so the time taken does not reveal
+            // how much of a guessed token is correct
+            .is_some_and(|token| token.as_bytes().ct_eq(self.token.as_bytes()).into())
+    }
+}
+
+fn recent_traces(query: Option<&str>) -> Response<Full<Bytes>> {
+    let after = match parse_after(query) {
+        Ok(after) => after,
+        Err(err) => return text(StatusCode::BAD_REQUEST, &format!("{err:#}")),
+    };
+    match spin_telemetry::recent_traces::traces_after(after) {
+        Some(traces) => json(&serde_json::json!({ "traces": traces })),
+        None => text(
+            StatusCode::NOT_FOUND,
+            "recent traces are not kept; run `spin up` with `--otel-recent-traces`",
+        ),
+    }
+}
+
+/// Parses the `after` parameter of a query string.
+fn parse_after(query: Option<&str>) -> anyhow::Result<Option<u64>> {
+    let Some(after) = query
+        .unwrap_or_default()
+        .split('&')
+        .find_map(|param| param.strip_prefix("after="))
+    else {
+        return Ok(None);
+    };
+    let after = after
+        .parse()
+        .with_context(|| format!("invalid `after` parameter {after:?}"))?;
+    Ok(Some(after))
+}
+
+async fn read_body(req: Request<Incoming>) -> anyhow::Result<String> {
+    let body = http_body_util::Limited::new(req.into_body(), MAX_BODY_SIZE)
+        .collect()
+        .await
+        .map_err(|err| anyhow::anyhow!("failed to read request body: {err}"))?
+        .to_bytes();
+    String::from_utf8(body.to_vec()).context("request body is not valid UTF-8")
+}
+
+fn text(status: StatusCode, body: &str) -> Response<Full<Bytes>> {
+    Response::builder()
+        .status(status)
+        .header(header::CONTENT_TYPE, "text/plain")
+        .body(Full::new(Bytes::copy_from_slice(body.as_bytes())))
+        .expect("response should be valid")
+}
+
+fn json(value: &impl Serialize) -> Response<Full<Bytes>> {
+    match serde_json::to_vec_pretty(value) {
+        Ok(body) => Response::builder()
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Full::new(Bytes::from(body)))
+            .expect("response should be valid"),
+        Err(err) => text(StatusCode::INTERNAL_SERVER_ERROR, &err.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn listen_addresses_must_be_local() {
+        assert_eq!(
+            AdminListen::Tcp("127.0.0.1:3999".parse().unwrap()),
+            "127.0.0.1:3999".parse().unwrap()
+        );
+        assert_eq!(
+            AdminListen::Unix("/run/spin/admin.sock".into()),
+            "unix:/run/spin/admin.sock".parse().unwrap()
+        );
+        assert!("0.0.0.0:3999".parse::<AdminListen>().is_err());
+        assert!("localhost".parse::<AdminListen>().is_err());
+    }
+
+    #[test]
+    fn new_component_sources_are_parsed() {
+        assert_eq!(
+            NewComponentSource::Path("/tmp/app.wasm".into()),
+            serde_json::from_str(r#"{"path": "/tmp/app.wasm"}"#).unwrap()
+        );
+        assert_eq!(
+            NewComponentSource::Digest("sha256:abc".into()),
+            serde_json::from_str(r#"{"digest": "sha256:abc"}"#).unwrap()
+        );
+        assert!(
+            serde_json::from_str::<NewComponentSource>(r#"{"url": "https://example.com"}"#)
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn cached_wasm_must_match_digest() {
+        let digest = "sha256:a948904f2f0f479b8f8197694b30184b0d2ed1c1cd2a1ec0fb85d299a192a447";
+        assert!(verify_digest(b"hello world\n", digest).is_ok());
+        assert!(verify_digest(b"goodbye world\n", digest).is_err());
+        assert!(verify_digest(b"hello world\n", "md5:6f5902ac237024bdd0c176cb93063dc4").is_err());
+    }
+
+    #[test]
+    fn trace_cursors_are_parsed() {
+        assert_eq!(None, parse_after(None).unwrap());
+        assert_eq!(Some(42), parse_after(Some("after=42")).unwrap());
+        assert_eq!(Some(7), parse_after(Some("limit=1&after=7")).unwrap());
+        assert!(parse_after(Some("after=latest")).is_err());
+    }
+}
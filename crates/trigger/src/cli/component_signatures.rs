@@ -0,0 +1,244 @@
+use std::collections::HashMap;
+
+use anyhow::{Context as _, bail, ensure};
+use base64::Engine as _;
+use ring::signature::{ED25519, UnparsedPublicKey};
+use serde::Deserialize;
+use spin_app::{AppComponent, MetadataKey};
+use spin_factors::RuntimeFactors;
+use spin_factors_executor::ExecutorHooks;
+
+/// The app metadata key recording where the app was loaded from.
+const ORIGIN_KEY: MetadataKey = MetadataKey::new("origin");
+/// The origin URL scheme of apps pulled from a registry; see `spin_oci::ORIGIN_URL_SCHEME`.
+const OCI_ORIGIN_SCHEME: &str = "vnd.fermyon.origin-oci:";
+/// The component metadata key of the component's signature.
+const SIGNATURE_KEY: MetadataKey = MetadataKey::new("signature");
+/// The component metadata key of the signatures of the component's dependencies.
+const DEPENDENCY_SIGNATURES_KEY: MetadataKey<HashMap<String, String>> =
+    MetadataKey::new("dependency_signatures");
+
+/// Which apps must have signed components.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum SignaturesRequired {
+    /// All apps, wherever they were loaded from.
+    #[default]
+    All,
+    /// Only apps pulled from a registry.
+    Registry,
+}
+
+/// An [`ExecutorHooks`] that refuses to load apps whose components are not
+/// signed by a trusted key.
+///
+/// Each component's signature is an Ed25519 signature of its Wasm source,
+/// given by the `signature` field of the component in the manifest. The
+/// sources of its dependencies must be signed too, by the
+/// `dependency_signatures` field. Sources are verified as the component
+/// loader reads them, so what is verified is exactly what is compiled.
+pub struct ComponentSignatureHook {
+    trusted_keys: Vec<Vec<u8>>,
+    required: SignaturesRequired,
+}
+
+impl ComponentSignatureHook {
+    /// Creates a hook trusting the given base64-encoded Ed25519 public keys.
+    pub fn new(trusted_keys: &[String], required: SignaturesRequired) -> anyhow::Result<Self> {
+        ensure!(
+            !trusted_keys.is_empty(),
+            "component signatures require at least one trusted key"
+        );
+        let trusted_keys = trusted_keys
+            .iter()
+            .map(|key| {
+                let key = base64::engine::general_purpose::STANDARD
+                    .decode(key)
+                    .with_context(|| format!("trusted key {key:?} is not valid base64"))?;
+                ensure!(
+                    key.len() == 32,
+                    "trusted key is {} bytes long; Ed25519 public keys are 32 bytes",
+                    key.len()
+                );
+                Ok(key)
+            })
+            .collect::<anyhow::Result<_>>()?;
+        Ok(Self {
+            trusted_keys,
+            required,
+        })
+    }
+
+    /// Whether the given app's components must be signed.
+    fn required_for(&self, app: &spin_app::App) -> anyhow::Result<bool> {
+        Ok(match self.required {
+            SignaturesRequired::All => true,
+            SignaturesRequired::Registry => app
+                .get_metadata(ORIGIN_KEY)?
+                .is_some_and(|origin| origin.starts_with(OCI_ORIGIN_SCHEME)),
+        })
+    }
+
+    /// Verifies the source of a component, or of its dependency with the given
+    /// name, against the signature given for it in the manifest.
+    fn verify_source(
+        &self,
+        component: &AppComponent,
+        dependency: Option<&str>,
+        wasm: &[u8],
+    ) -> anyhow::Result<()> {
+        let signature = match dependency {
+            None => component.get_metadata(SIGNATURE_KEY)?,
+            Some(name) => component
+                .get_metadata(DEPENDENCY_SIGNATURES_KEY)?
+                .and_then(|mut signatures| signatures.remove(name)),
+        };
+        self.verify(wasm, signature.as_deref())
+    }
+
+    fn verify(&self, wasm: &[u8], signature: Option<&str>) -> anyhow::Result<()> {
+        let Some(signature) = signature else {
+            bail!("the component is not signed");
+        };
+        let signature = base64::engine::general_purpose::STANDARD
+            .decode(signature)
+            .context("the component's signature is not valid base64")?;
+        let trusted = self.trusted_keys.iter().any(|key| {
+            UnparsedPublicKey::new(&ED25519, key)
+                .verify(wasm, &signature)
+                .is_ok()
+        });
+        ensure!(
+            trusted,
+            "the component's signature is invalid or not made by a trusted key"
+        );
+        Ok(())
+    }
+}
+
+#[spin_core::async_trait]
+impl<F: RuntimeFactors, U> ExecutorHooks<F, U> for ComponentSignatureHook {
+    fn load_source(
+        &self,
+        configured_app: &spin_factors::ConfiguredApp<F>,
+        component: &AppComponent,
+        dependency: Option<&str>,
+        wasm: &[u8],
+    ) -> anyhow::Result<()> {
+        if !self.required_for(configured_app.app())? {
+            return Ok(());
+        }
+        self.verify_source(component, dependency, wasm)
+            .with_context(|| match dependency {
+                None => format!(
+                    "refusing to run component {:?}: runtime config requires components to be signed",
+                    component.id()
+                ),
+                Some(name) => format!(
+                    "refusing to run dependency {name:?} of component {:?}: runtime config requires components and their dependencies to be signed",
+                    component.id()
+                ),
+            })
+    }
+
+    fn swap_component(
+        &self,
+        configured_app: &spin_factors::ConfiguredApp<F>,
+        component_id: &str,
+        _wasm: &[u8],
+    ) -> anyhow::Result<()> {
+        if !self.required_for(configured_app.app())? {
+            return Ok(());
+        }
+        // A swapped-in version has no signature in the manifest to check.
+        bail!(
+            "refusing to swap component {component_id:?}: runtime config requires components to be signed"
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ring::rand::SystemRandom;
+    use ring::signature::{Ed25519KeyPair, KeyPair as _};
+
+    use super::*;
+
+    fn key_pair() -> Ed25519KeyPair {
+        let pkcs8 = Ed25519KeyPair::generate_pkcs8(&SystemRandom::new()).unwrap();
+        Ed25519KeyPair::from_pkcs8(pkcs8.as_ref()).unwrap()
+    }
+
+    fn encode(bytes: &[u8]) -> String {
+        base64::engine::general_purpose::STANDARD.encode(bytes)
+    }
+
+    #[test]
+    fn verifies_signatures_by_trusted_keys() {
+        let trusted = key_pair();
+        let untrusted = key_pair();
+        let hook = ComponentSignatureHook::new(
+            &[encode(trusted.public_key().as_ref())],
+            SignaturesRequired::All,
+        )
+        .unwrap();
+        let wasm = b"\0asm component";
+
+        let signature = encode(trusted.sign(wasm).as_ref());
+        hook.verify(wasm, Some(&signature)).unwrap();
+
+        let err = hook
+            .verify(b"\0asm tampered", Some(&signature))
+            .unwrap_err();
+        assert!(err.to_string().contains("invalid"), "{err}");
+        let signature = encode(untrusted.sign(wasm).as_ref());
+        hook.verify(wasm, Some(&signature)).unwrap_err();
+        let err = hook.verify(wasm, None).unwrap_err();
+        assert_eq!("the component is not signed", err.to_string());
+    }
+
+    #[test]
+    fn verifies_dependency_sources() {
+        let key = key_pair();
+        let hook = ComponentSignatureHook::new(
+            &[encode(key.public_key().as_ref())],
+            SignaturesRequired::All,
+        )
+        .unwrap();
+        let locked = serde_json::from_value(serde_json::json!({
+            "spin_lock_version": 1,
+            "triggers": [],
+            "components": [{
+                "id": "signed",
+                "source": { "content_type": "application/wasm" },
+                "metadata": {
+                    "signature": encode(key.sign(b"component").as_ref()),
+                    "dependency_signatures": {
+                        "my:dep/import": encode(key.sign(b"dependency").as_ref()),
+                    },
+                },
+            }],
+        }))
+        .unwrap();
+        let app = spin_app::App::new("test", locked);
+        let component = app.get_component("signed").unwrap();
+
+        hook.verify_source(&component, None, b"component").unwrap();
+        hook.verify_source(&component, Some("my:dep/import"), b"dependency")
+            .unwrap();
+        hook.verify_source(&component, Some("my:dep/import"), b"component")
+            .unwrap_err();
+        let err = hook
+            .verify_source(&component, Some("my:other/import"), b"dependency")
+            .unwrap_err();
+        assert_eq!("the component is not signed", err.to_string());
+    }
+
+    #[test]
+    fn rejects_malformed_keys() {
+        assert!(ComponentSignatureHook::new(&[], SignaturesRequired::All).is_err());
+        assert!(
+            ComponentSignatureHook::new(&[encode(b"too short")], SignaturesRequired::All).is_err()
+        );
+    }
+}
@@ -0,0 +1,77 @@
+use std::{
+    collections::HashSet,
+    path::PathBuf,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use anyhow::Context as _;
+use spin_core::{async_trait, wasmtime::GuestProfiler};
+use spin_factors::RuntimeFactors;
+use spin_factors_executor::{ExecutorHooks, FactorsInstanceBuilder};
+
+/// An [`ExecutorHooks`] that profiles each instance of the given components,
+/// writing one profile per instance to the output directory.
+pub struct GuestProfilerHook {
+    component_ids: HashSet<String>,
+    output_dir: PathBuf,
+    instance_count: AtomicUsize,
+}
+
+impl GuestProfilerHook {
+    pub fn new(component_ids: impl IntoIterator<Item = String>, output_dir: PathBuf) -> Self {
+        Self {
+            component_ids: component_ids.into_iter().collect(),
+            output_dir,
+            instance_count: AtomicUsize::new(0),
+        }
+    }
+}
+
+#[async_trait]
+impl<F: RuntimeFactors, U> ExecutorHooks<F, U> for GuestProfilerHook {
+    async fn configure_app(
+        &self,
+        configured_app: &spin_factors::ConfiguredApp<F>,
+    ) -> anyhow::Result<()> {
+        let app = configured_app.app();
+        if let Some(id) = self
+            .component_ids
+            .iter()
+            .find(|id| app.get_component(id).is_none())
+        {
+            anyhow::bail!("Cannot profile component '{id}': it does not exist in the application");
+        }
+        std::fs::create_dir_all(&self.output_dir).with_context(|| {
+            format!(
+                "Failed to create guest profile directory {}",
+                self.output_dir.display()
+            )
+        })?;
+        Ok(())
+    }
+
+    fn prepare_instance(&self, builder: &mut FactorsInstanceBuilder<F, U>) -> anyhow::Result<()> {
+        let component_id = builder.app_component().id().to_owned();
+        if !self.component_ids.contains(&component_id) {
+            return Ok(());
+        }
+        let n = self.instance_count.fetch_add(1, Ordering::Relaxed);
+        // Samples are taken on epoch ticks
+        let sample_interval = builder.store_builder().epoch_tick_interval();
+        let profiler = GuestProfiler::new_component(
+            builder.wasmtime_engine(),
+            &component_id,
+            sample_interval,
+            builder.component().clone(),
+            [],
+        )?;
+        let file_name = format!(
+            "{}-{n}.profile.json",
+            sanitize_filename::sanitize(&component_id)
+        );
+        builder
+            .store_builder()
+            .guest_profiler(profiler, self.output_dir.join(file_name));
+        Ok(())
+    }
+}
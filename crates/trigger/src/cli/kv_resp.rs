@@ -0,0 +1,736 @@
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use anyhow::{Context as _, bail, ensure};
+use spin_factor_key_value::Store;
+use spin_world::MAX_HOST_BUFFERED_BYTES;
+use subtle::ConstantTimeEq as _;
+use tokio::io::{
+    AsyncBufRead, AsyncBufReadExt as _, AsyncRead, AsyncReadExt as _, AsyncWrite,
+    AsyncWriteExt as _, BufReader,
+};
+
+/// The most arguments a command may have.
+const MAX_ARGS: usize = 1024 * 1024;
+
+/// The commands the server knows, for telling unknown commands from known
+/// commands called with the wrong arguments.
+const COMMANDS: &[&str] = &[
+    "PING", "ECHO", "QUIT", "SELECT", "CLIENT", "COMMAND", "CONFIG", "HELLO", "INFO", "DBSIZE",
+    "KEYS", "SCAN", "GET", "MGET", "EXISTS", "TYPE", "STRLEN", "TTL", "PTTL", "SET", "MSET", "DEL",
+];
+
+/// Parses the address to serve the Redis protocol on, which must be a
+/// loopback address.
+pub fn parse_resp_listen(s: &str) -> anyhow::Result<SocketAddr> {
+    let addr: SocketAddr = s
+        .parse()
+        .with_context(|| format!("expected `<ip>:<port>`; got {s:?}"))?;
+    if !addr.ip().is_loopback() {
+        bail!("key-value stores may only be served on a loopback address; got {addr}");
+    }
+    Ok(addr)
+}
+
+/// Serves the app's key-value stores over the Redis protocol (RESP2), so that
+/// standard tools such as `redis-cli` can inspect live data.
+///
+/// Each store is a numbered database, chosen with `SELECT`. Stores are
+/// numbered in label order, and `INFO` lists which is which. The server
+/// offers `GET`, `MGET`, `EXISTS`, `TYPE`, `STRLEN`, `TTL`, `PTTL`, `KEYS`,
+/// `SCAN` and `DBSIZE`, and, if writable, `SET`, `MSET` and `DEL`. Values are
+/// all strings, and never expire. If the server has a token, clients must
+/// send it with `AUTH` before any other command.
+pub struct KeyValueRespServer {
+    stores: Vec<(String, Arc<dyn Store>)>,
+    writable: bool,
+    token: Option<String>,
+}
+
+impl KeyValueRespServer {
+    /// Creates a server for the app's stores, which may only be written to
+    /// if `writable` is set. A writable server must have a `token`.
+    pub async fn new(
+        key_value: &spin_factor_key_value::AppState,
+        writable: bool,
+        token: Option<String>,
+    ) -> anyhow::Result<Self> {
+        ensure!(
+            token.is_some() || !writable,
+            "a token is required to serve key-value stores writable"
+        );
+        let mut stores = vec![];
+        for label in key_value.store_labels() {
+            let store = key_value
+                .get_store(&label)
+                .await
+                .with_context(|| format!("failed to open key-value store {label:?}"))?;
+            stores.push((label, store));
+        }
+        Ok(Self {
+            stores,
+            writable,
+            token,
+        })
+    }
+
+    /// Starts serving the stores in the background.
+    pub async fn serve(self: Arc<Self>, addr: SocketAddr) -> anyhow::Result<()> {
+        let listener = tokio::net::TcpListener::bind(addr)
+            .await
+            .with_context(|| format!("failed to listen for Redis connections on {addr}"))?;
+        let access = if self.writable {
+            "read-write"
+        } else {
+            "read-only"
+        };
+        tracing::info!("Serving key-value stores {access} on redis://{addr}");
+        tokio::spawn(async move {
+            loop {
+                match listener.accept().await {
+                    Ok((stream, _)) => {
+                        let server = self.clone();
+                        tokio::spawn(async move {
+                            if let Err(err) = server.serve_connection(stream).await {
+                                tracing::debug!("Error serving Redis connection: {err:#}");
+                            }
+                        });
+                    }
+                    Err(err) => tracing::warn!("Failed to accept Redis connection: {err}"),
+                }
+            }
+        });
+        Ok(())
+    }
+
+    async fn serve_connection(
+        &self,
+        stream: impl AsyncRead + AsyncWrite + Unpin,
+    ) -> anyhow::Result<()> {
+        let (reader, mut writer) = tokio::io::split(stream);
+        let mut reader = BufReader::new(reader);
+        let mut db = 0;
+        let mut authenticated = self.token.is_none();
+        let mut out = vec![];
+        loop {
+            out.clear();
+            let args = match read_command(&mut reader).await {
+                Ok(Some(args)) if args.is_empty() => continue,
+                Ok(Some(args)) => args,
+                Ok(None) => return Ok(()),
+                Err(err) => {
+                    Reply::Error(format!("ERR Protocol error: {err:#}")).encode(&mut out);
+                    writer.write_all(&out).await?;
+                    return Ok(());
+                }
+            };
+            let quit = args[0].eq_ignore_ascii_case(b"QUIT");
+            let reply = if quit {
+                Reply::ok()
+            } else if args[0].eq_ignore_ascii_case(b"AUTH") {
+                self.auth(&args[1..], &mut authenticated)
+            } else if !authenticated {
+                Reply::Error("NOAUTH Authentication required.".into())
+            } else {
+                self.execute(&mut db, &args).await
+            };
+            reply.encode(&mut out);
+            writer.write_all(&out).await?;
+            if quit {
+                return Ok(());
+            }
+        }
+    }
+
+    /// Executes `AUTH [username] password`, in which the only user is
+    /// `default`.
+    fn auth(&self, args: &[Vec<u8>], authenticated: &mut bool) -> Reply {
+        let password = match args {
+            [password] => password,
+            [username, password] if username.as_slice() == b"default" => password,
+            [_, _] => {
+                return Reply::Error(
+                    "WRONGPASS invalid username-password pair or user is disabled.".into(),
+                );
+            }
+            _ => {
+                return Reply::Error("ERR wrong number of arguments for 'auth' command".into());
+            }
+        };
+        let Some(token) = &self.token else {
+            return Reply::Error(
+                "ERR AUTH <password> called without any password configured for the default user."
+                    .into(),
+            );
+        };
+        // Compared in constant time, so the time taken does not reveal how
+        // much of a guessed token is correct
+        if bool::from(password.as_slice().ct_eq(token.as_bytes())) {
+            *authenticated = true;
+            Reply::ok()
+        } else {
+            Reply::Error("WRONGPASS invalid username-password pair or user is disabled.".into())
+        }
+    }
+
+    async fn execute(&self, db: &mut usize, args: &[Vec<u8>]) -> Reply {
+        let name = String::from_utf8_lossy(&args[0]).to_ascii_uppercase();
+        match self.try_execute(db, &name, &args[1..]).await {
+            Ok(Some(reply)) => reply,
+            Ok(None) if COMMANDS.contains(&name.as_str()) => Reply::Error(format!(
+                "ERR wrong number of arguments for '{}' command",
+                name.to_ascii_lowercase()
+            )),
+            Ok(None) => Reply::Error(format!(
+                "ERR unknown command '{}'",
+                String::from_utf8_lossy(&args[0])
+            )),
+            Err(err) => Reply::Error(format!("ERR {err:#}")),
+        }
+    }
+
+    /// Executes a command, returning `None` if it is unknown or has the wrong
+    /// number of arguments.
+    async fn try_execute(
+        &self,
+        db: &mut usize,
+        name: &str,
+        args: &[Vec<u8>],
+    ) -> anyhow::Result<Option<Reply>> {
+        let current = *db;
+        let store = || {
+            self.stores
+                .get(current)
+                .map(|(_, store)| store)
+                .context("no key-value stores")
+        };
+        let reply = match (name, args) {
+            ("PING", []) => Reply::Simple("PONG"),
+            ("PING" | "ECHO", [message]) => Reply::bulk(message.clone()),
+            ("SELECT", [index]) => {
+                let index: usize = parse(index)?;
+                ensure!(index < self.stores.len(), "DB index is out of range");
+                *db = index;
+                Reply::ok()
+            }
+            // Clients name themselves with `CLIENT SETNAME` and `CLIENT SETINFO`.
+            ("CLIENT", [_, ..]) => Reply::ok(),
+            ("COMMAND", _) => Reply::Array(vec![]),
+            ("CONFIG", [subcommand, parameter]) if subcommand.eq_ignore_ascii_case(b"GET") => {
+                if parameter.eq_ignore_ascii_case(b"databases") {
+                    Reply::Array(vec![
+                        Reply::bulk("databases"),
+                        Reply::bulk(self.stores.len().to_string()),
+                    ])
+                } else {
+                    Reply::Array(vec![])
+                }
+            }
+            ("HELLO", _) => Reply::Error("NOPROTO unsupported protocol version".into()),
+            ("INFO", [] | [_]) => Reply::bulk(self.info().await?),
+            ("DBSIZE", []) => Reply::Integer(keys(store()?).await?.len() as i64),
+            ("KEYS", [pattern]) => {
+                let keys = keys(store()?).await?;
+                Reply::Array(
+                    keys.into_iter()
+                        .filter(|key| glob_match(pattern, key.as_bytes()))
+                        .map(Reply::bulk)
+                        .collect(),
+                )
+            }
+            ("SCAN", [_cursor, options @ ..]) => {
+                let mut pattern = None;
+                let mut options = options.iter();
+                while let Some(option) = options.next() {
+                    let value = options.next().context("syntax error")?;
+                    if option.eq_ignore_ascii_case(b"MATCH") {
+                        pattern = Some(value);
+                    } else if option.eq_ignore_ascii_case(b"TYPE") {
+                        if !value.eq_ignore_ascii_case(b"string") {
+                            return Ok(Some(Reply::Array(vec![
+                                Reply::bulk("0"),
+                                Reply::Array(vec![]),
+                            ])));
+                        }
+                    } else if !option.eq_ignore_ascii_case(b"COUNT") {
+                        bail!("syntax error");
+                    }
+                }
+                // Every key is returned at once, which `COUNT` permits.
+                let keys = keys(store()?).await?;
+                Reply::Array(vec![
+                    Reply::bulk("0"),
+                    Reply::Array(
+                        keys.into_iter()
+                            .filter(|key| {
+                                pattern.is_none_or(|pattern| glob_match(pattern, key.as_bytes()))
+                            })
+                            .map(Reply::bulk)
+                            .collect(),
+                    ),
+                ])
+            }
+            ("GET", [key]) => Reply::Bulk(store()?.get(utf8(key)?, MAX_HOST_BUFFERED_BYTES).await?),
+            ("MGET", keys @ [_, ..]) => {
+                let store = store()?;
+                let mut values = vec![];
+                for key in keys {
+                    values.push(Reply::Bulk(
+                        store.get(utf8(key)?, MAX_HOST_BUFFERED_BYTES).await?,
+                    ));
+                }
+                Reply::Array(values)
+            }
+            ("EXISTS", keys @ [_, ..]) => Reply::Integer(count_existing(store()?, keys).await?),
+            ("TYPE", [key]) => {
+                if store()?.exists(utf8(key)?).await? {
+                    Reply::Simple("string")
+                } else {
+                    Reply::Simple("none")
+                }
+            }
+            ("STRLEN", [key]) => {
+                let value = store()?.get(utf8(key)?, MAX_HOST_BUFFERED_BYTES).await?;
+                Reply::Integer(value.map_or(0, |value| value.len() as i64))
+            }
+            // Keys never expire.
+            ("TTL" | "PTTL", [key]) => {
+                if store()?.exists(utf8(key)?).await? {
+                    Reply::Integer(-1)
+                } else {
+                    Reply::Integer(-2)
+                }
+            }
+            ("SET" | "MSET" | "DEL", _) if !self.writable => Reply::Error(
+                "READONLY the key-value stores are served read-only; \
+                 pass --key-value-resp-writable to allow writes"
+                    .into(),
+            ),
+            ("SET", [key, value]) => {
+                store()?.set(utf8(key)?, value).await?;
+                Reply::ok()
+            }
+            ("MSET", pairs) if !pairs.is_empty() && pairs.len() % 2 == 0 => {
+                let pairs = pairs
+                    .chunks(2)
+                    .map(|pair| Ok((utf8(&pair[0])?.to_owned(), pair[1].clone())))
+                    .collect::<anyhow::Result<_>>()?;
+                store()?.set_many(pairs).await?;
+                Reply::ok()
+            }
+            ("DEL", keys @ [_, ..]) => {
+                let deleted = count_existing(store()?, keys).await?;
+                let keys = keys
+                    .iter()
+                    .map(|key| utf8(key).map(ToOwned::to_owned))
+                    .collect::<anyhow::Result<_>>()?;
+                store()?.delete_many(keys).await?;
+                Reply::Integer(deleted)
+            }
+            _ => return Ok(None),
+        };
+        Ok(Some(reply))
+    }
+
+    /// Describes the server and its stores, in the format of Redis's `INFO`.
+    async fn info(&self) -> anyhow::Result<String> {
+        let mut info = format!(
+            "# Server\r\nredis_version:7.0.0\r\nspin_version:{}\r\n\r\n# Keyspace\r\n",
+            env!("CARGO_PKG_VERSION")
+        );
+        for (index, (_, store)) in self.stores.iter().enumerate() {
+            let keys = keys(store).await?.len();
+            info.push_str(&format!("db{index}:keys={keys},expires=0,avg_ttl=0\r\n"));
+        }
+        info.push_str("\r\n# Stores\r\n");
+        for (index, (label, _)) in self.stores.iter().enumerate() {
+            info.push_str(&format!("db{index}:{label}\r\n"));
+        }
+        Ok(info)
+    }
+}
+
+async fn keys(store: &Arc<dyn Store>) -> anyhow::Result<Vec<String>> {
+    let mut keys = store.get_keys(MAX_HOST_BUFFERED_BYTES).await?;
+    keys.sort();
+    Ok(keys)
+}
+
+async fn count_existing(store: &Arc<dyn Store>, keys: &[Vec<u8>]) -> anyhow::Result<i64> {
+    let mut count = 0;
+    for key in keys {
+        if store.exists(utf8(key)?).await? {
+            count += 1;
+        }
+    }
+    Ok(count)
+}
+
+fn utf8(arg: &[u8]) -> anyhow::Result<&str> {
+    std::str::from_utf8(arg).context("keys must be valid UTF-8")
+}
+
+fn parse<T: std::str::FromStr>(arg: &[u8]) -> anyhow::Result<T> {
+    std::str::from_utf8(arg)
+        .ok()
+        .and_then(|arg| arg.parse().ok())
+        .context("value is not an integer or out of range")
+}
+
+/// Reads a command, either as an array of bulk strings or as an inline
+/// command of space-separated words, as typed into `telnet`. Returns `None`
+/// at the end of the stream, or if the client is speaking HTTP.
+async fn read_command(
+    reader: &mut (impl AsyncBufRead + Unpin),
+) -> anyhow::Result<Option<Vec<Vec<u8>>>> {
+    let Some(line) = read_line(reader).await? else {
+        return Ok(None);
+    };
+    let Some(count) = line.strip_prefix(b"*") else {
+        let args: Vec<Vec<u8>> = line
+            .split(u8::is_ascii_whitespace)
+            .filter(|word| !word.is_empty())
+            .map(ToOwned::to_owned)
+            .collect();
+        // A web page can have a browser send a request here, whose lines
+        // would be run as inline commands. Like Redis, hang up on seeing one.
+        if args.first().is_some_and(|word| {
+            word.eq_ignore_ascii_case(b"POST") || word.eq_ignore_ascii_case(b"Host:")
+        }) {
+            tracing::warn!("Closing a Redis connection that sent an HTTP request");
+            return Ok(None);
+        }
+        return Ok(Some(args));
+    };
+    let count: usize = parse(count).context("invalid multibulk length")?;
+    ensure!(count <= MAX_ARGS, "invalid multibulk length");
+    let mut args = Vec::with_capacity(count);
+    for _ in 0..count {
+        let line = read_line(reader)
+            .await?
+            .context("unexpected end of stream")?;
+        let len = line.strip_prefix(b"$").context("expected '$'")?;
+        let len: usize = parse(len).context("invalid bulk length")?;
+        ensure!(len <= MAX_HOST_BUFFERED_BYTES, "invalid bulk length");
+        let mut arg = vec![0; len + 2];
+        reader.read_exact(&mut arg).await?;
+        ensure!(arg.ends_with(b"\r\n"), "expected CRLF after bulk string");
+        arg.truncate(len);
+        args.push(arg);
+    }
+    Ok(Some(args))
+}
+
+async fn read_line(reader: &mut (impl AsyncBufRead + Unpin)) -> anyhow::Result<Option<Vec<u8>>> {
+    let mut line = vec![];
+    let read = (&mut *reader)
+        .take(MAX_HOST_BUFFERED_BYTES as u64)
+        .read_until(b'\n', &mut line)
+        .await?;
+    if read == 0 {
+        return Ok(None);
+    }
+    ensure!(line.ends_with(b"\n"), "line too long");
+    line.pop();
+    if line.ends_with(b"\r") {
+        line.pop();
+    }
+    Ok(Some(line))
+}
+
+/// Matches a key against a Redis glob pattern, in which `*` matches any
+/// sequence, `?` any byte, `[...]` any byte in a set or range (negated with
+/// `^`), and `\` escapes the next byte.
+///
+/// Only the most recent `*` is ever backtracked to, so matching takes time
+/// proportional to the product of the lengths at worst, however many `*`s
+/// the pattern has.
+fn glob_match(pattern: &[u8], key: &[u8]) -> bool {
+    let (mut p, mut k) = (0, 0);
+    // The pattern position after the most recent `*`, and the key position
+    // it was last tried from.
+    let mut star = None;
+    while k < key.len() {
+        if pattern.get(p) == Some(&b'*') {
+            p += 1;
+            star = Some((p, k));
+            continue;
+        }
+        match match_byte(&pattern[p..], key[k]) {
+            Some((true, len)) => {
+                p += len;
+                k += 1;
+            }
+            // Let the `*` swallow one more byte, and try again from there.
+            _ => match star {
+                Some((star_p, star_k)) => {
+                    p = star_p;
+                    k = star_k + 1;
+                    star = Some((star_p, k));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&b| b == b'*')
+}
+
+/// Matches a single byte against the first element of a glob pattern other
+/// than `*`, returning whether it matched and the length of the element, or
+/// `None` if the pattern is empty.
+fn match_byte(pattern: &[u8], byte: u8) -> Option<(bool, usize)> {
+    let (&first, rest) = pattern.split_first()?;
+    let matched = match (first, rest) {
+        (b'?', _) => (true, 1),
+        (b'[', _) => {
+            let Some(end) = rest.iter().skip(1).position(|&b| b == b']') else {
+                // An unclosed `[` matches itself.
+                return Some((byte == b'[', 1));
+            };
+            let set = &rest[..end + 1];
+            let (negated, set) = match set.strip_prefix(b"^") {
+                Some(set) => (true, set),
+                None => (false, set),
+            };
+            let mut matched = false;
+            let mut i = 0;
+            while i < set.len() {
+                if i + 2 < set.len() && set[i + 1] == b'-' {
+                    matched |= (set[i]..=set[i + 2]).contains(&byte);
+                    i += 3;
+                } else {
+                    matched |= set[i] == byte;
+                    i += 1;
+                }
+            }
+            (matched != negated, end + 3)
+        }
+        (b'\\', [escaped, ..]) => (byte == *escaped, 2),
+        (literal, _) => (byte == literal, 1),
+    };
+    Some(matched)
+}
+
+/// A RESP2 reply.
+#[derive(Debug, PartialEq)]
+enum Reply {
+    Simple(&'static str),
+    Error(String),
+    Integer(i64),
+    Bulk(Option<Vec<u8>>),
+    Array(Vec<Reply>),
+}
+
+impl Reply {
+    fn ok() -> Self {
+        Self::Simple("OK")
+    }
+
+    fn bulk(value: impl Into<Vec<u8>>) -> Self {
+        Self::Bulk(Some(value.into()))
+    }
+
+    fn encode(&self, out: &mut Vec<u8>) {
+        match self {
+            Self::Simple(s) => out.extend_from_slice(format!("+{s}\r\n").as_bytes()),
+            Self::Error(e) => {
+                // Errors are a single line.
+                let e = e.replace(['\r', '\n'], " ");
+                out.extend_from_slice(format!("-{e}\r\n").as_bytes())
+            }
+            Self::Integer(n) => out.extend_from_slice(format!(":{n}\r\n").as_bytes()),
+            Self::Bulk(None) => out.extend_from_slice(b"$-1\r\n"),
+            Self::Bulk(Some(value)) => {
+                out.extend_from_slice(format!("${}\r\n", value.len()).as_bytes());
+                out.extend_from_slice(value);
+                out.extend_from_slice(b"\r\n");
+            }
+            Self::Array(items) => {
+                out.extend_from_slice(format!("*{}\r\n", items.len()).as_bytes());
+                for item in items {
+                    item.encode(out);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use spin_factor_key_value::StoreManager as _;
+    use spin_factor_key_value::runtime_config::spin::MakeKeyValueStore as _;
+    use spin_key_value_spin::{SpinKeyValueRuntimeConfig, SpinKeyValueStore};
+
+    use super::*;
+
+    async fn server(labels: &[&str], writable: bool, token: Option<&str>) -> KeyValueRespServer {
+        let mut stores = vec![];
+        for label in labels {
+            let manager = SpinKeyValueStore::new(None)
+                .make_store(SpinKeyValueRuntimeConfig::new(None))
+                .unwrap();
+            let store: Arc<dyn Store> = manager.get(label).await.unwrap();
+            stores.push((label.to_string(), store));
+        }
+        KeyValueRespServer {
+            stores,
+            writable,
+            token: token.map(ToOwned::to_owned),
+        }
+    }
+
+    /// Sends the commands over a connection, returning the raw replies.
+    async fn exchange(server: &KeyValueRespServer, commands: &[&[&str]]) -> String {
+        let mut request = vec![];
+        for command in commands {
+            Reply::Array(command.iter().map(|arg| Reply::bulk(*arg)).collect())
+                .encode(&mut request);
+        }
+        let (mut client, connection) = tokio::io::duplex(64 * 1024);
+        client.write_all(&request).await.unwrap();
+        client.shutdown().await.unwrap();
+        server.serve_connection(connection).await.unwrap();
+        let mut replies = String::new();
+        client.read_to_string(&mut replies).await.unwrap();
+        replies
+    }
+
+    #[test]
+    fn listen_addresses_must_be_local() {
+        assert!(parse_resp_listen("127.0.0.1:6380").is_ok());
+        assert!(parse_resp_listen("[::1]:6380").is_ok());
+        assert!(parse_resp_listen("0.0.0.0:6380").is_err());
+        assert!(parse_resp_listen("localhost").is_err());
+    }
+
+    #[test]
+    fn globs_match_like_redis() {
+        assert!(glob_match(b"*", b""));
+        assert!(glob_match(b"user:*", b"user:42"));
+        assert!(!glob_match(b"user:*", b"session:42"));
+        assert!(glob_match(b"h?llo", b"hello"));
+        assert!(glob_match(b"h[ae]llo", b"hallo"));
+        assert!(!glob_match(b"h[^e]llo", b"hello"));
+        assert!(glob_match(b"h[a-c]llo", b"hbllo"));
+        assert!(glob_match(b"a\\*b", b"a*b"));
+        assert!(!glob_match(b"a\\*b", b"axb"));
+        assert!(glob_match(b"*a*b", b"xxaxxb"));
+        assert!(!glob_match(b"*a*b", b"xxaxxbx"));
+        assert!(glob_match(b"[ab]*[^c]", b"abd"));
+        assert!(!glob_match(b"[ab]*[^c]", b"abc"));
+    }
+
+    #[test]
+    fn globs_with_many_stars_match_quickly() {
+        let key = [b'a'; 100];
+        let pattern = [&b"a*"[..]; 30].concat();
+        assert!(glob_match(&pattern, &key));
+        assert!(!glob_match(&[&pattern[..], b"b"].concat(), &key));
+    }
+
+    #[tokio::test]
+    async fn inline_and_multibulk_commands_are_read() {
+        let mut input = &b"PING\r\n*2\r\n$4\r\nECHO\r\n$5\r\nhello\r\nget  foo\n"[..];
+        assert_eq!(
+            Some(vec![b"PING".to_vec()]),
+            read_command(&mut input).await.unwrap()
+        );
+        assert_eq!(
+            Some(vec![b"ECHO".to_vec(), b"hello".to_vec()]),
+            read_command(&mut input).await.unwrap()
+        );
+        assert_eq!(
+            Some(vec![b"get".to_vec(), b"foo".to_vec()]),
+            read_command(&mut input).await.unwrap()
+        );
+        assert_eq!(None, read_command(&mut input).await.unwrap());
+
+        let mut input = &b"*1\r\n$10\r\nPING\r\n"[..];
+        assert!(read_command(&mut input).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn http_requests_end_the_connection() {
+        let mut input = &b"POST / HTTP/1.1\r\nHost: localhost:6380\r\nFLUSHALL\r\n"[..];
+        assert_eq!(None, read_command(&mut input).await.unwrap());
+
+        let mut input = &b"GET / HTTP/1.1\r\nhost: localhost:6380\r\nFLUSHALL\r\n"[..];
+        assert!(read_command(&mut input).await.unwrap().is_some());
+        assert_eq!(None, read_command(&mut input).await.unwrap());
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn stores_are_read_only_by_default() {
+        let server = server(&["default"], false, None).await;
+        server.stores[0].1.set("greeting", b"hello").await.unwrap();
+
+        let replies = exchange(
+            &server,
+            &[
+                &["GET", "greeting"],
+                &["GET", "missing"],
+                &["EXISTS", "greeting", "missing"],
+                &["SET", "greeting", "goodbye"],
+                &["KEYS", "greet*"],
+            ],
+        )
+        .await;
+        assert!(replies.starts_with("$5\r\nhello\r\n$-1\r\n:1\r\n-READONLY "));
+        assert!(replies.ends_with("*1\r\n$8\r\ngreeting\r\n"), "{replies}");
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn writable_stores_are_selected_by_index() {
+        let server = server(&["cache", "default"], true, Some("secret")).await;
+
+        let replies = exchange(
+            &server,
+            &[
+                &["AUTH", "secret"],
+                &["SELECT", "1"],
+                &["SET", "greeting", "hello"],
+                &["DBSIZE"],
+                &["SELECT", "2"],
+                &["DEL", "greeting", "missing"],
+                &["FLUSHALL"],
+                &["GET"],
+            ],
+        )
+        .await;
+        assert_eq!(
+            "+OK\r\n+OK\r\n+OK\r\n:1\r\n-ERR DB index is out of range\r\n:1\r\n\
+             -ERR unknown command 'FLUSHALL'\r\n\
+             -ERR wrong number of arguments for 'get' command\r\n",
+            replies
+        );
+        assert!(!server.stores[1].1.exists("greeting").await.unwrap());
+        assert!(!server.stores[0].1.exists("greeting").await.unwrap());
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn commands_require_the_token() {
+        let server = server(&["default"], true, Some("secret")).await;
+
+        let replies = exchange(
+            &server,
+            &[
+                &["SET", "greeting", "hello"],
+                &["AUTH", "guess"],
+                &["AUTH", "admin", "secret"],
+                &["GET", "greeting"],
+                &["AUTH", "default", "secret"],
+                &["GET", "greeting"],
+            ],
+        )
+        .await;
+        assert_eq!(
+            "-NOAUTH Authentication required.\r\n\
+             -WRONGPASS invalid username-password pair or user is disabled.\r\n\
+             -WRONGPASS invalid username-password pair or user is disabled.\r\n\
+             -NOAUTH Authentication required.\r\n\
+             +OK\r\n$-1\r\n",
+            replies
+        );
+        assert!(!server.stores[0].1.exists("greeting").await.unwrap());
+    }
+}
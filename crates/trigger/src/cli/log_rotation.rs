@@ -0,0 +1,201 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::Mutex,
+    time::{Duration, SystemTime},
+};
+
+use spin_common::ui::quoted_path;
+
+/// When to rotate component log files, and how many rotated files to keep.
+#[derive(Clone, Debug, Default)]
+pub struct LogRotationConfig {
+    /// Rotate a log file once it reaches this size in bytes.
+    pub max_size: Option<u64>,
+    /// Rotate a log file once it is this old.
+    pub max_age: Option<Duration>,
+    /// The number of rotated files to keep for each log file.
+    pub retention: usize,
+}
+
+impl LogRotationConfig {
+    /// Whether any rotation is configured.
+    pub fn is_enabled(&self) -> bool {
+        self.max_size.is_some() || self.max_age.is_some()
+    }
+
+    /// Rotates the log file at `path` if it has grown too big or too old,
+    /// returning whether it was rotated.
+    ///
+    /// The current file becomes `<path>.1`, any existing `<path>.1` becomes
+    /// `<path>.2` and so on; files beyond the retention count are deleted.
+    pub fn rotate_if_needed(&self, path: &Path) -> std::io::Result<bool> {
+        let metadata = match std::fs::metadata(path) {
+            Ok(metadata) => metadata,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(false),
+            Err(e) => return Err(e),
+        };
+        let too_big = self.max_size.is_some_and(|max| metadata.len() >= max);
+        // Not all filesystems record creation times; in that case only size
+        // based rotation applies.
+        let too_old = self.max_age.is_some_and(|max| {
+            metadata
+                .created()
+                .ok()
+                .and_then(|created| SystemTime::now().duration_since(created).ok())
+                .is_some_and(|age| age >= max)
+        });
+        if !(too_big || too_old) {
+            return Ok(false);
+        }
+
+        if self.retention == 0 {
+            std::fs::remove_file(path)?;
+            return Ok(true);
+        }
+        remove_if_exists(&rotated_path(path, self.retention))?;
+        for n in (1..self.retention).rev() {
+            let from = rotated_path(path, n);
+            if from.exists() {
+                std::fs::rename(&from, rotated_path(path, n + 1))?;
+            }
+        }
+        std::fs::rename(path, rotated_path(path, 1))?;
+        Ok(true)
+    }
+}
+
+/// Rotates log files which many writers append to at once.
+///
+/// Each writer checks for rotation before it writes, and reopens its log
+/// file once the file has been rotated, whether by it or another writer.
+#[derive(Debug, Default)]
+pub(crate) struct LogRotator {
+    config: LogRotationConfig,
+    // Log path -> number of times it has been rotated
+    rotations: Mutex<HashMap<PathBuf, u64>>,
+}
+
+impl LogRotator {
+    pub(crate) fn new(config: LogRotationConfig) -> Self {
+        Self {
+            config,
+            rotations: Default::default(),
+        }
+    }
+
+    pub(crate) fn is_enabled(&self) -> bool {
+        self.config.is_enabled()
+    }
+
+    /// Rotates the log file at `path` if needed, returning the number of
+    /// times it has been rotated so far. A writer whose file was opened
+    /// before the latest rotation must reopen it.
+    pub(crate) fn rotate_if_needed(&self, path: &Path) -> u64 {
+        // Serializes rotation so that concurrent writers don't rotate the same file twice.
+        let mut rotations = self.rotations.lock().unwrap();
+        let rotations = rotations.entry(path.to_owned()).or_default();
+        match self.config.rotate_if_needed(path) {
+            Ok(true) => *rotations += 1,
+            Ok(false) => (),
+            // A failed rotation shouldn't fail the write; keep appending instead.
+            Err(e) => tracing::warn!("Failed to rotate log file {}: {e}", quoted_path(path)),
+        }
+        *rotations
+    }
+}
+
+fn rotated_path(path: &Path, n: usize) -> PathBuf {
+    let mut rotated = path.as_os_str().to_owned();
+    rotated.push(format!(".{n}"));
+    rotated.into()
+}
+
+fn remove_if_exists(path: &Path) -> std::io::Result<()> {
+    match std::fs::remove_file(path) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e),
+        _ => Ok(()),
+    }
+}
+
+/// Parses an age such as `90s`, `30m`, `12h` or `7d`.
+pub(crate) fn parse_age(s: &str) -> Result<Duration, String> {
+    let error = || format!("expected integer suffixed by `s`, `m`, `h`, or `d`; got {s:?}");
+    if !s.ends_with(['s', 'm', 'h', 'd']) || s.ends_with("ms") {
+        return Err(error());
+    }
+    spin_common::arg_parser::parse_duration(s).map_err(|_| error())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rotates_by_size_and_keeps_retention() {
+        let dir = tempfile::tempdir().unwrap();
+        let log = dir.path().join("hello_stdout.txt");
+        let config = LogRotationConfig {
+            max_size: Some(4),
+            max_age: None,
+            retention: 2,
+        };
+
+        for content in ["first", "second", "third"] {
+            std::fs::write(&log, content).unwrap();
+            config.rotate_if_needed(&log).unwrap();
+        }
+
+        assert!(!log.exists());
+        let read = |n| std::fs::read_to_string(rotated_path(&log, n)).unwrap();
+        assert_eq!("third", read(1));
+        assert_eq!("second", read(2));
+        assert!(!rotated_path(&log, 3).exists());
+    }
+
+    #[test]
+    fn rotator_counts_rotations() {
+        let dir = tempfile::tempdir().unwrap();
+        let log = dir.path().join("hello_stdout.txt");
+        let rotator = LogRotator::new(LogRotationConfig {
+            max_size: Some(4),
+            max_age: None,
+            retention: 1,
+        });
+
+        assert_eq!(0, rotator.rotate_if_needed(&log));
+        std::fs::write(&log, "hi").unwrap();
+        assert_eq!(0, rotator.rotate_if_needed(&log));
+        std::fs::write(&log, "hello").unwrap();
+        assert_eq!(1, rotator.rotate_if_needed(&log));
+        assert!(!log.exists());
+        assert_eq!(1, rotator.rotate_if_needed(&log));
+    }
+
+    #[test]
+    fn small_files_are_not_rotated() {
+        let dir = tempfile::tempdir().unwrap();
+        let log = dir.path().join("hello_stdout.txt");
+        std::fs::write(&log, "hi").unwrap();
+        let config = LogRotationConfig {
+            max_size: Some(1024),
+            max_age: None,
+            retention: 1,
+        };
+        config.rotate_if_needed(&log).unwrap();
+        assert!(log.exists());
+    }
+
+    #[test]
+    fn ages_are_parsed() {
+        assert_eq!(Duration::from_secs(90), parse_age("90s").unwrap());
+        assert_eq!(
+            Duration::from_secs(7 * 24 * 60 * 60),
+            parse_age("7d").unwrap()
+        );
+        assert!(parse_age("7").is_err());
+        assert!(parse_age("d").is_err());
+        assert!(parse_age("500ms").is_err());
+        assert!(parse_age(&format!("{}d", u64::MAX)).is_err());
+    }
+}
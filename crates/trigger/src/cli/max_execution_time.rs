@@ -0,0 +1,27 @@
+use std::time::Duration;
+
+use spin_core::async_trait;
+use spin_factors::RuntimeFactors;
+use spin_factors_executor::{ExecutorHooks, FactorsInstanceBuilder};
+
+/// An [`ExecutorHooks`] that limits the time an instance may spend executing
+/// guest code, excluding time spent waiting on the host.
+pub struct MaxExecutionTimeHook {
+    max_execution_time: Duration,
+}
+
+impl MaxExecutionTimeHook {
+    pub fn new(max_execution_time: Duration) -> Self {
+        Self { max_execution_time }
+    }
+}
+
+#[async_trait]
+impl<F: RuntimeFactors, U> ExecutorHooks<F, U> for MaxExecutionTimeHook {
+    fn prepare_instance(&self, builder: &mut FactorsInstanceBuilder<F, U>) -> anyhow::Result<()> {
+        builder
+            .store_builder()
+            .max_execution_time(self.max_execution_time);
+        Ok(())
+    }
+}
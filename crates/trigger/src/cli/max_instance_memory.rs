@@ -1,4 +1,6 @@
-use spin_core::async_trait;
+use std::sync::Arc;
+
+use spin_core::{MemoryBudget, async_trait};
 use spin_factors::RuntimeFactors;
 use spin_factors_executor::{ExecutorHooks, FactorsInstanceBuilder};
 
@@ -24,3 +26,30 @@ impl<F: RuntimeFactors, U> ExecutorHooks<F, U> for MaxInstanceMemoryHook {
         Ok(())
     }
 }
+
+/// An [`ExecutorHooks`] that limits the total memory allocated by all
+/// instances of an app at once.
+///
+/// Instances are refused while the limit is reached, with a
+/// [`MemoryBudgetExhausted`](spin_core::MemoryBudgetExhausted) error; any
+/// instance growing its memory beyond the limit fails.
+pub struct MaxAppMemoryHook {
+    budget: Arc<MemoryBudget>,
+}
+
+impl MaxAppMemoryHook {
+    pub fn new(max_app_memory: usize) -> Self {
+        Self {
+            budget: Arc::new(MemoryBudget::new(max_app_memory as u64)),
+        }
+    }
+}
+
+#[async_trait]
+impl<F: RuntimeFactors, U> ExecutorHooks<F, U> for MaxAppMemoryHook {
+    fn prepare_instance(&self, builder: &mut FactorsInstanceBuilder<F, U>) -> anyhow::Result<()> {
+        self.budget.check_admission()?;
+        builder.store_builder().memory_budget(self.budget.clone());
+        Ok(())
+    }
+}
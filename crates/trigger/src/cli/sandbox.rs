@@ -0,0 +1,76 @@
+/// System calls that a trigger never makes once it has initialized, and which
+/// would let a compromised process run other programs, inspect other
+/// processes, or change the system.
+#[cfg(target_os = "linux")]
+const DENIED_SYSCALLS: &[libc::c_long] = &[
+    libc::SYS_execve,
+    libc::SYS_execveat,
+    libc::SYS_ptrace,
+    libc::SYS_process_vm_readv,
+    libc::SYS_process_vm_writev,
+    libc::SYS_mount,
+    libc::SYS_umount2,
+    libc::SYS_pivot_root,
+    libc::SYS_chroot,
+    libc::SYS_unshare,
+    libc::SYS_setns,
+    libc::SYS_init_module,
+    libc::SYS_finit_module,
+    libc::SYS_delete_module,
+    libc::SYS_kexec_load,
+    libc::SYS_reboot,
+    libc::SYS_swapon,
+    libc::SYS_swapoff,
+    libc::SYS_bpf,
+    libc::SYS_perf_event_open,
+    libc::SYS_keyctl,
+    libc::SYS_add_key,
+    libc::SYS_request_key,
+];
+
+/// Makes the [`DENIED_SYSCALLS`] fail with `EPERM` in every thread of this
+/// process, for the rest of its life.
+///
+/// Filesystem access is restricted separately, by `spin up`, as it starts the
+/// trigger process.
+#[cfg(target_os = "linux")]
+pub fn restrict_syscalls() -> anyhow::Result<()> {
+    use anyhow::Context as _;
+
+    seccompiler::apply_filter_all_threads(&filter()?)
+        .context("failed to apply the host sandbox system call filter")?;
+    tracing::info!("Host sandbox enabled");
+    Ok(())
+}
+
+#[cfg(target_os = "linux")]
+fn filter() -> anyhow::Result<seccompiler::BpfProgram> {
+    use seccompiler::{SeccompAction, SeccompFilter};
+
+    let rules = DENIED_SYSCALLS
+        .iter()
+        .map(|&syscall| (syscall, vec![]))
+        .collect();
+    let filter = SeccompFilter::new(
+        rules,
+        SeccompAction::Allow,
+        SeccompAction::Errno(libc::EPERM as u32),
+        std::env::consts::ARCH.try_into()?,
+    )?;
+    Ok(filter.try_into()?)
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn restrict_syscalls() -> anyhow::Result<()> {
+    anyhow::bail!("host sandboxing is only supported on Linux")
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn denied_syscalls_compile_to_a_filter() {
+        assert!(!filter().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,204 @@
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Context as _;
+use serde::Deserialize;
+use spin_common::ui::quoted_path;
+use spin_core::async_trait;
+use spin_factor_key_value::KeyValueFactor;
+use spin_factor_sqlite::SqliteFactor;
+use spin_factors::RuntimeFactors;
+use spin_factors_executor::ExecutorHooks;
+
+/// Data to populate an app's key-value stores and SQLite databases with before
+/// its triggers start.
+///
+/// Expects a seed file, or the `[seed]` runtime config table, to be in the
+/// format:
+/// ```toml
+/// [key_value.default]
+/// greeting = "hello"
+///
+/// [sqlite.default]
+/// # Run first, in order; relative paths are resolved against the file's directory
+/// scripts = ["schema.sql", "fixtures.sql"]
+/// statements = ["INSERT INTO users (name) VALUES ('alice')"]
+/// ```
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SeedConfig {
+    /// Map of store labels to the key-value pairs to set in them.
+    #[serde(default)]
+    pub key_value: BTreeMap<String, BTreeMap<String, String>>,
+    /// Map of database labels to the SQL to run against them.
+    #[serde(default)]
+    pub sqlite: BTreeMap<String, SqliteSeed>,
+}
+
+/// SQL to run against a SQLite database when seeding.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SqliteSeed {
+    /// Files of SQL statements, run before `statements`.
+    #[serde(default)]
+    pub scripts: Vec<PathBuf>,
+    /// SQL statements.
+    #[serde(default)]
+    pub statements: Vec<String>,
+}
+
+impl SeedConfig {
+    /// Reads a seed file.
+    pub fn from_file(path: &Path) -> anyhow::Result<Self> {
+        let contents = std::fs::read_to_string(path)
+            .with_context(|| format!("failed to read seed file {}", quoted_path(path)))?;
+        let config: Self = toml::from_str(&contents)
+            .with_context(|| format!("failed to parse seed file {}", quoted_path(path)))?;
+        Ok(config.with_base_dir(path.parent().unwrap_or(Path::new("."))))
+    }
+
+    /// Parses a seed from a TOML value, such as the `[seed]` runtime config
+    /// table, resolving script paths relative to `base_dir`.
+    pub fn from_toml(toml: &toml::Value, base_dir: &Path) -> anyhow::Result<Self> {
+        let config: Self = toml.clone().try_into()?;
+        Ok(config.with_base_dir(base_dir))
+    }
+
+    fn with_base_dir(mut self, base_dir: &Path) -> Self {
+        for seed in self.sqlite.values_mut() {
+            for script in &mut seed.scripts {
+                *script = base_dir.join(&*script);
+            }
+        }
+        self
+    }
+
+    async fn apply(
+        &self,
+        kv: Option<&spin_factor_key_value::AppState>,
+        sqlite: Option<&spin_factor_sqlite::AppState>,
+    ) -> anyhow::Result<()> {
+        if !self.key_value.is_empty() {
+            let kv =
+                kv.context("the seed sets key-value pairs but key-value stores are not available")?;
+            for (label, pairs) in &self.key_value {
+                let store = kv.get_store(label).await.with_context(|| {
+                    format!(
+                        "the seed sets key-value pairs in store '{label}', which is not defined"
+                    )
+                })?;
+                for (key, value) in pairs {
+                    store
+                        .set(key, value.as_bytes())
+                        .await
+                        .with_context(|| format!("failed to set '{key}' in store '{label}'"))?;
+                }
+            }
+        }
+
+        if !self.sqlite.is_empty() {
+            let sqlite =
+                sqlite.context("the seed runs SQL but SQLite databases are not available")?;
+            for (label, seed) in &self.sqlite {
+                let database = sqlite
+                    .get_connection(label)
+                    .await
+                    .with_context(|| {
+                        format!(
+                            "the seed runs SQL against database '{label}', which is not defined"
+                        )
+                    })?
+                    .with_context(|| format!("failed to connect to database '{label}'"))?;
+                for script in &seed.scripts {
+                    let sql = std::fs::read_to_string(script).with_context(|| {
+                        format!("failed to read seed script {}", quoted_path(script))
+                    })?;
+                    database.execute_batch(&sql).await.with_context(|| {
+                        format!(
+                            "failed to run seed script {} against database '{label}'",
+                            quoted_path(script)
+                        )
+                    })?;
+                }
+                for statement in &seed.statements {
+                    database.execute_batch(statement).await.with_context(|| {
+                        format!(
+                            "failed to run seed statement against database '{label}': '{statement}'"
+                        )
+                    })?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// An [`ExecutorHooks`] that seeds key-value stores and SQLite databases
+/// before the app's triggers start.
+pub struct SeedHook {
+    seeds: Vec<SeedConfig>,
+}
+
+impl SeedHook {
+    /// Creates a hook which applies the seeds in order.
+    pub fn new(seeds: Vec<SeedConfig>) -> Self {
+        Self { seeds }
+    }
+}
+
+#[async_trait]
+impl<F: RuntimeFactors, U> ExecutorHooks<F, U> for SeedHook {
+    async fn configure_app(
+        &self,
+        configured_app: &spin_factors::ConfiguredApp<F>,
+    ) -> anyhow::Result<()> {
+        let kv = configured_app.app_state::<KeyValueFactor>().ok();
+        let sqlite = configured_app.app_state::<SqliteFactor>().ok();
+        for seed in &self.seeds {
+            seed.apply(kv, sqlite)
+                .await
+                .context("failed to seed app data")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn seed_file_is_parsed() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("seed.toml");
+        std::fs::write(
+            &path,
+            r#"
+            [key_value.default]
+            greeting = "hello"
+
+            [sqlite.default]
+            scripts = ["schema.sql"]
+            statements = ["INSERT INTO t VALUES (1)"]
+            "#,
+        )
+        .unwrap();
+
+        let seed = SeedConfig::from_file(&path).unwrap();
+        assert_eq!("hello", seed.key_value["default"]["greeting"]);
+        let sqlite = &seed.sqlite["default"];
+        assert_eq!(vec![dir.path().join("schema.sql")], sqlite.scripts);
+        assert_eq!(vec!["INSERT INTO t VALUES (1)"], sqlite.statements);
+    }
+
+    #[test]
+    fn unknown_seed_fields_are_rejected() {
+        let toml = toml::toml! {
+            [sqlite.default]
+            script = "schema.sql"
+        };
+        assert!(SeedConfig::from_toml(&toml.into(), Path::new(".")).is_err());
+    }
+}
@@ -0,0 +1,95 @@
+use std::time::{Duration, Instant};
+
+use spin_factors_executor::LoadTimings;
+
+/// Records how long each phase of trigger startup took.
+#[derive(Debug, Default)]
+pub struct StartupTimings {
+    phases: Vec<(String, Duration)>,
+}
+
+impl StartupTimings {
+    /// Runs `f`, recording its duration as the given phase.
+    pub fn time<R>(&mut self, phase: &str, f: impl FnOnce() -> R) -> R {
+        let start = Instant::now();
+        let result = f();
+        self.record(phase, start.elapsed());
+        result
+    }
+
+    /// Records the duration of the given phase.
+    pub fn record(&mut self, phase: impl Into<String>, duration: Duration) {
+        let phase = phase.into();
+        spin_telemetry::metrics::histogram!(
+            spin.startup_phase_duration = duration.as_secs_f64(),
+            phase = phase,
+            unit = "s"
+        );
+        self.phases.push((phase, duration));
+    }
+
+    /// Records the phases of loading an app with a [`spin_factors_executor::FactorsExecutor`].
+    pub fn record_load_timings(&mut self, load_timings: &LoadTimings) {
+        self.record("Configure app", load_timings.configure_app);
+        for (component_id, duration) in &load_timings.components {
+            self.record(format!("Load component '{component_id}'"), *duration);
+        }
+    }
+
+    /// The recorded phases, in the order they were recorded.
+    pub fn phases(&self) -> &[(String, Duration)] {
+        &self.phases
+    }
+
+    /// The total time across all recorded phases.
+    pub fn total(&self) -> Duration {
+        self.phases.iter().map(|(_, d)| *d).sum()
+    }
+
+    /// Prints the recorded phases as a table on stderr.
+    pub fn print_summary(&self) {
+        let width = self
+            .phases
+            .iter()
+            .map(|(phase, _)| phase.len())
+            .max()
+            .unwrap_or_default()
+            .max("Total".len());
+        eprintln!("Startup summary:");
+        for (phase, duration) in &self.phases {
+            eprintln!("  {phase:<width$}  {:>10}", format_duration(*duration));
+        }
+        eprintln!(
+            "  {:<width$}  {:>10}",
+            "Total",
+            format_duration(self.total())
+        );
+    }
+}
+
+fn format_duration(duration: Duration) -> String {
+    format!("{:.1}ms", duration.as_secs_f64() * 1000.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn total_sums_phases() {
+        let mut timings = StartupTimings::default();
+        timings.record("a", Duration::from_millis(5));
+        timings.record_load_timings(&LoadTimings {
+            configure_app: Duration::from_millis(10),
+            components: vec![("hello".into(), Duration::from_millis(20))],
+        });
+        assert_eq!(timings.phases().len(), 3);
+        assert_eq!(timings.phases()[2].0, "Load component 'hello'");
+        assert_eq!(timings.total(), Duration::from_millis(35));
+    }
+
+    #[test]
+    fn format_duration_uses_millis() {
+        assert_eq!(format_duration(Duration::from_micros(1500)), "1.5ms");
+    }
+}
@@ -1,6 +1,7 @@
 use std::{
     collections::HashSet,
     path::{Path, PathBuf},
+    sync::Arc,
     task::Poll,
 };
 
@@ -10,8 +11,12 @@ use spin_core::async_trait;
 use spin_factor_wasi::WasiFactor;
 use spin_factors::RuntimeFactors;
 use spin_factors_executor::ExecutorHooks;
+use spin_telemetry::logs::{AppLogForwarder, AppLogStream};
 use tokio::io::AsyncWrite;
 
+use super::LogRotationConfig;
+use super::log_rotation::LogRotator;
+
 pub const STDOUT_LOG_FILE_SUFFIX: &str = "stdout";
 pub const STDERR_LOG_FILE_SUFFIX: &str = "stderr";
 
@@ -43,6 +48,7 @@ pub struct StdioLoggingExecutorHooks {
     follow_components: FollowComponents,
     log_dir: Option<PathBuf>,
     truncate_log: bool,
+    log_rotator: Arc<LogRotator>,
 }
 
 impl StdioLoggingExecutorHooks {
@@ -50,30 +56,43 @@ impl StdioLoggingExecutorHooks {
         follow_components: FollowComponents,
         log_dir: Option<PathBuf>,
         truncate_log: bool,
+        log_rotation: LogRotationConfig,
     ) -> Self {
         Self {
             follow_components,
             log_dir,
             truncate_log,
+            log_rotator: Arc::new(LogRotator::new(log_rotation)),
         }
     }
 
     fn component_stdio_writer(
         &self,
         component_id: &str,
-        log_suffix: &str,
+        app_name: Option<&str>,
+        stream: AppLogStream,
         log_dir: Option<&Path>,
     ) -> Result<ComponentStdioWriter> {
+        let log_suffix = match stream {
+            AppLogStream::Stdout => STDOUT_LOG_FILE_SUFFIX,
+            AppLogStream::Stderr => STDERR_LOG_FILE_SUFFIX,
+        };
+        let log_forwarder = AppLogForwarder::new(component_id, app_name, stream);
         let sanitized_component_id = sanitize_filename::sanitize(component_id);
         let log_path = log_dir
             .map(|log_dir| log_dir.join(format!("{sanitized_component_id}_{log_suffix}.txt",)));
-        let log_path = log_path.as_deref();
 
         let follow = self.follow_components.should_follow(component_id);
         match log_path {
-            Some(log_path) => ComponentStdioWriter::new_forward(component_id, log_path, follow)
-                .with_context(|| format!("Failed to open log file {}", quoted_path(log_path))),
-            None => ComponentStdioWriter::new_inherit(component_id),
+            Some(log_path) => {
+                let rotator = self
+                    .log_rotator
+                    .is_enabled()
+                    .then(|| self.log_rotator.clone());
+                ComponentStdioWriter::new_forward(log_forwarder, log_path.clone(), rotator, follow)
+                    .with_context(|| format!("Failed to open log file {}", quoted_path(&log_path)))
+            }
+            None => ComponentStdioWriter::new_inherit(log_forwarder),
         }
     }
 
@@ -143,27 +162,34 @@ impl<F: RuntimeFactors, U> ExecutorHooks<F, U> for StdioLoggingExecutorHooks {
         builder: &mut spin_factors_executor::FactorsInstanceBuilder<F, U>,
     ) -> anyhow::Result<()> {
         let component_id = builder.app_component().id().to_string();
+        let app_name: Option<String> = builder
+            .app_component()
+            .app
+            .get_metadata(spin_app::APP_NAME_KEY)?;
+        let app_name = app_name.as_deref();
         let Some(wasi_builder) = builder.factor_builder::<WasiFactor>() else {
             return Ok(());
         };
         wasi_builder.stdout_pipe(self.component_stdio_writer(
             &component_id,
-            STDOUT_LOG_FILE_SUFFIX,
+            app_name,
+            AppLogStream::Stdout,
             self.log_dir.as_deref(),
         )?);
         wasi_builder.stderr_pipe(self.component_stdio_writer(
             &component_id,
-            STDERR_LOG_FILE_SUFFIX,
+            app_name,
+            AppLogStream::Stderr,
             self.log_dir.as_deref(),
         )?);
         Ok(())
     }
 }
 
-/// ComponentStdioWriter forwards output to a log file, (optionally) stderr, and (optionally) to a
-/// tracing compatibility layer.
+/// ComponentStdioWriter forwards output to a log file, (optionally) stderr, and (optionally) to OTel
+/// logs and a tracing compatibility layer.
 pub struct ComponentStdioWriter {
-    component_id: String,
+    log_forwarder: AppLogForwarder,
     inner: ComponentStdioWriterInner,
 }
 
@@ -176,9 +202,28 @@ enum ComponentStdioWriterInner {
         async_file: tokio::fs::File,
         state: ComponentStdioWriterState,
         follow: bool,
+        rotation: Option<LogFileRotation>,
     },
 }
 
+/// Rotation of the log file a [`ComponentStdioWriter`] forwards to.
+struct LogFileRotation {
+    log_path: PathBuf,
+    rotator: Arc<LogRotator>,
+    /// The number of times the file had been rotated when it was opened.
+    opened_at: u64,
+}
+
+/// Opens a log file for appending, returning sync and async handles to it.
+fn open_log_file(log_path: &Path) -> std::io::Result<(std::fs::File, tokio::fs::File)> {
+    let sync_file = std::fs::File::options()
+        .create(true)
+        .append(true)
+        .open(log_path)?;
+    let async_file = sync_file.try_clone()?.into();
+    Ok((sync_file, async_file))
+}
+
 #[derive(Debug)]
 enum ComponentStdioWriterState {
     File,
@@ -186,31 +231,54 @@ enum ComponentStdioWriterState {
 }
 
 impl ComponentStdioWriter {
-    fn new_forward(component_id: &str, log_path: &Path, follow: bool) -> anyhow::Result<Self> {
-        let sync_file = std::fs::File::options()
-            .create(true)
-            .append(true)
-            .open(log_path)?;
-
-        let async_file = sync_file
-            .try_clone()
-            .context("could not get async file handle")?
-            .into();
+    fn new_forward(
+        log_forwarder: AppLogForwarder,
+        log_path: PathBuf,
+        rotator: Option<Arc<LogRotator>>,
+        follow: bool,
+    ) -> anyhow::Result<Self> {
+        let rotation = rotator.map(|rotator| LogFileRotation {
+            opened_at: rotator.rotate_if_needed(&log_path),
+            log_path: log_path.clone(),
+            rotator,
+        });
+        let (sync_file, async_file) = open_log_file(&log_path)?;
 
         Ok(Self {
-            component_id: component_id.to_string(),
+            log_forwarder,
             inner: ComponentStdioWriterInner::Forward {
                 sync_file,
                 async_file,
                 state: ComponentStdioWriterState::File,
                 follow,
+                rotation,
             },
         })
     }
 
-    fn new_inherit(component_id: &str) -> anyhow::Result<Self> {
+    /// Rotates the log file if it has grown too big or too old, reopening it
+    /// if it has been rotated since it was opened.
+    fn rotate_if_needed(&mut self) -> std::io::Result<()> {
+        let ComponentStdioWriterInner::Forward {
+            sync_file,
+            async_file,
+            rotation: Some(rotation),
+            ..
+        } = &mut self.inner
+        else {
+            return Ok(());
+        };
+        let rotations = rotation.rotator.rotate_if_needed(&rotation.log_path);
+        if rotations != rotation.opened_at {
+            (*sync_file, *async_file) = open_log_file(&rotation.log_path)?;
+            rotation.opened_at = rotations;
+        }
+        Ok(())
+    }
+
+    fn new_inherit(log_forwarder: AppLogForwarder) -> anyhow::Result<Self> {
         Ok(Self {
-            component_id: component_id.to_string(),
+            log_forwarder,
             inner: ComponentStdioWriterInner::Inherit,
         })
     }
@@ -224,6 +292,17 @@ impl AsyncWrite for ComponentStdioWriter {
     ) -> Poll<std::result::Result<usize, std::io::Error>> {
         let this = self.get_mut();
 
+        // Only between writes, so that a write is never split across files
+        if matches!(
+            this.inner,
+            ComponentStdioWriterInner::Forward {
+                state: ComponentStdioWriterState::File,
+                ..
+            }
+        ) {
+            this.rotate_if_needed()?;
+        }
+
         loop {
             match &mut this.inner {
                 ComponentStdioWriterInner::Inherit => {
@@ -234,6 +313,7 @@ impl AsyncWrite for ComponentStdioWriter {
                         Ok(w) => w,
                         Err(e) => return Poll::Ready(Err(e)),
                     };
+                    this.log_forwarder.write(&buf[..written]);
                     return Poll::Ready(Ok(written));
                 }
                 ComponentStdioWriterInner::Forward {
@@ -249,6 +329,7 @@ impl AsyncWrite for ComponentStdioWriter {
                             Ok(w) => w,
                             Err(e) => return Poll::Ready(Err(e)),
                         };
+                        this.log_forwarder.write(&buf[..written]);
                         if *follow {
                             *state = ComponentStdioWriterState::Follow(0..written);
                         } else {
@@ -324,17 +405,18 @@ impl AsyncWrite for ComponentStdioWriter {
 
 impl std::io::Write for ComponentStdioWriter {
     fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
-        spin_telemetry::logs::handle_app_log(buf, &self.component_id);
-
+        self.rotate_if_needed()?;
         match &mut self.inner {
             ComponentStdioWriterInner::Inherit => {
                 std::io::stderr().write_all(buf)?;
+                self.log_forwarder.write(buf);
                 Ok(buf.len())
             }
             ComponentStdioWriterInner::Forward {
                 sync_file, follow, ..
             } => {
                 let written = sync_file.write(buf)?;
+                self.log_forwarder.write(&buf[..written]);
                 if *follow {
                     std::io::stderr().write_all(&buf[..written])?;
                 }
@@ -366,3 +448,43 @@ fn bullet_list<S: std::fmt::Display>(items: impl IntoIterator<Item = S>) -> Stri
         .collect::<Vec<_>>()
         .join("\n")
 }
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write as _;
+
+    use super::*;
+
+    #[test]
+    fn long_lived_writer_rotates_on_write() {
+        let dir = tempfile::tempdir().unwrap();
+        let log = dir.path().join("hello_stdout.txt");
+        let rotator = Arc::new(LogRotator::new(LogRotationConfig {
+            max_size: Some(8),
+            max_age: None,
+            retention: 1,
+        }));
+        let writer = || {
+            let forwarder = AppLogForwarder::new("hello", None, AppLogStream::Stdout);
+            ComponentStdioWriter::new_forward(forwarder, log.clone(), Some(rotator.clone()), false)
+                .unwrap()
+        };
+        let mut first = writer();
+        let mut second = writer();
+
+        first.write_all(b"hello world\n").unwrap();
+        // Rotated by the second writer, which then writes to a fresh file
+        second.write_all(b"hi\n").unwrap();
+        assert_eq!(
+            "hello world\n",
+            std::fs::read_to_string(dir.path().join("hello_stdout.txt.1")).unwrap()
+        );
+        // The first writer follows it to the fresh file rather than appending to the rotated one
+        first.write_all(b"x\n").unwrap();
+        assert_eq!(
+            "hello world\n",
+            std::fs::read_to_string(dir.path().join("hello_stdout.txt.1")).unwrap()
+        );
+        assert_eq!("hi\nx\n", std::fs::read_to_string(&log).unwrap());
+    }
+}
@@ -1,5 +1,6 @@
 pub mod cli;
 pub mod loader;
+pub mod shutdown;
 
 use heck::ToTitleCase;
 use std::future::Future;
@@ -1,7 +1,11 @@
+use std::path::{Path, PathBuf};
+
+use spin_app::locked::{LockedComponent, LockedComponentDependency, LockedComponentSource};
 use spin_common::{ui::quoted_path, url::parse_file_url};
-use spin_compose::ComponentSourceLoaderFs;
 use spin_core::{Component, async_trait, wasmtime};
 use spin_factors::{AppComponent, RuntimeFactors};
+use spin_factors_executor::SourceChecker;
+use spin_serde::DependencyName;
 use wasmtime::error::Context as _;
 
 #[derive(Default)]
@@ -50,12 +54,12 @@ impl ComponentLoader {
     fn load_precompiled_component(
         &self,
         engine: &wasmtime::Engine,
-        path: &std::path::Path,
+        bytes: &[u8],
     ) -> wasmtime::Result<Component> {
         assert!(self.aot_compilation_enabled);
-        match wasmtime::Engine::detect_precompiled_file(path)? {
+        match wasmtime::Engine::detect_precompiled(bytes) {
             Some(wasmtime::Precompiled::Component) => unsafe {
-                Component::deserialize_file(engine, path)
+                Component::deserialize(engine, bytes)
             },
             Some(wasmtime::Precompiled::Module) => {
                 wasmtime::bail!("expected AOT compiled component but found module");
@@ -73,6 +77,7 @@ impl<T: RuntimeFactors, U> spin_factors_executor::ComponentLoader<T, U> for Comp
         &self,
         engine: &wasmtime::Engine,
         component: &AppComponent,
+        sources: &SourceChecker<'_, T, U>,
     ) -> anyhow::Result<Component> {
         let source = component
             .source()
@@ -84,13 +89,16 @@ impl<T: RuntimeFactors, U> spin_factors_executor::ComponentLoader<T, U> for Comp
 
         #[cfg(feature = "unsafe-aot-compilation")]
         if self.aot_compilation_enabled {
+            let (_, bytes) = read_source(component.source()).await?;
+            sources.check(component, None, &bytes)?;
             let component = self
-                .load_precompiled_component(engine, &path)
+                .load_precompiled_component(engine, &bytes)
                 .with_context(|| format!("error deserializing component from {path:?}"))?;
             return Ok(component);
         }
 
-        let composed = spin_compose::compose(&ComponentSourceLoaderFs, component.locked)
+        let source_loader = CheckedSourceLoader { component, sources };
+        let composed = spin_compose::compose(&source_loader, component.locked)
             .await
             .with_context(|| {
                 format!(
@@ -104,3 +112,57 @@ impl<T: RuntimeFactors, U> spin_factors_executor::ComponentLoader<T, U> for Comp
         Ok(component)
     }
 }
+
+/// A [`spin_compose::ComponentSourceLoader`] that loads sources from the
+/// filesystem, checking each with the [`SourceChecker`] as it is read so that
+/// what is composed is exactly what was checked.
+struct CheckedSourceLoader<'a, T: RuntimeFactors, U: 'static> {
+    component: &'a AppComponent<'a>,
+    sources: &'a SourceChecker<'a, T, U>,
+}
+
+#[async_trait]
+impl<T: RuntimeFactors, U> spin_compose::ComponentSourceLoader for CheckedSourceLoader<'_, T, U> {
+    type Component = LockedComponent;
+    type Dependency = LockedComponentDependency;
+
+    async fn load_component_source(&self, source: &Self::Component) -> anyhow::Result<Vec<u8>> {
+        let (path, bytes) = read_source(&source.source).await?;
+        self.sources.check(self.component, None, &bytes)?;
+        componentize(&path, &bytes)
+    }
+
+    async fn load_dependency_source(
+        &self,
+        name: &DependencyName,
+        source: &Self::Dependency,
+    ) -> anyhow::Result<Vec<u8>> {
+        let (path, bytes) = read_source(&source.source).await?;
+        self.sources
+            .check(self.component, Some(&name.to_string()), &bytes)?;
+        componentize(&path, &bytes)
+    }
+}
+
+/// Reads a source from disk, returning its path and content.
+async fn read_source(source: &LockedComponentSource) -> anyhow::Result<(PathBuf, Vec<u8>)> {
+    let source = source
+        .content
+        .source
+        .as_ref()
+        .context("LockedComponentSource missing source field")?;
+    let path = parse_file_url(source)?;
+    let bytes = tokio::fs::read(&path).await.with_context(|| {
+        format!(
+            "failed to read component source from disk at path {}",
+            quoted_path(&path)
+        )
+    })?;
+    Ok((path, bytes))
+}
+
+fn componentize(path: &Path, bytes: &[u8]) -> anyhow::Result<Vec<u8>> {
+    let component = spin_componentize::componentize_if_necessary(bytes)
+        .map_err(|err| err.context(format!("failed to componentize {}", quoted_path(path))))?;
+    Ok(component.into())
+}
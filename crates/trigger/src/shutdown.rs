@@ -0,0 +1,210 @@
+//! Graceful shutdown, sequenced the way Kubernetes expects.
+//!
+//! When a pod is deleted, Kubernetes sends SIGTERM at the same time as it
+//! starts removing the pod from its services' endpoints, and load balancers
+//! may keep sending it requests for a few seconds. So on SIGTERM the trigger
+//! first fails readiness, then keeps serving for a pre-stop delay, and only
+//! then drains: open connections finish the requests they are handling and
+//! close, until none are left or the drain timeout passes.
+
+use std::{
+    sync::{
+        LazyLock,
+        atomic::{AtomicUsize, Ordering},
+    },
+    time::Duration,
+};
+
+use tokio::sync::watch;
+
+/// How often draining checks whether every connection has closed.
+const DRAIN_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// The shutdown state of this process.
+static STATE: LazyLock<ShutdownState> = LazyLock::new(ShutdownState::default);
+
+/// Returns the shutdown state of this process, which triggers consult to
+/// report readiness and to close connections once draining starts.
+pub fn state() -> &'static ShutdownState {
+    &STATE
+}
+
+/// How far shutdown has progressed.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Phase {
+    /// Serving as normal.
+    #[default]
+    Running,
+    /// Shutdown has been requested; not ready, but still serving as normal.
+    NotReady,
+    /// Open connections are finishing their requests and closing.
+    Draining,
+}
+
+/// Tracks the shutdown phase and the connections still open.
+#[derive(Debug)]
+pub struct ShutdownState {
+    phase: watch::Sender<Phase>,
+    connections: AtomicUsize,
+}
+
+impl Default for ShutdownState {
+    fn default() -> Self {
+        Self {
+            phase: watch::Sender::new(Phase::Running),
+            connections: AtomicUsize::new(0),
+        }
+    }
+}
+
+impl ShutdownState {
+    /// The current shutdown phase.
+    pub fn phase(&self) -> Phase {
+        *self.phase.borrow()
+    }
+
+    /// Whether the process should receive new traffic.
+    pub fn is_ready(&self) -> bool {
+        self.phase() == Phase::Running
+    }
+
+    /// Moves shutdown on to the given phase, if it is not there already.
+    pub fn advance(&self, phase: Phase) {
+        self.phase.send_if_modified(|current| {
+            let advanced = phase > *current;
+            if advanced {
+                *current = phase;
+            }
+            advanced
+        });
+    }
+
+    /// Resolves once draining starts.
+    pub async fn draining(&self) {
+        let mut phase = self.phase.subscribe();
+        // The sender lives as long as `self`, so this cannot fail.
+        _ = phase.wait_for(|phase| *phase == Phase::Draining).await;
+    }
+
+    /// Counts an open connection until the returned guard is dropped.
+    pub fn track_connection(&self) -> ConnectionGuard<'_> {
+        self.connections.fetch_add(1, Ordering::SeqCst);
+        ConnectionGuard { state: self }
+    }
+
+    /// The number of open connections.
+    pub fn open_connections(&self) -> usize {
+        self.connections.load(Ordering::SeqCst)
+    }
+
+    /// Waits for every connection to close, returning `false` if some are
+    /// still open after `timeout`.
+    pub async fn wait_for_connections(&self, timeout: Duration) -> bool {
+        let closed = async {
+            while self.open_connections() > 0 {
+                tokio::time::sleep(DRAIN_POLL_INTERVAL).await;
+            }
+        };
+        tokio::time::timeout(timeout, closed).await.is_ok()
+    }
+}
+
+/// Keeps a connection counted as open.
+pub struct ConnectionGuard<'a> {
+    state: &'a ShutdownState,
+}
+
+impl Drop for ConnectionGuard<'_> {
+    fn drop(&mut self) {
+        self.state.connections.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// How to shut down once asked to.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ShutdownConfig {
+    /// How long to keep serving, while not ready, before draining.
+    pub pre_stop_delay: Duration,
+    /// How long to wait for open connections to close.
+    pub drain_timeout: Duration,
+}
+
+impl ShutdownConfig {
+    /// Runs the shutdown sequence against the given state, returning once it
+    /// is time to exit.
+    pub async fn run(&self, state: &ShutdownState) {
+        state.advance(Phase::NotReady);
+        if !self.pre_stop_delay.is_zero() {
+            tracing::info!(
+                "Shutdown requested: serving for {:?} before draining",
+                self.pre_stop_delay
+            );
+            tokio::time::sleep(self.pre_stop_delay).await;
+        }
+        state.advance(Phase::Draining);
+        if self.drain_timeout.is_zero() {
+            return;
+        }
+        let open = state.open_connections();
+        if open > 0 {
+            tracing::info!("Draining {open} open connection(s)");
+            if !state.wait_for_connections(self.drain_timeout).await {
+                tracing::warn!(
+                    "{} connection(s) still open after {:?}; exiting anyway",
+                    state.open_connections(),
+                    self.drain_timeout
+                );
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test(start_paused = true)]
+    async fn sequence_fails_readiness_then_drains() {
+        let state = ShutdownState::default();
+        let config = ShutdownConfig {
+            pre_stop_delay: Duration::from_secs(5),
+            drain_timeout: Duration::from_secs(10),
+        };
+        let connection = state.track_connection();
+        assert!(state.is_ready());
+
+        let sequence = config.run(&state);
+        tokio::pin!(sequence);
+        tokio::select! {
+            () = &mut sequence => panic!("shutdown should wait for the pre-stop delay"),
+            () = tokio::time::sleep(Duration::from_secs(4)) => {}
+        }
+        assert_eq!(Phase::NotReady, state.phase());
+
+        tokio::select! {
+            () = &mut sequence => panic!("shutdown should wait for open connections"),
+            () = tokio::time::sleep(Duration::from_secs(2)) => {}
+        }
+        assert_eq!(Phase::Draining, state.phase());
+        state.draining().await;
+
+        drop(connection);
+        sequence.await;
+        assert_eq!(0, state.open_connections());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn draining_gives_up_after_timeout() {
+        let state = ShutdownState::default();
+        let _connection = state.track_connection();
+        assert!(!state.wait_for_connections(Duration::from_secs(1)).await);
+    }
+
+    #[test]
+    fn phases_only_advance() {
+        let state = ShutdownState::default();
+        state.advance(Phase::Draining);
+        state.advance(Phase::NotReady);
+        assert_eq!(Phase::Draining, state.phase());
+    }
+}
@@ -15,10 +15,19 @@ wasmtime::component::bindgen!({
         include spin:up/platform@4.0.0;
         include wasi:keyvalue/imports@0.2.0-draft2;
         export spin:redis/inbound-redis@3.0.0;
+        export spin:actors/actor@3.0.0;
+        export spin:jobs/handler@3.0.0;
+        export spin:workflows/orchestrator@3.0.0;
+        export spin:workflows/activity@3.0.0;
     }
     "#,
     path: "../../wit",
     imports: { default: async | trappable },
+    with: {
+        "wasi:io/error@0.2.0": wasmtime_wasi::p2::bindings::io::error,
+        "wasi:io/poll@0.2.0": wasmtime_wasi::p2::bindings::io::poll,
+        "wasi:io/streams@0.2.0": wasmtime_wasi::p2::bindings::io::streams,
+    },
     exports: { default: async },
     // The following is a roundabout way of saying "the host implementations for these interfaces don't trap"
     trappable_error_type: {
@@ -35,6 +44,10 @@ wasmtime::component::bindgen!({
         "fermyon:spin/sqlite@2.0.0.error" => v2::sqlite::Error,
         "fermyon:spin/sqlite.error" => v1::sqlite::Error,
         "fermyon:spin/variables@2.0.0.error" => v2::variables::Error,
+        "spin:actors/client@3.0.0.error" => spin::actors::client::Error,
+        "spin:crypto/signatures@3.0.0.error" => spin::crypto::signatures::Error,
+        "spin:email/smtp@3.0.0.error" => spin::email::smtp::Error,
+        "spin:jobs/queue@3.0.0.error" => spin::jobs::queue::Error,
         "spin:key-value/key-value@3.0.0.error" => spin::key_value::key_value::Error,
         "spin:mqtt/mqtt@3.0.0.error" => spin::mqtt::mqtt::Error,
         "spin:postgres/postgres@3.0.0.error" => spin::postgres3_0_0::postgres::Error,
@@ -42,6 +55,7 @@ wasmtime::component::bindgen!({
         "spin:redis/redis@3.0.0.error" => spin::redis::redis::Error,
         "spin:sqlite/sqlite@3.1.0.error" => spin::sqlite3_1_0::sqlite::Error,
         "spin:variables/variables@3.0.0.error" => spin::variables::variables::Error,
+        "spin:workflows/client@3.0.0.error" => spin::workflows::client::Error,
         "wasi:config/store@0.2.0-draft-2024-09-27.error" => wasi::config::store::Error,
         "wasi:keyvalue/store.error" => wasi::keyvalue::store::Error,
         "wasi:keyvalue/atomics.cas-error" => wasi::keyvalue::atomics::CasError,
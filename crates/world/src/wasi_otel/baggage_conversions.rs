@@ -0,0 +1,37 @@
+use crate::spin;
+use opentelemetry::baggage::Baggage;
+
+/// Converts OTel baggage to a list of WASI baggage entries.
+pub fn baggage_entries(baggage: &Baggage) -> Vec<spin::otel::baggage::Entry> {
+    baggage
+        .iter()
+        .map(|(key, (value, metadata))| spin::otel::baggage::Entry {
+            key: key.to_string(),
+            value: value.to_string(),
+            metadata: (!metadata.as_str().is_empty()).then(|| metadata.as_str().to_owned()),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn converts_baggage_entries() {
+        let mut baggage = Baggage::new();
+        baggage.insert("tenant", "acme");
+        baggage.insert_with_metadata("region", "eu", "sensitive");
+
+        let mut entries = baggage_entries(&baggage);
+        entries.sort_by(|a, b| a.key.cmp(&b.key));
+
+        assert_eq!(2, entries.len());
+        assert_eq!("region", entries[0].key);
+        assert_eq!("eu", entries[0].value);
+        assert_eq!(Some("sensitive"), entries[0].metadata.as_deref());
+        assert_eq!("tenant", entries[1].key);
+        assert_eq!("acme", entries[1].value);
+        assert_eq!(None, entries[1].metadata);
+    }
+}
@@ -1,7 +1,7 @@
 use crate::wasi::{self, clocks0_2_0::wall_clock};
 use serde::{
     Deserialize,
-    de::{self, SeqAccess, Visitor},
+    de::{self, MapAccess, SeqAccess, Visitor},
 };
 use std::{
     fmt,
@@ -10,20 +10,24 @@ use std::{
 
 impl From<wasi::otel::types::KeyValue> for opentelemetry::KeyValue {
     fn from(kv: wasi::otel::types::KeyValue) -> Self {
-        let owned: OwnedValue = from_json(&kv.value);
-        let value: opentelemetry::Value = owned.into();
+        let value: opentelemetry::Value = owned_value(&kv.value).into();
         opentelemetry::KeyValue::new(kv.key, value)
     }
 }
 
 impl From<&wasi::otel::types::KeyValue> for opentelemetry::KeyValue {
     fn from(kv: &wasi::otel::types::KeyValue) -> Self {
-        let owned: OwnedValue = from_json(&kv.value);
-        let value: opentelemetry::Value = owned.into();
+        let value: opentelemetry::Value = owned_value(&kv.value).into();
         opentelemetry::KeyValue::new(kv.key.to_owned(), value)
     }
 }
 
+/// Parses an attribute value from its JSON encoding, falling back to the raw
+/// text as a string if the guest sent malformed JSON.
+fn owned_value(json: &str) -> OwnedValue {
+    from_json(json).unwrap_or_else(|_| OwnedValue::String(json.to_owned()))
+}
+
 impl From<OwnedValue> for opentelemetry::Value {
     fn from(value: OwnedValue) -> Self {
         match value {
@@ -45,6 +49,15 @@ impl From<OwnedValue> for opentelemetry::Value {
     }
 }
 
+/// The prefix of guest attribute values encoding byte arrays, as a data URI of their base64
+/// encoding.
+pub(crate) const BYTES_DATA_URI_PREFIX: &str = "data:application/octet-stream;base64,";
+
+/// An attribute value as the OTel SDK can represent it.
+///
+/// The SDK's attribute values have no maps, byte arrays or heterogeneous arrays, so those are
+/// represented as OTel specifies for such protocols: as JSON strings, with byte arrays as their
+/// base64 encoding.
 enum OwnedValue {
     Bool(bool),
     I64(i64),
@@ -60,6 +73,50 @@ enum OwnedArray {
     String(Vec<String>),
 }
 
+impl OwnedValue {
+    /// Converts array elements to an array of their type, or to JSON if they are not all of the
+    /// same primitive type.
+    fn from_elements(elements: Vec<serde_json::Value>) -> Self {
+        use serde_json::Value as Json;
+        // An empty array is a bool array, as good as any other
+        if let Some(bools) = elements.iter().map(Json::as_bool).collect::<Option<_>>() {
+            return OwnedValue::Array(OwnedArray::Bool(bools));
+        }
+        if let Some(ints) = elements.iter().map(Json::as_i64).collect::<Option<_>>() {
+            return OwnedValue::Array(OwnedArray::I64(ints));
+        }
+        if let Some(nums) = elements.iter().map(Json::as_f64).collect::<Option<_>>() {
+            return OwnedValue::Array(OwnedArray::F64(nums));
+        }
+        if let Some(strings) = elements
+            .iter()
+            .map(|v| v.as_str().map(str::to_owned))
+            .collect::<Option<_>>()
+        {
+            return OwnedValue::Array(OwnedArray::String(strings));
+        }
+        OwnedValue::String(to_json(Json::Array(elements)))
+    }
+}
+
+/// Encodes a structured value as JSON, with byte arrays as their base64 encoding.
+fn to_json(mut value: serde_json::Value) -> String {
+    fn decode_bytes(value: &mut serde_json::Value) {
+        match value {
+            serde_json::Value::String(s) => {
+                if let Some(base64) = s.strip_prefix(BYTES_DATA_URI_PREFIX) {
+                    *s = base64.to_owned();
+                }
+            }
+            serde_json::Value::Array(elements) => elements.iter_mut().for_each(decode_bytes),
+            serde_json::Value::Object(entries) => entries.values_mut().for_each(decode_bytes),
+            _ => {}
+        }
+    }
+    decode_bytes(&mut value);
+    value.to_string()
+}
+
 impl<'de> Deserialize<'de> for OwnedValue {
     fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
     where
@@ -71,7 +128,7 @@ impl<'de> Deserialize<'de> for OwnedValue {
             type Value = OwnedValue;
 
             fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
-                formatter.write_str("a boolean, number, string, or array")
+                formatter.write_str("a boolean, number, string, array, or map")
             }
 
             fn visit_bool<E>(self, value: bool) -> Result<Self::Value, E>
@@ -109,6 +166,7 @@ impl<'de> Deserialize<'de> for OwnedValue {
             where
                 E: de::Error,
             {
+                let value = value.strip_prefix(BYTES_DATA_URI_PREFIX).unwrap_or(value);
                 Ok(OwnedValue::String(value.to_owned()))
             }
 
@@ -117,67 +175,19 @@ impl<'de> Deserialize<'de> for OwnedValue {
                 A: SeqAccess<'de>,
             {
                 let mut elements = Vec::new();
-
-                // Determine the type by looking at the first element
-                if let Some(first) = seq.next_element::<serde_json::Value>()? {
-                    elements.push(first);
-
-                    // Collect remaining elements
-                    while let Some(elem) = seq.next_element::<serde_json::Value>()? {
-                        elements.push(elem);
-                    }
-
-                    if elements.is_empty() {
-                        return Ok(OwnedValue::Array(OwnedArray::Bool(vec![])));
-                    }
-
-                    match &elements[0] {
-                        serde_json::Value::Bool(_) => {
-                            let bools: Result<Vec<bool>, _> = elements
-                                .iter()
-                                .map(|v| {
-                                    v.as_bool()
-                                        .ok_or_else(|| de::Error::custom("Mixed types in array"))
-                                })
-                                .collect();
-                            Ok(OwnedValue::Array(OwnedArray::Bool(bools?)))
-                        }
-                        serde_json::Value::Number(n) if n.is_i64() => {
-                            let ints: Result<Vec<i64>, _> = elements
-                                .iter()
-                                .map(|v| {
-                                    v.as_i64()
-                                        .ok_or_else(|| de::Error::custom("Mixed types in array"))
-                                })
-                                .collect();
-                            Ok(OwnedValue::Array(OwnedArray::I64(ints?)))
-                        }
-                        serde_json::Value::Number(n) if n.is_f64() => {
-                            let nums: Result<Vec<f64>, _> = elements
-                                .iter()
-                                .map(|v| {
-                                    v.as_f64()
-                                        .ok_or_else(|| de::Error::custom("Mixed types in array"))
-                                })
-                                .collect();
-                            Ok(OwnedValue::Array(OwnedArray::F64(nums?)))
-                        }
-                        serde_json::Value::String(_) => {
-                            let strings: Result<Vec<String>, _> = elements
-                                .iter()
-                                .map(|v| match v.as_str() {
-                                    Some(s) => Ok(s.to_string()),
-                                    None => Err(de::Error::custom("Mixed types in array")),
-                                })
-                                .collect();
-                            Ok(OwnedValue::Array(OwnedArray::String(strings?)))
-                        }
-                        _ => Err(de::Error::custom("Unsupported array element type")),
-                    }
-                } else {
-                    // Empty array using bool as the default
-                    Ok(OwnedValue::Array(OwnedArray::Bool(vec![])))
+                while let Some(elem) = seq.next_element::<serde_json::Value>()? {
+                    elements.push(elem);
                 }
+                Ok(OwnedValue::from_elements(elements))
+            }
+
+            fn visit_map<A>(self, map: A) -> Result<Self::Value, A::Error>
+            where
+                A: MapAccess<'de>,
+            {
+                let value =
+                    serde_json::Value::deserialize(de::value::MapAccessDeserializer::new(map))?;
+                Ok(OwnedValue::String(to_json(value)))
             }
         }
 
@@ -186,17 +196,8 @@ impl<'de> Deserialize<'de> for OwnedValue {
 }
 
 // Deserialize a JSON string to a Serde-serializable struct
-pub(crate) fn from_json<T: for<'de> Deserialize<'de>>(json: &str) -> T {
-    serde_json::from_str(json).unwrap_or_else(|e| {
-        panic!(
-            "Failed to deserialize JSON to {}\
-             \n Input: {}\
-             \n Error: {}",
-            std::any::type_name::<T>(),
-            json,
-            e
-        )
-    })
+pub(crate) fn from_json<T: for<'de> Deserialize<'de>>(json: &str) -> serde_json::Result<T> {
+    serde_json::from_str(json)
 }
 
 impl From<wasi::otel::types::InstrumentationScope> for opentelemetry::InstrumentationScope {
@@ -229,7 +230,7 @@ mod tests {
 
     macro_rules! compare_json_and_literal {
         ($json:expr, $literal:expr) => {{
-            let left: serde_json::Value = from_json($json);
+            let left: serde_json::Value = from_json($json).unwrap();
             let right: serde_json::Value = serde_json::json!($literal);
             assert_eq!(left, right);
         }};
@@ -251,4 +252,33 @@ mod tests {
             vec!["Hello, world!", "Goodnight, moon."]
         );
     }
+
+    fn attribute_value(json: &str) -> opentelemetry::Value {
+        let kv: opentelemetry::KeyValue = wasi::otel::types::KeyValue {
+            key: "key".into(),
+            value: json.into(),
+        }
+        .into();
+        kv.value
+    }
+
+    #[test]
+    fn converts_structured_values_to_json() {
+        assert_eq!(
+            opentelemetry::Value::from(r#"{"id":7,"tags":["a","b"]}"#),
+            attribute_value(r#"{"id":7,"tags":["a","b"]}"#)
+        );
+        assert_eq!(
+            opentelemetry::Value::from(r#"[1,"one",{"raw":"aGk="}]"#),
+            attribute_value(r#"[1,"one",{"raw":"data:application/octet-stream;base64,aGk="}]"#)
+        );
+        assert_eq!(
+            opentelemetry::Value::from("aGk="),
+            attribute_value(r#""data:application/octet-stream;base64,aGk=""#)
+        );
+        assert_eq!(
+            opentelemetry::Value::Array(opentelemetry::Array::F64(vec![1.0, 2.5])),
+            attribute_value("[1,2.5]")
+        );
+    }
 }
@@ -1,4 +1,4 @@
-use super::from_json;
+use super::{BYTES_DATA_URI_PREFIX, from_json};
 use crate::wasi;
 use base64::Engine;
 use opentelemetry::logs::{LogRecord, Logger, LoggerProvider};
@@ -29,8 +29,7 @@ pub fn parse_wasi_log_record(
     // Parse LogRecord
     let mut otel_log_record = logger.create_log_record();
     if let Some(body) = wasi_log_record.body {
-        let owned: OwnedAnyValue = from_json(&body);
-        let otel_body: opentelemetry::logs::AnyValue = owned.into();
+        let otel_body: opentelemetry::logs::AnyValue = owned_any_value(&body).into();
         otel_log_record.set_body(otel_body);
     }
     if let Some(name) = wasi_log_record.event_name {
@@ -39,8 +38,8 @@ pub fn parse_wasi_log_record(
     if let Some(timestamp) = wasi_log_record.observed_timestamp {
         otel_log_record.set_observed_timestamp(timestamp.into());
     }
-    if let Some(number) = wasi_log_record.severity_number {
-        otel_log_record.set_severity_number(severity_from_u8(number));
+    if let Some(severity) = wasi_log_record.severity_number.and_then(severity_from_u8) {
+        otel_log_record.set_severity_number(severity);
     }
     if let Some(text) = wasi_log_record.severity_text {
         otel_log_record.set_severity_text(Box::leak(text.into_boxed_str()));
@@ -48,15 +47,24 @@ pub fn parse_wasi_log_record(
     if let Some(timestamp) = wasi_log_record.timestamp {
         otel_log_record.set_timestamp(timestamp.into());
     }
+    for attribute in wasi_log_record.attributes.into_iter().flatten() {
+        let value: opentelemetry::logs::AnyValue = owned_any_value(&attribute.value).into();
+        otel_log_record.add_attribute(attribute.key, value);
+    }
     if let Some(trace_id) = wasi_log_record.trace_id
         && let Some(span_id) = wasi_log_record.span_id
     {
-        // Both the span ID and trace ID are required values to set trace context.
-        otel_log_record.set_trace_context(
-            opentelemetry::TraceId::from_hex(&trace_id).expect("Failed to parse trace ID"),
-            opentelemetry::SpanId::from_hex(&span_id).expect("Failed to parse span ID"),
-            wasi_log_record.trace_flags.map(Into::into),
-        );
+        // Both the span ID and trace ID are required values to set trace context. If the
+        // guest sent malformed IDs, the record is still exported, without the trace context.
+        if let Ok(trace_id) = opentelemetry::TraceId::from_hex(&trace_id)
+            && let Ok(span_id) = opentelemetry::SpanId::from_hex(&span_id)
+        {
+            otel_log_record.set_trace_context(
+                trace_id,
+                span_id,
+                wasi_log_record.trace_flags.map(Into::into),
+            );
+        }
     }
 
     // Parse InstrumentationScope
@@ -85,15 +93,19 @@ pub fn parse_wasi_log_record(
     (otel_log_record, otel_scope)
 }
 
-fn severity_from_u8(n: u8) -> opentelemetry::logs::Severity {
+/// Parses a log body or attribute value from its JSON encoding, falling back to
+/// the raw text as a string if the guest sent malformed JSON.
+fn owned_any_value(json: &str) -> OwnedAnyValue {
+    from_json(json).unwrap_or_else(|_| OwnedAnyValue::String(json.to_owned()))
+}
+
+fn severity_from_u8(n: u8) -> Option<opentelemetry::logs::Severity> {
     use opentelemetry::logs::Severity::*;
-    match n {
-        0 => {
-            // In the spec, there is a brief mention of SeverityNumber=0 used to represent an unspecified severity;
-            // however, this version of OpenTelemetry Rust doesn't implement it.
-            // See https://opentelemetry.io/docs/specs/otel/logs/data-model/#comparing-severity
-            unimplemented!()
-        }
+    let severity = match n {
+        // In the spec, SeverityNumber=0 represents an unspecified severity; this version
+        // of OpenTelemetry Rust has no variant for it, so leave the severity unset.
+        // See https://opentelemetry.io/docs/specs/otel/logs/data-model/#comparing-severity
+        0 => return None,
         1 => Trace,
         2 => Trace2,
         3 => Trace3,
@@ -118,8 +130,10 @@ fn severity_from_u8(n: u8) -> opentelemetry::logs::Severity {
         22 => Fatal2,
         23 => Fatal3,
         24 => Fatal4,
-        num => panic!("{num} is not a valid severity number"),
-    }
+        // Numbers outside the data model's range are treated as unspecified too.
+        _ => return None,
+    };
+    Some(severity)
 }
 
 #[derive(Clone)]
@@ -182,6 +196,16 @@ impl<'de> Deserialize<'de> for OwnedAnyValue {
                 Ok(OwnedAnyValue::Int(value))
             }
 
+            fn visit_u64<E>(self, value: u64) -> Result<Self::Value, E>
+            where
+                E: de::Error,
+            {
+                // JSON parsers hand non-negative integers to `visit_u64`.
+                Ok(i64::try_from(value)
+                    .map(OwnedAnyValue::Int)
+                    .unwrap_or(OwnedAnyValue::Double(value as f64)))
+            }
+
             fn visit_f64<E>(self, value: f64) -> Result<Self::Value, E>
             where
                 E: de::Error,
@@ -193,8 +217,7 @@ impl<'de> Deserialize<'de> for OwnedAnyValue {
             where
                 E: de::Error,
             {
-                if let Some(stripped) = value.strip_prefix("data:application/octet-stream;base64,")
-                {
+                if let Some(stripped) = value.strip_prefix(BYTES_DATA_URI_PREFIX) {
                     // Handle byte array
                     base64::engine::general_purpose::STANDARD
                         .decode(stripped)
@@ -240,8 +263,80 @@ impl<'de> Deserialize<'de> for OwnedAnyValue {
 
 #[cfg(test)]
 mod tests {
+    use opentelemetry::logs::{AnyValue, Severity};
+
     use super::*;
 
+    fn log_record() -> wasi::otel::logs::LogRecord {
+        wasi::otel::logs::LogRecord {
+            timestamp: None,
+            observed_timestamp: None,
+            severity_text: Some("INFO".into()),
+            severity_number: Some(9),
+            body: Some("\"order placed\"".into()),
+            attributes: Some(vec![
+                wasi::otel::types::KeyValue {
+                    key: "order.id".into(),
+                    value: "42".into(),
+                },
+                wasi::otel::types::KeyValue {
+                    key: "order.items".into(),
+                    value: "[\"apple\", \"pear\"]".into(),
+                },
+            ]),
+            event_name: None,
+            resource: None,
+            instrumentation_scope: None,
+            trace_id: None,
+            span_id: None,
+            trace_flags: None,
+        }
+    }
+
+    #[test]
+    fn converts_log_record_attributes() {
+        let (record, _) = parse_wasi_log_record(log_record());
+
+        assert_eq!(Some(Severity::Info), record.severity_number());
+        assert_eq!(Some(&AnyValue::from("order placed")), record.body());
+        let attributes: HashMap<_, _> = record
+            .attributes_iter()
+            .map(|(k, v)| (k.as_str().to_owned(), v.clone()))
+            .collect();
+        assert_eq!(Some(&AnyValue::Int(42)), attributes.get("order.id"));
+        assert_eq!(
+            Some(&AnyValue::ListAny(Box::new(vec![
+                AnyValue::from("apple"),
+                AnyValue::from("pear")
+            ]))),
+            attributes.get("order.items")
+        );
+    }
+
+    #[test]
+    fn unspecified_severity_is_left_unset() {
+        let (record, _) = parse_wasi_log_record(wasi::otel::logs::LogRecord {
+            severity_number: Some(0),
+            ..log_record()
+        });
+        assert_eq!(None, record.severity_number());
+    }
+
+    #[test]
+    fn malformed_guest_fields_do_not_panic() {
+        let (record, _) = parse_wasi_log_record(wasi::otel::logs::LogRecord {
+            severity_number: Some(200),
+            body: Some("not json".into()),
+            trace_id: Some("not a trace ID".into()),
+            span_id: Some("not a span ID".into()),
+            ..log_record()
+        });
+
+        assert_eq!(None, record.severity_number());
+        assert_eq!(Some(&AnyValue::from("not json")), record.body());
+        assert!(record.trace_context().is_none());
+    }
+
     #[test]
     fn deserialize_json_to_otel_log_any_value() {
         let test_json = "{\"key1\": false, \"key2\": 123.456, \"key3\": 41, \"key4\": \"data:application/octet-stream;base64,SGVsbG8sIHdvcmxkIQ==\", \"key5\": \"This is a string\", \"key6\": [1, 2, 3], \"key7\": {\"nestedkey1\": \"Hello, from within!\"}}";
@@ -257,7 +352,7 @@ mod tests {
                 "nestedkey1": "Hello, from within!"
             }
         });
-        let actual: serde_json::Value = from_json(test_json);
+        let actual: serde_json::Value = from_json(test_json).unwrap();
         assert_eq!(expected, actual);
     }
 }
@@ -1,14 +1,85 @@
 use crate::wasi;
 use std::borrow::Cow;
 
-impl From<wasi::otel::metrics::ResourceMetrics>
+/// An error converting metric data received from a guest to its OTel
+/// representation.
+#[derive(Debug, thiserror::Error)]
+pub enum MetricConversionError {
+    /// A metric number did not match the number type of its metric.
+    #[error("expected {expected} metric number but got {actual:?}")]
+    MismatchedNumber {
+        expected: &'static str,
+        actual: wasi::otel::metrics::MetricNumber,
+    },
+}
+
+/// Returns the number of exemplars whose span or trace ID is malformed. Conversion replaces such
+/// IDs with the invalid ID, dropping the link from the exemplar to its trace.
+pub fn malformed_exemplar_count(metrics: &wasi::otel::metrics::ResourceMetrics) -> usize {
+    use wasi::otel::metrics::MetricData;
+    let malformed = |exemplars: &[wasi::otel::metrics::Exemplar]| {
+        exemplars
+            .iter()
+            .filter(|e| {
+                !is_exemplar_id(&e.span_id, opentelemetry::trace::SpanId::from_hex)
+                    || !is_exemplar_id(&e.trace_id, opentelemetry::trace::TraceId::from_hex)
+            })
+            .count()
+    };
+    metrics
+        .scope_metrics
+        .iter()
+        .flat_map(|scope_metrics| &scope_metrics.metrics)
+        .map(|metric| match &metric.data {
+            MetricData::F64Gauge(gauge)
+            | MetricData::S64Gauge(gauge)
+            | MetricData::U64Gauge(gauge) => gauge
+                .data_points
+                .iter()
+                .map(|dp| malformed(&dp.exemplars))
+                .sum::<usize>(),
+            MetricData::F64Sum(sum) | MetricData::S64Sum(sum) | MetricData::U64Sum(sum) => sum
+                .data_points
+                .iter()
+                .map(|dp| malformed(&dp.exemplars))
+                .sum::<usize>(),
+            MetricData::F64Histogram(histogram)
+            | MetricData::S64Histogram(histogram)
+            | MetricData::U64Histogram(histogram) => histogram
+                .data_points
+                .iter()
+                .map(|dp| malformed(&dp.exemplars))
+                .sum::<usize>(),
+            MetricData::F64ExponentialHistogram(histogram)
+            | MetricData::S64ExponentialHistogram(histogram)
+            | MetricData::U64ExponentialHistogram(histogram) => histogram
+                .data_points
+                .iter()
+                .map(|dp| malformed(&dp.exemplars))
+                .sum::<usize>(),
+        })
+        .sum()
+}
+
+/// Returns whether an exemplar ID is well-formed, as it is if empty because no span was sampled.
+fn is_exemplar_id<T, E>(id: &str, from_hex: fn(&str) -> Result<T, E>) -> bool {
+    id.is_empty() || from_hex(id).is_ok()
+}
+
+impl TryFrom<wasi::otel::metrics::ResourceMetrics>
     for opentelemetry_sdk::metrics::data::ResourceMetrics
 {
-    fn from(value: wasi::otel::metrics::ResourceMetrics) -> Self {
-        Self {
+    type Error = MetricConversionError;
+
+    fn try_from(value: wasi::otel::metrics::ResourceMetrics) -> Result<Self, Self::Error> {
+        Ok(Self {
             resource: value.resource.into(),
-            scope_metrics: value.scope_metrics.into_iter().map(Into::into).collect(),
-        }
+            scope_metrics: value
+                .scope_metrics
+                .into_iter()
+                .map(TryInto::try_into)
+                .collect::<Result<_, _>>()?,
+        })
     }
 }
 
@@ -28,26 +99,50 @@ impl From<wasi::otel::metrics::Resource> for opentelemetry_sdk::Resource {
     }
 }
 
-impl From<wasi::otel::metrics::ScopeMetrics> for opentelemetry_sdk::metrics::data::ScopeMetrics {
-    fn from(value: wasi::otel::metrics::ScopeMetrics) -> Self {
-        Self {
+impl TryFrom<wasi::otel::metrics::ScopeMetrics> for opentelemetry_sdk::metrics::data::ScopeMetrics {
+    type Error = MetricConversionError;
+
+    fn try_from(value: wasi::otel::metrics::ScopeMetrics) -> Result<Self, Self::Error> {
+        Ok(Self {
             scope: value.scope.into(),
-            metrics: value.metrics.into_iter().map(Into::into).collect(),
-        }
+            metrics: value
+                .metrics
+                .into_iter()
+                .map(TryInto::try_into)
+                .collect::<Result<_, _>>()?,
+        })
     }
 }
 
-impl From<wasi::otel::metrics::Metric> for opentelemetry_sdk::metrics::data::Metric {
-    fn from(value: wasi::otel::metrics::Metric) -> Self {
-        Self {
+impl TryFrom<wasi::otel::metrics::Metric> for opentelemetry_sdk::metrics::data::Metric {
+    type Error = MetricConversionError;
+
+    fn try_from(value: wasi::otel::metrics::Metric) -> Result<Self, Self::Error> {
+        Ok(Self {
             name: Cow::Owned(value.name),
             description: Cow::Owned(value.description),
             unit: Cow::Owned(value.unit),
-            data: value.data.into(),
-        }
+            data: value.data.try_into()?,
+        })
     }
 }
 
+/// Parses an exemplar span ID, which is empty if no span was sampled, falling back to the invalid
+/// ID if it is malformed.
+fn exemplar_span_id(span_id: &str) -> [u8; 8] {
+    opentelemetry::trace::SpanId::from_hex(span_id)
+        .unwrap_or(opentelemetry::trace::SpanId::INVALID)
+        .to_bytes()
+}
+
+/// Parses an exemplar trace ID, which is empty if no span was sampled, falling back to the
+/// invalid ID if it is malformed.
+fn exemplar_trace_id(trace_id: &str) -> [u8; 16] {
+    opentelemetry::trace::TraceId::from_hex(trace_id)
+        .unwrap_or(opentelemetry::trace::TraceId::INVALID)
+        .to_bytes()
+}
+
 /// Converts a Wasi exemplar to an OTel exemplar
 macro_rules! exemplars_to_otel {
     (
@@ -56,31 +151,23 @@ macro_rules! exemplars_to_otel {
         ) => {
         $wasi_exemplar_list
             .iter()
-            .map(|e| {
-                let span_id: [u8; 8] = e
-                    .span_id
-                    .as_bytes()
-                    .try_into()
-                    .expect("failed to parse span ID");
-                let trace_id: [u8; 16] = e
-                    .trace_id
-                    .as_bytes()
-                    .try_into()
-                    .expect("failed to parse trace ID");
-                opentelemetry_sdk::metrics::data::Exemplar::<$exemplar_type> {
-                    filtered_attributes: e
-                        .filtered_attributes
-                        .to_owned()
-                        .into_iter()
-                        .map(Into::into)
-                        .collect(),
-                    time: e.time.into(),
-                    value: e.value.into(),
-                    span_id,
-                    trace_id,
-                }
+            .map(|e| -> Result<_, MetricConversionError> {
+                Ok(
+                    opentelemetry_sdk::metrics::data::Exemplar::<$exemplar_type> {
+                        filtered_attributes: e
+                            .filtered_attributes
+                            .to_owned()
+                            .into_iter()
+                            .map(Into::into)
+                            .collect(),
+                        time: e.time.into(),
+                        value: e.value.try_into()?,
+                        span_id: exemplar_span_id(&e.span_id),
+                        trace_id: exemplar_trace_id(&e.trace_id),
+                    },
+                )
             })
-            .collect()
+            .collect::<Result<_, _>>()?
     };
 }
 
@@ -91,12 +178,14 @@ macro_rules! wasi_gauge_to_otel {
             data_points: $gauge
                 .data_points
                 .iter()
-                .map(|dp| opentelemetry_sdk::metrics::data::GaugeDataPoint {
-                    attributes: dp.attributes.iter().map(Into::into).collect(),
-                    value: dp.value.into(),
-                    exemplars: exemplars_to_otel!(dp.exemplars, $number_type),
+                .map(|dp| -> Result<_, MetricConversionError> {
+                    Ok(opentelemetry_sdk::metrics::data::GaugeDataPoint {
+                        attributes: dp.attributes.iter().map(Into::into).collect(),
+                        value: dp.value.try_into()?,
+                        exemplars: exemplars_to_otel!(dp.exemplars, $number_type),
+                    })
                 })
-                .collect(),
+                .collect::<Result<_, _>>()?,
             start_time: match $gauge.start_time {
                 Some(t) => Some(t.into()),
                 None => None,
@@ -113,12 +202,14 @@ macro_rules! wasi_sum_to_otel {
             data_points: $sum
                 .data_points
                 .iter()
-                .map(|dp| opentelemetry_sdk::metrics::data::SumDataPoint {
-                    attributes: dp.attributes.iter().map(Into::into).collect(),
-                    exemplars: exemplars_to_otel!(dp.exemplars, $number_type),
-                    value: dp.value.into(),
+                .map(|dp| -> Result<_, MetricConversionError> {
+                    Ok(opentelemetry_sdk::metrics::data::SumDataPoint {
+                        attributes: dp.attributes.iter().map(Into::into).collect(),
+                        exemplars: exemplars_to_otel!(dp.exemplars, $number_type),
+                        value: dp.value.try_into()?,
+                    })
                 })
-                .collect(),
+                .collect::<Result<_, _>>()?,
             start_time: $sum.start_time.into(),
             time: $sum.time.into(),
             temporality: $sum.temporality.into(),
@@ -134,23 +225,19 @@ macro_rules! wasi_histogram_to_otel {
             data_points: $histogram
                 .data_points
                 .iter()
-                .map(|dp| opentelemetry_sdk::metrics::data::HistogramDataPoint {
-                    attributes: dp.attributes.iter().map(Into::into).collect(),
-                    bounds: dp.bounds.to_owned(),
-                    bucket_counts: dp.bucket_counts.to_owned(),
-                    exemplars: exemplars_to_otel!(dp.exemplars, $number_type),
-                    count: dp.count,
-                    max: match dp.max {
-                        Some(m) => Some(m.into()),
-                        None => None,
-                    },
-                    min: match dp.min {
-                        Some(m) => Some(m.into()),
-                        None => None,
-                    },
-                    sum: dp.sum.into(),
+                .map(|dp| -> Result<_, MetricConversionError> {
+                    Ok(opentelemetry_sdk::metrics::data::HistogramDataPoint {
+                        attributes: dp.attributes.iter().map(Into::into).collect(),
+                        bounds: dp.bounds.to_owned(),
+                        bucket_counts: dp.bucket_counts.to_owned(),
+                        exemplars: exemplars_to_otel!(dp.exemplars, $number_type),
+                        count: dp.count,
+                        max: dp.max.map(TryInto::try_into).transpose()?,
+                        min: dp.min.map(TryInto::try_into).transpose()?,
+                        sum: dp.sum.try_into()?,
+                    })
                 })
-                .collect(),
+                .collect::<Result<_, _>>()?,
             start_time: $histogram.start_time.into(),
             time: $histogram.time.into(),
             temporality: $histogram.temporality.into(),
@@ -165,28 +252,24 @@ macro_rules! wasi_exponential_histogram_to_otel {
             data_points: $histogram
                 .data_points
                 .iter()
-                .map(
-                    |dp| opentelemetry_sdk::metrics::data::ExponentialHistogramDataPoint {
-                        attributes: dp.attributes.iter().map(Into::into).collect(),
-                        exemplars: exemplars_to_otel!(dp.exemplars, $number_type),
-                        count: dp.count as usize,
-                        max: match dp.max {
-                            Some(m) => Some(m.into()),
-                            None => None,
+                .map(|dp| -> Result<_, MetricConversionError> {
+                    Ok(
+                        opentelemetry_sdk::metrics::data::ExponentialHistogramDataPoint {
+                            attributes: dp.attributes.iter().map(Into::into).collect(),
+                            exemplars: exemplars_to_otel!(dp.exemplars, $number_type),
+                            count: dp.count as usize,
+                            max: dp.max.map(TryInto::try_into).transpose()?,
+                            min: dp.min.map(TryInto::try_into).transpose()?,
+                            sum: dp.sum.try_into()?,
+                            scale: dp.scale,
+                            zero_count: dp.zero_count,
+                            positive_bucket: dp.positive_bucket.to_owned().into(),
+                            negative_bucket: dp.negative_bucket.to_owned().into(),
+                            zero_threshold: dp.zero_threshold,
                         },
-                        min: match dp.min {
-                            Some(m) => Some(m.into()),
-                            None => None,
-                        },
-                        sum: dp.sum.into(),
-                        scale: dp.scale,
-                        zero_count: dp.zero_count,
-                        positive_bucket: dp.positive_bucket.to_owned().into(),
-                        negative_bucket: dp.negative_bucket.to_owned().into(),
-                        zero_threshold: dp.zero_threshold,
-                    },
-                )
-                .collect(),
+                    )
+                })
+                .collect::<Result<_, _>>()?,
             start_time: $histogram.start_time.into(),
             time: $histogram.time.into(),
             temporality: $histogram.temporality.into(),
@@ -194,11 +277,13 @@ macro_rules! wasi_exponential_histogram_to_otel {
     };
 }
 
-impl From<wasi::otel::metrics::MetricData>
+impl TryFrom<wasi::otel::metrics::MetricData>
     for Box<dyn opentelemetry_sdk::metrics::data::Aggregation>
 {
-    fn from(value: wasi::otel::metrics::MetricData) -> Self {
-        match value {
+    type Error = MetricConversionError;
+
+    fn try_from(value: wasi::otel::metrics::MetricData) -> Result<Self, Self::Error> {
+        Ok(match value {
             wasi::otel::metrics::MetricData::F64Sum(s) => wasi_sum_to_otel!(s, f64),
             wasi::otel::metrics::MetricData::S64Sum(s) => wasi_sum_to_otel!(s, i64),
             wasi::otel::metrics::MetricData::U64Sum(s) => wasi_sum_to_otel!(s, u64),
@@ -217,33 +302,48 @@ impl From<wasi::otel::metrics::MetricData>
             wasi::otel::metrics::MetricData::U64ExponentialHistogram(h) => {
                 wasi_exponential_histogram_to_otel!(h, u64)
             }
-        }
+        })
     }
 }
 
-impl From<wasi::otel::metrics::MetricNumber> for f64 {
-    fn from(value: wasi::otel::metrics::MetricNumber) -> Self {
+impl TryFrom<wasi::otel::metrics::MetricNumber> for f64 {
+    type Error = MetricConversionError;
+
+    fn try_from(value: wasi::otel::metrics::MetricNumber) -> Result<Self, Self::Error> {
         match value {
-            wasi::otel::metrics::MetricNumber::F64(n) => n,
-            _ => panic!("error converting WASI MetricNumber to f64"),
+            wasi::otel::metrics::MetricNumber::F64(n) => Ok(n),
+            actual => Err(MetricConversionError::MismatchedNumber {
+                expected: "f64",
+                actual,
+            }),
         }
     }
 }
 
-impl From<wasi::otel::metrics::MetricNumber> for u64 {
-    fn from(value: wasi::otel::metrics::MetricNumber) -> Self {
+impl TryFrom<wasi::otel::metrics::MetricNumber> for u64 {
+    type Error = MetricConversionError;
+
+    fn try_from(value: wasi::otel::metrics::MetricNumber) -> Result<Self, Self::Error> {
         match value {
-            wasi::otel::metrics::MetricNumber::U64(n) => n,
-            _ => panic!("error converting WASI MetricNumber to u64"),
+            wasi::otel::metrics::MetricNumber::U64(n) => Ok(n),
+            actual => Err(MetricConversionError::MismatchedNumber {
+                expected: "u64",
+                actual,
+            }),
         }
     }
 }
 
-impl From<wasi::otel::metrics::MetricNumber> for i64 {
-    fn from(value: wasi::otel::metrics::MetricNumber) -> Self {
+impl TryFrom<wasi::otel::metrics::MetricNumber> for i64 {
+    type Error = MetricConversionError;
+
+    fn try_from(value: wasi::otel::metrics::MetricNumber) -> Result<Self, Self::Error> {
         match value {
-            wasi::otel::metrics::MetricNumber::S64(n) => n,
-            _ => panic!("error converting WASI MetricNumber to i64"),
+            wasi::otel::metrics::MetricNumber::S64(n) => Ok(n),
+            actual => Err(MetricConversionError::MismatchedNumber {
+                expected: "i64",
+                actual,
+            }),
         }
     }
 }
@@ -269,3 +369,111 @@ impl From<wasi::otel::metrics::Temporality> for opentelemetry_sdk::metrics::Temp
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wasi::otel::metrics::MetricNumber;
+
+    fn gauge(value: MetricNumber, span_id: &str) -> wasi::otel::metrics::Gauge {
+        let time = wasi::clocks0_2_0::wall_clock::Datetime {
+            seconds: 1,
+            nanoseconds: 0,
+        };
+        wasi::otel::metrics::Gauge {
+            data_points: vec![wasi::otel::metrics::GaugeDataPoint {
+                attributes: vec![],
+                value,
+                exemplars: vec![wasi::otel::metrics::Exemplar {
+                    filtered_attributes: vec![],
+                    time,
+                    value,
+                    span_id: span_id.to_owned(),
+                    trace_id: String::new(),
+                }],
+            }],
+            start_time: None,
+            time,
+        }
+    }
+
+    #[test]
+    fn metric_numbers_must_match_type() {
+        assert_eq!(1.5, f64::try_from(MetricNumber::F64(1.5)).unwrap());
+        assert_eq!(2, u64::try_from(MetricNumber::U64(2)).unwrap());
+        assert_eq!(-3, i64::try_from(MetricNumber::S64(-3)).unwrap());
+
+        assert!(matches!(
+            u64::try_from(MetricNumber::F64(1.5)),
+            Err(MetricConversionError::MismatchedNumber {
+                expected: "u64",
+                ..
+            })
+        ));
+        assert!(i64::try_from(MetricNumber::U64(2)).is_err());
+        assert!(f64::try_from(MetricNumber::S64(-3)).is_err());
+    }
+
+    #[test]
+    fn converts_gauge_with_unsampled_exemplar() {
+        let data = wasi::otel::metrics::MetricData::U64Gauge(gauge(MetricNumber::U64(7), ""));
+        let result: Result<Box<dyn opentelemetry_sdk::metrics::data::Aggregation>, _> =
+            data.try_into();
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn rejects_mismatched_gauge_values() {
+        let data = wasi::otel::metrics::MetricData::U64Gauge(gauge(MetricNumber::F64(7.0), ""));
+        let result: Result<Box<dyn opentelemetry_sdk::metrics::data::Aggregation>, _> =
+            data.try_into();
+        assert!(matches!(
+            result,
+            Err(MetricConversionError::MismatchedNumber { .. })
+        ));
+    }
+
+    #[test]
+    fn falls_back_on_malformed_exemplar_ids() {
+        let data = gauge(MetricNumber::F64(7.0), "not-hex");
+        assert_eq!(
+            opentelemetry::trace::SpanId::INVALID.to_bytes(),
+            exemplar_span_id(&data.data_points[0].exemplars[0].span_id)
+        );
+        let metrics = wasi::otel::metrics::ResourceMetrics {
+            resource: wasi::otel::metrics::Resource {
+                attributes: vec![],
+                schema_url: None,
+            },
+            scope_metrics: vec![wasi::otel::metrics::ScopeMetrics {
+                scope: wasi::otel::types::InstrumentationScope {
+                    name: "test".into(),
+                    version: None,
+                    schema_url: None,
+                    attributes: vec![],
+                },
+                metrics: vec![
+                    wasi::otel::metrics::Metric {
+                        name: "malformed".into(),
+                        description: String::new(),
+                        unit: String::new(),
+                        data: wasi::otel::metrics::MetricData::F64Gauge(data),
+                    },
+                    wasi::otel::metrics::Metric {
+                        name: "unsampled".into(),
+                        description: String::new(),
+                        unit: String::new(),
+                        data: wasi::otel::metrics::MetricData::F64Gauge(gauge(
+                            MetricNumber::F64(7.0),
+                            "",
+                        )),
+                    },
+                ],
+            }],
+        };
+        assert_eq!(1, malformed_exemplar_count(&metrics));
+        let converted: Result<opentelemetry_sdk::metrics::data::ResourceMetrics, _> =
+            metrics.try_into();
+        assert!(converted.is_ok());
+    }
+}
@@ -1,7 +1,10 @@
+mod baggage_conversions;
 mod common_conversions;
 mod log_conversions;
 mod metric_conversions;
 mod trace_conversions;
 
-use common_conversions::from_json;
+pub use baggage_conversions::baggage_entries;
+use common_conversions::{BYTES_DATA_URI_PREFIX, from_json};
 pub use log_conversions::parse_wasi_log_record;
+pub use metric_conversions::{MetricConversionError, malformed_exemplar_count};
@@ -1,4 +1,5 @@
 use spin_cli::subprocess::ExitStatusError;
+use spin_common::secrets::redact;
 
 #[tokio::main]
 async fn main() {
@@ -8,9 +9,10 @@ async fn main() {
             // exited unsuccessfully and thus already printed error messages. No need
             // to print anything additional.
             Some(e) => e.code(),
-            // Otherwise we print the error chain.
+            // Otherwise we print the error chain, with any secret variable
+            // values redacted.
             None => {
-                terminal::error!("{err}");
+                terminal::error!("{}", redact(&err.to_string()));
                 print_error_chain(err);
                 1
             }
@@ -26,9 +28,9 @@ fn print_error_chain(err: anyhow::Error) {
         eprintln!("\nCaused by:");
         for (i, err) in err.chain().skip(1).enumerate() {
             if is_multiple {
-                eprintln!("{i:>4}: {err}")
+                eprintln!("{i:>4}: {}", redact(&err.to_string()))
             } else {
-                eprintln!("      {err}")
+                eprintln!("      {}", redact(&err.to_string()))
             }
         }
     }
@@ -4,20 +4,44 @@
 pub mod build;
 /// Commands for publishing applications to the Fermyon Platform.
 pub mod cloud;
+/// Command for serving apps deployed through a control-plane API.
+pub mod daemon;
 /// Command for running the Spin Doctor.
 pub mod doctor;
 /// Commands for external subcommands (i.e. plugins)
 pub mod external;
+/// Command for sending random HTTP requests to a component.
+pub mod fuzz;
+/// Command for serving several applications from one process.
+pub mod gateway;
+/// Command for invoking a component once.
+pub mod invoke;
+/// Commands for inspecting an application's background jobs.
+pub mod jobs;
 /// Commands for Spin maintenance tasks.
 pub mod maintenance;
+/// Shared support for exporting and using offline mirrors.
+pub mod mirror;
 /// Command for creating a new application.
 pub mod new;
+/// Commands for describing an application's HTTP API in OpenAPI.
+pub mod openapi;
 /// Command for adding a plugin to Spin
 pub mod plugins;
 /// Commands for working with OCI registries.
 pub mod registry;
+/// Commands for running an application as a macOS or Windows service.
+pub mod service;
+/// Commands for inspecting and cleaning local application state.
+pub mod state;
+/// Commands for analyzing telemetry captured locally.
+pub mod telemetry;
 /// Commands for working with templates.
 pub mod templates;
+/// Command for running an application's tests.
+pub mod test;
+/// Commands for viewing the traces of a running app.
+pub mod trace;
 /// Commands for starting the runtime.
 pub mod up;
 /// Command for rebuilding and restarting a Spin app when files change.
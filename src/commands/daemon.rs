@@ -0,0 +1,169 @@
+use std::{net::SocketAddr, path::PathBuf, sync::Arc};
+
+use anyhow::{Context, Result, bail};
+use clap::Parser;
+use spin_common::ui::quoted_path;
+use spin_control_plane::{ControlPlaneServer, ControlPlaneService, require_bearer_token};
+use spin_runtime_factors::TriggerFactors;
+use spin_trigger_http::Gateway;
+use tonic::transport::{Identity, ServerTlsConfig};
+
+/// Run Spin as a daemon, serving the HTTP triggers of apps which orchestrators
+/// deploy through a gRPC control-plane API.
+///
+/// The API, `spin.control.v1.ControlPlane`, deploys apps from registries,
+/// lists them, drains them, reports health, and streams component output.
+/// Apps are routed by host and path prefix, as with `spin gateway`.
+#[derive(Parser, Debug)]
+#[clap(about = "Serve apps deployed through a gRPC control-plane API")]
+pub struct DaemonCommand {
+    /// IP address and port to serve apps' HTTP triggers on
+    #[clap(
+        long = "listen",
+        env = "SPIN_HTTP_LISTEN_ADDR",
+        default_value = "127.0.0.1:3000"
+    )]
+    pub address: SocketAddr,
+
+    /// IP address and port to serve the control-plane API on.
+    #[clap(
+        long = "control-listen",
+        env = "SPIN_CONTROL_LISTEN",
+        default_value = "127.0.0.1:3500"
+    )]
+    pub control_address: SocketAddr,
+
+    /// The bearer token calls to the control-plane API must carry.
+    #[clap(
+        long = "control-token",
+        env = "SPIN_CONTROL_TOKEN",
+        hide_env_values = true
+    )]
+    pub control_token: String,
+
+    /// The path to the certificate to serve the control-plane API over TLS
+    /// with, in PEM format. Required unless the API is served on a loopback
+    /// address.
+    #[clap(
+        long = "control-tls-cert",
+        env = "SPIN_CONTROL_TLS_CERT",
+        requires = "control_tls_key"
+    )]
+    pub control_tls_cert: Option<PathBuf>,
+
+    /// The path to the key of `--control-tls-cert`, in PEM format.
+    #[clap(
+        long = "control-tls-key",
+        env = "SPIN_CONTROL_TLS_KEY",
+        requires = "control_tls_cert"
+    )]
+    pub control_tls_key: Option<PathBuf>,
+
+    /// The directory to keep deployed apps' state and logs in. Defaults to
+    /// `daemon` in the Spin data directory.
+    #[clap(long = "data-dir", value_hint = clap::ValueHint::DirPath)]
+    pub data_dir: Option<PathBuf>,
+
+    /// Cache directory for downloaded components and assets.
+    #[clap(long, value_hint = clap::ValueHint::DirPath)]
+    pub cache_dir: Option<PathBuf>,
+}
+
+impl DaemonCommand {
+    pub async fn run(self) -> Result<()> {
+        self.validate()?;
+        let data_dir = match self.data_dir {
+            Some(dir) => dir,
+            None => spin_common::data_dir::data_dir()?.join("daemon"),
+        };
+        std::fs::create_dir_all(&data_dir)
+            .with_context(|| format!("failed to create {}", quoted_path(&data_dir)))?;
+
+        let gateway = Arc::new(Gateway::<TriggerFactors>::new(self.address));
+        let service = ControlPlaneService::new(gateway.clone(), data_dir, self.cache_dir);
+        let mut server = tonic::transport::Server::builder();
+        if let (Some(cert), Some(key)) = (&self.control_tls_cert, &self.control_tls_key) {
+            let cert = std::fs::read(cert)
+                .with_context(|| format!("failed to read {}", quoted_path(cert)))?;
+            let key = std::fs::read(key)
+                .with_context(|| format!("failed to read {}", quoted_path(key)))?;
+            server = server
+                .tls_config(ServerTlsConfig::new().identity(Identity::from_pem(cert, key)))
+                .context("invalid control-plane TLS certificate or key")?;
+        }
+        let router = server.add_service(ControlPlaneServer::with_interceptor(
+            service,
+            require_bearer_token(self.control_token),
+        ));
+
+        let scheme = if self.control_tls_cert.is_some() {
+            "grpcs"
+        } else {
+            "grpc"
+        };
+        println!("Serving apps on http://{}", self.address);
+        println!(
+            "Serving the control-plane API on {scheme}://{}",
+            self.control_address
+        );
+        let control = router.serve_with_shutdown(self.control_address, async {
+            _ = tokio::signal::ctrl_c().await;
+        });
+        tokio::select! {
+            result = gateway.serve() => result,
+            result = control => result.context("control-plane API failed"),
+        }
+    }
+
+    /// Checks that the control-plane API is only served in the clear on a
+    /// loopback address.
+    fn validate(&self) -> Result<()> {
+        if self.control_tls_cert.is_none() && !self.control_address.ip().is_loopback() {
+            bail!(
+                "the control-plane API may only be served on {} over TLS; pass \
+                 `--control-tls-cert` and `--control-tls-key`",
+                self.control_address
+            );
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_control_token_is_required() {
+        assert!(DaemonCommand::try_parse_from(["daemon"]).is_err());
+        let cmd = DaemonCommand::try_parse_from(["daemon", "--control-token", "secret"]).unwrap();
+        cmd.validate().unwrap();
+    }
+
+    #[test]
+    fn non_loopback_addresses_require_tls() {
+        let cmd = DaemonCommand::try_parse_from([
+            "daemon",
+            "--control-token",
+            "secret",
+            "--control-listen",
+            "0.0.0.0:3500",
+        ])
+        .unwrap();
+        assert!(cmd.validate().is_err());
+
+        let cmd = DaemonCommand::try_parse_from([
+            "daemon",
+            "--control-token",
+            "secret",
+            "--control-listen",
+            "0.0.0.0:3500",
+            "--control-tls-cert",
+            "cert.pem",
+            "--control-tls-key",
+            "key.pem",
+        ])
+        .unwrap();
+        cmd.validate().unwrap();
+    }
+}
@@ -1,9 +1,14 @@
 use std::{fmt::Debug, path::PathBuf};
 
-use anyhow::Result;
-use clap::Parser;
+use anyhow::{Context, Result};
+use clap::{Parser, Subcommand};
 use dialoguer::{Confirm, Select, console::Emoji};
-use spin_doctor::{Diagnosis, DryRunNotSupported, PatientDiagnosis};
+use spin_common::ui::quoted_path;
+use spin_doctor::{
+    Diagnosis, DryRunNotSupported, PatientDiagnosis,
+    egress::EgressReport,
+    telemetry::{Finding, Severity, TelemetryConfig},
+};
 
 use crate::opts::APP_MANIFEST_FILE_OPT;
 
@@ -20,10 +25,29 @@ pub struct DoctorCommand {
         alias = "file"
     )]
     pub app_source: Option<PathBuf>,
+
+    #[clap(subcommand)]
+    pub command: Option<DoctorSubcommand>,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum DoctorSubcommand {
+    /// Summarize the outbound connections an app made, as recorded by
+    /// `spin up --audit-log`, and suggest tighter `allowed_outbound_hosts`.
+    Egress(EgressCommand),
+    /// Check the OpenTelemetry exporter configuration in the environment, and
+    /// whether the configured collectors accept exports.
+    Telemetry(TelemetryCommand),
 }
 
 impl DoctorCommand {
     pub async fn run(self) -> Result<()> {
+        match self.command {
+            Some(DoctorSubcommand::Egress(cmd)) => return cmd.run(self.app_source),
+            Some(DoctorSubcommand::Telemetry(cmd)) => return cmd.run().await,
+            None => {}
+        }
+
         let (manifest_file, distance) =
             spin_common::paths::find_manifest_file_path(self.app_source.as_ref())?;
         if distance > 0 {
@@ -89,6 +113,119 @@ impl DoctorCommand {
     }
 }
 
+#[derive(Parser, Debug)]
+pub struct EgressCommand {
+    /// The audit log written by `spin up --audit-log`.
+    pub audit_log: PathBuf,
+}
+
+impl EgressCommand {
+    fn run(self, app_source: Option<PathBuf>) -> Result<()> {
+        let log = std::fs::File::open(&self.audit_log)
+            .with_context(|| format!("failed to open {}", quoted_path(&self.audit_log)))?;
+        let mut report = EgressReport::from_audit_log(std::io::BufReader::new(log))?;
+
+        // The manifest is optional: without it, only the connections are shown.
+        if let Ok((manifest_file, _)) =
+            spin_common::paths::find_manifest_file_path(app_source.as_ref())
+        {
+            let manifest =
+                spin_manifest::manifest_from_file(&manifest_file).with_context(|| {
+                    format!("failed to read manifest {}", quoted_path(&manifest_file))
+                })?;
+            report.add_grants(&manifest);
+        }
+
+        if report.components.is_empty() {
+            println!(
+                "No outbound connections found in {}.",
+                quoted_path(&self.audit_log)
+            );
+            return Ok(());
+        }
+
+        for (id, component) in &report.components {
+            println!("\nComponent {id:?}:");
+            if component.destinations.is_empty() {
+                println!("  No outbound connections.");
+            }
+            for (destination, summary) in &component.destinations {
+                let status = if summary.allowed {
+                    ""
+                } else {
+                    " (not allowed)"
+                };
+                println!("  {destination} - {} connection(s){status}", summary.count);
+            }
+            let wildcards = component.wildcard_grants();
+            if !wildcards.is_empty() {
+                println!(
+                    "{icon}allowed_outbound_hosts grants any host: {}",
+                    wildcards.join(", "),
+                    icon = Emoji("⚠  ", "")
+                );
+                let suggested = component
+                    .suggested_grants()
+                    .iter()
+                    .map(|grant| format!("{grant:?}"))
+                    .collect::<Vec<_>>();
+                println!(
+                    "  To allow only the destinations contacted, use 'allowed_outbound_hosts = [{}]'",
+                    suggested.join(", ")
+                );
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Parser, Debug)]
+pub struct TelemetryCommand {
+    /// Only check the configuration; don't send test exports to collectors.
+    #[clap(long = "skip-export")]
+    pub skip_export: bool,
+}
+
+impl TelemetryCommand {
+    async fn run(self) -> Result<()> {
+        let config = TelemetryConfig::from_env();
+        for export in &config.exports {
+            println!(
+                "{}: {} ({}={})",
+                export.signal, export.endpoint, export.protocol_var, export.protocol
+            );
+        }
+        let mut findings = spin_doctor::telemetry::check_config(&config);
+        if !self.skip_export && !config.sdk_disabled {
+            findings.extend(spin_doctor::telemetry::check_exports(&config).await);
+        }
+
+        let has_problems = findings.iter().any(|f| f.severity == Severity::Problem);
+        for finding in &findings {
+            show_finding(finding);
+        }
+        if has_problems {
+            anyhow::bail!("telemetry is misconfigured; Spin will not report export failures");
+        }
+        if findings.iter().all(|f| f.severity == Severity::Ok) {
+            println!("{icon}No problems found.", icon = Emoji("❤  ", ""));
+        }
+        Ok(())
+    }
+}
+
+fn show_finding(finding: &Finding) {
+    let icon = match finding.severity {
+        Severity::Ok => Emoji("✅ ", ""),
+        Severity::Warning => Emoji("⚠  ", ""),
+        Severity::Problem => Emoji("❗ ", ""),
+    };
+    match finding.signal {
+        Some(signal) => println!("{icon}{signal}: {}", finding.message),
+        None => println!("{icon}{}", finding.message),
+    }
+}
+
 fn show_diagnosis(diagnosis: &dyn Diagnosis) {
     let icon = if diagnosis.is_critical() {
         Emoji("❗ ", "")
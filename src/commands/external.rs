@@ -207,6 +207,8 @@ fn installer_for(plugin_name: &str) -> Install {
         override_compatibility_check: false,
         version: None,
         auth_header_value: None,
+        mirror: None,
+        mirror_insecure: false,
     }
 }
 
@@ -0,0 +1,452 @@
+use std::path::PathBuf;
+
+use anyhow::{Context, Result, bail};
+use clap::Parser;
+use http_body_util::BodyExt;
+use rand::{Rng, SeedableRng, rngs::StdRng, seq::IndexedRandom};
+use spin_app::App;
+use spin_common::ui::quoted_path;
+use spin_http::{config::HttpTriggerConfig, routes::HttpTriggerRouteConfig};
+use spin_runtime_factors::{FactorsBuilder, TriggerAppArgs};
+use spin_trigger::cli::{FactorsConfig, TriggerAppBuilder, UserProvidedPath};
+use spin_trigger_http::{HttpTrigger, HttpTriggerConfig};
+
+use crate::opts::APP_MANIFEST_FILE_OPT;
+
+/// Send randomly generated HTTP requests to a component in-process, reporting
+/// those it fails to handle.
+#[derive(Parser, Debug)]
+#[clap(about = "Send random HTTP requests to a component and report failures")]
+pub struct FuzzCommand {
+    /// The component to fuzz.
+    #[clap(value_name = "COMPONENT")]
+    pub component_id: String,
+
+    /// The application containing the component. This may be a manifest
+    /// (spin.toml) file, or a directory containing a spin.toml file.
+    /// If omitted, it defaults to "spin.toml".
+    #[clap(
+        name = APP_MANIFEST_FILE_OPT,
+        short = 'f',
+        long = "from",
+        alias = "file"
+    )]
+    pub app_source: Option<PathBuf>,
+
+    /// How many requests to send.
+    #[clap(long = "runs", default_value_t = 1000)]
+    pub runs: u64,
+
+    /// The seed of the first request. Each request is generated from its own
+    /// seed, so `--seed <SEED> --runs 1` replays a reported failure. If
+    /// omitted, a random seed is used.
+    #[clap(long = "seed")]
+    pub seed: Option<u64>,
+
+    /// The largest request body to generate, in bytes.
+    #[clap(long = "max-body-size", default_value_t = 4096)]
+    pub max_body_size: usize,
+
+    /// Configuration file for config providers and wasmtime config.
+    #[clap(long = "runtime-config-file")]
+    pub runtime_config_file: Option<PathBuf>,
+
+    #[clap(flatten)]
+    pub app_args: TriggerAppArgs,
+}
+
+impl FuzzCommand {
+    pub async fn run(self) -> Result<()> {
+        let (manifest_file, _) =
+            spin_common::paths::find_manifest_file_path(self.app_source.as_ref())?;
+        let app_dir = manifest_file
+            .parent()
+            .context("manifest file has no parent directory")?;
+
+        let working_dir = tempfile::tempdir()?;
+        let locked_app = spin_loader::from_file(
+            &manifest_file,
+            spin_loader::FilesMountStrategy::Direct,
+            None,
+            None,
+        )
+        .await?;
+        let app = App::new("fuzz", locked_app);
+
+        let routes = app
+            .triggers_with_type("http")
+            .filter(|trigger| {
+                trigger
+                    .component()
+                    .is_ok_and(|component| component.id() == self.component_id)
+            })
+            .map(|trigger| trigger.typed_config::<HttpTriggerConfig>())
+            .filter_map(|config| match config {
+                Ok(HttpTriggerConfig {
+                    route: HttpTriggerRouteConfig::Route(route),
+                    ..
+                }) => Some(Ok(route)),
+                Ok(_) => None,
+                Err(err) => Some(Err(err)),
+            })
+            .collect::<Result<Vec<_>>>()?;
+        if routes.is_empty() {
+            bail!(
+                "no HTTP route in {} invokes component {:?}",
+                quoted_path(&manifest_file),
+                self.component_id
+            );
+        }
+
+        let factors_config = FactorsConfig {
+            working_dir: working_dir.path().to_owned(),
+            runtime_config_file: self.runtime_config_file.clone(),
+            state_dir: UserProvidedPath::Default,
+            local_app_dir: Some(app_dir.to_string_lossy().into_owned()),
+            app_id: app
+                .get_metadata(spin_app::APP_ORIGIN_KEY)?
+                .and_then(|origin: String| spin_common::app_state::app_id_from_origin(&origin)),
+            ..Default::default()
+        };
+        let trigger = HttpTrigger::new(&app, HttpTriggerConfig::new("127.0.0.1:3000".parse()?))?;
+        let mut builder = TriggerAppBuilder::<_, FactorsBuilder>::new(trigger);
+        let trigger_app = builder
+            .build(
+                app,
+                factors_config,
+                self.app_args,
+                &spin_trigger::loader::ComponentLoader::new(),
+            )
+            .await?;
+        let server = builder.trigger.into_server(trigger_app)?;
+
+        let first_seed = self.seed.unwrap_or_else(|| rand::rng().random());
+        eprintln!(
+            "Fuzzing component {:?} with {} requests from seed {first_seed}",
+            self.component_id, self.runs
+        );
+
+        let mut failures = 0;
+        for n in 0..self.runs {
+            let seed = first_seed.wrapping_add(n);
+            let case = FuzzCase::generate(seed, &routes, self.max_body_size);
+            let summary = format!("{} {}", case.method, case.path);
+            let resp = server
+                .handle(
+                    case.into_request()?,
+                    http::uri::Scheme::HTTP,
+                    (std::net::Ipv4Addr::LOCALHOST, 0).into(),
+                )
+                .await;
+            if let Err(problem) = check_response(&summary, resp).await {
+                failures += 1;
+                eprintln!("Seed {seed}: {summary}: {problem}");
+            }
+        }
+
+        if failures > 0 {
+            eprintln!(
+                "To replay a failure, run `spin fuzz {} --seed <SEED> --runs 1`",
+                self.component_id
+            );
+            bail!("{failures} of {} requests failed", self.runs);
+        }
+        eprintln!("All {} requests were handled", self.runs);
+        Ok(())
+    }
+}
+
+/// A generated request.
+#[derive(Debug, PartialEq)]
+struct FuzzCase {
+    method: http::Method,
+    path: String,
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+}
+
+const METHODS: &[http::Method] = &[
+    http::Method::GET,
+    http::Method::POST,
+    http::Method::PUT,
+    http::Method::PATCH,
+    http::Method::DELETE,
+    http::Method::HEAD,
+    http::Method::OPTIONS,
+];
+
+/// Values which commonly expose mishandled input.
+const TRICKY_VALUES: &[&str] = &[
+    "",
+    "0",
+    "-1",
+    "18446744073709551616",
+    "1e309",
+    "NaN",
+    "null",
+    "true",
+    "..",
+    "%",
+    "%00",
+    " ",
+    "'",
+    "\"",
+    "<script>",
+    "\u{1F600}",
+    "\u{202E}",
+];
+
+const CONTENT_TYPES: &[&str] = &[
+    "application/json",
+    "application/x-www-form-urlencoded",
+    "text/plain",
+    "application/octet-stream",
+    "multipart/form-data; boundary=x",
+    "invalid",
+];
+
+impl FuzzCase {
+    fn generate(seed: u64, routes: &[String], max_body_size: usize) -> Self {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let method = METHODS.choose(&mut rng).unwrap().clone();
+        let route = routes.choose(&mut rng).unwrap();
+
+        let mut path = generate_path(&mut rng, route);
+        if rng.random_bool(0.3) {
+            let pairs = (0..rng.random_range(1..=3))
+                .map(|_| {
+                    format!(
+                        "{}={}",
+                        percent_encode(&random_value(&mut rng)),
+                        percent_encode(&random_value(&mut rng))
+                    )
+                })
+                .collect::<Vec<_>>();
+            path = format!("{path}?{}", pairs.join("&"));
+        }
+
+        let mut headers = vec![];
+        for _ in 0..rng.random_range(0..=3) {
+            let header = match rng.random_range(0..4) {
+                0 => (
+                    "content-type".to_owned(),
+                    CONTENT_TYPES.choose(&mut rng).unwrap().to_string(),
+                ),
+                1 => ("accept".to_owned(), "*/*".to_owned()),
+                2 => (
+                    "authorization".to_owned(),
+                    format!("Bearer {}", random_token(&mut rng)),
+                ),
+                _ => (
+                    format!("x-fuzz-{}", random_token(&mut rng).to_ascii_lowercase()),
+                    random_token(&mut rng),
+                ),
+            };
+            headers.push(header);
+        }
+
+        let body = if matches!(method, http::Method::GET | http::Method::HEAD) {
+            vec![]
+        } else {
+            generate_body(&mut rng, max_body_size)
+        };
+
+        Self {
+            method,
+            path,
+            headers,
+            body,
+        }
+    }
+
+    fn into_request(self) -> Result<http::Request<spin_http::Body>> {
+        let mut req = http::Request::builder()
+            .method(self.method)
+            .uri(&self.path)
+            .header(http::header::HOST, "localhost:3000");
+        for (name, value) in &self.headers {
+            req = req.header(name, value);
+        }
+        req.body(spin_http::body::full(self.body.into()))
+            .with_context(|| format!("generated an invalid request for {:?}", self.path))
+    }
+}
+
+/// Generates a path which matches the route, filling in its wildcards.
+fn generate_path(rng: &mut StdRng, route: &str) -> String {
+    let mut segments = vec![];
+    for segment in route.split('/').filter(|s| !s.is_empty()) {
+        if segment == "..." {
+            for _ in 0..rng.random_range(0..=3) {
+                segments.push(percent_encode(&random_value(rng)));
+            }
+        } else if segment.starts_with(':') {
+            let value = random_value(rng);
+            // An empty segment would not match the wildcard.
+            segments.push(if value.is_empty() {
+                "x".to_owned()
+            } else {
+                percent_encode(&value)
+            });
+        } else {
+            segments.push(segment.to_owned());
+        }
+    }
+    format!("/{}", segments.join("/"))
+}
+
+fn generate_body(rng: &mut StdRng, max_body_size: usize) -> Vec<u8> {
+    match rng.random_range(0..4) {
+        0 => vec![],
+        1 => {
+            let len = rng.random_range(0..=max_body_size);
+            (0..len).map(|_| rng.random()).collect()
+        }
+        2 => {
+            let mut json = serde_json::to_vec(&random_json(rng, 3)).unwrap();
+            json.truncate(max_body_size);
+            json
+        }
+        _ => {
+            let pairs = (0..rng.random_range(1..=4))
+                .map(|_| {
+                    format!(
+                        "{}={}",
+                        percent_encode(&random_token(rng)),
+                        percent_encode(&random_value(rng))
+                    )
+                })
+                .collect::<Vec<_>>();
+            let mut form = pairs.join("&").into_bytes();
+            form.truncate(max_body_size);
+            form
+        }
+    }
+}
+
+fn random_json(rng: &mut StdRng, depth: u32) -> serde_json::Value {
+    let kinds = if depth == 0 { 4 } else { 6 };
+    match rng.random_range(0..kinds) {
+        0 => serde_json::Value::Null,
+        1 => rng.random::<bool>().into(),
+        2 => rng.random::<i64>().into(),
+        3 => random_value(rng).into(),
+        4 => (0..rng.random_range(0..4))
+            .map(|_| random_json(rng, depth - 1))
+            .collect(),
+        _ => (0..rng.random_range(0..4))
+            .map(|_| (random_token(rng), random_json(rng, depth - 1)))
+            .collect::<serde_json::Map<_, _>>()
+            .into(),
+    }
+}
+
+fn random_value(rng: &mut StdRng) -> String {
+    if rng.random_bool(0.5) {
+        TRICKY_VALUES.choose(rng).unwrap().to_string()
+    } else {
+        random_token(rng)
+    }
+}
+
+fn random_token(rng: &mut StdRng) -> String {
+    let len = rng.random_range(1..=12);
+    (0..len)
+        .map(|_| rng.sample(rand::distr::Alphanumeric) as char)
+        .collect()
+}
+
+/// Percent-encodes everything but unreserved URI characters.
+fn percent_encode(value: &str) -> String {
+    value
+        .bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{b:02X}"),
+        })
+        .collect()
+}
+
+/// Checks that the component handled a request, and that its response is
+/// well formed.
+async fn check_response(
+    summary: &str,
+    resp: Result<http::Response<spin_http::Body>>,
+) -> Result<()> {
+    let resp = resp.context("the component failed")?;
+    let (parts, body) = resp.into_parts();
+    let body = body
+        .collect()
+        .await
+        .map_err(|err| anyhow::anyhow!("the response body failed: {err}"))?
+        .to_bytes();
+
+    if parts.status.is_server_error() {
+        bail!("the component responded with {}", parts.status);
+    }
+    let bodiless = summary.starts_with("HEAD ")
+        || parts.status.is_informational()
+        || parts.status == http::StatusCode::NO_CONTENT
+        || parts.status == http::StatusCode::NOT_MODIFIED;
+    if bodiless && !body.is_empty() {
+        bail!(
+            "the component responded with {} and a body, which is not allowed",
+            parts.status
+        );
+    }
+    if let Some(content_length) = parts.headers.get(http::header::CONTENT_LENGTH) {
+        let content_length = content_length
+            .to_str()
+            .ok()
+            .and_then(|len| len.parse::<usize>().ok())
+            .context("the response has an invalid content-length")?;
+        if !bodiless && content_length != body.len() {
+            bail!(
+                "the response content-length is {content_length} but its body is {} bytes",
+                body.len()
+            );
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn paths_match_their_route() {
+        let mut rng = StdRng::seed_from_u64(0);
+        for _ in 0..100 {
+            let path = generate_path(&mut rng, "/users/:id/posts");
+            let segments = path.split('/').collect::<Vec<_>>();
+            assert_eq!(["", "users"], segments[..2]);
+            assert!(!segments[2].is_empty());
+            assert_eq!("posts", segments[3]);
+            assert_eq!(4, segments.len());
+
+            let path = generate_path(&mut rng, "/static/...");
+            assert!(path.starts_with("/static"), "{path}");
+            assert!(path.parse::<http::Uri>().is_ok(), "{path}");
+        }
+    }
+
+    #[test]
+    fn cases_are_reproducible_from_their_seed() {
+        let routes = vec!["/api/...".to_owned()];
+        for seed in 0..20 {
+            let case = FuzzCase::generate(seed, &routes, 64);
+            assert_eq!(case, FuzzCase::generate(seed, &routes, 64));
+            assert!(case.body.len() <= 64);
+            assert!(case.into_request().is_ok());
+        }
+    }
+
+    #[test]
+    fn values_are_percent_encoded() {
+        assert_eq!("a-b_c.d~", percent_encode("a-b_c.d~"));
+        assert_eq!("%2F%20%25", percent_encode("/ %"));
+    }
+}
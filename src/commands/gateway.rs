@@ -0,0 +1,427 @@
+use std::{
+    collections::{HashMap, HashSet},
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::SystemTime,
+};
+
+use anyhow::{Context, Result, bail, ensure};
+use clap::Parser;
+use serde::Deserialize;
+use spin_app::App;
+use spin_common::{ui::quoted_path, url::parse_file_url};
+use spin_locked_app::locked::LockedApp;
+use spin_runtime_factors::{FactorsBuilder, TriggerAppArgs, TriggerFactors};
+use spin_trigger::{
+    Trigger,
+    cli::{FactorsConfig, TriggerAppBuilder, UserProvidedPath},
+};
+use spin_trigger_http::{Gateway, GatewayRoute, HttpServer, HttpTrigger, HttpTriggerConfig};
+use tempfile::TempDir;
+
+/// Serve the HTTP triggers of several applications from one process.
+///
+/// Requests are routed to the applications by host and path prefix, as listed
+/// in a gateway configuration file:
+///
+/// ```toml
+/// [[app]]
+/// name = "blog"
+/// manifest = "blog/spin.toml"
+/// hosts = ["blog.example.com"]
+///
+/// [[app]]
+/// name = "api"
+/// locked = "api/spin.lock"
+/// path_prefix = "/api"
+/// runtime_config_file = "api/runtime-config.toml"
+/// ```
+///
+/// Each application has its own engine, factors, and state. On SIGHUP, the
+/// configuration file is reread and only the applications whose configuration
+/// or files have changed are reloaded.
+#[derive(Parser, Debug)]
+#[clap(about = "Serve several applications from one process, routed by host and path")]
+pub struct GatewayCommand {
+    /// The gateway configuration file, listing the applications to serve.
+    #[clap(short = 'c', long = "config", default_value = "gateway.toml")]
+    pub config: PathBuf,
+
+    /// IP address and port to listen on
+    #[clap(
+        long = "listen",
+        env = "SPIN_HTTP_LISTEN_ADDR",
+        default_value = "127.0.0.1:3000"
+    )]
+    pub address: SocketAddr,
+}
+
+/// A gateway configuration file.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct GatewayConfig {
+    #[serde(rename = "app", default)]
+    apps: Vec<AppConfig>,
+}
+
+/// An application served by the gateway.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+struct AppConfig {
+    name: String,
+    /// The application's manifest.
+    manifest: Option<PathBuf>,
+    /// The application's lock file, as an alternative to its manifest.
+    locked: Option<PathBuf>,
+    /// The hosts to route to the application; any host if empty.
+    #[serde(default)]
+    hosts: Vec<String>,
+    /// The path prefix to route to the application, which is removed from
+    /// request paths before the application routes them.
+    #[serde(default = "default_path_prefix")]
+    path_prefix: String,
+    runtime_config_file: Option<PathBuf>,
+    state_dir: Option<PathBuf>,
+}
+
+fn default_path_prefix() -> String {
+    "/".into()
+}
+
+impl GatewayConfig {
+    fn from_file(path: &Path) -> Result<Self> {
+        let contents = std::fs::read_to_string(path)
+            .with_context(|| format!("failed to read {}", quoted_path(path)))?;
+        let mut config: Self = toml::from_str(&contents)
+            .with_context(|| format!("failed to parse {}", quoted_path(path)))?;
+        let base = spin_common::paths::parent_dir(path)?;
+        let mut names = HashSet::new();
+        for app in &mut config.apps {
+            ensure!(
+                names.insert(app.name.clone()),
+                "more than one app is named {:?}",
+                app.name
+            );
+            ensure!(
+                app.manifest.is_some() != app.locked.is_some(),
+                "app {:?} must set exactly one of `manifest` or `locked`",
+                app.name
+            );
+            for path in [
+                &mut app.manifest,
+                &mut app.locked,
+                &mut app.runtime_config_file,
+                &mut app.state_dir,
+            ]
+            .into_iter()
+            .flatten()
+            {
+                *path = base.join(&*path);
+            }
+        }
+        Ok(config)
+    }
+}
+
+impl AppConfig {
+    fn route(&self) -> Result<GatewayRoute> {
+        GatewayRoute::new(self.hosts.clone(), &self.path_prefix)
+            .with_context(|| format!("invalid route for app {:?}", self.name))
+    }
+
+    /// Loads the application, returning the server for its HTTP triggers.
+    async fn load(
+        &self,
+        listen_addr: SocketAddr,
+    ) -> Result<(Arc<HttpServer<TriggerFactors>>, LoadedApp)> {
+        let (source, locked_app) = match (&self.manifest, &self.locked) {
+            (Some(manifest), _) => {
+                let locked_app = spin_loader::from_file(
+                    manifest,
+                    spin_loader::FilesMountStrategy::Direct,
+                    None,
+                    None,
+                )
+                .await
+                .with_context(|| {
+                    format!("failed to load manifest from {}", quoted_path(manifest))
+                })?;
+                (manifest, locked_app)
+            }
+            (None, Some(locked)) => {
+                let contents = tokio::fs::read(locked)
+                    .await
+                    .with_context(|| format!("failed to read {}", quoted_path(locked)))?;
+                let locked_app: LockedApp = serde_json::from_slice(&contents)
+                    .with_context(|| format!("failed to parse {}", quoted_path(locked)))?;
+                (locked, locked_app)
+            }
+            (None, None) => unreachable!("validated when the config was read"),
+        };
+        let mut sources = vec![source.clone()];
+        sources.extend(
+            locked_app
+                .components
+                .iter()
+                .filter_map(|c| parse_file_url(c.source.content.source.as_deref()?).ok()),
+        );
+
+        let app = App::new(self.name.clone(), locked_app);
+        if app.triggers_with_type("http").next().is_none() {
+            bail!("app has no HTTP triggers");
+        }
+        if let Some(other) = app
+            .triggers()
+            .map(|trigger| trigger.trigger_type())
+            .find(|trigger_type| *trigger_type != "http")
+        {
+            tracing::warn!(
+                "App {:?} has {other:?} triggers, which the gateway does not run",
+                self.name
+            );
+        }
+        let requirements = <HttpTrigger as Trigger<TriggerFactors>>::supported_host_requirements();
+        if let Err(unmet) = app.ensure_needs_only("http", &requirements) {
+            bail!("app requires features the gateway does not support: {unmet}");
+        }
+
+        // Files are mounted directly, but the working directory may still be
+        // written to, e.g. for transient state.
+        let working_dir = tempfile::tempdir()?;
+        let factors_config = FactorsConfig {
+            working_dir: working_dir.path().to_owned(),
+            runtime_config_file: self.runtime_config_file.clone(),
+            state_dir: match &self.state_dir {
+                Some(dir) => UserProvidedPath::Provided(dir.clone()),
+                None => UserProvidedPath::Default,
+            },
+            local_app_dir: Some(
+                spin_common::paths::parent_dir(source)?
+                    .to_string_lossy()
+                    .into_owned(),
+            ),
+            app_id: app
+                .get_metadata(spin_app::APP_ORIGIN_KEY)?
+                .and_then(|origin: String| spin_common::app_state::app_id_from_origin(&origin)),
+            ..Default::default()
+        };
+
+        let trigger = HttpTrigger::new(&app, HttpTriggerConfig::new(listen_addr))?;
+        let mut builder = TriggerAppBuilder::<_, FactorsBuilder>::new(trigger);
+        let loader = spin_trigger::loader::ComponentLoader::new();
+        let trigger_app = builder
+            .build(app, factors_config, TriggerAppArgs::default(), &loader)
+            .await?;
+        let server = builder.trigger.into_server(trigger_app)?;
+
+        let loaded = LoadedApp {
+            config: self.clone(),
+            sources: sources
+                .into_iter()
+                .map(|path| {
+                    let modified = modified(&path);
+                    (path, modified)
+                })
+                .collect(),
+            _working_dir: working_dir,
+        };
+        Ok((server, loaded))
+    }
+}
+
+/// An application the gateway has loaded.
+struct LoadedApp {
+    config: AppConfig,
+    /// The files the application was loaded from, and when each was last
+    /// modified.
+    sources: Vec<(PathBuf, Option<SystemTime>)>,
+    _working_dir: TempDir,
+}
+
+impl LoadedApp {
+    /// Whether the application is loaded from the given configuration and
+    /// unchanged files.
+    fn is_current(&self, config: &AppConfig) -> bool {
+        self.config == *config
+            && self
+                .sources
+                .iter()
+                .all(|(path, modified_then)| modified(path) == *modified_then)
+    }
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+impl GatewayCommand {
+    pub async fn run(self) -> Result<()> {
+        let gateway = Arc::new(Gateway::<TriggerFactors>::new(self.address));
+        let mut loaded = HashMap::new();
+
+        let config = GatewayConfig::from_file(&self.config)?;
+        ensure!(
+            !config.apps.is_empty(),
+            "{} lists no apps",
+            quoted_path(&self.config)
+        );
+        for app in &config.apps {
+            let route = app.route()?;
+            let (server, loaded_app) = app
+                .load(self.address)
+                .await
+                .with_context(|| format!("failed to load app {:?}", app.name))?;
+            gateway.insert_app(&app.name, route, server)?;
+            loaded.insert(app.name.clone(), loaded_app);
+            println!(
+                "Serving app {:?} on http://{}{}{}",
+                app.name,
+                self.address,
+                app.path_prefix.trim_end_matches('/'),
+                match app.hosts.as_slice() {
+                    [] => String::new(),
+                    hosts => format!(" for {}", hosts.join(", ")),
+                }
+            );
+        }
+
+        // Served from its own task so requests are accepted during reloads.
+        let mut serve = tokio::spawn(gateway.clone().serve());
+        loop {
+            tokio::select! {
+                result = &mut serve => return result?,
+                _ = tokio::signal::ctrl_c() => return Ok(()),
+                _ = hangup() => self.reload(&gateway, &mut loaded).await,
+            }
+        }
+    }
+
+    /// Rereads the configuration file, reloading the applications whose
+    /// configuration or files have changed. An application which fails to
+    /// reload keeps running its previous version.
+    async fn reload(
+        &self,
+        gateway: &Gateway<TriggerFactors>,
+        loaded: &mut HashMap<String, LoadedApp>,
+    ) {
+        let config = match GatewayConfig::from_file(&self.config) {
+            Ok(config) => config,
+            Err(err) => {
+                tracing::error!("Not reloading: {err:#}");
+                return;
+            }
+        };
+        let names = config
+            .apps
+            .iter()
+            .map(|app| app.name.as_str())
+            .collect::<HashSet<_>>();
+        loaded.retain(|name, _| {
+            let keep = names.contains(name.as_str());
+            if !keep {
+                gateway.remove_app(name);
+                tracing::info!("Removed app {name:?}");
+            }
+            keep
+        });
+        for app in &config.apps {
+            if loaded
+                .get(&app.name)
+                .is_some_and(|loaded| loaded.is_current(app))
+            {
+                continue;
+            }
+            let result = async {
+                let route = app.route()?;
+                let (server, loaded_app) = app.load(gateway.listen_addr()).await?;
+                gateway.insert_app(&app.name, route, server)?;
+                anyhow::Ok(loaded_app)
+            }
+            .await;
+            match result {
+                Ok(loaded_app) => {
+                    loaded.insert(app.name.clone(), loaded_app);
+                    tracing::info!("Reloaded app {:?}", app.name);
+                }
+                Err(err) => tracing::error!("Failed to reload app {:?}: {err:#}", app.name),
+            }
+        }
+    }
+}
+
+/// Waits for the process to be sent SIGHUP.
+#[cfg(unix)]
+async fn hangup() {
+    use tokio::signal::unix::{SignalKind, signal};
+    match signal(SignalKind::hangup()) {
+        Ok(mut signal) => {
+            signal.recv().await;
+        }
+        Err(err) => {
+            tracing::warn!("Cannot reload on SIGHUP: {err}");
+            std::future::pending().await
+        }
+    }
+}
+
+/// Waits forever, as there is no SIGHUP to reload on.
+#[cfg(not(unix))]
+async fn hangup() {
+    std::future::pending().await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn config_paths_are_relative_to_the_file() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("gateway.toml");
+        std::fs::write(
+            &path,
+            r#"
+            [[app]]
+            name = "blog"
+            manifest = "blog/spin.toml"
+            hosts = ["blog.example.com"]
+
+            [[app]]
+            name = "api"
+            locked = "/srv/api/spin.lock"
+            path_prefix = "/api"
+            "#,
+        )?;
+        let config = GatewayConfig::from_file(&path)?;
+        assert_eq!(
+            Some(dir.path().join("blog/spin.toml")),
+            config.apps[0].manifest
+        );
+        assert_eq!("/", config.apps[0].path_prefix);
+        assert_eq!(
+            Some(PathBuf::from("/srv/api/spin.lock")),
+            config.apps[1].locked
+        );
+        assert_eq!("/api", config.apps[1].path_prefix);
+        Ok(())
+    }
+
+    #[test]
+    fn config_apps_are_validated() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("gateway.toml");
+        for invalid in [
+            // Neither manifest nor locked
+            "[[app]]\nname = \"a\"\n",
+            // Both manifest and locked
+            "[[app]]\nname = \"a\"\nmanifest = \"spin.toml\"\nlocked = \"spin.lock\"\n",
+            // Duplicate names
+            "[[app]]\nname = \"a\"\nmanifest = \"a.toml\"\n[[app]]\nname = \"a\"\nmanifest = \"b.toml\"\n",
+        ] {
+            std::fs::write(&path, invalid)?;
+            assert!(GatewayConfig::from_file(&path).is_err(), "{invalid}");
+        }
+        Ok(())
+    }
+}
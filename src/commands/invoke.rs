@@ -0,0 +1,232 @@
+use std::{io::Write as _, path::PathBuf};
+
+use anyhow::{Context, Result, bail};
+use clap::Parser;
+use http_body_util::BodyExt;
+use spin_app::App;
+use spin_common::ui::quoted_path;
+use spin_runtime_factors::{FactorsBuilder, TriggerAppArgs};
+use spin_trigger::cli::{FactorsConfig, TriggerAppBuilder, UserProvidedPath};
+use spin_trigger_http::{HttpTrigger, HttpTriggerConfig};
+use spin_trigger_redis::RedisTrigger;
+
+use crate::opts::APP_MANIFEST_FILE_OPT;
+
+/// Invoke a component once with a synthesized trigger event, print its
+/// response, and exit.
+#[derive(Parser, Debug)]
+#[clap(about = "Invoke a component once and print its response")]
+pub struct InvokeCommand {
+    /// The component to invoke.
+    #[clap(value_name = "COMPONENT")]
+    pub component_id: String,
+
+    /// The application containing the component. This may be a manifest
+    /// (spin.toml) file, or a directory containing a spin.toml file.
+    /// If omitted, it defaults to "spin.toml".
+    #[clap(
+        name = APP_MANIFEST_FILE_OPT,
+        short = 'f',
+        long = "from",
+        alias = "file"
+    )]
+    pub app_source: Option<PathBuf>,
+
+    /// The HTTP request method.
+    #[clap(short = 'X', long = "method", default_value = "GET")]
+    pub method: String,
+
+    /// The HTTP request path and query.
+    #[clap(long = "path", default_value = "/")]
+    pub path: String,
+
+    /// An HTTP request header, as `NAME: VALUE`. Can be used multiple times.
+    #[clap(short = 'H', long = "header", value_parser = parse_header)]
+    pub headers: Vec<(String, String)>,
+
+    /// The HTTP request body or Redis message payload. To read it from a
+    /// file, prefix the filename with @, or use @- to read standard input.
+    #[clap(short = 'd', long = "data")]
+    pub data: Option<String>,
+
+    /// Print the HTTP response status and headers as well as the body.
+    #[clap(short = 'i', long = "include")]
+    pub include: bool,
+
+    /// Configuration file for config providers and wasmtime config.
+    #[clap(long = "runtime-config-file")]
+    pub runtime_config_file: Option<PathBuf>,
+
+    #[clap(flatten)]
+    pub app_args: TriggerAppArgs,
+}
+
+impl InvokeCommand {
+    pub async fn run(self) -> Result<()> {
+        let (manifest_file, _) =
+            spin_common::paths::find_manifest_file_path(self.app_source.as_ref())?;
+        let app_dir = manifest_file
+            .parent()
+            .context("manifest file has no parent directory")?;
+        let data = self.read_data()?;
+
+        // Files are mounted directly, but the working directory may still be
+        // written to, e.g. for transient state.
+        let working_dir = tempfile::tempdir()?;
+        let locked_app = spin_loader::from_file(
+            &manifest_file,
+            spin_loader::FilesMountStrategy::Direct,
+            None,
+            None,
+        )
+        .await?;
+        let app = App::new("invoke", locked_app);
+
+        let Some(trigger_type) = app
+            .triggers()
+            .find(|trigger| {
+                trigger
+                    .component()
+                    .is_ok_and(|component| component.id() == self.component_id)
+            })
+            .map(|trigger| trigger.trigger_type().to_owned())
+        else {
+            bail!(
+                "no trigger in {} invokes component {:?}",
+                quoted_path(&manifest_file),
+                self.component_id
+            );
+        };
+
+        let factors_config = FactorsConfig {
+            working_dir: working_dir.path().to_owned(),
+            runtime_config_file: self.runtime_config_file.clone(),
+            state_dir: UserProvidedPath::Default,
+            local_app_dir: Some(app_dir.to_string_lossy().into_owned()),
+            app_id: app
+                .get_metadata(spin_app::APP_ORIGIN_KEY)?
+                .and_then(|origin: String| spin_common::app_state::app_id_from_origin(&origin)),
+            ..Default::default()
+        };
+        let loader = spin_trigger::loader::ComponentLoader::new();
+
+        match trigger_type.as_str() {
+            "http" => {
+                let trigger =
+                    HttpTrigger::new(&app, HttpTriggerConfig::new("127.0.0.1:3000".parse()?))?;
+                let mut builder = TriggerAppBuilder::<_, FactorsBuilder>::new(trigger);
+                let trigger_app = builder
+                    .build(app, factors_config, self.app_args, &loader)
+                    .await?;
+                let server = builder.trigger.into_server(trigger_app)?;
+
+                let mut req = http::Request::builder()
+                    .method(self.method.as_str())
+                    .uri(&self.path);
+                for (name, value) in &self.headers {
+                    req = req.header(name, value);
+                }
+                if !self
+                    .headers
+                    .iter()
+                    .any(|(name, _)| name.eq_ignore_ascii_case("host"))
+                {
+                    req = req.header(http::header::HOST, "localhost:3000");
+                }
+                let req = req
+                    .body(spin_http::body::full(data.into()))
+                    .context("invalid HTTP request")?;
+
+                let route_match = spin_http::routes::RouteMatch::synthetic(
+                    self.component_id.clone(),
+                    req.uri().path().to_owned(),
+                );
+                let resp = server
+                    .handle_trigger_route(
+                        req,
+                        route_match,
+                        http::uri::Scheme::HTTP,
+                        (std::net::Ipv4Addr::LOCALHOST, 0).into(),
+                    )
+                    .await?;
+
+                let (parts, body) = resp.into_parts();
+                let mut stdout = std::io::stdout().lock();
+                if self.include {
+                    writeln!(stdout, "{:?} {}", parts.version, parts.status)?;
+                    for (name, value) in &parts.headers {
+                        writeln!(
+                            stdout,
+                            "{name}: {}",
+                            String::from_utf8_lossy(value.as_bytes())
+                        )?;
+                    }
+                    writeln!(stdout)?;
+                }
+                let body = body
+                    .collect()
+                    .await
+                    .context("failed to read response body")?
+                    .to_bytes();
+                stdout.write_all(&body)?;
+                stdout.flush()?;
+            }
+            "redis" => {
+                let mut builder = TriggerAppBuilder::<_, FactorsBuilder>::new(RedisTrigger);
+                let trigger_app = builder
+                    .build(app, factors_config, self.app_args, &loader)
+                    .await?;
+                spin_trigger_redis::handle_message_payload(&trigger_app, &self.component_id, data)
+                    .await?;
+                eprintln!("Component {:?} handled the message", self.component_id);
+            }
+            other => bail!(
+                "component {:?} has a {other:?} trigger, which `spin invoke` does not support",
+                self.component_id
+            ),
+        }
+        Ok(())
+    }
+
+    fn read_data(&self) -> Result<Vec<u8>> {
+        match self.data.as_deref() {
+            None => Ok(vec![]),
+            Some("@-") => {
+                let mut data = vec![];
+                std::io::Read::read_to_end(&mut std::io::stdin(), &mut data)
+                    .context("failed to read data from standard input")?;
+                Ok(data)
+            }
+            Some(data) => match data.strip_prefix('@') {
+                Some(path) => std::fs::read(path)
+                    .with_context(|| format!("failed to read {}", quoted_path(path))),
+                None => Ok(data.as_bytes().to_vec()),
+            },
+        }
+    }
+}
+
+fn parse_header(header: &str) -> Result<(String, String)> {
+    let (name, value) = header
+        .split_once(':')
+        .with_context(|| format!("expected `NAME: VALUE`; got {header:?}"))?;
+    Ok((name.trim().to_owned(), value.trim().to_owned()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn headers_are_parsed() {
+        assert_eq!(
+            ("Content-Type".to_owned(), "application/json".to_owned()),
+            parse_header("Content-Type: application/json").unwrap()
+        );
+        assert_eq!(
+            ("x-token".to_owned(), "a:b".to_owned()),
+            parse_header("x-token:a:b").unwrap()
+        );
+        assert!(parse_header("no-colon").is_err());
+    }
+}
@@ -0,0 +1,219 @@
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::SystemTime;
+
+use anyhow::{Context, Result, bail};
+use clap::{Args, Parser, Subcommand};
+use comfy_table::Table;
+use spin_factor_jobs::{DEFAULT_KEY_VALUE_STORE, Job, JobQueue, millis_since_epoch};
+use spin_factor_key_value::DelegatingStoreManager;
+use spin_runtime_config::ResolvedRuntimeConfig;
+use spin_runtime_factors::TriggerFactorsRuntimeConfig;
+use spin_trigger::cli::UserProvidedPath;
+
+use crate::opts::APP_MANIFEST_FILE_OPT;
+
+/// Commands for inspecting an application's background jobs and scheduled
+/// invocations.
+#[derive(Subcommand, Debug)]
+pub enum JobsCommands {
+    /// List the jobs and scheduled invocations waiting to run.
+    List(ListCommand),
+    /// Cancel a job or scheduled invocation which is waiting to run.
+    Cancel(CancelCommand),
+}
+
+impl JobsCommands {
+    pub async fn run(self) -> Result<()> {
+        match self {
+            JobsCommands::List(cmd) => cmd.run().await,
+            JobsCommands::Cancel(cmd) => cmd.run().await,
+        }
+    }
+}
+
+/// Locates an application's job queue.
+#[derive(Args, Debug)]
+pub struct QueueOptions {
+    /// The application whose jobs to inspect. This may be a manifest
+    /// (spin.toml) file, or a directory containing a spin.toml file.
+    /// If omitted, it defaults to "spin.toml".
+    #[clap(
+        name = APP_MANIFEST_FILE_OPT,
+        short = 'f',
+        long = "from",
+        alias = "file"
+    )]
+    pub app_source: Option<PathBuf>,
+
+    /// The runtime config file the application is run with, if it configures
+    /// the key-value store holding jobs.
+    #[clap(long = "runtime-config-file")]
+    pub runtime_config_file: Option<PathBuf>,
+
+    /// The state directory the application is run with, if not the default.
+    #[clap(long = "state-dir")]
+    pub state_dir: Option<PathBuf>,
+}
+
+impl QueueOptions {
+    /// Opens the job queue in the key-value store the application is
+    /// configured to hold it in.
+    fn queue(&self) -> Result<JobQueue> {
+        let (manifest_file, distance) =
+            spin_common::paths::find_manifest_file_path(self.app_source.as_ref())?;
+        crate::directory_rels::notify_if_nondefault_rel(&manifest_file, distance);
+        let app_dir = spin_common::paths::parent_dir(&manifest_file)?;
+        let app_id = spin_common::app_state::app_id(&manifest_file)?;
+
+        let state_dir = match &self.state_dir {
+            Some(dir) => UserProvidedPath::Provided(dir.clone()),
+            None => UserProvidedPath::Default,
+        };
+        let resolved = ResolvedRuntimeConfig::<TriggerFactorsRuntimeConfig>::from_file(
+            self.runtime_config_file.as_deref(),
+            Some(app_dir),
+            Some(app_id),
+            state_dir,
+            UserProvidedPath::Unset,
+        )
+        .context("failed to resolve runtime config")?;
+        let runtime_config = resolved.runtime_config;
+        let label = runtime_config
+            .jobs
+            .map(|config| config.key_value_store)
+            .unwrap_or_else(|| DEFAULT_KEY_VALUE_STORE.into());
+        let store_manager = Arc::new(DelegatingStoreManager::new(
+            runtime_config.key_value.unwrap_or_default(),
+        ));
+        let queue = JobQueue::new(store_manager, label);
+        if !queue.is_defined() {
+            bail!(
+                "the key-value store {:?} for jobs is not defined",
+                queue.label()
+            );
+        }
+        Ok(queue)
+    }
+}
+
+#[derive(Parser, Debug)]
+pub struct ListCommand {
+    #[clap(flatten)]
+    pub queue: QueueOptions,
+
+    /// List the jobs which ran out of attempts instead.
+    #[clap(long = "dead")]
+    pub dead: bool,
+}
+
+impl ListCommand {
+    pub async fn run(self) -> Result<()> {
+        let queue = self.queue.queue()?;
+        let jobs = if self.dead {
+            queue.dead_letters().await?
+        } else {
+            queue.pending().await?
+        };
+        if jobs.is_empty() {
+            if self.dead {
+                println!("No jobs have run out of attempts");
+            } else {
+                println!("No jobs are waiting to run");
+            }
+            return Ok(());
+        }
+
+        let now = millis_since_epoch(SystemTime::now());
+        let mut table = Table::new();
+        if self.dead {
+            table.set_header(vec!["ID", "Job", "Attempts", "Last error"]);
+        } else {
+            table.set_header(vec!["ID", "Job", "Due", "Failed attempts"]);
+        }
+        table.load_preset(comfy_table::presets::ASCII_BORDERS_ONLY_CONDENSED);
+        for job in &jobs {
+            let row = if self.dead {
+                vec![
+                    job.id.clone(),
+                    describe(job),
+                    job.attempts.to_string(),
+                    job.last_error.clone().unwrap_or_default(),
+                ]
+            } else {
+                vec![
+                    job.id.clone(),
+                    describe(job),
+                    format_due(job.run_at_ms, now),
+                    job.attempts.to_string(),
+                ]
+            };
+            table.add_row(row);
+        }
+        println!("{table}");
+        Ok(())
+    }
+}
+
+#[derive(Parser, Debug)]
+pub struct CancelCommand {
+    #[clap(flatten)]
+    pub queue: QueueOptions,
+
+    /// The ID of the job or scheduled invocation to cancel, as shown by
+    /// `spin jobs list`.
+    pub id: String,
+}
+
+impl CancelCommand {
+    pub async fn run(self) -> Result<()> {
+        let queue = self.queue.queue()?;
+        if !queue.cancel(&self.id).await? {
+            bail!("No job with ID '{}' is waiting to run", self.id);
+        }
+        println!("Cancelled job '{}'", self.id);
+        Ok(())
+    }
+}
+
+/// Describes what a job runs.
+fn describe(job: &Job) -> String {
+    match (&job.component, &job.scheduled_by) {
+        (Some(component), Some(by)) => format!("{component} (scheduled by {by})"),
+        (Some(component), None) => component.clone(),
+        _ => job.name.clone(),
+    }
+}
+
+/// Describes when a job is due, relative to `now`.
+fn format_due(run_at_ms: u64, now_ms: u64) -> String {
+    let Some(wait) = run_at_ms.checked_sub(now_ms).filter(|ms| *ms > 0) else {
+        return "now".into();
+    };
+    let secs = wait.div_ceil(1000);
+    let (days, hours, mins) = (secs / 86400, secs / 3600 % 24, secs / 60 % 60);
+    if days > 0 {
+        format!("in {days}d {hours}h")
+    } else if hours > 0 {
+        format!("in {hours}h {mins}m")
+    } else if mins > 0 {
+        format!("in {mins}m {}s", secs % 60)
+    } else {
+        format!("in {secs}s")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn due_times_are_relative() {
+        assert_eq!("now", format_due(1_000, 2_000));
+        assert_eq!("now", format_due(2_000, 2_000));
+        assert_eq!("in 1s", format_due(2_001, 2_000));
+        assert_eq!("in 1m 30s", format_due(90_000, 0));
+        assert_eq!("in 2h 0m", format_due(2 * 3_600_000, 0));
+        assert_eq!("in 3d 1h", format_due(3 * 86_400_000 + 3_600_000, 0));
+    }
+}
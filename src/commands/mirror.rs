@@ -0,0 +1,124 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result, bail};
+use clap::Args;
+use tempfile::TempDir;
+
+/// Media type of the config of a mirror pushed to an OCI registry.
+const MIRROR_CONFIG_MEDIA_TYPE: &str = "application/vnd.fermyon.spin.mirror.v1+config";
+
+const MIRROR_OUTPUT_OPT: &str = "MIRROR_OUTPUT";
+const MIRROR_PUSH_OPT: &str = "MIRROR_PUSH";
+
+/// Where to write a mirror.
+#[derive(Args, Debug)]
+pub struct MirrorOutput {
+    /// The directory to write the mirror to. Existing content for the
+    /// mirrored items is replaced.
+    #[clap(
+        name = MIRROR_OUTPUT_OPT,
+        long = "output",
+        short = 'o',
+        required_unless_present = MIRROR_PUSH_OPT,
+        conflicts_with = MIRROR_PUSH_OPT,
+    )]
+    pub output: Option<PathBuf>,
+
+    /// A registry reference to push the mirror to as an OCI artifact, e.g.
+    /// registry.example.com/spin/mirror:latest.
+    #[clap(name = MIRROR_PUSH_OPT, long = "push")]
+    pub push: Option<String>,
+
+    /// Ignore server certificate errors when pushing the mirror.
+    #[clap(short = 'k', long = "insecure", requires = MIRROR_PUSH_OPT)]
+    pub insecure: bool,
+}
+
+/// A directory into which a mirror is being exported.
+pub struct MirrorTarget {
+    dir: PathBuf,
+    _temp_dir: Option<TempDir>,
+}
+
+impl MirrorTarget {
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+}
+
+impl MirrorOutput {
+    /// Gets the directory to export the mirror into: either the output
+    /// directory, or a staging directory for pushing.
+    pub fn target(&self) -> Result<MirrorTarget> {
+        let (dir, temp_dir) = match &self.output {
+            Some(dir) => (dir.clone(), None),
+            None => {
+                let temp_dir = tempfile::tempdir()?;
+                (temp_dir.path().join("mirror"), Some(temp_dir))
+            }
+        };
+        std::fs::create_dir_all(&dir)
+            .with_context(|| format!("Failed to create directory {}", dir.display()))?;
+        Ok(MirrorTarget {
+            dir,
+            _temp_dir: temp_dir,
+        })
+    }
+
+    /// Pushes the exported mirror to the registry, if requested, and reports
+    /// where the mirror was written.
+    pub async fn finish(&self, target: MirrorTarget) -> Result<()> {
+        match &self.push {
+            Some(reference) => {
+                let mut client = spin_oci::Client::new(self.insecure, None).await?;
+                let digest = client
+                    .push_directory(target.dir(), reference, MIRROR_CONFIG_MEDIA_TYPE)
+                    .await?;
+                match digest {
+                    Some(digest) => println!("Pushed mirror to {reference} with digest {digest}"),
+                    None => println!("Pushed mirror to {reference}"),
+                }
+            }
+            None => println!("Wrote mirror to {}", target.dir().display()),
+        }
+        Ok(())
+    }
+}
+
+/// A mirror available as a local directory.
+pub struct LocalMirror {
+    dir: PathBuf,
+    _temp_dir: Option<TempDir>,
+}
+
+impl LocalMirror {
+    /// Makes the mirror at `location`, which may be a directory or a registry
+    /// reference, available as a local directory.
+    pub async fn get(location: &str, insecure: bool) -> Result<Self> {
+        let path = Path::new(location);
+        if path.is_dir() {
+            return Ok(Self {
+                dir: path.to_owned(),
+                _temp_dir: None,
+            });
+        }
+        if !spin_oci::is_probably_oci_reference(location) {
+            bail!("Mirror {location} is not a directory or a registry reference");
+        }
+
+        let temp_dir = tempfile::tempdir()?;
+        let mut client = spin_oci::Client::new(insecure, None).await?;
+        client
+            .pull_directory(location, MIRROR_CONFIG_MEDIA_TYPE, temp_dir.path())
+            .await
+            .with_context(|| format!("Failed to pull mirror from {location}"))?;
+        Ok(Self {
+            dir: temp_dir.path().to_owned(),
+            _temp_dir: Some(temp_dir),
+        })
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+}
@@ -0,0 +1,390 @@
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+use anyhow::{Context, Result, bail};
+use clap::{Parser, Subcommand};
+use serde::Deserialize;
+use serde_json::{Map, Value, json};
+use spin_common::ui::quoted_path;
+use spin_http::routes::HttpTriggerRouteConfig;
+use spin_manifest::schema::v2::{AppManifest, Component, ComponentSpec};
+
+use crate::opts::APP_MANIFEST_FILE_OPT;
+
+/// The name of the component tool table holding OpenAPI annotations,
+/// `[component.<id>.tool.openapi]`.
+const TOOL_NAME: &str = "openapi";
+/// The HTTP methods an OpenAPI path item may describe.
+const METHODS: &[&str] = &[
+    "get", "put", "post", "delete", "options", "head", "patch", "trace",
+];
+/// The name of the path parameter standing for a trailing wildcard.
+const TRAILING_WILDCARD_PARAM: &str = "rest";
+
+/// Commands for describing an application's HTTP API in OpenAPI.
+#[derive(Subcommand, Debug)]
+pub enum OpenApiCommands {
+    /// Generate an OpenAPI 3.1 document from the application's HTTP routes.
+    ///
+    /// Each route becomes a path, with a path parameter for each wildcard.
+    /// Operations, and anything else the document should say about them,
+    /// come from `[component.<id>.tool.openapi]`:
+    ///
+    /// - `methods`: the methods the component handles, e.g. `["get", "post"]`
+    ///
+    /// - `operation`: fields of the OpenAPI Operation object added to every
+    ///   method, e.g. `{ tags = ["users"] }`
+    ///
+    /// - `operations.<method>`: fields added to a single method, overriding
+    ///   `operation`
+    ///
+    /// - `schemas`: schemas added to the document's `components.schemas`
+    Generate(GenerateCommand),
+}
+
+impl OpenApiCommands {
+    pub async fn run(self) -> Result<()> {
+        match self {
+            OpenApiCommands::Generate(cmd) => cmd.run().await,
+        }
+    }
+}
+
+#[derive(Parser, Debug)]
+pub struct GenerateCommand {
+    /// The application to describe. This may be a manifest (spin.toml) file,
+    /// or a directory containing a spin.toml file. If omitted, it defaults to
+    /// "spin.toml".
+    #[clap(
+        name = APP_MANIFEST_FILE_OPT,
+        short = 'f',
+        long = "from",
+        alias = "file"
+    )]
+    pub app_source: Option<PathBuf>,
+
+    /// The file to write the document to. If omitted, it is printed.
+    #[clap(short = 'o', long = "output")]
+    pub output: Option<PathBuf>,
+}
+
+impl GenerateCommand {
+    pub async fn run(self) -> Result<()> {
+        let (manifest_file, distance) =
+            spin_common::paths::find_manifest_file_path(self.app_source.as_ref())?;
+        crate::directory_rels::notify_if_nondefault_rel(&manifest_file, distance);
+        let manifest = spin_manifest::manifest_from_file(&manifest_file)?;
+        let document = serde_json::to_string_pretty(&generate(&manifest)?)?;
+        match &self.output {
+            Some(path) => std::fs::write(path, document + "\n")
+                .with_context(|| format!("failed to write {}", quoted_path(path)))?,
+            None => println!("{document}"),
+        }
+        Ok(())
+    }
+}
+
+/// The OpenAPI annotations of a component.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct Annotations {
+    methods: Vec<String>,
+    operation: Map<String, Value>,
+    operations: BTreeMap<String, Map<String, Value>>,
+    schemas: Map<String, Value>,
+}
+
+impl Annotations {
+    fn of(component: &Component) -> Result<Self> {
+        let Some(table) = component.tool.get(TOOL_NAME) else {
+            return Ok(Self::default());
+        };
+        let annotations: Self = toml::Value::Table(table.clone()).try_into()?;
+        for method in annotations.methods() {
+            if !METHODS.contains(&method) {
+                bail!("{method:?} is not an HTTP method OpenAPI describes");
+            }
+        }
+        Ok(annotations)
+    }
+
+    /// The declared methods, in the order OpenAPI lists them.
+    fn methods(&self) -> Vec<&str> {
+        let mut methods = self
+            .methods
+            .iter()
+            .chain(self.operations.keys())
+            .map(String::as_str)
+            .collect::<Vec<_>>();
+        methods.sort_by_key(|m| METHODS.iter().position(|known| known == m));
+        methods.dedup();
+        methods
+    }
+}
+
+/// Generates an OpenAPI document describing the HTTP routes of an app.
+fn generate(manifest: &AppManifest) -> Result<Value> {
+    let mut paths = Map::new();
+    let mut schemas = Map::new();
+    for trigger in manifest.triggers.get("http").into_iter().flatten() {
+        let route: HttpTriggerRouteConfig = trigger
+            .config
+            .get("route")
+            .cloned()
+            .context("an HTTP trigger has no route")?
+            .try_into()
+            .context("invalid HTTP trigger route")?;
+        let HttpTriggerRouteConfig::Route(route) = route else {
+            // Private endpoints are not reachable over HTTP.
+            continue;
+        };
+        let (path, params) = openapi_path(&route);
+
+        let mut path_item = Map::new();
+        if !params.is_empty() {
+            path_item.insert("parameters".into(), params.into());
+        }
+        let (component_id, component) = match &trigger.component {
+            Some(ComponentSpec::Reference(id)) => {
+                let component = manifest
+                    .components
+                    .get(id)
+                    .with_context(|| format!("route {route:?} uses undefined component {id:?}"))?;
+                (Some(id.to_string()), Some(component))
+            }
+            Some(ComponentSpec::Inline(component)) => (None, Some(component.as_ref())),
+            None => (None, None),
+        };
+        if let Some(id) = &component_id {
+            path_item.insert("x-spin-component".into(), id.clone().into());
+        }
+        if let Some(component) = component {
+            if !component.description.is_empty() {
+                path_item.insert("description".into(), component.description.clone().into());
+            }
+            let annotations = Annotations::of(component).with_context(|| {
+                format!(
+                    "invalid `tool.{TOOL_NAME}` annotations for the component of route {route:?}"
+                )
+            })?;
+            for method in annotations.methods() {
+                let mut operation = Value::Object(annotations.operation.clone());
+                if let Some(overrides) = annotations.operations.get(method) {
+                    merge(&mut operation, Value::Object(overrides.clone()));
+                }
+                path_item.insert(method.into(), operation);
+            }
+            schemas.extend(annotations.schemas);
+        }
+        paths.insert(path, path_item.into());
+    }
+
+    let app = &manifest.application;
+    let mut info = json!({
+        "title": app.name,
+        "version": if app.version.is_empty() { "0.0.0" } else { app.version.as_str() },
+    });
+    if !app.description.is_empty() {
+        info["description"] = app.description.clone().into();
+    }
+    let mut document = json!({
+        "openapi": "3.1.0",
+        "info": info,
+        "paths": paths,
+    });
+    if !schemas.is_empty() {
+        document["components"] = json!({ "schemas": schemas });
+    }
+    Ok(document)
+}
+
+/// Converts a Spin route to an OpenAPI path, and the parameters of the path.
+///
+/// Named wildcards (`:id`) become parameters of the same name, and a trailing
+/// wildcard (`/...`) a parameter matching the rest of the path.
+fn openapi_path(route: &str) -> (String, Vec<Value>) {
+    let (route, trailing) = match route
+        .strip_suffix("/...")
+        .or_else(|| route.strip_suffix("/*"))
+    {
+        Some(prefix) => (prefix, true),
+        None if route == "..." || route == "*" => ("", true),
+        None => (route, false),
+    };
+    let mut path = String::new();
+    let mut params = vec![];
+    for segment in route.split('/').filter(|s| !s.is_empty()) {
+        path.push('/');
+        match segment.strip_prefix(':') {
+            Some(name) => {
+                path.push_str(&format!("{{{name}}}"));
+                params.push(json!({
+                    "name": name,
+                    "in": "path",
+                    "required": true,
+                    "schema": { "type": "string" },
+                }));
+            }
+            None => path.push_str(segment),
+        }
+    }
+    if trailing {
+        path.push_str(&format!("/{{{TRAILING_WILDCARD_PARAM}}}"));
+        params.push(json!({
+            "name": TRAILING_WILDCARD_PARAM,
+            "in": "path",
+            "required": true,
+            "description": "The rest of the path, which may span several segments.",
+            "schema": { "type": "string" },
+        }));
+    } else if path.is_empty() {
+        path.push('/');
+    }
+    (path, params)
+}
+
+/// Merges `overrides` into `base`, recursing into objects and replacing
+/// everything else.
+fn merge(base: &mut Value, overrides: Value) {
+    match (base, overrides) {
+        (Value::Object(base), Value::Object(overrides)) => {
+            for (key, value) in overrides {
+                match base.get_mut(&key) {
+                    Some(existing) => merge(existing, value),
+                    None => {
+                        base.insert(key, value);
+                    }
+                }
+            }
+        }
+        (base, overrides) => *base = overrides,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn param_names(params: &[Value]) -> Vec<&str> {
+        params.iter().map(|p| p["name"].as_str().unwrap()).collect()
+    }
+
+    #[test]
+    fn routes_become_openapi_paths() {
+        let (path, params) = openapi_path("/users/:id/posts/:post");
+        assert_eq!("/users/{id}/posts/{post}", path);
+        assert_eq!(vec!["id", "post"], param_names(&params));
+
+        let (path, params) = openapi_path("/static/...");
+        assert_eq!("/static/{rest}", path);
+        assert_eq!(vec!["rest"], param_names(&params));
+
+        assert_eq!("/{rest}", openapi_path("/...").0);
+        assert_eq!("/", openapi_path("/").0);
+        assert_eq!("/health", openapi_path("/health").0);
+    }
+
+    #[test]
+    fn overrides_are_merged_deeply() {
+        let mut base = json!({ "tags": ["a"], "responses": { "200": { "description": "ok" } } });
+        merge(
+            &mut base,
+            json!({ "tags": ["b"], "responses": { "404": { "description": "missing" } } }),
+        );
+        assert_eq!(
+            json!({
+                "tags": ["b"],
+                "responses": {
+                    "200": { "description": "ok" },
+                    "404": { "description": "missing" },
+                },
+            }),
+            base
+        );
+    }
+
+    #[test]
+    fn document_describes_routes_and_annotations() -> Result<()> {
+        let manifest = spin_manifest::manifest_from_str(
+            r#"
+            spin_manifest_version = 2
+
+            [application]
+            name = "users"
+            version = "1.2.0"
+
+            [[trigger.http]]
+            route = "/users/:id"
+            component = "users"
+
+            [[trigger.http]]
+            route = "/assets/..."
+            component = "assets"
+
+            [[trigger.http]]
+            route = { private = true }
+            component = "users"
+
+            [component.users]
+            source = "users.wasm"
+            description = "Manages users"
+            [component.users.tool.openapi]
+            methods = ["put", "get"]
+            operation = { tags = ["users"] }
+            operations.get = { summary = "Get a user" }
+            schemas.User = { type = "object" }
+
+            [component.assets]
+            source = "assets.wasm"
+            "#,
+        )?;
+        let document = generate(&manifest)?;
+
+        assert_eq!("3.1.0", document["openapi"]);
+        assert_eq!("users", document["info"]["title"]);
+        assert_eq!("1.2.0", document["info"]["version"]);
+
+        let paths = document["paths"].as_object().unwrap();
+        assert_eq!(2, paths.len());
+        let users = &paths["/users/{id}"];
+        assert_eq!("users", users["x-spin-component"]);
+        assert_eq!("Manages users", users["description"]);
+        assert_eq!("id", users["parameters"][0]["name"]);
+        assert_eq!(json!({ "tags": ["users"] }), users["put"]);
+        assert_eq!(
+            json!({ "tags": ["users"], "summary": "Get a user" }),
+            users["get"]
+        );
+        assert_eq!(
+            json!({ "type": "object" }),
+            document["components"]["schemas"]["User"]
+        );
+
+        let assets = &paths["/assets/{rest}"];
+        assert_eq!("assets", assets["x-spin-component"]);
+        assert!(assets.get("get").is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn unknown_methods_are_rejected() -> Result<()> {
+        let manifest = spin_manifest::manifest_from_str(
+            r#"
+            spin_manifest_version = 2
+
+            [application]
+            name = "app"
+
+            [[trigger.http]]
+            route = "/"
+            component = "web"
+
+            [component.web]
+            source = "web.wasm"
+            tool.openapi = { methods = ["fetch"] }
+            "#,
+        )?;
+        assert!(generate(&manifest).is_err());
+        Ok(())
+    }
+}
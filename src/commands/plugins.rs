@@ -14,6 +14,7 @@ use std::path::{Path, PathBuf};
 use url::Url;
 
 use crate::build_info::*;
+use crate::commands::mirror::{LocalMirror, MirrorOutput};
 use crate::opts::*;
 
 /// Install/uninstall Spin plugins.
@@ -39,10 +40,17 @@ pub enum PluginCommands {
     Upgrade(Upgrade),
 
     /// Fetch the latest Spin plugins from the spin-plugins repository.
-    Update,
+    Update(Update),
 
     /// Print information about a plugin.
     Show(Show),
+
+    /// Export installed plugins to a mirror for use without network access.
+    ///
+    /// The mirror contains the plugin manifests and packages, and can be
+    /// used with `spin plugins install --mirror` or `spin plugins update
+    /// --mirror`.
+    Mirror(Mirror),
 }
 
 impl PluginCommands {
@@ -53,8 +61,9 @@ impl PluginCommands {
             PluginCommands::Search(cmd) => cmd.run().await,
             PluginCommands::Uninstall(cmd) => cmd.run().await,
             PluginCommands::Upgrade(cmd) => cmd.run().await,
-            PluginCommands::Update => update().await,
+            PluginCommands::Update(cmd) => cmd.run().await,
             PluginCommands::Show(cmd) => cmd.run().await,
+            PluginCommands::Mirror(cmd) => cmd.run().await,
         }
     }
 }
@@ -115,10 +124,30 @@ pub struct Install {
         requires(PLUGIN_NAME_OPT)
     )]
     pub version: Option<Version>,
+
+    /// Look up the plugin in a mirror created by `spin plugins mirror`,
+    /// instead of the spin-plugins repository. This may be a directory or a
+    /// registry reference. The mirror is used for later lookups too, until
+    /// `spin plugins update --no-mirror` is run.
+    #[clap(
+        long = "mirror",
+        conflicts_with = PLUGIN_REMOTE_PLUGIN_MANIFEST_OPT,
+        conflicts_with = PLUGIN_LOCAL_PLUGIN_MANIFEST_OPT,
+        requires(PLUGIN_NAME_OPT)
+    )]
+    pub mirror: Option<String>,
+
+    /// Ignore server certificate errors when pulling the mirror.
+    #[clap(long = "mirror-insecure", requires = "mirror")]
+    pub mirror_insecure: bool,
 }
 
 impl Install {
     pub async fn run(&self) -> Result<()> {
+        if let Some(mirror) = &self.mirror {
+            use_mirror(mirror, self.mirror_insecure).await?;
+        }
+
         let manifest_location = match (
             &self.local_manifest_src,
             &self.remote_manifest_src,
@@ -836,6 +865,84 @@ fn merge_plugin_lists(a: Vec<PluginDescriptor>, b: Vec<PluginDescriptor>) -> Vec
     result
 }
 
+/// Fetch the latest Spin plugins from the spin-plugins repository.
+#[derive(Parser, Debug)]
+pub struct Update {
+    /// Fetch plugins from a mirror created by `spin plugins mirror` instead of
+    /// the spin-plugins repository. This may be a directory or a registry
+    /// reference. The mirror is used for later lookups too.
+    #[clap(long = "mirror", conflicts_with = "no_mirror")]
+    pub mirror: Option<String>,
+
+    /// Ignore server certificate errors when pulling the mirror.
+    #[clap(long = "mirror-insecure", requires = "mirror")]
+    pub mirror_insecure: bool,
+
+    /// Stop using a previously configured mirror, and fetch plugins from the
+    /// spin-plugins repository again.
+    #[clap(long = "no-mirror")]
+    pub no_mirror: bool,
+}
+
+impl Update {
+    pub async fn run(self) -> Result<()> {
+        if let Some(mirror) = &self.mirror {
+            use_mirror(mirror, self.mirror_insecure).await?;
+            println!("Plugin information updated from mirror {mirror}");
+            return Ok(());
+        }
+        if self.no_mirror {
+            let manager = PluginManager::try_default()?;
+            spin_plugins::mirror::stop_using_mirror(manager.store().get_plugins_directory())?;
+        }
+        update().await
+    }
+}
+
+/// Export installed plugins to a mirror for use without network access.
+#[derive(Parser, Debug)]
+pub struct Mirror {
+    #[clap(flatten)]
+    pub output: MirrorOutput,
+
+    /// Include the packages for all the operating systems and architectures
+    /// that each plugin supports, rather than only the current one.
+    #[clap(long = "all-platforms")]
+    pub all_platforms: bool,
+}
+
+impl Mirror {
+    pub async fn run(self) -> Result<()> {
+        let manager = PluginManager::try_default()?;
+        let target = self.output.target()?;
+        let exported =
+            spin_plugins::mirror::export(manager.store(), target.dir(), self.all_platforms).await?;
+        if exported.is_empty() {
+            println!("No plugins are installed");
+            return Ok(());
+        }
+        for manifest in &exported {
+            println!("Exported {} {}", manifest.name(), manifest.version());
+        }
+        self.output.finish(target).await
+    }
+}
+
+/// Replaces the local plugins catalogue with the contents of a mirror.
+async fn use_mirror(location: &str, insecure: bool) -> Result<()> {
+    let manager = PluginManager::try_default()?;
+
+    let mut locker = manager.update_lock().await;
+    let guard = locker.lock_updates();
+    if guard.denied() {
+        anyhow::bail!("Another plugin update operation is already in progress");
+    }
+
+    let mirror = LocalMirror::get(location, insecure).await?;
+    spin_plugins::mirror::use_mirror(manager.store().get_plugins_directory(), mirror.dir())
+        .with_context(|| format!("Failed to use plugins mirror {location}"))
+}
+
 /// Updates the locally cached spin-plugins repository, fetching the latest plugins.
 pub(crate) async fn update() -> Result<()> {
     update_silent().await?;
@@ -3,8 +3,9 @@ use anyhow::{Context, Result};
 use clap::{Parser, Subcommand};
 use indicatif::{ProgressBar, ProgressStyle};
 use spin_common::arg_parser::parse_kv;
+use spin_oci::assets::{AssetStore, DEFAULT_ASSET_STORE_THRESHOLD};
 use spin_oci::{Client, ComposeMode, client::InferPredefinedAnnotations};
-use std::{io::Read, path::PathBuf, time::Duration};
+use std::{io::Read, path::PathBuf, sync::Arc, time::Duration};
 
 /// Commands for working with OCI registries to distribute applications.
 #[derive(Subcommand, Debug)]
@@ -80,6 +81,23 @@ pub struct Push {
     /// Any existing value will be overwritten. Can be used multiple times.
     #[clap(long = "annotation", value_parser = parse_kv)]
     pub annotations: Vec<(String, String)>,
+
+    /// Upload large files from file mounts to this object storage bucket
+    /// instead of packing them into the image, e.g. s3://my-bucket/assets.
+    /// Supports s3://, gs:// and az:// URLs, with credentials taken from the
+    /// environment.
+    #[clap(long, requires = "asset_store_public_url")]
+    pub asset_store: Option<String>,
+
+    /// The public HTTP(S) URL corresponding to the --asset-store bucket
+    /// location. Spin fetches offloaded files from here when running the
+    /// application.
+    #[clap(long, requires = "asset_store")]
+    pub asset_store_public_url: Option<String>,
+
+    /// The minimum size, in bytes, of files to upload to the --asset-store.
+    #[clap(long, default_value_t = DEFAULT_ASSET_STORE_THRESHOLD)]
+    pub asset_store_threshold: u64,
 }
 
 impl Push {
@@ -101,6 +119,11 @@ impl Push {
         };
 
         let mut client = spin_oci::Client::new(self.insecure, self.cache_dir.clone()).await?;
+        if let (Some(store), Some(public_url)) = (&self.asset_store, &self.asset_store_public_url) {
+            let asset_store = AssetStore::new(store, public_url, self.asset_store_threshold)
+                .context("could not configure asset store")?;
+            client.opts.asset_store = Some(Arc::new(asset_store));
+        }
 
         let _spinner = create_dotted_spinner(2000, "Pushing app to the Registry".to_owned());
 
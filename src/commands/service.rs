@@ -0,0 +1,567 @@
+use std::{
+    ffi::OsString,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result, bail, ensure};
+use clap::{Parser, Subcommand};
+use path_absolutize::Absolutize;
+use spin_common::ui::quoted_path;
+
+use crate::opts::APP_MANIFEST_FILE_OPT;
+
+/// Commands for running an application unattended, as a launchd agent on
+/// macOS or a service on Windows.
+///
+/// The service runs `spin up` for the application, and restarts it if it
+/// fails. Its output is written to `spin.log` in the service's log directory.
+/// On Linux, use a systemd unit which runs `spin up` instead.
+#[derive(Subcommand, Debug)]
+pub enum ServiceCommands {
+    /// Register an application to be run as a service.
+    Install(InstallCommand),
+    /// Stop and unregister a service.
+    Uninstall(NameCommand),
+    /// Start a service.
+    Start(NameCommand),
+    /// Stop a service.
+    Stop(NameCommand),
+    /// Run a service, under the Windows service control manager.
+    #[clap(hide = true)]
+    Run(RunCommand),
+}
+
+impl ServiceCommands {
+    pub async fn run(self) -> Result<()> {
+        match self {
+            ServiceCommands::Install(cmd) => cmd.run(),
+            ServiceCommands::Uninstall(cmd) => platform::uninstall(&cmd.name),
+            ServiceCommands::Start(cmd) => platform::start(&cmd.name),
+            ServiceCommands::Stop(cmd) => platform::stop(&cmd.name),
+            ServiceCommands::Run(cmd) => platform::run(cmd),
+        }
+    }
+}
+
+#[derive(Parser, Debug)]
+pub struct InstallCommand {
+    /// The name of the service.
+    #[clap(value_parser = parse_service_name)]
+    pub name: String,
+
+    /// The application to run. This may be a manifest (spin.toml) file, a
+    /// directory containing a spin.toml file, or a registry reference.
+    /// If omitted, it defaults to "spin.toml".
+    #[clap(
+        name = APP_MANIFEST_FILE_OPT,
+        short = 'f',
+        long = "from",
+        alias = "file"
+    )]
+    pub app_source: Option<String>,
+
+    /// The directory to write the service's output to. Defaults to a
+    /// directory named for the service under the platform's log directory.
+    #[clap(long = "log-dir")]
+    pub log_dir: Option<PathBuf>,
+
+    /// Start the service once it is installed.
+    #[clap(long = "start")]
+    pub start: bool,
+
+    /// Further options to run `spin up` with, after `--`.
+    #[clap(last = true)]
+    pub up_args: Vec<OsString>,
+}
+
+impl InstallCommand {
+    fn run(self) -> Result<()> {
+        let app_source = match &self.app_source {
+            Some(source) if !Path::new(source).exists() => source.into(),
+            source => {
+                let (manifest, _) = spin_common::paths::find_manifest_file_path(source.as_ref())?;
+                manifest.absolutize()?.into_owned().into_os_string()
+            }
+        };
+        let log_dir = match &self.log_dir {
+            Some(dir) => dir.absolutize()?.into_owned(),
+            None => default_log_dir(&self.name)?,
+        };
+        std::fs::create_dir_all(&log_dir)
+            .with_context(|| format!("failed to create {}", quoted_path(&log_dir)))?;
+
+        let mut up_args = vec!["--from".into(), app_source];
+        up_args.extend(self.up_args);
+        let service = Service {
+            name: self.name,
+            spin: std::env::current_exe().context("failed to find the Spin executable")?,
+            up_args,
+            log_dir,
+        };
+        platform::install(&service)?;
+        println!(
+            "Installed service {:?}, logging to {}",
+            service.name,
+            quoted_path(service.log_dir.join(LOG_FILE))
+        );
+        if self.start {
+            platform::start(&service.name)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Parser, Debug)]
+pub struct NameCommand {
+    /// The name of the service.
+    #[clap(value_parser = parse_service_name)]
+    pub name: String,
+}
+
+#[derive(Parser, Debug)]
+pub struct RunCommand {
+    /// The name of the service.
+    pub name: String,
+
+    /// The directory to write the service's output to.
+    #[clap(long = "log-dir")]
+    pub log_dir: PathBuf,
+
+    /// The options to run `spin up` with.
+    #[clap(last = true)]
+    pub up_args: Vec<OsString>,
+}
+
+/// The file, in the service's log directory, its output is written to.
+const LOG_FILE: &str = "spin.log";
+
+/// How a service runs its application.
+struct Service {
+    name: String,
+    /// The Spin executable.
+    spin: PathBuf,
+    up_args: Vec<OsString>,
+    log_dir: PathBuf,
+}
+
+fn parse_service_name(name: &str) -> Result<String> {
+    ensure!(
+        !name.is_empty()
+            && name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.')),
+        "service names may only contain letters, digits, `-`, `_` and `.`"
+    );
+    Ok(name.to_owned())
+}
+
+fn default_log_dir(name: &str) -> Result<PathBuf> {
+    let base = if cfg!(target_os = "macos") {
+        dirs::home_dir().map(|home| home.join("Library").join("Logs").join("spin"))
+    } else if cfg!(windows) {
+        // Services run as LocalSystem, so log where any account can read.
+        std::env::var_os("ProgramData").map(|dir| PathBuf::from(dir).join("spin").join("logs"))
+    } else {
+        dirs::data_local_dir().map(|dir| dir.join("spin").join("logs"))
+    };
+    Ok(base
+        .context("cannot find a log directory; set one with --log-dir")?
+        .join(name))
+}
+
+#[cfg(target_os = "macos")]
+mod platform {
+    use std::process::Command;
+
+    use super::*;
+
+    /// The launchd label of a service.
+    fn label(name: &str) -> String {
+        format!("dev.spinframework.{name}")
+    }
+
+    fn plist_path(name: &str) -> Result<PathBuf> {
+        let home = dirs::home_dir().context("cannot find the home directory")?;
+        Ok(home
+            .join("Library")
+            .join("LaunchAgents")
+            .join(format!("{}.plist", label(name))))
+    }
+
+    /// The launchd domain of the user's agents.
+    fn domain() -> String {
+        format!("gui/{}", nix::unistd::getuid())
+    }
+
+    pub fn install(service: &Service) -> Result<()> {
+        let path = plist_path(&service.name)?;
+        ensure!(
+            !path.exists(),
+            "service {:?} is already installed; uninstall it first",
+            service.name
+        );
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(&path, super::plist(&label(&service.name), service))
+            .with_context(|| format!("failed to write {}", quoted_path(&path)))
+    }
+
+    pub fn uninstall(name: &str) -> Result<()> {
+        let path = plist_path(name)?;
+        ensure!(path.exists(), "service {name:?} is not installed");
+        if is_loaded(name) {
+            stop(name)?;
+        }
+        std::fs::remove_file(&path)
+            .with_context(|| format!("failed to remove {}", quoted_path(&path)))
+    }
+
+    pub fn start(name: &str) -> Result<()> {
+        let path = plist_path(name)?;
+        ensure!(path.exists(), "service {name:?} is not installed");
+        if is_loaded(name) {
+            launchctl(&["kickstart", &format!("{}/{}", domain(), label(name))])
+        } else {
+            // The agent is run at load.
+            launchctl(&["bootstrap", &domain(), &path.to_string_lossy()])
+        }
+    }
+
+    pub fn stop(name: &str) -> Result<()> {
+        ensure!(is_loaded(name), "service {name:?} is not running");
+        // Unloading the agent sends `spin up` SIGTERM, on which it stops its
+        // triggers and exits.
+        launchctl(&["bootout", &format!("{}/{}", domain(), label(name))])
+    }
+
+    pub fn run(_cmd: RunCommand) -> Result<()> {
+        bail!("`spin service run` is only used by Windows services")
+    }
+
+    fn is_loaded(name: &str) -> bool {
+        Command::new("launchctl")
+            .args(["print", &format!("{}/{}", domain(), label(name))])
+            .output()
+            .is_ok_and(|output| output.status.success())
+    }
+
+    fn launchctl(args: &[&str]) -> Result<()> {
+        let output = Command::new("launchctl")
+            .args(args)
+            .output()
+            .context("failed to run launchctl")?;
+        if !output.status.success() {
+            bail!(
+                "`launchctl {}` failed: {}",
+                args.join(" "),
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+        Ok(())
+    }
+}
+
+#[cfg(windows)]
+mod platform {
+    use std::{
+        ffi::OsStr,
+        sync::{OnceLock, mpsc},
+        time::Duration,
+    };
+
+    use command_group::CommandGroup;
+    use windows_service::{
+        define_windows_service,
+        service::{
+            ServiceAccess, ServiceControl, ServiceControlAccept, ServiceErrorControl,
+            ServiceExitCode, ServiceInfo, ServiceStartType, ServiceState, ServiceStatus,
+            ServiceType,
+        },
+        service_control_handler::{self, ServiceControlHandlerResult},
+        service_dispatcher,
+        service_manager::{ServiceManager, ServiceManagerAccess},
+    };
+
+    use super::*;
+
+    /// How long the service control manager is told stopping may take.
+    const STOP_WAIT_HINT: Duration = Duration::from_secs(10);
+
+    fn manager(access: ServiceManagerAccess) -> Result<ServiceManager> {
+        ServiceManager::local_computer(None::<&str>, access)
+            .context("failed to connect to the service control manager; installing services requires an elevated prompt")
+    }
+
+    fn open(name: &str, access: ServiceAccess) -> Result<windows_service::service::Service> {
+        manager(ServiceManagerAccess::CONNECT)?
+            .open_service(name, access)
+            .with_context(|| format!("failed to open service {name:?}"))
+    }
+
+    pub fn install(service: &Service) -> Result<()> {
+        let mut launch_arguments: Vec<OsString> = vec![
+            "service".into(),
+            "run".into(),
+            service.name.clone().into(),
+            "--log-dir".into(),
+            service.log_dir.clone().into(),
+            "--".into(),
+        ];
+        launch_arguments.extend(service.up_args.iter().cloned());
+        let info = ServiceInfo {
+            name: service.name.clone().into(),
+            display_name: format!("Spin: {}", service.name).into(),
+            service_type: ServiceType::OWN_PROCESS,
+            start_type: ServiceStartType::AutoStart,
+            error_control: ServiceErrorControl::Normal,
+            executable_path: service.spin.clone(),
+            launch_arguments,
+            dependencies: vec![],
+            account_name: None,
+            account_password: None,
+        };
+        manager(ServiceManagerAccess::CONNECT | ServiceManagerAccess::CREATE_SERVICE)?
+            .create_service(&info, ServiceAccess::CHANGE_CONFIG)
+            .with_context(|| format!("failed to create service {:?}", service.name))?;
+        Ok(())
+    }
+
+    pub fn uninstall(name: &str) -> Result<()> {
+        let service = open(
+            name,
+            ServiceAccess::QUERY_STATUS | ServiceAccess::STOP | ServiceAccess::DELETE,
+        )?;
+        if service.query_status()?.current_state != ServiceState::Stopped {
+            service.stop()?;
+        }
+        service
+            .delete()
+            .with_context(|| format!("failed to delete service {name:?}"))
+    }
+
+    pub fn start(name: &str) -> Result<()> {
+        open(name, ServiceAccess::START)?
+            .start(&[] as &[&OsStr])
+            .with_context(|| format!("failed to start service {name:?}"))
+    }
+
+    pub fn stop(name: &str) -> Result<()> {
+        open(name, ServiceAccess::STOP)?
+            .stop()
+            .with_context(|| format!("failed to stop service {name:?}"))?;
+        Ok(())
+    }
+
+    /// The service being run, set before the dispatcher calls `service_main`.
+    static SERVICE: OnceLock<RunCommand> = OnceLock::new();
+
+    define_windows_service!(ffi_service_main, service_main);
+
+    pub fn run(cmd: RunCommand) -> Result<()> {
+        let name = cmd.name.clone();
+        SERVICE
+            .set(cmd)
+            .map_err(|_| anyhow::anyhow!("service is already running"))?;
+        service_dispatcher::start(&name, ffi_service_main)
+            .context("failed to connect to the service control manager; `spin service run` is only run by Windows services")
+    }
+
+    fn service_main(_args: Vec<OsString>) {
+        let cmd = SERVICE
+            .get()
+            .expect("service should be set before dispatch");
+        if let Err(err) = run_service(cmd) {
+            tracing::error!("Service {:?} failed: {err:?}", cmd.name);
+        }
+    }
+
+    fn run_service(cmd: &RunCommand) -> Result<()> {
+        let (stop_tx, stop_rx) = mpsc::channel();
+        let status_handle =
+            service_control_handler::register(&cmd.name, move |control| match control {
+                ServiceControl::Stop | ServiceControl::Shutdown => {
+                    _ = stop_tx.send(());
+                    ServiceControlHandlerResult::NoError
+                }
+                ServiceControl::Interrogate => ServiceControlHandlerResult::NoError,
+                _ => ServiceControlHandlerResult::NotImplemented,
+            })?;
+        let set_status = |state, controls_accepted, exit_code, wait_hint| {
+            status_handle.set_service_status(ServiceStatus {
+                service_type: ServiceType::OWN_PROCESS,
+                current_state: state,
+                controls_accepted,
+                exit_code,
+                checkpoint: 0,
+                wait_hint,
+                process_id: None,
+            })
+        };
+
+        let result = (|| -> Result<ServiceExitCode> {
+            let log_path = cmd.log_dir.join(LOG_FILE);
+            let log = std::fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&log_path)
+                .with_context(|| format!("failed to open {}", quoted_path(&log_path)))?;
+            // `spin up` runs each trigger in its own process, so it is run in
+            // a process group which can be stopped as a whole.
+            let mut child = std::process::Command::new(std::env::current_exe()?)
+                .arg("up")
+                .args(&cmd.up_args)
+                .stdin(std::process::Stdio::null())
+                .stdout(log.try_clone()?)
+                .stderr(log)
+                .group_spawn()
+                .context("failed to start `spin up`")?;
+            set_status(
+                ServiceState::Running,
+                ServiceControlAccept::STOP | ServiceControlAccept::SHUTDOWN,
+                ServiceExitCode::Win32(0),
+                Duration::ZERO,
+            )?;
+            loop {
+                if stop_rx.recv_timeout(Duration::from_secs(1)).is_ok() {
+                    set_status(
+                        ServiceState::StopPending,
+                        ServiceControlAccept::empty(),
+                        ServiceExitCode::Win32(0),
+                        STOP_WAIT_HINT,
+                    )?;
+                    child.kill()?;
+                    child.wait()?;
+                    return Ok(ServiceExitCode::Win32(0));
+                }
+                if let Some(status) = child.try_wait()? {
+                    // Report failures so the service control manager's
+                    // recovery actions apply.
+                    let code = status.code().unwrap_or(1) as u32;
+                    return Ok(match code {
+                        0 => ServiceExitCode::Win32(0),
+                        code => ServiceExitCode::ServiceSpecific(code),
+                    });
+                }
+            }
+        })();
+
+        let exit_code = match &result {
+            Ok(exit_code) => exit_code.clone(),
+            Err(_) => ServiceExitCode::ServiceSpecific(1),
+        };
+        set_status(
+            ServiceState::Stopped,
+            ServiceControlAccept::empty(),
+            exit_code,
+            Duration::ZERO,
+        )?;
+        result.map(|_| ())
+    }
+}
+
+#[cfg(not(any(target_os = "macos", windows)))]
+mod platform {
+    use super::*;
+
+    fn unsupported() -> Result<()> {
+        bail!(
+            "`spin service` supports macOS and Windows; on Linux, run `spin up` from a systemd unit"
+        )
+    }
+
+    pub fn install(_service: &Service) -> Result<()> {
+        unsupported()
+    }
+
+    pub fn uninstall(_name: &str) -> Result<()> {
+        unsupported()
+    }
+
+    pub fn start(_name: &str) -> Result<()> {
+        unsupported()
+    }
+
+    pub fn stop(_name: &str) -> Result<()> {
+        unsupported()
+    }
+
+    pub fn run(_cmd: RunCommand) -> Result<()> {
+        unsupported()
+    }
+}
+
+/// Returns the launchd property list of a service.
+#[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+fn plist(label: &str, service: &Service) -> String {
+    let string =
+        |s: &std::ffi::OsStr| format!("<string>{}</string>", xml_escape(&s.to_string_lossy()));
+    let program_arguments = [service.spin.as_os_str(), "up".as_ref()]
+        .into_iter()
+        .chain(service.up_args.iter().map(OsString::as_os_str))
+        .map(|arg| format!("\n        {}", string(arg)))
+        .collect::<String>();
+    let log = string(service.log_dir.join(LOG_FILE).as_os_str());
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
+<plist version="1.0">
+<dict>
+    <key>Label</key>
+    {label}
+    <key>ProgramArguments</key>
+    <array>{program_arguments}
+    </array>
+    <key>RunAtLoad</key>
+    <true/>
+    <key>KeepAlive</key>
+    <dict>
+        <key>SuccessfulExit</key>
+        <false/>
+    </dict>
+    <key>StandardOutPath</key>
+    {log}
+    <key>StandardErrorPath</key>
+    {log}
+</dict>
+</plist>
+"#,
+        label = string(label.as_ref()),
+    )
+}
+
+fn xml_escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn service_names_are_validated() {
+        assert!(parse_service_name("my-app_1.0").is_ok());
+        assert!(parse_service_name("").is_err());
+        assert!(parse_service_name("my app").is_err());
+        assert!(parse_service_name("../app").is_err());
+    }
+
+    #[test]
+    fn plist_runs_spin_up() {
+        let service = Service {
+            name: "app".into(),
+            spin: "/usr/local/bin/spin".into(),
+            up_args: vec!["--from".into(), "/srv/a&b/spin.toml".into()],
+            log_dir: "/var/log/spin/app".into(),
+        };
+        let plist = plist("dev.spinframework.app", &service);
+        assert!(plist.contains(
+            "<string>/usr/local/bin/spin</string>\n        <string>up</string>\n        <string>--from</string>\n        <string>/srv/a&amp;b/spin.toml</string>\n    </array>"
+        ));
+        assert!(plist.contains("<string>/var/log/spin/app/spin.log</string>"));
+        assert!(plist.contains("<string>dev.spinframework.app</string>"));
+    }
+}
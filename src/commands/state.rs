@@ -0,0 +1,202 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result, bail};
+use clap::{Parser, Subcommand};
+use dialoguer::Confirm;
+use spin_common::{
+    app_state::{AppStateDir, list_state_dirs},
+    ui::quoted_path,
+};
+
+use crate::opts::APP_MANIFEST_FILE_OPT;
+
+/// Commands for inspecting and cleaning local application state.
+#[derive(Subcommand, Debug)]
+pub enum StateCommands {
+    /// List the state directories of the applications in an application directory.
+    List(ListCommand),
+    /// Delete the state (key-value stores, SQLite databases, logs) of an application.
+    Clean(CleanCommand),
+}
+
+impl StateCommands {
+    pub async fn run(self) -> Result<()> {
+        match self {
+            StateCommands::List(cmd) => cmd.run().await,
+            StateCommands::Clean(cmd) => cmd.run().await,
+        }
+    }
+}
+
+#[derive(Parser, Debug)]
+pub struct ListCommand {
+    /// The application whose directory to inspect. This may be a manifest
+    /// (spin.toml) file, or a directory containing a spin.toml file.
+    /// If omitted, it defaults to "spin.toml".
+    #[clap(
+        name = APP_MANIFEST_FILE_OPT,
+        short = 'f',
+        long = "from",
+        alias = "file"
+    )]
+    pub app_source: Option<PathBuf>,
+}
+
+impl ListCommand {
+    pub async fn run(self) -> Result<()> {
+        let (app_dir, _) = resolve_app(self.app_source.as_ref())?;
+        let state_dirs = list_state_dirs(&app_dir)?;
+        if state_dirs.is_empty() {
+            println!("No application state in {}", quoted_path(&app_dir));
+            return Ok(());
+        }
+        for AppStateDir {
+            app_id,
+            app_name,
+            path,
+        } in state_dirs
+        {
+            let app_id = app_id.as_deref().unwrap_or("<unknown>");
+            let app_name = app_name
+                .map(|name| format!(" [{name}]"))
+                .unwrap_or_default();
+            println!(
+                "{app_id}{app_name}: {} ({})",
+                path.display(),
+                format_size(dir_size(&path)?)
+            );
+        }
+        Ok(())
+    }
+}
+
+#[derive(Parser, Debug)]
+pub struct CleanCommand {
+    /// The application whose state to delete. This may be a manifest
+    /// (spin.toml) file, or a directory containing a spin.toml file.
+    /// If omitted, it defaults to "spin.toml".
+    #[clap(
+        name = APP_MANIFEST_FILE_OPT,
+        short = 'f',
+        long = "from",
+        alias = "file"
+    )]
+    pub app_source: Option<PathBuf>,
+
+    /// The identity of the application whose state to delete, as shown by
+    /// `spin state list`. Defaults to the application in the manifest.
+    #[clap(long = "app-id")]
+    pub app_id: Option<String>,
+
+    /// Delete without asking for confirmation.
+    #[clap(short = 'y', long = "yes")]
+    pub yes: bool,
+}
+
+impl CleanCommand {
+    pub async fn run(self) -> Result<()> {
+        let (app_dir, manifest_app_id) = resolve_app(self.app_source.as_ref())?;
+        let app_id = self.app_id.unwrap_or(manifest_app_id);
+
+        let Some(state_dir) = list_state_dirs(&app_dir)?
+            .into_iter()
+            .find(|d| d.app_id.as_deref() == Some(app_id.as_str()))
+        else {
+            bail!("No state found for application '{app_id}'");
+        };
+
+        let prompt = format!(
+            "Delete all state for '{app_id}' in {}?",
+            quoted_path(&state_dir.path)
+        );
+        if !self.yes
+            && !Confirm::new()
+                .with_prompt(prompt)
+                .default(false)
+                .interact()?
+        {
+            return Ok(());
+        }
+
+        clean_state_dir(&app_dir, &state_dir.path)?;
+        println!("Deleted state for '{app_id}'");
+        Ok(())
+    }
+}
+
+/// Returns the application directory and application identity for the
+/// given (or default) manifest.
+fn resolve_app(app_source: Option<&PathBuf>) -> Result<(PathBuf, String)> {
+    let (manifest_file, distance) = spin_common::paths::find_manifest_file_path(app_source)?;
+    crate::directory_rels::notify_if_nondefault_rel(&manifest_file, distance);
+    let app_dir = spin_common::paths::parent_dir(&manifest_file)?;
+    Ok((app_dir, spin_common::app_state::app_id(&manifest_file)?))
+}
+
+/// Deletes everything in a state directory other than the state directories
+/// of other applications nested within it and the record of the identities
+/// of the applications in `app_dir`.
+fn clean_state_dir(app_dir: &Path, state_dir: &Path) -> Result<()> {
+    let nested = list_state_dirs(app_dir)?
+        .into_iter()
+        .map(|d| d.path)
+        .filter(|p| p != state_dir)
+        .collect::<Vec<_>>();
+    let app_ids = app_dir
+        .join(spin_common::app_state::STATE_DIR_NAME)
+        .join(spin_common::app_state::APP_IDS_DIR_NAME);
+    for entry in std::fs::read_dir(state_dir)? {
+        let path = entry?.path();
+        if nested.iter().any(|n| n.starts_with(&path)) || path == app_ids {
+            continue;
+        }
+        if path.is_dir() {
+            std::fs::remove_dir_all(&path)
+        } else {
+            std::fs::remove_file(&path)
+        }
+        .with_context(|| format!("failed to delete {}", quoted_path(&path)))?;
+    }
+    Ok(())
+}
+
+fn dir_size(path: &Path) -> Result<u64> {
+    let mut size = 0;
+    for entry in std::fs::read_dir(path)? {
+        let entry = entry?;
+        let metadata = entry.metadata()?;
+        size += if metadata.is_dir() {
+            dir_size(&entry.path())?
+        } else {
+            metadata.len()
+        };
+    }
+    Ok(size)
+}
+
+fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["B", "KiB", "MiB", "GiB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{bytes} B")
+    } else {
+        format!("{size:.1} {}", UNITS[unit])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sizes_are_human_readable() {
+        assert_eq!("512 B", format_size(512));
+        assert_eq!("1.5 KiB", format_size(1536));
+        assert_eq!("2.0 MiB", format_size(2 * 1024 * 1024));
+    }
+}
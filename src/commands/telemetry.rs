@@ -0,0 +1,279 @@
+use std::collections::{BTreeMap, HashMap};
+use std::path::PathBuf;
+
+use anyhow::{Context, Result};
+use clap::{Parser, Subcommand};
+use spin_common::ui::quoted_path;
+use spin_telemetry::capture::CapturedSpan;
+
+/// Commands for analyzing telemetry captured locally.
+#[derive(Subcommand, Debug)]
+pub enum TelemetryCommands {
+    /// Summarize the slowest routes and host calls in spans captured with
+    /// `spin up --otel-capture`.
+    Report(ReportCommand),
+}
+
+impl TelemetryCommands {
+    pub async fn run(self) -> Result<()> {
+        match self {
+            TelemetryCommands::Report(cmd) => cmd.run().await,
+        }
+    }
+}
+
+#[derive(Parser, Debug)]
+pub struct ReportCommand {
+    /// The span capture file written by `spin up --otel-capture`.
+    pub capture_file: PathBuf,
+
+    /// Also write the spans as folded stacks to this file. Folded stacks can be
+    /// rendered as a flamegraph by tools such as inferno, flamegraph.pl, or speedscope.
+    #[clap(long = "folded")]
+    pub folded: Option<PathBuf>,
+
+    /// How many routes and host calls to list.
+    #[clap(long = "top", default_value_t = 10)]
+    pub top: usize,
+}
+
+impl ReportCommand {
+    pub async fn run(self) -> Result<()> {
+        let contents = std::fs::read_to_string(&self.capture_file)
+            .with_context(|| format!("failed to read {}", quoted_path(&self.capture_file)))?;
+        let spans = parse_spans(&contents)
+            .with_context(|| format!("failed to parse {}", quoted_path(&self.capture_file)))?;
+        if spans.is_empty() {
+            println!("No spans in {}", quoted_path(&self.capture_file));
+            return Ok(());
+        }
+
+        print_table("Slowest routes", &route_stats(&spans), self.top);
+        println!();
+        print_table("Slowest host calls", &host_call_stats(&spans), self.top);
+
+        if let Some(folded) = &self.folded {
+            let stacks = folded_stacks(&spans)
+                .into_iter()
+                .map(|(stack, micros)| format!("{stack} {micros}\n"))
+                .collect::<String>();
+            std::fs::write(folded, stacks)
+                .with_context(|| format!("failed to write {}", quoted_path(folded)))?;
+            println!();
+            println!("Wrote folded stacks to {}", quoted_path(folded));
+        }
+        Ok(())
+    }
+}
+
+fn parse_spans(contents: &str) -> Result<Vec<CapturedSpan>> {
+    contents
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(i, line)| {
+            serde_json::from_str(line).with_context(|| format!("invalid span on line {}", i + 1))
+        })
+        .collect()
+}
+
+/// Duration statistics for a group of spans.
+#[derive(Debug, Default, PartialEq)]
+struct Stats {
+    count: u64,
+    total_nanos: u64,
+    max_nanos: u64,
+}
+
+impl Stats {
+    fn add(&mut self, duration_nanos: u64) {
+        self.count += 1;
+        self.total_nanos += duration_nanos;
+        self.max_nanos = self.max_nanos.max(duration_nanos);
+    }
+
+    fn mean_nanos(&self) -> u64 {
+        self.total_nanos / self.count.max(1)
+    }
+}
+
+/// Groups root spans by route (or name, if they have no route), slowest on average first.
+fn route_stats(spans: &[CapturedSpan]) -> Vec<(String, Stats)> {
+    group_stats(
+        spans.iter().filter(|span| span.parent_id.is_none()),
+        |span| span.route.as_deref().unwrap_or(&span.name),
+    )
+}
+
+/// Groups host call spans by name, slowest on average first.
+fn host_call_stats(spans: &[CapturedSpan]) -> Vec<(String, Stats)> {
+    group_stats(
+        spans
+            .iter()
+            .filter(|span| span.kind.as_deref() == Some("client")),
+        |span| &span.name,
+    )
+}
+
+fn group_stats<'a>(
+    spans: impl Iterator<Item = &'a CapturedSpan>,
+    key: impl Fn(&'a CapturedSpan) -> &'a str,
+) -> Vec<(String, Stats)> {
+    let mut groups: HashMap<&str, Stats> = HashMap::new();
+    for span in spans {
+        groups
+            .entry(key(span))
+            .or_default()
+            .add(span.duration_nanos);
+    }
+    let mut groups = groups
+        .into_iter()
+        .map(|(key, stats)| (key.to_owned(), stats))
+        .collect::<Vec<_>>();
+    groups.sort_by(|(a_key, a), (b_key, b)| {
+        b.mean_nanos()
+            .cmp(&a.mean_nanos())
+            .then_with(|| a_key.cmp(b_key))
+    });
+    groups
+}
+
+fn print_table(title: &str, rows: &[(String, Stats)], top: usize) {
+    println!("{title}:");
+    if rows.is_empty() {
+        println!("  (none)");
+        return;
+    }
+    let rows = &rows[..rows.len().min(top)];
+    let width = rows.iter().map(|(name, _)| name.len()).max().unwrap_or(0);
+    println!(
+        "  {:width$}  {:>7}  {:>10}  {:>10}  {:>10}",
+        "NAME", "COUNT", "MEAN", "MAX", "TOTAL"
+    );
+    for (name, stats) in rows {
+        println!(
+            "  {name:width$}  {:>7}  {:>10}  {:>10}  {:>10}",
+            stats.count,
+            format_millis(stats.mean_nanos()),
+            format_millis(stats.max_nanos),
+            format_millis(stats.total_nanos),
+        );
+    }
+}
+
+fn format_millis(nanos: u64) -> String {
+    format!("{:.2}ms", nanos as f64 / 1_000_000.0)
+}
+
+/// Returns the time spent in each stack of spans, excluding time spent in child spans, in
+/// microseconds. Stacks are span names from the root down, separated by `;`.
+fn folded_stacks(spans: &[CapturedSpan]) -> BTreeMap<String, u64> {
+    let by_id: HashMap<u64, &CapturedSpan> = spans.iter().map(|span| (span.id, span)).collect();
+    let mut child_nanos: HashMap<u64, u64> = HashMap::new();
+    for span in spans {
+        if let Some(parent_id) = span.parent_id {
+            *child_nanos.entry(parent_id).or_default() += span.duration_nanos;
+        }
+    }
+
+    let mut stacks = BTreeMap::new();
+    for span in spans {
+        let mut frames = vec![frame_name(span)];
+        let mut parent_id = span.parent_id;
+        while let Some(parent) = parent_id.and_then(|id| by_id.get(&id)) {
+            frames.push(frame_name(parent));
+            parent_id = parent.parent_id;
+        }
+        frames.reverse();
+        let self_nanos = span
+            .duration_nanos
+            .saturating_sub(child_nanos.get(&span.id).copied().unwrap_or_default());
+        *stacks.entry(frames.join(";")).or_default() += self_nanos / 1_000;
+    }
+    stacks.retain(|_, micros| *micros > 0);
+    stacks
+}
+
+/// Returns the name of a span as a frame of a folded stack, in which `;` separates frames.
+fn frame_name(span: &CapturedSpan) -> String {
+    span.name.replace(';', ":")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn span(id: u64, parent_id: Option<u64>, name: &str, millis: u64) -> CapturedSpan {
+        CapturedSpan {
+            id,
+            parent_id,
+            name: name.to_owned(),
+            duration_nanos: millis * 1_000_000,
+            ..Default::default()
+        }
+    }
+
+    fn sample() -> Vec<CapturedSpan> {
+        let mut spans = vec![
+            span(1, None, "GET /a", 10),
+            span(2, Some(1), "execute_wasm_component a", 8),
+            span(3, Some(2), "spin_key_value.get", 3),
+            span(4, None, "GET /b", 2),
+            span(5, Some(4), "spin_key_value.get", 1),
+        ];
+        spans[0].route = Some("/a".to_owned());
+        spans[2].kind = Some("client".to_owned());
+        spans[4].kind = Some("client".to_owned());
+        spans
+    }
+
+    #[test]
+    fn routes_are_ordered_slowest_first() {
+        let stats = route_stats(&sample());
+        let names = stats
+            .iter()
+            .map(|(name, _)| name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(vec!["/a", "GET /b"], names);
+    }
+
+    #[test]
+    fn host_calls_are_grouped_by_name() {
+        let stats = host_call_stats(&sample());
+        assert_eq!(
+            vec![(
+                "spin_key_value.get".to_owned(),
+                Stats {
+                    count: 2,
+                    total_nanos: 4_000_000,
+                    max_nanos: 3_000_000,
+                }
+            )],
+            stats
+        );
+    }
+
+    #[test]
+    fn folded_stacks_count_self_time() {
+        let stacks = folded_stacks(&sample());
+        assert_eq!(
+            BTreeMap::from([
+                ("GET /a".to_owned(), 2_000),
+                ("GET /a;execute_wasm_component a".to_owned(), 5_000),
+                (
+                    "GET /a;execute_wasm_component a;spin_key_value.get".to_owned(),
+                    3_000
+                ),
+                ("GET /b".to_owned(), 1_000),
+                ("GET /b;spin_key_value.get".to_owned(), 1_000),
+            ]),
+            stacks
+        );
+    }
+
+    #[test]
+    fn reports_invalid_lines() {
+        let err = parse_spans("\n{not json}\n").unwrap_err();
+        assert_eq!("invalid span on line 2", err.to_string());
+    }
+}
@@ -12,10 +12,12 @@ use spin_templates::{
 };
 
 use crate::build_info::*;
+use crate::commands::mirror::{LocalMirror, MirrorOutput};
 
 const INSTALL_FROM_DIR_OPT: &str = "FROM_DIR";
 const INSTALL_FROM_GIT_OPT: &str = "FROM_GIT";
 const INSTALL_FROM_TAR_OPT: &str = "FROM_TAR";
+const INSTALL_FROM_MIRROR_OPT: &str = "FROM_MIRROR";
 const UPGRADE_ONLY: &str = "GIT_URL";
 
 const DEFAULT_TEMPLATES_INSTALL_PROMPT: &str =
@@ -42,6 +44,11 @@ pub enum TemplateCommands {
 
     /// List the installed templates.
     List(List),
+
+    /// Export installed templates to a mirror for use without network access.
+    ///
+    /// The mirror can be used with `spin templates install --mirror`.
+    Mirror(Mirror),
 }
 
 impl TemplateCommands {
@@ -51,6 +58,7 @@ impl TemplateCommands {
             TemplateCommands::Upgrade(cmd) => cmd.run().await,
             TemplateCommands::Uninstall(cmd) => cmd.run().await,
             TemplateCommands::List(cmd) => cmd.run().await,
+            TemplateCommands::Mirror(cmd) => cmd.run().await,
         }
     }
 }
@@ -66,6 +74,7 @@ pub struct Install {
         alias = "repo",
         conflicts_with = INSTALL_FROM_DIR_OPT,
         conflicts_with = INSTALL_FROM_TAR_OPT,
+        conflicts_with = INSTALL_FROM_MIRROR_OPT,
     )]
     pub git: Option<String>,
 
@@ -79,6 +88,7 @@ pub struct Install {
         long = "dir",
         conflicts_with = INSTALL_FROM_GIT_OPT,
         conflicts_with = INSTALL_FROM_TAR_OPT,
+        conflicts_with = INSTALL_FROM_MIRROR_OPT,
     )]
     pub dir: Option<PathBuf>,
 
@@ -88,9 +98,25 @@ pub struct Install {
         long = "tar",
         conflicts_with = INSTALL_FROM_GIT_OPT,
         conflicts_with = INSTALL_FROM_DIR_OPT,
+        conflicts_with = INSTALL_FROM_MIRROR_OPT,
     )]
     pub tar_url: Option<String>,
 
+    /// A mirror created by `spin templates mirror` containing the template(s)
+    /// to install. This may be a directory or a registry reference.
+    #[clap(
+        name = INSTALL_FROM_MIRROR_OPT,
+        long = "mirror",
+        conflicts_with = INSTALL_FROM_GIT_OPT,
+        conflicts_with = INSTALL_FROM_DIR_OPT,
+        conflicts_with = INSTALL_FROM_TAR_OPT,
+    )]
+    pub mirror: Option<String>,
+
+    /// Ignore server certificate errors when pulling the mirror.
+    #[clap(long = "mirror-insecure", requires = INSTALL_FROM_MIRROR_OPT)]
+    pub mirror_insecure: bool,
+
     /// If present, updates existing templates instead of skipping.
     #[clap(long = "upgrade", alias = "update")]
     pub update: bool,
@@ -127,24 +153,58 @@ pub struct Uninstall {
     pub template_id: String,
 }
 
+/// Export installed templates to a mirror for use without network access.
+#[derive(Parser, Debug)]
+pub struct Mirror {
+    #[clap(flatten)]
+    pub output: MirrorOutput,
+}
+
+impl Mirror {
+    pub async fn run(self) -> Result<()> {
+        let template_manager = TemplateManager::try_default()
+            .context("Failed to construct template directory path")?;
+        let target = self.output.target()?;
+        let exported = template_manager
+            .export(target.dir())
+            .await
+            .context("Failed to export templates")?;
+        if exported.templates.is_empty() {
+            println!("No templates are installed");
+            return Ok(());
+        }
+        println!("Exported {} template(s)", exported.templates.len());
+        self.output.finish(target).await
+    }
+}
+
 impl Install {
     pub async fn run(self) -> Result<()> {
         let template_manager = TemplateManager::try_default()
             .context("Failed to construct template directory path")?;
-        let source = match (&self.git, &self.dir, &self.tar_url) {
-            (Some(git), None, None) => {
+        let mirror = match &self.mirror {
+            Some(location) => Some(LocalMirror::get(location, self.mirror_insecure).await?),
+            None => None,
+        };
+        let source = match (&self.git, &self.dir, &self.tar_url, &mirror) {
+            (Some(git), None, None, None) => {
                 let git_url = infer_github(git);
                 TemplateSource::try_from_git(git_url, &self.branch, SPIN_VERSION)?
             }
-            (None, Some(dir), None) => {
+            (None, Some(dir), None, None) => {
                 let abs_dir = dir.absolutize().map(|d| d.to_path_buf());
                 TemplateSource::File(abs_dir.unwrap_or_else(|_| dir.clone()))
             }
-            (None, None, Some(tar_url)) => {
+            (None, None, Some(tar_url), None) => {
                 let url = url::Url::parse(tar_url).context("Invalid URL for remote tar")?;
                 TemplateSource::RemoteTar(url)
             }
-            _ => anyhow::bail!("Exactly one of `git`, `dir`, or `tar` must be specified"),
+            (None, None, None, Some(mirror)) => {
+                let dir = mirror.dir();
+                let abs_dir = dir.absolutize().map(|d| d.to_path_buf());
+                TemplateSource::File(abs_dir.unwrap_or_else(|_| dir.to_owned()))
+            }
+            _ => anyhow::bail!("Exactly one of `git`, `dir`, `tar`, or `mirror` must be specified"),
         };
 
         let reporter = ConsoleProgressReporter;
@@ -221,6 +281,8 @@ impl Upgrade {
                 branch: self.branch.clone(),
                 dir: None,
                 tar_url: None,
+                mirror: None,
+                mirror_insecure: false,
                 update: true,
             };
 
@@ -644,6 +706,8 @@ async fn install_default_templates() -> anyhow::Result<()> {
         branch: None,
         dir: None,
         tar_url: None,
+        mirror: None,
+        mirror_insecure: false,
         update: false,
     };
     install_cmd
@@ -0,0 +1,629 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use anyhow::{Context, Result, bail};
+use clap::Parser;
+use http_body_util::{BodyExt, Full};
+use serde::Deserialize;
+use spin_app::App;
+use spin_common::ui::quoted_path;
+use spin_factor_outbound_http::{
+    ErrorCode, HttpResult,
+    intercept::{InterceptOutcome, InterceptRequest, OutboundHttpInterceptor},
+};
+use spin_factors_executor::FactorsExecutor;
+use spin_fault_injection::Scenario;
+use spin_runtime_factors::{FactorsBuilder, TriggerAppArgs, TriggerFactors};
+use spin_trigger::cli::{
+    FactorsConfig, RuntimeFactorsBuilder, TriggerAppBuilder, UserProvidedPath,
+};
+use spin_trigger_http::{HttpServer, HttpTrigger, HttpTriggerConfig};
+use spin_variables_static::VariableSource;
+
+use crate::opts::APP_MANIFEST_FILE_OPT;
+
+mod coverage;
+mod snapshots;
+
+/// The test config file used if none is given, relative to the manifest.
+const DEFAULT_TEST_CONFIG_FILE: &str = "spin-test.toml";
+
+/// The snapshot directory used if none is given, relative to the manifest.
+const DEFAULT_SNAPSHOT_DIR: &str = "snapshots";
+
+/// The coverage report written if no file is given, relative to the manifest.
+const DEFAULT_COVERAGE_FILE: &str = "lcov.info";
+
+/// Run an application's tests, with its state and outbound HTTP requests
+/// kept in memory.
+#[derive(Parser, Debug)]
+#[clap(about = "Run an application's tests against in-memory services")]
+pub struct TestCommand {
+    /// The application to test. This may be a manifest (spin.toml) file, or a
+    /// directory containing a spin.toml file.
+    /// If omitted, it defaults to "spin.toml".
+    #[clap(
+        name = APP_MANIFEST_FILE_OPT,
+        short = 'f',
+        long = "from",
+        alias = "file"
+    )]
+    pub app_source: Option<PathBuf>,
+
+    /// The test config file. If omitted, it defaults to "spin-test.toml"
+    /// alongside the manifest.
+    #[clap(short = 'c', long = "config")]
+    pub test_config: Option<PathBuf>,
+
+    /// Also replay the request fixtures (`<name>.request.toml`) in this
+    /// directory, and compare the responses with their snapshots
+    /// (`<name>.snap`). If no directory is given, it defaults to "snapshots"
+    /// alongside the manifest.
+    #[clap(long = "snapshots", value_name = "DIR")]
+    pub snapshots: Option<Option<PathBuf>>,
+
+    /// Write the responses to the request fixtures as their snapshots,
+    /// instead of failing when they differ.
+    #[clap(long = "update-snapshots", requires = "snapshots")]
+    pub update_snapshots: bool,
+
+    /// Run the tests under a scenario file, which can override the test
+    /// config's variables, fail chosen key-value, SQLite and outbound HTTP
+    /// calls, and delay them.
+    #[clap(long = "scenario", value_name = "FILE")]
+    pub scenario: Option<PathBuf>,
+
+    /// Collect code coverage from components built with LLVM coverage
+    /// instrumentation, and write it as an lcov report to this file. If no
+    /// file is given, it defaults to "lcov.info" alongside the manifest.
+    /// Components must write their raw profiles to the directory named by
+    /// the SPIN_COVERAGE_DIR environment variable.
+    #[clap(long = "coverage", value_name = "FILE")]
+    pub coverage: Option<Option<PathBuf>>,
+
+    /// Run only the tests and fixtures whose names contain this text.
+    pub filter: Option<String>,
+}
+
+/// The tests to run, and the state and services they run against.
+///
+/// ```toml
+/// sqlite = ["CREATE TABLE users (name TEXT)"]
+///
+/// [variables]
+/// api_base = "https://api.example.com"
+///
+/// [key_value]
+/// greeting = "hello"
+///
+/// [[http_stub]]
+/// url = "https://api.example.com/users/*"
+/// body = '{"name": "Ada"}'
+///
+/// [[test]]
+/// name = "greets the user"
+/// request = { path = "/hello/1" }
+/// expect = { status = 200, body_contains = "Ada" }
+///
+/// [snapshots]
+/// ignore_headers = ["date"]
+/// ```
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TestConfig {
+    /// Values for the app's variables.
+    #[serde(default)]
+    variables: HashMap<String, String>,
+    /// Initial entries in the default key-value store.
+    #[serde(default)]
+    key_value: HashMap<String, String>,
+    /// Statements to run against the default SQLite database, in order.
+    #[serde(default)]
+    sqlite: Vec<String>,
+    /// Responses to outbound HTTP requests.
+    #[serde(default, rename = "http_stub")]
+    http_stubs: Vec<HttpStub>,
+    /// The tests, which run in order against the same state.
+    #[serde(default, rename = "test")]
+    tests: Vec<TestCase>,
+    /// How responses are normalized for snapshots.
+    #[serde(default)]
+    snapshots: snapshots::SnapshotConfig,
+}
+
+/// A response to outbound HTTP requests to a URL.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct HttpStub {
+    /// The URL to respond to. A trailing `*` matches any URL with the
+    /// preceding prefix.
+    url: String,
+    /// The method to respond to. If omitted, any method matches.
+    method: Option<String>,
+    #[serde(default = "default_status")]
+    status: u16,
+    #[serde(default)]
+    headers: HashMap<String, String>,
+    #[serde(default)]
+    body: String,
+}
+
+impl HttpStub {
+    fn matches(&self, method: &http::Method, url: &str) -> bool {
+        let url_matches = match self.url.strip_suffix('*') {
+            Some(prefix) => url.starts_with(prefix),
+            None => url == self.url,
+        };
+        url_matches
+            && self
+                .method
+                .as_deref()
+                .is_none_or(|m| m.eq_ignore_ascii_case(method.as_str()))
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TestCase {
+    name: String,
+    request: TestRequest,
+    #[serde(default)]
+    expect: Expectation,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TestRequest {
+    #[serde(default = "default_method")]
+    method: String,
+    /// The path and query of the request, e.g. `/users?page=2`.
+    path: String,
+    #[serde(default)]
+    headers: HashMap<String, String>,
+    #[serde(default)]
+    body: String,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Expectation {
+    status: Option<u16>,
+    #[serde(default)]
+    headers: HashMap<String, String>,
+    body: Option<String>,
+    body_contains: Option<String>,
+}
+
+fn default_status() -> u16 {
+    200
+}
+
+fn default_method() -> String {
+    "GET".into()
+}
+
+impl TestCommand {
+    pub async fn run(self) -> Result<()> {
+        let (manifest_file, _) =
+            spin_common::paths::find_manifest_file_path(self.app_source.as_ref())?;
+        let app_dir = manifest_file.parent().unwrap_or(Path::new("."));
+        let config_file = self
+            .test_config
+            .unwrap_or_else(|| app_dir.join(DEFAULT_TEST_CONFIG_FILE));
+        // Snapshots may be run without a test config.
+        let config: TestConfig = if self.snapshots.is_some() && !config_file.exists() {
+            TestConfig::default()
+        } else {
+            toml::from_str(
+                &std::fs::read_to_string(&config_file)
+                    .with_context(|| format!("failed to read {}", quoted_path(&config_file)))?,
+            )
+            .with_context(|| format!("invalid test config {}", quoted_path(&config_file)))?
+        };
+        let included = |name: &str| self.filter.as_deref().is_none_or(|f| name.contains(f));
+
+        let tests = config
+            .tests
+            .iter()
+            .filter(|test| included(&test.name))
+            .collect::<Vec<_>>();
+        let fixtures = match &self.snapshots {
+            Some(dir) => {
+                let dir = dir
+                    .clone()
+                    .unwrap_or_else(|| app_dir.join(DEFAULT_SNAPSHOT_DIR));
+                snapshots::load_fixtures(&dir)?
+                    .into_iter()
+                    .filter(|fixture| included(&fixture.name))
+                    .collect()
+            }
+            None => vec![],
+        };
+        let normalizer = snapshots::Normalizer::new(&config.snapshots)?;
+        if tests.is_empty() && fixtures.is_empty() {
+            bail!("no tests to run in {}", quoted_path(&config_file));
+        }
+
+        // State lives only as long as the test run.
+        let state_dir = tempfile::tempdir()?;
+
+        // Components write coverage profiles to the filesystem, so when
+        // collecting coverage mounted files are made writable, and copied so
+        // that the app's own files are left alone.
+        let files_mount_strategy = if self.coverage.is_some() {
+            spin_loader::FilesMountStrategy::Copy(state_dir.path().join("files"))
+        } else {
+            spin_loader::FilesMountStrategy::Direct
+        };
+        let mut locked_app =
+            spin_loader::from_file(&manifest_file, files_mount_strategy, None, None).await?;
+        let coverage = match &self.coverage {
+            Some(_) => Some(coverage::Coverage::prepare(&mut locked_app)?),
+            None => None,
+        };
+        let app = App::new("spin-test", locked_app);
+
+        let server = build_server(
+            app,
+            &config,
+            self.scenario.as_deref(),
+            coverage.is_some(),
+            state_dir.path(),
+        )
+        .await?;
+
+        println!("running {} tests", tests.len() + fixtures.len());
+        let mut failures = vec![];
+        for test in tests {
+            match run_test(&server, test).await {
+                Ok(()) => println!("test {} ... ok", test.name),
+                Err(err) => {
+                    println!("test {} ... FAILED", test.name);
+                    failures.push((test.name.clone(), format!("{err:#}")));
+                }
+            }
+        }
+        for fixture in &fixtures {
+            let name = format!("snapshot {}", fixture.name);
+            let comparison = async {
+                let (parts, body) = send_request(&server, &fixture.request).await?;
+                fixture.compare(&normalizer.render(&parts, &body), self.update_snapshots)
+            };
+            match comparison.await {
+                Ok(snapshots::Comparison::Matched) => println!("{name} ... ok"),
+                Ok(snapshots::Comparison::Updated) => println!("{name} ... updated"),
+                Ok(snapshots::Comparison::Missing) => {
+                    println!("{name} ... FAILED");
+                    failures.push((
+                        name,
+                        "no snapshot; run with --update-snapshots to write it".into(),
+                    ));
+                }
+                Ok(snapshots::Comparison::Changed(diff)) => {
+                    println!("{name} ... FAILED");
+                    failures.push((name, format!("response differs from snapshot:\n{diff}")));
+                }
+                Err(err) => {
+                    println!("{name} ... FAILED");
+                    failures.push((name, format!("{err:#}")));
+                }
+            }
+        }
+
+        if let Some(coverage) = &coverage {
+            let output = self
+                .coverage
+                .clone()
+                .flatten()
+                .unwrap_or_else(|| app_dir.join(DEFAULT_COVERAGE_FILE));
+            let covered = coverage.write_lcov(&output)?;
+            println!(
+                "\ncoverage of {} written to {}",
+                covered.join(", "),
+                quoted_path(&output)
+            );
+        }
+
+        if !failures.is_empty() {
+            println!("\nfailures:");
+            for (name, err) in &failures {
+                println!("    {name}: {err}");
+            }
+            bail!("{} of the tests failed", failures.len());
+        }
+        println!("\nall tests passed");
+        Ok(())
+    }
+}
+
+/// Loads the app with its stores and databases in `state_dir`, its variables
+/// and initial state from `config`, and outbound HTTP requests answered by the
+/// config's stubs, under any `scenario`. Mounted files are writable if
+/// `writable_files` is set.
+async fn build_server(
+    app: App,
+    config: &TestConfig,
+    scenario: Option<&Path>,
+    writable_files: bool,
+    state_dir: &Path,
+) -> Result<Arc<HttpServer<TriggerFactors>>> {
+    let factors_config = FactorsConfig {
+        working_dir: state_dir.to_owned(),
+        runtime_config_file: Some(write_runtime_config(&app, state_dir)?),
+        state_dir: UserProvidedPath::Provided(state_dir.to_owned()),
+        log_dir: UserProvidedPath::Unset,
+        ..Default::default()
+    };
+    let mut app_args = TriggerAppArgs::default();
+    let mut variables = config.variables.clone();
+    if let Some(scenario) = scenario {
+        // The config's variables are passed as if on the command line, which
+        // would take precedence over the scenario's.
+        variables.extend(Scenario::from_file(scenario)?.variables);
+        app_args.scenario = Some(scenario.to_owned());
+    }
+    app_args.variable = variables
+        .into_iter()
+        .map(|(name, value)| VariableSource::Literal(name, value))
+        .collect();
+    app_args.key_values = config.key_value.clone().into_iter().collect();
+    app_args.allow_transient_write = writable_files;
+    app_args.sqlite_statements = config.sqlite.clone();
+    let args = TestFactorsArgs {
+        app: app_args,
+        http_stubs: config.http_stubs.clone(),
+    };
+
+    let trigger = HttpTrigger::new(&app, HttpTriggerConfig::new("127.0.0.1:3000".parse()?))?;
+    let mut builder = TriggerAppBuilder::<_, TestFactorsBuilder>::new(trigger);
+    let trigger_app = builder
+        .build(
+            app,
+            factors_config,
+            args,
+            &spin_trigger::loader::ComponentLoader::new(),
+        )
+        .await?;
+    builder.trigger.into_server(trigger_app)
+}
+
+/// Writes a runtime config which puts each of the app's key-value stores and
+/// SQLite databases in `state_dir`. (The default store and database are
+/// already there.)
+fn write_runtime_config(app: &App, state_dir: &Path) -> Result<PathBuf> {
+    let mut key_value_stores = toml::Table::new();
+    let mut databases = toml::Table::new();
+    for component in app.components() {
+        let labels = component
+            .get_metadata(spin_factor_key_value::KEY_VALUE_STORES_KEY)?
+            .unwrap_or_default();
+        for label in labels.into_iter().filter(|label| label != "default") {
+            let path = state_dir.join(format!("key-value-{label}.db"));
+            key_value_stores.insert(label, spin_store_config(&path));
+        }
+        let labels = component
+            .get_metadata(spin_factor_sqlite::ALLOWED_DATABASES_KEY)?
+            .unwrap_or_default();
+        for label in labels.into_iter().filter(|label| label != "default") {
+            let path = state_dir.join(format!("sqlite-{label}.db"));
+            databases.insert(label, spin_store_config(&path));
+        }
+    }
+
+    let runtime_config = toml::Table::from_iter([
+        ("key_value_store".to_owned(), key_value_stores.into()),
+        ("sqlite_database".to_owned(), databases.into()),
+    ]);
+    let path = state_dir.join("runtime-config.toml");
+    std::fs::write(&path, toml::to_string(&runtime_config)?)?;
+    Ok(path)
+}
+
+fn spin_store_config(path: &Path) -> toml::Value {
+    toml::Table::from_iter([
+        ("type".to_owned(), "spin".into()),
+        (
+            "path".to_owned(),
+            path.to_string_lossy().into_owned().into(),
+        ),
+    ])
+    .into()
+}
+
+/// Sends a request to the app, returning its response.
+async fn send_request(
+    server: &Arc<HttpServer<TriggerFactors>>,
+    request: &TestRequest,
+) -> Result<(http::response::Parts, bytes::Bytes)> {
+    let mut builder = http::Request::builder()
+        .method(request.method.as_str())
+        .uri(&request.path);
+    for (name, value) in &request.headers {
+        builder = builder.header(name, value);
+    }
+    if !request
+        .headers
+        .keys()
+        .any(|name| name.eq_ignore_ascii_case("host"))
+    {
+        builder = builder.header(http::header::HOST, "localhost:3000");
+    }
+    let req = builder
+        .body(spin_http::body::full(request.body.clone().into()))
+        .context("invalid test request")?;
+
+    let resp = server
+        .handle(
+            req,
+            http::uri::Scheme::HTTP,
+            (std::net::Ipv4Addr::LOCALHOST, 7000).into(),
+        )
+        .await?;
+    let (parts, body) = resp.into_parts();
+    let body = body
+        .collect()
+        .await
+        .context("failed to read response body")?
+        .to_bytes();
+    Ok((parts, body))
+}
+
+async fn run_test(server: &Arc<HttpServer<TriggerFactors>>, test: &TestCase) -> Result<()> {
+    let (parts, body) = send_request(server, &test.request).await?;
+    let body = String::from_utf8_lossy(&body);
+
+    let expect = &test.expect;
+    if let Some(status) = expect.status
+        && parts.status != status
+    {
+        bail!("expected status {status}, got {}", parts.status.as_u16());
+    }
+    for (name, expected) in &expect.headers {
+        let actual = parts.headers.get(name).and_then(|v| v.to_str().ok());
+        if actual != Some(expected.as_str()) {
+            bail!("expected header {name}: {expected:?}, got {actual:?}");
+        }
+    }
+    if let Some(expected) = &expect.body
+        && body != expected.as_str()
+    {
+        bail!("expected body {expected:?}, got {body:?}");
+    }
+    if let Some(expected) = &expect.body_contains
+        && !body.contains(expected.as_str())
+    {
+        bail!("expected body containing {expected:?}, got {body:?}");
+    }
+    Ok(())
+}
+
+/// A [`RuntimeFactorsBuilder`] which builds [`TriggerFactors`] as
+/// [`FactorsBuilder`] does, with outbound HTTP requests answered by stubs.
+struct TestFactorsBuilder;
+
+#[derive(Default, clap::Args)]
+struct TestFactorsArgs {
+    #[clap(flatten)]
+    app: TriggerAppArgs,
+    #[clap(skip)]
+    http_stubs: Vec<HttpStub>,
+}
+
+impl RuntimeFactorsBuilder for TestFactorsBuilder {
+    type CliArgs = TestFactorsArgs;
+    type Factors = TriggerFactors;
+    type RuntimeConfig = <FactorsBuilder as RuntimeFactorsBuilder>::RuntimeConfig;
+
+    fn configure_engine(
+        engine_config: &mut spin_core::Config,
+        config: &FactorsConfig,
+        args: &Self::CliArgs,
+    ) -> Result<()> {
+        FactorsBuilder::configure_engine(engine_config, config, &args.app)
+    }
+
+    fn build(
+        config: &FactorsConfig,
+        args: &Self::CliArgs,
+    ) -> Result<(Self::Factors, Self::RuntimeConfig)> {
+        let (mut factors, runtime_config) = FactorsBuilder::build(config, &args.app)?;
+        factors
+            .outbound_http
+            .add_request_interceptor(HttpStubs(args.http_stubs.clone()));
+        Ok((factors, runtime_config))
+    }
+
+    fn configure_app<U: Send + 'static>(
+        executor: &mut FactorsExecutor<Self::Factors, U>,
+        runtime_config: &Self::RuntimeConfig,
+        config: &FactorsConfig,
+        args: &Self::CliArgs,
+    ) -> Result<()> {
+        FactorsBuilder::configure_app(executor, runtime_config, config, &args.app)
+    }
+}
+
+/// Answers outbound HTTP requests from [`HttpStub`]s, and denies any request
+/// no stub matches, so that tests never reach the network.
+struct HttpStubs(Vec<HttpStub>);
+
+#[async_trait::async_trait]
+impl OutboundHttpInterceptor for HttpStubs {
+    async fn intercept(&self, request: InterceptRequest) -> HttpResult<InterceptOutcome> {
+        let url = request.uri().to_string();
+        let Some(stub) = self
+            .0
+            .iter()
+            .find(|stub| stub.matches(request.method(), &url))
+        else {
+            tracing::warn!("No HTTP stub for {} {url}", request.method());
+            return Err(ErrorCode::HttpRequestDenied.into());
+        };
+        let mut builder = http::Response::builder().status(stub.status);
+        for (name, value) in &stub.headers {
+            builder = builder.header(name, value);
+        }
+        let body = Full::new(stub.body.clone().into())
+            .map_err(|err| match err {})
+            .boxed_unsync();
+        let resp = builder
+            .body(body)
+            .map_err(|_| ErrorCode::InternalError(Some("invalid HTTP stub".into())))?;
+        Ok(InterceptOutcome::Complete(resp))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stubs_match_urls_and_methods() {
+        let stub: HttpStub = toml::from_str(
+            r#"
+            url = "https://api.example.com/users/*"
+            method = "get"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(200, stub.status);
+        assert!(stub.matches(&http::Method::GET, "https://api.example.com/users/1"));
+        assert!(!stub.matches(&http::Method::POST, "https://api.example.com/users/1"));
+        assert!(!stub.matches(&http::Method::GET, "https://api.example.com/teams/1"));
+
+        let stub: HttpStub = toml::from_str(r#"url = "https://api.example.com/""#).unwrap();
+        assert!(stub.matches(&http::Method::DELETE, "https://api.example.com/"));
+        assert!(!stub.matches(&http::Method::GET, "https://api.example.com/users"));
+    }
+
+    #[test]
+    fn test_config_parses() {
+        let config: TestConfig = toml::from_str(
+            r#"
+            sqlite = ["CREATE TABLE users (name TEXT)"]
+
+            [variables]
+            api_base = "https://api.example.com"
+
+            [key_value]
+            greeting = "hello"
+
+            [[http_stub]]
+            url = "https://api.example.com/users/*"
+            body = '{"name": "Ada"}'
+
+            [[test]]
+            name = "greets the user"
+            request = { path = "/hello/1" }
+            expect = { status = 200, body_contains = "Ada" }
+            "#,
+        )
+        .unwrap();
+        assert_eq!(1, config.http_stubs.len());
+        assert_eq!("GET", config.tests[0].request.method);
+        assert_eq!(Some(200), config.tests[0].expect.status);
+    }
+}
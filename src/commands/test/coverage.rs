@@ -0,0 +1,218 @@
+//! Collecting code coverage from guests built with LLVM source-based coverage
+//! (`-C instrument-coverage`), and merging it into an lcov report.
+//!
+//! Guests capture their own coverage, e.g. with the `minicov` crate: each
+//! component is given a writable directory, named by the `SPIN_COVERAGE_DIR`
+//! environment variable, to which it should write raw profiles (`*.profraw`)
+//! before returning from a request. The profiles are then merged with
+//! `llvm-profdata` and exported with `llvm-cov`, which must be on the path or
+//! named by the `LLVM_PROFDATA` and `LLVM_COV` environment variables.
+
+use std::{
+    ffi::OsString,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use anyhow::{Context, Result, bail};
+use spin_common::{ui::quoted_path, url::parse_file_url};
+use spin_locked_app::locked::{ContentPath, ContentRef, LockedApp};
+use wasmparser::{Parser, Payload};
+
+/// Where components' coverage directories are mounted in the guest.
+const GUEST_COVERAGE_DIR: &str = "/.spin-coverage";
+/// The environment variable naming a component's coverage directory.
+const COVERAGE_DIR_ENV: &str = "SPIN_COVERAGE_DIR";
+/// The custom section holding a module's LLVM coverage mapping.
+const COVERAGE_MAPPING_SECTION: &str = "__llvm_covmap";
+
+/// The coverage directories of an app's components.
+pub(super) struct Coverage {
+    dir: tempfile::TempDir,
+    /// Each component's ID and Wasm file.
+    components: Vec<(String, PathBuf)>,
+}
+
+impl Coverage {
+    /// Mounts a coverage directory into each of the app's components whose
+    /// source is a local file.
+    pub fn prepare(app: &mut LockedApp) -> Result<Self> {
+        let dir = tempfile::tempdir()?;
+        let mut components = vec![];
+        for component in &mut app.components {
+            let Some(source) = component.source.content.source.as_deref() else {
+                continue;
+            };
+            let Ok(wasm_path) = parse_file_url(source) else {
+                continue;
+            };
+            let component_dir = dir.path().join(&component.id);
+            std::fs::create_dir(&component_dir)?;
+            let url = url::Url::from_directory_path(&component_dir)
+                .map_err(|()| anyhow::anyhow!("invalid coverage directory {component_dir:?}"))?;
+            component.files.push(ContentPath {
+                content: ContentRef {
+                    source: Some(url.to_string()),
+                    ..Default::default()
+                },
+                path: GUEST_COVERAGE_DIR.into(),
+            });
+            component
+                .env
+                .insert(COVERAGE_DIR_ENV.into(), GUEST_COVERAGE_DIR.into());
+            components.push((component.id.clone(), wasm_path));
+        }
+        Ok(Self { dir, components })
+    }
+
+    /// Merges the profiles the components wrote into an lcov report at
+    /// `output`, returning the IDs of the components it covers.
+    pub fn write_lcov(&self, output: &Path) -> Result<Vec<String>> {
+        let mut lcov = vec![];
+        let mut covered = vec![];
+        for (id, wasm_path) in &self.components {
+            let component_dir = self.dir.path().join(id);
+            let mut profiles = vec![];
+            for entry in std::fs::read_dir(&component_dir)? {
+                let path = entry?.path();
+                if path.extension().is_some_and(|ext| ext == "profraw") {
+                    profiles.push(path);
+                }
+            }
+            if profiles.is_empty() {
+                tracing::debug!("Component {id:?} wrote no coverage profiles");
+                continue;
+            }
+
+            let wasm = std::fs::read(wasm_path)
+                .with_context(|| format!("failed to read {}", quoted_path(wasm_path)))?;
+            let modules = instrumented_modules(&wasm)?;
+            if modules.is_empty() {
+                terminal::warn!(
+                    "Component {id:?} wrote coverage profiles, but {} has no coverage mapping.",
+                    quoted_path(wasm_path)
+                );
+                continue;
+            }
+            let mut module_paths = vec![];
+            for (index, module) in modules.iter().enumerate() {
+                let path = component_dir.join(format!("module-{index}.wasm"));
+                std::fs::write(&path, module)?;
+                module_paths.push(path);
+            }
+
+            let profdata = component_dir.join("merged.profdata");
+            run(Command::new(llvm_tool("LLVM_PROFDATA", "llvm-profdata"))
+                .args(["merge", "-sparse", "-o"])
+                .arg(&profdata)
+                .args(&profiles))?;
+            let mut export = Command::new(llvm_tool("LLVM_COV", "llvm-cov"));
+            export
+                .args(["export", "-format=lcov"])
+                .arg(format!("-instr-profile={}", profdata.display()))
+                .arg(&module_paths[0]);
+            for path in &module_paths[1..] {
+                export.arg("-object").arg(path);
+            }
+            lcov.extend(run(&mut export)?);
+            covered.push(id.clone());
+        }
+
+        if covered.is_empty() {
+            bail!(
+                "no component wrote coverage profiles to ${COVERAGE_DIR_ENV}; guests must be built with `-C instrument-coverage` and write their profiles there"
+            );
+        }
+        std::fs::write(output, lcov)
+            .with_context(|| format!("failed to write {}", quoted_path(output)))?;
+        Ok(covered)
+    }
+}
+
+/// Returns the core modules in `wasm`, a component or core module, that have a
+/// coverage mapping.
+fn instrumented_modules(wasm: &[u8]) -> Result<Vec<Vec<u8>>> {
+    let modules = if Parser::is_core_wasm(wasm) {
+        vec![wasm]
+    } else {
+        let mut modules = vec![];
+        for payload in Parser::new(0).parse_all(wasm) {
+            if let Payload::ModuleSection {
+                unchecked_range, ..
+            } = payload?
+            {
+                modules.push(&wasm[unchecked_range]);
+            }
+        }
+        modules
+    };
+    let mut instrumented = vec![];
+    for module in modules {
+        if has_coverage_mapping(module)? {
+            instrumented.push(module.to_vec());
+        }
+    }
+    Ok(instrumented)
+}
+
+fn has_coverage_mapping(module: &[u8]) -> Result<bool> {
+    for payload in Parser::new(0).parse_all(module) {
+        if let Payload::CustomSection(section) = payload?
+            && section.name() == COVERAGE_MAPPING_SECTION
+        {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+fn llvm_tool(env_var: &str, default: &str) -> OsString {
+    std::env::var_os(env_var).unwrap_or_else(|| default.into())
+}
+
+/// Runs an LLVM tool, returning its standard output.
+fn run(command: &mut Command) -> Result<Vec<u8>> {
+    let program = command.get_program().to_string_lossy().into_owned();
+    let output = command.output().with_context(|| {
+        format!(
+            "failed to run {program}; install the LLVM tools (e.g. `rustup component add llvm-tools`) or set LLVM_PROFDATA and LLVM_COV"
+        )
+    })?;
+    if !output.status.success() {
+        bail!(
+            "{program} failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(output.stdout)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn module(custom_section: &str) -> wasm_encoder::Module {
+        let mut module = wasm_encoder::Module::new();
+        module.section(&wasm_encoder::CustomSection {
+            name: custom_section.into(),
+            data: b"mapping".into(),
+        });
+        module
+    }
+
+    #[test]
+    fn finds_instrumented_modules() {
+        let instrumented = module(COVERAGE_MAPPING_SECTION);
+        let plain = module("name");
+
+        let mut component = wasm_encoder::Component::new();
+        component.section(&wasm_encoder::ModuleSection(&plain));
+        component.section(&wasm_encoder::ModuleSection(&instrumented));
+        let modules = instrumented_modules(&component.finish()).unwrap();
+        assert_eq!(vec![instrumented.clone().finish()], modules);
+
+        let modules = instrumented_modules(&instrumented.clone().finish()).unwrap();
+        assert_eq!(1, modules.len());
+        assert!(instrumented_modules(&plain.finish()).unwrap().is_empty());
+    }
+}
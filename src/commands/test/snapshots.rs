@@ -0,0 +1,325 @@
+use std::{
+    collections::{BTreeMap, HashSet},
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result};
+use regex::Regex;
+use serde::Deserialize;
+use spin_common::ui::quoted_path;
+
+use super::TestRequest;
+
+/// The suffix of request fixture files in a snapshot directory.
+const FIXTURE_SUFFIX: &str = ".request.toml";
+/// The extension of snapshot files, which sit alongside their fixtures.
+const SNAPSHOT_EXTENSION: &str = "snap";
+
+/// How responses are normalized before they are compared with snapshots, so
+/// that values which change from run to run don't fail the comparison.
+///
+/// ```toml
+/// [snapshots]
+/// ignore_headers = ["date", "x-request-id"]
+///
+/// [[snapshots.redact]]
+/// pattern = "[0-9a-f]{8}-[0-9a-f]{4}-[0-9a-f]{4}-[0-9a-f]{4}-[0-9a-f]{12}"
+/// replacement = "[uuid]"
+/// ```
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(super) struct SnapshotConfig {
+    /// Response headers to leave out of snapshots.
+    #[serde(default)]
+    ignore_headers: Vec<String>,
+    /// Text to replace in response headers and bodies.
+    #[serde(default)]
+    redact: Vec<Redaction>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Redaction {
+    /// A regular expression matching the text to replace.
+    pattern: String,
+    #[serde(default = "default_replacement")]
+    replacement: String,
+}
+
+fn default_replacement() -> String {
+    "[redacted]".into()
+}
+
+/// Renders responses as snapshot text.
+pub(super) struct Normalizer {
+    ignore_headers: HashSet<String>,
+    redactions: Vec<(Regex, String)>,
+}
+
+impl Normalizer {
+    pub fn new(config: &SnapshotConfig) -> Result<Self> {
+        let redactions = config
+            .redact
+            .iter()
+            .map(|redaction| {
+                let regex = Regex::new(&redaction.pattern).with_context(|| {
+                    format!("invalid snapshot redaction pattern {:?}", redaction.pattern)
+                })?;
+                Ok((regex, redaction.replacement.clone()))
+            })
+            .collect::<Result<_>>()?;
+        Ok(Self {
+            ignore_headers: config
+                .ignore_headers
+                .iter()
+                .map(|name| name.to_ascii_lowercase())
+                .collect(),
+            redactions,
+        })
+    }
+
+    /// Renders the status line, the headers sorted by name, and the body,
+    /// pretty-printed with sorted keys if it is JSON.
+    pub fn render(&self, parts: &http::response::Parts, body: &[u8]) -> String {
+        let mut text = format!("{}\n", parts.status);
+
+        let mut headers = parts
+            .headers
+            .iter()
+            .filter(|(name, _)| !self.ignore_headers.contains(name.as_str()))
+            .map(|(name, value)| (name.as_str(), String::from_utf8_lossy(value.as_bytes())))
+            .collect::<Vec<_>>();
+        headers.sort_by_key(|(name, _)| *name);
+        for (name, value) in headers {
+            text.push_str(&format!("{name}: {value}\n"));
+        }
+        text.push('\n');
+
+        match serde_json::from_slice::<serde_json::Value>(body) {
+            Ok(json) if !body.is_empty() => {
+                let json = serde_json::to_string_pretty(&sort_keys(json)).unwrap();
+                text.push_str(&json);
+                text.push('\n');
+            }
+            _ => text.push_str(&String::from_utf8_lossy(body)),
+        }
+
+        for (regex, replacement) in &self.redactions {
+            text = regex.replace_all(&text, replacement.as_str()).into_owned();
+        }
+        text
+    }
+}
+
+fn sort_keys(value: serde_json::Value) -> serde_json::Value {
+    match value {
+        serde_json::Value::Object(map) => map
+            .into_iter()
+            .map(|(key, value)| (key, sort_keys(value)))
+            .collect::<BTreeMap<_, _>>()
+            .into_iter()
+            .collect::<serde_json::Map<_, _>>()
+            .into(),
+        serde_json::Value::Array(values) => values.into_iter().map(sort_keys).collect(),
+        value => value,
+    }
+}
+
+/// A request to replay, and where its response's snapshot is kept.
+pub(super) struct Fixture {
+    pub name: String,
+    pub request: TestRequest,
+    snapshot_path: PathBuf,
+}
+
+/// Loads the fixtures in `dir`, in name order.
+pub(super) fn load_fixtures(dir: &Path) -> Result<Vec<Fixture>> {
+    let entries = std::fs::read_dir(dir)
+        .with_context(|| format!("failed to read snapshot directory {}", quoted_path(dir)))?;
+    let mut fixtures = vec![];
+    for entry in entries {
+        let path = entry?.path();
+        let Some(name) = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .and_then(|name| name.strip_suffix(FIXTURE_SUFFIX))
+        else {
+            continue;
+        };
+        let request = toml::from_str(
+            &std::fs::read_to_string(&path)
+                .with_context(|| format!("failed to read {}", quoted_path(&path)))?,
+        )
+        .with_context(|| format!("invalid request fixture {}", quoted_path(&path)))?;
+        fixtures.push(Fixture {
+            name: name.to_owned(),
+            request,
+            snapshot_path: dir.join(format!("{name}.{SNAPSHOT_EXTENSION}")),
+        });
+    }
+    fixtures.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(fixtures)
+}
+
+/// The result of comparing a response with its snapshot.
+pub(super) enum Comparison {
+    Matched,
+    /// The snapshot was written from the response.
+    Updated,
+    Missing,
+    /// The response differs from the snapshot, as shown by the diff.
+    Changed(String),
+}
+
+impl Fixture {
+    /// Compares `actual` with the fixture's snapshot, or replaces the
+    /// snapshot with it if `update` is set.
+    pub fn compare(&self, actual: &str, update: bool) -> Result<Comparison> {
+        let expected = match std::fs::read_to_string(&self.snapshot_path) {
+            Ok(expected) => Some(expected),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => None,
+            Err(err) => {
+                return Err(err).with_context(|| {
+                    format!("failed to read {}", quoted_path(&self.snapshot_path))
+                });
+            }
+        };
+        if expected.as_deref() == Some(actual) {
+            return Ok(Comparison::Matched);
+        }
+        if update {
+            std::fs::write(&self.snapshot_path, actual)
+                .with_context(|| format!("failed to write {}", quoted_path(&self.snapshot_path)))?;
+            return Ok(Comparison::Updated);
+        }
+        Ok(match expected {
+            Some(expected) => Comparison::Changed(diff(&expected, actual)),
+            None => Comparison::Missing,
+        })
+    }
+}
+
+/// A line diff from `expected` to `actual`, with removed lines prefixed `-`
+/// and added lines `+`.
+fn diff(expected: &str, actual: &str) -> String {
+    let expected = expected.lines().collect::<Vec<_>>();
+    let actual = actual.lines().collect::<Vec<_>>();
+
+    // lcs[i][j] is the length of the longest common subsequence of
+    // expected[i..] and actual[j..].
+    let mut lcs = vec![vec![0usize; actual.len() + 1]; expected.len() + 1];
+    for i in (0..expected.len()).rev() {
+        for j in (0..actual.len()).rev() {
+            lcs[i][j] = if expected[i] == actual[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let mut out = String::new();
+    let (mut i, mut j) = (0, 0);
+    while i < expected.len() || j < actual.len() {
+        if i < expected.len() && j < actual.len() && expected[i] == actual[j] {
+            out.push_str(&format!("  {}\n", expected[i]));
+            i += 1;
+            j += 1;
+        } else if i < expected.len() && (j == actual.len() || lcs[i + 1][j] >= lcs[i][j + 1]) {
+            out.push_str(&format!("- {}\n", expected[i]));
+            i += 1;
+        } else {
+            out.push_str(&format!("+ {}\n", actual[j]));
+            j += 1;
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn response(headers: &[(&str, &str)]) -> http::response::Parts {
+        let mut builder = http::Response::builder().status(201);
+        for (name, value) in headers {
+            builder = builder.header(*name, *value);
+        }
+        builder.body(()).unwrap().into_parts().0
+    }
+
+    #[test]
+    fn responses_are_normalized() {
+        let config: SnapshotConfig = toml::from_str(
+            r#"
+            ignore_headers = ["Date"]
+
+            [[redact]]
+            pattern = "id-[0-9]+"
+            "#,
+        )
+        .unwrap();
+        let normalizer = Normalizer::new(&config).unwrap();
+        let parts = response(&[
+            ("x-b", "2"),
+            ("date", "Tue, 15 Oct 2024 00:00:00 GMT"),
+            ("x-a", "id-42"),
+        ]);
+        let text = normalizer.render(&parts, br#"{"z": 1, "a": {"y": "id-7", "b": null}}"#);
+        assert_eq!(
+            text,
+            r#"201 Created
+x-a: [redacted]
+x-b: 2
+
+{
+  "a": {
+    "b": null,
+    "y": "[redacted]"
+  },
+  "z": 1
+}
+"#
+        );
+
+        let text = normalizer.render(&response(&[]), b"plain text");
+        assert_eq!("201 Created\n\nplain text", text);
+    }
+
+    #[test]
+    fn snapshots_are_compared_and_updated() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("b.request.toml"), r#"path = "/b""#).unwrap();
+        std::fs::write(dir.path().join("a.request.toml"), r#"path = "/a""#).unwrap();
+        std::fs::write(dir.path().join("a.snap"), "200 OK\n\nhello\n").unwrap();
+        std::fs::write(dir.path().join("notes.txt"), "not a fixture").unwrap();
+
+        let fixtures = load_fixtures(dir.path()).unwrap();
+        let names = fixtures.iter().map(|f| f.name.as_str()).collect::<Vec<_>>();
+        assert_eq!(["a", "b"], names[..]);
+        assert_eq!("/a", fixtures[0].request.path);
+
+        let [a, b] = &fixtures[..] else { panic!() };
+        assert!(matches!(
+            a.compare("200 OK\n\nhello\n", false).unwrap(),
+            Comparison::Matched
+        ));
+        let Comparison::Changed(diff) = a.compare("200 OK\n\nhi\n", false).unwrap() else {
+            panic!("expected a changed snapshot");
+        };
+        assert_eq!("  200 OK\n  \n- hello\n+ hi\n", diff);
+
+        assert!(matches!(
+            b.compare("404 Not Found\n\n", false).unwrap(),
+            Comparison::Missing
+        ));
+        assert!(matches!(
+            b.compare("404 Not Found\n\n", true).unwrap(),
+            Comparison::Updated
+        ));
+        assert_eq!(
+            "404 Not Found\n\n",
+            std::fs::read_to_string(dir.path().join("b.snap")).unwrap()
+        );
+    }
+}
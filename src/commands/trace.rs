@@ -0,0 +1,315 @@
+use std::fmt::Write as _;
+use std::time::Duration;
+
+use anyhow::{Context, Result, bail};
+use clap::{Parser, Subcommand, ValueEnum};
+use http_body_util::{BodyExt, Empty};
+use hyper::{Request, StatusCode, body::Bytes, header};
+use hyper_util::rt::TokioIo;
+use serde::Deserialize;
+use spin_telemetry::recent_traces::{RecentSpan, RecentTrace};
+use spin_trigger::cli::AdminListen;
+
+/// Commands for viewing the traces of a running app.
+#[derive(Subcommand, Debug)]
+pub enum TraceCommands {
+    /// Stream the traces of an app run with `spin up --otel-recent-traces` as
+    /// they complete, rendered as span trees.
+    Tail(TailCommand),
+}
+
+impl TraceCommands {
+    pub async fn run(self) -> Result<()> {
+        match self {
+            TraceCommands::Tail(cmd) => cmd.run().await,
+        }
+    }
+}
+
+#[derive(Parser, Debug)]
+pub struct TailCommand {
+    /// The admin API address of the running app, as given to its
+    /// `--admin-listen` option: a loopback `ip:port` or `unix:<path>`.
+    #[clap(long = "admin")]
+    pub admin: AdminListen,
+
+    /// The bearer token the admin API requires.
+    #[clap(long = "admin-token", env = "SPIN_ADMIN_TOKEN", hide_env_values = true)]
+    pub admin_token: String,
+
+    /// Only show traces involving this component.
+    #[clap(long = "component")]
+    pub component: Option<String>,
+
+    /// Only show traces with this status.
+    #[clap(long = "status", value_enum)]
+    pub status: Option<TraceStatus>,
+
+    /// How often to check for newly completed traces, e.g. `500ms` or `2s`.
+    #[clap(
+        long = "interval",
+        default_value = "1s",
+        value_parser = spin_common::arg_parser::parse_duration,
+    )]
+    pub interval: Duration,
+}
+
+/// Whether a trace contains a span that ended with an error.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum TraceStatus {
+    Ok,
+    Error,
+}
+
+#[derive(Deserialize)]
+struct TracesResponse {
+    traces: Vec<RecentTrace>,
+}
+
+impl TailCommand {
+    pub async fn run(self) -> Result<()> {
+        let mut after = None;
+        loop {
+            let traces = self.fetch(after).await?;
+            if let Some(last) = traces.last() {
+                after = Some(last.seq);
+            }
+            for trace in traces.iter().filter(|trace| self.matches(trace)) {
+                println!("{}", render_trace(trace));
+            }
+            tokio::time::sleep(self.interval).await;
+        }
+    }
+
+    fn matches(&self, trace: &RecentTrace) -> bool {
+        let component_matches = self
+            .component
+            .as_deref()
+            .is_none_or(|id| trace.involves_component(id));
+        let status_matches = match self.status {
+            None => true,
+            Some(TraceStatus::Ok) => !trace.has_error(),
+            Some(TraceStatus::Error) => trace.has_error(),
+        };
+        component_matches && status_matches
+    }
+
+    /// Fetches the traces that completed after the one with the given sequence number.
+    async fn fetch(&self, after: Option<u64>) -> Result<Vec<RecentTrace>> {
+        let uri = match after {
+            Some(seq) => format!("/traces?after={seq}"),
+            None => "/traces".to_owned(),
+        };
+        let request = Request::get(uri)
+            .header(header::HOST, "localhost")
+            .header(
+                header::AUTHORIZATION,
+                format!("Bearer {}", self.admin_token),
+            )
+            .body(Empty::<Bytes>::new())?;
+        let response = match &self.admin {
+            AdminListen::Tcp(addr) => {
+                let stream = tokio::net::TcpStream::connect(addr)
+                    .await
+                    .with_context(|| format!("failed to connect to the admin API at {addr}"))?;
+                send(stream, request).await?
+            }
+            #[cfg(unix)]
+            AdminListen::Unix(path) => {
+                let stream = tokio::net::UnixStream::connect(path)
+                    .await
+                    .with_context(|| {
+                        format!("failed to connect to the admin API at {}", path.display())
+                    })?;
+                send(stream, request).await?
+            }
+            #[cfg(not(unix))]
+            AdminListen::Unix(_) => bail!("Unix domain sockets are not supported on this platform"),
+        };
+        let (status, body) = response;
+        if status != StatusCode::OK {
+            bail!(
+                "admin API returned {status}: {}",
+                String::from_utf8_lossy(&body).trim()
+            );
+        }
+        let response: TracesResponse =
+            serde_json::from_slice(&body).context("admin API returned invalid traces")?;
+        Ok(response.traces)
+    }
+}
+
+/// Sends a request over a fresh connection, returning the response status and body.
+async fn send<S>(stream: S, request: Request<Empty<Bytes>>) -> Result<(StatusCode, Bytes)>
+where
+    S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Send + Unpin + 'static,
+{
+    let (mut sender, conn) = hyper::client::conn::http1::handshake(TokioIo::new(stream))
+        .await
+        .context("failed to connect to the admin API")?;
+    tokio::spawn(conn);
+    let response = sender
+        .send_request(request)
+        .await
+        .context("failed to send request to the admin API")?;
+    let status = response.status();
+    let body = response
+        .into_body()
+        .collect()
+        .await
+        .context("failed to read the admin API response")?
+        .to_bytes();
+    Ok((status, body))
+}
+
+/// Renders a trace as a header line followed by its span trees, one per span whose parent is not
+/// in the trace.
+fn render_trace(trace: &RecentTrace) -> String {
+    let mut out = format!(
+        "trace {}{}\n",
+        trace.trace_id,
+        if trace.has_error() { " (error)" } else { "" }
+    );
+    let mut roots: Vec<_> = trace
+        .spans
+        .iter()
+        .filter(|span| {
+            !trace
+                .spans
+                .iter()
+                .any(|parent| Some(&parent.span_id) == span.parent_span_id.as_ref())
+        })
+        .collect();
+    roots.sort_by_key(|root| root.start_unix_nanos);
+    for root in roots {
+        render_span(&trace.spans, root, "", None, &mut out);
+    }
+    out
+}
+
+/// Renders a span and its descendants, one per line, indented by depth. `is_last` is `None` for
+/// roots.
+fn render_span(
+    spans: &[RecentSpan],
+    span: &RecentSpan,
+    prefix: &str,
+    is_last: Option<bool>,
+    out: &mut String,
+) {
+    let (branch, child_prefix) = match is_last {
+        None => ("", String::new()),
+        Some(true) => ("└─ ", format!("{prefix}   ")),
+        Some(false) => ("├─ ", format!("{prefix}│  ")),
+    };
+    let _ = write!(
+        out,
+        "{prefix}{branch}{}{}",
+        if span.guest { "guest: " } else { "" },
+        span.name
+    );
+    if let Some(component_id) = &span.component_id {
+        let _ = write!(out, " [{component_id}]");
+    }
+    let _ = write!(out, " {}", format_duration(span.duration_nanos));
+    if let Some(error) = &span.error {
+        let _ = write!(out, " ERROR: {error}");
+    }
+    out.push('\n');
+
+    let mut children: Vec<_> = spans
+        .iter()
+        .filter(|child| child.parent_span_id.as_ref() == Some(&span.span_id))
+        .collect();
+    children.sort_by_key(|child| child.start_unix_nanos);
+    for (i, child) in children.iter().enumerate() {
+        render_span(
+            spans,
+            child,
+            &child_prefix,
+            Some(i + 1 == children.len()),
+            out,
+        );
+    }
+}
+
+fn format_duration(nanos: u64) -> String {
+    let duration = Duration::from_nanos(nanos);
+    let micros = duration.as_micros();
+    if micros < 1_000 {
+        format!("{micros}µs")
+    } else if micros < 1_000_000 {
+        format!("{:.2}ms", duration.as_secs_f64() * 1_000.0)
+    } else {
+        format!("{:.2}s", duration.as_secs_f64())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn span(id: &str, parent: Option<&str>, name: &str, start: u64, millis: u64) -> RecentSpan {
+        RecentSpan {
+            span_id: id.to_owned(),
+            parent_span_id: parent.map(ToOwned::to_owned),
+            name: name.to_owned(),
+            start_unix_nanos: start,
+            duration_nanos: millis * 1_000_000,
+            ..Default::default()
+        }
+    }
+
+    fn trace() -> RecentTrace {
+        RecentTrace {
+            seq: 0,
+            trace_id: "abc".to_owned(),
+            spans: vec![
+                RecentSpan {
+                    guest: true,
+                    component_id: Some("hello".to_owned()),
+                    ..span("4", Some("2"), "render", 3, 3)
+                },
+                RecentSpan {
+                    component_id: Some("hello".to_owned()),
+                    ..span("2", Some("1"), "execute_wasm_component hello", 2, 10)
+                },
+                RecentSpan {
+                    error: Some("connection refused".to_owned()),
+                    ..span("3", Some("1"), "spin_outbound_http.send_request", 4, 1)
+                },
+                span("1", Some("0"), "GET /hello", 1, 12),
+            ],
+        }
+    }
+
+    fn tail(component: Option<&str>, status: Option<TraceStatus>) -> TailCommand {
+        TailCommand {
+            admin: "127.0.0.1:3001".parse().unwrap(),
+            admin_token: "token".to_owned(),
+            component: component.map(ToOwned::to_owned),
+            status,
+            interval: Duration::from_secs(1),
+        }
+    }
+
+    #[test]
+    fn renders_trace_tree() {
+        assert_eq!(
+            "trace abc (error)\n\
+             GET /hello 12.00ms\n\
+             ├─ execute_wasm_component hello [hello] 10.00ms\n\
+             │  └─ guest: render [hello] 3.00ms\n\
+             └─ spin_outbound_http.send_request 1.00ms ERROR: connection refused\n",
+            render_trace(&trace())
+        );
+    }
+
+    #[test]
+    fn filters_by_component_and_status() {
+        let trace = trace();
+        assert!(tail(None, None).matches(&trace));
+        assert!(tail(Some("hello"), Some(TraceStatus::Error)).matches(&trace));
+        assert!(!tail(Some("goodbye"), None).matches(&trace));
+        assert!(!tail(None, Some(TraceStatus::Ok)).matches(&trace));
+    }
+}
@@ -1,5 +1,7 @@
 mod app_source;
+mod dev_services;
 mod parsing;
+mod sandbox;
 
 use std::{
     collections::{HashMap, HashSet},
@@ -7,6 +9,7 @@ use std::{
     fmt::Debug,
     path::{Path, PathBuf},
     process::Stdio,
+    time::Duration,
 };
 
 use anyhow::{Context, Result, anyhow, bail, ensure};
@@ -17,12 +20,16 @@ use spin_common::ui::quoted_path;
 use spin_factor_outbound_networking::validate_service_chaining_for_components;
 use spin_loader::FilesMountStrategy;
 use spin_oci::{ExecutableArtifact, OciLoader};
-use spin_trigger::cli::{LaunchMetadata, SPIN_LOCAL_APP_DIR, SPIN_LOCKED_URL, SPIN_WORKING_DIR};
+use spin_trigger::cli::{
+    LaunchMetadata, SPIN_LOCAL_APP_DIR, SPIN_LOCKED_URL, SPIN_SANDBOX_HOST, SPIN_WORKING_DIR,
+};
 use tempfile::TempDir;
 
 use crate::{directory_rels::notify_if_nondefault_rel, opts::*};
 
 use self::app_source::{AppSource, ResolvedAppSource};
+use self::dev_services::{DEV_FILE_NAME, DevServices};
+use self::sandbox::SandboxPaths;
 
 const APPLICATION_OPT: &str = "APPLICATION";
 
@@ -160,18 +167,98 @@ pub(crate) struct UpCommandInner {
     #[clap(long, env = ALWAYS_BUILD_ENV)]
     pub build: bool,
 
+    /// For local apps, start the services declared in `spin-dev.toml` beside
+    /// the manifest, such as Redis, Postgres or an OpenTelemetry collector,
+    /// under Docker or Podman, and wire their addresses into the app's runtime
+    /// config. The services are stopped when Spin exits.
+    #[clap(long = "launch-deps")]
+    pub launch_deps: bool,
+
     /// [Experimental] Component ID to run. This can be specified multiple times. The default is all components.
     #[clap(short = 'c', long = "component-id")]
     #[arg(add = clap_complete::ArgValueCandidates::new(crate::completions::components))]
     pub components: Vec<String>,
 
+    /// The format of the host's logs: `text` (the default) or `json`. JSON logs
+    /// are emitted one object per line, with trace IDs when OpenTelemetry tracing
+    /// is enabled.
+    #[clap(
+        long = "log-format",
+        env = spin_telemetry::env::SPIN_LOG_FORMAT,
+        value_parser = ["text", "json"],
+    )]
+    pub log_format: Option<String>,
+
+    /// Render spans as a latency tree and metrics as summaries in the terminal,
+    /// without needing an OpenTelemetry collector. This includes the spans and
+    /// metrics of components using the experimental wasi:otel interface.
+    #[clap(
+        long = "otel-console",
+        alias = "otel-stdout",
+        env = spin_telemetry::env::SPIN_OTEL_CONSOLE
+    )]
+    pub otel_console: bool,
+
+    /// Capture spans to the given file, for analysis with `spin telemetry report`.
+    #[clap(long = "otel-capture", env = spin_telemetry::env::SPIN_OTEL_CAPTURE)]
+    pub otel_capture: Option<PathBuf>,
+
+    /// Keep this many recently completed traces in memory, for viewing live
+    /// with `spin trace tail`. Requires the admin API (`--admin-listen`).
+    #[clap(
+        long = "otel-recent-traces",
+        env = spin_telemetry::env::SPIN_OTEL_RECENT_TRACES
+    )]
+    pub otel_recent_traces: Option<usize>,
+
+    /// Log a warning, with a breakdown of guest and host call time, for every
+    /// request that takes longer than this, e.g. `500ms` or `2s`.
+    #[clap(
+        long = "slow-request-threshold",
+        env = spin_telemetry::env::SPIN_SLOW_REQUEST_THRESHOLD,
+        value_parser = spin_common::arg_parser::parse_duration,
+    )]
+    pub slow_request_threshold: Option<Duration>,
+
+    /// Record every privileged host operation components perform (outbound
+    /// connections, key-value access, SQL statements, variable reads) for
+    /// security review. Either a file to append JSON lines to, or `otel` to
+    /// export OpenTelemetry log records.
+    #[clap(long = "audit-log", env = spin_telemetry::env::SPIN_AUDIT_LOG)]
+    pub audit_log: Option<OsString>,
+
+    /// Restrict the trigger processes to the filesystem paths the app needs and
+    /// block system calls they never make once running, limiting the damage
+    /// if one is compromised. Linux only.
+    #[clap(long = "sandbox-host", env = SPIN_SANDBOX_HOST)]
+    pub sandbox_host: bool,
+
+    /// An additional path that sandboxed trigger processes may write, such as
+    /// a database file configured in the runtime config. May be repeated.
+    #[clap(long = "sandbox-allow-write", requires = "sandbox_host", value_hint = clap::ValueHint::AnyPath)]
+    pub sandbox_allow_write: Vec<PathBuf>,
+
+    /// Run as one node of a cluster serving the same app, identified by this
+    /// ID in telemetry. Every key-value store, SQLite database and job queue
+    /// the app uses must be held in a remote backend configured in the
+    /// runtime config, such as Redis or libSQL; the app refuses to start if
+    /// any is held locally.
+    #[clap(long = "cluster-node-id", env = spin_telemetry::env::SPIN_CLUSTER_NODE_ID)]
+    pub cluster_node_id: Option<String>,
+
     /// All other args, to be passed through to the trigger
     #[clap(skip)]
     pub trigger_args: Vec<OsString>,
 }
 
 impl UpCommandInner {
-    async fn run(self) -> Result<()> {
+    async fn run(mut self) -> Result<()> {
+        // Telemetry is initialized before arguments are parsed, so apply a
+        // `--log-format` flag to this process's own logs here.
+        if let Some(log_format) = &self.log_format {
+            spin_telemetry::set_json_log_format(log_format == "json");
+        }
+
         let app_source = self.app_source();
 
         if app_source == AppSource::None {
@@ -261,12 +348,42 @@ impl UpCommandInner {
         self.update_locked_app(&mut locked_app);
         let locked_url = self.write_locked_app(&locked_app, &working_dir).await?;
 
-        let local_app_dir = app_source.local_app_dir().map(Into::into);
+        let local_app_dir: Option<PathBuf> = app_source.local_app_dir().map(Into::into);
+
+        let mut sandbox = self.sandbox_host.then(|| {
+            SandboxPaths::new(
+                &locked_app,
+                &working_dir,
+                local_app_dir.as_deref(),
+                &self.trigger_args,
+                &self.sandbox_allow_write,
+            )
+        });
+
+        // Hold on to the services until the triggers exit.
+        let dev_services = match (self.launch_deps, &local_app_dir) {
+            (false, _) => None,
+            (true, None) => bail!("`--launch-deps` is only supported for local apps"),
+            (true, Some(app_dir)) => {
+                let services = self.launch_dev_services(app_dir, &working_dir).await?;
+                if let (Some(sandbox), Some(runtime_config)) =
+                    (&mut sandbox, services.runtime_config_file())
+                {
+                    sandbox.allow_read(runtime_config);
+                }
+                Some(services)
+            }
+        };
 
         let run_opts = RunTriggerOpts {
             locked_url,
             working_dir,
             local_app_dir,
+            sandbox,
+            env: dev_services
+                .as_ref()
+                .map(|services| services.env().to_vec())
+                .unwrap_or_default(),
         };
 
         let trigger_processes = self.start_trigger_processes(trigger_cmds, run_opts).await?;
@@ -296,9 +413,36 @@ impl UpCommandInner {
             }
         }
 
+        drop(dev_services);
         Ok(())
     }
 
+    /// Starts the app's development services. If they generate a runtime
+    /// config, the app's own runtime config is replaced by it.
+    async fn launch_dev_services(
+        &mut self,
+        app_dir: &Path,
+        working_dir: &Path,
+    ) -> Result<DevServices> {
+        const RUNTIME_CONFIG_FLAG: &str = "--runtime-config-file";
+        let runtime_config_file =
+            sandbox::trigger_arg_value(&self.trigger_args, RUNTIME_CONFIG_FLAG).or_else(|| {
+                std::env::var_os(spin_trigger::cli::RUNTIME_CONFIG_FILE).map(PathBuf::from)
+            });
+        let services = DevServices::launch(
+            &app_dir.join(DEV_FILE_NAME),
+            runtime_config_file.as_deref(),
+            working_dir,
+        )
+        .await?;
+        if services.runtime_config_file().is_some() {
+            // The generated runtime config is passed in the environment,
+            // which the flag would take precedence over.
+            self.trigger_args = remove_trigger_arg(&self.trigger_args, RUNTIME_CONFIG_FLAG);
+        }
+        Ok(services)
+    }
+
     fn get_canonical_working_dir(&self) -> Result<WorkingDirectory, anyhow::Error> {
         let working_dir_holder = match &self.tmp {
             None => WorkingDirectory::Temporary(TempDir::with_prefix("spinup-")?),
@@ -399,10 +543,46 @@ impl UpCommandInner {
         let mut cmd = tokio::process::Command::new(std::env::current_exe().unwrap());
         cmd.args(&trigger_cmd);
 
+        if let Some(log_format) = &self.log_format {
+            cmd.env(spin_telemetry::env::SPIN_LOG_FORMAT, log_format);
+        }
+
+        if self.otel_console {
+            cmd.env(spin_telemetry::env::SPIN_OTEL_CONSOLE, "1");
+        }
+
+        if let Some(otel_capture) = &self.otel_capture {
+            cmd.env(spin_telemetry::env::SPIN_OTEL_CAPTURE, otel_capture);
+        }
+
+        if let Some(capacity) = self.otel_recent_traces {
+            cmd.env(
+                spin_telemetry::env::SPIN_OTEL_RECENT_TRACES,
+                capacity.to_string(),
+            );
+        }
+
+        if let Some(threshold) = self.slow_request_threshold {
+            cmd.env(
+                spin_telemetry::env::SPIN_SLOW_REQUEST_THRESHOLD,
+                format!("{}ms", threshold.as_millis()),
+            );
+        }
+
+        if let Some(audit_log) = &self.audit_log {
+            cmd.env(spin_telemetry::env::SPIN_AUDIT_LOG, audit_log);
+        }
+
+        if let Some(node_id) = &self.cluster_node_id {
+            cmd.env(spin_telemetry::env::SPIN_CLUSTER_NODE_ID, node_id);
+        }
+
         if let Some(RunTriggerOpts {
             locked_url,
             working_dir,
             local_app_dir,
+            sandbox,
+            env,
         }) = opts
         {
             cmd.env(SPIN_LOCKED_URL, locked_url)
@@ -413,6 +593,13 @@ impl UpCommandInner {
                 cmd.env(SPIN_LOCAL_APP_DIR, local_app_dir);
             }
 
+            cmd.envs(env);
+
+            if let Some(sandbox) = sandbox {
+                sandbox.restrict(&mut cmd)?;
+                cmd.env(SPIN_SANDBOX_HOST, "1");
+            }
+
             cmd.kill_on_drop(true);
         } else {
             cmd.env("SPIN_PLUGINS_SUPPRESS_COMPATIBILITY_WARNINGS", "1");
@@ -600,6 +787,25 @@ impl UpCommandInner {
     }
 }
 
+/// Removes a flag passed through to the trigger, and its value, from the
+/// trigger args.
+fn remove_trigger_arg(trigger_args: &[OsString], flag: &str) -> Vec<OsString> {
+    let mut remaining = vec![];
+    let mut args = trigger_args.iter();
+    while let Some(arg) = args.next() {
+        let arg_str = arg.to_string_lossy();
+        if arg_str == flag {
+            args.next();
+        } else if !arg_str
+            .strip_prefix(flag)
+            .is_some_and(|rest| rest.starts_with('='))
+        {
+            remaining.push(arg.clone());
+        }
+    }
+    remaining
+}
+
 fn is_flag_arg(arg: &OsString) -> bool {
     if let Some(s) = arg.to_str() {
         s.starts_with('-')
@@ -655,6 +861,8 @@ struct RunTriggerOpts {
     locked_url: String,
     working_dir: PathBuf,
     local_app_dir: Option<PathBuf>,
+    sandbox: Option<SandboxPaths>,
+    env: Vec<(String, String)>,
 }
 
 enum WorkingDirectory {
@@ -727,7 +935,7 @@ fn trigger_commands_for_trigger_types(trigger_types: Vec<&str>) -> Result<Vec<Ve
     trigger_types
         .iter()
         .map(|&t| match t {
-            "http" | "redis" => Ok(trigger_command(t)),
+            "http" | "redis" | "graphql" | "job" | "workflow" | "actor" => Ok(trigger_command(t)),
             _ => {
                 let cmd = resolve_trigger_plugin(t)?;
                 Ok(vec![cmd])
@@ -1012,4 +1220,21 @@ mod test {
             ]
         );
     }
+
+    #[test]
+    fn removes_trigger_args() {
+        let args = |args: &[&str]| args.iter().map(OsString::from).collect::<Vec<_>>();
+        let trigger_args = args(&[
+            "--listen",
+            "127.0.0.1:3000",
+            "--runtime-config-file",
+            "rc.toml",
+            "--runtime-config-file=other.toml",
+            "--runtime-config-filex",
+        ]);
+        assert_eq!(
+            args(&["--listen", "127.0.0.1:3000", "--runtime-config-filex"]),
+            remove_trigger_arg(&trigger_args, "--runtime-config-file")
+        );
+    }
 }
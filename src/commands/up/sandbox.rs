@@ -0,0 +1,231 @@
+//! Host process sandboxing for `spin up --sandbox-host`.
+//!
+//! The filesystem is restricted with Landlock as each trigger process is
+//! started, so that every thread of the trigger inherits the restriction. (A
+//! Landlock ruleset only applies to the thread that enforces it, and the
+//! trigger's runtime threads already exist by the time it has initialized.) The
+//! trigger then restricts its own system calls once it has initialized; see
+//! `spin_trigger::cli::sandbox`.
+
+use std::{
+    ffi::OsString,
+    path::{Path, PathBuf},
+};
+
+use spin_app::locked::LockedApp;
+use spin_common::url::parse_file_url;
+
+/// System paths that the trigger reads, e.g. for shared libraries, DNS
+/// configuration and TLS root certificates.
+const SYSTEM_READ_ONLY_PATHS: &[&str] =
+    &["/bin", "/etc", "/lib", "/lib64", "/proc", "/sys", "/usr"];
+/// System paths that the trigger writes, e.g. `/dev/null`.
+const SYSTEM_READ_WRITE_PATHS: &[&str] = &["/dev"];
+
+/// The paths a sandboxed trigger process may access.
+#[derive(Clone, Debug, Default)]
+pub(super) struct SandboxPaths {
+    read_only: Vec<PathBuf>,
+    read_write: Vec<PathBuf>,
+}
+
+impl SandboxPaths {
+    /// Determines the paths needed to run the given app.
+    ///
+    /// Trigger processes may read the app directory, the Spin binary, component
+    /// sources and file mounts, and may write the working directory, the app's
+    /// state and log directories, the Spin data and cache directories, the
+    /// temporary directory and any paths given in `allow_write`.
+    pub fn new(
+        locked_app: &LockedApp,
+        working_dir: &Path,
+        local_app_dir: Option<&Path>,
+        trigger_args: &[OsString],
+        allow_write: &[PathBuf],
+    ) -> Self {
+        let mut paths = Self::default();
+
+        paths
+            .read_only
+            .extend(SYSTEM_READ_ONLY_PATHS.iter().map(PathBuf::from));
+        paths
+            .read_write
+            .extend(SYSTEM_READ_WRITE_PATHS.iter().map(PathBuf::from));
+        paths.read_only.extend(std::env::current_exe().ok());
+
+        for component in &locked_app.components {
+            let sources = std::iter::once(&component.source.content)
+                .chain(component.files.iter().map(|file| &file.content));
+            for source in sources.filter_map(|content| content.source.as_deref()) {
+                if let Ok(path) = parse_file_url(source) {
+                    paths.read_only.push(path);
+                }
+            }
+        }
+
+        if let Some(local_app_dir) = local_app_dir {
+            paths.read_only.push(local_app_dir.to_owned());
+            paths
+                .read_write
+                .push(local_app_dir.join(spin_common::app_state::STATE_DIR_NAME));
+        }
+        for flag in ["--runtime-config-file", "--cache"] {
+            paths
+                .read_only
+                .extend(trigger_arg_value(trigger_args, flag));
+        }
+        for flag in ["--state-dir", "--log-dir"] {
+            paths
+                .read_write
+                .extend(trigger_arg_value(trigger_args, flag));
+        }
+
+        paths.read_write.push(working_dir.to_owned());
+        paths.read_write.push(std::env::temp_dir());
+        paths
+            .read_write
+            .extend(spin_common::data_dir::data_dir().ok());
+        paths.read_write.extend(dirs::cache_dir());
+        paths.read_write.extend(allow_write.iter().cloned());
+
+        paths
+    }
+
+    /// Allows reading an additional path.
+    pub fn allow_read(&mut self, path: PathBuf) {
+        self.read_only.push(path);
+    }
+
+    /// Restricts the process started by `cmd` to these paths.
+    #[cfg(target_os = "linux")]
+    pub fn restrict(&self, cmd: &mut tokio::process::Command) -> anyhow::Result<()> {
+        use anyhow::Context as _;
+        use landlock::{
+            ABI, Access, AccessFs, CompatLevel, Compatible, Ruleset, RulesetAttr,
+            RulesetCreatedAttr, path_beneath_rules,
+        };
+
+        // ABI v2 (Linux 5.19) is the first to govern renaming files between
+        // directories, without which files could be moved out of the sandbox.
+        let abi = ABI::V2;
+        let ruleset = Ruleset::default()
+            .set_compatibility(CompatLevel::HardRequirement)
+            .handle_access(AccessFs::from_all(abi))
+            .and_then(|ruleset| ruleset.create())
+            .context("host sandboxing requires Linux 5.19 or later with Landlock enabled")?
+            .add_rules(path_beneath_rules(
+                existing(&self.read_only),
+                AccessFs::from_read(abi),
+            ))?
+            .add_rules(path_beneath_rules(
+                existing(&self.read_write),
+                AccessFs::from_all(abi),
+            ))?;
+
+        // The ruleset is created here, where errors can be reported, and only
+        // enforced in the child, which cannot allocate after forking.
+        let ruleset = std::sync::Mutex::new(Some(ruleset));
+        // SAFETY: the closure only takes a lock that no other thread holds and
+        // makes the `prctl` and `landlock_restrict_self` system calls, neither
+        // of which allocates.
+        unsafe {
+            cmd.pre_exec(move || {
+                let ruleset = ruleset.lock().ok().and_then(|mut ruleset| ruleset.take());
+                match ruleset.map(|ruleset| ruleset.restrict_self()) {
+                    Some(Ok(_)) => Ok(()),
+                    _ => Err(std::io::ErrorKind::PermissionDenied.into()),
+                }
+            });
+        }
+        Ok(())
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    pub fn restrict(&self, _cmd: &mut tokio::process::Command) -> anyhow::Result<()> {
+        anyhow::bail!("host sandboxing is only supported on Linux")
+    }
+}
+
+/// Landlock rules cannot be added for paths that don't exist.
+#[cfg(target_os = "linux")]
+fn existing(paths: &[PathBuf]) -> impl Iterator<Item = &PathBuf> {
+    paths.iter().filter(|path| path.exists())
+}
+
+/// Finds the value of a flag passed through to the trigger, as either
+/// `--flag value` or `--flag=value`.
+pub(super) fn trigger_arg_value(trigger_args: &[OsString], flag: &str) -> Option<PathBuf> {
+    let mut args = trigger_args.iter();
+    while let Some(arg) = args.next() {
+        let arg = arg.to_string_lossy();
+        if arg == flag {
+            return args.next().map(PathBuf::from);
+        }
+        if let Some(value) = arg
+            .strip_prefix(flag)
+            .and_then(|rest| rest.strip_prefix('='))
+        {
+            return Some(PathBuf::from(value));
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<OsString> {
+        args.iter().map(OsString::from).collect()
+    }
+
+    #[test]
+    fn finds_trigger_arg_values() {
+        let trigger_args = args(&["--listen", "127.0.0.1:3000", "--state-dir", "/var/spin"]);
+        assert_eq!(
+            Some(PathBuf::from("/var/spin")),
+            trigger_arg_value(&trigger_args, "--state-dir")
+        );
+        let trigger_args = args(&["--log-dir=/var/log/spin"]);
+        assert_eq!(
+            Some(PathBuf::from("/var/log/spin")),
+            trigger_arg_value(&trigger_args, "--log-dir")
+        );
+        assert_eq!(None, trigger_arg_value(&trigger_args, "--log"));
+        assert_eq!(
+            None,
+            trigger_arg_value(&args(&["--state-dir"]), "--state-dir")
+        );
+    }
+
+    #[test]
+    fn app_directory_is_read_only_except_state() {
+        let locked_app: LockedApp = serde_json::from_value(serde_json::json!({
+            "spin_lock_version": 1,
+            "triggers": [],
+            "components": [{
+                "id": "web",
+                "source": {
+                    "content_type": "application/wasm",
+                    "source": "file:///opt/components/web.wasm",
+                },
+                "files": [{ "source": "file:///srv/static", "path": "/static" }],
+            }],
+        }))
+        .unwrap();
+        let paths = SandboxPaths::new(
+            &locked_app,
+            Path::new("/tmp/spinup-1"),
+            Some(Path::new("/home/app")),
+            &args(&["--state-dir", "/var/spin"]),
+            &[PathBuf::from("/var/db")],
+        );
+        for path in ["/home/app", "/opt/components/web.wasm", "/srv/static"] {
+            assert!(paths.read_only.contains(&PathBuf::from(path)), "{path}");
+        }
+        for path in ["/home/app/.spin", "/var/spin", "/tmp/spinup-1", "/var/db"] {
+            assert!(paths.read_write.contains(&PathBuf::from(path)), "{path}");
+        }
+        assert!(!paths.read_write.contains(&PathBuf::from("/home/app")));
+    }
+}
@@ -12,20 +12,35 @@ use commands::maintenance::MaintenanceCommands;
 use commands::{
     build::BuildCommand,
     cloud::{DeployCommand, LoginCommand},
+    daemon::DaemonCommand,
     doctor::DoctorCommand,
     external::execute_external_subcommand,
+    fuzz::FuzzCommand,
+    gateway::GatewayCommand,
+    invoke::InvokeCommand,
+    jobs::JobsCommands,
     new::{AddCommand, NewCommand},
+    openapi::OpenApiCommands,
     plugins::PluginCommands,
     registry::RegistryCommands,
+    service::ServiceCommands,
+    state::StateCommands,
+    telemetry::TelemetryCommands,
     templates::TemplateCommands,
+    test::TestCommand,
+    trace::TraceCommands,
     up::UpCommand,
     watch::WatchCommand,
 };
 use spin_runtime_factors::FactorsBuilder;
 use spin_trigger::cli::FactorsTriggerCommand;
 use spin_trigger::cli::help::HelpArgsOnlyTrigger;
+use spin_trigger_actors::ActorTrigger;
+use spin_trigger_graphql::GraphqlTrigger;
 use spin_trigger_http::HttpTrigger;
+use spin_trigger_jobs::JobTrigger;
 use spin_trigger_redis::RedisTrigger;
+use spin_trigger_workflows::WorkflowTrigger;
 
 pub use opts::HELP_ARGS_ONLY_TRIGGER_TYPE;
 
@@ -111,7 +126,24 @@ enum SpinApp {
     External(Vec<String>),
     #[clap(alias = "w")]
     Watch(WatchCommand),
+    Invoke(InvokeCommand),
+    Fuzz(FuzzCommand),
+    Gateway(GatewayCommand),
+    Daemon(DaemonCommand),
     Doctor(DoctorCommand),
+    #[clap(subcommand)]
+    State(StateCommands),
+    #[clap(subcommand)]
+    Jobs(JobsCommands),
+    #[clap(subcommand, name = "openapi")]
+    OpenApi(OpenApiCommands),
+    #[clap(subcommand)]
+    Service(ServiceCommands),
+    #[clap(subcommand)]
+    Telemetry(TelemetryCommands),
+    #[clap(subcommand)]
+    Trace(TraceCommands),
+    Test(TestCommand),
     #[clap(subcommand, hide = true)]
     Maintenance(MaintenanceCommands),
 }
@@ -121,6 +153,10 @@ enum SpinApp {
 enum TriggerCommands {
     Http(FactorsTriggerCommand<HttpTrigger, FactorsBuilder>),
     Redis(FactorsTriggerCommand<RedisTrigger, FactorsBuilder>),
+    Graphql(FactorsTriggerCommand<GraphqlTrigger, FactorsBuilder>),
+    Job(FactorsTriggerCommand<JobTrigger, FactorsBuilder>),
+    Workflow(FactorsTriggerCommand<WorkflowTrigger, FactorsBuilder>),
+    Actor(FactorsTriggerCommand<ActorTrigger, FactorsBuilder>),
     #[clap(name = crate::HELP_ARGS_ONLY_TRIGGER_TYPE, hide = true)]
     HelpArgsOnly(FactorsTriggerCommand<HelpArgsOnlyTrigger, FactorsBuilder>),
 }
@@ -139,11 +175,26 @@ impl SpinApp {
             Self::Build(cmd) => cmd.run().await,
             Self::Trigger(TriggerCommands::Http(cmd)) => cmd.run().await,
             Self::Trigger(TriggerCommands::Redis(cmd)) => cmd.run().await,
+            Self::Trigger(TriggerCommands::Graphql(cmd)) => cmd.run().await,
+            Self::Trigger(TriggerCommands::Job(cmd)) => cmd.run().await,
+            Self::Trigger(TriggerCommands::Workflow(cmd)) => cmd.run().await,
+            Self::Trigger(TriggerCommands::Actor(cmd)) => cmd.run().await,
             Self::Trigger(TriggerCommands::HelpArgsOnly(cmd)) => cmd.run().await,
             Self::Plugins(cmd) => cmd.run().await,
             Self::External(args) => execute_external_subcommand(args, SpinApp::command()).await,
             Self::Watch(cmd) => cmd.run().await,
+            Self::Invoke(cmd) => cmd.run().await,
+            Self::Fuzz(cmd) => cmd.run().await,
+            Self::Gateway(cmd) => cmd.run().await,
+            Self::Daemon(cmd) => cmd.run().await,
             Self::Doctor(cmd) => cmd.run().await,
+            Self::State(cmd) => cmd.run().await,
+            Self::Service(cmd) => cmd.run().await,
+            Self::Jobs(cmd) => cmd.run().await,
+            Self::OpenApi(cmd) => cmd.run().await,
+            Self::Telemetry(cmd) => cmd.run().await,
+            Self::Trace(cmd) => cmd.run().await,
+            Self::Test(cmd) => cmd.run().await,
             Self::Maintenance(cmd) => cmd.run().await,
         }
     }
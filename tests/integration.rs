@@ -1434,6 +1434,60 @@ route = "/..."
         Ok(())
     }
 
+    #[test]
+    fn test_wasi_http_p2_trailers_and_continue() -> anyhow::Result<()> {
+        use std::io::{Read, Write};
+
+        run_test(
+            "wasi-http-p2-streaming",
+            SpinConfig {
+                binary_path: spin_binary(),
+                spin_up_args: Vec::new(),
+                app_type: SpinAppType::Http,
+            },
+            ServicesConfig::none(),
+            move |env| {
+                let spin = env.runtime_mut();
+                let url = spin.http_url().context("spin should be serving HTTP")?;
+                let mut stream = std::net::TcpStream::connect(url.trim_start_matches("http://"))?;
+                stream.set_read_timeout(Some(std::time::Duration::from_secs(10)))?;
+                // Trailers are only sent over HTTP/1.1 to clients which
+                // accept them.
+                stream.write_all(
+                    b"POST /trailers HTTP/1.1\r\n\
+                    host: localhost\r\n\
+                    content-length: 5\r\n\
+                    expect: 100-continue\r\n\
+                    te: trailers\r\n\
+                    connection: close\r\n\r\n",
+                )?;
+
+                // The body is sent once the server asks for it.
+                let mut interim = [0; 1024];
+                let len = stream.read(&mut interim)?;
+                let interim = String::from_utf8_lossy(&interim[..len]);
+                assert!(
+                    interim.starts_with("HTTP/1.1 100 Continue\r\n"),
+                    "{interim}"
+                );
+
+                stream.write_all(b"hello")?;
+                let mut response = String::new();
+                stream.read_to_string(&mut response)?;
+                let response = response.to_ascii_lowercase();
+                assert!(response.starts_with("http/1.1 200 ok\r\n"), "{response}");
+                assert!(
+                    response.ends_with("\r\n0\r\nx-body-length: 5\r\n\r\n"),
+                    "{response}"
+                );
+
+                Ok(())
+            },
+        )?;
+
+        Ok(())
+    }
+
     #[test]
     fn test_wasi_http_p2_hash_all() -> anyhow::Result<()> {
         wasi_http_hash_all("wasi-http-p2-streaming")
@@ -141,6 +141,39 @@ async fn handle_request(request: IncomingRequest, response_out: ResponseOutparam
             }
         }
 
+        (Method::Post, Some("/trailers")) => {
+            // Read the request body, then respond with its length in a trailer.
+
+            let mut length = 0;
+            let mut stream = request.into_body_stream();
+            while let Some(chunk) = stream.next().await {
+                match chunk {
+                    Ok(chunk) => length += chunk.len(),
+                    Err(e) => {
+                        eprintln!("Error receiving body: {e}");
+                        return bad_request(response_out);
+                    }
+                }
+            }
+
+            let response = OutgoingResponse::new(
+                200,
+                &Headers::new(&[("trailer".to_string(), b"x-body-length".to_vec())]),
+            );
+
+            let body = response.write().expect("response should be writable");
+
+            response_out.set(response);
+
+            OutgoingBody::finish(
+                body,
+                Some(Headers::new(&[(
+                    "x-body-length".to_string(),
+                    length.to_string().into_bytes(),
+                )])),
+            );
+        }
+
         _ => method_not_allowed(response_out),
     }
 }
@@ -5,7 +5,7 @@ use std::sync::Arc;
 use anyhow::Context as _;
 use spin_runtime_factors::{FactorsBuilder, TriggerAppArgs, TriggerFactors};
 use spin_trigger::{cli::TriggerAppBuilder, loader::ComponentLoader};
-use spin_trigger_http::{HttpServer, HttpTrigger, InstanceReuseConfig, OutputFormat};
+use spin_trigger_http::{HttpServer, HttpTrigger, HttpTriggerConfig};
 use test_environment::{
     Runtime, TestEnvironment, TestEnvironmentConfig,
     http::{Request, Response},
@@ -107,12 +107,7 @@ async fn initialize_trigger(
     let app = spin_app::App::new("my-app", locked_app);
     let trigger = HttpTrigger::new(
         &app,
-        "127.0.0.1:80".parse().unwrap(),
-        None,
-        false,
-        None,
-        InstanceReuseConfig::default(),
-        OutputFormat::default(),
+        HttpTriggerConfig::new("127.0.0.1:80".parse().unwrap()),
     )?;
     let mut builder = TriggerAppBuilder::<_, FactorsBuilder>::new(trigger);
     let trigger_app = builder